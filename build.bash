@@ -13,18 +13,27 @@ case $1 in
         glslc rend3-routine/shaders/src/cull.comp   -DPREFIX_SUM                         -O -g -o rend3-routine/shaders/spirv/cull-prefix-sum.comp.spv
         glslc rend3-routine/shaders/src/cull.comp   -DPREFIX_OUTPUT                      -O -g -o rend3-routine/shaders/spirv/cull-prefix-output.comp.spv
         glslc rend3-routine/shaders/src/depth.vert  -DCPU_DRIVEN                         -O -g -o rend3-routine/shaders/spirv/depth.vert.cpu.spv
+        glslc rend3-routine/shaders/src/depth.vert  -DCPU_DRIVEN -DVERTEX_SKINNING       -O -g -o rend3-routine/shaders/spirv/depth-skinned.vert.cpu.spv
         glslc rend3-routine/shaders/src/depth.frag  -DCPU_DRIVEN                         -O -g -o rend3-routine/shaders/spirv/depth-opaque.frag.cpu.spv
         glslc rend3-routine/shaders/src/depth.frag  -DCPU_DRIVEN -DALPHA_CUTOUT          -O -g -o rend3-routine/shaders/spirv/depth-cutout.frag.cpu.spv
+        glslc rend3-routine/shaders/src/depth.vert  -DCPU_DRIVEN -DDOWNLEVEL             -O -g -o rend3-routine/shaders/spirv/depth.vert.downlevel.spv
+        glslc rend3-routine/shaders/src/depth.frag  -DCPU_DRIVEN -DDOWNLEVEL -DALPHA_CUTOUT -O -g -o rend3-routine/shaders/spirv/depth-cutout.frag.downlevel.spv
         glslc rend3-routine/shaders/src/depth.vert  -DGPU_DRIVEN                         -O -g -o rend3-routine/shaders/spirv/depth.vert.gpu.spv
+        glslc rend3-routine/shaders/src/depth.vert  -DGPU_DRIVEN -DVERTEX_SKINNING       -O -g -o rend3-routine/shaders/spirv/depth-skinned.vert.gpu.spv
         glslc rend3-routine/shaders/src/depth.frag  -DGPU_DRIVEN                         -O -g -o rend3-routine/shaders/spirv/depth-opaque.frag.gpu.spv
         glslc rend3-routine/shaders/src/depth.frag  -DGPU_DRIVEN -DALPHA_CUTOUT          -O -g -o rend3-routine/shaders/spirv/depth-cutout.frag.gpu.spv
         glslc rend3-routine/shaders/src/opaque.vert -DCPU_DRIVEN                         -O -g -o rend3-routine/shaders/spirv/opaque.vert.cpu.spv
+        glslc rend3-routine/shaders/src/opaque.vert -DCPU_DRIVEN -DVERTEX_SKINNING       -O -g -o rend3-routine/shaders/spirv/opaque-skinned.vert.cpu.spv
         glslc rend3-routine/shaders/src/opaque.vert -DCPU_DRIVEN -DBAKING                -O -g -o rend3-routine/shaders/spirv/opaque-baking.vert.cpu.spv
         glslc rend3-routine/shaders/src/opaque.frag -DCPU_DRIVEN                         -O -g -o rend3-routine/shaders/spirv/opaque.frag.cpu.spv
+        glslc rend3-routine/shaders/src/opaque.vert -DCPU_DRIVEN -DDOWNLEVEL             -O -g -o rend3-routine/shaders/spirv/opaque.vert.downlevel.spv
+        glslc rend3-routine/shaders/src/opaque.frag -DCPU_DRIVEN -DDOWNLEVEL             -O -g -o rend3-routine/shaders/spirv/opaque.frag.downlevel.spv
         glslc rend3-routine/shaders/src/opaque.vert -DGPU_DRIVEN                         -O -g -o rend3-routine/shaders/spirv/opaque.vert.gpu.spv
+        glslc rend3-routine/shaders/src/opaque.vert -DGPU_DRIVEN -DVERTEX_SKINNING       -O -g -o rend3-routine/shaders/spirv/opaque-skinned.vert.gpu.spv
         glslc rend3-routine/shaders/src/opaque.vert -DGPU_DRIVEN -DBAKING                -O -g -o rend3-routine/shaders/spirv/opaque-baking.vert.gpu.spv
         glslc rend3-routine/shaders/src/opaque.frag -DGPU_DRIVEN                         -O -g -o rend3-routine/shaders/spirv/opaque.frag.gpu.spv
         glslc rend3-routine/shaders/src/skybox.vert                                      -O -g -o rend3-routine/shaders/spirv/skybox.vert.spv
+        glslc rend3-routine/shaders/src/skybox.vert -DSTANDARD_DEPTH                     -O -g -o rend3-routine/shaders/spirv/skybox.vert.standard.spv
         glslc rend3-routine/shaders/src/skybox.frag                                      -O -g -o rend3-routine/shaders/spirv/skybox.frag.spv
 
         naga rend3-routine/shaders/spirv/blit.vert.spv                --keep-coordinate-space rend3-routine/shaders/wgsl/blit.vert.wgsl
@@ -34,12 +43,19 @@ case $1 in
         naga rend3-routine/shaders/spirv/cull-prefix-sum.comp.spv     --keep-coordinate-space rend3-routine/shaders/wgsl/cull-prefix-sum.comp.wgsl
         naga rend3-routine/shaders/spirv/cull-prefix-output.comp.spv  --keep-coordinate-space rend3-routine/shaders/wgsl/cull-prefix-output.comp.wgsl
         naga rend3-routine/shaders/spirv/depth.vert.cpu.spv           --keep-coordinate-space rend3-routine/shaders/wgsl/depth.vert.cpu.wgsl
+        naga rend3-routine/shaders/spirv/depth-skinned.vert.cpu.spv   --keep-coordinate-space rend3-routine/shaders/wgsl/depth-skinned.vert.cpu.wgsl
         naga rend3-routine/shaders/spirv/depth-opaque.frag.cpu.spv    --keep-coordinate-space rend3-routine/shaders/wgsl/depth-opaque.frag.cpu.wgsl
         naga rend3-routine/shaders/spirv/depth-cutout.frag.cpu.spv    --keep-coordinate-space rend3-routine/shaders/wgsl/depth-cutout.frag.cpu.wgsl
+        naga rend3-routine/shaders/spirv/depth.vert.downlevel.spv        --keep-coordinate-space rend3-routine/shaders/wgsl/depth.vert.downlevel.wgsl
+        naga rend3-routine/shaders/spirv/depth-cutout.frag.downlevel.spv --keep-coordinate-space rend3-routine/shaders/wgsl/depth-cutout.frag.downlevel.wgsl
         naga rend3-routine/shaders/spirv/opaque.vert.cpu.spv          --keep-coordinate-space rend3-routine/shaders/wgsl/opaque.vert.cpu.wgsl
+        naga rend3-routine/shaders/spirv/opaque-skinned.vert.cpu.spv  --keep-coordinate-space rend3-routine/shaders/wgsl/opaque-skinned.vert.cpu.wgsl
         naga rend3-routine/shaders/spirv/opaque-baking.vert.cpu.spv   --keep-coordinate-space rend3-routine/shaders/wgsl/opaque-baking.vert.cpu.wgsl
         naga rend3-routine/shaders/spirv/opaque.frag.cpu.spv          --keep-coordinate-space rend3-routine/shaders/wgsl/opaque.frag.cpu.wgsl
+        naga rend3-routine/shaders/spirv/opaque.vert.downlevel.spv       --keep-coordinate-space rend3-routine/shaders/wgsl/opaque.vert.downlevel.wgsl
+        naga rend3-routine/shaders/spirv/opaque.frag.downlevel.spv       --keep-coordinate-space rend3-routine/shaders/wgsl/opaque.frag.downlevel.wgsl
         naga rend3-routine/shaders/spirv/skybox.vert.spv              --keep-coordinate-space rend3-routine/shaders/wgsl/skybox.vert.wgsl
+        naga rend3-routine/shaders/spirv/skybox.vert.standard.spv     --keep-coordinate-space rend3-routine/shaders/wgsl/skybox.vert.standard.wgsl
         naga rend3-routine/shaders/spirv/skybox.frag.spv              --keep-coordinate-space rend3-routine/shaders/wgsl/skybox.frag.wgsl
     ;;
     web-bin)
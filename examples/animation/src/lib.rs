@@ -1,4 +1,4 @@
-use std::{path::Path, sync::Arc};
+use std::{future::Future, path::Path, pin::Pin, sync::Arc};
 
 const SAMPLE_COUNT: rend3::types::SampleCount = rend3::types::SampleCount::One;
 
@@ -10,7 +10,6 @@ struct InitializedData {
     animation_data: rend3_anim::AnimationData,
     _directional_light_handle: rend3::types::DirectionalLightHandle,
     animation_time: f32,
-    last_frame_time: instant::Instant,
 }
 
 #[derive(Default)]
@@ -19,21 +18,6 @@ struct AnimationExample {
     data: Option<InitializedData>,
 }
 
-impl AnimationExample {
-    pub fn update(&mut self, renderer: &rend3::Renderer, delta: f32) {
-        let data = self.data.as_mut().unwrap();
-        data.animation_time = (data.animation_time + delta) % data.loaded_scene.animations[0].inner.duration;
-        rend3_anim::pose_animation_frame(
-            renderer,
-            &data.loaded_scene,
-            &data.loaded_instance,
-            &data.animation_data,
-            0,
-            data.animation_time,
-        )
-    }
-}
-
 impl rend3_framework::App for AnimationExample {
     const HANDEDNESS: rend3::types::Handedness = rend3::types::Handedness::Left;
 
@@ -41,57 +25,79 @@ impl rend3_framework::App for AnimationExample {
         SAMPLE_COUNT
     }
 
-    fn setup(
+    fn setup<'a>(
+        &'a mut self,
+        _window: &'a winit::window::Window,
+        renderer: &'a Arc<rend3::Renderer>,
+        _routines: &'a Arc<rend3_framework::DefaultRoutines>,
+        _surface_format: rend3::types::TextureFormat,
+    ) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        Box::pin(async move {
+            let view_location = glam::Vec3::new(0.0, 1.5, -5.0);
+            let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0);
+            let view = view * glam::Mat4::from_translation(-view_location);
+
+            // Set camera's location
+            renderer.set_camera_data(rend3::types::Camera {
+                projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+                view,
+            });
+
+            // Load a gltf model with animation data
+            // Needs to be stored somewhere, otherwise all the data gets freed.
+            let path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/scene.gltf"));
+            let gltf_data = std::fs::read(&path).unwrap();
+            let parent_directory = path.parent().unwrap();
+            let (loaded_scene, loaded_instance) = rend3_gltf::load_gltf(
+                renderer,
+                &gltf_data,
+                &rend3_gltf::GltfLoadSettings::default(),
+                |p| async move { rend3_gltf::filesystem_io_func(&parent_directory, &p).await },
+            )
+            .await
+            .expect("Loading gltf scene");
+
+            // Create a single directional light
+            //
+            // We need to keep the directional light handle alive.
+            let directional_light_handle = renderer.add_directional_light(rend3::types::DirectionalLight {
+                color: glam::Vec3::ONE,
+                intensity: 10.0,
+                // Direction will be normalized
+                direction: glam::Vec3::new(-1.0, -4.0, 2.0),
+                distance: 400.0,
+            });
+
+            let init_data = InitializedData {
+                animation_data: rend3_anim::AnimationData::from_gltf_scene(&loaded_scene, &loaded_instance),
+                loaded_scene,
+                loaded_instance,
+                _directional_light_handle: directional_light_handle,
+                animation_time: 0.0,
+            };
+
+            self.data = Some(init_data);
+        })
+    }
+
+    fn update(
         &mut self,
         _window: &winit::window::Window,
         renderer: &Arc<rend3::Renderer>,
         _routines: &Arc<rend3_framework::DefaultRoutines>,
-        _surface_format: rend3::types::TextureFormat,
+        delta: f32,
+        _timing: &rend3_framework::FrameTiming,
     ) {
-        let view_location = glam::Vec3::new(0.0, 1.5, -5.0);
-        let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0);
-        let view = view * glam::Mat4::from_translation(-view_location);
-
-        // Set camera's location
-        renderer.set_camera_data(rend3::types::Camera {
-            projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
-            view,
-        });
-
-        // Load a gltf model with animation data
-        // Needs to be stored somewhere, otherwise all the data gets freed.
-        let path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/resources/scene.gltf"));
-        let gltf_data = std::fs::read(&path).unwrap();
-        let parent_directory = path.parent().unwrap();
-        let (loaded_scene, loaded_instance) = pollster::block_on(rend3_gltf::load_gltf(
+        let data = self.data.as_mut().unwrap();
+        data.animation_time = (data.animation_time + delta) % data.loaded_scene.animations[0].inner.duration;
+        rend3_anim::pose_animation_frame(
             renderer,
-            &gltf_data,
-            &rend3_gltf::GltfLoadSettings::default(),
-            |p| async move { rend3_gltf::filesystem_io_func(&parent_directory, &p).await },
-        ))
-        .expect("Loading gltf scene");
-
-        // Create a single directional light
-        //
-        // We need to keep the directional light handle alive.
-        let directional_light_handle = renderer.add_directional_light(rend3::types::DirectionalLight {
-            color: glam::Vec3::ONE,
-            intensity: 10.0,
-            // Direction will be normalized
-            direction: glam::Vec3::new(-1.0, -4.0, 2.0),
-            distance: 400.0,
-        });
-
-        let init_data = InitializedData {
-            animation_data: rend3_anim::AnimationData::from_gltf_scene(&loaded_scene, &loaded_instance),
-            loaded_scene,
-            loaded_instance,
-            _directional_light_handle: directional_light_handle,
-            animation_time: 0.0,
-            last_frame_time: instant::Instant::now(),
-        };
-
-        self.data = Some(init_data);
+            &data.loaded_scene,
+            &data.loaded_instance,
+            &mut data.animation_data,
+            0,
+            data.animation_time,
+        )
     }
 
     fn handle_event(
@@ -114,11 +120,6 @@ impl rend3_framework::App for AnimationExample {
                 control_flow(winit::event_loop::ControlFlow::Exit);
             }
             rend3_framework::Event::MainEventsCleared => {
-                let now = instant::Instant::now();
-                let last_frame_time = &mut self.data.as_mut().unwrap().last_frame_time;
-                let delta = now.duration_since(*last_frame_time).as_secs_f32();
-                *last_frame_time = now;
-                self.update(renderer, delta);
                 window.request_redraw();
             }
             // Render!
@@ -141,13 +142,15 @@ impl rend3_framework::App for AnimationExample {
                 base_rendergraph.add_to_graph(
                     &mut graph,
                     &ready,
-                    &pbr_routine,
-                    None,
-                    &tonemapping_routine,
-                    resolution,
-                    SAMPLE_COUNT,
-                    glam::Vec4::splat(0.15),
-                    glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                    rend3_routine::base::BaseRenderGraphRoutines {
+                        pbr: &pbr_routine,
+                        skybox: None,
+                        tonemapping: &tonemapping_routine,
+                    },
+                    &rend3_routine::base::BaseRenderGraphSettings::new(resolution)
+                        .with_samples(SAMPLE_COUNT)
+                        .with_ambient(glam::Vec4::splat(0.15))
+                        .with_clear_color(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)), // Nice scene-referred purple
                 );
 
                 // Dispatch a render using the built up rendergraph!
@@ -113,7 +113,7 @@ fn main() {
     //
     // All handles are refcounted, so we only need to hang onto the handle until we
     // make an object.
-    let mesh_handle = renderer.add_mesh(mesh);
+    let mesh_handle = renderer.add_mesh(mesh).unwrap();
 
     // Add PBR material with all defaults except a single color.
     let material = rend3_routine::pbr::PbrMaterial {
@@ -198,13 +198,15 @@ fn main() {
             base_rendergraph.add_to_graph(
                 &mut graph,
                 &ready,
-                &pbr_routine,
-                None,
-                &tonemapping_routine,
-                resolution,
-                rend3::types::SampleCount::One,
-                glam::Vec4::ZERO,
-                glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                rend3_routine::base::BaseRenderGraphRoutines {
+                    pbr: &pbr_routine,
+                    skybox: None,
+                    tonemapping: &tonemapping_routine,
+                },
+                &rend3_routine::base::BaseRenderGraphSettings::new(resolution)
+                    .with_samples(rend3::types::SampleCount::One)
+                    .with_ambient(glam::Vec4::ZERO)
+                    .with_clear_color(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)), // Nice scene-referred purple
             );
 
             // Dispatch a render using the built up rendergraph!
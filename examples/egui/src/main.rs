@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{future::Future, pin::Pin, sync::Arc};
 
 struct EguiExampleData {
     _object_handle: rend3::types::ObjectHandle,
@@ -7,6 +7,9 @@ struct EguiExampleData {
 
     egui_routine: rend3_egui::EguiRenderRoutine,
     platform: egui_winit_platform::Platform,
+    camera: rend3::types::Camera,
+    // Created on the first frame, as it needs the base rendergraph.
+    viewport: Option<rend3_egui::EguiViewport>,
     start_time: instant::Instant,
     color: [f32; 4],
 }
@@ -25,121 +28,126 @@ impl rend3_framework::App for EguiExample {
         SAMPLE_COUNT
     }
 
-    fn setup(
-        &mut self,
-        window: &winit::window::Window,
-        renderer: &Arc<rend3::Renderer>,
-        _routines: &Arc<rend3_framework::DefaultRoutines>,
+    fn setup<'a>(
+        &'a mut self,
+        window: &'a winit::window::Window,
+        renderer: &'a Arc<rend3::Renderer>,
+        _routines: &'a Arc<rend3_framework::DefaultRoutines>,
         surface_format: rend3::types::TextureFormat,
-    ) {
-        let window_size = window.inner_size();
-
-        // Create the egui render routine
-        let mut egui_routine = rend3_egui::EguiRenderRoutine::new(
-            renderer,
-            surface_format,
-            rend3::types::SampleCount::One,
-            window_size.width,
-            window_size.height,
-            window.scale_factor() as f32,
-        );
-
-        // Create mesh and calculate smooth normals based on vertices
-        let mesh = create_mesh();
-
-        // Add mesh to renderer's world.
-        //
-        // All handles are refcounted, so we only need to hang onto the handle until we
-        // make an object.
-        let mesh_handle = renderer.add_mesh(mesh);
-
-        // Add PBR material with all defaults except a single color.
-        let material = rend3_routine::pbr::PbrMaterial {
-            albedo: rend3_routine::pbr::AlbedoComponent::Value(glam::Vec4::new(0.0, 0.5, 0.5, 1.0)),
-            transparency: rend3_routine::pbr::Transparency::Blend,
-            ..rend3_routine::pbr::PbrMaterial::default()
-        };
-        let material_handle = renderer.add_material(material);
-
-        // Combine the mesh and the material with a location to give an object.
-        let object = rend3::types::Object {
-            mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
-            material: material_handle.clone(),
-            transform: glam::Mat4::IDENTITY,
-        };
-
-        // Creating an object will hold onto both the mesh and the material
-        // even if they are deleted.
-        //
-        // We need to keep the object handle alive.
-        let _object_handle = renderer.add_object(object);
-
-        let camera_pitch = std::f32::consts::FRAC_PI_4;
-        let camera_yaw = -std::f32::consts::FRAC_PI_4;
-        // These values may seem arbitrary, but they center the camera on the cube in
-        // the scene
-        let camera_location = glam::Vec3A::new(5.0, 7.5, -5.0);
-        let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, -camera_pitch, -camera_yaw, 0.0);
-        let view = view * glam::Mat4::from_translation((-camera_location).into());
-
-        // Set camera location data
-        renderer.set_camera_data(rend3::types::Camera {
-            projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
-            view,
-        });
-
-        // Create a single directional light
-        //
-        // We need to keep the directional light handle alive.
-        let _directional_handle = renderer.add_directional_light(rend3::types::DirectionalLight {
-            color: glam::Vec3::ONE,
-            intensity: 10.0,
-            // Direction will be normalized
-            direction: glam::Vec3::new(-1.0, -4.0, 2.0),
-            distance: 400.0,
-        });
-
-        // Create the winit/egui integration, which manages our egui context for us.
-        let platform = egui_winit_platform::Platform::new(egui_winit_platform::PlatformDescriptor {
-            physical_width: window_size.width as u32,
-            physical_height: window_size.height as u32,
-            scale_factor: window.scale_factor(),
-            font_definitions: egui::FontDefinitions::default(),
-            style: Default::default(),
-        });
-
-        //Images
-        let image_bytes = include_bytes!("images/rust-logo-128x128-blk.png");
-        let image_image = image::load_from_memory(image_bytes).unwrap();
-        let image_rgba = image_image.as_rgba8().unwrap().clone().into_raw();
-
-        use image::GenericImageView;
-        let dimensions = image_image.dimensions();
-
-        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
-
-        self.rust_logo = rend3_egui::EguiRenderRoutine::create_egui_texture(
-            &mut egui_routine.internal,
-            renderer,
-            format,
-            &image_rgba,
-            dimensions,
-            Some("rust_logo_texture"),
-        );
-
-        let start_time = instant::Instant::now();
-        let color: [f32; 4] = [0.0, 0.5, 0.5, 1.0];
-
-        self.data = Some(EguiExampleData {
-            _object_handle,
-            material_handle,
-            _directional_handle,
-
-            egui_routine,
-            platform,
-            start_time,
-            color,
-        });
+    ) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        Box::pin(async move {
+            let window_size = window.inner_size();
+
+            // Create the egui render routine
+            let mut egui_routine = rend3_egui::EguiRenderRoutine::new(
+                renderer,
+                surface_format,
+                rend3::types::SampleCount::One,
+                window_size.width,
+                window_size.height,
+                window.scale_factor() as f32,
+            );
+
+            // Create mesh and calculate smooth normals based on vertices
+            let mesh = create_mesh();
+
+            // Add mesh to renderer's world.
+            //
+            // All handles are refcounted, so we only need to hang onto the handle until we
+            // make an object.
+            let mesh_handle = renderer.add_mesh(mesh).unwrap();
+
+            // Add PBR material with all defaults except a single color.
+            let material = rend3_routine::pbr::PbrMaterial {
+                albedo: rend3_routine::pbr::AlbedoComponent::Value(glam::Vec4::new(0.0, 0.5, 0.5, 1.0)),
+                transparency: rend3_routine::pbr::Transparency::Blend,
+                ..rend3_routine::pbr::PbrMaterial::default()
+            };
+            let material_handle = renderer.add_material(material);
+
+            // Combine the mesh and the material with a location to give an object.
+            let object = rend3::types::Object {
+                mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
+                material: material_handle.clone(),
+                transform: glam::Mat4::IDENTITY,
+            };
+
+            // Creating an object will hold onto both the mesh and the material
+            // even if they are deleted.
+            //
+            // We need to keep the object handle alive.
+            let _object_handle = renderer.add_object(object);
+
+            let camera_pitch = std::f32::consts::FRAC_PI_4;
+            let camera_yaw = -std::f32::consts::FRAC_PI_4;
+            // These values may seem arbitrary, but they center the camera on the cube in
+            // the scene
+            let camera_location = glam::Vec3A::new(5.0, 7.5, -5.0);
+            let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, -camera_pitch, -camera_yaw, 0.0);
+            let view = view * glam::Mat4::from_translation((-camera_location).into());
+
+            // Set camera location data
+            let camera = rend3::types::Camera {
+                projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+                view,
+            };
+            renderer.set_camera_data(camera);
+
+            // Create a single directional light
+            //
+            // We need to keep the directional light handle alive.
+            let _directional_handle = renderer.add_directional_light(rend3::types::DirectionalLight {
+                color: glam::Vec3::ONE,
+                intensity: 10.0,
+                // Direction will be normalized
+                direction: glam::Vec3::new(-1.0, -4.0, 2.0),
+                distance: 400.0,
+            });
+
+            // Create the winit/egui integration, which manages our egui context for us.
+            let platform = egui_winit_platform::Platform::new(egui_winit_platform::PlatformDescriptor {
+                physical_width: window_size.width as u32,
+                physical_height: window_size.height as u32,
+                scale_factor: window.scale_factor(),
+                font_definitions: egui::FontDefinitions::default(),
+                style: Default::default(),
+            });
+
+            //Images
+            let image_bytes = include_bytes!("images/rust-logo-128x128-blk.png");
+            let image_image = image::load_from_memory(image_bytes).unwrap();
+            let image_rgba = image_image.as_rgba8().unwrap().clone().into_raw();
+
+            use image::GenericImageView;
+            let dimensions = image_image.dimensions();
+
+            let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+            self.rust_logo = rend3_egui::EguiRenderRoutine::create_egui_texture(
+                &mut egui_routine.internal,
+                renderer,
+                format,
+                &image_rgba,
+                dimensions,
+                Some("rust_logo_texture"),
+            );
+
+            let start_time = instant::Instant::now();
+            let color: [f32; 4] = [0.0, 0.5, 0.5, 1.0];
+
+            self.data = Some(EguiExampleData {
+                _object_handle,
+                material_handle,
+                _directional_handle,
+
+                egui_routine,
+                platform,
+                camera,
+                viewport: None,
+                start_time,
+                color,
+            });
+        })
     }
 
     fn handle_event(
@@ -163,8 +171,29 @@ impl rend3_framework::App for EguiExample {
                 data.platform.update_time(data.start_time.elapsed().as_secs_f64());
                 data.platform.begin_frame();
 
+                // Look at the cube from the other side in the viewport window
+                let viewport = data.viewport.get_or_insert_with(|| {
+                    let location = glam::Vec3::new(-5.0, 7.5, 5.0);
+                    let view = glam::Mat4::from_euler(
+                        glam::EulerRot::XYZ,
+                        -std::f32::consts::FRAC_PI_4,
+                        -3.0 * std::f32::consts::FRAC_PI_4,
+                        0.0,
+                    );
+                    let camera = rend3::types::Camera {
+                        projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+                        view: view * glam::Mat4::from_translation(-location),
+                    };
+                    rend3_egui::EguiViewport::new(renderer, base_rendergraph, camera)
+                });
+
                 // Insert egui commands here
                 let ctx = data.platform.context();
+                let egui_routine = &mut data.egui_routine;
+                egui::Window::new("Viewport")
+                    .default_size(egui::Vec2::new(320.0, 240.0))
+                    .resizable(true)
+                    .show(&ctx, |ui| viewport.show(ui, egui_routine, renderer));
                 egui::Window::new("Change color").resizable(true).show(&ctx, |ui| {
                     ui.label("Change the color of the cube");
                     if ui.color_edit_button_rgba_unmultiplied(&mut data.color).changed() {
@@ -204,13 +233,26 @@ impl rend3_framework::App for EguiExample {
                     surface: Arc::clone(surface.unwrap()),
                 };
 
-                // Ready up the renderer
-                let (cmd_bufs, ready) = renderer.ready();
-
                 // Lock the routines
                 let pbr_routine = rend3_framework::lock(&routines.pbr);
                 let tonemapping_routine = rend3_framework::lock(&routines.tonemapping);
 
+                // Render the viewport first, then switch back to the camera of the window
+                viewport.render(
+                    renderer,
+                    base_rendergraph,
+                    &pbr_routine,
+                    None,
+                    SAMPLE_COUNT,
+                    glam::Vec4::ZERO,
+                    glam::Vec4::new(0.10, 0.05, 0.10, 1.0),
+                );
+                renderer.set_aspect_ratio(resolution.x as f32 / resolution.y as f32);
+                renderer.set_camera_data(data.camera);
+
+                // Ready up the renderer
+                let (cmd_bufs, ready) = renderer.ready();
+
                 // Build a rendergraph
                 let mut graph = rend3::graph::RenderGraph::new();
 
@@ -218,13 +260,15 @@ impl rend3_framework::App for EguiExample {
                 base_rendergraph.add_to_graph(
                     &mut graph,
                     &ready,
-                    &pbr_routine,
-                    None,
-                    &tonemapping_routine,
-                    resolution,
-                    SAMPLE_COUNT,
-                    glam::Vec4::ZERO,
-                    glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                    rend3_routine::base::BaseRenderGraphRoutines {
+                        pbr: &pbr_routine,
+                        skybox: None,
+                        tonemapping: &tonemapping_routine,
+                    },
+                    &rend3_routine::base::BaseRenderGraphSettings::new(resolution)
+                        .with_samples(SAMPLE_COUNT)
+                        .with_ambient(glam::Vec4::ZERO)
+                        .with_clear_color(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)), // Nice scene-referred purple
                 );
 
                 // Add egui on top of all the other passes
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{future::Future, pin::Pin, sync::Arc};
 
 struct ImguiExampleData {
     _object_handle: rend3::types::ObjectHandle,
@@ -25,102 +25,104 @@ impl rend3_framework::App for ImguiExample {
         SAMPLE_COUNT
     }
 
-    fn setup(
-        &mut self,
-        window: &winit::window::Window,
-        renderer: &Arc<rend3::Renderer>,
-        _routines: &Arc<rend3_framework::DefaultRoutines>,
+    fn setup<'a>(
+        &'a mut self,
+        window: &'a winit::window::Window,
+        renderer: &'a Arc<rend3::Renderer>,
+        _routines: &'a Arc<rend3_framework::DefaultRoutines>,
         surface_format: rend3::types::TextureFormat,
-    ) {
-        // Set up imgui
-        let mut imgui = imgui::Context::create();
-        let mut platform = imgui_winit_support::WinitPlatform::init(&mut imgui);
-        platform.attach_window(imgui.io_mut(), window, imgui_winit_support::HiDpiMode::Default);
-        imgui.set_ini_filename(None);
-
-        let hidpi_factor = window.scale_factor();
-
-        let font_size = (13.0 * hidpi_factor) as f32;
-        imgui.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
-
-        imgui.fonts().add_font(&[imgui::FontSource::DefaultFontData {
-            config: Some(imgui::FontConfig {
-                oversample_h: 1,
-                pixel_snap_h: true,
-                size_pixels: font_size,
-                ..Default::default()
-            }),
-        }]);
-
-        // Create the imgui render routine
-        let imgui_routine = rend3_imgui::ImguiRenderRoutine::new(renderer, &mut imgui, surface_format);
-
-        // Create mesh and calculate smooth normals based on vertices
-        let mesh = create_mesh();
-
-        // Add mesh to renderer's world.
-        //
-        // All handles are refcounted, so we only need to hang onto the handle until we
-        // make an object.
-        let mesh_handle = renderer.add_mesh(mesh);
-
-        // Add PBR material with all defaults except a single color.
-        let material = rend3_routine::pbr::PbrMaterial {
-            albedo: rend3_routine::pbr::AlbedoComponent::Value(glam::Vec4::new(0.0, 0.5, 0.5, 1.0)),
-            ..rend3_routine::pbr::PbrMaterial::default()
-        };
-        let material_handle = renderer.add_material(material);
-
-        // Combine the mesh and the material with a location to give an object.
-        let object = rend3::types::Object {
-            mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
-            material: material_handle,
-            transform: glam::Mat4::IDENTITY,
-        };
-
-        // Creating an object will hold onto both the mesh and the material
-        // even if they are deleted.
-        //
-        // We need to keep the object handle alive.
-        let _object_handle = renderer.add_object(object);
-
-        let camera_pitch = std::f32::consts::FRAC_PI_4;
-        let camera_yaw = -std::f32::consts::FRAC_PI_4;
-        // These values may seem arbitrary, but they center the camera on the cube in
-        // the scene
-        let camera_location = glam::Vec3A::new(5.0, 7.5, -5.0);
-        let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, -camera_pitch, -camera_yaw, 0.0);
-        let view = view * glam::Mat4::from_translation((-camera_location).into());
-
-        // Set camera location data
-        renderer.set_camera_data(rend3::types::Camera {
-            projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
-            view,
-        });
-
-        // Create a single directional light
-        //
-        // We need to keep the directional light handle alive.
-        let _directional_handle = renderer.add_directional_light(rend3::types::DirectionalLight {
-            color: glam::Vec3::ONE,
-            intensity: 10.0,
-            // Direction will be normalized
-            direction: glam::Vec3::new(-1.0, -4.0, 2.0),
-            distance: 400.0,
-        });
-
-        let frame_start = instant::Instant::now();
-
-        self.data = Some(ImguiExampleData {
-            _object_handle,
-            _directional_handle,
-
-            imgui,
-            platform,
-            imgui_routine,
-            frame_start,
-
-            demo_window_open: true,
+    ) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        Box::pin(async move {
+            // Set up imgui
+            let mut imgui = imgui::Context::create();
+            let mut platform = imgui_winit_support::WinitPlatform::init(&mut imgui);
+            platform.attach_window(imgui.io_mut(), window, imgui_winit_support::HiDpiMode::Default);
+            imgui.set_ini_filename(None);
+
+            let hidpi_factor = window.scale_factor();
+
+            let font_size = (13.0 * hidpi_factor) as f32;
+            imgui.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
+
+            imgui.fonts().add_font(&[imgui::FontSource::DefaultFontData {
+                config: Some(imgui::FontConfig {
+                    oversample_h: 1,
+                    pixel_snap_h: true,
+                    size_pixels: font_size,
+                    ..Default::default()
+                }),
+            }]);
+
+            // Create the imgui render routine
+            let imgui_routine = rend3_imgui::ImguiRenderRoutine::new(renderer, &mut imgui, surface_format);
+
+            // Create mesh and calculate smooth normals based on vertices
+            let mesh = create_mesh();
+
+            // Add mesh to renderer's world.
+            //
+            // All handles are refcounted, so we only need to hang onto the handle until we
+            // make an object.
+            let mesh_handle = renderer.add_mesh(mesh).unwrap();
+
+            // Add PBR material with all defaults except a single color.
+            let material = rend3_routine::pbr::PbrMaterial {
+                albedo: rend3_routine::pbr::AlbedoComponent::Value(glam::Vec4::new(0.0, 0.5, 0.5, 1.0)),
+                ..rend3_routine::pbr::PbrMaterial::default()
+            };
+            let material_handle = renderer.add_material(material);
+
+            // Combine the mesh and the material with a location to give an object.
+            let object = rend3::types::Object {
+                mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
+                material: material_handle,
+                transform: glam::Mat4::IDENTITY,
+            };
+
+            // Creating an object will hold onto both the mesh and the material
+            // even if they are deleted.
+            //
+            // We need to keep the object handle alive.
+            let _object_handle = renderer.add_object(object);
+
+            let camera_pitch = std::f32::consts::FRAC_PI_4;
+            let camera_yaw = -std::f32::consts::FRAC_PI_4;
+            // These values may seem arbitrary, but they center the camera on the cube in
+            // the scene
+            let camera_location = glam::Vec3A::new(5.0, 7.5, -5.0);
+            let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, -camera_pitch, -camera_yaw, 0.0);
+            let view = view * glam::Mat4::from_translation((-camera_location).into());
+
+            // Set camera location data
+            renderer.set_camera_data(rend3::types::Camera {
+                projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+                view,
+            });
+
+            // Create a single directional light
+            //
+            // We need to keep the directional light handle alive.
+            let _directional_handle = renderer.add_directional_light(rend3::types::DirectionalLight {
+                color: glam::Vec3::ONE,
+                intensity: 10.0,
+                // Direction will be normalized
+                direction: glam::Vec3::new(-1.0, -4.0, 2.0),
+                distance: 400.0,
+            });
+
+            let frame_start = instant::Instant::now();
+
+            self.data = Some(ImguiExampleData {
+                _object_handle,
+                _directional_handle,
+
+                imgui,
+                platform,
+                imgui_routine,
+                frame_start,
+
+                demo_window_open: true,
+            })
         })
     }
 
@@ -173,13 +175,15 @@ impl rend3_framework::App for ImguiExample {
                 base_rendergraph.add_to_graph(
                     &mut graph,
                     &ready,
-                    &pbr_routine,
-                    None,
-                    &tonemapping_routine,
-                    resolution,
-                    SAMPLE_COUNT,
-                    glam::Vec4::ZERO,
-                    glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                    rend3_routine::base::BaseRenderGraphRoutines {
+                        pbr: &pbr_routine,
+                        skybox: None,
+                        tonemapping: &tonemapping_routine,
+                    },
+                    &rend3_routine::base::BaseRenderGraphSettings::new(resolution)
+                        .with_samples(SAMPLE_COUNT)
+                        .with_ambient(glam::Vec4::ZERO)
+                        .with_clear_color(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)), // Nice scene-referred purple
                 );
 
                 // Add imgui on top of all the other passes
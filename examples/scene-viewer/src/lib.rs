@@ -3,8 +3,8 @@ use instant::Instant;
 use pico_args::Arguments;
 use rend3::{
     types::{
-        Backend, Camera, CameraProjection, DirectionalLight, DirectionalLightHandle, SampleCount, Texture,
-        TextureFormat,
+        Backend, Camera, CameraProjection, ColorSpace, DirectionalLight, DirectionalLightHandle, PowerPreference,
+        SampleCount, Texture, TextureFormat,
     },
     util::typedefs::FastHashMap,
     Renderer, RendererProfile,
@@ -12,7 +12,15 @@ use rend3::{
 use rend3_framework::{lock, AssetPath, Mutex};
 use rend3_gltf::GltfSceneInstance;
 use rend3_routine::{base::BaseRenderGraph, pbr::NormalTextureYDirection, skybox::SkyboxRoutine};
-use std::{collections::HashMap, future::Future, hash::BuildHasher, path::Path, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::BuildHasher,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
 use wgpu_profiler::GpuTimerScopeResult;
 use winit::{
     event::{DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, WindowEvent},
@@ -49,12 +57,13 @@ async fn load_skybox(
 
     let handle = renderer.add_texture_cube(Texture {
         format: TextureFormat::Rgba8UnormSrgb,
+        color_space: ColorSpace::FromFormat,
         size: UVec2::new(2048, 2048),
         data,
         label: Some("background".into()),
         mip_count: rend3::types::MipmapCount::ONE,
         mip_source: rend3::types::MipmapSource::Uploaded,
-    });
+    })?;
     lock(skybox_routine).set_background_texture(Some(handle));
     Ok(())
 }
@@ -144,6 +153,7 @@ fn extract_mode(value: &str) -> Result<rend3::RendererProfile, &'static str> {
     Ok(match value.to_lowercase().as_str() {
         "legacy" | "c" | "cpu" => rend3::RendererProfile::CpuDriven,
         "modern" | "g" | "gpu" => rend3::RendererProfile::GpuDriven,
+        "downlevel" | "d" | "webgl" => rend3::RendererProfile::Downlevel,
         _ => return Err("unknown rendermode"),
     })
 }
@@ -223,8 +233,10 @@ Meta:
 Rendering:
   -b --backend                 Choose backend to run on ('vk', 'dx12', 'dx11', 'metal', 'gl').
   -d --device                  Choose device to run on (case insensitive device substring).
-  -p --profile                 Choose rendering profile to use ('cpu', 'gpu').
+  -p --profile                 Choose rendering profile to use ('cpu', 'gpu', 'downlevel').
+  --low-power                  Prefer integrated GPUs over discrete GPUs.
   --msaa <level>               Level of antialiasing (either 1 or 4). Default 1.
+  --shader-dir <path>          Load the shaders from this copy of rend3-routine/shaders and reload them when they change.
 
 Windowing:
   --absolute-mouse             Interpret the relative mouse coordinates as absolute. Useful when using things like VNC.
@@ -249,6 +261,7 @@ struct SceneViewer {
     desired_backend: Option<Backend>,
     desired_device_name: Option<String>,
     desired_profile: Option<RendererProfile>,
+    power_preference: PowerPreference,
     file_to_load: Option<String>,
     walk_speed: f32,
     run_speed: f32,
@@ -258,6 +271,7 @@ struct SceneViewer {
     directional_light: Option<DirectionalLightHandle>,
     ambient_light_level: f32,
     samples: SampleCount,
+    shader_directory: Option<PathBuf>,
 
     fullscreen: bool,
 
@@ -266,6 +280,7 @@ struct SceneViewer {
     camera_yaw: f32,
     camera_location: Vec3A,
     previous_profiling_stats: Option<Vec<GpuTimerScopeResult>>,
+    last_frame_time: Duration,
     timestamp_last_second: Instant,
     timestamp_last_frame: Instant,
     frame_times: histogram::Histogram,
@@ -286,6 +301,11 @@ impl SceneViewer {
             option_arg(args.opt_value_from_str(["-d", "--device"])).map(|s: String| s.to_lowercase());
         let desired_mode = option_arg(args.opt_value_from_fn(["-p", "--profile"], extract_mode));
         let samples = option_arg(args.opt_value_from_fn("--msaa", extract_msaa)).unwrap_or(SampleCount::One);
+        let shader_directory: Option<PathBuf> = option_arg(args.opt_value_from_str("--shader-dir"));
+        let power_preference = match args.contains("--low-power") {
+            true => PowerPreference::LowPower,
+            false => PowerPreference::HighPerformance,
+        };
 
         // Windowing
         let absolute_mouse: bool = args.contains("--absolute-mouse");
@@ -346,6 +366,7 @@ impl SceneViewer {
             desired_backend,
             desired_device_name,
             desired_profile: desired_mode,
+            power_preference,
             file_to_load,
             walk_speed,
             run_speed,
@@ -355,6 +376,7 @@ impl SceneViewer {
             directional_light: None,
             ambient_light_level,
             samples,
+            shader_directory,
 
             fullscreen,
 
@@ -363,6 +385,7 @@ impl SceneViewer {
             camera_yaw: std::f32::consts::FRAC_PI_4,
             camera_location: Vec3A::new(3.0, 3.0, 3.0),
             previous_profiling_stats: None,
+            last_frame_time: Duration::ZERO,
             timestamp_last_second: Instant::now(),
             timestamp_last_frame: Instant::now(),
             frame_times: histogram::Histogram::new(),
@@ -375,18 +398,23 @@ impl SceneViewer {
 impl rend3_framework::App for SceneViewer {
     const HANDEDNESS: rend3::types::Handedness = rend3::types::Handedness::Right;
 
-    fn create_iad<'a>(
-        &'a mut self,
-    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<rend3::InstanceAdapterDevice>> + 'a>> {
-        Box::pin(async move {
-            Ok(rend3::create_iad(
-                self.desired_backend,
-                self.desired_device_name.clone(),
-                self.desired_profile,
-                None,
-            )
-            .await?)
-        })
+    fn iad_settings(&self) -> rend3::IadSettings {
+        rend3::IadSettings {
+            backend: self.desired_backend,
+            device_name: self.desired_device_name.clone(),
+            profile: self.desired_profile,
+            power_preference: self.power_preference,
+            ..rend3::IadSettings::default()
+        }
+    }
+
+    fn shader_hot_reload(&self) -> Option<rend3_framework::ShaderHotReload> {
+        self.shader_directory
+            .clone()
+            .map(|directory| rend3_framework::ShaderHotReload {
+                routine_shaders: Some(directory),
+                watch: Vec::new(),
+            })
     }
 
     fn sample_count(&self) -> SampleCount {
@@ -411,43 +439,45 @@ impl rend3_framework::App for SceneViewer {
         renderer: &'a Arc<Renderer>,
         routines: &'a Arc<rend3_framework::DefaultRoutines>,
         _surface_format: rend3::types::TextureFormat,
-    ) {
-        self.grabber = Some(rend3_framework::Grabber::new(window));
-
-        if let Some(direction) = self.directional_light_direction {
-            self.directional_light = Some(renderer.add_directional_light(DirectionalLight {
-                color: Vec3::splat(1.0),
-                intensity: self.directional_light_intensity,
-                direction,
-                distance: self.gltf_settings.directional_light_shadow_distance,
-            }));
-        }
+    ) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        Box::pin(async move {
+            self.grabber = Some(rend3_framework::Grabber::new(window));
+
+            if let Some(direction) = self.directional_light_direction {
+                self.directional_light = Some(renderer.add_directional_light(DirectionalLight {
+                    color: Vec3::splat(1.0),
+                    intensity: self.directional_light_intensity,
+                    direction,
+                    distance: self.gltf_settings.directional_light_shadow_distance,
+                }));
+            }
 
-        let gltf_settings = self.gltf_settings;
-        let file_to_load = self.file_to_load.take();
-        let renderer = Arc::clone(renderer);
-        let routines = Arc::clone(routines);
-        spawn(async move {
-            let loader = rend3_framework::AssetLoader::new_local(
-                concat!(env!("CARGO_MANIFEST_DIR"), "/resources/"),
-                "",
-                "http://localhost:8000/resources/",
-            );
-            if let Err(e) = load_skybox(&renderer, &loader, &routines.skybox).await {
-                println!("Failed to load skybox {}", e)
-            };
-            Box::leak(Box::new(
-                load_gltf(
-                    &renderer,
-                    &loader,
-                    &gltf_settings,
-                    file_to_load
-                        .as_deref()
-                        .map_or_else(|| AssetPath::Internal("default-scene/scene.gltf"), AssetPath::External),
-                )
-                .await,
-            ));
-        });
+            let gltf_settings = self.gltf_settings;
+            let file_to_load = self.file_to_load.take();
+            let renderer = Arc::clone(renderer);
+            let routines = Arc::clone(routines);
+            spawn(async move {
+                let loader = rend3_framework::AssetLoader::new_local(
+                    concat!(env!("CARGO_MANIFEST_DIR"), "/resources/"),
+                    "",
+                    "http://localhost:8000/resources/",
+                );
+                if let Err(e) = load_skybox(&renderer, &loader, &routines.skybox).await {
+                    println!("Failed to load skybox {}", e)
+                };
+                Box::leak(Box::new(
+                    load_gltf(
+                        &renderer,
+                        &loader,
+                        &gltf_settings,
+                        file_to_load
+                            .as_deref()
+                            .map_or_else(|| AssetPath::Internal("default-scene/scene.gltf"), AssetPath::External),
+                    )
+                    .await,
+                ));
+            });
+        })
     }
 
     fn handle_event(
@@ -494,6 +524,7 @@ impl rend3_framework::App for SceneViewer {
                 }
 
                 self.timestamp_last_frame = now;
+                self.last_frame_time = delta_time;
 
                 let rotation =
                     Mat3A::from_euler(glam::EulerRot::XYZ, -self.camera_pitch, -self.camera_yaw, 0.0).transpose();
@@ -557,6 +588,15 @@ impl rend3_framework::App for SceneViewer {
                 let pbr_routine = lock(&routines.pbr);
                 let mut skybox_routine = lock(&routines.skybox);
                 let tonemapping_routine = lock(&routines.tonemapping);
+                let mut hud_routine = lock(&routines.hud);
+
+                // Record the statistics of the last frame for the HUD
+                hud_routine.record_frame(
+                    self.last_frame_time,
+                    self.previous_profiling_stats.as_ref(),
+                    base_rendergraph.draw_statistics.counts(),
+                    renderer.memory_usage(),
+                );
 
                 // Ready up the renderer
                 let (cmd_bufs, ready) = renderer.ready();
@@ -570,15 +610,20 @@ impl rend3_framework::App for SceneViewer {
                 base_rendergraph.add_to_graph(
                     &mut graph,
                     &ready,
-                    &pbr_routine,
-                    Some(&skybox_routine),
-                    &tonemapping_routine,
-                    resolution,
-                    self.samples,
-                    Vec3::splat(self.ambient_light_level).extend(1.0),
-                    glam::Vec4::new(0.0, 0.0, 0.0, 1.0),
+                    rend3_routine::base::BaseRenderGraphRoutines {
+                        pbr: &pbr_routine,
+                        skybox: Some(&skybox_routine),
+                        tonemapping: &tonemapping_routine,
+                    },
+                    &rend3_routine::base::BaseRenderGraphSettings::for_renderer(renderer, resolution)
+                        .with_ambient(Vec3::splat(self.ambient_light_level).extend(1.0))
+                        .with_clear_color(glam::Vec4::new(0.0, 0.0, 0.0, 1.0)),
                 );
 
+                // Draw the HUD on top, if it is shown
+                let surface = graph.add_surface_texture();
+                hud_routine.add_to_graph(&mut graph, surface, resolution);
+
                 // Dispatch a render using the built up rendergraph!
                 self.previous_profiling_stats = graph.execute(renderer, frame, cmd_bufs, &ready);
                 // mark the end of the frame for tracy/other profilers
@@ -601,6 +646,22 @@ impl rend3_framework::App for SceneViewer {
                 ..
             } => {
                 log::info!("WE scancode {:x}", scancode);
+                if scancode == platform::Scancodes::H
+                    && state == ElementState::Pressed
+                    && !button_pressed(&self.scancode_status, scancode)
+                {
+                    lock(&routines.hud).toggle();
+                }
+                // Toggle MSAA, which the framework hands to the renderer.
+                if scancode == platform::Scancodes::M
+                    && state == ElementState::Pressed
+                    && !button_pressed(&self.scancode_status, scancode)
+                {
+                    self.samples = match self.samples {
+                        SampleCount::One => SampleCount::Four,
+                        SampleCount::Four => SampleCount::One,
+                    };
+                }
                 self.scancode_status.insert(
                     scancode,
                     match state {
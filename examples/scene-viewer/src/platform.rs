@@ -11,6 +11,8 @@ cfg_if::cfg_if!(
             pub const Q: u32 = 0x0C;
             pub const Z: u32 = 0x06;
             pub const P: u32 = 0x23;
+            pub const H: u32 = 0x04;
+            pub const M: u32 = 0x2E;
             pub const SEMICOLON: u32 = 0x29;
             pub const QUOTE: u32 = 0x27;
             pub const COMMA: u32 = 0x2B;
@@ -28,6 +30,8 @@ cfg_if::cfg_if!(
             pub const Q: u32 = 0x51;
             pub const Z: u32 = 0x5a;
             pub const P: u32 = 0x50;
+            pub const H: u32 = 0x48;
+            pub const M: u32 = 0x4D;
             pub const SEMICOLON: u32 = 0xba;
             pub const QUOTE: u32 = 0xde;
             pub const COMMA: u32 = 0xbc;
@@ -45,6 +49,8 @@ cfg_if::cfg_if!(
             pub const Q: u32 = 0x10;
             pub const Z: u32 = 0x2C;
             pub const P: u32 = 0x19;
+            pub const H: u32 = 0x23;
+            pub const M: u32 = 0x32;
             pub const SEMICOLON: u32 = 0x27;
             pub const QUOTE: u32 = 0x28;
             pub const COMMA: u32 = 0x33;
@@ -1,4 +1,4 @@
-use std::{path::Path, sync::Arc, time::Instant};
+use std::{future::Future, path::Path, pin::Pin, sync::Arc, time::Instant};
 
 use rend3_gltf::GltfSceneInstance;
 
@@ -63,57 +63,60 @@ impl rend3_framework::App for SkinningExample {
         SAMPLE_COUNT
     }
 
-    fn setup(
-        &mut self,
-        _window: &winit::window::Window,
-        renderer: &Arc<rend3::Renderer>,
-        _routines: &Arc<rend3_framework::DefaultRoutines>,
+    fn setup<'a>(
+        &'a mut self,
+        _window: &'a winit::window::Window,
+        renderer: &'a Arc<rend3::Renderer>,
+        _routines: &'a Arc<rend3_framework::DefaultRoutines>,
         _surface_format: rend3::types::TextureFormat,
-    ) {
-        // Store the startup time. Use later to animate the joint rotation
-        self.start_time = Some(Instant::now());
-
-        let view_location = glam::Vec3::new(0.0, 0.0, -10.0);
-        let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0);
-        let view = view * glam::Mat4::from_translation(-view_location);
-
-        // Set camera's location
-        renderer.set_camera_data(rend3::types::Camera {
-            projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
-            view,
-        });
-
-        // Load a gltf model with animation data
-        let path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/RiggedSimple.glb"));
-        let gltf_data = std::fs::read(&path).unwrap();
-        let parent_directory = path.parent().unwrap();
-        let (loaded_scene, loaded_instance) = pollster::block_on(rend3_gltf::load_gltf(
-            renderer,
-            &gltf_data,
-            &rend3_gltf::GltfLoadSettings::default(),
-            |p| async move { rend3_gltf::filesystem_io_func(&parent_directory, &p).await },
-        ))
-        .expect("Loading gltf scene");
-
-        // The returned loaded model contains a node hierarchy with a complete
-        // scene. We know in our case there will be a single node in the tree
-        // with an armature.
-        self.armature = Some(find_armature(&loaded_instance).unwrap());
-
-        // Store the loaded model somewhere, otherwise all the data gets freed.
-        self.loaded_scene = Some(loaded_scene);
-        self.loaded_instance = Some(loaded_instance);
-
-        // Create a single directional light
-        //
-        // We need to keep the directional light handle alive.
-        self.directional_light_handle = Some(renderer.add_directional_light(rend3::types::DirectionalLight {
-            color: glam::Vec3::ONE,
-            intensity: 10.0,
-            // Direction will be normalized
-            direction: glam::Vec3::new(-1.0, -4.0, 2.0),
-            distance: 400.0,
-        }));
+    ) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        Box::pin(async move {
+            // Store the startup time. Use later to animate the joint rotation
+            self.start_time = Some(Instant::now());
+
+            let view_location = glam::Vec3::new(0.0, 0.0, -10.0);
+            let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0);
+            let view = view * glam::Mat4::from_translation(-view_location);
+
+            // Set camera's location
+            renderer.set_camera_data(rend3::types::Camera {
+                projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+                view,
+            });
+
+            // Load a gltf model with animation data
+            let path = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/RiggedSimple.glb"));
+            let gltf_data = std::fs::read(&path).unwrap();
+            let parent_directory = path.parent().unwrap();
+            let (loaded_scene, loaded_instance) = rend3_gltf::load_gltf(
+                renderer,
+                &gltf_data,
+                &rend3_gltf::GltfLoadSettings::default(),
+                |p| async move { rend3_gltf::filesystem_io_func(&parent_directory, &p).await },
+            )
+            .await
+            .expect("Loading gltf scene");
+
+            // The returned loaded model contains a node hierarchy with a complete
+            // scene. We know in our case there will be a single node in the tree
+            // with an armature.
+            self.armature = Some(find_armature(&loaded_instance).unwrap());
+
+            // Store the loaded model somewhere, otherwise all the data gets freed.
+            self.loaded_scene = Some(loaded_scene);
+            self.loaded_instance = Some(loaded_instance);
+
+            // Create a single directional light
+            //
+            // We need to keep the directional light handle alive.
+            self.directional_light_handle = Some(renderer.add_directional_light(rend3::types::DirectionalLight {
+                color: glam::Vec3::ONE,
+                intensity: 10.0,
+                // Direction will be normalized
+                direction: glam::Vec3::new(-1.0, -4.0, 2.0),
+                distance: 400.0,
+            }));
+        })
     }
 
     fn handle_event(
@@ -159,13 +162,15 @@ impl rend3_framework::App for SkinningExample {
                 base_rendergraph.add_to_graph(
                     &mut graph,
                     &ready,
-                    &pbr_routine,
-                    None,
-                    &tonemapping_routine,
-                    resolution,
-                    SAMPLE_COUNT,
-                    glam::Vec4::splat(0.15),
-                    glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                    rend3_routine::base::BaseRenderGraphRoutines {
+                        pbr: &pbr_routine,
+                        skybox: None,
+                        tonemapping: &tonemapping_routine,
+                    },
+                    &rend3_routine::base::BaseRenderGraphSettings::new(resolution)
+                        .with_samples(SAMPLE_COUNT)
+                        .with_ambient(glam::Vec4::splat(0.15))
+                        .with_clear_color(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)), // Nice scene-referred purple
                 );
 
                 // Dispatch a render using the built up rendergraph!
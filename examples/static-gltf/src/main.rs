@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{future::Future, pin::Pin, sync::Arc};
 
 fn load_gltf(
     renderer: &rend3::Renderer,
@@ -36,7 +36,7 @@ fn load_gltf(
         .unwrap();
 
     // Add mesh to renderer's world
-    let mesh_handle = renderer.add_mesh(mesh);
+    let mesh_handle = renderer.add_mesh(mesh).unwrap();
 
     // Add basic material with all defaults except a single color.
     let material = primitive.material();
@@ -64,47 +64,49 @@ impl rend3_framework::App for GltfExample {
         SAMPLE_COUNT
     }
 
-    fn setup(
-        &mut self,
-        _window: &winit::window::Window,
-        renderer: &Arc<rend3::Renderer>,
-        _routines: &Arc<rend3_framework::DefaultRoutines>,
+    fn setup<'a>(
+        &'a mut self,
+        _window: &'a winit::window::Window,
+        renderer: &'a Arc<rend3::Renderer>,
+        _routines: &'a Arc<rend3_framework::DefaultRoutines>,
         _surface_format: rend3::types::TextureFormat,
-    ) {
-        // Create mesh and calculate smooth normals based on vertices.
-        //
-        // We do not need to keep these handles alive once we make the object
-        let (mesh, material) = load_gltf(renderer, concat!(env!("CARGO_MANIFEST_DIR"), "/data.glb"));
-
-        // Combine the mesh and the material with a location to give an object.
-        let object = rend3::types::Object {
-            mesh_kind: rend3::types::ObjectMeshKind::Static(mesh),
-            material,
-            transform: glam::Mat4::from_scale(glam::Vec3::new(1.0, 1.0, -1.0)),
-        };
-        // We need to keep the object alive.
-        self.object_handle = Some(renderer.add_object(object));
-
-        let view_location = glam::Vec3::new(3.0, 3.0, -5.0);
-        let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, -0.55, 0.5, 0.0);
-        let view = view * glam::Mat4::from_translation(-view_location);
-
-        // Set camera's location
-        renderer.set_camera_data(rend3::types::Camera {
-            projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
-            view,
-        });
-
-        // Create a single directional light
-        //
-        // We need to keep the directional light handle alive.
-        self.directional_light_handle = Some(renderer.add_directional_light(rend3::types::DirectionalLight {
-            color: glam::Vec3::ONE,
-            intensity: 4.0,
-            // Direction will be normalized
-            direction: glam::Vec3::new(-1.0, -4.0, 2.0),
-            distance: 20.0,
-        }));
+    ) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        Box::pin(async move {
+            // Create mesh and calculate smooth normals based on vertices.
+            //
+            // We do not need to keep these handles alive once we make the object
+            let (mesh, material) = load_gltf(renderer, concat!(env!("CARGO_MANIFEST_DIR"), "/data.glb"));
+
+            // Combine the mesh and the material with a location to give an object.
+            let object = rend3::types::Object {
+                mesh_kind: rend3::types::ObjectMeshKind::Static(mesh),
+                material,
+                transform: glam::Mat4::from_scale(glam::Vec3::new(1.0, 1.0, -1.0)),
+            };
+            // We need to keep the object alive.
+            self.object_handle = Some(renderer.add_object(object));
+
+            let view_location = glam::Vec3::new(3.0, 3.0, -5.0);
+            let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, -0.55, 0.5, 0.0);
+            let view = view * glam::Mat4::from_translation(-view_location);
+
+            // Set camera's location
+            renderer.set_camera_data(rend3::types::Camera {
+                projection: rend3::types::CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+                view,
+            });
+
+            // Create a single directional light
+            //
+            // We need to keep the directional light handle alive.
+            self.directional_light_handle = Some(renderer.add_directional_light(rend3::types::DirectionalLight {
+                color: glam::Vec3::ONE,
+                intensity: 4.0,
+                // Direction will be normalized
+                direction: glam::Vec3::new(-1.0, -4.0, 2.0),
+                distance: 20.0,
+            }));
+        })
     }
 
     fn handle_event(
@@ -149,13 +151,15 @@ impl rend3_framework::App for GltfExample {
                 base_rendergraph.add_to_graph(
                     &mut graph,
                     &ready,
-                    &pbr_routine,
-                    None,
-                    &tonemapping_routine,
-                    resolution,
-                    SAMPLE_COUNT,
-                    glam::Vec4::ZERO,
-                    glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                    rend3_routine::base::BaseRenderGraphRoutines {
+                        pbr: &pbr_routine,
+                        skybox: None,
+                        tonemapping: &tonemapping_routine,
+                    },
+                    &rend3_routine::base::BaseRenderGraphSettings::new(resolution)
+                        .with_samples(SAMPLE_COUNT)
+                        .with_ambient(glam::Vec4::ZERO)
+                        .with_clear_color(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)), // Nice scene-referred purple
                 );
                 // Dispatch a render using the built up rendergraph!
                 graph.execute(renderer, frame, cmd_bufs, &ready);
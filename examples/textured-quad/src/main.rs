@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{future::Future, pin::Pin, sync::Arc};
 
 use image::GenericImageView;
 
@@ -47,79 +47,82 @@ impl rend3_framework::App for TexturedQuadExample {
         SAMPLE_COUNT
     }
 
-    fn setup(
-        &mut self,
-        window: &winit::window::Window,
-        renderer: &Arc<rend3::Renderer>,
-        _routines: &Arc<rend3_framework::DefaultRoutines>,
+    fn setup<'a>(
+        &'a mut self,
+        window: &'a winit::window::Window,
+        renderer: &'a Arc<rend3::Renderer>,
+        _routines: &'a Arc<rend3_framework::DefaultRoutines>,
         _surface_format: rend3::types::TextureFormat,
-    ) {
-        // Create mesh and calculate smooth normals based on vertices
-        let mesh = create_quad(300.0);
-
-        // Add mesh to renderer's world.
-        //
-        // All handles are refcounted, so we only need to hang onto the handle until we
-        // make an object.
-        let mesh_handle = renderer.add_mesh(mesh);
-
-        // Add texture to renderer's world.
-        let image_checker =
-            image::load_from_memory(include_bytes!("checker.png")).expect("Failed to load image from memory");
-        let image_checker_rgba8 = image_checker.to_rgba8();
-        let texture_checker = rend3::types::Texture {
-            label: Option::None,
-            data: image_checker_rgba8.to_vec(),
-            format: rend3::types::TextureFormat::Rgba8UnormSrgb,
-            size: glam::UVec2::new(image_checker.dimensions().0, image_checker.dimensions().1),
-            mip_count: rend3::types::MipmapCount::ONE,
-            mip_source: rend3::types::MipmapSource::Uploaded,
-        };
-        let texture_checker_handle = renderer.add_texture_2d(texture_checker);
-
-        // Add PBR material with all defaults except a single color.
-        let material = rend3_routine::pbr::PbrMaterial {
-            albedo: rend3_routine::pbr::AlbedoComponent::Texture(texture_checker_handle),
-            unlit: true,
-            sample_type: rend3_routine::pbr::SampleType::Nearest,
-            ..rend3_routine::pbr::PbrMaterial::default()
-        };
-        let material_handle = renderer.add_material(material);
-
-        // Combine the mesh and the material with a location to give an object.
-        let object = rend3::types::Object {
-            mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
-            material: material_handle,
-            transform: glam::Mat4::from_scale_rotation_translation(
-                glam::Vec3::new(1.0, 1.0, 1.0),
-                glam::Quat::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0),
-                glam::Vec3::new(0.0, 0.0, 0.0),
-            ),
-        };
-
-        // Creating an object will hold onto both the mesh and the material
-        // even if they are deleted.
-        //
-        // We need to keep the object handle alive.
-        let _object_handle = renderer.add_object(object);
-
-        let view_location = glam::Vec3::new(0.0, 0.0, -1.0);
-        let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0);
-        let view = view * glam::Mat4::from_translation(-view_location);
-
-        // Set camera's location
-        renderer.set_camera_data(rend3::types::Camera {
-            projection: rend3::types::CameraProjection::Orthographic {
-                size: glam::Vec3A::new(
-                    window.inner_size().width as f32,
-                    window.inner_size().height as f32,
-                    CAMERA_DEPTH,
+    ) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        Box::pin(async move {
+            // Create mesh and calculate smooth normals based on vertices
+            let mesh = create_quad(300.0);
+
+            // Add mesh to renderer's world.
+            //
+            // All handles are refcounted, so we only need to hang onto the handle until we
+            // make an object.
+            let mesh_handle = renderer.add_mesh(mesh).unwrap();
+
+            // Add texture to renderer's world.
+            let image_checker =
+                image::load_from_memory(include_bytes!("checker.png")).expect("Failed to load image from memory");
+            let image_checker_rgba8 = image_checker.to_rgba8();
+            let texture_checker = rend3::types::Texture {
+                label: Option::None,
+                data: image_checker_rgba8.to_vec(),
+                format: rend3::types::TextureFormat::Rgba8UnormSrgb,
+                color_space: rend3::types::ColorSpace::Srgb,
+                size: glam::UVec2::new(image_checker.dimensions().0, image_checker.dimensions().1),
+                mip_count: rend3::types::MipmapCount::ONE,
+                mip_source: rend3::types::MipmapSource::Uploaded,
+            };
+            let texture_checker_handle = renderer.add_texture_2d(texture_checker).unwrap();
+
+            // Add PBR material with all defaults except a single color.
+            let material = rend3_routine::pbr::PbrMaterial {
+                albedo: rend3_routine::pbr::AlbedoComponent::Texture(texture_checker_handle),
+                unlit: true,
+                sample_type: rend3_routine::pbr::SampleType::Nearest,
+                ..rend3_routine::pbr::PbrMaterial::default()
+            };
+            let material_handle = renderer.add_material(material);
+
+            // Combine the mesh and the material with a location to give an object.
+            let object = rend3::types::Object {
+                mesh_kind: rend3::types::ObjectMeshKind::Static(mesh_handle),
+                material: material_handle,
+                transform: glam::Mat4::from_scale_rotation_translation(
+                    glam::Vec3::new(1.0, 1.0, 1.0),
+                    glam::Quat::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0),
+                    glam::Vec3::new(0.0, 0.0, 0.0),
                 ),
-            },
-            view,
-        });
-
-        self.data = Some(TexturedQuadExampleData { _object_handle, view })
+            };
+
+            // Creating an object will hold onto both the mesh and the material
+            // even if they are deleted.
+            //
+            // We need to keep the object handle alive.
+            let _object_handle = renderer.add_object(object);
+
+            let view_location = glam::Vec3::new(0.0, 0.0, -1.0);
+            let view = glam::Mat4::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 0.0);
+            let view = view * glam::Mat4::from_translation(-view_location);
+
+            // Set camera's location
+            renderer.set_camera_data(rend3::types::Camera {
+                projection: rend3::types::CameraProjection::Orthographic {
+                    size: glam::Vec3A::new(
+                        window.inner_size().width as f32,
+                        window.inner_size().height as f32,
+                        CAMERA_DEPTH,
+                    ),
+                },
+                view,
+            });
+
+            self.data = Some(TexturedQuadExampleData { _object_handle, view })
+        })
     }
 
     fn handle_event(
@@ -176,13 +179,15 @@ impl rend3_framework::App for TexturedQuadExample {
                 base_rendergraph.add_to_graph(
                     &mut graph,
                     &ready,
-                    &pbr_routine,
-                    None,
-                    &tonemapping_routine,
-                    resolution,
-                    SAMPLE_COUNT,
-                    glam::Vec4::ZERO,
-                    glam::Vec4::new(0.10, 0.05, 0.10, 1.0), // Nice scene-referred purple
+                    rend3_routine::base::BaseRenderGraphRoutines {
+                        pbr: &pbr_routine,
+                        skybox: None,
+                        tonemapping: &tonemapping_routine,
+                    },
+                    &rend3_routine::base::BaseRenderGraphSettings::new(resolution)
+                        .with_samples(SAMPLE_COUNT)
+                        .with_ambient(glam::Vec4::ZERO)
+                        .with_clear_color(glam::Vec4::new(0.10, 0.05, 0.10, 1.0)), // Nice scene-referred purple
                 );
 
                 // Dispatch a render using the built up rendergraph!
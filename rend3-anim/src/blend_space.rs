@@ -0,0 +1,238 @@
+//! Blend spaces, which place animations at points of a 1D or 2D parameter
+//! space and blend between the closest ones.
+//!
+//! For example, a 1D blend space over speed can go from idle, to walk, to run,
+//! and a 2D blend space over the strafe direction can blend forward, backward
+//! and sideways locomotion.
+//!
+//! All animations of a blend space are played in sync: they are sampled at the
+//! same fraction of their duration, so that e.g. footsteps line up when
+//! blending walk and run cycles of different lengths.
+
+use rend3::types::glam::Vec2;
+use rend3_gltf::LoadedGltfScene;
+
+use crate::{mixer::AnimationMixer, AnimationIndex};
+
+/// Animations placed along a single parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlendSpace1D {
+    /// Position of every animation. Does not need to be sorted.
+    pub points: Vec<(f32, AnimationIndex)>,
+}
+
+impl BlendSpace1D {
+    /// Computes the weight of every point for the given parameter value.
+    /// Values outside the range of the points use the closest point.
+    pub fn weights(&self, value: f32) -> Vec<f32> {
+        let mut weights = vec![0.0; self.points.len()];
+
+        // Closest point on each side of the value.
+        let below = closest(
+            self.points.iter().map(|p| p.0).enumerate(),
+            |p| p <= value,
+            |p| value - p,
+        );
+        let above = closest(
+            self.points.iter().map(|p| p.0).enumerate(),
+            |p| p >= value,
+            |p| p - value,
+        );
+
+        match (below, above) {
+            (Some(below), Some(above)) if below != above => {
+                let (below_pos, above_pos) = (self.points[below].0, self.points[above].0);
+                let t = (value - below_pos) / (above_pos - below_pos);
+                weights[below] = 1.0 - t;
+                weights[above] = t;
+            }
+            (Some(idx), _) | (_, Some(idx)) => weights[idx] = 1.0,
+            (None, None) => {}
+        }
+
+        weights
+    }
+
+    /// Adds the animations of the blend space to `mixer`, sampled at
+    /// `normalized_time` (`0.0` to `1.0`) of their duration.
+    pub fn fill_mixer(
+        &self,
+        scene: &LoadedGltfScene,
+        mixer: &mut AnimationMixer,
+        value: f32,
+        normalized_time: f32,
+        weight: f32,
+    ) {
+        let weights = self.weights(value);
+        fill_mixer(
+            scene,
+            mixer,
+            self.points.iter().map(|p| p.1),
+            &weights,
+            normalized_time,
+            weight,
+        );
+    }
+
+    /// Duration of the blend, the weighted average of the durations of its
+    /// animations.
+    pub fn duration(&self, scene: &LoadedGltfScene, value: f32) -> f32 {
+        blended_duration(scene, self.points.iter().map(|p| p.1), &self.weights(value))
+    }
+}
+
+/// Animations placed on a plane of two parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlendSpace2D {
+    /// Position of every animation.
+    pub points: Vec<(Vec2, AnimationIndex)>,
+}
+
+impl BlendSpace2D {
+    /// Computes the weight of every point for the given parameter values, using
+    /// gradient band interpolation. The weights always add up to one.
+    pub fn weights(&self, value: Vec2) -> Vec<f32> {
+        let mut weights: Vec<f32> = self
+            .points
+            .iter()
+            .enumerate()
+            .map(|(i, &(point_i, _))| {
+                let to_value = value - point_i;
+                // Each other point defines a band between itself and this one;
+                // the weight is how far the value is from crossing the closest
+                // band.
+                self.points
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, &(point_j, _))| {
+                        let to_other = point_j - point_i;
+                        let length_squared = to_other.length_squared();
+                        if length_squared <= f32::EPSILON {
+                            1.0
+                        } else {
+                            1.0 - to_value.dot(to_other) / length_squared
+                        }
+                    })
+                    .fold(1.0_f32, f32::min)
+                    .max(0.0)
+            })
+            .collect();
+
+        let total: f32 = weights.iter().sum();
+        if total > 0.0 {
+            weights.iter_mut().for_each(|w| *w /= total);
+        }
+        weights
+    }
+
+    /// Adds the animations of the blend space to `mixer`, sampled at
+    /// `normalized_time` (`0.0` to `1.0`) of their duration.
+    pub fn fill_mixer(
+        &self,
+        scene: &LoadedGltfScene,
+        mixer: &mut AnimationMixer,
+        value: Vec2,
+        normalized_time: f32,
+        weight: f32,
+    ) {
+        let weights = self.weights(value);
+        fill_mixer(
+            scene,
+            mixer,
+            self.points.iter().map(|p| p.1),
+            &weights,
+            normalized_time,
+            weight,
+        );
+    }
+
+    /// Duration of the blend, the weighted average of the durations of its
+    /// animations.
+    pub fn duration(&self, scene: &LoadedGltfScene, value: Vec2) -> f32 {
+        blended_duration(scene, self.points.iter().map(|p| p.1), &self.weights(value))
+    }
+}
+
+/// Index of the point which passes `filter` and has the lowest `distance`.
+fn closest(
+    points: impl Iterator<Item = (usize, f32)>,
+    filter: impl Fn(f32) -> bool,
+    distance: impl Fn(f32) -> f32,
+) -> Option<usize> {
+    points
+        .filter(|&(_, p)| filter(p))
+        .min_by(|&(_, a), &(_, b)| {
+            distance(a)
+                .partial_cmp(&distance(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(idx, _)| idx)
+}
+
+fn fill_mixer(
+    scene: &LoadedGltfScene,
+    mixer: &mut AnimationMixer,
+    animations: impl Iterator<Item = AnimationIndex>,
+    weights: &[f32],
+    normalized_time: f32,
+    weight: f32,
+) {
+    for (animation, &animation_weight) in animations.zip(weights) {
+        if animation_weight > 0.0 {
+            let duration = scene.animations[animation.0].inner.duration;
+            mixer.add(animation, normalized_time * duration, animation_weight * weight);
+        }
+    }
+}
+
+fn blended_duration(scene: &LoadedGltfScene, animations: impl Iterator<Item = AnimationIndex>, weights: &[f32]) -> f32 {
+    animations
+        .zip(weights)
+        .map(|(animation, weight)| scene.animations[animation.0].inner.duration * weight)
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use rend3::types::glam::Vec2;
+
+    use super::{BlendSpace1D, BlendSpace2D};
+    use crate::AnimationIndex;
+
+    #[test]
+    fn blends_between_neighbours_in_1d() {
+        let space = BlendSpace1D {
+            points: vec![
+                (3.0, AnimationIndex(2)),
+                (0.0, AnimationIndex(0)),
+                (1.0, AnimationIndex(1)),
+            ],
+        };
+        assert_eq!(space.weights(2.0), [0.5, 0.0, 0.5]);
+        assert_eq!(space.weights(1.0), [0.0, 0.0, 1.0]);
+        // Clamped to the ends.
+        assert_eq!(space.weights(-1.0), [0.0, 1.0, 0.0]);
+        assert_eq!(space.weights(10.0), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn weights_add_up_to_one_in_2d() {
+        let space = BlendSpace2D {
+            points: vec![
+                (Vec2::ZERO, AnimationIndex(0)),
+                (Vec2::Y, AnimationIndex(1)),
+                (-Vec2::Y, AnimationIndex(2)),
+                (Vec2::X, AnimationIndex(3)),
+                (-Vec2::X, AnimationIndex(4)),
+            ],
+        };
+        for value in [Vec2::ZERO, Vec2::new(0.3, 0.6), Vec2::new(-0.9, -0.2), Vec2::splat(4.0)] {
+            let weights = space.weights(value);
+            assert!(weights.iter().all(|&weight| weight >= 0.0), "{:?}", weights);
+            assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5, "{:?}", weights);
+        }
+        // On a point, only that point plays.
+        assert_eq!(space.weights(Vec2::Y), [0.0, 1.0, 0.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,62 @@
+//! Bakes gltf animations into the skinning matrix textures of GPU crowds, see
+//! [`rend3_routine::crowd`].
+//!
+//! Baking samples every frame of a clip through [`sample_animation`] once, so
+//! procedural animation and blending are not available to crowds. Draw the
+//! mesh of the skin with a [`Crowd`](rend3_routine::crowd::Crowd), whose joint
+//! indices match the joints of the skin.
+
+use rend3::types::Skeleton;
+use rend3_gltf::{GltfSceneInstance, LoadedGltfScene};
+use rend3_routine::crowd::BakedCrowdAnimation;
+
+use crate::{joint_global_transforms, sample_animation, AnimationData, Pose, SkinIndex};
+
+/// Creates an empty [`BakedCrowdAnimation`] for the joints of the skin at
+/// `skin_index`.
+pub fn crowd_animation_for_skin(scene: &LoadedGltfScene, skin_index: SkinIndex) -> BakedCrowdAnimation {
+    BakedCrowdAnimation::new(scene.skins[skin_index.0].inner.inverse_bind_matrices.len() as u32)
+}
+
+/// Samples the animation at index `animation_index` on the skin at
+/// `skin_index`, `sample_rate` times per second, and adds it to `baked` as a
+/// new clip. Returns the index of the clip.
+///
+/// Looping clips don't store a frame at the very end of the animation, as the
+/// crowd shader blends from their last frame back to the first.
+#[allow(clippy::too_many_arguments)]
+pub fn bake_crowd_clip(
+    scene: &LoadedGltfScene,
+    instance: &GltfSceneInstance,
+    animation_data: &mut AnimationData,
+    skin_index: SkinIndex,
+    animation_index: usize,
+    sample_rate: f32,
+    looping: bool,
+    baked: &mut BakedCrowdAnimation,
+) -> u32 {
+    let duration = scene.animations[animation_index].inner.duration;
+    let frame_count = match looping {
+        true => ((duration * sample_rate).ceil() as usize).max(1),
+        false => (duration * sample_rate).ceil() as usize + 1,
+    };
+
+    let inv_bind_mats = &scene.skins[skin_index.0].inner.inverse_bind_matrices;
+    let per_skin_data = animation_data.shared.skin_data[&skin_index].clone();
+
+    let mut matrices = Vec::with_capacity(frame_count * inv_bind_mats.len());
+    let mut pose = Pose::bind_pose(instance);
+    for frame in 0..frame_count {
+        pose.reset_to_bind_pose(instance);
+        let time = frame as f32 / sample_rate;
+        sample_animation(scene, instance, animation_data, animation_index, time, &mut pose);
+
+        let global_joint_transforms = joint_global_transforms(scene, instance, skin_index, &per_skin_data, &pose);
+        matrices.extend(Skeleton::compute_joint_matrices(
+            &global_joint_transforms,
+            inv_bind_mats,
+        ));
+    }
+
+    baked.add_clip(sample_rate, looping, &matrices)
+}
@@ -0,0 +1,210 @@
+//! Procedural animation solvers.
+//!
+//! These run on a [`Pose`] after sampling animations into it with
+//! [`sample_animation`](crate::sample_animation) and before uploading it with
+//! [`apply_pose`](crate::apply_pose), so they can correct the sampled motion,
+//! e.g. to plant feet on uneven ground or make a head track a point of
+//! interest.
+//!
+//! All positions are given relative to the root of the scene instance, the same
+//! space as [`Pose::global_transforms`].
+
+use rend3::types::glam::{Mat4, Quat, Vec3};
+use rend3_gltf::GltfSceneInstance;
+
+use crate::{NodeIndex, Pose};
+
+/// Vectors shorter than this are considered degenerate.
+const EPSILON: f32 = 1e-6;
+
+/// A chain of three nodes, such as shoulder, elbow and wrist, or hip, knee and
+/// ankle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TwoBoneChain {
+    pub root: NodeIndex,
+    pub middle: NodeIndex,
+    pub end: NodeIndex,
+}
+
+/// Rotates the root and middle nodes of `chain` so the end node reaches
+/// `target`, or gets as close as possible if it is out of reach.
+///
+/// The chain bends in the plane containing the root, the target and `pole`: a
+/// knee or an elbow will point towards the pole position.
+pub fn solve_two_bone(pose: &mut Pose, instance: &GltfSceneInstance, chain: TwoBoneChain, target: Vec3, pole: Vec3) {
+    let globals = pose.global_transforms(instance);
+    let root_global = globals[chain.root.0];
+    let middle_global = globals[chain.middle.0];
+
+    let a = root_global.w_axis.truncate();
+    let b = middle_global.w_axis.truncate();
+    let c = globals[chain.end.0].w_axis.truncate();
+
+    let len_ab = (b - a).length();
+    let len_bc = (c - b).length();
+    if len_ab < EPSILON || len_bc < EPSILON {
+        return;
+    }
+    // Keep the target strictly within reach so the chain never fully
+    // straightens, which would make the bend direction undefined.
+    let len_at = (target - a).length().clamp(EPSILON, (len_ab + len_bc) * 0.9999);
+
+    // Current and desired interior angles of the triangle formed by the chain.
+    let ac_ab_0 = angle_between(c - a, b - a);
+    let ba_bc_0 = angle_between(a - b, c - b);
+    let ac_at_0 = angle_between(c - a, target - a);
+    let ac_ab_1 = law_of_cosines(len_ab, len_at, len_bc);
+    let ba_bc_1 = law_of_cosines(len_ab, len_bc, len_at);
+
+    // Bend around the axis the chain is already bent around, falling back to
+    // the pole direction when the chain is straight.
+    let bend_axis = (c - a)
+        .cross(b - a)
+        .try_normalize()
+        .or_else(|| (c - a).cross(pole - a).try_normalize());
+    let bend_axis = match bend_axis {
+        Some(axis) => axis,
+        None => return,
+    };
+    let root_bend = Quat::from_axis_angle(bend_axis, ac_ab_1 - ac_ab_0);
+    let middle_bend = Quat::from_axis_angle(bend_axis, ba_bc_1 - ba_bc_0);
+    let root_aim = match (c - a).cross(target - a).try_normalize() {
+        Some(axis) => Quat::from_axis_angle(axis, ac_at_0),
+        None => Quat::IDENTITY,
+    };
+    let root_rotation = root_aim * root_bend;
+
+    // Twist the whole chain around the root-target axis to bring the middle
+    // node as close as possible to the pole.
+    let twist_axis = (target - a).normalize_or_zero();
+    let new_b = a + root_rotation * (b - a);
+    let twist = signed_angle_around(new_b - a, pole - a, twist_axis)
+        .map(|angle| Quat::from_axis_angle(twist_axis, angle))
+        .unwrap_or(Quat::IDENTITY);
+
+    rotate_global(pose, instance, &globals, chain.root, twist * root_rotation);
+    // The middle node's parent has changed rotation, but the bend is expressed
+    // in the original frame, so we can still use the original globals.
+    rotate_global(pose, instance, &globals, chain.middle, middle_bend);
+}
+
+/// Rotates `node` so its local `forward` axis points towards `target`.
+///
+/// `weight` blends between the current rotation at `0.0` and fully facing the
+/// target at `1.0`, and `max_angle` limits how far, in radians, the node can
+/// turn away from its current rotation.
+pub fn look_at(
+    pose: &mut Pose,
+    instance: &GltfSceneInstance,
+    node: NodeIndex,
+    target: Vec3,
+    forward: Vec3,
+    weight: f32,
+    max_angle: f32,
+) {
+    let globals = pose.global_transforms(instance);
+    let (_, global_rotation, position) = globals[node.0].to_scale_rotation_translation();
+
+    let current = (global_rotation * forward).normalize_or_zero();
+    let desired = (target - position).normalize_or_zero();
+    if current == Vec3::ZERO || desired == Vec3::ZERO {
+        return;
+    }
+
+    let angle = angle_between(current, desired);
+    let axis = match current.cross(desired).try_normalize() {
+        Some(axis) => axis,
+        // Already facing the target, or facing directly away from it, in which
+        // case there's no preferred way to turn.
+        None => return,
+    };
+    let rotation = Quat::from_axis_angle(axis, (angle * weight.clamp(0.0, 1.0)).min(max_angle));
+
+    rotate_global(pose, instance, &globals, node, rotation);
+}
+
+/// Applies `rotation`, expressed relative to the root of the scene instance, on
+/// top of the current rotation of `node`.
+fn rotate_global(pose: &mut Pose, instance: &GltfSceneInstance, globals: &[Mat4], node: NodeIndex, rotation: Quat) {
+    let parent_rotation = instance.nodes[node.0]
+        .inner
+        .parent
+        .map(|parent| globals[parent].to_scale_rotation_translation().1)
+        .unwrap_or(Quat::IDENTITY);
+
+    let local = &mut pose.local_transforms[node.0];
+    local.rotation = (parent_rotation.inverse() * rotation * parent_rotation * local.rotation).normalize();
+}
+
+/// Unsigned angle between two vectors, in radians.
+fn angle_between(a: Vec3, b: Vec3) -> f32 {
+    a.normalize_or_zero().dot(b.normalize_or_zero()).clamp(-1.0, 1.0).acos()
+}
+
+/// Angle opposite to side `opposite` in a triangle with the given side lengths.
+fn law_of_cosines(adjacent_a: f32, adjacent_b: f32, opposite: f32) -> f32 {
+    ((adjacent_a * adjacent_a + adjacent_b * adjacent_b - opposite * opposite) / (2.0 * adjacent_a * adjacent_b))
+        .clamp(-1.0, 1.0)
+        .acos()
+}
+
+/// Signed angle from `from` to `to`, both projected on the plane perpendicular
+/// to `axis`. Returns `None` if either projection is degenerate.
+fn signed_angle_around(from: Vec3, to: Vec3, axis: Vec3) -> Option<f32> {
+    let from = (from - axis * from.dot(axis)).try_normalize()?;
+    let to = (to - axis * to.dot(axis)).try_normalize()?;
+    Some(from.cross(to).dot(axis).atan2(from.dot(to)))
+}
+
+#[cfg(test)]
+mod test {
+    use rend3::types::glam::Vec3;
+
+    use super::{solve_two_bone, TwoBoneChain};
+    use crate::{test::chain_instance, NodeIndex, Pose};
+
+    /// Solves a straight arm of two unit bones along Y, returning the
+    /// positions of the elbow and the hand.
+    fn solve(target: Vec3, pole: Vec3) -> (Vec3, Vec3) {
+        let instance = chain_instance(&[("shoulder", Vec3::ZERO), ("elbow", Vec3::Y), ("hand", Vec3::Y)]);
+        let mut pose = Pose::bind_pose(&instance);
+        let chain = TwoBoneChain {
+            root: NodeIndex(0),
+            middle: NodeIndex(1),
+            end: NodeIndex(2),
+        };
+        solve_two_bone(&mut pose, &instance, chain, target, pole);
+
+        let globals = pose.global_transforms(&instance);
+        (globals[1].w_axis.truncate(), globals[2].w_axis.truncate())
+    }
+
+    #[test]
+    fn reaches_targets_in_reach() {
+        let target = Vec3::new(1.0, 1.0, 0.0);
+        let (elbow, hand) = solve(target, Vec3::Z);
+        assert!(hand.abs_diff_eq(target, 1e-4), "{}", hand);
+        // Bones keep their length.
+        assert!((elbow.length() - 1.0).abs() < 1e-4);
+        assert!(((hand - elbow).length() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn stretches_towards_targets_out_of_reach() {
+        let (elbow, hand) = solve(Vec3::new(5.0, 0.0, 0.0), Vec3::Z);
+        assert!(hand.abs_diff_eq(Vec3::new(2.0, 0.0, 0.0), 1e-2), "{}", hand);
+        assert!((elbow.length() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn bends_towards_the_pole() {
+        let target = Vec3::new(0.0, 1.0, 0.0);
+        for pole in [Vec3::Z, -Vec3::Z, Vec3::X] {
+            let (elbow, hand) = solve(target, pole);
+            assert!(hand.abs_diff_eq(target, 1e-4), "{}", hand);
+            // The elbow sticks out of the shoulder-hand line towards the pole.
+            let out = elbow - target * elbow.dot(target);
+            assert!(out.normalize().abs_diff_eq(pole, 1e-3), "{} for pole {}", out, pole);
+        }
+    }
+}
@@ -8,22 +8,59 @@
 //! - Each simulation frame, use [`pose_animation_frame`] to set the mesh's
 //!   joints to a specific animation at a specific time.
 //!
-//! For now, this library aims to be a simple utility abstraction. Updating the
-//! current state of the animation by changing the currently played animation or
-//! increasing the playback time should be handled in user code.
+//! [`AnimationData`] keeps a keyframe cursor for every channel it has played,
+//! so advancing an animation forward in time only looks at the keyframes
+//! between the previous and the current time. For large amounts of instances,
+//! animations can additionally be resampled at a fixed rate with
+//! [`AnimationData::bake_uniform_sampling`], which turns every keyframe lookup
+//! into a single index computation.
+//!
+//! Morph target weight channels are not applied by [`pose_animation_frame`],
+//! as rend3 does not render morph targets itself. Use [`pose_morph_weights`]
+//! to get the animated weights of every node and forward them to whatever is
+//! deforming the meshes.
+//!
+//! For more control, the frame can be split in three steps: sample the
+//! animation into a [`Pose`] with [`sample_animation`], adjust it with
+//! procedural animation such as the solvers in [`ik`], then upload it with
+//! [`apply_pose`].
+//!
+//! After a pose is applied, [`AnimationData::node_world_transform`] gives the
+//! transform of any node, and objects attached with
+//! [`AnimationData::attach_object`] follow their node automatically.
+//!
+//! Poses sampled on one skeleton can be played on another with the
+//! [`retarget`] module.
+//!
+//! Several animations can be blended into one pose with a
+//! [`mixer::AnimationMixer`], which in turn can be driven by an
+//! [`state_machine::AnimationStateMachine`] for the usual locomotion setups,
+//! with [`blend_space`]s smoothly blending directional movement.
+//!
+//! When not using the state machine, updating the current state of the
+//! animation by changing the currently played animation or increasing the
+//! playback time should be handled in user code.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use itertools::Itertools;
 use rend3::{
     types::{
-        glam::{Mat4, Quat, Vec3},
-        SkeletonHandle,
+        glam::{Mat4, Quat, Vec3, Vec4},
+        ObjectHandle, SkeletonHandle,
     },
     util::typedefs::{FastHashMap, FastHashSet},
     Renderer,
 };
-use rend3_gltf::{AnimationChannel, GltfSceneInstance, LoadedGltfScene};
+use rend3_gltf::{AnimationChannel, AnimationInterpolation, GltfSceneInstance, LoadedGltfScene, MorphWeightsChannel};
+use thiserror::Error;
+
+pub mod blend_space;
+pub mod crowd;
+pub mod ik;
+pub mod mixer;
+pub mod retarget;
+pub mod state_machine;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct AnimationIndex(pub usize);
@@ -34,8 +71,28 @@ pub struct NodeIndex(pub usize);
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct JointIndex(pub usize);
 
+/// Returns the index of the first node of `instance` with the given name.
+pub fn find_node(instance: &GltfSceneInstance, name: &str) -> Option<NodeIndex> {
+    instance
+        .nodes
+        .iter()
+        .position(|node| node.label.as_deref() == Some(name))
+        .map(NodeIndex)
+}
+
+/// An object that follows a node of an animated scene instance. See
+/// [`AnimationData::attach_object`].
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    pub object: ObjectHandle,
+    pub node: NodeIndex,
+    /// Transform of the object relative to the node.
+    pub offset: Mat4,
+}
+
 /// Cached data structures per each of the Skins in a gltf model. This struct is
-/// part of [`AnimationData`]
+/// part of [`SharedAnimationData`]
+#[derive(Clone, Debug)]
 pub struct PerSkinData {
     /// Translates node indices to joint indices for this particular skin. This
     /// translation is necessary because an animation may be animating a scene
@@ -45,14 +102,104 @@ pub struct PerSkinData {
     /// This is used to avoid iterating all the scene hierarchy when
     /// computing global positions for a node.
     pub joint_nodes_topological_order: Vec<NodeIndex>,
-    /// The list of skeletons deformed by this animation. There's one skeleton
-    /// for each of the mesh primitives. Every skeleton in this list is
-    /// shares the same bone structure.
-    pub skeletons: Vec<SkeletonHandle>,
 }
 
-/// Caches animation data necessary to run [`pose_animation_frame`].
-pub struct AnimationData {
+/// Index of the last keyframe visited by each of the channels of a node.
+///
+/// Playing an animation forward in time only needs to move these cursors
+/// forward by a few keyframes, instead of searching from the first keyframe.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct PosRotScaleCursors {
+    pub translation: usize,
+    pub rotation: usize,
+    pub scale: usize,
+    pub weights: usize,
+}
+
+/// Values of an animation channel, resampled at a fixed interval.
+#[derive(Clone, Debug)]
+pub struct BakedChannel<T> {
+    /// Sample `i` contains the value of the channel at time `i * interval`.
+    pub values: Vec<T>,
+    /// If true, the samples are held instead of interpolated, as the original
+    /// channel used [`AnimationInterpolation::Step`].
+    pub step: bool,
+}
+
+/// A rotation with each component quantized to 16 bits, taking half the
+/// memory of a [`Quat`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuantizedQuat(pub [i16; 4]);
+
+impl QuantizedQuat {
+    pub fn new(quat: Quat) -> Self {
+        let quantize = |v: f32| (v.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+        let [x, y, z, w] = quat.normalize().to_array();
+        Self([quantize(x), quantize(y), quantize(z), quantize(w)])
+    }
+
+    pub fn to_quat(self) -> Quat {
+        let [x, y, z, w] = self.0.map(|v| v as f32 / i16::MAX as f32);
+        Quat::from_xyzw(x, y, z, w).normalize()
+    }
+}
+
+/// Resampled rotations, optionally quantized.
+#[derive(Clone, Debug)]
+pub enum BakedRotationChannel {
+    Full(BakedChannel<Quat>),
+    Quantized(BakedChannel<QuantizedQuat>),
+}
+
+/// Resampled translation, rotation and scale channels for a single node.
+#[derive(Clone, Debug, Default)]
+pub struct BakedPosRotScale {
+    pub translation: Option<BakedChannel<Vec3>>,
+    pub rotation: Option<BakedRotationChannel>,
+    pub scale: Option<BakedChannel<Vec3>>,
+}
+
+/// Settings for [`AnimationData::bake_uniform_sampling`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BakeSettings {
+    /// Amount of samples per second.
+    pub sample_rate: f32,
+    /// Store rotations as [`QuantizedQuat`]s, halving their memory usage at a
+    /// small cost in precision.
+    pub quantize_rotations: bool,
+}
+
+impl Default for BakeSettings {
+    fn default() -> Self {
+        Self {
+            sample_rate: 30.0,
+            quantize_rotations: false,
+        }
+    }
+}
+
+/// Describes how baking an animation failed.
+#[derive(Debug, Error)]
+pub enum BakeError {
+    #[error("Sample rate must be positive and finite, got {0}")]
+    InvalidSampleRate(f32),
+}
+
+/// An animation whose channels have all been resampled at the same fixed
+/// interval. See [`AnimationData::bake_uniform_sampling`].
+#[derive(Clone, Debug)]
+pub struct BakedAnimation {
+    /// Time between two consecutive samples, in seconds.
+    pub interval: f32,
+    /// Maps the node index of a joint to its resampled channels.
+    pub channels: FastHashMap<NodeIndex, BakedPosRotScale>,
+}
+
+/// Animation data that is the same for every instance of a scene. Shared
+/// between [`AnimationData`]s through an [`Arc`], so spawning many copies of
+/// the same animated scene only costs their playback state.
+#[derive(Clone, Debug)]
+pub struct SharedAnimationData {
     /// For each skin, stores several cached data structures that speed up the
     /// animation loop at runtime.
     pub skin_data: FastHashMap<SkinIndex, PerSkinData>,
@@ -60,21 +207,15 @@ pub struct AnimationData {
     /// affects a skin if it deforms any of its joints. This is used to avoid
     /// iterating unaffected skins when playing an animation.
     pub animation_skin_usage: FastHashMap<AnimationIndex, Vec<SkinIndex>>,
+    /// Animations which have been resampled at a fixed rate. When an animation
+    /// is present here, [`pose_animation_frame`] samples the baked data instead
+    /// of the original keyframes.
+    pub baked: FastHashMap<AnimationIndex, BakedAnimation>,
 }
 
-impl AnimationData {
-    /// Creates an [`AnimationData`] from a loaded gltf scene and instance.
-    ///
-    /// Note that the instance is necessary, as one `AnimationData` must exist
-    /// per each instance of the same scene.
-    ///
-    /// ## Parameters
-    /// - scene: The loaded scene, as returned by
-    ///   [`load_gltf`](rend3_gltf::load_gltf) or
-    ///   [`load_gltf_data`](rend3_gltf::load_gltf_data)
-    /// - instance: An instance of `scene`, as returned by
-    ///   [`load_gltf`](rend3_gltf::load_gltf) or
-    ///   [`instance_loaded_scene`](rend3_gltf::instance_loaded_scene)
+impl SharedAnimationData {
+    /// Creates the [`SharedAnimationData`] of a loaded gltf scene. Any instance
+    /// of the scene can be used, as they all share the same node hierarchy.
     pub fn from_gltf_scene(scene: &LoadedGltfScene, instance: &GltfSceneInstance) -> Self {
         // The set of joints that each animation affects, stored as node indices
         // NOTE: Uses a std HashMap because `GroupingMap::collect()` is
@@ -135,37 +276,215 @@ impl AnimationData {
                 .filter(|node_idx| skin_nodes.contains(node_idx))
                 .collect();
 
-            let skeletons: Vec<SkeletonHandle> = instance
-                .nodes
-                .iter()
-                .flat_map(|node| &node.inner.object)
-                .flat_map(|object| &object.inner.armature)
-                .filter(|armature| armature.skin_index == skin_index.0)
-                .flat_map(|armature| &armature.skeletons)
-                .cloned()
-                .collect();
-
             skin_data.insert(
                 skin_index,
                 PerSkinData {
                     node_to_joint_idx,
                     joint_nodes_topological_order,
-                    skeletons,
                 },
             );
         }
 
-        AnimationData {
+        SharedAnimationData {
             skin_data,
             animation_skin_usage,
+            baked: FastHashMap::default(),
+        }
+    }
+
+    /// Resamples the animation at index `animation_index` at a fixed rate.
+    ///
+    /// Sampling a baked animation never needs to search for keyframes, at the
+    /// cost of storing `duration * sample_rate` values per channel. Animations
+    /// with many sparse keyframes may lose some precision if the sample rate
+    /// is too low. Channels without keyframes are left out.
+    ///
+    /// Fails without changing anything if the sample rate of `settings` isn't
+    /// positive and finite.
+    pub fn bake_uniform_sampling(
+        &mut self,
+        scene: &LoadedGltfScene,
+        animation_index: usize,
+        settings: BakeSettings,
+    ) -> Result<(), BakeError> {
+        fn bake<T: Interpolate, U>(
+            channel: &AnimationChannel<T>,
+            interval: f32,
+            sample_count: usize,
+            encode: impl Fn(T) -> U,
+        ) -> Option<BakedChannel<U>> {
+            let values = (0..sample_count)
+                .map(|i| sample_at_time(channel, i as f32 * interval).map(&encode))
+                .collect::<Option<_>>()?;
+            Some(BakedChannel {
+                values,
+                step: channel.interpolation == AnimationInterpolation::Step,
+            })
+        }
+
+        if !(settings.sample_rate > 0.0 && settings.sample_rate.is_finite()) {
+            return Err(BakeError::InvalidSampleRate(settings.sample_rate));
+        }
+
+        let animation = &scene.animations[animation_index].inner;
+        let interval = 1.0 / settings.sample_rate;
+        // Always store one sample at or past the end, so the last keyframe is
+        // reached exactly.
+        let sample_count = (animation.duration * settings.sample_rate).ceil() as usize + 1;
+
+        let channels = animation
+            .channels
+            .iter()
+            .map(|(&node_idx, channels)| {
+                let baked = BakedPosRotScale {
+                    translation: channels
+                        .translation
+                        .as_ref()
+                        .and_then(|c| bake(c, interval, sample_count, |v| v)),
+                    rotation: channels.rotation.as_ref().and_then(|c| {
+                        if settings.quantize_rotations {
+                            bake(c, interval, sample_count, QuantizedQuat::new).map(BakedRotationChannel::Quantized)
+                        } else {
+                            bake(c, interval, sample_count, |v| v).map(BakedRotationChannel::Full)
+                        }
+                    }),
+                    scale: channels
+                        .scale
+                        .as_ref()
+                        .and_then(|c| bake(c, interval, sample_count, |v| v)),
+                };
+                (NodeIndex(node_idx), baked)
+            })
+            .collect();
+
+        self.baked
+            .insert(AnimationIndex(animation_index), BakedAnimation { interval, channels });
+        Ok(())
+    }
+}
+
+/// Caches animation data necessary to run [`pose_animation_frame`] on a single
+/// scene instance.
+pub struct AnimationData {
+    /// Data shared between all instances of the scene.
+    pub shared: Arc<SharedAnimationData>,
+    /// For each skin, the list of skeletons of this instance deformed by it.
+    /// There's one skeleton for each of the mesh primitives. Every skeleton in
+    /// a list shares the same bone structure.
+    pub skeletons: FastHashMap<SkinIndex, Vec<SkeletonHandle>>,
+    /// For each animation that has been played, stores the keyframe cursors of
+    /// every animated node. Cursors are created lazily the first time an
+    /// animation is posed.
+    pub cursors: FastHashMap<AnimationIndex, FastHashMap<NodeIndex, PosRotScaleCursors>>,
+    /// Transform the scene instance was placed with, initially its
+    /// [`parent_transform`](GltfSceneInstance::parent_transform). Used to
+    /// compute world transforms of nodes and attachments, so update it when
+    /// moving the instance.
+    pub instance_transform: Mat4,
+    /// Transform of every node relative to the root of the scene instance, as
+    /// of the last call to [`apply_pose`].
+    pub global_transforms: Vec<Mat4>,
+    /// Objects kept at the world transform of a node every time a pose is
+    /// applied.
+    pub attachments: Vec<Attachment>,
+}
+
+impl AnimationData {
+    /// Creates an [`AnimationData`] from a loaded gltf scene and instance.
+    ///
+    /// Note that the instance is necessary, as one `AnimationData` must exist
+    /// per each instance of the same scene. When spawning many instances, use
+    /// [`AnimationData::from_shared`] instead, so they all share the same
+    /// [`SharedAnimationData`].
+    ///
+    /// ## Parameters
+    /// - scene: The loaded scene, as returned by
+    ///   [`load_gltf`](rend3_gltf::load_gltf) or
+    ///   [`load_gltf_data`](rend3_gltf::load_gltf_data)
+    /// - instance: An instance of `scene`, as returned by
+    ///   [`load_gltf`](rend3_gltf::load_gltf) or
+    ///   [`instance_loaded_scene`](rend3_gltf::instance_loaded_scene)
+    pub fn from_gltf_scene(scene: &LoadedGltfScene, instance: &GltfSceneInstance) -> Self {
+        Self::from_shared(
+            Arc::new(SharedAnimationData::from_gltf_scene(scene, instance)),
+            instance,
+        )
+    }
+
+    /// Creates an [`AnimationData`] for an instance of the scene `shared` was
+    /// created from.
+    pub fn from_shared(shared: Arc<SharedAnimationData>, instance: &GltfSceneInstance) -> Self {
+        let skeletons = shared
+            .skin_data
+            .keys()
+            .map(|&skin_index| {
+                let skeletons = instance
+                    .nodes
+                    .iter()
+                    .flat_map(|node| &node.inner.object)
+                    .flat_map(|object| &object.inner.armature)
+                    .filter(|armature| armature.skin_index == skin_index.0)
+                    .flat_map(|armature| &armature.skeletons)
+                    .cloned()
+                    .collect();
+                (skin_index, skeletons)
+            })
+            .collect();
+
+        Self {
+            shared,
+            skeletons,
+            cursors: FastHashMap::default(),
+            instance_transform: instance.parent_transform,
+            global_transforms: Pose::bind_pose(instance).global_transforms(instance),
+            attachments: Vec::new(),
         }
     }
+
+    /// World transform of `node` as of the last call to [`apply_pose`], or in
+    /// its bind pose if the instance has never been posed.
+    pub fn node_world_transform(&self, node: NodeIndex) -> Mat4 {
+        self.instance_transform * self.global_transforms[node.0]
+    }
+
+    /// Keeps `object` at the world transform of `node`, times `offset`. The
+    /// object's transform is updated every time [`apply_pose`] is called.
+    ///
+    /// Useful to make weapons or props follow a hand bone.
+    pub fn attach_object(&mut self, object: ObjectHandle, node: NodeIndex, offset: Mat4) {
+        self.detach_object(&object);
+        self.attachments.push(Attachment { object, node, offset });
+    }
+
+    /// Stops updating the transform of `object`, if it was attached.
+    pub fn detach_object(&mut self, object: &ObjectHandle) {
+        self.attachments.retain(|attachment| attachment.object != *object);
+    }
+
+    /// Resamples an animation at a fixed rate. See
+    /// [`SharedAnimationData::bake_uniform_sampling`].
+    ///
+    /// If the shared data is used by other instances, this instance gets its
+    /// own copy of it. Prefer baking before sharing the data.
+    pub fn bake_uniform_sampling(
+        &mut self,
+        scene: &LoadedGltfScene,
+        animation_index: usize,
+        settings: BakeSettings,
+    ) -> Result<(), BakeError> {
+        Arc::make_mut(&mut self.shared).bake_uniform_sampling(scene, animation_index, settings)
+    }
 }
 
 /// Helper trait that exposes a generic `lerp` function for various `glam` types
 pub trait Lerp {
     fn lerp(self, other: Self, t: f32) -> Self;
 }
+impl Lerp for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
 impl Lerp for Vec3 {
     fn lerp(self, other: Self, t: f32) -> Self {
         self.lerp(other, t)
@@ -180,99 +499,798 @@ impl Lerp for Quat {
     }
 }
 
+/// Helper trait that exposes cubic hermite spline interpolation, as used by
+/// gltf's `CUBICSPLINE` channels, for various `glam` types.
+pub trait CubicSpline {
+    /// Interpolates between `start` and `end` at `t` in `[0, 1]`. Tangents are
+    /// given per second, so they get scaled by `delta`, the time between the
+    /// two keyframes.
+    fn cubic_spline(start: Self, start_out_tangent: Self, end: Self, end_in_tangent: Self, t: f32, delta: f32) -> Self;
+}
+impl CubicSpline for f32 {
+    fn cubic_spline(start: Self, start_out_tangent: Self, end: Self, end_in_tangent: Self, t: f32, delta: f32) -> Self {
+        let [a, b, c, d] = hermite_basis(t);
+        start * a + start_out_tangent * (b * delta) + end * c + end_in_tangent * (d * delta)
+    }
+}
+impl CubicSpline for Vec3 {
+    fn cubic_spline(start: Self, start_out_tangent: Self, end: Self, end_in_tangent: Self, t: f32, delta: f32) -> Self {
+        let [a, b, c, d] = hermite_basis(t);
+        start * a + start_out_tangent * (b * delta) + end * c + end_in_tangent * (d * delta)
+    }
+}
+impl CubicSpline for Quat {
+    fn cubic_spline(start: Self, start_out_tangent: Self, end: Self, end_in_tangent: Self, t: f32, delta: f32) -> Self {
+        // Per the gltf spec, rotations are interpolated component-wise, the
+        // result is then normalized to get back a valid rotation.
+        let [a, b, c, d] = hermite_basis(t);
+        let result = Vec4::from(start) * a
+            + Vec4::from(start_out_tangent) * (b * delta)
+            + Vec4::from(end) * c
+            + Vec4::from(end_in_tangent) * (d * delta);
+        Quat::from_vec4(result).normalize()
+    }
+}
+
+/// Weights of the start point, start tangent, end point and end tangent of a
+/// cubic hermite spline at `t`.
+fn hermite_basis(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        2.0 * t3 - 3.0 * t2 + 1.0,
+        t3 - 2.0 * t2 + t,
+        -2.0 * t3 + 3.0 * t2,
+        t3 - t2,
+    ]
+}
+
+/// Types that can be stored in an animation channel.
+pub trait Interpolate: Lerp + CubicSpline + Copy {}
+impl<T: Lerp + CubicSpline + Copy> Interpolate for T {}
+
+/// Interpolates between the keyframes `prev_idx` and `next_idx` of a channel,
+/// following the channel's interpolation mode.
+fn interpolate_keyframes<T: Interpolate>(
+    channel: &AnimationChannel<T>,
+    prev_idx: usize,
+    next_idx: usize,
+    current_time: f32,
+) -> T {
+    if prev_idx == next_idx {
+        return channel.values[prev_idx];
+    }
+
+    let delta = channel.times[next_idx] - channel.times[prev_idx];
+    let interp_factor = f32::clamp((current_time - channel.times[prev_idx]) / delta, 0.0, 1.0);
+
+    match channel.interpolation {
+        AnimationInterpolation::Step => channel.values[prev_idx],
+        AnimationInterpolation::Linear => channel.values[prev_idx].lerp(channel.values[next_idx], interp_factor),
+        AnimationInterpolation::CubicSpline => T::cubic_spline(
+            channel.values[prev_idx],
+            channel.out_tangents[prev_idx],
+            channel.values[next_idx],
+            channel.in_tangents[next_idx],
+            interp_factor,
+            delta,
+        ),
+    }
+}
+
 /// Samples the data value for an animation channel at a given time. Will
-/// interpolate between the two closest keyframes.
-fn sample_at_time<T: Lerp + Copy>(channel: &AnimationChannel<T>, current_time: f32) -> T {
-    let next_idx = channel
-        .times
-        .iter()
-        .position(|time| *time > current_time)
-        .unwrap_or(channel.times.len() - 1);
-    let prev_idx = next_idx.saturating_sub(1);
+/// interpolate between the two closest keyframes according to the channel's
+/// interpolation mode. Returns `None` if the channel has no keyframes.
+fn sample_at_time<T: Interpolate>(channel: &AnimationChannel<T>, current_time: f32) -> Option<T> {
+    let mut cursor = 0;
+    sample_with_cursor(channel, current_time, &mut cursor)
+}
+
+/// Same as [`sample_at_time`], but starts searching for the keyframe at
+/// `cursor` and leaves it pointing to the keyframe right before
+/// `current_time`.
+fn sample_with_cursor<T: Interpolate>(
+    channel: &AnimationChannel<T>,
+    current_time: f32,
+    cursor: &mut usize,
+) -> Option<T> {
+    let (prev_idx, next_idx) = advance_cursor(&channel.times, current_time, cursor)?;
+
+    Some(interpolate_keyframes(channel, prev_idx, next_idx, current_time))
+}
+
+/// Moves `cursor` to the last keyframe at or before `current_time` and returns
+/// the indices of the keyframes to interpolate between. When time moves
+/// forward, this only visits the keyframes in between. When it moves
+/// backwards, the keyframe gets binary searched. Returns `None` if there are
+/// no keyframes.
+fn advance_cursor(times: &[f32], current_time: f32, cursor: &mut usize) -> Option<(usize, usize)> {
+    let last_idx = times.len().checked_sub(1)?;
+
+    if *cursor > last_idx || times[*cursor] > current_time {
+        *cursor = times.partition_point(|&time| time <= current_time).saturating_sub(1);
+    }
+    while *cursor < last_idx && times[*cursor + 1] <= current_time {
+        *cursor += 1;
+    }
+
+    Some((*cursor, (*cursor + 1).min(last_idx)))
+}
+
+/// Writes the weights of a morph target weight channel at a given time into
+/// `output`, interpolating each weight between the two closest keyframes.
+/// Leaves `output` empty if the channel has no keyframes.
+fn sample_weights_with_cursor(
+    channel: &MorphWeightsChannel,
+    current_time: f32,
+    cursor: &mut usize,
+    output: &mut Vec<f32>,
+) {
+    output.clear();
+    let (prev_idx, next_idx) = match advance_cursor(&channel.times, current_time, cursor) {
+        Some(indices) => indices,
+        None => return,
+    };
+    let count = channel.target_count;
+    let prev = prev_idx * count..(prev_idx + 1) * count;
+    let next = next_idx * count..(next_idx + 1) * count;
+
+    if prev_idx == next_idx || channel.interpolation == AnimationInterpolation::Step {
+        output.extend_from_slice(&channel.values[prev]);
+        return;
+    }
+
+    let delta = channel.times[next_idx] - channel.times[prev_idx];
+    let interp_factor = f32::clamp((current_time - channel.times[prev_idx]) / delta, 0.0, 1.0);
+
+    output.extend((0..count).map(|i| {
+        let (prev, next) = (prev.start + i, next.start + i);
+        match channel.interpolation {
+            AnimationInterpolation::CubicSpline => f32::cubic_spline(
+                channel.values[prev],
+                channel.out_tangents[prev],
+                channel.values[next],
+                channel.in_tangents[next],
+                interp_factor,
+                delta,
+            ),
+            _ => channel.values[prev].lerp(channel.values[next], interp_factor),
+        }
+    }));
+}
 
-    let interp_factor = f32::clamp(
-        (current_time - channel.times[prev_idx]) / (channel.times[next_idx] - channel.times[prev_idx]),
-        0.0,
-        1.0,
-    );
+/// Samples a baked channel at a given time, interpolating between the two
+/// closest samples after decoding them. Returns `None` if the channel has no
+/// samples.
+fn sample_baked<T: Copy, U: Lerp>(
+    channel: &BakedChannel<T>,
+    interval: f32,
+    current_time: f32,
+    decode: impl Fn(T) -> U,
+) -> Option<U> {
+    let last_idx = channel.values.len().checked_sub(1)?;
+    let position = current_time / interval;
+    let prev_idx = (position as usize).min(last_idx);
+    let next_idx = (prev_idx + 1).min(last_idx);
 
-    channel.values[prev_idx].lerp(channel.values[next_idx], interp_factor)
+    let prev = decode(channel.values[prev_idx]);
+    if channel.step {
+        return Some(prev);
+    }
+
+    Some(prev.lerp(
+        decode(channel.values[next_idx]),
+        (position - prev_idx as f32).clamp(0.0, 1.0),
+    ))
+}
+
+/// Translation, rotation and scale of a single node, relative to its parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    /// Decomposes a matrix into a [`Transform`].
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn to_matrix(self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+impl Lerp for Transform {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            translation: Lerp::lerp(self.translation, other.translation, t),
+            rotation: Lerp::lerp(self.rotation, other.rotation, t),
+            scale: Lerp::lerp(self.scale, other.scale, t),
+        }
+    }
+}
+
+/// The local transform of every node in a scene instance.
+///
+/// A pose is filled by sampling animations with [`sample_animation`], can then
+/// be modified by procedural animation such as the solvers in [`ik`], and is
+/// finally uploaded to the skeletons with [`apply_pose`].
+#[derive(Clone, Debug)]
+pub struct Pose {
+    /// Transform of each node relative to its parent. Indexed the same as
+    /// [`GltfSceneInstance::nodes`].
+    pub local_transforms: Vec<Transform>,
+}
+
+impl Pose {
+    /// Creates the pose the nodes of `instance` were loaded with.
+    pub fn bind_pose(instance: &GltfSceneInstance) -> Self {
+        Self {
+            local_transforms: instance
+                .nodes
+                .iter()
+                .map(|node| Transform::from_matrix(node.inner.local_transform))
+                .collect(),
+        }
+    }
+
+    /// Sets every node back to the pose it was loaded with.
+    pub fn reset_to_bind_pose(&mut self, instance: &GltfSceneInstance) {
+        for (local, node) in self.local_transforms.iter_mut().zip(&instance.nodes) {
+            *local = Transform::from_matrix(node.inner.local_transform);
+        }
+    }
+
+    /// Interpolates every node towards `other`, with `t` going from `0.0`
+    /// (keep this pose) to `1.0` (use `other`).
+    pub fn blend(&mut self, other: &Pose, t: f32) {
+        for (local, &other) in self.local_transforms.iter_mut().zip(&other.local_transforms) {
+            *local = local.lerp(other, t);
+        }
+    }
+
+    /// Computes the transform of every node relative to the root of the scene
+    /// instance.
+    pub fn global_transforms(&self, instance: &GltfSceneInstance) -> Vec<Mat4> {
+        let mut global_transforms = vec![Mat4::IDENTITY; self.local_transforms.len()];
+        for &node_idx in &instance.topological_order {
+            let local = self.local_transforms[node_idx].to_matrix();
+            global_transforms[node_idx] = match instance.nodes[node_idx].inner.parent {
+                // This is guaranteed to be computed because we're iterating
+                // the hierarchy nodes in topological order
+                Some(parent_idx) => global_transforms[parent_idx] * local,
+                None => local,
+            };
+        }
+        global_transforms
+    }
+}
+
+/// Sets the nodes of `pose` to the animation at index `animation_index` at a
+/// given `time`. The provided time gets clamped to the valid range of times for
+/// the selected animation.
+///
+/// Nodes that are not animated are left untouched. Properties that an animated
+/// node has no channel for are set to their bind pose.
+pub fn sample_animation(
+    scene: &LoadedGltfScene,
+    instance: &GltfSceneInstance,
+    animation_data: &mut AnimationData,
+    animation_index: usize,
+    time: f32,
+    pose: &mut Pose,
+) {
+    let animation = &scene.animations[animation_index];
+    let time = time.clamp(0.0, animation.inner.duration);
+
+    let baked = animation_data.shared.baked.get(&AnimationIndex(animation_index));
+    let cursors = animation_data
+        .cursors
+        .entry(AnimationIndex(animation_index))
+        .or_default();
+
+    for (&node_idx, channels) in &animation.inner.channels {
+        // NOTE: If a channel's property is not present, we need to set the
+        // joint at its bind pose for that individual property
+        let local_transform = instance.nodes[node_idx].inner.local_transform;
+        let (bind_scale, bind_rotation, bind_translation) = local_transform.to_scale_rotation_translation();
+
+        let (translation, rotation, scale) = match baked {
+            Some(baked) => {
+                let channels = &baked.channels[&NodeIndex(node_idx)];
+                (
+                    channels
+                        .translation
+                        .as_ref()
+                        .and_then(|tra| sample_baked(tra, baked.interval, time, |v| v))
+                        .unwrap_or(bind_translation),
+                    match &channels.rotation {
+                        Some(BakedRotationChannel::Full(rot)) => sample_baked(rot, baked.interval, time, |v| v),
+                        Some(BakedRotationChannel::Quantized(rot)) => {
+                            sample_baked(rot, baked.interval, time, QuantizedQuat::to_quat)
+                        }
+                        None => None,
+                    }
+                    .unwrap_or(bind_rotation),
+                    channels
+                        .scale
+                        .as_ref()
+                        .and_then(|sca| sample_baked(sca, baked.interval, time, |v| v))
+                        .unwrap_or(bind_scale),
+                )
+            }
+            None => {
+                let cursor = cursors.entry(NodeIndex(node_idx)).or_default();
+                (
+                    channels
+                        .translation
+                        .as_ref()
+                        .and_then(|tra| sample_with_cursor(tra, time, &mut cursor.translation))
+                        .unwrap_or(bind_translation),
+                    channels
+                        .rotation
+                        .as_ref()
+                        .and_then(|rot| sample_with_cursor(rot, time, &mut cursor.rotation))
+                        .unwrap_or(bind_rotation),
+                    channels
+                        .scale
+                        .as_ref()
+                        .and_then(|sca| sample_with_cursor(sca, time, &mut cursor.scale))
+                        .unwrap_or(bind_scale),
+                )
+            }
+        };
+
+        pose.local_transforms[node_idx] = Transform {
+            translation,
+            rotation,
+            scale,
+        };
+    }
+}
+
+/// Computes the global transform of every joint of the skin at `skin_index`
+/// in `pose`, indexed by joint.
+fn joint_global_transforms(
+    scene: &LoadedGltfScene,
+    instance: &GltfSceneInstance,
+    skin_index: SkinIndex,
+    per_skin_data: &PerSkinData,
+    pose: &Pose,
+) -> Vec<Mat4> {
+    let node_to_joint_idx = &per_skin_data.node_to_joint_idx;
+
+    let mut global_joint_transforms = vec![Mat4::IDENTITY; scene.skins[skin_index.0].inner.inverse_bind_matrices.len()];
+
+    // Compute bone global transformations
+    for node_idx in &per_skin_data.joint_nodes_topological_order {
+        let node = &instance.nodes[node_idx.0].inner;
+        let joint_idx = node_to_joint_idx[node_idx];
+        let current_transform = pose.local_transforms[node_idx.0].to_matrix();
+        if let Some(parent_joint_idx) = node.parent.map(|pi| node_to_joint_idx.get(&NodeIndex(pi))) {
+            // This is guaranteed to be computed because we're iterating
+            // the hierarchy nodes in topological order
+            let parent_transform = parent_joint_idx
+                .map(|p| global_joint_transforms[p.0])
+                .unwrap_or(Mat4::IDENTITY);
+
+            global_joint_transforms[joint_idx.0] = parent_transform * current_transform;
+        } else {
+            global_joint_transforms[joint_idx.0] = current_transform;
+        }
+    }
+
+    global_joint_transforms
+}
+
+/// Sets the joint transforms of every skeleton in the scene instance to match
+/// `pose`, and moves the objects attached to its nodes.
+pub fn apply_pose(
+    renderer: &Renderer,
+    scene: &LoadedGltfScene,
+    instance: &GltfSceneInstance,
+    animation_data: &mut AnimationData,
+    pose: &Pose,
+) {
+    for (skin_index, per_skin_data) in &animation_data.shared.skin_data {
+        let inv_bind_mats = &scene.skins[skin_index.0].inner.inverse_bind_matrices;
+        let global_joint_transforms = joint_global_transforms(scene, instance, *skin_index, per_skin_data, pose);
+
+        // Set the joint positions in rend3
+        for skeleton in &animation_data.skeletons[skin_index] {
+            renderer.set_skeleton_joint_transforms(skeleton, &global_joint_transforms, inv_bind_mats);
+        }
+    }
+
+    animation_data.global_transforms = pose.global_transforms(instance);
+    for attachment in &animation_data.attachments {
+        let transform = animation_data.node_world_transform(attachment.node) * attachment.offset;
+        renderer.set_object_transform(&attachment.object, transform);
+    }
 }
 
 /// Sets the pose of the meshes at the given scene by using the animation at
 /// index `animation_index` at a given `time`. The provided time gets clamped to
 /// the valid range of times for the selected animation.
+///
+/// This is a shorthand for [`sample_animation`] on top of the bind pose,
+/// followed by [`apply_pose`].
 pub fn pose_animation_frame(
     renderer: &Renderer,
     scene: &LoadedGltfScene,
     instance: &GltfSceneInstance,
-    animation_data: &AnimationData,
+    animation_data: &mut AnimationData,
     animation_index: usize,
     time: f32,
+) {
+    let mut pose = Pose::bind_pose(instance);
+    sample_animation(scene, instance, animation_data, animation_index, time, &mut pose);
+    apply_pose(renderer, scene, instance, animation_data, &pose);
+}
+
+/// Samples the morph target weight channels of the animation at index
+/// `animation_index` at a given `time`, calling `write_weights` once for every
+/// animated node with its weights. The provided time gets clamped to the valid
+/// range of times for the selected animation.
+///
+/// rend3 does not deform meshes with morph targets, so it's up to the caller
+/// to apply the weights, usually on the objects of the given node in the
+/// scene instance.
+pub fn pose_morph_weights(
+    scene: &LoadedGltfScene,
+    animation_data: &mut AnimationData,
+    animation_index: usize,
+    time: f32,
+    mut write_weights: impl FnMut(NodeIndex, &[f32]),
 ) {
     let animation = &scene.animations[animation_index];
     let time = time.clamp(0.0, animation.inner.duration);
 
-    for (skin_index, per_skin_data) in &animation_data.skin_data {
-        let skin = &scene.skins[skin_index.0];
-        let inv_bind_mats = &skin.inner.inverse_bind_matrices;
-
-        // The local position of each joint, relative to its parent
-        let mut joint_local_matrices = vec![Mat4::IDENTITY; inv_bind_mats.len()];
-
-        let node_to_joint_idx = &per_skin_data.node_to_joint_idx;
-
-        // Compute each bone's local transformation
-        for (&node_idx, channels) in &animation.inner.channels {
-            // NOTE: If a channel's property is not present, we need to set the
-            // joint at its bind pose for that individual property
-            let local_transform = instance.nodes[node_idx].inner.local_transform;
-            let (bind_scale, bind_rotation, bind_translation) = local_transform.to_scale_rotation_translation();
-
-            let translation = channels
-                .translation
-                .as_ref()
-                .map(|tra| sample_at_time(tra, time))
-                .unwrap_or(bind_translation);
-            let rotation = channels
-                .rotation
-                .as_ref()
-                .map(|rot| sample_at_time(rot, time))
-                .unwrap_or(bind_rotation);
-            let scale = channels
-                .scale
-                .as_ref()
-                .map(|sca| sample_at_time(sca, time))
-                .unwrap_or(bind_scale);
-
-            let matrix = Mat4::from_scale_rotation_translation(scale, rotation, translation);
-            let joint_idx = node_to_joint_idx[&NodeIndex(node_idx)];
-            joint_local_matrices[joint_idx.0] = matrix;
+    let cursors = animation_data
+        .cursors
+        .entry(AnimationIndex(animation_index))
+        .or_default();
+
+    let mut weights = Vec::new();
+    for (&node_idx, channels) in &animation.inner.channels {
+        if let Some(channel) = &channels.weights {
+            let cursor = cursors.entry(NodeIndex(node_idx)).or_default();
+            sample_weights_with_cursor(channel, time, &mut cursor.weights, &mut weights);
+            if !weights.is_empty() {
+                write_weights(NodeIndex(node_idx), &weights);
+            }
         }
+    }
+}
 
-        let mut global_joint_transforms = vec![Mat4::IDENTITY; inv_bind_mats.len()];
+#[cfg(test)]
+mod test {
+    use rend3::types::{
+        glam::{Mat4, Quat, Vec3, Vec4},
+        MaterialHandle,
+    };
+    use rend3_gltf::{
+        Animation, AnimationChannel, AnimationInterpolation, GltfSceneInstance, Labeled, LoadedGltfScene, Node,
+        PosRotScale,
+    };
 
-        // Compute bone global transformations
-        for node_idx in &per_skin_data.joint_nodes_topological_order {
-            let node = &instance.nodes[node_idx.0].inner;
-            let joint_idx = node_to_joint_idx[node_idx];
-            if let Some(parent_joint_idx) = node.parent.map(|pi| node_to_joint_idx.get(&NodeIndex(pi))) {
-                // This is guaranteed to be computed because we're iterating
-                // the hierarchy nodes in topological order
-                let parent_transform = parent_joint_idx
-                    .map(|p| global_joint_transforms[p.0])
-                    .unwrap_or(Mat4::IDENTITY);
-                let current_transform = joint_local_matrices[joint_idx.0];
-
-                global_joint_transforms[joint_idx.0] = parent_transform * current_transform;
-            } else {
-                global_joint_transforms[joint_idx.0] = joint_local_matrices[joint_idx.0];
-            }
+    use crate::{
+        advance_cursor, find_node, sample_at_time, sample_baked, AnimationIndex, BakeError, BakeSettings, BakedChannel,
+        Pose, QuantizedQuat, SharedAnimationData,
+    };
+
+    /// An instance whose nodes each are the child of the one before, offset
+    /// from it by their translation.
+    pub(crate) fn chain_instance(nodes: &[(&str, Vec3)]) -> GltfSceneInstance {
+        GltfSceneInstance {
+            nodes: nodes
+                .iter()
+                .enumerate()
+                .map(|(idx, &(name, translation))| {
+                    let node = Node {
+                        parent: idx.checked_sub(1),
+                        children: (idx + 1..nodes.len()).take(1).collect(),
+                        local_transform: Mat4::from_translation(translation),
+                        ..Node::default()
+                    };
+                    Labeled::new(node, Some(name))
+                })
+                .collect(),
+            topological_order: (0..nodes.len()).collect(),
+            parent_transform: Mat4::IDENTITY,
         }
+    }
 
-        // Set the joint positions in rend3
-        for skeleton in &per_skin_data.skeletons {
-            renderer.set_skeleton_joint_transforms(skeleton, &global_joint_transforms, inv_bind_mats);
+    /// A scene with an animation per translation, each holding node 0 at that
+    /// translation for one second.
+    pub(crate) fn translation_scene(translations: &[Vec3]) -> LoadedGltfScene {
+        let animations = translations
+            .iter()
+            .map(|&translation| {
+                let mut channels = PosRotScale::new(0);
+                channels.translation = Some(AnimationChannel {
+                    values: vec![translation; 2],
+                    times: vec![0.0, 1.0],
+                    interpolation: AnimationInterpolation::Linear,
+                    in_tangents: Vec::new(),
+                    out_tangents: Vec::new(),
+                });
+                let animation = Animation {
+                    channels: [(0, channels)].into_iter().collect(),
+                    duration: 1.0,
+                };
+                Labeled::new(animation, None)
+            })
+            .collect();
+
+        LoadedGltfScene {
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            default_material: MaterialHandle::new(0),
+            images: Default::default(),
+            skins: Vec::new(),
+            animations,
+        }
+    }
+
+    #[test]
+    fn global_transforms_follow_parents() {
+        let instance = chain_instance(&[("hips", Vec3::Y), ("spine", Vec3::Y), ("head", Vec3::Y)]);
+        let mut pose = Pose::bind_pose(&instance);
+        pose.local_transforms[0].rotation = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+
+        let head = pose.global_transforms(&instance)[2].w_axis.truncate();
+        assert!(head.abs_diff_eq(Vec3::new(-2.0, 1.0, 0.0), 1e-5), "{}", head);
+        assert_eq!(find_node(&instance, "spine").map(|node| node.0), Some(1));
+    }
+
+    fn channel<T>(
+        times: &[f32],
+        values: Vec<T>,
+        interpolation: AnimationInterpolation,
+        tangents: Option<(Vec<T>, Vec<T>)>,
+    ) -> AnimationChannel<T> {
+        let (in_tangents, out_tangents) = tangents.unwrap_or_default();
+        AnimationChannel {
+            values,
+            times: times.to_vec(),
+            interpolation,
+            in_tangents,
+            out_tangents,
+        }
+    }
+
+    #[test]
+    fn step_channels_hold_the_previous_keyframe() {
+        let channel = channel(
+            &[0.0, 1.0, 2.0],
+            vec![0.0, 10.0, 20.0],
+            AnimationInterpolation::Step,
+            None,
+        );
+
+        for (time, expected) in [
+            (-1.0, 0.0),
+            (0.0, 0.0),
+            (0.99, 0.0),
+            (1.0, 10.0),
+            (1.5, 10.0),
+            (5.0, 20.0),
+        ] {
+            assert_eq!(sample_at_time(&channel, time), Some(expected), "at {}", time);
+        }
+    }
+
+    #[test]
+    fn cubic_spline_channels_follow_their_tangents() {
+        // Tangents matching the slope between the keyframes give a straight line.
+        let tangents = (vec![0.5, 0.5], vec![0.5, 0.5]);
+        let line = channel(
+            &[0.0, 2.0],
+            vec![0.0, 1.0],
+            AnimationInterpolation::CubicSpline,
+            Some(tangents),
+        );
+        for time in [0.0, 0.5, 1.0, 1.5, 2.0] {
+            assert!(
+                (sample_at_time(&line, time).unwrap() - time / 2.0).abs() < 1e-6,
+                "at {}",
+                time
+            );
+        }
+
+        // Flat tangents ease in and out.
+        let tangents = (vec![0.0, 0.0], vec![0.0, 0.0]);
+        let ease = channel(
+            &[0.0, 2.0],
+            vec![0.0, 1.0],
+            AnimationInterpolation::CubicSpline,
+            Some(tangents),
+        );
+        assert!((sample_at_time(&ease, 0.5).unwrap() - 0.15625).abs() < 1e-6);
+        assert!((sample_at_time(&ease, 1.0).unwrap() - 0.5).abs() < 1e-6);
+        assert!((sample_at_time(&ease, 1.5).unwrap() - 0.84375).abs() < 1e-6);
+
+        // Rotations are interpolated component-wise, then normalized.
+        let end = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        let tangents = (
+            vec![Quat::from_xyzw(0.0, 0.0, 0.0, 0.0); 2],
+            vec![Quat::from_xyzw(0.0, 0.0, 0.0, 0.0); 2],
+        );
+        let rotation = channel(
+            &[0.0, 1.0],
+            vec![Quat::IDENTITY, end],
+            AnimationInterpolation::CubicSpline,
+            Some(tangents),
+        );
+        let middle = sample_at_time(&rotation, 0.5).unwrap();
+        assert!(middle.is_normalized());
+        assert!(
+            middle.abs_diff_eq(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4), 1e-5),
+            "{}",
+            middle
+        );
+        assert!(sample_at_time(&rotation, 1.0).unwrap().abs_diff_eq(end, 1e-6));
+    }
+
+    #[test]
+    fn cursor_sampling_matches_a_linear_search() {
+        let times = [0.0, 0.1, 0.15, 0.5, 0.7, 1.2, 2.0];
+        let linear_search = |time: f32| {
+            let prev = times.iter().rposition(|&t| t <= time).unwrap_or(0);
+            (prev, (prev + 1).min(times.len() - 1))
+        };
+
+        let forward = (0..=25).map(|i| i as f32 * 0.09);
+        let backward = (0..=25).rev().map(|i| i as f32 * 0.09);
+        // Loops back to the start twice, like a looping animation.
+        let wrapping = (0..=60).map(|i| (i as f32 * 0.07) % 2.0);
+
+        let mut cursor = 0;
+        for time in forward.chain(backward).chain(wrapping).chain([-1.0, 5.0, 0.0]) {
+            assert_eq!(
+                advance_cursor(&times, time, &mut cursor),
+                Some(linear_search(time)),
+                "at {}",
+                time
+            );
         }
+
+        // A cursor out of range of the channel is searched for again.
+        let mut cursor = 100;
+        assert_eq!(advance_cursor(&times, 0.6, &mut cursor), Some((3, 4)));
+    }
+
+    #[test]
+    fn empty_channels_have_no_samples() {
+        let mut cursor = 3;
+        assert_eq!(advance_cursor(&[], 1.0, &mut cursor), None);
+
+        let empty = channel::<f32>(&[], Vec::new(), AnimationInterpolation::Linear, None);
+        assert_eq!(sample_at_time(&empty, 1.0), None);
+
+        let empty = BakedChannel::<f32> {
+            values: Vec::new(),
+            step: false,
+        };
+        assert_eq!(sample_baked(&empty, 0.1, 1.0, |v| v), None);
+    }
+
+    #[test]
+    fn baked_sampling_matches_keyframe_sampling() {
+        let interval = 0.25;
+        let times = [0.0, 0.5, 0.75, 2.0];
+        let translations = channel(
+            &times,
+            vec![Vec3::ZERO, Vec3::X, Vec3::new(1.0, 2.0, -1.0), Vec3::Z * 3.0],
+            AnimationInterpolation::Linear,
+            None,
+        );
+        let steps = channel(&times, vec![1.0, 2.0, 3.0, 4.0], AnimationInterpolation::Step, None);
+
+        // Every keyframe lands on a sample, so both agree everywhere.
+        let samples = (0..=8).map(|i| i as f32 * interval);
+        let baked_translations = BakedChannel {
+            values: samples
+                .clone()
+                .map(|t| sample_at_time(&translations, t).unwrap())
+                .collect(),
+            step: false,
+        };
+        let baked_steps = BakedChannel {
+            values: samples.map(|t| sample_at_time(&steps, t).unwrap()).collect(),
+            step: true,
+        };
+
+        for time in (0..=45).map(|i| i as f32 * 0.05) {
+            let sampled = sample_at_time(&translations, time).unwrap();
+            let baked = sample_baked(&baked_translations, interval, time, |v| v).unwrap();
+            assert!(
+                baked.abs_diff_eq(sampled, 1e-5),
+                "at {}: {} != {}",
+                time,
+                baked,
+                sampled
+            );
+            assert_eq!(
+                sample_baked(&baked_steps, interval, time, |v| v),
+                sample_at_time(&steps, time),
+                "at {}",
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn baking_rejects_invalid_sample_rates() {
+        let scene = translation_scene(&[Vec3::X]);
+        let instance = chain_instance(&[("root", Vec3::ZERO)]);
+        let mut shared = SharedAnimationData::from_gltf_scene(&scene, &instance);
+        for sample_rate in [0.0, -30.0, f32::NAN, f32::INFINITY] {
+            let settings = BakeSettings {
+                sample_rate,
+                ..BakeSettings::default()
+            };
+            assert!(matches!(
+                shared.bake_uniform_sampling(&scene, 0, settings),
+                Err(BakeError::InvalidSampleRate(_))
+            ));
+        }
+        assert!(shared.baked.is_empty());
+
+        shared
+            .bake_uniform_sampling(&scene, 0, BakeSettings::default())
+            .unwrap();
+        assert_eq!(shared.baked[&AnimationIndex(0)].interval, 1.0 / 30.0);
+    }
+
+    #[test]
+    fn quantized_quats_round_trip() {
+        // Each component is off by at most half a step of 1 / i16::MAX.
+        let max_error = 1.0 / i16::MAX as f32;
+
+        assert_eq!(QuantizedQuat::new(Quat::IDENTITY).0, [0, 0, 0, i16::MAX]);
+        assert_eq!(QuantizedQuat::new(-Quat::IDENTITY).0, [0, 0, 0, -i16::MAX]);
+
+        let quats = [
+            // A single component holding the whole rotation, of either sign.
+            Quat::from_xyzw(1.0, 0.0, 0.0, 0.0),
+            Quat::from_xyzw(0.0, -1.0, 0.0, 0.0),
+            Quat::from_xyzw(0.0, 0.0, 1.0, 0.0),
+            Quat::from_xyzw(0.0, 0.0, 0.0, -1.0),
+            Quat::from_rotation_x(0.3),
+            Quat::from_rotation_y(-2.5),
+            Quat::from_axis_angle(Vec3::new(-1.0, 2.0, -3.0).normalize(), 1.7),
+            Quat::from_xyzw(-0.5, -0.5, -0.5, -0.5),
+            Quat::from_xyzw(0.001, -0.7, 0.7, -0.001).normalize(),
+        ];
+        for quat in quats {
+            let quantized = QuantizedQuat::new(quat);
+            let round_trip = quantized.to_quat();
+            assert!(round_trip.is_normalized());
+            assert!(
+                Vec4::from(round_trip).abs_diff_eq(Vec4::from(quat), max_error),
+                "{} != {}",
+                round_trip,
+                quat
+            );
+            // Signs are kept, so the rotation doesn't flip to the other hemisphere.
+            for (original, quantized) in quat.to_array().into_iter().zip(quantized.0) {
+                assert!(original * quantized as f32 >= 0.0, "{} vs {:?}", quat, quantized);
+            }
+        }
+
+        // Non-normalized input is normalized first.
+        assert_eq!(
+            QuantizedQuat::new(Quat::from_xyzw(0.0, 0.0, 3.0, 0.0)).0,
+            [0, 0, i16::MAX, 0]
+        );
     }
 }
@@ -0,0 +1,101 @@
+//! Blending several animations into a single pose.
+
+use rend3_gltf::{GltfSceneInstance, LoadedGltfScene};
+
+use crate::{sample_animation, AnimationData, AnimationIndex, Pose};
+
+/// A single animation fed into an [`AnimationMixer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MixerInput {
+    pub animation: AnimationIndex,
+    /// Time at which the animation is sampled.
+    pub time: f32,
+    /// Influence of this animation on the final pose. Weights do not need to
+    /// add up to one, they get normalized when sampling.
+    pub weight: f32,
+}
+
+/// Computes the weighted blend of several animations.
+///
+/// Every frame, add the animations to play with [`AnimationMixer::add`], then
+/// call [`AnimationMixer::sample`] to blend them into a [`Pose`].
+#[derive(Clone, Debug, Default)]
+pub struct AnimationMixer {
+    pub inputs: Vec<MixerInput>,
+    /// Pose each input gets sampled into before being blended.
+    scratch: Option<Pose>,
+}
+
+impl AnimationMixer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes all inputs.
+    pub fn clear(&mut self) {
+        self.inputs.clear();
+    }
+
+    /// Adds an animation to the blend.
+    pub fn add(&mut self, animation: AnimationIndex, time: f32, weight: f32) {
+        self.inputs.push(MixerInput {
+            animation,
+            time,
+            weight,
+        });
+    }
+
+    /// Samples every input and blends them into `pose`. If no input has a
+    /// positive weight, `pose` is left untouched.
+    pub fn sample(
+        &mut self,
+        scene: &LoadedGltfScene,
+        instance: &GltfSceneInstance,
+        animation_data: &mut AnimationData,
+        pose: &mut Pose,
+    ) {
+        let scratch = self.scratch.get_or_insert_with(|| Pose::bind_pose(instance));
+
+        let mut accumulated_weight = 0.0;
+        for input in self.inputs.iter().filter(|input| input.weight > 0.0) {
+            scratch.reset_to_bind_pose(instance);
+            sample_animation(scene, instance, animation_data, input.animation.0, input.time, scratch);
+
+            accumulated_weight += input.weight;
+            // Blending incrementally by the ratio of the weight seen so far
+            // gives the same result as a normalized weighted average.
+            pose.blend(scratch, input.weight / accumulated_weight);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rend3::types::glam::Vec3;
+
+    use super::AnimationMixer;
+    use crate::{
+        test::{chain_instance, translation_scene},
+        AnimationData, AnimationIndex, Pose,
+    };
+
+    #[test]
+    fn normalizes_weights() {
+        let scene = translation_scene(&[Vec3::ZERO, Vec3::X * 4.0, Vec3::Y]);
+        let instance = chain_instance(&[("root", Vec3::Z)]);
+        let mut animation_data = AnimationData::from_gltf_scene(&scene, &instance);
+        let mut pose = Pose::bind_pose(&instance);
+
+        let mut mixer = AnimationMixer::new();
+        // Without positive weights the pose is left alone.
+        mixer.add(AnimationIndex(2), 0.5, 0.0);
+        mixer.sample(&scene, &instance, &mut animation_data, &mut pose);
+        assert_eq!(pose.local_transforms[0].translation, Vec3::Z);
+
+        mixer.add(AnimationIndex(0), 0.5, 1.0);
+        mixer.add(AnimationIndex(1), 0.5, 3.0);
+        mixer.sample(&scene, &instance, &mut animation_data, &mut pose);
+        let translation = pose.local_transforms[0].translation;
+        assert!(translation.abs_diff_eq(Vec3::X * 3.0, 1e-5), "{}", translation);
+    }
+}
@@ -0,0 +1,218 @@
+//! Playing animations authored for one skeleton on another.
+//!
+//! A [`Retargeter`] pairs up the nodes of a source and a target scene instance
+//! by name. Animations get sampled into a [`Pose`] of the source instance as
+//! usual, and [`Retargeter::retarget`] then transfers that pose to a [`Pose`]
+//! of the target instance, correcting for the differences between the rest
+//! poses of both skeletons.
+
+use rend3::{
+    types::glam::{Quat, Vec3},
+    util::typedefs::SsoString,
+};
+use rend3_gltf::GltfSceneInstance;
+use thiserror::Error;
+
+use crate::{find_node, NodeIndex, Pose, Transform};
+
+/// Describes how creating a [`Retargeter`] failed.
+#[derive(Debug, Error)]
+pub enum RetargetError {
+    #[error("Source skeleton does not have a node named {0}")]
+    MissingSourceNode(SsoString),
+    #[error("Target skeleton does not have a node named {0}")]
+    MissingTargetNode(SsoString),
+}
+
+/// A pair of nodes that get retargeted onto each other.
+#[derive(Debug, Clone)]
+struct RetargetedNode {
+    source: NodeIndex,
+    target: NodeIndex,
+    source_rest: Transform,
+    target_rest: Transform,
+    /// Rotation from the rest frame of the source node to the rest frame of
+    /// the target node, both relative to the root of their instance.
+    correction: Quat,
+}
+
+/// Transfers poses between two skeletons with different proportions or rest
+/// poses.
+#[derive(Debug, Clone)]
+pub struct Retargeter {
+    /// Sorted so parents always come before their children.
+    nodes: Vec<RetargetedNode>,
+    /// Factor applied to the translation of the top-most retargeted node,
+    /// usually the hips, to account for the difference in size of the two
+    /// skeletons. Defaults to the ratio between the rest heights of that node
+    /// in both skeletons.
+    pub root_translation_scale: f32,
+}
+
+impl Retargeter {
+    /// Creates a retargeter from a list of `(source name, target name)` pairs.
+    ///
+    /// The rest pose of each skeleton is the pose their instances were loaded
+    /// with. Nodes that aren't in the mapping are left untouched.
+    pub fn new(
+        source: &GltfSceneInstance,
+        target: &GltfSceneInstance,
+        mapping: &[(&str, &str)],
+    ) -> Result<Self, RetargetError> {
+        let pairs = mapping
+            .iter()
+            .map(|&(source_name, target_name)| {
+                let source_idx = find_node(source, source_name)
+                    .ok_or_else(|| RetargetError::MissingSourceNode(SsoString::from(source_name)))?;
+                let target_idx = find_node(target, target_name)
+                    .ok_or_else(|| RetargetError::MissingTargetNode(SsoString::from(target_name)))?;
+                Ok((source_idx, target_idx))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::from_node_pairs(source, target, pairs))
+    }
+
+    /// Creates a retargeter mapping every node of `source` to the node of
+    /// `target` with the same name, for rigs that follow the same naming
+    /// convention.
+    pub fn from_matching_names(source: &GltfSceneInstance, target: &GltfSceneInstance) -> Self {
+        let pairs = source
+            .nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(source_idx, node)| {
+                let target_idx = find_node(target, node.label.as_deref()?)?;
+                Some((NodeIndex(source_idx), target_idx))
+            })
+            .collect();
+
+        Self::from_node_pairs(source, target, pairs)
+    }
+
+    fn from_node_pairs(
+        source: &GltfSceneInstance,
+        target: &GltfSceneInstance,
+        mut pairs: Vec<(NodeIndex, NodeIndex)>,
+    ) -> Self {
+        let source_rest = Pose::bind_pose(source);
+        let target_rest = Pose::bind_pose(target);
+        let source_globals = source_rest.global_transforms(source);
+        let target_globals = target_rest.global_transforms(target);
+
+        let topological_position = |node: NodeIndex| {
+            source
+                .topological_order
+                .iter()
+                .position(|&idx| idx == node.0)
+                .unwrap_or(usize::MAX)
+        };
+        pairs.sort_by_key(|&(source_idx, _)| topological_position(source_idx));
+
+        let nodes: Vec<_> = pairs
+            .into_iter()
+            .map(|(source_idx, target_idx)| {
+                let (_, source_global_rotation, _) = source_globals[source_idx.0].to_scale_rotation_translation();
+                let (_, target_global_rotation, _) = target_globals[target_idx.0].to_scale_rotation_translation();
+                RetargetedNode {
+                    source: source_idx,
+                    target: target_idx,
+                    source_rest: source_rest.local_transforms[source_idx.0],
+                    target_rest: target_rest.local_transforms[target_idx.0],
+                    correction: source_global_rotation.inverse() * target_global_rotation,
+                }
+            })
+            .collect();
+
+        let root_translation_scale = nodes
+            .first()
+            .map(|root| {
+                let source_height = source_globals[root.source.0].w_axis.truncate().length();
+                let target_height = target_globals[root.target.0].w_axis.truncate().length();
+                if source_height > f32::EPSILON {
+                    target_height / source_height
+                } else {
+                    1.0
+                }
+            })
+            .unwrap_or(1.0);
+
+        Self {
+            nodes,
+            root_translation_scale,
+        }
+    }
+
+    /// Sets the retargeted nodes of `target_pose` to match `source_pose`.
+    ///
+    /// Each node gets the rotation it has relative to its rest pose in the
+    /// source skeleton, converted to the rest frame of the target node. Only
+    /// the top-most node gets translated, the rest keep the bone lengths of the
+    /// target skeleton.
+    pub fn retarget(&self, source_pose: &Pose, target_pose: &mut Pose) {
+        for (idx, node) in self.nodes.iter().enumerate() {
+            let source = source_pose.local_transforms[node.source.0];
+
+            // Rotation relative to the rest pose, in the frame of the source node
+            let delta = node.source_rest.rotation.inverse() * source.rotation;
+            let retargeted_delta = node.correction.inverse() * delta * node.correction;
+
+            let target = &mut target_pose.local_transforms[node.target.0];
+            target.rotation = (node.target_rest.rotation * retargeted_delta).normalize();
+            target.translation = if idx == 0 {
+                node.target_rest.translation
+                    + (source.translation - node.source_rest.translation) * self.root_translation_scale
+            } else {
+                node.target_rest.translation
+            };
+            target.scale = node.target_rest.scale * safe_div(source.scale, node.source_rest.scale);
+        }
+    }
+}
+
+fn safe_div(a: Vec3, b: Vec3) -> Vec3 {
+    Vec3::select(b.cmpeq(Vec3::ZERO), Vec3::ONE, a / b)
+}
+
+#[cfg(test)]
+mod test {
+    use rend3::types::glam::{Mat4, Quat, Vec3};
+
+    use super::{RetargetError, Retargeter};
+    use crate::{test::chain_instance, Pose};
+
+    #[test]
+    fn keeps_world_space_rotations() {
+        let source = chain_instance(&[("hips", Vec3::Y), ("spine", Vec3::Y)]);
+        // Twice as tall, with a spine whose rest pose is turned around.
+        let mut target = chain_instance(&[("Hips", Vec3::Y * 2.0), ("Spine", Vec3::Y * 2.0)]);
+        let spine_rest = Quat::from_rotation_y(1.0);
+        target.nodes[1].inner.local_transform = Mat4::from_rotation_translation(spine_rest, Vec3::Y * 2.0);
+
+        let retargeter = Retargeter::new(&source, &target, &[("hips", "Hips"), ("spine", "Spine")]).unwrap();
+        assert_eq!(retargeter.root_translation_scale, 2.0);
+
+        let mut source_pose = Pose::bind_pose(&source);
+        source_pose.local_transforms[0].translation += Vec3::Z;
+        let bend = Quat::from_rotation_x(0.5);
+        source_pose.local_transforms[1].rotation = bend;
+        let mut target_pose = Pose::bind_pose(&target);
+        retargeter.retarget(&source_pose, &mut target_pose);
+
+        let hips = target_pose.local_transforms[0].translation;
+        assert!(hips.abs_diff_eq(Vec3::new(0.0, 2.0, 2.0), 1e-5), "{}", hips);
+        // The spine bends the same way in the world as the source's does.
+        let spine = target_pose.local_transforms[1];
+        assert!(
+            spine.rotation.abs_diff_eq(bend * spine_rest, 1e-5),
+            "{}",
+            spine.rotation
+        );
+        assert_eq!(spine.translation, Vec3::Y * 2.0);
+
+        assert!(matches!(
+            Retargeter::new(&source, &target, &[("tail", "Spine")]),
+            Err(RetargetError::MissingSourceNode(name)) if name.as_str() == "tail"
+        ));
+    }
+}
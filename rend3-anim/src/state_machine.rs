@@ -0,0 +1,303 @@
+//! State machine driving which animations play, and how they blend into each
+//! other.
+//!
+//! Each [`AnimationState`] plays a [`Motion`], either a single animation or a
+//! blend space. [`Transition`]s move between
+//! states when all of their [`Condition`]s hold, cross-fading the two states
+//! over the transition's blend duration. Conditions are evaluated against
+//! named parameters set by game code, e.g. a `speed` float or an `is_jumping`
+//! bool.
+//!
+//! Every frame, set the parameters, advance the machine with
+//! [`AnimationStateMachine::update`] and feed the resulting blend into an
+//! [`AnimationMixer`] with [`AnimationStateMachine::fill_mixer`].
+
+use rend3::{
+    types::glam::Vec2,
+    util::typedefs::{FastHashMap, SsoString},
+};
+use rend3_gltf::LoadedGltfScene;
+
+use crate::{
+    blend_space::{BlendSpace1D, BlendSpace2D},
+    mixer::AnimationMixer,
+    AnimationIndex,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct StateIndex(pub usize);
+
+/// What a state plays while active.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Motion {
+    /// A single animation.
+    Clip(AnimationIndex),
+    /// A 1D blend space driven by a float parameter.
+    BlendSpace1D { parameter: SsoString, space: BlendSpace1D },
+    /// A 2D blend space driven by two float parameters.
+    BlendSpace2D {
+        x_parameter: SsoString,
+        y_parameter: SsoString,
+        space: BlendSpace2D,
+    },
+}
+
+impl Motion {
+    /// Duration of the motion, in seconds.
+    fn duration(&self, scene: &LoadedGltfScene, parameters: &FastHashMap<SsoString, ParameterValue>) -> f32 {
+        match self {
+            Motion::Clip(animation) => scene.animations[animation.0].inner.duration,
+            Motion::BlendSpace1D { parameter, space } => space.duration(scene, float(parameters, parameter)),
+            Motion::BlendSpace2D {
+                x_parameter,
+                y_parameter,
+                space,
+            } => space.duration(
+                scene,
+                Vec2::new(float(parameters, x_parameter), float(parameters, y_parameter)),
+            ),
+        }
+    }
+
+    /// Adds the animations of this motion at the given time and weight.
+    fn fill_mixer(
+        &self,
+        scene: &LoadedGltfScene,
+        parameters: &FastHashMap<SsoString, ParameterValue>,
+        mixer: &mut AnimationMixer,
+        time: f32,
+        weight: f32,
+    ) {
+        let normalized_time = |duration: f32| if duration > 0.0 { time / duration } else { 0.0 };
+        match self {
+            Motion::Clip(animation) => mixer.add(*animation, time, weight),
+            Motion::BlendSpace1D { parameter, space } => {
+                let value = float(parameters, parameter);
+                let normalized_time = normalized_time(space.duration(scene, value));
+                space.fill_mixer(scene, mixer, value, normalized_time, weight);
+            }
+            Motion::BlendSpace2D {
+                x_parameter,
+                y_parameter,
+                space,
+            } => {
+                let value = Vec2::new(float(parameters, x_parameter), float(parameters, y_parameter));
+                let normalized_time = normalized_time(space.duration(scene, value));
+                space.fill_mixer(scene, mixer, value, normalized_time, weight);
+            }
+        }
+    }
+}
+
+/// Value of a float parameter, or zero if it's missing or not a float.
+fn float(parameters: &FastHashMap<SsoString, ParameterValue>, name: &str) -> f32 {
+    match parameters.get(name) {
+        Some(&ParameterValue::Float(value)) => value,
+        _ => 0.0,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationState {
+    pub name: SsoString,
+    pub motion: Motion,
+    /// Playback speed multiplier.
+    pub speed: f32,
+    /// If true, the motion restarts when reaching its end. Otherwise it stays
+    /// on its last frame.
+    pub looping: bool,
+}
+
+/// Value of a state machine parameter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParameterValue {
+    Float(f32),
+    Bool(bool),
+}
+
+/// A check that must pass for a [`Transition`] to happen. Missing parameters,
+/// or parameters of the wrong type, never pass.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// The float parameter is greater than the value.
+    Greater(SsoString, f32),
+    /// The float parameter is less than the value.
+    Less(SsoString, f32),
+    /// The bool parameter is true.
+    IsTrue(SsoString),
+    /// The bool parameter is false.
+    IsFalse(SsoString),
+    /// The current state has played at least this fraction of its motion,
+    /// counting loops. `1.0` waits until the motion has played once.
+    ExitTime(f32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    /// State the transition starts from. `None` allows it to happen from any
+    /// state other than `to`.
+    pub from: Option<StateIndex>,
+    pub to: StateIndex,
+    /// All conditions must pass for the transition to happen.
+    pub conditions: Vec<Condition>,
+    /// Time, in seconds, the previous state takes to fade out.
+    pub blend_duration: f32,
+}
+
+/// A state that is fading out after a transition.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FadingState {
+    state: StateIndex,
+    time: f32,
+    elapsed: f32,
+    duration: f32,
+}
+
+/// Chooses the active animation state based on parameters, and cross-fades
+/// between states on transitions.
+#[derive(Clone, Debug)]
+pub struct AnimationStateMachine {
+    pub states: Vec<AnimationState>,
+    /// Transitions are checked in order, the first one that passes is taken.
+    pub transitions: Vec<Transition>,
+    parameters: FastHashMap<SsoString, ParameterValue>,
+    current: StateIndex,
+    /// Playback time of the current state, not wrapped by looping.
+    current_time: f32,
+    fading: Option<FadingState>,
+}
+
+impl AnimationStateMachine {
+    pub fn new(states: Vec<AnimationState>, transitions: Vec<Transition>, initial: StateIndex) -> Self {
+        Self {
+            states,
+            transitions,
+            parameters: FastHashMap::default(),
+            current: initial,
+            current_time: 0.0,
+            fading: None,
+        }
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.parameters
+            .insert(SsoString::from(name), ParameterValue::Float(value));
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.parameters
+            .insert(SsoString::from(name), ParameterValue::Bool(value));
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<ParameterValue> {
+        self.parameters.get(name).copied()
+    }
+
+    pub fn current_state(&self) -> StateIndex {
+        self.current
+    }
+
+    /// Returns true while the previous state is still fading out.
+    pub fn is_transitioning(&self) -> bool {
+        self.fading.is_some()
+    }
+
+    /// Immediately switches to `state`, without blending.
+    pub fn jump_to(&mut self, state: StateIndex) {
+        self.current = state;
+        self.current_time = 0.0;
+        self.fading = None;
+    }
+
+    /// Advances playback by `delta` seconds, then takes the first transition
+    /// whose conditions pass, if any.
+    pub fn update(&mut self, scene: &LoadedGltfScene, delta: f32) {
+        self.current_time += delta * self.states[self.current.0].speed;
+        if let Some(fading) = &mut self.fading {
+            fading.time += delta * self.states[fading.state.0].speed;
+            fading.elapsed += delta;
+            if fading.elapsed >= fading.duration {
+                self.fading = None;
+            }
+        }
+
+        let duration = self.states[self.current.0].motion.duration(scene, &self.parameters);
+        let normalized_time = if duration > 0.0 {
+            self.current_time / duration
+        } else {
+            1.0
+        };
+
+        let transition = self.transitions.iter().find(|transition| {
+            let from_matches = match transition.from {
+                Some(from) => from == self.current,
+                None => transition.to != self.current,
+            };
+            from_matches
+                && transition
+                    .conditions
+                    .iter()
+                    .all(|condition| self.check(condition, normalized_time))
+        });
+
+        if let Some(transition) = transition {
+            self.fading = Some(FadingState {
+                state: self.current,
+                time: self.current_time,
+                elapsed: 0.0,
+                duration: transition.blend_duration,
+            })
+            .filter(|fading| fading.duration > 0.0);
+            self.current = transition.to;
+            self.current_time = 0.0;
+        }
+    }
+
+    fn check(&self, condition: &Condition, normalized_time: f32) -> bool {
+        match condition {
+            Condition::Greater(name, value) => {
+                matches!(self.parameters.get(name), Some(&ParameterValue::Float(v)) if v > *value)
+            }
+            Condition::Less(name, value) => {
+                matches!(self.parameters.get(name), Some(&ParameterValue::Float(v)) if v < *value)
+            }
+            Condition::IsTrue(name) => matches!(self.parameters.get(name), Some(ParameterValue::Bool(true))),
+            Condition::IsFalse(name) => matches!(self.parameters.get(name), Some(ParameterValue::Bool(false))),
+            Condition::ExitTime(fraction) => normalized_time >= *fraction,
+        }
+    }
+
+    /// Replaces the inputs of `mixer` with the animations of the current state
+    /// and, during a transition, of the state fading out.
+    pub fn fill_mixer(&self, scene: &LoadedGltfScene, mixer: &mut AnimationMixer) {
+        mixer.clear();
+
+        let fade_in = match self.fading {
+            Some(fading) => {
+                let fade_in = (fading.elapsed / fading.duration).clamp(0.0, 1.0);
+                self.fill_state(scene, mixer, fading.state, fading.time, 1.0 - fade_in);
+                fade_in
+            }
+            None => 1.0,
+        };
+        self.fill_state(scene, mixer, self.current, self.current_time, fade_in);
+    }
+
+    fn fill_state(
+        &self,
+        scene: &LoadedGltfScene,
+        mixer: &mut AnimationMixer,
+        state: StateIndex,
+        time: f32,
+        weight: f32,
+    ) {
+        let state = &self.states[state.0];
+        let duration = state.motion.duration(scene, &self.parameters);
+        let time = if state.looping && duration > 0.0 {
+            time.rem_euclid(duration)
+        } else {
+            time.min(duration)
+        };
+        state.motion.fill_mixer(scene, &self.parameters, mixer, time, weight);
+    }
+}
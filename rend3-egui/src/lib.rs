@@ -1,20 +1,31 @@
 //! Render routine integrating egui into a rend3 rendergraph.
 //!
-//! Call [`EguiRenderRoutine::add_to_graph`] to add it to the graph.
+//! Call [`EguiRenderRoutine::add_to_graph`] to add it to the graph. To show a
+//! 3D view of the scene inside the UI, use an [`EguiViewport`].
 
 use egui::TexturesDelta;
 use rend3::{
     graph::{RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
-    types::SampleCount,
+    types::{SampleCount, TextureHandle},
+    util::typedefs::FastHashMap,
     Renderer,
 };
 use std::{mem, sync::Arc};
 use wgpu::{Color, TextureFormat};
 
+mod viewport;
+
+pub use viewport::EguiViewport;
+
 pub struct EguiRenderRoutine {
     pub internal: egui_wgpu_backend::RenderPass,
     screen_descriptor: egui_wgpu_backend::ScreenDescriptor,
     textures_to_free: Vec<egui::TextureId>,
+    /// Keeps registered rend3 textures alive until they are unregistered.
+    rend3_textures: FastHashMap<egui::TextureId, TextureHandle>,
+    /// Registered rend3 textures still showing the placeholder.
+    pending_rend3_textures: Vec<egui::TextureId>,
+    placeholder: wgpu::TextureView,
 }
 
 impl EguiRenderRoutine {
@@ -32,6 +43,23 @@ impl EguiRenderRoutine {
     ) -> Self {
         let rpass = egui_wgpu_backend::RenderPass::new(&renderer.device, surface_format, samples as _);
 
+        let placeholder = renderer
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("egui rend3 texture placeholder"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
         Self {
             internal: rpass,
             screen_descriptor: egui_wgpu_backend::ScreenDescriptor {
@@ -40,6 +68,9 @@ impl EguiRenderRoutine {
                 scale_factor,
             },
             textures_to_free: Vec::new(),
+            rend3_textures: FastHashMap::default(),
+            pending_rend3_textures: Vec::new(),
+            placeholder,
         }
     }
 
@@ -51,6 +82,31 @@ impl EguiRenderRoutine {
         };
     }
 
+    /// Makes a 2D texture of the renderer available to egui, e.g. to preview
+    /// materials in an `egui::Image`, without copying it through the CPU.
+    ///
+    /// The texture is kept alive until it is unregistered with
+    /// [`EguiRenderRoutine::unregister_rend3_texture`]. As textures are only
+    /// uploaded when the renderer is readied, it shows up the next time egui
+    /// is rendered; until then, the id shows a transparent placeholder.
+    pub fn register_rend3_texture(&mut self, renderer: &Renderer, handle: &TextureHandle) -> egui::TextureId {
+        let id =
+            self.internal
+                .egui_texture_from_wgpu_texture(&renderer.device, &self.placeholder, wgpu::FilterMode::Linear);
+        self.rend3_textures.insert(id, handle.clone());
+        self.pending_rend3_textures.push(id);
+        id
+    }
+
+    /// Stops showing a texture registered with
+    /// [`EguiRenderRoutine::register_rend3_texture`], and releases the
+    /// routine's hold on it. The id must not be used afterwards.
+    pub fn unregister_rend3_texture(&mut self, id: egui::TextureId) {
+        if self.rend3_textures.remove(&id).is_some() {
+            self.textures_to_free.push(id);
+        }
+    }
+
     pub fn add_to_graph<'node>(
         &'node mut self,
         graph: &mut RenderGraph<'node>,
@@ -74,12 +130,23 @@ impl EguiRenderRoutine {
         // the lifetime of `self` for the remainder of the closure. so we instead buffer the textures
         // to free for a frame so we can clean them up before the next call.
         let textures_to_free = mem::replace(&mut self.textures_to_free, mem::take(&mut input.textures_delta.free));
+        let pending_rend3_textures = mem::take(&mut self.pending_rend3_textures);
         let pt_handle = builder.passthrough_ref_mut(self);
 
-        builder.build(move |pt, renderer, encoder_or_pass, _temps, _ready, _graph_data| {
+        builder.build(move |pt, renderer, encoder_or_pass, _temps, _ready, graph_data| {
             let this = pt.get_mut(pt_handle);
             let rpass = encoder_or_pass.get_rpass(rpass_handle);
 
+            // The textures have been uploaded by now, so swap out the placeholders.
+            for id in pending_rend3_textures {
+                if let Some(handle) = this.rend3_textures.get(&id) {
+                    let view = graph_data.d2_texture_manager.get_view(handle.get_raw());
+                    this.internal
+                        .update_egui_texture_from_wgpu_texture(&renderer.device, view, wgpu::FilterMode::Linear, id)
+                        .unwrap();
+                }
+            }
+
             let _ = this.internal.remove_textures(TexturesDelta {
                 set: Default::default(),
                 free: textures_to_free,
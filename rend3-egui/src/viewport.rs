@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use glam::{UVec2, Vec4};
+use rend3::{
+    graph::RenderGraph,
+    types::{Camera, SampleCount},
+    util::output::OutputFrame,
+    Renderer,
+};
+use rend3_routine::{base::BaseRenderGraph, pbr::PbrRoutine, skybox::SkyboxRoutine, tonemapping::TonemappingRoutine};
+use wgpu::{FilterMode, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView};
+
+use crate::EguiRenderRoutine;
+
+/// Format viewports are rendered in. Egui samples user textures as srgb.
+const VIEWPORT_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// A 3D view of the scene shown inside an egui UI, e.g. in a dockable panel
+/// of an editor.
+///
+/// The scene is rendered from [`EguiViewport::camera`] into a texture, which
+/// [`EguiViewport::show`] displays as an image filling the space available
+/// to it. The texture follows the size of that space, and is rendered in the
+/// format egui expects regardless of the format of the surface.
+///
+/// Every frame, call [`EguiViewport::show`] while building the UI, then
+/// [`EguiViewport::render`] before rendering the main view.
+pub struct EguiViewport {
+    pub camera: Camera,
+    tonemapping: TonemappingRoutine,
+    view: Option<Arc<TextureView>>,
+    texture_id: Option<egui::TextureId>,
+    resolution: UVec2,
+}
+
+impl EguiViewport {
+    pub fn new(renderer: &Renderer, base_rendergraph: &BaseRenderGraph, camera: Camera) -> Self {
+        Self {
+            camera,
+            tonemapping: TonemappingRoutine::new(renderer, &base_rendergraph.interfaces, VIEWPORT_FORMAT),
+            view: None,
+            texture_id: None,
+            resolution: UVec2::ZERO,
+        }
+    }
+
+    /// Resolution of the texture the viewport is rendered into. Zero until
+    /// the viewport is first shown.
+    pub fn resolution(&self) -> UVec2 {
+        self.resolution
+    }
+
+    /// Shows the viewport in `ui`, taking up all the available space.
+    ///
+    /// The returned response senses clicks and drags, so it can be used to
+    /// control the camera.
+    pub fn show(&mut self, ui: &mut egui::Ui, routine: &mut EguiRenderRoutine, renderer: &Renderer) -> egui::Response {
+        let size = ui.available_size();
+        let resolution = (glam::Vec2::new(size.x, size.y) * ui.ctx().pixels_per_point())
+            .round()
+            .as_uvec2()
+            .max(UVec2::ONE);
+
+        if resolution != self.resolution || self.view.is_none() {
+            self.resize(routine, renderer, resolution);
+        }
+
+        let image = egui::Image::new(self.texture_id.unwrap(), size).sense(egui::Sense::click_and_drag());
+        ui.add(image)
+    }
+
+    fn resize(&mut self, routine: &mut EguiRenderRoutine, renderer: &Renderer, resolution: UVec2) {
+        let texture = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("egui viewport"),
+            size: wgpu::Extent3d {
+                width: resolution.x,
+                height: resolution.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: VIEWPORT_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        });
+        let view = Arc::new(texture.create_view(&wgpu::TextureViewDescriptor::default()));
+
+        // Reuse the egui texture, so ids handed out in earlier frames stay valid.
+        self.texture_id = Some(match self.texture_id {
+            Some(id) => {
+                routine
+                    .internal
+                    .update_egui_texture_from_wgpu_texture(&renderer.device, &view, FilterMode::Linear, id)
+                    .expect("Viewport texture was freed");
+                id
+            }
+            None => routine
+                .internal
+                .egui_texture_from_wgpu_texture(&renderer.device, &view, FilterMode::Linear),
+        });
+        self.view = Some(view);
+        self.resolution = resolution;
+    }
+
+    /// Renders the scene from [`EguiViewport::camera`] into the viewport with
+    /// the default rendergraph. Does nothing until the viewport was shown.
+    ///
+    /// This changes the camera and aspect ratio of the renderer, so set the
+    /// ones of the main view again afterwards.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        renderer: &Arc<Renderer>,
+        base_rendergraph: &BaseRenderGraph,
+        pbr: &PbrRoutine,
+        skybox: Option<&SkyboxRoutine>,
+        samples: SampleCount,
+        ambient: Vec4,
+        clear_color: Vec4,
+    ) {
+        let view = match self.view {
+            Some(ref view) => Arc::clone(view),
+            None => return,
+        };
+
+        renderer.set_aspect_ratio(self.resolution.x as f32 / self.resolution.y as f32);
+        renderer.set_camera_data(self.camera);
+
+        let (cmd_bufs, ready) = renderer.ready();
+        let mut graph = RenderGraph::new();
+        base_rendergraph.add_to_graph(
+            &mut graph,
+            &ready,
+            rend3_routine::base::BaseRenderGraphRoutines {
+                pbr,
+                skybox,
+                tonemapping: &self.tonemapping,
+            },
+            &rend3_routine::base::BaseRenderGraphSettings::new(self.resolution)
+                .with_samples(samples)
+                .with_ambient(ambient)
+                .with_clear_color(clear_color),
+        );
+        graph.execute(renderer, OutputFrame::View(view), cmd_bufs, &ready);
+    }
+}
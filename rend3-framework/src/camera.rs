@@ -0,0 +1,421 @@
+use glam::{Mat4, Quat, Vec2, Vec3};
+use rend3::{
+    types::{Camera, CameraProjection, Handedness},
+    util::typedefs::FastHashSet,
+    Renderer,
+};
+use winit::event::{
+    DeviceEvent, ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+use crate::Event;
+
+/// Keeps the pitch just short of straight up/down so the view never flips.
+const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 - 0.0001;
+
+/// Rotation of a camera with the given yaw and pitch.
+///
+/// Right handed cameras look down -Z, so both angles are flipped to keep
+/// positive yaw turning right and positive pitch looking down in either
+/// handedness.
+fn rotation(handedness: Handedness, yaw: f32, pitch: f32) -> Quat {
+    let sign = match handedness {
+        Handedness::Left => 1.0,
+        Handedness::Right => -1.0,
+    };
+    Quat::from_rotation_y(yaw * sign) * Quat::from_rotation_x(pitch * sign)
+}
+
+/// Direction the camera looks at when it has no rotation.
+fn local_forward(handedness: Handedness) -> Vec3 {
+    match handedness {
+        Handedness::Left => Vec3::Z,
+        Handedness::Right => -Vec3::Z,
+    }
+}
+
+/// First person camera, moved with WASD and rotated with the mouse.
+///
+/// Feed it every event from [`App::handle_event`](crate::App::handle_event)
+/// with [`FlyCameraController::handle_event`], then call
+/// [`FlyCameraController::update`] once per frame to move the camera and send
+/// it to the renderer.
+///
+/// | Key        | Action        |
+/// |------------|---------------|
+/// | W/A/S/D    | Move          |
+/// | Q/Z        | Move up/down  |
+/// | Left Shift | Move faster   |
+///
+/// All mouse motion rotates the camera; if the cursor should only look around
+/// while grabbed, only forward mouse events while grabbed.
+#[derive(Debug, Clone)]
+pub struct FlyCameraController {
+    pub position: Vec3,
+    /// Rotation around the up axis, in radians. Positive turns right in either
+    /// handedness.
+    pub yaw: f32,
+    /// Rotation around the side axis, in radians. Positive looks down in
+    /// either handedness.
+    pub pitch: f32,
+    /// Movement speed in units per second.
+    pub walk_speed: f32,
+    /// Movement speed in units per second while holding shift.
+    pub run_speed: f32,
+    /// Radians turned per pixel of mouse movement.
+    pub mouse_sensitivity: f32,
+    pub projection: CameraProjection,
+    pub handedness: Handedness,
+    pressed: FastHashSet<VirtualKeyCode>,
+}
+
+impl FlyCameraController {
+    pub fn new(handedness: Handedness, position: Vec3) -> Self {
+        Self {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            walk_speed: 10.0,
+            run_speed: 50.0,
+            mouse_sensitivity: 0.001,
+            projection: CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+            handedness,
+            pressed: FastHashSet::default(),
+        }
+    }
+
+    /// Updates the pressed keys and the camera rotation.
+    pub fn handle_event<T>(&mut self, event: &Event<'_, T>) {
+        match *event {
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(key),
+                                state,
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.pressed.insert(key);
+                }
+                ElementState::Released => {
+                    self.pressed.remove(&key);
+                }
+            },
+            Event::WindowEvent {
+                event: WindowEvent::Focused(false),
+                ..
+            } => self.pressed.clear(),
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta: (x, y) },
+                ..
+            } => {
+                let delta = Vec2::new(x as f32, y as f32) * self.mouse_sensitivity;
+                self.yaw = (self.yaw + delta.x).rem_euclid(std::f32::consts::TAU);
+                self.pitch = (self.pitch + delta.y).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves the camera according to the held keys over `delta_time` seconds,
+    /// then sends the camera to the renderer.
+    pub fn update(&mut self, renderer: &Renderer, delta_time: f32) {
+        self.move_with_held_keys(delta_time);
+        renderer.set_camera_data(self.camera());
+    }
+
+    fn move_with_held_keys(&mut self, delta_time: f32) {
+        let rotation = rotation(self.handedness, self.yaw, self.pitch);
+        let forward = rotation * local_forward(self.handedness);
+        let right = rotation * Vec3::X;
+
+        let mut direction = Vec3::ZERO;
+        for (key, axis) in [
+            (VirtualKeyCode::W, forward),
+            (VirtualKeyCode::S, -forward),
+            (VirtualKeyCode::D, right),
+            (VirtualKeyCode::A, -right),
+            (VirtualKeyCode::Q, Vec3::Y),
+            (VirtualKeyCode::Z, -Vec3::Y),
+        ] {
+            if self.pressed.contains(&key) {
+                direction += axis;
+            }
+        }
+
+        let speed = if self.pressed.contains(&VirtualKeyCode::LShift) {
+            self.run_speed
+        } else {
+            self.walk_speed
+        };
+        self.position += direction.normalize_or_zero() * speed * delta_time;
+    }
+
+    pub fn camera(&self) -> Camera {
+        Camera {
+            projection: self.projection,
+            view: Mat4::from_rotation_translation(rotation(self.handedness, self.yaw, self.pitch), self.position)
+                .inverse(),
+        }
+    }
+}
+
+/// Turntable camera orbiting around a target point.
+///
+/// Feed it every event from [`App::handle_event`](crate::App::handle_event)
+/// with [`OrbitCameraController::handle_event`], and send the camera to the
+/// renderer with [`OrbitCameraController::update`] once per frame.
+///
+/// | Input              | Action            |
+/// |--------------------|-------------------|
+/// | Left mouse drag    | Orbit             |
+/// | Right/middle drag  | Pan the target    |
+/// | Scroll wheel       | Zoom              |
+#[derive(Debug, Clone)]
+pub struct OrbitCameraController {
+    /// Point the camera orbits around and looks at.
+    pub target: Vec3,
+    /// Distance from the camera to the target.
+    pub distance: f32,
+    /// Rotation around the up axis, in radians. Positive turns right in either
+    /// handedness.
+    pub yaw: f32,
+    /// Rotation around the side axis, in radians. Positive looks down in
+    /// either handedness.
+    pub pitch: f32,
+    /// Closest and furthest the camera can get to the target.
+    pub distance_range: (f32, f32),
+    /// Radians turned per pixel of mouse movement.
+    pub rotate_sensitivity: f32,
+    /// Fraction of the distance moved per pixel of mouse movement when panning.
+    pub pan_sensitivity: f32,
+    /// Fraction of the distance moved per line scrolled.
+    pub zoom_sensitivity: f32,
+    pub projection: CameraProjection,
+    pub handedness: Handedness,
+    rotating: bool,
+    panning: bool,
+}
+
+impl OrbitCameraController {
+    pub fn new(handedness: Handedness, target: Vec3, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: std::f32::consts::FRAC_PI_8,
+            distance_range: (0.01, f32::INFINITY),
+            rotate_sensitivity: 0.005,
+            pan_sensitivity: 0.001,
+            zoom_sensitivity: 0.1,
+            projection: CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+            handedness,
+            rotating: false,
+            panning: false,
+        }
+    }
+
+    /// Updates the camera from mouse buttons, motion and scrolling.
+    pub fn handle_event<T>(&mut self, event: &Event<'_, T>) {
+        match *event {
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { button, state, .. },
+                ..
+            } => {
+                let pressed = state == ElementState::Pressed;
+                match button {
+                    MouseButton::Left => self.rotating = pressed,
+                    MouseButton::Right | MouseButton::Middle => self.panning = pressed,
+                    MouseButton::Other(_) => {}
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Focused(false),
+                ..
+            } => {
+                self.rotating = false;
+                self.panning = false;
+            }
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    // Roughly the height of a line of text.
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                self.distance = (self.distance * (1.0 - self.zoom_sensitivity).powf(lines))
+                    .clamp(self.distance_range.0, self.distance_range.1);
+            }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta: (x, y) },
+                ..
+            } => {
+                let mouse_delta = Vec2::new(x as f32, y as f32);
+                if self.rotating {
+                    let delta = mouse_delta * self.rotate_sensitivity;
+                    self.yaw = (self.yaw + delta.x).rem_euclid(std::f32::consts::TAU);
+                    self.pitch = (self.pitch + delta.y).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+                } else if self.panning {
+                    let rotation = rotation(self.handedness, self.yaw, self.pitch);
+                    let right = rotation * Vec3::X;
+                    let up = rotation * Vec3::Y;
+                    let scale = self.distance * self.pan_sensitivity;
+                    // Drag the scene with the cursor.
+                    self.target += (-right * mouse_delta.x + up * mouse_delta.y) * scale;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sends the camera to the renderer.
+    pub fn update(&self, renderer: &Renderer) {
+        renderer.set_camera_data(self.camera());
+    }
+
+    /// Position of the camera itself.
+    pub fn position(&self) -> Vec3 {
+        self.target - rotation(self.handedness, self.yaw, self.pitch) * local_forward(self.handedness) * self.distance
+    }
+
+    pub fn camera(&self) -> Camera {
+        Camera {
+            projection: self.projection,
+            view: Mat4::from_rotation_translation(rotation(self.handedness, self.yaw, self.pitch), self.position())
+                .inverse(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Vec3, Vec4Swizzles};
+    use rend3::types::Handedness;
+    use winit::event::{DeviceEvent, DeviceId, VirtualKeyCode};
+
+    use super::{local_forward, rotation, FlyCameraController, OrbitCameraController};
+    use crate::Event;
+
+    const HANDEDNESSES: [Handedness; 2] = [Handedness::Left, Handedness::Right];
+
+    fn forward(handedness: Handedness, yaw: f32, pitch: f32) -> Vec3 {
+        rotation(handedness, yaw, pitch) * local_forward(handedness)
+    }
+
+    fn mouse_motion(x: f64, y: f64) -> Event<'static, ()> {
+        Event::DeviceEvent {
+            // SAFETY: The id is only compared against, never passed to the platform.
+            device_id: unsafe { DeviceId::dummy() },
+            event: DeviceEvent::MouseMotion { delta: (x, y) },
+        }
+    }
+
+    #[test]
+    fn yaw_turns_right_and_pitch_looks_down() {
+        for handedness in HANDEDNESSES {
+            // +X is to the right of the initial view in both handednesses.
+            assert_eq!(rotation(handedness, 0.0, 0.0) * Vec3::X, Vec3::X);
+
+            let turned = forward(handedness, 0.1, 0.0);
+            assert!(turned.x > 0.0, "{:?}: {}", handedness, turned);
+            let down = forward(handedness, 0.0, 0.1);
+            assert!(down.y < 0.0, "{:?}: {}", handedness, down);
+        }
+    }
+
+    #[test]
+    fn mouse_motion_rotates_like_the_cursor() {
+        for handedness in HANDEDNESSES {
+            let mut controller = FlyCameraController::new(handedness, Vec3::ZERO);
+            // Right and down on screen.
+            controller.handle_event(&mouse_motion(100.0, 100.0));
+            assert!(controller.yaw > 0.0 && controller.pitch > 0.0);
+
+            let forward = forward(handedness, controller.yaw, controller.pitch);
+            assert!(forward.x > 0.0 && forward.y < 0.0, "{:?}: {}", handedness, forward);
+        }
+    }
+
+    #[test]
+    fn fly_camera_moves_along_its_view() {
+        for handedness in HANDEDNESSES {
+            let mut controller = FlyCameraController::new(handedness, Vec3::ZERO);
+            let step = |controller: &mut FlyCameraController, keys: &[VirtualKeyCode]| {
+                controller.position = Vec3::ZERO;
+                controller.pressed = keys.iter().copied().collect();
+                controller.move_with_held_keys(0.5);
+                controller.position
+            };
+
+            let walk = controller.walk_speed * 0.5;
+            let forward = local_forward(handedness) * walk;
+            assert!(step(&mut controller, &[VirtualKeyCode::W]).abs_diff_eq(forward, 1e-5));
+            assert!(step(&mut controller, &[VirtualKeyCode::S]).abs_diff_eq(-forward, 1e-5));
+            assert!(step(&mut controller, &[VirtualKeyCode::D]).abs_diff_eq(Vec3::X * walk, 1e-5));
+            assert!(step(&mut controller, &[VirtualKeyCode::Q]).abs_diff_eq(Vec3::Y * walk, 1e-5));
+            assert_eq!(
+                step(&mut controller, &[VirtualKeyCode::W, VirtualKeyCode::S]),
+                Vec3::ZERO
+            );
+            let run = step(&mut controller, &[VirtualKeyCode::A, VirtualKeyCode::LShift]);
+            assert!(run.abs_diff_eq(-Vec3::X * controller.run_speed * 0.5, 1e-5));
+            // Diagonal movement isn't faster.
+            let diagonal = step(&mut controller, &[VirtualKeyCode::W, VirtualKeyCode::D]);
+            assert!((diagonal.length() - walk).abs() < 1e-5);
+
+            // Turned right by 90 degrees, forward is +X.
+            controller.yaw = std::f32::consts::FRAC_PI_2;
+            let turned = step(&mut controller, &[VirtualKeyCode::W]);
+            assert!(turned.abs_diff_eq(Vec3::X * walk, 1e-5), "{:?}: {}", handedness, turned);
+        }
+    }
+
+    #[test]
+    fn cameras_look_forward_in_view_space() {
+        for handedness in HANDEDNESSES {
+            let mut controller = FlyCameraController::new(handedness, Vec3::new(1.0, 2.0, 3.0));
+            controller.yaw = 0.7;
+            controller.pitch = -0.3;
+            let ahead = controller.position + forward(handedness, controller.yaw, controller.pitch);
+            let view = controller.camera().view * ahead.extend(1.0);
+            assert!(
+                view.xyz().abs_diff_eq(local_forward(handedness), 1e-5),
+                "{:?}: {}",
+                handedness,
+                view
+            );
+        }
+    }
+
+    #[test]
+    fn orbit_camera_looks_at_its_target() {
+        for handedness in HANDEDNESSES {
+            let target = Vec3::new(1.0, 0.0, -2.0);
+            let mut controller = OrbitCameraController::new(handedness, target, 5.0);
+            // Looking down at the target from above.
+            assert!(controller.position().y > target.y);
+            assert!((controller.position().distance(target) - 5.0).abs() < 1e-5);
+
+            // Turning right moves the camera to the left of the target.
+            controller.pitch = 0.0;
+            controller.yaw = 0.1;
+            assert!(controller.position().x < target.x, "{:?}", handedness);
+
+            let view = controller.camera().view * target.extend(1.0);
+            assert!(
+                view.xyz().abs_diff_eq(local_forward(handedness) * 5.0, 1e-4),
+                "{:?}: {}",
+                handedness,
+                view
+            );
+        }
+    }
+}
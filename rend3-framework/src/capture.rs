@@ -0,0 +1,329 @@
+use std::{num::NonZeroU32, sync::Arc};
+
+use glam::UVec2;
+use rend3::{
+    types::{Surface, TextureFormat},
+    util::{color_space::encode_srgb, output::OutputFrame, quantize::f32_from_f16},
+    Renderer,
+};
+use thiserror::Error;
+use wgpu::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Device, Extent3d, ImageCopyBuffer, ImageCopyTexture,
+    ImageDataLayout, Maintain, MapMode, Origin3d, Queue, Texture, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureUsages, TextureViewDescriptor,
+};
+
+#[derive(Debug, Clone, Error)]
+pub enum CaptureError {
+    #[error("Capturing frames with format {0:?} is unsupported")]
+    UnsupportedFormat(TextureFormat),
+    #[error("Failed to encode frame as png: {0}")]
+    Encoding(String),
+}
+
+type CaptureCallback = Box<dyn FnOnce(Result<Vec<u8>, CaptureError>)>;
+
+struct CaptureTarget {
+    texture: Texture,
+    resolution: UVec2,
+    format: TextureFormat,
+}
+
+/// Captures rendered frames as png images.
+///
+/// Request a capture with [`FrameCapture::capture_next_frame`], e.g. when a
+/// key is pressed. Then, when rendering, get the frame to render to from
+/// [`FrameCapture::output_frame`] instead of using the surface directly, and
+/// call [`FrameCapture::finish`] after executing the rendergraph.
+///
+/// While capturing, the frame is rendered into a texture instead of the
+/// surface, so the window keeps showing the previous frame for one more frame.
+///
+/// Captures can be taken at a multiple of the window resolution with
+/// [`FrameCapture::capture_next_frame_scaled`], e.g. a 4K screenshot from a
+/// 1080p window. Build the rendergraph of that frame at
+/// [`FrameCapture::resolution`] for it to fill the capture.
+#[derive(Default)]
+pub struct FrameCapture {
+    callbacks: Vec<CaptureCallback>,
+    target: Option<CaptureTarget>,
+    /// Largest scale requested for the next frame, 0 if none was.
+    scale: u32,
+}
+
+impl FrameCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Captures the next frame, and calls `callback` with the encoded png.
+    pub fn capture_next_frame(&mut self, callback: impl FnOnce(Result<Vec<u8>, CaptureError>) + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Captures the next frame at `scale` times the resolution of the window
+    /// on each axis, and calls `callback` with the encoded png. If several
+    /// captures of the frame are requested, all of them get the largest
+    /// scale.
+    pub fn capture_next_frame_scaled(
+        &mut self,
+        scale: u32,
+        callback: impl FnOnce(Result<Vec<u8>, CaptureError>) + 'static,
+    ) {
+        self.scale = self.scale.max(scale);
+        self.capture_next_frame(callback);
+    }
+
+    /// Captures the next frame, and writes it to the png file at `path`.
+    /// Errors are logged.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn capture_next_frame_to_file(&mut self, path: impl Into<std::path::PathBuf>) {
+        let path = path.into();
+        self.capture_next_frame(move |png| match png {
+            Ok(png) => match std::fs::write(&path, png) {
+                Ok(()) => log::info!("Saved frame capture to {}", path.display()),
+                Err(e) => log::error!("Could not write frame capture to {}: {}", path.display(), e),
+            },
+            Err(e) => log::error!("Could not capture frame: {}", e),
+        });
+    }
+
+    /// True if the next frame will be captured.
+    pub fn is_pending(&self) -> bool {
+        !self.callbacks.is_empty()
+    }
+
+    /// Resolution to render the next frame at, given the resolution of the
+    /// window. This is larger than the window when a scaled capture is
+    /// pending, as far as the texture size limit of the device allows.
+    pub fn resolution(&self, renderer: &Renderer, resolution: UVec2) -> UVec2 {
+        if !self.is_pending() || self.scale <= 1 {
+            return resolution;
+        }
+        let limit = renderer.limits.max_texture_dimension_2d;
+        let largest = resolution.max_element().max(1);
+        resolution * self.scale.min(limit / largest).max(1)
+    }
+
+    /// Returns the frame to render to. This is the surface, unless a capture
+    /// is pending. `resolution` is that of the window; the capture is made
+    /// at [`FrameCapture::resolution`].
+    pub fn output_frame(
+        &mut self,
+        renderer: &Renderer,
+        surface: &Arc<Surface>,
+        resolution: UVec2,
+        format: TextureFormat,
+    ) -> OutputFrame {
+        if !self.is_pending() {
+            return OutputFrame::Surface {
+                surface: Arc::clone(surface),
+            };
+        }
+
+        let resolution = self.resolution(renderer, resolution);
+        let texture = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("frame capture"),
+            size: extent(resolution),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        self.target = Some(CaptureTarget {
+            texture,
+            resolution,
+            format,
+        });
+
+        OutputFrame::View(Arc::new(view))
+    }
+
+    /// Reads back the frame rendered into the texture given out by
+    /// [`FrameCapture::output_frame`], and hands it to the callbacks. Does
+    /// nothing if the frame wasn't captured.
+    pub fn finish(&mut self, renderer: &Renderer) {
+        let target = match self.target.take() {
+            Some(target) => target,
+            None => return,
+        };
+        let callbacks = std::mem::take(&mut self.callbacks);
+        self.scale = 0;
+        let device = Arc::clone(&renderer.device);
+        let queue = Arc::clone(&renderer.queue);
+
+        let capture = async move {
+            let pixels = read_texture(&device, &queue, &target.texture, target.resolution, target.format).await;
+            let png = encode_png(&pixels, target.resolution, target.format);
+            for callback in callbacks {
+                callback(png.clone());
+            }
+        };
+
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(capture);
+        #[cfg(not(target_arch = "wasm32"))]
+        pollster::block_on(capture);
+    }
+}
+
+pub(crate) fn extent(resolution: UVec2) -> Extent3d {
+    Extent3d {
+        width: resolution.x,
+        height: resolution.y,
+        depth_or_array_layers: 1,
+    }
+}
+
+/// Copies the first mip of `texture` back to the CPU, as tightly packed rows
+/// of pixels from top to bottom.
+pub(crate) async fn read_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    resolution: UVec2,
+    format: TextureFormat,
+) -> Vec<u8> {
+    let bytes_per_pixel = format.describe().block_size as u32;
+    let unpadded_bytes_per_row = resolution.x * bytes_per_pixel;
+    // Copies from textures need rows aligned to COPY_BYTES_PER_ROW_ALIGNMENT.
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("texture readback"),
+        size: (padded_bytes_per_row * resolution.y) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("texture readback"),
+    });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        extent(resolution),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let mapping = slice.map_async(MapMode::Read);
+    device.poll(Maintain::Wait);
+    mapping.await.expect("Failed to map texture readback buffer");
+
+    let padded = slice.get_mapped_range();
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * resolution.y) as usize);
+    for row in padded.chunks(padded_bytes_per_row as usize) {
+        pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+    }
+    drop(padded);
+    buffer.unmap();
+
+    pixels
+}
+
+/// Converts pixels read back from a texture of the given format to 8 bit RGBA.
+///
+/// `Rgba16Float` pixels are linear scRGB, as written by the tonemapping
+/// routine for HDR surfaces; they're clamped to the SDR range and srgb
+/// encoded.
+pub(crate) fn to_rgba8(pixels: &[u8], format: TextureFormat) -> Result<Vec<u8>, CaptureError> {
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => Ok(pixels.to_vec()),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => Ok(pixels
+            .chunks_exact(4)
+            .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+            .collect()),
+        TextureFormat::Rgb10a2Unorm => Ok(pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let packed = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                // Keep the top 8 bits of each 10 bit channel, and scale the 2 bit alpha up.
+                [
+                    (packed >> 2) as u8,
+                    (packed >> 12) as u8,
+                    (packed >> 22) as u8,
+                    ((packed >> 30) * 85) as u8,
+                ]
+            })
+            .collect()),
+        TextureFormat::Rgba16Float => Ok(pixels
+            .chunks_exact(8)
+            .flat_map(|pixel| {
+                let channel = |i: usize| f32_from_f16(u16::from_le_bytes([pixel[2 * i], pixel[2 * i + 1]]));
+                let unorm8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                [
+                    unorm8(encode_srgb(channel(0).clamp(0.0, 1.0))),
+                    unorm8(encode_srgb(channel(1).clamp(0.0, 1.0))),
+                    unorm8(encode_srgb(channel(2).clamp(0.0, 1.0))),
+                    unorm8(channel(3)),
+                ]
+            })
+            .collect()),
+        _ => Err(CaptureError::UnsupportedFormat(format)),
+    }
+}
+
+/// Encodes pixels read back from a texture of the given format as a png.
+pub(crate) fn encode_png(pixels: &[u8], resolution: UVec2, format: TextureFormat) -> Result<Vec<u8>, CaptureError> {
+    let rgba = to_rgba8(pixels, format)?;
+
+    let mut png = Vec::new();
+    image::codecs::png::PngEncoder::new(&mut png)
+        .encode(&rgba, resolution.x, resolution.y, image::ColorType::Rgba8)
+        .map_err(|e| CaptureError::Encoding(e.to_string()))?;
+
+    Ok(png)
+}
+
+#[cfg(test)]
+mod test {
+    use rend3::{types::TextureFormat, util::quantize::f16_from_f32};
+
+    use super::{to_rgba8, CaptureError};
+
+    fn rgba16f(pixels: &[[f32; 4]]) -> Vec<u8> {
+        pixels
+            .iter()
+            .flatten()
+            .flat_map(|&value| f16_from_f32(value).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn hdr_pixels_clamp_to_srgb() {
+        let pixels = rgba16f(&[
+            [0.0, 1.0, 0.214, 1.0],
+            // Brighter than SDR white, negative, and half transparent.
+            [4.0, -0.5, f32::NAN, 0.5],
+        ]);
+        let rgba = to_rgba8(&pixels, TextureFormat::Rgba16Float).unwrap();
+
+        // Linear 0.214 is about half way up in srgb.
+        assert_eq!(&rgba[..4], &[0, 255, 127, 255]);
+        assert_eq!(&rgba[4..], &[255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn unsupported_formats_fail() {
+        assert!(matches!(
+            to_rgba8(&[0; 16], TextureFormat::Rgba32Float),
+            Err(CaptureError::UnsupportedFormat(TextureFormat::Rgba32Float))
+        ));
+    }
+}
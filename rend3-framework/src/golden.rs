@@ -0,0 +1,300 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use glam::UVec2;
+use rend3::ExtendedAdapterInfo;
+use thiserror::Error;
+
+use crate::{start_headless, CaptureError, HeadlessApp, HeadlessFrame, HeadlessSettings};
+
+/// Environment variable which, when set to `1`, makes [`GoldenTest`] write
+/// the rendered images as the new baselines instead of comparing them.
+pub const UPDATE_GOLDEN_VAR: &str = "REND3_UPDATE_GOLDEN";
+
+#[derive(Debug, Error)]
+pub enum GoldenError {
+    #[error("Failed to render the frame")]
+    Render(#[source] anyhow::Error),
+    #[error("Failed to read back the frame")]
+    Capture(#[from] CaptureError),
+    #[error("Failed to read or write {path}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to decode or encode {path}")]
+    Image {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+    #[error("No baseline at {0}, run with {UPDATE_GOLDEN_VAR}=1 to create it")]
+    MissingBaseline(PathBuf),
+    #[error("Rendered {actual} frame, but the baseline {path} is {expected}")]
+    SizeMismatch {
+        path: PathBuf,
+        expected: UVec2,
+        actual: UVec2,
+    },
+    #[error("{0}")]
+    Mismatch(GoldenReport),
+}
+
+/// Result of comparing a frame against its baseline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoldenReport {
+    pub baseline: PathBuf,
+    /// Pixels whose difference is above [`GoldenTest::threshold`].
+    pub differing_pixels: usize,
+    pub total_pixels: usize,
+    /// Largest perceptual difference of a pixel, from 0 to 1.
+    pub max_difference: f32,
+    /// Where the rendered frame and an image highlighting the differing
+    /// pixels were written, if the comparison failed.
+    pub failure_images: Option<(PathBuf, PathBuf)>,
+    /// True if the baseline was written instead of compared against.
+    pub updated: bool,
+}
+
+impl GoldenReport {
+    pub fn differing_fraction(&self) -> f32 {
+        self.differing_pixels as f32 / self.total_pixels.max(1) as f32
+    }
+}
+
+impl fmt::Display for GoldenReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} pixels ({:.3}%) differ from {}, by up to {:.3}",
+            self.differing_pixels,
+            self.total_pixels,
+            self.differing_fraction() * 100.0,
+            self.baseline.display(),
+            self.max_difference,
+        )?;
+        if let Some((ref actual, ref diff)) = self.failure_images {
+            write!(f, ". See {} and {}", actual.display(), diff.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares headlessly rendered frames against reference images, to catch
+/// rendering regressions.
+///
+/// Baselines are pngs named after the test in [`GoldenTest::directory`].
+/// As GPUs and drivers rasterize and filter slightly differently, a baseline
+/// can be overridden for a platform by one named
+/// `<name>.<platform>.png`, see [`GoldenTest::platform`]. Run with
+/// [`UPDATE_GOLDEN_VAR`] set to `1` to write the baselines.
+///
+/// ```no_run
+/// # fn run(scene: impl rend3_framework::HeadlessApp) {
+/// let golden = rend3_framework::GoldenTest::new("tests/golden");
+/// golden.check("cube", scene, Default::default()).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct GoldenTest {
+    pub directory: PathBuf,
+    /// Perceptual difference, from 0 to 1, above which a pixel differs.
+    pub threshold: f32,
+    /// Fraction of pixels which may differ before the comparison fails.
+    pub max_differing_fraction: f32,
+    /// Write new baselines for the current platform only, instead of for all
+    /// platforms without a baseline of their own.
+    pub per_platform: bool,
+    /// Write the baselines instead of comparing against them. Set from
+    /// [`UPDATE_GOLDEN_VAR`] by [`GoldenTest::new`].
+    pub update: bool,
+}
+
+impl GoldenTest {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            threshold: 0.1,
+            max_differing_fraction: 0.001,
+            per_platform: false,
+            update: std::env::var(UPDATE_GOLDEN_VAR).map_or(false, |value| value == "1"),
+        }
+    }
+
+    /// Name of the platform a frame was rendered on, used to pick its
+    /// baseline, e.g. `linux-vulkan-amd`.
+    pub fn platform(adapter: &ExtendedAdapterInfo) -> String {
+        format!("{}-{:?}-{:?}", std::env::consts::OS, adapter.backend, adapter.vendor).to_lowercase()
+    }
+
+    /// Renders `app` headlessly and compares the last frame against the
+    /// baseline called `name`.
+    pub fn check<A: HeadlessApp>(
+        &self,
+        name: &str,
+        app: A,
+        settings: HeadlessSettings,
+    ) -> Result<GoldenReport, GoldenError> {
+        let frames = start_headless(app, settings).map_err(GoldenError::Render)?;
+        let frame = frames
+            .last()
+            .ok_or_else(|| GoldenError::Render(anyhow::anyhow!("No frames were rendered")))?;
+        self.check_frame(name, frame)
+    }
+
+    /// Compares an already rendered frame against the baseline called `name`.
+    pub fn check_frame(&self, name: &str, frame: &HeadlessFrame) -> Result<GoldenReport, GoldenError> {
+        profiling::scope!("GoldenTest::check_frame");
+
+        let pixels = pollster::block_on(frame.read_rgba8())?;
+        let platform = Self::platform(&frame.adapter_info);
+        let platform_path = self.directory.join(format!("{}.{}.png", name, platform));
+        let generic_path = self.directory.join(format!("{}.png", name));
+        let baseline = match platform_path.exists() || self.per_platform {
+            true => platform_path,
+            false => generic_path,
+        };
+
+        let total_pixels = (frame.resolution.x * frame.resolution.y) as usize;
+        if self.update {
+            std::fs::create_dir_all(&self.directory).map_err(|source| GoldenError::Io {
+                path: self.directory.clone(),
+                source,
+            })?;
+            write_png(&baseline, &pixels, frame.resolution)?;
+            return Ok(GoldenReport {
+                baseline,
+                differing_pixels: 0,
+                total_pixels,
+                max_difference: 0.0,
+                failure_images: None,
+                updated: true,
+            });
+        }
+
+        if !baseline.exists() {
+            return Err(GoldenError::MissingBaseline(baseline));
+        }
+        let expected = image::open(&baseline)
+            .map_err(|source| GoldenError::Image {
+                path: baseline.clone(),
+                source,
+            })?
+            .to_rgba8();
+        let expected_size = UVec2::new(expected.width(), expected.height());
+        if expected_size != frame.resolution {
+            return Err(GoldenError::SizeMismatch {
+                path: baseline,
+                expected: expected_size,
+                actual: frame.resolution,
+            });
+        }
+
+        let comparison = compare_rgba8(expected.as_raw(), &pixels, self.threshold);
+        let mut report = GoldenReport {
+            baseline,
+            differing_pixels: comparison.differing_pixels,
+            total_pixels,
+            max_difference: comparison.max_difference,
+            failure_images: None,
+            updated: false,
+        };
+        if report.differing_fraction() <= self.max_differing_fraction {
+            return Ok(report);
+        }
+
+        let actual_path = self.directory.join(format!("{}.{}.actual.png", name, platform));
+        let diff_path = self.directory.join(format!("{}.{}.diff.png", name, platform));
+        write_png(&actual_path, &pixels, frame.resolution)?;
+        write_png(&diff_path, &comparison.diff, frame.resolution)?;
+        report.failure_images = Some((actual_path, diff_path));
+        Err(GoldenError::Mismatch(report))
+    }
+}
+
+fn write_png(path: &Path, pixels: &[u8], resolution: UVec2) -> Result<(), GoldenError> {
+    image::save_buffer(path, pixels, resolution.x, resolution.y, image::ColorType::Rgba8).map_err(|source| {
+        GoldenError::Image {
+            path: path.to_owned(),
+            source,
+        }
+    })
+}
+
+struct Comparison {
+    differing_pixels: usize,
+    max_difference: f32,
+    /// The expected image faded out, with differing pixels in red.
+    diff: Vec<u8>,
+}
+
+/// Compares two srgb RGBA8 images of the same size pixel by pixel.
+fn compare_rgba8(expected: &[u8], actual: &[u8], threshold: f32) -> Comparison {
+    let mut differing_pixels = 0;
+    let mut max_difference = 0.0_f32;
+    let mut diff = Vec::with_capacity(expected.len());
+    for (expected, actual) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let difference = pixel_difference(expected, actual);
+        max_difference = max_difference.max(difference);
+        if difference > threshold {
+            differing_pixels += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let [y, _, _] = yiq(expected);
+            let faded = (255.0 - (255.0 - y) * 0.1) as u8;
+            diff.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+    Comparison {
+        differing_pixels,
+        max_difference,
+        diff,
+    }
+}
+
+/// Pixel composited over white, in YIQ, which separates brightness from
+/// color the way the eye does.
+fn yiq(pixel: &[u8]) -> [f32; 3] {
+    let alpha = pixel[3] as f32 / 255.0;
+    let [r, g, b] = [0, 1, 2].map(|channel| 255.0 + (pixel[channel] as f32 - 255.0) * alpha);
+    [
+        r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_2,
+        r * 0.595_977_9 - g * 0.274_176_3 - b * 0.321_801_6,
+        r * 0.211_470_2 - g * 0.522_617_4 + b * 0.311_147_2,
+    ]
+}
+
+/// Perceptual difference of two pixels from 0 to 1, weighing brightness
+/// over color as in "Measuring perceived color difference using YIQ NTSC
+/// transmission color space in mobile applications" by Kotsarenko and
+/// Ramos.
+fn pixel_difference(expected: &[u8], actual: &[u8]) -> f32 {
+    // Largest possible value of the weighted sum, between black and white.
+    const MAX_DELTA: f32 = 35215.0;
+
+    let [y0, i0, q0] = yiq(expected);
+    let [y1, i1, q1] = yiq(actual);
+    let (y, i, q) = (y0 - y1, i0 - i1, q0 - q1);
+    ((0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / MAX_DELTA).sqrt()
+}
+
+#[cfg(test)]
+mod test {
+    use super::compare_rgba8;
+
+    #[test]
+    fn perceptual_tolerance() {
+        let expected = [[200, 100, 50, 255], [0, 0, 0, 255], [255, 255, 255, 255]].concat();
+        let actual = [[201, 99, 52, 255], [255, 255, 255, 255], [255, 255, 255, 0]].concat();
+        let comparison = compare_rgba8(&expected, &actual, 0.1);
+
+        // Off by a few values passes, black to white fails by nearly the
+        // largest possible difference, and transparent is composited over white.
+        assert_eq!(comparison.differing_pixels, 1);
+        assert!(comparison.max_difference > 0.9);
+        assert_eq!(&comparison.diff[4..8], &[255, 0, 0, 255]);
+    }
+}
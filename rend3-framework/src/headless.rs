@@ -0,0 +1,286 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use glam::UVec2;
+use rend3::{
+    managers::{SkinningMode, VertexAttributes, VertexQuantization},
+    types::{DepthMode, Handedness, SampleCount, TextureFormat},
+    util::output::OutputFrame,
+    ExtendedAdapterInfo, IadSettings, InstanceAdapterDevice, Renderer, RendererSettings,
+};
+use rend3_routine::{
+    base::BaseRenderGraph,
+    tonemapping::{TonemappingAlpha, TonemappingOutput},
+};
+use wgpu::{
+    Device, Maintain, Queue, Texture, TextureDescriptor, TextureDimension, TextureUsages, TextureViewDescriptor,
+};
+
+use crate::{
+    capture::{encode_png, extent, read_texture, to_rgba8},
+    timing::FrameTimer,
+    CaptureError, DefaultRoutines, FrameTiming,
+};
+
+/// An application rendering into textures instead of a window. Run it with
+/// [`start_headless`] or [`async_start_headless`].
+pub trait HeadlessApp {
+    /// The handedness of the coordinate system of the renderer.
+    const HANDEDNESS: Handedness;
+
+    /// Choices used by the default [`create_iad`](Self::create_iad) to pick
+    /// the adapter, e.g. to force a discrete GPU or the GL backend.
+    fn iad_settings(&self) -> IadSettings {
+        IadSettings::default()
+    }
+
+    fn create_iad<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = anyhow::Result<InstanceAdapterDevice>> + 'a>> {
+        Box::pin(async move { Ok(rend3::create_iad_with_settings(&self.iad_settings()).await?) })
+    }
+
+    fn create_base_rendergraph(&mut self, renderer: &Renderer) -> BaseRenderGraph {
+        BaseRenderGraph::new(renderer)
+    }
+
+    fn sample_count(&self) -> SampleCount;
+
+    /// Layout of depth in the depth buffers of the renderer.
+    fn depth_mode(&self) -> DepthMode {
+        DepthMode::default()
+    }
+
+    /// Vertex attributes the renderer stores.
+    fn vertex_attributes(&self) -> VertexAttributes {
+        VertexAttributes::all()
+    }
+
+    /// Vertex attributes the renderer stores quantized.
+    fn vertex_quantization(&self) -> VertexQuantization {
+        VertexQuantization::empty()
+    }
+
+    /// Where the renderer deforms skinned meshes.
+    fn skinning_mode(&self) -> SkinningMode {
+        SkinningMode::default()
+    }
+
+    /// Called once the renderer is created. The returned future is awaited
+    /// before the first frame is rendered.
+    fn setup<'a>(
+        &'a mut self,
+        renderer: &'a Arc<Renderer>,
+        routines: &'a Arc<DefaultRoutines>,
+        output_format: TextureFormat,
+    ) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        let _ = (renderer, routines, output_format);
+        Box::pin(async {})
+    }
+
+    /// Advances the simulation before a frame is rendered. In movie mode
+    /// frames are exactly [`MovieSettings::frame_time`] apart, otherwise
+    /// `timing` follows the wall clock.
+    fn update(&mut self, renderer: &Arc<Renderer>, routines: &Arc<DefaultRoutines>, timing: &FrameTiming) {
+        let _ = (renderer, routines, timing);
+    }
+
+    /// In movie mode, awaited before each frame is rendered, for work the
+    /// frame must wait for, like assets still being loaded.
+    fn pending_work<'a>(&'a mut self, renderer: &'a Arc<Renderer>) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        let _ = renderer;
+        Box::pin(async {})
+    }
+
+    /// Renders frame number `frame_index` into `frame`.
+    fn render(
+        &mut self,
+        renderer: &Arc<Renderer>,
+        routines: &Arc<DefaultRoutines>,
+        base_rendergraph: &BaseRenderGraph,
+        frame: OutputFrame,
+        resolution: UVec2,
+        frame_index: usize,
+    );
+}
+
+/// Describes the frames a [`HeadlessApp`] renders.
+#[derive(Debug, Clone)]
+pub struct HeadlessSettings {
+    pub resolution: UVec2,
+    pub format: TextureFormat,
+    /// Encoding of the default tonemapping routine, e.g. PQ with an
+    /// `Rgb10a2Unorm` format for HDR10 video.
+    pub tonemapping_output: TonemappingOutput,
+    /// Alpha of the default tonemapping routine, e.g. premultiplied to
+    /// render overlays composited later.
+    pub tonemapping_alpha: TonemappingAlpha,
+    /// Number of frames to render.
+    pub frame_count: usize,
+    /// Renders the frames as a reproducible sequence instead of as fast as
+    /// possible, see [`MovieSettings`].
+    pub movie: Option<MovieSettings>,
+}
+
+/// Offline rendering of a sequence, e.g. to encode into a video.
+///
+/// Every frame is [`frame_time`](Self::frame_time) after the previous one
+/// no matter how long it takes to render, and starts once the app's
+/// [`pending_work`](HeadlessApp::pending_work) and everything the gpu was
+/// doing are done, so uploads and readbacks of earlier frames have landed.
+/// The randomness of rend3's routines, like the seeds of particles, follows
+/// frame counters rather than time, so rendering the same updates again
+/// gives the same frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovieSettings {
+    /// Time between frames, in seconds.
+    pub frame_time: f32,
+}
+
+impl MovieSettings {
+    pub fn from_frame_rate(frames_per_second: f32) -> Self {
+        Self {
+            frame_time: 1.0 / frames_per_second,
+        }
+    }
+}
+
+impl Default for HeadlessSettings {
+    fn default() -> Self {
+        Self {
+            resolution: UVec2::new(1024, 1024),
+            format: TextureFormat::Rgba8UnormSrgb,
+            tonemapping_output: TonemappingOutput::Sdr,
+            tonemapping_alpha: TonemappingAlpha::Passthrough,
+            frame_count: 1,
+            movie: None,
+        }
+    }
+}
+
+/// A frame rendered by a [`HeadlessApp`].
+pub struct HeadlessFrame {
+    pub texture: Texture,
+    pub resolution: UVec2,
+    pub format: TextureFormat,
+    /// Adapter the frame was rendered with.
+    pub adapter_info: ExtendedAdapterInfo,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+}
+
+impl HeadlessFrame {
+    /// Copies the contents of the frame back to the CPU, as tightly packed
+    /// rows of pixels from top to bottom.
+    pub async fn read_pixels(&self) -> Vec<u8> {
+        read_texture(&self.device, &self.queue, &self.texture, self.resolution, self.format).await
+    }
+
+    /// Copies the contents of the frame back to the CPU, converted to 8 bit
+    /// RGBA.
+    pub async fn read_rgba8(&self) -> Result<Vec<u8>, CaptureError> {
+        to_rgba8(&self.read_pixels().await, self.format)
+    }
+
+    /// Copies the contents of the frame back to the CPU, encoded as a png.
+    pub async fn encode_png(&self) -> Result<Vec<u8>, CaptureError> {
+        encode_png(&self.read_pixels().await, self.resolution, self.format)
+    }
+}
+
+/// Sets up the renderer without any window, then renders
+/// [`HeadlessSettings::frame_count`] frames of `app`, each into its own
+/// texture.
+pub async fn async_start_headless<A: HeadlessApp>(
+    mut app: A,
+    settings: HeadlessSettings,
+) -> anyhow::Result<Vec<HeadlessFrame>> {
+    let iad = app.create_iad().await?;
+
+    let renderer = Renderer::new_with_settings(
+        iad,
+        A::HANDEDNESS,
+        RendererSettings {
+            depth_mode: app.depth_mode(),
+            vertex_attributes: app.vertex_attributes(),
+            vertex_quantization: app.vertex_quantization(),
+            skinning_mode: app.skinning_mode(),
+        },
+        Some(settings.resolution.x as f32 / settings.resolution.y as f32),
+    )?;
+
+    let max_size = renderer.limits.max_texture_dimension_2d;
+    if settings.resolution.cmpgt(UVec2::splat(max_size)).any() {
+        anyhow::bail!(
+            "Resolution {}x{} exceeds the {} pixels the adapter supports",
+            settings.resolution.x,
+            settings.resolution.y,
+            max_size
+        );
+    }
+
+    let base_rendergraph = app.create_base_rendergraph(&renderer);
+    let routines = Arc::new(DefaultRoutines::new(
+        &renderer,
+        &base_rendergraph,
+        settings.format,
+        settings.tonemapping_output,
+        settings.tonemapping_alpha,
+    ));
+
+    app.setup(&renderer, &routines, settings.format).await;
+
+    let mut frame_timer = FrameTimer::new();
+    let mut frames = Vec::with_capacity(settings.frame_count);
+    for frame_index in 0..settings.frame_count {
+        let timing = match settings.movie {
+            Some(movie) => {
+                app.pending_work(&renderer).await;
+                renderer.device.poll(Maintain::Wait);
+                FrameTiming::fixed(movie.frame_time, frame_index as u64)
+            }
+            None => frame_timer.tick(None).0,
+        };
+        app.update(&renderer, &routines, &timing);
+
+        profiling::scope!("headless frame");
+
+        let texture = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("headless frame"),
+            size: extent(settings.resolution),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: settings.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC | TextureUsages::TEXTURE_BINDING,
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        app.render(
+            &renderer,
+            &routines,
+            &base_rendergraph,
+            OutputFrame::View(Arc::new(view)),
+            settings.resolution,
+            frame_index,
+        );
+
+        frames.push(HeadlessFrame {
+            texture,
+            resolution: settings.resolution,
+            format: settings.format,
+            adapter_info: renderer.adapter_info.clone(),
+            device: Arc::clone(&renderer.device),
+            queue: Arc::clone(&renderer.queue),
+        });
+    }
+
+    if settings.movie.is_some() {
+        renderer.device.poll(Maintain::Wait);
+    }
+
+    Ok(frames)
+}
+
+/// Blocking version of [`async_start_headless`].
+#[cfg(not(target_arch = "wasm32"))]
+pub fn start_headless<A: HeadlessApp>(app: A, settings: HeadlessSettings) -> anyhow::Result<Vec<HeadlessFrame>> {
+    pollster::block_on(async_start_headless(app, settings))
+}
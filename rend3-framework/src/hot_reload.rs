@@ -0,0 +1,67 @@
+use std::{path::PathBuf, time::Duration};
+
+use instant::Instant;
+use rend3_routine::hot_reload::ShaderWatcher;
+use thiserror::Error;
+
+/// How often watched shaders are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Shaders to reload while the app is running, returned from
+/// [`App::shader_hot_reload`](crate::App::shader_hot_reload).
+#[derive(Debug, Clone, Default)]
+pub struct ShaderHotReload {
+    /// Directory to load the shaders of the default routines from, laid out
+    /// like `rend3-routine/shaders`. See
+    /// [`set_shader_directory`](rend3_routine::shaders::set_shader_directory).
+    pub routine_shaders: Option<PathBuf>,
+    /// Further files or directories to watch, like the shaders of the app's
+    /// own materials.
+    pub watch: Vec<PathBuf>,
+}
+
+/// Building pipelines from changed shaders failed.
+#[derive(Debug, Error)]
+#[error("Failed to build pipelines from the changed shaders: {0}")]
+pub struct ShaderError(#[from] pub wgpu::Error);
+
+/// Runs `create`, which builds shader modules and pipelines, and returns its
+/// result only if wgpu reported no validation errors while it ran.
+///
+/// Errors in the shaders then don't bring down the app, the pipelines built
+/// from them are dropped and the old ones can be kept. Shaders loaded as
+/// SPIRV passthrough by the GpuDriven profile are not validated.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn catch_shader_errors<T>(device: &wgpu::Device, create: impl FnOnce() -> T) -> Result<T, ShaderError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = create();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => Err(ShaderError(error)),
+        None => Ok(value),
+    }
+}
+
+pub(crate) struct ShaderReloader {
+    watcher: ShaderWatcher,
+    next_poll: Instant,
+}
+
+impl ShaderReloader {
+    pub fn new(settings: ShaderHotReload) -> Self {
+        rend3_routine::shaders::set_shader_directory(settings.routine_shaders.clone());
+        Self {
+            watcher: ShaderWatcher::new(settings.routine_shaders.into_iter().chain(settings.watch)),
+            next_poll: Instant::now() + POLL_INTERVAL,
+        }
+    }
+
+    /// Returns true if shaders changed since the last call.
+    pub fn poll(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next_poll {
+            return false;
+        }
+        self.next_poll = now + POLL_INTERVAL;
+        self.watcher.poll()
+    }
+}
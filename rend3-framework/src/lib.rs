@@ -1,26 +1,54 @@
-use std::{future::Future, pin::Pin, sync::Arc};
+use std::{future::Future, marker::PhantomData, pin::Pin, sync::Arc, time::Duration};
 
+use anyhow::Context;
 use glam::UVec2;
+use instant::Instant;
 use rend3::{
-    types::{Handedness, SampleCount, Surface, TextureFormat},
-    InstanceAdapterDevice, Renderer,
+    managers::{SkinningMode, VertexAttributes, VertexQuantization},
+    types::{DepthMode, Handedness, PresentMode, SampleCount, Surface, TextureFormat},
+    IadSettings, InstanceAdapterDevice, Renderer, RendererSettings,
 };
-use rend3_routine::base::BaseRenderGraph;
-use wgpu::Instance;
+use rend3_routine::{
+    base::BaseRenderGraph,
+    tonemapping::{TonemappingAlpha, TonemappingOutput},
+};
+use wgpu::{Backend, Instance, SurfaceError};
 use winit::{
     dpi::PhysicalSize,
     event::WindowEvent,
-    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy, EventLoopWindowTarget},
     window::{Window, WindowBuilder, WindowId},
 };
 
 mod assets;
+mod camera;
+mod capture;
+#[cfg(not(target_arch = "wasm32"))]
+mod golden;
 mod grab;
+mod headless;
+mod hot_reload;
 #[cfg(target_arch = "wasm32")]
 mod resize_observer;
+mod stereo;
+mod stream;
+mod timing;
+mod windows;
 
 pub use assets::*;
+pub use camera::*;
+pub use capture::{CaptureError, FrameCapture};
+#[cfg(not(target_arch = "wasm32"))]
+pub use golden::*;
 pub use grab::*;
+pub use headless::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use hot_reload::catch_shader_errors;
+pub use hot_reload::{ShaderError, ShaderHotReload};
+pub use stereo::*;
+pub use stream::*;
+pub use timing::FrameTiming;
+pub use windows::*;
 
 pub use parking_lot::{Mutex, MutexGuard};
 pub type Event<'a, T> = winit::event::Event<'a, UserResizeEvent<T>>;
@@ -37,6 +65,60 @@ pub enum UserResizeEvent<T: 'static> {
     Other(T),
 }
 
+/// How the resolution the app renders at relates to the size of the window.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderScale {
+    /// Render at the physical size of the window, one pixel per screen pixel.
+    Physical,
+    /// Render at the logical size of the window: the physical size divided by
+    /// the scale factor of the monitor the window is on.
+    Logical,
+    /// Render at the physical size of the window multiplied by this factor.
+    Custom(f32),
+}
+
+impl RenderScale {
+    /// Resolution to render at for a window with the given physical size and
+    /// monitor scale factor.
+    pub fn render_resolution(self, physical_size: UVec2, window_scale_factor: f64) -> UVec2 {
+        let factor = match self {
+            RenderScale::Physical => 1.0,
+            RenderScale::Logical => (1.0 / window_scale_factor) as f32,
+            RenderScale::Custom(factor) => factor,
+        };
+        (physical_size.as_vec2() * factor).round().as_uvec2().max(UVec2::ONE)
+    }
+}
+
+/// How the surface of the main window follows changes to the size of the
+/// window.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResizePolicy {
+    /// Reconfigure the surface on every resize event.
+    Immediate,
+    /// Reconfigure the surface once no resize event arrived for this long.
+    ///
+    /// While the window is being dragged, frames keep being rendered at the
+    /// previous resolution, with the aspect ratio of the new size, and are
+    /// stretched to fit the window. This avoids reallocating the surface and
+    /// size dependent render targets for every intermediate size.
+    Debounced(Duration),
+}
+
+/// Why the framework stopped rendering, see [`App::device_lost`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DeviceLost {
+    /// Acquiring the surface of a window ran out of memory.
+    OutOfMemory,
+    /// Surfaces kept being lost right after being recreated, which is how a
+    /// lost device shows up to the framework.
+    Lost,
+}
+
+/// Consecutive frames whose surface is lost before the device is assumed to
+/// be lost too.
+const MAX_LOST_SURFACES: u32 = 3;
+
 pub trait App<T: 'static = ()> {
     /// The handedness of the coordinate system of the renderer.
     const HANDEDNESS: Handedness;
@@ -54,6 +136,12 @@ pub trait App<T: 'static = ()> {
         std::panic::set_hook(Box::new(console_error_panic_hook::hook));
     }
 
+    /// Gives the app a proxy to send its own events to the event loop. They
+    /// are received in [`App::handle_event`] as [`UserResizeEvent::Other`].
+    fn register_event_loop_proxy(&mut self, proxy: EventLoopProxy<UserResizeEvent<T>>) {
+        let _ = proxy;
+    }
+
     fn create_window(&mut self, builder: WindowBuilder) -> (EventLoop<UserResizeEvent<T>>, Window) {
         profiling::scope!("creating window");
 
@@ -79,8 +167,14 @@ pub trait App<T: 'static = ()> {
         (event_loop, window)
     }
 
+    /// Choices used by the default [`create_iad`](Self::create_iad) to pick
+    /// the adapter, e.g. to force a discrete GPU or the GL backend.
+    fn iad_settings(&self) -> IadSettings {
+        IadSettings::default()
+    }
+
     fn create_iad<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = anyhow::Result<InstanceAdapterDevice>> + 'a>> {
-        Box::pin(async move { Ok(rend3::create_iad(None, None, None, None).await?) })
+        Box::pin(async move { Ok(rend3::create_iad_with_settings(&self.iad_settings()).await?) })
     }
 
     fn create_base_rendergraph(&mut self, renderer: &Renderer) -> BaseRenderGraph {
@@ -91,29 +185,233 @@ pub trait App<T: 'static = ()> {
     /// function is what the framework actually calls, so overriding this
     /// will always use the right values.
     ///
-    /// It is called on main events cleared and things are remade if this
-    /// changes.
+    /// It is called on main events cleared and passed on to
+    /// [`Renderer::set_sample_count`], so graphs built with
+    /// [`BaseRenderGraphSettings::for_renderer`](rend3_routine::base::BaseRenderGraphSettings::for_renderer)
+    /// follow it.
     fn sample_count(&self) -> SampleCount;
 
-    /// Determines the scale factor used
+    /// Determines the scale factor used by the default [`App::render_scale`].
     fn scale_factor(&self) -> f32 {
         1.0
     }
 
-    fn setup(
+    /// Determines the resolution passed to [`App::handle_event`], which is the
+    /// resolution the scene should be rendered at. The final image is scaled
+    /// to the size of the window by tonemapping.
+    ///
+    /// This is checked every frame, and [`App::render_resolution_changed`] is
+    /// called when the resulting resolution changes, including when the window
+    /// moves to a monitor with a different scale factor.
+    fn render_scale(&self) -> RenderScale {
+        RenderScale::Custom(self.scale_factor())
+    }
+
+    /// Determines how the surface of the main window is resized. Checked on
+    /// every resize event.
+    fn resize_policy(&self) -> ResizePolicy {
+        ResizePolicy::Immediate
+    }
+
+    /// Called when the resolution to render at changes, before the next
+    /// event is handled. With [`ResizePolicy::Debounced`], this is only called
+    /// once the window stopped changing size.
+    fn render_resolution_changed(&mut self, renderer: &Arc<Renderer>, resolution: UVec2) {
+        let _ = (renderer, resolution);
+    }
+
+    /// Determines the present mode of the surfaces. This is checked every
+    /// frame and the surfaces are reconfigured when it changes, so vsync can
+    /// be toggled at runtime, see [`vsync_present_mode`].
+    ///
+    /// If the surface doesn't support the present mode, Fifo is used instead.
+    fn present_mode(&self) -> PresentMode {
+        PresentMode::Mailbox
+    }
+
+    /// Layout of depth in the depth buffers of the renderer. Only read once,
+    /// when the renderer is created.
+    fn depth_mode(&self) -> DepthMode {
+        DepthMode::default()
+    }
+
+    /// Vertex attributes the renderer stores, leaving out those no material
+    /// of the app reads. Only read once, when the renderer is created.
+    fn vertex_attributes(&self) -> VertexAttributes {
+        VertexAttributes::all()
+    }
+
+    /// Vertex attributes the renderer stores in compact encodings, trading
+    /// precision for memory. Only read once, when the renderer is created.
+    fn vertex_quantization(&self) -> VertexQuantization {
+        VertexQuantization::empty()
+    }
+
+    /// Where skinned meshes are deformed, trading memory for ALU. Only read
+    /// once, when the renderer is created.
+    fn skinning_mode(&self) -> SkinningMode {
+        SkinningMode::default()
+    }
+
+    /// Chooses the format of the main surface, given the format the surface
+    /// prefers. Called once, during startup.
+    ///
+    /// wgpu can't list the formats a surface supports yet, and configuring a
+    /// surface with an unsupported format panics, so only return formats
+    /// known to work on the target platform. Not called when
+    /// [`App::hdr_output`] is used.
+    fn surface_format(&self, preferred: TextureFormat) -> TextureFormat {
+        preferred
+    }
+
+    /// HDR encoding to output, if the platform can present it. Called once,
+    /// during startup. See [`hdr_surface_format`] for when it can; otherwise
+    /// the output falls back to SDR.
+    fn hdr_output(&self) -> Option<TonemappingOutput> {
+        None
+    }
+
+    /// How the default tonemapping routine writes alpha. Called once, during
+    /// startup.
+    ///
+    /// For a window the desktop shows through, create it with
+    /// `WindowBuilder::with_transparent`, clear to a transparent color and
+    /// return [`TonemappingAlpha::Premultiplied`]. wgpu doesn't let surfaces
+    /// pick their composite alpha mode yet and asks for an opaque one, so
+    /// whether the platform blends the window depends on its compositor.
+    fn tonemapping_alpha(&self) -> TonemappingAlpha {
+        TonemappingAlpha::Passthrough
+    }
+
+    /// Called once the renderer is created, before the event loop starts.
+    ///
+    /// The returned future is awaited before the first event is handled, so
+    /// assets can be loaded here without blocking the thread. On wasm,
+    /// blocking on a future with `pollster` never finishes; `.await` it in
+    /// here instead. Resources can be added to the renderer at any point of
+    /// the future, none of its methods block.
+    fn setup<'a>(
+        &'a mut self,
+        window: &'a Window,
+        renderer: &'a Arc<Renderer>,
+        routines: &'a Arc<DefaultRoutines>,
+        surface_format: rend3::types::TextureFormat,
+    ) -> Pin<Box<dyn Future<Output = ()> + 'a>> {
+        let _ = (window, renderer, routines, surface_format);
+        Box::pin(async {})
+    }
+
+    /// Called when the app is sent to the background. The surface of the main
+    /// window has been dropped, and no frames are rendered until the app is
+    /// resumed.
+    ///
+    /// On Android, the app may be killed while suspended, so this is the place
+    /// to save state.
+    fn suspended(&mut self, renderer: &Arc<Renderer>) {
+        let _ = renderer;
+    }
+
+    /// Called when the app comes back from the background, after the surface
+    /// of the main window has been recreated and configured.
+    ///
+    /// The renderer and everything uploaded to it survive suspension, so this
+    /// is only needed to check on resources managed outside of rend3, or to
+    /// restart rendering related work stopped in [`App::suspended`].
+    fn resumed(&mut self, window: &Window, renderer: &Arc<Renderer>, surface: &Arc<Surface>) {
+        let _ = (window, renderer, surface);
+    }
+
+    /// Shaders to watch and reload while the app is running, to iterate on
+    /// them without restarting. Called once, before the default routines are
+    /// created. Not supported on wasm.
+    ///
+    /// When any watched file changes, the base rendergraph and the default
+    /// routines are rebuilt, then [`App::shaders_changed`] is called.
+    fn shader_hot_reload(&self) -> Option<ShaderHotReload> {
+        None
+    }
+
+    /// Called after watched shaders changed and the base rendergraph and
+    /// default routines were rebuilt, to rebuild the app's own pipelines.
+    /// Wrap that in [`catch_shader_errors`] to keep the old pipelines when
+    /// the new shaders are broken.
+    fn shaders_changed(
+        &mut self,
+        renderer: &Arc<Renderer>,
+        routines: &Arc<DefaultRoutines>,
+        base_rendergraph: &BaseRenderGraph,
+    ) {
+        let _ = (renderer, routines, base_rendergraph);
+    }
+
+    /// Called when the pipelines couldn't be rebuilt after shaders changed.
+    /// The previous pipelines stay in use.
+    fn shader_error(&mut self, error: &ShaderError) {
+        log::error!("{}", error);
+    }
+
+    /// Called after the framework recreated the surface of a window, because
+    /// the old one was lost. The renderer and its resources are unaffected, so
+    /// this is only needed for state tied to the surface itself.
+    ///
+    /// Outdated surfaces are reconfigured without calling this.
+    fn surface_recreated(&mut self, window_id: WindowId, renderer: &Arc<Renderer>) {
+        let _ = (window_id, renderer);
+    }
+
+    /// Called once when the framework can't render anymore, because the
+    /// device ran out of memory or was lost. Afterwards the framework stops
+    /// rendering and asks the event loop to exit, so this is the place to
+    /// save state.
+    ///
+    /// wgpu 0.12 can't recreate a device in place, and every resource of the
+    /// renderer belongs to it, so recovering means restarting the app. It
+    /// also doesn't report losing the device directly: most calls on a lost
+    /// device panic inside wgpu, and losing it is only noticed here when the
+    /// surface keeps being lost after being recreated.
+    fn device_lost(&mut self, reason: DeviceLost, renderer: &Arc<Renderer>) {
+        let _ = renderer;
+        log::error!("Can't render anymore: {:?}", reason);
+    }
+
+    /// Windows the app opened besides the main window. The framework creates
+    /// queued windows and keeps their surfaces sized to the window.
+    fn additional_windows(&mut self) -> Option<&mut AdditionalWindows> {
+        None
+    }
+
+    /// Length of a simulation step, in seconds.
+    ///
+    /// If this returns `Some`, [`App::update`] is called at that fixed rate,
+    /// as many times per frame as needed to keep up with real time. If it
+    /// returns `None`, [`App::update`] is called once per frame with the time
+    /// since the last frame. Steps of zero or less are treated as `None`.
+    fn fixed_timestep(&self) -> Option<f32> {
+        None
+    }
+
+    /// Advances the simulation by `delta` seconds.
+    ///
+    /// Called on MainEventsCleared, before it is given to
+    /// [`App::handle_event`], so it keeps running when minimized.
+    fn update(
         &mut self,
         window: &Window,
         renderer: &Arc<Renderer>,
         routines: &Arc<DefaultRoutines>,
-        surface_format: rend3::types::TextureFormat,
+        delta: f32,
+        timing: &FrameTiming,
     ) {
-        let _ = (window, renderer, routines, surface_format);
+        let _ = (window, renderer, routines, delta, timing);
     }
 
     /// RedrawRequested/RedrawEventsCleared will only be fired if the window
     /// size is non-zero. As such you should always render
     /// in RedrawRequested and use MainEventsCleared for things that need to
     /// keep running when minimized.
+    ///
+    /// `resolution` is the resolution to render at, see
+    /// [`App::render_scale`].
     #[allow(clippy::too_many_arguments)]
     fn handle_event(
         &mut self,
@@ -139,6 +437,15 @@ pub trait App<T: 'static = ()> {
     }
 }
 
+/// Present mode to use from [`App::present_mode`] to turn vsync on or off.
+pub fn vsync_present_mode(vsync: bool) -> PresentMode {
+    if vsync {
+        PresentMode::Fifo
+    } else {
+        PresentMode::Immediate
+    }
+}
+
 pub fn lock<T>(lock: &parking_lot::Mutex<T>) -> parking_lot::MutexGuard<'_, T> {
     #[cfg(target_arch = "wasm32")]
     let guard = lock.try_lock().expect("Could not lock mutex on single-threaded wasm. Do not hold locks open while an .await causes you to yield execution.");
@@ -148,10 +455,72 @@ pub fn lock<T>(lock: &parking_lot::Mutex<T>) -> parking_lot::MutexGuard<'_, T> {
     guard
 }
 
+/// Format of a surface presenting `output` with `backend`, or `None` if
+/// the surface can't be told to interpret it as HDR.
+///
+/// wgpu doesn't control the color space of surfaces yet, so this only
+/// succeeds where the format alone implies it: DXGI presents `Rgba16Float`
+/// swapchains as scRGB. HDR10 needs a color space wgpu can't request, so PQ
+/// output is only useful for offscreen targets, e.g. video encoding.
+pub fn hdr_surface_format(backend: Backend, output: TonemappingOutput) -> Option<TextureFormat> {
+    match (backend, output) {
+        (Backend::Dx12, TonemappingOutput::ScRgb { .. }) => output.format(),
+        _ => None,
+    }
+}
+
 pub struct DefaultRoutines {
     pub pbr: Mutex<rend3_routine::pbr::PbrRoutine>,
     pub skybox: Mutex<rend3_routine::skybox::SkyboxRoutine>,
     pub tonemapping: Mutex<rend3_routine::tonemapping::TonemappingRoutine>,
+    /// Hidden until enabled.
+    pub hud: Mutex<rend3_routine::hud::ProfilingHudRoutine>,
+}
+
+impl DefaultRoutines {
+    fn new(
+        renderer: &Arc<Renderer>,
+        base_rendergraph: &BaseRenderGraph,
+        output_format: TextureFormat,
+        output: TonemappingOutput,
+        alpha: TonemappingAlpha,
+    ) -> Self {
+        let mut data_core = renderer.data_core.lock();
+        Self {
+            pbr: Mutex::new(rend3_routine::pbr::PbrRoutine::new(
+                renderer,
+                &mut data_core,
+                &base_rendergraph.interfaces,
+            )),
+            skybox: Mutex::new(rend3_routine::skybox::SkyboxRoutine::new(
+                renderer,
+                &base_rendergraph.interfaces,
+            )),
+            tonemapping: Mutex::new(rend3_routine::tonemapping::TonemappingRoutine::with_output(
+                renderer,
+                &base_rendergraph.interfaces,
+                output_format,
+                output,
+                alpha,
+            )),
+            hud: Mutex::new(rend3_routine::hud::ProfilingHudRoutine::new(renderer, output_format)),
+        }
+    }
+
+    /// Swaps in routines rebuilt after shaders changed, keeping the settings
+    /// of the HUD.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn replace(&self, new: Self) {
+        *self.pbr.lock() = new.pbr.into_inner();
+        *self.skybox.lock() = new.skybox.into_inner();
+        *self.tonemapping.lock() = new.tonemapping.into_inner();
+
+        let mut hud = self.hud.lock();
+        let mut new_hud = new.hud.into_inner();
+        new_hud.enabled = hud.enabled;
+        new_hud.scale = hud.scale;
+        *hud = new_hud;
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -194,90 +563,215 @@ where
     }
 }
 
-pub async fn async_start<A: App + 'static>(mut app: A, window_builder: WindowBuilder) {
-    app.register_logger();
-    app.register_panic_hook();
+/// Owns the renderer and the surface of a window, and drives an [`App`] from
+/// the events of that window.
+///
+/// [`start`] creates and runs one of these with its own event loop. To render
+/// into a window of an existing application instead, create the framework
+/// with [`Framework::new`] and forward it every event of your event loop with
+/// [`Framework::handle_event`]. Events of an event loop with a user event
+/// type other than [`UserResizeEvent`] can be converted with
+/// [`wrap_user_event`].
+pub struct Framework<A: App<T>, T: 'static = ()> {
+    // Fields drop in declaration order, and the surface must be dropped
+    // before the window it was created from. The renderer and the app may
+    // hold on to frames of it, so they go first too.
+    app: A,
+    surface: Option<Arc<Surface>>,
+    renderer: Arc<Renderer>,
+    window: Window,
+    iad: InstanceAdapterDevice,
+    format: TextureFormat,
+    base_rendergraph: BaseRenderGraph,
+    routines: Arc<DefaultRoutines>,
+    suspended: bool,
+    last_user_control_mode: ControlFlow,
+    stored_surface_info: StoredSurfaceInfo,
+    pending_resize: Option<PendingResize>,
+    frame_timer: timing::FrameTimer,
+    shader_reloader: Option<hot_reload::ShaderReloader>,
+    /// Consecutive redraws which lost their surface.
+    lost_surfaces: u32,
+    device_lost: Option<DeviceLost>,
+    _phantom: PhantomData<fn(T)>,
+}
 
-    // Create the window invisible until we are rendering
-    let (event_loop, window) = app.create_window(window_builder.with_visible(false));
-    let window_size = window.inner_size();
+impl<A: App<T>, T: 'static> Framework<A, T> {
+    /// Creates the renderer and the surface for `window`, then calls
+    /// [`App::setup`].
+    ///
+    /// Fails if no device can be created, or the surface can't be presented
+    /// with it.
+    pub async fn new(mut app: A, window: Window) -> anyhow::Result<Self> {
+        let window_size = window.inner_size();
 
-    let iad = app.create_iad().await.unwrap();
+        let iad = app.create_iad().await?;
 
-    // The one line of unsafe needed. We just need to guarentee that the window
-    // outlives the use of the surface.
-    //
-    // Android has to defer the surface until `Resumed` is fired. This doesn't fire
-    // on other platforms though :|
-    let mut surface = if cfg!(target_os = "android") {
-        None
-    } else {
-        Some(Arc::new(unsafe { iad.instance.create_surface(&window) }))
-    };
+        // The one line of unsafe needed. We just need to guarentee that the window
+        // outlives the use of the surface.
+        //
+        // Android has to defer the surface until `Resumed` is fired. This doesn't fire
+        // on other platforms though :|
+        let surface = if cfg!(target_os = "android") {
+            None
+        } else {
+            Some(Arc::new(unsafe { iad.instance.create_surface(&window) }))
+        };
 
-    // Make us a renderer.
-    let renderer = rend3::Renderer::new(
-        iad.clone(),
-        A::HANDEDNESS,
-        Some(window_size.width as f32 / window_size.height as f32),
-    )
-    .unwrap();
-
-    // Get the preferred format for the surface.
-    //
-    // Assume android supports Rgba8Srgb, as it has 100% device coverage
-    let format = surface.as_ref().map_or(TextureFormat::Rgba8UnormSrgb, |s| {
-        let format = s.get_preferred_format(&iad.adapter).unwrap();
-
-        // Configure the surface to be ready for rendering.
-        rend3::configure_surface(
-            s,
-            &iad.device,
-            format,
-            glam::UVec2::new(window_size.width, window_size.height),
-            rend3::types::PresentMode::Mailbox,
-        );
+        // Make us a renderer.
+        let renderer = rend3::Renderer::new_with_settings(
+            iad.clone(),
+            A::HANDEDNESS,
+            RendererSettings {
+                depth_mode: app.depth_mode(),
+                vertex_attributes: app.vertex_attributes(),
+                vertex_quantization: app.vertex_quantization(),
+                skinning_mode: app.skinning_mode(),
+            },
+            Some(window_size.width as f32 / window_size.height as f32),
+        )?;
 
-        format
-    });
+        // Get the preferred format for the surface.
+        //
+        // Assume android supports Rgba8Srgb, as it has 100% device coverage
+        let present_mode = app.present_mode();
+        let mut tonemapping_output = TonemappingOutput::Sdr;
+        let format = match &surface {
+            Some(s) => {
+                let hdr = app.hdr_output().and_then(|output| {
+                    let format = hdr_surface_format(iad.info.backend, output);
+                    if format.is_none() {
+                        log::info!(
+                            "{:?} can't present {:?} with {:?}, falling back to SDR",
+                            iad.info.backend,
+                            output,
+                            output.format()
+                        );
+                    }
+                    Some((format?, output))
+                });
+                let format = match hdr {
+                    Some((format, output)) => {
+                        tonemapping_output = output;
+                        format
+                    }
+                    None => app.surface_format(
+                        s.get_preferred_format(&iad.adapter)
+                            .context("the surface can't be presented with the adapter")?,
+                    ),
+                };
 
-    let base_rendergraph = app.create_base_rendergraph(&renderer);
-    let mut data_core = renderer.data_core.lock();
-    let routines = Arc::new(DefaultRoutines {
-        pbr: Mutex::new(rend3_routine::pbr::PbrRoutine::new(
-            &renderer,
-            &mut data_core,
-            &base_rendergraph.interfaces,
-        )),
-        skybox: Mutex::new(rend3_routine::skybox::SkyboxRoutine::new(
-            &renderer,
-            &base_rendergraph.interfaces,
-        )),
-        tonemapping: Mutex::new(rend3_routine::tonemapping::TonemappingRoutine::new(
+                // Configure the surface to be ready for rendering.
+                rend3::configure_surface(
+                    s,
+                    &iad.device,
+                    format,
+                    glam::UVec2::new(window_size.width, window_size.height),
+                    present_mode,
+                );
+
+                format
+            }
+            None => TextureFormat::Rgba8UnormSrgb,
+        };
+
+        // Set up before the routines are created, so they load the watched shaders.
+        let shader_reloader = match app.shader_hot_reload() {
+            Some(_) if cfg!(target_arch = "wasm32") => {
+                log::warn!("Shader hot reloading isn't supported on wasm");
+                None
+            }
+            Some(settings) => Some(hot_reload::ShaderReloader::new(settings)),
+            None => None,
+        };
+
+        let base_rendergraph = app.create_base_rendergraph(&renderer);
+        let routines = Arc::new(DefaultRoutines::new(
             &renderer,
-            &base_rendergraph.interfaces,
+            &base_rendergraph,
             format,
-        )),
-    });
-    drop(data_core);
+            tonemapping_output,
+            app.tonemapping_alpha(),
+        ));
 
-    app.setup(&window, &renderer, &routines, format);
+        renderer.set_sample_count(app.sample_count());
+        app.setup(&window, &renderer, &routines, format).await;
 
-    #[cfg(target_arch = "wasm32")]
-    let _observer = resize_observer::ResizeObserver::new(&window, event_loop.create_proxy());
+        let stored_surface_info = StoredSurfaceInfo {
+            size: glam::UVec2::new(window_size.width, window_size.height),
+            render_resolution: app
+                .render_scale()
+                .render_resolution(UVec2::new(window_size.width, window_size.height), window.scale_factor()),
+            sample_count: app.sample_count(),
+            present_mode,
+        };
 
-    // We're ready, so lets make things visible
-    window.set_visible(true);
-
-    let mut suspended = cfg!(target_os = "android");
-    let mut last_user_control_mode = ControlFlow::Poll;
-    let mut stored_surface_info = StoredSurfaceInfo {
-        size: glam::UVec2::new(window_size.width, window_size.height),
-        scale_factor: app.scale_factor(),
-        sample_count: app.sample_count(),
-    };
+        Ok(Self {
+            app,
+            window,
+            iad,
+            surface,
+            renderer,
+            format,
+            base_rendergraph,
+            routines,
+            suspended: cfg!(target_os = "android"),
+            last_user_control_mode: ControlFlow::Poll,
+            stored_surface_info,
+            pending_resize: None,
+            frame_timer: timing::FrameTimer::new(),
+            shader_reloader,
+            lost_surfaces: 0,
+            device_lost: None,
+            _phantom: PhantomData,
+        })
+    }
+
+    pub fn app(&self) -> &A {
+        &self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut A {
+        &mut self.app
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    pub fn renderer(&self) -> &Arc<Renderer> {
+        &self.renderer
+    }
+
+    pub fn routines(&self) -> &Arc<DefaultRoutines> {
+        &self.routines
+    }
+
+    /// Format of the surface of the window.
+    pub fn surface_format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Set once the framework can't render anymore, see [`App::device_lost`].
+    /// Events are ignored from then on, and an event loop driven with
+    /// [`Framework::handle_event`] should be shut down.
+    pub fn device_lost(&self) -> Option<DeviceLost> {
+        self.device_lost
+    }
+
+    /// Handles an event of the event loop, keeping the surface up to date and
+    /// passing the event on to the app.
+    pub fn handle_event<U: 'static>(
+        &mut self,
+        event: Event<'_, T>,
+        event_loop: &EventLoopWindowTarget<U>,
+        control_flow: &mut ControlFlow,
+    ) {
+        if self.device_lost.is_some() {
+            *control_flow = ControlFlow::Exit;
+            return;
+        }
 
-    winit_run(event_loop, move |event, _event_loop, control_flow| {
         let event = match event {
             Event::UserEvent(UserResizeEvent::Resize { size, window_id }) => Event::WindowEvent {
                 window_id,
@@ -286,61 +780,352 @@ pub async fn async_start<A: App + 'static>(mut app: A, window_builder: WindowBui
             e => e,
         };
 
+        if let Some(additional_windows) = self.app.additional_windows() {
+            additional_windows.create_pending(event_loop, &self.iad);
+
+            if let Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(size),
+            } = event
+            {
+                additional_windows.resize(window_id, UVec2::new(size.width, size.height), &self.iad);
+            }
+        }
+
         if let Some(suspend) = handle_surface(
-            &app,
-            &window,
+            &self.app,
+            &self.window,
             &event,
-            &iad.instance,
-            &mut surface,
-            &renderer,
-            format,
-            &mut stored_surface_info,
+            &self.iad.instance,
+            &mut self.surface,
+            &self.renderer,
+            self.format,
+            &mut self.stored_surface_info,
+            &mut self.pending_resize,
         ) {
-            suspended = suspend;
+            self.suspended = suspend;
         }
 
         // We move to Wait when we get suspended so we don't spin at 50k FPS.
         match event {
             Event::Suspended => {
                 *control_flow = ControlFlow::Wait;
+                self.app.suspended(&self.renderer);
             }
             Event::Resumed => {
-                *control_flow = last_user_control_mode;
+                *control_flow = self.last_user_control_mode;
+                self.frame_timer.reset();
+                self.app
+                    .resumed(&self.window, &self.renderer, self.surface.as_ref().unwrap());
             }
             _ => {}
         }
 
         // We need to block all updates
         if let Event::RedrawRequested(_) | Event::RedrawEventsCleared | Event::MainEventsCleared = event {
-            if suspended {
+            if self.suspended {
                 return;
             }
         }
 
-        app.handle_event(
-            &window,
-            &renderer,
-            &routines,
-            &base_rendergraph,
-            surface.as_ref(),
-            stored_surface_info.size,
+        if let Event::MainEventsCleared = event {
+            if let Some(pending) = &self.pending_resize {
+                if Instant::now() >= pending.deadline {
+                    self.apply_pending_resize(control_flow);
+                }
+            }
+
+            let present_mode = self.app.present_mode();
+            if present_mode != self.stored_surface_info.present_mode {
+                self.stored_surface_info.present_mode = present_mode;
+                if let Some(surface) = &self.surface {
+                    rend3::configure_surface(
+                        surface,
+                        &self.renderer.device,
+                        self.format,
+                        self.stored_surface_info.size,
+                        present_mode,
+                    );
+                }
+            }
+            if let Some(additional_windows) = self.app.additional_windows() {
+                additional_windows.set_present_mode(present_mode, &self.iad);
+            }
+
+            // Routines have pipelines for every sample count, so switching
+            // only needs the renderer to know.
+            self.stored_surface_info.sample_count = self.app.sample_count();
+            self.renderer.set_sample_count(self.stored_surface_info.sample_count);
+
+            if self.shader_reloader.as_mut().map_or(false, |reloader| reloader.poll()) {
+                self.reload_shaders();
+            }
+
+            let fixed_timestep = self.app.fixed_timestep().filter(|&step| step > 0.0);
+            let (timing, steps) = self.frame_timer.tick(fixed_timestep);
+            let delta = fixed_timestep.unwrap_or(timing.delta);
+            for _ in 0..steps {
+                self.app
+                    .update(&self.window, &self.renderer, &self.routines, delta, &timing);
+            }
+        }
+
+        let render_resolution = self
+            .app
+            .render_scale()
+            .render_resolution(self.stored_surface_info.size, self.window.scale_factor());
+        if render_resolution != self.stored_surface_info.render_resolution {
+            self.stored_surface_info.render_resolution = render_resolution;
+            self.app.render_resolution_changed(&self.renderer, render_resolution);
+        }
+
+        let redrawn_window = match event {
+            Event::RedrawRequested(window_id) => Some(window_id),
+            _ => None,
+        };
+
+        let last_user_control_mode = &mut self.last_user_control_mode;
+        self.app.handle_event(
+            &self.window,
+            &self.renderer,
+            &self.routines,
+            &self.base_rendergraph,
+            self.surface.as_ref(),
+            self.stored_surface_info.render_resolution,
             event,
             |c: ControlFlow| {
                 *control_flow = c;
-                last_user_control_mode = c;
+                *last_user_control_mode = c;
             },
-        )
+        );
+
+        // Rendering the window may have failed to acquire its surface, bring it back for the next frame.
+        if let Some(window_id) = redrawn_window {
+            match self.renderer.take_surface_error() {
+                Some(error) => {
+                    if let Err(reason) = self.recover_window_surface(window_id, error, control_flow) {
+                        self.device_lost = Some(reason);
+                        self.app.device_lost(reason, &self.renderer);
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                }
+                None => self.lost_surfaces = 0,
+            }
+        }
+
+        // Make sure we wake up to finish the resize, even if nothing else happens.
+        if let Some(pending) = &self.pending_resize {
+            match *control_flow {
+                ControlFlow::Wait => *control_flow = ControlFlow::WaitUntil(pending.deadline),
+                ControlFlow::WaitUntil(until) if until > pending.deadline => {
+                    *control_flow = ControlFlow::WaitUntil(pending.deadline)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Brings back the surface of a window which failed to be acquired, or
+    /// gives up once the device seems to be lost.
+    fn recover_window_surface(
+        &mut self,
+        window_id: WindowId,
+        error: SurfaceError,
+        control_flow: &mut ControlFlow,
+    ) -> Result<(), DeviceLost> {
+        if matches!(error, SurfaceError::Lost) && self.lost_surfaces >= MAX_LOST_SURFACES {
+            return Err(DeviceLost::Lost);
+        }
+
+        let recreated = if window_id == self.window.id()
+            && matches!(error, SurfaceError::Outdated)
+            && self.pending_resize.is_some()
+        {
+            // The platform won't present a surface of the old size, stop waiting for the resize to settle.
+            self.apply_pending_resize(control_flow);
+            false
+        } else if window_id == self.window.id() {
+            recover_surface(
+                &self.window,
+                &self.iad,
+                &mut self.surface,
+                self.format,
+                &self.stored_surface_info,
+                error,
+            )?
+        } else {
+            let iad = &self.iad;
+            match self.app.additional_windows() {
+                Some(windows) => windows.recover_surface(window_id, iad, error)?,
+                None => false,
+            }
+        };
+
+        if recreated {
+            self.lost_surfaces += 1;
+            self.app.surface_recreated(window_id, &self.renderer);
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the base rendergraph and the default routines from the changed
+    /// shaders, keeping the old ones if that fails.
+    fn reload_shaders(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let renderer = &self.renderer;
+            let app = &mut self.app;
+            let format = self.format;
+            let (output, alpha) = {
+                let tonemapping = self.routines.tonemapping.lock();
+                (tonemapping.output(), tonemapping.alpha())
+            };
+            let rebuilt = catch_shader_errors(&renderer.device, || {
+                let base_rendergraph = app.create_base_rendergraph(renderer);
+                let routines = DefaultRoutines::new(renderer, &base_rendergraph, format, output, alpha);
+                (base_rendergraph, routines)
+            });
+
+            match rebuilt {
+                Ok((base_rendergraph, routines)) => {
+                    log::info!("Rebuilt pipelines from the changed shaders");
+                    self.base_rendergraph = base_rendergraph;
+                    self.routines.replace(routines);
+                    self.app
+                        .shaders_changed(&self.renderer, &self.routines, &self.base_rendergraph);
+                }
+                Err(error) => self.app.shader_error(&error),
+            }
+        }
+    }
+
+    fn apply_pending_resize(&mut self, control_flow: &mut ControlFlow) {
+        let pending = match self.pending_resize.take() {
+            Some(pending) => pending,
+            None => return,
+        };
+
+        resize_surface(
+            &self.app,
+            self.surface.as_ref(),
+            &self.renderer,
+            self.format,
+            &mut self.stored_surface_info,
+            pending.size,
+        );
+
+        // Undo the wake up we may have scheduled for the resize.
+        if let ControlFlow::WaitUntil(_) = *control_flow {
+            *control_flow = self.last_user_control_mode;
+        }
+    }
+}
+
+/// Converts an event from an event loop with any user event type into an
+/// event the framework can handle, for use with [`Framework::handle_event`].
+pub fn wrap_user_event<T>(event: winit::event::Event<'_, T>) -> Event<'_, T> {
+    match event.map_nonuser_event() {
+        Ok(event) => event,
+        Err(winit::event::Event::UserEvent(user_event)) => Event::UserEvent(UserResizeEvent::Other(user_event)),
+        Err(_) => unreachable!("map_nonuser_event only fails on user events"),
+    }
+}
+
+pub async fn async_start<A: App<T> + 'static, T: 'static>(mut app: A, window_builder: WindowBuilder) {
+    app.register_logger();
+    app.register_panic_hook();
+
+    // Create the window invisible until we are rendering
+    let (event_loop, window) = app.create_window(window_builder.with_visible(false));
+    app.register_event_loop_proxy(event_loop.create_proxy());
+
+    let mut framework = Framework::new(app, window).await.unwrap();
+
+    #[cfg(target_arch = "wasm32")]
+    let _observer = resize_observer::ResizeObserver::new(framework.window(), event_loop.create_proxy());
+
+    // We're ready, so lets make things visible
+    framework.window().set_visible(true);
+
+    winit_run(event_loop, move |event, event_loop, control_flow| {
+        framework.handle_event(event, event_loop, control_flow)
     });
 }
 
+/// Reconfigures or recreates the surface of the main window after it failed to
+/// be acquired. Returns true if the surface was recreated.
+fn recover_surface(
+    window: &Window,
+    iad: &InstanceAdapterDevice,
+    surface: &mut Option<Arc<Surface>>,
+    format: TextureFormat,
+    surface_info: &StoredSurfaceInfo,
+    error: SurfaceError,
+) -> Result<bool, DeviceLost> {
+    let recreate = match error {
+        // Might just be a slow frame, try again next frame.
+        SurfaceError::Timeout => return Ok(false),
+        SurfaceError::Outdated => false,
+        SurfaceError::Lost => true,
+        SurfaceError::OutOfMemory => return Err(DeviceLost::OutOfMemory),
+    };
+
+    if recreate {
+        log::warn!("Surface lost, recreating it");
+        *surface = Some(Arc::new(unsafe { iad.instance.create_surface(window) }));
+    }
+
+    if let Some(surface) = surface {
+        rend3::configure_surface(
+            surface,
+            &iad.device,
+            format,
+            surface_info.size,
+            surface_info.present_mode,
+        );
+    }
+
+    Ok(recreate)
+}
+
 struct StoredSurfaceInfo {
     size: UVec2,
-    scale_factor: f32,
+    /// Resolution to render at, derived from the size.
+    render_resolution: UVec2,
     sample_count: SampleCount,
+    present_mode: PresentMode,
+}
+
+/// A resize held back by [`ResizePolicy::Debounced`].
+#[derive(Debug, Copy, Clone)]
+struct PendingResize {
+    size: UVec2,
+    deadline: Instant,
+}
+
+/// Reconfigures the surface of the main window for a new size.
+fn resize_surface<A: App<T>, T: 'static>(
+    app: &A,
+    surface: Option<&Arc<Surface>>,
+    renderer: &Renderer,
+    format: TextureFormat,
+    surface_info: &mut StoredSurfaceInfo,
+    size: UVec2,
+) {
+    surface_info.size = size;
+    surface_info.sample_count = app.sample_count();
+    renderer.set_sample_count(surface_info.sample_count);
+    surface_info.present_mode = app.present_mode();
+
+    // While suspended there is no surface, it gets configured when resuming.
+    if let Some(surface) = surface {
+        rend3::configure_surface(surface, &renderer.device, format, size, surface_info.present_mode);
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
-fn handle_surface<A: App, T: 'static>(
+fn handle_surface<A: App<T>, T: 'static>(
     app: &A,
     window: &Window,
     event: &Event<T>,
@@ -349,10 +1134,30 @@ fn handle_surface<A: App, T: 'static>(
     renderer: &Arc<Renderer>,
     format: rend3::types::TextureFormat,
     surface_info: &mut StoredSurfaceInfo,
+    pending_resize: &mut Option<PendingResize>,
 ) -> Option<bool> {
     match *event {
         Event::Resumed => {
-            *surface = Some(Arc::new(unsafe { instance.create_surface(window) }));
+            let new_surface = Arc::new(unsafe { instance.create_surface(window) });
+
+            // The window may have changed size while suspended, and we might not get
+            // a resize event for it.
+            let size = window.inner_size();
+            let size = UVec2::new(size.width, size.height);
+            *pending_resize = None;
+            if size.x != 0 && size.y != 0 {
+                surface_info.size = size;
+                renderer.set_aspect_ratio(size.x as f32 / size.y as f32);
+            }
+            rend3::configure_surface(
+                &new_surface,
+                &renderer.device,
+                format,
+                surface_info.size,
+                surface_info.present_mode,
+            );
+
+            *surface = Some(new_surface);
             Some(false)
         }
         Event::Suspended => {
@@ -360,37 +1165,38 @@ fn handle_surface<A: App, T: 'static>(
             Some(true)
         }
         Event::WindowEvent {
+            window_id,
             event: winit::event::WindowEvent::Resized(size),
-            ..
-        } => {
+        } if window_id == window.id() => {
             log::debug!("resize {:?}", size);
             let size = UVec2::new(size.width, size.height);
 
             if size.x == 0 || size.y == 0 {
-                return Some(false);
+                return None;
             }
 
-            surface_info.size = size;
-            surface_info.scale_factor = app.scale_factor();
-            surface_info.sample_count = app.sample_count();
-
-            // Reconfigure the surface for the new size.
-            rend3::configure_surface(
-                surface.as_ref().unwrap(),
-                &renderer.device,
-                format,
-                glam::UVec2::new(size.x, size.y),
-                rend3::types::PresentMode::Mailbox,
-            );
-            // Tell the renderer about the new aspect ratio.
+            match app.resize_policy() {
+                ResizePolicy::Immediate => {
+                    *pending_resize = None;
+                    resize_surface(app, surface.as_ref(), renderer, format, surface_info, size);
+                }
+                ResizePolicy::Debounced(delay) => {
+                    *pending_resize = Some(PendingResize {
+                        size,
+                        deadline: Instant::now() + delay,
+                    });
+                }
+            }
+            // Tell the renderer about the new aspect ratio. This happens right away even when the resize
+            // is debounced, so the stretched frames keep their proportions.
             renderer.set_aspect_ratio(size.x as f32 / size.y as f32);
-            Some(false)
+            None
         }
         _ => None,
     }
 }
 
-pub fn start<A: App + 'static>(app: A, window_builder: WindowBuilder) {
+pub fn start<A: App<T> + 'static, T: 'static>(app: A, window_builder: WindowBuilder) {
     #[cfg(target_arch = "wasm32")]
     {
         wasm_bindgen_futures::spawn_local(async_start(app, window_builder));
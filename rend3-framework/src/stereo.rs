@@ -0,0 +1,214 @@
+use std::sync::Arc;
+
+use glam::{Mat4, Quat, UVec2, Vec3, Vec4};
+use rend3::{
+    graph::RenderGraph,
+    types::{Camera, CameraProjection, SampleCount, TextureFormat},
+    util::output::OutputFrame,
+    Renderer,
+};
+use rend3_routine::base::BaseRenderGraph;
+use wgpu::{
+    Texture, TextureDescriptor, TextureDimension, TextureUsages, TextureView, TextureViewDescriptor,
+    TextureViewDimension,
+};
+
+use crate::{capture::extent, lock, DefaultRoutines};
+
+/// One of the two eyes of a stereo frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    /// Both eyes, in the order they are rendered.
+    pub const ALL: [Eye; 2] = [Eye::Left, Eye::Right];
+
+    /// Index of the eye in per-eye arrays, which is also the layer it uses in
+    /// array textures.
+    pub fn index(self) -> usize {
+        match self {
+            Eye::Left => 0,
+            Eye::Right => 1,
+        }
+    }
+}
+
+/// Pose and field of view of an eye, laid out the way VR runtimes such as
+/// OpenXR report them for every frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EyeView {
+    pub position: Vec3,
+    pub orientation: Quat,
+    /// Angles in radians from the view direction to the left, right, top and
+    /// bottom edges of the view.
+    pub fov_left: f32,
+    pub fov_right: f32,
+    pub fov_up: f32,
+    pub fov_down: f32,
+}
+
+impl EyeView {
+    /// Camera looking through this eye.
+    ///
+    /// Poses are used as-is, so they must be in the handedness of the
+    /// renderer. OpenXR poses are right handed.
+    pub fn camera(&self, near: f32) -> Camera {
+        Camera {
+            projection: CameraProjection::Asymmetric {
+                left: self.fov_left,
+                right: self.fov_right,
+                up: self.fov_up,
+                down: self.fov_down,
+                near,
+            },
+            view: Mat4::from_rotation_translation(self.orientation, self.position).inverse(),
+        }
+    }
+}
+
+/// The views both eyes of a frame are rendered into.
+///
+/// Images of a VR runtime's swapchain, once wrapped in wgpu textures, are
+/// used through [`StereoFrame::from_array_texture`] when the runtime hands out
+/// one image with a layer per eye, and through [`StereoFrame::from_views`]
+/// when every eye has its own swapchain.
+#[derive(Clone)]
+pub struct StereoFrame {
+    pub views: [Arc<TextureView>; 2],
+    /// Resolution of each eye.
+    pub resolution: UVec2,
+}
+
+impl StereoFrame {
+    /// Renders the left eye into layer 0 and the right eye into layer 1 of
+    /// `texture`.
+    pub fn from_array_texture(texture: &Texture, resolution: UVec2) -> Self {
+        let layer_view = |eye: Eye| {
+            Arc::new(texture.create_view(&TextureViewDescriptor {
+                label: Some("stereo eye"),
+                dimension: Some(TextureViewDimension::D2),
+                base_array_layer: eye.index() as u32,
+                array_layer_count: std::num::NonZeroU32::new(1),
+                ..TextureViewDescriptor::default()
+            }))
+        };
+
+        Self {
+            views: [layer_view(Eye::Left), layer_view(Eye::Right)],
+            resolution,
+        }
+    }
+
+    pub fn from_views(left: Arc<TextureView>, right: Arc<TextureView>, resolution: UVec2) -> Self {
+        Self {
+            views: [left, right],
+            resolution,
+        }
+    }
+
+    /// Output frame to render `eye` into.
+    pub fn eye_output(&self, eye: Eye) -> OutputFrame {
+        OutputFrame::View(Arc::clone(&self.views[eye.index()]))
+    }
+}
+
+/// A texture array with a layer per eye, to render stereo frames into
+/// without a VR runtime, e.g. to preview them in a window.
+pub struct StereoTargets {
+    pub texture: Texture,
+    /// Resolution of each eye.
+    pub resolution: UVec2,
+    pub format: TextureFormat,
+}
+
+impl StereoTargets {
+    pub fn new(renderer: &Renderer, resolution: UVec2, format: TextureFormat) -> Self {
+        let texture = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("stereo targets"),
+            size: wgpu::Extent3d {
+                depth_or_array_layers: 2,
+                ..extent(resolution)
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC,
+        });
+
+        Self {
+            texture,
+            resolution,
+            format,
+        }
+    }
+
+    pub fn frame(&self) -> StereoFrame {
+        StereoFrame::from_array_texture(&self.texture, self.resolution)
+    }
+}
+
+/// Renders both eyes of `frame` with the default rendergraph, one after the
+/// other. Each eye uses its camera from `cameras`, indexed by
+/// [`Eye::index`].
+///
+/// The renderer is readied once for both eyes, so shadows are fitted to the
+/// camera of the left eye. The views of `frame` must have the format the
+/// routines were created with. The camera of the renderer is left at the
+/// camera of the right eye.
+///
+/// To render with a custom rendergraph, ready the renderer once, then for
+/// every eye set its camera and build and execute the rendergraph into
+/// [`StereoFrame::eye_output`] with the same [`ReadyData`](rend3::graph::ReadyData).
+#[allow(clippy::too_many_arguments)]
+pub fn render_stereo(
+    renderer: &Arc<Renderer>,
+    routines: &DefaultRoutines,
+    base_rendergraph: &BaseRenderGraph,
+    cameras: [Camera; 2],
+    frame: &StereoFrame,
+    samples: SampleCount,
+    ambient: Vec4,
+    clear_color: Vec4,
+) {
+    profiling::scope!("render_stereo");
+
+    let pbr_routine = lock(&routines.pbr);
+    let mut skybox_routine = lock(&routines.skybox);
+    let tonemapping_routine = lock(&routines.tonemapping);
+
+    renderer.set_camera_data(cameras[Eye::Left.index()]);
+
+    let (cmd_bufs, ready) = renderer.ready();
+    skybox_routine.ready(renderer);
+
+    // Only the first eye submits the uploads of this frame.
+    let mut cmd_bufs = Some(cmd_bufs);
+    for eye in Eye::ALL {
+        renderer.data_core.lock().camera_manager.set_data(cameras[eye.index()]);
+
+        let mut graph = RenderGraph::new();
+        base_rendergraph.add_to_graph(
+            &mut graph,
+            &ready,
+            rend3_routine::base::BaseRenderGraphRoutines {
+                pbr: &pbr_routine,
+                skybox: Some(&skybox_routine),
+                tonemapping: &tonemapping_routine,
+            },
+            &rend3_routine::base::BaseRenderGraphSettings::new(frame.resolution)
+                .with_samples(samples)
+                .with_ambient(ambient)
+                .with_clear_color(clear_color),
+        );
+        graph.execute(
+            renderer,
+            frame.eye_output(eye),
+            cmd_bufs.take().unwrap_or_default(),
+            &ready,
+        );
+    }
+}
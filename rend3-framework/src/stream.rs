@@ -0,0 +1,577 @@
+use std::{
+    future::Future,
+    num::NonZeroU64,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use glam::UVec2;
+use rend3::{
+    types::{Surface, TextureFormat},
+    util::output::OutputFrame,
+    Renderer,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingResource, BindingType, Buffer, BufferAsyncError, BufferBinding, BufferBindingType, BufferDescriptor,
+    BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor, ComputePassDescriptor,
+    ComputePipeline, ComputePipelineDescriptor, Device, FragmentState, ImageCopyBuffer, ImageCopyTexture,
+    ImageDataLayout, LoadOp, Maintain, MapMode, MultisampleState, Operations, Origin3d, PipelineLayoutDescriptor,
+    PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, Texture, TextureAspect, TextureDescriptor, TextureDimension,
+    TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use crate::capture::extent;
+
+/// Layout of the pixels of a [`StreamedFrame`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// The format the frame was rendered in, with rows padded to
+    /// [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`]. Nothing is converted.
+    Native,
+    /// 8 bit BGRA, gamma encoded if the frame was rendered in an srgb format.
+    Bgra8,
+    /// 8 bit BT.709 limited range YUV 4:2:0, a full resolution luma plane
+    /// followed by a half resolution plane of interleaved U and V. Frames
+    /// must have a width divisible by 4 and an even height.
+    Nv12,
+}
+
+/// A frame read back by a [`FrameStream`].
+#[derive(Debug)]
+pub struct StreamedFrame<'a> {
+    /// Number of the frame since the stream was created.
+    pub index: u64,
+    pub resolution: UVec2,
+    pub format: StreamFormat,
+    /// Format of the texture the frame was rendered in.
+    pub texture_format: TextureFormat,
+    /// Rows of `bytes_per_row` bytes, which may be padded past the end of
+    /// the pixels.
+    pub data: &'a [u8],
+    pub bytes_per_row: u32,
+}
+
+impl<'a> StreamedFrame<'a> {
+    /// Bytes of pixels in a row, without padding.
+    pub fn packed_bytes_per_row(&self) -> u32 {
+        match self.format {
+            StreamFormat::Native => self.resolution.x * self.texture_format.describe().block_size as u32,
+            StreamFormat::Bgra8 => self.resolution.x * 4,
+            StreamFormat::Nv12 => self.resolution.x,
+        }
+    }
+
+    /// Rows of pixels without padding, top to bottom. Nv12 frames have the
+    /// rows of their chroma plane after the ones of their luma plane.
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> {
+        let packed = self.packed_bytes_per_row() as usize;
+        self.data
+            .chunks(self.bytes_per_row as usize)
+            .map(move |row| &row[..packed])
+    }
+}
+
+type StreamCallback = Box<dyn FnMut(StreamedFrame<'_>)>;
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send>>;
+
+struct PendingFrame {
+    mapping: MapFuture,
+    index: u64,
+    resolution: UVec2,
+    texture_format: TextureFormat,
+    bytes_per_row: u32,
+    size: u64,
+}
+
+struct StreamSlot {
+    buffer: Option<Buffer>,
+    capacity: u64,
+    pending: Option<PendingFrame>,
+}
+
+struct StreamTarget {
+    texture: Texture,
+    view: Arc<TextureView>,
+    resolution: UVec2,
+    format: TextureFormat,
+}
+
+struct StreamPipelines {
+    format: TextureFormat,
+    layout: BindGroupLayout,
+    blit: RenderPipeline,
+    bgra: ComputePipeline,
+    luma: ComputePipeline,
+    chroma: ComputePipeline,
+    /// Storage buffer compute conversions write into, and its size.
+    converted: Option<(Buffer, u64)>,
+}
+
+/// Reads back every frame into a ring of buffers, handing them to a callback
+/// once the GPU is done with them, e.g. to pipe them into ffmpeg or a video
+/// encoder.
+///
+/// Frames are rendered into a texture given out by
+/// [`FrameStream::output_frame`], then [`FrameStream::finish`] copies it to
+/// the surface, converts it if needed and starts reading it back. Frames are
+/// delivered a few frames later, in order, only stalling when all buffers of
+/// the ring are still in flight. Call [`FrameStream::flush`] to deliver the
+/// remaining frames when done.
+///
+/// Conversions run in compute shaders, so they aren't available on the
+/// downlevel profile.
+pub struct FrameStream {
+    format: StreamFormat,
+    callback: StreamCallback,
+    slots: Vec<StreamSlot>,
+    /// Slot the next frame is read back into, which is also the oldest one
+    /// in flight.
+    next_slot: usize,
+    frame_index: u64,
+    target: Option<StreamTarget>,
+    pipelines: Option<StreamPipelines>,
+}
+
+impl FrameStream {
+    /// Creates a stream with `ring_size` readback buffers. More buffers mean
+    /// fewer stalls, at the cost of memory and latency.
+    pub fn new(ring_size: usize, format: StreamFormat, callback: impl FnMut(StreamedFrame<'_>) + 'static) -> Self {
+        assert!(ring_size > 0, "Frame streams need at least one buffer");
+        Self {
+            format,
+            callback: Box::new(callback),
+            slots: (0..ring_size)
+                .map(|_| StreamSlot {
+                    buffer: None,
+                    capacity: 0,
+                    pending: None,
+                })
+                .collect(),
+            next_slot: 0,
+            frame_index: 0,
+            target: None,
+            pipelines: None,
+        }
+    }
+
+    /// Returns the frame to render to. `resolution` and `format` must match
+    /// the ones of the surface, if the frame is shown.
+    pub fn output_frame(&mut self, renderer: &Renderer, resolution: UVec2, format: TextureFormat) -> OutputFrame {
+        if self.format == StreamFormat::Nv12 {
+            assert!(
+                resolution.x % 4 == 0 && resolution.y % 2 == 0,
+                "Nv12 frames need a width divisible by 4 and an even height, not {}",
+                resolution
+            );
+        }
+
+        let reuse =
+            matches!(self.target, Some(ref target) if target.resolution == resolution && target.format == format);
+        if !reuse {
+            let texture = renderer.device.create_texture(&TextureDescriptor {
+                label: Some("frame stream"),
+                size: extent(resolution),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC | TextureUsages::TEXTURE_BINDING,
+            });
+            let view = Arc::new(texture.create_view(&TextureViewDescriptor::default()));
+            self.target = Some(StreamTarget {
+                texture,
+                view,
+                resolution,
+                format,
+            });
+        }
+
+        OutputFrame::View(Arc::clone(&self.target.as_ref().unwrap().view))
+    }
+
+    /// Shows the frame rendered into the texture given out by
+    /// [`FrameStream::output_frame`] on `surface`, if given, and starts
+    /// reading it back. Delivers the frames whose readback finished.
+    pub fn finish(&mut self, renderer: &Renderer, surface: Option<&Arc<Surface>>) {
+        profiling::scope!("FrameStream::finish");
+
+        let (resolution, texture_format) = match self.target {
+            Some(ref target) => (target.resolution, target.format),
+            None => return,
+        };
+
+        // The slot of this frame must be free, so wait for the oldest frame
+        // if the ring is full.
+        if self.slots[self.next_slot].pending.is_some() {
+            profiling::scope!("FrameStream stall");
+            renderer.device.poll(Maintain::Wait);
+        }
+        self.deliver_ready();
+
+        let device = &renderer.device;
+        if self
+            .pipelines
+            .as_ref()
+            .map_or(true, |pipelines| pipelines.format != texture_format)
+        {
+            self.pipelines = Some(create_pipelines(device, texture_format));
+        }
+        let pipelines = self.pipelines.as_mut().unwrap();
+        let target = self.target.as_ref().unwrap();
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("frame stream"),
+        });
+
+        let mut surface_frame = surface.map(|surface| OutputFrame::Surface {
+            surface: Arc::clone(surface),
+        });
+        if let Some(frame) = surface_frame.as_mut() {
+            match frame.acquire() {
+                Ok(()) => {
+                    let bind_group = bind_group(
+                        device,
+                        pipelines,
+                        &target.view,
+                        None,
+                        [resolution.x, resolution.y, 0, 0],
+                    );
+                    let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                        label: Some("frame stream blit"),
+                        color_attachments: &[RenderPassColorAttachment {
+                            view: frame.as_view().unwrap(),
+                            resolve_target: None,
+                            ops: Operations {
+                                load: LoadOp::Clear(Color::BLACK),
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+                    rpass.set_pipeline(&pipelines.blit);
+                    rpass.set_bind_group(0, &bind_group, &[]);
+                    rpass.draw(0..3, 0..1);
+                }
+                Err(e) => {
+                    log::warn!("Could not acquire the surface to show a streamed frame: {}", e);
+                    surface_frame = None;
+                }
+            }
+        }
+
+        let (bytes_per_row, size) = match self.format {
+            StreamFormat::Native => {
+                let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+                let unpadded = resolution.x * texture_format.describe().block_size as u32;
+                let padded = (unpadded + align - 1) / align * align;
+                (padded, padded as u64 * resolution.y as u64)
+            }
+            StreamFormat::Bgra8 => (resolution.x * 4, resolution.x as u64 * resolution.y as u64 * 4),
+            StreamFormat::Nv12 => (resolution.x, resolution.x as u64 * resolution.y as u64 * 3 / 2),
+        };
+
+        let slot = &mut self.slots[self.next_slot];
+        if slot.buffer.is_none() || slot.capacity < size {
+            slot.buffer = Some(device.create_buffer(&BufferDescriptor {
+                label: Some("frame stream readback"),
+                size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }));
+            slot.capacity = size;
+        }
+        let readback = slot.buffer.as_ref().unwrap();
+
+        match self.format {
+            StreamFormat::Native => encoder.copy_texture_to_buffer(
+                ImageCopyTexture {
+                    texture: &target.texture,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                ImageCopyBuffer {
+                    buffer: readback,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: std::num::NonZeroU32::new(bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                extent(resolution),
+            ),
+            StreamFormat::Bgra8 | StreamFormat::Nv12 => {
+                if pipelines
+                    .converted
+                    .as_ref()
+                    .map_or(true, |&(_, converted_size)| converted_size < size)
+                {
+                    pipelines.converted = Some((
+                        device.create_buffer(&BufferDescriptor {
+                            label: Some("frame stream conversion"),
+                            size,
+                            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+                            mapped_at_creation: false,
+                        }),
+                        size,
+                    ));
+                }
+                let srgb = texture_format.describe().srgb as u32;
+                let chroma_offset = resolution.x * resolution.y / 4;
+                let params = [resolution.x, resolution.y, srgb, chroma_offset];
+                let converted = &pipelines.converted.as_ref().unwrap().0;
+                let bind_group = bind_group(device, pipelines, &target.view, Some(converted), params);
+
+                let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("frame stream conversion"),
+                });
+                cpass.set_bind_group(0, &bind_group, &[]);
+                let groups = |width: u32, height: u32| ((width + 7) / 8, (height + 7) / 8);
+                if self.format == StreamFormat::Bgra8 {
+                    cpass.set_pipeline(&pipelines.bgra);
+                    let (x, y) = groups(resolution.x, resolution.y);
+                    cpass.dispatch(x, y, 1);
+                } else {
+                    cpass.set_pipeline(&pipelines.luma);
+                    let (x, y) = groups(resolution.x / 4, resolution.y);
+                    cpass.dispatch(x, y, 1);
+                    cpass.set_pipeline(&pipelines.chroma);
+                    let (x, y) = groups(resolution.x / 4, resolution.y / 2);
+                    cpass.dispatch(x, y, 1);
+                }
+                drop(cpass);
+
+                encoder.copy_buffer_to_buffer(converted, 0, readback, 0, size);
+            }
+        }
+
+        renderer.queue.submit(Some(encoder.finish()));
+        if let Some(frame) = surface_frame {
+            frame.present();
+        }
+
+        slot.pending = Some(PendingFrame {
+            mapping: Box::pin(readback.slice(..size).map_async(MapMode::Read)),
+            index: self.frame_index,
+            resolution,
+            texture_format,
+            bytes_per_row,
+            size,
+        });
+        self.frame_index += 1;
+        self.next_slot = (self.next_slot + 1) % self.slots.len();
+
+        device.poll(Maintain::Poll);
+        self.deliver_ready();
+    }
+
+    /// Waits for all frames in flight and delivers them.
+    pub fn flush(&mut self, renderer: &Renderer) {
+        profiling::scope!("FrameStream::flush");
+
+        renderer.device.poll(Maintain::Wait);
+        self.deliver_ready();
+    }
+
+    /// Number of frames read back but not yet delivered.
+    pub fn frames_in_flight(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.pending.is_some()).count()
+    }
+
+    /// Delivers finished frames, oldest first, stopping at the first one
+    /// still in flight so frames are never delivered out of order.
+    fn deliver_ready(&mut self) {
+        let slot_count = self.slots.len();
+        for offset in 0..slot_count {
+            let slot = &mut self.slots[(self.next_slot + offset) % slot_count];
+            let pending = match slot.pending {
+                Some(ref mut pending) => pending,
+                None => continue,
+            };
+            match poll_now(&mut pending.mapping) {
+                Poll::Pending => break,
+                Poll::Ready(result) => {
+                    let pending = slot.pending.take().unwrap();
+                    let buffer = slot.buffer.as_ref().unwrap();
+                    if let Err(e) = result {
+                        log::error!("Could not read back streamed frame {}: {}", pending.index, e);
+                        continue;
+                    }
+                    let data = buffer.slice(..pending.size).get_mapped_range();
+                    (self.callback)(StreamedFrame {
+                        index: pending.index,
+                        resolution: pending.resolution,
+                        format: self.format,
+                        texture_format: pending.texture_format,
+                        data: &data,
+                        bytes_per_row: pending.bytes_per_row,
+                    });
+                    drop(data);
+                    buffer.unmap();
+                }
+            }
+        }
+    }
+}
+
+/// Polls a future once, without waiting for it to be woken up.
+fn poll_now(future: &mut MapFuture) -> Poll<Result<(), BufferAsyncError>> {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    // SAFETY: The waker does nothing, so its vtable trivially upholds the
+    // contract of RawWaker.
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    future.as_mut().poll(&mut Context::from_waker(&waker))
+}
+
+fn bind_group(
+    device: &Device,
+    pipelines: &StreamPipelines,
+    view: &TextureView,
+    output: Option<&Buffer>,
+    params: [u32; 4],
+) -> wgpu::BindGroup {
+    let params = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("frame stream params"),
+        contents: &params.iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<u8>>(),
+        usage: BufferUsages::UNIFORM,
+    });
+    // The blit doesn't write anything, but the layout is shared.
+    let dummy;
+    let output = match output {
+        Some(output) => output,
+        None => {
+            dummy = device.create_buffer(&BufferDescriptor {
+                label: Some("frame stream dummy output"),
+                size: 4,
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            });
+            &dummy
+        }
+    };
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("frame stream"),
+        layout: &pipelines.layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: output,
+                    offset: 0,
+                    size: None,
+                }),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: params.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+fn create_pipelines(device: &Device, format: TextureFormat) -> StreamPipelines {
+    profiling::scope!("FrameStream pipelines");
+
+    let module = device.create_shader_module(&ShaderModuleDescriptor {
+        label: Some("frame stream"),
+        source: ShaderSource::Wgsl(include_str!("stream.wgsl").into()),
+    });
+
+    let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("frame stream"),
+        entries: &[
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT | ShaderStages::COMPUTE,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(4),
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(16),
+                },
+                count: None,
+            },
+        ],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("frame stream"),
+        bind_group_layouts: &[&layout],
+        push_constant_ranges: &[],
+    });
+
+    let blit = device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("frame stream blit"),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module: &module,
+            entry_point: "vs_blit",
+            buffers: &[],
+        },
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module: &module,
+            entry_point: "fs_blit",
+            targets: &[ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::all(),
+            }],
+        }),
+        multiview: None,
+    });
+    let compute = |entry_point: &str| {
+        device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("frame stream conversion"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point,
+        })
+    };
+
+    StreamPipelines {
+        format,
+        blit,
+        bgra: compute("cs_bgra"),
+        luma: compute("cs_luma"),
+        chroma: compute("cs_chroma"),
+        layout,
+        converted: None,
+    }
+}
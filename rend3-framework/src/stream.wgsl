@@ -0,0 +1,101 @@
+struct StreamParams {
+    width: u32;
+    height: u32;
+    srgb: u32;
+    /// Offset of the chroma plane of nv12 frames, in words.
+    chroma_offset: u32;
+};
+
+struct Output {
+    data: array<u32>;
+};
+
+[[group(0), binding(0)]]
+var source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var<storage, read_write> output: Output;
+[[group(0), binding(2)]]
+var<uniform> params: StreamParams;
+
+/// Fullscreen triangle.
+[[stage(vertex)]]
+fn vs_blit([[builtin(vertex_index)]] idx: u32) -> [[builtin(position)]] vec4<f32> {
+    let uv = vec2<f32>(f32((idx << 1u) & 2u), f32(idx & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_blit([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    return textureLoad(source, vec2<i32>(position.xy), 0);
+}
+
+/// Loads a pixel, gamma encoded as it is stored.
+fn load(x: u32, y: u32) -> vec4<f32> {
+    let color = textureLoad(source, vec2<i32>(i32(x), i32(y)), 0);
+    if (params.srgb == 0u) {
+        return color;
+    }
+    let rgb = clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    let encoded = select(
+        1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055,
+        rgb * 12.92,
+        rgb <= vec3<f32>(0.0031308)
+    );
+    return vec4<f32>(encoded, color.a);
+}
+
+fn pack_bytes(bytes: vec4<f32>) -> u32 {
+    let values = vec4<u32>(clamp(round(bytes), vec4<f32>(0.0), vec4<f32>(255.0)));
+    return values.x | (values.y << 8u) | (values.z << 16u) | (values.w << 24u);
+}
+
+/// BT.709, limited range, from gamma encoded RGB.
+fn luma(color: vec4<f32>) -> f32 {
+    return 16.0 + 219.0 * dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn chroma(color: vec3<f32>) -> vec2<f32> {
+    let y = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return vec2<f32>(128.0 + 224.0 * (color.b - y) / 1.8556, 128.0 + 224.0 * (color.r - y) / 1.5748);
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn cs_bgra([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+    let color = load(id.x, id.y) * 255.0;
+    output.data[id.y * params.width + id.x] = pack_bytes(color.bgra);
+}
+
+/// Four luma samples per invocation.
+[[stage(compute), workgroup_size(8, 8)]]
+fn cs_luma([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let words_per_row = params.width / 4u;
+    if (id.x >= words_per_row || id.y >= params.height) {
+        return;
+    }
+    let x = id.x * 4u;
+    let samples = vec4<f32>(
+        luma(load(x, id.y)),
+        luma(load(x + 1u, id.y)),
+        luma(load(x + 2u, id.y)),
+        luma(load(x + 3u, id.y)),
+    );
+    output.data[id.y * words_per_row + id.x] = pack_bytes(samples);
+}
+
+/// Two interleaved chroma samples per invocation, each averaging 2x2 pixels.
+[[stage(compute), workgroup_size(8, 8)]]
+fn cs_chroma([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let words_per_row = params.width / 4u;
+    if (id.x >= words_per_row || id.y >= params.height / 2u) {
+        return;
+    }
+    let x = id.x * 4u;
+    let y = id.y * 2u;
+    let left = (load(x, y) + load(x + 1u, y) + load(x, y + 1u) + load(x + 1u, y + 1u)).rgb * 0.25;
+    let right = (load(x + 2u, y) + load(x + 3u, y) + load(x + 2u, y + 1u) + load(x + 3u, y + 1u)).rgb * 0.25;
+    let samples = vec4<f32>(chroma(left), chroma(right));
+    output.data[params.chroma_offset + id.y * words_per_row + id.x] = pack_bytes(samples);
+}
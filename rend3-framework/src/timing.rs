@@ -0,0 +1,105 @@
+use instant::Instant;
+
+/// Most fixed steps run in a single frame. If a frame takes longer than this
+/// many steps, the simulation slows down instead of trying to catch up and
+/// making the next frame even slower.
+const MAX_FIXED_STEPS_PER_FRAME: u32 = 8;
+
+/// How much each new frame contributes to the smoothed framerate.
+const FPS_SMOOTHING: f32 = 0.1;
+
+/// Timing information about the current frame, given to [`App::update`](crate::App::update).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FrameTiming {
+    /// Time since the previous frame, in seconds.
+    pub delta: f32,
+    /// Frames per second, averaged over the last few frames.
+    pub smoothed_fps: f32,
+    /// Number of frames before this one.
+    pub frame_index: u64,
+    /// With a fixed timestep, how far into the next step the frame is, from
+    /// `0.0` to `1.0`. Use this to interpolate rendered state between the last
+    /// two steps. Always zero without a fixed timestep.
+    pub fixed_step_alpha: f32,
+}
+
+impl FrameTiming {
+    /// Timing of frame `frame_index` of a sequence whose frames are exactly
+    /// `delta` seconds apart.
+    pub fn fixed(delta: f32, frame_index: u64) -> Self {
+        Self {
+            delta,
+            smoothed_fps: if delta > 0.0 { 1.0 / delta } else { 0.0 },
+            frame_index,
+            fixed_step_alpha: 0.0,
+        }
+    }
+}
+
+pub(crate) struct FrameTimer {
+    last_frame: Instant,
+    smoothed_delta: Option<f32>,
+    frame_index: u64,
+    accumulator: f32,
+}
+
+impl FrameTimer {
+    pub fn new() -> Self {
+        Self {
+            last_frame: Instant::now(),
+            smoothed_delta: None,
+            frame_index: 0,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Forgets the time spent since the last frame, so time spent suspended
+    /// doesn't show up as one long frame.
+    pub fn reset(&mut self) {
+        self.last_frame = Instant::now();
+    }
+
+    /// Starts a new frame, returning its timing and how many fixed steps
+    /// should be run during it.
+    pub fn tick(&mut self, fixed_timestep: Option<f32>) -> (FrameTiming, u32) {
+        let now = Instant::now();
+        let delta = now.duration_since(self.last_frame).as_secs_f32();
+        self.last_frame = now;
+
+        let smoothed_delta = match self.smoothed_delta {
+            Some(smoothed) => smoothed + (delta - smoothed) * FPS_SMOOTHING,
+            None => delta,
+        };
+        self.smoothed_delta = Some(smoothed_delta);
+
+        let (steps, fixed_step_alpha) = match fixed_timestep {
+            Some(step) if step > 0.0 => {
+                self.accumulator += delta;
+                let steps = (self.accumulator / step) as u32;
+                self.accumulator -= steps as f32 * step;
+                if steps > MAX_FIXED_STEPS_PER_FRAME {
+                    self.accumulator = 0.0;
+                }
+                (steps.min(MAX_FIXED_STEPS_PER_FRAME), self.accumulator / step)
+            }
+            _ => {
+                self.accumulator = 0.0;
+                (1, 0.0)
+            }
+        };
+
+        let timing = FrameTiming {
+            delta,
+            smoothed_fps: if smoothed_delta > 0.0 {
+                1.0 / smoothed_delta
+            } else {
+                0.0
+            },
+            frame_index: self.frame_index,
+            fixed_step_alpha,
+        };
+        self.frame_index += 1;
+
+        (timing, steps)
+    }
+}
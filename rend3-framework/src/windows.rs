@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use glam::UVec2;
+use rend3::{
+    types::{PresentMode, Surface, TextureFormat},
+    util::typedefs::FastHashMap,
+    InstanceAdapterDevice,
+};
+use wgpu::SurfaceError;
+
+use crate::DeviceLost;
+use winit::{
+    event_loop::EventLoopWindowTarget,
+    window::{Window, WindowBuilder, WindowId},
+};
+
+/// A window opened in addition to the main window of an [`App`](crate::App).
+pub struct AdditionalWindow {
+    // Declared before the window so it gets dropped first.
+    pub surface: Arc<Surface>,
+    pub window: Window,
+    /// Format of the surface. If it differs from the format of the main
+    /// window, the default tonemapping routine can't render to this window.
+    pub format: TextureFormat,
+    /// Size of the surface, kept up to date by the framework.
+    pub resolution: UVec2,
+}
+
+/// Windows opened by an [`App`](crate::App) in addition to its main window,
+/// e.g. detached viewports in an editor. All windows share the same
+/// [`Renderer`](rend3::Renderer).
+///
+/// Expose these to the framework through
+/// [`App::additional_windows`](crate::App::additional_windows). Events of
+/// every window go through [`App::handle_event`](crate::App::handle_event);
+/// use the window id of `RedrawRequested` to pick the surface to render to.
+/// The camera and aspect ratio of the renderer are shared between windows, so
+/// set them for each window before rendering it.
+pub struct AdditionalWindows {
+    windows: FastHashMap<WindowId, AdditionalWindow>,
+    pending: Vec<WindowBuilder>,
+    /// Follows [`App::present_mode`](crate::App::present_mode).
+    present_mode: PresentMode,
+}
+
+impl Default for AdditionalWindows {
+    fn default() -> Self {
+        Self {
+            windows: FastHashMap::default(),
+            pending: Vec::new(),
+            present_mode: PresentMode::Mailbox,
+        }
+    }
+}
+
+impl AdditionalWindows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a window to be opened. The framework creates it before handling
+    /// the next event.
+    pub fn open(&mut self, builder: WindowBuilder) {
+        self.pending.push(builder);
+    }
+
+    /// Closes a window, returning it if it was open.
+    pub fn close(&mut self, id: WindowId) -> Option<AdditionalWindow> {
+        self.windows.remove(&id)
+    }
+
+    pub fn get(&self, id: WindowId) -> Option<&AdditionalWindow> {
+        self.windows.get(&id)
+    }
+
+    pub fn contains(&self, id: WindowId) -> bool {
+        self.windows.contains_key(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AdditionalWindow> {
+        self.windows.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    pub(crate) fn create_pending<T>(&mut self, event_loop: &EventLoopWindowTarget<T>, iad: &InstanceAdapterDevice) {
+        for builder in self.pending.drain(..) {
+            let window = match builder.build(event_loop) {
+                Ok(window) => window,
+                Err(e) => {
+                    log::error!("Could not build additional window: {}", e);
+                    continue;
+                }
+            };
+            // The surface is dropped before the window, see AdditionalWindow.
+            let surface = Arc::new(unsafe { iad.instance.create_surface(&window) });
+            let format = surface
+                .get_preferred_format(&iad.adapter)
+                .unwrap_or(TextureFormat::Rgba8UnormSrgb);
+            let size = window.inner_size();
+            let resolution = UVec2::new(size.width, size.height);
+
+            rend3::configure_surface(&surface, &iad.device, format, resolution, self.present_mode);
+
+            self.windows.insert(
+                window.id(),
+                AdditionalWindow {
+                    window,
+                    surface,
+                    format,
+                    resolution,
+                },
+            );
+        }
+    }
+
+    /// Reconfigures the surface of the window after a resize, if it is one of
+    /// these windows.
+    pub(crate) fn resize(&mut self, id: WindowId, size: UVec2, iad: &InstanceAdapterDevice) {
+        let window = match self.windows.get_mut(&id) {
+            Some(window) => window,
+            None => return,
+        };
+
+        if size.x != 0 && size.y != 0 {
+            window.resolution = size;
+            rend3::configure_surface(&window.surface, &iad.device, window.format, size, self.present_mode);
+        }
+    }
+
+    /// Reconfigures or recreates the surface of a window after it failed to be
+    /// acquired. Returns true if the surface was recreated.
+    pub(crate) fn recover_surface(
+        &mut self,
+        id: WindowId,
+        iad: &InstanceAdapterDevice,
+        error: SurfaceError,
+    ) -> Result<bool, DeviceLost> {
+        let window = match self.windows.get_mut(&id) {
+            Some(window) => window,
+            None => return Ok(false),
+        };
+
+        let recreate = match error {
+            SurfaceError::Timeout => return Ok(false),
+            SurfaceError::Outdated => false,
+            SurfaceError::Lost => true,
+            SurfaceError::OutOfMemory => return Err(DeviceLost::OutOfMemory),
+        };
+
+        if recreate {
+            log::warn!("Surface of additional window lost, recreating it");
+            window.surface = Arc::new(unsafe { iad.instance.create_surface(&window.window) });
+        }
+
+        rend3::configure_surface(
+            &window.surface,
+            &iad.device,
+            window.format,
+            window.resolution,
+            self.present_mode,
+        );
+
+        Ok(recreate)
+    }
+
+    /// Reconfigures the surfaces of all windows if the present mode changed.
+    pub(crate) fn set_present_mode(&mut self, present_mode: PresentMode, iad: &InstanceAdapterDevice) {
+        if self.present_mode == present_mode {
+            return;
+        }
+        self.present_mode = present_mode;
+
+        for window in self.windows.values() {
+            rend3::configure_surface(
+                &window.surface,
+                &iad.device,
+                window.format,
+                window.resolution,
+                present_mode,
+            );
+        }
+    }
+}
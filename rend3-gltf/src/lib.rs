@@ -14,11 +14,19 @@
 //! - `KHR_punctual_lights`
 //! - `KHR_texture_transform`
 //! - `KHR_material_unlit`
+//! - `KHR_materials_emissive_strength`
+//! - `KHR_materials_anisotropy`
+//! - `KHR_materials_sheen`
 //!
 //! # Known Limitations
 //! - Only the albedo texture's transform from `KHR_texture_transform` will be
 //!   used.
-//! - Double sided materials are currently unsupported.
+//! - Only the factors of `KHR_materials_sheen` are used, not its textures.
+//!
+//! # USD
+//!
+//! With the `usd` feature, [`usd::load_usd`] loads usda layers and usdz
+//! packages into the same types as gltf scenes.
 
 use glam::{Mat3, Mat4, Quat, UVec2, Vec2, Vec3, Vec4};
 use gltf::buffer::Source;
@@ -37,6 +45,9 @@ use std::{
 };
 use thiserror::Error;
 
+#[cfg(feature = "usd")]
+pub mod usd;
+
 /// Wrapper around a T that stores an optional label.
 #[derive(Debug, Clone)]
 pub struct Labeled<T> {
@@ -137,20 +148,142 @@ pub struct Skin {
     pub joints: Vec<Labeled<Joint>>,
 }
 
+/// How the values of an [`AnimationChannel`] are interpolated between
+/// keyframes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AnimationInterpolation {
+    /// The value of the previous keyframe is held until the next keyframe.
+    Step,
+    /// Values are linearly interpolated between keyframes.
+    Linear,
+    /// Values are interpolated with a cubic hermite spline, using the tangents
+    /// stored alongside each keyframe.
+    CubicSpline,
+}
+
 #[derive(Debug)]
 pub struct AnimationChannel<T> {
     pub values: Vec<T>,
     pub times: Vec<f32>,
+    pub interpolation: AnimationInterpolation,
+    /// Incoming tangent of every keyframe. Only filled in when using
+    /// [`AnimationInterpolation::CubicSpline`].
+    pub in_tangents: Vec<T>,
+    /// Outgoing tangent of every keyframe. Only filled in when using
+    /// [`AnimationInterpolation::CubicSpline`].
+    pub out_tangents: Vec<T>,
+}
+
+impl<T> AnimationChannel<T> {
+    /// Returns `None` if there are no keyframes, or the amount of outputs
+    /// doesn't match the amount of keyframes.
+    fn new(times: Vec<f32>, interpolation: AnimationInterpolation, outputs: impl Iterator<Item = T>) -> Option<Self> {
+        let outputs: Vec<T> = outputs.collect();
+        let outputs_per_keyframe = match interpolation {
+            AnimationInterpolation::Step | AnimationInterpolation::Linear => 1,
+            AnimationInterpolation::CubicSpline => 3,
+        };
+        if times.is_empty() || outputs.len() != times.len() * outputs_per_keyframe {
+            return None;
+        }
+
+        let (values, in_tangents, out_tangents) = match interpolation {
+            AnimationInterpolation::Step | AnimationInterpolation::Linear => (outputs, Vec::new(), Vec::new()),
+            // Cubic spline keyframes are stored as (in-tangent, value, out-tangent) triples.
+            AnimationInterpolation::CubicSpline => {
+                let mut values = Vec::with_capacity(times.len());
+                let mut in_tangents = Vec::with_capacity(times.len());
+                let mut out_tangents = Vec::with_capacity(times.len());
+                for (idx, output) in outputs.into_iter().enumerate() {
+                    match idx % 3 {
+                        0 => in_tangents.push(output),
+                        1 => values.push(output),
+                        _ => out_tangents.push(output),
+                    }
+                }
+                (values, in_tangents, out_tangents)
+            }
+        };
+
+        Some(Self {
+            values,
+            times,
+            interpolation,
+            in_tangents,
+            out_tangents,
+        })
+    }
 }
 
-/// Animation data for a single joint, with translation, rotation and scale
-/// channels.
+/// Morph target weight keyframes of a node. Unlike the other channels, every
+/// keyframe holds one weight per morph target of the node's mesh.
+#[derive(Debug)]
+pub struct MorphWeightsChannel {
+    pub times: Vec<f32>,
+    pub interpolation: AnimationInterpolation,
+    /// Amount of weights stored per keyframe.
+    pub target_count: usize,
+    /// The weights of keyframe `i` are stored in
+    /// `values[i * target_count..(i + 1) * target_count]`.
+    pub values: Vec<f32>,
+    /// Incoming tangents, laid out like `values`. Only filled in when using
+    /// [`AnimationInterpolation::CubicSpline`].
+    pub in_tangents: Vec<f32>,
+    /// Outgoing tangents, laid out like `values`. Only filled in when using
+    /// [`AnimationInterpolation::CubicSpline`].
+    pub out_tangents: Vec<f32>,
+}
+
+impl MorphWeightsChannel {
+    /// Returns `None` if there are fewer outputs than keyframes, so not even
+    /// one weight per keyframe.
+    fn new(times: Vec<f32>, interpolation: AnimationInterpolation, outputs: Vec<f32>) -> Option<Self> {
+        let outputs_per_keyframe = match interpolation {
+            AnimationInterpolation::Step | AnimationInterpolation::Linear => 1,
+            AnimationInterpolation::CubicSpline => 3,
+        };
+        let target_count = outputs.len() / (times.len() * outputs_per_keyframe).max(1);
+        if target_count == 0 {
+            return None;
+        }
+
+        let (values, in_tangents, out_tangents) = match interpolation {
+            AnimationInterpolation::Step | AnimationInterpolation::Linear => (outputs, Vec::new(), Vec::new()),
+            // Cubic spline keyframes are stored as (in-tangents, values, out-tangents)
+            // triples, each containing `target_count` weights.
+            AnimationInterpolation::CubicSpline => {
+                let mut values = Vec::with_capacity(times.len() * target_count);
+                let mut in_tangents = Vec::with_capacity(times.len() * target_count);
+                let mut out_tangents = Vec::with_capacity(times.len() * target_count);
+                for keyframe in outputs.chunks_exact(target_count * 3) {
+                    in_tangents.extend_from_slice(&keyframe[..target_count]);
+                    values.extend_from_slice(&keyframe[target_count..target_count * 2]);
+                    out_tangents.extend_from_slice(&keyframe[target_count * 2..]);
+                }
+                (values, in_tangents, out_tangents)
+            }
+        };
+
+        Some(Self {
+            times,
+            interpolation,
+            target_count,
+            values,
+            in_tangents,
+            out_tangents,
+        })
+    }
+}
+
+/// Animation data for a single node, with translation, rotation, scale and
+/// morph target weight channels.
 #[derive(Debug)]
 pub struct PosRotScale {
     pub node_idx: u32,
     pub translation: Option<AnimationChannel<Vec3>>,
     pub rotation: Option<AnimationChannel<Quat>>,
     pub scale: Option<AnimationChannel<Vec3>>,
+    pub weights: Option<MorphWeightsChannel>,
 }
 
 impl PosRotScale {
@@ -160,6 +293,7 @@ impl PosRotScale {
             translation: None,
             rotation: None,
             scale: None,
+            weights: None,
         }
     }
 }
@@ -197,6 +331,9 @@ pub struct GltfSceneInstance {
     /// parents will always be visited before children. This allows avoiding
     /// recursion in several algorithms.
     pub topological_order: Vec<usize>,
+    /// Transform the root nodes were placed with, the `parent_transform`
+    /// given to [`instance_loaded_scene`].
+    pub parent_transform: Mat4,
 }
 
 /// Describes how loading gltf failed.
@@ -237,6 +374,10 @@ pub enum GltfLoadError<E: std::error::Error + 'static> {
     UnsupportedPrimitiveMode(usize, usize, gltf::mesh::Mode),
     #[error("Mesh {0} failed validation")]
     MeshValidationError(usize, #[source] MeshValidationError),
+    #[error("Mesh {0} could not be added to the renderer")]
+    MeshCreation(usize, #[source] rend3::RendererError),
+    #[error("Image {0} could not be added to the renderer")]
+    TextureCreation(usize, #[source] rend3::RendererError),
     #[error("Animation {0} channel {1} does not have keyframe times.")]
     MissingKeyframeTimes(usize, usize),
     #[error("Animation {0} channel {1} does not have keyframe values.")]
@@ -290,6 +431,9 @@ pub struct GltfLoadSettings {
     pub normal_direction: pbr::NormalTextureYDirection,
     /// Enable built-in directional lights (default true)
     pub enable_directional: bool,
+    /// Optimizations applied to every mesh before it's uploaded (default all)
+    #[cfg(feature = "meshopt")]
+    pub mesh_optimization: Option<types::MeshOptimization>,
 }
 
 impl Default for GltfLoadSettings {
@@ -299,7 +443,19 @@ impl Default for GltfLoadSettings {
             directional_light_shadow_distance: 100.0,
             normal_direction: pbr::NormalTextureYDirection::Up,
             enable_directional: true,
+            #[cfg(feature = "meshopt")]
+            mesh_optimization: Some(types::MeshOptimization::default()),
+        }
+    }
+}
+
+impl GltfLoadSettings {
+    fn apply_mesh_settings(&self, builder: types::MeshBuilder) -> types::MeshBuilder {
+        #[cfg(feature = "meshopt")]
+        if let Some(optimization) = self.mesh_optimization {
+            return builder.with_optimization(optimization);
         }
+        builder
     }
 }
 
@@ -393,9 +549,9 @@ where
     let buffers = load_buffers(file.buffers(), blob, &mut io_func).await?;
 
     let default_material = load_default_material(renderer);
-    let meshes = load_meshes(renderer, file.meshes(), &buffers)?;
+    let meshes = load_meshes(renderer, file.meshes(), &buffers, settings)?;
     let (materials, images) =
-        load_materials_and_textures(renderer, file.materials(), &buffers, settings, &mut io_func).await?;
+        load_materials_and_textures(renderer, &file.document, &buffers, settings, &mut io_func).await?;
     let skins = load_skins(file.skins(), &buffers)?;
     let animations = load_animations(file.animations(), &buffers)?;
 
@@ -586,6 +742,7 @@ pub fn instance_loaded_scene<'a, E: std::error::Error + 'static>(
     Ok(GltfSceneInstance {
         nodes: final_nodes,
         topological_order,
+        parent_transform,
     })
 }
 
@@ -636,6 +793,7 @@ pub fn load_meshes<'a, E: std::error::Error + 'static>(
     renderer: &Renderer,
     meshes: impl Iterator<Item = gltf::Mesh<'a>>,
     buffers: &[Vec<u8>],
+    settings: &GltfLoadSettings,
 ) -> Result<Vec<Labeled<Mesh>>, GltfLoadError<E>> {
     profiling::scope!("loading meshes");
     meshes
@@ -669,6 +827,8 @@ pub fn load_meshes<'a, E: std::error::Error + 'static>(
                     builder = builder.with_vertex_normals(normals.map(Vec3::from).collect())
                 }
 
+                // Primitives without tangents get MikkTSpace ones generated by
+                // the builder, as the spec asks for.
                 if let Some(tangents) = reader.read_tangents() {
                     // todo: handedness
                     builder = builder.with_vertex_tangents(tangents.map(|[x, y, z, _]| Vec3::new(x, y, z)).collect())
@@ -698,11 +858,15 @@ pub fn load_meshes<'a, E: std::error::Error + 'static>(
                     builder = builder.with_vertex_joint_weights(joint_weights.into_f32().map(Vec4::from).collect())
                 }
 
-                let mesh = builder
+                builder = settings.apply_mesh_settings(builder);
+
+                let built = builder
                     .build()
                     .map_err(|valid| GltfLoadError::MeshValidationError(mesh.index(), valid))?;
 
-                let handle = renderer.add_mesh(mesh);
+                let handle = renderer
+                    .add_mesh(built)
+                    .map_err(|e| GltfLoadError::MeshCreation(mesh.index(), e))?;
 
                 res_prims.push(MeshPrimitive {
                     handle,
@@ -751,8 +915,10 @@ fn load_skins<E: std::error::Error + 'static>(
 
 fn compute_animation_duration(channels: &HashMap<usize, PosRotScale>) -> f32 {
     fn channel_duration<T>(channel: &AnimationChannel<T>) -> f32 {
-        channel
-            .times
+        keyframes_duration(&channel.times)
+    }
+    fn keyframes_duration(times: &[f32]) -> f32 {
+        times
             .iter()
             .copied()
             .map(float_ord::FloatOrd)
@@ -766,7 +932,8 @@ fn compute_animation_duration(channels: &HashMap<usize, PosRotScale>) -> f32 {
             let m1 = ch.translation.as_ref().map(channel_duration).unwrap_or(0.0);
             let m2 = ch.rotation.as_ref().map(channel_duration).unwrap_or(0.0);
             let m3 = ch.scale.as_ref().map(channel_duration).unwrap_or(0.0);
-            m1.max(m2).max(m3)
+            let m4 = ch.weights.as_ref().map(|c| keyframes_duration(&c.times)).unwrap_or(0.0);
+            m1.max(m2).max(m3).max(m4)
         })
         .map(float_ord::FloatOrd)
         .max()
@@ -794,8 +961,14 @@ fn load_animations<E: std::error::Error + 'static>(
 
             let reader = ch.reader(|b| Some(&buffers[b.index()][..b.length()]));
 
+            let interpolation = match ch.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => AnimationInterpolation::Step,
+                gltf::animation::Interpolation::Linear => AnimationInterpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => AnimationInterpolation::CubicSpline,
+            };
+
             // In gltf, 'inputs' refers to the keyframe times
-            let times = reader
+            let times: Vec<f32> = reader
                 .read_inputs()
                 .ok_or_else(|| GltfLoadError::MissingKeyframeTimes(anim.index(), ch_idx))?
                 .collect();
@@ -807,25 +980,16 @@ fn load_animations<E: std::error::Error + 'static>(
                 .ok_or_else(|| GltfLoadError::MissingKeyframeValues(anim.index(), ch_idx))?
             {
                 gltf::animation::util::ReadOutputs::Translations(trs) => {
-                    chs.translation = Some(AnimationChannel {
-                        values: trs.map(Vec3::from).collect(),
-                        times,
-                    })
+                    chs.translation = AnimationChannel::new(times, interpolation, trs.map(Vec3::from))
                 }
                 gltf::animation::util::ReadOutputs::Rotations(rots) => {
-                    chs.rotation = Some(AnimationChannel {
-                        values: rots.into_f32().map(Quat::from_array).collect(),
-                        times,
-                    });
+                    chs.rotation = AnimationChannel::new(times, interpolation, rots.into_f32().map(Quat::from_array));
                 }
                 gltf::animation::util::ReadOutputs::Scales(scls) => {
-                    chs.scale = Some(AnimationChannel {
-                        values: scls.map(Vec3::from).collect(),
-                        times,
-                    });
+                    chs.scale = AnimationChannel::new(times, interpolation, scls.map(Vec3::from));
                 }
-                gltf::animation::util::ReadOutputs::MorphTargetWeights(_) => {
-                    // TODO
+                gltf::animation::util::ReadOutputs::MorphTargetWeights(weights) => {
+                    chs.weights = MorphWeightsChannel::new(times, interpolation, weights.into_f32().collect());
                 }
             }
         }
@@ -848,6 +1012,7 @@ pub fn load_default_material(renderer: &Renderer) -> types::MaterialHandle {
     renderer.add_material(pbr::PbrMaterial {
         albedo: pbr::AlbedoComponent::Value(Vec4::splat(1.0)),
         transparency: pbr::Transparency::Opaque,
+        double_sided: false,
         normal: pbr::NormalTexture::None,
         aomr_textures: pbr::AoMRTextures::None,
         ao_factor: Some(1.0),
@@ -857,24 +1022,30 @@ pub fn load_default_material(renderer: &Renderer) -> types::MaterialHandle {
         clearcoat_factor: Some(1.0),
         clearcoat_roughness_factor: Some(1.0),
         emissive: pbr::MaterialComponent::None,
+        emissive_intensity: None,
         reflectance: pbr::MaterialComponent::None,
-        anisotropy: pbr::MaterialComponent::None,
+        anisotropy: None,
         uv_transform0: Mat3::IDENTITY,
         uv_transform1: Mat3::IDENTITY,
         unlit: false,
         sample_type: pbr::SampleType::Linear,
+        parallax: None,
+        detail: None,
+        subsurface: None,
+        hair: None,
+        sheen: None,
+        transmission: None,
     })
 }
 
-/// Loads materials and textures from a [`gltf::Material`] iterator.
+/// Loads the materials of a [`gltf::Document`] and their textures.
 ///
-/// All binary data buffers must be provided. Call this with
-/// [`gltf::Document::materials`] as the materials argument.
+/// All binary data buffers must be provided.
 ///
 /// io_func determines how URIs are resolved into their underlying data.
 pub async fn load_materials_and_textures<F, Fut, E>(
     renderer: &Renderer,
-    materials: impl ExactSizeIterator<Item = gltf::Material<'_>>,
+    document: &gltf::Document,
     buffers: &[Vec<u8>],
     settings: &GltfLoadSettings,
     io_func: &mut F,
@@ -887,8 +1058,8 @@ where
     // profiling::scope!("loading materials and textures");
 
     let mut images = ImageMap::default();
-    let mut result = Vec::with_capacity(materials.len());
-    for material in materials {
+    let mut result = Vec::with_capacity(document.materials().len());
+    for material in document.materials() {
         // profiling::scope!("load material", material.name().unwrap_or_default());
 
         let pbr = material.pbr_metallic_roughness();
@@ -901,6 +1072,8 @@ where
         let roughness_factor = pbr.roughness_factor();
         let metallic_factor = pbr.metallic_factor();
         let metallic_roughness = pbr.metallic_roughness_texture();
+        let anisotropy = AnisotropyExtension::from_material(&material);
+        let sheen = sheen_extension(&material);
 
         let nearest = albedo
             .as_ref()
@@ -944,6 +1117,13 @@ where
                 .map(|i| load_image_cached(renderer, &mut images, i.texture().source(), false, buffers, io_func)),
         )
         .await?;
+        let anisotropy_tex = util::texture_option_resolve(
+            anisotropy
+                .as_ref()
+                .and_then(|anisotropy| document.textures().nth(anisotropy.texture?))
+                .map(|t| load_image_cached(renderer, &mut images, t.source(), false, buffers, io_func)),
+        )
+        .await?;
 
         let handle = renderer.add_material(pbr::PbrMaterial {
             albedo: match albedo_tex {
@@ -964,6 +1144,7 @@ where
                 },
                 gltf::material::AlphaMode::Blend => pbr::Transparency::Blend,
             },
+            double_sided: material.double_sided(),
             normal: match normals_tex {
                 Some(tex) if tex.format.describe().components == 2 => {
                     pbr::NormalTexture::Bicomponent(tex.handle, settings.normal_direction)
@@ -1002,6 +1183,13 @@ where
                 },
                 None => pbr::MaterialComponent::Value(Vec3::from(emissive_factor)),
             },
+            emissive_intensity: material.emissive_strength(),
+            anisotropy: anisotropy.map(|anisotropy| pbr::Anisotropy {
+                strength: anisotropy.strength,
+                rotation: anisotropy.rotation,
+                texture: util::extract_handle(anisotropy_tex),
+            }),
+            sheen,
             uv_transform0: uv_transform,
             uv_transform1: uv_transform,
             unlit: material.unlit(),
@@ -1015,6 +1203,47 @@ where
     Ok((result, images))
 }
 
+/// `KHR_materials_anisotropy`, which the gltf crate doesn't parse.
+struct AnisotropyExtension {
+    strength: f32,
+    rotation: f32,
+    /// Index of the texture.
+    texture: Option<usize>,
+}
+
+impl AnisotropyExtension {
+    fn from_material(material: &gltf::Material<'_>) -> Option<Self> {
+        let extension = material.extension_value("KHR_materials_anisotropy")?;
+        let float = |key| extension.get(key).and_then(gltf::json::Value::as_f64).unwrap_or(0.0) as f32;
+        Some(Self {
+            strength: float("anisotropyStrength"),
+            rotation: float("anisotropyRotation"),
+            texture: extension
+                .get("anisotropyTexture")
+                .and_then(|texture| texture.get("index"))
+                .and_then(gltf::json::Value::as_u64)
+                .map(|index| index as usize),
+        })
+    }
+}
+
+/// The factors of `KHR_materials_sheen`, which the gltf crate doesn't parse.
+fn sheen_extension(material: &gltf::Material<'_>) -> Option<pbr::Sheen> {
+    let extension = material.extension_value("KHR_materials_sheen")?;
+    let color = extension
+        .get("sheenColorFactor")
+        .and_then(gltf::json::Value::as_array)
+        .map_or(Vec3::ZERO, |color| {
+            let channel = |i: usize| color.get(i).and_then(gltf::json::Value::as_f64).unwrap_or(0.0) as f32;
+            Vec3::new(channel(0), channel(1), channel(2))
+        });
+    let roughness = extension
+        .get("sheenRoughnessFactor")
+        .and_then(gltf::json::Value::as_f64)
+        .unwrap_or(0.0) as f32;
+    Some(pbr::Sheen { color, roughness })
+}
+
 /// Loads a single image from a [`gltf::Image`], with caching.
 ///
 /// Uses the given ImageMap as a cache.
@@ -1127,6 +1356,7 @@ where
         texture = Some(types::Texture {
             label: image.name().map(str::to_owned),
             format,
+            color_space: types::ColorSpace::FromFormat,
             size: UVec2::new(header.pixel_width, header.pixel_height),
             data,
             mip_count: if generate {
@@ -1180,6 +1410,7 @@ where
             texture = Some(types::Texture {
                 label: image.name().map(str::to_owned),
                 format,
+                color_space: types::ColorSpace::FromFormat,
                 size: UVec2::new(dds.get_width(), dds.get_height()),
                 data: data.to_vec(),
                 mip_count: if generate {
@@ -1206,6 +1437,7 @@ where
         texture = Some(types::Texture {
             label: image.name().map(str::to_owned),
             format,
+            color_space: types::ColorSpace::FromFormat,
             size,
             data,
             mip_count: types::MipmapCount::Maximum,
@@ -1215,7 +1447,9 @@ where
 
     let texture = texture.unwrap();
     let format = texture.format;
-    let handle = renderer.add_texture_2d(texture);
+    let handle = renderer
+        .add_texture_2d(texture)
+        .map_err(|e| GltfLoadError::TextureCreation(image.index(), e))?;
 
     Ok(Labeled::new(Texture { handle, format }, image.name()))
 }
@@ -1771,3 +2005,40 @@ pub mod util {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{AnimationChannel, AnimationInterpolation, MorphWeightsChannel};
+
+    #[test]
+    fn morph_weights_split_cubic_spline_keyframes() {
+        // Two keyframes of two targets, as (in-tangents, values, out-tangents).
+        let outputs = (0..12).map(|i| i as f32).collect();
+        let channel = MorphWeightsChannel::new(vec![0.0, 1.0], AnimationInterpolation::CubicSpline, outputs).unwrap();
+        assert_eq!(channel.target_count, 2);
+        assert_eq!(channel.in_tangents, [0.0, 1.0, 6.0, 7.0]);
+        assert_eq!(channel.values, [2.0, 3.0, 8.0, 9.0]);
+        assert_eq!(channel.out_tangents, [4.0, 5.0, 10.0, 11.0]);
+
+        // Fewer outputs than a triple per keyframe.
+        let outputs = vec![0.0; 5];
+        assert!(MorphWeightsChannel::new(vec![0.0, 1.0], AnimationInterpolation::CubicSpline, outputs).is_none());
+        assert!(MorphWeightsChannel::new(vec![0.0, 1.0], AnimationInterpolation::Linear, vec![0.0]).is_none());
+    }
+
+    #[test]
+    fn animation_channels_split_cubic_spline_keyframes() {
+        let outputs = (0..6).map(|i| i as f32);
+        let channel = AnimationChannel::new(vec![0.0, 1.0], AnimationInterpolation::CubicSpline, outputs).unwrap();
+        assert_eq!(channel.in_tangents, [0.0, 3.0]);
+        assert_eq!(channel.values, [1.0, 4.0]);
+        assert_eq!(channel.out_tangents, [2.0, 5.0]);
+
+        // A truncated output accessor.
+        let outputs = (0..5).map(|i| i as f32);
+        assert!(AnimationChannel::new(vec![0.0, 1.0], AnimationInterpolation::CubicSpline, outputs).is_none());
+        let outputs = [0.0f32].into_iter();
+        assert!(AnimationChannel::new(vec![0.0, 1.0], AnimationInterpolation::Linear, outputs).is_none());
+        assert!(AnimationChannel::<f32>::new(Vec::new(), AnimationInterpolation::Step, [].into_iter()).is_none());
+    }
+}
@@ -0,0 +1,1033 @@
+//! USD scene loader, behind the `usd` feature.
+//!
+//! Loads text `.usda` layers and `.usdz` packages whose root layer is text
+//! into the same [`LoadedGltfScene`] and [`GltfSceneInstance`] as gltf
+//! scenes, so the rest of an application can't tell them apart. See
+//! [`load_usd`].
+//!
+//! # Supported Schemas
+//! - `Xform`, `Scope` and untyped prims, with every kind of `xformOp`.
+//! - `Mesh`, with normals, texture coordinates and `GeomSubset` material
+//!   bindings. Faces with more than three vertices are triangulated as fans.
+//! - `Material` with a `UsdPreviewSurface` shader, whose inputs are values or
+//!   `UsdUVTexture` shaders.
+//! - `DistantLight`
+//!
+//! # Known Limitations
+//! - Binary `.usdc` layers can't be read.
+//! - Nothing is composed: sublayers, references, payloads, inherits and
+//!   variants are ignored, as are `over` and `class` prims.
+//! - Only the earliest time sample of animated attributes is used.
+//! - Texture transforms and wrap modes of `UsdUVTexture` are ignored, and
+//!   metallic and roughness textures are only used in the channel layouts of
+//!   [`pbr::AoMRTextures`].
+
+use std::future::Future;
+
+use glam::{Mat4, Quat, UVec2, Vec2, Vec3, Vec4};
+use image::GenericImageView;
+use rend3::{
+    types::{self, Handedness, MeshValidationError},
+    util::typedefs::{FastHashMap, SsoString},
+    Renderer, RendererError,
+};
+use rend3_routine::pbr;
+use thiserror::Error;
+
+use crate::{
+    load_default_material, util, GltfLoadSettings, GltfSceneInstance, ImageKey, ImageMap, Labeled, LoadedGltfScene,
+    Mesh, MeshPrimitive, Node, Object, Texture,
+};
+
+mod usda;
+mod usdz;
+
+use usda::{Layer, Prim, Value};
+use usdz::Package;
+
+/// Magic number binary usdc layers start with.
+const USDC_MAGIC: &[u8] = b"PXR-USDC";
+
+/// Describes where parsing a usda layer failed.
+#[derive(Debug, Error)]
+#[error("Line {line}: {message}")]
+pub struct UsdParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Describes how loading a USD scene failed.
+#[derive(Debug, Error)]
+pub enum UsdLoadError<E: std::error::Error + 'static> {
+    #[error("Usda layer failed to parse")]
+    Parse(#[from] UsdParseError),
+    #[error("Binary usdc layers are not supported, only usda")]
+    Usdc,
+    #[error("Usdz package is malformed")]
+    MalformedUsdz,
+    #[error("File {0} of the usdz package is compressed, which usdz forbids")]
+    CompressedUsdz(String),
+    #[error("File {0} is not in the usdz package")]
+    MissingUsdzFile(SsoString),
+    #[error("Texture {0} failed to be loaded from the fs")]
+    TextureIo(SsoString, #[source] E),
+    #[error("Texture {0} failed to be loaded as an image")]
+    TextureDecode(SsoString, #[source] image::ImageError),
+    #[error("Texture {0} could not be added to the renderer")]
+    TextureCreation(SsoString, #[source] RendererError),
+    #[error("Mesh {0} has face vertex counts or indices out of range")]
+    InvalidTopology(SsoString),
+    #[error("Mesh {0} failed validation")]
+    MeshValidation(SsoString, #[source] MeshValidationError),
+    #[error("Mesh {0} could not be added to the renderer")]
+    MeshCreation(SsoString, #[source] RendererError),
+}
+
+/// Load a given usda layer or usdz package into the renderer's world.
+///
+/// `io_func` resolves the paths of textures outside of usdz packages, see
+/// [`load_gltf`](crate::load_gltf). The root layer's `upAxis` and
+/// `metersPerUnit` are applied on top of the scale of `settings`, so scenes
+/// come out Y up and in meters.
+///
+/// **Must** keep the [`LoadedGltfScene`] alive for the scene to remain.
+pub async fn load_usd<F, Fut, E>(
+    renderer: &Renderer,
+    data: &[u8],
+    settings: &GltfLoadSettings,
+    io_func: F,
+) -> Result<(LoadedGltfScene, GltfSceneInstance), UsdLoadError<E>>
+where
+    F: FnMut(SsoString) -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, E>>,
+    E: std::error::Error + 'static,
+{
+    profiling::scope!("loading usd");
+
+    let package = match Package::is_usdz(data) {
+        true => Some(Package::parse(data)?),
+        false => None,
+    };
+    let root = match &package {
+        Some(package) => package.files.first().ok_or(UsdLoadError::MalformedUsdz)?.1,
+        None => data,
+    };
+    if root.starts_with(USDC_MAGIC) {
+        return Err(UsdLoadError::Usdc);
+    }
+    let source = std::str::from_utf8(root).map_err(|_| UsdParseError {
+        line: 0,
+        message: "layer is not valid utf-8".into(),
+    })?;
+    let layer = {
+        profiling::scope!("parsing usda");
+        usda::parse(source)?
+    };
+
+    let mut loader = Loader {
+        renderer,
+        settings,
+        package: package.as_ref(),
+        io_func,
+        prims: FastHashMap::default(),
+        loaded: LoadedGltfScene {
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            default_material: load_default_material(renderer),
+            images: ImageMap::default(),
+            skins: Vec::new(),
+            animations: Vec::new(),
+        },
+        material_indices: FastHashMap::default(),
+        image_indices: FastHashMap::default(),
+    };
+    let entries = loader.index_prims(&layer);
+
+    let root_transform = stage_transform(&layer, settings, renderer.handedness);
+    let mut nodes: Vec<Labeled<Node>> = Vec::with_capacity(entries.len());
+    let mut world_transforms = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        let prim = entry.prim;
+        let (local_transform, resets) = local_transform(prim);
+        let parent_transform = match entry.parent {
+            Some(parent) if !resets => world_transforms[parent],
+            _ => root_transform,
+        };
+        let transform = parent_transform * local_transform;
+        world_transforms.push(transform);
+
+        let object = match prim.type_name.as_deref() {
+            Some("Mesh") => Some(loader.add_mesh(entry, transform).await?),
+            _ => None,
+        };
+        let directional_light = match prim.type_name.as_deref() {
+            Some("DistantLight") if settings.enable_directional => {
+                let float = |name: &str| prim.value(name).and_then(Value::as_f32);
+                let color = prim.value("inputs:color").map_or(Vec3::ONE, vec3);
+                let exposure = float("inputs:exposure").unwrap_or(0.0);
+                Some(renderer.add_directional_light(types::DirectionalLight {
+                    color,
+                    intensity: float("inputs:intensity").unwrap_or(1.0) * exposure.exp2(),
+                    direction: transform.transform_vector3(-Vec3::Z).normalize(),
+                    distance: settings.directional_light_shadow_distance,
+                }))
+            }
+            _ => None,
+        };
+
+        if let Some(parent) = entry.parent {
+            let index = nodes.len();
+            nodes[parent].inner.children.push(index);
+        }
+        nodes.push(Labeled::new(
+            Node {
+                parent: entry.parent,
+                children: Vec::new(),
+                local_transform,
+                object,
+                directional_light,
+            },
+            Some(prim.name.as_str()),
+        ));
+    }
+
+    let instance = GltfSceneInstance {
+        topological_order: (0..nodes.len()).collect(),
+        nodes,
+        parent_transform: root_transform,
+    };
+    Ok((loader.loaded, instance))
+}
+
+/// Maps the stage's up axis and units to Y up meters, and its right handed
+/// coordinates to the renderer's.
+fn stage_transform(layer: &Layer, settings: &GltfLoadSettings, handedness: Handedness) -> Mat4 {
+    let meters_per_unit = layer
+        .metadata
+        .get("metersPerUnit")
+        .and_then(Value::as_f32)
+        // The default of USD is centimeters.
+        .unwrap_or(0.01);
+    let scale = settings.scale * meters_per_unit;
+    let up = match layer.metadata.get("upAxis").and_then(Value::as_str) {
+        Some("Z") => Mat4::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+        _ => Mat4::IDENTITY,
+    };
+    let scale = Mat4::from_scale(Vec3::new(
+        scale,
+        scale,
+        if handedness == Handedness::Left { -scale } else { scale },
+    ));
+    scale * up
+}
+
+/// A prim which becomes a node, in depth first order.
+struct Entry<'a> {
+    prim: &'a Prim,
+    path: String,
+    parent: Option<usize>,
+    /// Path of the material bound to the prim or its closest ancestor.
+    material: Option<String>,
+}
+
+struct Loader<'a, F> {
+    renderer: &'a Renderer,
+    settings: &'a GltfLoadSettings,
+    package: Option<&'a Package<'a>>,
+    io_func: F,
+    /// Every prim by path, including those which aren't nodes.
+    prims: FastHashMap<String, &'a Prim>,
+    loaded: LoadedGltfScene,
+    /// Index in `loaded.materials` of materials by path and double
+    /// sidedness, or display color of meshes without one.
+    material_indices: FastHashMap<(String, bool), usize>,
+    /// Index in the [`ImageKey`]s of textures by path.
+    image_indices: FastHashMap<String, usize>,
+}
+
+impl<'a, F, Fut, E> Loader<'a, F>
+where
+    F: FnMut(SsoString) -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, E>>,
+    E: std::error::Error + 'static,
+{
+    /// Indexes every prim of `layer` by path, returning the ones which become
+    /// nodes, parents first.
+    fn index_prims(&mut self, layer: &'a Layer) -> Vec<Entry<'a>> {
+        let mut entries = Vec::new();
+        // Prims to visit, with the index of their parent entry, its path and
+        // its material, last first.
+        let mut stack: Vec<(&'a Prim, Option<usize>, String, Option<String>)> = layer
+            .prims
+            .iter()
+            .rev()
+            .map(|prim| (prim, None, String::new(), None))
+            .collect();
+        while let Some((prim, parent, parent_path, parent_material)) = stack.pop() {
+            let path = format!("{}/{}", parent_path, prim.name);
+            self.prims.insert(path.clone(), prim);
+
+            let inactive = prim.metadata.get("active").and_then(Value::as_f32) == Some(0.0);
+            let is_node = prim.specifier == "def"
+                && !inactive
+                && !matches!(
+                    prim.type_name.as_deref(),
+                    Some("Material" | "Shader" | "NodeGraph" | "GeomSubset")
+                );
+            let material = material_binding(prim, &path).or(parent_material);
+
+            let index = match is_node {
+                true => {
+                    entries.push(Entry {
+                        prim,
+                        path: path.clone(),
+                        parent,
+                        material: material.clone(),
+                    });
+                    Some(entries.len() - 1)
+                }
+                false => None,
+            };
+            for child in prim.children.iter().rev() {
+                match is_node {
+                    true => stack.push((child, index, path.clone(), material.clone())),
+                    // Children of other prims are only indexed.
+                    false => self.index_subtree(child, &path),
+                }
+            }
+        }
+        entries
+    }
+
+    fn index_subtree(&mut self, prim: &'a Prim, parent_path: &str) {
+        let path = format!("{}/{}", parent_path, prim.name);
+        for child in &prim.children {
+            self.index_subtree(child, &path);
+        }
+        self.prims.insert(path, prim);
+    }
+
+    /// Adds the mesh of `entry` and objects using it at `transform`.
+    async fn add_mesh(&mut self, entry: &Entry<'a>, transform: Mat4) -> Result<Labeled<Object>, UsdLoadError<E>> {
+        let prim = entry.prim;
+        let label = SsoString::from(entry.path.as_str());
+        let geometry = MeshGeometry::from_prim(prim).ok_or_else(|| UsdLoadError::InvalidTopology(label.clone()))?;
+        let double_sided = prim.value("doubleSided").and_then(Value::as_f32).unwrap_or(0.0) != 0.0;
+
+        // Faces of each material, in the order materials first appear.
+        let mut face_materials = vec![entry.material.clone(); geometry.face_count()];
+        for subset in &prim.children {
+            let is_material_subset = subset.type_name.as_deref() == Some("GeomSubset")
+                && subset.value("familyName").and_then(Value::as_str) == Some("materialBind");
+            let subset_path = format!("{}/{}", entry.path, subset.name);
+            if let (true, Some(material)) = (is_material_subset, material_binding(subset, &subset_path)) {
+                for face in subset.value("indices").map(ints).unwrap_or_default() {
+                    if let Some(face_material) = face_materials.get_mut(face) {
+                        *face_material = Some(material.clone());
+                    }
+                }
+            }
+        }
+        let mut groups: Vec<(Option<String>, Vec<u32>)> = Vec::new();
+        for (face, material) in face_materials.into_iter().enumerate() {
+            let group = match groups
+                .iter()
+                .position(|(group_material, _)| *group_material == material)
+            {
+                Some(group) => group,
+                None => {
+                    groups.push((material, Vec::new()));
+                    groups.len() - 1
+                }
+            };
+            geometry.triangulate_face(face, &mut groups[group].1);
+        }
+
+        let display_color = prim.value("primvars:displayColor").and_then(|value| match value {
+            Value::Array(colors) => colors.first().map(vec3),
+            value => Some(vec3(value)),
+        });
+
+        let mut primitives = Vec::with_capacity(groups.len());
+        for (material, indices) in groups {
+            let material = match material {
+                Some(path) => self.material(&path, double_sided).await?,
+                None => display_color.map(|color| self.display_color_material(color, double_sided)),
+            };
+
+            let mut builder = types::MeshBuilder::new(geometry.positions.clone(), self.renderer.handedness);
+            // USD is right handed, like gltf.
+            if self.renderer.handedness == Handedness::Left {
+                builder = builder.with_flip_winding_order();
+            }
+            if let Some(normals) = &geometry.normals {
+                builder = builder.with_vertex_normals(normals.clone());
+            }
+            if let Some(uvs) = &geometry.uvs {
+                builder = builder.with_vertex_uv0(uvs.clone());
+            }
+            builder = self.settings.apply_mesh_settings(builder);
+            let mesh = builder
+                .with_indices(indices)
+                .build()
+                .map_err(|e| UsdLoadError::MeshValidation(label.clone(), e))?;
+            let handle = self
+                .renderer
+                .add_mesh(mesh)
+                .map_err(|e| UsdLoadError::MeshCreation(label.clone(), e))?;
+            primitives.push(MeshPrimitive { handle, material });
+        }
+
+        let objects = primitives
+            .iter()
+            .map(|primitive| {
+                let material = primitive.material.map_or(&self.loaded.default_material, |index| {
+                    &self.loaded.materials[index].inner
+                });
+                self.renderer.add_object(types::Object {
+                    mesh_kind: types::ObjectMeshKind::Static(primitive.handle.clone()),
+                    material: material.clone(),
+                    transform,
+                })
+            })
+            .collect();
+        self.loaded
+            .meshes
+            .push(Labeled::new(Mesh { primitives }, Some(prim.name.as_str())));
+
+        Ok(Labeled::new(
+            Object {
+                primitives: objects,
+                armature: None,
+            },
+            Some(prim.name.as_str()),
+        ))
+    }
+
+    fn display_color_material(&mut self, color: Vec3, double_sided: bool) -> usize {
+        let key = (format!("displayColor {:?}", color.to_array()), double_sided);
+        if let Some(&index) = self.material_indices.get(&key) {
+            return index;
+        }
+        let handle = self.renderer.add_material(pbr::PbrMaterial {
+            albedo: pbr::AlbedoComponent::Value(color.extend(1.0)),
+            double_sided,
+            ..pbr::PbrMaterial::default()
+        });
+        self.loaded.materials.push(Labeled::new(handle, None));
+        let index = self.loaded.materials.len() - 1;
+        self.material_indices.insert(key, index);
+        index
+    }
+
+    /// Index of the material at `path`, loading it the first time. Returns
+    /// `None` if it doesn't exist or has no `UsdPreviewSurface`.
+    async fn material(&mut self, path: &str, double_sided: bool) -> Result<Option<usize>, UsdLoadError<E>> {
+        let key = (path.to_owned(), double_sided);
+        if let Some(&index) = self.material_indices.get(&key) {
+            return Ok(Some(index));
+        }
+        let shader = match self.surface_shader(path) {
+            Some(shader) => shader,
+            None => {
+                log::warn!("Material {} has no UsdPreviewSurface shader", path);
+                return Ok(None);
+            }
+        };
+
+        let diffuse = self.input(shader, "inputs:diffuseColor");
+        let opacity = self.input(shader, "inputs:opacity");
+        let metallic = self.input(shader, "inputs:metallic");
+        let roughness = self.input(shader, "inputs:roughness");
+        let emissive = self.input(shader, "inputs:emissiveColor");
+        let normal = self.input(shader, "inputs:normal");
+        let occlusion = self.input(shader, "inputs:occlusion");
+        let clearcoat = self.input(shader, "inputs:clearcoat");
+        let clearcoat_roughness = self.input(shader, "inputs:clearcoatRoughness");
+        let opacity_threshold = shader
+            .value("inputs:opacityThreshold")
+            .and_then(Value::as_f32)
+            .unwrap_or(0.0);
+
+        let albedo_value = diffuse.value().map_or(Vec3::splat(0.18), vec3);
+        let opacity_value = opacity.value().and_then(Value::as_f32).unwrap_or(1.0);
+        let albedo = match self.texture(&diffuse, true).await? {
+            Some(texture) => pbr::AlbedoComponent::TextureValue {
+                texture: texture.handle,
+                value: Vec4::new(1.0, 1.0, 1.0, opacity_value),
+            },
+            None => pbr::AlbedoComponent::Value(albedo_value.extend(opacity_value)),
+        };
+        // Opacity is only read from the alpha of the diffuse texture.
+        let textured_opacity = matches!((&opacity, &diffuse), (
+                Input::Texture { file, channel, .. },
+                Input::Texture { file: diffuse_file, .. },
+            ) if file == diffuse_file && channel == "a");
+        let transparency = match (opacity_value < 1.0 || textured_opacity, opacity_threshold > 0.0) {
+            (_, true) => pbr::Transparency::Cutout {
+                cutout: opacity_threshold,
+            },
+            (true, false) => pbr::Transparency::Blend,
+            (false, false) => pbr::Transparency::Opaque,
+        };
+
+        let occlusion_texture = util::extract_handle(self.texture(&occlusion, false).await?);
+        let aomr_textures = match (&roughness, &metallic) {
+            (
+                Input::Texture {
+                    file: roughness_file,
+                    channel: roughness_channel,
+                    ..
+                },
+                Input::Texture {
+                    file: metallic_file,
+                    channel: metallic_channel,
+                    ..
+                },
+            ) => {
+                let roughness_texture = util::extract_handle(self.texture(&roughness, false).await?);
+                match (roughness_channel.as_str(), metallic_channel.as_str()) {
+                    ("g", "b") if roughness_file == metallic_file => pbr::AoMRTextures::SwizzledSplit {
+                        ao_texture: occlusion_texture,
+                        mr_texture: roughness_texture,
+                    },
+                    ("r", "r") => pbr::AoMRTextures::BWSplit {
+                        ao_texture: occlusion_texture,
+                        m_texture: util::extract_handle(self.texture(&metallic, false).await?),
+                        r_texture: roughness_texture,
+                    },
+                    _ => {
+                        log::warn!("Material {} has metallic and roughness textures rend3 can't read", path);
+                        pbr::AoMRTextures::None
+                    }
+                }
+            }
+            _ => pbr::AoMRTextures::SwizzledSplit {
+                ao_texture: occlusion_texture,
+                mr_texture: None,
+            },
+        };
+        let mr_textured = aomr_textures.to_roughness_texture().is_some();
+        let factor = |input: &Input, default: f32| match input {
+            // Textures are multiplied by the factors.
+            Input::Texture { .. } if mr_textured => 1.0,
+            input => input.value().and_then(Value::as_f32).unwrap_or(default),
+        };
+        let metallic_factor = factor(&metallic, 0.0);
+        let roughness_factor = factor(&roughness, 0.5);
+
+        let emissive_value = emissive.value().map_or(Vec3::ZERO, vec3);
+        let emissive = match self.texture(&emissive, true).await? {
+            Some(texture) => pbr::MaterialComponent::TextureValue {
+                texture: texture.handle,
+                value: Vec3::ONE,
+            },
+            None => pbr::MaterialComponent::Value(emissive_value),
+        };
+        let normal = match self.texture(&normal, false).await? {
+            Some(texture) => pbr::NormalTexture::Tricomponent(texture.handle, self.settings.normal_direction),
+            None => pbr::NormalTexture::None,
+        };
+
+        let handle = self.renderer.add_material(pbr::PbrMaterial {
+            albedo,
+            transparency,
+            double_sided,
+            normal,
+            aomr_textures,
+            metallic_factor: Some(metallic_factor),
+            roughness_factor: Some(roughness_factor),
+            clearcoat_factor: Some(factor(&clearcoat, 0.0)),
+            clearcoat_roughness_factor: Some(factor(&clearcoat_roughness, 0.01)),
+            emissive,
+            ..pbr::PbrMaterial::default()
+        });
+        let name = path.rsplit('/').next();
+        self.loaded.materials.push(Labeled::new(handle, name));
+        let index = self.loaded.materials.len() - 1;
+        self.material_indices.insert(key, index);
+        Ok(Some(index))
+    }
+
+    /// The `UsdPreviewSurface` of the material at `path`, the one its surface
+    /// output is connected to, or else the first one it contains.
+    fn surface_shader(&self, path: &str) -> Option<&'a Prim> {
+        let material = self.prims.get(path)?;
+        let is_preview_surface =
+            |prim: &Prim| prim.value("info:id").and_then(Value::as_str) == Some("UsdPreviewSurface");
+        let connected = material
+            .attributes
+            .get("outputs:surface")
+            .and_then(|output| output.connection.as_deref())
+            .and_then(|connection| self.prims.get(connection_prim(connection)))
+            .copied()
+            .filter(|prim| is_preview_surface(prim));
+
+        connected.or_else(|| {
+            let mut stack: Vec<&Prim> = material.children.iter().collect();
+            while let Some(prim) = stack.pop() {
+                if is_preview_surface(prim) {
+                    return Some(prim);
+                }
+                stack.extend(&prim.children);
+            }
+            None
+        })
+    }
+
+    /// Follows the connections of the input `name` of `shader` to a value or
+    /// a texture.
+    fn input(&self, shader: &'a Prim, name: &str) -> Input {
+        let mut prim = shader;
+        let mut name = name.to_owned();
+        // Connections go through the interface inputs of materials and node
+        // graphs, give up on cycles.
+        for _ in 0..16 {
+            let attribute = match prim.attributes.get(&name) {
+                Some(attribute) => attribute,
+                None => return Input::None,
+            };
+            let connection = match &attribute.connection {
+                Some(connection) => connection,
+                None => return attribute.value.clone().map_or(Input::None, Input::Value),
+            };
+            let (target_path, output) = match connection.rsplit_once('.') {
+                Some(split) => split,
+                None => return Input::None,
+            };
+            let target = match self.prims.get(target_path) {
+                Some(&target) => target,
+                None => return Input::None,
+            };
+            if target.value("info:id").and_then(Value::as_str) == Some("UsdUVTexture") {
+                return match target.value("inputs:file").and_then(Value::as_str) {
+                    Some(file) => Input::Texture {
+                        file: file.to_owned(),
+                        channel: output.trim_start_matches("outputs:").to_owned(),
+                        srgb: match target.value("inputs:sourceColorSpace").and_then(Value::as_str) {
+                            Some("raw") => Some(false),
+                            Some("sRGB") => Some(true),
+                            _ => None,
+                        },
+                    },
+                    None => Input::None,
+                };
+            }
+            prim = target;
+            name = output.to_owned();
+        }
+        Input::None
+    }
+
+    /// Loads the texture of `input`, if it is one.
+    async fn texture(&mut self, input: &Input, srgb: bool) -> Result<Option<Texture>, UsdLoadError<E>> {
+        let (file, srgb) = match input {
+            Input::Texture { file, srgb: source, .. } => (file, source.unwrap_or(srgb)),
+            _ => return Ok(None),
+        };
+        let next_index = self.image_indices.len();
+        let index = *self.image_indices.entry(file.clone()).or_insert(next_index);
+        let key = ImageKey { index, srgb };
+        if let Some(texture) = self.loaded.images.get(&key) {
+            return Ok(Some(texture.inner.clone()));
+        }
+
+        let uri = SsoString::from(file.as_str());
+        let data = match self.package {
+            Some(package) => package
+                .file(file)
+                .ok_or_else(|| UsdLoadError::MissingUsdzFile(uri.clone()))?
+                .to_vec(),
+            None => (self.io_func)(uri.clone())
+                .await
+                .map_err(|e| UsdLoadError::TextureIo(uri.clone(), e))?,
+        };
+        let image = {
+            profiling::scope!("decoding image");
+            image::load_from_memory(&data).map_err(|e| UsdLoadError::TextureDecode(uri.clone(), e))?
+        };
+        let size = UVec2::new(image.width(), image.height());
+        let (data, format) = util::convert_dynamic_image(image, srgb);
+        let handle = self
+            .renderer
+            .add_texture_2d(types::Texture {
+                label: Some(file.clone()),
+                data,
+                format,
+                color_space: types::ColorSpace::FromFormat,
+                size,
+                mip_count: types::MipmapCount::Maximum,
+                mip_source: types::MipmapSource::Generated,
+            })
+            .map_err(|e| UsdLoadError::TextureCreation(uri, e))?;
+
+        let texture = Texture { handle, format };
+        self.loaded
+            .images
+            .insert(key, Labeled::new(texture.clone(), Some(file.as_str())));
+        Ok(Some(texture))
+    }
+}
+
+/// What an input of a shader is connected to.
+enum Input {
+    None,
+    Value(Value),
+    Texture {
+        file: String,
+        /// Output of the texture, like `rgb` or `r`.
+        channel: String,
+        /// Color space authored on the texture.
+        srgb: Option<bool>,
+    },
+}
+
+impl Input {
+    fn value(&self) -> Option<&Value> {
+        match self {
+            Input::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Geometry of a mesh prim, with a vertex per point, or per face vertex when
+/// normals or texture coordinates vary across faces.
+struct MeshGeometry {
+    positions: Vec<Vec3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<Vec2>>,
+    /// Index of the first face vertex of each face, and one past the last.
+    face_starts: Vec<usize>,
+    /// Vertex of each face vertex.
+    face_vertices: Vec<u32>,
+    left_handed: bool,
+}
+
+impl MeshGeometry {
+    fn from_prim(prim: &Prim) -> Option<Self> {
+        let points: Vec<Vec3> = prim
+            .value("points")
+            .and_then(Value::as_array)?
+            .iter()
+            .map(vec3)
+            .collect();
+        let counts = prim.value("faceVertexCounts").map(ints).unwrap_or_default();
+        let indices = prim.value("faceVertexIndices").map(ints).unwrap_or_default();
+
+        let mut face_starts = Vec::with_capacity(counts.len() + 1);
+        face_starts.push(0);
+        for count in counts {
+            let start: usize = *face_starts.last()?;
+            face_starts.push(start.checked_add(count)?);
+        }
+        if *face_starts.last()? != indices.len() || indices.iter().any(|&index| index >= points.len()) {
+            return None;
+        }
+        let face_of_vertex: Vec<usize> = face_starts
+            .windows(2)
+            .enumerate()
+            .flat_map(|(face, range)| std::iter::repeat(face).take(range[1] - range[0]))
+            .collect();
+
+        let normals = Primvar::from_prim(prim, "normals").or_else(|| Primvar::from_prim(prim, "primvars:normals"));
+        let uvs = Primvar::from_prim(prim, "primvars:st").or_else(|| {
+            let name = prim.attributes.iter().find_map(|(name, attribute)| {
+                (name.starts_with("primvars:") && attribute.type_name.starts_with("texCoord2")).then(|| name)
+            })?;
+            Primvar::from_prim(prim, name)
+        });
+
+        let per_face_vertex = [&normals, &uvs].iter().any(|primvar| {
+            primvar.as_ref().map_or(false, |primvar| {
+                matches!(
+                    primvar.interpolation,
+                    Interpolation::Uniform | Interpolation::FaceVarying
+                )
+            })
+        });
+        let (vertex_count, face_vertices) = match per_face_vertex {
+            true => (indices.len(), (0..indices.len() as u32).collect()),
+            false => (points.len(), indices.iter().map(|&index| index as u32).collect()),
+        };
+        // Face, face vertex and point of each vertex.
+        let vertex = |vertex: usize| match per_face_vertex {
+            true => (face_of_vertex[vertex], vertex, indices[vertex]),
+            false => (0, 0, vertex),
+        };
+
+        let positions = (0..vertex_count).map(|v| points[vertex(v).2]).collect();
+        let normals = normals.map(|normals| {
+            (0..vertex_count)
+                .map(|v| {
+                    let (face, face_vertex, point) = vertex(v);
+                    normals.get(face, face_vertex, point).map_or(Vec3::Y, vec3_from)
+                })
+                .collect()
+        });
+        let uvs = uvs.map(|uvs| {
+            (0..vertex_count)
+                .map(|v| {
+                    let (face, face_vertex, point) = vertex(v);
+                    let uv = uvs.get(face, face_vertex, point).unwrap_or(&[]);
+                    let coord = |i: usize| uv.get(i).copied().unwrap_or(0.0);
+                    Vec2::new(coord(0), 1.0 - coord(1))
+                })
+                .collect()
+        });
+
+        Some(Self {
+            positions,
+            normals,
+            uvs,
+            face_starts,
+            face_vertices,
+            left_handed: prim.value("orientation").and_then(Value::as_str) == Some("leftHanded"),
+        })
+    }
+
+    fn face_count(&self) -> usize {
+        self.face_starts.len() - 1
+    }
+
+    /// Appends the triangles of a fan covering `face` to `indices`.
+    fn triangulate_face(&self, face: usize, indices: &mut Vec<u32>) {
+        let start = self.face_starts[face];
+        let end = self.face_starts[face + 1];
+        for second in start + 1..end.saturating_sub(1) {
+            let (b, c) = match self.left_handed {
+                false => (second, second + 1),
+                true => (second + 1, second),
+            };
+            indices.extend([self.face_vertices[start], self.face_vertices[b], self.face_vertices[c]]);
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Interpolation {
+    Constant,
+    /// Per face.
+    Uniform,
+    /// Per point, `varying` being the same for meshes.
+    Vertex,
+    FaceVarying,
+}
+
+/// Values of a primvar, and their indices if they are indexed.
+struct Primvar {
+    values: Vec<Vec<f32>>,
+    indices: Option<Vec<usize>>,
+    interpolation: Interpolation,
+}
+
+impl Primvar {
+    fn from_prim(prim: &Prim, name: &str) -> Option<Self> {
+        let attribute = prim.attributes.get(name)?;
+        let values: Vec<Vec<f32>> = attribute
+            .value
+            .as_ref()?
+            .as_array()?
+            .iter()
+            .map(|value| value.as_floats().unwrap_or_default())
+            .collect();
+        let indices = prim.value(&format!("{}:indices", name)).map(ints);
+        let interpolation = match attribute.metadata.get("interpolation").and_then(Value::as_str) {
+            Some("constant") => Interpolation::Constant,
+            Some("uniform") => Interpolation::Uniform,
+            Some("faceVarying") => Interpolation::FaceVarying,
+            _ => Interpolation::Vertex,
+        };
+        Some(Self {
+            values,
+            indices,
+            interpolation,
+        })
+    }
+
+    fn get(&self, face: usize, face_vertex: usize, point: usize) -> Option<&[f32]> {
+        let index = match self.interpolation {
+            Interpolation::Constant => 0,
+            Interpolation::Uniform => face,
+            Interpolation::Vertex => point,
+            Interpolation::FaceVarying => face_vertex,
+        };
+        let index = match &self.indices {
+            Some(indices) => *indices.get(index)?,
+            None => index,
+        };
+        self.values.get(index).map(Vec::as_slice)
+    }
+}
+
+/// Transform of `prim` relative to its parent, and whether it ignores the
+/// transform of its parent.
+fn local_transform(prim: &Prim) -> (Mat4, bool) {
+    let order = match prim.value("xformOpOrder").and_then(Value::as_array) {
+        Some(order) => order,
+        None => return (Mat4::IDENTITY, false),
+    };
+    let mut transform = Mat4::IDENTITY;
+    let mut resets = false;
+    for op in order.iter().filter_map(Value::as_str) {
+        if op == "!resetXformStack!" {
+            transform = Mat4::IDENTITY;
+            resets = true;
+            continue;
+        }
+        let (name, invert) = match op.strip_prefix("!invert!") {
+            Some(name) => (name, true),
+            None => (op, false),
+        };
+        // Ops are applied right to left.
+        match prim.value(name).and_then(|value| xform_op(name, value)) {
+            Some(op) if invert => transform *= op.inverse(),
+            Some(op) => transform *= op,
+            None => log::warn!("Prim {} has an unsupported transform op {}", prim.name, name),
+        }
+    }
+    (transform, resets)
+}
+
+fn xform_op(name: &str, value: &Value) -> Option<Mat4> {
+    let kind = name.strip_prefix("xformOp:")?.split(':').next()?;
+    let floats = flatten(value);
+    let vector = || Some(Vec3::new(*floats.first()?, *floats.get(1)?, *floats.get(2)?));
+    let degrees = || floats.first().map(|angle| angle.to_radians());
+    Some(match kind {
+        // Rows of the matrix transform row vectors, so they are the columns
+        // of the matrix transforming column vectors.
+        "transform" => Mat4::from_cols_slice(floats.get(..16)?),
+        "translate" => Mat4::from_translation(vector()?),
+        "scale" => Mat4::from_scale(vector()?),
+        "rotateX" => Mat4::from_rotation_x(degrees()?),
+        "rotateY" => Mat4::from_rotation_y(degrees()?),
+        "rotateZ" => Mat4::from_rotation_z(degrees()?),
+        "orient" => {
+            let [w, x, y, z] = [*floats.first()?, *floats.get(1)?, *floats.get(2)?, *floats.get(3)?];
+            Mat4::from_quat(Quat::from_xyzw(x, y, z, w).normalize())
+        }
+        rotate => {
+            // `rotateXYZ` rotates around X first, then Y, then Z.
+            let axes = rotate.strip_prefix("rotate")?;
+            let angles = vector()?;
+            let rotation = |axis: char| match axis {
+                'X' => Some(Quat::from_rotation_x(angles.x.to_radians())),
+                'Y' => Some(Quat::from_rotation_y(angles.y.to_radians())),
+                'Z' => Some(Quat::from_rotation_z(angles.z.to_radians())),
+                _ => None,
+            };
+            if axes.len() != 3 {
+                return None;
+            }
+            let mut quat = Quat::IDENTITY;
+            for axis in axes.chars() {
+                quat = rotation(axis)? * quat;
+            }
+            Mat4::from_quat(quat)
+        }
+    })
+}
+
+/// Path of the material bound to `prim`, at `path`.
+fn material_binding(prim: &Prim, path: &str) -> Option<String> {
+    let targets = prim
+        .relationships
+        .get("material:binding")
+        .or_else(|| prim.relationships.get("material:binding:preview"))?;
+    targets.first().map(|target| resolve_path(path, target))
+}
+
+/// Makes `target`, relative to the prim at `base`, absolute.
+fn resolve_path(base: &str, target: &str) -> String {
+    if target.starts_with('/') {
+        return target.to_owned();
+    }
+    let mut segments: Vec<&str> = base.split('/').filter(|segment| !segment.is_empty()).collect();
+    for segment in target.split('/') {
+        match segment {
+            ".." => {
+                segments.pop();
+            }
+            "." | "" => {}
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+/// Prim part of the path of a property.
+fn connection_prim(connection: &str) -> &str {
+    connection.rsplit_once('.').map_or(connection, |(prim, _)| prim)
+}
+
+/// Every number in `value`, depth first.
+fn flatten(value: &Value) -> Vec<f32> {
+    match value {
+        Value::Tuple(values) | Value::Array(values) => values.iter().flat_map(flatten).collect(),
+        value => value.as_f32().into_iter().collect(),
+    }
+}
+
+/// Every number in an array, not going through f32 so large indices stay
+/// exact.
+fn ints(value: &Value) -> Vec<usize> {
+    match value {
+        Value::Array(values) => values.iter().flat_map(ints).collect(),
+        Value::Number(n) => vec![n.max(0.0) as usize],
+        _ => Vec::new(),
+    }
+}
+
+fn vec3(value: &Value) -> Vec3 {
+    vec3_from(&flatten(value))
+}
+
+fn vec3_from(floats: &[f32]) -> Vec3 {
+    let get = |i: usize| floats.get(i).copied().unwrap_or(0.0);
+    Vec3::new(get(0), get(1), get(2))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{usda, MeshGeometry};
+
+    fn mesh(body: &str) -> Option<MeshGeometry> {
+        let source = format!("def Mesh \"Mesh\" {{\n{}\n}}", body);
+        MeshGeometry::from_prim(&usda::parse(&source).unwrap().prims[0])
+    }
+
+    const POINTS: &str = "point3f[] points = [(0, 0, 0), (1, 0, 0), (1, 1, 0), (0, 1, 0), (2, 0, 0)]";
+
+    #[test]
+    fn triangulates_faces_as_fans() {
+        let geometry = mesh(&format!(
+            "{}\nint[] faceVertexCounts = [4, 3]\nint[] faceVertexIndices = [0, 1, 2, 3, 1, 4, 2]",
+            POINTS
+        ))
+        .unwrap();
+        assert_eq!(geometry.face_count(), 2);
+
+        let mut indices = Vec::new();
+        for face in 0..geometry.face_count() {
+            geometry.triangulate_face(face, &mut indices);
+        }
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3, 1, 4, 2]);
+    }
+
+    #[test]
+    fn rejects_invalid_faces() {
+        for topology in [
+            // Index past the points.
+            "int[] faceVertexCounts = [3]\nint[] faceVertexIndices = [0, 1, 5]",
+            // Counts and indices disagree.
+            "int[] faceVertexCounts = [3, 3]\nint[] faceVertexIndices = [0, 1, 2]",
+            "int[] faceVertexCounts = [3]\nint[] faceVertexIndices = [0, 1, 2, 3]",
+            // Counts overflowing when summed.
+            "int[] faceVertexCounts = [1e30, 1]\nint[] faceVertexIndices = [0]",
+        ] {
+            assert!(mesh(&format!("{}\n{}", POINTS, topology)).is_none(), "{}", topology);
+        }
+        assert!(mesh("int[] faceVertexCounts = [3]").is_none());
+    }
+}
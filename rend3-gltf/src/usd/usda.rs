@@ -0,0 +1,682 @@
+//! Parser of the text format of USD layers, keeping the prims and the
+//! properties of a single layer without composing anything.
+
+use rend3::util::typedefs::FastHashMap;
+
+use super::UsdParseError;
+
+/// Value of an attribute or a metadata entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    String(String),
+    /// Tokens like `true` or `None`.
+    Ident(String),
+    /// `@path@`
+    Asset(String),
+    /// `</Prim/path>`
+    Path(String),
+    Tuple(Vec<Value>),
+    Array(Vec<Value>),
+    /// Dictionaries and other values there is no use for.
+    Other,
+}
+
+impl Value {
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Value::Number(n) => Some(*n as f32),
+            Value::Ident(ident) => match ident.as_str() {
+                "true" => Some(1.0),
+                "false" => Some(0.0),
+                "inf" => Some(f32::INFINITY),
+                "-inf" => Some(f32::NEG_INFINITY),
+                "nan" => Some(f32::NAN),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) | Value::Ident(s) | Value::Asset(s) | Value::Path(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Numbers of a tuple, or the number itself.
+    pub fn as_floats(&self) -> Option<Vec<f32>> {
+        match self {
+            Value::Tuple(values) => values.iter().map(Value::as_f32).collect(),
+            value => value.as_f32().map(|n| vec![n]),
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Attribute {
+    pub type_name: String,
+    pub value: Option<Value>,
+    /// Path of the attribute this one is connected to, with `.connect`.
+    pub connection: Option<String>,
+    pub metadata: FastHashMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Prim {
+    /// `def`, `over` or `class`.
+    pub specifier: String,
+    pub type_name: Option<String>,
+    pub name: String,
+    pub metadata: FastHashMap<String, Value>,
+    pub attributes: FastHashMap<String, Attribute>,
+    /// Targets of each relationship.
+    pub relationships: FastHashMap<String, Vec<String>>,
+    pub children: Vec<Prim>,
+}
+
+impl Prim {
+    pub fn value(&self, name: &str) -> Option<&Value> {
+        self.attributes.get(name)?.value.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Layer {
+    pub metadata: FastHashMap<String, Value>,
+    pub prims: Vec<Prim>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    String(String),
+    Asset(String),
+    Path(String),
+    Punct(char),
+}
+
+/// Words which can precede the type of a property.
+const PROPERTY_MODIFIERS: &[&str] = &["custom", "uniform", "varying", "prepend", "append", "delete", "add"];
+
+pub fn parse(source: &str) -> Result<Layer, UsdParseError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser { tokens, position: 0 };
+
+    let mut layer = Layer::default();
+    if parser.peek_punct('(') {
+        layer.metadata = parser.metadata()?;
+    }
+    while !parser.at_end() {
+        match parser.ident()?.as_str() {
+            specifier @ ("def" | "over" | "class") => {
+                let prim = parser.prim(specifier)?;
+                layer.prims.push(prim);
+            }
+            other => return Err(parser.error(format!("expected a prim, found `{}`", other))),
+        }
+    }
+    Ok(layer)
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, UsdParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    let mut line = 1;
+    while let Some(&(start, c)) = chars.peek() {
+        let token_line = line;
+        let token = match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+                continue;
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '#' => {
+                // Comments, including the `#usda 1.0` header.
+                while chars.peek().map_or(false, |&(_, c)| c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '"' | '\'' => {
+                let triple = source[start..].starts_with(&format!("{0}{0}{0}", c));
+                let quote_len = if triple { 3 } else { 1 };
+                let content_start = start + quote_len;
+                let mut end = None;
+                let mut escaped = false;
+                for (i, ch) in source[content_start..].char_indices() {
+                    if escaped {
+                        escaped = false;
+                    } else if ch == '\\' {
+                        escaped = true;
+                    } else if ch == c
+                        && (!triple || source[content_start + i..].starts_with(&source[start..content_start]))
+                    {
+                        end = Some(content_start + i);
+                        break;
+                    }
+                }
+                let end = end.ok_or(UsdParseError {
+                    line,
+                    message: "unterminated string".into(),
+                })?;
+                let content = &source[content_start..end];
+                line += content.matches('\n').count();
+                while chars.peek().map_or(false, |&(i, _)| i < end + quote_len) {
+                    chars.next();
+                }
+                Token::String(unescape(content))
+            }
+            '@' => {
+                chars.next();
+                let content: String =
+                    std::iter::from_fn(|| chars.next_if(|&(_, c)| c != '@').map(|(_, c)| c)).collect();
+                chars.next();
+                Token::Asset(content)
+            }
+            '<' => {
+                chars.next();
+                let content: String =
+                    std::iter::from_fn(|| chars.next_if(|&(_, c)| c != '>').map(|(_, c)| c)).collect();
+                chars.next();
+                Token::Path(content)
+            }
+            '(' | ')' | '[' | ']' | '{' | '}' | '=' | ',' | ';' | ':' => {
+                chars.next();
+                Token::Punct(c)
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let word: String =
+                    std::iter::from_fn(|| chars.next_if(|&(_, c)| is_number_char(c)).map(|(_, c)| c)).collect();
+                match word.parse::<f64>() {
+                    Ok(n) => Token::Number(n),
+                    // Not a number after all, like a lone `-`.
+                    Err(_) => Token::Ident(word),
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = String::new();
+                while let Some(&(i, c)) = chars.peek() {
+                    // Namespaced names like `xformOp:translate` or
+                    // `inputs:file.connect` are single tokens, but `:` also
+                    // separates time samples.
+                    let continues = c.is_alphanumeric()
+                        || c == '_'
+                        || ((c == ':' || c == '.')
+                            && source[i + 1..]
+                                .chars()
+                                .next()
+                                .map_or(false, |next| next.is_alphabetic() || next == '_'));
+                    if !continues {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                Token::Ident(word)
+            }
+            c => {
+                return Err(UsdParseError {
+                    line,
+                    message: format!("unexpected character `{}`", c),
+                })
+            }
+        };
+        tokens.push((token, token_line));
+    }
+    Ok(tokens)
+}
+
+fn is_number_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+'
+}
+
+fn unescape(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut chars = content.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(other) => result.push(other),
+                None => {}
+            },
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn error(&self, message: String) -> UsdParseError {
+        let line = self
+            .tokens
+            .get(self.position.min(self.tokens.len().saturating_sub(1)))
+            .map_or(0, |&(_, line)| line);
+        UsdParseError { line, message }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn peek_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn next(&mut self) -> Result<Token, UsdParseError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(token, _)| token.clone())
+            .ok_or_else(|| self.error("unexpected end of file".into()))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), UsdParseError> {
+        match self.next()? {
+            Token::Punct(p) if p == c => Ok(()),
+            other => {
+                self.position -= 1;
+                Err(self.error(format!("expected `{}`, found {:?}", c, other)))
+            }
+        }
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        let found = self.peek_punct(c);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn ident(&mut self) -> Result<String, UsdParseError> {
+        match self.next()? {
+            Token::Ident(ident) => Ok(ident),
+            other => {
+                self.position -= 1;
+                Err(self.error(format!("expected a name, found {:?}", other)))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, UsdParseError> {
+        match self.next()? {
+            Token::String(string) => Ok(string),
+            other => {
+                self.position -= 1;
+                Err(self.error(format!("expected a string, found {:?}", other)))
+            }
+        }
+    }
+
+    /// Skips a bracketed group, starting at its opening bracket.
+    fn skip_group(&mut self) -> Result<(), UsdParseError> {
+        if !matches!(self.peek(), Some(Token::Punct('(' | '[' | '{'))) {
+            return Err(self.error(format!("expected a bracket, found {:?}", self.peek())));
+        }
+        let mut depth = 0_usize;
+        loop {
+            match self.next()? {
+                Token::Punct('(' | '[' | '{') => depth += 1,
+                Token::Punct(')' | ']' | '}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// `( key = value ... )`, keeping the last value of each key.
+    fn metadata(&mut self) -> Result<FastHashMap<String, Value>, UsdParseError> {
+        let mut metadata = FastHashMap::default();
+        self.expect_punct('(')?;
+        loop {
+            match self.next()? {
+                Token::Punct(')') => return Ok(metadata),
+                Token::Punct(';') => {}
+                // Documentation.
+                Token::String(_) => {}
+                Token::Ident(mut key) => {
+                    // List edits like `prepend references = ...`.
+                    while let Some(Token::Ident(_)) = self.peek() {
+                        key = self.ident()?;
+                    }
+                    self.expect_punct('=')?;
+                    let value = self.value()?;
+                    metadata.insert(key, value);
+                }
+                other => {
+                    self.position -= 1;
+                    return Err(self.error(format!("unexpected {:?} in metadata", other)));
+                }
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, UsdParseError> {
+        Ok(match self.next()? {
+            Token::Number(n) => Value::Number(n),
+            Token::String(s) => Value::String(s),
+            Token::Ident(s) => Value::Ident(s),
+            Token::Asset(s) => {
+                // References and payloads name a prim after the layer.
+                if let Some(Token::Path(_)) = self.peek() {
+                    self.position += 1;
+                }
+                Value::Asset(s)
+            }
+            Token::Path(s) => Value::Path(s),
+            Token::Punct(open @ ('(' | '[')) => {
+                let close = if open == '(' { ')' } else { ']' };
+                let mut values = Vec::new();
+                while !self.eat_punct(close) {
+                    values.push(self.value()?);
+                    if !self.eat_punct(',') && !self.peek_punct(close) {
+                        return Err(self.error(format!("expected `,` or `{}`", close)));
+                    }
+                }
+                match open {
+                    '(' => Value::Tuple(values),
+                    _ => Value::Array(values),
+                }
+            }
+            Token::Punct('{') => {
+                self.position -= 1;
+                self.skip_group()?;
+                Value::Other
+            }
+            other => {
+                self.position -= 1;
+                return Err(self.error(format!("expected a value, found {:?}", other)));
+            }
+        })
+    }
+
+    /// `{ time: value, ... }`, keeping the value of the earliest sample.
+    fn time_samples(&mut self) -> Result<Option<Value>, UsdParseError> {
+        self.expect_punct('{')?;
+        let mut first: Option<(f64, Value)> = None;
+        while !self.eat_punct('}') {
+            let time = match self.value()? {
+                Value::Number(time) => time,
+                _ => return Err(self.error("expected the time of a sample".into())),
+            };
+            self.expect_punct(':')?;
+            let value = self.value()?;
+            if first.as_ref().map_or(true, |&(first_time, _)| time < first_time) {
+                first = Some((time, value));
+            }
+            self.eat_punct(',');
+        }
+        Ok(first.map(|(_, value)| value))
+    }
+
+    /// A prim, after its specifier.
+    fn prim(&mut self, specifier: &str) -> Result<Prim, UsdParseError> {
+        let type_name = match self.peek() {
+            Some(Token::Ident(_)) => Some(self.ident()?),
+            _ => None,
+        };
+        let name = self.string()?;
+        let mut prim = Prim {
+            specifier: specifier.to_owned(),
+            type_name,
+            name,
+            ..Prim::default()
+        };
+        if self.peek_punct('(') {
+            prim.metadata = self.metadata()?;
+        }
+
+        self.expect_punct('{')?;
+        loop {
+            if self.eat_punct('}') {
+                return Ok(prim);
+            }
+            if self.eat_punct(';') {
+                continue;
+            }
+            let word = self.ident()?;
+            match word.as_str() {
+                "def" | "over" | "class" => {
+                    let child = self.prim(&word)?;
+                    prim.children.push(child);
+                }
+                "variantSet" => {
+                    // Variants would need composing.
+                    self.string()?;
+                    self.expect_punct('=')?;
+                    self.skip_group()?;
+                }
+                "reorder" => {
+                    self.ident()?;
+                    self.expect_punct('=')?;
+                    self.value()?;
+                }
+                _ => self.property(&mut prim, word)?,
+            }
+        }
+    }
+
+    /// A property, starting at its first word.
+    fn property(&mut self, prim: &mut Prim, mut word: String) -> Result<(), UsdParseError> {
+        while PROPERTY_MODIFIERS.contains(&word.as_str()) {
+            word = self.ident()?;
+        }
+
+        if word == "rel" {
+            let name = self.ident()?;
+            let mut targets = Vec::new();
+            if self.eat_punct('=') {
+                match self.value()? {
+                    Value::Path(path) => targets.push(path),
+                    Value::Array(values) => targets.extend(values.iter().filter_map(|value| match value {
+                        Value::Path(path) => Some(path.clone()),
+                        _ => None,
+                    })),
+                    _ => {}
+                }
+            }
+            if self.peek_punct('(') {
+                self.skip_group()?;
+            }
+            // Strips `.default` and friends, which only pick list edits.
+            let name = name.split('.').next().unwrap_or_default().to_owned();
+            prim.relationships.entry(name).or_default().extend(targets);
+            return Ok(());
+        }
+
+        let mut type_name = word;
+        if self.peek_punct('[') {
+            self.expect_punct('[')?;
+            self.expect_punct(']')?;
+            type_name.push_str("[]");
+        }
+        let full_name = self.ident()?;
+        let (name, suffix) = match full_name.rsplit_once('.') {
+            Some((name, suffix @ ("connect" | "timeSamples" | "spline"))) => (name.to_owned(), Some(suffix)),
+            _ => (full_name.clone(), None),
+        };
+
+        let mut value = None;
+        let mut connection = None;
+        if self.eat_punct('=') {
+            match suffix {
+                Some("timeSamples") => value = self.time_samples()?,
+                Some("connect") => {
+                    connection = match self.value()? {
+                        Value::Path(path) => Some(path),
+                        Value::Array(values) => values.first().and_then(Value::as_str).map(str::to_owned),
+                        _ => None,
+                    }
+                }
+                Some(_) => {
+                    self.skip_group()?;
+                }
+                None => value = Some(self.value()?),
+            }
+        }
+        let metadata = match self.peek_punct('(') {
+            true => self.metadata()?,
+            false => FastHashMap::default(),
+        };
+
+        let attribute = prim.attributes.entry(name).or_default();
+        attribute.type_name = type_name;
+        // A default value and time samples may both be authored, the default
+        // wins.
+        if value.is_some() && (attribute.value.is_none() || suffix.is_none()) {
+            attribute.value = value;
+        }
+        if connection.is_some() {
+            attribute.connection = connection;
+        }
+        attribute.metadata.extend(metadata);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, tokenize, Token, Value};
+
+    fn tokens(source: &str) -> Vec<(Token, usize)> {
+        tokenize(source).unwrap()
+    }
+
+    #[test]
+    fn tokenizes_strings_and_comments() {
+        assert_eq!(
+            tokens("#usda 1.0\n\"a \\\"b\\\"\\n\" 'c' # comment\nd"),
+            [
+                (Token::String("a \"b\"\n".into()), 2),
+                (Token::String("c".into()), 2),
+                (Token::Ident("d".into()), 3),
+            ]
+        );
+
+        // Triple quotes may contain single quotes and newlines, which still
+        // count towards the line of the following tokens.
+        assert_eq!(
+            tokens("\"\"\"one \"two\"\nthree\"\"\" '''it's''' four"),
+            [
+                (Token::String("one \"two\"\nthree".into()), 1),
+                (Token::String("it's".into()), 2),
+                (Token::Ident("four".into()), 2),
+            ]
+        );
+
+        let error = tokenize("\n\n\"unterminated").unwrap_err();
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn tokenizes_names_numbers_and_paths() {
+        assert_eq!(
+            tokens("inputs:file.connect = </Mat/Tex.outputs:rgb> @./a.png@ -1.5e2 2: -"),
+            [
+                (Token::Ident("inputs:file.connect".into()), 1),
+                (Token::Punct('='), 1),
+                (Token::Path("/Mat/Tex.outputs:rgb".into()), 1),
+                (Token::Asset("./a.png".into()), 1),
+                (Token::Number(-150.0), 1),
+                (Token::Number(2.0), 1),
+                (Token::Punct(':'), 1),
+                (Token::Ident("-".into()), 1),
+            ]
+        );
+        assert!(tokenize("def Mesh \"a\" { ! }").is_err());
+    }
+
+    #[test]
+    fn parses_prims_and_properties() {
+        let layer = parse(
+            r#"#usda 1.0
+(
+    upAxis = "Z"
+)
+
+def Xform "Root" (
+    kind = "component"
+)
+{
+    double3 xformOp:translate.timeSamples = { 2: (0, 0, 2), 1: (0, 0, 1) }
+    uniform token[] xformOpOrder = ["xformOp:translate"]
+
+    def Mesh "Mesh"
+    {
+        int[] faceVertexCounts = [3]
+        rel material:binding = </Root/Material>
+        float2[] primvars:st = [(0, 0), (1, 0), (1, 1)] (
+            interpolation = "faceVarying"
+        )
+    }
+}
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(layer.metadata["upAxis"], Value::String("Z".into()));
+        let root = &layer.prims[0];
+        assert_eq!((root.type_name.as_deref(), root.name.as_str()), (Some("Xform"), "Root"));
+        assert_eq!(root.metadata["kind"], Value::String("component".into()));
+        // The earliest time sample is kept.
+        assert_eq!(
+            root.value("xformOp:translate").and_then(Value::as_floats),
+            Some(vec![0.0, 0.0, 1.0])
+        );
+        assert_eq!(root.attributes["xformOpOrder"].type_name, "token[]");
+
+        let mesh = &root.children[0];
+        assert_eq!(
+            mesh.value("faceVertexCounts"),
+            Some(&Value::Array(vec![Value::Number(3.0)]))
+        );
+        assert_eq!(mesh.relationships["material:binding"], ["/Root/Material"]);
+        let st = &mesh.attributes["primvars:st"];
+        assert_eq!(st.metadata["interpolation"], Value::String("faceVarying".into()));
+        assert_eq!(st.value.as_ref().and_then(Value::as_array).map(<[_]>::len), Some(3));
+    }
+
+    #[test]
+    fn rejects_malformed_layers() {
+        for source in [
+            "def Xform \"A\" {",
+            "def Xform \"A\" { float a = [1 2] }",
+            "def Xform \"A\" { float a.spline = ) }",
+            "def Xform \"A\" { float a = ( }",
+            "Xform \"A\" {}",
+        ] {
+            assert!(parse(source).is_err(), "{}", source);
+        }
+    }
+}
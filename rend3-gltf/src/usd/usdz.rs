@@ -0,0 +1,171 @@
+//! Reader of usdz packages, zip archives whose files are stored
+//! uncompressed.
+
+use super::UsdLoadError;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+
+/// Files of a usdz package, in the order of the archive. The first one is
+/// the root layer.
+pub struct Package<'a> {
+    pub files: Vec<(String, &'a [u8])>,
+}
+
+impl<'a> Package<'a> {
+    pub fn is_usdz(data: &[u8]) -> bool {
+        read_u32(data, 0) == Some(LOCAL_HEADER)
+    }
+
+    pub fn parse<E: std::error::Error + 'static>(data: &'a [u8]) -> Result<Self, UsdLoadError<E>> {
+        let malformed = || UsdLoadError::MalformedUsdz;
+
+        // The end of central directory record is followed by a comment of up
+        // to 64k.
+        let search_start = data.len().saturating_sub(22 + u16::MAX as usize);
+        let end = (search_start..data.len().saturating_sub(21))
+            .rev()
+            .find(|&offset| read_u32(data, offset) == Some(END_OF_CENTRAL_DIRECTORY))
+            .ok_or_else(malformed)?;
+        let count = read_u16(data, end + 10).ok_or_else(malformed)? as usize;
+        let mut offset = read_u32(data, end + 16).ok_or_else(malformed)? as usize;
+
+        let mut files = Vec::with_capacity(count);
+        for _ in 0..count {
+            // Reading the fixed size part of the header first keeps offsets
+            // into it from overflowing.
+            let header = read_bytes(data, offset, 46).ok_or_else(malformed)?;
+            if read_u32(header, 0) != Some(CENTRAL_HEADER) {
+                return Err(malformed());
+            }
+            let method = read_u16(header, 10).ok_or_else(malformed)?;
+            let size = read_u32(header, 20).ok_or_else(malformed)? as usize;
+            let name_len = read_u16(header, 28).ok_or_else(malformed)? as usize;
+            let extra_len = read_u16(header, 30).ok_or_else(malformed)? as usize;
+            let comment_len = read_u16(header, 32).ok_or_else(malformed)? as usize;
+            let local = read_u32(header, 42).ok_or_else(malformed)? as usize;
+            let name = read_bytes(data, offset + 46, name_len).ok_or_else(malformed)?;
+            let name = String::from_utf8_lossy(name).into_owned();
+            offset += 46 + name_len + extra_len + comment_len;
+
+            if method != 0 {
+                return Err(UsdLoadError::CompressedUsdz(name));
+            }
+
+            // The extra field of the local header pads the data to 64 bytes,
+            // and differs from the one of the central directory.
+            let local_header = read_bytes(data, local, 30).ok_or_else(malformed)?;
+            if read_u32(local_header, 0) != Some(LOCAL_HEADER) {
+                return Err(malformed());
+            }
+            let local_name_len = read_u16(local_header, 26).ok_or_else(malformed)? as usize;
+            let local_extra_len = read_u16(local_header, 28).ok_or_else(malformed)? as usize;
+            let start = local + 30 + local_name_len + local_extra_len;
+            let contents = read_bytes(data, start, size).ok_or_else(malformed)?;
+            files.push((name, contents));
+        }
+
+        Ok(Self { files })
+    }
+
+    /// Contents of the file at `path`, relative to the root of the package.
+    pub fn file(&self, path: &str) -> Option<&'a [u8]> {
+        let path = path.trim_start_matches("./");
+        self.files
+            .iter()
+            .find(|(name, _)| name.as_str() == path)
+            .map(|&(_, contents)| contents)
+    }
+}
+
+/// `len` bytes at `offset`, which come from the file and may be out of range.
+fn read_bytes(data: &[u8], offset: usize, len: usize) -> Option<&[u8]> {
+    data.get(offset..offset.checked_add(len)?)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(read_bytes(data, offset, 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(read_bytes(data, offset, 4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Package, CENTRAL_HEADER, END_OF_CENTRAL_DIRECTORY, LOCAL_HEADER};
+    use crate::usd::UsdLoadError;
+
+    type Error = UsdLoadError<std::io::Error>;
+
+    /// A zip archive storing `files` with compression `method`.
+    fn archive(files: &[(&str, &[u8])], method: u16) -> Vec<u8> {
+        let u16 = |data: &mut Vec<u8>, value: u16| data.extend(value.to_le_bytes());
+        let u32 = |data: &mut Vec<u8>, value: u32| data.extend(value.to_le_bytes());
+
+        let mut data = Vec::new();
+        let mut locals = Vec::new();
+        for (name, contents) in files {
+            locals.push(data.len() as u32);
+            u32(&mut data, LOCAL_HEADER);
+            data.extend([0; 22]);
+            u16(&mut data, name.len() as u16);
+            u16(&mut data, 0);
+            data.extend(name.as_bytes());
+            data.extend(*contents);
+        }
+
+        let directory = data.len() as u32;
+        for ((name, contents), local) in files.iter().zip(locals) {
+            u32(&mut data, CENTRAL_HEADER);
+            data.extend([0; 6]);
+            u16(&mut data, method);
+            data.extend([0; 8]);
+            u32(&mut data, contents.len() as u32);
+            u32(&mut data, contents.len() as u32);
+            u16(&mut data, name.len() as u16);
+            data.extend([0; 12]);
+            u32(&mut data, local);
+            data.extend(name.as_bytes());
+        }
+
+        let directory_size = data.len() as u32 - directory;
+        u32(&mut data, END_OF_CENTRAL_DIRECTORY);
+        data.extend([0; 4]);
+        u16(&mut data, files.len() as u16);
+        u16(&mut data, files.len() as u16);
+        u32(&mut data, directory_size);
+        u32(&mut data, directory);
+        u16(&mut data, 0);
+        data
+    }
+
+    #[test]
+    fn reads_stored_files() {
+        let data = archive(&[("scene.usda", b"#usda 1.0\n"), ("textures/a.png", b"png")], 0);
+        assert!(Package::is_usdz(&data));
+        let package = Package::parse::<std::io::Error>(&data).unwrap();
+        assert_eq!(package.files[0], ("scene.usda".to_owned(), &b"#usda 1.0\n"[..]));
+        assert_eq!(package.file("./textures/a.png"), Some(&b"png"[..]));
+        assert_eq!(package.file("missing.png"), None);
+    }
+
+    #[test]
+    fn rejects_broken_packages() {
+        let data = archive(&[("scene.usda", b"#usda 1.0\n")], 8);
+        assert!(matches!(Package::parse(&data), Err(Error::CompressedUsdz(name)) if name == "scene.usda"));
+
+        // The directory claims a file more than it holds.
+        let mut data = archive(&[("scene.usda", b"#usda 1.0\n")], 0);
+        let end = data.len() - 22;
+        data[end + 10] = 2;
+        assert!(matches!(Package::parse(&data), Err(Error::MalformedUsdz)));
+        data[end + 10] = 1;
+
+        // The local header points past the end of the file.
+        let local = data.len() - 22 - 46 - "scene.usda".len() + 42;
+        data[local..local + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(Package::parse(&data), Err(Error::MalformedUsdz)));
+    }
+}
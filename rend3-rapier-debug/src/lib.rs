@@ -0,0 +1,119 @@
+//! Draws the debug rendering of [rapier](https://rapier.rs) physics worlds
+//! through rend3-routine's [`DebugDraw`].
+//!
+//! Keep a [`RapierDebugRender`] next to the physics world, call
+//! [`RapierDebugRender::render`] each frame after stepping the simulation,
+//! then draw the [`DebugDraw`] with a
+//! [`DebugDrawRoutine`](rend3_routine::debug::DebugDrawRoutine) as usual.
+//! Colliders, joints and body axes are drawn as lines, as chosen by the
+//! [`DebugRenderMode`].
+
+use glam::{Vec3, Vec4};
+use rapier3d::{
+    dynamics::{ImpulseJointSet, MultibodyJointSet, RigidBodySet},
+    geometry::ColliderSet,
+    math::{Point, Real},
+    pipeline::{DebugRenderBackend, DebugRenderMode, DebugRenderObject, DebugRenderPipeline, DebugRenderStyle},
+};
+use rend3_routine::debug::DebugDraw;
+
+/// Turns rapier's debug rendering into lines of a [`DebugDraw`], see the
+/// [crate](crate) docs.
+pub struct RapierDebugRender {
+    pub pipeline: DebugRenderPipeline,
+    /// Whether to draw anything, to toggle the debug view without losing
+    /// the settings.
+    pub enabled: bool,
+}
+
+impl Default for RapierDebugRender {
+    fn default() -> Self {
+        Self::new(DebugRenderStyle::default(), DebugRenderMode::all())
+    }
+}
+
+impl RapierDebugRender {
+    pub fn new(style: DebugRenderStyle, mode: DebugRenderMode) -> Self {
+        Self {
+            pipeline: DebugRenderPipeline::new(style, mode),
+            enabled: true,
+        }
+    }
+
+    /// Adds the lines of the current state of the physics world to `draw`.
+    /// Clearing `draw` between frames is up to the caller, so other debug
+    /// shapes can be collected alongside.
+    pub fn render(
+        &mut self,
+        draw: &mut DebugDraw,
+        bodies: &RigidBodySet,
+        colliders: &ColliderSet,
+        impulse_joints: &ImpulseJointSet,
+        multibody_joints: &MultibodyJointSet,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut backend = DebugDrawBackend { draw };
+        self.pipeline
+            .render(&mut backend, bodies, colliders, impulse_joints, multibody_joints);
+    }
+}
+
+struct DebugDrawBackend<'a> {
+    draw: &'a mut DebugDraw,
+}
+
+impl DebugRenderBackend for DebugDrawBackend<'_> {
+    fn draw_line(&mut self, _object: DebugRenderObject<'_>, a: Point<Real>, b: Point<Real>, color: [f32; 4]) {
+        self.draw
+            .line(Vec3::new(a.x, a.y, a.z), Vec3::new(b.x, b.y, b.z), hsla_to_srgba(color));
+    }
+}
+
+/// Converts the hue in degrees, saturation, lightness and alpha rapier
+/// colors its debug rendering with into the srgb colors of [`DebugDraw`].
+pub fn hsla_to_srgba([hue, saturation, lightness, alpha]: [f32; 4]) -> Vec4 {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let channel = |n: f32| {
+        let k = (n + hue.rem_euclid(360.0) / 30.0) % 12.0;
+        lightness - chroma / 2.0 * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    Vec4::new(channel(0.0), channel(8.0), channel(4.0), alpha)
+}
+
+#[cfg(test)]
+mod test {
+    use super::hsla_to_srgba;
+    use glam::Vec4;
+
+    fn assert_close(a: Vec4, b: Vec4) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn primary_hues() {
+        assert_close(hsla_to_srgba([0.0, 1.0, 0.5, 1.0]), Vec4::new(1.0, 0.0, 0.0, 1.0));
+        assert_close(hsla_to_srgba([120.0, 1.0, 0.5, 1.0]), Vec4::new(0.0, 1.0, 0.0, 1.0));
+        assert_close(hsla_to_srgba([240.0, 1.0, 0.5, 1.0]), Vec4::new(0.0, 0.0, 1.0, 1.0));
+        assert_close(hsla_to_srgba([60.0, 1.0, 0.5, 1.0]), Vec4::new(1.0, 1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn lightness_and_saturation() {
+        assert_close(hsla_to_srgba([200.0, 0.0, 0.3, 1.0]), Vec4::new(0.3, 0.3, 0.3, 1.0));
+        assert_close(hsla_to_srgba([90.0, 1.0, 1.0, 1.0]), Vec4::ONE);
+        assert_close(hsla_to_srgba([90.0, 1.0, 0.0, 1.0]), Vec4::new(0.0, 0.0, 0.0, 1.0));
+        assert_close(hsla_to_srgba([0.0, 0.5, 0.5, 1.0]), Vec4::new(0.75, 0.25, 0.25, 1.0));
+    }
+
+    #[test]
+    fn hue_wraps_and_alpha_passes_through() {
+        assert_close(
+            hsla_to_srgba([-120.0, 1.0, 0.5, 0.25]),
+            hsla_to_srgba([240.0, 1.0, 0.5, 0.25]),
+        );
+        assert_close(hsla_to_srgba([480.0, 1.0, 0.5, 0.5]), Vec4::new(0.0, 1.0, 0.0, 0.5));
+    }
+}
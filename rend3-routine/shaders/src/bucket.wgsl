@@ -0,0 +1,114 @@
+/// Groups the output of the atomic culling pass by material, so the draws of
+/// each material end up next to each other. See gpu.rs for the bindings.
+///
+/// Objects and indirect calls are copied as words, the layouts being those of
+/// ObjectOutputData and IndirectCall in structures.glsl.
+
+/// Words in PerObjectDataAbi.
+let OBJECT_WORDS: u32 = 40u;
+/// Word of PerObjectDataAbi holding the material index.
+let MATERIAL_WORD: u32 = 32u;
+/// Words in an IndirectCall.
+let CALL_WORDS: u32 = 5u;
+/// Words before the first IndirectCall, the first being the draw count.
+let CALL_HEADER_WORDS: u32 = 4u;
+/// Word of an IndirectCall holding the base instance, the object index.
+let BASE_INSTANCE_WORD: u32 = 4u;
+
+struct BucketUniforms {
+    material_count: u32;
+};
+
+struct Words {
+    data: array<u32>;
+};
+
+struct AtomicWords {
+    data: array<atomic<u32>>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: BucketUniforms;
+[[group(0), binding(1)]]
+var<storage, read> objects: Words;
+[[group(0), binding(2)]]
+var<storage, read> calls: Words;
+/// Objects per material after count_materials, then the index of the next
+/// free slot of each material after scan_materials.
+[[group(0), binding(3)]]
+var<storage, read_write> offsets: AtomicWords;
+[[group(0), binding(4)]]
+var<storage, read_write> sorted_objects: Words;
+[[group(0), binding(5)]]
+var<storage, read_write> sorted_calls: Words;
+
+fn material_of(object: u32) -> u32 {
+    return min(objects.data[object * OBJECT_WORDS + MATERIAL_WORD], uniforms.material_count - 1u);
+}
+
+[[stage(compute), workgroup_size(256)]]
+fn count_materials([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= calls.data[0]) {
+        return;
+    }
+    atomicAdd(&offsets.data[material_of(id.x)], 1u);
+}
+
+var<workgroup> chunk_sums: array<u32, 256>;
+
+/// Exclusive scan of the counts in a single workgroup, each invocation
+/// handling a contiguous chunk of materials.
+[[stage(compute), workgroup_size(256)]]
+fn scan_materials([[builtin(local_invocation_index)]] index: u32) {
+    let chunk = (uniforms.material_count + 255u) / 256u;
+    let start = min(index * chunk, uniforms.material_count);
+    let end = min(start + chunk, uniforms.material_count);
+
+    var sum = 0u;
+    for (var material = start; material < end; material = material + 1u) {
+        sum = sum + atomicLoad(&offsets.data[material]);
+    }
+    chunk_sums[index] = sum;
+    workgroupBarrier();
+
+    if (index == 0u) {
+        var total = 0u;
+        for (var i = 0u; i < 256u; i = i + 1u) {
+            let count = chunk_sums[i];
+            chunk_sums[i] = total;
+            total = total + count;
+        }
+    }
+    workgroupBarrier();
+
+    var offset = chunk_sums[index];
+    for (var material = start; material < end; material = material + 1u) {
+        let count = atomicLoad(&offsets.data[material]);
+        atomicStore(&offsets.data[material], offset);
+        offset = offset + count;
+    }
+}
+
+[[stage(compute), workgroup_size(256)]]
+fn scatter([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let draw_count = calls.data[0];
+    if (id.x == 0u) {
+        sorted_calls.data[0] = draw_count;
+    }
+    if (id.x >= draw_count) {
+        return;
+    }
+
+    let slot = atomicAdd(&offsets.data[material_of(id.x)], 1u);
+
+    for (var word = 0u; word < OBJECT_WORDS; word = word + 1u) {
+        sorted_objects.data[slot * OBJECT_WORDS + word] = objects.data[id.x * OBJECT_WORDS + word];
+    }
+
+    let src = CALL_HEADER_WORDS + id.x * CALL_WORDS;
+    let dst = CALL_HEADER_WORDS + slot * CALL_WORDS;
+    for (var word = 0u; word < CALL_WORDS; word = word + 1u) {
+        sorted_calls.data[dst + word] = calls.data[src + word];
+    }
+    sorted_calls.data[dst + BASE_INSTANCE_WORD] = slot;
+}
@@ -0,0 +1,146 @@
+struct CrowdUniforms {
+    view_proj: mat4x4<f32>;
+    color: vec4<f32>;
+    ambient: vec4<f32>;
+    // Direction the light travels in xyz.
+    light_directions: array<vec4<f32>, 4>;
+    // Color times intensity in rgb.
+    light_colors: array<vec4<f32>, 4>;
+    // Quantized positions are position_offset + position_scale * position.
+    position_offset: vec3<f32>;
+    position_scale: vec3<f32>;
+    light_count: u32;
+    time: f32;
+    // Bits of VertexQuantization.
+    vertex_quantization: u32;
+};
+
+struct CrowdClips {
+    // First frame, frame count, sample rate, and 1 if looping.
+    clips: array<vec4<f32>, 32>;
+};
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] uv: vec2<f32>;
+    [[location(3)]] joint_indices: vec4<u32>;
+    [[location(4)]] joint_weights: vec4<f32>;
+    [[location(5)]] transform_0: vec4<f32>;
+    [[location(6)]] transform_1: vec4<f32>;
+    [[location(7)]] transform_2: vec4<f32>;
+    [[location(8)]] transform_3: vec4<f32>;
+    [[location(9)]] clip: u32;
+    [[location(10)]] time_offset: f32;
+    [[location(11)]] speed: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] normal: vec3<f32>;
+    [[location(1)]] uv: vec2<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: CrowdUniforms;
+[[group(0), binding(1)]]
+var animation: texture_2d<f32>;
+[[group(0), binding(2)]]
+var<uniform> clips: CrowdClips;
+[[group(0), binding(3)]]
+var albedo: texture_2d<f32>;
+[[group(0), binding(4)]]
+var albedo_sampler: sampler;
+
+// Undoes the octahedral encoding of normals if the renderer quantizes them.
+fn decode_normal(normal: vec3<f32>) -> vec3<f32> {
+    // VertexQuantization::NORMALS
+    if ((uniforms.vertex_quantization & 1u) == 0u) {
+        return normal;
+    }
+    var n = vec3<f32>(normal.xy, 1.0 - abs(normal.x) - abs(normal.y));
+    let t = max(-n.z, 0.0);
+    n.x = n.x - select(-t, t, n.x >= 0.0);
+    n.y = n.y - select(-t, t, n.y >= 0.0);
+    return n;
+}
+
+// Matrices are stored as the first three rows, one per texel.
+fn joint_matrix(frame: u32, joint: u32) -> mat4x4<f32> {
+    let x = i32(joint * 3u);
+    let y = i32(frame);
+    return transpose(mat4x4<f32>(
+        textureLoad(animation, vec2<i32>(x, y), 0),
+        textureLoad(animation, vec2<i32>(x + 1, y), 0),
+        textureLoad(animation, vec2<i32>(x + 2, y), 0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0)
+    ));
+}
+
+[[stage(vertex)]]
+fn vs_main(input: VertexInput) -> VertexOutput {
+    let clip = clips.clips[input.clip];
+    let count = clip.y;
+    let position = (uniforms.time * input.speed + input.time_offset) * clip.z;
+
+    var frame: f32;
+    if (clip.w > 0.5) {
+        frame = position - count * floor(position / count);
+    } else {
+        frame = clamp(position, 0.0, count - 1.0);
+    }
+    let index = floor(frame);
+    var next = index + 1.0;
+    if (next >= count) {
+        if (clip.w > 0.5) {
+            next = 0.0;
+        } else {
+            next = index;
+        }
+    }
+    let frame_0 = u32(clip.x + index);
+    let frame_1 = u32(clip.x + next);
+    let t = frame - index;
+
+    var skin = mat4x4<f32>(vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0), vec4<f32>(0.0));
+    var i = 0;
+    loop {
+        if (i >= 4) {
+            break;
+        }
+        let joint = input.joint_indices[i];
+        let weight = input.joint_weights[i];
+        skin = skin + joint_matrix(frame_0, joint) * ((1.0 - t) * weight) + joint_matrix(frame_1, joint) * (t * weight);
+        continuing {
+            i = i + 1;
+        }
+    }
+
+    let transform = mat4x4<f32>(input.transform_0, input.transform_1, input.transform_2, input.transform_3) * skin;
+    let local = uniforms.position_offset + uniforms.position_scale * input.position;
+    let world = transform * vec4<f32>(local, 1.0);
+    // Normals of quantized meshes are scaled by their bounds.
+    let local_normal = decode_normal(input.normal) / uniforms.position_scale;
+    let normal = (transform * vec4<f32>(local_normal, 0.0)).xyz;
+    return VertexOutput(uniforms.view_proj * world, normal, input.uv);
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let base = textureSample(albedo, albedo_sampler, input.uv) * uniforms.color;
+    let normal = normalize(input.normal);
+
+    var light = uniforms.ambient.rgb;
+    var i = 0u;
+    loop {
+        if (i >= uniforms.light_count) {
+            break;
+        }
+        let diffuse = max(dot(normal, -uniforms.light_directions[i].xyz), 0.0);
+        light = light + uniforms.light_colors[i].rgb * diffuse;
+        continuing {
+            i = i + 1u;
+        }
+    }
+    return vec4<f32>(base.rgb * light, base.a);
+}
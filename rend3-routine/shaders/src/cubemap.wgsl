@@ -0,0 +1,59 @@
+struct EquirectParams {
+    /// Right, up and forward directions of the center of the panorama.
+    basis: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var panorama: texture_2d<f32>;
+[[group(0), binding(1)]]
+var panorama_sampler: sampler;
+[[group(0), binding(2)]]
+var<uniform> params: EquirectParams;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1), interpolate(flat)]] face: u32;
+};
+
+let PI: f32 = 3.14159265358979;
+
+/// Fullscreen triangle, the instance being the face of the cube it covers.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] idx: u32, [[builtin(instance_index)]] face: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((idx << 1u) & 2u), f32(idx & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    out.face = face;
+    return out;
+}
+
+/// Direction a texel of a face of a cube texture is sampled from, faces
+/// being in the order +X, -X, +Y, -Y, +Z, -Z.
+fn cube_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let s = uv.x * 2.0 - 1.0;
+    let t = uv.y * 2.0 - 1.0;
+    switch (face) {
+        case 0u: { return vec3<f32>(1.0, -t, -s); }
+        case 1u: { return vec3<f32>(-1.0, -t, s); }
+        case 2u: { return vec3<f32>(s, 1.0, t); }
+        case 3u: { return vec3<f32>(s, -1.0, -t); }
+        case 4u: { return vec3<f32>(s, -t, 1.0); }
+        default: { return vec3<f32>(-s, -t, -1.0); }
+    }
+}
+
+[[stage(fragment)]]
+fn fs_equirect(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let direction = normalize(cube_direction(in.face, in.uv));
+    // The basis is orthonormal, so its transpose takes world directions back
+    // around the center of the panorama.
+    let local = transpose(params.basis) * vec4<f32>(direction, 0.0);
+    let longitude = atan2(local.x, local.z);
+    let latitude = asin(clamp(local.y, -1.0, 1.0));
+    let uv = vec2<f32>(longitude / (2.0 * PI) + 0.5, 0.5 - latitude / PI);
+    // The longitude wraps around, which would pick the smallest mip along
+    // the seam with implicit derivatives.
+    return vec4<f32>(textureSampleLevel(panorama, panorama_sampler, uv, 0.0).rgb, 1.0);
+}
@@ -0,0 +1,173 @@
+struct FilterParams {
+    /// Perceptual roughness the mip being rendered is filtered for.
+    roughness: f32;
+    sample_count: u32;
+    /// Width of the faces of the first mip of the source, in texels.
+    source_size: f32;
+};
+
+[[group(0), binding(0)]]
+var source: texture_cube<f32>;
+[[group(0), binding(1)]]
+var source_sampler: sampler;
+[[group(0), binding(2)]]
+var<uniform> params: FilterParams;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1), interpolate(flat)]] face: u32;
+};
+
+let PI: f32 = 3.14159265358979;
+
+/// Fullscreen triangle, the instance being the face of the cube it covers.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] idx: u32, [[builtin(instance_index)]] face: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((idx << 1u) & 2u), f32(idx & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    out.face = face;
+    return out;
+}
+
+/// Direction a texel of a face of a cube texture is sampled from, faces
+/// being in the order +X, -X, +Y, -Y, +Z, -Z.
+fn cube_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let s = uv.x * 2.0 - 1.0;
+    let t = uv.y * 2.0 - 1.0;
+    switch (face) {
+        case 0u: { return vec3<f32>(1.0, -t, -s); }
+        case 1u: { return vec3<f32>(-1.0, -t, s); }
+        case 2u: { return vec3<f32>(s, 1.0, t); }
+        case 3u: { return vec3<f32>(s, -1.0, -t); }
+        case 4u: { return vec3<f32>(s, -t, 1.0); }
+        default: { return vec3<f32>(-s, -t, -1.0); }
+    }
+}
+
+/// Van der Corput sequence, the second coordinate of the Hammersley set.
+fn radical_inverse(index: u32) -> f32 {
+    var bits = (index << 16u) | (index >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+fn hammersley(index: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(index) / f32(count), radical_inverse(index));
+}
+
+/// Half vector around +Z distributed like the GGX normal distribution with
+/// `alpha`.
+fn importance_sample_ggx(xi: vec2<f32>, alpha: f32) -> vec3<f32> {
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+}
+
+fn d_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+/// Rotates `v`, around +Z, to be around `n`.
+fn tangent_to_world(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+    var up = vec3<f32>(0.0, 0.0, 1.0);
+    if (abs(n.z) > 0.999) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(up, n));
+    let bitangent = cross(n, tangent);
+    return tangent * v.x + bitangent * v.y + n * v.z;
+}
+
+/// Mip of the source whose texels cover about the solid angle of a sample
+/// with the probability `pdf`, which removes the noise of sampling a bright
+/// texel with few samples.
+fn source_lod(pdf: f32) -> f32 {
+    let sample_angle = 1.0 / (f32(params.sample_count) * pdf + 0.0001);
+    let texel_angle = 4.0 * PI / (6.0 * params.source_size * params.source_size);
+    let max_lod = f32(textureNumLevels(source) - 1);
+    return clamp(0.5 * log2(sample_angle / texel_angle) + 1.0, 0.0, max_lod);
+}
+
+/// Cosine weighted average of the hemisphere around each texel, the
+/// radiance reflected by a white lambertian surface.
+[[stage(fragment)]]
+fn fs_irradiance(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let n = normalize(cube_direction(in.face, in.uv));
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < params.sample_count; i = i + 1u) {
+        let xi = hammersley(i, params.sample_count);
+        let phi = 2.0 * PI * xi.x;
+        let cos_theta = sqrt(1.0 - xi.y);
+        let sin_theta = sqrt(xi.y);
+        let l = tangent_to_world(vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta), n);
+        let lod = source_lod(cos_theta / PI);
+        color = color + textureSampleLevel(source, source_sampler, l, lod).rgb;
+    }
+    return vec4<f32>(color / f32(params.sample_count), 1.0);
+}
+
+/// GGX filtered radiance around each texel, assuming the view direction is
+/// the normal, for the split sum approximation of specular reflections.
+[[stage(fragment)]]
+fn fs_prefilter(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let n = normalize(cube_direction(in.face, in.uv));
+    if (params.roughness == 0.0) {
+        return vec4<f32>(textureSampleLevel(source, source_sampler, n, 0.0).rgb, 1.0);
+    }
+
+    let alpha = params.roughness * params.roughness;
+    var color = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < params.sample_count; i = i + 1u) {
+        let h = tangent_to_world(importance_sample_ggx(hammersley(i, params.sample_count), alpha), n);
+        let l = 2.0 * dot(n, h) * h - n;
+        let n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            // With the view along the normal, the pdf of l is D / 4.
+            let lod = source_lod(d_ggx(max(dot(n, h), 0.0), alpha) * 0.25);
+            color = color + textureSampleLevel(source, source_sampler, l, lod).rgb * n_dot_l;
+            weight = weight + n_dot_l;
+        }
+    }
+    return vec4<f32>(color / max(weight, 0.0001), 1.0);
+}
+
+/// Scale and bias applied to F0 by the specular lobe of a surface, for the
+/// cosine between the normal and the view along x and the perceptual
+/// roughness along y.
+[[stage(fragment)]]
+fn fs_brdf_lut(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let n_dot_v = max(in.uv.x, 0.0001);
+    let roughness = in.uv.y;
+    let alpha = roughness * roughness;
+    let k = alpha * 0.5;
+    let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+    let sample_count = 512u;
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < sample_count; i = i + 1u) {
+        let h = importance_sample_ggx(hammersley(i, sample_count), alpha);
+        let l = 2.0 * dot(v, h) * h - v;
+        let n_dot_l = l.z;
+        if (n_dot_l > 0.0) {
+            let n_dot_h = max(h.z, 0.0);
+            let v_dot_h = max(dot(v, h), 0.0);
+            let g = (n_dot_v / (n_dot_v * (1.0 - k) + k)) * (n_dot_l / (n_dot_l * (1.0 - k) + k));
+            let g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale = scale + (1.0 - fresnel) * g_vis;
+            bias = bias + fresnel * g_vis;
+        }
+    }
+    return vec4<f32>(scale / f32(sample_count), bias / f32(sample_count), 0.0, 1.0);
+}
@@ -0,0 +1,36 @@
+/// Frame uniforms at group 0, see object_bind_group_layouts in
+/// common/pipeline.rs. Prepended to every custom shader.
+
+struct Frustum {
+    left: vec4<f32>;
+    right: vec4<f32>;
+    top: vec4<f32>;
+    bottom: vec4<f32>;
+    near: vec4<f32>;
+};
+
+/// Layout of FrameUniforms in uniforms.rs.
+struct FrameUniforms {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    origin_view_proj: mat4x4<f32>;
+    inv_view: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_origin_view_proj: mat4x4<f32>;
+    frustum: Frustum;
+    ambient: vec4<f32>;
+    resolution: vec2<u32>;
+    clip_plane_count: u32;
+    /// Bits of VertexQuantization, see object_direction.
+    vertex_quantization: u32;
+    clip_planes: array<vec4<f32>, 4>;
+};
+
+[[group(0), binding(0)]]
+var primary_sampler: sampler;
+[[group(0), binding(1)]]
+var nearest_sampler: sampler;
+[[group(0), binding(2)]]
+var comparison_sampler: sampler_comparison;
+[[group(0), binding(3)]]
+var<uniform> frame: FrameUniforms;
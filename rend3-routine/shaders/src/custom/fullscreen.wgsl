@@ -0,0 +1,18 @@
+/// Vertex shader of full-screen passes, and the texture they read at group 1.
+
+struct FullscreenVertex {
+    [[builtin(position)]] position: vec4<f32>;
+    /// 0 to 1 across the screen, y down.
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[group(1), binding(0)]]
+var source: texture_2d<f32>;
+
+/// A single triangle covering the screen.
+[[stage(vertex)]]
+fn fullscreen_vs([[builtin(vertex_index)]] index: u32) -> FullscreenVertex {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return FullscreenVertex(position, uv);
+}
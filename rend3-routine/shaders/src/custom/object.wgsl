@@ -0,0 +1,89 @@
+/// Vertex inputs and per object data of passes drawing objects, for each
+/// profile. See object_bind_group_layouts in common/pipeline.rs.
+
+/// Layout of PerObjectDataAbi in common/interfaces.rs.
+struct ObjectData {
+    model_view: mat4x4<f32>;
+    model_view_proj: mat4x4<f32>;
+    material_idx: u32;
+    texture_layer: u32;
+    inv_squared_scale: vec3<f32>;
+    /// ObjectFade::to_shader, see object_faded_out.
+    fade: f32;
+};
+
+/// Attributes of the vertex buffers rend3 binds, take it as the input of the
+/// vertex shader.
+struct ObjectVertex {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(2)]] tangent: vec3<f32>;
+    [[location(3)]] uv0: vec2<f32>;
+    [[location(4)]] uv1: vec2<f32>;
+    [[location(5)]] color: vec4<f32>;
+#ifdef GPU_DRIVEN
+    [[location(8)]] object_index: u32;
+#else
+    [[builtin(instance_index)]] object_index: u32;
+#endif
+};
+
+#ifdef DOWNLEVEL
+/// Bound with a dynamic offset pointing at the current object.
+[[group(1), binding(0)]]
+var<uniform> object_data_single: ObjectData;
+
+fn object_data(vertex: ObjectVertex) -> ObjectData {
+    return object_data_single;
+}
+#else
+struct ObjectDataArray {
+    objects: array<ObjectData>;
+};
+
+[[group(1), binding(0)]]
+var<storage, read> object_data_array: ObjectDataArray;
+
+fn object_data(vertex: ObjectVertex) -> ObjectData {
+    return object_data_array.objects[vertex.object_index];
+}
+#endif
+
+/// Undoes the octahedral encoding of the normal or tangent of a vertex if
+/// the renderer quantizes them. The result isn't normalized.
+fn object_direction(direction: vec3<f32>) -> vec3<f32> {
+    // VertexQuantization::NORMALS
+    if ((frame.vertex_quantization & 1u) == 0u) {
+        return direction;
+    }
+    var n = vec3<f32>(direction.xy, 1.0 - abs(direction.x) - abs(direction.y));
+    let t = max(-n.z, 0.0);
+    n.x = n.x - select(-t, t, n.x >= 0.0);
+    n.y = n.y - select(-t, t, n.y >= 0.0);
+    return n;
+}
+
+/// Normal of the vertex in view space, from the vertex's `normal`.
+fn object_view_normal(data: ObjectData, normal: vec3<f32>) -> vec3<f32> {
+    let model_view = mat3x3<f32>(data.model_view[0].xyz, data.model_view[1].xyz, data.model_view[2].xyz);
+    return normalize(model_view * (data.inv_squared_scale * object_direction(normal)));
+}
+
+/// Whether the fragment at `frag_coord` is left out of an object with the
+/// `fade` of its ObjectData, see ObjectFade. Pass the fade on from the vertex
+/// shader with `[[interpolate(flat)]]` and discard these fragments to dither
+/// like the built-in passes.
+fn object_faded_out(fade: f32, frag_coord: vec2<f32>) -> bool {
+    var bayer = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0
+    );
+    let pixel = vec2<u32>(frag_coord) % vec2<u32>(4u);
+    let threshold = (bayer[pixel.y * 4u + pixel.x] + 0.5) / 16.0;
+    if (fade >= 0.0) {
+        return threshold >= fade;
+    }
+    return threshold < -1.0 - fade;
+}
@@ -0,0 +1,34 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+/// Distance field of the built-in font, see common/font.rs
+[[group(0), binding(0)]]
+var atlas: texture_2d<f32>;
+[[group(0), binding(1)]]
+var atlas_sampler: sampler;
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec4<f32>,
+    [[location(1)]] uv: vec2<f32>,
+    [[location(2)]] color: vec4<f32>,
+) -> VertexOutput {
+    return VertexOutput(position, uv, color);
+}
+
+[[stage(fragment)]]
+fn fs_line(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return input.color;
+}
+
+[[stage(fragment)]]
+fn fs_text(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let distance = textureSample(atlas, atlas_sampler, input.uv).r;
+    // Antialias over a pixel, whatever size the text is drawn at.
+    let width = max(fwidth(distance), 0.0001);
+    let coverage = clamp((distance - 0.5) / (2.0 * width) + 0.5, 0.0, 1.0);
+    return vec4<f32>(input.color.rgb, input.color.a * coverage);
+}
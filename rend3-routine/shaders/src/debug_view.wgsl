@@ -0,0 +1,47 @@
+// Draws the red channel of a debug target as a heatmap, see DebugViewRoutine
+// in lighting_debug.rs.
+
+struct DebugViewUniforms {
+    /// Value drawn red, zero being drawn black.
+    range: f32;
+    opacity: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: DebugViewUniforms;
+[[group(0), binding(1)]]
+var source: texture_2d<f32>;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] id: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(id / 2u) * 2.0, 1.0 - f32(id % 2u) * 2.0);
+    return VertexOutput(vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0), uv);
+}
+
+// Must match heatmap in lighting_debug.rs.
+fn heatmap(value: f32) -> vec3<f32> {
+    if (value <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let t = min(value, 1.0) * 3.0;
+    if (t < 1.0) {
+        return vec3<f32>(0.0, t, 1.0 - t);
+    }
+    if (t < 2.0) {
+        return vec3<f32>(t - 1.0, 1.0, 0.0);
+    }
+    return vec3<f32>(1.0, 3.0 - t, 0.0);
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let size = textureDimensions(source);
+    let pixel = min(vec2<i32>(input.uv * vec2<f32>(size)), size - vec2<i32>(1));
+    let value = textureLoad(source, pixel, 0).r;
+    return vec4<f32>(heatmap(value / view.range), view.opacity);
+}
@@ -4,10 +4,17 @@
 #extension GL_EXT_nonuniform_qualifier : require
 #endif
 
+#include "structures.glsl"
+
 layout(location = 0) in vec4 i_position;
 layout(location = 1) in vec2 i_coords0;
 layout(location = 2) in vec4 i_color;
 layout(location = 3) flat in uint i_material;
+layout(location = 4) flat in float i_fade;
+
+layout(set = 0, binding = 3) uniform UniformBuffer {
+    UniformData uniforms;
+};
 
 #ifdef ALPHA_CUTOUT
 
@@ -17,11 +24,21 @@ layout(set = 1, binding = 1, std430) readonly buffer MaterialBuffer {
     float material_data[];
 };
 layout(set = 3, binding = 0) uniform texture2D textures[];
+#define MATERIAL_DATA(idx) material_data[idx]
 #endif
-#ifdef CPU_DRIVEN
+#ifdef DOWNLEVEL
+// Uniform arrays have a 16 byte stride, so the floats are packed in vec4s.
+layout(set = 3, binding = 0) uniform TextureData {
+    vec4 material_data_packed[16];
+};
+#define MATERIAL_DATA(idx) material_data_packed[(idx) / 4][(idx) % 4]
+#elif defined(CPU_DRIVEN)
 layout(set = 3, binding = 0, std430) readonly buffer TextureData {
     float material_data[];
 };
+#define MATERIAL_DATA(idx) material_data[idx]
+#endif
+#ifdef CPU_DRIVEN
 layout(set = 3, binding = 1) uniform texture2D texture;
 #endif
 layout(set = 2, binding = 0) uniform DataAbi {
@@ -33,21 +50,21 @@ layout(set = 2, binding = 0) uniform DataAbi {
 
 void main() {
     uint base_material_offset = stride * i_material;
-    float cutoff = material_data[base_material_offset + cutoff_offset];
+    float cutoff = MATERIAL_DATA(base_material_offset + cutoff_offset);
 
     vec2 coords;
     if (uv_transform_offset != 0xFFFFFFFF) {
         uint base_transform_offset = base_material_offset + uv_transform_offset;
         mat3 transform = mat3(
-            material_data[base_transform_offset + 0],
-            material_data[base_transform_offset + 1],
-            material_data[base_transform_offset + 2],
-            material_data[base_transform_offset + 4],
-            material_data[base_transform_offset + 5],
-            material_data[base_transform_offset + 6],
-            material_data[base_transform_offset + 8],
-            material_data[base_transform_offset + 9],
-            material_data[base_transform_offset + 10]
+            MATERIAL_DATA(base_transform_offset + 0),
+            MATERIAL_DATA(base_transform_offset + 1),
+            MATERIAL_DATA(base_transform_offset + 2),
+            MATERIAL_DATA(base_transform_offset + 4),
+            MATERIAL_DATA(base_transform_offset + 5),
+            MATERIAL_DATA(base_transform_offset + 6),
+            MATERIAL_DATA(base_transform_offset + 8),
+            MATERIAL_DATA(base_transform_offset + 9),
+            MATERIAL_DATA(base_transform_offset + 10)
         );
         coords = vec2(transform * vec3(i_coords0, 1.0));
     } else {
@@ -56,8 +73,13 @@ void main() {
     vec2 uvdx = dFdx(coords);
     vec2 uvdy = dFdy(coords);
 
+    // Only after the derivatives, which need uniform control flow.
+    if (is_clipped(uniforms, uniforms.inv_view_proj * i_position) || is_faded_out(i_fade, gl_FragCoord.xy)) {
+        discard;
+    }
+
     #ifdef GPU_DRIVEN
-    uint texture_index = floatBitsToUint(material_data[base_material_offset + texture_offset]);
+    uint texture_index = floatBitsToUint(MATERIAL_DATA(base_material_offset + texture_offset));
     if (texture_index != 0) {
         float alpha = textureGrad(sampler2D(textures[nonuniformEXT(texture_index - 1)], primary_sampler), coords, uvdx, uvdy).a;
 
@@ -67,7 +89,7 @@ void main() {
     }
     #endif
     #ifdef CPU_DRIVEN
-    uint texture_enable_bitflags = floatBitsToUint(material_data[base_material_offset + texture_offset]);
+    uint texture_enable_bitflags = floatBitsToUint(MATERIAL_DATA(base_material_offset + texture_offset));
     if (bool(texture_enable_bitflags & 0x1)) {
         float alpha = textureGrad(sampler2D(texture, primary_sampler), coords, uvdx, uvdy).a;
 
@@ -78,5 +100,9 @@ void main() {
     #endif
 }
 #else // ALPHA_CUTOUT
-void main() {}
+void main() {
+    if (is_clipped(uniforms, uniforms.inv_view_proj * i_position) || is_faded_out(i_fade, gl_FragCoord.xy)) {
+        discard;
+    }
+}
 #endif // ALPHA_CUTOUT
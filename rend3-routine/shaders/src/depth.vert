@@ -17,25 +17,42 @@ layout(location = 0) out vec4 o_position;
 layout(location = 1) out vec2 o_coords0;
 layout(location = 2) out vec4 o_color;
 layout(location = 3) flat out uint o_material;
+layout(location = 4) flat out float o_fade;
 
+#ifdef DOWNLEVEL
+// Bound with a dynamic offset pointing at the current object.
+layout(set = 1, binding = 0) uniform ObjectOutputDataBuffer {
+    ObjectOutputData object_output;
+};
+#else
 layout(set = 1, binding = 0, std430) readonly buffer ObjectOutputDataBuffer {
     ObjectOutputData object_output[];
 };
+#endif
+#ifdef VERTEX_SKINNING
+#include "skinning.glsl"
+#endif
 
 void main() {
-    #ifdef CPU_DRIVEN
-    uint object_idx = gl_InstanceIndex;
+    #if defined(DOWNLEVEL)
+    ObjectOutputData data = object_output;
+    #elif defined(CPU_DRIVEN)
+    ObjectOutputData data = object_output[gl_InstanceIndex];
     #else
-    uint object_idx = i_object_idx;
+    ObjectOutputData data = object_output[i_object_idx];
     #endif
 
-    ObjectOutputData data = object_output[object_idx];
+    vec4 model_position = vec4(i_position, 1.0);
+    #ifdef VERTEX_SKINNING
+    model_position = skinning_matrix(data.joint_offset) * model_position;
+    #endif
 
-    vec4 position = data.model_view_proj * vec4(i_position, 1.0);
+    vec4 position = data.model_view_proj * model_position;
     o_position = position;
     gl_Position = position;
 
     o_material = data.material_idx;
+    o_fade = data.fade;
 
     o_color = i_color;
 
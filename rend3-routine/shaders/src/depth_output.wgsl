@@ -0,0 +1,14 @@
+[[group(0), binding(0)]]
+var depth: texture_depth_2d;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] id: u32) -> [[builtin(position)]] vec4<f32> {
+    return vec4<f32>(f32(id / 2u) * 4.0 - 1.0, f32(id % 2u) * 4.0 - 1.0, 0.0, 1.0);
+}
+
+// Depth as stored in the depth target, in every color channel.
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let value = textureLoad(depth, vec2<i32>(position.xy), 0);
+    return vec4<f32>(value, value, value, 1.0);
+}
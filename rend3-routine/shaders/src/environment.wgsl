@@ -0,0 +1,53 @@
+struct EquirectParams {
+    /// Right, up and forward directions of the center of the image.
+    basis: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var cube: texture_cube<f32>;
+[[group(0), binding(1)]]
+var cube_sampler: sampler;
+[[group(0), binding(2)]]
+var<uniform> params: EquirectParams;
+[[group(0), binding(3)]]
+var face: texture_2d<f32>;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+let PI: f32 = 3.14159265358979;
+
+/// Fullscreen triangle, with uvs going down like texture coordinates.
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] idx: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((idx << 1u) & 2u), f32(idx & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_face([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    return textureLoad(face, vec2<i32>(position.xy), 0);
+}
+
+/// Copies the face flipped horizontally, for faces rendered from right
+/// handed cameras.
+[[stage(fragment)]]
+fn fs_face_mirrored([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let coords = vec2<i32>(position.xy);
+    let width = textureDimensions(face).x;
+    return textureLoad(face, vec2<i32>(width - 1 - coords.x, coords.y), 0);
+}
+
+[[stage(fragment)]]
+fn fs_equirect(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let longitude = (in.uv.x - 0.5) * 2.0 * PI;
+    let latitude = (0.5 - in.uv.y) * PI;
+    let local = vec3<f32>(cos(latitude) * sin(longitude), sin(latitude), cos(latitude) * cos(longitude));
+    let direction = (params.basis * vec4<f32>(local, 0.0)).xyz;
+    return vec4<f32>(textureSample(cube, cube_sampler, direction).rgb, 1.0);
+}
@@ -0,0 +1,24 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] texel: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+/// Coverage of the font atlas, see common/font.rs
+[[group(0), binding(0)]]
+var atlas: texture_2d<f32>;
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec2<f32>,
+    [[location(1)]] texel: vec2<f32>,
+    [[location(2)]] color: vec4<f32>,
+) -> VertexOutput {
+    return VertexOutput(vec4<f32>(position, 0.0, 1.0), texel, color);
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let coverage = textureLoad(atlas, vec2<i32>(floor(input.texel)), 0).r;
+    return vec4<f32>(input.color.rgb, input.color.a * coverage);
+}
@@ -0,0 +1,186 @@
+struct BakeUniforms {
+    // Orthographic projection of the bounding sphere of the mesh seen from
+    // one direction.
+    view_proj: mat4x4<f32>;
+    color: vec4<f32>;
+    // Quantized positions are position_offset + position_scale * position.
+    position_offset: vec3<f32>;
+    position_scale: vec3<f32>;
+    alpha_cutoff: f32;
+    // Bits of VertexQuantization.
+    vertex_quantization: u32;
+};
+
+struct ImpostorParams {
+    // Bounding sphere of the mesh, center in xyz and radius in w.
+    sphere: vec4<f32>;
+    directions: u32;
+    columns: u32;
+    rows: u32;
+};
+
+struct DrawUniforms {
+    view_proj: mat4x4<f32>;
+    camera: vec4<f32>;
+    ambient: vec4<f32>;
+    // Direction the light travels in xyz.
+    light_directions: array<vec4<f32>, 4>;
+    // Color times intensity in rgb.
+    light_colors: array<vec4<f32>, 4>;
+    light_count: u32;
+};
+
+struct BakeVertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] normal: vec3<f32>;
+    [[location(1)]] uv: vec2<f32>;
+};
+
+struct BakeOutput {
+    [[location(0)]] albedo: vec4<f32>;
+    [[location(1)]] normal: vec4<f32>;
+};
+
+struct DrawVertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    // Rotation of the instance around the up axis, as cos and sin.
+    [[location(1)]] yaw: vec2<f32>;
+    // Opacity of the impostor while it cross-fades with the full object.
+    [[location(2), interpolate(flat)]] fade: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> bake: BakeUniforms;
+[[group(0), binding(1)]]
+var bake_albedo: texture_2d<f32>;
+[[group(0), binding(2)]]
+var bake_sampler: sampler;
+
+[[stage(vertex)]]
+fn bake_vs(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] normal: vec3<f32>,
+    [[location(2)]] uv: vec2<f32>
+) -> BakeVertexOutput {
+    var local_normal = normal;
+    // VertexQuantization::NORMALS, undoing the octahedral encoding.
+    if ((bake.vertex_quantization & 1u) != 0u) {
+        local_normal = vec3<f32>(normal.xy, 1.0 - abs(normal.x) - abs(normal.y));
+        let t = max(-local_normal.z, 0.0);
+        local_normal.x = local_normal.x - select(-t, t, local_normal.x >= 0.0);
+        local_normal.y = local_normal.y - select(-t, t, local_normal.y >= 0.0);
+    }
+    // Normals of quantized meshes are scaled by their bounds.
+    local_normal = local_normal / bake.position_scale;
+    let local = bake.position_offset + bake.position_scale * position;
+    return BakeVertexOutput(bake.view_proj * vec4<f32>(local, 1.0), local_normal, uv);
+}
+
+[[stage(fragment)]]
+fn bake_fs(input: BakeVertexOutput) -> BakeOutput {
+    let albedo = textureSample(bake_albedo, bake_sampler, input.uv) * bake.color;
+    if (albedo.a < bake.alpha_cutoff) {
+        discard;
+    }
+    return BakeOutput(vec4<f32>(albedo.rgb, 1.0), vec4<f32>(normalize(input.normal) * 0.5 + 0.5, 1.0));
+}
+
+[[group(0), binding(0)]]
+var<uniform> draw: DrawUniforms;
+[[group(1), binding(0)]]
+var<uniform> params: ImpostorParams;
+[[group(1), binding(1)]]
+var atlas_albedo: texture_2d<f32>;
+[[group(1), binding(2)]]
+var atlas_normal: texture_2d<f32>;
+[[group(1), binding(3)]]
+var atlas_sampler: sampler;
+
+let TAU: f32 = 6.2831853;
+
+var<private> CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, 1.0)
+);
+
+[[stage(vertex)]]
+fn draw_vs(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    [[location(0)]] transform_0: vec4<f32>,
+    [[location(1)]] transform_1: vec4<f32>,
+    [[location(2)]] transform_2: vec4<f32>,
+    [[location(3)]] transform_3: vec4<f32>,
+    [[location(4)]] fade: f32
+) -> DrawVertexOutput {
+    let transform = mat4x4<f32>(transform_0, transform_1, transform_2, transform_3);
+    let center = (transform * vec4<f32>(params.sphere.xyz, 1.0)).xyz;
+    let radius = params.sphere.w * length(transform_0.xyz);
+
+    // The quad turns around the up axis to face the camera.
+    let to_camera = draw.camera.xyz - center;
+    let facing = normalize(vec2<f32>(to_camera.x, to_camera.z) + vec2<f32>(0.0, 1e-6));
+    let right = vec3<f32>(facing.y, 0.0, -facing.x);
+
+    // Pick the baked view closest to the direction of the camera, relative
+    // to the rotation of the instance.
+    let yaw = atan2(transform_2.x, transform_2.z);
+    let step = TAU / f32(params.directions);
+    let angle = atan2(facing.x, facing.y) - yaw;
+    let cell = u32(i32(round(angle / step)) % i32(params.directions) + i32(params.directions)) % params.directions;
+
+    let corner = CORNERS[vertex_index];
+    let position = center + (right * corner.x + vec3<f32>(0.0, 1.0, 0.0) * corner.y) * radius;
+    let cell_uv = vec2<f32>(corner.x + 1.0, 1.0 - corner.y) * 0.5;
+    let uv = (vec2<f32>(f32(cell % params.columns), f32(cell / params.columns)) + cell_uv)
+        / vec2<f32>(f32(params.columns), f32(params.rows));
+
+    return DrawVertexOutput(draw.view_proj * vec4<f32>(position, 1.0), uv, vec2<f32>(cos(yaw), sin(yaw)), fade);
+}
+
+// Same dither as objects use for ObjectFade, so the pixels the fading object
+// leaves out are the ones the impostor draws.
+fn faded_out(fade: f32, frag_coord: vec2<f32>) -> bool {
+    var bayer = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0
+    );
+    let pixel = vec2<u32>(frag_coord) % vec2<u32>(4u);
+    return (bayer[pixel.y * 4u + pixel.x] + 0.5) / 16.0 >= fade;
+}
+
+[[stage(fragment)]]
+fn draw_fs(input: DrawVertexOutput) -> [[location(0)]] vec4<f32> {
+    let albedo = textureSample(atlas_albedo, atlas_sampler, input.uv);
+    let local = textureSample(atlas_normal, atlas_sampler, input.uv).xyz * 2.0 - 1.0;
+    if (albedo.a < 0.5 || faded_out(input.fade, input.position.xy)) {
+        discard;
+    }
+    // Normals are baked in the space of the mesh, so they turn with the
+    // instance.
+    let normal = normalize(vec3<f32>(
+        local.x * input.yaw.x + local.z * input.yaw.y,
+        local.y,
+        -local.x * input.yaw.y + local.z * input.yaw.x
+    ));
+
+    var light = draw.ambient.rgb;
+    var i = 0u;
+    loop {
+        if (i >= draw.light_count) {
+            break;
+        }
+        let diffuse = max(dot(normal, -draw.light_directions[i].xyz), 0.0);
+        light = light + draw.light_colors[i].rgb * diffuse;
+        continuing {
+            i = i + 1u;
+        }
+    }
+    return vec4<f32>(albedo.rgb * light, 1.0);
+}
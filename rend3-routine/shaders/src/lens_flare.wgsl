@@ -0,0 +1,98 @@
+/// Flare elements drawn as additive quads, one instance per element. See
+/// lens_flare.rs for the instance layout and defines.
+
+struct FlareUniforms {
+    resolution: vec2<f32>;
+    /// 1 if closer depths are greater.
+    reversed: u32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    /// -1 to 1 across the quad.
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+    [[location(2)]] shape: vec2<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: FlareUniforms;
+#ifndef DOWNLEVEL
+[[group(0), binding(1)]]
+#ifdef MULTISAMPLED
+var scene_depth: texture_depth_multisampled_2d;
+#else
+var scene_depth: texture_depth_2d;
+#endif
+#endif
+
+let SHAPE_GHOST: f32 = 0.0;
+let SHAPE_HALO: f32 = 1.0;
+let SHAPE_GLOW: f32 = 2.0;
+let SHAPE_STREAK: f32 = 3.0;
+
+/// Fraction of a 5x5 grid of pixels around the light where the scene is
+/// further away than the light. GL can't load from depth textures, so lights
+/// are always visible on the downlevel profile.
+fn light_visibility(light: vec4<f32>) -> f32 {
+#ifdef DOWNLEVEL
+    return 1.0;
+#else
+    let pixel = vec2<i32>((vec2<f32>(light.x, -light.y) * 0.5 + 0.5) * uniforms.resolution);
+    let max_pixel = vec2<i32>(uniforms.resolution) - 1;
+    var visible = 0.0;
+    for (var y = -2; y <= 2; y = y + 1) {
+        for (var x = -2; x <= 2; x = x + 1) {
+            let tap = clamp(pixel + vec2<i32>(x, y) * 2, vec2<i32>(0), max_pixel);
+            let scene = textureLoad(scene_depth, tap, 0);
+            let behind = select(scene >= light.z, scene <= light.z, uniforms.reversed != 0u);
+            visible = visible + select(0.0, 1.0, behind);
+        }
+    }
+    return visible / 25.0;
+#endif
+}
+
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex: u32,
+    /// Center and half size of the quad in NDC.
+    [[location(0)]] quad: vec4<f32>,
+    [[location(1)]] color: vec4<f32>,
+    /// NDC position and depth of the light, w is 1 to test the depth buffer.
+    [[location(2)]] light: vec4<f32>,
+    /// Shape and halo thickness.
+    [[location(3)]] shape: vec2<f32>,
+) -> VertexOutput {
+    let uv = vec2<f32>(f32(vertex & 1u), f32((vertex >> 1u) & 1u)) * 2.0 - 1.0;
+
+    var visibility = 1.0;
+    if (light.w != 0.0) {
+        visibility = light_visibility(light);
+    }
+
+    var output: VertexOutput;
+    output.position = vec4<f32>(quad.xy + uv * quad.zw, 0.0, 1.0);
+    output.uv = uv;
+    output.color = color * visibility;
+    output.shape = shape;
+    return output;
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let radius = length(input.uv);
+    var intensity: f32;
+    if (input.shape.x == SHAPE_GHOST) {
+        intensity = 1.0 - smoothStep(0.7, 1.0, radius);
+    } else if (input.shape.x == SHAPE_HALO) {
+        let thickness = max(input.shape.y, 0.001);
+        intensity = 1.0 - smoothStep(0.0, thickness, abs(radius - (1.0 - thickness)));
+    } else if (input.shape.x == SHAPE_GLOW) {
+        let falloff = max(1.0 - radius, 0.0);
+        intensity = falloff * falloff;
+    } else {
+        intensity = max(1.0 - abs(input.uv.x), 0.0) * exp(-input.uv.y * input.uv.y * 8.0);
+    }
+    return vec4<f32>(input.color.rgb * input.color.a * intensity, 0.0);
+}
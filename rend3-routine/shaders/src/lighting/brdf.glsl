@@ -9,6 +9,24 @@ float D_GGX(float NoH, float a) {
     return a2 / (PI * f * f);
 }
 
+// at and ab are the roughnesses along the tangent t and the bitangent b.
+float D_GGX_Anisotropic(float NoH, vec3 h, vec3 t, vec3 b, float at, float ab) {
+    float ToH = dot(t, h);
+    float BoH = dot(b, h);
+    float a2 = at * ab;
+    vec3 v = vec3(ab * ToH, at * BoH, a2 * NoH);
+    float v2 = dot(v, v);
+    float w2 = a2 / v2;
+    return a2 * w2 * w2 * (1.0 / PI);
+}
+
+// "Charlie" sheen distribution of Estevez and Kulla, for cloth.
+float D_Charlie(float a, float NoH) {
+    float inv_alpha = 1.0 / a;
+    float sin2h = max(1.0 - NoH * NoH, 0.0078125);
+    return (2.0 + inv_alpha) * pow(sin2h, inv_alpha * 0.5) / (2.0 * PI);
+}
+
 vec3 F_Schlick(float u, vec3 f0, float f90) {
     return f0 + (f90 - f0) * pow(1.0 - u, 5.0);
 }
@@ -35,4 +53,14 @@ float V_SmithGGXCorrelated(float NoV, float NoL, float a) {
     return 0.5 / (GGXV + GGXL);
 }
 
+float V_Neubelt(float NoV, float NoL) {
+    return saturate(1.0 / (4.0 * (NoL + NoV - NoL * NoV)));
+}
+
+float V_SmithGGXCorrelated_Anisotropic(float at, float ab, float ToV, float BoV, float ToL, float BoL, float NoV, float NoL) {
+    float lambdaV = NoL * length(vec3(at * ToV, ab * BoV, NoV));
+    float lambdaL = NoV * length(vec3(at * ToL, ab * BoL, NoL));
+    return saturate(0.5 / (lambdaV + lambdaL));
+}
+
 #endif
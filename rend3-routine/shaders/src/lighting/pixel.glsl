@@ -35,10 +35,66 @@ struct PixelData {
     float clear_coat_roughness;
     float clear_coat_perceptual_roughness;
     float anisotropy;
+    // Direction the highlight stretches across, before following the normal
+    vec3 anisotropic_direction;
     float ambient_occlusion;
     uint material_flags;
+    // Direction of the hair strands
+    vec3 tangent;
+    vec4 hair;
+    // Color, then perceptual roughness
+    vec4 sheen;
 };
 
+// The interpolated normal, flipped on back faces of double sided materials so
+// they are lit as if they were facing the viewer.
+vec3 vertex_normal(MATERIAL_TYPE material) {
+    if (MATERIAL_FLAG(FLAGS_DOUBLE_SIDED) && !gl_FrontFacing) {
+        return -i_normal;
+    }
+    return i_normal;
+}
+
+// Marches the view ray through the height map in tangent space, returning the
+// coordinates where it first dips below the surface. Red is the height, with
+// 1.0 at the surface of the mesh.
+vec2 parallax_occlusion(MATERIAL_TYPE material, sampler s, vec2 coords, vec2 uvdx, vec2 uvdy) {
+    vec3 normal = normalize(vertex_normal(material));
+    vec3 tangent = normalize(i_tangent);
+    vec3 bitangent = cross(normal, tangent);
+    vec3 view = -normalize(i_view_position.xyz);
+    vec3 view_tangent = vec3(dot(view, tangent), dot(view, bitangent), dot(view, normal));
+
+    uint steps = max(material.parallax_steps, 1);
+    float layer_depth = 1.0 / float(steps);
+    // Clamped so grazing angles don't shift the coordinates across the whole texture.
+    vec2 delta = view_tangent.xy / max(view_tangent.z, 0.05) * material.parallax_scale * layer_depth;
+
+    vec2 current = coords;
+    float current_depth = 0.0;
+    float sampled_depth = 1.0 - textureGrad(sampler2D(HEIGHT_TEXTURE, s), current, uvdx, uvdy).r;
+    vec2 previous = current;
+    float previous_depth = current_depth;
+    float previous_sampled_depth = sampled_depth;
+    for (uint i = 0; i < steps && current_depth < sampled_depth; ++i) {
+        previous = current;
+        previous_depth = current_depth;
+        previous_sampled_depth = sampled_depth;
+        current -= delta;
+        current_depth += layer_depth;
+        sampled_depth = 1.0 - textureGrad(sampler2D(HEIGHT_TEXTURE, s), current, uvdx, uvdy).r;
+    }
+
+    // Interpolates between the last two layers to find where the ray crosses the surface.
+    if (MATERIAL_FLAG(FLAGS_PARALLAX_REFINE) && current_depth > 0.0 && current_depth >= sampled_depth) {
+        float after = sampled_depth - current_depth;
+        float before = previous_sampled_depth - previous_depth;
+        current = mix(current, previous, after / (after - before));
+    }
+
+    return current;
+}
+
 PixelData get_per_pixel_data_sampled(MATERIAL_TYPE material, sampler s) {
     PixelData pixel;
     
@@ -46,6 +102,14 @@ PixelData get_per_pixel_data_sampled(MATERIAL_TYPE material, sampler s) {
     vec2 uvdx = dFdx(coords);
     vec2 uvdy = dFdy(coords);
 
+    if (HAS_HEIGHT_TEXTURE) {
+        coords = parallax_occlusion(material, s, coords, uvdx, uvdy);
+    }
+
+    vec2 detail_coords = coords * material.detail_transform.xy + material.detail_transform.zw;
+    vec2 detail_uvdx = uvdx * material.detail_transform.xy;
+    vec2 detail_uvdy = uvdy * material.detail_transform.xy;
+
     if (MATERIAL_FLAG(FLAGS_ALBEDO_ACTIVE)) {
         if (HAS_ALBEDO_TEXTURE) {
             pixel.albedo = textureGrad(sampler2D(ALBEDO_TEXTURE, s), coords, uvdx, uvdy);
@@ -63,31 +127,42 @@ PixelData get_per_pixel_data_sampled(MATERIAL_TYPE material, sampler s) {
         pixel.albedo = vec4(0.0, 0.0, 0.0, 1.0);
     }
     pixel.albedo *= material.albedo;
+    // Mid gray leaves the albedo as is.
+    if (HAS_DETAIL_ALBEDO_TEXTURE) {
+        pixel.albedo.rgb *= textureGrad(sampler2D(DETAIL_ALBEDO_TEXTURE, s), detail_coords, detail_uvdx, detail_uvdy).rgb * 2.0;
+    }
 
     if (MATERIAL_FLAG(FLAGS_UNLIT)) {
-        pixel.normal = normalize(i_normal);
+        pixel.normal = normalize(vertex_normal(material));
     }
     else {
-        if (HAS_NORMAL_TEXTURE) {
-            vec4 texture_read = textureGrad(sampler2D(NORMAL_TEXTURE, s), coords, uvdx, uvdy);
-            vec3 normal;
-            if (MATERIAL_FLAG(FLAGS_BICOMPONENT_NORMAL)) {
-                vec2 bicomp;
-                if (MATERIAL_FLAG(FLAGS_SWIZZLED_NORMAL)) {
-                    bicomp = texture_read.ag;
+        if (HAS_NORMAL_TEXTURE || HAS_DETAIL_NORMAL_TEXTURE) {
+            vec3 normal = vec3(0.0, 0.0, 1.0);
+            if (HAS_NORMAL_TEXTURE) {
+                vec4 texture_read = textureGrad(sampler2D(NORMAL_TEXTURE, s), coords, uvdx, uvdy);
+                if (MATERIAL_FLAG(FLAGS_BICOMPONENT_NORMAL)) {
+                    vec2 bicomp;
+                    if (MATERIAL_FLAG(FLAGS_SWIZZLED_NORMAL)) {
+                        bicomp = texture_read.ag;
+                    } else {
+                        bicomp = texture_read.rg;
+                    }
+                    bicomp = bicomp * 2.0 - 1.0;
+
+                    normal = vec3(bicomp, sqrt(1 - (bicomp.r * bicomp.r) - (bicomp.g * bicomp.g)));
                 } else {
-                    bicomp = texture_read.rg;
+                    normal = normalize(texture_read.rgb * 2.0 - 1.0);
+                }
+                if (MATERIAL_FLAG(FLAGS_YDOWN_NORMAL)) {
+                    normal.y = -normal.y;
                 }
-                bicomp = bicomp * 2.0 - 1.0;
-
-                normal = vec3(bicomp, sqrt(1 - (bicomp.r * bicomp.r) - (bicomp.g * bicomp.g)));
-            } else {
-                normal = normalize(texture_read.rgb * 2.0 - 1.0);
             }
-            if (MATERIAL_FLAG(FLAGS_YDOWN_NORMAL)) {
-                normal.y = -normal.y;
+            // Whiteout blend of the detail normal onto the base normal.
+            if (HAS_DETAIL_NORMAL_TEXTURE) {
+                vec3 detail = textureGrad(sampler2D(DETAIL_NORMAL_TEXTURE, s), detail_coords, detail_uvdx, detail_uvdy).rgb * 2.0 - 1.0;
+                normal = normalize(vec3(normal.xy + detail.xy, normal.z * detail.z));
             }
-            vec3 normal_norm = normalize(i_normal);
+            vec3 normal_norm = normalize(vertex_normal(material));
             vec3 tangent_norm = normalize(i_tangent);
             vec3 bitangent = cross(normal_norm, tangent_norm);
 
@@ -95,7 +170,7 @@ PixelData get_per_pixel_data_sampled(MATERIAL_TYPE material, sampler s) {
 
             pixel.normal = tbn * normal;
         } else {
-            pixel.normal = i_normal;
+            pixel.normal = vertex_normal(material);
         }
         pixel.normal = normalize(pixel.normal);
 
@@ -222,15 +297,28 @@ PixelData get_per_pixel_data_sampled(MATERIAL_TYPE material, sampler s) {
             pixel.emissive = material.emissive;
         }
 
-        // TODO: Aniso info
+        // KHR_materials_anisotropy: the texture holds a tangent space direction,
+        // rotated by the material, and a strength multiplier.
+        vec2 anisotropy_direction = vec2(cos(material.anisotropy_rotation), sin(material.anisotropy_rotation));
+        pixel.anisotropy = material.anisotropy;
         if (HAS_ANISOTROPY_TEXTURE) {
-            pixel.anisotropy = material.anisotropy * textureGrad(sampler2D(ANISOTROPY_TEXTURE, s), coords, uvdx, uvdy).r;
-        } else {
-            pixel.anisotropy = material.anisotropy;
+            vec3 texel = textureGrad(sampler2D(ANISOTROPY_TEXTURE, s), coords, uvdx, uvdy).rgb;
+            vec2 texel_direction = texel.rg * 2.0 - 1.0;
+            if (dot(texel_direction, texel_direction) > 0.0) {
+                mat2 rotation = mat2(anisotropy_direction, vec2(-anisotropy_direction.y, anisotropy_direction.x));
+                anisotropy_direction = rotation * normalize(texel_direction);
+            }
+            pixel.anisotropy *= texel.b;
         }
+        vec3 geometric_normal = normalize(vertex_normal(material));
+        vec3 geometric_tangent = normalize(i_tangent);
+        pixel.anisotropic_direction = geometric_tangent * anisotropy_direction.x + cross(geometric_normal, geometric_tangent) * anisotropy_direction.y;
     }
 
     pixel.material_flags = material.material_flags;
+    pixel.tangent = normalize(i_tangent);
+    pixel.hair = material.hair;
+    pixel.sheen = material.sheen;
 
     return pixel;
 }
@@ -6,7 +6,38 @@
 #include "brdf.glsl"
 #include "pixel.glsl"
 
+// Kajiya-Kay highlight of strands along t, shifted along the normal
+// following Scheuermann's "Hair Rendering and Shading".
+float strand_specular(vec3 t, vec3 h, float exponent) {
+    float ToH = dot(t, h);
+    float sin_th = sqrt(max(1.0 - ToH * ToH, 0.0));
+    float direction_attenuation = smoothstep(-1.0, 0.0, ToH);
+    return direction_attenuation * pow(sin_th, exponent) * (exponent + 2.0) / (2.0 * PI);
+}
+
+vec3 hair_shading(DirectionalLight light, PixelData pixel, vec3 v, float occlusion) {
+    vec3 l = normalize(mat3(uniforms.view) * -light.direction);
+
+    vec3 n = pixel.normal;
+    vec3 h = normalize(v + l);
+    vec3 t = normalize(pixel.tangent - n * dot(n, pixel.tangent));
+
+    // Wrapped, as light passes between the strands.
+    float wrap = saturate(dot(n, l) * 0.75 + 0.25);
+
+    vec3 primary = pixel.f0 * strand_specular(normalize(t + n * pixel.hair.x), h, pixel.hair.z);
+    vec3 secondary = pixel.f0 * pixel.albedo.rgb * strand_specular(normalize(t + n * pixel.hair.y), h, pixel.hair.w);
+
+    vec3 color = pixel.diffuse_color * Fd_Lambert() + primary + secondary;
+
+    return (color * light.color) * (wrap * occlusion);
+}
+
 vec3 surface_shading(DirectionalLight light, PixelData pixel, vec3 v, float occlusion) {
+    if (bool(pixel.material_flags & FLAGS_HAIR)) {
+        return hair_shading(light, pixel, v, occlusion);
+    }
+
     vec3 l = normalize(mat3(uniforms.view) * -light.direction);
 
     vec3 n = pixel.normal;
@@ -19,9 +50,20 @@ vec3 surface_shading(DirectionalLight light, PixelData pixel, vec3 v, float occl
 
     float f90 = saturate(dot(pixel.f0, vec3(50.0 * 0.33)));
 
-    float D = D_GGX(NoH, pixel.roughness);
+    float D;
+    float V;
+    if (pixel.anisotropy != 0.0) {
+        vec3 t = normalize(pixel.anisotropic_direction - n * dot(n, pixel.anisotropic_direction));
+        vec3 b = cross(n, t);
+        float at = max(pixel.roughness * (1.0 + pixel.anisotropy), 0.001);
+        float ab = max(pixel.roughness * (1.0 - pixel.anisotropy), 0.001);
+        D = D_GGX_Anisotropic(NoH, h, t, b, at, ab);
+        V = V_SmithGGXCorrelated_Anisotropic(at, ab, dot(t, v), dot(b, v), dot(t, l), dot(b, l), NoV, NoL);
+    } else {
+        D = D_GGX(NoH, pixel.roughness);
+        V = V_SmithGGXCorrelated(NoV, NoL, pixel.roughness);
+    }
     vec3  F = F_Schlick(LoH, pixel.f0, f90);
-    float V = V_SmithGGXCorrelated(NoV, NoL, pixel.roughness);
 
     // TODO: figure out how they generate their lut
     float energy_compensation = 1.0;
@@ -34,6 +76,12 @@ vec3 surface_shading(DirectionalLight light, PixelData pixel, vec3 v, float occl
 
     vec3 color = Fd + Fr * energy_compensation;
 
+    // Sheen layer of cloth, following KHR_materials_sheen without its albedo scaling.
+    if (max(pixel.sheen.r, max(pixel.sheen.g, pixel.sheen.b)) > 0.0) {
+        float sheen_roughness = max(pixel.sheen.a, 0.07);
+        color += pixel.sheen.rgb * (D_Charlie(sheen_roughness * sheen_roughness, NoH) * V_Neubelt(NoV, NoL));
+    }
+
     float light_attenuation = 1.0;
 
     return (color * light.color) * (light_attenuation * NoL * occlusion);
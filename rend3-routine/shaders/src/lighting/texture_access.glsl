@@ -18,6 +18,9 @@ bool has_texture(uint idx) {
 #define HAS_EMISSIVE_TEXTURE has_texture(material.emissive_tex)
 #define HAS_ANISOTROPY_TEXTURE has_texture(material.anisotropy_tex)
 #define HAS_AMBIENT_OCCLUSION_TEXTURE has_texture(material.ambient_occlusion_tex)
+#define HAS_HEIGHT_TEXTURE has_texture(material.height_tex)
+#define HAS_DETAIL_ALBEDO_TEXTURE has_texture(material.detail_albedo_tex)
+#define HAS_DETAIL_NORMAL_TEXTURE has_texture(material.detail_normal_tex)
 
 #define ALBEDO_TEXTURE textures[nonuniformEXT(material.albedo_tex - 1)]
 #define NORMAL_TEXTURE textures[nonuniformEXT(material.normal_tex - 1)]
@@ -29,6 +32,9 @@ bool has_texture(uint idx) {
 #define EMISSIVE_TEXTURE textures[nonuniformEXT(material.emissive_tex - 1)]
 #define ANISOTROPY_TEXTURE textures[nonuniformEXT(material.anisotropy_tex - 1)]
 #define AMBIENT_OCCLUSION_TEXTURE textures[nonuniformEXT(material.ambient_occlusion_tex - 1)]
+#define HEIGHT_TEXTURE textures[nonuniformEXT(material.height_tex - 1)]
+#define DETAIL_ALBEDO_TEXTURE textures[nonuniformEXT(material.detail_albedo_tex - 1)]
+#define DETAIL_NORMAL_TEXTURE textures[nonuniformEXT(material.detail_normal_tex - 1)]
 #endif
 
 #ifdef CPU_DRIVEN
@@ -44,6 +50,9 @@ bool has_texture(uint idx) {
 #define HAS_EMISSIVE_TEXTURE bool((material.texture_enable >> 7) & 0x1)
 #define HAS_ANISOTROPY_TEXTURE bool((material.texture_enable >> 8) & 0x1)
 #define HAS_AMBIENT_OCCLUSION_TEXTURE bool((material.texture_enable >> 9) & 0x1)
+#define HAS_HEIGHT_TEXTURE bool((material.texture_enable >> 10) & 0x1)
+#define HAS_DETAIL_ALBEDO_TEXTURE bool((material.texture_enable >> 11) & 0x1)
+#define HAS_DETAIL_NORMAL_TEXTURE bool((material.texture_enable >> 12) & 0x1)
 
 #define ALBEDO_TEXTURE albedo_tex
 #define NORMAL_TEXTURE normal_tex
@@ -55,6 +64,9 @@ bool has_texture(uint idx) {
 #define EMISSIVE_TEXTURE emissive_tex
 #define ANISOTROPY_TEXTURE anisotropy_tex
 #define AMBIENT_OCCLUSION_TEXTURE ambient_occlusion_tex
+#define HEIGHT_TEXTURE height_tex
+#define DETAIL_ALBEDO_TEXTURE detail_albedo_tex
+#define DETAIL_NORMAL_TEXTURE detail_normal_tex
 #endif
 
 #endif // SHADER_TEXTURE_ACCESS_GLSL
\ No newline at end of file
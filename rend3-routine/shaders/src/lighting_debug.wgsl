@@ -0,0 +1,86 @@
+// Per-pixel lighting data for debug views, see lighting_debug.rs. Prepended
+// with custom/frame.wgsl, which declares the start of the forward uniforms.
+
+/// Layout of ShaderDirectionalLight in directional.rs.
+struct DirectionalLight {
+    view_proj: mat4x4<f32>;
+    color: vec3<f32>;
+    direction: vec3<f32>;
+    offset: vec2<f32>;
+    size: f32;
+};
+
+struct DirectionalLightBuffer {
+    total_lights: u32;
+    lights: array<DirectionalLight>;
+};
+
+[[group(0), binding(4)]]
+var<storage, read> directional_lights: DirectionalLightBuffer;
+[[group(0), binding(5)]]
+var shadows: texture_depth_2d_array;
+
+#ifdef MULTISAMPLED
+[[group(1), binding(0)]]
+var depth: texture_depth_multisampled_2d;
+#else
+[[group(1), binding(0)]]
+var depth: texture_depth_2d;
+#endif
+
+struct LightingDebugOutput {
+    [[location(0)]] light_count: f32;
+    [[location(1)]] shadow_index: f32;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] id: u32) -> [[builtin(position)]] vec4<f32> {
+    return vec4<f32>(f32(id / 2u) * 4.0 - 1.0, f32(id % 2u) * 4.0 - 1.0, 0.0, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> LightingDebugOutput {
+    // The first sample stands for the pixel with MSAA.
+    let depth_value = textureLoad(depth, vec2<i32>(position.xy), 0);
+#ifdef REVERSED_Z
+    let sky = depth_value <= 0.0;
+#else
+    let sky = depth_value >= 1.0;
+#endif
+    if (sky) {
+        return LightingDebugOutput(0.0, 0.0);
+    }
+
+    let uv = position.xy / vec2<f32>(frame.resolution);
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth_value, 1.0);
+    let world_h = frame.inv_view_proj * ndc;
+    let world = vec4<f32>(world_h.xyz / world_h.w, 1.0);
+
+    // Same lookup as the opaque pass: light i has layer i of the shadows,
+    // and is unshadowed outside of its map.
+    var light_count = 0u;
+    var shadow_index = 0u;
+    var i = 0u;
+    loop {
+        if (i >= directional_lights.total_lights) {
+            break;
+        }
+        let shadow_ndc = (directional_lights.lights[i].view_proj * world).xyz;
+        let shadow_uv = vec2<f32>(shadow_ndc.x * 0.5 + 0.5, 0.5 - shadow_ndc.y * 0.5);
+        let covered = all(shadow_uv >= vec2<f32>(0.0)) && all(shadow_uv <= vec2<f32>(1.0))
+            && shadow_ndc.z >= -1.0 && shadow_ndc.z <= 1.0;
+        var lit = 1.0;
+        if (covered) {
+            lit = textureSampleCompareLevel(shadows, comparison_sampler, shadow_uv, i32(i), shadow_ndc.z);
+            if (shadow_index == 0u) {
+                shadow_index = i + 1u;
+            }
+        }
+        if (lit > 0.5) {
+            light_count = light_count + 1u;
+        }
+        i = i + 1u;
+    }
+
+    return LightingDebugOutput(f32(light_count), f32(shadow_index));
+}
@@ -0,0 +1,73 @@
+/// See LineVertex in line.rs
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] local: vec2<f32>;
+    [[location(1)]] segment: vec4<f32>;
+    [[location(2)]] dash: vec3<f32>;
+    [[location(3)]] color_start: vec4<f32>;
+    [[location(4)]] color_end: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec4<f32>,
+    [[location(1)]] local: vec2<f32>,
+    [[location(2)]] segment: vec4<f32>,
+    [[location(3)]] dash: vec3<f32>,
+    [[location(4)]] color_start: vec4<f32>,
+    [[location(5)]] color_end: vec4<f32>,
+) -> VertexOutput {
+    return VertexOutput(position, local, segment, dash, color_start, color_end);
+}
+
+/// Signed distance in pixels from the end of a segment, negative inside.
+fn cap_distance(along: f32, across: f32, half_width: f32, cap: f32) -> f32 {
+    // Butt
+    if (cap < 0.5) {
+        return max(across - half_width, along);
+    }
+    // Square
+    if (cap < 1.5) {
+        return max(across - half_width, along - half_width);
+    }
+    // Round, also used to join segments.
+    return length(vec2<f32>(along, across)) - half_width;
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let segment_length = input.segment.x;
+    let half_width = input.segment.y;
+    let along = input.local.x;
+    let across = abs(input.local.y);
+
+    var distance: f32;
+    if (along < 0.0) {
+        distance = cap_distance(-along, across, half_width, input.segment.z);
+    } else if (along > segment_length) {
+        distance = cap_distance(along - segment_length, across, half_width, input.segment.w);
+    } else {
+        distance = across - half_width;
+    }
+    var coverage = clamp(0.5 - distance, 0.0, 1.0);
+
+    let period = input.dash.y + input.dash.z;
+    if (period > 0.0) {
+        let position = input.dash.x + clamp(along, 0.0, segment_length);
+        let phase = position - floor(position / period) * period;
+        var edge: f32;
+        if (phase < input.dash.y) {
+            edge = min(phase, input.dash.y - phase);
+        } else {
+            edge = -min(phase - input.dash.y, period - phase);
+        }
+        coverage = coverage * clamp(edge + 0.5, 0.0, 1.0);
+    }
+
+    if (coverage <= 0.0) {
+        discard;
+    }
+
+    let color = mix(input.color_start, input.color_end, clamp(along / max(segment_length, 0.0001), 0.0, 1.0));
+    return vec4<f32>(color.rgb, color.a * coverage);
+}
@@ -0,0 +1,46 @@
+/// Tests the screen rects of occlusion queries against the depth buffer, a
+/// workgroup per query. See occlusion.rs for the bindings.
+
+struct Query {
+    /// Pixels covered by the tested box, max exclusive.
+    rect_min: vec2<u32>;
+    rect_max: vec2<u32>;
+    /// Depth of the point of the box closest to the camera.
+    depth: f32;
+    /// 1 if closer depths are greater.
+    reversed: u32;
+};
+
+struct Queries {
+    data: array<Query>;
+};
+
+struct Results {
+    data: array<u32>;
+};
+
+[[group(0), binding(0)]]
+var scene_depth: texture_depth_2d;
+[[group(0), binding(1)]]
+var<storage, read> queries: Queries;
+/// 1 for each query with a pixel closer than the scene, left at 0 otherwise.
+[[group(0), binding(2)]]
+var<storage, read_write> results: Results;
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn main(
+    [[builtin(workgroup_id)]] query_id: vec3<u32>,
+    [[builtin(local_invocation_id)]] local: vec3<u32>,
+) {
+    let query = queries.data[query_id.x];
+    for (var y = query.rect_min.y + local.y; y < query.rect_max.y; y = y + 8u) {
+        for (var x = query.rect_min.x + local.x; x < query.rect_max.x; x = x + 8u) {
+            let scene = textureLoad(scene_depth, vec2<i32>(i32(x), i32(y)), 0);
+            let closer = select(query.depth <= scene, query.depth >= scene, query.reversed != 0u);
+            if (closer) {
+                results.data[query_id.x] = 1u;
+                return;
+            }
+        }
+    }
+}
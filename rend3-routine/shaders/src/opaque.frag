@@ -13,6 +13,7 @@ layout(location = 3) in vec2 i_coords0;
 layout(location = 4) in vec2 i_coords1;
 layout(location = 5) in vec4 i_color;
 layout(location = 6) flat in uint i_material;
+layout(location = 8) flat in float i_fade;
 
 layout(location = 0) out vec4 o_color;
 
@@ -22,10 +23,19 @@ layout(set = 0, binding = 2) uniform samplerShadow shadow_sampler;
 layout(set = 0, binding = 3) uniform UniformBuffer {
     UniformData uniforms;
 };
+#ifdef DOWNLEVEL
+// Must match DOWNLEVEL_MAX_DIRECTIONAL_LIGHTS.
+#define MAX_DIRECTIONAL_LIGHTS 4
+layout(set = 0, binding = 4) uniform DirectionalLightBuffer {
+    DirectionalLightBufferHeader directional_light_header;
+    DirectionalLight directional_lights[MAX_DIRECTIONAL_LIGHTS];
+};
+#else
 layout(set = 0, binding = 4) restrict readonly buffer DirectionalLightBuffer {
     DirectionalLightBufferHeader directional_light_header;
     DirectionalLight directional_lights[];
 };
+#endif
 layout(set = 0, binding = 5) uniform texture2DArray shadow;
 #ifdef GPU_DRIVEN
 layout(set = 1, binding = 1, std430) restrict readonly buffer MaterialBuffer {
@@ -33,10 +43,16 @@ layout(set = 1, binding = 1, std430) restrict readonly buffer MaterialBuffer {
 };
 layout(set = 2, binding = 0) uniform texture2D textures[];
 #endif
-#ifdef CPU_DRIVEN
+#if defined(DOWNLEVEL)
+layout(set = 2, binding = 0) uniform TextureData {
+    CPUMaterialData material;
+};
+#elif defined(CPU_DRIVEN)
 layout(set = 2, binding = 0) readonly buffer TextureData {
     CPUMaterialData material;
 };
+#endif
+#ifdef CPU_DRIVEN
 layout(set = 2, binding = 1) uniform texture2D albedo_tex;
 layout(set = 2, binding = 2) uniform texture2D normal_tex;
 layout(set = 2, binding = 3) uniform texture2D roughness_tex;
@@ -47,6 +63,9 @@ layout(set = 2, binding = 7) uniform texture2D clear_coat_roughness_tex;
 layout(set = 2, binding = 8) uniform texture2D emissive_tex;
 layout(set = 2, binding = 9) uniform texture2D anisotropy_tex;
 layout(set = 2, binding = 10) uniform texture2D ambient_occlusion_tex;
+layout(set = 2, binding = 11) uniform texture2D height_tex;
+layout(set = 2, binding = 12) uniform texture2D detail_albedo_tex;
+layout(set = 2, binding = 13) uniform texture2D detail_normal_tex;
 #endif
 
 #include "lighting/surface.glsl"
@@ -59,6 +78,11 @@ void main() {
 
     PixelData pixel = get_per_pixel_data(material);
 
+    // Only after sampling the textures, which needs uniform control flow.
+    if (is_clipped(uniforms, uniforms.inv_view * i_view_position) || is_faded_out(i_fade, gl_FragCoord.xy)) {
+        discard;
+    }
+
     if (MATERIAL_FLAG(FLAGS_UNLIT)) {
         o_color = pixel.albedo;
         // o_normal = vec4(i_normal, 0.0);
@@ -66,7 +90,12 @@ void main() {
         vec3 v = -normalize(i_view_position.xyz);
 
         vec3 color = vec3(pixel.emissive);
-        for (uint i = 0; i < directional_light_header.total_lights; ++i) {
+        #ifdef DOWNLEVEL
+        uint total_lights = min(directional_light_header.total_lights, MAX_DIRECTIONAL_LIGHTS);
+        #else
+        uint total_lights = directional_light_header.total_lights;
+        #endif
+        for (uint i = 0; i < total_lights; ++i) {
             DirectionalLight light = directional_lights[i];
 
             vec3 shadow_ndc = (directional_lights[i].view_proj * uniforms.inv_view * i_view_position).xyz;
@@ -84,5 +113,10 @@ void main() {
         }
 
         o_color = max(vec4(color, pixel.albedo.a), uniforms.ambient * pixel.albedo);
+
+        // Marks the pixel for the subsurface blur, see subsurface.rs.
+        if (material.subsurface_profile != 0) {
+            o_color.a = float(material.subsurface_profile) + 1.0;
+        }
     }
 }
@@ -21,45 +21,70 @@ layout(location = 3) out vec2 o_coords0;
 layout(location = 4) out vec2 o_coords1;
 layout(location = 5) out vec4 o_color;
 layout(location = 6) flat out uint o_material;
+layout(location = 7) flat out uint o_texture_layer;
+layout(location = 8) flat out float o_fade;
 
 layout(set = 0, binding = 3) uniform UniformBuffer {
     UniformData uniforms;
 };
+#ifdef DOWNLEVEL
+// Bound with a dynamic offset pointing at the current object.
+layout(set = 1, binding = 0) uniform ObjectOutputDataBuffer {
+    ObjectOutputData object_output;
+};
+#else
 layout(set = 1, binding = 0, std430) restrict readonly buffer ObjectOutputDataBuffer {
     ObjectOutputData object_output[];
 };
+#endif
 #ifdef GPU_DRIVEN
 layout(set = 1, binding = 1, std430) readonly buffer MaterialBuffer {
     GPUMaterialData materials[];
 };
 #endif
-#ifdef CPU_DRIVEN
+#if defined(CPU_DRIVEN) && !defined(DOWNLEVEL)
 layout(set = 2, binding = 0) readonly buffer TextureData {
     CPUMaterialData material;
 };
 #endif
+#ifdef VERTEX_SKINNING
+#include "skinning.glsl"
+#endif
 
 void main() {
-    #ifdef CPU_DRIVEN
-    uint object_idx = gl_InstanceIndex;
+    #if defined(DOWNLEVEL)
+    ObjectOutputData data = object_output;
+    #elif defined(CPU_DRIVEN)
+    ObjectOutputData data = object_output[gl_InstanceIndex];
     #else
-    uint object_idx = i_object_idx;
+    ObjectOutputData data = object_output[i_object_idx];
     #endif
 
-    ObjectOutputData data = object_output[object_idx];
-
     o_material = data.material_idx;
+    o_texture_layer = data.texture_layer;
+    o_fade = data.fade;
+
+    vec4 position = vec4(i_position, 1.0);
+    vec3 normal = decode_direction(uniforms, i_normal);
+    vec3 tangent = decode_direction(uniforms, i_tangent);
+
+    #ifdef VERTEX_SKINNING
+    mat4 skinning = skinning_matrix(data.joint_offset);
+    position = skinning * position;
+    normal = skin_direction(skinning, normal);
+    tangent = skin_direction(skinning, tangent);
+    #endif
 
-    o_view_position = data.model_view * vec4(i_position, 1.0);
+    o_view_position = data.model_view * position;
 
-    o_normal = normalize(mat3(data.model_view) * (data.inv_squared_scale * i_normal));
+    o_normal = normalize(mat3(data.model_view) * (data.inv_squared_scale * normal));
 
-    o_tangent = normalize(mat3(data.model_view) * (data.inv_squared_scale * i_tangent));
+    o_tangent = normalize(mat3(data.model_view) * (data.inv_squared_scale * tangent));
 
     o_color = i_color;
 
     o_coords0 = i_coords0;
     o_coords1 = i_coords1;
 
-    gl_Position = data.model_view_proj * vec4(i_position, 1.0);
+    gl_Position = data.model_view_proj * position;
 }
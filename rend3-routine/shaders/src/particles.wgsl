@@ -0,0 +1,223 @@
+/// See ParticleCameraUniform in particles.rs
+struct Camera {
+    view_proj: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_proj: mat4x4<f32>;
+    location: vec4<f32>;
+    right: vec4<f32>;
+    up: vec4<f32>;
+    resolution: vec2<f32>;
+    delta: f32;
+};
+
+/// See EmitterUniform in particles.rs
+struct Emitter {
+    origin: vec4<f32>;
+    velocity: vec4<f32>;
+    acceleration: vec4<f32>;
+    speed: vec4<f32>;
+    size: vec4<f32>;
+    colors: array<vec4<f32>, 4>;
+    lifetime: f32;
+    lifetime_variation: f32;
+    restitution: f32;
+    softness: f32;
+    spawn_start: u32;
+    spawn_count: u32;
+    capacity: u32;
+    seed: u32;
+};
+
+struct Particle {
+    position: vec3<f32>;
+    age: f32;
+    velocity: vec3<f32>;
+    lifetime: f32;
+};
+
+struct Particles {
+    data: array<Particle>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: Camera;
+[[group(0), binding(1)]]
+var scene_depth: texture_depth_2d;
+
+[[group(1), binding(0)]]
+var<uniform> emitter: Emitter;
+[[group(1), binding(1)]]
+var<storage, read_write> particles: Particles;
+
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(seed: ptr<function, u32>) -> f32 {
+    *seed = hash(*seed);
+    return f32(*seed) / 4294967295.0;
+}
+
+/// Uniformly distributed in the unit sphere.
+fn random_in_sphere(seed: ptr<function, u32>) -> vec3<f32> {
+    let z = random(seed) * 2.0 - 1.0;
+    let angle = random(seed) * 6.2831853;
+    let radius = pow(random(seed), 1.0 / 3.0);
+    let ring = sqrt(1.0 - z * z);
+    return vec3<f32>(ring * cos(angle), ring * sin(angle), z) * radius;
+}
+
+/// Piecewise linear curve through four keys evenly spread over the lifetime.
+fn curve(keys: vec4<f32>, t: f32) -> f32 {
+    let position = clamp(t, 0.0, 1.0) * 3.0;
+    let index = min(u32(position), 2u);
+    return mix(keys[index], keys[index + 1u], position - f32(index));
+}
+
+fn color_curve(t: f32) -> vec4<f32> {
+    let position = clamp(t, 0.0, 1.0) * 3.0;
+    let index = min(u32(position), 2u);
+    return mix(emitter.colors[index], emitter.colors[index + 1u], position - f32(index));
+}
+
+/// Distance from the camera plane of a point at the given depth buffer value.
+fn view_distance(pixel: vec2<f32>, depth: f32) -> f32 {
+    let ndc = vec2<f32>(pixel.x / camera.resolution.x * 2.0 - 1.0, 1.0 - pixel.y / camera.resolution.y * 2.0);
+    let view = camera.inv_proj * vec4<f32>(ndc, depth, 1.0);
+    return abs(view.z / view.w);
+}
+
+fn world_position(pixel: vec2<f32>) -> vec3<f32> {
+    let depth = textureLoad(scene_depth, vec2<i32>(pixel), 0);
+    let ndc = vec2<f32>(pixel.x / camera.resolution.x * 2.0 - 1.0, 1.0 - pixel.y / camera.resolution.y * 2.0);
+    let world = camera.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    return world.xyz / world.w;
+}
+
+/// Bounces the particle off the depth buffer if it would move behind it.
+fn collide(particle: ptr<function, Particle>, next: vec3<f32>, size: f32) -> bool {
+    let clip = camera.view_proj * vec4<f32>(next, 1.0);
+    if (clip.w <= 0.0) {
+        return false;
+    }
+    let ndc = clip.xyz / clip.w;
+    if (abs(ndc.x) >= 1.0 || abs(ndc.y) >= 1.0) {
+        return false;
+    }
+
+    let pixel = floor((ndc.xy * vec2<f32>(0.5, -0.5) + 0.5) * camera.resolution) + 0.5;
+    let depth = textureLoad(scene_depth, vec2<i32>(pixel), 0);
+    let surface_distance = view_distance(pixel, depth);
+    let behind = view_distance(pixel, ndc.z) - surface_distance;
+    // Particles far behind the surface are hidden by it, not colliding with it.
+    if (behind <= 0.0 || behind > max(size, 0.1)) {
+        return false;
+    }
+
+    let pixel_x = min(pixel + vec2<f32>(1.0, 0.0), camera.resolution - 0.5);
+    let pixel_y = min(pixel + vec2<f32>(0.0, 1.0), camera.resolution - 0.5);
+    let surface = world_position(pixel);
+    var normal = normalize(cross(world_position(pixel_x) - surface, world_position(pixel_y) - surface));
+    if (dot(normal, camera.location.xyz - surface) < 0.0) {
+        normal = -normal;
+    }
+    if (dot((*particle).velocity, normal) < 0.0) {
+        (*particle).velocity = reflect((*particle).velocity, normal) * emitter.restitution;
+    }
+    return true;
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let index = id.x;
+    if (index >= emitter.capacity) {
+        return;
+    }
+
+    var particle = particles.data[index];
+    let delta = camera.delta;
+
+    if ((index + emitter.capacity - emitter.spawn_start) % emitter.capacity < emitter.spawn_count) {
+        var seed = hash(index ^ emitter.seed);
+        particle.position = emitter.origin.xyz + random_in_sphere(&seed) * emitter.origin.w;
+        particle.velocity = emitter.velocity.xyz + random_in_sphere(&seed) * emitter.velocity.w;
+        particle.lifetime = emitter.lifetime * (1.0 - random(&seed) * emitter.lifetime_variation);
+        particle.age = 0.0;
+    } else if (particle.age < particle.lifetime) {
+        let t = particle.age / particle.lifetime;
+        particle.velocity = particle.velocity + emitter.acceleration.xyz * delta;
+        particle.velocity = particle.velocity * max(1.0 - emitter.acceleration.w * delta, 0.0);
+        let next = particle.position + particle.velocity * curve(emitter.speed, t) * delta;
+
+        var collided = false;
+        if (emitter.restitution >= 0.0) {
+            collided = collide(&particle, next, curve(emitter.size, t));
+        }
+        if (!collided) {
+            particle.position = next;
+        }
+        particle.age = particle.age + delta;
+    } else {
+        return;
+    }
+
+    particles.data[index] = particle;
+}
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] offset: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    [[location(0)]] position_age: vec4<f32>,
+    [[location(1)]] velocity_lifetime: vec4<f32>,
+) -> VertexOutput {
+    var output: VertexOutput;
+    let age = position_age.w;
+    let lifetime = velocity_lifetime.w;
+    // Dead particles collapse into a point which isn't rasterized.
+    if (age >= lifetime) {
+        output.position = vec4<f32>(0.0);
+        return output;
+    }
+
+    let t = age / lifetime;
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+    );
+    let corner = corners[vertex_index];
+    let half_size = curve(emitter.size, t) * 0.5;
+    let world = position_age.xyz + (camera.right.xyz * corner.x + camera.up.xyz * corner.y) * half_size;
+
+    output.position = camera.view_proj * vec4<f32>(world, 1.0);
+    output.offset = corner;
+    output.color = color_curve(t);
+    return output;
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let depth = textureLoad(scene_depth, vec2<i32>(input.position.xy), 0);
+    let surface_distance = view_distance(input.position.xy, depth);
+    let distance = view_distance(input.position.xy, input.position.z);
+    // Fade out close to the surface instead of cutting into it.
+    let depth_fade = clamp((surface_distance - distance) / max(emitter.softness, 0.0001), 0.0, 1.0);
+
+    let radial = 1.0 - clamp(length(input.offset), 0.0, 1.0);
+    let alpha = input.color.a * radial * radial * depth_fade;
+    if (alpha <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(input.color.rgb, alpha);
+}
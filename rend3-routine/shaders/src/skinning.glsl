@@ -0,0 +1,46 @@
+#ifndef SHADER_SKINNING_GLSL
+#define SHADER_SKINNING_GLSL
+
+// Skinning in the vertex shader, see SkinningMode::VertexShader. Needs
+// structures.glsl.
+
+layout(location = 6) in uvec4 i_joint_indices;
+layout(location = 7) in vec4 i_joint_weights;
+
+// After the object data, and the materials when GpuDriven. Must match
+// PerMaterialArchetypeInterface.
+#ifdef GPU_DRIVEN
+#define JOINT_MATRIX_BUFFER_BINDING 2
+#else
+#define JOINT_MATRIX_BUFFER_BINDING 1
+#endif
+
+layout(set = 1, binding = JOINT_MATRIX_BUFFER_BINDING, std430) restrict readonly buffer JointMatrixBuffer {
+    mat4 joint_matrices[];
+};
+
+// Blend of the joints of the vertex, from the joints of the skeleton of the
+// object starting at joint_offset. Identity for objects without a skeleton.
+mat4 skinning_matrix(uint joint_offset) {
+    if (joint_offset == NO_JOINTS) {
+        return mat4(1.0);
+    }
+    return joint_matrices[joint_offset + i_joint_indices.x] * i_joint_weights.x
+         + joint_matrices[joint_offset + i_joint_indices.y] * i_joint_weights.y
+         + joint_matrices[joint_offset + i_joint_indices.z] * i_joint_weights.z
+         + joint_matrices[joint_offset + i_joint_indices.w] * i_joint_weights.w;
+}
+
+// Skins a normal or tangent, undoing the scale of the joints like the
+// pre-skinning compute pass.
+vec3 skin_direction(mat4 skinning, vec3 direction) {
+    mat3 skinning3 = mat3(skinning);
+    vec3 squared_scale = vec3(
+        dot(skinning3[0], skinning3[0]),
+        dot(skinning3[1], skinning3[1]),
+        dot(skinning3[2], skinning3[2])
+    );
+    return skinning3 * (direction / squared_scale);
+}
+
+#endif
@@ -18,6 +18,49 @@ struct JointMatrices {
 struct Vec3 { x: f32; y: f32; z: f32; };
 struct Vec3Array { data: array<Vec3>; };
 
+fn to_v(v: Vec3) -> vec3<f32> {
+    return vec3<f32>(v.x, v.y, v.z);
+}
+fn from_v(v: vec3<f32>) -> Vec3 {
+    var res : Vec3;
+    res.x = v.x; res.y = v.y; res.z = v.z;
+    return res;
+}
+
+#ifdef QUANTIZED_NORMALS
+/// Normals and tangents are octahedral encoded, two snorm16 in a word.
+struct DirectionArray { data: array<u32>; };
+
+fn read_direction(encoded: u32) -> vec3<f32> {
+    let e = unpack2x16snorm(encoded);
+    var n = vec3<f32>(e, 1.0 - abs(e.x) - abs(e.y));
+    let t = max(-n.z, 0.0);
+    n.x = n.x - select(-t, t, n.x >= 0.0);
+    n.y = n.y - select(-t, t, n.y >= 0.0);
+    return normalize(n);
+}
+fn write_direction(direction: vec3<f32>) -> u32 {
+    let n = direction / (abs(direction.x) + abs(direction.y) + abs(direction.z));
+    var e = n.xy;
+    if (n.z < 0.0) {
+        e = vec2<f32>(
+            (1.0 - abs(n.y)) * select(-1.0, 1.0, n.x >= 0.0),
+            (1.0 - abs(n.x)) * select(-1.0, 1.0, n.y >= 0.0)
+        );
+    }
+    return pack2x16snorm(e);
+}
+#else
+struct DirectionArray { data: array<Vec3>; };
+
+fn read_direction(v: Vec3) -> vec3<f32> {
+    return to_v(v);
+}
+fn write_direction(v: vec3<f32>) -> Vec3 {
+    return from_v(v);
+}
+#endif
+
 struct JointWeightVec { ws: array<f32,4>; };
 struct JointWeightVecArray { data: array<JointWeightVec>; };
 
@@ -30,10 +73,10 @@ struct JointIndexVecArray { data: array<JointIndexVec>; };
 var<storage, read_write> positions: Vec3Array;
 
 [[group(0), binding(1)]]
-var<storage, read_write> normals: Vec3Array;
+var<storage, read_write> normals: DirectionArray;
 
 [[group(0), binding(2)]]
-var<storage, read_write> tangents: Vec3Array;
+var<storage, read_write> tangents: DirectionArray;
 
 [[group(0), binding(3)]]
 var<storage, read_write> joint_indices: JointIndexVecArray;
@@ -47,16 +90,6 @@ var<storage> joint_matrices: JointMatrices;
 [[group(1), binding(0)]]
 var<storage> input : GpuSkinningInput;
 
-fn to_v(v: Vec3) -> vec3<f32> {
-    return vec3<f32>(v.x, v.y, v.z);
-}
-fn from_v(v: vec3<f32>) -> Vec3 {
-    var res : Vec3;
-    res.x = v.x; res.y = v.y; res.z = v.z;
-    return res;
-}
-
-
 fn get_joint_matrix(joint_idx: u32) -> mat4x4<f32> {
     return joint_matrices.matrices[input.joints_start_idx + joint_idx];
 }
@@ -94,8 +127,14 @@ fn main([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
     var tang_acc = vec3<f32>(0.0, 0.0, 0.0);
 
     let pos = to_v(positions.data[input.mesh_range.start + idx]);
-    let normal = to_v(normals.data[input.mesh_range.start + idx]);
-    let tangent = to_v(tangents.data[input.mesh_range.start + idx]);
+    let normal = read_direction(normals.data[input.mesh_range.start + idx]);
+    // Without tangents, the renderer keeps a default tangent per instance,
+    // all of them the same.
+    let has_tangents = arrayLength(&tangents.data) == arrayLength(&normals.data);
+    var tangent = read_direction(tangents.data[0]);
+    if (has_tangents) {
+        tangent = read_direction(tangents.data[input.mesh_range.start + idx]);
+    }
     
     for (var i = 0; i < 4; i = i + 1) {
         let weight = joint_weights.data[input.mesh_range.start + idx].ws[i];
@@ -113,6 +152,8 @@ fn main([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
     
     // Write to output region of buffer
     positions.data[input.skeleton_range.start + idx] = from_v(pos_acc);
-    normals.data[input.skeleton_range.start + idx] = from_v(normalize(norm_acc));
-    tangents.data[input.skeleton_range.start + idx] = from_v(normalize(tang_acc));
+    normals.data[input.skeleton_range.start + idx] = write_direction(normalize(norm_acc));
+    if (has_tangents) {
+        tangents.data[input.skeleton_range.start + idx] = write_direction(normalize(tang_acc));
+    }
 }
\ No newline at end of file
@@ -7,6 +7,11 @@ void main() {
     vec2 position = vec2(float(id / 2) * 4.0 - 1.0, float(id % 2) * 4.0 - 1.0);
     o_clip_position = position;
 
+#ifdef STANDARD_DEPTH
+    // We use 1.0 (the far plane) as depth
+    gl_Position = vec4(position, 1.0, 1.0);
+#else
     // We use 0.0 (the infinite far plane) as depth
     gl_Position = vec4(position, 0.0, 1.0);
+#endif
 }
\ No newline at end of file
@@ -0,0 +1,24 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var sprite_texture: texture_2d<f32>;
+[[group(0), binding(1)]]
+var sprite_sampler: sampler;
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec4<f32>,
+    [[location(1)]] uv: vec2<f32>,
+    [[location(2)]] color: vec4<f32>,
+) -> VertexOutput {
+    return VertexOutput(position, uv, color);
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(sprite_texture, sprite_sampler, input.uv) * input.color;
+}
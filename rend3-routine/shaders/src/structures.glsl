@@ -1,6 +1,9 @@
 #ifndef SHADER_STRUCTURES_GLSL
 #define SHADER_STRUCTURES_GLSL
 
+// Must match MAX_CLIP_PLANES.
+#define MAX_CLIP_PLANES 4
+
 struct Plane {
     vec4 inner;
 };
@@ -22,15 +25,29 @@ struct ObjectInputData {
     mat4 transform;
     // xyz position; w radius
     vec4 bounding_sphere;
+    // Quantized positions are position_offset + position_scale * position.
+    vec3 position_offset;
+    vec3 position_scale;
+    float _padding0;
+    // ObjectFade::to_shader
+    float fade;
+    // First joint of the skeleton of the object, or NO_JOINTS
+    uint joint_offset;
 }; 
 
+// Must match NO_JOINTS.
+#define NO_JOINTS 0xFFFFFFFFu
+
 /// If you change this struct, change the object output size in culling.rs
 struct ObjectOutputData {
     mat4 model_view;
     mat4 model_view_proj;
     uint material_idx;
+    uint texture_layer;
+    uint joint_offset;
     // Work around https://github.com/gfx-rs/naga/issues/1561
     vec3 inv_squared_scale;
+    float fade;
 };
 
 struct IndirectCall {
@@ -56,6 +73,9 @@ struct IndirectCall {
 #define FLAGS_CC_BW_SPLIT         0x1000
 #define FLAGS_UNLIT               0x2000
 #define FLAGS_NEAREST             0x4000
+#define FLAGS_DOUBLE_SIDED        0x8000
+#define FLAGS_PARALLAX_REFINE     0x10000
+#define FLAGS_HAIR                0x20000
 
 #define MATERIAL_FLAG(name) bool(material.material_flags & name)
 
@@ -72,8 +92,13 @@ struct GPUMaterialData {
     // -- 16 --
     uint anisotropy_tex;
     uint ambient_occlusion_tex;
+    uint height_tex;
+    uint detail_albedo_tex;
+    // -- 16 --
+    uint detail_normal_tex;
     uint _padding0;
     uint _padding1;
+    uint _padding2;
     
     // -- 16 --
 
@@ -95,6 +120,28 @@ struct GPUMaterialData {
     float ambient_occlusion;
     float alpha_cutout;
     uint material_flags;
+    // -- 16 --
+    float parallax_scale;
+    uint parallax_steps;
+    // Diffusion profile index + 1, 0 without subsurface scattering.
+    uint subsurface_profile;
+    // Radians from the tangent towards the bitangent
+    float anisotropy_rotation;
+    // -- 16 --
+    // xy tiling, zw offset
+    vec4 detail_transform;
+    // -- 16 --
+    // Shifts of the primary and secondary highlights, then their exponents
+    vec4 hair;
+    // -- 16 --
+    // Color, then perceptual roughness
+    vec4 sheen;
+    // -- 16 --
+    // Factor, ior, thickness, attenuation distance (0 absorbs nothing)
+    vec4 transmission;
+    // -- 16 --
+    // Color light absorbed over the attenuation distance turns into
+    vec4 attenuation;
 };
 
 struct CPUMaterialData {
@@ -116,6 +163,28 @@ struct CPUMaterialData {
     float ambient_occlusion;
     float alpha_cutout;
     uint material_flags;
+    // -- 16 --
+    float parallax_scale;
+    uint parallax_steps;
+    // Diffusion profile index + 1, 0 without subsurface scattering.
+    uint subsurface_profile;
+    // Radians from the tangent towards the bitangent
+    float anisotropy_rotation;
+    // -- 16 --
+    // xy tiling, zw offset
+    vec4 detail_transform;
+    // -- 16 --
+    // Shifts of the primary and secondary highlights, then their exponents
+    vec4 hair;
+    // -- 16 --
+    // Color, then perceptual roughness
+    vec4 sheen;
+    // -- 16 --
+    // Factor, ior, thickness, attenuation distance (0 absorbs nothing)
+    vec4 transmission;
+    // -- 16 --
+    // Color light absorbed over the attenuation distance turns into
+    vec4 attenuation;
     
     // -- 16 --
     uint texture_enable;
@@ -131,8 +200,55 @@ struct UniformData {
     Frustum frustum;
     vec4 ambient;
     uvec2 resolution;
+    uint clip_plane_count;
+    uint vertex_quantization;
+    // World space, keeping dot(plane, position) >= 0.
+    vec4 clip_planes[MAX_CLIP_PLANES];
 };
 
+bool is_clipped(UniformData uniforms, vec4 world_position) {
+    for (uint i = 0; i < uniforms.clip_plane_count; i++) {
+        if (dot(uniforms.clip_planes[i], world_position) < 0.0) {
+            return true;
+        }
+    }
+    return false;
+}
+
+// 4x4 ordered dither thresholds, scaled to 0..16.
+const float BAYER_4X4[16] = float[16](
+    0.0, 8.0, 2.0, 10.0,
+    12.0, 4.0, 14.0, 6.0,
+    3.0, 11.0, 1.0, 9.0,
+    15.0, 7.0, 13.0, 5.0
+);
+
+// Whether the pixel at frag_coord is left out of an object with this fade,
+// see ObjectFade. Inverted fades are stored as opacity - 2.0.
+bool is_faded_out(float fade, vec2 frag_coord) {
+    uvec2 pixel = uvec2(frag_coord) % 4u;
+    float threshold = (BAYER_4X4[pixel.y * 4u + pixel.x] + 0.5) / 16.0;
+    if (fade >= 0.0) {
+        return threshold >= fade;
+    }
+    return threshold < -1.0 - fade;
+}
+
+// Must match VertexQuantization::NORMALS.
+#define QUANTIZED_NORMALS 0x1
+
+// Undoes the octahedral encoding of normals and tangents if the renderer
+// quantizes them, see quantize.rs. The result isn't normalized.
+vec3 decode_direction(UniformData uniforms, vec3 direction) {
+    if ((uniforms.vertex_quantization & QUANTIZED_NORMALS) == 0) {
+        return direction;
+    }
+    vec3 n = vec3(direction.xy, 1.0 - abs(direction.x) - abs(direction.y));
+    float t = max(-n.z, 0.0);
+    n.xy -= t * mix(vec2(-1.0), vec2(1.0), greaterThanEqual(n.xy, vec2(0.0)));
+    return n;
+}
+
 struct DirectionalLightBufferHeader {
     uint total_lights;
 };
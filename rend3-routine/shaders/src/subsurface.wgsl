@@ -0,0 +1,108 @@
+/// One direction of the separable subsurface scattering blur, drawn as a
+/// fullscreen triangle. See subsurface.rs for the uniforms and defines.
+
+struct Profile {
+    /// xyz standard deviation of the scattering of each color in world
+    /// units, w strength.
+    scatter: vec4<f32>;
+};
+
+struct SubsurfaceUniforms {
+    /// Rows z and w of the inverse projection, to get view depths back.
+    unproject: vec4<f32>;
+    /// One pixel along the blur.
+    direction: vec2<i32>;
+    /// Pixels per world unit at a view depth of one, or at any depth if the
+    /// projection is orthographic.
+    pixel_scale: f32;
+    perspective: u32;
+    profile_count: u32;
+    _padding0: u32;
+    _padding1: u32;
+    _padding2: u32;
+    profiles: array<Profile, 16>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: SubsurfaceUniforms;
+[[group(0), binding(1)]]
+var source: texture_2d<f32>;
+[[group(0), binding(2)]]
+#ifdef MULTISAMPLED
+var scene_depth: texture_depth_multisampled_2d;
+#else
+var scene_depth: texture_depth_2d;
+#endif
+
+/// Taps on each side of the pixel.
+let SAMPLES: i32 = 8;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex: u32) -> [[builtin(position)]] vec4<f32> {
+    let uv = vec2<f32>(f32((vertex << 1u) & 2u), f32(vertex & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn view_depth(pixel: vec2<i32>) -> f32 {
+    let depth = textureLoad(scene_depth, pixel, 0);
+    let unproject = uniforms.unproject;
+    return abs((unproject.x * depth + unproject.y) / (unproject.z * depth + unproject.w));
+}
+
+/// The opaque shader writes the diffusion profile index + 2 into the alpha
+/// of marked pixels, all others are at most 1.
+fn profile_index(color: vec4<f32>) -> i32 {
+    if (color.a < 1.5) {
+        return -1;
+    }
+    let index = i32(round(color.a)) - 2;
+    return select(-1, index, index < i32(uniforms.profile_count));
+}
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let center = textureLoad(source, pixel, 0);
+    let index = profile_index(center);
+    if (index < 0) {
+        return center;
+    }
+
+    let scatter = uniforms.profiles[index].scatter;
+    let depth = view_depth(pixel);
+    let scale = uniforms.pixel_scale / select(1.0, depth, uniforms.perspective != 0u);
+    let width = max(scatter.x, max(scatter.y, scatter.z)) * 3.0;
+    // Too far away for the scattering to cover a pixel.
+    if (width * scale < 1.0) {
+        return center;
+    }
+
+    let step = width * scale / f32(SAMPLES);
+    let max_pixel = vec2<i32>(textureDimensions(source)) - 1;
+    let inv_variance = 1.0 / max(2.0 * scatter.xyz * scatter.xyz, vec3<f32>(1e-10));
+    var sum = center.rgb;
+    var weights = vec3<f32>(1.0);
+    for (var i = -SAMPLES; i <= SAMPLES; i = i + 1) {
+        if (i == 0) {
+            continue;
+        }
+        let offset = f32(i) * step;
+        let tap = clamp(pixel + uniforms.direction * i32(round(offset)), vec2<i32>(0), max_pixel);
+        let color = textureLoad(source, tap, 0);
+
+        // Taps off the material or across a depth discontinuity fall back to
+        // the center, so light doesn't bleed onto the background.
+        var follow = 0.0;
+        if (profile_index(color) == index) {
+            follow = 1.0 - clamp(abs(view_depth(tap) - depth) / width, 0.0, 1.0);
+        }
+        let tap_color = mix(center.rgb, color.rgb, follow);
+
+        let distance = offset / scale;
+        let weight = exp(-distance * distance * inv_variance);
+        sum = sum + tap_color * weight;
+        weights = weights + weight;
+    }
+
+    return vec4<f32>(mix(center.rgb, sum / weights, scatter.w), center.a);
+}
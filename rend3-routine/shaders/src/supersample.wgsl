@@ -0,0 +1,44 @@
+/// Filters `source`, rendered at `factor` times the resolution of the
+/// target, down to the target. Each pixel is a tent filtered average of the
+/// texels within `factor` texels of its center, weighted down where they are
+/// bright so single very bright texels don't flicker across pixels. Drawn as
+/// a fullscreen triangle.
+
+struct SupersampleParams {
+    factor: u32;
+};
+
+[[group(0), binding(0)]]
+var source: texture_2d<f32>;
+[[group(0), binding(1)]]
+var<uniform> params: SupersampleParams;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex: u32) -> [[builtin(position)]] vec4<f32> {
+    let uv = vec2<f32>(f32((vertex << 1u) & 2u), f32(vertex & 2u));
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let factor = i32(params.factor);
+    let radius = f32(factor);
+    let last_texel = textureDimensions(source) - vec2<i32>(1);
+    let center = position.xy * radius;
+    let first = vec2<i32>(floor(center - radius));
+
+    var sum = vec4<f32>(0.0);
+    var weights = 0.0;
+    for (var y: i32 = 0; y < 2 * factor; y = y + 1) {
+        for (var x: i32 = 0; x < 2 * factor; x = x + 1) {
+            let texel = first + vec2<i32>(x, y);
+            let offset = (vec2<f32>(texel) + 0.5 - center) / radius;
+            let tent = max(vec2<f32>(1.0) - abs(offset), vec2<f32>(0.0));
+            let color = textureLoad(source, clamp(texel, vec2<i32>(0), last_texel), 0);
+            let weight = tent.x * tent.y / (1.0 + max(color.r, max(color.g, color.b)));
+            sum = sum + color * weight;
+            weights = weights + weight;
+        }
+    }
+    return sum / max(weights, 0.000001);
+}
@@ -0,0 +1,87 @@
+struct TonemappingParams {
+    /// 0 for SDR, 1 for scRGB, 2 for PQ.
+    mode: u32;
+    /// Nits of a linear value of 1.0.
+    paper_white: f32;
+    max_luminance: f32;
+    /// 1 if the output is premultiplied by alpha, 2 if the output format is
+    /// srgb.
+    flags: u32;
+};
+
+[[group(0), binding(0)]]
+var primary_sampler: sampler;
+[[group(1), binding(0)]]
+var source: texture_2d<f32>;
+[[group(1), binding(1)]]
+var<uniform> params: TonemappingParams;
+
+/// Nits of a linear scRGB value of 1.0.
+let SCRGB_WHITE: f32 = 80.0;
+
+fn srgb_encode(color: vec3<f32>) -> vec3<f32> {
+    return select(
+        1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055,
+        color * 12.92,
+        color <= vec3<f32>(0.0031308)
+    );
+}
+
+fn srgb_decode(color: vec3<f32>) -> vec3<f32> {
+    return select(
+        pow((color + 0.055) / 1.055, vec3<f32>(2.4)),
+        color / 12.92,
+        color <= vec3<f32>(0.04045)
+    );
+}
+
+/// SMPTE ST 2084 inverse EOTF, from nits.
+fn pq_encode(nits: vec3<f32>) -> vec3<f32> {
+    let m1 = 0.1593017578125;
+    let m2 = 78.84375;
+    let c1 = 0.8359375;
+    let c2 = 18.8515625;
+    let c3 = 18.6875;
+    let y = pow(clamp(nits / 10000.0, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(m1));
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3<f32>(m2));
+}
+
+[[stage(fragment)]]
+fn main([[location(0)]] tex_coords: vec2<f32>) -> [[location(0)]] vec4<f32> {
+    let input = textureSample(source, primary_sampler, tex_coords);
+    let premultiplied = (params.flags & 1u) != 0u;
+
+    // The scene is premultiplied by blending over a transparent clear color,
+    // so undo it to encode the color itself.
+    var color = input.rgb;
+    if (premultiplied) {
+        color = color / max(input.a, 0.0001);
+    }
+
+    var encoded: vec3<f32>;
+    if (params.mode == 0u) {
+        encoded = srgb_encode(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)));
+    } else {
+        let nits = clamp(color * params.paper_white, vec3<f32>(0.0), vec3<f32>(params.max_luminance));
+        if (params.mode == 2u) {
+            // Rec.709 primaries to Rec.2020.
+            let to_rec2020 = mat3x3<f32>(
+                vec3<f32>(0.6274040, 0.0690970, 0.0163916),
+                vec3<f32>(0.3292820, 0.9195400, 0.0880132),
+                vec3<f32>(0.0433136, 0.0113612, 0.8955950),
+            );
+            encoded = pq_encode(to_rec2020 * nits);
+        } else {
+            encoded = nits / SCRGB_WHITE;
+        }
+    }
+
+    if (premultiplied) {
+        encoded = encoded * input.a;
+    }
+    // Srgb formats encode what is written, so hand them linear values.
+    if (params.mode == 0u && (params.flags & 2u) != 0u) {
+        encoded = srgb_decode(encoded);
+    }
+    return vec4<f32>(encoded, input.a);
+}
@@ -0,0 +1,131 @@
+/// What blended materials with transmission see through their surface,
+/// drawn with the default vertex shader before the surface itself. The frame
+/// prelude is prepended, see transmission.rs for group 3.
+
+/// The end of the material structs in structures.glsl, after the layout of
+/// the GpuDriven or CpuDriven struct.
+struct MaterialTail {
+    albedo: vec4<f32>;
+    emissive: vec3<f32>;
+    roughness: f32;
+    _unused0: vec4<f32>;
+    _unused1: vec4<f32>;
+    _unused2: vec4<f32>;
+    _unused3: vec4<f32>;
+    _unused4: vec4<f32>;
+    _unused5: vec4<f32>;
+    /// Factor, ior, thickness, attenuation distance (0 absorbs nothing)
+    transmission: vec4<f32>;
+    attenuation: vec4<f32>;
+};
+
+#ifdef GPU_DRIVEN
+struct Material {
+    texture_indices: array<vec4<u32>, 4>;
+    uv_transform0: mat3x3<f32>;
+    uv_transform1: mat3x3<f32>;
+    tail: MaterialTail;
+};
+
+struct Materials {
+    materials: array<Material>;
+};
+
+[[group(1), binding(1)]]
+var<storage, read> materials: Materials;
+#else
+struct Material {
+    uv_transform0: mat3x3<f32>;
+    uv_transform1: mat3x3<f32>;
+    tail: MaterialTail;
+    texture_enable: u32;
+};
+
+#ifdef DOWNLEVEL
+[[group(2), binding(0)]]
+var<uniform> material: Material;
+#else
+[[group(2), binding(0)]]
+var<storage, read> material: Material;
+#endif
+[[group(2), binding(1)]]
+var albedo_tex: texture_2d<f32>;
+#endif
+
+struct TransmissionUniforms {
+    /// Level of the scene color mip chain reached at a roughness of 1.
+    max_lod: f32;
+};
+
+[[group(3), binding(0)]]
+var scene_color: texture_2d<f32>;
+[[group(3), binding(1)]]
+var scene_sampler: sampler;
+[[group(3), binding(2)]]
+var<uniform> uniforms: TransmissionUniforms;
+
+/// GLSL's refract, which WGSL lacks.
+fn refract_ray(incident: vec3<f32>, normal: vec3<f32>, eta: f32) -> vec3<f32> {
+    let cos_i = dot(normal, incident);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if (k < 0.0) {
+        return vec3<f32>(0.0);
+    }
+    return eta * incident - (eta * cos_i + sqrt(k)) * normal;
+}
+
+struct Input {
+    [[builtin(position)]] position: vec4<f32>;
+    [[builtin(front_facing)]] front_facing: bool;
+    [[location(0)]] view_position: vec4<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[location(3)]] coords0: vec2<f32>;
+    [[location(6), interpolate(flat)]] material: u32;
+};
+
+[[stage(fragment)]]
+fn main(input: Input) -> [[location(0)]] vec4<f32> {
+#ifdef GPU_DRIVEN
+    let tail = materials.materials[input.material].tail;
+    let base_color = tail.albedo.rgb;
+#else
+    let tail = material.tail;
+    var base_color = tail.albedo.rgb;
+    if ((material.texture_enable & 1u) != 0u) {
+        let coords = (material.uv_transform0 * vec3<f32>(input.coords0, 1.0)).xy;
+        base_color = base_color * textureSample(albedo_tex, primary_sampler, coords).rgb;
+    }
+#endif
+
+    let transmission = tail.transmission;
+    if (transmission.x <= 0.0) {
+        discard;
+    }
+    let ior = transmission.y;
+    let thickness = transmission.z;
+
+    var normal = normalize(input.normal);
+    if (!input.front_facing) {
+        normal = -normal;
+    }
+
+    // Where the view ray leaves the volume, after bending at the surface.
+    let incident = normalize(input.view_position.xyz);
+    let refracted = refract_ray(incident, normal, 1.0 / ior);
+    let exit = input.view_position.xyz + refracted * thickness;
+    let clip = frame.view_proj * (frame.inv_view * vec4<f32>(exit, 1.0));
+    let uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + 0.5;
+
+    // Rough surfaces see a blurrier background, and less so the closer the
+    // ior is to air.
+    let lod = uniforms.max_lod * tail.roughness * clamp(ior * 2.0 - 2.0, 0.0, 1.0);
+    let behind = textureSampleLevel(scene_color, scene_sampler, uv, lod).rgb;
+
+    // Beer-Lambert absorption along the path through the volume.
+    var absorbed = vec3<f32>(1.0);
+    if (transmission.w > 0.0) {
+        absorbed = pow(tail.attenuation.rgb, vec3<f32>(thickness / transmission.w));
+    }
+
+    return vec4<f32>(behind * base_color * absorbed, transmission.x);
+}
@@ -0,0 +1,131 @@
+struct VolumeUniforms {
+    model_view_proj: mat4x4<f32>;
+    // Camera position in the local space of the volume, where it spans 0..1.
+    camera: vec4<f32>;
+    // Kept where dot(plane.xyz, position) + plane.w >= 0, in local space.
+    planes: array<vec4<f32>, 4>;
+    transfer: array<vec4<f32>, 16>;
+    transfer_count: u32;
+    plane_count: u32;
+    mode: u32;
+    steps: u32;
+    density_scale: f32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] local: vec3<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: VolumeUniforms;
+[[group(0), binding(1)]]
+var volume_texture: texture_3d<f32>;
+[[group(0), binding(2)]]
+var volume_sampler: sampler;
+
+let MODE_MAX_INTENSITY: u32 = 0u;
+
+// Two triangles per face of the unit cube.
+var<private> CUBE_INDICES: array<u32, 36> = array<u32, 36>(
+    0u, 1u, 3u, 0u, 3u, 2u,
+    4u, 6u, 7u, 4u, 7u, 5u,
+    0u, 4u, 5u, 0u, 5u, 1u,
+    2u, 3u, 7u, 2u, 7u, 6u,
+    0u, 2u, 6u, 0u, 6u, 4u,
+    1u, 5u, 7u, 1u, 7u, 3u
+);
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let corner = CUBE_INDICES[vertex_index];
+    let local = vec3<f32>(f32(corner & 1u), f32((corner >> 1u) & 1u), f32((corner >> 2u) & 1u));
+    return VertexOutput(uniforms.model_view_proj * vec4<f32>(local, 1.0), local);
+}
+
+fn transfer(density: f32) -> vec4<f32> {
+    let x = clamp(density, 0.0, 1.0) * f32(uniforms.transfer_count - 1u);
+    let index = u32(floor(x));
+    let next = min(index + 1u, uniforms.transfer_count - 1u);
+    return mix(uniforms.transfer[index], uniforms.transfer[next], fract(x));
+}
+
+[[stage(fragment)]]
+fn fs_main(input: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let origin = uniforms.camera.xyz;
+    // The fragment is at t = 1 along the ray.
+    let dir = input.local - origin;
+
+    let inv_dir = 1.0 / dir;
+    let t0 = -origin * inv_dir;
+    let t1 = (vec3<f32>(1.0) - origin) * inv_dir;
+    let t_min = min(t0, t1);
+    let t_max = max(t0, t1);
+    var t_enter = max(max(max(t_min.x, t_min.y), t_min.z), 0.0);
+    let t_box_exit = min(min(t_max.x, t_max.y), t_max.z);
+
+    // Only the faces the ray leaves the volume through march it, so it is
+    // drawn once whatever the winding of the cube.
+    if (t_box_exit > 1.001) {
+        discard;
+    }
+    var t_exit = 1.0;
+
+    var i = 0u;
+    loop {
+        if (i >= uniforms.plane_count) {
+            break;
+        }
+        let plane = uniforms.planes[i];
+        let start = dot(plane.xyz, origin) + plane.w;
+        let rate = dot(plane.xyz, dir);
+        if (rate > 0.0) {
+            t_enter = max(t_enter, -start / rate);
+        } else {
+            if (rate < 0.0) {
+                t_exit = min(t_exit, -start / rate);
+            } else {
+                if (start < 0.0) {
+                    discard;
+                }
+            }
+        }
+        continuing {
+            i = i + 1u;
+        }
+    }
+    if (t_enter >= t_exit) {
+        discard;
+    }
+
+    // Steps are spaced evenly along the diagonal of the volume.
+    let step_length = 1.7320508 / f32(uniforms.steps);
+    let dt = step_length / length(dir);
+
+    var max_density = 0.0;
+    var color = vec4<f32>(0.0);
+    var t = t_enter + dt * 0.5;
+    loop {
+        if (t >= t_exit || color.a >= 0.99) {
+            break;
+        }
+        let density = textureSampleLevel(volume_texture, volume_sampler, origin + dir * t, 0.0).r;
+        if (uniforms.mode == MODE_MAX_INTENSITY) {
+            max_density = max(max_density, density);
+        } else {
+            let sample = transfer(density);
+            let alpha = 1.0 - exp(-sample.a * uniforms.density_scale * step_length);
+            color = color + (1.0 - color.a) * vec4<f32>(sample.rgb * alpha, alpha);
+        }
+        continuing {
+            t = t + dt;
+        }
+    }
+
+    if (uniforms.mode == MODE_MAX_INTENSITY) {
+        let sample = transfer(max_density);
+        color = vec4<f32>(sample.rgb * sample.a, sample.a);
+    }
+    // Premultiplied alpha.
+    return color;
+}
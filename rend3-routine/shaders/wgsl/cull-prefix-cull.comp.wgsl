@@ -28,10 +28,15 @@ struct ObjectInputData {
     material_idx: u32;
     transform: mat4x4<f32>;
     bounding_sphere: vec4<f32>;
+    position_offset: vec3<f32>;
+    position_scale: vec3<f32>;
+    _padding0_: f32;
+    fade: f32;
+    joint_offset: u32;
 };
 
 struct ObjectInputDataBuffer {
-    object_input: [[stride(96)]] array<ObjectInputData>;
+    object_input: [[stride(144)]] array<ObjectInputData>;
 };
 
 struct IntermediateBufferA {
@@ -47,7 +52,7 @@ var<storage> unnamed_1: ObjectInputDataBuffer;
 var<storage, read_write> unnamed_2: IntermediateBufferA;
 
 fn main_1() {
-    var phi_502_: bool;
+    var phi_540_: bool;
 
     switch(bitcast<i32>(0u)) {
         default: {
@@ -68,30 +73,30 @@ fn main_1() {
                     let _e75 = vec4<f32>(_e54.x, _e54.y, _e54.z, _e56).xyz;
                     let _e76 = -(_e56);
                     if (!(((dot(_e63.left.inner.xyz, _e75) + _e63.left.inner.w) >= _e76))) {
-                        phi_502_ = false;
+                        phi_540_ = false;
                         break;
                     }
                     if (!(((dot(_e63.right.inner.xyz, _e75) + _e63.right.inner.w) >= _e76))) {
-                        phi_502_ = false;
+                        phi_540_ = false;
                         break;
                     }
                     if (!(((dot(_e63.top.inner.xyz, _e75) + _e63.top.inner.w) >= _e76))) {
-                        phi_502_ = false;
+                        phi_540_ = false;
                         break;
                     }
                     if (!(((dot(_e63.bottom.inner.xyz, _e75) + _e63.bottom.inner.w) >= _e76))) {
-                        phi_502_ = false;
+                        phi_540_ = false;
                         break;
                     }
                     if (!(((dot(_e63.near.inner.xyz, _e75) + _e63.near.inner.w) >= _e76))) {
-                        phi_502_ = false;
+                        phi_540_ = false;
                         break;
                     }
-                    phi_502_ = true;
+                    phi_540_ = true;
                     break;
                 }
             }
-            let _e108 = phi_502_;
+            let _e108 = phi_540_;
             let _e109 = select(0u, 1u, _e108);
             unnamed_2.result_index_a[_e24] = insertBits(insertBits(0u, _e109, bitcast<u32>(31), bitcast<u32>(1)), _e109, bitcast<u32>(0), bitcast<u32>(31));
             break;
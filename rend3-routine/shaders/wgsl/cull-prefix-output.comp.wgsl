@@ -25,7 +25,10 @@ struct ObjectOutputData {
     model_view: mat4x4<f32>;
     model_view_proj: mat4x4<f32>;
     material_idx: u32;
+    texture_layer: u32;
+    joint_offset: u32;
     inv_squared_scale: vec3<f32>;
+    fade: f32;
 };
 
 struct ObjectOutputDataBuffer {
@@ -59,10 +62,15 @@ struct ObjectInputData {
     material_idx: u32;
     transform: mat4x4<f32>;
     bounding_sphere: vec4<f32>;
+    position_offset: vec3<f32>;
+    position_scale: vec3<f32>;
+    _padding0_: f32;
+    fade: f32;
+    joint_offset: u32;
 };
 
 struct ObjectInputDataBuffer {
-    object_input: [[stride(96)]] array<ObjectInputData>;
+    object_input: [[stride(144)]] array<ObjectInputData>;
 };
 
 [[group(0), binding(1)]]
@@ -80,40 +88,48 @@ var<storage> unnamed_4: ObjectInputDataBuffer;
 fn main_1() {
     switch(bitcast<i32>(0u)) {
         default: {
-            let _e27 = gl_GlobalInvocationID_1[0u];
-            let _e30 = unnamed.uniforms.object_count;
-            if ((_e27 >= _e30)) {
+            let _e34 = gl_GlobalInvocationID_1[0u];
+            let _e37 = unnamed.uniforms.object_count;
+            if ((_e34 >= _e37)) {
                 break;
             }
-            let _e34 = unnamed_3.result_index_a[_e27];
-            let _e37 = extractBits(_e34, bitcast<u32>(0), bitcast<u32>(31));
-            let _e38 = (_e37 - 1u);
-            if ((_e27 == (_e30 - 1u))) {
-                unnamed_2.draw_call_count = _e37;
+            let _e41 = unnamed_3.result_index_a[_e34];
+            let _e44 = extractBits(_e41, bitcast<u32>(0), bitcast<u32>(31));
+            let _e45 = (_e44 - 1u);
+            if ((_e34 == (_e37 - 1u))) {
+                unnamed_2.draw_call_count = _e44;
             }
-            if (!((extractBits(_e34, bitcast<u32>(31), bitcast<u32>(1)) != 0u))) {
+            if (!((extractBits(_e41, bitcast<u32>(31), bitcast<u32>(1)) != 0u))) {
                 break;
             }
-            let _e50 = unnamed_4.object_input[_e27].start_idx;
-            let _e52 = unnamed_4.object_input[_e27].count;
-            let _e54 = unnamed_4.object_input[_e27].vertex_offset;
-            let _e56 = unnamed_4.object_input[_e27].material_idx;
-            let _e58 = unnamed_4.object_input[_e27].transform;
-            let _e61 = unnamed.uniforms.view;
-            let _e62 = (_e61 * _e58);
-            let _e65 = unnamed.uniforms.view_proj;
-            let _e68 = _e62[0].xyz;
-            let _e70 = _e62[1].xyz;
-            let _e72 = _e62[2].xyz;
-            unnamed_1.object_output[_e38].model_view = _e62;
-            unnamed_1.object_output[_e38].model_view_proj = (_e65 * _e58);
-            unnamed_1.object_output[_e38].material_idx = _e56;
-            unnamed_1.object_output[_e38].inv_squared_scale = (vec3<f32>(1.0, 1.0, 1.0) / vec3<f32>(dot(_e68, _e68), dot(_e70, _e70), dot(_e72, _e72)));
-            unnamed_2.indirect_call[_e38].vertex_count = _e52;
-            unnamed_2.indirect_call[_e38].instance_count = 1u;
-            unnamed_2.indirect_call[_e38].base_index = _e50;
-            unnamed_2.indirect_call[_e38].vertex_offset = _e54;
-            unnamed_2.indirect_call[_e38].base_instance = _e38;
+            let _e57 = unnamed_4.object_input[_e34].start_idx;
+            let _e59 = unnamed_4.object_input[_e34].count;
+            let _e61 = unnamed_4.object_input[_e34].vertex_offset;
+            let _e63 = unnamed_4.object_input[_e34].material_idx;
+            let _e65 = unnamed_4.object_input[_e34].transform;
+            let _e67 = unnamed_4.object_input[_e34].position_offset;
+            let _e69 = unnamed_4.object_input[_e34].position_scale;
+            let _e71 = unnamed_4.object_input[_e34].fade;
+            let _e73 = unnamed_4.object_input[_e34].joint_offset;
+            let _e85 = (_e65 * mat4x4<f32>(vec4<f32>(_e69.x, 0.0, 0.0, 0.0), vec4<f32>(0.0, _e69.y, 0.0, 0.0), vec4<f32>(0.0, 0.0, _e69.z, 0.0), vec4<f32>(_e67.x, _e67.y, _e67.z, 1.0)));
+            let _e88 = unnamed.uniforms.view;
+            let _e89 = (_e88 * _e85);
+            let _e92 = unnamed.uniforms.view_proj;
+            let _e95 = _e89[0].xyz;
+            let _e97 = _e89[1].xyz;
+            let _e99 = _e89[2].xyz;
+            unnamed_1.object_output[_e45].model_view = _e89;
+            unnamed_1.object_output[_e45].model_view_proj = (_e92 * _e85);
+            unnamed_1.object_output[_e45].material_idx = _e63;
+            unnamed_1.object_output[_e45].texture_layer = 0u;
+            unnamed_1.object_output[_e45].joint_offset = _e73;
+            unnamed_1.object_output[_e45].inv_squared_scale = (vec3<f32>(1.0, 1.0, 1.0) / vec3<f32>(dot(_e95, _e95), dot(_e97, _e97), dot(_e99, _e99)));
+            unnamed_1.object_output[_e45].fade = _e71;
+            unnamed_2.indirect_call[_e45].vertex_count = _e59;
+            unnamed_2.indirect_call[_e45].instance_count = 1u;
+            unnamed_2.indirect_call[_e45].base_index = _e57;
+            unnamed_2.indirect_call[_e45].vertex_offset = _e61;
+            unnamed_2.indirect_call[_e45].base_instance = _e45;
             break;
         }
     }
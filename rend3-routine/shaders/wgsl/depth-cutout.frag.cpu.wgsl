@@ -9,54 +9,163 @@ struct TextureData {
     material_data: [[stride(4)]] array<f32>;
 };
 
+struct Plane {
+    inner: vec4<f32>;
+};
+
+struct Frustum {
+    left: Plane;
+    right: Plane;
+    top: Plane;
+    bottom: Plane;
+    near: Plane;
+};
+
+struct UniformData {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    origin_view_proj: mat4x4<f32>;
+    inv_view: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_origin_view_proj: mat4x4<f32>;
+    frustum: Frustum;
+    ambient: vec4<f32>;
+    resolution: vec2<u32>;
+    clip_plane_count: u32;
+    vertex_quantization: u32;
+    clip_planes: [[stride(16)]] array<vec4<f32>,4u>;
+};
+
+struct UniformBuffer {
+    uniforms: UniformData;
+};
+
 [[group(2), binding(0)]]
 var<uniform> unnamed: DataAbi;
 var<private> i_material_1: u32;
 [[group(3), binding(0)]]
 var<storage> unnamed_1: TextureData;
 var<private> i_coords0_1: vec2<f32>;
+[[group(0), binding(3)]]
+var<uniform> unnamed_2: UniformBuffer;
+var<private> i_position_1: vec4<f32>;
+var<private> i_fade_1: f32;
+var<private> gl_FragCoord_1: vec4<f32>;
 [[group(3), binding(1)]]
 var texture: texture_2d<f32>;
 [[group(0), binding(0)]]
 var primary_sampler: sampler;
-var<private> i_position_1: vec4<f32>;
 var<private> i_color_1: vec4<f32>;
 
 fn main_1() {
-    var phi_168_: vec2<f32>;
+    var local: array<vec4<f32>,4u>;
+    var local_1: array<f32,16u>;
+    var phi_465_: vec2<f32>;
+    var phi_466_: u32;
+    var phi_470_: bool;
+    var phi_467_: bool;
+    var phi_469_: bool;
+    var phi_474_: bool;
+    var phi_322_: bool;
 
-    let _e28 = unnamed.stride;
-    let _e29 = i_material_1;
-    let _e30 = (_e28 * _e29);
-    let _e32 = unnamed.cutoff_offset;
-    let _e36 = unnamed_1.material_data[(_e30 + _e32)];
-    let _e38 = unnamed.uv_transform_offset;
-    if ((_e38 != 4294967295u)) {
-        let _e40 = (_e30 + _e38);
-        let _e43 = unnamed_1.material_data[_e40];
-        let _e47 = unnamed_1.material_data[(_e40 + 1u)];
-        let _e51 = unnamed_1.material_data[(_e40 + 2u)];
-        let _e55 = unnamed_1.material_data[(_e40 + 4u)];
-        let _e59 = unnamed_1.material_data[(_e40 + 5u)];
-        let _e63 = unnamed_1.material_data[(_e40 + 6u)];
-        let _e67 = unnamed_1.material_data[(_e40 + 8u)];
-        let _e71 = unnamed_1.material_data[(_e40 + 9u)];
-        let _e75 = unnamed_1.material_data[(_e40 + 10u)];
-        let _e80 = i_coords0_1;
-        let _e84 = (mat3x3<f32>(vec3<f32>(_e43, _e47, _e51), vec3<f32>(_e55, _e59, _e63), vec3<f32>(_e67, _e71, _e75)) * vec3<f32>(_e80.x, _e80.y, 1.0));
-        phi_168_ = vec2<f32>(_e84.x, _e84.y);
+    let _e59 = unnamed.stride;
+    let _e60 = i_material_1;
+    let _e61 = (_e59 * _e60);
+    let _e63 = unnamed.cutoff_offset;
+    let _e67 = unnamed_1.material_data[(_e61 + _e63)];
+    let _e69 = unnamed.uv_transform_offset;
+    if ((_e69 != 4294967295u)) {
+        let _e71 = (_e61 + _e69);
+        let _e74 = unnamed_1.material_data[_e71];
+        let _e78 = unnamed_1.material_data[(_e71 + 1u)];
+        let _e82 = unnamed_1.material_data[(_e71 + 2u)];
+        let _e86 = unnamed_1.material_data[(_e71 + 4u)];
+        let _e90 = unnamed_1.material_data[(_e71 + 5u)];
+        let _e94 = unnamed_1.material_data[(_e71 + 6u)];
+        let _e98 = unnamed_1.material_data[(_e71 + 8u)];
+        let _e102 = unnamed_1.material_data[(_e71 + 9u)];
+        let _e106 = unnamed_1.material_data[(_e71 + 10u)];
+        let _e111 = i_coords0_1;
+        let _e115 = (mat3x3<f32>(vec3<f32>(_e74, _e78, _e82), vec3<f32>(_e86, _e90, _e94), vec3<f32>(_e98, _e102, _e106)) * vec3<f32>(_e111.x, _e111.y, 1.0));
+        phi_465_ = vec2<f32>(_e115.x, _e115.y);
     } else {
-        let _e88 = i_coords0_1;
-        phi_168_ = _e88;
+        let _e119 = i_coords0_1;
+        phi_465_ = _e119;
+    }
+    let _e121 = phi_465_;
+    let _e122 = dpdx(_e121);
+    let _e123 = dpdy(_e121);
+    let _e126 = unnamed_2.uniforms.inv_view_proj;
+    let _e127 = i_position_1;
+    let _e131 = unnamed_2.uniforms.clip_plane_count;
+    let _e133 = unnamed_2.uniforms.clip_planes;
+    local[0] = _e133[0];
+    local[1] = _e133[1];
+    local[2] = _e133[2];
+    local[3] = _e133[3];
+    switch(bitcast<i32>(0u)) {
+        default: {
+            phi_466_ = 0u;
+            loop {
+                let _e144 = phi_466_;
+                phi_470_ = false;
+                phi_467_ = false;
+                if ((_e144 < _e131)) {
+                    let _e147 = local[_e144];
+                    if ((dot(_e147, (_e126 * _e127)) < 0.0)) {
+                        phi_470_ = true;
+                        phi_467_ = true;
+                        break;
+                    }
+                    continue;
+                } else {
+                    break;
+                }
+                continuing {
+                    phi_466_ = (_e144 + bitcast<u32>(1));
+                }
+            }
+            let _e153 = phi_470_;
+            let _e155 = phi_467_;
+            phi_469_ = _e153;
+            if (_e155) {
+                break;
+            }
+            phi_469_ = false;
+            break;
+        }
+    }
+    let _e157 = phi_469_;
+    phi_322_ = _e157;
+    if (!(_e157)) {
+        let _e159 = i_fade_1;
+        let _e160 = gl_FragCoord_1;
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e164 = (vec2<u32>(_e160.xy) % vec2<u32>(4u, 4u));
+                local_1 = array<f32,16u>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+                let _e170 = local_1[((_e164.y * 4u) + _e164.x)];
+                let _e172 = ((_e170 + 0.5) * 0.0625);
+                if ((_e159 >= 0.0)) {
+                    phi_474_ = (_e172 >= _e159);
+                    break;
+                }
+                phi_474_ = (_e172 < (-1.0 - _e159));
+                break;
+            }
+        }
+        let _e178 = phi_474_;
+        phi_322_ = _e178;
+    }
+    let _e180 = phi_322_;
+    if (_e180) {
+        discard;
     }
-    let _e90 = phi_168_;
-    let _e91 = dpdx(_e90);
-    let _e92 = dpdy(_e90);
-    let _e94 = unnamed.texture_offset;
-    let _e98 = unnamed_1.material_data[(_e30 + _e94)];
-    if (((bitcast<u32>(_e98) & 1u) != 0u)) {
-        let _e102 = textureSampleGrad(texture, primary_sampler, _e90, _e91, _e92);
-        if ((_e102.w <= _e36)) {
+    let _e182 = unnamed.texture_offset;
+    let _e186 = unnamed_1.material_data[(_e61 + _e182)];
+    if (((bitcast<u32>(_e186) & 1u) != 0u)) {
+        let _e190 = textureSampleGrad(texture, primary_sampler, _e121, _e122, _e123);
+        if ((_e190.w <= _e67)) {
             discard;
         }
     }
@@ -64,10 +173,12 @@ fn main_1() {
 }
 
 [[stage(fragment)]]
-fn main([[location(3)]] i_material: u32, [[location(1)]] i_coords0_: vec2<f32>, [[location(0)]] i_position: vec4<f32>, [[location(2)]] i_color: vec4<f32>) {
+fn main([[location(3)]] i_material: u32, [[location(1)]] i_coords0_: vec2<f32>, [[location(0)]] i_position: vec4<f32>, [[location(4), interpolate(flat)]] i_fade: f32, [[builtin(position)]] gl_FragCoord: vec4<f32>, [[location(2)]] i_color: vec4<f32>) {
     i_material_1 = i_material;
     i_coords0_1 = i_coords0_;
     i_position_1 = i_position;
+    i_fade_1 = i_fade;
+    gl_FragCoord_1 = gl_FragCoord;
     i_color_1 = i_color;
     main_1();
 }
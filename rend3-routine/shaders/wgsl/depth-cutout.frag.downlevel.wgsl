@@ -0,0 +1,194 @@
+struct DataAbi {
+    stride: u32;
+    texture_offset: u32;
+    cutoff_offset: u32;
+    uv_transform_offset: u32;
+};
+
+struct TextureData {
+    material_data_packed: [[stride(16)]] array<vec4<f32>,16u>;
+};
+
+struct Plane {
+    inner: vec4<f32>;
+};
+
+struct Frustum {
+    left: Plane;
+    right: Plane;
+    top: Plane;
+    bottom: Plane;
+    near: Plane;
+};
+
+struct UniformData {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    origin_view_proj: mat4x4<f32>;
+    inv_view: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_origin_view_proj: mat4x4<f32>;
+    frustum: Frustum;
+    ambient: vec4<f32>;
+    resolution: vec2<u32>;
+    clip_plane_count: u32;
+    vertex_quantization: u32;
+    clip_planes: [[stride(16)]] array<vec4<f32>,4u>;
+};
+
+struct UniformBuffer {
+    uniforms: UniformData;
+};
+
+[[group(2), binding(0)]]
+var<uniform> unnamed: DataAbi;
+var<private> i_material_1: u32;
+[[group(3), binding(0)]]
+var<uniform> unnamed_1: TextureData;
+var<private> i_coords0_1: vec2<f32>;
+[[group(0), binding(3)]]
+var<uniform> unnamed_2: UniformBuffer;
+var<private> i_position_1: vec4<f32>;
+var<private> i_fade_1: f32;
+var<private> gl_FragCoord_1: vec4<f32>;
+[[group(3), binding(1)]]
+var texture: texture_2d<f32>;
+[[group(0), binding(0)]]
+var primary_sampler: sampler;
+var<private> i_color_1: vec4<f32>;
+
+fn main_1() {
+    var local: array<vec4<f32>,4u>;
+    var local_1: array<f32,16u>;
+    var phi_513_: vec2<f32>;
+    var phi_514_: u32;
+    var phi_518_: bool;
+    var phi_515_: bool;
+    var phi_517_: bool;
+    var phi_522_: bool;
+    var phi_364_: bool;
+
+    let _e59 = unnamed.stride;
+    let _e60 = i_material_1;
+    let _e61 = (_e59 * _e60);
+    let _e63 = unnamed.cutoff_offset;
+    let _e64 = (_e61 + _e63);
+    let _e70 = unnamed_1.material_data_packed[(_e64 / 4u)][(_e64 % 4u)];
+    let _e72 = unnamed.uv_transform_offset;
+    if ((_e72 != 4294967295u)) {
+        let _e74 = (_e61 + _e72);
+        let _e80 = unnamed_1.material_data_packed[(_e74 / 4u)][(_e74 % 4u)];
+        let _e81 = (_e74 + 1u);
+        let _e87 = unnamed_1.material_data_packed[(_e81 / 4u)][(_e81 % 4u)];
+        let _e88 = (_e74 + 2u);
+        let _e94 = unnamed_1.material_data_packed[(_e88 / 4u)][(_e88 % 4u)];
+        let _e95 = (_e74 + 4u);
+        let _e101 = unnamed_1.material_data_packed[(_e95 / 4u)][(_e95 % 4u)];
+        let _e102 = (_e74 + 5u);
+        let _e108 = unnamed_1.material_data_packed[(_e102 / 4u)][(_e102 % 4u)];
+        let _e109 = (_e74 + 6u);
+        let _e115 = unnamed_1.material_data_packed[(_e109 / 4u)][(_e109 % 4u)];
+        let _e116 = (_e74 + 8u);
+        let _e122 = unnamed_1.material_data_packed[(_e116 / 4u)][(_e116 % 4u)];
+        let _e123 = (_e74 + 9u);
+        let _e129 = unnamed_1.material_data_packed[(_e123 / 4u)][(_e123 % 4u)];
+        let _e130 = (_e74 + 10u);
+        let _e136 = unnamed_1.material_data_packed[(_e130 / 4u)][(_e130 % 4u)];
+        let _e141 = i_coords0_1;
+        let _e145 = (mat3x3<f32>(vec3<f32>(_e80, _e87, _e94), vec3<f32>(_e101, _e108, _e115), vec3<f32>(_e122, _e129, _e136)) * vec3<f32>(_e141.x, _e141.y, 1.0));
+        phi_513_ = vec2<f32>(_e145.x, _e145.y);
+    } else {
+        let _e149 = i_coords0_1;
+        phi_513_ = _e149;
+    }
+    let _e151 = phi_513_;
+    let _e152 = dpdx(_e151);
+    let _e153 = dpdy(_e151);
+    let _e156 = unnamed_2.uniforms.inv_view_proj;
+    let _e157 = i_position_1;
+    let _e161 = unnamed_2.uniforms.clip_plane_count;
+    let _e163 = unnamed_2.uniforms.clip_planes;
+    local[0] = _e163[0];
+    local[1] = _e163[1];
+    local[2] = _e163[2];
+    local[3] = _e163[3];
+    switch(bitcast<i32>(0u)) {
+        default: {
+            phi_514_ = 0u;
+            loop {
+                let _e174 = phi_514_;
+                phi_518_ = false;
+                phi_515_ = false;
+                if ((_e174 < _e161)) {
+                    let _e177 = local[_e174];
+                    if ((dot(_e177, (_e156 * _e157)) < 0.0)) {
+                        phi_518_ = true;
+                        phi_515_ = true;
+                        break;
+                    }
+                    continue;
+                } else {
+                    break;
+                }
+                continuing {
+                    phi_514_ = (_e174 + bitcast<u32>(1));
+                }
+            }
+            let _e183 = phi_518_;
+            let _e185 = phi_515_;
+            phi_517_ = _e183;
+            if (_e185) {
+                break;
+            }
+            phi_517_ = false;
+            break;
+        }
+    }
+    let _e187 = phi_517_;
+    phi_364_ = _e187;
+    if (!(_e187)) {
+        let _e189 = i_fade_1;
+        let _e190 = gl_FragCoord_1;
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e194 = (vec2<u32>(_e190.xy) % vec2<u32>(4u, 4u));
+                local_1 = array<f32,16u>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+                let _e200 = local_1[((_e194.y * 4u) + _e194.x)];
+                let _e202 = ((_e200 + 0.5) * 0.0625);
+                if ((_e189 >= 0.0)) {
+                    phi_522_ = (_e202 >= _e189);
+                    break;
+                }
+                phi_522_ = (_e202 < (-1.0 - _e189));
+                break;
+            }
+        }
+        let _e208 = phi_522_;
+        phi_364_ = _e208;
+    }
+    let _e210 = phi_364_;
+    if (_e210) {
+        discard;
+    }
+    let _e212 = unnamed.texture_offset;
+    let _e213 = (_e61 + _e212);
+    let _e219 = unnamed_1.material_data_packed[(_e213 / 4u)][(_e213 % 4u)];
+    if (((bitcast<u32>(_e219) & 1u) != 0u)) {
+        let _e223 = textureSampleGrad(texture, primary_sampler, _e151, _e152, _e153);
+        if ((_e223.w <= _e70)) {
+            discard;
+        }
+    }
+    return;
+}
+
+[[stage(fragment)]]
+fn main([[location(3)]] i_material: u32, [[location(1)]] i_coords0_: vec2<f32>, [[location(0)]] i_position: vec4<f32>, [[location(4), interpolate(flat)]] i_fade: f32, [[builtin(position)]] gl_FragCoord: vec4<f32>, [[location(2)]] i_color: vec4<f32>) {
+    i_material_1 = i_material;
+    i_coords0_1 = i_coords0_;
+    i_position_1 = i_position;
+    i_fade_1 = i_fade;
+    gl_FragCoord_1 = gl_FragCoord;
+    i_color_1 = i_color;
+    main_1();
+}
@@ -1,15 +1,127 @@
+struct Plane {
+    inner: vec4<f32>;
+};
+
+struct Frustum {
+    left: Plane;
+    right: Plane;
+    top: Plane;
+    bottom: Plane;
+    near: Plane;
+};
+
+struct UniformData {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    origin_view_proj: mat4x4<f32>;
+    inv_view: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_origin_view_proj: mat4x4<f32>;
+    frustum: Frustum;
+    ambient: vec4<f32>;
+    resolution: vec2<u32>;
+    clip_plane_count: u32;
+    vertex_quantization: u32;
+    clip_planes: [[stride(16)]] array<vec4<f32>,4u>;
+};
+
+struct UniformBuffer {
+    uniforms: UniformData;
+};
+
+[[group(0), binding(3)]]
+var<uniform> unnamed: UniformBuffer;
 var<private> i_position_1: vec4<f32>;
+var<private> i_fade_1: f32;
+var<private> gl_FragCoord_1: vec4<f32>;
 var<private> i_coords0_1: vec2<f32>;
 var<private> i_color_1: vec4<f32>;
 var<private> i_material_1: u32;
 
 fn main_1() {
+    var local: array<vec4<f32>,4u>;
+    var local_1: array<f32,16u>;
+    var phi_329_: u32;
+    var phi_333_: bool;
+    var phi_330_: bool;
+    var phi_332_: bool;
+    var phi_336_: bool;
+    var phi_218_: bool;
+
+    let _e49 = unnamed.uniforms.inv_view_proj;
+    let _e50 = i_position_1;
+    let _e54 = unnamed.uniforms.clip_plane_count;
+    let _e56 = unnamed.uniforms.clip_planes;
+    local[0] = _e56[0];
+    local[1] = _e56[1];
+    local[2] = _e56[2];
+    local[3] = _e56[3];
+    switch(bitcast<i32>(0u)) {
+        default: {
+            phi_329_ = 0u;
+            loop {
+                let _e67 = phi_329_;
+                phi_333_ = false;
+                phi_330_ = false;
+                if ((_e67 < _e54)) {
+                    let _e70 = local[_e67];
+                    if ((dot(_e70, (_e49 * _e50)) < 0.0)) {
+                        phi_333_ = true;
+                        phi_330_ = true;
+                        break;
+                    }
+                    continue;
+                } else {
+                    break;
+                }
+                continuing {
+                    phi_329_ = (_e67 + bitcast<u32>(1));
+                }
+            }
+            let _e76 = phi_333_;
+            let _e78 = phi_330_;
+            phi_332_ = _e76;
+            if (_e78) {
+                break;
+            }
+            phi_332_ = false;
+            break;
+        }
+    }
+    let _e80 = phi_332_;
+    phi_218_ = _e80;
+    if (!(_e80)) {
+        let _e82 = i_fade_1;
+        let _e83 = gl_FragCoord_1;
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e87 = (vec2<u32>(_e83.xy) % vec2<u32>(4u, 4u));
+                local_1 = array<f32,16u>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+                let _e93 = local_1[((_e87.y * 4u) + _e87.x)];
+                let _e95 = ((_e93 + 0.5) * 0.0625);
+                if ((_e82 >= 0.0)) {
+                    phi_336_ = (_e95 >= _e82);
+                    break;
+                }
+                phi_336_ = (_e95 < (-1.0 - _e82));
+                break;
+            }
+        }
+        let _e101 = phi_336_;
+        phi_218_ = _e101;
+    }
+    let _e103 = phi_218_;
+    if (_e103) {
+        discard;
+    }
     return;
 }
 
 [[stage(fragment)]]
-fn main([[location(0)]] i_position: vec4<f32>, [[location(1)]] i_coords0_: vec2<f32>, [[location(2)]] i_color: vec4<f32>, [[location(3)]] i_material: u32) {
+fn main([[location(0)]] i_position: vec4<f32>, [[location(4), interpolate(flat)]] i_fade: f32, [[builtin(position)]] gl_FragCoord: vec4<f32>, [[location(1)]] i_coords0_: vec2<f32>, [[location(2)]] i_color: vec4<f32>, [[location(3)]] i_material: u32) {
     i_position_1 = i_position;
+    i_fade_1 = i_fade;
+    gl_FragCoord_1 = gl_FragCoord;
     i_coords0_1 = i_coords0_;
     i_color_1 = i_color;
     i_material_1 = i_material;
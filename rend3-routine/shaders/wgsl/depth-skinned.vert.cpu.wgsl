@@ -0,0 +1,119 @@
+struct JointMatrixBuffer {
+    joint_matrices: [[stride(64)]] array<mat4x4<f32>>;
+};
+
+struct ObjectOutputData {
+    model_view: mat4x4<f32>;
+    model_view_proj: mat4x4<f32>;
+    material_idx: u32;
+    texture_layer: u32;
+    joint_offset: u32;
+    inv_squared_scale: vec3<f32>;
+    fade: f32;
+};
+
+struct ObjectOutputDataBuffer {
+    object_output: [[stride(160)]] array<ObjectOutputData>;
+};
+
+struct gl_PerVertex {
+    [[builtin(position)]] gl_Position: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[location(0)]] member: vec4<f32>;
+    [[builtin(position)]] gl_Position: vec4<f32>;
+    [[location(3)]] member_1: u32;
+    [[location(4), interpolate(flat)]] member_2: f32;
+    [[location(2)]] member_3: vec4<f32>;
+    [[location(1)]] member_4: vec2<f32>;
+};
+
+[[group(1), binding(1)]]
+var<storage> unnamed: JointMatrixBuffer;
+var<private> i_joint_indices_1: vec4<u32>;
+var<private> i_joint_weights_1: vec4<f32>;
+[[group(1), binding(0)]]
+var<storage> unnamed_1: ObjectOutputDataBuffer;
+var<private> gl_InstanceIndex_1: i32;
+var<private> i_position_1: vec3<f32>;
+var<private> o_position: vec4<f32>;
+var<private> perVertexStruct: gl_PerVertex = gl_PerVertex(vec4<f32>(0.0, 0.0, 0.0, 1.0), );
+var<private> o_material: u32;
+var<private> o_fade: f32;
+var<private> o_color: vec4<f32>;
+var<private> i_color_1: vec4<f32>;
+var<private> o_coords0_: vec2<f32>;
+var<private> i_coords0_1: vec2<f32>;
+var<private> i_normal_1: vec3<f32>;
+var<private> i_tangent_1: vec3<f32>;
+var<private> i_coords1_1: vec2<f32>;
+
+fn main_1() {
+    var phi_332_: mat4x4<f32>;
+
+    let _e36 = gl_InstanceIndex_1;
+    let _e40 = unnamed_1.object_output[_e36].model_view_proj;
+    let _e42 = unnamed_1.object_output[_e36].material_idx;
+    let _e44 = unnamed_1.object_output[_e36].joint_offset;
+    let _e46 = unnamed_1.object_output[_e36].fade;
+    let _e47 = i_position_1;
+    switch(bitcast<i32>(0u)) {
+        default: {
+            if ((_e44 == 4294967295u)) {
+                phi_332_ = mat4x4<f32>(vec4<f32>(1.0, 0.0, 0.0, 0.0), vec4<f32>(0.0, 1.0, 0.0, 0.0), vec4<f32>(0.0, 0.0, 1.0, 0.0), vec4<f32>(0.0, 0.0, 0.0, 1.0));
+                break;
+            }
+            let _e55 = i_joint_indices_1[0u];
+            let _e59 = unnamed.joint_matrices[(_e44 + _e55)];
+            let _e61 = i_joint_weights_1[0u];
+            let _e62 = (_e59 * _e61);
+            let _e64 = i_joint_indices_1[1u];
+            let _e68 = unnamed.joint_matrices[(_e44 + _e64)];
+            let _e70 = i_joint_weights_1[1u];
+            let _e71 = (_e68 * _e70);
+            let _e85 = i_joint_indices_1[2u];
+            let _e89 = unnamed.joint_matrices[(_e44 + _e85)];
+            let _e91 = i_joint_weights_1[2u];
+            let _e92 = (_e89 * _e91);
+            let _e102 = i_joint_indices_1[3u];
+            let _e106 = unnamed.joint_matrices[(_e44 + _e102)];
+            let _e108 = i_joint_weights_1[3u];
+            let _e109 = (_e106 * _e108);
+            phi_332_ = mat4x4<f32>((((_e62[0] + _e71[0]) + _e92[0]) + _e109[0]), (((_e62[1] + _e71[1]) + _e92[1]) + _e109[1]), (((_e62[2] + _e71[2]) + _e92[2]) + _e109[2]), (((_e62[3] + _e71[3]) + _e92[3]) + _e109[3]));
+            break;
+        }
+    }
+    let _e120 = phi_332_;
+    let _e122 = (_e40 * (_e120 * vec4<f32>(_e47.x, _e47.y, _e47.z, 1.0)));
+    o_position = _e122;
+    perVertexStruct.gl_Position = _e122;
+    o_material = _e42;
+    o_fade = _e46;
+    let _e124 = i_color_1;
+    o_color = _e124;
+    let _e125 = i_coords0_1;
+    o_coords0_ = _e125;
+    return;
+}
+
+[[stage(vertex)]]
+fn main([[location(6)]] i_joint_indices: vec4<u32>, [[location(7)]] i_joint_weights: vec4<f32>, [[builtin(instance_index)]] gl_InstanceIndex: u32, [[location(0)]] i_position: vec3<f32>, [[location(5)]] i_color: vec4<f32>, [[location(3)]] i_coords0_: vec2<f32>, [[location(1)]] i_normal: vec3<f32>, [[location(2)]] i_tangent: vec3<f32>, [[location(4)]] i_coords1_: vec2<f32>) -> VertexOutput {
+    i_joint_indices_1 = i_joint_indices;
+    i_joint_weights_1 = i_joint_weights;
+    gl_InstanceIndex_1 = i32(gl_InstanceIndex);
+    i_position_1 = i_position;
+    i_color_1 = i_color;
+    i_coords0_1 = i_coords0_;
+    i_normal_1 = i_normal;
+    i_tangent_1 = i_tangent;
+    i_coords1_1 = i_coords1_;
+    main_1();
+    let _e26 = o_position;
+    let _e27 = perVertexStruct.gl_Position;
+    let _e28 = o_material;
+    let _e29 = o_fade;
+    let _e30 = o_color;
+    let _e31 = o_coords0_;
+    return VertexOutput(_e26, _e27, _e28, _e29, _e30, _e31);
+}
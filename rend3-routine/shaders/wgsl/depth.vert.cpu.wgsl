@@ -2,7 +2,10 @@ struct ObjectOutputData {
     model_view: mat4x4<f32>;
     model_view_proj: mat4x4<f32>;
     material_idx: u32;
+    texture_layer: u32;
+    joint_offset: u32;
     inv_squared_scale: vec3<f32>;
+    fade: f32;
 };
 
 struct ObjectOutputDataBuffer {
@@ -17,17 +20,19 @@ struct VertexOutput {
     [[location(0)]] member: vec4<f32>;
     [[builtin(position)]] gl_Position: vec4<f32>;
     [[location(3)]] member_1: u32;
-    [[location(2)]] member_2: vec4<f32>;
-    [[location(1)]] member_3: vec2<f32>;
+    [[location(4), interpolate(flat)]] member_2: f32;
+    [[location(2)]] member_3: vec4<f32>;
+    [[location(1)]] member_4: vec2<f32>;
 };
 
-var<private> gl_InstanceIndex_1: i32;
 [[group(1), binding(0)]]
 var<storage> unnamed: ObjectOutputDataBuffer;
+var<private> gl_InstanceIndex_1: i32;
 var<private> i_position_1: vec3<f32>;
 var<private> o_position: vec4<f32>;
 var<private> perVertexStruct: gl_PerVertex = gl_PerVertex(vec4<f32>(0.0, 0.0, 0.0, 1.0), );
 var<private> o_material: u32;
+var<private> o_fade: f32;
 var<private> o_color: vec4<f32>;
 var<private> i_color_1: vec4<f32>;
 var<private> o_coords0_: vec2<f32>;
@@ -37,18 +42,20 @@ var<private> i_tangent_1: vec3<f32>;
 var<private> i_coords1_1: vec2<f32>;
 
 fn main_1() {
-    let _e21 = gl_InstanceIndex_1;
-    let _e26 = unnamed.object_output[bitcast<u32>(_e21)].model_view_proj;
-    let _e28 = unnamed.object_output[bitcast<u32>(_e21)].material_idx;
-    let _e29 = i_position_1;
-    let _e34 = (_e26 * vec4<f32>(_e29.x, _e29.y, _e29.z, 1.0));
-    o_position = _e34;
-    perVertexStruct.gl_Position = _e34;
-    o_material = _e28;
-    let _e36 = i_color_1;
-    o_color = _e36;
-    let _e37 = i_coords0_1;
-    o_coords0_ = _e37;
+    let _e23 = gl_InstanceIndex_1;
+    let _e27 = unnamed.object_output[_e23].model_view_proj;
+    let _e29 = unnamed.object_output[_e23].material_idx;
+    let _e31 = unnamed.object_output[_e23].fade;
+    let _e32 = i_position_1;
+    let _e37 = (_e27 * vec4<f32>(_e32.x, _e32.y, _e32.z, 1.0));
+    o_position = _e37;
+    perVertexStruct.gl_Position = _e37;
+    o_material = _e29;
+    o_fade = _e31;
+    let _e39 = i_color_1;
+    o_color = _e39;
+    let _e40 = i_coords0_1;
+    o_coords0_ = _e40;
     return;
 }
 
@@ -62,10 +69,11 @@ fn main([[builtin(instance_index)]] gl_InstanceIndex: u32, [[location(0)]] i_pos
     i_tangent_1 = i_tangent;
     i_coords1_1 = i_coords1_;
     main_1();
-    let _e21 = o_position;
-    let _e22 = perVertexStruct.gl_Position;
-    let _e23 = o_material;
-    let _e24 = o_color;
-    let _e25 = o_coords0_;
-    return VertexOutput(_e21, _e22, _e23, _e24, _e25);
+    let _e22 = o_position;
+    let _e23 = perVertexStruct.gl_Position;
+    let _e24 = o_material;
+    let _e25 = o_fade;
+    let _e26 = o_color;
+    let _e27 = o_coords0_;
+    return VertexOutput(_e22, _e23, _e24, _e25, _e26, _e27);
 }
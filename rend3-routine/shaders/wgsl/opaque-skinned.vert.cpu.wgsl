@@ -0,0 +1,211 @@
+struct JointMatrixBuffer {
+    joint_matrices: [[stride(64)]] array<mat4x4<f32>>;
+};
+
+struct ObjectOutputData {
+    model_view: mat4x4<f32>;
+    model_view_proj: mat4x4<f32>;
+    material_idx: u32;
+    texture_layer: u32;
+    joint_offset: u32;
+    inv_squared_scale: vec3<f32>;
+    fade: f32;
+};
+
+struct ObjectOutputDataBuffer {
+    object_output: [[stride(160)]] array<ObjectOutputData>;
+};
+
+struct Plane {
+    inner: vec4<f32>;
+};
+
+struct Frustum {
+    left: Plane;
+    right: Plane;
+    top: Plane;
+    bottom: Plane;
+    near: Plane;
+};
+
+struct UniformData {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    origin_view_proj: mat4x4<f32>;
+    inv_view: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_origin_view_proj: mat4x4<f32>;
+    frustum: Frustum;
+    ambient: vec4<f32>;
+    resolution: vec2<u32>;
+    clip_plane_count: u32;
+    vertex_quantization: u32;
+    clip_planes: [[stride(16)]] array<vec4<f32>,4u>;
+};
+
+struct UniformBuffer {
+    uniforms: UniformData;
+};
+
+struct gl_PerVertex {
+    [[builtin(position)]] gl_Position: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[location(6)]] member: u32;
+    [[location(7)]] member_1: u32;
+    [[location(8), interpolate(flat)]] member_2: f32;
+    [[location(0)]] member_3: vec4<f32>;
+    [[location(1)]] member_4: vec3<f32>;
+    [[location(2)]] member_5: vec3<f32>;
+    [[location(5)]] member_6: vec4<f32>;
+    [[location(3)]] member_7: vec2<f32>;
+    [[location(4)]] member_8: vec2<f32>;
+    [[builtin(position)]] gl_Position: vec4<f32>;
+};
+
+[[group(1), binding(1)]]
+var<storage> unnamed: JointMatrixBuffer;
+var<private> i_joint_indices_1: vec4<u32>;
+var<private> i_joint_weights_1: vec4<f32>;
+[[group(1), binding(0)]]
+var<storage> unnamed_1: ObjectOutputDataBuffer;
+var<private> gl_InstanceIndex_1: i32;
+var<private> o_material: u32;
+var<private> o_texture_layer: u32;
+var<private> o_fade: f32;
+var<private> i_position_1: vec3<f32>;
+[[group(0), binding(3)]]
+var<uniform> unnamed_2: UniformBuffer;
+var<private> i_normal_1: vec3<f32>;
+var<private> i_tangent_1: vec3<f32>;
+var<private> o_view_position: vec4<f32>;
+var<private> o_normal: vec3<f32>;
+var<private> o_tangent: vec3<f32>;
+var<private> o_color: vec4<f32>;
+var<private> i_color_1: vec4<f32>;
+var<private> o_coords0_: vec2<f32>;
+var<private> i_coords0_1: vec2<f32>;
+var<private> o_coords1_: vec2<f32>;
+var<private> i_coords1_1: vec2<f32>;
+var<private> perVertexStruct: gl_PerVertex = gl_PerVertex(vec4<f32>(0.0, 0.0, 0.0, 1.0), );
+
+fn main_1() {
+    var phi_851_: vec3<f32>;
+    var local: bool;
+    var phi_852_: vec3<f32>;
+    var phi_853_: mat4x4<f32>;
+
+    let _e45 = gl_InstanceIndex_1;
+    let _e48 = unnamed_1.object_output[_e45];
+    o_material = _e48.material_idx;
+    o_texture_layer = _e48.texture_layer;
+    o_fade = _e48.fade;
+    let _e56 = i_position_1;
+    let _e63 = unnamed_2.uniforms.vertex_quantization;
+    let _e64 = i_normal_1;
+    switch(bitcast<i32>(0u)) {
+        default: {
+            let _e67 = ((_e63 & 1u) == 0u);
+            local = _e67;
+            if (_e67) {
+                phi_851_ = _e64;
+                break;
+            }
+            let _e73 = ((1.0 - abs(_e64.x)) - abs(_e64.y));
+            let _e74 = vec3<f32>(_e64.x, _e64.y, _e73);
+            let _e77 = _e74.xy;
+            let _e81 = (_e77 - (select(vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), (_e77 >= vec2<f32>(0.0, 0.0))) * max(-(_e73), 0.0)));
+            phi_851_ = vec3<f32>(_e81.x, _e81.y, _e74.z);
+            break;
+        }
+    }
+    let _e87 = phi_851_;
+    let _e88 = i_tangent_1;
+    switch(bitcast<i32>(0u)) {
+        default: {
+            let _e91 = local;
+            if (_e91) {
+                phi_852_ = _e88;
+                break;
+            }
+            let _e97 = ((1.0 - abs(_e88.x)) - abs(_e88.y));
+            let _e98 = vec3<f32>(_e88.x, _e88.y, _e97);
+            let _e101 = _e98.xy;
+            let _e105 = (_e101 - (select(vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), (_e101 >= vec2<f32>(0.0, 0.0))) * max(-(_e97), 0.0)));
+            phi_852_ = vec3<f32>(_e105.x, _e105.y, _e98.z);
+            break;
+        }
+    }
+    let _e111 = phi_852_;
+    switch(bitcast<i32>(0u)) {
+        default: {
+            if ((_e48.joint_offset == 4294967295u)) {
+                phi_853_ = mat4x4<f32>(vec4<f32>(1.0, 0.0, 0.0, 0.0), vec4<f32>(0.0, 1.0, 0.0, 0.0), vec4<f32>(0.0, 0.0, 1.0, 0.0), vec4<f32>(0.0, 0.0, 0.0, 1.0));
+                break;
+            }
+            let _e115 = i_joint_indices_1[0u];
+            let _e119 = unnamed.joint_matrices[(_e48.joint_offset + _e115)];
+            let _e121 = i_joint_weights_1[0u];
+            let _e122 = (_e119 * _e121);
+            let _e124 = i_joint_indices_1[1u];
+            let _e128 = unnamed.joint_matrices[(_e48.joint_offset + _e124)];
+            let _e130 = i_joint_weights_1[1u];
+            let _e131 = (_e128 * _e130);
+            let _e145 = i_joint_indices_1[2u];
+            let _e149 = unnamed.joint_matrices[(_e48.joint_offset + _e145)];
+            let _e151 = i_joint_weights_1[2u];
+            let _e152 = (_e149 * _e151);
+            let _e162 = i_joint_indices_1[3u];
+            let _e166 = unnamed.joint_matrices[(_e48.joint_offset + _e162)];
+            let _e168 = i_joint_weights_1[3u];
+            let _e169 = (_e166 * _e168);
+            phi_853_ = mat4x4<f32>((((_e122[0] + _e131[0]) + _e152[0]) + _e169[0]), (((_e122[1] + _e131[1]) + _e152[1]) + _e169[1]), (((_e122[2] + _e131[2]) + _e152[2]) + _e169[2]), (((_e122[3] + _e131[3]) + _e152[3]) + _e169[3]));
+            break;
+        }
+    }
+    let _e180 = phi_853_;
+    let _e181 = (_e180 * vec4<f32>(_e56.x, _e56.y, _e56.z, 1.0));
+    let _e183 = _e180[0].xyz;
+    let _e185 = _e180[1].xyz;
+    let _e187 = _e180[2].xyz;
+    let _e188 = mat3x3<f32>(_e183, _e185, _e187);
+    let _e192 = vec3<f32>(dot(_e183, _e183), dot(_e185, _e185), dot(_e187, _e187));
+    o_view_position = (_e48.model_view * _e181);
+    let _e204 = mat3x3<f32>(_e48.model_view[0].xyz, _e48.model_view[1].xyz, _e48.model_view[2].xyz);
+    o_normal = normalize((_e204 * (_e48.inv_squared_scale * (_e188 * (_e87 / _e192)))));
+    o_tangent = normalize((_e204 * (_e48.inv_squared_scale * (_e188 * (_e111 / _e192)))));
+    let _e211 = i_color_1;
+    o_color = _e211;
+    let _e212 = i_coords0_1;
+    o_coords0_ = _e212;
+    let _e213 = i_coords1_1;
+    o_coords1_ = _e213;
+    perVertexStruct.gl_Position = (_e48.model_view_proj * _e181);
+    return;
+}
+
+[[stage(vertex)]]
+fn main([[location(6)]] i_joint_indices: vec4<u32>, [[location(7)]] i_joint_weights: vec4<f32>, [[builtin(instance_index)]] gl_InstanceIndex: u32, [[location(0)]] i_position: vec3<f32>, [[location(1)]] i_normal: vec3<f32>, [[location(2)]] i_tangent: vec3<f32>, [[location(5)]] i_color: vec4<f32>, [[location(3)]] i_coords0_: vec2<f32>, [[location(4)]] i_coords1_: vec2<f32>) -> VertexOutput {
+    i_joint_indices_1 = i_joint_indices;
+    i_joint_weights_1 = i_joint_weights;
+    gl_InstanceIndex_1 = i32(gl_InstanceIndex);
+    i_position_1 = i_position;
+    i_normal_1 = i_normal;
+    i_tangent_1 = i_tangent;
+    i_color_1 = i_color;
+    i_coords0_1 = i_coords0_;
+    i_coords1_1 = i_coords1_;
+    main_1();
+    let _e30 = o_material;
+    let _e31 = o_texture_layer;
+    let _e32 = o_fade;
+    let _e33 = o_view_position;
+    let _e34 = o_normal;
+    let _e35 = o_tangent;
+    let _e36 = o_color;
+    let _e37 = o_coords0_;
+    let _e38 = o_coords1_;
+    let _e39 = perVertexStruct.gl_Position;
+    return VertexOutput(_e30, _e31, _e32, _e33, _e34, _e35, _e36, _e37, _e38, _e39);
+}
@@ -20,6 +20,9 @@ struct UniformData {
     frustum: Frustum;
     ambient: vec4<f32>;
     resolution: vec2<u32>;
+    clip_plane_count: u32;
+    vertex_quantization: u32;
+    clip_planes: [[stride(16)]] array<vec4<f32>,4u>;
 };
 
 struct UniformBuffer {
@@ -40,6 +43,15 @@ struct CPUMaterialData {
     ambient_occlusion: f32;
     alpha_cutout: f32;
     material_flags: u32;
+    parallax_scale: f32;
+    parallax_steps: u32;
+    subsurface_profile: u32;
+    anisotropy_rotation: f32;
+    detail_transform: vec4<f32>;
+    hair: vec4<f32>;
+    sheen: vec4<f32>;
+    transmission: vec4<f32>;
+    attenuation: vec4<f32>;
     texture_enable: u32;
 };
 
@@ -64,14 +76,22 @@ struct DirectionalLightBuffer {
     directional_lights: [[stride(112)]] array<DirectionalLight>;
 };
 
+var<private> gl_FrontFacing_1: bool;
+var<private> i_normal_1: vec3<f32>;
+var<private> i_tangent_1: vec3<f32>;
+var<private> i_view_position_1: vec4<f32>;
+[[group(2), binding(11)]]
+var height_tex: texture_2d<f32>;
 var<private> i_coords0_1: vec2<f32>;
 [[group(2), binding(1)]]
 var albedo_tex: texture_2d<f32>;
 var<private> i_color_1: vec4<f32>;
-var<private> i_normal_1: vec3<f32>;
+[[group(2), binding(12)]]
+var detail_albedo_tex: texture_2d<f32>;
 [[group(2), binding(2)]]
 var normal_tex: texture_2d<f32>;
-var<private> i_tangent_1: vec3<f32>;
+[[group(2), binding(13)]]
+var detail_normal_tex: texture_2d<f32>;
 [[group(2), binding(3)]]
 var roughness_tex: texture_2d<f32>;
 [[group(2), binding(10)]]
@@ -86,14 +106,17 @@ var clear_coat_tex: texture_2d<f32>;
 var clear_coat_roughness_tex: texture_2d<f32>;
 [[group(2), binding(8)]]
 var emissive_tex: texture_2d<f32>;
+[[group(2), binding(9)]]
+var anisotropy_tex: texture_2d<f32>;
 [[group(0), binding(0)]]
 var primary_sampler: sampler;
 [[group(0), binding(3)]]
 var<uniform> unnamed: UniformBuffer;
 [[group(2), binding(0)]]
 var<storage> unnamed_1: TextureData;
+var<private> i_fade_1: f32;
+var<private> gl_FragCoord_1: vec4<f32>;
 var<private> o_color: vec4<f32>;
-var<private> i_view_position_1: vec4<f32>;
 [[group(0), binding(4)]]
 var<storage> unnamed_2: DirectionalLightBuffer;
 [[group(0), binding(5)]]
@@ -104,446 +127,845 @@ var<private> i_coords1_1: vec2<f32>;
 var<private> i_material_1: u32;
 
 fn main_1() {
-    var phi_2521_: vec4<f32>;
-    var phi_2519_: vec4<f32>;
-    var phi_2523_: vec4<f32>;
-    var phi_2522_: vec4<f32>;
-    var phi_2524_: vec2<f32>;
-    var phi_2525_: vec3<f32>;
-    var phi_2526_: vec3<f32>;
-    var phi_2527_: vec3<f32>;
-    var phi_2659_: f32;
-    var phi_2597_: f32;
-    var phi_2547_: f32;
-    var phi_1660_: bool;
-    var phi_2528_: vec2<f32>;
-    var phi_2600_: f32;
-    var phi_2550_: f32;
-    var phi_2661_: f32;
-    var phi_2613_: f32;
-    var phi_2563_: f32;
-    var phi_2668_: f32;
-    var phi_2662_: f32;
-    var phi_2601_: f32;
-    var phi_2551_: f32;
-    var phi_2660_: f32;
-    var phi_2598_: f32;
-    var phi_2548_: f32;
-    var phi_2658_: f32;
-    var phi_2596_: f32;
-    var phi_2546_: f32;
-    var phi_2564_: f32;
-    var phi_2621_: f32;
-    var phi_2566_: f32;
-    var phi_2569_: f32;
-    var phi_2623_: f32;
-    var phi_2592_: f32;
-    var phi_2645_: f32;
-    var phi_2624_: f32;
-    var phi_2570_: f32;
-    var phi_2622_: f32;
-    var phi_2567_: f32;
-    var phi_2620_: f32;
-    var phi_2565_: f32;
-    var phi_2646_: f32;
-    var phi_2744_: vec3<f32>;
-    var phi_2807_: vec3<f32>;
-    var phi_2800_: f32;
-    var phi_2776_: vec3<f32>;
-    var phi_2748_: vec3<f32>;
-    var phi_2737_: vec3<f32>;
-    var phi_2647_: f32;
-    var phi_2836_: vec3<f32>;
-    var phi_2835_: u32;
-    var phi_1305_: bool;
-    var phi_1312_: bool;
-    var phi_1319_: bool;
-    var phi_1327_: bool;
-    var phi_1334_: bool;
-    var phi_2843_: f32;
-    var local: vec3<f32>;
-    var local_1: vec3<f32>;
-    var local_2: vec3<f32>;
-    var local_3: vec3<f32>;
+    var local: array<vec4<f32>,4u>;
+    var local_1: array<f32,16u>;
+    var phi_3395_: bool;
+    var phi_4529_: vec3<f32>;
+    var phi_4536_: vec2<f32>;
+    var phi_4535_: vec2<f32>;
+    var phi_4534_: f32;
+    var phi_4533_: f32;
+    var phi_4532_: f32;
+    var phi_4531_: f32;
+    var phi_4530_: u32;
+    var local_2: f32;
+    var local_3: f32;
+    var local_4: f32;
+    var local_5: f32;
+    var local_6: f32;
+    var local_7: f32;
+    var local_8: f32;
+    var local_9: vec2<f32>;
+    var local_10: vec2<f32>;
+    var phi_4537_: vec2<f32>;
+    var phi_4538_: vec2<f32>;
+    var phi_4541_: vec4<f32>;
+    var phi_4539_: vec4<f32>;
+    var phi_4543_: vec4<f32>;
+    var phi_4542_: vec4<f32>;
+    var phi_4591_: vec4<f32>;
+    var phi_3452_: bool;
+    var phi_4777_: vec3<f32>;
+    var phi_2576_: bool;
+    var phi_4551_: vec2<f32>;
+    var phi_4552_: vec3<f32>;
+    var phi_4554_: vec3<f32>;
+    var phi_4553_: vec3<f32>;
+    var phi_4558_: vec3<f32>;
+    var phi_3473_: bool;
+    var phi_4555_: vec3<f32>;
+    var phi_3494_: bool;
+    var phi_4544_: vec3<f32>;
+    var phi_4559_: vec3<f32>;
+    var phi_4800_: f32;
+    var phi_4687_: f32;
+    var phi_4608_: f32;
+    var phi_2776_: bool;
+    var phi_4573_: vec2<f32>;
+    var phi_4690_: f32;
+    var phi_4611_: f32;
+    var phi_4802_: f32;
+    var phi_4716_: f32;
+    var phi_4637_: f32;
+    var phi_4816_: f32;
+    var phi_4803_: f32;
+    var phi_4691_: f32;
+    var phi_4612_: f32;
+    var phi_4801_: f32;
+    var phi_4688_: f32;
+    var phi_4609_: f32;
+    var phi_4799_: f32;
+    var phi_4686_: f32;
+    var phi_4607_: f32;
+    var phi_4638_: f32;
+    var phi_4724_: f32;
+    var phi_4643_: f32;
+    var phi_4646_: f32;
+    var phi_4726_: f32;
+    var phi_4682_: f32;
+    var phi_4761_: f32;
+    var phi_4727_: f32;
+    var phi_4647_: f32;
+    var phi_4725_: f32;
+    var phi_4644_: f32;
+    var phi_4723_: f32;
+    var phi_4642_: f32;
+    var phi_4762_: f32;
+    var phi_4965_: vec3<f32>;
+    var phi_4776_: vec2<f32>;
+    var phi_4853_: f32;
+    var phi_4775_: vec2<f32>;
+    var phi_3548_: bool;
+    var phi_4772_: vec3<f32>;
+    var phi_5064_: vec3<f32>;
+    var phi_5046_: f32;
+    var phi_5019_: vec3<f32>;
+    var phi_4975_: vec3<f32>;
+    var phi_4949_: vec3<f32>;
+    var phi_4838_: f32;
+    var phi_4824_: vec3<f32>;
+    var phi_4778_: f32;
+    var phi_5108_: u32;
+    var phi_5112_: bool;
+    var phi_5109_: bool;
+    var phi_5111_: bool;
+    var phi_5165_: bool;
+    var phi_2168_: bool;
+    var phi_5167_: vec3<f32>;
+    var phi_5166_: u32;
+    var phi_2265_: bool;
+    var phi_2272_: bool;
+    var phi_2279_: bool;
+    var phi_2286_: bool;
+    var phi_2293_: bool;
+    var phi_5174_: f32;
+    var phi_5176_: f32;
+    var phi_5175_: f32;
+    var phi_5177_: vec3<f32>;
+    var phi_5178_: vec3<f32>;
+    var local_11: vec3<f32>;
+    var local_12: vec3<f32>;
+    var local_13: vec3<f32>;
+    var local_14: vec2<f32>;
+    var local_15: vec3<f32>;
 
-    let _e100 = unnamed_1.material.uv_transform0_;
-    let _e102 = unnamed_1.material.albedo;
-    let _e104 = unnamed_1.material.emissive;
-    let _e106 = unnamed_1.material.roughness;
-    let _e108 = unnamed_1.material.metallic;
-    let _e110 = unnamed_1.material.reflectance;
-    let _e112 = unnamed_1.material.clear_coat;
-    let _e114 = unnamed_1.material.clear_coat_roughness;
-    let _e116 = unnamed_1.material.ambient_occlusion;
-    let _e118 = unnamed_1.material.material_flags;
-    let _e120 = unnamed_1.material.texture_enable;
-    let _e121 = i_coords0_1;
-    let _e125 = (_e100 * vec3<f32>(_e121.x, _e121.y, 1.0));
-    let _e128 = vec2<f32>(_e125.x, _e125.y);
-    let _e129 = dpdx(_e128);
-    let _e130 = dpdy(_e128);
-    if (((_e118 & 1u) != 0u)) {
-        if ((((_e120 >> bitcast<u32>(0)) & 1u) != 0u)) {
-            let _e137 = textureSampleGrad(albedo_tex, primary_sampler, _e128, _e129, _e130);
-            phi_2521_ = _e137;
+    let _e149 = unnamed_1.material.uv_transform0_;
+    let _e151 = unnamed_1.material.albedo;
+    let _e153 = unnamed_1.material.emissive;
+    let _e155 = unnamed_1.material.roughness;
+    let _e157 = unnamed_1.material.metallic;
+    let _e159 = unnamed_1.material.reflectance;
+    let _e161 = unnamed_1.material.clear_coat;
+    let _e163 = unnamed_1.material.clear_coat_roughness;
+    let _e165 = unnamed_1.material.anisotropy;
+    let _e167 = unnamed_1.material.ambient_occlusion;
+    let _e169 = unnamed_1.material.material_flags;
+    let _e171 = unnamed_1.material.parallax_scale;
+    let _e173 = unnamed_1.material.parallax_steps;
+    let _e175 = unnamed_1.material.anisotropy_rotation;
+    let _e177 = unnamed_1.material.detail_transform;
+    let _e179 = unnamed_1.material.hair;
+    let _e181 = unnamed_1.material.sheen;
+    let _e183 = unnamed_1.material.texture_enable;
+    let _e184 = i_coords0_1;
+    let _e188 = (_e149 * vec3<f32>(_e184.x, _e184.y, 1.0));
+    let _e191 = vec2<f32>(_e188.x, _e188.y);
+    let _e192 = dpdx(_e191);
+    let _e193 = dpdy(_e191);
+    phi_4538_ = _e191;
+    if ((((_e183 >> bitcast<u32>(10)) & 1u) != 0u)) {
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e200 = ((_e169 & 32768u) != 0u);
+                phi_3395_ = _e200;
+                if (_e200) {
+                    let _e201 = gl_FrontFacing_1;
+                    phi_3395_ = !(_e201);
+                }
+                let _e204 = phi_3395_;
+                if (_e204) {
+                    let _e205 = i_normal_1;
+                    phi_4529_ = -(_e205);
+                    break;
+                }
+                let _e207 = i_normal_1;
+                phi_4529_ = _e207;
+                break;
+            }
+        }
+        let _e209 = phi_4529_;
+        let _e210 = normalize(_e209);
+        let _e211 = i_tangent_1;
+        let _e212 = normalize(_e211);
+        let _e214 = i_view_position_1;
+        let _e217 = -(normalize(_e214.xyz));
+        let _e220 = dot(_e217, _e210);
+        let _e222 = max(_e173, 1u);
+        let _e224 = (1.0 / f32(_e222));
+        let _e231 = textureSampleGrad(height_tex, primary_sampler, _e191, _e192, _e193);
+        let _e233 = (1.0 - _e231.x);
+        phi_4536_ = _e191;
+        phi_4535_ = _e191;
+        phi_4534_ = 0.0;
+        phi_4533_ = _e233;
+        phi_4532_ = _e233;
+        phi_4531_ = 0.0;
+        phi_4530_ = 0u;
+        loop {
+            let _e235 = phi_4536_;
+            let _e237 = phi_4535_;
+            let _e239 = phi_4534_;
+            let _e241 = phi_4533_;
+            let _e243 = phi_4532_;
+            let _e245 = phi_4531_;
+            let _e247 = phi_4530_;
+            local_2 = _e245;
+            local_3 = _e245;
+            local_4 = _e243;
+            local_5 = _e243;
+            local_6 = _e245;
+            local_7 = _e241;
+            local_8 = _e239;
+            local_9 = _e237;
+            local_10 = _e235;
+            local_14 = _e237;
+            if (((_e247 < _e222) && (_e245 < _e243))) {
+                continue;
+            } else {
+                break;
+            }
+            continuing {
+                let _e251 = (_e237 - (((vec3<f32>(dot(_e217, _e212), dot(_e217, cross(_e210, _e212)), _e220).xy / vec2<f32>(max(_e220, 0.05000000074505806))) * _e171) * _e224));
+                let _e253 = textureSampleGrad(height_tex, primary_sampler, _e251, _e192, _e193);
+                phi_4536_ = _e237;
+                phi_4535_ = _e251;
+                phi_4534_ = _e245;
+                phi_4533_ = _e243;
+                phi_4532_ = (1.0 - _e253.x);
+                phi_4531_ = (_e245 + _e224);
+                phi_4530_ = (_e247 + bitcast<u32>(1));
+            }
+        }
+        let _e261 = local_2;
+        let _e265 = local_3;
+        let _e267 = local_4;
+        let _e1138 = local_14;
+        phi_4537_ = _e1138;
+        if (((((_e169 & 65536u) != 0u) && (_e261 > 0.0)) && (_e265 >= _e267))) {
+            let _e271 = local_5;
+            let _e273 = local_6;
+            let _e274 = (_e271 - _e273);
+            let _e276 = local_7;
+            let _e278 = local_8;
+            let _e284 = local_9;
+            let _e286 = local_10;
+            phi_4537_ = mix(_e284, _e286, vec2<f32>((_e274 / (_e274 - (_e276 - _e278)))));
+        }
+        let _e289 = phi_4537_;
+        phi_4538_ = _e289;
+    }
+    let _e291 = phi_4538_;
+    let _e292 = _e177.xy;
+    let _e295 = ((_e291 * _e292) + _e177.zw);
+    let _e296 = (_e192 * _e292);
+    let _e297 = (_e193 * _e292);
+    if (((_e169 & 1u) != 0u)) {
+        if ((((_e183 >> bitcast<u32>(0)) & 1u) != 0u)) {
+            let _e304 = textureSampleGrad(albedo_tex, primary_sampler, _e291, _e192, _e193);
+            phi_4541_ = _e304;
         } else {
-            phi_2521_ = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+            phi_4541_ = vec4<f32>(1.0, 1.0, 1.0, 1.0);
         }
-        let _e139 = phi_2521_;
-        phi_2523_ = _e139;
-        if (((_e118 & 2u) != 0u)) {
-            let _e142 = i_color_1;
-            phi_2519_ = _e142;
-            if (((_e118 & 4u) != 0u)) {
-                let _e145 = _e142.xyz;
-                let _e153 = mix((_e145 * vec3<f32>(0.07739938050508499, 0.07739938050508499, 0.07739938050508499)), pow(((_e145 + vec3<f32>(0.054999999701976776, 0.054999999701976776, 0.054999999701976776)) * vec3<f32>(0.9478673338890076, 0.9478673338890076, 0.9478673338890076)), vec3<f32>(2.4000000953674316, 2.4000000953674316, 2.4000000953674316)), clamp(ceil((_e145 - vec3<f32>(0.040449999272823334, 0.040449999272823334, 0.040449999272823334))), vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0)));
-                phi_2519_ = vec4<f32>(_e153.x, _e153.y, _e153.z, _e142.w);
+        let _e306 = phi_4541_;
+        phi_4543_ = _e306;
+        if (((_e169 & 2u) != 0u)) {
+            let _e309 = i_color_1;
+            phi_4539_ = _e309;
+            if (((_e169 & 4u) != 0u)) {
+                let _e312 = _e309.xyz;
+                let _e320 = mix((_e312 * vec3<f32>(0.07739938050508499, 0.07739938050508499, 0.07739938050508499)), pow(((_e312 + vec3<f32>(0.054999999701976776, 0.054999999701976776, 0.054999999701976776)) * vec3<f32>(0.9478673338890076, 0.9478673338890076, 0.9478673338890076)), vec3<f32>(2.4000000953674316, 2.4000000953674316, 2.4000000953674316)), clamp(ceil((_e312 - vec3<f32>(0.040449999272823334, 0.040449999272823334, 0.040449999272823334))), vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0)));
+                phi_4539_ = vec4<f32>(_e320.x, _e320.y, _e320.z, _e309.w);
             }
-            let _e160 = phi_2519_;
-            phi_2523_ = (_e139 * _e160);
+            let _e327 = phi_4539_;
+            phi_4543_ = (_e306 * _e327);
         }
-        let _e163 = phi_2523_;
-        phi_2522_ = _e163;
+        let _e330 = phi_4543_;
+        phi_4542_ = _e330;
     } else {
-        phi_2522_ = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        phi_4542_ = vec4<f32>(0.0, 0.0, 0.0, 1.0);
     }
-    let _e165 = phi_2522_;
-    let _e166 = (_e165 * _e102);
-    if (((_e118 & 8192u) != 0u)) {
-        let _e169 = i_normal_1;
-        phi_2807_ = vec3<f32>(0.0, 0.0, 0.0);
-        phi_2800_ = 0.0;
-        phi_2776_ = normalize(_e169);
-        phi_2748_ = vec3<f32>(0.0, 0.0, 0.0);
-        phi_2737_ = vec3<f32>(0.0, 0.0, 0.0);
-        phi_2647_ = 0.0;
+    let _e332 = phi_4542_;
+    let _e333 = (_e332 * _e151);
+    phi_4591_ = _e333;
+    if ((((_e183 >> bitcast<u32>(11)) & 1u) != 0u)) {
+        let _e338 = textureSampleGrad(detail_albedo_tex, primary_sampler, _e295, _e296, _e297);
+        let _e342 = (_e333.xyz * (_e338.xyz * 2.0));
+        phi_4591_ = vec4<f32>(_e342.x, _e342.y, _e342.z, _e333.w);
+    }
+    let _e349 = phi_4591_;
+    if (((_e169 & 8192u) != 0u)) {
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e354 = ((_e169 & 32768u) != 0u);
+                phi_3452_ = _e354;
+                if (_e354) {
+                    let _e355 = gl_FrontFacing_1;
+                    phi_3452_ = !(_e355);
+                }
+                let _e358 = phi_3452_;
+                if (_e358) {
+                    let _e359 = i_normal_1;
+                    phi_4777_ = -(_e359);
+                    break;
+                }
+                let _e361 = i_normal_1;
+                phi_4777_ = _e361;
+                break;
+            }
+        }
+        let _e363 = phi_4777_;
+        phi_5064_ = vec3<f32>(0.0, 0.0, 0.0);
+        phi_5046_ = 0.0;
+        phi_5019_ = normalize(_e363);
+        phi_4975_ = vec3<f32>(0.0, 0.0, 0.0);
+        phi_4949_ = vec3<f32>(0.0, 0.0, 0.0);
+        phi_4838_ = 0.0;
+        phi_4824_ = vec3<f32>(0.0, 0.0, 0.0);
+        phi_4778_ = 0.0;
     } else {
-        if ((((_e120 >> bitcast<u32>(1)) & 1u) != 0u)) {
-            let _e175 = textureSampleGrad(normal_tex, primary_sampler, _e128, _e129, _e130);
-            if (((_e118 & 8u) != 0u)) {
-                if (((_e118 & 16u) != 0u)) {
-                    phi_2524_ = _e175.wy;
+        let _e368 = (((_e183 >> bitcast<u32>(1)) & 1u) != 0u);
+        phi_2576_ = _e368;
+        if (!(_e368)) {
+            phi_2576_ = (((_e183 >> bitcast<u32>(12)) & 1u) != 0u);
+        }
+        let _e375 = phi_2576_;
+        if (_e375) {
+            phi_4553_ = vec3<f32>(0.0, 0.0, 1.0);
+            if (_e368) {
+                let _e376 = textureSampleGrad(normal_tex, primary_sampler, _e291, _e192, _e193);
+                if (((_e169 & 8u) != 0u)) {
+                    if (((_e169 & 16u) != 0u)) {
+                        phi_4551_ = _e376.wy;
+                    } else {
+                        phi_4551_ = _e376.xy;
+                    }
+                    let _e384 = phi_4551_;
+                    let _e386 = ((_e384 * 2.0) - vec2<f32>(1.0, 1.0));
+                    phi_4552_ = vec3<f32>(_e386.x, _e386.y, sqrt(((1.0 - (_e386.x * _e386.x)) - (_e386.y * _e386.y))));
                 } else {
-                    phi_2524_ = _e175.xy;
+                    phi_4552_ = normalize(((_e376.xyz * 2.0) - vec3<f32>(1.0, 1.0, 1.0)));
                 }
-                let _e183 = phi_2524_;
-                let _e185 = ((_e183 * 2.0) - vec2<f32>(1.0, 1.0));
-                phi_2525_ = vec3<f32>(_e185.x, _e185.y, sqrt(((1.0 - (_e185.x * _e185.x)) - (_e185.y * _e185.y))));
-            } else {
-                phi_2525_ = normalize(((_e175.xyz * 2.0) - vec3<f32>(1.0, 1.0, 1.0)));
+                let _e400 = phi_4552_;
+                phi_4554_ = _e400;
+                if (((_e169 & 32u) != 0u)) {
+                    phi_4554_ = vec3<f32>(_e400.x, -(_e400.y), _e400.z);
+                }
+                let _e410 = phi_4554_;
+                phi_4553_ = _e410;
             }
-            let _e199 = phi_2525_;
-            phi_2526_ = _e199;
-            if (((_e118 & 32u) != 0u)) {
-                phi_2526_ = vec3<f32>(_e199.x, -(_e199.y), _e199.z);
+            let _e412 = phi_4553_;
+            phi_4558_ = _e412;
+            if ((((_e183 >> bitcast<u32>(12)) & 1u) != 0u)) {
+                let _e417 = textureSampleGrad(detail_normal_tex, primary_sampler, _e295, _e296, _e297);
+                let _e420 = ((_e417.xyz * 2.0) - vec3<f32>(1.0, 1.0, 1.0));
+                let _e423 = (_e412.xy + _e420.xy);
+                phi_4558_ = normalize(vec3<f32>(_e423.x, _e423.y, (_e412.z * _e420.z)));
             }
-            let _e209 = phi_2526_;
-            let _e210 = i_normal_1;
-            let _e211 = normalize(_e210);
-            let _e212 = i_tangent_1;
-            let _e213 = normalize(_e212);
-            phi_2527_ = (mat3x3<f32>(_e213, cross(_e211, _e213), _e211) * _e209);
+            let _e432 = phi_4558_;
+            switch(bitcast<i32>(0u)) {
+                default: {
+                    let _e435 = ((_e169 & 32768u) != 0u);
+                    phi_3473_ = _e435;
+                    if (_e435) {
+                        let _e436 = gl_FrontFacing_1;
+                        phi_3473_ = !(_e436);
+                    }
+                    let _e439 = phi_3473_;
+                    if (_e439) {
+                        let _e440 = i_normal_1;
+                        phi_4555_ = -(_e440);
+                        break;
+                    }
+                    let _e442 = i_normal_1;
+                    phi_4555_ = _e442;
+                    break;
+                }
+            }
+            let _e444 = phi_4555_;
+            let _e445 = normalize(_e444);
+            let _e446 = i_tangent_1;
+            let _e447 = normalize(_e446);
+            phi_4559_ = (mat3x3<f32>(_e447, cross(_e445, _e447), _e445) * _e432);
         } else {
-            let _e217 = i_normal_1;
-            phi_2527_ = _e217;
+            switch(bitcast<i32>(0u)) {
+                default: {
+                    let _e453 = ((_e169 & 32768u) != 0u);
+                    phi_3494_ = _e453;
+                    if (_e453) {
+                        let _e454 = gl_FrontFacing_1;
+                        phi_3494_ = !(_e454);
+                    }
+                    let _e457 = phi_3494_;
+                    if (_e457) {
+                        let _e458 = i_normal_1;
+                        phi_4544_ = -(_e458);
+                        break;
+                    }
+                    let _e460 = i_normal_1;
+                    phi_4544_ = _e460;
+                    break;
+                }
+            }
+            let _e462 = phi_4544_;
+            phi_4559_ = _e462;
         }
-        let _e219 = phi_2527_;
-        if (((_e118 & 64u) != 0u)) {
-            if ((((_e120 >> bitcast<u32>(2)) & 1u) != 0u)) {
-                let _e227 = textureSampleGrad(roughness_tex, primary_sampler, _e128, _e129, _e130);
-                phi_2659_ = (_e116 * _e227.x);
-                phi_2597_ = (_e106 * _e227.y);
-                phi_2547_ = (_e108 * _e227.z);
+        let _e464 = phi_4559_;
+        if (((_e169 & 64u) != 0u)) {
+            if ((((_e183 >> bitcast<u32>(2)) & 1u) != 0u)) {
+                let _e472 = textureSampleGrad(roughness_tex, primary_sampler, _e291, _e192, _e193);
+                phi_4800_ = (_e167 * _e472.x);
+                phi_4687_ = (_e155 * _e472.y);
+                phi_4608_ = (_e157 * _e472.z);
             } else {
-                phi_2659_ = _e116;
-                phi_2597_ = _e106;
-                phi_2547_ = _e108;
+                phi_4800_ = _e167;
+                phi_4687_ = _e155;
+                phi_4608_ = _e157;
             }
-            let _e235 = phi_2659_;
-            let _e237 = phi_2597_;
-            let _e239 = phi_2547_;
-            phi_2658_ = _e235;
-            phi_2596_ = _e237;
-            phi_2546_ = _e239;
+            let _e480 = phi_4800_;
+            let _e482 = phi_4687_;
+            let _e484 = phi_4608_;
+            phi_4799_ = _e480;
+            phi_4686_ = _e482;
+            phi_4607_ = _e484;
         } else {
-            let _e241 = ((_e118 & 128u) != 0u);
-            phi_1660_ = _e241;
-            if (!(_e241)) {
-                phi_1660_ = ((_e118 & 256u) != 0u);
+            let _e486 = ((_e169 & 128u) != 0u);
+            phi_2776_ = _e486;
+            if (!(_e486)) {
+                phi_2776_ = ((_e169 & 256u) != 0u);
             }
-            let _e246 = phi_1660_;
-            if (_e246) {
-                if ((((_e120 >> bitcast<u32>(2)) & 1u) != 0u)) {
-                    let _e251 = textureSampleGrad(roughness_tex, primary_sampler, _e128, _e129, _e130);
-                    if (_e241) {
-                        phi_2528_ = _e251.yz;
+            let _e491 = phi_2776_;
+            if (_e491) {
+                if ((((_e183 >> bitcast<u32>(2)) & 1u) != 0u)) {
+                    let _e496 = textureSampleGrad(roughness_tex, primary_sampler, _e291, _e192, _e193);
+                    if (_e486) {
+                        phi_4573_ = _e496.yz;
                     } else {
-                        phi_2528_ = _e251.xy;
+                        phi_4573_ = _e496.xy;
                     }
-                    let _e255 = phi_2528_;
-                    phi_2600_ = (_e106 * _e255.x);
-                    phi_2550_ = (_e108 * _e255.y);
+                    let _e500 = phi_4573_;
+                    phi_4690_ = (_e155 * _e500.x);
+                    phi_4611_ = (_e157 * _e500.y);
                 } else {
-                    phi_2600_ = _e106;
-                    phi_2550_ = _e108;
+                    phi_4690_ = _e155;
+                    phi_4611_ = _e157;
                 }
-                let _e261 = phi_2600_;
-                let _e263 = phi_2550_;
-                if ((((_e120 >> bitcast<u32>(9)) & 1u) != 0u)) {
-                    let _e268 = textureSampleGrad(ambient_occlusion_tex, primary_sampler, _e128, _e129, _e130);
-                    phi_2661_ = (_e116 * _e268.x);
+                let _e506 = phi_4690_;
+                let _e508 = phi_4611_;
+                if ((((_e183 >> bitcast<u32>(9)) & 1u) != 0u)) {
+                    let _e513 = textureSampleGrad(ambient_occlusion_tex, primary_sampler, _e291, _e192, _e193);
+                    phi_4802_ = (_e167 * _e513.x);
                 } else {
-                    phi_2661_ = _e116;
+                    phi_4802_ = _e167;
                 }
-                let _e272 = phi_2661_;
-                phi_2660_ = _e272;
-                phi_2598_ = _e261;
-                phi_2548_ = _e263;
+                let _e517 = phi_4802_;
+                phi_4801_ = _e517;
+                phi_4688_ = _e506;
+                phi_4609_ = _e508;
             } else {
-                phi_2662_ = 0.0;
-                phi_2601_ = 0.0;
-                phi_2551_ = 0.0;
-                if (((_e118 & 512u) != 0u)) {
-                    if ((((_e120 >> bitcast<u32>(2)) & 1u) != 0u)) {
-                        let _e279 = textureSampleGrad(roughness_tex, primary_sampler, _e128, _e129, _e130);
-                        phi_2613_ = (_e106 * _e279.x);
+                phi_4803_ = 0.0;
+                phi_4691_ = 0.0;
+                phi_4612_ = 0.0;
+                if (((_e169 & 512u) != 0u)) {
+                    if ((((_e183 >> bitcast<u32>(2)) & 1u) != 0u)) {
+                        let _e524 = textureSampleGrad(roughness_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4716_ = (_e155 * _e524.x);
                     } else {
-                        phi_2613_ = _e106;
+                        phi_4716_ = _e155;
                     }
-                    let _e283 = phi_2613_;
-                    if ((((_e120 >> bitcast<u32>(3)) & 1u) != 0u)) {
-                        let _e288 = textureSampleGrad(metallic_tex, primary_sampler, _e128, _e129, _e130);
-                        phi_2563_ = (_e108 * _e288.x);
+                    let _e528 = phi_4716_;
+                    if ((((_e183 >> bitcast<u32>(3)) & 1u) != 0u)) {
+                        let _e533 = textureSampleGrad(metallic_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4637_ = (_e157 * _e533.x);
                     } else {
-                        phi_2563_ = _e108;
+                        phi_4637_ = _e157;
                     }
-                    let _e292 = phi_2563_;
-                    if ((((_e120 >> bitcast<u32>(9)) & 1u) != 0u)) {
-                        let _e297 = textureSampleGrad(ambient_occlusion_tex, primary_sampler, _e128, _e129, _e130);
-                        phi_2668_ = (_e116 * _e297.x);
+                    let _e537 = phi_4637_;
+                    if ((((_e183 >> bitcast<u32>(9)) & 1u) != 0u)) {
+                        let _e542 = textureSampleGrad(ambient_occlusion_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4816_ = (_e167 * _e542.x);
                     } else {
-                        phi_2668_ = _e116;
+                        phi_4816_ = _e167;
                     }
-                    let _e301 = phi_2668_;
-                    phi_2662_ = _e301;
-                    phi_2601_ = _e283;
-                    phi_2551_ = _e292;
+                    let _e546 = phi_4816_;
+                    phi_4803_ = _e546;
+                    phi_4691_ = _e528;
+                    phi_4612_ = _e537;
                 }
-                let _e303 = phi_2662_;
-                let _e305 = phi_2601_;
-                let _e307 = phi_2551_;
-                phi_2660_ = _e303;
-                phi_2598_ = _e305;
-                phi_2548_ = _e307;
+                let _e548 = phi_4803_;
+                let _e550 = phi_4691_;
+                let _e552 = phi_4612_;
+                phi_4801_ = _e548;
+                phi_4688_ = _e550;
+                phi_4609_ = _e552;
             }
-            let _e309 = phi_2660_;
-            let _e311 = phi_2598_;
-            let _e313 = phi_2548_;
-            phi_2658_ = _e309;
-            phi_2596_ = _e311;
-            phi_2546_ = _e313;
+            let _e554 = phi_4801_;
+            let _e556 = phi_4688_;
+            let _e558 = phi_4609_;
+            phi_4799_ = _e554;
+            phi_4686_ = _e556;
+            phi_4607_ = _e558;
         }
-        let _e315 = phi_2658_;
-        let _e317 = phi_2596_;
-        let _e319 = phi_2546_;
-        if ((((_e120 >> bitcast<u32>(4)) & 1u) != 0u)) {
-            let _e324 = textureSampleGrad(reflectance_tex, primary_sampler, _e128, _e129, _e130);
-            phi_2564_ = (_e110 * _e324.x);
+        let _e560 = phi_4799_;
+        let _e562 = phi_4686_;
+        let _e564 = phi_4607_;
+        if ((((_e183 >> bitcast<u32>(4)) & 1u) != 0u)) {
+            let _e569 = textureSampleGrad(reflectance_tex, primary_sampler, _e291, _e192, _e193);
+            phi_4638_ = (_e159 * _e569.x);
         } else {
-            phi_2564_ = _e110;
+            phi_4638_ = _e159;
         }
-        let _e328 = phi_2564_;
-        let _e329 = _e166.xyz;
-        let _e330 = (1.0 - _e319);
-        if (((_e118 & 1024u) != 0u)) {
-            if ((((_e120 >> bitcast<u32>(5)) & 1u) != 0u)) {
-                let _e344 = textureSampleGrad(clear_coat_tex, primary_sampler, _e128, _e129, _e130);
-                phi_2621_ = (_e114 * _e344.y);
-                phi_2566_ = (_e112 * _e344.x);
+        let _e573 = phi_4638_;
+        let _e574 = _e349.xyz;
+        let _e575 = (1.0 - _e564);
+        if (((_e169 & 1024u) != 0u)) {
+            if ((((_e183 >> bitcast<u32>(5)) & 1u) != 0u)) {
+                let _e589 = textureSampleGrad(clear_coat_tex, primary_sampler, _e291, _e192, _e193);
+                phi_4724_ = (_e163 * _e589.y);
+                phi_4643_ = (_e161 * _e589.x);
             } else {
-                phi_2621_ = _e114;
-                phi_2566_ = _e112;
+                phi_4724_ = _e163;
+                phi_4643_ = _e161;
             }
-            let _e350 = phi_2621_;
-            let _e352 = phi_2566_;
-            phi_2620_ = _e350;
-            phi_2565_ = _e352;
+            let _e595 = phi_4724_;
+            let _e597 = phi_4643_;
+            phi_4723_ = _e595;
+            phi_4642_ = _e597;
         } else {
-            if (((_e118 & 2048u) != 0u)) {
-                if ((((_e120 >> bitcast<u32>(5)) & 1u) != 0u)) {
-                    let _e359 = textureSampleGrad(clear_coat_tex, primary_sampler, _e128, _e129, _e130);
-                    phi_2569_ = (_e112 * _e359.x);
+            if (((_e169 & 2048u) != 0u)) {
+                if ((((_e183 >> bitcast<u32>(5)) & 1u) != 0u)) {
+                    let _e604 = textureSampleGrad(clear_coat_tex, primary_sampler, _e291, _e192, _e193);
+                    phi_4646_ = (_e161 * _e604.x);
                 } else {
-                    phi_2569_ = _e112;
+                    phi_4646_ = _e161;
                 }
-                let _e363 = phi_2569_;
-                if ((((_e120 >> bitcast<u32>(6)) & 1u) != 0u)) {
-                    let _e368 = textureSampleGrad(clear_coat_roughness_tex, primary_sampler, _e128, _e129, _e130);
-                    phi_2623_ = (_e114 * _e368.y);
+                let _e608 = phi_4646_;
+                if ((((_e183 >> bitcast<u32>(6)) & 1u) != 0u)) {
+                    let _e613 = textureSampleGrad(clear_coat_roughness_tex, primary_sampler, _e291, _e192, _e193);
+                    phi_4726_ = (_e163 * _e613.y);
                 } else {
-                    phi_2623_ = _e114;
+                    phi_4726_ = _e163;
                 }
-                let _e372 = phi_2623_;
-                phi_2622_ = _e372;
-                phi_2567_ = _e363;
+                let _e617 = phi_4726_;
+                phi_4725_ = _e617;
+                phi_4644_ = _e608;
             } else {
-                phi_2624_ = 0.0;
-                phi_2570_ = 0.0;
-                if (((_e118 & 4096u) != 0u)) {
-                    if ((((_e120 >> bitcast<u32>(5)) & 1u) != 0u)) {
-                        let _e379 = textureSampleGrad(clear_coat_tex, primary_sampler, _e128, _e129, _e130);
-                        phi_2592_ = (_e112 * _e379.x);
+                phi_4727_ = 0.0;
+                phi_4647_ = 0.0;
+                if (((_e169 & 4096u) != 0u)) {
+                    if ((((_e183 >> bitcast<u32>(5)) & 1u) != 0u)) {
+                        let _e624 = textureSampleGrad(clear_coat_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4682_ = (_e161 * _e624.x);
                     } else {
-                        phi_2592_ = _e112;
+                        phi_4682_ = _e161;
                     }
-                    let _e383 = phi_2592_;
-                    if ((((_e120 >> bitcast<u32>(6)) & 1u) != 0u)) {
-                        let _e388 = textureSampleGrad(clear_coat_roughness_tex, primary_sampler, _e128, _e129, _e130);
-                        phi_2645_ = (_e114 * _e388.x);
+                    let _e628 = phi_4682_;
+                    if ((((_e183 >> bitcast<u32>(6)) & 1u) != 0u)) {
+                        let _e633 = textureSampleGrad(clear_coat_roughness_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4761_ = (_e163 * _e633.x);
                     } else {
-                        phi_2645_ = _e114;
+                        phi_4761_ = _e163;
                     }
-                    let _e392 = phi_2645_;
-                    phi_2624_ = _e392;
-                    phi_2570_ = _e383;
+                    let _e637 = phi_4761_;
+                    phi_4727_ = _e637;
+                    phi_4647_ = _e628;
                 }
-                let _e394 = phi_2624_;
-                let _e396 = phi_2570_;
-                phi_2622_ = _e394;
-                phi_2567_ = _e396;
+                let _e639 = phi_4727_;
+                let _e641 = phi_4647_;
+                phi_4725_ = _e639;
+                phi_4644_ = _e641;
             }
-            let _e398 = phi_2622_;
-            let _e400 = phi_2567_;
-            phi_2620_ = _e398;
-            phi_2565_ = _e400;
+            let _e643 = phi_4725_;
+            let _e645 = phi_4644_;
+            phi_4723_ = _e643;
+            phi_4642_ = _e645;
         }
-        let _e402 = phi_2620_;
-        let _e404 = phi_2565_;
-        phi_2646_ = _e317;
-        if ((_e404 != 0.0)) {
-            phi_2646_ = mix(_e317, max(_e317, _e402), _e404);
+        let _e647 = phi_4723_;
+        let _e649 = phi_4642_;
+        phi_4762_ = _e562;
+        if ((_e649 != 0.0)) {
+            phi_4762_ = mix(_e562, max(_e562, _e647), _e649);
         }
-        let _e409 = phi_2646_;
-        if ((((_e120 >> bitcast<u32>(7)) & 1u) != 0u)) {
-            let _e415 = textureSampleGrad(emissive_tex, primary_sampler, _e128, _e129, _e130);
-            phi_2744_ = (_e104 * _e415.xyz);
+        let _e654 = phi_4762_;
+        if ((((_e183 >> bitcast<u32>(7)) & 1u) != 0u)) {
+            let _e660 = textureSampleGrad(emissive_tex, primary_sampler, _e291, _e192, _e193);
+            phi_4965_ = (_e153 * _e660.xyz);
         } else {
-            phi_2744_ = _e104;
+            phi_4965_ = _e153;
+        }
+        let _e664 = phi_4965_;
+        let _e665 = cos(_e175);
+        let _e666 = sin(_e175);
+        let _e667 = vec2<f32>(_e665, _e666);
+        phi_4853_ = _e165;
+        phi_4775_ = _e667;
+        if ((((_e183 >> bitcast<u32>(8)) & 1u) != 0u)) {
+            let _e672 = textureSampleGrad(anisotropy_tex, primary_sampler, _e291, _e192, _e193);
+            let _e675 = ((_e672.xy * 2.0) - vec2<f32>(1.0, 1.0));
+            phi_4776_ = _e667;
+            if ((dot(_e675, _e675) > 0.0)) {
+                phi_4776_ = (mat2x2<f32>(_e667, vec2<f32>(-(_e666), _e665)) * normalize(_e675));
+            }
+            let _e684 = phi_4776_;
+            phi_4853_ = (_e165 * _e672.z);
+            phi_4775_ = _e684;
+        }
+        let _e688 = phi_4853_;
+        let _e690 = phi_4775_;
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e693 = ((_e169 & 32768u) != 0u);
+                phi_3548_ = _e693;
+                if (_e693) {
+                    let _e694 = gl_FrontFacing_1;
+                    phi_3548_ = !(_e694);
+                }
+                let _e697 = phi_3548_;
+                if (_e697) {
+                    let _e698 = i_normal_1;
+                    phi_4772_ = -(_e698);
+                    break;
+                }
+                let _e700 = i_normal_1;
+                phi_4772_ = _e700;
+                break;
+            }
+        }
+        let _e702 = phi_4772_;
+        let _e704 = i_tangent_1;
+        let _e705 = normalize(_e704);
+        phi_5064_ = (_e574 * _e575);
+        phi_5046_ = (_e654 * _e654);
+        phi_5019_ = normalize(_e464);
+        phi_4975_ = ((_e574 * _e564) + vec3<f32>((((0.1599999964237213 * _e573) * _e573) * _e575)));
+        phi_4949_ = _e664;
+        phi_4838_ = _e688;
+        phi_4824_ = ((_e705 * _e690.x) + (cross(normalize(_e702), _e705) * _e690.y));
+        phi_4778_ = _e560;
+    }
+    let _e713 = phi_5064_;
+    let _e715 = phi_5046_;
+    let _e717 = phi_5019_;
+    let _e719 = phi_4975_;
+    let _e721 = phi_4949_;
+    let _e723 = phi_4838_;
+    let _e725 = phi_4824_;
+    let _e727 = phi_4778_;
+    let _e728 = i_tangent_1;
+    let _e729 = normalize(_e728);
+    let _e732 = unnamed.uniforms.inv_view;
+    let _e733 = i_view_position_1;
+    let _e737 = unnamed.uniforms.clip_plane_count;
+    let _e739 = unnamed.uniforms.clip_planes;
+    local[0] = _e739[0];
+    local[1] = _e739[1];
+    local[2] = _e739[2];
+    local[3] = _e739[3];
+    switch(bitcast<i32>(0u)) {
+        default: {
+            phi_5108_ = 0u;
+            loop {
+                let _e750 = phi_5108_;
+                phi_5112_ = false;
+                phi_5109_ = false;
+                if ((_e750 < _e737)) {
+                    let _e753 = local[_e750];
+                    if ((dot(_e753, (_e732 * _e733)) < 0.0)) {
+                        phi_5112_ = true;
+                        phi_5109_ = true;
+                        break;
+                    }
+                    continue;
+                } else {
+                    break;
+                }
+                continuing {
+                    phi_5108_ = (_e750 + bitcast<u32>(1));
+                }
+            }
+            let _e759 = phi_5112_;
+            let _e761 = phi_5109_;
+            phi_5111_ = _e759;
+            if (_e761) {
+                break;
+            }
+            phi_5111_ = false;
+            break;
+        }
+    }
+    let _e763 = phi_5111_;
+    phi_2168_ = _e763;
+    if (!(_e763)) {
+        let _e765 = i_fade_1;
+        let _e766 = gl_FragCoord_1;
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e770 = (vec2<u32>(_e766.xy) % vec2<u32>(4u, 4u));
+                local_1 = array<f32,16u>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+                let _e776 = local_1[((_e770.y * 4u) + _e770.x)];
+                let _e778 = ((_e776 + 0.5) * 0.0625);
+                if ((_e765 >= 0.0)) {
+                    phi_5165_ = (_e778 >= _e765);
+                    break;
+                }
+                phi_5165_ = (_e778 < (-1.0 - _e765));
+                break;
+            }
         }
-        let _e419 = phi_2744_;
-        phi_2807_ = (_e329 * _e330);
-        phi_2800_ = (_e409 * _e409);
-        phi_2776_ = normalize(_e219);
-        phi_2748_ = ((_e329 * _e319) + vec3<f32>((((0.1599999964237213 * _e328) * _e328) * _e330)));
-        phi_2737_ = _e419;
-        phi_2647_ = _e315;
+        let _e784 = phi_5165_;
+        phi_2168_ = _e784;
     }
-    let _e421 = phi_2807_;
-    let _e423 = phi_2800_;
-    let _e425 = phi_2776_;
-    let _e427 = phi_2748_;
-    let _e429 = phi_2737_;
-    let _e431 = phi_2647_;
-    let _e434 = unnamed_1.material.material_flags;
-    if (((_e434 & 8192u) != 0u)) {
-        o_color = _e166;
+    let _e786 = phi_2168_;
+    if (_e786) {
+        discard;
+    }
+    let _e789 = unnamed_1.material.material_flags;
+    if (((_e789 & 8192u) != 0u)) {
+        o_color = _e349;
     } else {
-        let _e437 = i_view_position_1;
-        let _e440 = -(normalize(_e437.xyz));
-        phi_2836_ = _e429;
-        phi_2835_ = 0u;
+        let _e794 = -(normalize(_e733.xyz));
+        let _e797 = unnamed_2.directional_light_header.total_lights;
+        phi_5167_ = _e721;
+        phi_5166_ = 0u;
         loop {
-            let _e442 = phi_2836_;
-            let _e444 = phi_2835_;
-            let _e447 = unnamed_2.directional_light_header.total_lights;
-            local = _e442;
-            local_1 = _e442;
-            local_2 = _e442;
-            if ((_e444 < _e447)) {
-                let _e452 = unnamed_2.directional_lights[_e444].view_proj;
-                let _e455 = unnamed.uniforms.inv_view;
-                let _e457 = ((_e452 * _e455) * _e437);
-                let _e460 = ((_e457.xy * 0.5) + vec2<f32>(0.5, 0.5));
-                let _e463 = (1.0 - _e460.y);
-                let _e466 = vec4<f32>(_e460.x, _e463, f32(_e444), _e457.z);
-                let _e467 = (_e460.x < 0.0);
-                phi_1305_ = _e467;
-                if (!(_e467)) {
-                    phi_1305_ = (_e460.x > 1.0);
+            let _e799 = phi_5167_;
+            let _e801 = phi_5166_;
+            local_11 = _e799;
+            local_12 = _e799;
+            local_13 = _e799;
+            if ((_e801 < _e797)) {
+                let _e806 = unnamed_2.directional_lights[_e801].view_proj;
+                let _e808 = ((_e806 * _e732) * _e733);
+                let _e811 = ((_e808.xy * 0.5) + vec2<f32>(0.5, 0.5));
+                let _e814 = (1.0 - _e811.y);
+                let _e817 = vec4<f32>(_e811.x, _e814, f32(_e801), _e808.z);
+                let _e818 = (_e811.x < 0.0);
+                phi_2265_ = _e818;
+                if (!(_e818)) {
+                    phi_2265_ = (_e811.x > 1.0);
                 }
-                let _e471 = phi_1305_;
-                phi_1312_ = _e471;
-                if (!(_e471)) {
-                    phi_1312_ = (_e463 < 0.0);
+                let _e822 = phi_2265_;
+                phi_2272_ = _e822;
+                if (!(_e822)) {
+                    phi_2272_ = (_e814 < 0.0);
                 }
-                let _e475 = phi_1312_;
-                phi_1319_ = _e475;
-                if (!(_e475)) {
-                    phi_1319_ = (_e463 > 1.0);
+                let _e826 = phi_2272_;
+                phi_2279_ = _e826;
+                if (!(_e826)) {
+                    phi_2279_ = (_e814 > 1.0);
                 }
-                let _e479 = phi_1319_;
-                phi_1327_ = _e479;
-                if (!(_e479)) {
-                    phi_1327_ = (_e457.z < -1.0);
+                let _e830 = phi_2279_;
+                phi_2286_ = _e830;
+                if (!(_e830)) {
+                    phi_2286_ = (_e808.z < -1.0);
                 }
-                let _e483 = phi_1327_;
-                phi_1334_ = _e483;
-                if (!(_e483)) {
-                    phi_1334_ = (_e457.z > 1.0);
+                let _e834 = phi_2286_;
+                phi_2293_ = _e834;
+                if (!(_e834)) {
+                    phi_2293_ = (_e808.z > 1.0);
                 }
-                let _e487 = phi_1334_;
-                if (_e487) {
-                    phi_2843_ = 1.0;
+                let _e838 = phi_2293_;
+                if (_e838) {
+                    phi_5174_ = 1.0;
                 } else {
-                    let _e493 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e466.x, _e466.y), i32(_e466.z), _e457.z);
-                    let _e499 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e466.x, _e466.y), i32(_e466.z), _e457.z, vec2<i32>(0, 1));
-                    let _e506 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e466.x, _e466.y), i32(_e466.z), _e457.z, vec2<i32>(0, -1));
-                    let _e513 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e466.x, _e466.y), i32(_e466.z), _e457.z, vec2<i32>(1, 0));
-                    let _e520 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e466.x, _e466.y), i32(_e466.z), _e457.z, vec2<i32>(-1, 0));
-                    phi_2843_ = (0.20000000298023224 * ((((_e493 + _e499) + _e506) + _e513) + _e520));
+                    let _e844 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e817.x, _e817.y), i32(_e817.z), _e808.z);
+                    let _e850 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e817.x, _e817.y), i32(_e817.z), _e808.z, vec2<i32>(0, 1));
+                    let _e857 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e817.x, _e817.y), i32(_e817.z), _e808.z, vec2<i32>(0, -1));
+                    let _e864 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e817.x, _e817.y), i32(_e817.z), _e808.z, vec2<i32>(1, 0));
+                    let _e871 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e817.x, _e817.y), i32(_e817.z), _e808.z, vec2<i32>(-1, 0));
+                    phi_5174_ = (0.20000000298023224 * ((((_e844 + _e850) + _e857) + _e864) + _e871));
+                }
+                let _e875 = phi_5174_;
+                let _e876 = (_e875 * _e727);
+                let _e880 = unnamed_2.directional_lights[_e801].color;
+                let _e882 = unnamed_2.directional_lights[_e801].direction;
+                switch(bitcast<i32>(0u)) {
+                    default: {
+                        if (((_e169 & 131072u) != 0u)) {
+                            let _e888 = unnamed.uniforms.view;
+                            let _e898 = normalize((mat3x3<f32>(_e888[0].xyz, _e888[1].xyz, _e888[2].xyz) * -(_e882)));
+                            let _e900 = normalize((_e794 + _e898));
+                            let _e904 = normalize((_e729 - (_e717 * dot(_e717, _e729))));
+                            let _e914 = dot(normalize((_e904 + (_e717 * _e179.x))), _e900);
+                            let _e933 = dot(normalize((_e904 + (_e717 * _e179.y))), _e900);
+                            phi_5178_ = (((((_e713 * 0.31830987334251404) + (_e719 * (((smoothStep(-1.0, 0.0, _e914) * pow(sqrt(max((1.0 - (_e914 * _e914)), 0.0)), _e179.z)) * (_e179.z + 2.0)) * 0.15915493667125702))) + ((_e719 * _e349.xyz) * (((smoothStep(-1.0, 0.0, _e933) * pow(sqrt(max((1.0 - (_e933 * _e933)), 0.0)), _e179.w)) * (_e179.w + 2.0)) * 0.15915493667125702))) * _e880) * (clamp(((dot(_e717, _e898) * 0.75) + 0.25), 0.0, 1.0) * _e876));
+                            break;
+                        }
+                        let _e953 = unnamed.uniforms.view;
+                        let _e963 = normalize((mat3x3<f32>(_e953[0].xyz, _e953[1].xyz, _e953[2].xyz) * -(_e882)));
+                        let _e965 = normalize((_e794 + _e963));
+                        let _e967 = abs(dot(_e717, _e794));
+                        let _e968 = (_e967 + 9.999999747378752e-6);
+                        let _e970 = clamp(dot(_e717, _e963), 0.0, 1.0);
+                        let _e972 = clamp(dot(_e717, _e965), 0.0, 1.0);
+                        if ((_e723 != 0.0)) {
+                            let _e981 = normalize((_e725 - (_e717 * dot(_e717, _e725))));
+                            let _e982 = cross(_e717, _e981);
+                            let _e985 = max((_e715 * (1.0 + _e723)), 0.0010000000474974513);
+                            let _e988 = max((_e715 * (1.0 - _e723)), 0.0010000000474974513);
+                            let _e991 = (_e985 * _e988);
+                            let _e995 = vec3<f32>((_e988 * dot(_e981, _e965)), (_e985 * dot(_e982, _e965)), (_e991 * _e972));
+                            let _e997 = (_e991 / dot(_e995, _e995));
+                            phi_5176_ = clamp((0.5 / ((_e970 * length(vec3<f32>((_e985 * dot(_e981, _e794)), (_e988 * dot(_e982, _e794)), _e968))) + (_e968 * length(vec3<f32>((_e985 * dot(_e981, _e963)), (_e988 * dot(_e982, _e963)), _e970))))), 0.0, 1.0);
+                            phi_5175_ = (((_e991 * _e997) * _e997) * 0.31830987334251404);
+                        } else {
+                            let _e1018 = (_e715 * _e715);
+                            let _e1022 = ((((_e972 * _e1018) - _e972) * _e972) + 1.0);
+                            phi_5176_ = (0.5 / ((_e970 * sqrt((((((-9.999999747378752e-6 - _e967) * _e1018) + _e968) * _e968) + _e1018))) + (_e968 * sqrt(((((-(_e970) * _e1018) + _e970) * _e970) + _e1018)))));
+                            phi_5175_ = (_e1018 / ((3.1415927410125732 * _e1022) * _e1022));
+                        }
+                        let _e1043 = phi_5176_;
+                        let _e1045 = phi_5175_;
+                        let _e1056 = ((_e713 * 0.31830987334251404) + (((_e719 + ((vec3<f32>(clamp(dot(_e719, vec3<f32>(16.5, 16.5, 16.5)), 0.0, 1.0)) - _e719) * pow((1.0 - clamp(dot(_e963, _e965), 0.0, 1.0)), 5.0))) * (_e1045 * _e1043)) * 1.0));
+                        phi_5177_ = _e1056;
+                        if ((max(_e181.x, max(_e181.y, _e181.z)) > 0.0)) {
+                            let _e1064 = max(_e181.w, 0.07000000029802322);
+                            let _e1066 = (_e1064 * _e1064);
+                            phi_5177_ = (_e1056 + (_e181.xyz * ((((2.0 + (1.0 / _e1066)) * pow(max((1.0 - (_e972 * _e972)), 0.0078125), (0.5 / _e1066))) * 0.15915493667125702) * clamp((0.25 / ((_e970 + _e968) - (_e970 * _e968))), 0.0, 1.0))));
+                        }
+                        let _e1085 = phi_5177_;
+                        phi_5178_ = ((_e1085 * _e880) * (_e970 * _e876));
+                        break;
+                    }
                 }
-                let _e524 = phi_2843_;
-                let _e529 = unnamed_2.directional_lights[_e444].color;
-                let _e531 = unnamed_2.directional_lights[_e444].direction;
-                let _e534 = unnamed.uniforms.view;
-                let _e544 = normalize((mat3x3<f32>(_e534[0].xyz, _e534[1].xyz, _e534[2].xyz) * -(_e531)));
-                let _e546 = normalize((_e440 + _e544));
-                let _e548 = abs(dot(_e425, _e440));
-                let _e549 = (_e548 + 9.999999747378752e-6);
-                let _e551 = clamp(dot(_e425, _e544), 0.0, 1.0);
-                let _e553 = clamp(dot(_e425, _e546), 0.0, 1.0);
-                let _e558 = (_e423 * _e423);
-                let _e562 = ((((_e553 * _e558) - _e553) * _e553) + 1.0);
-                local_3 = (_e442 + ((((_e421 * 0.31830987334251404) + (((_e427 + ((vec3<f32>(clamp(dot(_e427, vec3<f32>(16.5, 16.5, 16.5)), 0.0, 1.0)) - _e427) * pow((1.0 - clamp(dot(_e544, _e546), 0.0, 1.0)), 5.0))) * ((_e558 / ((3.1415927410125732 * _e562) * _e562)) * (0.5 / ((_e551 * sqrt((((((-9.999999747378752e-6 - _e548) * _e558) + _e549) * _e549) + _e558))) + (_e549 * sqrt(((((-(_e551) * _e558) + _e551) * _e551) + _e558))))))) * 1.0)) * _e529) * (_e551 * (_e524 * _e431))));
+                let _e1090 = phi_5178_;
+                local_15 = (_e799 + _e1090);
                 continue;
             } else {
                 break;
             }
             continuing {
-                let _e666 = local_3;
-                phi_2836_ = _e666;
-                phi_2835_ = (_e444 + bitcast<u32>(1));
+                let _e1214 = local_15;
+                phi_5167_ = _e1214;
+                phi_5166_ = (_e801 + bitcast<u32>(1));
             }
         }
-        let _e601 = local;
-        let _e604 = local_1;
-        let _e607 = local_2;
-        let _e612 = unnamed.uniforms.ambient;
-        o_color = max(vec4<f32>(_e601.x, _e604.y, _e607.z, _e166.w), (_e612 * _e166));
+        let _e1096 = local_11;
+        let _e1099 = local_12;
+        let _e1102 = local_13;
+        let _e1107 = unnamed.uniforms.ambient;
+        o_color = max(vec4<f32>(_e1096.x, _e1099.y, _e1102.z, _e349.w), (_e1107 * _e349));
+        let _e1112 = unnamed_1.material.subsurface_profile;
+        if ((_e1112 != 0u)) {
+            let _e1114 = unnamed_1.material.subsurface_profile;
+            o_color[3u] = (f32(_e1114) + 1.0);
+        }
     }
     return;
 }
 
 [[stage(fragment)]]
-fn main([[location(3)]] i_coords0_: vec2<f32>, [[location(5)]] i_color: vec4<f32>, [[location(1)]] i_normal: vec3<f32>, [[location(2)]] i_tangent: vec3<f32>, [[location(0)]] i_view_position: vec4<f32>, [[location(4)]] i_coords1_: vec2<f32>, [[location(6)]] i_material: u32) -> [[location(0)]] vec4<f32> {
-    i_coords0_1 = i_coords0_;
-    i_color_1 = i_color;
+fn main([[builtin(front_facing)]] gl_FrontFacing: bool, [[location(1)]] i_normal: vec3<f32>, [[location(2)]] i_tangent: vec3<f32>, [[location(0)]] i_view_position: vec4<f32>, [[location(3)]] i_coords0_: vec2<f32>, [[location(5)]] i_color: vec4<f32>, [[location(8), interpolate(flat)]] i_fade: f32, [[builtin(position)]] gl_FragCoord: vec4<f32>, [[location(4)]] i_coords1_: vec2<f32>, [[location(6)]] i_material: u32) -> [[location(0)]] vec4<f32> {
+    gl_FrontFacing_1 = gl_FrontFacing;
     i_normal_1 = i_normal;
     i_tangent_1 = i_tangent;
     i_view_position_1 = i_view_position;
+    i_coords0_1 = i_coords0_;
+    i_color_1 = i_color;
+    i_fade_1 = i_fade;
+    gl_FragCoord_1 = gl_FragCoord;
     i_coords1_1 = i_coords1_;
     i_material_1 = i_material;
     main_1();
-    let _e15 = o_color;
-    return _e15;
+    let _e21 = o_color;
+    return _e21;
 }
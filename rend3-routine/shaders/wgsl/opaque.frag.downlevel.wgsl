@@ -0,0 +1,970 @@
+struct Plane {
+    inner: vec4<f32>;
+};
+
+struct Frustum {
+    left: Plane;
+    right: Plane;
+    top: Plane;
+    bottom: Plane;
+    near: Plane;
+};
+
+struct UniformData {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    origin_view_proj: mat4x4<f32>;
+    inv_view: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_origin_view_proj: mat4x4<f32>;
+    frustum: Frustum;
+    ambient: vec4<f32>;
+    resolution: vec2<u32>;
+    clip_plane_count: u32;
+    vertex_quantization: u32;
+    clip_planes: [[stride(16)]] array<vec4<f32>,4u>;
+};
+
+struct UniformBuffer {
+    uniforms: UniformData;
+};
+
+struct CPUMaterialData {
+    uv_transform0_: mat3x3<f32>;
+    uv_transform1_: mat3x3<f32>;
+    albedo: vec4<f32>;
+    emissive: vec3<f32>;
+    roughness: f32;
+    metallic: f32;
+    reflectance: f32;
+    clear_coat: f32;
+    clear_coat_roughness: f32;
+    anisotropy: f32;
+    ambient_occlusion: f32;
+    alpha_cutout: f32;
+    material_flags: u32;
+    parallax_scale: f32;
+    parallax_steps: u32;
+    subsurface_profile: u32;
+    anisotropy_rotation: f32;
+    detail_transform: vec4<f32>;
+    hair: vec4<f32>;
+    sheen: vec4<f32>;
+    transmission: vec4<f32>;
+    attenuation: vec4<f32>;
+    texture_enable: u32;
+};
+
+struct TextureData {
+    material: CPUMaterialData;
+};
+
+struct DirectionalLightBufferHeader {
+    total_lights: u32;
+};
+
+struct DirectionalLight {
+    view_proj: mat4x4<f32>;
+    color: vec3<f32>;
+    direction: vec3<f32>;
+    offset: vec2<f32>;
+    size: f32;
+};
+
+struct DirectionalLightBuffer {
+    directional_light_header: DirectionalLightBufferHeader;
+    directional_lights: [[stride(112)]] array<DirectionalLight,4u>;
+};
+
+var<private> gl_FrontFacing_1: bool;
+var<private> i_normal_1: vec3<f32>;
+var<private> i_tangent_1: vec3<f32>;
+var<private> i_view_position_1: vec4<f32>;
+[[group(2), binding(11)]]
+var height_tex: texture_2d<f32>;
+var<private> i_coords0_1: vec2<f32>;
+[[group(2), binding(1)]]
+var albedo_tex: texture_2d<f32>;
+var<private> i_color_1: vec4<f32>;
+[[group(2), binding(12)]]
+var detail_albedo_tex: texture_2d<f32>;
+[[group(2), binding(2)]]
+var normal_tex: texture_2d<f32>;
+[[group(2), binding(13)]]
+var detail_normal_tex: texture_2d<f32>;
+[[group(2), binding(3)]]
+var roughness_tex: texture_2d<f32>;
+[[group(2), binding(10)]]
+var ambient_occlusion_tex: texture_2d<f32>;
+[[group(2), binding(4)]]
+var metallic_tex: texture_2d<f32>;
+[[group(2), binding(5)]]
+var reflectance_tex: texture_2d<f32>;
+[[group(2), binding(6)]]
+var clear_coat_tex: texture_2d<f32>;
+[[group(2), binding(7)]]
+var clear_coat_roughness_tex: texture_2d<f32>;
+[[group(2), binding(8)]]
+var emissive_tex: texture_2d<f32>;
+[[group(2), binding(9)]]
+var anisotropy_tex: texture_2d<f32>;
+[[group(0), binding(0)]]
+var primary_sampler: sampler;
+[[group(0), binding(3)]]
+var<uniform> unnamed: UniformBuffer;
+[[group(2), binding(0)]]
+var<uniform> unnamed_1: TextureData;
+var<private> i_fade_1: f32;
+var<private> gl_FragCoord_1: vec4<f32>;
+var<private> o_color: vec4<f32>;
+[[group(0), binding(4)]]
+var<uniform> unnamed_2: DirectionalLightBuffer;
+[[group(0), binding(5)]]
+var shadow: texture_depth_2d_array;
+[[group(0), binding(2)]]
+var shadow_sampler: sampler_comparison;
+var<private> i_coords1_1: vec2<f32>;
+var<private> i_material_1: u32;
+
+fn main_1() {
+    var local: array<vec4<f32>,4u>;
+    var local_1: array<f32,16u>;
+    var phi_3396_: bool;
+    var phi_4530_: vec3<f32>;
+    var phi_4537_: vec2<f32>;
+    var phi_4536_: vec2<f32>;
+    var phi_4535_: f32;
+    var phi_4534_: f32;
+    var phi_4533_: f32;
+    var phi_4532_: f32;
+    var phi_4531_: u32;
+    var local_2: f32;
+    var local_3: f32;
+    var local_4: f32;
+    var local_5: f32;
+    var local_6: f32;
+    var local_7: f32;
+    var local_8: f32;
+    var local_9: vec2<f32>;
+    var local_10: vec2<f32>;
+    var phi_4538_: vec2<f32>;
+    var phi_4539_: vec2<f32>;
+    var phi_4542_: vec4<f32>;
+    var phi_4540_: vec4<f32>;
+    var phi_4544_: vec4<f32>;
+    var phi_4543_: vec4<f32>;
+    var phi_4592_: vec4<f32>;
+    var phi_3453_: bool;
+    var phi_4778_: vec3<f32>;
+    var phi_2577_: bool;
+    var phi_4552_: vec2<f32>;
+    var phi_4553_: vec3<f32>;
+    var phi_4555_: vec3<f32>;
+    var phi_4554_: vec3<f32>;
+    var phi_4559_: vec3<f32>;
+    var phi_3474_: bool;
+    var phi_4556_: vec3<f32>;
+    var phi_3495_: bool;
+    var phi_4545_: vec3<f32>;
+    var phi_4560_: vec3<f32>;
+    var phi_4801_: f32;
+    var phi_4688_: f32;
+    var phi_4609_: f32;
+    var phi_2777_: bool;
+    var phi_4574_: vec2<f32>;
+    var phi_4691_: f32;
+    var phi_4612_: f32;
+    var phi_4803_: f32;
+    var phi_4717_: f32;
+    var phi_4638_: f32;
+    var phi_4817_: f32;
+    var phi_4804_: f32;
+    var phi_4692_: f32;
+    var phi_4613_: f32;
+    var phi_4802_: f32;
+    var phi_4689_: f32;
+    var phi_4610_: f32;
+    var phi_4800_: f32;
+    var phi_4687_: f32;
+    var phi_4608_: f32;
+    var phi_4639_: f32;
+    var phi_4725_: f32;
+    var phi_4644_: f32;
+    var phi_4647_: f32;
+    var phi_4727_: f32;
+    var phi_4683_: f32;
+    var phi_4762_: f32;
+    var phi_4728_: f32;
+    var phi_4648_: f32;
+    var phi_4726_: f32;
+    var phi_4645_: f32;
+    var phi_4724_: f32;
+    var phi_4643_: f32;
+    var phi_4763_: f32;
+    var phi_4966_: vec3<f32>;
+    var phi_4777_: vec2<f32>;
+    var phi_4854_: f32;
+    var phi_4776_: vec2<f32>;
+    var phi_3549_: bool;
+    var phi_4773_: vec3<f32>;
+    var phi_5065_: vec3<f32>;
+    var phi_5047_: f32;
+    var phi_5020_: vec3<f32>;
+    var phi_4976_: vec3<f32>;
+    var phi_4950_: vec3<f32>;
+    var phi_4839_: f32;
+    var phi_4825_: vec3<f32>;
+    var phi_4779_: f32;
+    var phi_5109_: u32;
+    var phi_5113_: bool;
+    var phi_5110_: bool;
+    var phi_5112_: bool;
+    var phi_5166_: bool;
+    var phi_2168_: bool;
+    var phi_5168_: vec3<f32>;
+    var phi_5167_: u32;
+    var phi_2266_: bool;
+    var phi_2273_: bool;
+    var phi_2280_: bool;
+    var phi_2287_: bool;
+    var phi_2294_: bool;
+    var phi_5175_: f32;
+    var phi_5177_: f32;
+    var phi_5176_: f32;
+    var phi_5178_: vec3<f32>;
+    var phi_5179_: vec3<f32>;
+    var local_11: vec3<f32>;
+    var local_12: vec3<f32>;
+    var local_13: vec3<f32>;
+    var local_14: vec2<f32>;
+    var local_15: vec3<f32>;
+
+    let _e149 = unnamed_1.material.uv_transform0_;
+    let _e151 = unnamed_1.material.albedo;
+    let _e153 = unnamed_1.material.emissive;
+    let _e155 = unnamed_1.material.roughness;
+    let _e157 = unnamed_1.material.metallic;
+    let _e159 = unnamed_1.material.reflectance;
+    let _e161 = unnamed_1.material.clear_coat;
+    let _e163 = unnamed_1.material.clear_coat_roughness;
+    let _e165 = unnamed_1.material.anisotropy;
+    let _e167 = unnamed_1.material.ambient_occlusion;
+    let _e169 = unnamed_1.material.material_flags;
+    let _e171 = unnamed_1.material.parallax_scale;
+    let _e173 = unnamed_1.material.parallax_steps;
+    let _e175 = unnamed_1.material.anisotropy_rotation;
+    let _e177 = unnamed_1.material.detail_transform;
+    let _e179 = unnamed_1.material.hair;
+    let _e181 = unnamed_1.material.sheen;
+    let _e183 = unnamed_1.material.texture_enable;
+    let _e184 = i_coords0_1;
+    let _e188 = (_e149 * vec3<f32>(_e184.x, _e184.y, 1.0));
+    let _e191 = vec2<f32>(_e188.x, _e188.y);
+    let _e192 = dpdx(_e191);
+    let _e193 = dpdy(_e191);
+    phi_4539_ = _e191;
+    if ((((_e183 >> bitcast<u32>(10)) & 1u) != 0u)) {
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e200 = ((_e169 & 32768u) != 0u);
+                phi_3396_ = _e200;
+                if (_e200) {
+                    let _e201 = gl_FrontFacing_1;
+                    phi_3396_ = !(_e201);
+                }
+                let _e204 = phi_3396_;
+                if (_e204) {
+                    let _e205 = i_normal_1;
+                    phi_4530_ = -(_e205);
+                    break;
+                }
+                let _e207 = i_normal_1;
+                phi_4530_ = _e207;
+                break;
+            }
+        }
+        let _e209 = phi_4530_;
+        let _e210 = normalize(_e209);
+        let _e211 = i_tangent_1;
+        let _e212 = normalize(_e211);
+        let _e214 = i_view_position_1;
+        let _e217 = -(normalize(_e214.xyz));
+        let _e220 = dot(_e217, _e210);
+        let _e222 = max(_e173, 1u);
+        let _e224 = (1.0 / f32(_e222));
+        let _e231 = textureSampleGrad(height_tex, primary_sampler, _e191, _e192, _e193);
+        let _e233 = (1.0 - _e231.x);
+        phi_4537_ = _e191;
+        phi_4536_ = _e191;
+        phi_4535_ = 0.0;
+        phi_4534_ = _e233;
+        phi_4533_ = _e233;
+        phi_4532_ = 0.0;
+        phi_4531_ = 0u;
+        loop {
+            let _e235 = phi_4537_;
+            let _e237 = phi_4536_;
+            let _e239 = phi_4535_;
+            let _e241 = phi_4534_;
+            let _e243 = phi_4533_;
+            let _e245 = phi_4532_;
+            let _e247 = phi_4531_;
+            local_2 = _e245;
+            local_3 = _e245;
+            local_4 = _e243;
+            local_5 = _e243;
+            local_6 = _e245;
+            local_7 = _e241;
+            local_8 = _e239;
+            local_9 = _e237;
+            local_10 = _e235;
+            local_14 = _e237;
+            if (((_e247 < _e222) && (_e245 < _e243))) {
+                continue;
+            } else {
+                break;
+            }
+            continuing {
+                let _e251 = (_e237 - (((vec3<f32>(dot(_e217, _e212), dot(_e217, cross(_e210, _e212)), _e220).xy / vec2<f32>(max(_e220, 0.05000000074505806))) * _e171) * _e224));
+                let _e253 = textureSampleGrad(height_tex, primary_sampler, _e251, _e192, _e193);
+                phi_4537_ = _e237;
+                phi_4536_ = _e251;
+                phi_4535_ = _e245;
+                phi_4534_ = _e243;
+                phi_4533_ = (1.0 - _e253.x);
+                phi_4532_ = (_e245 + _e224);
+                phi_4531_ = (_e247 + bitcast<u32>(1));
+            }
+        }
+        let _e261 = local_2;
+        let _e265 = local_3;
+        let _e267 = local_4;
+        let _e1138 = local_14;
+        phi_4538_ = _e1138;
+        if (((((_e169 & 65536u) != 0u) && (_e261 > 0.0)) && (_e265 >= _e267))) {
+            let _e271 = local_5;
+            let _e273 = local_6;
+            let _e274 = (_e271 - _e273);
+            let _e276 = local_7;
+            let _e278 = local_8;
+            let _e284 = local_9;
+            let _e286 = local_10;
+            phi_4538_ = mix(_e284, _e286, vec2<f32>((_e274 / (_e274 - (_e276 - _e278)))));
+        }
+        let _e289 = phi_4538_;
+        phi_4539_ = _e289;
+    }
+    let _e291 = phi_4539_;
+    let _e292 = _e177.xy;
+    let _e295 = ((_e291 * _e292) + _e177.zw);
+    let _e296 = (_e192 * _e292);
+    let _e297 = (_e193 * _e292);
+    if (((_e169 & 1u) != 0u)) {
+        if ((((_e183 >> bitcast<u32>(0)) & 1u) != 0u)) {
+            let _e304 = textureSampleGrad(albedo_tex, primary_sampler, _e291, _e192, _e193);
+            phi_4542_ = _e304;
+        } else {
+            phi_4542_ = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+        }
+        let _e306 = phi_4542_;
+        phi_4544_ = _e306;
+        if (((_e169 & 2u) != 0u)) {
+            let _e309 = i_color_1;
+            phi_4540_ = _e309;
+            if (((_e169 & 4u) != 0u)) {
+                let _e312 = _e309.xyz;
+                let _e320 = mix((_e312 * vec3<f32>(0.07739938050508499, 0.07739938050508499, 0.07739938050508499)), pow(((_e312 + vec3<f32>(0.054999999701976776, 0.054999999701976776, 0.054999999701976776)) * vec3<f32>(0.9478673338890076, 0.9478673338890076, 0.9478673338890076)), vec3<f32>(2.4000000953674316, 2.4000000953674316, 2.4000000953674316)), clamp(ceil((_e312 - vec3<f32>(0.040449999272823334, 0.040449999272823334, 0.040449999272823334))), vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0)));
+                phi_4540_ = vec4<f32>(_e320.x, _e320.y, _e320.z, _e309.w);
+            }
+            let _e327 = phi_4540_;
+            phi_4544_ = (_e306 * _e327);
+        }
+        let _e330 = phi_4544_;
+        phi_4543_ = _e330;
+    } else {
+        phi_4543_ = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let _e332 = phi_4543_;
+    let _e333 = (_e332 * _e151);
+    phi_4592_ = _e333;
+    if ((((_e183 >> bitcast<u32>(11)) & 1u) != 0u)) {
+        let _e338 = textureSampleGrad(detail_albedo_tex, primary_sampler, _e295, _e296, _e297);
+        let _e342 = (_e333.xyz * (_e338.xyz * 2.0));
+        phi_4592_ = vec4<f32>(_e342.x, _e342.y, _e342.z, _e333.w);
+    }
+    let _e349 = phi_4592_;
+    if (((_e169 & 8192u) != 0u)) {
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e354 = ((_e169 & 32768u) != 0u);
+                phi_3453_ = _e354;
+                if (_e354) {
+                    let _e355 = gl_FrontFacing_1;
+                    phi_3453_ = !(_e355);
+                }
+                let _e358 = phi_3453_;
+                if (_e358) {
+                    let _e359 = i_normal_1;
+                    phi_4778_ = -(_e359);
+                    break;
+                }
+                let _e361 = i_normal_1;
+                phi_4778_ = _e361;
+                break;
+            }
+        }
+        let _e363 = phi_4778_;
+        phi_5065_ = vec3<f32>(0.0, 0.0, 0.0);
+        phi_5047_ = 0.0;
+        phi_5020_ = normalize(_e363);
+        phi_4976_ = vec3<f32>(0.0, 0.0, 0.0);
+        phi_4950_ = vec3<f32>(0.0, 0.0, 0.0);
+        phi_4839_ = 0.0;
+        phi_4825_ = vec3<f32>(0.0, 0.0, 0.0);
+        phi_4779_ = 0.0;
+    } else {
+        let _e368 = (((_e183 >> bitcast<u32>(1)) & 1u) != 0u);
+        phi_2577_ = _e368;
+        if (!(_e368)) {
+            phi_2577_ = (((_e183 >> bitcast<u32>(12)) & 1u) != 0u);
+        }
+        let _e375 = phi_2577_;
+        if (_e375) {
+            phi_4554_ = vec3<f32>(0.0, 0.0, 1.0);
+            if (_e368) {
+                let _e376 = textureSampleGrad(normal_tex, primary_sampler, _e291, _e192, _e193);
+                if (((_e169 & 8u) != 0u)) {
+                    if (((_e169 & 16u) != 0u)) {
+                        phi_4552_ = _e376.wy;
+                    } else {
+                        phi_4552_ = _e376.xy;
+                    }
+                    let _e384 = phi_4552_;
+                    let _e386 = ((_e384 * 2.0) - vec2<f32>(1.0, 1.0));
+                    phi_4553_ = vec3<f32>(_e386.x, _e386.y, sqrt(((1.0 - (_e386.x * _e386.x)) - (_e386.y * _e386.y))));
+                } else {
+                    phi_4553_ = normalize(((_e376.xyz * 2.0) - vec3<f32>(1.0, 1.0, 1.0)));
+                }
+                let _e400 = phi_4553_;
+                phi_4555_ = _e400;
+                if (((_e169 & 32u) != 0u)) {
+                    phi_4555_ = vec3<f32>(_e400.x, -(_e400.y), _e400.z);
+                }
+                let _e410 = phi_4555_;
+                phi_4554_ = _e410;
+            }
+            let _e412 = phi_4554_;
+            phi_4559_ = _e412;
+            if ((((_e183 >> bitcast<u32>(12)) & 1u) != 0u)) {
+                let _e417 = textureSampleGrad(detail_normal_tex, primary_sampler, _e295, _e296, _e297);
+                let _e420 = ((_e417.xyz * 2.0) - vec3<f32>(1.0, 1.0, 1.0));
+                let _e423 = (_e412.xy + _e420.xy);
+                phi_4559_ = normalize(vec3<f32>(_e423.x, _e423.y, (_e412.z * _e420.z)));
+            }
+            let _e432 = phi_4559_;
+            switch(bitcast<i32>(0u)) {
+                default: {
+                    let _e435 = ((_e169 & 32768u) != 0u);
+                    phi_3474_ = _e435;
+                    if (_e435) {
+                        let _e436 = gl_FrontFacing_1;
+                        phi_3474_ = !(_e436);
+                    }
+                    let _e439 = phi_3474_;
+                    if (_e439) {
+                        let _e440 = i_normal_1;
+                        phi_4556_ = -(_e440);
+                        break;
+                    }
+                    let _e442 = i_normal_1;
+                    phi_4556_ = _e442;
+                    break;
+                }
+            }
+            let _e444 = phi_4556_;
+            let _e445 = normalize(_e444);
+            let _e446 = i_tangent_1;
+            let _e447 = normalize(_e446);
+            phi_4560_ = (mat3x3<f32>(_e447, cross(_e445, _e447), _e445) * _e432);
+        } else {
+            switch(bitcast<i32>(0u)) {
+                default: {
+                    let _e453 = ((_e169 & 32768u) != 0u);
+                    phi_3495_ = _e453;
+                    if (_e453) {
+                        let _e454 = gl_FrontFacing_1;
+                        phi_3495_ = !(_e454);
+                    }
+                    let _e457 = phi_3495_;
+                    if (_e457) {
+                        let _e458 = i_normal_1;
+                        phi_4545_ = -(_e458);
+                        break;
+                    }
+                    let _e460 = i_normal_1;
+                    phi_4545_ = _e460;
+                    break;
+                }
+            }
+            let _e462 = phi_4545_;
+            phi_4560_ = _e462;
+        }
+        let _e464 = phi_4560_;
+        if (((_e169 & 64u) != 0u)) {
+            if ((((_e183 >> bitcast<u32>(2)) & 1u) != 0u)) {
+                let _e472 = textureSampleGrad(roughness_tex, primary_sampler, _e291, _e192, _e193);
+                phi_4801_ = (_e167 * _e472.x);
+                phi_4688_ = (_e155 * _e472.y);
+                phi_4609_ = (_e157 * _e472.z);
+            } else {
+                phi_4801_ = _e167;
+                phi_4688_ = _e155;
+                phi_4609_ = _e157;
+            }
+            let _e480 = phi_4801_;
+            let _e482 = phi_4688_;
+            let _e484 = phi_4609_;
+            phi_4800_ = _e480;
+            phi_4687_ = _e482;
+            phi_4608_ = _e484;
+        } else {
+            let _e486 = ((_e169 & 128u) != 0u);
+            phi_2777_ = _e486;
+            if (!(_e486)) {
+                phi_2777_ = ((_e169 & 256u) != 0u);
+            }
+            let _e491 = phi_2777_;
+            if (_e491) {
+                if ((((_e183 >> bitcast<u32>(2)) & 1u) != 0u)) {
+                    let _e496 = textureSampleGrad(roughness_tex, primary_sampler, _e291, _e192, _e193);
+                    if (_e486) {
+                        phi_4574_ = _e496.yz;
+                    } else {
+                        phi_4574_ = _e496.xy;
+                    }
+                    let _e500 = phi_4574_;
+                    phi_4691_ = (_e155 * _e500.x);
+                    phi_4612_ = (_e157 * _e500.y);
+                } else {
+                    phi_4691_ = _e155;
+                    phi_4612_ = _e157;
+                }
+                let _e506 = phi_4691_;
+                let _e508 = phi_4612_;
+                if ((((_e183 >> bitcast<u32>(9)) & 1u) != 0u)) {
+                    let _e513 = textureSampleGrad(ambient_occlusion_tex, primary_sampler, _e291, _e192, _e193);
+                    phi_4803_ = (_e167 * _e513.x);
+                } else {
+                    phi_4803_ = _e167;
+                }
+                let _e517 = phi_4803_;
+                phi_4802_ = _e517;
+                phi_4689_ = _e506;
+                phi_4610_ = _e508;
+            } else {
+                phi_4804_ = 0.0;
+                phi_4692_ = 0.0;
+                phi_4613_ = 0.0;
+                if (((_e169 & 512u) != 0u)) {
+                    if ((((_e183 >> bitcast<u32>(2)) & 1u) != 0u)) {
+                        let _e524 = textureSampleGrad(roughness_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4717_ = (_e155 * _e524.x);
+                    } else {
+                        phi_4717_ = _e155;
+                    }
+                    let _e528 = phi_4717_;
+                    if ((((_e183 >> bitcast<u32>(3)) & 1u) != 0u)) {
+                        let _e533 = textureSampleGrad(metallic_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4638_ = (_e157 * _e533.x);
+                    } else {
+                        phi_4638_ = _e157;
+                    }
+                    let _e537 = phi_4638_;
+                    if ((((_e183 >> bitcast<u32>(9)) & 1u) != 0u)) {
+                        let _e542 = textureSampleGrad(ambient_occlusion_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4817_ = (_e167 * _e542.x);
+                    } else {
+                        phi_4817_ = _e167;
+                    }
+                    let _e546 = phi_4817_;
+                    phi_4804_ = _e546;
+                    phi_4692_ = _e528;
+                    phi_4613_ = _e537;
+                }
+                let _e548 = phi_4804_;
+                let _e550 = phi_4692_;
+                let _e552 = phi_4613_;
+                phi_4802_ = _e548;
+                phi_4689_ = _e550;
+                phi_4610_ = _e552;
+            }
+            let _e554 = phi_4802_;
+            let _e556 = phi_4689_;
+            let _e558 = phi_4610_;
+            phi_4800_ = _e554;
+            phi_4687_ = _e556;
+            phi_4608_ = _e558;
+        }
+        let _e560 = phi_4800_;
+        let _e562 = phi_4687_;
+        let _e564 = phi_4608_;
+        if ((((_e183 >> bitcast<u32>(4)) & 1u) != 0u)) {
+            let _e569 = textureSampleGrad(reflectance_tex, primary_sampler, _e291, _e192, _e193);
+            phi_4639_ = (_e159 * _e569.x);
+        } else {
+            phi_4639_ = _e159;
+        }
+        let _e573 = phi_4639_;
+        let _e574 = _e349.xyz;
+        let _e575 = (1.0 - _e564);
+        if (((_e169 & 1024u) != 0u)) {
+            if ((((_e183 >> bitcast<u32>(5)) & 1u) != 0u)) {
+                let _e589 = textureSampleGrad(clear_coat_tex, primary_sampler, _e291, _e192, _e193);
+                phi_4725_ = (_e163 * _e589.y);
+                phi_4644_ = (_e161 * _e589.x);
+            } else {
+                phi_4725_ = _e163;
+                phi_4644_ = _e161;
+            }
+            let _e595 = phi_4725_;
+            let _e597 = phi_4644_;
+            phi_4724_ = _e595;
+            phi_4643_ = _e597;
+        } else {
+            if (((_e169 & 2048u) != 0u)) {
+                if ((((_e183 >> bitcast<u32>(5)) & 1u) != 0u)) {
+                    let _e604 = textureSampleGrad(clear_coat_tex, primary_sampler, _e291, _e192, _e193);
+                    phi_4647_ = (_e161 * _e604.x);
+                } else {
+                    phi_4647_ = _e161;
+                }
+                let _e608 = phi_4647_;
+                if ((((_e183 >> bitcast<u32>(6)) & 1u) != 0u)) {
+                    let _e613 = textureSampleGrad(clear_coat_roughness_tex, primary_sampler, _e291, _e192, _e193);
+                    phi_4727_ = (_e163 * _e613.y);
+                } else {
+                    phi_4727_ = _e163;
+                }
+                let _e617 = phi_4727_;
+                phi_4726_ = _e617;
+                phi_4645_ = _e608;
+            } else {
+                phi_4728_ = 0.0;
+                phi_4648_ = 0.0;
+                if (((_e169 & 4096u) != 0u)) {
+                    if ((((_e183 >> bitcast<u32>(5)) & 1u) != 0u)) {
+                        let _e624 = textureSampleGrad(clear_coat_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4683_ = (_e161 * _e624.x);
+                    } else {
+                        phi_4683_ = _e161;
+                    }
+                    let _e628 = phi_4683_;
+                    if ((((_e183 >> bitcast<u32>(6)) & 1u) != 0u)) {
+                        let _e633 = textureSampleGrad(clear_coat_roughness_tex, primary_sampler, _e291, _e192, _e193);
+                        phi_4762_ = (_e163 * _e633.x);
+                    } else {
+                        phi_4762_ = _e163;
+                    }
+                    let _e637 = phi_4762_;
+                    phi_4728_ = _e637;
+                    phi_4648_ = _e628;
+                }
+                let _e639 = phi_4728_;
+                let _e641 = phi_4648_;
+                phi_4726_ = _e639;
+                phi_4645_ = _e641;
+            }
+            let _e643 = phi_4726_;
+            let _e645 = phi_4645_;
+            phi_4724_ = _e643;
+            phi_4643_ = _e645;
+        }
+        let _e647 = phi_4724_;
+        let _e649 = phi_4643_;
+        phi_4763_ = _e562;
+        if ((_e649 != 0.0)) {
+            phi_4763_ = mix(_e562, max(_e562, _e647), _e649);
+        }
+        let _e654 = phi_4763_;
+        if ((((_e183 >> bitcast<u32>(7)) & 1u) != 0u)) {
+            let _e660 = textureSampleGrad(emissive_tex, primary_sampler, _e291, _e192, _e193);
+            phi_4966_ = (_e153 * _e660.xyz);
+        } else {
+            phi_4966_ = _e153;
+        }
+        let _e664 = phi_4966_;
+        let _e665 = cos(_e175);
+        let _e666 = sin(_e175);
+        let _e667 = vec2<f32>(_e665, _e666);
+        phi_4854_ = _e165;
+        phi_4776_ = _e667;
+        if ((((_e183 >> bitcast<u32>(8)) & 1u) != 0u)) {
+            let _e672 = textureSampleGrad(anisotropy_tex, primary_sampler, _e291, _e192, _e193);
+            let _e675 = ((_e672.xy * 2.0) - vec2<f32>(1.0, 1.0));
+            phi_4777_ = _e667;
+            if ((dot(_e675, _e675) > 0.0)) {
+                phi_4777_ = (mat2x2<f32>(_e667, vec2<f32>(-(_e666), _e665)) * normalize(_e675));
+            }
+            let _e684 = phi_4777_;
+            phi_4854_ = (_e165 * _e672.z);
+            phi_4776_ = _e684;
+        }
+        let _e688 = phi_4854_;
+        let _e690 = phi_4776_;
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e693 = ((_e169 & 32768u) != 0u);
+                phi_3549_ = _e693;
+                if (_e693) {
+                    let _e694 = gl_FrontFacing_1;
+                    phi_3549_ = !(_e694);
+                }
+                let _e697 = phi_3549_;
+                if (_e697) {
+                    let _e698 = i_normal_1;
+                    phi_4773_ = -(_e698);
+                    break;
+                }
+                let _e700 = i_normal_1;
+                phi_4773_ = _e700;
+                break;
+            }
+        }
+        let _e702 = phi_4773_;
+        let _e704 = i_tangent_1;
+        let _e705 = normalize(_e704);
+        phi_5065_ = (_e574 * _e575);
+        phi_5047_ = (_e654 * _e654);
+        phi_5020_ = normalize(_e464);
+        phi_4976_ = ((_e574 * _e564) + vec3<f32>((((0.1599999964237213 * _e573) * _e573) * _e575)));
+        phi_4950_ = _e664;
+        phi_4839_ = _e688;
+        phi_4825_ = ((_e705 * _e690.x) + (cross(normalize(_e702), _e705) * _e690.y));
+        phi_4779_ = _e560;
+    }
+    let _e713 = phi_5065_;
+    let _e715 = phi_5047_;
+    let _e717 = phi_5020_;
+    let _e719 = phi_4976_;
+    let _e721 = phi_4950_;
+    let _e723 = phi_4839_;
+    let _e725 = phi_4825_;
+    let _e727 = phi_4779_;
+    let _e728 = i_tangent_1;
+    let _e729 = normalize(_e728);
+    let _e732 = unnamed.uniforms.inv_view;
+    let _e733 = i_view_position_1;
+    let _e737 = unnamed.uniforms.clip_plane_count;
+    let _e739 = unnamed.uniforms.clip_planes;
+    local[0] = _e739[0];
+    local[1] = _e739[1];
+    local[2] = _e739[2];
+    local[3] = _e739[3];
+    switch(bitcast<i32>(0u)) {
+        default: {
+            phi_5109_ = 0u;
+            loop {
+                let _e750 = phi_5109_;
+                phi_5113_ = false;
+                phi_5110_ = false;
+                if ((_e750 < _e737)) {
+                    let _e753 = local[_e750];
+                    if ((dot(_e753, (_e732 * _e733)) < 0.0)) {
+                        phi_5113_ = true;
+                        phi_5110_ = true;
+                        break;
+                    }
+                    continue;
+                } else {
+                    break;
+                }
+                continuing {
+                    phi_5109_ = (_e750 + bitcast<u32>(1));
+                }
+            }
+            let _e759 = phi_5113_;
+            let _e761 = phi_5110_;
+            phi_5112_ = _e759;
+            if (_e761) {
+                break;
+            }
+            phi_5112_ = false;
+            break;
+        }
+    }
+    let _e763 = phi_5112_;
+    phi_2168_ = _e763;
+    if (!(_e763)) {
+        let _e765 = i_fade_1;
+        let _e766 = gl_FragCoord_1;
+        switch(bitcast<i32>(0u)) {
+            default: {
+                let _e770 = (vec2<u32>(_e766.xy) % vec2<u32>(4u, 4u));
+                local_1 = array<f32,16u>(0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+                let _e776 = local_1[((_e770.y * 4u) + _e770.x)];
+                let _e778 = ((_e776 + 0.5) * 0.0625);
+                if ((_e765 >= 0.0)) {
+                    phi_5166_ = (_e778 >= _e765);
+                    break;
+                }
+                phi_5166_ = (_e778 < (-1.0 - _e765));
+                break;
+            }
+        }
+        let _e784 = phi_5166_;
+        phi_2168_ = _e784;
+    }
+    let _e786 = phi_2168_;
+    if (_e786) {
+        discard;
+    }
+    let _e789 = unnamed_1.material.material_flags;
+    if (((_e789 & 8192u) != 0u)) {
+        o_color = _e349;
+    } else {
+        let _e794 = -(normalize(_e733.xyz));
+        let _e797 = unnamed_2.directional_light_header.total_lights;
+        phi_5168_ = _e721;
+        phi_5167_ = 0u;
+        loop {
+            let _e800 = phi_5168_;
+            let _e802 = phi_5167_;
+            local_11 = _e800;
+            local_12 = _e800;
+            local_13 = _e800;
+            if ((_e802 < min(_e797, 4u))) {
+                let _e807 = unnamed_2.directional_lights[_e802].view_proj;
+                let _e809 = ((_e807 * _e732) * _e733);
+                let _e812 = ((_e809.xy * 0.5) + vec2<f32>(0.5, 0.5));
+                let _e815 = (1.0 - _e812.y);
+                let _e818 = vec4<f32>(_e812.x, _e815, f32(_e802), _e809.z);
+                let _e819 = (_e812.x < 0.0);
+                phi_2266_ = _e819;
+                if (!(_e819)) {
+                    phi_2266_ = (_e812.x > 1.0);
+                }
+                let _e823 = phi_2266_;
+                phi_2273_ = _e823;
+                if (!(_e823)) {
+                    phi_2273_ = (_e815 < 0.0);
+                }
+                let _e827 = phi_2273_;
+                phi_2280_ = _e827;
+                if (!(_e827)) {
+                    phi_2280_ = (_e815 > 1.0);
+                }
+                let _e831 = phi_2280_;
+                phi_2287_ = _e831;
+                if (!(_e831)) {
+                    phi_2287_ = (_e809.z < -1.0);
+                }
+                let _e835 = phi_2287_;
+                phi_2294_ = _e835;
+                if (!(_e835)) {
+                    phi_2294_ = (_e809.z > 1.0);
+                }
+                let _e839 = phi_2294_;
+                if (_e839) {
+                    phi_5175_ = 1.0;
+                } else {
+                    let _e845 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e818.x, _e818.y), i32(_e818.z), _e809.z);
+                    let _e851 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e818.x, _e818.y), i32(_e818.z), _e809.z, vec2<i32>(0, 1));
+                    let _e858 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e818.x, _e818.y), i32(_e818.z), _e809.z, vec2<i32>(0, -1));
+                    let _e865 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e818.x, _e818.y), i32(_e818.z), _e809.z, vec2<i32>(1, 0));
+                    let _e872 = textureSampleCompareLevel(shadow, shadow_sampler, vec2<f32>(_e818.x, _e818.y), i32(_e818.z), _e809.z, vec2<i32>(-1, 0));
+                    phi_5175_ = (0.20000000298023224 * ((((_e845 + _e851) + _e858) + _e865) + _e872));
+                }
+                let _e876 = phi_5175_;
+                let _e877 = (_e876 * _e727);
+                let _e881 = unnamed_2.directional_lights[_e802].color;
+                let _e883 = unnamed_2.directional_lights[_e802].direction;
+                switch(bitcast<i32>(0u)) {
+                    default: {
+                        if (((_e169 & 131072u) != 0u)) {
+                            let _e889 = unnamed.uniforms.view;
+                            let _e899 = normalize((mat3x3<f32>(_e889[0].xyz, _e889[1].xyz, _e889[2].xyz) * -(_e883)));
+                            let _e901 = normalize((_e794 + _e899));
+                            let _e905 = normalize((_e729 - (_e717 * dot(_e717, _e729))));
+                            let _e915 = dot(normalize((_e905 + (_e717 * _e179.x))), _e901);
+                            let _e934 = dot(normalize((_e905 + (_e717 * _e179.y))), _e901);
+                            phi_5179_ = (((((_e713 * 0.31830987334251404) + (_e719 * (((smoothStep(-1.0, 0.0, _e915) * pow(sqrt(max((1.0 - (_e915 * _e915)), 0.0)), _e179.z)) * (_e179.z + 2.0)) * 0.15915493667125702))) + ((_e719 * _e349.xyz) * (((smoothStep(-1.0, 0.0, _e934) * pow(sqrt(max((1.0 - (_e934 * _e934)), 0.0)), _e179.w)) * (_e179.w + 2.0)) * 0.15915493667125702))) * _e881) * (clamp(((dot(_e717, _e899) * 0.75) + 0.25), 0.0, 1.0) * _e877));
+                            break;
+                        }
+                        let _e954 = unnamed.uniforms.view;
+                        let _e964 = normalize((mat3x3<f32>(_e954[0].xyz, _e954[1].xyz, _e954[2].xyz) * -(_e883)));
+                        let _e966 = normalize((_e794 + _e964));
+                        let _e968 = abs(dot(_e717, _e794));
+                        let _e969 = (_e968 + 9.999999747378752e-6);
+                        let _e971 = clamp(dot(_e717, _e964), 0.0, 1.0);
+                        let _e973 = clamp(dot(_e717, _e966), 0.0, 1.0);
+                        if ((_e723 != 0.0)) {
+                            let _e982 = normalize((_e725 - (_e717 * dot(_e717, _e725))));
+                            let _e983 = cross(_e717, _e982);
+                            let _e986 = max((_e715 * (1.0 + _e723)), 0.0010000000474974513);
+                            let _e989 = max((_e715 * (1.0 - _e723)), 0.0010000000474974513);
+                            let _e992 = (_e986 * _e989);
+                            let _e996 = vec3<f32>((_e989 * dot(_e982, _e966)), (_e986 * dot(_e983, _e966)), (_e992 * _e973));
+                            let _e998 = (_e992 / dot(_e996, _e996));
+                            phi_5177_ = clamp((0.5 / ((_e971 * length(vec3<f32>((_e986 * dot(_e982, _e794)), (_e989 * dot(_e983, _e794)), _e969))) + (_e969 * length(vec3<f32>((_e986 * dot(_e982, _e964)), (_e989 * dot(_e983, _e964)), _e971))))), 0.0, 1.0);
+                            phi_5176_ = (((_e992 * _e998) * _e998) * 0.31830987334251404);
+                        } else {
+                            let _e1019 = (_e715 * _e715);
+                            let _e1023 = ((((_e973 * _e1019) - _e973) * _e973) + 1.0);
+                            phi_5177_ = (0.5 / ((_e971 * sqrt((((((-9.999999747378752e-6 - _e968) * _e1019) + _e969) * _e969) + _e1019))) + (_e969 * sqrt(((((-(_e971) * _e1019) + _e971) * _e971) + _e1019)))));
+                            phi_5176_ = (_e1019 / ((3.1415927410125732 * _e1023) * _e1023));
+                        }
+                        let _e1044 = phi_5177_;
+                        let _e1046 = phi_5176_;
+                        let _e1057 = ((_e713 * 0.31830987334251404) + (((_e719 + ((vec3<f32>(clamp(dot(_e719, vec3<f32>(16.5, 16.5, 16.5)), 0.0, 1.0)) - _e719) * pow((1.0 - clamp(dot(_e964, _e966), 0.0, 1.0)), 5.0))) * (_e1046 * _e1044)) * 1.0));
+                        phi_5178_ = _e1057;
+                        if ((max(_e181.x, max(_e181.y, _e181.z)) > 0.0)) {
+                            let _e1065 = max(_e181.w, 0.07000000029802322);
+                            let _e1067 = (_e1065 * _e1065);
+                            phi_5178_ = (_e1057 + (_e181.xyz * ((((2.0 + (1.0 / _e1067)) * pow(max((1.0 - (_e973 * _e973)), 0.0078125), (0.5 / _e1067))) * 0.15915493667125702) * clamp((0.25 / ((_e971 + _e969) - (_e971 * _e969))), 0.0, 1.0))));
+                        }
+                        let _e1086 = phi_5178_;
+                        phi_5179_ = ((_e1086 * _e881) * (_e971 * _e877));
+                        break;
+                    }
+                }
+                let _e1091 = phi_5179_;
+                local_15 = (_e800 + _e1091);
+                continue;
+            } else {
+                break;
+            }
+            continuing {
+                let _e1214 = local_15;
+                phi_5168_ = _e1214;
+                phi_5167_ = (_e802 + bitcast<u32>(1));
+            }
+        }
+        let _e1097 = local_11;
+        let _e1100 = local_12;
+        let _e1103 = local_13;
+        let _e1108 = unnamed.uniforms.ambient;
+        o_color = max(vec4<f32>(_e1097.x, _e1100.y, _e1103.z, _e349.w), (_e1108 * _e349));
+        let _e1113 = unnamed_1.material.subsurface_profile;
+        if ((_e1113 != 0u)) {
+            o_color[3u] = (f32(_e1113) + 1.0);
+        }
+    }
+    return;
+}
+
+[[stage(fragment)]]
+fn main([[builtin(front_facing)]] gl_FrontFacing: bool, [[location(1)]] i_normal: vec3<f32>, [[location(2)]] i_tangent: vec3<f32>, [[location(0)]] i_view_position: vec4<f32>, [[location(3)]] i_coords0_: vec2<f32>, [[location(5)]] i_color: vec4<f32>, [[location(8), interpolate(flat)]] i_fade: f32, [[builtin(position)]] gl_FragCoord: vec4<f32>, [[location(4)]] i_coords1_: vec2<f32>, [[location(6)]] i_material: u32) -> [[location(0)]] vec4<f32> {
+    gl_FrontFacing_1 = gl_FrontFacing;
+    i_normal_1 = i_normal;
+    i_tangent_1 = i_tangent;
+    i_view_position_1 = i_view_position;
+    i_coords0_1 = i_coords0_;
+    i_color_1 = i_color;
+    i_fade_1 = i_fade;
+    gl_FragCoord_1 = gl_FragCoord;
+    i_coords1_1 = i_coords1_;
+    i_material_1 = i_material;
+    main_1();
+    let _e21 = o_color;
+    return _e21;
+}
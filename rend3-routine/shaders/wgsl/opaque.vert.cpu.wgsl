@@ -2,38 +2,78 @@ struct ObjectOutputData {
     model_view: mat4x4<f32>;
     model_view_proj: mat4x4<f32>;
     material_idx: u32;
+    texture_layer: u32;
+    joint_offset: u32;
     inv_squared_scale: vec3<f32>;
+    fade: f32;
 };
 
 struct ObjectOutputDataBuffer {
     object_output: [[stride(160)]] array<ObjectOutputData>;
 };
 
+struct Plane {
+    inner: vec4<f32>;
+};
+
+struct Frustum {
+    left: Plane;
+    right: Plane;
+    top: Plane;
+    bottom: Plane;
+    near: Plane;
+};
+
+struct UniformData {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    origin_view_proj: mat4x4<f32>;
+    inv_view: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_origin_view_proj: mat4x4<f32>;
+    frustum: Frustum;
+    ambient: vec4<f32>;
+    resolution: vec2<u32>;
+    clip_plane_count: u32;
+    vertex_quantization: u32;
+    clip_planes: [[stride(16)]] array<vec4<f32>,4u>;
+};
+
+struct UniformBuffer {
+    uniforms: UniformData;
+};
+
 struct gl_PerVertex {
     [[builtin(position)]] gl_Position: vec4<f32>;
 };
 
 struct VertexOutput {
     [[location(6)]] member: u32;
-    [[location(0)]] member_1: vec4<f32>;
-    [[location(1)]] member_2: vec3<f32>;
-    [[location(2)]] member_3: vec3<f32>;
-    [[location(5)]] member_4: vec4<f32>;
-    [[location(3)]] member_5: vec2<f32>;
-    [[location(4)]] member_6: vec2<f32>;
+    [[location(7)]] member_1: u32;
+    [[location(8), interpolate(flat)]] member_2: f32;
+    [[location(0)]] member_3: vec4<f32>;
+    [[location(1)]] member_4: vec3<f32>;
+    [[location(2)]] member_5: vec3<f32>;
+    [[location(5)]] member_6: vec4<f32>;
+    [[location(3)]] member_7: vec2<f32>;
+    [[location(4)]] member_8: vec2<f32>;
     [[builtin(position)]] gl_Position: vec4<f32>;
 };
 
-var<private> gl_InstanceIndex_1: i32;
 [[group(1), binding(0)]]
 var<storage> unnamed: ObjectOutputDataBuffer;
+var<private> gl_InstanceIndex_1: i32;
 var<private> o_material: u32;
-var<private> o_view_position: vec4<f32>;
+var<private> o_texture_layer: u32;
+var<private> o_fade: f32;
 var<private> i_position_1: vec3<f32>;
-var<private> o_normal: vec3<f32>;
+[[group(0), binding(3)]]
+var<uniform> unnamed_1: UniformBuffer;
 var<private> i_normal_1: vec3<f32>;
-var<private> o_tangent: vec3<f32>;
 var<private> i_tangent_1: vec3<f32>;
+var<private> o_view_position: vec4<f32>;
+var<private> o_normal: vec3<f32>;
+var<private> o_tangent: vec3<f32>;
 var<private> o_color: vec4<f32>;
 var<private> i_color_1: vec4<f32>;
 var<private> o_coords0_: vec2<f32>;
@@ -43,24 +83,69 @@ var<private> i_coords1_1: vec2<f32>;
 var<private> perVertexStruct: gl_PerVertex = gl_PerVertex(vec4<f32>(0.0, 0.0, 0.0, 1.0), );
 
 fn main_1() {
-    let _e23 = gl_InstanceIndex_1;
-    let _e27 = unnamed.object_output[bitcast<u32>(_e23)];
-    o_material = _e27.material_idx;
-    let _e32 = i_position_1;
-    let _e36 = vec4<f32>(_e32.x, _e32.y, _e32.z, 1.0);
-    o_view_position = (_e27.model_view * _e36);
-    let _e44 = mat3x3<f32>(_e27.model_view[0].xyz, _e27.model_view[1].xyz, _e27.model_view[2].xyz);
-    let _e45 = i_normal_1;
-    o_normal = normalize((_e44 * (_e27.inv_squared_scale * _e45)));
-    let _e49 = i_tangent_1;
-    o_tangent = normalize((_e44 * (_e27.inv_squared_scale * _e49)));
-    let _e53 = i_color_1;
-    o_color = _e53;
-    let _e54 = i_coords0_1;
-    o_coords0_ = _e54;
-    let _e55 = i_coords1_1;
-    o_coords1_ = _e55;
-    perVertexStruct.gl_Position = (_e27.model_view_proj * _e36);
+    var phi_520_: vec3<f32>;
+    var local: bool;
+    var phi_521_: vec3<f32>;
+
+    let _e38 = gl_InstanceIndex_1;
+    let _e42 = unnamed.object_output[_e38].model_view;
+    let _e44 = unnamed.object_output[_e38].model_view_proj;
+    let _e46 = unnamed.object_output[_e38].material_idx;
+    let _e48 = unnamed.object_output[_e38].texture_layer;
+    let _e50 = unnamed.object_output[_e38].inv_squared_scale;
+    let _e52 = unnamed.object_output[_e38].fade;
+    o_material = _e46;
+    o_texture_layer = _e48;
+    o_fade = _e52;
+    let _e53 = i_position_1;
+    let _e57 = vec4<f32>(_e53.x, _e53.y, _e53.z, 1.0);
+    let _e60 = unnamed_1.uniforms.vertex_quantization;
+    let _e61 = i_normal_1;
+    switch(bitcast<i32>(0u)) {
+        default: {
+            let _e64 = ((_e60 & 1u) == 0u);
+            local = _e64;
+            if (_e64) {
+                phi_520_ = _e61;
+                break;
+            }
+            let _e70 = ((1.0 - abs(_e61.x)) - abs(_e61.y));
+            let _e71 = vec3<f32>(_e61.x, _e61.y, _e70);
+            let _e74 = _e71.xy;
+            let _e78 = (_e74 - (select(vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), (_e74 >= vec2<f32>(0.0, 0.0))) * max(-(_e70), 0.0)));
+            phi_520_ = vec3<f32>(_e78.x, _e78.y, _e71.z);
+            break;
+        }
+    }
+    let _e84 = phi_520_;
+    let _e85 = i_tangent_1;
+    switch(bitcast<i32>(0u)) {
+        default: {
+            let _e88 = local;
+            if (_e88) {
+                phi_521_ = _e85;
+                break;
+            }
+            let _e94 = ((1.0 - abs(_e85.x)) - abs(_e85.y));
+            let _e95 = vec3<f32>(_e85.x, _e85.y, _e94);
+            let _e98 = _e95.xy;
+            let _e102 = (_e98 - (select(vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), (_e98 >= vec2<f32>(0.0, 0.0))) * max(-(_e94), 0.0)));
+            phi_521_ = vec3<f32>(_e102.x, _e102.y, _e95.z);
+            break;
+        }
+    }
+    let _e108 = phi_521_;
+    o_view_position = (_e42 * _e57);
+    let _e116 = mat3x3<f32>(_e42[0].xyz, _e42[1].xyz, _e42[2].xyz);
+    o_normal = normalize((_e116 * (_e50 * _e84)));
+    o_tangent = normalize((_e116 * (_e50 * _e108)));
+    let _e123 = i_color_1;
+    o_color = _e123;
+    let _e124 = i_coords0_1;
+    o_coords0_ = _e124;
+    let _e125 = i_coords1_1;
+    o_coords1_ = _e125;
+    perVertexStruct.gl_Position = (_e44 * _e57);
     return;
 }
 
@@ -74,13 +159,15 @@ fn main([[builtin(instance_index)]] gl_InstanceIndex: u32, [[location(0)]] i_pos
     i_coords0_1 = i_coords0_;
     i_coords1_1 = i_coords1_;
     main_1();
-    let _e24 = o_material;
-    let _e25 = o_view_position;
-    let _e26 = o_normal;
-    let _e27 = o_tangent;
-    let _e28 = o_color;
-    let _e29 = o_coords0_;
-    let _e30 = o_coords1_;
-    let _e31 = perVertexStruct.gl_Position;
-    return VertexOutput(_e24, _e25, _e26, _e27, _e28, _e29, _e30, _e31);
+    let _e26 = o_material;
+    let _e27 = o_texture_layer;
+    let _e28 = o_fade;
+    let _e29 = o_view_position;
+    let _e30 = o_normal;
+    let _e31 = o_tangent;
+    let _e32 = o_color;
+    let _e33 = o_coords0_;
+    let _e34 = o_coords1_;
+    let _e35 = perVertexStruct.gl_Position;
+    return VertexOutput(_e26, _e27, _e28, _e29, _e30, _e31, _e32, _e33, _e34, _e35);
 }
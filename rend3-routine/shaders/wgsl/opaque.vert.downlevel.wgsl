@@ -0,0 +1,170 @@
+struct ObjectOutputData {
+    model_view: mat4x4<f32>;
+    model_view_proj: mat4x4<f32>;
+    material_idx: u32;
+    texture_layer: u32;
+    joint_offset: u32;
+    inv_squared_scale: vec3<f32>;
+    fade: f32;
+};
+
+struct ObjectOutputDataBuffer {
+    object_output: ObjectOutputData;
+};
+
+struct Plane {
+    inner: vec4<f32>;
+};
+
+struct Frustum {
+    left: Plane;
+    right: Plane;
+    top: Plane;
+    bottom: Plane;
+    near: Plane;
+};
+
+struct UniformData {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    origin_view_proj: mat4x4<f32>;
+    inv_view: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_origin_view_proj: mat4x4<f32>;
+    frustum: Frustum;
+    ambient: vec4<f32>;
+    resolution: vec2<u32>;
+    clip_plane_count: u32;
+    vertex_quantization: u32;
+    clip_planes: [[stride(16)]] array<vec4<f32>,4u>;
+};
+
+struct UniformBuffer {
+    uniforms: UniformData;
+};
+
+struct gl_PerVertex {
+    [[builtin(position)]] gl_Position: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[location(6)]] member: u32;
+    [[location(7)]] member_1: u32;
+    [[location(8), interpolate(flat)]] member_2: f32;
+    [[location(0)]] member_3: vec4<f32>;
+    [[location(1)]] member_4: vec3<f32>;
+    [[location(2)]] member_5: vec3<f32>;
+    [[location(5)]] member_6: vec4<f32>;
+    [[location(3)]] member_7: vec2<f32>;
+    [[location(4)]] member_8: vec2<f32>;
+    [[builtin(position)]] gl_Position: vec4<f32>;
+};
+
+[[group(1), binding(0)]]
+var<uniform> unnamed: ObjectOutputDataBuffer;
+var<private> o_material: u32;
+var<private> o_texture_layer: u32;
+var<private> o_fade: f32;
+var<private> i_position_1: vec3<f32>;
+[[group(0), binding(3)]]
+var<uniform> unnamed_1: UniformBuffer;
+var<private> i_normal_1: vec3<f32>;
+var<private> i_tangent_1: vec3<f32>;
+var<private> o_view_position: vec4<f32>;
+var<private> o_normal: vec3<f32>;
+var<private> o_tangent: vec3<f32>;
+var<private> o_color: vec4<f32>;
+var<private> i_color_1: vec4<f32>;
+var<private> o_coords0_: vec2<f32>;
+var<private> i_coords0_1: vec2<f32>;
+var<private> o_coords1_: vec2<f32>;
+var<private> i_coords1_1: vec2<f32>;
+var<private> perVertexStruct: gl_PerVertex = gl_PerVertex(vec4<f32>(0.0, 0.0, 0.0, 1.0), );
+
+fn main_1() {
+    var phi_512_: vec3<f32>;
+    var local: bool;
+    var phi_513_: vec3<f32>;
+
+    let _e39 = unnamed.object_output.model_view;
+    let _e41 = unnamed.object_output.model_view_proj;
+    let _e43 = unnamed.object_output.material_idx;
+    let _e45 = unnamed.object_output.texture_layer;
+    let _e47 = unnamed.object_output.inv_squared_scale;
+    let _e49 = unnamed.object_output.fade;
+    o_material = _e43;
+    o_texture_layer = _e45;
+    o_fade = _e49;
+    let _e50 = i_position_1;
+    let _e54 = vec4<f32>(_e50.x, _e50.y, _e50.z, 1.0);
+    let _e57 = unnamed_1.uniforms.vertex_quantization;
+    let _e58 = i_normal_1;
+    switch(bitcast<i32>(0u)) {
+        default: {
+            let _e61 = ((_e57 & 1u) == 0u);
+            local = _e61;
+            if (_e61) {
+                phi_512_ = _e58;
+                break;
+            }
+            let _e67 = ((1.0 - abs(_e58.x)) - abs(_e58.y));
+            let _e68 = vec3<f32>(_e58.x, _e58.y, _e67);
+            let _e71 = _e68.xy;
+            let _e75 = (_e71 - (select(vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), (_e71 >= vec2<f32>(0.0, 0.0))) * max(-(_e67), 0.0)));
+            phi_512_ = vec3<f32>(_e75.x, _e75.y, _e68.z);
+            break;
+        }
+    }
+    let _e81 = phi_512_;
+    let _e82 = i_tangent_1;
+    switch(bitcast<i32>(0u)) {
+        default: {
+            let _e85 = local;
+            if (_e85) {
+                phi_513_ = _e82;
+                break;
+            }
+            let _e91 = ((1.0 - abs(_e82.x)) - abs(_e82.y));
+            let _e92 = vec3<f32>(_e82.x, _e82.y, _e91);
+            let _e95 = _e92.xy;
+            let _e99 = (_e95 - (select(vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), (_e95 >= vec2<f32>(0.0, 0.0))) * max(-(_e91), 0.0)));
+            phi_513_ = vec3<f32>(_e99.x, _e99.y, _e92.z);
+            break;
+        }
+    }
+    let _e105 = phi_513_;
+    o_view_position = (_e39 * _e54);
+    let _e113 = mat3x3<f32>(_e39[0].xyz, _e39[1].xyz, _e39[2].xyz);
+    o_normal = normalize((_e113 * (_e47 * _e81)));
+    o_tangent = normalize((_e113 * (_e47 * _e105)));
+    let _e120 = i_color_1;
+    o_color = _e120;
+    let _e121 = i_coords0_1;
+    o_coords0_ = _e121;
+    let _e122 = i_coords1_1;
+    o_coords1_ = _e122;
+    perVertexStruct.gl_Position = (_e41 * _e54);
+    return;
+}
+
+[[stage(vertex)]]
+fn main([[location(0)]] i_position: vec3<f32>, [[location(1)]] i_normal: vec3<f32>, [[location(2)]] i_tangent: vec3<f32>, [[location(5)]] i_color: vec4<f32>, [[location(3)]] i_coords0_: vec2<f32>, [[location(4)]] i_coords1_: vec2<f32>) -> VertexOutput {
+    i_position_1 = i_position;
+    i_normal_1 = i_normal;
+    i_tangent_1 = i_tangent;
+    i_color_1 = i_color;
+    i_coords0_1 = i_coords0_;
+    i_coords1_1 = i_coords1_;
+    main_1();
+    let _e23 = o_material;
+    let _e24 = o_texture_layer;
+    let _e25 = o_fade;
+    let _e26 = o_view_position;
+    let _e27 = o_normal;
+    let _e28 = o_tangent;
+    let _e29 = o_color;
+    let _e30 = o_coords0_;
+    let _e31 = o_coords1_;
+    let _e32 = perVertexStruct.gl_Position;
+    return VertexOutput(_e23, _e24, _e25, _e26, _e27, _e28, _e29, _e30, _e31, _e32);
+}
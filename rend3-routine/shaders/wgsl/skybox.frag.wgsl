@@ -20,6 +20,9 @@ struct UniformData {
     frustum: Frustum;
     ambient: vec4<f32>;
     resolution: vec2<u32>;
+    clip_plane_count: u32;
+    vertex_quantization: u32;
+    clip_planes: [[stride(16)]] array<vec4<f32>,4u>;
 };
 
 struct UniformBuffer {
@@ -36,11 +39,11 @@ var primary_sampler: sampler;
 var<private> o_color: vec4<f32>;
 
 fn main_1() {
-    let _e12 = i_clip_position_1;
-    let _e18 = unnamed.uniforms.inv_origin_view_proj;
-    let _e19 = (_e18 * vec4<f32>(_e12.x, _e12.y, 1.0, 1.0));
-    let _e25 = textureSample(skybox, primary_sampler, normalize((_e19.xyz / vec3<f32>(_e19.w))));
-    o_color = vec4<f32>(_e25.x, _e25.y, _e25.z, 1.0);
+    let _e13 = i_clip_position_1;
+    let _e19 = unnamed.uniforms.inv_origin_view_proj;
+    let _e20 = (_e19 * vec4<f32>(_e13.x, _e13.y, 1.0, 1.0));
+    let _e26 = textureSample(skybox, primary_sampler, normalize((_e20.xyz / vec3<f32>(_e20.w))));
+    o_color = vec4<f32>(_e26.x, _e26.y, _e26.z, 1.0);
     return;
 }
 
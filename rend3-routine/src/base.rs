@@ -3,10 +3,14 @@
 //! This is a fully put together pipeline to render with rend3. If you don't
 //! need any customization, this should be drop in without worrying about it.
 //!
-//! In order to start customizing it, copy the contents of
-//! [`BaseRenderGraph::add_to_graph`] into your own code and start modifying it.
-//! This will allow you to insert your own routines and customize the behavior
-//! of the existing routines.
+//! The graph is built in [`BasePhase`]s. To add your own nodes between them,
+//! or replace one of them, pass [`BaseRenderGraphHooks`] to
+//! [`BaseRenderGraph::add_to_graph_with_hooks`].
+//!
+//! For deeper changes, copy the contents of
+//! [`BaseRenderGraph::add_to_graph_with_hooks`] into your own code and start
+//! modifying it. This will allow you to insert your own routines and customize
+//! the behavior of the existing routines.
 //!
 //! [`BaseRenderGraphIntermediateState`] intentionally has all of its members
 //! public. If you want to change what rendergraph image things are rendering
@@ -18,26 +22,284 @@ use glam::{UVec2, Vec4};
 use rend3::{
     format_sso,
     graph::{DataHandle, ReadyData, RenderGraph, RenderTargetDescriptor, RenderTargetHandle},
-    types::{SampleCount, TextureFormat, TextureUsages},
-    ProfileData, Renderer,
+    managers::{SkinningMode, VertexAttributes, VertexQuantization},
+    types::{DepthMode, ObjectDepthMode, SampleCount, TextureFormat, TextureUsages},
+    ProfileData, Renderer, RendererProfile,
 };
 use wgpu::{BindGroup, Buffer};
 
 use crate::{
-    common, culling, pbr,
+    common::{self, Sorting},
+    crowd, culling, impostor, lighting_debug, particles, pbr,
     skinning::{self, GpuSkinner, SkinningOutput},
-    skybox, tonemapping,
+    skybox, sprite, supersample, tonemapping, transmission, volume,
 };
 
 /// Handles and information for a single type of transparency in the PBR
 /// pipeline.
 pub struct PerTransparencyInfo {
     ty: pbr::TransparencyType,
+    double_sided: bool,
+    depth_mode: ObjectDepthMode,
     pre_cull: DataHandle<Buffer>,
     shadow_cull: Vec<DataHandle<culling::PerMaterialArchetypeData>>,
     cull: DataHandle<culling::PerMaterialArchetypeData>,
 }
 
+impl PerTransparencyInfo {
+    fn key(&self) -> u64 {
+        self.ty.to_object_key(self.double_sided) | self.depth_mode.to_key_bits()
+    }
+
+    fn name(&self) -> String {
+        let name = match self.double_sided {
+            true => format!("{:?} Double Sided", self.ty),
+            false => format!("{:?}", self.ty),
+        };
+        match self.depth_mode {
+            ObjectDepthMode::Default => name,
+            depth_mode => format!("{} {:?}", name, depth_mode),
+        }
+    }
+
+    /// Order the culling of this archetype draws objects in.
+    ///
+    /// Opaque and cutout objects are drawn front to back when culling on the
+    /// CPU, so the depth test rejects hidden fragments before shading them.
+    /// GPU culling would need its sort on the GPU, so leaves them in
+    /// archetype order, as do objects drawn on top, which aren't depth tested.
+    /// `profile` is the one of the culler, where Downlevel counts as CpuDriven.
+    fn sorting(&self, profile: RendererProfile) -> Option<Sorting> {
+        match self.ty.to_sorting() {
+            None if profile == RendererProfile::CpuDriven => match self.depth_mode {
+                ObjectDepthMode::AlwaysOnTop => None,
+                _ => Some(Sorting::FrontToBack),
+            },
+            sorting => sorting,
+        }
+    }
+
+    /// Blended objects and objects drawn on top don't cast shadows or write
+    /// depth.
+    fn casts_depth(&self) -> bool {
+        self.ty != pbr::TransparencyType::Blend && self.depth_mode != ObjectDepthMode::AlwaysOnTop
+    }
+}
+
+/// Phases of the [`BaseRenderGraph`], in the order they are added to the
+/// graph. Uploads, skinning, culling and clearing the targets come before all
+/// of them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BasePhase {
+    /// Shadow maps of the directional lights.
+    Shadows,
+    /// Depth prepass of the PBR materials which write depth.
+    Prepass,
+    Skybox,
+    /// Opaque and cutout PBR materials.
+    ForwardOpaque,
+    /// Blended PBR materials, then objects drawn on top of everything.
+    Transparent,
+    /// Empty by default. Effects drawing into or reading the HDR targets
+    /// before tonemapping go here.
+    Post,
+    /// Tonemapping the HDR targets onto the surface.
+    Tonemap,
+}
+
+impl BasePhase {
+    pub const ALL: [Self; PHASE_COUNT] = [
+        Self::Shadows,
+        Self::Prepass,
+        Self::Skybox,
+        Self::ForwardOpaque,
+        Self::Transparent,
+        Self::Post,
+        Self::Tonemap,
+    ];
+}
+
+const PHASE_COUNT: usize = 7;
+
+/// Adds nodes to the graph, reading and writing the targets of the base
+/// rendergraph through the intermediate state.
+pub type BasePhaseHook<'node> = Box<dyn FnOnce(&mut RenderGraph<'node>, &BaseRenderGraphIntermediateState) + 'node>;
+
+/// Nodes to add around the [`BasePhase`]s of the base rendergraph, or
+/// instead of them.
+///
+/// Hooks on the same side of a phase run in the order they were added.
+#[derive(Default)]
+pub struct BaseRenderGraphHooks<'node> {
+    before: [Vec<BasePhaseHook<'node>>; PHASE_COUNT],
+    after: [Vec<BasePhaseHook<'node>>; PHASE_COUNT],
+    replace: [Option<BasePhaseHook<'node>>; PHASE_COUNT],
+}
+
+impl<'node> BaseRenderGraphHooks<'node> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add nodes right before `phase`.
+    pub fn before(
+        mut self,
+        phase: BasePhase,
+        hook: impl FnOnce(&mut RenderGraph<'node>, &BaseRenderGraphIntermediateState) + 'node,
+    ) -> Self {
+        self.before[phase as usize].push(Box::new(hook));
+        self
+    }
+
+    /// Add nodes right after `phase`.
+    pub fn after(
+        mut self,
+        phase: BasePhase,
+        hook: impl FnOnce(&mut RenderGraph<'node>, &BaseRenderGraphIntermediateState) + 'node,
+    ) -> Self {
+        self.after[phase as usize].push(Box::new(hook));
+        self
+    }
+
+    /// Add these nodes instead of the ones of `phase`. Hooks before and after
+    /// the phase still run. Replacing a phase twice keeps the last hook.
+    pub fn replace(
+        mut self,
+        phase: BasePhase,
+        hook: impl FnOnce(&mut RenderGraph<'node>, &BaseRenderGraphIntermediateState) + 'node,
+    ) -> Self {
+        self.replace[phase as usize] = Some(Box::new(hook));
+        self
+    }
+
+    fn run(
+        &mut self,
+        phase: BasePhase,
+        graph: &mut RenderGraph<'node>,
+        state: &BaseRenderGraphIntermediateState,
+        default: impl FnOnce(&mut RenderGraph<'node>),
+    ) {
+        let index = phase as usize;
+        for hook in self.before[index].drain(..) {
+            hook(graph, state);
+        }
+        match self.replace[index].take() {
+            Some(hook) => hook(graph, state),
+            None => default(graph),
+        }
+        for hook in self.after[index].drain(..) {
+            hook(graph, state);
+        }
+    }
+}
+
+/// Routines the [`BaseRenderGraph`] draws with.
+#[derive(Clone, Copy)]
+pub struct BaseRenderGraphRoutines<'node> {
+    pub pbr: &'node pbr::PbrRoutine,
+    pub skybox: Option<&'node skybox::SkyboxRoutine>,
+    pub tonemapping: &'node tonemapping::TonemappingRoutine,
+}
+
+/// How the [`BaseRenderGraph`] renders a frame.
+///
+/// Start from [`BaseRenderGraphSettings::new`] so options added later get
+/// their defaults.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BaseRenderGraphSettings {
+    pub resolution: UVec2,
+    pub samples: SampleCount,
+    pub ambient: Vec4,
+    pub clear_color: Vec4,
+    /// Clears the shadow maps instead of rendering them, leaving every
+    /// directional light unshadowed.
+    pub skip_shadows: bool,
+    /// Leaves out the [`BasePhase::Transparent`] phase: blended PBR materials
+    /// and objects drawn on top of everything.
+    pub skip_transparency: bool,
+    /// Format of the HDR depth target. Every pipeline drawing into it must
+    /// have been created for the same format. The routines of this crate use
+    /// [`TextureFormat::Depth32Float`], apart from the PBR passes, which
+    /// follow [`RenderStateOverride::depth_format`].
+    ///
+    /// [`RenderStateOverride::depth_format`]: crate::common::RenderStateOverride::depth_format
+    pub depth_format: TextureFormat,
+    /// Factor the HDR targets are larger than `resolution` by on each axis,
+    /// from 1 to [`MAX_SUPERSAMPLING`](supersample::MAX_SUPERSAMPLING). They
+    /// are filtered down to `resolution` before tonemapping, anti-aliasing
+    /// what MSAA can't, at the cost of shading factor squared times as many
+    /// pixels. The two can be combined.
+    pub supersampling: u32,
+}
+
+impl BaseRenderGraphSettings {
+    /// Settings rendering at `resolution` without MSAA or ambient light,
+    /// cleared to black.
+    pub fn new(resolution: UVec2) -> Self {
+        Self {
+            resolution,
+            samples: SampleCount::One,
+            ambient: Vec4::ZERO,
+            clear_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            skip_shadows: false,
+            skip_transparency: false,
+            depth_format: TextureFormat::Depth32Float,
+            supersampling: 1,
+        }
+    }
+
+    /// Settings rendering at `resolution` with the sample count set with
+    /// [`Renderer::set_sample_count`], so switching MSAA there applies to
+    /// the next graph.
+    pub fn for_renderer(renderer: &Renderer, resolution: UVec2) -> Self {
+        Self {
+            samples: renderer.sample_count(),
+            ..Self::new(resolution)
+        }
+    }
+
+    pub fn with_samples(mut self, samples: SampleCount) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_ambient(mut self, ambient: Vec4) -> Self {
+        self.ambient = ambient;
+        self
+    }
+
+    pub fn with_clear_color(mut self, clear_color: Vec4) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    pub fn with_skip_shadows(mut self, skip_shadows: bool) -> Self {
+        self.skip_shadows = skip_shadows;
+        self
+    }
+
+    pub fn with_skip_transparency(mut self, skip_transparency: bool) -> Self {
+        self.skip_transparency = skip_transparency;
+        self
+    }
+
+    pub fn with_depth_format(mut self, depth_format: TextureFormat) -> Self {
+        self.depth_format = depth_format;
+        self
+    }
+
+    pub fn with_supersampling(mut self, supersampling: u32) -> Self {
+        self.supersampling = supersampling;
+        self
+    }
+
+    /// Resolution the HDR targets are rendered at, `resolution` times the
+    /// supersampling factor.
+    pub fn render_resolution(&self) -> UVec2 {
+        self.resolution * self.supersampling.clamp(1, supersample::MAX_SUPERSAMPLING)
+    }
+}
+
 /// Starter RenderGraph.
 ///
 /// See module for documentation.
@@ -45,77 +307,150 @@ pub struct BaseRenderGraph {
     pub interfaces: common::WholeFrameInterfaces,
     pub samplers: common::Samplers,
     pub gpu_culler: ProfileData<(), culling::GpuCuller>,
-    pub gpu_skinner: GpuSkinner,
+    /// None with [`SkinningMode::VertexShader`], and when pre-skinning isn't
+    /// supported: in the downlevel profile, which can't run compute shaders,
+    /// and if the renderer stores no joints.
+    pub gpu_skinner: Option<GpuSkinner>,
+    /// Depth mode of the renderer, which decides what depth targets are
+    /// cleared to.
+    pub depth_mode: DepthMode,
+    /// Draws issued by the culling of the last rendergraph built with
+    /// [`BaseRenderGraph::add_to_graph`].
+    pub draw_statistics: culling::DrawStatistics,
+    /// World space planes cutting open everything on their negative side, in
+    /// the depth, shadow and forward passes of PBR materials. At most
+    /// [`MAX_CLIP_PLANES`](crate::uniforms::MAX_CLIP_PLANES).
+    pub clip_planes: Vec<Vec4>,
+    pub supersampler: supersample::SupersampleRoutine,
 }
 
 impl BaseRenderGraph {
     pub fn new(renderer: &Renderer) -> Self {
         profiling::scope!("DefaultRenderGraphData::new");
 
-        let interfaces = common::WholeFrameInterfaces::new(&renderer.device);
+        let interfaces = common::WholeFrameInterfaces::new(&renderer.device, renderer.profile);
 
-        let samplers = common::Samplers::new(&renderer.device);
+        let samplers = common::Samplers::new(&renderer.device, renderer.depth_mode);
 
         let gpu_culler = renderer
             .profile
             .into_data(|| (), || culling::GpuCuller::new(&renderer.device));
 
-        let gpu_skinner = GpuSkinner::new(&renderer.device);
+        // The renderer already fell back from vertex shader skinning where it
+        // isn't supported.
+        let gpu_skinner = match (renderer.profile, renderer.skinning_mode) {
+            (_, SkinningMode::VertexShader) => None,
+            (RendererProfile::Downlevel, SkinningMode::PreSkinned) => {
+                log::warn!("Pre-skinning needs compute shaders, drawing skinned meshes in bind pose");
+                None
+            }
+            (_, SkinningMode::PreSkinned) if !renderer.vertex_attributes.contains(VertexAttributes::JOINTS) => {
+                log::warn!("The renderer stores no joints, drawing skinned meshes in bind pose");
+                None
+            }
+            (_, SkinningMode::PreSkinned) if renderer.vertex_quantization.contains(VertexQuantization::POSITIONS) => {
+                log::warn!("Quantized positions can't be pre-skinned, drawing skinned meshes in bind pose");
+                None
+            }
+            (RendererProfile::GpuDriven | RendererProfile::CpuDriven, SkinningMode::PreSkinned) => {
+                Some(GpuSkinner::new(&renderer.device, renderer.vertex_quantization))
+            }
+        };
 
         Self {
             interfaces,
             samplers,
             gpu_culler,
             gpu_skinner,
+            depth_mode: renderer.depth_mode,
+            draw_statistics: culling::DrawStatistics::new(),
+            clip_planes: Vec::new(),
+            supersampler: supersample::SupersampleRoutine::new(&renderer.device),
         }
     }
 
-    /// Add this to the rendergraph. This is the function you should start
-    /// customizing.
-    #[allow(clippy::too_many_arguments)]
+    /// Add this to the rendergraph.
     pub fn add_to_graph<'node>(
         &'node self,
         graph: &mut RenderGraph<'node>,
         ready: &ReadyData,
-        pbr: &'node crate::pbr::PbrRoutine,
-        skybox: Option<&'node crate::skybox::SkyboxRoutine>,
-        tonemapping: &'node crate::tonemapping::TonemappingRoutine,
-        resolution: UVec2,
-        samples: SampleCount,
-        ambient: Vec4,
-        clear_color: Vec4,
+        routines: BaseRenderGraphRoutines<'node>,
+        settings: &BaseRenderGraphSettings,
+    ) {
+        self.add_to_graph_with_hooks(graph, ready, routines, settings, BaseRenderGraphHooks::new());
+    }
+
+    /// Add this to the rendergraph, running `hooks` around its phases. This
+    /// is the function you should start customizing.
+    pub fn add_to_graph_with_hooks<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        ready: &ReadyData,
+        routines: BaseRenderGraphRoutines<'node>,
+        settings: &BaseRenderGraphSettings,
+        mut hooks: BaseRenderGraphHooks<'node>,
     ) {
+        let BaseRenderGraphRoutines {
+            pbr,
+            skybox,
+            tonemapping,
+        } = routines;
+        let samples = settings.samples;
+
+        // Start counting draws from zero
+        self.draw_statistics.reset();
+
         // Create intermediate storage
-        let state = BaseRenderGraphIntermediateState::new(graph, ready, resolution, samples);
+        let state = BaseRenderGraphIntermediateState::new(graph, ready, settings);
 
         // Preparing and uploading data
         state.pre_skinning(graph);
         state.pbr_pre_culling(graph);
-        state.create_frame_uniforms(graph, self, ambient, resolution);
+        state.create_frame_uniforms(graph, self, settings.ambient, settings.render_resolution());
 
         // Skinning
         state.skinning(graph, self);
 
         // Culling
-        state.pbr_shadow_culling(graph, self, pbr);
+        if !settings.skip_shadows {
+            state.pbr_shadow_culling(graph, self, pbr);
+        }
         state.pbr_culling(graph, self, pbr);
 
         // Clear targets
-        state.clear(graph, clear_color);
+        state.clear(graph, self, settings.clear_color);
 
         // Depth-only rendering
-        state.pbr_shadow_rendering(graph, pbr);
-        state.pbr_prepass_rendering(graph, pbr, samples);
+        hooks.run(BasePhase::Shadows, graph, &state, |graph| match settings.skip_shadows {
+            true => state.clear_shadows(graph, self),
+            false => state.pbr_shadow_rendering(graph, pbr),
+        });
+        hooks.run(BasePhase::Prepass, graph, &state, |graph| {
+            state.pbr_prepass_rendering(graph, pbr, samples)
+        });
 
         // Skybox
-        state.skybox(graph, skybox, samples);
+        hooks.run(BasePhase::Skybox, graph, &state, |graph| {
+            state.skybox(graph, skybox, samples)
+        });
 
         // Forward rendering
-        state.pbr_forward_rendering(graph, pbr, samples);
+        hooks.run(BasePhase::ForwardOpaque, graph, &state, |graph| {
+            state.pbr_forward_rendering_opaque(graph, pbr, samples)
+        });
+        hooks.run(BasePhase::Transparent, graph, &state, |graph| {
+            if !settings.skip_transparency {
+                state.pbr_forward_rendering_transparent(graph, pbr, samples)
+            }
+        });
+        hooks.run(BasePhase::Post, graph, &state, |_| {});
 
         // Make the reference to the surface
-        let surface = graph.add_surface_texture();
-        state.tonemapping(graph, tonemapping, surface);
+        hooks.run(BasePhase::Tonemap, graph, &state, |graph| {
+            let surface = graph.add_surface_texture();
+            state.supersample(graph, self);
+            state.tonemapping(graph, tonemapping, surface);
+        });
     }
 }
 
@@ -124,30 +459,56 @@ impl BaseRenderGraph {
 /// This is intentionally public so all this can be changed by the user if they
 /// so desire.
 pub struct BaseRenderGraphIntermediateState {
-    pub per_transparency: ArrayVec<PerTransparencyInfo, 3>,
-    pub shadow_uniform_bg: DataHandle<BindGroup>,
+    pub per_transparency: ArrayVec<PerTransparencyInfo, 18>,
+    /// Uniforms of each shadow, seen from its light.
+    pub shadow_uniform_bg: Vec<DataHandle<BindGroup>>,
     pub forward_uniform_bg: DataHandle<BindGroup>,
     pub color: RenderTargetHandle,
     pub resolve: Option<RenderTargetHandle>,
     pub depth: RenderTargetHandle,
+    /// The HDR color filtered down to the output resolution when
+    /// supersampling, see [`BaseRenderGraphSettings::supersampling`].
+    pub downsampled: Option<RenderTargetHandle>,
+    /// Supersampling factor the targets were created with.
+    pub supersampling: u32,
+    /// Resolution of the HDR targets, including supersampling.
+    pub resolution: UVec2,
     pub pre_skinning_buffers: DataHandle<skinning::PreSkinningBuffers>,
     pub skinned_data: DataHandle<skinning::SkinningOutput>,
 }
 impl BaseRenderGraphIntermediateState {
     /// Create the default setting for all state.
-    pub fn new(graph: &mut RenderGraph<'_>, ready: &ReadyData, resolution: UVec2, samples: SampleCount) -> Self {
+    pub fn new(graph: &mut RenderGraph<'_>, ready: &ReadyData, settings: &BaseRenderGraphSettings) -> Self {
+        let output_resolution = settings.resolution;
+        let resolution = settings.render_resolution();
+        let supersampling = settings.supersampling.clamp(1, supersample::MAX_SUPERSAMPLING);
+        let samples = settings.samples;
+
         // We need to know how many shadows we need to render
         let shadow_count = ready.directional_light_cameras.len();
 
-        // Setup all of our per-transparency data
-        let mut per_transparency = ArrayVec::new();
-        for ty in [
+        // Setup all of our per-transparency data. They are drawn in this order,
+        // so objects drawn on top come after everything else.
+        let transparencies = [
             pbr::TransparencyType::Opaque,
             pbr::TransparencyType::Cutout,
             pbr::TransparencyType::Blend,
-        ] {
+        ];
+        let depth_tested = transparencies.into_iter().flat_map(|ty| {
+            [ObjectDepthMode::Default, ObjectDepthMode::Biased]
+                .into_iter()
+                .map(move |depth_mode| (ty, depth_mode))
+        });
+        let on_top = transparencies.into_iter().map(|ty| (ty, ObjectDepthMode::AlwaysOnTop));
+        let mut per_transparency = ArrayVec::new();
+        for ((ty, depth_mode), double_sided) in depth_tested
+            .chain(on_top)
+            .flat_map(|archetype| [(archetype, false), (archetype, true)])
+        {
             per_transparency.push(PerTransparencyInfo {
                 ty,
+                double_sided,
+                depth_mode,
                 pre_cull: graph.add_data(),
                 shadow_cull: {
                     let mut shadows = Vec::with_capacity(shadow_count);
@@ -159,7 +520,7 @@ impl BaseRenderGraphIntermediateState {
         }
 
         // Create global bind group information
-        let shadow_uniform_bg = graph.add_data::<BindGroup>();
+        let shadow_uniform_bg = (0..shadow_count).map(|_| graph.add_data::<BindGroup>()).collect();
         let forward_uniform_bg = graph.add_data::<BindGroup>();
 
         // Make the actual render targets we want to render to.
@@ -183,8 +544,18 @@ impl BaseRenderGraphIntermediateState {
             label: Some("hdr depth".into()),
             resolution,
             samples,
-            format: TextureFormat::Depth32Float,
-            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: settings.depth_format,
+            // Sampled by particles.
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        });
+        let downsampled = (supersampling > 1).then(|| {
+            graph.add_render_target(RenderTargetDescriptor {
+                label: Some("hdr downsampled".into()),
+                resolution: output_resolution,
+                samples: SampleCount::One,
+                format: TextureFormat::Rgba16Float,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            })
         });
 
         let pre_skinning_buffers = graph.add_data::<skinning::PreSkinningBuffers>();
@@ -197,6 +568,9 @@ impl BaseRenderGraphIntermediateState {
             color,
             resolve,
             depth,
+            downsampled,
+            supersampling,
+            resolution,
             pre_skinning_buffers,
             skinned_data,
         }
@@ -207,9 +581,9 @@ impl BaseRenderGraphIntermediateState {
         for trans in &self.per_transparency {
             crate::pre_cull::add_to_graph::<pbr::PbrMaterial>(
                 graph,
-                trans.ty as u64,
+                trans.key(),
                 trans.ty.to_sorting(),
-                &format_sso!("{:?}", trans.ty),
+                &format_sso!("{}", trans.name()),
                 trans.pre_cull,
             );
         }
@@ -230,12 +604,13 @@ impl BaseRenderGraphIntermediateState {
     ) {
         crate::uniforms::add_to_graph(
             graph,
-            self.shadow_uniform_bg,
+            &self.shadow_uniform_bg,
             self.forward_uniform_bg,
             &base.interfaces,
             &base.samplers,
             ambient,
             resolution,
+            &base.clip_planes,
         );
     }
 
@@ -246,26 +621,33 @@ impl BaseRenderGraphIntermediateState {
         base: &'node BaseRenderGraph,
         pbr: &'node pbr::PbrRoutine,
     ) {
-        for trans in &self.per_transparency[0..2] {
+        for trans in self.per_transparency.iter().filter(|trans| trans.casts_depth()) {
             for (shadow_index, &shadow_culled) in trans.shadow_cull.iter().enumerate() {
                 crate::culling::add_culling_to_graph::<pbr::PbrMaterial>(
                     graph,
                     trans.pre_cull,
                     shadow_culled,
+                    self.pre_skinning_buffers,
                     self.skinned_data,
                     &pbr.per_material,
                     &base.gpu_culler,
+                    &base.draw_statistics,
                     Some(shadow_index),
-                    trans.ty as u64,
-                    trans.ty.to_sorting(),
-                    &format_sso!("Shadow Culling S{} {:?}", shadow_index, trans.ty),
+                    trans.key(),
+                    trans.sorting(base.gpu_culler.profile()),
+                    &format_sso!("Shadow Culling S{} {}", shadow_index, trans.name()),
                 );
             }
         }
     }
 
     pub fn skinning<'node>(&self, graph: &mut RenderGraph<'node>, base: &'node BaseRenderGraph) {
-        crate::skinning::add_skinning_to_graph(graph, &base.gpu_skinner, self.pre_skinning_buffers, self.skinned_data);
+        crate::skinning::add_skinning_to_graph(
+            graph,
+            base.gpu_skinner.as_ref(),
+            self.pre_skinning_buffers,
+            self.skinned_data,
+        );
     }
 
     /// Does all culling for the forward PBR materials.
@@ -280,33 +662,50 @@ impl BaseRenderGraphIntermediateState {
                 graph,
                 trans.pre_cull,
                 trans.cull,
+                self.pre_skinning_buffers,
                 self.skinned_data,
                 &pbr.per_material,
                 &base.gpu_culler,
+                &base.draw_statistics,
                 None,
-                trans.ty as u64,
-                trans.ty.to_sorting(),
-                &format_sso!("Primary Culling {:?}", trans.ty),
+                trans.key(),
+                trans.sorting(base.gpu_culler.profile()),
+                &format_sso!("Primary Culling {}", trans.name()),
             );
         }
     }
 
     /// Clear all the targets to their needed values
-    pub fn clear<'node>(&self, graph: &mut RenderGraph<'node>, clear_color: Vec4) {
-        crate::clear::add_clear_to_graph(graph, self.color, self.resolve, self.depth, clear_color, 0.0);
+    pub fn clear<'node>(&self, graph: &mut RenderGraph<'node>, base: &BaseRenderGraph, clear_color: Vec4) {
+        crate::clear::add_clear_to_graph(
+            graph,
+            self.color,
+            self.resolve,
+            self.depth,
+            clear_color,
+            base.depth_mode.far_depth(),
+        );
+    }
+
+    /// Clear the shadow maps without rendering anything into them.
+    pub fn clear_shadows(&self, graph: &mut RenderGraph<'_>, base: &BaseRenderGraph) {
+        for shadow_index in 0..self.shadow_uniform_bg.len() {
+            crate::clear::add_shadow_clear_to_graph(graph, shadow_index, base.depth_mode.far_depth());
+        }
     }
 
     /// Render all shadows for the PBR materials.
     pub fn pbr_shadow_rendering<'node>(&self, graph: &mut RenderGraph<'node>, pbr: &'node pbr::PbrRoutine) {
-        for trans in &self.per_transparency[0..2] {
+        for trans in self.per_transparency.iter().filter(|trans| trans.casts_depth()) {
             for (shadow_index, &shadow_culled) in trans.shadow_cull.iter().enumerate() {
-                pbr.depth_pipelines.add_shadow_rendering_to_graph(
-                    graph,
-                    matches!(trans.ty, pbr::TransparencyType::Cutout),
-                    shadow_index,
-                    self.shadow_uniform_bg,
-                    shadow_culled,
-                );
+                pbr.depth_routine(trans.double_sided, trans.depth_mode)
+                    .add_shadow_rendering_to_graph(
+                        graph,
+                        matches!(trans.ty, pbr::TransparencyType::Cutout),
+                        shadow_index,
+                        self.shadow_uniform_bg[shadow_index],
+                        shadow_culled,
+                    );
             }
         }
     }
@@ -318,17 +717,18 @@ impl BaseRenderGraphIntermediateState {
         pbr: &'node pbr::PbrRoutine,
         samples: SampleCount,
     ) {
-        for trans in &self.per_transparency[0..2] {
-            pbr.depth_pipelines.add_prepass_to_graph(
-                graph,
-                self.forward_uniform_bg,
-                trans.cull,
-                samples,
-                matches!(trans.ty, pbr::TransparencyType::Cutout),
-                self.color,
-                self.resolve,
-                self.depth,
-            );
+        for trans in self.per_transparency.iter().filter(|trans| trans.casts_depth()) {
+            pbr.depth_routine(trans.double_sided, trans.depth_mode)
+                .add_prepass_to_graph(
+                    graph,
+                    self.forward_uniform_bg,
+                    trans.cull,
+                    samples,
+                    matches!(trans.ty, pbr::TransparencyType::Cutout),
+                    self.color,
+                    self.resolve,
+                    self.depth,
+                );
         }
     }
 
@@ -351,6 +751,75 @@ impl BaseRenderGraphIntermediateState {
         }
     }
 
+    /// Render sprites, after the PBR materials so they blend over them.
+    pub fn sprites<'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        sprites: &'node sprite::SpriteRoutine,
+        batch: &'node sprite::SpriteBatch,
+        samples: SampleCount,
+    ) {
+        sprites.add_to_graph(graph, batch, self.color, self.resolve, self.depth, samples);
+    }
+
+    /// Render volumes, after the PBR materials so they blend over them.
+    pub fn volumes<'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        routine: &'node volume::VolumeRoutine,
+        volumes: &'node [volume::Volume],
+        samples: SampleCount,
+    ) {
+        routine.add_to_graph(graph, volumes, self.color, self.resolve, self.depth, samples);
+    }
+
+    /// Render crowds of skinned characters, after the PBR materials.
+    pub fn crowds<'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        routine: &'node crowd::CrowdRoutine,
+        crowds: &'node [crowd::Crowd],
+        ambient: Vec4,
+        samples: SampleCount,
+    ) {
+        routine.add_to_graph(graph, crowds, self.color, self.resolve, self.depth, ambient, samples);
+    }
+
+    /// Bake new impostors and render the distant copies of `lods` as
+    /// impostors, after the PBR materials.
+    pub fn impostors<'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        routine: &'node impostor::ImpostorRoutine,
+        lods: &'node [impostor::ImpostorLod],
+        ambient: Vec4,
+        samples: SampleCount,
+    ) {
+        routine.add_to_graph(graph, lods, self.color, self.resolve, self.depth, ambient, samples);
+    }
+
+    /// Render the lighting debug targets, after the shadow maps and the
+    /// prepass. See [`lighting_debug`] for how to show them.
+    pub fn lighting_debug<'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        routine: &'node lighting_debug::LightingDebugRoutine,
+    ) -> lighting_debug::LightingDebugOutputs {
+        routine.add_to_graph(graph, self.forward_uniform_bg, self.depth, self.resolution)
+    }
+
+    /// Simulate and render particles, after the PBR materials so they can
+    /// collide with and fade into them.
+    pub fn particles<'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        particles: &'node particles::ParticleRoutine,
+        resolution: UVec2,
+        samples: SampleCount,
+    ) {
+        particles.add_to_graph(graph, self.color, self.resolve, self.depth, resolution, samples);
+    }
+
     /// Render the PBR materials.
     pub fn pbr_forward_rendering<'node>(
         &self,
@@ -358,23 +827,97 @@ impl BaseRenderGraphIntermediateState {
         pbr: &'node pbr::PbrRoutine,
         samples: SampleCount,
     ) {
-        for trans in &self.per_transparency {
-            let inner = match trans.ty {
-                pbr::TransparencyType::Opaque => &pbr.opaque_routine,
-                pbr::TransparencyType::Cutout => &pbr.cutout_routine,
-                pbr::TransparencyType::Blend => &pbr.blend_routine,
-            };
+        self.pbr_forward_rendering_opaque(graph, pbr, samples);
+        self.pbr_forward_rendering_transparent(graph, pbr, samples);
+    }
+
+    /// Render the opaque and cutout PBR materials.
+    pub fn pbr_forward_rendering_opaque<'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        pbr: &'node pbr::PbrRoutine,
+        samples: SampleCount,
+    ) {
+        // Exactly the ones which write depth, and all of them come first.
+        let opaque = self.per_transparency.iter().filter(|trans| trans.casts_depth());
+        self.pbr_forward_rendering_of(graph, pbr, samples, opaque);
+    }
 
-            inner.add_forward_to_graph(
+    /// Render the blended PBR materials, then the objects drawn on top of
+    /// everything.
+    pub fn pbr_forward_rendering_transparent<'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        pbr: &'node pbr::PbrRoutine,
+        samples: SampleCount,
+    ) {
+        let transparent = self.per_transparency.iter().filter(|trans| !trans.casts_depth());
+        self.pbr_forward_rendering_of(graph, pbr, samples, transparent);
+    }
+
+    fn pbr_forward_rendering_of<'a, 'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        pbr: &'node pbr::PbrRoutine,
+        samples: SampleCount,
+        transparencies: impl Iterator<Item = &'a PerTransparencyInfo>,
+    ) {
+        for trans in transparencies {
+            pbr.forward_routine(trans.ty, trans.double_sided, trans.depth_mode)
+                .add_forward_to_graph(
+                    graph,
+                    self.forward_uniform_bg,
+                    trans.cull,
+                    None,
+                    &format_sso!("PBR Forward {}", trans.name()),
+                    samples,
+                    self.color,
+                    self.resolve,
+                    self.depth,
+                );
+        }
+    }
+
+    /// Draw what is seen through blended PBR materials with transmission, see
+    /// [`transmission`](crate::transmission). Belongs right before
+    /// [`BasePhase::Transparent`].
+    pub fn transmission<'node>(
+        &self,
+        graph: &mut RenderGraph<'node>,
+        transmission: &'node transmission::TransmissionRoutine,
+        samples: SampleCount,
+    ) {
+        transmission.add_scene_copy_to_graph(graph, self.resolve.unwrap_or(self.color));
+
+        let blended = self.per_transparency.iter().filter(|trans| {
+            trans.ty == pbr::TransparencyType::Blend && trans.depth_mode != ObjectDepthMode::AlwaysOnTop
+        });
+        for trans in blended {
+            transmission
+                .forward_routine(trans.double_sided, trans.depth_mode)
+                .add_forward_to_graph(
+                    graph,
+                    self.forward_uniform_bg,
+                    trans.cull,
+                    Some(transmission.extra_bgs()),
+                    &format_sso!("Transmission {}", trans.name()),
+                    samples,
+                    self.color,
+                    self.resolve,
+                    self.depth,
+                );
+        }
+    }
+
+    /// Filter the HDR color down into the downsampled target, if
+    /// supersampling.
+    pub fn supersample<'node>(&self, graph: &mut RenderGraph<'node>, base: &'node BaseRenderGraph) {
+        if let Some(downsampled) = self.downsampled {
+            base.supersampler.add_to_graph(
                 graph,
-                self.forward_uniform_bg,
-                trans.cull,
-                None,
-                &format_sso!("PBR Forward {:?}", trans.ty),
-                samples,
-                self.color,
-                self.resolve,
-                self.depth,
+                self.resolve.unwrap_or(self.color),
+                downsampled,
+                self.supersampling,
             );
         }
     }
@@ -388,7 +931,7 @@ impl BaseRenderGraphIntermediateState {
     ) {
         tonemapping.add_to_graph(
             graph,
-            self.resolve.unwrap_or(self.color),
+            self.downsampled.or(self.resolve).unwrap_or(self.color),
             target,
             self.forward_uniform_bg,
         );
@@ -1,6 +1,7 @@
 use glam::Vec4;
-use rend3::graph::{
-    DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets, RenderTargetHandle,
+use rend3::{
+    format_sso,
+    graph::{DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
 };
 
 /// Uses the given targets to create a node which merely sets the clear color to what we want.
@@ -41,3 +42,22 @@ pub fn add_clear_to_graph(
 
     builder.build(|_, _, _, _, _, _| ())
 }
+
+/// Creates a node clearing the shadow map of `shadow_index` without drawing
+/// anything into it, which leaves the light unshadowed.
+pub fn add_shadow_clear_to_graph(graph: &mut RenderGraph<'_>, shadow_index: usize, depth_clear: f32) {
+    let mut builder = graph.add_node(&*format_sso!("Clear Shadow S{}", shadow_index));
+
+    let shadow_output_handle = builder.add_shadow_output(shadow_index);
+
+    let _rpass_handle = builder.add_renderpass(RenderPassTargets {
+        targets: vec![],
+        depth_stencil: Some(RenderPassDepthTarget {
+            target: DepthHandle::Shadow(shadow_output_handle),
+            depth_clear: Some(depth_clear),
+            stencil_clear: None,
+        }),
+    });
+
+    builder.build(|_, _, _, _, _, _| ())
+}
@@ -0,0 +1,16 @@
+use glam::Vec4;
+
+/// Converts an srgb color to linear, keeping alpha as-is.
+///
+/// Colors written to srgb outputs are converted back by the GPU, so routines
+/// taking srgb colors convert them first when drawing to those outputs.
+pub fn srgb_to_linear(color: Vec4) -> Vec4 {
+    let convert = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    Vec4::new(convert(color.x), convert(color.y), convert(color.z), color.w)
+}
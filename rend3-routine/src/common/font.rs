@@ -0,0 +1,191 @@
+use glam::UVec2;
+
+/// Width of a glyph of the built-in font in texels.
+pub const GLYPH_WIDTH: u32 = 5;
+/// Height of a glyph of the built-in font in texels.
+pub const GLYPH_HEIGHT: u32 = 7;
+/// Horizontal distance between two characters of a line, which is also the
+/// width of a cell of the font atlas.
+pub const GLYPH_ADVANCE: u32 = 6;
+/// Vertical distance between two lines of text.
+pub const LINE_HEIGHT: u32 = 9;
+
+/// Glyphs of the built-in font. Every row is a byte, with the leftmost texel
+/// in bit 4. Lowercase letters use the uppercase glyphs.
+const GLYPHS: &[(char, [u8; GLYPH_HEIGHT as usize])] = &[
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    ('A', [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+    ('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+    ('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+    ('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04]),
+    ('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+    (':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+    (';', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08]),
+    ('!', [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04]),
+    ('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+    ('\'', [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00]),
+    ('"', [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+    ('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+    ('*', [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00]),
+    ('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+    ('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+    ('<', [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02]),
+    ('>', [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('[', [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E]),
+    (']', [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E]),
+    ('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+    ('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+];
+
+/// Cell of the font atlas which is entirely covered, to draw solid
+/// rectangles with the same texture as text.
+pub const SOLID_CELL: u32 = 0;
+
+/// Cell of the font atlas holding the glyph of `c`. Characters the font has
+/// no glyph for use the glyph of `?`.
+pub fn glyph_cell(c: char) -> u32 {
+    let c = c.to_ascii_uppercase();
+    let index = GLYPHS
+        .iter()
+        .position(|&(glyph, _)| glyph == c)
+        .or_else(|| GLYPHS.iter().position(|&(glyph, _)| glyph == '?'))
+        .unwrap();
+
+    // The solid cell comes first.
+    index as u32 + 1
+}
+
+/// Resolution and single channel texels of the atlas of the built-in font.
+///
+/// Every cell is [`GLYPH_ADVANCE`] texels wide, with the glyph in its top left
+/// corner and uncovered texels around it, so neighboring glyphs never bleed
+/// into each other.
+pub fn font_atlas() -> (UVec2, Vec<u8>) {
+    let cells = GLYPHS.len() as u32 + 1;
+    let resolution = UVec2::new(cells * GLYPH_ADVANCE, GLYPH_HEIGHT + 1);
+
+    let mut texels = vec![0_u8; (resolution.x * resolution.y) as usize];
+    for y in 0..resolution.y {
+        for x in 0..GLYPH_ADVANCE {
+            texels[(y * resolution.x + SOLID_CELL * GLYPH_ADVANCE + x) as usize] = 255;
+        }
+    }
+    for (index, (_, rows)) in GLYPHS.iter().enumerate() {
+        let cell_start = (index as u32 + 1) * GLYPH_ADVANCE;
+        for (y, row) in rows.iter().enumerate() {
+            for x in 0..GLYPH_WIDTH {
+                if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                    texels[(y as u32 * resolution.x + cell_start + x) as usize] = 255;
+                }
+            }
+        }
+    }
+
+    (resolution, texels)
+}
+
+/// Texels of the distance field atlas per texel of the built-in font.
+pub const SDF_TEXELS_PER_FONT_TEXEL: u32 = 8;
+/// Cells per row of the distance field atlas, which wraps its cells to stay
+/// within the texture size limits of every device.
+pub const SDF_ATLAS_COLUMNS: u32 = 16;
+
+/// Position of the top left corner of `cell` in the distance field atlas, in
+/// texels.
+pub fn sdf_cell_origin(cell: u32) -> UVec2 {
+    UVec2::new(cell % SDF_ATLAS_COLUMNS, cell / SDF_ATLAS_COLUMNS)
+        * UVec2::new(GLYPH_ADVANCE, GLYPH_HEIGHT + 1)
+        * SDF_TEXELS_PER_FONT_TEXEL
+}
+
+/// Resolution and single channel texels of a signed distance field of the
+/// built-in font, which stays sharp when text is scaled or transformed.
+///
+/// Cells are laid out by [`sdf_cell_origin`] and are
+/// [`SDF_TEXELS_PER_FONT_TEXEL`] times the size of those of [`font_atlas`].
+/// Texels store the distance to the edge of the glyph, in texels of the font:
+/// 0.5 is on the edge, higher values are inside the glyph, and the distance is
+/// clamped to one texel on either side.
+pub fn font_sdf_atlas() -> (UVec2, Vec<u8>) {
+    let (bitmap_resolution, bitmap) = font_atlas();
+    let cell_size = UVec2::new(GLYPH_ADVANCE, GLYPH_HEIGHT + 1);
+    let cells = bitmap_resolution.x / GLYPH_ADVANCE;
+    let rows = (cells + SDF_ATLAS_COLUMNS - 1) / SDF_ATLAS_COLUMNS;
+    let resolution = UVec2::new(SDF_ATLAS_COLUMNS, rows) * cell_size * SDF_TEXELS_PER_FONT_TEXEL;
+
+    let mut texels = vec![0_u8; (resolution.x * resolution.y) as usize];
+    for cell in 0..cells {
+        let covered = |x: i32, y: i32| {
+            x >= 0
+                && y >= 0
+                && x < cell_size.x as i32
+                && y < cell_size.y as i32
+                && bitmap[(y as u32 * bitmap_resolution.x + cell * GLYPH_ADVANCE + x as u32) as usize] != 0
+        };
+
+        let origin = sdf_cell_origin(cell);
+        for sdf_y in 0..cell_size.y * SDF_TEXELS_PER_FONT_TEXEL {
+            for sdf_x in 0..cell_size.x * SDF_TEXELS_PER_FONT_TEXEL {
+                let point = (glam::Vec2::new(sdf_x as f32, sdf_y as f32) + 0.5) / SDF_TEXELS_PER_FONT_TEXEL as f32;
+                let texel = point.floor().as_ivec2();
+                let inside = covered(texel.x, texel.y);
+
+                // Distances are clamped to one texel, so only the direct
+                // neighbors can hold the closest texel of the other kind.
+                let mut distance = 1.0_f32;
+                for y in texel.y - 1..=texel.y + 1 {
+                    for x in texel.x - 1..=texel.x + 1 {
+                        if covered(x, y) != inside {
+                            let center = glam::Vec2::new(x as f32, y as f32) + 0.5;
+                            let outside_square = ((point - center).abs() - 0.5).max(glam::Vec2::ZERO);
+                            distance = distance.min(outside_square.length());
+                        }
+                    }
+                }
+
+                let signed = if inside { distance } else { -distance };
+                let index = (origin.y + sdf_y) * resolution.x + origin.x + sdf_x;
+                texels[index as usize] = ((signed * 0.5 + 0.5) * 255.0).round() as u8;
+            }
+        }
+    }
+
+    (resolution, texels)
+}
@@ -0,0 +1,224 @@
+use std::borrow::Cow;
+
+use rend3::RendererProfile;
+use wgpu::{Device, ShaderModule, ShaderModuleDescriptor, ShaderSource};
+
+use crate::{common::profile_safe_shader, shaders::wgsl_shader};
+
+/// Declarations every hook snippet can use.
+const HOOK_PRELUDE: &str = "
+struct HookVertex {
+    position: vec3<f32>;
+    normal: vec3<f32>;
+    tangent: vec3<f32>;
+    color: vec4<f32>;
+    coords0: vec2<f32>;
+    coords1: vec2<f32>;
+};
+
+struct HookFragment {
+    coords0: vec2<f32>;
+    color: vec4<f32>;
+    material: u32;
+};
+";
+
+/// WGSL snippets spliced into the built-in depth and PBR shaders of a
+/// material archetype, to customize them without writing the whole shader.
+///
+/// Each snippet defines one function, and may define helpers and constants
+/// for it. Prefix their names, as the shaders they are spliced into use
+/// names like `main`, `main_1` and `unnamed`. The snippets can use these
+/// structs:
+///
+/// ```wgsl
+/// struct HookVertex {
+///     position: vec3<f32>;
+///     normal: vec3<f32>;
+///     tangent: vec3<f32>;
+///     color: vec4<f32>;
+///     coords0: vec2<f32>;
+///     coords1: vec2<f32>;
+/// };
+///
+/// struct HookFragment {
+///     coords0: vec2<f32>;
+///     color: vec4<f32>;
+///     material: u32;
+/// };
+/// ```
+///
+/// Hooks only see the data of the vertex or fragment they run for. Per object
+/// parameters, like the strength of wind sway, can be passed through the
+/// vertex colors or the second set of texture coordinates.
+///
+/// The built-in shaders of the GpuDriven profile are SPIRV, so hooks are only
+/// applied in the CpuDriven and Downlevel profiles.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShaderHooks {
+    /// Defines `fn vertex_hook(vertex: HookVertex) -> HookVertex`, which
+    /// modifies the vertex in model space before it is transformed. Runs in
+    /// the forward, prepass and shadow passes, so displaced geometry casts
+    /// matching shadows.
+    pub vertex: Option<String>,
+    /// Defines `fn fragment_hook(fragment: HookFragment) -> HookFragment`,
+    /// which runs before the material is evaluated. It can change the texture
+    /// coordinates and vertex color, or `discard` the fragment to mask out
+    /// parts of the surface. Changes to `material` are ignored. Runs in the
+    /// forward, prepass and shadow passes.
+    pub fragment: Option<String>,
+    /// Defines `fn output_hook(fragment: HookFragment, color: vec4<f32>) ->
+    /// vec4<f32>`, which modifies the lit color written by the forward pass.
+    pub output: Option<String>,
+}
+
+impl ShaderHooks {
+    pub fn is_empty(&self) -> bool {
+        self.vertex.is_none() && self.fragment.is_none() && self.output.is_none()
+    }
+}
+
+/// Which hooks a shader gets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HookedStage {
+    Vertex,
+    /// Fragment shaders of the depth passes, which have no color output.
+    DepthFragment,
+    /// Fragment shaders writing the lit color.
+    ForwardFragment,
+}
+
+/// Like [`profile_safe_shader`], with the hooks of `stage` spliced into the
+/// shader.
+///
+/// # Safety
+///
+/// See [`profile_safe_shader`].
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn hooked_shader(
+    device: &Device,
+    profile: RendererProfile,
+    label: &str,
+    cpu_source: &str,
+    gpu_source: &str,
+    downlevel_source: &str,
+    stage: HookedStage,
+    hooks: &ShaderHooks,
+) -> ShaderModule {
+    match profile {
+        RendererProfile::CpuDriven => hooked_module(device, label, cpu_source, stage, hooks),
+        RendererProfile::Downlevel => hooked_module(device, label, downlevel_source, stage, hooks),
+        RendererProfile::GpuDriven => {
+            if !stage_snippets(stage, hooks).is_empty() {
+                log::warn!(
+                    "Shader hooks aren't supported in the GpuDriven profile, ignoring them for {}",
+                    label
+                );
+            }
+            profile_safe_shader(device, profile, label, cpu_source, gpu_source, downlevel_source)
+        }
+    }
+}
+
+fn hooked_module(device: &Device, label: &str, name: &str, stage: HookedStage, hooks: &ShaderHooks) -> ShaderModule {
+    device.create_shader_module(&ShaderModuleDescriptor {
+        label: Some(label),
+        source: ShaderSource::Wgsl(hooked_wgsl(name, stage, hooks)),
+    })
+}
+
+/// The builtin WGSL shader `name` with the hooks of `stage` spliced in.
+pub(crate) fn hooked_wgsl(name: &str, stage: HookedStage, hooks: &ShaderHooks) -> Cow<'static, str> {
+    let snippets = stage_snippets(stage, hooks);
+    if snippets.is_empty() {
+        return wgsl_shader(name);
+    }
+    Cow::Owned(splice_hooks(&wgsl_shader(name), stage, hooks, &snippets))
+}
+
+fn stage_snippets(stage: HookedStage, hooks: &ShaderHooks) -> Vec<&str> {
+    match stage {
+        HookedStage::Vertex => hooks.vertex.iter().map(String::as_str).collect(),
+        HookedStage::DepthFragment => hooks.fragment.iter().map(String::as_str).collect(),
+        HookedStage::ForwardFragment => hooks.fragment.iter().chain(&hooks.output).map(String::as_str).collect(),
+    }
+}
+
+fn splice_hooks(source: &str, stage: HookedStage, hooks: &ShaderHooks, snippets: &[&str]) -> String {
+    const MAIN_CALL: &str = "\n    main_1();\n";
+
+    let entry_point = source.find("[[stage(").expect("shader has no entry point");
+    let main_call = entry_point
+        + source[entry_point..]
+            .find(MAIN_CALL)
+            .expect("entry point doesn't call main_1");
+
+    let mut before = String::new();
+    let mut after = String::new();
+    if stage == HookedStage::Vertex {
+        before.push_str(concat!(
+            "\n    let hooked_vertex = vertex_hook(HookVertex(i_position_1, i_normal_1, i_tangent_1, i_color_1, ",
+            "i_coords0_1, i_coords1_1));\n",
+            "    i_position_1 = hooked_vertex.position;\n",
+            "    i_normal_1 = hooked_vertex.normal;\n",
+            "    i_tangent_1 = hooked_vertex.tangent;\n",
+            "    i_color_1 = hooked_vertex.color;\n",
+            "    i_coords0_1 = hooked_vertex.coords0;\n",
+            "    i_coords1_1 = hooked_vertex.coords1;",
+        ));
+    } else {
+        if hooks.fragment.is_some() {
+            before.push_str(concat!(
+                "\n    let hooked_fragment = fragment_hook(HookFragment(i_coords0_1, i_color_1, i_material_1));\n",
+                "    i_coords0_1 = hooked_fragment.coords0;\n",
+                "    i_color_1 = hooked_fragment.color;",
+            ));
+        }
+        if stage == HookedStage::ForwardFragment && hooks.output.is_some() {
+            after.push_str("    o_color = output_hook(HookFragment(i_coords0_1, i_color_1, i_material_1), o_color);\n");
+        }
+    }
+
+    let mut spliced = String::with_capacity(source.len() + 1024);
+    spliced.push_str(HOOK_PRELUDE);
+    for snippet in snippets {
+        spliced.push('\n');
+        spliced.push_str(snippet);
+        spliced.push('\n');
+    }
+    spliced.push('\n');
+    spliced.push_str(&source[..main_call]);
+    spliced.push_str(&before);
+    spliced.push_str(MAIN_CALL);
+    spliced.push_str(&after);
+    spliced.push_str(&source[main_call + MAIN_CALL.len()..]);
+    spliced
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hooks_wrap_main_call() {
+        let hooks = ShaderHooks {
+            fragment: Some("fn fragment_hook(fragment: HookFragment) -> HookFragment { return fragment; }".into()),
+            output: Some(
+                "fn output_hook(fragment: HookFragment, color: vec4<f32>) -> vec4<f32> { return color; }".into(),
+            ),
+            ..ShaderHooks::default()
+        };
+        let snippets = [hooks.fragment.as_deref().unwrap(), hooks.output.as_deref().unwrap()];
+        let source =
+            "fn main_1() {\n    main_1();\n}\n[[stage(fragment)]]\nfn main() {\n    main_1();\n    return;\n}\n";
+
+        let spliced = splice_hooks(source, HookedStage::ForwardFragment, &hooks, &snippets);
+
+        let entry = spliced.find("[[stage(").unwrap();
+        let hook = spliced.find("fragment_hook(HookFragment(").unwrap();
+        let main = entry + spliced[entry..].find("main_1();").unwrap();
+        let output = spliced.find("o_color = output_hook").unwrap();
+        assert!(entry < hook && hook < main && main < output);
+        assert!(spliced.starts_with(HOOK_PRELUDE));
+    }
+}
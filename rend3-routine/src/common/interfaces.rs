@@ -2,7 +2,7 @@ use std::{marker::PhantomData, mem, num::NonZeroU64};
 
 use glam::{Mat4, Vec3};
 use rend3::{
-    managers::{DirectionalLightManager, MaterialManager},
+    managers::{DirectionalLightManager, MaterialManager, SkinningMode},
     types::Material,
     util::bind_merge::BindGroupLayoutBuilder,
     RendererProfile,
@@ -14,7 +14,8 @@ use crate::{common::samplers::Samplers, uniforms::FrameUniforms};
 /// Interfaces which are used throughout the whole frame.
 ///
 /// Contains the samplers, per frame uniforms, and directional light
-/// information.
+/// information. Their binding indices are listed by
+/// [`object_bind_group_layouts`](crate::common::object_bind_group_layouts).
 pub struct WholeFrameInterfaces {
     /// Includes everything excluding the directional light information to
     /// prevent cycles when rendering to shadow maps.
@@ -24,7 +25,7 @@ pub struct WholeFrameInterfaces {
 }
 
 impl WholeFrameInterfaces {
-    pub fn new(device: &Device) -> Self {
+    pub fn new(device: &Device, profile: RendererProfile) -> Self {
         profiling::scope!("ShaderInterfaces::new");
 
         let mut uniform_bglb = BindGroupLayoutBuilder::new();
@@ -43,7 +44,7 @@ impl WholeFrameInterfaces {
 
         let shadow_uniform_bgl = uniform_bglb.build(device, Some("shadow uniform bgl"));
 
-        DirectionalLightManager::add_to_bgl(&mut uniform_bglb);
+        DirectionalLightManager::add_to_bgl(&mut uniform_bglb, profile);
 
         let forward_uniform_bgl = uniform_bglb.build(device, Some("forward uniform bgl"));
 
@@ -63,28 +64,42 @@ pub struct PerObjectDataAbi {
     pub model_view_proj: Mat4,
     // Only read when GpuDriven. Materials are directly bound when CpuDriven.
     pub material_idx: u32,
-    pub pad0: [u8; 12],
+    // Only written when CpuDriven or Downlevel.
+    pub texture_layer: u32,
+    /// See `GpuCullingInput::joint_offset`. Only read by vertex shaders
+    /// skinning the object.
+    pub joint_offset: u32,
+    pub pad0: [u8; 4],
     pub inv_squared_scale: Vec3,
+    /// See `ObjectFade::to_shader`.
+    pub fade: f32,
 }
 
 unsafe impl bytemuck::Pod for PerObjectDataAbi {}
 unsafe impl bytemuck::Zeroable for PerObjectDataAbi {}
 
 /// Interface which has all per-material-archetype data: the object output
-/// buffer and the gpu material buffer.
+/// buffer, the gpu material buffer, and the joint matrices when skinning in
+/// the vertex shader.
 pub struct PerMaterialArchetypeInterface<M> {
     pub bgl: BindGroupLayout,
     _phantom: PhantomData<M>,
 }
 impl<M: Material> PerMaterialArchetypeInterface<M> {
-    pub fn new(device: &Device, profile: RendererProfile) -> Self {
+    pub fn new(device: &Device, profile: RendererProfile, skinning_mode: SkinningMode) -> Self {
         let mut per_material_bglb = BindGroupLayoutBuilder::new();
 
+        // Downlevel can't use storage buffers, so it binds the data of a single
+        // object from a uniform buffer, moving the offset between draws.
+        let (ty, has_dynamic_offset) = match profile {
+            RendererProfile::Downlevel => (BufferBindingType::Uniform, true),
+            _ => (BufferBindingType::Storage { read_only: true }, false),
+        };
         per_material_bglb.append(
             ShaderStages::VERTEX,
             BindingType::Buffer {
-                ty: BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset: false,
+                ty,
+                has_dynamic_offset,
                 min_binding_size: NonZeroU64::new(mem::size_of::<PerObjectDataAbi>() as _),
             },
             None,
@@ -94,6 +109,18 @@ impl<M: Material> PerMaterialArchetypeInterface<M> {
             MaterialManager::add_to_bgl_gpu::<M>(&mut per_material_bglb);
         }
 
+        if skinning_mode == SkinningMode::VertexShader {
+            per_material_bglb.append(
+                ShaderStages::VERTEX,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(mem::size_of::<Mat4>() as _),
+                },
+                None,
+            );
+        }
+
         let bgl = per_material_bglb.build(device, Some("per material bgl"));
 
         Self {
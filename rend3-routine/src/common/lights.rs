@@ -0,0 +1,31 @@
+use glam::Vec4;
+use rend3::managers::DirectionalLightManager;
+
+/// Most directional lights in [`UnshadowedLights`].
+pub const MAX_UNSHADOWED_LIGHTS: usize = 4;
+
+/// The first few directional lights of the renderer, packed for routines
+/// which light things with a plain lambert term instead of the PBR shaders.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct UnshadowedLights {
+    /// Direction the light travels in xyz.
+    pub directions: [Vec4; MAX_UNSHADOWED_LIGHTS],
+    /// Color times intensity in rgb.
+    pub colors: [Vec4; MAX_UNSHADOWED_LIGHTS],
+    pub count: u32,
+}
+
+impl UnshadowedLights {
+    pub fn new(manager: &DirectionalLightManager) -> Self {
+        let mut lights = Self::default();
+        for (light, (direction, color)) in manager
+            .values()
+            .zip(lights.directions.iter_mut().zip(&mut lights.colors))
+        {
+            *direction = light.inner.direction.normalize_or_zero().extend(0.0);
+            *color = (light.inner.color * light.inner.intensity).extend(0.0);
+            lights.count += 1;
+        }
+        lights
+    }
+}
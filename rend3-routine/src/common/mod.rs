@@ -1,12 +1,26 @@
 //! Common utilities used throughout the crate.
 
+mod color;
+mod font;
+mod hooks;
 mod interfaces;
+mod lights;
+mod permutation;
+mod pipeline;
+mod render_state;
 mod samplers;
 mod shaders;
 mod sorting;
 mod vertex;
 
+pub use color::*;
+pub use font::*;
+pub use hooks::*;
 pub use interfaces::*;
+pub use lights::*;
+pub use permutation::*;
+pub use pipeline::*;
+pub use render_state::*;
 pub use samplers::*;
 pub use shaders::*;
 pub use sorting::*;
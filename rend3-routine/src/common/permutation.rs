@@ -0,0 +1,246 @@
+use std::{borrow::Cow, collections::BTreeSet, sync::Arc};
+
+use parking_lot::Mutex;
+use rend3::util::typedefs::FastHashMap;
+use thiserror::Error;
+use wgpu::{Device, ShaderModule, ShaderModuleDescriptor, ShaderSource};
+
+/// Define for shaders sampling shadow maps.
+pub const SHADOWS_DEFINE: &str = "SHADOWS";
+/// Define for shaders lit by image based lighting.
+pub const IBL_DEFINE: &str = "IBL";
+/// Define for shaders reading vertex colors.
+pub const VERTEX_COLORS_DEFINE: &str = "VERTEX_COLORS";
+/// Define for shaders of skinned meshes.
+pub const SKINNING_DEFINE: &str = "SKINNING";
+/// Define for shaders reading clustered lights.
+pub const CLUSTERED_LIGHTING_DEFINE: &str = "CLUSTERED_LIGHTING";
+
+/// Set of defines selecting a permutation of a shader, see
+/// [`preprocess_wgsl`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderDefines {
+    defines: BTreeSet<String>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, define: impl Into<String>) -> Self {
+        self.defines.insert(define.into());
+        self
+    }
+
+    /// Adds `define` if `enabled`, to build defines from settings.
+    pub fn with_if(self, define: impl Into<String>, enabled: bool) -> Self {
+        match enabled {
+            true => self.with(define),
+            false => self,
+        }
+    }
+
+    pub fn contains(&self, define: &str) -> bool {
+        self.defines.contains(define)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.defines.iter().map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum PreprocessError {
+    #[error("Line {line}: {directive} without a matching #ifdef or #ifndef")]
+    Unmatched { line: usize, directive: &'static str },
+    #[error("Line {line}: #ifdef or #ifndef is never closed with #endif")]
+    Unclosed { line: usize },
+    #[error("Line {line}: unknown directive {directive:?}")]
+    UnknownDirective { line: usize, directive: String },
+}
+
+struct Block {
+    line: usize,
+    /// Whether the block containing this one is kept.
+    parent_active: bool,
+    /// Whether the current branch of this block is kept.
+    active: bool,
+    seen_else: bool,
+}
+
+/// Resolves `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` lines in WGSL
+/// source, keeping the lines of the branches `defines` select.
+///
+/// Directives must be alone on their line, and may be nested. Removed lines
+/// are replaced by empty ones, so naga errors point at the right line.
+pub fn preprocess_wgsl(source: &str, defines: &ShaderDefines) -> Result<String, PreprocessError> {
+    let mut output = String::with_capacity(source.len());
+    let mut blocks: Vec<Block> = Vec::new();
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_number = idx + 1;
+        let active = blocks.last().map_or(true, |block| block.active);
+        let trimmed = line.trim();
+
+        if let Some(directive) = trimmed.strip_prefix('#') {
+            let mut words = directive.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some(kind @ ("ifdef" | "ifndef")), Some(name)) => {
+                    let defined = defines.contains(name);
+                    blocks.push(Block {
+                        line: line_number,
+                        parent_active: active,
+                        active: active && (defined == (kind == "ifdef")),
+                        seen_else: false,
+                    });
+                }
+                (Some("else"), None) => match blocks.last_mut() {
+                    Some(block) if !block.seen_else => {
+                        block.active = block.parent_active && !block.active;
+                        block.seen_else = true;
+                    }
+                    _ => {
+                        return Err(PreprocessError::Unmatched {
+                            line: line_number,
+                            directive: "#else",
+                        })
+                    }
+                },
+                (Some("endif"), None) => {
+                    if blocks.pop().is_none() {
+                        return Err(PreprocessError::Unmatched {
+                            line: line_number,
+                            directive: "#endif",
+                        });
+                    }
+                }
+                _ => {
+                    return Err(PreprocessError::UnknownDirective {
+                        line: line_number,
+                        directive: trimmed.to_owned(),
+                    })
+                }
+            }
+        } else if active {
+            output.push_str(line);
+        }
+        output.push('\n');
+    }
+
+    match blocks.last() {
+        Some(block) => Err(PreprocessError::Unclosed { line: block.line }),
+        None => Ok(output),
+    }
+}
+
+/// The permutations of a WGSL shader, compiled the first time they are
+/// asked for.
+///
+/// Features are written as `#ifdef` blocks in one source, see
+/// [`preprocess_wgsl`], and only the permutations a renderer actually uses
+/// are compiled. The built-in PBR shaders are compiled ahead of time from
+/// GLSL instead, with a permutation per profile.
+pub struct ShaderPermutations {
+    label: String,
+    source: Cow<'static, str>,
+    modules: Mutex<FastHashMap<ShaderDefines, Arc<ShaderModule>>>,
+}
+
+impl ShaderPermutations {
+    pub fn new(label: impl Into<String>, source: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            label: label.into(),
+            source: source.into(),
+            modules: Mutex::new(FastHashMap::default()),
+        }
+    }
+
+    /// The module of the permutation selected by `defines`, compiling it if
+    /// it is the first time it is used.
+    pub fn get(&self, device: &Device, defines: &ShaderDefines) -> Result<Arc<ShaderModule>, PreprocessError> {
+        if let Some(module) = self.modules.lock().get(defines) {
+            return Ok(Arc::clone(module));
+        }
+
+        profiling::scope!("ShaderPermutations::get");
+        let source = preprocess_wgsl(&self.source, defines)?;
+        let defines_label: Vec<&str> = defines.iter().collect();
+        let module = Arc::new(device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some(&format!("{} [{}]", self.label, defines_label.join(", "))),
+            source: ShaderSource::Wgsl(Cow::Owned(source)),
+        }));
+
+        self.modules.lock().insert(defines.clone(), Arc::clone(&module));
+        Ok(module)
+    }
+
+    /// The permutations compiled so far, sorted. Can be saved and fed back
+    /// to [`ShaderPermutations::get`] at startup, to compile the shaders a
+    /// game needs ahead of time.
+    pub fn manifest(&self) -> Vec<ShaderDefines> {
+        let mut manifest: Vec<ShaderDefines> = self.modules.lock().keys().cloned().collect();
+        manifest.sort();
+        manifest
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{preprocess_wgsl, PreprocessError, ShaderDefines, IBL_DEFINE, SHADOWS_DEFINE};
+
+    const SOURCE: &str = "a
+#ifdef SHADOWS
+b
+#ifndef IBL
+c
+#else
+d
+#endif
+#endif
+e";
+
+    fn kept(defines: &ShaderDefines) -> Vec<String> {
+        preprocess_wgsl(SOURCE, defines)
+            .unwrap()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn selects_branches() {
+        assert_eq!(kept(&ShaderDefines::new()), ["a", "e"]);
+        assert_eq!(kept(&ShaderDefines::new().with(SHADOWS_DEFINE)), ["a", "b", "c", "e"]);
+        let both = ShaderDefines::new().with(SHADOWS_DEFINE).with_if(IBL_DEFINE, true);
+        assert_eq!(kept(&both), ["a", "b", "d", "e"]);
+        assert_eq!(kept(&ShaderDefines::new().with(IBL_DEFINE)), ["a", "e"]);
+
+        // Line numbers are kept for naga errors.
+        assert_eq!(
+            preprocess_wgsl(SOURCE, &ShaderDefines::new()).unwrap().lines().count(),
+            10
+        );
+    }
+
+    #[test]
+    fn reports_broken_blocks() {
+        let defines = ShaderDefines::new();
+        assert_eq!(
+            preprocess_wgsl("#ifdef A\n#else\n#else\n#endif", &defines),
+            Err(PreprocessError::Unmatched {
+                line: 3,
+                directive: "#else"
+            })
+        );
+        assert_eq!(
+            preprocess_wgsl("x\n#ifdef A\n", &defines),
+            Err(PreprocessError::Unclosed { line: 2 })
+        );
+        assert!(matches!(
+            preprocess_wgsl("#if A", &defines),
+            Err(PreprocessError::UnknownDirective { line: 1, .. })
+        ));
+    }
+}
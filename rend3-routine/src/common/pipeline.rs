@@ -0,0 +1,234 @@
+use arrayvec::ArrayVec;
+use rend3::{
+    types::{Handedness, Material, SampleCount},
+    Renderer, RendererDataCore, RendererProfile,
+};
+use wgpu::{
+    BindGroupLayout, ColorTargetState, CompareFunction, DepthBiasState, DepthStencilState, Device, Face, FragmentState,
+    FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModule, StencilState, TextureFormat, VertexBufferLayout,
+    VertexState,
+};
+
+use crate::common::{vertex_buffer_layouts, PerMaterialArchetypeInterface, WholeFrameInterfaces};
+
+/// Frame uniforms bound at group 0.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UniformInterface {
+    /// Without directional lights, as used by shadow passes which render the
+    /// shadow maps bound by the forward uniforms.
+    Depth,
+    /// Everything, as used by the depth prepass and forward passes.
+    Forward,
+}
+
+/// Bind group layouts of groups 0 to 2 of a pass drawing the objects of
+/// material archetype `M`.
+///
+/// Every pass drawing objects uses the following bind groups. Binding indices
+/// within a group are stable.
+///
+/// Group 0, the frame uniforms, see [`WholeFrameInterfaces`]:
+/// - 0: linear filtering sampler
+/// - 1: nearest sampler
+/// - 2: comparison sampler for shadow maps
+/// - 3: [`FrameUniforms`](crate::uniforms::FrameUniforms) uniform buffer
+/// - 4: directional lights, a storage buffer, or a uniform buffer on the
+///   downlevel profile (forward uniforms only)
+/// - 5: directional light shadow maps, a depth `texture_depth_2d_array`
+///   (forward uniforms only)
+///
+/// Group 1, the per material archetype data, see
+/// [`PerMaterialArchetypeInterface`]:
+/// - 0: culled objects as an array of
+///   [`PerObjectDataAbi`](crate::common::PerObjectDataAbi) in a storage
+///   buffer, or a single one in a uniform buffer bound with a dynamic offset on
+///   the downlevel profile
+/// - 1: material data of every material of the archetype (GpuDriven only)
+/// - next: with [`SkinningMode::VertexShader`](rend3::managers::SkinningMode::VertexShader),
+///   the joint matrices of all skeletons in a storage buffer, indexed from
+///   the `joint_offset` of each object
+///
+/// Group 2, the materials:
+/// - GpuDriven: 0: the binding array of every 2D texture
+/// - CpuDriven and downlevel: 0: material data buffer, 1 and up: one 2D
+///   texture per [`Material::TEXTURE_COUNT`]
+///
+/// Group 3 and up are free for the bind groups of the pass itself.
+///
+/// Vertex attributes come in the buffers of [`vertex_buffer_layouts`], those
+/// of the GpuDriven profile adding the object index.
+pub fn object_bind_group_layouts<'a, M: Material>(
+    profile: RendererProfile,
+    data_core: &'a RendererDataCore,
+    interfaces: &'a WholeFrameInterfaces,
+    per_material: &'a PerMaterialArchetypeInterface<M>,
+    uniforms: UniformInterface,
+) -> ArrayVec<&'a BindGroupLayout, 3> {
+    let mut bgls = ArrayVec::new();
+    bgls.push(match uniforms {
+        UniformInterface::Depth => &interfaces.depth_uniform_bgl,
+        UniformInterface::Forward => &interfaces.forward_uniform_bgl,
+    });
+    bgls.push(&per_material.bgl);
+    if profile == RendererProfile::GpuDriven {
+        bgls.push(data_core.d2_texture_manager.gpu_bgl())
+    } else {
+        bgls.push(data_core.material_manager.get_bind_group_layout_cpu::<M>());
+    }
+    bgls
+}
+
+/// Builds a pipeline drawing the objects of a material archetype, compatible
+/// with the bind groups and vertex buffers rend3 binds for them, as listed
+/// by [`object_bind_group_layouts`].
+///
+/// Defaults to what the opaque forward pass does: back face culling, depth
+/// testing and writing, and a single `Rgba16Float` color target.
+pub struct ObjectPipelineBuilder<'a> {
+    label: &'a str,
+    vertex_buffers: ArrayVec<VertexBufferLayout<'static>, 9>,
+    handedness: Handedness,
+    bgls: ArrayVec<&'a BindGroupLayout, 8>,
+    vertex: (&'a str, &'a ShaderModule),
+    fragment: Option<(&'a str, &'a ShaderModule)>,
+    targets: Vec<ColorTargetState>,
+    topology: PrimitiveTopology,
+    cull_mode: Option<Face>,
+    depth_write: bool,
+    depth_compare: CompareFunction,
+    depth_bias: DepthBiasState,
+    samples: SampleCount,
+}
+
+impl<'a> ObjectPipelineBuilder<'a> {
+    /// `vertex` is the entry point and module of the vertex shader.
+    pub fn new<M: Material>(
+        renderer: &Renderer,
+        data_core: &'a RendererDataCore,
+        interfaces: &'a WholeFrameInterfaces,
+        per_material: &'a PerMaterialArchetypeInterface<M>,
+        uniforms: UniformInterface,
+        label: &'a str,
+        vertex: (&'a str, &'a ShaderModule),
+    ) -> Self {
+        let mut bgls = ArrayVec::new();
+        bgls.extend(object_bind_group_layouts(
+            renderer.profile,
+            data_core,
+            interfaces,
+            per_material,
+            uniforms,
+        ));
+        Self {
+            label,
+            vertex_buffers: vertex_buffer_layouts(renderer),
+            handedness: renderer.handedness,
+            bgls,
+            vertex,
+            fragment: None,
+            targets: vec![TextureFormat::Rgba16Float.into()],
+            topology: PrimitiveTopology::TriangleList,
+            cull_mode: Some(Face::Back),
+            depth_write: true,
+            depth_compare: renderer.depth_mode.closer_or_equal(),
+            depth_bias: DepthBiasState::default(),
+            samples: SampleCount::One,
+        }
+    }
+
+    /// Appends the layout of the next group, starting at group 3.
+    pub fn bind_group_layout(mut self, bgl: &'a BindGroupLayout) -> Self {
+        self.bgls.push(bgl);
+        self
+    }
+
+    /// Sets the fragment shader. Without one, only depth is written.
+    pub fn fragment(mut self, entry_point: &'a str, module: &'a ShaderModule) -> Self {
+        self.fragment = Some((entry_point, module));
+        self
+    }
+
+    /// Sets the color targets written by the fragment shader.
+    pub fn targets(mut self, targets: Vec<ColorTargetState>) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    pub fn topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<Face>) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    /// Sets how depth is tested and written, e.g. [`CompareFunction::Equal`]
+    /// without writes after a depth prepass. The default compare function
+    /// follows the depth mode of the renderer.
+    pub fn depth(mut self, write: bool, compare: CompareFunction) -> Self {
+        self.depth_write = write;
+        self.depth_compare = compare;
+        self
+    }
+
+    pub fn depth_bias(mut self, depth_bias: DepthBiasState) -> Self {
+        self.depth_bias = depth_bias;
+        self
+    }
+
+    pub fn samples(mut self, samples: SampleCount) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn build(&self, device: &Device) -> RenderPipeline {
+        profiling::scope!("ObjectPipelineBuilder::build");
+        let pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(self.label),
+            bind_group_layouts: &self.bgls,
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(self.label),
+            layout: Some(&pll),
+            vertex: VertexState {
+                module: self.vertex.1,
+                entry_point: self.vertex.0,
+                buffers: &self.vertex_buffers,
+            },
+            primitive: PrimitiveState {
+                topology: self.topology,
+                strip_index_format: None,
+                front_face: match self.handedness {
+                    Handedness::Left => FrontFace::Cw,
+                    Handedness::Right => FrontFace::Ccw,
+                },
+                cull_mode: self.cull_mode,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: self.depth_write,
+                depth_compare: self.depth_compare,
+                stencil: StencilState::default(),
+                bias: self.depth_bias,
+            }),
+            multisample: MultisampleState {
+                count: self.samples as u32,
+                ..Default::default()
+            },
+            fragment: self.fragment.map(|(entry_point, module)| FragmentState {
+                module,
+                entry_point,
+                targets: &self.targets,
+            }),
+            multiview: None,
+        })
+    }
+}
@@ -0,0 +1,152 @@
+use wgpu::{
+    ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState, Face, PolygonMode,
+    PrimitiveState, TextureFormat,
+};
+
+/// A pass drawing the objects of a material archetype.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ObjectPass {
+    Shadow,
+    Prepass,
+    Opaque,
+    Cutout,
+    Blend,
+}
+
+/// Pipeline state replacing what a pass uses by default. Fields left as
+/// `None` keep the default of the pass.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct RenderStateOverride {
+    /// `Some(None)` draws both sides of triangles.
+    pub cull_mode: Option<Option<Face>>,
+    /// Anything but fill needs the matching `POLYGON_MODE_*` feature.
+    pub polygon_mode: Option<PolygonMode>,
+    pub depth_bias: Option<DepthBiasState>,
+    pub depth_compare: Option<CompareFunction>,
+    pub depth_write_enabled: Option<bool>,
+    /// Format of the depth target, for rendering into one that isn't
+    /// `Depth32Float`. Shadow maps are always `Depth32Float`, so leave it
+    /// unset for [`ObjectPass::Shadow`].
+    pub depth_format: Option<TextureFormat>,
+    /// Applied to every color target. Depth passes write no color.
+    pub write_mask: Option<ColorWrites>,
+    /// Turns the alpha of cutout materials into MSAA coverage instead of
+    /// discarding fragments below the cutoff, which smooths the edges of
+    /// foliage. Set it on [`ObjectPass::Cutout`]. Only takes effect with 4x
+    /// MSAA, and not in the GpuDriven profile, whose shaders are SPIRV.
+    pub alpha_to_coverage: Option<bool>,
+}
+
+impl RenderStateOverride {
+    /// Fields set in `other` replace those of `self`.
+    fn merge(self, other: Self) -> Self {
+        Self {
+            cull_mode: other.cull_mode.or(self.cull_mode),
+            polygon_mode: other.polygon_mode.or(self.polygon_mode),
+            depth_bias: other.depth_bias.or(self.depth_bias),
+            depth_compare: other.depth_compare.or(self.depth_compare),
+            depth_write_enabled: other.depth_write_enabled.or(self.depth_write_enabled),
+            depth_format: other.depth_format.or(self.depth_format),
+            write_mask: other.write_mask.or(self.write_mask),
+            alpha_to_coverage: other.alpha_to_coverage.or(self.alpha_to_coverage),
+        }
+    }
+
+    pub fn apply_primitive(&self, primitive: &mut PrimitiveState) {
+        if let Some(cull_mode) = self.cull_mode {
+            primitive.cull_mode = cull_mode;
+        }
+        if let Some(polygon_mode) = self.polygon_mode {
+            primitive.polygon_mode = polygon_mode;
+        }
+    }
+
+    pub fn apply_depth_stencil(&self, depth_stencil: &mut DepthStencilState) {
+        if let Some(depth_bias) = self.depth_bias {
+            depth_stencil.bias = depth_bias;
+        }
+        if let Some(depth_compare) = self.depth_compare {
+            depth_stencil.depth_compare = depth_compare;
+        }
+        if let Some(depth_write_enabled) = self.depth_write_enabled {
+            depth_stencil.depth_write_enabled = depth_write_enabled;
+        }
+        if let Some(depth_format) = self.depth_format {
+            depth_stencil.format = depth_format;
+        }
+    }
+
+    pub fn apply_color_targets(&self, targets: &mut [ColorTargetState]) {
+        if let Some(write_mask) = self.write_mask {
+            for target in targets {
+                target.write_mask = write_mask;
+            }
+        }
+    }
+}
+
+/// Overrides of the pipeline state of every pass of a material archetype,
+/// e.g. disabling culling for two-sided foliage, biasing the depth of decals
+/// or drawing a debug archetype in wireframe.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RenderStateOverrides {
+    /// Applies to every pass.
+    pub all: RenderStateOverride,
+    /// Applies to a single pass, taking precedence over `all`.
+    pub passes: Vec<(ObjectPass, RenderStateOverride)>,
+}
+
+impl RenderStateOverrides {
+    /// Overrides for every pass.
+    pub fn all(all: RenderStateOverride) -> Self {
+        Self {
+            all,
+            passes: Vec::new(),
+        }
+    }
+
+    /// Adds overrides for `pass`.
+    pub fn with_pass(mut self, pass: ObjectPass, state: RenderStateOverride) -> Self {
+        self.passes.push((pass, state));
+        self
+    }
+
+    /// The overrides `pass` ends up with.
+    pub fn get(&self, pass: ObjectPass) -> RenderStateOverride {
+        self.passes
+            .iter()
+            .filter(|&&(overridden, _)| overridden == pass)
+            .fold(self.all, |state, &(_, pass_state)| state.merge(pass_state))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wgpu::{Face, PolygonMode};
+
+    use super::{ObjectPass, RenderStateOverride, RenderStateOverrides};
+
+    #[test]
+    fn passes_take_precedence() {
+        let overrides = RenderStateOverrides::all(RenderStateOverride {
+            cull_mode: Some(None),
+            ..Default::default()
+        })
+        .with_pass(
+            ObjectPass::Shadow,
+            RenderStateOverride {
+                cull_mode: Some(Some(Face::Front)),
+                polygon_mode: Some(PolygonMode::Line),
+                ..Default::default()
+            },
+        );
+
+        let opaque = overrides.get(ObjectPass::Opaque);
+        assert_eq!(opaque.cull_mode, Some(None));
+        assert_eq!(opaque.polygon_mode, None);
+
+        let shadow = overrides.get(ObjectPass::Shadow);
+        assert_eq!(shadow.cull_mode, Some(Some(Face::Front)));
+        assert_eq!(shadow.polygon_mode, Some(PolygonMode::Line));
+    }
+}
@@ -1,6 +1,9 @@
 use std::num::NonZeroU8;
 
-use rend3::util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder};
+use rend3::{
+    types::DepthMode,
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+};
 use wgpu::{
     AddressMode, BindingType, CompareFunction, Device, FilterMode, Sampler, SamplerBindingType, SamplerDescriptor,
     ShaderStages,
@@ -12,18 +15,20 @@ pub struct Samplers {
     pub linear: Sampler,
     /// Nearest neighbor sampler
     pub nearest: Sampler,
-    /// Bilinear greater-or-equal comparison sampler
+    /// Bilinear comparison sampler, passing when the fragment is at least as
+    /// close to the light as the shadow map.
     pub shadow: Sampler,
 }
 
 impl Samplers {
-    /// Create a new set of samplers with this device.
-    pub fn new(device: &Device) -> Self {
+    /// Create a new set of samplers with this device, comparing shadows
+    /// according to `depth_mode`.
+    pub fn new(device: &Device, depth_mode: DepthMode) -> Self {
         profiling::scope!("Samplers::new");
 
         let linear = create_sampler(device, FilterMode::Linear, None);
         let nearest = create_sampler(device, FilterMode::Nearest, None);
-        let shadow = create_sampler(device, FilterMode::Linear, Some(CompareFunction::GreaterEqual));
+        let shadow = create_sampler(device, FilterMode::Linear, Some(depth_mode.closer_or_equal()));
 
         Self {
             linear,
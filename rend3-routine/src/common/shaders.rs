@@ -1,12 +1,10 @@
-use std::borrow::Cow;
-
 use rend3::RendererProfile;
 use wgpu::{Device, ShaderModule, ShaderModuleDescriptor, ShaderModuleDescriptorSpirV, ShaderSource};
 
-use crate::shaders::{SPIRV_SHADERS, WGSL_SHADERS};
+use crate::shaders::{spirv_shader, wgsl_shader};
 
-/// When CpuDriven, creates a checked wgsl shader, when CpuDriven creates a
-/// passthrough SPIRV shader.
+/// When CpuDriven or Downlevel, creates a checked wgsl shader, when GpuDriven
+/// creates a passthrough SPIRV shader.
 ///
 /// # Safety
 ///
@@ -18,30 +16,24 @@ pub unsafe fn profile_safe_shader(
     label: &str,
     cpu_source: &str,
     gpu_source: &str,
+    downlevel_source: &str,
 ) -> ShaderModule {
-    let shader_dir = match profile {
-        RendererProfile::CpuDriven => &WGSL_SHADERS,
-        RendererProfile::GpuDriven => &SPIRV_SHADERS,
+    let name = match profile {
+        RendererProfile::CpuDriven => cpu_source,
+        RendererProfile::GpuDriven => gpu_source,
+        RendererProfile::Downlevel => downlevel_source,
     };
 
-    let source = shader_dir
-        .get_file(match profile {
-            RendererProfile::CpuDriven => cpu_source,
-            RendererProfile::GpuDriven => gpu_source,
-        })
-        .unwrap()
-        .contents();
-
     let use_unsafe = profile == RendererProfile::GpuDriven;
 
     match use_unsafe {
         false => device.create_shader_module(&ShaderModuleDescriptor {
             label: Some(label),
-            source: ShaderSource::Wgsl(Cow::Borrowed(std::str::from_utf8(source).unwrap())),
+            source: ShaderSource::Wgsl(wgsl_shader(name)),
         }),
         true => device.create_shader_module_spirv(&ShaderModuleDescriptorSpirV {
             label: Some(label),
-            source: wgpu::util::make_spirv_raw(source),
+            source: wgpu::util::make_spirv_raw(&spirv_shader(name)),
         }),
     }
 }
@@ -12,32 +12,108 @@ pub enum Sorting {
     BackToFront,
 }
 
-/// Takes a set of objects and possibly sorts them if a sorting is provided.
+/// Takes a set of objects and sorts them by their sort key, then by distance
+/// if a sorting is provided.
+///
+/// Objects are only copied when they need reordering.
 pub fn sort_objects<'a>(
     objects: &'a [InternalObject],
     camera_manager: &CameraManager,
     sorting: Option<Sorting>,
 ) -> Cow<'a, [InternalObject]> {
-    if let Some(sorting) = sorting {
-        profiling::scope!("Sorting");
-
-        let camera_location = camera_manager.location().into();
-
-        let mut sorted_objects = objects.to_vec();
-
-        match sorting {
-            Sorting::FrontToBack => {
-                sorted_objects
-                    .sort_unstable_by_key(|o| OrderedFloat(o.mesh_location().distance_squared(camera_location)));
-            }
-            Sorting::BackToFront => {
-                sorted_objects
-                    .sort_unstable_by_key(|o| OrderedFloat(-o.mesh_location().distance_squared(camera_location)));
-            }
+    let keyed = objects.iter().any(|o| o.sort_key != 0);
+    if sorting.is_none() && !keyed {
+        return Cow::Borrowed(objects);
+    }
+
+    profiling::scope!("Sorting");
+
+    let camera_location = camera_manager.location().into();
+
+    let mut sorted_objects = objects.to_vec();
+
+    match sorting {
+        Some(Sorting::FrontToBack) => {
+            sorted_objects.sort_unstable_by_key(|o| {
+                (
+                    o.sort_key,
+                    OrderedFloat(o.mesh_location().distance_squared(camera_location)),
+                )
+            });
         }
+        Some(Sorting::BackToFront) => {
+            sorted_objects.sort_unstable_by_key(|o| {
+                (
+                    o.sort_key,
+                    OrderedFloat(-o.mesh_location().distance_squared(camera_location)),
+                )
+            });
+        }
+        // Stable, so objects sharing a key keep their order.
+        None => sorted_objects.sort_by_key(|o| o.sort_key),
+    }
+
+    Cow::Owned(sorted_objects)
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Vec3A};
+    use rend3::{
+        managers::{CameraManager, GpuCullingInput, InternalObject},
+        types::{
+            Camera, DepthMode, Handedness, MaterialHandle, MeshHandle, ObjectDepthMode, ObjectFade, ObjectHandle,
+            ObjectMeshKind, ObjectShadowMode,
+        },
+    };
+
+    use super::{sort_objects, Sorting};
+
+    fn object(idx: usize, distance: f32, sort_key: i32) -> InternalObject {
+        InternalObject {
+            handle: ObjectHandle::new(idx).get_raw(),
+            mesh_kind: ObjectMeshKind::Static(MeshHandle::new(0)),
+            material_handle: MaterialHandle::new(0),
+            location: Vec3A::new(0.0, 0.0, distance),
+            input: GpuCullingInput {
+                transform: Mat4::IDENTITY,
+                ..bytemuck::Zeroable::zeroed()
+            },
+            texture_layer: 0,
+            depth_mode: ObjectDepthMode::Default,
+            sort_key,
+            cell: None,
+            local_transform: Mat4::IDENTITY,
+            group: None,
+            hidden: false,
+            shadow_mode: ObjectShadowMode::Default,
+            fade: ObjectFade::OPAQUE,
+            moved: false,
+            previous_transform: Mat4::IDENTITY,
+            label: None,
+        }
+    }
+
+    fn order(objects: &[InternalObject], sorting: Option<Sorting>) -> Vec<usize> {
+        let camera = CameraManager::new(Camera::default(), Handedness::Left, DepthMode::default(), None);
+        sort_objects(objects, &camera, sorting)
+            .iter()
+            .map(|o| o.handle.idx)
+            .collect()
+    }
+
+    #[test]
+    fn keys_before_distance() {
+        let objects = [
+            object(0, 3.0, 0),
+            object(1, 1.0, 1),
+            object(2, 2.0, 0),
+            object(3, 4.0, -1),
+        ];
 
-        Cow::Owned(sorted_objects)
-    } else {
-        Cow::Borrowed(objects)
+        assert_eq!(order(&objects, Some(Sorting::FrontToBack)), [3, 2, 0, 1]);
+        assert_eq!(order(&objects, Some(Sorting::BackToFront)), [3, 0, 2, 1]);
+        assert_eq!(order(&objects, None), [3, 0, 2, 1]);
+        assert_eq!(order(&objects[..1], None), [0]);
     }
 }
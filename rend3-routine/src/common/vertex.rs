@@ -1,9 +1,105 @@
-use rend3::managers::{
-    VERTEX_COLOR_SLOT, VERTEX_JOINT_INDEX_SLOT, VERTEX_JOINT_WEIGHT_SLOT, VERTEX_NORMAL_SLOT, VERTEX_OBJECT_INDEX_SLOT,
-    VERTEX_POSITION_SLOT, VERTEX_TANGENT_SLOT, VERTEX_UV0_SLOT, VERTEX_UV1_SLOT,
+use arrayvec::ArrayVec;
+use rend3::{
+    managers::{
+        VertexAttributes, VertexQuantization, VERTEX_COLOR_SLOT, VERTEX_JOINT_INDEX_SLOT, VERTEX_JOINT_WEIGHT_SLOT,
+        VERTEX_NORMAL_SLOT, VERTEX_OBJECT_INDEX_SLOT, VERTEX_POSITION_SLOT, VERTEX_TANGENT_SLOT, VERTEX_UV0_SLOT,
+        VERTEX_UV1_SLOT,
+    },
+    Renderer, RendererProfile,
 };
 use wgpu::{VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode};
 
+/// Vertex buffer layouts of pipelines drawing the meshes of `renderer`:
+/// [`CPU_VERTEX_BUFFERS`] or [`GPU_VERTEX_BUFFERS`] depending on the
+/// profile, with the formats of the attributes the renderer quantizes, and
+/// stepping per instance through the attributes the renderer was created
+/// without, so every vertex reads the default value of its instance.
+pub fn vertex_buffer_layouts(renderer: &Renderer) -> ArrayVec<VertexBufferLayout<'static>, 9> {
+    layouts(
+        renderer.profile,
+        renderer.vertex_attributes,
+        renderer.vertex_quantization,
+    )
+}
+
+fn layouts(
+    profile: RendererProfile,
+    attributes: VertexAttributes,
+    quantization: VertexQuantization,
+) -> ArrayVec<VertexBufferLayout<'static>, 9> {
+    let mut layouts: ArrayVec<_, 9> = match profile {
+        RendererProfile::CpuDriven | RendererProfile::Downlevel => CPU_VERTEX_BUFFERS.iter().cloned().collect(),
+        RendererProfile::GpuDriven => GPU_VERTEX_BUFFERS.iter().cloned().collect(),
+    };
+    let quantized: [(u32, VertexQuantization, usize, &'static [VertexAttribute]); 5] = [
+        (
+            VERTEX_POSITION_SLOT,
+            VertexQuantization::POSITIONS,
+            quantization.position_size(),
+            &QUANTIZED_POSITION,
+        ),
+        (
+            VERTEX_NORMAL_SLOT,
+            VertexQuantization::NORMALS,
+            quantization.normal_size(),
+            &QUANTIZED_NORMAL,
+        ),
+        (
+            VERTEX_TANGENT_SLOT,
+            VertexQuantization::NORMALS,
+            quantization.normal_size(),
+            &QUANTIZED_TANGENT,
+        ),
+        (
+            VERTEX_UV0_SLOT,
+            VertexQuantization::UVS,
+            quantization.uv_size(),
+            &QUANTIZED_UV0,
+        ),
+        (
+            VERTEX_UV1_SLOT,
+            VertexQuantization::UVS,
+            quantization.uv_size(),
+            &QUANTIZED_UV1,
+        ),
+    ];
+    for (slot, flag, size, attributes) in quantized {
+        if quantization.contains(flag) {
+            let layout = &mut layouts[slot as usize];
+            layout.array_stride = size as u64;
+            layout.attributes = attributes;
+        }
+    }
+    let optional = [
+        (VERTEX_TANGENT_SLOT, VertexAttributes::TANGENT),
+        (VERTEX_UV0_SLOT, VertexAttributes::UV0),
+        (VERTEX_UV1_SLOT, VertexAttributes::UV1),
+        (VERTEX_COLOR_SLOT, VertexAttributes::COLOR),
+        (VERTEX_JOINT_INDEX_SLOT, VertexAttributes::JOINTS),
+        (VERTEX_JOINT_WEIGHT_SLOT, VertexAttributes::JOINTS),
+    ];
+    for (slot, attribute) in optional {
+        if !attributes.contains(attribute) {
+            layouts[slot as usize].step_mode = VertexStepMode::Instance;
+        }
+    }
+    layouts
+}
+
+const fn attribute(format: VertexFormat, shader_location: u32) -> [VertexAttribute; 1] {
+    [VertexAttribute {
+        format,
+        offset: 0,
+        shader_location,
+    }]
+}
+
+static QUANTIZED_POSITION: [VertexAttribute; 1] = attribute(VertexFormat::Snorm16x4, VERTEX_POSITION_SLOT);
+static QUANTIZED_NORMAL: [VertexAttribute; 1] = attribute(VertexFormat::Snorm16x2, VERTEX_NORMAL_SLOT);
+static QUANTIZED_TANGENT: [VertexAttribute; 1] = attribute(VertexFormat::Snorm16x2, VERTEX_TANGENT_SLOT);
+static QUANTIZED_UV0: [VertexAttribute; 1] = attribute(VertexFormat::Float16x2, VERTEX_UV0_SLOT);
+static QUANTIZED_UV1: [VertexAttribute; 1] = attribute(VertexFormat::Float16x2, VERTEX_UV1_SLOT);
+
 /// Vertex buffer layouts used when CpuDriven.
 pub static CPU_VERTEX_BUFFERS: [VertexBufferLayout<'static>; 8] = [
     VertexBufferLayout {
@@ -164,3 +260,66 @@ pub static GPU_VERTEX_BUFFERS: [VertexBufferLayout<'static>; 9] = [
         }],
     },
 ];
+
+#[cfg(test)]
+mod test {
+    use rend3::{
+        managers::{
+            VertexAttributes, VertexQuantization, VERTEX_COLOR_SIZE, VERTEX_COLOR_SLOT, VERTEX_JOINT_WEIGHT_SIZE,
+            VERTEX_JOINT_WEIGHT_SLOT, VERTEX_NORMAL_SIZE, VERTEX_NORMAL_SLOT, VERTEX_OBJECT_INDEX_SLOT,
+            VERTEX_UV0_SLOT, VERTEX_UV_SIZE,
+        },
+        RendererProfile,
+    };
+    use wgpu::{VertexFormat, VertexStepMode};
+
+    use super::layouts;
+
+    #[test]
+    fn every_attribute_steps_per_vertex() {
+        for profile in [RendererProfile::CpuDriven, RendererProfile::GpuDriven] {
+            let layouts = layouts(profile, VertexAttributes::all(), VertexQuantization::empty());
+            for layout in &layouts[..VERTEX_OBJECT_INDEX_SLOT as usize] {
+                assert_eq!(layout.step_mode, VertexStepMode::Vertex);
+                assert_ne!(layout.array_stride, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn left_out_attributes_step_per_instance_with_their_size() {
+        let attributes = VertexAttributes::all() - VertexAttributes::UV0 - VertexAttributes::COLOR;
+        for profile in [RendererProfile::CpuDriven, RendererProfile::GpuDriven] {
+            let layouts = layouts(profile, attributes, VertexQuantization::empty());
+
+            let uv0 = &layouts[VERTEX_UV0_SLOT as usize];
+            assert_eq!(uv0.step_mode, VertexStepMode::Instance);
+            assert_eq!(uv0.array_stride, VERTEX_UV_SIZE as u64);
+            let color = &layouts[VERTEX_COLOR_SLOT as usize];
+            assert_eq!(color.step_mode, VertexStepMode::Instance);
+            assert_eq!(color.array_stride, VERTEX_COLOR_SIZE as u64);
+
+            let normal = &layouts[VERTEX_NORMAL_SLOT as usize];
+            assert_eq!(normal.step_mode, VertexStepMode::Vertex);
+            assert_eq!(normal.array_stride, VERTEX_NORMAL_SIZE as u64);
+        }
+    }
+
+    #[test]
+    fn left_out_quantized_attributes_keep_their_quantized_size() {
+        let attributes = VertexAttributes::all() - VertexAttributes::UV0 - VertexAttributes::JOINTS;
+        let layouts = layouts(RendererProfile::GpuDriven, attributes, VertexQuantization::UVS);
+
+        let uv0 = &layouts[VERTEX_UV0_SLOT as usize];
+        assert_eq!(uv0.step_mode, VertexStepMode::Instance);
+        assert_eq!(uv0.array_stride, VertexQuantization::UVS.uv_size() as u64);
+        assert_eq!(uv0.attributes[0].format, VertexFormat::Float16x2);
+
+        let joint_weight = &layouts[VERTEX_JOINT_WEIGHT_SLOT as usize];
+        assert_eq!(joint_weight.step_mode, VertexStepMode::Instance);
+        assert_eq!(joint_weight.array_stride, VERTEX_JOINT_WEIGHT_SIZE as u64);
+
+        let object_index = &layouts[VERTEX_OBJECT_INDEX_SLOT as usize];
+        assert_eq!(object_index.step_mode, VertexStepMode::Instance);
+    }
+}
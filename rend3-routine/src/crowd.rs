@@ -0,0 +1,696 @@
+//! Crowds of skinned characters animated entirely on the GPU.
+//!
+//! Sampled skeletal animation is baked into a [`BakedCrowdAnimation`], whose
+//! skinning matrices are uploaded as a texture holding one row per frame with
+//! [`CrowdRoutine::upload_animation`]. A [`Crowd`] then draws a skinned mesh
+//! once per [`CrowdInstance`], each playing its own clip at its own time, so
+//! thousands of characters cost one draw and no posing on the CPU. The
+//! instances are uploaded once, and only the clock of the crowd changes every
+//! frame. `rend3_anim::crowd` bakes the animations of gltf scenes.
+//!
+//! Crowds are drawn with a [`CrowdRoutine`] into the HDR targets of the base
+//! rendergraph after the forward pass, see
+//! [`BaseRenderGraphIntermediateState::crowds`]. They are not PBR objects:
+//! they are lit by the ambient light and [`UnshadowedLights`], are not culled
+//! per instance, and cast no shadows themselves.
+//!
+//! [`BaseRenderGraphIntermediateState::crowds`]: crate::base::BaseRenderGraphIntermediateState::crowds
+
+use std::{mem, sync::Arc};
+
+use glam::{Mat4, Vec4};
+use rend3::{
+    graph::{DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    managers::{VertexAttributes, VERTEX_JOINT_INDEX_SIZE, VERTEX_JOINT_WEIGHT_SIZE},
+    types::{DepthMode, Handedness, MeshHandle, SampleCount, TextureHandle},
+    util::{
+        bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+        quantize::PositionDequantization,
+    },
+    Renderer,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroupLayout, BindingType, Buffer, BufferAddress, BufferBindingType, BufferUsages, Color,
+    ColorTargetState, ColorWrites, DepthBiasState, DepthStencilState, Extent3d, Face, FilterMode, FragmentState,
+    FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology,
+    RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, StencilState, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+    TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexAttribute, VertexBufferLayout,
+    VertexFormat, VertexState, VertexStepMode,
+};
+
+use crate::{
+    common::{UnshadowedLights, MAX_UNSHADOWED_LIGHTS},
+    shaders,
+};
+
+/// Most clips a [`BakedCrowdAnimation`] can have.
+pub const MAX_CROWD_CLIPS: usize = 32;
+
+/// A clip of a [`BakedCrowdAnimation`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrowdClip {
+    /// Frame the clip starts at, counting over the frames of all clips.
+    pub first_frame: u32,
+    pub frame_count: u32,
+    /// Frames per second.
+    pub sample_rate: f32,
+    /// Wraps the time around instead of holding the last frame, blending from
+    /// the last frame back to the first.
+    pub looping: bool,
+}
+
+impl CrowdClip {
+    /// The two frames blended at `time` seconds into the clip, and how far to
+    /// blend from the first to the second, as the shader computes them.
+    pub fn frames_at(&self, time: f32) -> (u32, u32, f32) {
+        let count = self.frame_count as f32;
+        let position = time * self.sample_rate;
+        let frame = match self.looping {
+            true => position - count * (position / count).floor(),
+            false => position.clamp(0.0, count - 1.0),
+        };
+        let index = frame.floor();
+        let next = match (index + 1.0 >= count, self.looping) {
+            (false, _) => index + 1.0,
+            (true, true) => 0.0,
+            (true, false) => index,
+        };
+        (
+            self.first_frame + index as u32,
+            self.first_frame + next as u32,
+            frame - index,
+        )
+    }
+}
+
+/// Skinning matrices of a skeleton, sampled at a fixed rate for several
+/// clips.
+///
+/// A skinning matrix takes a vertex of the mesh in its bind pose to where the
+/// joint moves it, i.e. the global transform of the joint times its inverse
+/// bind matrix.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BakedCrowdAnimation {
+    pub joint_count: u32,
+    pub clips: Vec<CrowdClip>,
+    /// `joint_count` matrices for every frame of every clip, in order.
+    pub matrices: Vec<Mat4>,
+}
+
+impl BakedCrowdAnimation {
+    pub fn new(joint_count: u32) -> Self {
+        Self {
+            joint_count,
+            clips: Vec::new(),
+            matrices: Vec::new(),
+        }
+    }
+
+    /// Adds a clip sampled `sample_rate` times per second, with the skinning
+    /// matrices of every joint for each of its frames. Returns the index of
+    /// the clip.
+    pub fn add_clip(&mut self, sample_rate: f32, looping: bool, matrices: &[Mat4]) -> u32 {
+        let joint_count = self.joint_count as usize;
+        assert!(
+            !matrices.is_empty() && matrices.len() % joint_count == 0,
+            "clips need whole frames of {} joint matrices",
+            joint_count
+        );
+        assert!(
+            self.clips.len() < MAX_CROWD_CLIPS,
+            "crowd animations have at most {} clips",
+            MAX_CROWD_CLIPS
+        );
+
+        self.clips.push(CrowdClip {
+            first_frame: self.frame_count(),
+            frame_count: (matrices.len() / joint_count) as u32,
+            sample_rate,
+            looping,
+        });
+        self.matrices.extend_from_slice(matrices);
+        self.clips.len() as u32 - 1
+    }
+
+    /// Frames of all clips together.
+    pub fn frame_count(&self) -> u32 {
+        (self.matrices.len() / (self.joint_count as usize).max(1)) as u32
+    }
+
+    /// Texels of the animation texture, holding the first three rows of every
+    /// matrix, as the bottom row of an affine transform is always the same.
+    fn texels(&self) -> Vec<[f32; 4]> {
+        self.matrices
+            .iter()
+            .flat_map(|matrix| {
+                let rows = matrix.transpose();
+                [rows.x_axis.into(), rows.y_axis.into(), rows.z_axis.into()]
+            })
+            .collect()
+    }
+}
+
+/// A [`BakedCrowdAnimation`] uploaded to the GPU with
+/// [`CrowdRoutine::upload_animation`].
+pub struct CrowdAnimation {
+    view: TextureView,
+    clips: Buffer,
+}
+
+/// A character of a [`Crowd`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrowdInstance {
+    pub transform: Mat4,
+    /// Index of the clip played, as returned by
+    /// [`BakedCrowdAnimation::add_clip`].
+    pub clip: u32,
+    /// Time into the clip when the clock of the crowd is at zero, in seconds.
+    /// Spreading these keeps the crowd out of lockstep.
+    pub time_offset: f32,
+    /// How fast the clip plays compared to the clock of the crowd.
+    pub speed: f32,
+}
+
+impl CrowdInstance {
+    pub fn new(transform: Mat4, clip: u32) -> Self {
+        Self {
+            transform,
+            clip,
+            time_offset: 0.0,
+            speed: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct CrowdInstanceAbi {
+    transform: Mat4,
+    clip: u32,
+    time_offset: f32,
+    speed: f32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Pod for CrowdInstanceAbi {}
+unsafe impl bytemuck::Zeroable for CrowdInstanceAbi {}
+
+/// Many instances of a skinned mesh playing a [`CrowdAnimation`], created
+/// with [`CrowdRoutine::create_crowd`].
+pub struct Crowd {
+    /// Skinned mesh whose joint indices index the joints of the animation.
+    pub mesh: MeshHandle,
+    pub animation: Arc<CrowdAnimation>,
+    /// 2D texture multiplied with `color`, sampled with the first UV set.
+    pub albedo: Option<TextureHandle>,
+    /// Linear color, with alpha.
+    pub color: Vec4,
+    /// Clock of the crowd in seconds, which every instance plays its clip
+    /// with. Advance it every frame.
+    pub time: f32,
+    instances: Buffer,
+    instance_count: u32,
+}
+
+impl Crowd {
+    /// Replaces the instances of the crowd.
+    pub fn set_instances(&mut self, renderer: &Renderer, instances: &[CrowdInstance]) {
+        self.instances = create_instance_buffer(renderer, instances);
+        self.instance_count = instances.len() as u32;
+    }
+
+    pub fn instance_count(&self) -> u32 {
+        self.instance_count
+    }
+}
+
+fn create_instance_buffer(renderer: &Renderer, instances: &[CrowdInstance]) -> Buffer {
+    // Every instance reads its own value of the attributes the renderer
+    // doesn't store.
+    renderer
+        .data_core
+        .lock()
+        .mesh_manager
+        .reserve_default_instances(&renderer.device, instances.len());
+
+    let data: Vec<CrowdInstanceAbi> = instances
+        .iter()
+        .map(|instance| CrowdInstanceAbi {
+            transform: instance.transform,
+            clip: instance.clip,
+            time_offset: instance.time_offset,
+            speed: instance.speed,
+            _padding: 0.0,
+        })
+        .collect();
+    renderer.device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("crowd instances"),
+        contents: bytemuck::cast_slice(&data),
+        usage: BufferUsages::VERTEX,
+    })
+}
+
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+struct CrowdUniforms {
+    view_proj: Mat4,
+    color: Vec4,
+    ambient: Vec4,
+    light_directions: [Vec4; MAX_UNSHADOWED_LIGHTS],
+    light_colors: [Vec4; MAX_UNSHADOWED_LIGHTS],
+    position_dequantization: PositionDequantization,
+    light_count: u32,
+    time: f32,
+    vertex_quantization: u32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Pod for CrowdUniforms {}
+unsafe impl bytemuck::Zeroable for CrowdUniforms {}
+
+/// Draws [`Crowd`]s.
+///
+/// See module for documentation.
+pub struct CrowdRoutine {
+    pipeline_s1: RenderPipeline,
+    pipeline_s4: RenderPipeline,
+    bgl: BindGroupLayout,
+    sampler: Sampler,
+    white: TextureView,
+    depth_mode: DepthMode,
+}
+
+impl CrowdRoutine {
+    pub fn new(renderer: &Renderer) -> Self {
+        profiling::scope!("CrowdRoutine::new");
+
+        let sampler = renderer.device.create_sampler(&SamplerDescriptor {
+            label: Some("crowd"),
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+
+        // Sampled by crowds without an albedo texture.
+        let white = renderer
+            .device
+            .create_texture_with_data(
+                &renderer.queue,
+                &TextureDescriptor {
+                    label: Some("crowd white"),
+                    size: Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8Unorm,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                },
+                &[255; 4],
+            )
+            .create_view(&TextureViewDescriptor::default());
+
+        let uniform = BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(ShaderStages::VERTEX_FRAGMENT, uniform, None)
+            .append(
+                ShaderStages::VERTEX,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(ShaderStages::VERTEX, uniform, None)
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .build(&renderer.device, Some("crowd bgl"));
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("crowd"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "crowd.wgsl",
+                include_str!("../shaders/src/crowd.wgsl"),
+            )),
+        });
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("crowd"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        // Attributes the renderer doesn't store are a value per instance.
+        let vertex_buffer = |stride: usize, needs: VertexAttributes, location: u32, format: VertexFormat| {
+            let step_mode = match renderer.vertex_attributes.contains(needs) {
+                true => VertexStepMode::Vertex,
+                false => VertexStepMode::Instance,
+            };
+            (
+                stride as BufferAddress,
+                step_mode,
+                [VertexAttribute {
+                    format,
+                    offset: 0,
+                    shader_location: location,
+                }],
+            )
+        };
+        let quantization = renderer.vertex_quantization;
+        let position = vertex_buffer(
+            quantization.position_size(),
+            VertexAttributes::empty(),
+            0,
+            quantization.position_format(),
+        );
+        let normal = vertex_buffer(
+            quantization.normal_size(),
+            VertexAttributes::empty(),
+            1,
+            quantization.normal_format(),
+        );
+        let uv = vertex_buffer(
+            quantization.uv_size(),
+            VertexAttributes::UV0,
+            2,
+            quantization.uv_format(),
+        );
+        let joint_index = vertex_buffer(
+            VERTEX_JOINT_INDEX_SIZE,
+            VertexAttributes::JOINTS,
+            3,
+            VertexFormat::Uint16x4,
+        );
+        let joint_weight = vertex_buffer(
+            VERTEX_JOINT_WEIGHT_SIZE,
+            VertexAttributes::JOINTS,
+            4,
+            VertexFormat::Float32x4,
+        );
+        let instance_attributes = wgpu::vertex_attr_array![
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32x4,
+            9 => Uint32,
+            10 => Float32,
+            11 => Float32,
+        ];
+        let buffers: Vec<VertexBufferLayout> = [&position, &normal, &uv, &joint_index, &joint_weight]
+            .into_iter()
+            .map(|(stride, step_mode, attributes)| VertexBufferLayout {
+                array_stride: *stride,
+                step_mode: *step_mode,
+                attributes,
+            })
+            .chain([VertexBufferLayout {
+                array_stride: mem::size_of::<CrowdInstanceAbi>() as BufferAddress,
+                step_mode: VertexStepMode::Instance,
+                attributes: &instance_attributes,
+            }])
+            .collect();
+
+        let inner = |samples: SampleCount| {
+            renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("crowd"),
+                layout: Some(&pll),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &buffers,
+                },
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: match renderer.handedness {
+                        Handedness::Left => FrontFace::Cw,
+                        Handedness::Right => FrontFace::Ccw,
+                    },
+                    cull_mode: Some(Face::Back),
+                    unclipped_depth: false,
+                    polygon_mode: PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format: TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: renderer.depth_mode.closer_or_equal(),
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState {
+                    count: samples as u32,
+                    ..Default::default()
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::all(),
+                    }],
+                }),
+                multiview: None,
+            })
+        };
+
+        Self {
+            pipeline_s1: inner(SampleCount::One),
+            pipeline_s4: inner(SampleCount::Four),
+            bgl,
+            sampler,
+            white,
+            depth_mode: renderer.depth_mode,
+        }
+    }
+
+    /// Uploads the skinning matrices of `animation` as a texture three texels
+    /// wide per joint and one row high per frame, so both must fit in the
+    /// texture size limit of the device.
+    pub fn upload_animation(&self, renderer: &Renderer, animation: &BakedCrowdAnimation) -> Arc<CrowdAnimation> {
+        let max_size = renderer.limits.max_texture_dimension_2d;
+        let size = Extent3d {
+            width: animation.joint_count * 3,
+            height: animation.frame_count(),
+            depth_or_array_layers: 1,
+        };
+        assert!(
+            size.width <= max_size && size.height <= max_size,
+            "crowd animation of {} joints and {} frames doesn't fit in a texture",
+            animation.joint_count,
+            size.height
+        );
+
+        let texels = animation.texels();
+        let view = renderer
+            .device
+            .create_texture_with_data(
+                &renderer.queue,
+                &TextureDescriptor {
+                    label: Some("crowd animation"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba32Float,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                },
+                bytemuck::cast_slice(&texels),
+            )
+            .create_view(&TextureViewDescriptor::default());
+
+        let mut clips = [Vec4::ZERO; MAX_CROWD_CLIPS];
+        for (dst, clip) in clips.iter_mut().zip(&animation.clips) {
+            *dst = Vec4::new(
+                clip.first_frame as f32,
+                clip.frame_count as f32,
+                clip.sample_rate,
+                clip.looping as u32 as f32,
+            );
+        }
+        let clips = renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("crowd clips"),
+            contents: bytemuck::cast_slice(&clips),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        Arc::new(CrowdAnimation { view, clips })
+    }
+
+    /// Creates a crowd of `instances` of `mesh` playing clips of `animation`.
+    pub fn create_crowd(
+        &self,
+        renderer: &Renderer,
+        mesh: MeshHandle,
+        animation: Arc<CrowdAnimation>,
+        instances: &[CrowdInstance],
+    ) -> Crowd {
+        Crowd {
+            mesh,
+            animation,
+            albedo: None,
+            color: Vec4::ONE,
+            time: 0.0,
+            instances: create_instance_buffer(renderer, instances),
+            instance_count: instances.len() as u32,
+        }
+    }
+
+    /// Draws `crowds` into the HDR targets, lit by `ambient` and the
+    /// directional lights of the renderer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        crowds: &'node [Crowd],
+        color: RenderTargetHandle,
+        resolve: Option<RenderTargetHandle>,
+        depth: RenderTargetHandle,
+        ambient: Vec4,
+        samples: SampleCount,
+    ) {
+        if crowds.is_empty() {
+            return;
+        }
+
+        let mut builder = graph.add_node("Crowds");
+
+        let hdr_color_handle = builder.add_render_target_output(color);
+        let hdr_resolve = builder.add_optional_render_target_output(resolve);
+        let hdr_depth_handle = builder.add_render_target_output(depth);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: hdr_color_handle,
+                clear: Color::BLACK,
+                resolve: hdr_resolve,
+            }],
+            depth_stencil: Some(RenderPassDepthTarget {
+                target: DepthHandle::RenderTarget(hdr_depth_handle),
+                depth_clear: Some(self.depth_mode.far_depth()),
+                stencil_clear: None,
+            }),
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+        let crowd_handles: Vec<_> = crowds.iter().map(|crowd| builder.passthrough_ref(crowd)).collect();
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("crowds");
+
+            let lights = UnshadowedLights::new(graph_data.directional_light_manager);
+
+            let pipeline = match samples {
+                SampleCount::One => &this.pipeline_s1,
+                SampleCount::Four => &this.pipeline_s4,
+            };
+            rpass.set_pipeline(pipeline);
+
+            let buffers = graph_data.mesh_manager.buffers();
+            rpass.set_vertex_buffer(0, buffers.vertex_position.slice(..));
+            rpass.set_vertex_buffer(1, buffers.vertex_normal.slice(..));
+            rpass.set_vertex_buffer(2, buffers.vertex_uv0.slice(..));
+            rpass.set_vertex_buffer(3, buffers.vertex_joint_index.slice(..));
+            rpass.set_vertex_buffer(4, buffers.vertex_joint_weight.slice(..));
+            rpass.set_index_buffer(buffers.index.slice(..), wgpu::IndexFormat::Uint32);
+
+            for handle in crowd_handles {
+                let crowd = pt.get(handle);
+                if crowd.instance_count == 0 {
+                    continue;
+                }
+
+                let mesh = graph_data.mesh_manager.internal_data(crowd.mesh.get_raw());
+
+                let uniforms = CrowdUniforms {
+                    view_proj: graph_data.camera_manager.view_proj(),
+                    color: crowd.color,
+                    ambient,
+                    light_directions: lights.directions,
+                    light_colors: lights.colors,
+                    position_dequantization: mesh.position_dequantization,
+                    light_count: lights.count,
+                    time: crowd.time,
+                    vertex_quantization: renderer.vertex_quantization.bits() as u32,
+                    _padding: 0.0,
+                };
+                let buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("crowd uniforms"),
+                    contents: bytemuck::bytes_of(&uniforms),
+                    usage: BufferUsages::UNIFORM,
+                });
+                let albedo = match &crowd.albedo {
+                    Some(texture) => graph_data.d2_texture_manager.get_view(texture.get_raw()),
+                    None => &this.white,
+                };
+                let bg = temps.add(
+                    BindGroupBuilder::new()
+                        .append_buffer(&buffer)
+                        .append_texture_view(&crowd.animation.view)
+                        .append_buffer(&crowd.animation.clips)
+                        .append_texture_view(albedo)
+                        .append_sampler(&this.sampler)
+                        .build(&renderer.device, Some("crowd bg"), &this.bgl),
+                );
+
+                let indices = mesh.index_range.start as u32..mesh.index_range.end as u32;
+
+                rpass.set_bind_group(0, bg, &[]);
+                rpass.set_vertex_buffer(5, crowd.instances.slice(..));
+                rpass.draw_indexed(indices, mesh.vertex_range.start as i32, 0..crowd.instance_count);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CrowdClip;
+
+    #[test]
+    fn clips_wrap_or_hold() {
+        let mut clip = CrowdClip {
+            first_frame: 10,
+            frame_count: 4,
+            sample_rate: 2.0,
+            looping: true,
+        };
+        assert_eq!(clip.frames_at(0.25), (10, 11, 0.5));
+        // Blends from the last frame back to the first.
+        assert_eq!(clip.frames_at(1.75), (13, 10, 0.5));
+        assert_eq!(clip.frames_at(2.25), (10, 11, 0.5));
+        assert_eq!(clip.frames_at(-0.25), (13, 10, 0.5));
+
+        clip.looping = false;
+        assert_eq!(clip.frames_at(1.75), (13, 13, 0.0));
+        assert_eq!(clip.frames_at(-1.0), (10, 11, 0.0));
+    }
+}
@@ -0,0 +1,531 @@
+//! Conversions of environment maps into cube textures, for skyboxes and
+//! image based lighting.
+//!
+//! Environment maps are usually shipped as equirectangular panoramas, which
+//! [`EquirectToCube`] projects onto the faces of a cube texture on the gpu.
+//! Load hdr or OpenEXR panoramas with
+//! [`Texture::from_encoded_bytes`](rend3::types::Texture::from_encoded_bytes)
+//! to keep their full range.
+//!
+//! The cubes can then be turned into the inputs of the split sum
+//! approximation of image based lighting: [`CubemapFilter`] convolves them
+//! into irradiance maps and mip chains prefiltered for each roughness, and
+//! [`brdf_lut`] renders the lookup table of the specular lobe. None of these
+//! are tied to a routine, so they can feed custom lighting pipelines.
+
+use std::{num::NonZeroU64, sync::Arc};
+
+use glam::{UVec2, Vec3};
+use rend3::{
+    types::{Texture, TextureFormat, TextureHandle},
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer, RendererError,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroupLayout, BindingType, BufferBindingType, BufferUsages, Color, CommandEncoder,
+    CommandEncoderDescriptor, Device, Extent3d, FilterMode, LoadOp, Operations, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, Sampler, SamplerBindingType, SamplerDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureDescriptor, TextureDimension, TextureSampleType,
+    TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
+};
+
+use crate::{
+    environment::{create_pipeline, equirect_basis},
+    shaders::source_shader,
+};
+
+/// Format of the cube textures made by [`EquirectToCube::convert`]. It keeps
+/// the range of hdr panoramas, and can be rendered into, unlike
+/// `Rg11b10Float`.
+pub const CUBEMAP_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Format of the lookup tables made by [`brdf_lut`], holding the scale in
+/// red and the bias in green.
+pub const BRDF_LUT_FORMAT: TextureFormat = TextureFormat::Rg16Float;
+
+/// Projects equirectangular panoramas onto the faces of cube textures.
+///
+/// The projection is the inverse of
+/// [`EnvironmentCapture::equirect`](crate::environment::EnvironmentCapture::equirect),
+/// so captured panoramas turn back into the cube they came from.
+pub struct EquirectToCube {
+    bgl: BindGroupLayout,
+    sampler: Sampler,
+    pipeline: RenderPipeline,
+}
+
+impl EquirectToCube {
+    pub fn new(renderer: &Renderer) -> Self {
+        profiling::scope!("EquirectToCube::new");
+
+        let device = &renderer.device;
+        let module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("equirect to cube"),
+            source: ShaderSource::Wgsl(source_shader(
+                "cubemap.wgsl",
+                include_str!("../shaders/src/cubemap.wgsl"),
+            )),
+        });
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(64),
+                },
+                None,
+            )
+            .build(device, Some("equirect to cube"));
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("equirect to cube"),
+            // Longitude wraps around, latitude stops at the poles.
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        let pipeline = create_pipeline(device, &module, &[&bgl], "fs_equirect", CUBEMAP_FORMAT);
+
+        Self { bgl, sampler, pipeline }
+    }
+
+    /// Uploads `panorama`, which should be twice as wide as it is high, and
+    /// projects it onto a new [`CUBEMAP_FORMAT`] cube texture whose faces
+    /// are `size` pixels wide. The center of the panorama ends up along
+    /// `forward`.
+    ///
+    /// Only the first mip of `panorama` is used, so faces much smaller than
+    /// a quarter of its width alias.
+    pub fn convert(
+        &self,
+        renderer: &Renderer,
+        panorama: &Texture,
+        size: u32,
+        forward: Vec3,
+        up: Vec3,
+    ) -> Result<TextureHandle, RendererError> {
+        self.convert_with_view(renderer, panorama, size, forward, up)
+            .map(|(handle, _)| handle)
+    }
+
+    /// Same as [`convert`](Self::convert), also returning a cube view of the
+    /// texture to pass to [`CubemapFilter`].
+    pub fn convert_with_view(
+        &self,
+        renderer: &Renderer,
+        panorama: &Texture,
+        size: u32,
+        forward: Vec3,
+        up: Vec3,
+    ) -> Result<(TextureHandle, Arc<TextureView>), RendererError> {
+        profiling::scope!("Equirect To Cube");
+
+        let info = panorama.format.describe();
+        if info.sample_type != (TextureSampleType::Float { filterable: true }) {
+            return Err(RendererError::UnsupportedTextureFormat {
+                format: panorama.format,
+                sample_type: info.sample_type,
+            });
+        }
+        let extent = Extent3d {
+            width: panorama.size.x,
+            height: panorama.size.y,
+            depth_or_array_layers: 1,
+        };
+        let limit = renderer.limits.max_texture_dimension_2d;
+        if extent.width.max(extent.height) > limit {
+            return Err(RendererError::TextureTooLarge { size: extent, limit });
+        }
+        let (block_width, block_height) = info.block_dimensions;
+        let blocks_wide = (extent.width + block_width as u32 - 1) / block_width as u32;
+        let blocks_high = (extent.height + block_height as u32 - 1) / block_height as u32;
+        let expected = (blocks_wide * blocks_high) as usize * info.block_size as usize;
+        if panorama.data.len() < expected {
+            return Err(RendererError::TextureDataTooSmall {
+                actual: panorama.data.len(),
+                expected,
+            });
+        }
+
+        let (handle, cube_view, faces) = renderer.add_texture_cube_render_target(size, CUBEMAP_FORMAT)?;
+
+        // Data past the first mip is ignored.
+        let source = renderer.device.create_texture_with_data(
+            &renderer.queue,
+            &TextureDescriptor {
+                label: Some("equirect panorama"),
+                size: extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: panorama.format,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            },
+            &panorama.data,
+        );
+        let source_view = source.create_view(&TextureViewDescriptor::default());
+        self.convert_into(renderer, &source_view, &faces, forward, up);
+
+        Ok((handle, cube_view))
+    }
+
+    /// Projects the panorama `source` onto the faces of a [`CUBEMAP_FORMAT`]
+    /// cube, such as those returned by
+    /// [`Renderer::add_texture_cube_render_target`], in the order +X, -X,
+    /// +Y, -Y, +Z, -Z.
+    pub fn convert_into(
+        &self,
+        renderer: &Renderer,
+        source: &TextureView,
+        faces: &[Arc<TextureView>; 6],
+        forward: Vec3,
+        up: Vec3,
+    ) {
+        let device = &renderer.device;
+        let basis = equirect_basis(renderer.handedness, forward, up);
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("equirect to cube"),
+            contents: bytemuck::bytes_of(&basis),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = BindGroupBuilder::new()
+            .append_texture_view(source)
+            .append_sampler(&self.sampler)
+            .append_buffer(&params)
+            .build(device, Some("equirect to cube"), &self.bgl);
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("equirect to cube"),
+        });
+        for (face, view) in (0..6).zip(faces) {
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("equirect to cube face"),
+                color_attachments: &[RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, face..face + 1);
+        }
+        renderer.queue.submit(Some(encoder.finish()));
+    }
+}
+
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+struct FilterParams {
+    roughness: f32,
+    sample_count: u32,
+    source_size: f32,
+    _padding: u32,
+}
+
+unsafe impl bytemuck::Pod for FilterParams {}
+unsafe impl bytemuck::Zeroable for FilterParams {}
+
+fn filter_module(device: &Device) -> ShaderModule {
+    device.create_shader_module(&ShaderModuleDescriptor {
+        label: Some("cubemap filter"),
+        source: ShaderSource::Wgsl(source_shader(
+            "cubemap_filter.wgsl",
+            include_str!("../shaders/src/cubemap_filter.wgsl"),
+        )),
+    })
+}
+
+/// Convolutions of cube textures into the inputs of image based lighting:
+/// irradiance maps for diffuse lighting, and mip chains prefiltered for
+/// increasingly rough specular reflections.
+///
+/// Sources are importance sampled, `sample_count` times per texel. Sources
+/// with mips, like those from
+/// [`Renderer::add_texture_cube_render_target_with_mips`], are sampled at
+/// the mip matching the spread of the samples, which removes most of the
+/// noise bright texels would cause with few samples.
+pub struct CubemapFilter {
+    bgl: BindGroupLayout,
+    sampler: Sampler,
+    irradiance_pipeline: RenderPipeline,
+    prefilter_pipeline: RenderPipeline,
+}
+
+impl CubemapFilter {
+    pub fn new(renderer: &Renderer) -> Self {
+        profiling::scope!("CubemapFilter::new");
+
+        let device = &renderer.device;
+        let module = filter_module(device);
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::Cube,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(16),
+                },
+                None,
+            )
+            .build(device, Some("cubemap filter"));
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("cubemap filter"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+        let irradiance_pipeline = create_pipeline(device, &module, &[&bgl], "fs_irradiance", CUBEMAP_FORMAT);
+        let prefilter_pipeline = create_pipeline(device, &module, &[&bgl], "fs_prefilter", CUBEMAP_FORMAT);
+
+        Self {
+            bgl,
+            sampler,
+            irradiance_pipeline,
+            prefilter_pipeline,
+        }
+    }
+
+    /// Convolves the cube view `source`, whose faces are `source_size`
+    /// texels wide, into a new [`CUBEMAP_FORMAT`] irradiance cube whose faces
+    /// are `size` texels wide. Irradiance varies slowly, so 32 texels are
+    /// usually plenty.
+    pub fn irradiance(
+        &self,
+        renderer: &Renderer,
+        source: &TextureView,
+        source_size: u32,
+        size: u32,
+        sample_count: u32,
+    ) -> Result<(TextureHandle, Arc<TextureView>), RendererError> {
+        let (handle, cube_view, faces) = renderer.add_texture_cube_render_target(size, CUBEMAP_FORMAT)?;
+        self.irradiance_into(renderer, source, source_size, &faces, sample_count);
+        Ok((handle, cube_view))
+    }
+
+    /// Convolves `source` into the faces of a [`CUBEMAP_FORMAT`] cube, in
+    /// the order +X, -X, +Y, -Y, +Z, -Z, see [`irradiance`](Self::irradiance).
+    pub fn irradiance_into(
+        &self,
+        renderer: &Renderer,
+        source: &TextureView,
+        source_size: u32,
+        faces: &[Arc<TextureView>; 6],
+        sample_count: u32,
+    ) {
+        profiling::scope!("Cubemap Irradiance");
+
+        let mut encoder = renderer.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("cubemap irradiance"),
+        });
+        let params = FilterParams {
+            roughness: 1.0,
+            sample_count,
+            source_size: source_size as f32,
+            _padding: 0,
+        };
+        self.filter(
+            &renderer.device,
+            &mut encoder,
+            &self.irradiance_pipeline,
+            source,
+            faces,
+            params,
+        );
+        renderer.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Prefilters the cube view `source`, whose faces are `source_size`
+    /// texels wide, into a new [`CUBEMAP_FORMAT`] cube whose faces are `size`
+    /// texels wide, with `mip_count` mips. Mip `i` holds the reflections of
+    /// a surface with a perceptual roughness of `i / (mip_count - 1)`, so
+    /// materials sample the mip of their roughness.
+    pub fn prefilter(
+        &self,
+        renderer: &Renderer,
+        source: &TextureView,
+        source_size: u32,
+        size: u32,
+        mip_count: u32,
+        sample_count: u32,
+    ) -> Result<(TextureHandle, Arc<TextureView>), RendererError> {
+        let (handle, cube_view, mips) =
+            renderer.add_texture_cube_render_target_with_mips(size, mip_count, CUBEMAP_FORMAT)?;
+        self.prefilter_into(renderer, source, source_size, &mips, sample_count);
+        Ok((handle, cube_view))
+    }
+
+    /// Prefilters `source` into the faces of each mip of a [`CUBEMAP_FORMAT`]
+    /// cube, as returned by
+    /// [`Renderer::add_texture_cube_render_target_with_mips`], see
+    /// [`prefilter`](Self::prefilter).
+    pub fn prefilter_into(
+        &self,
+        renderer: &Renderer,
+        source: &TextureView,
+        source_size: u32,
+        mips: &[[Arc<TextureView>; 6]],
+        sample_count: u32,
+    ) {
+        profiling::scope!("Cubemap Prefilter");
+
+        let mut encoder = renderer.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("cubemap prefilter"),
+        });
+        for (mip, faces) in mips.iter().enumerate() {
+            let params = FilterParams {
+                roughness: mip_roughness(mip, mips.len()),
+                sample_count,
+                source_size: source_size as f32,
+                _padding: 0,
+            };
+            self.filter(
+                &renderer.device,
+                &mut encoder,
+                &self.prefilter_pipeline,
+                source,
+                faces,
+                params,
+            );
+        }
+        renderer.queue.submit(Some(encoder.finish()));
+    }
+
+    fn filter(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        pipeline: &RenderPipeline,
+        source: &TextureView,
+        faces: &[Arc<TextureView>; 6],
+        params: FilterParams,
+    ) {
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("cubemap filter"),
+            contents: bytemuck::bytes_of(&params),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = BindGroupBuilder::new()
+            .append_texture_view(source)
+            .append_sampler(&self.sampler)
+            .append_buffer(&params)
+            .build(device, Some("cubemap filter"), &self.bgl);
+
+        for (face, view) in (0..6).zip(faces) {
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("cubemap filter face"),
+                color_attachments: &[RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, face..face + 1);
+        }
+    }
+}
+
+/// Perceptual roughness mip `mip` of `mip_count` is prefiltered for.
+fn mip_roughness(mip: usize, mip_count: usize) -> f32 {
+    match mip_count {
+        0 | 1 => 0.0,
+        _ => mip as f32 / (mip_count - 1) as f32,
+    }
+}
+
+/// Renders the lookup table of the split sum approximation into a new
+/// [`BRDF_LUT_FORMAT`] texture `size` texels wide and high. Each texel holds
+/// the scale and bias the GGX specular lobe applies to F0, for the cosine
+/// between the normal and the view along u and the perceptual roughness
+/// along v.
+///
+/// The table doesn't depend on the scene, so it only needs rendering once.
+pub fn brdf_lut(renderer: &Renderer, size: u32) -> Result<(TextureHandle, Arc<TextureView>), RendererError> {
+    profiling::scope!("BRDF LUT");
+
+    let (handle, view) = renderer.add_texture_2d_render_target(UVec2::splat(size), BRDF_LUT_FORMAT)?;
+
+    let device = &renderer.device;
+    let module = filter_module(device);
+    let pipeline = create_pipeline(device, &module, &[], "fs_brdf_lut", BRDF_LUT_FORMAT);
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("brdf lut"),
+    });
+    let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+        label: Some("brdf lut"),
+        color_attachments: &[RenderPassColorAttachment {
+            view: &view,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Clear(Color::BLACK),
+                store: true,
+            },
+        }],
+        depth_stencil_attachment: None,
+    });
+    rpass.set_pipeline(&pipeline);
+    rpass.draw(0..3, 0..1);
+    drop(rpass);
+    renderer.queue.submit(Some(encoder.finish()));
+
+    Ok((handle, view))
+}
+
+#[cfg(test)]
+mod test {
+    use super::mip_roughness;
+
+    #[test]
+    fn mips_span_roughness() {
+        assert_eq!(mip_roughness(0, 1), 0.0);
+        assert_eq!(mip_roughness(0, 5), 0.0);
+        assert_eq!(mip_roughness(2, 5), 0.5);
+        assert_eq!(mip_roughness(4, 5), 1.0);
+    }
+}
@@ -1,13 +1,13 @@
-use glam::{Mat4, Vec3};
+use glam::{Mat4, Vec3, Vec3A};
 use rend3::{
-    managers::{CameraManager, InternalObject, MaterialManager, ObjectManager},
-    types::Material,
-    util::frustum::ShaderFrustum,
-    ProfileData,
+    managers::{CameraManager, InternalObject, MaterialManager, ObjectManager, NO_JOINTS},
+    types::{Material, ObjectShadowMode, RawObjectHandle, ShadowSettings},
+    util::geometry::ShaderFrustum,
+    ProfileData, RendererProfile, SHADOW_DIMENSIONS,
 };
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BufferUsages, Device, RenderPass,
+    BindGroup, BufferUsages, Device, RenderPass,
 };
 
 use crate::{
@@ -24,16 +24,75 @@ pub struct CpuDrawCall {
     pub material_index: u32,
 }
 
+/// Distance between the data of two objects in the output buffer in the
+/// downlevel profile. Objects are bound one at a time there, with dynamic
+/// offsets that must be aligned to 256 bytes.
+pub const DOWNLEVEL_OBJECT_STRIDE: usize = 256;
+
+/// Decides which objects cast shadows into a shadow map, following
+/// [`ShadowSettings`] and the [`ObjectShadowMode`] of each object.
+#[derive(Debug, Copy, Clone)]
+pub struct ShadowCasterFilter {
+    /// Objects in [`ObjectShadowMode::Default`] with a smaller bounding
+    /// sphere are skipped.
+    pub min_radius: f32,
+    /// Where [`ObjectShadowMode::MaxDistance`] is measured from.
+    pub camera_location: Vec3A,
+}
+
+impl ShadowCasterFilter {
+    pub fn new(settings: ShadowSettings, shadow_camera: &CameraManager, camera: &CameraManager) -> Self {
+        // Shadow cameras are orthographic, which scales x by 2 / width.
+        let width = 2.0 / shadow_camera.proj().x_axis.x.abs();
+        let texel_size = width / SHADOW_DIMENSIONS as f32;
+        Self {
+            min_radius: settings.min_caster_texels * texel_size * 0.5,
+            camera_location: camera.location().into(),
+        }
+    }
+
+    pub fn casts_shadow(&self, object: &InternalObject) -> bool {
+        let radius = || {
+            object
+                .input
+                .bounding_sphere
+                .apply_transform(object.input.transform)
+                .radius
+        };
+        match object.shadow_mode {
+            ObjectShadowMode::Default => radius() >= self.min_radius,
+            ObjectShadowMode::Always => true,
+            ObjectShadowMode::Never => false,
+            ObjectShadowMode::MaxDistance(distance) => {
+                object.mesh_location().distance_squared(self.camera_location) <= distance * distance
+                    && radius() >= self.min_radius
+            }
+        }
+    }
+}
+
 /// Do all object culling on the CPU and upload the per-object data to the GPU.
+///
+/// In the downlevel profile the data is put in a uniform buffer, with
+/// [`DOWNLEVEL_OBJECT_STRIDE`] bytes between objects.
+///
+/// `visible_cells`, from [`CellGraph::visible_cells`](rend3::util::cells::CellGraph::visible_cells),
+/// culls the objects of cells the camera can't see before the frustum test.
+/// When culling for a shadow map, `shadow_casters` skips the objects which
+/// cast no shadow into it.
+#[allow(clippy::too_many_arguments)]
 pub fn cull_cpu<M: Material>(
     device: &Device,
+    profile: RendererProfile,
     camera: &CameraManager,
     objects: &ObjectManager,
     sorting: Option<Sorting>,
     key: u64,
+    visible_cells: Option<&[bool]>,
+    shadow_casters: Option<ShadowCasterFilter>,
 ) -> CulledObjectSet {
     profiling::scope!("CPU Culling");
-    let frustum = ShaderFrustum::from_matrix(camera.proj());
+    let frustum = ShaderFrustum::from_matrix(camera.proj(), camera.depth_mode());
     let view = camera.view();
     let view_proj = camera.view_proj();
 
@@ -41,7 +100,7 @@ pub fn cull_cpu<M: Material>(
 
     let objects = crate::common::sort_objects(objects, camera, sorting);
 
-    let (mut outputs, calls) = cull_internal(&objects, frustum, view, view_proj);
+    let (mut outputs, calls) = cull_internal(&objects, frustum, view, view_proj, visible_cells, shadow_casters);
 
     assert_eq!(calls.len(), outputs.len());
 
@@ -50,17 +109,33 @@ pub fn cull_cpu<M: Material>(
         outputs.push(PerObjectDataAbi {
             model_view: Mat4::ZERO,
             model_view_proj: Mat4::ZERO,
-            pad0: [0; 12],
             material_idx: 0,
+            texture_layer: 0,
+            joint_offset: NO_JOINTS,
+            pad0: [0; 4],
             inv_squared_scale: Vec3::ZERO,
+            fade: 1.0,
         });
     }
 
-    let output_buffer = device.create_buffer_init(&BufferInitDescriptor {
-        label: Some("culling output"),
-        contents: bytemuck::cast_slice(&outputs),
-        usage: BufferUsages::STORAGE,
-    });
+    let output_buffer = match profile {
+        RendererProfile::Downlevel => {
+            let mut contents = vec![0_u8; outputs.len() * DOWNLEVEL_OBJECT_STRIDE];
+            for (output, chunk) in outputs.iter().zip(contents.chunks_exact_mut(DOWNLEVEL_OBJECT_STRIDE)) {
+                chunk[..std::mem::size_of::<PerObjectDataAbi>()].copy_from_slice(bytemuck::bytes_of(output));
+            }
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("culling output"),
+                contents: &contents,
+                usage: BufferUsages::UNIFORM,
+            })
+        }
+        _ => device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("culling output"),
+            contents: bytemuck::cast_slice(&outputs),
+            usage: BufferUsages::STORAGE,
+        }),
+    };
 
     CulledObjectSet {
         calls: ProfileData::Cpu(calls),
@@ -73,6 +148,8 @@ fn cull_internal(
     frustum: ShaderFrustum,
     view: Mat4,
     view_proj: Mat4,
+    visible_cells: Option<&[bool]>,
+    shadow_casters: Option<ShadowCasterFilter>,
 ) -> (Vec<PerObjectDataAbi>, Vec<CpuDrawCall>) {
     let mut outputs = Vec::with_capacity(objects.len());
     let mut calls = Vec::with_capacity(objects.len());
@@ -81,12 +158,17 @@ fn cull_internal(
         let model = object.input.transform;
         let model_view = view * model;
 
-        let transformed = object.input.bounding_sphere.apply_transform(model_view);
-        if !frustum.contains_sphere(transformed) {
+        if !in_visible_cell(object, visible_cells)
+            || !casts_shadow(object, shadow_casters)
+            || !is_visible(object, frustum, model_view)
+        {
             continue;
         }
 
-        let model_view_proj = view_proj * model;
+        // Quantized positions are mapped to mesh space by the matrices.
+        let dequantize = object.input.position_dequantization.matrix();
+        let model_view = model_view * dequantize;
+        let model_view_proj = view_proj * model * dequantize;
 
         calls.push(CpuDrawCall {
             start_idx: object.input.start_idx,
@@ -107,27 +189,88 @@ fn cull_internal(
             model_view,
             model_view_proj,
             material_idx: 0,
-            pad0: [0; 12],
+            texture_layer: object.texture_layer,
+            joint_offset: object.input.joint_offset,
+            pad0: [0; 4],
             inv_squared_scale,
+            fade: object.input.fade,
         });
     }
 
     (outputs, calls)
 }
 
+fn is_visible(object: &InternalObject, frustum: ShaderFrustum, model_view: Mat4) -> bool {
+    let transformed = object.input.bounding_sphere.apply_transform(model_view);
+    frustum.contains_sphere(transformed)
+}
+
+/// Objects outside of any cell are always in a visible one.
+fn in_visible_cell(object: &InternalObject, visible_cells: Option<&[bool]>) -> bool {
+    match (visible_cells, object.cell) {
+        (Some(visible_cells), Some(cell)) => visible_cells[cell],
+        _ => true,
+    }
+}
+
+fn casts_shadow(object: &InternalObject, shadow_casters: Option<ShadowCasterFilter>) -> bool {
+    shadow_casters.map_or(true, |filter| filter.casts_shadow(object))
+}
+
+/// Objects whose bounding sphere is inside the frustum of `camera`, in a
+/// visible cell and casting shadows, the same tests culling does.
+pub fn visible_objects<'a>(
+    camera: &CameraManager,
+    objects: &'a [InternalObject],
+    visible_cells: Option<&'a [bool]>,
+    shadow_casters: Option<ShadowCasterFilter>,
+) -> impl Iterator<Item = RawObjectHandle> + 'a {
+    let frustum = ShaderFrustum::from_matrix(camera.proj(), camera.depth_mode());
+    let view = camera.view();
+    objects
+        .iter()
+        .filter(move |object| {
+            in_visible_cell(object, visible_cells)
+                && casts_shadow(object, shadow_casters)
+                && is_visible(object, frustum, view * object.input.transform)
+        })
+        .map(|object| object.handle)
+}
+
 /// Draw the given cpu draw calls.
 ///
+/// In the downlevel profile, pass the index and bind group holding the culling
+/// output as `per_object`. It is rebound at each object's offset before its
+/// draw, as the shaders can't index the objects by instance.
+///
+/// With `debug_markers`, the draws are wrapped in a debug group named after
+/// the material archetype, with a marker at each change of material.
+///
 /// No-op if there are 0 objects.
 pub fn draw_cpu_powered<'rpass, M: Material>(
     rpass: &mut RenderPass<'rpass>,
     draws: &'rpass [CpuDrawCall],
     materials: &'rpass MaterialManager,
     material_binding_index: u32,
+    per_object: Option<(u32, &'rpass BindGroup)>,
+    debug_markers: bool,
 ) {
+    if draws.is_empty() {
+        return;
+    }
+
+    if debug_markers {
+        rpass.push_debug_group(std::any::type_name::<M>());
+    }
+
     let mut previous_mat_handle = None;
     for (idx, draw) in draws.iter().enumerate() {
         if previous_mat_handle != Some(draw.material_index) {
             previous_mat_handle = Some(draw.material_index);
+            if debug_markers {
+                // Materials don't carry a label, so their index is the best we can show.
+                rpass.insert_debug_marker(&format!("material {}", draw.material_index));
+            }
             // TODO(material): only resolve the archetype lookup once
             let (_, internal) = materials.get_internal_material_full_by_index::<M>(draw.material_index as usize);
 
@@ -136,6 +279,16 @@ pub fn draw_cpu_powered<'rpass, M: Material>(
             rpass.set_bind_group(material_binding_index, internal.bind_group.as_ref().as_cpu(), &[]);
         }
         let idx = idx as u32;
-        rpass.draw_indexed(draw.start_idx..draw.end_idx, draw.vertex_offset, idx..idx + 1);
+        match per_object {
+            Some((per_object_binding_index, bind_group)) => {
+                let offset = idx * DOWNLEVEL_OBJECT_STRIDE as u32;
+                rpass.set_bind_group(per_object_binding_index, bind_group, &[offset]);
+                rpass.draw_indexed(draw.start_idx..draw.end_idx, draw.vertex_offset, 0..1);
+            }
+            None => rpass.draw_indexed(draw.start_idx..draw.end_idx, draw.vertex_offset, idx..idx + 1),
+        }
+    }
+    if debug_markers {
+        rpass.pop_debug_group();
     }
 }
@@ -1,9 +1,10 @@
-use std::{borrow::Cow, mem, num::NonZeroU64};
+use std::{mem, num::NonZeroU64};
 
 use glam::Mat4;
 use rend3::{
     managers::{CameraManager, GpuCullingInput, InternalObject, VERTEX_OBJECT_INDEX_SLOT},
-    util::{bind_merge::BindGroupBuilder, frustum::ShaderFrustum},
+    types::Material,
+    util::{bind_merge::BindGroupBuilder, geometry::ShaderFrustum},
     ProfileData,
 };
 use wgpu::{
@@ -17,7 +18,7 @@ use wgpu::{
 use crate::{
     common::{PerObjectDataAbi, Sorting},
     culling::CulledObjectSet,
-    shaders::{SPIRV_SHADERS, WGSL_SHADERS},
+    shaders::{source_shader, spirv_shader, wgsl_shader},
 };
 
 #[repr(C, align(16))]
@@ -32,6 +33,16 @@ struct GPUCullingUniforms {
 unsafe impl bytemuck::Pod for GPUCullingUniforms {}
 unsafe impl bytemuck::Zeroable for GPUCullingUniforms {}
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct BucketUniforms {
+    material_count: u32,
+    _padding: [u32; 3],
+}
+
+unsafe impl bytemuck::Pod for BucketUniforms {}
+unsafe impl bytemuck::Zeroable for BucketUniforms {}
+
 /// The data needed to do an indirect draw call for an entire material
 /// archetype.
 pub struct GpuIndirectData {
@@ -48,6 +59,11 @@ pub struct GpuCuller {
     prefix_cull_pipeline: ComputePipeline,
     prefix_sum_pipeline: ComputePipeline,
     prefix_output_pipeline: ComputePipeline,
+
+    bucket_bgl: BindGroupLayout,
+    bucket_count_pipeline: ComputePipeline,
+    bucket_scan_pipeline: ComputePipeline,
+    bucket_scatter_pipeline: ComputePipeline,
 }
 impl GpuCuller {
     pub fn new(device: &Device) -> Self {
@@ -165,6 +181,38 @@ impl GpuCuller {
             ],
         });
 
+        let bucket_storage = |binding, read_only, min_size: usize| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(min_size as _),
+            },
+            count: None,
+        };
+
+        let bucket_bgl = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("material bucketing bgl"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(mem::size_of::<BucketUniforms>() as _),
+                    },
+                    count: None,
+                },
+                bucket_storage(1, true, mem::size_of::<PerObjectDataAbi>()),
+                bucket_storage(2, true, 16 + 20),
+                bucket_storage(3, false, mem::size_of::<u32>()),
+                bucket_storage(4, false, mem::size_of::<PerObjectDataAbi>()),
+                bucket_storage(5, false, 16 + 20),
+            ],
+        });
+
         let atomic_pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("atomic culling pll"),
             bind_group_layouts: &[&atomic_bgl],
@@ -186,45 +234,39 @@ impl GpuCuller {
             }],
         });
 
+        let bucket_pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("material bucketing pll"),
+            bind_group_layouts: &[&bucket_bgl],
+            push_constant_ranges: &[],
+        });
+
         let atomic_sm = unsafe {
             device.create_shader_module_spirv(&ShaderModuleDescriptorSpirV {
                 label: Some("cull-atomic-cull"),
-                source: wgpu::util::make_spirv_raw(
-                    SPIRV_SHADERS.get_file("cull-atomic-cull.comp.spv").unwrap().contents(),
-                ),
+                source: wgpu::util::make_spirv_raw(&spirv_shader("cull-atomic-cull.comp.spv")),
             })
         };
 
         let prefix_cull_sm = device.create_shader_module(&ShaderModuleDescriptor {
             label: Some("cull-prefix-cull"),
-            source: ShaderSource::Wgsl(Cow::Borrowed(
-                WGSL_SHADERS
-                    .get_file("cull-prefix-cull.comp.wgsl")
-                    .unwrap()
-                    .contents_utf8()
-                    .unwrap(),
-            )),
+            source: ShaderSource::Wgsl(wgsl_shader("cull-prefix-cull.comp.wgsl")),
         });
 
         let prefix_sum_sm = device.create_shader_module(&ShaderModuleDescriptor {
             label: Some("cull-prefix-sum"),
-            source: ShaderSource::Wgsl(Cow::Borrowed(
-                WGSL_SHADERS
-                    .get_file("cull-prefix-sum.comp.wgsl")
-                    .unwrap()
-                    .contents_utf8()
-                    .unwrap(),
-            )),
+            source: ShaderSource::Wgsl(wgsl_shader("cull-prefix-sum.comp.wgsl")),
         });
 
         let prefix_output_sm = device.create_shader_module(&ShaderModuleDescriptor {
             label: Some("cull-prefix-output"),
-            source: ShaderSource::Wgsl(Cow::Borrowed(
-                WGSL_SHADERS
-                    .get_file("cull-prefix-output.comp.wgsl")
-                    .unwrap()
-                    .contents_utf8()
-                    .unwrap(),
+            source: ShaderSource::Wgsl(wgsl_shader("cull-prefix-output.comp.wgsl")),
+        });
+
+        let bucket_sm = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("material bucketing"),
+            source: ShaderSource::Wgsl(source_shader(
+                "bucket.wgsl",
+                include_str!("../../shaders/src/bucket.wgsl"),
             )),
         });
 
@@ -256,6 +298,18 @@ impl GpuCuller {
             entry_point: "main",
         });
 
+        let bucket_pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&bucket_pll),
+                module: &bucket_sm,
+                entry_point,
+            })
+        };
+        let bucket_count_pipeline = bucket_pipeline("material bucket count pl", "count_materials");
+        let bucket_scan_pipeline = bucket_pipeline("material bucket scan pl", "scan_materials");
+        let bucket_scatter_pipeline = bucket_pipeline("material bucket scatter pl", "scatter");
+
         Self {
             atomic_bgl,
             atomic_pipeline,
@@ -263,10 +317,19 @@ impl GpuCuller {
             prefix_cull_pipeline,
             prefix_sum_pipeline,
             prefix_output_pipeline,
+            bucket_bgl,
+            bucket_count_pipeline,
+            bucket_scan_pipeline,
+            bucket_scatter_pipeline,
         }
     }
 
     /// Perform culling on a given camera and input.
+    ///
+    /// Without `sorting`, the surviving objects are grouped by material
+    /// when there are several of the `material_count` materials of the
+    /// archetype, so consecutive draws share their material data.
+    #[allow(clippy::too_many_arguments)]
     pub fn cull(
         &self,
         device: &Device,
@@ -274,6 +337,7 @@ impl GpuCuller {
         camera: &CameraManager,
         input_buffer: &Buffer,
         input_count: usize,
+        material_count: usize,
         sorting: Option<Sorting>,
     ) -> CulledObjectSet {
         profiling::scope!("Record GPU Culling");
@@ -283,7 +347,7 @@ impl GpuCuller {
         let uniform = GPUCullingUniforms {
             view: camera.view(),
             view_proj: camera.view_proj(),
-            frustum: ShaderFrustum::from_matrix(camera.proj()),
+            frustum: ShaderFrustum::from_matrix(camera.proj(), camera.depth_mode()),
             object_count: count as u32,
         };
 
@@ -293,20 +357,8 @@ impl GpuCuller {
             usage: BufferUsages::UNIFORM,
         });
 
-        let output_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("culling output"),
-            size: (count.max(1) * mem::size_of::<PerObjectDataAbi>()) as _,
-            usage: BufferUsages::STORAGE,
-            mapped_at_creation: false,
-        });
-
-        let indirect_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("indirect buffer"),
-            // 16 bytes for count, the rest for the indirect count
-            size: (count * 20 + 16) as _,
-            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::VERTEX,
-            mapped_at_creation: false,
-        });
+        let output_buffer = create_output_buffer(device, count, "culling output");
+        let indirect_buffer = create_indirect_buffer(device, count, "indirect buffer");
 
         if count != 0 {
             let dispatch_count = ((count + 255) / 256) as u32;
@@ -370,11 +422,25 @@ impl GpuCuller {
                 cpass.set_bind_group(0, bind_group, &[]);
                 cpass.dispatch(dispatch_count, 1, 1);
             } else {
+                // Atomic culling outputs objects in no particular order, so
+                // it writes to intermediate buffers when they get bucketed.
+                let bucketing = material_count > 1;
+                let unbucketed = bucketing.then(|| {
+                    (
+                        create_output_buffer(device, count, "unbucketed culling output"),
+                        create_indirect_buffer(device, count, "unbucketed indirect buffer"),
+                    )
+                });
+                let (atomic_output, atomic_indirect) = match &unbucketed {
+                    Some((output, indirect)) => (output, indirect),
+                    None => (&output_buffer, &indirect_buffer),
+                };
+
                 let bg = BindGroupBuilder::new()
                     .append_buffer(input_buffer)
                     .append_buffer(&uniform_buffer)
-                    .append_buffer(&output_buffer)
-                    .append_buffer(&indirect_buffer)
+                    .append_buffer(atomic_output)
+                    .append_buffer(atomic_indirect)
                     .build(device, Some("atomic culling bg"), &self.atomic_bgl);
 
                 let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
@@ -386,6 +452,45 @@ impl GpuCuller {
                 cpass.dispatch(dispatch_count, 1, 1);
 
                 drop(cpass);
+
+                if bucketing {
+                    let bucket_uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                        label: Some("material bucketing uniform buffer"),
+                        contents: bytemuck::bytes_of(&BucketUniforms {
+                            material_count: material_count as u32,
+                            _padding: [0; 3],
+                        }),
+                        usage: BufferUsages::UNIFORM,
+                    });
+
+                    let offsets_buffer = device.create_buffer(&BufferDescriptor {
+                        label: Some("material bucket offsets"),
+                        size: (material_count * mem::size_of::<u32>()) as _,
+                        usage: BufferUsages::STORAGE,
+                        mapped_at_creation: false,
+                    });
+
+                    let bg = BindGroupBuilder::new()
+                        .append_buffer(&bucket_uniform_buffer)
+                        .append_buffer(atomic_output)
+                        .append_buffer(atomic_indirect)
+                        .append_buffer(&offsets_buffer)
+                        .append_buffer(&output_buffer)
+                        .append_buffer(&indirect_buffer)
+                        .build(device, Some("material bucketing bg"), &self.bucket_bgl);
+
+                    let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                        label: Some("material bucketing"),
+                    });
+
+                    cpass.set_bind_group(0, &bg, &[]);
+                    cpass.set_pipeline(&self.bucket_count_pipeline);
+                    cpass.dispatch(dispatch_count, 1, 1);
+                    cpass.set_pipeline(&self.bucket_scan_pipeline);
+                    cpass.dispatch(1, 1, 1);
+                    cpass.set_pipeline(&self.bucket_scatter_pipeline);
+                    cpass.dispatch(dispatch_count, 1, 1);
+                }
             }
         }
 
@@ -396,6 +501,25 @@ impl GpuCuller {
     }
 }
 
+fn create_output_buffer(device: &Device, count: usize, label: &str) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        size: (count.max(1) * mem::size_of::<PerObjectDataAbi>()) as _,
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_indirect_buffer(device: &Device, count: usize, label: &str) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some(label),
+        // 16 bytes for count, the rest for the indirect count
+        size: (count * 20 + 16) as _,
+        usage: BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::VERTEX | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
 /// Build and upload the inputs into a buffer to be passed to
 /// [`GpuCuller::cull`].
 pub fn build_gpu_cull_input(device: &Device, objects: &[InternalObject]) -> Buffer {
@@ -436,9 +560,19 @@ pub fn build_gpu_cull_input(device: &Device, objects: &[InternalObject]) -> Buff
 
 /// Draw the given indirect call.
 ///
+/// With `debug_markers`, the draw is wrapped in a debug group named after the
+/// material archetype.
+///
 /// No-op if there are 0 objects.
-pub fn draw_gpu_powered<'rpass>(rpass: &mut RenderPass<'rpass>, indirect_data: &'rpass GpuIndirectData) {
+pub fn draw_gpu_powered<'rpass, M: Material>(
+    rpass: &mut RenderPass<'rpass>,
+    indirect_data: &'rpass GpuIndirectData,
+    debug_markers: bool,
+) {
     if indirect_data.count != 0 {
+        if debug_markers {
+            rpass.push_debug_group(std::any::type_name::<M>());
+        }
         rpass.set_vertex_buffer(VERTEX_OBJECT_INDEX_SLOT, indirect_data.indirect_buffer.slice(16..));
         rpass.multi_draw_indexed_indirect_count(
             &indirect_data.indirect_buffer,
@@ -447,5 +581,8 @@ pub fn draw_gpu_powered<'rpass>(rpass: &mut RenderPass<'rpass>, indirect_data: &
             0,
             indirect_data.count as _,
         );
+        if debug_markers {
+            rpass.pop_debug_group();
+        }
     }
 }
@@ -1,17 +1,34 @@
 //! Material agnostic culling on either the CPU or GPU.
 
+use std::{
+    fmt, mem,
+    num::NonZeroU64,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    task::Poll,
+};
+
+use parking_lot::Mutex;
+
 use rend3::{
     format_sso,
     graph::{DataHandle, RenderGraph},
+    managers::SkinningMode,
     types::Material,
-    util::bind_merge::BindGroupBuilder,
+    util::{
+        bind_merge::BindGroupBuilder,
+        buffer::{poll_now, MapFuture},
+        visibility::VisibilityCamera,
+    },
     ProfileData, RendererProfile,
 };
-use wgpu::{BindGroup, Buffer};
+use wgpu::{
+    BindGroup, BindingResource, Buffer, BufferBinding, BufferDescriptor, BufferUsages, CommandEncoder, Device,
+    Maintain, MapMode,
+};
 
 use crate::{
-    common::{PerMaterialArchetypeInterface, Sorting},
-    skinning::SkinningOutput,
+    common::{PerMaterialArchetypeInterface, PerObjectDataAbi, Sorting},
+    skinning::{PreSkinningBuffers, SkinningOutput},
 };
 
 mod cpu;
@@ -26,6 +43,24 @@ pub struct PerMaterialArchetypeData {
     pub per_material: BindGroup,
 }
 
+impl PerMaterialArchetypeData {
+    /// Dynamic offsets to use when binding `per_material` outside of
+    /// [`draw_cpu_powered`]. The downlevel profile binds the object data with a
+    /// dynamic offset.
+    pub fn per_material_offsets(profile: RendererProfile) -> &'static [u32] {
+        match profile {
+            RendererProfile::Downlevel => &[0],
+            _ => &[],
+        }
+    }
+
+    /// The `per_object` argument of [`draw_cpu_powered`] when `per_material` is
+    /// bound at `index`.
+    pub fn per_object_binding(&self, profile: RendererProfile, index: u32) -> Option<(u32, &BindGroup)> {
+        (profile == RendererProfile::Downlevel).then(|| (index, &self.per_material))
+    }
+}
+
 /// A set of objects that have been called. Contains the information needed to
 /// dispatch a render.
 pub struct CulledObjectSet {
@@ -33,6 +68,233 @@ pub struct CulledObjectSet {
     pub output_buffer: Buffer,
 }
 
+/// Number of draws issued for the object sets culled in a rendergraph, as
+/// returned by [`DrawStatistics::counts`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct DrawCounts {
+    /// Draw calls of objects which survived CPU culling.
+    pub draws: u64,
+    /// Triangles of objects which survived CPU culling.
+    pub triangles: u64,
+    /// Object sets culled on the GPU. Their draws are only known to the GPU,
+    /// so they aren't included in `draws` and `triangles`.
+    pub gpu_culled_sets: u64,
+    /// Objects which survived GPU culling, summed over every set. Only
+    /// available with [`DrawStatistics::set_indirect_readback`], and lags a
+    /// few frames behind the other counts.
+    pub gpu_draws: Option<u64>,
+}
+
+/// Draws of a single object set culled on the GPU, read back from its
+/// indirect buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndirectDrawCount {
+    /// Name of the culling node, e.g. `Primary Culling Opaque`.
+    pub name: String,
+    /// Objects given to culling.
+    pub objects: u32,
+    /// Objects which survived culling and were drawn.
+    pub drawn: u32,
+}
+
+/// Copy of the draw count of an object set culled on the GPU.
+struct IndirectCopy {
+    name: String,
+    objects: u32,
+    buffer: Buffer,
+}
+
+/// Copies of a submitted frame, waiting for their buffers to be mapped.
+struct MappingFrame {
+    copies: Vec<(IndirectCopy, MapFuture)>,
+}
+
+/// Frames read back at most at once. Older frames are dropped rather than
+/// letting them pile up behind a slow GPU.
+const MAX_MAPPING_FRAMES: usize = 3;
+
+#[derive(Default)]
+struct IndirectReadback {
+    /// Copies recorded by the rendergraph being executed.
+    recorded: Vec<IndirectCopy>,
+    /// Frames submitted to the GPU, oldest first.
+    mapping: Vec<MappingFrame>,
+    /// Counts of the most recent frame read back.
+    latest: Option<Vec<IndirectDrawCount>>,
+}
+
+impl IndirectReadback {
+    /// Starts mapping the copies of the frame which was just submitted.
+    fn submit(&mut self) {
+        if self.recorded.is_empty() {
+            return;
+        }
+        let copies = self
+            .recorded
+            .drain(..)
+            .map(|copy| {
+                let future: MapFuture = Box::pin(copy.buffer.slice(..).map_async(MapMode::Read));
+                (copy, future)
+            })
+            .collect();
+        self.mapping.push(MappingFrame { copies });
+        if self.mapping.len() > MAX_MAPPING_FRAMES {
+            self.mapping.remove(0);
+        }
+    }
+
+    /// Reads back every frame whose buffers finished mapping.
+    fn resolve(&mut self, device: &Device) {
+        if self.mapping.is_empty() {
+            return;
+        }
+        device.poll(Maintain::Poll);
+
+        while let Some(frame) = self.mapping.first_mut() {
+            let mut failed = false;
+            for (_, future) in &mut frame.copies {
+                match poll_now(future) {
+                    Poll::Ready(Ok(())) => {}
+                    Poll::Ready(Err(_)) => failed = true,
+                    Poll::Pending => return,
+                }
+            }
+
+            let frame = self.mapping.remove(0);
+            if failed {
+                log::warn!("Failed to read back indirect draw counts");
+                continue;
+            }
+            let counts = frame
+                .copies
+                .into_iter()
+                .map(|(copy, _)| {
+                    let data = copy.buffer.slice(..).get_mapped_range();
+                    let drawn = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+                    IndirectDrawCount {
+                        name: copy.name,
+                        objects: copy.objects,
+                        drawn,
+                    }
+                })
+                .collect();
+            self.latest = Some(counts);
+        }
+    }
+}
+
+/// Counts the draws of every object set culled by [`add_culling_to_graph`],
+/// across shadow and forward passes.
+///
+/// Counting happens while the rendergraph executes, so read the counts after
+/// [`RenderGraph::execute`] and [`reset`](Self::reset) them before building
+/// the next rendergraph.
+///
+/// Draws of object sets culled on the GPU are only counted with
+/// [`set_indirect_readback`](Self::set_indirect_readback), which copies the
+/// draw count out of every indirect buffer.
+#[derive(Default)]
+pub struct DrawStatistics {
+    draws: AtomicU64,
+    triangles: AtomicU64,
+    gpu_culled_sets: AtomicU64,
+    indirect_readback: AtomicBool,
+    indirect: Mutex<IndirectReadback>,
+}
+
+impl fmt::Debug for DrawStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DrawStatistics")
+            .field("counts", &self.counts())
+            .field("indirect_readback", &self.indirect_readback.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl DrawStatistics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&self) {
+        self.draws.store(0, Ordering::Relaxed);
+        self.triangles.store(0, Ordering::Relaxed);
+        self.gpu_culled_sets.store(0, Ordering::Relaxed);
+        self.indirect.lock().submit();
+    }
+
+    pub fn counts(&self) -> DrawCounts {
+        let gpu_draws = self
+            .indirect
+            .lock()
+            .latest
+            .as_ref()
+            .map(|counts| counts.iter().map(|count| count.drawn as u64).sum());
+        DrawCounts {
+            draws: self.draws.load(Ordering::Relaxed),
+            triangles: self.triangles.load(Ordering::Relaxed),
+            gpu_culled_sets: self.gpu_culled_sets.load(Ordering::Relaxed),
+            gpu_draws,
+        }
+    }
+
+    /// Reads back how many objects survived GPU culling in every object set,
+    /// e.g. to catch passes where everything gets culled away. Costs a small
+    /// copy and readback per object set every frame.
+    pub fn set_indirect_readback(&self, enabled: bool) {
+        self.indirect_readback.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            let mut indirect = self.indirect.lock();
+            indirect.recorded.clear();
+            indirect.mapping.clear();
+            indirect.latest = None;
+        }
+    }
+
+    /// Per object set draws of the most recent frame read back, `None` until
+    /// a frame has been read back or without
+    /// [`set_indirect_readback`](Self::set_indirect_readback).
+    pub fn indirect_counts(&self) -> Option<Vec<IndirectDrawCount>> {
+        self.indirect.lock().latest.clone()
+    }
+
+    fn record(&self, device: &Device, encoder: &mut CommandEncoder, name: &str, culled: &CulledObjectSet) {
+        match culled.calls {
+            ProfileData::Cpu(ref calls) => {
+                let triangles: u64 = calls
+                    .iter()
+                    .map(|call| (call.end_idx - call.start_idx) as u64 / 3)
+                    .sum();
+                self.draws.fetch_add(calls.len() as u64, Ordering::Relaxed);
+                self.triangles.fetch_add(triangles, Ordering::Relaxed);
+            }
+            ProfileData::Gpu(ref indirect_data) => {
+                self.gpu_culled_sets.fetch_add(1, Ordering::Relaxed);
+
+                if !self.indirect_readback.load(Ordering::Relaxed) {
+                    return;
+                }
+                let buffer = device.create_buffer(&BufferDescriptor {
+                    label: Some("indirect count readback"),
+                    size: 4,
+                    usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                });
+                // The draw count is the first u32 of the indirect buffer.
+                encoder.copy_buffer_to_buffer(&indirect_data.indirect_buffer, 0, &buffer, 0, 4);
+
+                let mut indirect = self.indirect.lock();
+                indirect.resolve(device);
+                indirect.recorded.push(IndirectCopy {
+                    name: name.to_string(),
+                    objects: indirect_data.count as u32,
+                    buffer,
+                });
+            }
+        }
+    }
+}
+
 /// Add the profile-approprate culling for the given material archetype to the
 /// graph.
 #[allow(clippy::too_many_arguments)]
@@ -40,15 +302,18 @@ pub fn add_culling_to_graph<'node, M: Material>(
     graph: &mut RenderGraph<'node>,
     pre_cull_data: DataHandle<Buffer>,
     culled: DataHandle<PerMaterialArchetypeData>,
+    pre_skin: DataHandle<PreSkinningBuffers>,
     skinned: DataHandle<SkinningOutput>,
     per_material: &'node PerMaterialArchetypeInterface<M>,
     gpu_culler: &'node ProfileData<(), gpu::GpuCuller>,
+    statistics: &'node DrawStatistics,
     shadow_index: Option<usize>,
     key: u64,
     sorting: Option<Sorting>,
     name: &str,
 ) {
     let mut builder = graph.add_node(format_sso!("Culling {}", name));
+    let name = name.to_string();
 
     let pre_cull_handle = gpu_culler
         .profile()
@@ -57,6 +322,7 @@ pub fn add_culling_to_graph<'node, M: Material>(
 
     // Just connect the input, we don't need its value.
     builder.add_data_input(skinned);
+    let pre_skin_handle = builder.add_data_input(pre_skin);
 
     builder.build(move |_pt, renderer, encoder_or_rpass, temps, ready, graph_data| {
         let encoder = encoder_or_rpass.get_encoder();
@@ -70,27 +336,97 @@ pub fn add_culling_to_graph<'node, M: Material>(
             None => graph_data.camera_manager,
         };
 
+        // GPU culling only tests the frustum.
+        let cpu_culling = gpu_culler.profile() == RendererProfile::CpuDriven;
+
+        // Shadow cameras see into cells the main camera can't, so only the
+        // main camera goes through the portals.
+        let visible_cells = match shadow_index {
+            None if cpu_culling => graph_data
+                .object_manager
+                .cell_graph()
+                .and_then(|cells| cells.visible_cells(camera.location(), camera.view_proj())),
+            _ => None,
+        };
+
+        let shadow_casters = shadow_index.filter(|_| cpu_culling).map(|_| {
+            cpu::ShadowCasterFilter::new(
+                graph_data.directional_light_manager.settings(),
+                camera,
+                graph_data.camera_manager,
+            )
+        });
+
         let culled_objects = match gpu_culler {
-            ProfileData::Cpu(_) => {
-                cpu::cull_cpu::<M>(&renderer.device, camera, graph_data.object_manager, sorting, key)
-            }
-            ProfileData::Gpu(ref gpu_culler) => gpu_culler.cull(
+            ProfileData::Cpu(_) => cpu::cull_cpu::<M>(
                 &renderer.device,
-                encoder,
+                renderer.profile,
                 camera,
-                culling_input.into_gpu(),
-                count,
+                graph_data.object_manager,
                 sorting,
+                key,
+                visible_cells.as_deref(),
+                shadow_casters,
             ),
+            ProfileData::Gpu(ref gpu_culler) => {
+                // The input was ordered by sort key when uploaded, which only
+                // survives the order preserving path of culling.
+                let keyed = graph_data
+                    .object_manager
+                    .get_objects::<M>(key)
+                    .iter()
+                    .any(|o| o.sort_key != 0);
+                let ordered = sorting.or_else(|| keyed.then(|| Sorting::FrontToBack));
+                gpu_culler.cull(
+                    &renderer.device,
+                    encoder,
+                    camera,
+                    culling_input.into_gpu(),
+                    count,
+                    graph_data.material_manager.archetype_len::<M>(),
+                    ordered,
+                )
+            }
         };
 
+        statistics.record(&renderer.device, encoder, &name, &culled_objects);
+
+        if renderer.visibility.is_enabled() {
+            let visibility_camera = match shadow_index {
+                Some(idx) => VisibilityCamera::Shadow(idx),
+                None => VisibilityCamera::Main,
+            };
+            let objects = graph_data.object_manager.get_objects::<M>(key);
+            renderer.visibility.record(
+                visibility_camera,
+                cpu::visible_objects(camera, objects, visible_cells.as_deref(), shadow_casters),
+            );
+        }
+
         let mut per_material_bgb = BindGroupBuilder::new();
-        per_material_bgb.append_buffer(&culled_objects.output_buffer);
+        if renderer.profile == RendererProfile::Downlevel {
+            // Only one object is visible at a time, see draw_cpu_powered.
+            per_material_bgb.append(BindingResource::Buffer(BufferBinding {
+                buffer: &culled_objects.output_buffer,
+                offset: 0,
+                size: NonZeroU64::new(mem::size_of::<PerObjectDataAbi>() as _),
+            }));
+        } else {
+            per_material_bgb.append_buffer(&culled_objects.output_buffer);
+        }
 
         if renderer.profile == RendererProfile::GpuDriven {
             graph_data.material_manager.add_to_bg_gpu::<M>(&mut per_material_bgb);
         }
 
+        // Vertex shaders skinning the objects read the joint matrices.
+        if renderer.skinning_mode == SkinningMode::VertexShader {
+            let pre_skin = graph_data
+                .get_data(temps, pre_skin_handle)
+                .expect("Skinning in the vertex shader requires pre-skinning to run first");
+            per_material_bgb.append_buffer(&pre_skin.joint_matrices);
+        }
+
         let per_material_bg = per_material_bgb.build(&renderer.device, None, &per_material.bgl);
 
         graph_data.set_data(
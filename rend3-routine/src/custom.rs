@@ -0,0 +1,422 @@
+//! Helpers to write routines in WGSL without knowing rend3's bind group and
+//! vertex buffer plumbing.
+//!
+//! Custom shaders are the source the user writes, with a prelude prepended
+//! which declares what rend3 binds:
+//!
+//! - Every shader gets the frame uniforms at group 0: `primary_sampler`,
+//!   `nearest_sampler`, `comparison_sampler` and `frame`, a `FrameUniforms`
+//!   with the layout of [`FrameUniforms`](crate::uniforms::FrameUniforms).
+//! - Full-screen passes, see [`FullscreenRoutine`], get the `fullscreen_vs`
+//!   vertex shader, which outputs a `FullscreenVertex` with `position` and
+//!   `uv`, and the texture they read as `source` at group 1. The fragment
+//!   shader takes a `FullscreenVertex`.
+//! - Passes drawing objects, see [`object_shader`], get an `ObjectVertex`
+//!   struct to take as the input of the vertex shader, with `position`,
+//!   `normal`, `tangent`, `uv0`, `uv1` and `color`, and the
+//!   `object_data(vertex)` function returning the `ObjectData` of the object
+//!   being drawn, with its `model_view` and `model_view_proj` matrices and
+//!   `material_idx`. `object_view_normal(data, vertex.normal)` gives the
+//!   view space normal, decoding it if the renderer quantizes normals, see
+//!   [`VertexQuantization`](rend3::managers::VertexQuantization). The
+//!   materials at group 2 are left to the user, as
+//!   their layout depends on the material, see
+//!   [`object_bind_group_layouts`](crate::common::object_bind_group_layouts).
+//!
+//! Shaders are validated with naga before being handed to wgpu, and errors
+//! are reported with line numbers of the user's source, instead of a wgpu
+//! validation panic.
+
+use std::{borrow::Cow, error::Error as _};
+
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use rend3::{
+    graph::{DataHandle, RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer, RendererProfile,
+};
+use thiserror::Error;
+use wgpu::{
+    BindGroup, BindGroupLayout, BindingType, BlendState, Color, ColorTargetState, ColorWrites, Device, FragmentState,
+    FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    TextureFormat, TextureSampleType, TextureViewDimension, VertexState,
+};
+
+use crate::{
+    common::{preprocess_wgsl, ShaderDefines, WholeFrameInterfaces},
+    shaders::source_shader,
+};
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum CustomShaderError {
+    #[error("Shader {label} failed to parse:\n{message}")]
+    Parse {
+        label: String,
+        /// Line of the user's source the error is on, `None` if it is in the
+        /// prelude.
+        line: Option<usize>,
+        message: String,
+    },
+    #[error("Shader {label} is invalid:\n{message}")]
+    Validation {
+        label: String,
+        /// Line of the user's source the error is on, `None` if it is in the
+        /// prelude or naga doesn't know where it is.
+        line: Option<usize>,
+        message: String,
+    },
+}
+
+impl CustomShaderError {
+    pub fn line(&self) -> Option<usize> {
+        match *self {
+            Self::Parse { line, .. } | Self::Validation { line, .. } => line,
+        }
+    }
+}
+
+/// Source with a prelude, to map lines back to the user's source.
+struct PreludedSource {
+    source: String,
+    prelude_lines: usize,
+}
+
+impl PreludedSource {
+    fn new(prelude: &str, user: &str) -> Self {
+        let mut source = String::with_capacity(prelude.len() + user.len() + 1);
+        source.push_str(prelude);
+        if !source.is_empty() && !source.ends_with('\n') {
+            source.push('\n');
+        }
+        let prelude_lines = source.lines().count();
+        source.push_str(user);
+        Self { source, prelude_lines }
+    }
+
+    /// 1-based line of the user's source holding the byte `offset`.
+    fn user_line(&self, offset: usize) -> Option<usize> {
+        let line = self.source[..offset.min(self.source.len())].matches('\n').count() + 1;
+        line.checked_sub(self.prelude_lines).filter(|&line| line > 0)
+    }
+
+    fn user_line_text(&self, line: usize) -> &str {
+        self.source
+            .lines()
+            .nth(self.prelude_lines + line - 1)
+            .unwrap_or("")
+            .trim()
+    }
+
+    fn validate(&self, label: &str) -> Result<(), CustomShaderError> {
+        let module = naga::front::wgsl::parse_str(&self.source).map_err(|err| {
+            let (line, _) = err.location(&self.source);
+            CustomShaderError::Parse {
+                label: label.to_owned(),
+                line: line.checked_sub(self.prelude_lines).filter(|&line| line > 0),
+                message: err.emit_to_string(&self.source),
+            }
+        })?;
+
+        Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&module)
+            .map_err(|err| {
+                let mut message = err.to_string();
+                let mut source = err.source();
+                while let Some(inner) = source {
+                    message.push_str(&format!(": {}", inner));
+                    source = inner.source();
+                }
+
+                let mut line = None;
+                for (span, description) in err.spans() {
+                    let span_line = span.to_range().and_then(|range| self.user_line(range.start));
+                    match span_line {
+                        Some(span_line) => {
+                            message.push_str(&format!(
+                                "\n  line {}: {}\n    {}",
+                                span_line,
+                                description,
+                                self.user_line_text(span_line)
+                            ));
+                            line = line.or(Some(span_line));
+                        }
+                        None => message.push_str(&format!("\n  in the prelude: {}", description)),
+                    }
+                }
+
+                CustomShaderError::Validation {
+                    label: label.to_owned(),
+                    line,
+                    message,
+                }
+            })?;
+
+        Ok(())
+    }
+
+    fn create_module(self, device: &Device, label: &str) -> Result<ShaderModule, CustomShaderError> {
+        self.validate(label)?;
+        Ok(device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some(label),
+            source: ShaderSource::Wgsl(Cow::Owned(self.source)),
+        }))
+    }
+}
+
+/// Parses and validates WGSL with naga, without a prelude.
+pub fn validate_wgsl(label: &str, source: &str) -> Result<(), CustomShaderError> {
+    PreludedSource::new("", source).validate(label)
+}
+
+fn resolve_prelude(name: &str, builtin: &'static str, defines: &ShaderDefines) -> String {
+    preprocess_wgsl(&source_shader(name, builtin), defines).expect("preludes are well formed")
+}
+
+pub(crate) fn frame_prelude() -> String {
+    resolve_prelude(
+        "custom/frame.wgsl",
+        include_str!("../shaders/src/custom/frame.wgsl"),
+        &ShaderDefines::new(),
+    )
+}
+
+fn fullscreen_prelude() -> String {
+    let mut prelude = frame_prelude();
+    prelude.push_str(&resolve_prelude(
+        "custom/fullscreen.wgsl",
+        include_str!("../shaders/src/custom/fullscreen.wgsl"),
+        &ShaderDefines::new(),
+    ));
+    prelude
+}
+
+fn object_prelude(profile: RendererProfile) -> String {
+    let defines = ShaderDefines::new()
+        .with_if("GPU_DRIVEN", profile == RendererProfile::GpuDriven)
+        .with_if("DOWNLEVEL", profile == RendererProfile::Downlevel);
+    let mut prelude = frame_prelude();
+    prelude.push_str(&resolve_prelude(
+        "custom/object.wgsl",
+        include_str!("../shaders/src/custom/object.wgsl"),
+        &defines,
+    ));
+    prelude
+}
+
+/// Validates and creates a shader drawing objects, with the object prelude
+/// for `profile` prepended to `source`, see the [module](self) docs.
+///
+/// The module can be passed to
+/// [`ObjectPipelineBuilder`](crate::common::ObjectPipelineBuilder) or
+/// [`ForwardRoutine::new`](crate::forward::ForwardRoutine::new), the
+/// vertex buffers and first three bind groups being the ones they set up.
+pub fn object_shader(
+    device: &Device,
+    profile: RendererProfile,
+    label: &str,
+    source: &str,
+) -> Result<ShaderModule, CustomShaderError> {
+    PreludedSource::new(&object_prelude(profile), source).create_module(device, label)
+}
+
+/// Validates and creates the shader of a full-screen pass, with the
+/// full-screen prelude prepended to `source`, see the [module](self) docs.
+pub fn fullscreen_shader(device: &Device, label: &str, source: &str) -> Result<ShaderModule, CustomShaderError> {
+    PreludedSource::new(&fullscreen_prelude(), source).create_module(device, label)
+}
+
+/// A full-screen pass running a user fragment shader over a texture, e.g. a
+/// post process effect.
+///
+/// Bind groups are the forward uniforms at group 0, the source texture at
+/// group 1, and the `extra_bgl` if any at group 2.
+pub struct FullscreenRoutine {
+    label: String,
+    source_bgl: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl FullscreenRoutine {
+    /// `source` is the WGSL of the fragment shader, with entry point
+    /// `fragment_entry`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        renderer: &Renderer,
+        interfaces: &WholeFrameInterfaces,
+        label: &str,
+        source: &str,
+        fragment_entry: &str,
+        output_format: TextureFormat,
+        blend: Option<BlendState>,
+        extra_bgl: Option<&BindGroupLayout>,
+    ) -> Result<Self, CustomShaderError> {
+        profiling::scope!("FullscreenRoutine::new");
+
+        let module = fullscreen_shader(&renderer.device, label, source)?;
+
+        let mut bglb = BindGroupLayoutBuilder::new();
+        bglb.append(
+            ShaderStages::FRAGMENT,
+            BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            None,
+        );
+        let source_bgl = bglb.build(&renderer.device, Some(label));
+
+        let mut bgls = vec![&interfaces.forward_uniform_bgl, &source_bgl];
+        bgls.extend(extra_bgl);
+
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &bgls,
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pll),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "fullscreen_vs",
+                buffers: &[],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Cw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: fragment_entry,
+                targets: &[ColorTargetState {
+                    format: output_format,
+                    blend,
+                    write_mask: ColorWrites::all(),
+                }],
+            }),
+            multiview: None,
+        });
+
+        Ok(Self {
+            label: label.to_owned(),
+            source_bgl,
+            pipeline,
+        })
+    }
+
+    /// Runs the pass reading `src` and writing `dst`. `extra_bg` must be
+    /// given if the routine was created with an `extra_bgl`.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        src: RenderTargetHandle,
+        dst: RenderTargetHandle,
+        forward_uniform_bg: DataHandle<BindGroup>,
+        extra_bg: Option<&'node BindGroup>,
+    ) {
+        let mut builder = graph.add_node(&self.label);
+
+        let input_handle = builder.add_render_target_input(src);
+        let output_handle = builder.add_render_target_output(dst);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: output_handle,
+                clear: Color::BLACK,
+                resolve: None,
+            }],
+            depth_stencil: None,
+        });
+
+        let forward_uniform_handle = builder.add_data_input(forward_uniform_bg);
+
+        let pt_handle = builder.passthrough_ref(self);
+        let extra_handle = extra_bg.map(|bg| builder.passthrough_ref(bg));
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+            let forward_uniform_bg = graph_data.get_data(temps, forward_uniform_handle).unwrap();
+            let source = graph_data.get_render_target(input_handle);
+
+            profiling::scope!("custom fullscreen pass");
+
+            let mut bgb = BindGroupBuilder::new();
+            bgb.append_texture_view(source);
+            let source_bg = temps.add(bgb.build(&renderer.device, Some(&this.label), &this.source_bgl));
+
+            rpass.set_pipeline(&this.pipeline);
+            rpass.set_bind_group(0, forward_uniform_bg, &[]);
+            rpass.set_bind_group(1, source_bg, &[]);
+            if let Some(extra_handle) = extra_handle {
+                rpass.set_bind_group(2, pt.get(extra_handle), &[]);
+            }
+            rpass.draw(0..3, 0..1);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rend3::RendererProfile;
+
+    use super::{fullscreen_prelude, object_prelude, CustomShaderError, PreludedSource};
+
+    const OBJECT_VS: &str = "
+[[stage(vertex)]]
+fn vs_main(vertex: ObjectVertex) -> [[builtin(position)]] vec4<f32> {
+    let data = object_data(vertex);
+    return data.model_view_proj * vec4<f32>(vertex.position, 1.0);
+}
+";
+
+    #[test]
+    fn preludes_validate() {
+        for profile in [
+            RendererProfile::CpuDriven,
+            RendererProfile::GpuDriven,
+            RendererProfile::Downlevel,
+        ] {
+            PreludedSource::new(&object_prelude(profile), OBJECT_VS)
+                .validate("object")
+                .unwrap();
+        }
+
+        let fs = "
+[[stage(fragment)]]
+fn fs_main(vertex: FullscreenVertex) -> [[location(0)]] vec4<f32> {
+    return textureSample(source, primary_sampler, vertex.uv) * frame.ambient;
+}
+";
+        PreludedSource::new(&fullscreen_prelude(), fs)
+            .validate("fullscreen")
+            .unwrap();
+    }
+
+    #[test]
+    fn errors_point_at_user_lines() {
+        let prelude = fullscreen_prelude();
+
+        let parse = PreludedSource::new(&prelude, "\nfn broken( {}\n").validate("parse");
+        assert!(matches!(parse, Err(CustomShaderError::Parse { line: Some(2), .. })));
+
+        let invalid = "
+fn invalid() -> f32 {
+    return 1u;
+}
+";
+        let err = PreludedSource::new(&prelude, invalid).validate("invalid").unwrap_err();
+        assert!(matches!(err, CustomShaderError::Validation { .. }));
+        assert_eq!(err.line(), Some(3));
+    }
+}
@@ -0,0 +1,481 @@
+//! Debug drawing of lines, boxes and text labels, on top of the output.
+//!
+//! Collect shapes in a [`DebugDraw`] while updating the scene, e.g. bounding
+//! boxes of entities and labels with their names, then draw them with a
+//! [`DebugDrawRoutine`] after everything else that renders to the output, and
+//! clear them for the next frame.
+//!
+//! Text uses a small built-in font, rendered from a distance field so it stays
+//! crisp at any size, which avoids needing a full text stack to label things.
+//! Everything is drawn without depth testing, so it is never hidden by the
+//! scene.
+
+use std::mem;
+
+use glam::{Mat4, UVec2, Vec2, Vec3, Vec4};
+use rend3::{
+    graph::{RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupLayout, BindingType, BlendState, BufferAddress, BufferUsages, Color,
+    ColorTargetState, ColorWrites, Device, FilterMode, FragmentState, FrontFace, MultisampleState,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor,
+    SamplerBindingType, SamplerDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor,
+    TextureViewDimension, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::{
+    common::{self, GLYPH_ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH, LINE_HEIGHT, SDF_TEXELS_PER_FONT_TEXEL},
+    shaders,
+};
+
+/// Color and size of debug text.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextStyle {
+    /// Srgb color, with alpha.
+    pub color: Vec4,
+    /// Height of a character in pixels.
+    pub size: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            color: Vec4::ONE,
+            size: 14.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DebugLine {
+    start: Vec3,
+    end: Vec3,
+    color: Vec4,
+}
+
+#[derive(Debug, Clone)]
+enum TextAnchor {
+    /// Centered above a point in world space.
+    World(Vec3),
+    /// Top left corner in pixels from the top left of the output.
+    Screen(Vec2),
+}
+
+#[derive(Debug, Clone)]
+struct DebugText {
+    anchor: TextAnchor,
+    text: String,
+    style: TextStyle,
+}
+
+/// Shapes and text to draw with a [`DebugDrawRoutine`].
+///
+/// Colors are srgb with alpha.
+#[derive(Debug, Default, Clone)]
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+    texts: Vec<DebugText>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes everything, e.g. once a frame has been rendered.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.texts.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.texts.is_empty()
+    }
+
+    /// Line in world space, one pixel wide.
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Vec4) {
+        self.lines.push(DebugLine { start, end, color });
+    }
+
+    /// Edges of an axis aligned box in world space.
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: Vec4) {
+        let corner = |x: bool, y: bool, z: bool| {
+            Vec3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            )
+        };
+
+        for a in [false, true] {
+            for b in [false, true] {
+                self.line(corner(false, a, b), corner(true, a, b), color);
+                self.line(corner(a, false, b), corner(a, true, b), color);
+                self.line(corner(a, b, false), corner(a, b, true), color);
+            }
+        }
+    }
+
+    /// Text facing the camera, centered above `position` in world space. It
+    /// keeps the same size on screen however far away it is.
+    pub fn text_3d(&mut self, position: Vec3, text: impl Into<String>) {
+        self.text_3d_styled(position, text, TextStyle::default());
+    }
+
+    pub fn text_3d_styled(&mut self, position: Vec3, text: impl Into<String>, style: TextStyle) {
+        self.texts.push(DebugText {
+            anchor: TextAnchor::World(position),
+            text: text.into(),
+            style,
+        });
+    }
+
+    /// Text with its top left corner at `position`, in pixels from the top
+    /// left of the output.
+    pub fn text_2d(&mut self, position: Vec2, text: impl Into<String>) {
+        self.text_2d_styled(position, text, TextStyle::default());
+    }
+
+    pub fn text_2d_styled(&mut self, position: Vec2, text: impl Into<String>, style: TextStyle) {
+        self.texts.push(DebugText {
+            anchor: TextAnchor::Screen(position),
+            text: text.into(),
+            style,
+        });
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct DebugVertex {
+    // Arrays, as the alignment of glam's vectors would pad the struct.
+    /// Clip space position.
+    position: [f32; 4],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for DebugVertex {}
+unsafe impl bytemuck::Zeroable for DebugVertex {}
+
+/// Turns the contents of a [`DebugDraw`] into vertices.
+struct VertexBuilder {
+    view_proj: Mat4,
+    resolution: Vec2,
+    atlas_resolution: Vec2,
+    srgb: bool,
+}
+
+impl VertexBuilder {
+    fn color(&self, color: Vec4) -> Vec4 {
+        if self.srgb {
+            common::srgb_to_linear(color)
+        } else {
+            color
+        }
+    }
+
+    fn lines(&self, lines: &[DebugLine]) -> Vec<DebugVertex> {
+        lines
+            .iter()
+            .flat_map(|line| {
+                let color = self.color(line.color);
+                [line.start, line.end].map(|point| DebugVertex {
+                    position: (self.view_proj * point.extend(1.0)).to_array(),
+                    uv: [0.0; 2],
+                    color: color.to_array(),
+                })
+            })
+            .collect()
+    }
+
+    fn texts(&self, texts: &[DebugText]) -> Vec<DebugVertex> {
+        let mut vertices = Vec::new();
+        for text in texts {
+            let texel_size = text.style.size / GLYPH_HEIGHT as f32;
+            let columns = text.text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+            let rows = text.text.lines().count();
+            let block_size = Vec2::new(
+                (columns as u32 * GLYPH_ADVANCE).saturating_sub(GLYPH_ADVANCE - GLYPH_WIDTH) as f32,
+                (rows as u32 * LINE_HEIGHT).saturating_sub(LINE_HEIGHT - GLYPH_HEIGHT) as f32,
+            ) * texel_size;
+
+            // Anchor in clip space, and the offset of the top left corner of
+            // the text from it in pixels.
+            let (anchor, top_left) = match text.anchor {
+                TextAnchor::World(position) => {
+                    let clip = self.view_proj * position.extend(1.0);
+                    // Behind the camera.
+                    if clip.w <= 0.0 {
+                        continue;
+                    }
+                    (clip, Vec2::new(-block_size.x / 2.0, -block_size.y))
+                }
+                TextAnchor::Screen(position) => (Vec4::new(-1.0, 1.0, 0.0, 1.0), position),
+            };
+            // Pixels are a constant size in clip space after the divide by w.
+            let pixel_to_clip = Vec2::new(2.0, -2.0) / self.resolution * anchor.w;
+            let color = self.color(text.style.color);
+
+            for (row, line) in text.text.lines().enumerate() {
+                for (column, c) in line.chars().enumerate() {
+                    if c == ' ' {
+                        continue;
+                    }
+
+                    let glyph_start = top_left
+                        + Vec2::new(
+                            (column as u32 * GLYPH_ADVANCE) as f32,
+                            (row as u32 * LINE_HEIGHT) as f32,
+                        ) * texel_size;
+                    let glyph_size = Vec2::new(GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32);
+                    let uv_start = common::sdf_cell_origin(common::glyph_cell(c)).as_vec2();
+                    let uv_size = glyph_size * SDF_TEXELS_PER_FONT_TEXEL as f32;
+
+                    let corner = |x: f32, y: f32| {
+                        let offset = (glyph_start + Vec2::new(x, y) * glyph_size * texel_size) * pixel_to_clip;
+                        DebugVertex {
+                            position: (anchor + offset.extend(0.0).extend(0.0)).to_array(),
+                            uv: ((uv_start + Vec2::new(x, y) * uv_size) / self.atlas_resolution).to_array(),
+                            color: color.to_array(),
+                        }
+                    };
+
+                    vertices.extend_from_slice(&[
+                        corner(0.0, 0.0),
+                        corner(1.0, 0.0),
+                        corner(0.0, 1.0),
+                        corner(0.0, 1.0),
+                        corner(1.0, 0.0),
+                        corner(1.0, 1.0),
+                    ]);
+                }
+            }
+        }
+        vertices
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    bgl: &BindGroupLayout,
+    module: &ShaderModule,
+    output_format: TextureFormat,
+    topology: PrimitiveTopology,
+    fragment_entry_point: &str,
+) -> RenderPipeline {
+    let pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("debug draw"),
+        bind_group_layouts: &[bgl],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("debug draw"),
+        layout: Some(&pll),
+        vertex: VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[VertexBufferLayout {
+                array_stride: mem::size_of::<DebugVertex>() as BufferAddress,
+                step_mode: VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x2, 2 => Float32x4],
+            }],
+        },
+        primitive: PrimitiveState {
+            topology,
+            strip_index_format: None,
+            front_face: FrontFace::Cw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module,
+            entry_point: fragment_entry_point,
+            targets: &[ColorTargetState {
+                format: output_format,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::all(),
+            }],
+        }),
+        multiview: None,
+    })
+}
+
+/// Draws the contents of a [`DebugDraw`].
+///
+/// See module for documentation.
+pub struct DebugDrawRoutine {
+    line_pipeline: RenderPipeline,
+    text_pipeline: RenderPipeline,
+    atlas_bg: BindGroup,
+    atlas_resolution: UVec2,
+    srgb: bool,
+}
+
+impl DebugDrawRoutine {
+    pub fn new(renderer: &Renderer, output_format: TextureFormat) -> Self {
+        profiling::scope!("DebugDrawRoutine::new");
+
+        let (atlas_resolution, atlas_texels) = common::font_sdf_atlas();
+        let atlas = renderer.device.create_texture_with_data(
+            &renderer.queue,
+            &TextureDescriptor {
+                label: Some("debug draw font"),
+                size: wgpu::Extent3d {
+                    width: atlas_resolution.x,
+                    height: atlas_resolution.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::R8Unorm,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            },
+            &atlas_texels,
+        );
+        let atlas_view = atlas.create_view(&TextureViewDescriptor::default());
+        let sampler = renderer.device.create_sampler(&SamplerDescriptor {
+            label: Some("debug draw font"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..SamplerDescriptor::default()
+        });
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .build(&renderer.device, Some("debug draw bgl"));
+        let atlas_bg = BindGroupBuilder::new()
+            .append_texture_view(&atlas_view)
+            .append_sampler(&sampler)
+            .build(&renderer.device, Some("debug draw bg"), &bgl);
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("debug draw"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "debug.wgsl",
+                include_str!("../shaders/src/debug.wgsl"),
+            )),
+        });
+
+        let line_pipeline = create_pipeline(
+            &renderer.device,
+            &bgl,
+            &module,
+            output_format,
+            PrimitiveTopology::LineList,
+            "fs_line",
+        );
+        let text_pipeline = create_pipeline(
+            &renderer.device,
+            &bgl,
+            &module,
+            output_format,
+            PrimitiveTopology::TriangleList,
+            "fs_text",
+        );
+
+        Self {
+            line_pipeline,
+            text_pipeline,
+            atlas_bg,
+            atlas_resolution,
+            srgb: output_format.describe().srgb,
+        }
+    }
+
+    /// Draws everything in `draw` on top of `output`, seen from the camera of
+    /// the renderer.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        draw: &'node DebugDraw,
+        output: RenderTargetHandle,
+        resolution: UVec2,
+    ) {
+        if draw.is_empty() {
+            return;
+        }
+
+        let mut builder = graph.add_node("Debug Draw");
+
+        let output_handle = builder.add_render_target_output(output);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: output_handle,
+                clear: Color::BLACK,
+                resolve: None,
+            }],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+        let draw_handle = builder.passthrough_ref(draw);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let draw = pt.get(draw_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("debug draw");
+
+            // The camera is only final once the renderer is readied.
+            let vertex_builder = VertexBuilder {
+                view_proj: graph_data.camera_manager.view_proj(),
+                resolution: resolution.as_vec2(),
+                atlas_resolution: this.atlas_resolution.as_vec2(),
+                srgb: this.srgb,
+            };
+            let line_vertices = vertex_builder.lines(&draw.lines);
+            let text_vertices = vertex_builder.texts(&draw.texts);
+
+            rpass.set_bind_group(0, &this.atlas_bg, &[]);
+            for (pipeline, vertices, label) in [
+                (&this.line_pipeline, line_vertices, "debug draw lines"),
+                (&this.text_pipeline, text_vertices, "debug draw text"),
+            ] {
+                if vertices.is_empty() {
+                    continue;
+                }
+
+                let vertex_buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some(label),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: BufferUsages::VERTEX,
+                }));
+
+                rpass.set_pipeline(pipeline);
+                rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                rpass.draw(0..vertices.len() as u32, 0..1);
+            }
+        });
+    }
+}
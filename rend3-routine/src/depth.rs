@@ -12,7 +12,8 @@ use rend3::{
         DataHandle, DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets,
         RenderTargetHandle,
     },
-    types::{Handedness, Material, SampleCount},
+    managers::SkinningMode,
+    types::{DepthMode, Handedness, Material, SampleCount},
     util::{
         bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
         math::round_up_pot,
@@ -21,16 +22,16 @@ use rend3::{
 };
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BindGroup, BindGroupLayout, BufferUsages, Color, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
-    DepthStencilState, Face, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode,
-    PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderStages, StencilState,
-    TextureFormat, VertexState,
+    BindGroup, BindGroupLayout, BufferUsages, Color, ColorTargetState, ColorWrites, DepthBiasState, DepthStencilState,
+    Face, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState,
+    PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StencilState, TextureFormat, VertexState,
 };
 
 use crate::{
     common::{
-        profile_safe_shader, PerMaterialArchetypeInterface, WholeFrameInterfaces, CPU_VERTEX_BUFFERS,
-        GPU_VERTEX_BUFFERS,
+        hooked_shader, hooked_wgsl, vertex_buffer_layouts, HookedStage, ObjectPass, PerMaterialArchetypeInterface,
+        RenderStateOverride, RenderStateOverrides, ShaderHooks, WholeFrameInterfaces,
     },
     culling::{self, PerMaterialArchetypeData},
 };
@@ -84,6 +85,7 @@ unsafe impl bytemuck::Zeroable for AlphaDataAbi {}
 pub struct DepthRoutine<M> {
     pipelines: DepthPipelines<M>,
     bg: Option<BindGroup>,
+    depth_mode: DepthMode,
 }
 
 impl<M: DepthRenderableMaterial> DepthRoutine<M> {
@@ -93,6 +95,8 @@ impl<M: DepthRenderableMaterial> DepthRoutine<M> {
         interfaces: &WholeFrameInterfaces,
         per_material: &PerMaterialArchetypeInterface<M>,
         unclipped_depth_supported: bool,
+        hooks: &ShaderHooks,
+        overrides: &RenderStateOverrides,
     ) -> Self {
         let abi_bgl;
         let bg;
@@ -155,9 +159,15 @@ impl<M: DepthRenderableMaterial> DepthRoutine<M> {
             &per_material.bgl,
             abi_bgl.as_ref(),
             unclipped_depth_supported,
+            hooks,
+            overrides,
         );
 
-        Self { pipelines, bg }
+        Self {
+            pipelines,
+            bg,
+            depth_mode: renderer.depth_mode,
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -189,14 +199,14 @@ impl<M: DepthRenderableMaterial> DepthRoutine<M> {
             }],
             depth_stencil: Some(RenderPassDepthTarget {
                 target: DepthHandle::RenderTarget(hdr_depth_handle),
-                depth_clear: Some(0.0),
+                depth_clear: Some(self.depth_mode.far_depth()),
                 stencil_clear: None,
             }),
         });
 
         let pt_handle = builder.passthrough_ref(self);
 
-        builder.build(move |pt, _renderer, encoder_or_pass, temps, ready, graph_data| {
+        builder.build(move |pt, renderer, encoder_or_pass, temps, ready, graph_data| {
             let this = pt.get(pt_handle);
             let rpass = encoder_or_pass.get_rpass(rpass_handle);
             let forward_uniform_bg = graph_data.get_data(temps, forward_uniform_handle).unwrap();
@@ -213,7 +223,11 @@ impl<M: DepthRenderableMaterial> DepthRoutine<M> {
 
             rpass.set_pipeline(pipeline);
             rpass.set_bind_group(0, forward_uniform_bg, &[]);
-            rpass.set_bind_group(1, &culled.per_material, &[]);
+            rpass.set_bind_group(
+                1,
+                &culled.per_material,
+                PerMaterialArchetypeData::per_material_offsets(renderer.profile),
+            );
             let material_index = if let Some(ref bg) = this.bg {
                 rpass.set_bind_group(2, bg, &[]);
                 3
@@ -222,12 +236,17 @@ impl<M: DepthRenderableMaterial> DepthRoutine<M> {
             };
 
             match culled.inner.calls {
-                ProfileData::Cpu(ref draws) => {
-                    culling::draw_cpu_powered::<M>(rpass, draws, graph_data.material_manager, material_index)
-                }
+                ProfileData::Cpu(ref draws) => culling::draw_cpu_powered::<M>(
+                    rpass,
+                    draws,
+                    graph_data.material_manager,
+                    material_index,
+                    culled.per_object_binding(renderer.profile, 1),
+                    graph_data.debug_markers,
+                ),
                 ProfileData::Gpu(ref data) => {
                     rpass.set_bind_group(material_index, ready.d2_texture.bg.as_gpu(), &[]);
-                    culling::draw_gpu_powered(rpass, data);
+                    culling::draw_gpu_powered::<M>(rpass, data, graph_data.debug_markers);
                 }
             }
         });
@@ -255,14 +274,14 @@ impl<M: DepthRenderableMaterial> DepthRoutine<M> {
             targets: vec![],
             depth_stencil: Some(RenderPassDepthTarget {
                 target: DepthHandle::Shadow(shadow_output_handle),
-                depth_clear: Some(0.0),
+                depth_clear: Some(self.depth_mode.far_depth()),
                 stencil_clear: None,
             }),
         });
 
         let pt_handle = builder.passthrough_ref(self);
 
-        builder.build(move |pt, _renderer, encoder_or_pass, temps, ready, graph_data| {
+        builder.build(move |pt, renderer, encoder_or_pass, temps, ready, graph_data| {
             let this = pt.get(pt_handle);
             let rpass = encoder_or_pass.get_rpass(rpass_handle);
             let shadow_uniform = graph_data.get_data(temps, shadow_uniform_handle).unwrap();
@@ -276,7 +295,11 @@ impl<M: DepthRenderableMaterial> DepthRoutine<M> {
             graph_data.mesh_manager.buffers().bind(rpass);
             rpass.set_pipeline(pipeline);
             rpass.set_bind_group(0, shadow_uniform, &[]);
-            rpass.set_bind_group(1, &culled.per_material, &[]);
+            rpass.set_bind_group(
+                1,
+                &culled.per_material,
+                PerMaterialArchetypeData::per_material_offsets(renderer.profile),
+            );
             let material_index = if let Some(ref bg) = this.bg {
                 rpass.set_bind_group(2, bg, &[]);
                 3
@@ -285,12 +308,17 @@ impl<M: DepthRenderableMaterial> DepthRoutine<M> {
             };
 
             match culled.inner.calls {
-                ProfileData::Cpu(ref draws) => {
-                    culling::draw_cpu_powered::<M>(rpass, draws, graph_data.material_manager, material_index)
-                }
+                ProfileData::Cpu(ref draws) => culling::draw_cpu_powered::<M>(
+                    rpass,
+                    draws,
+                    graph_data.material_manager,
+                    material_index,
+                    culled.per_object_binding(renderer.profile, 1),
+                    graph_data.debug_markers,
+                ),
                 ProfileData::Gpu(ref data) => {
                     rpass.set_bind_group(material_index, ready.d2_texture.bg.as_gpu(), &[]);
-                    culling::draw_gpu_powered(rpass, data);
+                    culling::draw_gpu_powered::<M>(rpass, data, graph_data.debug_markers);
                 }
             }
         });
@@ -321,6 +349,11 @@ impl<M: DepthRenderableMaterial> DepthPipelines<M> {
     /// If abi_bgl is Some, cutout shaders will be generated, otherwise they won't.
     ///
     /// The abi_bgl is how we communicate _how_ to do the cutout.
+    ///
+    /// The vertex and fragment `hooks` are spliced into the depth shaders,
+    /// and the shadow and prepass `overrides` replace parts of their pipeline
+    /// state.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         renderer: &Renderer,
         data_core: &RendererDataCore,
@@ -328,38 +361,74 @@ impl<M: DepthRenderableMaterial> DepthPipelines<M> {
         per_material_bgl: &BindGroupLayout,
         abi_bgl: Option<&BindGroupLayout>,
         unclipped_depth_supported: bool,
+        hooks: &ShaderHooks,
+        overrides: &RenderStateOverrides,
     ) -> DepthPipelines<M> {
         profiling::scope!("build depth pass pipelines");
+        let (depth_vert_cpu, depth_vert_gpu) = match renderer.skinning_mode {
+            SkinningMode::PreSkinned => ("depth.vert.cpu.wgsl", "depth.vert.gpu.spv"),
+            SkinningMode::VertexShader => ("depth-skinned.vert.cpu.wgsl", "depth-skinned.vert.gpu.spv"),
+        };
         let depth_vert = unsafe {
-            profile_safe_shader(
+            hooked_shader(
                 &renderer.device,
                 renderer.profile,
                 "depth pass vert",
-                "depth.vert.cpu.wgsl",
-                "depth.vert.gpu.spv",
+                depth_vert_cpu,
+                depth_vert_gpu,
+                "depth.vert.downlevel.wgsl",
+                HookedStage::Vertex,
+                hooks,
             )
         };
 
         let depth_opaque_frag = unsafe {
-            profile_safe_shader(
+            hooked_shader(
                 &renderer.device,
                 renderer.profile,
                 "depth pass opaque frag",
                 "depth-opaque.frag.cpu.wgsl",
                 "depth-opaque.frag.gpu.spv",
+                "depth-opaque.frag.cpu.wgsl",
+                HookedStage::DepthFragment,
+                hooks,
             )
         };
 
         let depth_cutout_frag = unsafe {
-            profile_safe_shader(
+            hooked_shader(
                 &renderer.device,
                 renderer.profile,
                 "depth pass cutout frag",
                 "depth-cutout.frag.cpu.wgsl",
                 "depth-cutout.frag.gpu.spv",
+                "depth-cutout.frag.downlevel.wgsl",
+                HookedStage::DepthFragment,
+                hooks,
             )
         };
 
+        // The cutout prepass writes the alpha as coverage instead of discarding.
+        let alpha_to_coverage = overrides.get(ObjectPass::Cutout).alpha_to_coverage == Some(true);
+        let depth_coverage_frag = match (alpha_to_coverage && abi_bgl.is_some(), renderer.profile) {
+            (false, _) => None,
+            (true, RendererProfile::GpuDriven) => {
+                log::warn!("Alpha to coverage isn't supported in the GpuDriven profile, discarding instead");
+                None
+            }
+            (true, RendererProfile::CpuDriven | RendererProfile::Downlevel) => {
+                let name = match renderer.profile {
+                    RendererProfile::Downlevel => "depth-cutout.frag.downlevel.wgsl",
+                    _ => "depth-cutout.frag.cpu.wgsl",
+                };
+                let source = hooked_wgsl(name, HookedStage::DepthFragment, hooks);
+                Some(renderer.device.create_shader_module(&ShaderModuleDescriptor {
+                    label: Some("depth pass coverage frag"),
+                    source: ShaderSource::Wgsl(alpha_to_coverage_shader(&source).into()),
+                }))
+            }
+        };
+
         let mut bgls: ArrayVec<&BindGroupLayout, 4> = ArrayVec::new();
         bgls.push(&interfaces.depth_uniform_bgl);
         bgls.push(per_material_bgl);
@@ -385,18 +454,24 @@ impl<M: DepthRenderableMaterial> DepthPipelines<M> {
             push_constant_ranges: &[],
         });
 
-        let inner = |name, ty, pll, frag, samples| {
+        let inner_coverage = |name, ty, pll, frag, samples, alpha_to_coverage| {
             create_depth_inner(
                 renderer,
                 samples,
                 ty,
                 unclipped_depth_supported,
+                overrides.get(match ty {
+                    DepthPassType::Shadow => ObjectPass::Shadow,
+                    DepthPassType::Prepass => ObjectPass::Prepass,
+                }),
                 pll,
                 &depth_vert,
                 frag,
+                alpha_to_coverage,
                 name,
             )
         };
+        let inner = |name, ty, pll, frag, samples| inner_coverage(name, ty, pll, frag, samples, false);
 
         let optional_inner = |name, ty, pll, frag, samples| {
             if abi_bgl.is_some() {
@@ -442,27 +517,86 @@ impl<M: DepthRenderableMaterial> DepthPipelines<M> {
                 &depth_opaque_frag,
                 SampleCount::Four,
             ),
-            prepass_cutout_s4: optional_inner(
-                "Prepass Cutout 4x",
-                DepthPassType::Prepass,
-                &prepass_pll,
-                &depth_cutout_frag,
-                SampleCount::Four,
-            ),
+            prepass_cutout_s4: match depth_coverage_frag {
+                Some(ref coverage_frag) => Some(inner_coverage(
+                    "Prepass Cutout Coverage 4x",
+                    DepthPassType::Prepass,
+                    &prepass_pll,
+                    coverage_frag,
+                    SampleCount::Four,
+                    true,
+                )),
+                None => optional_inner(
+                    "Prepass Cutout 4x",
+                    DepthPassType::Prepass,
+                    &prepass_pll,
+                    &depth_cutout_frag,
+                    SampleCount::Four,
+                ),
+            },
             _phantom: PhantomData::<M>::default(),
         }
     }
 }
 
+/// Turns the cutout depth shader `source` into one writing how far the alpha
+/// is above the cutoff, scaled to about a pixel, as the alpha of its output
+/// for alpha to coverage.
+fn alpha_to_coverage_shader(source: &str) -> String {
+    const DISCARD: &str = "discard;";
+    const MAIN_CALL: &str = "\n    main_1();\n";
+
+    // Clip planes discard too, so look for the discard comparing with the cutoff.
+    let (condition_start, discard) = source
+        .match_indices(DISCARD)
+        .filter_map(|(discard, _)| Some((source[..discard].rfind("if ((")?, discard)))
+        .find(|&(condition_start, discard)| source[condition_start..discard].contains(" <= "))
+        .expect("cutout shader doesn't discard below the cutoff");
+    let condition_end = condition_start + source[condition_start..].find(")) {").unwrap();
+    let (alpha, cutoff) = source[condition_start + "if ((".len()..condition_end]
+        .split_once(" <= ")
+        .expect("cutout condition isn't alpha <= cutoff");
+    let block_end = discard + source[discard..].find('}').unwrap() + 1;
+
+    let entry_point = source.find("[[stage(").expect("shader has no entry point");
+    let signature_end = entry_point + source[entry_point..].find(") {").unwrap();
+    let main_call = entry_point + source[entry_point..].find(MAIN_CALL).unwrap();
+
+    // The other discards, of clip planes and fades, are delayed until after
+    // the derivative, which needs uniform control flow.
+    let main_fn = source.find("fn main_1(").expect("shader has no main_1");
+    let delay_discards = |code: &str| code.replace(DISCARD, "o_discarded = true;");
+
+    let mut result = String::with_capacity(source.len() + 256);
+    result.push_str("var<private> o_alpha: f32;\nvar<private> o_cutoff: f32;\nvar<private> o_discarded: bool;\n\n");
+    result.push_str(&source[..main_fn]);
+    result.push_str(&delay_discards(&source[main_fn..condition_start]));
+    result.push_str(&format!("o_alpha = {};\n        o_cutoff = {};", alpha, cutoff));
+    result.push_str(&delay_discards(&source[block_end..entry_point]));
+    result.push_str(&source[entry_point..signature_end]);
+    result.push_str(") -> [[location(0)]] vec4<f32> {");
+    result.push_str(&source[signature_end + ") {".len()..main_call]);
+    result.push_str("\n    o_alpha = 1.0;\n    o_cutoff = 0.0;\n    o_discarded = false;");
+    result.push_str(MAIN_CALL);
+    // Derivatives need uniform control flow, which the texture sample isn't in.
+    result.push_str("    let coverage = (o_alpha - o_cutoff) / max(fwidth(o_alpha), 0.0001) + 0.5;\n");
+    result.push_str("    if (o_discarded) {\n        discard;\n    }\n");
+    result.push_str("    return vec4<f32>(0.0, 0.0, 0.0, coverage);\n");
+    result.push_str(&source[main_call + MAIN_CALL.len()..]);
+    result
+}
+
 #[allow(clippy::too_many_arguments)]
 fn create_depth_inner(
     renderer: &Renderer,
     samples: SampleCount,
     ty: DepthPassType,
     unclipped_depth_supported: bool,
+    render_state: RenderStateOverride,
     pll: &wgpu::PipelineLayout,
     vert: &wgpu::ShaderModule,
     frag: &wgpu::ShaderModule,
+    alpha_to_coverage: bool,
     name: &str,
 ) -> RenderPipeline {
     profiling::scope!("build depth pipeline", name);
@@ -471,48 +605,59 @@ fn create_depth_inner(
         blend: None,
         write_mask: ColorWrites::empty(),
     }];
+    let mut primitive = PrimitiveState {
+        topology: PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        front_face: match renderer.handedness {
+            Handedness::Left => FrontFace::Cw,
+            Handedness::Right => FrontFace::Ccw,
+        },
+        cull_mode: Some(match ty {
+            DepthPassType::Shadow => Face::Front,
+            DepthPassType::Prepass => Face::Back,
+        }),
+        unclipped_depth: matches!(ty, DepthPassType::Shadow) && unclipped_depth_supported,
+        polygon_mode: PolygonMode::Fill,
+        conservative: false,
+    };
+    render_state.apply_primitive(&mut primitive);
+
+    let mut depth_stencil = DepthStencilState {
+        format: TextureFormat::Depth32Float,
+        depth_write_enabled: true,
+        depth_compare: renderer.depth_mode.closer_or_equal(),
+        stencil: StencilState::default(),
+        bias: match ty {
+            DepthPassType::Prepass => DepthBiasState::default(),
+            // Push shadow casters away from the light, which is towards the far depth.
+            DepthPassType::Shadow => {
+                let sign = match renderer.depth_mode.is_reversed() {
+                    true => -1,
+                    false => 1,
+                };
+                DepthBiasState {
+                    constant: 2 * sign,
+                    slope_scale: 2.0 * sign as f32,
+                    clamp: 0.0,
+                }
+            }
+        },
+    };
+    render_state.apply_depth_stencil(&mut depth_stencil);
+
     renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
         label: Some(name),
         layout: Some(pll),
         vertex: VertexState {
             module: vert,
             entry_point: "main",
-            buffers: match renderer.profile {
-                RendererProfile::CpuDriven => &CPU_VERTEX_BUFFERS,
-                RendererProfile::GpuDriven => &GPU_VERTEX_BUFFERS,
-            },
+            buffers: &vertex_buffer_layouts(renderer),
         },
-        primitive: PrimitiveState {
-            topology: PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: match renderer.handedness {
-                Handedness::Left => FrontFace::Cw,
-                Handedness::Right => FrontFace::Ccw,
-            },
-            cull_mode: Some(match ty {
-                DepthPassType::Shadow => Face::Front,
-                DepthPassType::Prepass => Face::Back,
-            }),
-            unclipped_depth: matches!(ty, DepthPassType::Shadow) && unclipped_depth_supported,
-            polygon_mode: PolygonMode::Fill,
-            conservative: false,
-        },
-        depth_stencil: Some(DepthStencilState {
-            format: TextureFormat::Depth32Float,
-            depth_write_enabled: true,
-            depth_compare: CompareFunction::GreaterEqual,
-            stencil: StencilState::default(),
-            bias: match ty {
-                DepthPassType::Prepass => DepthBiasState::default(),
-                DepthPassType::Shadow => DepthBiasState {
-                    constant: -2,
-                    slope_scale: -2.0,
-                    clamp: 0.0,
-                },
-            },
-        }),
+        primitive,
+        depth_stencil: Some(depth_stencil),
         multisample: MultisampleState {
             count: samples as u32,
+            alpha_to_coverage_enabled: alpha_to_coverage,
             ..Default::default()
         },
         fragment: Some(FragmentState {
@@ -526,3 +671,21 @@ fn create_depth_inner(
         multiview: None,
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::alpha_to_coverage_shader;
+    use crate::shaders::wgsl_shader;
+
+    #[test]
+    fn coverage_replaces_discard() {
+        for name in ["depth-cutout.frag.cpu.wgsl", "depth-cutout.frag.downlevel.wgsl"] {
+            let shader = alpha_to_coverage_shader(&wgsl_shader(name));
+            // Clip planes and fades only discard after the coverage is computed.
+            assert_eq!(shader.matches("discard;").count(), 1);
+            assert!(shader.find("fwidth(o_alpha)").unwrap() < shader.find("discard;").unwrap());
+            assert!(shader.contains("-> [[location(0)]] vec4<f32>"));
+            assert!(shader.contains("return vec4<f32>(0.0, 0.0, 0.0, coverage);"));
+        }
+    }
+}
@@ -0,0 +1,471 @@
+//! Captures of the whole environment around a point.
+//!
+//! The scene is rendered once per face of a cube texture, which can be used
+//! as a skybox or a reflection probe, and projected into equirectangular
+//! images for 360° screenshots.
+
+use std::{f32::consts::FRAC_PI_4, num::NonZeroU64, sync::Arc};
+
+use glam::{Mat4, UVec2, Vec3, Vec4};
+use parking_lot::Mutex;
+use rend3::{
+    graph::RenderGraph,
+    types::{Camera, CameraProjection, Handedness, SampleCount, TextureFormat, TextureHandle},
+    util::{
+        bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+        output::OutputFrame,
+    },
+    Renderer, RendererError,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BufferBindingType, BufferUsages, Color, ColorTargetState,
+    ColorWrites, CommandEncoderDescriptor, Device, Extent3d, FilterMode, FragmentState, LoadOp, MultisampleState,
+    Operations, PipelineLayoutDescriptor, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureDescriptor, TextureDimension, TextureSampleType,
+    TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use crate::{
+    base::BaseRenderGraph, pbr::PbrRoutine, shaders::source_shader, skybox::SkyboxRoutine,
+    tonemapping::TonemappingRoutine,
+};
+
+/// Format environments are captured in.
+pub const ENVIRONMENT_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Forward, right and up directions of the faces of a cube texture, in the
+/// order of its layers, as defined by how cube textures are sampled.
+const CUBE_FACES: [(Vec3, Vec3, Vec3); 6] = [
+    (Vec3::X, glam::const_vec3!([0.0, 0.0, -1.0]), Vec3::Y),
+    (glam::const_vec3!([-1.0, 0.0, 0.0]), Vec3::Z, Vec3::Y),
+    (Vec3::Y, Vec3::X, glam::const_vec3!([0.0, 0.0, -1.0])),
+    (glam::const_vec3!([0.0, -1.0, 0.0]), Vec3::X, Vec3::Z),
+    (Vec3::Z, Vec3::X, Vec3::Y),
+    (
+        glam::const_vec3!([0.0, 0.0, -1.0]),
+        glam::const_vec3!([-1.0, 0.0, 0.0]),
+        Vec3::Y,
+    ),
+];
+
+/// Camera seeing the face `face` of a cube texture centered on `position`,
+/// faces being in the order +X, -X, +Y, -Y, +Z, -Z.
+///
+/// Cube textures are laid out as seen from a left handed camera, so with
+/// [`Handedness::Right`] the image is mirrored horizontally compared to the
+/// face, which must be undone when copying it into the cube.
+pub fn cube_face_camera(face: usize, position: Vec3, handedness: Handedness, near: f32) -> Camera {
+    let (forward, right, up) = CUBE_FACES[face];
+    let camera_to_world = match handedness {
+        Handedness::Left => Mat4::from_cols(
+            right.extend(0.0),
+            up.extend(0.0),
+            forward.extend(0.0),
+            position.extend(1.0),
+        ),
+        Handedness::Right => Mat4::from_cols(
+            (-right).extend(0.0),
+            up.extend(0.0),
+            (-forward).extend(0.0),
+            position.extend(1.0),
+        ),
+    };
+    Camera {
+        projection: CameraProjection::Asymmetric {
+            left: -FRAC_PI_4,
+            right: FRAC_PI_4,
+            up: FRAC_PI_4,
+            down: -FRAC_PI_4,
+            near,
+        },
+        view: camera_to_world.inverse(),
+    }
+}
+
+/// A cube texture the scene is rendered into with the default rendergraph,
+/// from a point.
+///
+/// Use [`EnvironmentCapture::texture`] as the background of a
+/// [`SkyboxRoutine`] or in materials as a reflection probe, and
+/// [`EnvironmentCapture::equirect`] to unwrap it into a panorama. Captures
+/// are only made when [`EnvironmentCapture::render`] is called, so they can
+/// be baked once or refreshed every few frames.
+pub struct EnvironmentCapture {
+    /// Distance of the near plane from the capture point.
+    pub near: f32,
+    tonemapping: TonemappingRoutine,
+    texture: TextureHandle,
+    cube_view: Arc<TextureView>,
+    faces: [Arc<TextureView>; 6],
+    size: u32,
+    /// Faces are rendered here first, then copied into the cube.
+    face_target: Arc<TextureView>,
+    face_bind_group: BindGroup,
+    face_pipeline: RenderPipeline,
+    module: ShaderModule,
+    equirect_bgl: BindGroupLayout,
+    sampler: Sampler,
+    equirect_pipelines: Mutex<Vec<(TextureFormat, RenderPipeline)>>,
+}
+
+impl EnvironmentCapture {
+    /// Creates a capture whose faces are `size` pixels wide. Fails if the
+    /// device can't create a cube texture that large.
+    pub fn new(renderer: &Renderer, base_rendergraph: &BaseRenderGraph, size: u32) -> Result<Self, RendererError> {
+        profiling::scope!("EnvironmentCapture::new");
+
+        let device = &renderer.device;
+        let (texture, cube_view, faces) = renderer.add_texture_cube_render_target(size, ENVIRONMENT_FORMAT)?;
+
+        let face_texture = device.create_texture(&TextureDescriptor {
+            label: Some("environment face"),
+            size: Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: ENVIRONMENT_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        });
+        let face_target = Arc::new(face_texture.create_view(&TextureViewDescriptor::default()));
+
+        let module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("environment"),
+            source: ShaderSource::Wgsl(source_shader(
+                "environment.wgsl",
+                include_str!("../shaders/src/environment.wgsl"),
+            )),
+        });
+
+        let face_bgl = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("environment face"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let face_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("environment face"),
+            layout: &face_bgl,
+            entries: &[BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(&face_target),
+            }],
+        });
+        let face_pipeline = create_pipeline(
+            device,
+            &module,
+            &[&face_bgl],
+            match renderer.handedness {
+                Handedness::Left => "fs_face",
+                Handedness::Right => "fs_face_mirrored",
+            },
+            ENVIRONMENT_FORMAT,
+        );
+
+        let equirect_bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::Cube,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(64),
+                },
+                None,
+            )
+            .build(device, Some("environment equirect"));
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("environment equirect"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+
+        Ok(Self {
+            near: 0.1,
+            tonemapping: TonemappingRoutine::new(renderer, &base_rendergraph.interfaces, ENVIRONMENT_FORMAT),
+            texture,
+            cube_view,
+            faces,
+            size,
+            face_target,
+            face_bind_group,
+            face_pipeline,
+            module,
+            equirect_bgl,
+            sampler,
+            equirect_pipelines: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Cube texture the environment is rendered into.
+    pub fn texture(&self) -> &TextureHandle {
+        &self.texture
+    }
+
+    /// Width of a face, in pixels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Renders the scene around `position` into the six faces of the cube.
+    ///
+    /// This changes the camera of the renderer, so set the one of the main
+    /// view again afterwards.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        renderer: &Arc<Renderer>,
+        base_rendergraph: &BaseRenderGraph,
+        pbr: &PbrRoutine,
+        skybox: Option<&SkyboxRoutine>,
+        position: Vec3,
+        samples: SampleCount,
+        ambient: Vec4,
+        clear_color: Vec4,
+    ) {
+        profiling::scope!("Environment Capture");
+
+        for (face, face_view) in self.faces.iter().enumerate() {
+            renderer.set_camera_data(cube_face_camera(face, position, renderer.handedness, self.near));
+
+            let (cmd_bufs, ready) = renderer.ready();
+            let mut graph = RenderGraph::new();
+            base_rendergraph.add_to_graph(
+                &mut graph,
+                &ready,
+                crate::base::BaseRenderGraphRoutines {
+                    pbr,
+                    skybox,
+                    tonemapping: &self.tonemapping,
+                },
+                &crate::base::BaseRenderGraphSettings::new(UVec2::splat(self.size))
+                    .with_samples(samples)
+                    .with_ambient(ambient)
+                    .with_clear_color(clear_color),
+            );
+            graph.execute(
+                renderer,
+                OutputFrame::View(Arc::clone(&self.face_target)),
+                cmd_bufs,
+                &ready,
+            );
+
+            let mut encoder = renderer.device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("environment face copy"),
+            });
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("environment face copy"),
+                color_attachments: &[RenderPassColorAttachment {
+                    view: face_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.face_pipeline);
+            rpass.set_bind_group(0, &self.face_bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+            drop(rpass);
+            renderer.queue.submit(Some(encoder.finish()));
+        }
+    }
+
+    /// Projects the last capture into an equirectangular panorama filling
+    /// `target`, which should be twice as wide as it is high. The center of
+    /// the panorama looks along `forward`.
+    ///
+    /// `format` is the format of `target`. Formats which aren't srgb receive
+    /// linear color.
+    pub fn equirect(&self, renderer: &Renderer, target: &TextureView, format: TextureFormat, forward: Vec3, up: Vec3) {
+        profiling::scope!("Environment Equirect");
+
+        let device = &renderer.device;
+        let basis = equirect_basis(renderer.handedness, forward, up);
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("environment equirect"),
+            contents: bytemuck::bytes_of(&basis),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = BindGroupBuilder::new()
+            .append_texture_view(&self.cube_view)
+            .append_sampler(&self.sampler)
+            .append_buffer(&params)
+            .build(device, Some("environment equirect"), &self.equirect_bgl);
+
+        let mut pipelines = self.equirect_pipelines.lock();
+        let idx = match pipelines
+            .iter()
+            .position(|&(pipeline_format, _)| pipeline_format == format)
+        {
+            Some(idx) => idx,
+            None => {
+                let pipeline = create_pipeline(device, &self.module, &[&self.equirect_bgl], "fs_equirect", format);
+                pipelines.push((format, pipeline));
+                pipelines.len() - 1
+            }
+        };
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("environment equirect"),
+        });
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("environment equirect"),
+            color_attachments: &[RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&pipelines[idx].1);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+        drop(rpass);
+        renderer.queue.submit(Some(encoder.finish()));
+    }
+}
+
+/// Maps directions around the center of a panorama, with x to the right, y
+/// up and z forward, to world space.
+pub(crate) fn equirect_basis(handedness: Handedness, forward: Vec3, up: Vec3) -> Mat4 {
+    let forward = forward.normalize();
+    let right = match handedness {
+        Handedness::Left => up.cross(forward),
+        Handedness::Right => forward.cross(up),
+    }
+    .normalize();
+    let up = match handedness {
+        Handedness::Left => forward.cross(right),
+        Handedness::Right => right.cross(forward),
+    };
+    Mat4::from_cols(right.extend(0.0), up.extend(0.0), forward.extend(0.0), Vec4::W)
+}
+
+pub(crate) fn create_pipeline(
+    device: &Device,
+    module: &ShaderModule,
+    bind_group_layouts: &[&BindGroupLayout],
+    fragment_entry_point: &str,
+    format: TextureFormat,
+) -> RenderPipeline {
+    let pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("environment"),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("environment"),
+        layout: Some(&pll),
+        vertex: VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module,
+            entry_point: fragment_entry_point,
+            targets: &[ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::all(),
+            }],
+        }),
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::cube_face_camera;
+    use glam::Vec3;
+    use rend3::types::Handedness;
+
+    /// Texture coordinates a direction samples in a cube, and its face.
+    fn cube_coords(dir: Vec3) -> (usize, f32, f32) {
+        let abs = dir.abs();
+        let (face, major, sc, tc) = if abs.x >= abs.y && abs.x >= abs.z {
+            match dir.x > 0.0 {
+                true => (0, abs.x, -dir.z, -dir.y),
+                false => (1, abs.x, dir.z, -dir.y),
+            }
+        } else if abs.y >= abs.z {
+            match dir.y > 0.0 {
+                true => (2, abs.y, dir.x, dir.z),
+                false => (3, abs.y, dir.x, -dir.z),
+            }
+        } else {
+            match dir.z > 0.0 {
+                true => (4, abs.z, dir.x, -dir.y),
+                false => (5, abs.z, -dir.x, -dir.y),
+            }
+        };
+        (face, (sc / major + 1.0) * 0.5, (tc / major + 1.0) * 0.5)
+    }
+
+    #[test]
+    fn faces_match_cube_sampling() {
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let dirs = [
+            Vec3::new(1.0, 0.3, -0.2),
+            Vec3::new(-1.0, -0.4, 0.1),
+            Vec3::new(0.2, 1.0, 0.5),
+            Vec3::new(-0.3, -1.0, 0.6),
+            Vec3::new(0.5, -0.1, 1.0),
+            Vec3::new(-0.7, 0.2, -1.0),
+        ];
+        for handedness in [Handedness::Left, Handedness::Right] {
+            for dir in dirs {
+                let (face, s, t) = cube_coords(dir);
+                let camera = cube_face_camera(face, position, handedness, 0.1);
+                let view = camera.view.transform_point3(position + dir);
+                let mut x = view.x / view.z.abs();
+                let y = view.y / view.z.abs();
+                if handedness == Handedness::Right {
+                    // Undone when copying into the cube.
+                    x = -x;
+                }
+                assert!(((x + 1.0) * 0.5 - s).abs() < 1e-5, "{:?} {:?}", handedness, dir);
+                assert!(((1.0 - y) * 0.5 - t).abs() < 1e-5, "{:?} {:?}", handedness, dir);
+            }
+        }
+    }
+}
@@ -10,8 +10,9 @@ use rend3::{
         DataHandle, DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets,
         RenderTargetHandle,
     },
-    types::{Handedness, Material, SampleCount},
-    ProfileData, Renderer, RendererDataCore, RendererProfile,
+    managers::SkinningMode,
+    types::{DepthMode, Handedness, Material, SampleCount},
+    ProfileData, Renderer, RendererDataCore,
 };
 use wgpu::{
     BindGroup, BindGroupLayout, BlendState, Color, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
@@ -22,8 +23,8 @@ use wgpu::{
 
 use crate::{
     common::{
-        profile_safe_shader, PerMaterialArchetypeInterface, WholeFrameInterfaces, CPU_VERTEX_BUFFERS,
-        GPU_VERTEX_BUFFERS,
+        object_bind_group_layouts, profile_safe_shader, vertex_buffer_layouts, PerMaterialArchetypeInterface,
+        RenderStateOverride, UniformInterface, WholeFrameInterfaces,
     },
     culling,
 };
@@ -32,6 +33,7 @@ use crate::{
 pub struct ForwardRoutine<M: Material> {
     pub pipeline_s1: RenderPipeline,
     pub pipeline_s4: RenderPipeline,
+    pub depth_mode: DepthMode,
     pub _phantom: PhantomData<M>,
 }
 impl<M: Material> ForwardRoutine<M> {
@@ -49,6 +51,8 @@ impl<M: Material> ForwardRoutine<M> {
     ///
     /// If use_prepass is true, depth tests/writes are set such that it is
     /// assumed a full depth-prepass has happened before.
+    ///
+    /// `render_state` replaces parts of the resulting pipeline state.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         renderer: &Renderer,
@@ -63,6 +67,7 @@ impl<M: Material> ForwardRoutine<M> {
         blend: Option<BlendState>,
         use_prepass: bool,
         primitive_topology: wgpu::PrimitiveTopology,
+        render_state: RenderStateOverride,
         label: &str,
     ) -> Self {
         profiling::scope!("PrimaryPasses::new");
@@ -77,13 +82,18 @@ impl<M: Material> ForwardRoutine<M> {
                 vert_entry_point = inner_name;
             }
             None => {
+                let (cpu_source, gpu_source) = match renderer.skinning_mode {
+                    SkinningMode::PreSkinned => ("opaque.vert.cpu.wgsl", "opaque.vert.gpu.spv"),
+                    SkinningMode::VertexShader => ("opaque-skinned.vert.cpu.wgsl", "opaque-skinned.vert.gpu.spv"),
+                };
                 _forward_pass_vert_owned = Some(unsafe {
                     profile_safe_shader(
                         &renderer.device,
                         renderer.profile,
                         "forward pass vert",
-                        "opaque.vert.cpu.wgsl",
-                        "opaque.vert.gpu.spv",
+                        cpu_source,
+                        gpu_source,
+                        "opaque.vert.downlevel.wgsl",
                     )
                 });
                 vert_entry_point = "main";
@@ -108,6 +118,7 @@ impl<M: Material> ForwardRoutine<M> {
                         "forward pass frag",
                         "opaque.frag.cpu.wgsl",
                         "opaque.frag.gpu.spv",
+                        "opaque.frag.downlevel.wgsl",
                     )
                 });
                 frag_entry_point = "main";
@@ -116,13 +127,13 @@ impl<M: Material> ForwardRoutine<M> {
         };
 
         let mut bgls: ArrayVec<&BindGroupLayout, 8> = ArrayVec::new();
-        bgls.push(&interfaces.forward_uniform_bgl);
-        bgls.push(&per_material.bgl);
-        if renderer.profile == RendererProfile::GpuDriven {
-            bgls.push(data_core.d2_texture_manager.gpu_bgl())
-        } else {
-            bgls.push(data_core.material_manager.get_bind_group_layout_cpu::<M>());
-        }
+        bgls.extend(object_bind_group_layouts(
+            renderer.profile,
+            data_core,
+            interfaces,
+            per_material,
+            UniformInterface::Forward,
+        ));
         bgls.extend(extra_bgls);
 
         let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
@@ -142,6 +153,7 @@ impl<M: Material> ForwardRoutine<M> {
                 blend,
                 use_prepass,
                 primitive_topology,
+                render_state,
                 label,
                 samples,
             )
@@ -150,6 +162,7 @@ impl<M: Material> ForwardRoutine<M> {
         Self {
             pipeline_s1: inner(SampleCount::One),
             pipeline_s4: inner(SampleCount::Four),
+            depth_mode: renderer.depth_mode,
             _phantom: PhantomData,
         }
     }
@@ -186,7 +199,7 @@ impl<M: Material> ForwardRoutine<M> {
             }],
             depth_stencil: Some(RenderPassDepthTarget {
                 target: DepthHandle::RenderTarget(hdr_depth_handle),
-                depth_clear: Some(0.0),
+                depth_clear: Some(self.depth_mode.far_depth()),
                 stencil_clear: None,
             }),
         });
@@ -199,7 +212,7 @@ impl<M: Material> ForwardRoutine<M> {
         let this_pt_handle = builder.passthrough_ref(self);
         let extra_bg_pt_handle = extra_bgs.map(|v| builder.passthrough_ref(v));
 
-        builder.build(move |pt, _renderer, encoder_or_pass, temps, ready, graph_data| {
+        builder.build(move |pt, renderer, encoder_or_pass, temps, ready, graph_data| {
             let this = pt.get(this_pt_handle);
             let extra_bgs = extra_bg_pt_handle.map(|h| pt.get(h));
             let rpass = encoder_or_pass.get_rpass(rpass_handle);
@@ -215,7 +228,11 @@ impl<M: Material> ForwardRoutine<M> {
 
             rpass.set_pipeline(pipeline);
             rpass.set_bind_group(0, forward_uniform_bg, &[]);
-            rpass.set_bind_group(1, &culled.per_material, &[]);
+            rpass.set_bind_group(
+                1,
+                &culled.per_material,
+                culling::PerMaterialArchetypeData::per_material_offsets(renderer.profile),
+            );
             if let Some(v) = extra_bgs {
                 for (idx, bg) in v.iter().enumerate() {
                     rpass.set_bind_group((idx + 3) as _, bg, &[])
@@ -223,12 +240,17 @@ impl<M: Material> ForwardRoutine<M> {
             }
 
             match culled.inner.calls {
-                ProfileData::Cpu(ref draws) => {
-                    culling::draw_cpu_powered::<M>(rpass, draws, graph_data.material_manager, 2)
-                }
+                ProfileData::Cpu(ref draws) => culling::draw_cpu_powered::<M>(
+                    rpass,
+                    draws,
+                    graph_data.material_manager,
+                    2,
+                    culled.per_object_binding(renderer.profile, 1),
+                    graph_data.debug_markers,
+                ),
                 ProfileData::Gpu(ref data) => {
                     rpass.set_bind_group(2, ready.d2_texture.bg.as_gpu(), &[]);
-                    culling::draw_gpu_powered(rpass, data);
+                    culling::draw_gpu_powered::<M>(rpass, data, graph_data.debug_markers);
                 }
             }
         });
@@ -246,42 +268,53 @@ fn build_forward_pipeline_inner(
     blend: Option<BlendState>,
     use_prepass: bool,
     primitive_topology: PrimitiveTopology,
+    render_state: RenderStateOverride,
     label: &str,
     samples: SampleCount,
 ) -> RenderPipeline {
+    let mut primitive = PrimitiveState {
+        topology: primitive_topology,
+        strip_index_format: None,
+        front_face: match renderer.handedness {
+            Handedness::Left => FrontFace::Cw,
+            Handedness::Right => FrontFace::Ccw,
+        },
+        cull_mode: Some(Face::Back),
+        unclipped_depth: false,
+        polygon_mode: PolygonMode::Fill,
+        conservative: false,
+    };
+    render_state.apply_primitive(&mut primitive);
+
+    let mut depth_stencil = DepthStencilState {
+        format: TextureFormat::Depth32Float,
+        depth_write_enabled: blend.is_none() && use_prepass,
+        depth_compare: match use_prepass {
+            true => CompareFunction::Equal,
+            false => renderer.depth_mode.closer_or_equal(),
+        },
+        stencil: StencilState::default(),
+        bias: DepthBiasState::default(),
+    };
+    render_state.apply_depth_stencil(&mut depth_stencil);
+
+    let mut targets = [ColorTargetState {
+        format: TextureFormat::Rgba16Float,
+        blend,
+        write_mask: ColorWrites::all(),
+    }];
+    render_state.apply_color_targets(&mut targets);
+
     renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
         label: Some(label),
         layout: Some(pll),
         vertex: VertexState {
             module: forward_pass_vert,
             entry_point: vert_entry_point,
-            buffers: match renderer.profile {
-                RendererProfile::CpuDriven => &CPU_VERTEX_BUFFERS,
-                RendererProfile::GpuDriven => &GPU_VERTEX_BUFFERS,
-            },
-        },
-        primitive: PrimitiveState {
-            topology: primitive_topology,
-            strip_index_format: None,
-            front_face: match renderer.handedness {
-                Handedness::Left => FrontFace::Cw,
-                Handedness::Right => FrontFace::Ccw,
-            },
-            cull_mode: Some(Face::Back),
-            unclipped_depth: false,
-            polygon_mode: PolygonMode::Fill,
-            conservative: false,
+            buffers: &vertex_buffer_layouts(renderer),
         },
-        depth_stencil: Some(DepthStencilState {
-            format: TextureFormat::Depth32Float,
-            depth_write_enabled: blend.is_none() && use_prepass,
-            depth_compare: match use_prepass {
-                true => CompareFunction::Equal,
-                false => CompareFunction::GreaterEqual,
-            },
-            stencil: StencilState::default(),
-            bias: DepthBiasState::default(),
-        }),
+        primitive,
+        depth_stencil: Some(depth_stencil),
         multisample: MultisampleState {
             count: samples as u32,
             ..Default::default()
@@ -289,11 +322,7 @@ fn build_forward_pipeline_inner(
         fragment: Some(FragmentState {
             module: forward_pass_frag,
             entry_point: frag_entry_point,
-            targets: &[ColorTargetState {
-                format: TextureFormat::Rgba16Float,
-                blend,
-                write_mask: ColorWrites::all(),
-            }],
+            targets: &targets,
         }),
         multiview: None,
     })
@@ -0,0 +1,80 @@
+//! Watches shaders on disk so pipelines can be rebuilt when they change.
+//!
+//! Point [`set_shader_directory`](crate::shaders::set_shader_directory) at a
+//! checkout of `rend3-routine/shaders` and recreate the routines whenever
+//! [`ShaderWatcher::poll`] reports a change. Building pipelines from broken
+//! shaders raises wgpu validation errors, so wrap the recreation in an error
+//! scope and keep the old routines if it fails. rend3-framework does all of
+//! this when `App::shader_hot_reload` returns some directory.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Polls the modification times of files to notice when shaders are edited.
+///
+/// There is no file system notification involved, polling once every few
+/// hundred milliseconds is plenty for iterating on shaders.
+pub struct ShaderWatcher {
+    paths: Vec<PathBuf>,
+    modified: HashMap<PathBuf, SystemTime>,
+}
+
+impl ShaderWatcher {
+    /// Watches the given files, and every file inside the given directories.
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let paths: Vec<_> = paths.into_iter().collect();
+        let modified = scan(&paths);
+        Self { paths, modified }
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Returns true if any watched file was modified, added or removed since
+    /// the watcher was created or last polled.
+    pub fn poll(&mut self) -> bool {
+        let modified = scan(&self.paths);
+        let changed = modified != self.modified;
+        if changed {
+            for (path, time) in &modified {
+                if self.modified.get(path) != Some(time) {
+                    log::info!("Shader {} changed", path.display());
+                }
+            }
+        }
+        self.modified = modified;
+        changed
+    }
+}
+
+fn scan(paths: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+    let mut modified = HashMap::new();
+    for path in paths {
+        scan_path(path, &mut modified);
+    }
+    modified
+}
+
+fn scan_path(path: &Path, modified: &mut HashMap<PathBuf, SystemTime>) {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        // Editors often replace files by deleting and renaming, a missing file is picked up on the next poll.
+        Err(_) => return,
+    };
+
+    if metadata.is_dir() {
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            scan_path(&entry.path(), modified);
+        }
+    } else if let Ok(time) = metadata.modified() {
+        modified.insert(path.to_path_buf(), time);
+    }
+}
@@ -0,0 +1,506 @@
+//! Overlay showing frame times, GPU timings, draw counts and memory usage on
+//! top of the output.
+//!
+//! The HUD only shows what it is given: every frame, pass it the time since
+//! the last frame, the statistics returned by
+//! [`RenderGraph::execute`](rend3::graph::RenderGraph::execute), the counts
+//! from [`BaseRenderGraph::draw_statistics`](crate::base::BaseRenderGraph::draw_statistics)
+//! and [`Renderer::memory_usage`] through
+//! [`ProfilingHudRoutine::record_frame`], then add it to the rendergraph after
+//! everything else that renders to the output.
+//!
+//! GPU timings are only available when the device supports timestamp queries.
+
+use std::{collections::VecDeque, mem, time::Duration};
+
+use glam::{UVec2, Vec2, Vec4};
+use rend3::{
+    graph::{RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    util::{
+        bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+        typedefs::RendererStatistics,
+    },
+    MemoryUsage, Renderer,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindingType, BlendState, BufferAddress, BufferUsages, Color, ColorTargetState, ColorWrites,
+    FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState,
+    PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor,
+    TextureViewDimension, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::{
+    common::{self, GLYPH_ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH, LINE_HEIGHT},
+    culling::DrawCounts,
+    shaders,
+};
+
+/// Number of frames shown in the frame time graph.
+const FRAME_HISTORY: usize = 120;
+/// Width of the HUD in characters.
+const COLUMNS: usize = 46;
+/// Most GPU timings listed, to keep the HUD on screen.
+const MAX_GPU_TIMINGS: usize = 24;
+/// Height of the frame time graph in font texels.
+const GRAPH_HEIGHT: f32 = 40.0;
+/// Frame time the graph is marked at and colored relative to: 60 fps.
+const TARGET_FRAME_TIME: f32 = 1.0 / 60.0;
+
+const BACKGROUND: Vec4 = glam::const_vec4!([0.0, 0.0, 0.0, 0.6]);
+const TEXT: Vec4 = glam::const_vec4!([1.0, 1.0, 1.0, 1.0]);
+const GOOD: Vec4 = glam::const_vec4!([0.3, 0.85, 0.3, 1.0]);
+const SLOW: Vec4 = glam::const_vec4!([0.95, 0.8, 0.2, 1.0]);
+const VERY_SLOW: Vec4 = glam::const_vec4!([0.95, 0.3, 0.3, 1.0]);
+const TARGET_LINE: Vec4 = glam::const_vec4!([1.0, 1.0, 1.0, 0.4]);
+const TIMING_BAR: Vec4 = glam::const_vec4!([0.3, 0.5, 0.95, 0.5]);
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct HudVertex {
+    position: Vec2,
+    texel: Vec2,
+    color: Vec4,
+}
+
+unsafe impl bytemuck::Pod for HudVertex {}
+unsafe impl bytemuck::Zeroable for HudVertex {}
+
+/// GPU time of a rendergraph node or one of the scopes inside it.
+#[derive(Debug, Clone)]
+struct GpuTiming {
+    label: String,
+    depth: usize,
+    seconds: f32,
+}
+
+fn flatten_timings(scopes: &[wgpu_profiler::GpuTimerScopeResult], depth: usize, output: &mut Vec<GpuTiming>) {
+    for scope in scopes {
+        output.push(GpuTiming {
+            label: scope.label.clone(),
+            depth,
+            seconds: (scope.time.end - scope.time.start) as f32,
+        });
+        flatten_timings(&scope.nested_scopes, depth + 1, output);
+    }
+}
+
+fn format_count(count: u64) -> String {
+    match count {
+        0..=9_999 => count.to_string(),
+        10_000..=9_999_999 => format!("{:.1}K", count as f32 / 1_000.0),
+        _ => format!("{:.1}M", count as f32 / 1_000_000.0),
+    }
+}
+
+fn format_megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Builds the quads of the HUD, in pixels from the top left corner of the
+/// output.
+struct QuadBuilder {
+    vertices: Vec<HudVertex>,
+    resolution: Vec2,
+    scale: f32,
+    srgb: bool,
+}
+
+impl QuadBuilder {
+    fn quad(&mut self, position: Vec2, size: Vec2, texel_start: Vec2, texel_end: Vec2, color: Vec4) {
+        let color = if self.srgb {
+            common::srgb_to_linear(color)
+        } else {
+            color
+        };
+        let to_clip = |pixel: Vec2| {
+            Vec2::new(
+                pixel.x / self.resolution.x * 2.0 - 1.0,
+                1.0 - pixel.y / self.resolution.y * 2.0,
+            )
+        };
+
+        let corner = |x: bool, y: bool| HudVertex {
+            position: to_clip(position + Vec2::new(x as u8 as f32, y as u8 as f32) * size),
+            texel: Vec2::new(
+                if x { texel_end.x } else { texel_start.x },
+                if y { texel_end.y } else { texel_start.y },
+            ),
+            color,
+        };
+
+        self.vertices.extend_from_slice(&[
+            corner(false, false),
+            corner(true, false),
+            corner(false, true),
+            corner(false, true),
+            corner(true, false),
+            corner(true, true),
+        ]);
+    }
+
+    /// Solid rectangle, with position and size in font texels.
+    fn rect(&mut self, position: Vec2, size: Vec2, color: Vec4) {
+        let texel = Vec2::splat((common::SOLID_CELL * GLYPH_ADVANCE) as f32 + 0.5);
+        self.quad(position * self.scale, size * self.scale, texel, texel, color);
+    }
+
+    /// Single line of text, with the position in font texels.
+    fn text(&mut self, position: Vec2, text: &str, color: Vec4) {
+        let glyph_size = Vec2::new(GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32);
+        for (index, c) in text.chars().enumerate() {
+            if c == ' ' {
+                continue;
+            }
+            let cell_start = Vec2::new((common::glyph_cell(c) * GLYPH_ADVANCE) as f32, 0.0);
+            let glyph_position = position + Vec2::new((index as u32 * GLYPH_ADVANCE) as f32, 0.0);
+            self.quad(
+                glyph_position * self.scale,
+                glyph_size * self.scale,
+                cell_start,
+                cell_start + glyph_size,
+                color,
+            );
+        }
+    }
+}
+
+/// Overlay showing performance statistics.
+///
+/// See module for documentation.
+pub struct ProfilingHudRoutine {
+    /// Whether [`ProfilingHudRoutine::add_to_graph`] draws the HUD. Frames are
+    /// recorded either way, so the graphs are filled when it is shown.
+    pub enabled: bool,
+    /// Size of a texel of the built-in font in pixels.
+    pub scale: f32,
+
+    pipeline: RenderPipeline,
+    atlas_bg: BindGroup,
+    srgb: bool,
+
+    frame_times: VecDeque<f32>,
+    gpu_timings: Vec<GpuTiming>,
+    draw_counts: DrawCounts,
+    memory: MemoryUsage,
+}
+
+impl ProfilingHudRoutine {
+    /// Creates the HUD, hidden, for outputs of the given format.
+    pub fn new(renderer: &Renderer, output_format: TextureFormat) -> Self {
+        profiling::scope!("ProfilingHudRoutine::new");
+
+        let (atlas_resolution, atlas_texels) = common::font_atlas();
+        let atlas = renderer.device.create_texture_with_data(
+            &renderer.queue,
+            &TextureDescriptor {
+                label: Some("profiling hud font"),
+                size: wgpu::Extent3d {
+                    width: atlas_resolution.x,
+                    height: atlas_resolution.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::R8Unorm,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            },
+            &atlas_texels,
+        );
+        let atlas_view = atlas.create_view(&TextureViewDescriptor::default());
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .build(&renderer.device, Some("profiling hud bgl"));
+        let atlas_bg = BindGroupBuilder::new().append_texture_view(&atlas_view).build(
+            &renderer.device,
+            Some("profiling hud bg"),
+            &bgl,
+        );
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("profiling hud"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "hud.wgsl",
+                include_str!("../shaders/src/hud.wgsl"),
+            )),
+        });
+
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("profiling hud"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("profiling hud"),
+            layout: Some(&pll),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: mem::size_of::<HudVertex>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4],
+                }],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Cw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[ColorTargetState {
+                    format: output_format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::all(),
+                }],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            enabled: false,
+            scale: 2.0,
+            pipeline,
+            atlas_bg,
+            srgb: output_format.describe().srgb,
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+            gpu_timings: Vec::new(),
+            draw_counts: DrawCounts::default(),
+            memory: MemoryUsage::default(),
+        }
+    }
+
+    /// Shows the HUD if it is hidden, and hides it otherwise.
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    /// Records the statistics of a frame.
+    ///
+    /// `gpu_statistics` are the statistics returned by the last
+    /// [`RenderGraph::execute`]. They are often unavailable for a few frames,
+    /// in which case the timings of the last frame they were available for
+    /// are kept.
+    pub fn record_frame(
+        &mut self,
+        frame_time: Duration,
+        gpu_statistics: Option<&RendererStatistics>,
+        draw_counts: DrawCounts,
+        memory: MemoryUsage,
+    ) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time.as_secs_f32());
+
+        if let Some(statistics) = gpu_statistics {
+            self.gpu_timings.clear();
+            flatten_timings(statistics, 0, &mut self.gpu_timings);
+        }
+
+        self.draw_counts = draw_counts;
+        self.memory = memory;
+    }
+
+    fn build_vertices(&self, resolution: UVec2) -> Vec<HudVertex> {
+        let mut builder = QuadBuilder {
+            vertices: Vec::new(),
+            resolution: resolution.as_vec2(),
+            scale: self.scale,
+            srgb: self.srgb,
+        };
+
+        let margin = 4.0;
+        let width = (COLUMNS as u32 * GLYPH_ADVANCE) as f32;
+        let line_height = LINE_HEIGHT as f32;
+        let gpu_lines = self.gpu_timings.len().min(MAX_GPU_TIMINGS);
+        let height = line_height * (5 + gpu_lines) as f32 + GRAPH_HEIGHT + margin * 3.0;
+
+        builder.rect(Vec2::ZERO, Vec2::new(width, height) + margin * 2.0, BACKGROUND);
+
+        let mut cursor = Vec2::splat(margin);
+        let line = |builder: &mut QuadBuilder, cursor: &mut Vec2, text: &str| {
+            builder.text(*cursor, text, TEXT);
+            cursor.y += line_height;
+        };
+
+        // Frame times
+        let last = self.frame_times.back().copied().unwrap_or_default();
+        let average = self.frame_times.iter().sum::<f32>() / self.frame_times.len().max(1) as f32;
+        let max = self.frame_times.iter().copied().fold(0.0, f32::max);
+        let fps = if last > 0.0 { 1.0 / last } else { 0.0 };
+        line(
+            &mut builder,
+            &mut cursor,
+            &format!("FRAME {:.2} MS  {:.0} FPS", last * 1000.0, fps),
+        );
+        line(
+            &mut builder,
+            &mut cursor,
+            &format!("AVG {:.2} MS  MAX {:.2} MS", average * 1000.0, max * 1000.0),
+        );
+
+        // Frame time graph, scaled to fit the slowest frame, and at least twice
+        // the target.
+        let graph_max = max.max(TARGET_FRAME_TIME * 2.0);
+        let bar_width = width / FRAME_HISTORY as f32;
+        let graph_bottom = cursor.y + GRAPH_HEIGHT;
+        for (index, &time) in self.frame_times.iter().enumerate() {
+            let bar_height = (time / graph_max * GRAPH_HEIGHT).max(1.0);
+            let color = if time <= TARGET_FRAME_TIME * 1.05 {
+                GOOD
+            } else if time <= TARGET_FRAME_TIME * 2.05 {
+                SLOW
+            } else {
+                VERY_SLOW
+            };
+            builder.rect(
+                Vec2::new(cursor.x + index as f32 * bar_width, graph_bottom - bar_height),
+                Vec2::new(bar_width, bar_height),
+                color,
+            );
+        }
+        let target_y = graph_bottom - TARGET_FRAME_TIME / graph_max * GRAPH_HEIGHT;
+        builder.rect(Vec2::new(cursor.x, target_y), Vec2::new(width, 0.5), TARGET_LINE);
+        cursor.y = graph_bottom + margin;
+
+        // Draws
+        let mut draws = format!(
+            "DRAWS {}  TRIANGLES {}",
+            format_count(self.draw_counts.draws),
+            format_count(self.draw_counts.triangles)
+        );
+        if self.draw_counts.gpu_culled_sets != 0 {
+            draws += &format!(" +{} GPU SETS", self.draw_counts.gpu_culled_sets);
+            if let Some(gpu_draws) = self.draw_counts.gpu_draws {
+                draws += &format!(" ({} DRAWN)", format_count(gpu_draws));
+            }
+        }
+        line(&mut builder, &mut cursor, &draws);
+
+        // Memory
+        line(
+            &mut builder,
+            &mut cursor,
+            &format!(
+                "OBJECTS {}  MESH {}",
+                format_count(self.memory.object_count as u64),
+                format_megabytes(self.memory.mesh_bytes)
+            ),
+        );
+        line(
+            &mut builder,
+            &mut cursor,
+            &format!(
+                "TEXTURES {}  TOTAL {}",
+                format_megabytes(
+                    self.memory.texture_2d_bytes + self.memory.texture_cube_bytes + self.memory.texture_3d_bytes
+                ),
+                format_megabytes(self.memory.total_bytes())
+            ),
+        );
+
+        // GPU timings
+        cursor.y += margin;
+        if self.gpu_timings.is_empty() {
+            line(&mut builder, &mut cursor, "GPU TIMINGS UNAVAILABLE");
+        } else {
+            let total: f32 = self
+                .gpu_timings
+                .iter()
+                .filter(|timing| timing.depth == 0)
+                .map(|timing| timing.seconds)
+                .sum();
+            line(&mut builder, &mut cursor, &format!("GPU {:.3} MS", total * 1000.0));
+
+            for timing in self.gpu_timings.iter().take(gpu_lines) {
+                let time = format!("{:.3}", timing.seconds * 1000.0);
+                let indent = timing.depth * 2;
+                let label_columns = COLUMNS.saturating_sub(indent + time.len() + 1);
+                let label: String = timing.label.chars().take(label_columns).collect();
+
+                let bar = if total > 0.0 { timing.seconds / total } else { 0.0 };
+                builder.rect(
+                    Vec2::new(cursor.x, cursor.y - 1.0),
+                    Vec2::new(width * bar.min(1.0), line_height),
+                    TIMING_BAR,
+                );
+                builder.text(
+                    cursor + Vec2::new((indent as u32 * GLYPH_ADVANCE) as f32, 0.0),
+                    &label,
+                    TEXT,
+                );
+                line(&mut builder, &mut cursor, &format!("{:>width$}", time, width = COLUMNS));
+            }
+        }
+
+        builder.vertices
+    }
+
+    /// Draws the HUD on top of `output`, if it is enabled.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        output: RenderTargetHandle,
+        resolution: UVec2,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let vertices = self.build_vertices(resolution);
+
+        let mut builder = graph.add_node("Profiling HUD");
+
+        let output_handle = builder.add_render_target_output(output);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: output_handle,
+                clear: Color::BLACK,
+                resolve: None,
+            }],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, _graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("profiling hud");
+
+            let vertex_buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("profiling hud vertices"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: BufferUsages::VERTEX,
+            }));
+
+            rpass.set_pipeline(&this.pipeline);
+            rpass.set_bind_group(0, &this.atlas_bg, &[]);
+            rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            rpass.draw(0..vertices.len() as u32, 0..1);
+        });
+    }
+}
@@ -0,0 +1,894 @@
+//! Impostors, which draw far away copies of a mesh as a quad showing the mesh
+//! baked from the closest of several directions, for dense vegetation.
+//!
+//! [`ImpostorRoutine::create_impostor`] renders a mesh from directions evenly
+//! spaced around the up axis into an atlas of albedo and mesh space normals.
+//! Baking happens the next time the routine is added to the graph, so the
+//! mesh and texture only need to have been added to the renderer by then.
+//!
+//! An [`ImpostorLod`] places many copies of the mesh. Every frame,
+//! [`ImpostorLod::update`] adds full detail objects for the copies closer than
+//! its switch distance and removes them for the ones further away, which the
+//! routine then draws as impostors into the HDR targets of the base
+//! rendergraph, see [`BaseRenderGraphIntermediateState::impostors`].
+//! Impostors turn around the up axis to face the camera, and are relit from
+//! their baked normals by the ambient light and [`UnshadowedLights`], without
+//! shadows. Copies may only be rotated around the up axis. Given a
+//! [`ImpostorLod::fade_distance`], copies dither from one to the other
+//! before the switch distance instead of popping.
+//!
+//! [`BaseRenderGraphIntermediateState::impostors`]: crate::base::BaseRenderGraphIntermediateState::impostors
+
+use std::{f32::consts::TAU, mem, sync::Arc};
+
+use glam::{Mat4, Vec3, Vec4};
+use parking_lot::Mutex;
+use rend3::{
+    graph::{
+        DataHandle, DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets,
+        RenderTargetHandle,
+    },
+    managers::VertexAttributes,
+    types::{
+        DepthMode, MaterialHandle, MeshHandle, Object, ObjectFade, ObjectHandle, ObjectMeshKind, SampleCount,
+        TextureHandle,
+    },
+    util::{
+        bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+        quantize::PositionDequantization,
+    },
+    Renderer,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupLayout, BindingType, Buffer, BufferAddress, BufferBindingType, BufferDescriptor,
+    BufferUsages, Color, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState, DepthStencilState, Extent3d,
+    FilterMode, FragmentState, FrontFace, IndexFormat, LoadOp, MultisampleState, Operations, PipelineLayoutDescriptor,
+    PolygonMode, PrimitiveState, PrimitiveTopology, RenderPassColorAttachment, RenderPassDepthStencilAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState, Texture, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
+    VertexAttribute, VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+};
+
+use crate::{
+    common::{UnshadowedLights, MAX_UNSHADOWED_LIGHTS},
+    shaders,
+};
+
+const ALBEDO_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+const NORMAL_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// What [`ImpostorRoutine::create_impostor`] bakes.
+#[derive(Debug, Clone)]
+pub struct ImpostorDescriptor {
+    pub mesh: MeshHandle,
+    /// 2D texture multiplied with `color`, sampled with the first UV set.
+    pub albedo: Option<TextureHandle>,
+    /// Linear color, with alpha.
+    pub color: Vec4,
+    /// Fragments with less alpha are left out, like cutout materials.
+    pub alpha_cutoff: f32,
+    /// Number of directions around the up axis the mesh is baked from.
+    pub directions: u32,
+    /// Width and height of each baked view in the atlas.
+    pub resolution: u32,
+}
+
+impl ImpostorDescriptor {
+    /// Bakes `mesh` from 8 directions at 256x256.
+    pub fn new(mesh: MeshHandle) -> Self {
+        Self {
+            mesh,
+            albedo: None,
+            color: Vec4::ONE,
+            alpha_cutoff: 0.5,
+            directions: 8,
+            resolution: 256,
+        }
+    }
+
+    pub fn with_albedo(mut self, albedo: TextureHandle) -> Self {
+        self.albedo = Some(albedo);
+        self
+    }
+
+    pub fn with_directions(mut self, directions: u32) -> Self {
+        self.directions = directions;
+        self
+    }
+
+    pub fn with_resolution(mut self, resolution: u32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// Columns and rows of the atlas, as square as the directions allow.
+    fn grid(&self) -> (u32, u32) {
+        let columns = (self.directions as f32).sqrt().ceil() as u32;
+        (columns, (self.directions + columns - 1) / columns)
+    }
+}
+
+/// Orthographic projection of a sphere seen from `angle` radians around the
+/// up axis, starting from +Z. The nearest depth is at 1.
+fn bake_view_proj(center: Vec3, radius: f32, angle: f32) -> Mat4 {
+    let direction = Vec3::new(angle.sin(), 0.0, angle.cos());
+    let right = Vec3::new(direction.z, 0.0, -direction.x);
+    let row = |axis: Vec3, scale: f32, offset: f32| (axis * scale).extend(offset - center.dot(axis) * scale);
+    Mat4::from_cols(
+        row(right, 1.0 / radius, 0.0),
+        row(Vec3::Y, 1.0 / radius, 0.0),
+        row(direction, 0.5 / radius, 0.5),
+        Vec4::W,
+    )
+    .transpose()
+}
+
+/// Albedo and normal atlas of a mesh, created with
+/// [`ImpostorRoutine::create_impostor`].
+pub struct Impostor {
+    albedo: Texture,
+    normal: Texture,
+    albedo_desc: TextureDescriptor<'static>,
+    normal_desc: TextureDescriptor<'static>,
+    params: Buffer,
+    bind_group: BindGroup,
+}
+
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+struct ImpostorParams {
+    sphere: Vec4,
+    directions: u32,
+    columns: u32,
+    rows: u32,
+    _padding: u32,
+}
+
+unsafe impl bytemuck::Pod for ImpostorParams {}
+unsafe impl bytemuck::Zeroable for ImpostorParams {}
+
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+struct BakeUniforms {
+    view_proj: Mat4,
+    color: Vec4,
+    position_dequantization: PositionDequantization,
+    alpha_cutoff: f32,
+    vertex_quantization: u32,
+    _padding: [f32; 2],
+}
+
+unsafe impl bytemuck::Pod for BakeUniforms {}
+unsafe impl bytemuck::Zeroable for BakeUniforms {}
+
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+struct DrawUniforms {
+    view_proj: Mat4,
+    camera: Vec4,
+    ambient: Vec4,
+    light_directions: [Vec4; MAX_UNSHADOWED_LIGHTS],
+    light_colors: [Vec4; MAX_UNSHADOWED_LIGHTS],
+    light_count: u32,
+    _padding: [u32; 3],
+}
+
+unsafe impl bytemuck::Pod for DrawUniforms {}
+unsafe impl bytemuck::Zeroable for DrawUniforms {}
+
+struct PendingBake {
+    impostor: Arc<Impostor>,
+    desc: ImpostorDescriptor,
+}
+
+/// Instance data of an impostor draw.
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+struct ImpostorInstance {
+    transform: Mat4,
+    /// Opacity of the dithered impostor, see [`ImpostorLod::fade_distance`].
+    fade: f32,
+    _padding: [u32; 3],
+}
+
+unsafe impl bytemuck::Pod for ImpostorInstance {}
+unsafe impl bytemuck::Zeroable for ImpostorInstance {}
+
+/// Whether a copy at `distance` from the camera is drawn as a full object.
+/// Copies switch a little past the switch distance either way, so the ones
+/// right at it don't keep switching as the camera moves. Copies which fade
+/// are invisible by the switch distance, so they switch right at it.
+fn wants_object(has_object: bool, distance: f32, switch_distance: f32, fade_distance: f32) -> bool {
+    if fade_distance > 0.0 {
+        return distance < switch_distance;
+    }
+    let hysteresis = switch_distance * 0.05;
+    match has_object {
+        true => distance < switch_distance + hysteresis,
+        false => distance < switch_distance - hysteresis,
+    }
+}
+
+/// How far a copy with a full object at `distance` from the camera is
+/// through the cross-fade to its impostor, from 0.0 to 1.0.
+fn fade_amount(distance: f32, switch_distance: f32, fade_distance: f32) -> f32 {
+    if fade_distance <= 0.0 {
+        return 0.0;
+    }
+    ((distance - switch_distance + fade_distance) / fade_distance).clamp(0.0, 1.0)
+}
+
+/// Copies of a mesh, drawn as full objects up close and as an [`Impostor`]
+/// further away.
+pub struct ImpostorLod {
+    pub impostor: Arc<Impostor>,
+    pub mesh: MeshHandle,
+    pub material: MaterialHandle,
+    /// Distance from the camera past which copies are drawn as impostors.
+    pub switch_distance: f32,
+    /// Width of the band before the switch distance over which copies
+    /// cross-fade from their full object to their impostor with
+    /// [`ObjectFade`]. Zero, the default, switches at once.
+    pub fade_distance: f32,
+    transforms: Vec<Mat4>,
+    objects: Vec<Option<ObjectHandle>>,
+    /// Cross-fade amount of each copy as of the last update, 1.0 for
+    /// impostors.
+    amounts: Vec<f32>,
+}
+
+impl ImpostorLod {
+    /// Copies of `mesh` drawn with `material` at `transforms`. All copies are
+    /// impostors until the first [`update`](Self::update).
+    pub fn new(
+        impostor: Arc<Impostor>,
+        mesh: MeshHandle,
+        material: MaterialHandle,
+        switch_distance: f32,
+        transforms: Vec<Mat4>,
+    ) -> Self {
+        let objects = vec![None; transforms.len()];
+        let amounts = vec![1.0; transforms.len()];
+        Self {
+            impostor,
+            mesh,
+            material,
+            switch_distance,
+            fade_distance: 0.0,
+            transforms,
+            objects,
+            amounts,
+        }
+    }
+
+    pub fn transforms(&self) -> &[Mat4] {
+        &self.transforms
+    }
+
+    /// Adds and removes the full detail objects of the copies which crossed
+    /// the switch distance since the last update, and fades the ones within
+    /// the fade distance of it.
+    pub fn update(&mut self, renderer: &Renderer, camera_position: Vec3) {
+        profiling::scope!("ImpostorLod::update");
+
+        for ((transform, object), last_amount) in self.transforms.iter().zip(&mut self.objects).zip(&mut self.amounts) {
+            let distance = camera_position.distance(transform.w_axis.truncate());
+            let wants = wants_object(object.is_some(), distance, self.switch_distance, self.fade_distance);
+            match (wants, &object) {
+                (true, None) => {
+                    *object = Some(renderer.add_object(Object {
+                        mesh_kind: ObjectMeshKind::Static(self.mesh.clone()),
+                        material: self.material.clone(),
+                        transform: *transform,
+                    }));
+                    // Fades of new objects are set below.
+                    *last_amount = -1.0;
+                }
+                (false, Some(_)) => *object = None,
+                _ => {}
+            }
+
+            let amount = match object {
+                Some(_) => fade_amount(distance, self.switch_distance, self.fade_distance),
+                None => 1.0,
+            };
+            if let Some(object) = object {
+                if amount != *last_amount {
+                    renderer.set_object_fade(object, ObjectFade::cross_fade(amount).0);
+                }
+            }
+            *last_amount = amount;
+        }
+    }
+
+    /// Copies drawn as impostors, fully or fading in.
+    fn impostor_instances(&self) -> Vec<ImpostorInstance> {
+        self.transforms
+            .iter()
+            .zip(&self.amounts)
+            .filter(|(_, &amount)| amount > 0.0)
+            .map(|(&transform, &amount)| ImpostorInstance {
+                transform,
+                fade: ObjectFade::cross_fade(amount).1.to_shader(),
+                _padding: [0; 3],
+            })
+            .collect()
+    }
+}
+
+/// Bakes and draws [`Impostor`]s.
+///
+/// See module for documentation.
+pub struct ImpostorRoutine {
+    bake_pipeline: RenderPipeline,
+    bake_bgl: BindGroupLayout,
+    draw_pipeline_s1: RenderPipeline,
+    draw_pipeline_s4: RenderPipeline,
+    draw_bgl: BindGroupLayout,
+    impostor_bgl: BindGroupLayout,
+    sampler: Sampler,
+    white: TextureView,
+    depth_mode: DepthMode,
+    pending: Mutex<Vec<PendingBake>>,
+}
+
+impl ImpostorRoutine {
+    pub fn new(renderer: &Renderer) -> Self {
+        profiling::scope!("ImpostorRoutine::new");
+
+        let sampler = renderer.device.create_sampler(&SamplerDescriptor {
+            label: Some("impostor"),
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+
+        // Sampled when baking meshes without an albedo texture.
+        let white = renderer
+            .device
+            .create_texture_with_data(
+                &renderer.queue,
+                &TextureDescriptor {
+                    label: Some("impostor white"),
+                    size: Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8Unorm,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                },
+                &[255; 4],
+            )
+            .create_view(&TextureViewDescriptor::default());
+
+        let uniform = BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let texture = BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable: true },
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        };
+        let filtering = BindingType::Sampler(SamplerBindingType::Filtering);
+
+        let bake_bgl = BindGroupLayoutBuilder::new()
+            .append(ShaderStages::VERTEX_FRAGMENT, uniform, None)
+            .append(ShaderStages::FRAGMENT, texture, None)
+            .append(ShaderStages::FRAGMENT, filtering, None)
+            .build(&renderer.device, Some("impostor bake bgl"));
+        let draw_bgl = BindGroupLayoutBuilder::new()
+            .append(ShaderStages::VERTEX_FRAGMENT, uniform, None)
+            .build(&renderer.device, Some("impostor draw bgl"));
+        let impostor_bgl = BindGroupLayoutBuilder::new()
+            .append(ShaderStages::VERTEX, uniform, None)
+            .append(ShaderStages::FRAGMENT, texture, None)
+            .append(ShaderStages::FRAGMENT, texture, None)
+            .append(ShaderStages::FRAGMENT, filtering, None)
+            .build(&renderer.device, Some("impostor bgl"));
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("impostor"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "impostor.wgsl",
+                include_str!("../shaders/src/impostor.wgsl"),
+            )),
+        });
+
+        let primitive = PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            // Foliage is usually single sided geometry seen from both sides.
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        };
+
+        let bake_pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("impostor bake"),
+            bind_group_layouts: &[&bake_bgl],
+            push_constant_ranges: &[],
+        });
+        // Attributes the renderer doesn't store are a value per instance.
+        let vertex_buffer = |stride: usize, needs: VertexAttributes, location: u32, format: VertexFormat| {
+            let step_mode = match renderer.vertex_attributes.contains(needs) {
+                true => VertexStepMode::Vertex,
+                false => VertexStepMode::Instance,
+            };
+            (
+                stride as BufferAddress,
+                step_mode,
+                [VertexAttribute {
+                    format,
+                    offset: 0,
+                    shader_location: location,
+                }],
+            )
+        };
+        let quantization = renderer.vertex_quantization;
+        let mesh_buffers = [
+            vertex_buffer(
+                quantization.position_size(),
+                VertexAttributes::empty(),
+                0,
+                quantization.position_format(),
+            ),
+            vertex_buffer(
+                quantization.normal_size(),
+                VertexAttributes::empty(),
+                1,
+                quantization.normal_format(),
+            ),
+            vertex_buffer(
+                quantization.uv_size(),
+                VertexAttributes::UV0,
+                2,
+                quantization.uv_format(),
+            ),
+        ];
+        let mesh_buffers: Vec<VertexBufferLayout> = mesh_buffers
+            .iter()
+            .map(|(stride, step_mode, attributes)| VertexBufferLayout {
+                array_stride: *stride,
+                step_mode: *step_mode,
+                attributes,
+            })
+            .collect();
+        let bake_pipeline = renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("impostor bake"),
+            layout: Some(&bake_pll),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "bake_vs",
+                buffers: &mesh_buffers,
+            },
+            primitive,
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Greater,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "bake_fs",
+                targets: &[ALBEDO_FORMAT.into(), NORMAL_FORMAT.into()],
+            }),
+            multiview: None,
+        });
+
+        let draw_pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("impostor draw"),
+            bind_group_layouts: &[&draw_bgl, &impostor_bgl],
+            push_constant_ranges: &[],
+        });
+        let instance_attributes = wgpu::vertex_attr_array![
+            0 => Float32x4,
+            1 => Float32x4,
+            2 => Float32x4,
+            3 => Float32x4,
+            4 => Float32,
+        ];
+        let draw_inner = |samples: SampleCount| {
+            renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("impostor draw"),
+                layout: Some(&draw_pll),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "draw_vs",
+                    buffers: &[VertexBufferLayout {
+                        array_stride: mem::size_of::<ImpostorInstance>() as BufferAddress,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &instance_attributes,
+                    }],
+                },
+                primitive,
+                depth_stencil: Some(DepthStencilState {
+                    format: TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: renderer.depth_mode.closer_or_equal(),
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState {
+                    count: samples as u32,
+                    ..Default::default()
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "draw_fs",
+                    targets: &[ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::all(),
+                    }],
+                }),
+                multiview: None,
+            })
+        };
+
+        Self {
+            bake_pipeline,
+            bake_bgl,
+            draw_pipeline_s1: draw_inner(SampleCount::One),
+            draw_pipeline_s4: draw_inner(SampleCount::Four),
+            draw_bgl,
+            impostor_bgl,
+            sampler,
+            white,
+            depth_mode: renderer.depth_mode,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Creates the atlas of an impostor of `desc.mesh`, which is baked the
+    /// next time the routine is added to the graph.
+    pub fn create_impostor(&self, renderer: &Renderer, desc: ImpostorDescriptor) -> Arc<Impostor> {
+        assert!(desc.directions > 0, "impostors need at least one direction");
+
+        let (columns, rows) = desc.grid();
+        let size = Extent3d {
+            width: columns * desc.resolution,
+            height: rows * desc.resolution,
+            depth_or_array_layers: 1,
+        };
+        let atlas_desc = |label, format| TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: size.max_mips(),
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        };
+        let albedo_desc = atlas_desc("impostor albedo", ALBEDO_FORMAT);
+        let normal_desc = atlas_desc("impostor normal", NORMAL_FORMAT);
+        let albedo = renderer.device.create_texture(&albedo_desc);
+        let normal = renderer.device.create_texture(&normal_desc);
+
+        let params = renderer.device.create_buffer(&BufferDescriptor {
+            label: Some("impostor params"),
+            size: mem::size_of::<ImpostorParams>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = BindGroupBuilder::new()
+            .append_buffer(&params)
+            .append_texture_view(&albedo.create_view(&TextureViewDescriptor::default()))
+            .append_texture_view(&normal.create_view(&TextureViewDescriptor::default()))
+            .append_sampler(&self.sampler)
+            .build(&renderer.device, Some("impostor bg"), &self.impostor_bgl);
+
+        let impostor = Arc::new(Impostor {
+            albedo,
+            normal,
+            albedo_desc,
+            normal_desc,
+            params,
+            bind_group,
+        });
+        self.pending.lock().push(PendingBake {
+            impostor: Arc::clone(&impostor),
+            desc,
+        });
+        impostor
+    }
+
+    fn add_bakes_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        bakes: Vec<PendingBake>,
+    ) -> DataHandle<()> {
+        let baked = graph.add_data::<()>();
+
+        let mut builder = graph.add_node("Impostor Baking");
+        let baked_handle = builder.add_data_output(baked);
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, _temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let encoder = encoder_or_pass.get_encoder();
+
+            profiling::scope!("impostor baking");
+
+            let mesh_buffers = graph_data.mesh_manager.buffers();
+            for bake in &bakes {
+                let desc = &bake.desc;
+                let (columns, rows) = desc.grid();
+                let mesh = graph_data.mesh_manager.internal_data(desc.mesh.get_raw());
+                let center = mesh.bounding_sphere.center;
+                let radius = mesh.bounding_sphere.radius.max(f32::EPSILON);
+
+                renderer.queue.write_buffer(
+                    &bake.impostor.params,
+                    0,
+                    bytemuck::bytes_of(&ImpostorParams {
+                        sphere: center.extend(radius),
+                        directions: desc.directions,
+                        columns,
+                        rows,
+                        _padding: 0,
+                    }),
+                );
+
+                let albedo = match &desc.albedo {
+                    Some(texture) => graph_data.d2_texture_manager.get_view(texture.get_raw()),
+                    None => &this.white,
+                };
+                let bind_groups: Vec<BindGroup> = (0..desc.directions)
+                    .map(|direction| {
+                        let uniforms = BakeUniforms {
+                            view_proj: bake_view_proj(center, radius, TAU * direction as f32 / desc.directions as f32),
+                            color: desc.color,
+                            position_dequantization: mesh.position_dequantization,
+                            alpha_cutoff: desc.alpha_cutoff,
+                            vertex_quantization: renderer.vertex_quantization.bits() as u32,
+                            _padding: [0.0; 2],
+                        };
+                        let buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
+                            label: Some("impostor bake uniforms"),
+                            contents: bytemuck::bytes_of(&uniforms),
+                            usage: BufferUsages::UNIFORM,
+                        });
+                        BindGroupBuilder::new()
+                            .append_buffer(&buffer)
+                            .append_texture_view(albedo)
+                            .append_sampler(&this.sampler)
+                            .build(&renderer.device, Some("impostor bake bg"), &this.bake_bgl)
+                    })
+                    .collect();
+
+                let depth = renderer
+                    .device
+                    .create_texture(&TextureDescriptor {
+                        label: Some("impostor bake depth"),
+                        mip_level_count: 1,
+                        format: TextureFormat::Depth32Float,
+                        usage: TextureUsages::RENDER_ATTACHMENT,
+                        ..bake.impostor.albedo_desc.clone()
+                    })
+                    .create_view(&TextureViewDescriptor::default());
+                let mip_0 = |texture: &Texture| {
+                    texture.create_view(&TextureViewDescriptor {
+                        mip_level_count: std::num::NonZeroU32::new(1),
+                        ..TextureViewDescriptor::default()
+                    })
+                };
+                let albedo_view = mip_0(&bake.impostor.albedo);
+                let normal_view = mip_0(&bake.impostor.normal);
+                let clear = Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: true,
+                };
+
+                let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("impostor bake"),
+                    color_attachments: &[
+                        RenderPassColorAttachment {
+                            view: &albedo_view,
+                            resolve_target: None,
+                            ops: clear,
+                        },
+                        RenderPassColorAttachment {
+                            view: &normal_view,
+                            resolve_target: None,
+                            ops: clear,
+                        },
+                    ],
+                    depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                        view: &depth,
+                        depth_ops: Some(Operations {
+                            load: LoadOp::Clear(0.0),
+                            store: false,
+                        }),
+                        stencil_ops: None,
+                    }),
+                });
+                rpass.set_pipeline(&this.bake_pipeline);
+                rpass.set_vertex_buffer(0, mesh_buffers.vertex_position.slice(..));
+                rpass.set_vertex_buffer(1, mesh_buffers.vertex_normal.slice(..));
+                rpass.set_vertex_buffer(2, mesh_buffers.vertex_uv0.slice(..));
+                rpass.set_index_buffer(mesh_buffers.index.slice(..), IndexFormat::Uint32);
+
+                let cell = desc.resolution as f32;
+                let indices = mesh.index_range.start as u32..mesh.index_range.end as u32;
+                for (direction, bind_group) in bind_groups.iter().enumerate() {
+                    let direction = direction as u32;
+                    let x = (direction % columns) as f32 * cell;
+                    let y = (direction / columns) as f32 * cell;
+                    rpass.set_viewport(x, y, cell, cell, 0.0, 1.0);
+                    rpass.set_bind_group(0, bind_group, &[]);
+                    rpass.draw_indexed(indices.clone(), mesh.vertex_range.start as i32, 0..1);
+                }
+                drop(rpass);
+
+                renderer.mipmap_generator.generate_mipmaps(
+                    &renderer.device,
+                    encoder,
+                    &bake.impostor.albedo,
+                    &bake.impostor.albedo_desc,
+                );
+                renderer.mipmap_generator.generate_mipmaps(
+                    &renderer.device,
+                    encoder,
+                    &bake.impostor.normal,
+                    &bake.impostor.normal_desc,
+                );
+            }
+
+            graph_data.set_data(baked_handle, Some(()));
+        });
+
+        baked
+    }
+
+    /// Bakes the impostors created since the last call, then draws the copies
+    /// of `lods` further than their switch distance into the HDR targets.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        lods: &'node [ImpostorLod],
+        color: RenderTargetHandle,
+        resolve: Option<RenderTargetHandle>,
+        depth: RenderTargetHandle,
+        ambient: Vec4,
+        samples: SampleCount,
+    ) {
+        let bakes = mem::take(&mut *self.pending.lock());
+        if bakes.is_empty() && lods.is_empty() {
+            return;
+        }
+        let baked = (!bakes.is_empty()).then(|| self.add_bakes_to_graph(graph, bakes));
+
+        let mut builder = graph.add_node("Impostors");
+
+        let hdr_color_handle = builder.add_render_target_output(color);
+        let hdr_resolve = builder.add_optional_render_target_output(resolve);
+        let hdr_depth_handle = builder.add_render_target_output(depth);
+        let _baked_handle = baked.map(|baked| builder.add_data_input(baked));
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: hdr_color_handle,
+                clear: Color::BLACK,
+                resolve: hdr_resolve,
+            }],
+            depth_stencil: Some(RenderPassDepthTarget {
+                target: DepthHandle::RenderTarget(hdr_depth_handle),
+                depth_clear: Some(self.depth_mode.far_depth()),
+                stencil_clear: None,
+            }),
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+        let lod_handles: Vec<_> = lods.iter().map(|lod| builder.passthrough_ref(lod)).collect();
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("impostors");
+
+            let camera = graph_data.camera_manager;
+            let lights = UnshadowedLights::new(graph_data.directional_light_manager);
+            let uniforms = DrawUniforms {
+                view_proj: camera.view_proj(),
+                camera: camera.view().inverse().w_axis,
+                ambient,
+                light_directions: lights.directions,
+                light_colors: lights.colors,
+                light_count: lights.count,
+                _padding: [0; 3],
+            };
+            let buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("impostor uniforms"),
+                contents: bytemuck::bytes_of(&uniforms),
+                usage: BufferUsages::UNIFORM,
+            });
+            let bg = temps.add(BindGroupBuilder::new().append_buffer(&buffer).build(
+                &renderer.device,
+                Some("impostor draw bg"),
+                &this.draw_bgl,
+            ));
+
+            let pipeline = match samples {
+                SampleCount::One => &this.draw_pipeline_s1,
+                SampleCount::Four => &this.draw_pipeline_s4,
+            };
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, bg, &[]);
+
+            for handle in lod_handles {
+                let lod = pt.get(handle);
+                let instances = lod.impostor_instances();
+                if instances.is_empty() {
+                    continue;
+                }
+                let instance_buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("impostor instances"),
+                    contents: bytemuck::cast_slice(&instances),
+                    usage: BufferUsages::VERTEX,
+                }));
+
+                rpass.set_bind_group(1, &lod.impostor.bind_group, &[]);
+                rpass.set_vertex_buffer(0, instance_buffer.slice(..));
+                rpass.draw(0..6, 0..instances.len() as u32);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glam::{Vec3, Vec4};
+
+    use super::{bake_view_proj, fade_amount, wants_object};
+
+    #[test]
+    fn bake_views_fit_the_sphere() {
+        let center = Vec3::new(1.0, 2.0, 3.0);
+        // Seen from +X, +Z is to the left and +X nearest.
+        let view_proj = bake_view_proj(center, 2.0, FRAC_PI_2);
+        let project = |offset: Vec3| view_proj * (center + offset).extend(1.0);
+        assert!(project(Vec3::ZERO).abs_diff_eq(Vec4::new(0.0, 0.0, 0.5, 1.0), 1e-6));
+        assert!(project(Vec3::Z * 2.0).abs_diff_eq(Vec4::new(-1.0, 0.0, 0.5, 1.0), 1e-6));
+        assert!(project(Vec3::Y * 2.0).abs_diff_eq(Vec4::new(0.0, 1.0, 0.5, 1.0), 1e-6));
+        assert!(project(Vec3::X * 2.0).abs_diff_eq(Vec4::new(0.0, 0.0, 1.0, 1.0), 1e-6));
+    }
+
+    #[test]
+    fn lod_switch_has_hysteresis() {
+        assert!(wants_object(false, 90.0, 100.0, 0.0));
+        assert!(!wants_object(false, 99.0, 100.0, 0.0));
+        assert!(wants_object(true, 101.0, 100.0, 0.0));
+        assert!(!wants_object(true, 110.0, 100.0, 0.0));
+    }
+
+    #[test]
+    fn lod_fades_before_the_switch() {
+        assert_eq!(fade_amount(85.0, 100.0, 20.0), 0.25);
+        assert_eq!(fade_amount(50.0, 100.0, 20.0), 0.0);
+        assert_eq!(fade_amount(95.0, 100.0, 0.0), 0.0);
+        // The object is invisible by the time it is removed.
+        assert!(wants_object(false, 99.0, 100.0, 20.0));
+        assert!(!wants_object(true, 101.0, 100.0, 20.0));
+        assert_eq!(fade_amount(100.0, 100.0, 20.0), 1.0);
+    }
+}
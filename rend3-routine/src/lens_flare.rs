@@ -0,0 +1,545 @@
+//! Lens flares and sun glare, drawn over the tonemapped output.
+//!
+//! Each [`FlareLight`] on screen draws the [`FlareElement`]s of the
+//! [`LensFlareSettings`] along the line from the light through the center of
+//! the screen, and an optional [`AnamorphicStreak`] through the light. Add the
+//! routine after tonemapping, it blends additively into the output.
+//!
+//! Flares fade out as their light gets hidden. By default this is decided
+//! from the depth buffer around the light, which is right for the sun and
+//! for lights in the open. Lights inside fixtures like lamps can use an
+//! occlusion query on a box around the bulb instead, see
+//! [`FlareVisibility::Query`].
+
+use std::{mem, num::NonZeroU64};
+
+use glam::{Mat4, UVec2, Vec2, Vec3, Vec4, Vec4Swizzles};
+use parking_lot::Mutex;
+use rend3::{
+    graph::{RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    types::{DepthMode, SampleCount},
+    util::{
+        bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+        occlusion::{OcclusionQuery, OcclusionTarget},
+    },
+    Renderer, RendererProfile,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayout, BindingType, BlendComponent, BlendFactor, BlendOperation, BlendState, Buffer, BufferBindingType,
+    BufferUsages, Color, ColorTargetState, ColorWrites, FragmentState, FrontFace, MultisampleState,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType, TextureViewDimension,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::{
+    common::{preprocess_wgsl, ShaderDefines},
+    shaders,
+};
+
+/// Where a flare comes from.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FlareSource {
+    /// Infinitely far away, like the sun. The direction the light shines in,
+    /// as with [`DirectionalLight::direction`](rend3::types::DirectionalLight::direction).
+    Direction(Vec3),
+    /// A point in the world.
+    Position(Vec3),
+}
+
+/// How a flare decides whether its light is hidden.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FlareVisibility {
+    /// Tests the depth buffer around the light, in the same frame.
+    Depth,
+    /// Tests the target with an occlusion query every frame. Results arrive
+    /// a frame or two late and are faded in and out, and need an
+    /// [`OcclusionRoutine`](crate::occlusion::OcclusionRoutine) in the
+    /// rendergraph.
+    Query(OcclusionTarget),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FlareLight {
+    pub source: FlareSource,
+    /// Linear color of the flare, multiplied with the color of the elements.
+    pub color: Vec3,
+    pub intensity: f32,
+    pub visibility: FlareVisibility,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FlareShape {
+    /// Soft edged disc, the reflections between lens elements.
+    Ghost,
+    /// Ring, `thickness` being a fraction of its radius.
+    Halo { thickness: f32 },
+    /// Glow fading out from the center.
+    Glow,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FlareElement {
+    pub shape: FlareShape,
+    /// Position along the line from the light, at 0, through the center of
+    /// the screen, at 1. Past 1 elements are mirrored across the center.
+    pub position: f32,
+    /// Radius as a fraction of the height of the screen.
+    pub size: f32,
+    /// Linear color, alpha scaling its intensity.
+    pub color: Vec4,
+}
+
+/// Horizontal streak through the light, as anamorphic lenses make.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AnamorphicStreak {
+    /// Half length as a fraction of the width of the screen.
+    pub length: f32,
+    /// Half thickness as a fraction of the height of the screen.
+    pub thickness: f32,
+    pub color: Vec4,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LensFlareSettings {
+    pub elements: Vec<FlareElement>,
+    pub streak: Option<AnamorphicStreak>,
+}
+
+impl Default for LensFlareSettings {
+    /// A glow around the light, a halo, and a few faint ghosts.
+    fn default() -> Self {
+        let ghost = |position, size, color: [f32; 4]| FlareElement {
+            shape: FlareShape::Ghost,
+            position,
+            size,
+            color: Vec4::from(color),
+        };
+        Self {
+            elements: vec![
+                FlareElement {
+                    shape: FlareShape::Glow,
+                    position: 0.0,
+                    size: 0.25,
+                    color: Vec4::new(1.0, 0.95, 0.85, 0.6),
+                },
+                FlareElement {
+                    shape: FlareShape::Halo { thickness: 0.08 },
+                    position: 0.0,
+                    size: 0.45,
+                    color: Vec4::new(0.8, 0.85, 1.0, 0.08),
+                },
+                ghost(0.6, 0.04, [0.6, 0.8, 1.0, 0.15]),
+                ghost(1.2, 0.08, [1.0, 0.7, 0.4, 0.1]),
+                ghost(1.5, 0.03, [0.5, 1.0, 0.6, 0.15]),
+                ghost(1.9, 0.12, [0.7, 0.6, 1.0, 0.06]),
+            ],
+            streak: None,
+        }
+    }
+}
+
+/// One quad, with the layout of the vertex inputs of lens_flare.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct FlareInstance {
+    // Arrays, as the alignment of glam's vectors would pad the struct.
+    /// Center and half size in NDC.
+    quad: [f32; 4],
+    color: [f32; 4],
+    /// NDC position and depth of the light, w is 1 to test the depth buffer.
+    light: [f32; 4],
+    /// Shape and halo thickness.
+    shape: [f32; 2],
+}
+
+unsafe impl bytemuck::Pod for FlareInstance {}
+unsafe impl bytemuck::Zeroable for FlareInstance {}
+
+const SHAPE_GHOST: f32 = 0.0;
+const SHAPE_HALO: f32 = 1.0;
+const SHAPE_GLOW: f32 = 2.0;
+const SHAPE_STREAK: f32 = 3.0;
+
+/// Fraction of the screen from the edge over which flares fade out.
+const EDGE_FADE: f32 = 0.1;
+
+/// Change of the visibility of query tested lights per frame.
+const QUERY_FADE: f32 = 0.25;
+
+/// Quads of every element of every light on screen. `visibilities` scales
+/// each light, and lights tested against the depth buffer are also scaled on
+/// the GPU.
+fn build_instances(
+    settings: &LensFlareSettings,
+    lights: &[FlareLight],
+    visibilities: &[f32],
+    view_proj: Mat4,
+    resolution: UVec2,
+    depth_mode: DepthMode,
+) -> Vec<FlareInstance> {
+    let aspect = resolution.y as f32 / resolution.x.max(1) as f32;
+    let mut instances = Vec::new();
+    for (light, &visibility) in lights.iter().zip(visibilities) {
+        let (clip, depth) = match light.source {
+            FlareSource::Direction(direction) => (view_proj * (-direction).extend(0.0), depth_mode.far_depth()),
+            FlareSource::Position(position) => {
+                let clip = view_proj * position.extend(1.0);
+                (clip, clip.z / clip.w)
+            }
+        };
+        if clip.w <= f32::EPSILON || visibility <= 0.0 {
+            continue;
+        }
+        let ndc = clip.xy() / clip.w;
+        let edge = ((1.0 - ndc.abs().max_element()) / EDGE_FADE).clamp(0.0, 1.0);
+        if edge <= 0.0 {
+            continue;
+        }
+
+        let strength = light.color.extend(1.0) * light.intensity * visibility * edge;
+        let depth_test = matches!(light.visibility, FlareVisibility::Depth) as u8 as f32;
+        let light_data = ndc.extend(depth).extend(depth_test).to_array();
+
+        for element in &settings.elements {
+            let (shape, thickness) = match element.shape {
+                FlareShape::Ghost => (SHAPE_GHOST, 0.0),
+                FlareShape::Halo { thickness } => (SHAPE_HALO, thickness),
+                FlareShape::Glow => (SHAPE_GLOW, 0.0),
+            };
+            let center = ndc * (1.0 - element.position);
+            instances.push(FlareInstance {
+                quad: center.extend(element.size * aspect).extend(element.size).to_array(),
+                color: (element.color * strength).to_array(),
+                light: light_data,
+                shape: [shape, thickness],
+            });
+        }
+        if let Some(streak) = settings.streak {
+            instances.push(FlareInstance {
+                quad: ndc.extend(streak.length).extend(streak.thickness).to_array(),
+                color: (streak.color * strength).to_array(),
+                light: light_data,
+                shape: [SHAPE_STREAK, 0.0],
+            });
+        }
+    }
+    instances
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct FlareUniforms {
+    resolution: Vec2,
+    reversed: u32,
+    _padding: u32,
+}
+
+unsafe impl bytemuck::Pod for FlareUniforms {}
+unsafe impl bytemuck::Zeroable for FlareUniforms {}
+
+/// Occlusion query state of a light using [`FlareVisibility::Query`].
+#[derive(Default)]
+struct LightQuery {
+    in_flight: Option<OcclusionQuery>,
+    visible: bool,
+    visibility: f32,
+}
+
+impl LightQuery {
+    /// Takes the result of the query in flight if it arrived, starts the
+    /// next one, and fades towards the latest result.
+    fn update(&mut self, renderer: &Renderer, target: OcclusionTarget) -> f32 {
+        let resolved = self
+            .in_flight
+            .as_ref()
+            .map_or(Some(self.visible), OcclusionQuery::result);
+        if let Some(visible) = resolved {
+            self.visible = visible;
+            self.in_flight = Some(renderer.begin_occlusion_query(target));
+        }
+        let target_visibility = self.visible as u8 as f32;
+        self.visibility += (target_visibility - self.visibility).clamp(-QUERY_FADE, QUERY_FADE);
+        self.visibility
+    }
+}
+
+/// Routine drawing lens flares, see the [module](self) docs.
+pub struct LensFlareRoutine {
+    settings: LensFlareSettings,
+    lights: Vec<FlareLight>,
+    queries: Mutex<Vec<LightQuery>>,
+    bgl: BindGroupLayout,
+    uniforms: Buffer,
+    pipeline: RenderPipeline,
+    downlevel: bool,
+}
+
+impl LensFlareRoutine {
+    /// `samples` is the sample count of the depth buffer passed to
+    /// [`add_to_graph`](Self::add_to_graph).
+    pub fn new(renderer: &Renderer, output_format: TextureFormat, samples: SampleCount) -> Self {
+        profiling::scope!("LensFlareRoutine::new");
+
+        let downlevel = renderer.profile == RendererProfile::Downlevel;
+
+        let mut bglb = BindGroupLayoutBuilder::new();
+        bglb.append(
+            ShaderStages::VERTEX,
+            BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(mem::size_of::<FlareUniforms>() as u64),
+            },
+            None,
+        );
+        if !downlevel {
+            bglb.append(
+                ShaderStages::VERTEX,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: samples.needs_resolve(),
+                },
+                None,
+            );
+        }
+        let bgl = bglb.build(&renderer.device, Some("lens flare bgl"));
+
+        let uniforms = renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("lens flare uniforms"),
+            contents: bytemuck::bytes_of(&FlareUniforms {
+                resolution: Vec2::ONE,
+                reversed: renderer.depth_mode.is_reversed() as u32,
+                _padding: 0,
+            }),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let defines = ShaderDefines::new()
+            .with_if("DOWNLEVEL", downlevel)
+            .with_if("MULTISAMPLED", samples.needs_resolve());
+        let source = shaders::source_shader("lens_flare.wgsl", include_str!("../shaders/src/lens_flare.wgsl"));
+        let source = preprocess_wgsl(&source, &defines).expect("lens_flare.wgsl has broken #ifdef blocks");
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("lens flare"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
+
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("lens flare"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let pipeline = renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("lens flare"),
+            layout: Some(&pll),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: mem::size_of::<FlareInstance>() as u64,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x4,
+                        1 => Float32x4,
+                        2 => Float32x4,
+                        3 => Float32x2,
+                    ],
+                }],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[ColorTargetState {
+                    format: output_format,
+                    blend: Some(BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: ColorWrites::COLOR,
+                }],
+            }),
+            multiview: None,
+        });
+
+        Self {
+            settings: LensFlareSettings::default(),
+            lights: Vec::new(),
+            queries: Mutex::new(Vec::new()),
+            bgl,
+            uniforms,
+            pipeline,
+            downlevel,
+        }
+    }
+
+    pub fn settings(&self) -> &LensFlareSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: LensFlareSettings) {
+        self.settings = settings;
+    }
+
+    pub fn lights(&self) -> &[FlareLight] {
+        &self.lights
+    }
+
+    /// Sets the lights drawing flares. Lights keep the state of their
+    /// occlusion queries as long as they stay at the same index.
+    pub fn set_lights(&mut self, lights: Vec<FlareLight>) {
+        self.queries.get_mut().resize_with(lights.len(), LightQuery::default);
+        self.lights = lights;
+    }
+
+    /// Draws the flares into `output`, testing visibility against `depth`,
+    /// both of size `resolution`.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        depth: RenderTargetHandle,
+        output: RenderTargetHandle,
+        resolution: UVec2,
+    ) {
+        if self.lights.is_empty() {
+            return;
+        }
+
+        let mut builder = graph.add_node("Lens Flare");
+
+        let depth_handle = (!self.downlevel).then(|| builder.add_render_target_input(depth));
+        let output_handle = builder.add_render_target_output(output);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: output_handle,
+                clear: Color::BLACK,
+                resolve: None,
+            }],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("lens flare");
+
+            let visibilities: Vec<f32> = {
+                let mut queries = this.queries.lock();
+                this.lights
+                    .iter()
+                    .zip(queries.iter_mut())
+                    .map(|(light, query)| match light.visibility {
+                        FlareVisibility::Depth => 1.0,
+                        FlareVisibility::Query(target) => query.update(renderer, target),
+                    })
+                    .collect()
+            };
+
+            let instances = build_instances(
+                &this.settings,
+                &this.lights,
+                &visibilities,
+                graph_data.camera_manager.view_proj(),
+                resolution,
+                renderer.depth_mode,
+            );
+            if instances.is_empty() {
+                return;
+            }
+
+            renderer.queue.write_buffer(
+                &this.uniforms,
+                0,
+                bytemuck::bytes_of(&FlareUniforms {
+                    resolution: resolution.as_vec2(),
+                    reversed: renderer.depth_mode.is_reversed() as u32,
+                    _padding: 0,
+                }),
+            );
+
+            let mut bgb = BindGroupBuilder::new();
+            bgb.append_buffer(&this.uniforms);
+            if let Some(depth_handle) = depth_handle {
+                bgb.append_texture_view(graph_data.get_render_target(depth_handle));
+            }
+            let bg = temps.add(bgb.build(&renderer.device, Some("lens flare bg"), &this.bgl));
+
+            let instance_buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("lens flare instances"),
+                contents: bytemuck::cast_slice(&instances),
+                usage: BufferUsages::VERTEX,
+            }));
+
+            rpass.set_pipeline(&this.pipeline);
+            rpass.set_bind_group(0, bg, &[]);
+            rpass.set_vertex_buffer(0, instance_buffer.slice(..));
+            rpass.draw(0..4, 0..instances.len() as u32);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, UVec2, Vec3};
+    use rend3::types::DepthMode;
+
+    use super::{build_instances, FlareLight, FlareSource, FlareVisibility, LensFlareSettings};
+
+    #[test]
+    fn only_lights_in_view_flare() {
+        let settings = LensFlareSettings::default();
+        let view_proj = Mat4::perspective_infinite_reverse_lh(1.0, 1.0, 0.1);
+        let light = |source| FlareLight {
+            source,
+            color: Vec3::ONE,
+            intensity: 1.0,
+            visibility: FlareVisibility::Depth,
+        };
+        let build = |lights: &[FlareLight], visibilities: &[f32]| {
+            build_instances(
+                &settings,
+                lights,
+                visibilities,
+                view_proj,
+                UVec2::new(200, 100),
+                DepthMode::ReversedZInfinite,
+            )
+        };
+
+        // The sun ahead, shining back at the camera.
+        let sun = light(FlareSource::Direction(-Vec3::Z));
+        let instances = build(&[sun], &[1.0]);
+        assert_eq!(instances.len(), settings.elements.len());
+        // Sun depth is the far plane, and half the width covers the height.
+        assert_eq!(instances[0].light[2], 0.0);
+        assert_eq!(instances[0].quad[2], instances[0].quad[3] * 0.5);
+
+        let behind = light(FlareSource::Position(Vec3::new(0.0, 0.0, -5.0)));
+        let off_screen = light(FlareSource::Position(Vec3::new(50.0, 0.0, 5.0)));
+        assert!(build(&[behind, off_screen], &[1.0, 1.0]).is_empty());
+        assert!(build(&[sun], &[0.0]).is_empty());
+    }
+}
@@ -19,13 +19,33 @@
 pub mod base;
 pub mod clear;
 pub mod common;
+pub mod crowd;
+pub mod cubemap;
 pub mod culling;
+pub mod custom;
+pub mod debug;
 pub mod depth;
+pub mod environment;
 pub mod forward;
+pub mod hot_reload;
+pub mod hud;
+pub mod impostor;
+pub mod lens_flare;
+pub mod lighting_debug;
+pub mod line;
+pub mod occlusion;
+pub mod particles;
 pub mod pbr;
 pub mod pre_cull;
+pub mod presets;
+pub mod reflection;
 pub mod shaders;
 pub mod skinning;
 pub mod skybox;
+pub mod sprite;
+pub mod subsurface;
+pub mod supersample;
 pub mod tonemapping;
+pub mod transmission;
 pub mod uniforms;
+pub mod volume;
@@ -0,0 +1,418 @@
+//! Debug views of how the scene is lit, for checking lighting changes by eye.
+//!
+//! A [`LightingDebugRoutine`] renders per-pixel lighting data of the base
+//! rendergraph into render targets, which it hands back by name in
+//! [`LightingDebugOutputs`]:
+//!
+//! - [`LIGHT_COUNT`]: how many directional lights reach the pixel unshadowed.
+//! - [`SHADOW_INDEX`]: one plus the index of the first directional light
+//!   whose shadow map covers the pixel, zero if none does. Each light has a
+//!   single shadow map, so this stands in for a cascade index.
+//!
+//! Both are zero where nothing was drawn. The targets are only rendered when a
+//! node reads one of them, so adding the routine costs nothing while no view
+//! is shown. Nodes of your own can add their data to the outputs, e.g. the
+//! raw occlusion of an ambient occlusion pass, so that every debug target is
+//! found in one place.
+//!
+//! A [`DebugViewRoutine`] draws one of the targets onto the output as a
+//! heatmap, e.g. from a hook after [`BasePhase::Tonemap`]:
+//!
+//! ```ignore
+//! let hooks = BaseRenderGraphHooks::new().after(BasePhase::Tonemap, |graph, state| {
+//!     let outputs = state.lighting_debug(graph, &lighting_debug);
+//!     if let Some(light_count) = outputs.get(LIGHT_COUNT) {
+//!         let surface = graph.add_surface_texture();
+//!         debug_view.add_to_graph(graph, light_count, surface, 4.0, 0.5);
+//!     }
+//! });
+//! ```
+//!
+//! Not available on the downlevel profile, as GL can't load from depth
+//! textures.
+//!
+//! [`BasePhase::Tonemap`]: crate::base::BasePhase::Tonemap
+
+use std::{borrow::Cow, mem, num::NonZeroU64};
+
+use glam::{UVec2, Vec3};
+use rend3::{
+    graph::{DataHandle, RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetDescriptor, RenderTargetHandle},
+    types::SampleCount,
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer, RendererProfile,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupLayout, BindingType, BlendState, BufferBindingType, BufferUsages, Color, ColorTargetState,
+    ColorWrites, FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType,
+    TextureUsages, TextureViewDimension, VertexState,
+};
+
+use crate::{
+    common::{preprocess_wgsl, ShaderDefines, WholeFrameInterfaces},
+    custom, shaders,
+};
+
+/// Name of the number of unshadowed directional lights reaching each pixel.
+pub const LIGHT_COUNT: &str = "light_count";
+/// Name of one plus the index of the directional light whose shadow map
+/// covers each pixel.
+pub const SHADOW_INDEX: &str = "shadow_index";
+
+/// Format of the targets of a [`LightingDebugRoutine`].
+pub const LIGHTING_DEBUG_FORMAT: TextureFormat = TextureFormat::R16Float;
+
+/// Debug targets by name, see the [module](self) docs.
+#[derive(Debug, Default, Clone)]
+pub struct LightingDebugOutputs {
+    targets: Vec<(Cow<'static, str>, RenderTargetHandle)>,
+}
+
+impl LightingDebugOutputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a target, replacing the one of the same name.
+    pub fn insert(&mut self, name: impl Into<Cow<'static, str>>, target: RenderTargetHandle) {
+        let name = name.into();
+        match self.targets.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing)) => *existing = target,
+            None => self.targets.push((name, target)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<RenderTargetHandle> {
+        self.targets
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|&(_, target)| target)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.targets.iter().map(|(name, _)| &**name)
+    }
+}
+
+/// Renders the lighting debug targets, see the [module](self) docs.
+pub struct LightingDebugRoutine {
+    bgl: BindGroupLayout,
+    pipeline: Option<RenderPipeline>,
+}
+
+impl LightingDebugRoutine {
+    /// Reads the depth target of the base rendergraph, which has `samples`.
+    pub fn new(renderer: &Renderer, interfaces: &WholeFrameInterfaces, samples: SampleCount) -> Self {
+        profiling::scope!("LightingDebugRoutine::new");
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: samples.needs_resolve(),
+                },
+                None,
+            )
+            .build(&renderer.device, Some("lighting debug bgl"));
+
+        let pipeline = (renderer.profile != RendererProfile::Downlevel).then(|| {
+            let defines = ShaderDefines::new()
+                .with_if("MULTISAMPLED", samples.needs_resolve())
+                .with_if("REVERSED_Z", renderer.depth_mode.is_reversed());
+            let mut source = custom::frame_prelude();
+            source.push_str(&shaders::source_shader(
+                "lighting_debug.wgsl",
+                include_str!("../shaders/src/lighting_debug.wgsl"),
+            ));
+            let source = preprocess_wgsl(&source, &defines).expect("lighting_debug.wgsl has broken #ifdef blocks");
+            let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+                label: Some("lighting debug"),
+                source: ShaderSource::Wgsl(source.into()),
+            });
+
+            let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("lighting debug"),
+                bind_group_layouts: &[&interfaces.forward_uniform_bgl, &bgl],
+                push_constant_ranges: &[],
+            });
+
+            let target = ColorTargetState {
+                format: LIGHTING_DEBUG_FORMAT,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            };
+            renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("lighting debug"),
+                layout: Some(&pll),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[target.clone(), target],
+                }),
+                multiview: None,
+            })
+        });
+
+        Self { bgl, pipeline }
+    }
+
+    /// Renders the targets at `resolution` from `depth`, after the shadow
+    /// maps. Returns no outputs on the downlevel profile.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        forward_uniform_bg: DataHandle<BindGroup>,
+        depth: RenderTargetHandle,
+        resolution: UVec2,
+    ) -> LightingDebugOutputs {
+        let mut outputs = LightingDebugOutputs::new();
+        if self.pipeline.is_none() {
+            return outputs;
+        }
+
+        let mut add_target = |name: &'static str| {
+            let target = graph.add_render_target(RenderTargetDescriptor {
+                label: Some(name.into()),
+                resolution,
+                samples: SampleCount::One,
+                format: LIGHTING_DEBUG_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            });
+            outputs.insert(name, target);
+            target
+        };
+        let light_count = add_target(LIGHT_COUNT);
+        let shadow_index = add_target(SHADOW_INDEX);
+
+        let mut builder = graph.add_node("Lighting Debug");
+
+        let _ = builder.add_shadow_array_input();
+        let forward_uniform_handle = builder.add_data_input(forward_uniform_bg);
+        let depth_handle = builder.add_render_target_input(depth);
+        let light_count_handle = builder.add_render_target_output(light_count);
+        let shadow_index_handle = builder.add_render_target_output(shadow_index);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![
+                RenderPassTarget {
+                    color: light_count_handle,
+                    clear: Color::BLACK,
+                    resolve: None,
+                },
+                RenderPassTarget {
+                    color: shadow_index_handle,
+                    clear: Color::BLACK,
+                    resolve: None,
+                },
+            ],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+            let pipeline = match this.pipeline {
+                Some(ref pipeline) => pipeline,
+                None => return,
+            };
+            let forward_uniform_bg = graph_data.get_data(temps, forward_uniform_handle).unwrap();
+
+            let bg = temps.add(
+                BindGroupBuilder::new()
+                    .append_texture_view(graph_data.get_render_target(depth_handle))
+                    .build(&renderer.device, Some("lighting debug bg"), &this.bgl),
+            );
+
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, forward_uniform_bg, &[]);
+            rpass.set_bind_group(1, bg, &[]);
+            rpass.draw(0..3, 0..1);
+        });
+
+        outputs
+    }
+}
+
+/// The color a [`DebugViewRoutine`] draws `value`, relative to its range:
+/// black at zero, then blue, green, yellow and red from one on.
+pub fn heatmap(value: f32) -> Vec3 {
+    if value <= 0.0 {
+        return Vec3::ZERO;
+    }
+    let t = value.min(1.0) * 3.0;
+    if t < 1.0 {
+        Vec3::new(0.0, t, 1.0 - t)
+    } else if t < 2.0 {
+        Vec3::new(t - 1.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 3.0 - t, 0.0)
+    }
+}
+
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+struct DebugViewUniforms {
+    range: f32,
+    opacity: f32,
+    _padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Pod for DebugViewUniforms {}
+unsafe impl bytemuck::Zeroable for DebugViewUniforms {}
+
+/// Draws a debug target onto the output as a [`heatmap`] of its red
+/// channel, see the [module](self) docs.
+pub struct DebugViewRoutine {
+    bgl: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl DebugViewRoutine {
+    pub fn new(renderer: &Renderer, output_format: TextureFormat) -> Self {
+        profiling::scope!("DebugViewRoutine::new");
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(mem::size_of::<DebugViewUniforms>() as u64),
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .build(&renderer.device, Some("debug view bgl"));
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("debug view"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "debug_view.wgsl",
+                include_str!("../shaders/src/debug_view.wgsl"),
+            )),
+        });
+
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("debug view"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("debug view"),
+            layout: Some(&pll),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[ColorTargetState {
+                    format: output_format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            multiview: None,
+        });
+
+        Self { bgl, pipeline }
+    }
+
+    /// Draws `source` stretched over `output`, with values of `range` and
+    /// above red, blended over what is there by `opacity`.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        source: RenderTargetHandle,
+        output: RenderTargetHandle,
+        range: f32,
+        opacity: f32,
+    ) {
+        let mut builder = graph.add_node("Debug View");
+
+        let source_handle = builder.add_render_target_input(source);
+        let output_handle = builder.add_render_target_output(output);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: output_handle,
+                clear: Color::BLACK,
+                resolve: None,
+            }],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            let uniforms = DebugViewUniforms {
+                range: range.max(f32::EPSILON),
+                opacity,
+                _padding: [0; 2],
+            };
+            let buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("debug view uniforms"),
+                contents: bytemuck::bytes_of(&uniforms),
+                usage: BufferUsages::UNIFORM,
+            }));
+
+            let bg = temps.add(
+                BindGroupBuilder::new()
+                    .append_buffer(buffer)
+                    .append_texture_view(graph_data.get_render_target(source_handle))
+                    .build(&renderer.device, Some("debug view bg"), &this.bgl),
+            );
+
+            rpass.set_pipeline(&this.pipeline);
+            rpass.set_bind_group(0, bg, &[]);
+            rpass.draw(0..3, 0..1);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::heatmap;
+
+    #[test]
+    fn heatmap_runs_from_black_through_blue_to_red() {
+        assert_eq!(heatmap(0.0), Vec3::ZERO);
+        assert_eq!(heatmap(0.25), Vec3::new(0.0, 0.75, 0.25));
+        assert_eq!(heatmap(0.5), Vec3::new(0.5, 1.0, 0.0));
+        assert_eq!(heatmap(1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(heatmap(7.0), heatmap(1.0));
+    }
+}
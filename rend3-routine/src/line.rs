@@ -0,0 +1,505 @@
+//! Anti-aliased lines with a width in pixels, for gizmos, trajectories and
+//! wireframes where the one pixel wide lines of
+//! [`PrimitiveTopology::LineList`] aren't good enough.
+//!
+//! Collect lines and polylines in a [`LineBatch`] and draw them with a
+//! [`LineRoutine`]. Every segment is expanded into a quad on screen, wide
+//! enough for its caps, and shaded with its distance to the segment, so
+//! lines are smooth at any width and angle. Segments of a polyline are joined
+//! with round joins. Where translucent segments overlap at the joins they are
+//! blended twice.
+//!
+//! Lines can be hidden by the scene when drawn with the depth buffer of the
+//! HDR targets of the base rendergraph, e.g. right after the forward pass,
+//! with a routine created for [`TextureFormat::Rgba16Float`]. Without a depth
+//! buffer, e.g. on the surface after tonemapping, all lines are drawn on top.
+
+use std::mem;
+
+use glam::{Mat4, UVec2, Vec2, Vec3, Vec4};
+use rend3::{
+    graph::{DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    types::{DepthMode, SampleCount},
+    Renderer,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BlendState, BufferAddress, BufferUsages, Color, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
+    DepthStencilState, Device, FragmentState, FrontFace, MultisampleState, PipelineLayout, PipelineLayoutDescriptor,
+    PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, StencilState, TextureFormat, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::{common, shaders};
+
+/// Shape of the ends of a line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineCap {
+    /// Ends exactly at the end point.
+    Butt,
+    /// Extends past the end point by half the width.
+    Square,
+    /// Half circle around the end point.
+    Round,
+}
+
+impl LineCap {
+    fn shader_value(self) -> f32 {
+        match self {
+            LineCap::Butt => 0.0,
+            LineCap::Square => 1.0,
+            LineCap::Round => 2.0,
+        }
+    }
+}
+
+/// Dashes along a line, in pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LineDash {
+    pub dash: f32,
+    pub gap: f32,
+}
+
+/// How a line is drawn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LineStyle {
+    /// Width in pixels.
+    pub width: f32,
+    pub cap: LineCap,
+    /// None draws a solid line.
+    pub dash: Option<LineDash>,
+    /// Hide the line behind the scene. Only has an effect when the routine is
+    /// given the depth buffer.
+    pub depth_test: bool,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self {
+            width: 2.0,
+            cap: LineCap::Round,
+            dash: None,
+            depth_test: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Polyline {
+    /// Positions and srgb colors.
+    points: Vec<(Vec3, Vec4)>,
+    closed: bool,
+    style: LineStyle,
+}
+
+/// Lines to draw with a [`LineRoutine`].
+///
+/// Colors are srgb with alpha, and are blended along each segment.
+#[derive(Debug, Default, Clone)]
+pub struct LineBatch {
+    polylines: Vec<Polyline>,
+}
+
+impl LineBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes all lines, e.g. once a frame has been rendered.
+    pub fn clear(&mut self) {
+        self.polylines.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.polylines.is_empty()
+    }
+
+    /// Line between two points in world space.
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Vec4, style: LineStyle) {
+        self.polyline_colored([(start, color), (end, color)], style);
+    }
+
+    /// Connected line through points in world space.
+    pub fn polyline(&mut self, points: impl IntoIterator<Item = Vec3>, color: Vec4, style: LineStyle) {
+        self.polyline_colored(points.into_iter().map(|point| (point, color)), style);
+    }
+
+    /// Connected line through points in world space, each with its own
+    /// color.
+    pub fn polyline_colored(&mut self, points: impl IntoIterator<Item = (Vec3, Vec4)>, style: LineStyle) {
+        self.push(points, false, style);
+    }
+
+    /// Closed line through points in world space, connecting the last point
+    /// back to the first.
+    pub fn polygon(&mut self, points: impl IntoIterator<Item = Vec3>, color: Vec4, style: LineStyle) {
+        self.push(points.into_iter().map(|point| (point, color)), true, style);
+    }
+
+    fn push(&mut self, points: impl IntoIterator<Item = (Vec3, Vec4)>, closed: bool, style: LineStyle) {
+        let points: Vec<_> = points.into_iter().collect();
+        if points.len() < 2 {
+            return;
+        }
+        self.polylines.push(Polyline { points, closed, style });
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct LineVertex {
+    // Arrays, as the alignment of glam's vectors would pad the struct.
+    /// Clip space position.
+    position: [f32; 4],
+    /// Position in pixels along the segment from its start, and across it
+    /// from its center.
+    local: [f32; 2],
+    /// Length and half width in pixels, then caps at the start and end.
+    segment: [f32; 4],
+    /// Distance along the polyline at the start of the segment, then the
+    /// lengths of dashes and gaps, all in pixels.
+    dash: [f32; 3],
+    color_start: [f32; 4],
+    color_end: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for LineVertex {}
+unsafe impl bytemuck::Zeroable for LineVertex {}
+
+/// Turns the contents of a [`LineBatch`] into vertices.
+struct VertexBuilder {
+    view_proj: Mat4,
+    resolution: Vec2,
+    linear_output: bool,
+}
+
+impl VertexBuilder {
+    fn color(&self, color: Vec4) -> Vec4 {
+        if self.linear_output {
+            common::srgb_to_linear(color)
+        } else {
+            color
+        }
+    }
+
+    /// Clip space point to pixels, with y down, and its depth.
+    fn to_screen(&self, clip: Vec4) -> (Vec2, f32) {
+        let ndc = clip.truncate() / clip.w;
+        let pixel = (ndc.truncate() * Vec2::new(0.5, -0.5) + 0.5) * self.resolution;
+        (pixel, ndc.z)
+    }
+
+    fn to_clip(&self, pixel: Vec2, depth: f32) -> Vec4 {
+        let ndc = pixel / self.resolution * Vec2::new(2.0, -2.0) + Vec2::new(-1.0, 1.0);
+        ndc.extend(depth).extend(1.0)
+    }
+
+    fn polyline(&self, vertices: &mut Vec<LineVertex>, polyline: &Polyline) {
+        let style = &polyline.style;
+        let half_width = style.width.max(0.0) / 2.0;
+        let (dash, gap) = style
+            .dash
+            .map_or((0.0, 0.0), |dash| (dash.dash.max(0.0), dash.gap.max(0.0)));
+
+        let points = &polyline.points;
+        let segment_count = if polyline.closed {
+            points.len()
+        } else {
+            points.len() - 1
+        };
+
+        let mut distance = 0.0;
+        for idx in 0..segment_count {
+            let (start, start_color) = points[idx];
+            let (end, end_color) = points[(idx + 1) % points.len()];
+
+            let mut start_clip = self.view_proj * start.extend(1.0);
+            let mut end_clip = self.view_proj * end.extend(1.0);
+
+            // Cut the segment at the camera plane, where the divide by w breaks down.
+            const MIN_W: f32 = 1e-5;
+            if start_clip.w < MIN_W && end_clip.w < MIN_W {
+                continue;
+            }
+            if start_clip.w < MIN_W {
+                start_clip = start_clip.lerp(end_clip, (MIN_W - start_clip.w) / (end_clip.w - start_clip.w));
+            } else if end_clip.w < MIN_W {
+                end_clip = end_clip.lerp(start_clip, (MIN_W - end_clip.w) / (start_clip.w - end_clip.w));
+            }
+
+            let (start_pixel, start_depth) = self.to_screen(start_clip);
+            let (end_pixel, end_depth) = self.to_screen(end_clip);
+
+            let length = start_pixel.distance(end_pixel);
+            let along = if length > 0.0 {
+                (end_pixel - start_pixel) / length
+            } else {
+                Vec2::X
+            };
+            let across = along.perp();
+
+            // Joins between segments are round, only the ends of open polylines get the cap.
+            let start_cap = match idx == 0 && !polyline.closed {
+                true => style.cap,
+                false => LineCap::Round,
+            };
+            let end_cap = match idx == segment_count - 1 && !polyline.closed {
+                true => style.cap,
+                false => LineCap::Round,
+            };
+
+            // Room for the caps and for antialiasing.
+            let extent = half_width + 1.0;
+            let segment = [length, half_width, start_cap.shader_value(), end_cap.shader_value()];
+            let color_start = self.color(start_color).to_array();
+            let color_end = self.color(end_color).to_array();
+
+            let corner = |x: f32, y: f32| {
+                let local = Vec2::new(x * (length + 2.0 * extent) - extent, y * extent);
+                let (pixel, depth) = match x > 0.5 {
+                    true => (end_pixel, end_depth),
+                    false => (start_pixel, start_depth),
+                };
+                let offset = along * (local.x - if x > 0.5 { length } else { 0.0 }) + across * local.y;
+                LineVertex {
+                    position: self.to_clip(pixel + offset, depth).to_array(),
+                    local: local.to_array(),
+                    segment,
+                    dash: [distance, dash, gap],
+                    color_start,
+                    color_end,
+                }
+            };
+
+            vertices.extend_from_slice(&[
+                corner(0.0, -1.0),
+                corner(1.0, -1.0),
+                corner(0.0, 1.0),
+                corner(0.0, 1.0),
+                corner(1.0, -1.0),
+                corner(1.0, 1.0),
+            ]);
+
+            distance += length;
+        }
+    }
+}
+
+/// Pipelines for one sample count.
+struct LinePipelines {
+    /// For passes without a depth buffer.
+    no_depth: RenderPipeline,
+    depth_tested: RenderPipeline,
+    depth_ignored: RenderPipeline,
+}
+
+impl LinePipelines {
+    fn new(
+        device: &Device,
+        pll: &PipelineLayout,
+        module: &ShaderModule,
+        format: TextureFormat,
+        samples: SampleCount,
+        depth_mode: DepthMode,
+    ) -> Self {
+        Self {
+            no_depth: create_pipeline(device, pll, module, format, samples, None),
+            depth_tested: create_pipeline(device, pll, module, format, samples, Some(depth_mode.closer_or_equal())),
+            depth_ignored: create_pipeline(device, pll, module, format, samples, Some(CompareFunction::Always)),
+        }
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    pll: &PipelineLayout,
+    module: &ShaderModule,
+    format: TextureFormat,
+    samples: SampleCount,
+    depth_compare: Option<CompareFunction>,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("line"),
+        layout: Some(pll),
+        vertex: VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[VertexBufferLayout {
+                array_stride: mem::size_of::<LineVertex>() as BufferAddress,
+                step_mode: VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![
+                    0 => Float32x4,
+                    1 => Float32x2,
+                    2 => Float32x4,
+                    3 => Float32x3,
+                    4 => Float32x4,
+                    5 => Float32x4,
+                ],
+            }],
+        },
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Cw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: depth_compare.map(|depth_compare| DepthStencilState {
+            format: TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        multisample: MultisampleState {
+            count: samples as u32,
+            ..MultisampleState::default()
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point: "fs_main",
+            targets: &[ColorTargetState {
+                format,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::all(),
+            }],
+        }),
+        multiview: None,
+    })
+}
+
+/// Draws the contents of a [`LineBatch`].
+///
+/// See module for documentation.
+pub struct LineRoutine {
+    pipelines_s1: LinePipelines,
+    pipelines_s4: LinePipelines,
+    linear_output: bool,
+    depth_mode: DepthMode,
+}
+
+impl LineRoutine {
+    /// Creates a routine drawing lines into targets of `format`.
+    pub fn new(renderer: &Renderer, format: TextureFormat) -> Self {
+        profiling::scope!("LineRoutine::new");
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("line"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "line.wgsl",
+                include_str!("../shaders/src/line.wgsl"),
+            )),
+        });
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("line"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let pipelines =
+            |samples| LinePipelines::new(&renderer.device, &pll, &module, format, samples, renderer.depth_mode);
+
+        Self {
+            pipelines_s1: pipelines(SampleCount::One),
+            pipelines_s4: pipelines(SampleCount::Four),
+            // Float targets hold linear color, like srgb ones after decoding.
+            linear_output: format.describe().srgb || format == TextureFormat::Rgba16Float,
+            depth_mode: renderer.depth_mode,
+        }
+    }
+
+    /// Draws everything in `batch` on top of `color`, seen from the camera of
+    /// the renderer. With `depth`, lines with [`LineStyle::depth_test`] are
+    /// hidden behind the scene.
+    ///
+    /// `resolution` and `samples` are those of `color` and `depth`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        batch: &'node LineBatch,
+        color: RenderTargetHandle,
+        resolve: Option<RenderTargetHandle>,
+        depth: Option<RenderTargetHandle>,
+        resolution: UVec2,
+        samples: SampleCount,
+    ) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let mut builder = graph.add_node("Lines");
+
+        let color_handle = builder.add_render_target_output(color);
+        let resolve_handle = builder.add_optional_render_target_output(resolve);
+        let depth_handle = depth.map(|depth| builder.add_render_target_output(depth));
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: color_handle,
+                clear: Color::BLACK,
+                resolve: resolve_handle,
+            }],
+            depth_stencil: depth_handle.map(|depth| RenderPassDepthTarget {
+                target: DepthHandle::RenderTarget(depth),
+                depth_clear: Some(self.depth_mode.far_depth()),
+                stencil_clear: None,
+            }),
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+        let batch_handle = builder.passthrough_ref(batch);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let batch = pt.get(batch_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("lines");
+
+            // The camera is only final once the renderer is readied.
+            let vertex_builder = VertexBuilder {
+                view_proj: graph_data.camera_manager.view_proj(),
+                resolution: resolution.as_vec2(),
+                linear_output: this.linear_output,
+            };
+            let mut tested = Vec::new();
+            let mut untested = Vec::new();
+            for polyline in &batch.polylines {
+                let vertices = match polyline.style.depth_test && depth.is_some() {
+                    true => &mut tested,
+                    false => &mut untested,
+                };
+                vertex_builder.polyline(vertices, polyline);
+            }
+
+            let pipelines = match samples {
+                SampleCount::One => &this.pipelines_s1,
+                SampleCount::Four => &this.pipelines_s4,
+            };
+            let untested_pipeline = match depth {
+                Some(_) => &pipelines.depth_ignored,
+                None => &pipelines.no_depth,
+            };
+
+            // Lines hidden by the scene first, so lines drawn on top aren't covered by them.
+            for (pipeline, vertices) in [(&pipelines.depth_tested, tested), (untested_pipeline, untested)] {
+                if vertices.is_empty() {
+                    continue;
+                }
+
+                let vertex_buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("line vertices"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: BufferUsages::VERTEX,
+                }));
+
+                rpass.set_pipeline(pipeline);
+                rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                rpass.draw(0..vertices.len() as u32, 0..1);
+            }
+        });
+    }
+}
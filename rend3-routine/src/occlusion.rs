@@ -0,0 +1,368 @@
+//! Answers the occlusion queries of
+//! [`Renderer::begin_occlusion_query`](rend3::Renderer::begin_occlusion_query)
+//! by testing them against the depth buffer.
+//!
+//! Each query box is projected on the CPU to the rect of pixels it covers and
+//! the depth of its closest point. A compute shader then looks for a pixel of
+//! the rect where the scene is further away than that, and the results are
+//! read back once the GPU is done, without stalling. Boxes crossing the
+//! camera plane are visible, and boxes outside the view occluded, without
+//! touching the GPU.
+//!
+//! Add the routine after everything writing depth. The Downlevel profile has
+//! no compute shaders, and counts every box in view as visible.
+
+use std::{mem, task::Poll};
+
+use glam::{Mat4, UVec2, Vec2, Vec3};
+use parking_lot::Mutex;
+use rend3::{
+    graph::{RenderGraph, RenderTargetHandle},
+    types::{DepthMode, SampleCount},
+    util::{
+        bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+        buffer::{poll_now, MapFuture},
+        geometry::{project_aabb, Aabb},
+        occlusion::PendingOcclusionQuery,
+    },
+    Renderer, RendererProfile,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayout, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages, ComputePassDescriptor,
+    ComputePipeline, ComputePipelineDescriptor, Device, Maintain, MapMode, PipelineLayoutDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureSampleType, TextureViewDimension,
+};
+
+use crate::shaders;
+
+/// Queries tested by a single dispatch at most, the limit of workgroups per
+/// dimension. Further queries count as visible.
+const MAX_QUERIES_PER_FRAME: usize = 65535;
+
+/// Frames read back at most at once. Queries of older frames count as visible
+/// rather than piling up behind a slow GPU.
+const MAX_MAPPING_FRAMES: usize = 3;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct GpuQuery {
+    rect_min: UVec2,
+    rect_max: UVec2,
+    depth: f32,
+    reversed: u32,
+    _padding: [u32; 2],
+}
+
+unsafe impl bytemuck::Pod for GpuQuery {}
+unsafe impl bytemuck::Zeroable for GpuQuery {}
+
+/// Outcome of projecting a query box, see [`project_box`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Projection {
+    /// The box crosses the camera plane, so covers part of the view.
+    Visible,
+    /// The box is outside the view.
+    Offscreen,
+    /// The box covers these pixels, and needs testing against the depth.
+    Rect(GpuQuery),
+}
+
+fn project_box(min: Vec3, max: Vec3, view_proj: Mat4, resolution: UVec2, depth_mode: DepthMode) -> Projection {
+    let (ndc_min, ndc_max) = match project_aabb(Aabb { min, max }, view_proj) {
+        Some(ndc) => (ndc.min, ndc.max),
+        None => return Projection::Visible,
+    };
+
+    let reversed = depth_mode.is_reversed();
+    let (depth, beyond_far) = match reversed {
+        true => (ndc_max.z, ndc_max.z < 0.0),
+        false => (ndc_min.z, ndc_min.z > 1.0),
+    };
+
+    // Y points down in pixels.
+    let size = resolution.as_vec2();
+    let to_pixels = |x: f32, y: f32| Vec2::new(x * 0.5 + 0.5, 0.5 - y * 0.5) * size;
+    let rect_min = to_pixels(ndc_min.x, ndc_max.y).floor().max(Vec2::ZERO).as_uvec2();
+    let rect_max = to_pixels(ndc_max.x, ndc_min.y)
+        .ceil()
+        .min(size)
+        .max(Vec2::ZERO)
+        .as_uvec2();
+    if beyond_far || rect_min.cmpge(rect_max).any() {
+        return Projection::Offscreen;
+    }
+
+    Projection::Rect(GpuQuery {
+        rect_min,
+        rect_max,
+        depth,
+        reversed: reversed as u32,
+        _padding: [0; 2],
+    })
+}
+
+/// Queries tested by a frame, waiting for their results.
+struct QueryFrame {
+    queries: Vec<PendingOcclusionQuery>,
+    buffer: Buffer,
+    /// Started once the frame was submitted.
+    mapping: Option<MapFuture>,
+}
+
+impl QueryFrame {
+    fn resolve_visible(self) {
+        for query in self.queries {
+            query.resolve(true);
+        }
+    }
+}
+
+/// Routine answering occlusion queries, see the [module](self) docs.
+pub struct OcclusionRoutine {
+    samples: SampleCount,
+    bgl: BindGroupLayout,
+    pipeline: Option<ComputePipeline>,
+    frames: Mutex<Vec<QueryFrame>>,
+}
+
+impl OcclusionRoutine {
+    /// `samples` is the sample count of the depth buffer the routine tests
+    /// against.
+    pub fn new(renderer: &Renderer, samples: SampleCount) -> Self {
+        profiling::scope!("OcclusionRoutine::new");
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::COMPUTE,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: samples.needs_resolve(),
+                },
+                None,
+            )
+            .append(
+                ShaderStages::COMPUTE,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::COMPUTE,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                None,
+            )
+            .build(&renderer.device, Some("occlusion bgl"));
+
+        let pipeline = (renderer.profile != RendererProfile::Downlevel).then(|| {
+            let source = shaders::source_shader("occlusion.wgsl", include_str!("../shaders/src/occlusion.wgsl"));
+            let source = match samples {
+                SampleCount::One => source,
+                // Testing the first sample is close enough for visibility.
+                SampleCount::Four => source
+                    .replace("texture_depth_2d", "texture_depth_multisampled_2d")
+                    .into(),
+            };
+            let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+                label: Some("occlusion"),
+                source: ShaderSource::Wgsl(source),
+            });
+            let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("occlusion"),
+                bind_group_layouts: &[&bgl],
+                push_constant_ranges: &[],
+            });
+            renderer.device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some("occlusion"),
+                layout: Some(&pll),
+                module: &module,
+                entry_point: "main",
+            })
+        });
+
+        Self {
+            samples,
+            bgl,
+            pipeline,
+            frames: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn samples(&self) -> SampleCount {
+        self.samples
+    }
+
+    /// Resolves the queries of every frame whose results were read back.
+    fn read_back(&self, device: &Device) {
+        let mut frames = self.frames.lock();
+        if frames.is_empty() {
+            return;
+        }
+
+        // Frames recorded by earlier rendergraphs have been submitted by now.
+        for frame in frames.iter_mut().filter(|frame| frame.mapping.is_none()) {
+            frame.mapping = Some(Box::pin(frame.buffer.slice(..).map_async(MapMode::Read)));
+        }
+        while frames.len() > MAX_MAPPING_FRAMES {
+            frames.remove(0).resolve_visible();
+        }
+        device.poll(Maintain::Poll);
+
+        while let Some(frame) = frames.first_mut() {
+            let result = poll_now(frame.mapping.as_mut().unwrap());
+            let frame = match result {
+                Poll::Pending => return,
+                Poll::Ready(Err(_)) => {
+                    log::warn!("Failed to read back occlusion query results");
+                    frames.remove(0).resolve_visible();
+                    continue;
+                }
+                Poll::Ready(Ok(())) => frames.remove(0),
+            };
+
+            let data = frame.buffer.slice(..).get_mapped_range();
+            let results: &[u32] = bytemuck::cast_slice(&data);
+            for (query, &result) in frame.queries.into_iter().zip(results) {
+                query.resolve(result != 0);
+            }
+        }
+    }
+
+    /// Tests the queries started since the last frame against `depth`, of
+    /// size `resolution`.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        depth: RenderTargetHandle,
+        resolution: UVec2,
+    ) {
+        let mut builder = graph.add_node("Occlusion Queries");
+
+        let depth_handle = builder.add_render_target_input(depth);
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, _temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+
+            profiling::scope!("occlusion queries");
+
+            this.read_back(&renderer.device);
+
+            let mut pending = renderer.occlusion.take_pending();
+            if pending.len() > MAX_QUERIES_PER_FRAME {
+                for query in pending.split_off(MAX_QUERIES_PER_FRAME) {
+                    query.resolve(true);
+                }
+            }
+
+            let view_proj = graph_data.camera_manager.view_proj();
+            let mut queries = Vec::new();
+            let mut gpu_queries = Vec::new();
+            for query in pending {
+                let bounds = query.target.world_bounds(graph_data.object_manager);
+                let projection = match bounds {
+                    Some((min, max)) => project_box(min, max, view_proj, resolution, renderer.depth_mode),
+                    None => Projection::Offscreen,
+                };
+                match (projection, &this.pipeline) {
+                    (Projection::Visible, _) | (Projection::Rect(_), None) => query.resolve(true),
+                    (Projection::Offscreen, _) => query.resolve(false),
+                    (Projection::Rect(gpu_query), Some(_)) => {
+                        queries.push(query);
+                        gpu_queries.push(gpu_query);
+                    }
+                }
+            }
+
+            let pipeline = match this.pipeline {
+                Some(ref pipeline) if !queries.is_empty() => pipeline,
+                _ => return,
+            };
+
+            let size = (queries.len() * mem::size_of::<u32>()) as u64;
+            let query_buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("occlusion queries"),
+                contents: bytemuck::cast_slice(&gpu_queries),
+                usage: BufferUsages::STORAGE,
+            });
+            let result_buffer = renderer.device.create_buffer(&BufferDescriptor {
+                label: Some("occlusion results"),
+                size,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            let readback = renderer.device.create_buffer(&BufferDescriptor {
+                label: Some("occlusion readback"),
+                size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+
+            let bg = BindGroupBuilder::new()
+                .append_texture_view(graph_data.get_render_target(depth_handle))
+                .append_buffer(&query_buffer)
+                .append_buffer(&result_buffer)
+                .build(&renderer.device, Some("occlusion bg"), &this.bgl);
+
+            let encoder = encoder_or_pass.get_encoder();
+            let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("occlusion queries"),
+            });
+            cpass.set_pipeline(pipeline);
+            cpass.set_bind_group(0, &bg, &[]);
+            cpass.dispatch(queries.len() as u32, 1, 1);
+            drop(cpass);
+            encoder.copy_buffer_to_buffer(&result_buffer, 0, &readback, 0, size);
+
+            this.frames.lock().push(QueryFrame {
+                queries,
+                buffer: readback,
+                mapping: None,
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, UVec2, Vec3};
+    use rend3::types::DepthMode;
+
+    use super::{project_box, Projection};
+
+    #[test]
+    fn boxes_project_to_pixel_rects() {
+        let view_proj = Mat4::perspective_infinite_reverse_lh(1.0, 1.0, 0.1);
+        let resolution = UVec2::new(100, 100);
+        let project = |min, max| project_box(min, max, view_proj, resolution, DepthMode::ReversedZInfinite);
+
+        match project(Vec3::new(-1.0, -1.0, 10.0), Vec3::new(1.0, 1.0, 12.0)) {
+            Projection::Rect(query) => {
+                assert!(query.rect_min.cmplt(UVec2::splat(50)).all());
+                assert!(query.rect_max.cmpgt(UVec2::splat(50)).all());
+                // The closest point is on the front face.
+                assert!((query.depth - 0.1 / 10.0).abs() < 1e-5);
+                assert_eq!(query.reversed, 1);
+            }
+            other => panic!("expected a rect, got {:?}", other),
+        }
+
+        assert_eq!(
+            project(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)),
+            Projection::Visible
+        );
+        assert_eq!(
+            project(Vec3::new(50.0, -1.0, 10.0), Vec3::new(52.0, 1.0, 12.0)),
+            Projection::Offscreen
+        );
+    }
+}
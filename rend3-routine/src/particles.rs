@@ -0,0 +1,672 @@
+//! GPU simulated particles, for smoke, sparks, dust and the like.
+//!
+//! Add [`ParticleEmitter`]s to a [`ParticleRoutine`], call
+//! [`ParticleRoutine::update`] once a frame with the time passed, and add the
+//! routine to the graph after the forward pass, see
+//! [`BaseRenderGraphIntermediateState::particles`]. Each emitter owns a fixed
+//! number of particles which are spawned, moved and aged by a compute shader,
+//! so nothing is read back to the CPU.
+//!
+//! Particles can bounce off the scene by testing against its depth buffer,
+//! which only knows about surfaces visible on screen, so particles going off
+//! screen or behind objects pass through them. They are drawn as soft round
+//! sprites facing the camera, fading out where they get close to the scene
+//! instead of cutting hard edges into it. Particles are not sorted, so dense
+//! emitters look best with [`ParticleBlend::Additive`].
+//!
+//! Needs compute shaders, so it is not available in the Downlevel profile.
+//!
+//! [`BaseRenderGraphIntermediateState::particles`]: crate::base::BaseRenderGraphIntermediateState::particles
+
+use std::{mem, num::NonZeroU64};
+
+use glam::{Mat4, UVec2, Vec2, Vec3, Vec4};
+use rend3::{
+    graph::{DataHandle, RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    types::SampleCount,
+    util::{
+        bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+        math::round_up_div,
+    },
+    Renderer, RendererProfile,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupLayout, BindingType, BlendComponent, BlendFactor, BlendOperation, BlendState, Buffer,
+    BufferAddress, BufferBindingType, BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites,
+    ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device, FragmentState, FrontFace,
+    MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType,
+    TextureViewDimension, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::shaders;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// A value changing over the lifetime of a particle, linearly between four
+/// keys spread evenly from birth to death.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ParticleCurve<T> {
+    pub keys: [T; 4],
+}
+
+impl<T: Copy> ParticleCurve<T> {
+    pub fn constant(value: T) -> Self {
+        Self { keys: [value; 4] }
+    }
+
+    pub fn keys(keys: [T; 4]) -> Self {
+        Self { keys }
+    }
+}
+
+impl ParticleCurve<f32> {
+    /// Changes evenly from `start` to `end`.
+    pub fn linear(start: f32, end: f32) -> Self {
+        Self {
+            keys: [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0].map(|t| start + (end - start) * t),
+        }
+    }
+}
+
+impl ParticleCurve<Vec4> {
+    /// Changes evenly from `start` to `end`.
+    pub fn linear(start: Vec4, end: Vec4) -> Self {
+        Self {
+            keys: [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0].map(|t| start.lerp(end, t)),
+        }
+    }
+}
+
+/// How particles are blended over what is behind them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParticleBlend {
+    /// Covers what is behind, like smoke.
+    Alpha,
+    /// Adds light to what is behind, like sparks and fire.
+    Additive,
+}
+
+/// Describes how an emitter spawns particles and how they behave.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParticleEmitter {
+    /// Center of the emitter in world space.
+    pub position: Vec3,
+    /// Particles spawn at random points in a sphere of this radius.
+    pub spawn_radius: f32,
+    /// Particles spawned per second.
+    pub spawn_rate: f32,
+    /// Seconds a particle lives.
+    pub lifetime: f32,
+    /// Fraction of the lifetime particles randomly live shorter.
+    pub lifetime_variation: f32,
+    /// Velocity particles spawn with, in units per second.
+    pub velocity: Vec3,
+    /// Random velocity added to each particle, up to this speed in any
+    /// direction.
+    pub velocity_spread: f32,
+    /// Constant acceleration, like gravity or buoyancy.
+    pub acceleration: Vec3,
+    /// Fraction of the velocity lost per second.
+    pub drag: f32,
+    /// Multiplier of the speed over the lifetime.
+    pub speed: ParticleCurve<f32>,
+    /// Diameter in world units over the lifetime.
+    pub size: ParticleCurve<f32>,
+    /// Linear color with alpha over the lifetime. Values above one glow once
+    /// tonemapped.
+    pub color: ParticleCurve<Vec4>,
+    pub blend: ParticleBlend,
+    /// Bounce off the depth buffer, keeping this fraction of the speed. None
+    /// lets particles pass through the scene.
+    pub collision: Option<f32>,
+    /// Distance in world units over which particles fade out as they get
+    /// close to the scene behind them.
+    pub softness: f32,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            spawn_radius: 0.0,
+            spawn_rate: 10.0,
+            lifetime: 2.0,
+            lifetime_variation: 0.0,
+            velocity: Vec3::Y,
+            velocity_spread: 0.0,
+            acceleration: Vec3::ZERO,
+            drag: 0.0,
+            speed: ParticleCurve::constant(1.0),
+            size: ParticleCurve::constant(0.1),
+            color: ParticleCurve::constant(Vec4::ONE),
+            blend: ParticleBlend::Alpha,
+            collision: None,
+            softness: 0.1,
+        }
+    }
+}
+
+/// Identifies an emitter of a [`ParticleRoutine`]. Ids of removed emitters
+/// are reused.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ParticleEmitterId(usize);
+
+/// The actual structure passed to the shader as the camera.
+#[derive(Debug, Copy, Clone)]
+#[repr(C, align(16))]
+struct ParticleCameraUniform {
+    view_proj: Mat4,
+    inv_view_proj: Mat4,
+    inv_proj: Mat4,
+    location: Vec4,
+    right: Vec4,
+    up: Vec4,
+    resolution: Vec2,
+    delta: f32,
+    _padding: f32,
+}
+
+unsafe impl bytemuck::Zeroable for ParticleCameraUniform {}
+unsafe impl bytemuck::Pod for ParticleCameraUniform {}
+
+/// The actual structure passed to the shader as the emitter.
+#[derive(Debug, Copy, Clone)]
+#[repr(C, align(16))]
+struct EmitterUniform {
+    /// xyz is the position, w the spawn radius.
+    origin: Vec4,
+    /// xyz is the velocity, w the spread.
+    velocity: Vec4,
+    /// xyz is the acceleration, w the drag.
+    acceleration: Vec4,
+    speed: Vec4,
+    size: Vec4,
+    colors: [Vec4; 4],
+    lifetime: f32,
+    lifetime_variation: f32,
+    /// Negative without collision.
+    restitution: f32,
+    softness: f32,
+    /// Particles from spawn_start on, wrapping around, are spawned this frame.
+    spawn_start: u32,
+    spawn_count: u32,
+    capacity: u32,
+    seed: u32,
+}
+
+unsafe impl bytemuck::Zeroable for EmitterUniform {}
+unsafe impl bytemuck::Pod for EmitterUniform {}
+
+/// Layout of a particle in the storage buffer, which is also the instance
+/// vertex buffer when drawing.
+const PARTICLE_SIZE: BufferAddress = 32;
+
+struct EmitterData {
+    emitter: ParticleEmitter,
+    capacity: u32,
+    uniform_buffer: Buffer,
+    particle_buffer: Buffer,
+    compute_bg: BindGroup,
+    render_bg: BindGroup,
+    /// Fraction of a particle left to spawn from previous frames.
+    spawn_accumulator: f32,
+    burst: u32,
+    cursor: u32,
+    frame: u32,
+}
+
+/// Pipelines for one sample count of the scene depth.
+struct ParticlePipelines {
+    frame_bgl: BindGroupLayout,
+    compute: ComputePipeline,
+    alpha: RenderPipeline,
+    additive: RenderPipeline,
+}
+
+impl ParticlePipelines {
+    fn new(
+        device: &Device,
+        emitter_compute_bgl: &BindGroupLayout,
+        emitter_render_bgl: &BindGroupLayout,
+        samples: SampleCount,
+    ) -> Self {
+        let frame_bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::COMPUTE | ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(mem::size_of::<ParticleCameraUniform>() as u64),
+                },
+                None,
+            )
+            .append(
+                ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: samples.needs_resolve(),
+                },
+                None,
+            )
+            .build(device, Some("particle frame bgl"));
+
+        let source = shaders::source_shader("particles.wgsl", include_str!("../shaders/src/particles.wgsl"));
+        let source = match samples {
+            SampleCount::One => source,
+            SampleCount::Four => source
+                .replace("texture_depth_2d", "texture_depth_multisampled_2d")
+                .into(),
+        };
+        let module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("particles"),
+            source: ShaderSource::Wgsl(source),
+        });
+
+        let compute_pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("particle simulation"),
+            bind_group_layouts: &[&frame_bgl, emitter_compute_bgl],
+            push_constant_ranges: &[],
+        });
+        let compute = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("particle simulation"),
+            layout: Some(&compute_pll),
+            module: &module,
+            entry_point: "cs_main",
+        });
+
+        let render_pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("particles"),
+            bind_group_layouts: &[&frame_bgl, emitter_render_bgl],
+            push_constant_ranges: &[],
+        });
+        let render = |blend| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("particles"),
+                layout: Some(&render_pll),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[VertexBufferLayout {
+                        array_stride: PARTICLE_SIZE,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4],
+                    }],
+                },
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Cw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: PolygonMode::Fill,
+                    conservative: false,
+                },
+                // Depth is tested in the fragment shader, to fade out instead.
+                depth_stencil: None,
+                multisample: MultisampleState {
+                    count: samples as u32,
+                    ..Default::default()
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: Some(blend),
+                        write_mask: ColorWrites::all(),
+                    }],
+                }),
+                multiview: None,
+            })
+        };
+
+        Self {
+            frame_bgl,
+            compute,
+            alpha: render(BlendState::ALPHA_BLENDING),
+            additive: render(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            }),
+        }
+    }
+}
+
+/// Simulates and draws particle emitters.
+///
+/// See module for documentation.
+pub struct ParticleRoutine {
+    emitters: Vec<Option<EmitterData>>,
+    emitter_compute_bgl: BindGroupLayout,
+    emitter_render_bgl: BindGroupLayout,
+    pipelines_s1: ParticlePipelines,
+    pipelines_s4: ParticlePipelines,
+    camera_buffer: Buffer,
+    delta: f32,
+}
+
+impl ParticleRoutine {
+    /// # Panics
+    ///
+    /// In the Downlevel profile.
+    pub fn new(renderer: &Renderer) -> Self {
+        profiling::scope!("ParticleRoutine::new");
+
+        assert_ne!(
+            renderer.profile,
+            RendererProfile::Downlevel,
+            "Particles need compute shaders, which the Downlevel profile doesn't have"
+        );
+
+        let emitter_uniform = BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(mem::size_of::<EmitterUniform>() as u64),
+        };
+        let emitter_compute_bgl = BindGroupLayoutBuilder::new()
+            .append(ShaderStages::COMPUTE, emitter_uniform, None)
+            .append(
+                ShaderStages::COMPUTE,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(PARTICLE_SIZE),
+                },
+                None,
+            )
+            .build(&renderer.device, Some("particle emitter compute bgl"));
+        // The particles are the instance buffer when drawing, so they can't be bound as storage.
+        let emitter_render_bgl = BindGroupLayoutBuilder::new()
+            .append(ShaderStages::VERTEX | ShaderStages::FRAGMENT, emitter_uniform, None)
+            .build(&renderer.device, Some("particle emitter render bgl"));
+
+        let pipelines =
+            |samples| ParticlePipelines::new(&renderer.device, &emitter_compute_bgl, &emitter_render_bgl, samples);
+        let pipelines_s1 = pipelines(SampleCount::One);
+        let pipelines_s4 = pipelines(SampleCount::Four);
+
+        let camera_buffer = renderer.device.create_buffer(&BufferDescriptor {
+            label: Some("particle camera"),
+            size: mem::size_of::<ParticleCameraUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            emitters: Vec::new(),
+            emitter_compute_bgl,
+            emitter_render_bgl,
+            pipelines_s1,
+            pipelines_s4,
+            camera_buffer,
+            delta: 0.0,
+        }
+    }
+
+    /// Adds an emitter with room for `capacity` particles alive at once. Once
+    /// all are alive, the oldest are respawned first.
+    pub fn add_emitter(&mut self, renderer: &Renderer, emitter: ParticleEmitter, capacity: u32) -> ParticleEmitterId {
+        let capacity = capacity.max(1);
+
+        let uniform_buffer = renderer.device.create_buffer(&BufferDescriptor {
+            label: Some("particle emitter"),
+            size: mem::size_of::<EmitterUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Zeroed particles have no lifetime, so they start out dead.
+        let particle_buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("particles"),
+            contents: &vec![0; (capacity as BufferAddress * PARTICLE_SIZE) as usize],
+            usage: BufferUsages::STORAGE | BufferUsages::VERTEX,
+        });
+
+        let compute_bg = BindGroupBuilder::new()
+            .append_buffer(&uniform_buffer)
+            .append_buffer(&particle_buffer)
+            .build(
+                &renderer.device,
+                Some("particle emitter compute bg"),
+                &self.emitter_compute_bgl,
+            );
+        let render_bg = BindGroupBuilder::new().append_buffer(&uniform_buffer).build(
+            &renderer.device,
+            Some("particle emitter render bg"),
+            &self.emitter_render_bgl,
+        );
+
+        let data = EmitterData {
+            emitter,
+            capacity,
+            uniform_buffer,
+            particle_buffer,
+            compute_bg,
+            render_bg,
+            spawn_accumulator: 0.0,
+            burst: 0,
+            cursor: 0,
+            frame: 0,
+        };
+
+        match self.emitters.iter().position(Option::is_none) {
+            Some(idx) => {
+                self.emitters[idx] = Some(data);
+                ParticleEmitterId(idx)
+            }
+            None => {
+                self.emitters.push(Some(data));
+                ParticleEmitterId(self.emitters.len() - 1)
+            }
+        }
+    }
+
+    /// Removes the emitter along with its living particles.
+    pub fn remove_emitter(&mut self, id: ParticleEmitterId) {
+        if let Some(slot) = self.emitters.get_mut(id.0) {
+            *slot = None;
+        }
+    }
+
+    /// The description of an emitter, to move it or change how it behaves
+    /// from the next update on.
+    pub fn emitter_mut(&mut self, id: ParticleEmitterId) -> Option<&mut ParticleEmitter> {
+        self.emitters
+            .get_mut(id.0)
+            .and_then(Option::as_mut)
+            .map(|data| &mut data.emitter)
+    }
+
+    /// Spawns `count` particles at once on the next update, on top of the
+    /// spawn rate, e.g. for an explosion.
+    pub fn burst(&mut self, id: ParticleEmitterId, count: u32) {
+        if let Some(Some(data)) = self.emitters.get_mut(id.0) {
+            data.burst += count;
+        }
+    }
+
+    /// Advances the emitters by `delta` seconds, deciding which particles
+    /// spawn in the simulation of this frame. Call once per frame before
+    /// adding the routine to the graph.
+    pub fn update(&mut self, renderer: &Renderer, delta: f32) {
+        profiling::scope!("ParticleRoutine::update");
+
+        self.delta = delta;
+        for data in self.emitters.iter_mut().flatten() {
+            let emitter = &data.emitter;
+
+            data.spawn_accumulator += emitter.spawn_rate.max(0.0) * delta;
+            let spawned = data.spawn_accumulator.floor();
+            data.spawn_accumulator -= spawned;
+            let spawn_count = (spawned as u32).saturating_add(data.burst).min(data.capacity);
+            data.burst = 0;
+
+            let spawn_start = data.cursor;
+            data.cursor = (data.cursor + spawn_count) % data.capacity;
+            data.frame = data.frame.wrapping_add(1);
+
+            let uniform = EmitterUniform {
+                origin: emitter.position.extend(emitter.spawn_radius),
+                velocity: emitter.velocity.extend(emitter.velocity_spread),
+                acceleration: emitter.acceleration.extend(emitter.drag),
+                speed: Vec4::from(emitter.speed.keys),
+                size: Vec4::from(emitter.size.keys),
+                colors: emitter.color.keys,
+                lifetime: emitter.lifetime,
+                lifetime_variation: emitter.lifetime_variation.clamp(0.0, 1.0),
+                restitution: emitter.collision.map_or(-1.0, |restitution| restitution.max(0.0)),
+                softness: emitter.softness,
+                spawn_start,
+                spawn_count,
+                capacity: data.capacity,
+                // Different random numbers for every frame.
+                seed: data.frame.wrapping_mul(0x9E37_79B9),
+            };
+            renderer
+                .queue
+                .write_buffer(&data.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+        }
+    }
+
+    /// Simulates the particles against `depth` and draws them into `color`,
+    /// which are the HDR targets of the base rendergraph.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        color: RenderTargetHandle,
+        resolve: Option<RenderTargetHandle>,
+        depth: RenderTargetHandle,
+        resolution: UVec2,
+        samples: SampleCount,
+    ) {
+        if self.emitters.iter().all(Option::is_none) {
+            return;
+        }
+
+        let pipelines = match samples {
+            SampleCount::One => &self.pipelines_s1,
+            SampleCount::Four => &self.pipelines_s4,
+        };
+        let frame_bg = graph.add_data::<BindGroup>();
+
+        self.add_simulation_to_graph(graph, pipelines, frame_bg, depth, resolution);
+        self.add_rendering_to_graph(graph, pipelines, frame_bg, color, resolve);
+    }
+
+    fn add_simulation_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        pipelines: &'node ParticlePipelines,
+        frame_bg: DataHandle<BindGroup>,
+        depth: RenderTargetHandle,
+        resolution: UVec2,
+    ) {
+        let mut builder = graph.add_node("Particle Simulation");
+
+        let depth_handle = builder.add_render_target_input(depth);
+        let frame_bg_handle = builder.add_data_output(frame_bg);
+
+        let pt_handle = builder.passthrough_ref(self);
+        let pipelines_handle = builder.passthrough_ref(pipelines);
+
+        builder.build(move |pt, renderer, encoder_or_pass, _temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let pipelines = pt.get(pipelines_handle);
+            let encoder = encoder_or_pass.get_encoder();
+
+            profiling::scope!("particle simulation");
+
+            let camera = graph_data.camera_manager;
+            let inv_view = camera.view().inverse();
+            let view_proj = camera.view_proj();
+            let uniform = ParticleCameraUniform {
+                view_proj,
+                inv_view_proj: view_proj.inverse(),
+                inv_proj: camera.proj().inverse(),
+                location: inv_view.w_axis,
+                right: inv_view.x_axis,
+                up: inv_view.y_axis,
+                resolution: resolution.as_vec2(),
+                delta: this.delta,
+                _padding: 0.0,
+            };
+            renderer
+                .queue
+                .write_buffer(&this.camera_buffer, 0, bytemuck::bytes_of(&uniform));
+
+            let bg = BindGroupBuilder::new()
+                .append_buffer(&this.camera_buffer)
+                .append_texture_view(graph_data.get_render_target(depth_handle))
+                .build(&renderer.device, Some("particle frame bg"), &pipelines.frame_bgl);
+
+            let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("particle simulation"),
+            });
+            cpass.set_pipeline(&pipelines.compute);
+            cpass.set_bind_group(0, &bg, &[]);
+            for data in this.emitters.iter().flatten() {
+                cpass.set_bind_group(1, &data.compute_bg, &[]);
+                cpass.dispatch(round_up_div(data.capacity, WORKGROUP_SIZE), 1, 1);
+            }
+            drop(cpass);
+
+            graph_data.set_data(frame_bg_handle, Some(bg));
+        });
+    }
+
+    fn add_rendering_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        pipelines: &'node ParticlePipelines,
+        frame_bg: DataHandle<BindGroup>,
+        color: RenderTargetHandle,
+        resolve: Option<RenderTargetHandle>,
+    ) {
+        let mut builder = graph.add_node("Particles");
+
+        let hdr_color_handle = builder.add_render_target_output(color);
+        let hdr_resolve = builder.add_optional_render_target_output(resolve);
+        let frame_bg_handle = builder.add_data_input(frame_bg);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: hdr_color_handle,
+                clear: Color::BLACK,
+                resolve: hdr_resolve,
+            }],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+        let pipelines_handle = builder.passthrough_ref(pipelines);
+
+        builder.build(move |pt, _renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let pipelines = pt.get(pipelines_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("particles");
+
+            let frame_bg = graph_data.get_data(temps, frame_bg_handle).unwrap();
+
+            rpass.set_bind_group(0, frame_bg, &[]);
+            for data in this.emitters.iter().flatten() {
+                let pipeline = match data.emitter.blend {
+                    ParticleBlend::Alpha => &pipelines.alpha,
+                    ParticleBlend::Additive => &pipelines.additive,
+                };
+
+                rpass.set_pipeline(pipeline);
+                rpass.set_bind_group(1, &data.render_bg, &[]);
+                rpass.set_vertex_buffer(0, data.particle_buffer.slice(..));
+                rpass.draw(0..6, 0..data.capacity);
+            }
+        });
+    }
+}
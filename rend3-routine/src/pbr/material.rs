@@ -2,7 +2,7 @@
 
 use std::mem;
 
-use glam::{Mat3, Mat3A, Vec3, Vec4};
+use glam::{Mat3, Mat3A, Vec2, Vec3, Vec4};
 use rend3::types::{Material, TextureHandle};
 
 use crate::{
@@ -29,6 +29,9 @@ bitflags::bitflags! {
         const CC_BW_SPLIT =         0b0001_0000_0000_0000;
         const UNLIT =               0b0010_0000_0000_0000;
         const NEAREST =             0b0100_0000_0000_0000;
+        const DOUBLE_SIDED =        0b1000_0000_0000_0000;
+        const PARALLAX_REFINE =   0b1_0000_0000_0000_0000;
+        const HAIR =             0b10_0000_0000_0000_0000;
     }
 }
 
@@ -406,6 +409,193 @@ impl Default for ClearcoatTextures {
     }
 }
 
+/// Parallax occlusion mapping, which shifts the texture coordinates of every
+/// texture of the material along the view direction as if the surface had
+/// the depth of a height map, without adding any geometry.
+///
+/// Needs tangents on the mesh.
+#[derive(Debug, Clone)]
+pub struct Parallax {
+    /// Height map in the red channel, where 1.0 is the surface of the mesh
+    /// and 0.0 is `scale` below it.
+    pub texture: TextureHandle,
+    /// Depth of the height map in texture coordinates, usually around 0.05.
+    pub scale: f32,
+    /// Number of layers the height map is marched in. More steps cost more
+    /// texture reads but avoid stair stepping at grazing angles.
+    pub steps: u32,
+    /// Interpolates between the last two steps to find where the view ray
+    /// hits the height map, smoothing out the layers for one extra read.
+    pub contact_refinement: bool,
+}
+
+/// Textures tiled at a higher frequency than the rest of the material, adding
+/// fine detail up close to large surfaces.
+#[derive(Debug, Clone)]
+pub struct DetailTextures {
+    /// Multiplied with the albedo, scaled so mid gray leaves it unchanged.
+    /// Holds data, not color, so it must be in a linear format.
+    pub albedo: Option<TextureHandle>,
+    /// Tricomponent, y up normal map blended onto the normal map of the
+    /// material.
+    pub normal: Option<TextureHandle>,
+    /// Repetitions of the detail textures per repetition of the other
+    /// textures.
+    pub tiling: Vec2,
+    /// Offset of the detail textures, in detail texture coordinates.
+    pub offset: Vec2,
+}
+
+/// Highlights stretched across a direction, like on brushed metal, vinyl
+/// records and satin. Follows `KHR_materials_anisotropy`.
+///
+/// Needs tangents on the mesh.
+#[derive(Debug, Clone)]
+pub struct Anisotropy {
+    /// From 0, round highlights, to 1, highlights stretched into lines.
+    pub strength: f32,
+    /// Radians from the tangent towards the bitangent, the direction the
+    /// highlights stretch across.
+    pub rotation: f32,
+    /// Red and green hold a tangent space direction, which is rotated by
+    /// `rotation`, and blue multiplies the strength. Holds data, not color,
+    /// so it must be in a linear format.
+    pub texture: Option<TextureHandle>,
+}
+
+/// Soft highlight at grazing angles from the fibers of cloth like velvet,
+/// layered over the rest of the material. Follows the factors of
+/// `KHR_materials_sheen`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sheen {
+    /// Linear color of the sheen. Black turns it off.
+    pub color: Vec3,
+    /// Perceptual roughness of the sheen, clamped to at least 0.07.
+    pub roughness: f32,
+}
+
+/// Light passing through the surface and refracting, for glass, water and
+/// other liquids. Follows the factors of `KHR_materials_transmission`,
+/// `KHR_materials_ior` and `KHR_materials_volume`.
+///
+/// Only used by [`Transparency::Blend`] materials, and only drawn when a
+/// [`TransmissionRoutine`](crate::transmission::TransmissionRoutine) is in
+/// the rendergraph. It draws what is behind the surface, distorted and
+/// tinted, then the surface itself is blended over it with the alpha of its
+/// albedo, so clear glass has an alpha close to 0.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transmission {
+    /// How much of what is behind shows through, from 0 to 1.
+    pub factor: f32,
+    /// Index of refraction, 1.5 for glass and 1.33 for water.
+    pub ior: f32,
+    /// How far light travels through the volume under the surface, in world
+    /// units. 0 is a thin wall, which doesn't bend light.
+    pub thickness: f32,
+    /// Linear color white light becomes after going `attenuation_distance`
+    /// through the volume.
+    pub attenuation_color: Vec3,
+    /// `f32::INFINITY` absorbs nothing.
+    pub attenuation_distance: f32,
+}
+
+impl Default for Transmission {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            ior: 1.5,
+            thickness: 0.0,
+            attenuation_color: Vec3::ONE,
+            attenuation_distance: f32::INFINITY,
+        }
+    }
+}
+
+/// Light scattering under the surface, softening the lighting of skin, wax
+/// and marble.
+///
+/// The lighting is blurred in screen space by a
+/// [`SubsurfaceRoutine`](crate::subsurface::SubsurfaceRoutine), which must be
+/// in the rendergraph. Ignored by blended materials.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SubsurfaceScattering {
+    /// Index of the [`DiffusionProfile`](crate::subsurface::DiffusionProfile)
+    /// of the routine, deciding how far each color of light spreads.
+    pub profile: u8,
+}
+
+/// Kajiya-Kay shading of hair and fur: a white highlight and a second one
+/// tinted by the albedo, both stretched across the strands.
+///
+/// Strands run along the tangents of the mesh, so it needs tangents. The
+/// highlights are shifted along the normal, which moves them towards the
+/// root or the tip depending on which way the tangents point.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HairShading {
+    pub primary_shift: f32,
+    pub secondary_shift: f32,
+    /// Sharpness of the white highlight, higher being tighter.
+    pub primary_exponent: f32,
+    /// Sharpness of the tinted highlight, usually softer than the white one.
+    pub secondary_exponent: f32,
+}
+
+impl Default for HairShading {
+    fn default() -> Self {
+        Self {
+            primary_shift: 0.1,
+            secondary_shift: -0.1,
+            primary_exponent: 80.0,
+            secondary_exponent: 16.0,
+        }
+    }
+}
+
+/// Hair and fur made of layered alpha tested cards, drawn as two objects
+/// sharing a mesh: a core alpha tested at `cutout` which writes depth, and
+/// the soft fringe blended over it.
+///
+/// Cards are authored from the scalp outwards. Inside one mesh they are
+/// drawn in index order, and meshes of separate layers should use
+/// [`HairCards::layer_sort_key`], as their distances to the camera are about
+/// the same.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HairCards {
+    /// Alpha above which cards are part of the core. Higher values leave more
+    /// to the blended fringe, which has no depth to sort it.
+    pub cutout: f32,
+}
+
+impl Default for HairCards {
+    fn default() -> Self {
+        Self { cutout: 0.9 }
+    }
+}
+
+impl HairCards {
+    /// Double sided materials of the core and of the fringe, in that order.
+    pub fn materials(&self, material: PbrMaterial) -> [PbrMaterial; 2] {
+        let core = PbrMaterial {
+            transparency: Transparency::Cutout { cutout: self.cutout },
+            double_sided: true,
+            ..material.clone()
+        };
+        let fringe = PbrMaterial {
+            transparency: Transparency::Blend,
+            double_sided: true,
+            ..material
+        };
+        [core, fringe]
+    }
+
+    /// Sort key for the objects of card layer `layer`, counting from the
+    /// innermost, so fringes blend from the inside out. See
+    /// [`Renderer::set_object_sort_key`](rend3::Renderer::set_object_sort_key).
+    pub fn layer_sort_key(layer: u16) -> i32 {
+        i32::from(layer)
+    }
+}
+
 /// How textures should be sampled.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SampleType {
@@ -447,6 +637,11 @@ impl TransparencyType {
         }
     }
 
+    /// Key of the objects with this transparency in the object manager.
+    pub fn to_object_key(self, double_sided: bool) -> u64 {
+        self as u64 | (double_sided as u64) << 8
+    }
+
     pub fn to_sorting(self) -> Option<Sorting> {
         match self {
             Self::Opaque => None,
@@ -491,7 +686,7 @@ impl Default for Transparency {
 // - Green screen value
 /// A set of textures and values that determine the how an object interacts with
 /// light.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct PbrMaterial {
     pub albedo: AlbedoComponent,
     pub transparency: Transparency,
@@ -503,22 +698,44 @@ pub struct PbrMaterial {
     pub clearcoat_textures: ClearcoatTextures,
     pub clearcoat_factor: Option<f32>,
     pub clearcoat_roughness_factor: Option<f32>,
+    /// Emitted light, added after lighting. Values may go above 1.0, as the
+    /// scene is rendered in HDR and only clamped by tonemapping.
     pub emissive: MaterialComponent<Vec3>,
+    /// Multiplier of `emissive`, for glowing surfaces far brighter than
+    /// their texture can hold. Defaults to 1.0.
+    pub emissive_intensity: Option<f32>,
     pub reflectance: MaterialComponent<f32>,
-    pub anisotropy: MaterialComponent<f32>,
+    pub anisotropy: Option<Anisotropy>,
+    /// Transform of the texture coordinates of every texture, such as the
+    /// tiling and offset given by [`Mat3::from_scale_angle_translation`].
     pub uv_transform0: Mat3,
     pub uv_transform1: Mat3,
     // TODO: Determine how to make this a clearer part of the type system, esp. with the changable_struct macro.
     pub unlit: bool,
     pub sample_type: SampleType,
+    /// Draws back faces too, with their normal flipped to face the viewer,
+    /// for thin geometry like leaves, cloth and paper.
+    pub double_sided: bool,
+    /// Offsets texture coordinates by a height map for depth detail like
+    /// bricks and rocks.
+    pub parallax: Option<Parallax>,
+    pub detail: Option<DetailTextures>,
+    pub subsurface: Option<SubsurfaceScattering>,
+    /// Shades the material as hair or fur instead, see [`HairCards`] for
+    /// card based hair.
+    pub hair: Option<HairShading>,
+    pub sheen: Option<Sheen>,
+    pub transmission: Option<Transmission>,
 }
 
 impl Material for PbrMaterial {
-    const TEXTURE_COUNT: u32 = 10;
+    const TEXTURE_COUNT: u32 = 13;
     const DATA_SIZE: u32 = mem::size_of::<ShaderMaterial>() as _;
+    /// Everything but albedo and emissive.
+    const DATA_TEXTURES: u32 = !(1 | 1 << 7) & 0b1_1111_1111_1111;
 
     fn object_key(&self) -> u64 {
-        TransparencyType::from(self.transparency) as u64
+        TransparencyType::from(self.transparency).to_object_key(self.double_sided)
     }
 
     fn to_textures<'a>(&'a self, slice: &mut [Option<&'a TextureHandle>]) {
@@ -530,8 +747,14 @@ impl Material for PbrMaterial {
         slice[5] = self.clearcoat_textures.to_clearcoat_texture();
         slice[6] = self.clearcoat_textures.to_clearcoat_roughness_texture();
         slice[7] = self.emissive.to_texture();
-        slice[8] = self.anisotropy.to_texture();
+        slice[8] = self
+            .anisotropy
+            .as_ref()
+            .and_then(|anisotropy| anisotropy.texture.as_ref());
         slice[9] = self.aomr_textures.to_ao_texture();
+        slice[10] = self.parallax.as_ref().map(|parallax| &parallax.texture);
+        slice[11] = self.detail.as_ref().and_then(|detail| detail.albedo.as_ref());
+        slice[12] = self.detail.as_ref().and_then(|detail| detail.normal.as_ref());
     }
 
     fn to_data(&self, slice: &mut [u8]) {
@@ -553,6 +776,19 @@ fn cutout_offset() {
     assert_eq!(bytemuck::offset_of!(ShaderMaterial, uv_transform0), 0);
 }
 
+#[test]
+fn extension_offsets() {
+    // Must match the material structs in structures.glsl.
+    assert_eq!(bytemuck::offset_of!(ShaderMaterial, parallax_scale), 160);
+    assert_eq!(bytemuck::offset_of!(ShaderMaterial, subsurface_profile), 168);
+    assert_eq!(bytemuck::offset_of!(ShaderMaterial, detail_transform), 176);
+    assert_eq!(bytemuck::offset_of!(ShaderMaterial, hair), 192);
+    assert_eq!(bytemuck::offset_of!(ShaderMaterial, sheen), 208);
+    assert_eq!(bytemuck::offset_of!(ShaderMaterial, transmission), 224);
+    assert_eq!(bytemuck::offset_of!(ShaderMaterial, attenuation), 240);
+    assert_eq!(mem::size_of::<ShaderMaterial>(), 256);
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 struct ShaderMaterial {
@@ -571,6 +807,25 @@ struct ShaderMaterial {
     alpha_cutout: f32,
 
     material_flags: MaterialFlags,
+
+    parallax_scale: f32,
+    parallax_steps: u32,
+    // Profile index + 1, 0 is none.
+    subsurface_profile: u32,
+    anisotropy_rotation: f32,
+
+    // xy tiling, zw offset
+    detail_transform: Vec4,
+
+    // Shifts, then exponents
+    hair: Vec4,
+
+    // Color, then roughness
+    sheen: Vec4,
+
+    // Factor, ior, thickness, attenuation distance (0 absorbs nothing)
+    transmission: Vec4,
+    attenuation: Vec4,
 }
 
 unsafe impl bytemuck::Zeroable for ShaderMaterial {}
@@ -587,8 +842,11 @@ impl ShaderMaterial {
             reflectance: material.reflectance.to_value(0.5),
             clear_coat: material.clearcoat_factor.unwrap_or(0.0),
             clear_coat_roughness: material.clearcoat_roughness_factor.unwrap_or(0.0),
-            emissive: material.emissive.to_value(Vec3::ZERO),
-            anisotropy: material.anisotropy.to_value(0.0),
+            emissive: material.emissive.to_value(Vec3::ZERO) * material.emissive_intensity.unwrap_or(1.0),
+            anisotropy: material
+                .anisotropy
+                .as_ref()
+                .map_or(0.0, |anisotropy| anisotropy.strength),
             ambient_occlusion: material.ao_factor.unwrap_or(1.0),
             alpha_cutout: match material.transparency {
                 Transparency::Cutout { cutout } => cutout,
@@ -607,8 +865,59 @@ impl ShaderMaterial {
                         SampleType::Linear => false,
                     },
                 );
+                flags.set(MaterialFlags::DOUBLE_SIDED, material.double_sided);
+                flags.set(MaterialFlags::HAIR, material.hair.is_some());
+                flags.set(
+                    MaterialFlags::PARALLAX_REFINE,
+                    material
+                        .parallax
+                        .as_ref()
+                        .map_or(false, |parallax| parallax.contact_refinement),
+                );
                 flags
             },
+            parallax_scale: material.parallax.as_ref().map_or(0.0, |parallax| parallax.scale),
+            parallax_steps: material.parallax.as_ref().map_or(0, |parallax| parallax.steps),
+            subsurface_profile: match (material.subsurface, material.transparency) {
+                (_, Transparency::Blend) | (None, _) => 0,
+                (Some(subsurface), _) => subsurface.profile as u32 + 1,
+            },
+            anisotropy_rotation: material
+                .anisotropy
+                .as_ref()
+                .map_or(0.0, |anisotropy| anisotropy.rotation),
+            detail_transform: material
+                .detail
+                .as_ref()
+                .map_or(Vec4::new(1.0, 1.0, 0.0, 0.0), |detail| {
+                    Vec4::new(detail.tiling.x, detail.tiling.y, detail.offset.x, detail.offset.y)
+                }),
+            hair: material.hair.map_or(Vec4::ZERO, |hair| {
+                Vec4::new(
+                    hair.primary_shift,
+                    hair.secondary_shift,
+                    hair.primary_exponent,
+                    hair.secondary_exponent,
+                )
+            }),
+            sheen: material
+                .sheen
+                .map_or(Vec4::ZERO, |sheen| sheen.color.extend(sheen.roughness)),
+            transmission: match (material.transmission, material.transparency) {
+                (Some(transmission), Transparency::Blend) => Vec4::new(
+                    transmission.factor,
+                    transmission.ior,
+                    transmission.thickness,
+                    match transmission.attenuation_distance.is_finite() {
+                        true => transmission.attenuation_distance,
+                        false => 0.0,
+                    },
+                ),
+                _ => Vec4::ZERO,
+            },
+            attenuation: material
+                .transmission
+                .map_or(Vec4::ONE, |transmission| transmission.attenuation_color.extend(1.0)),
         }
     }
 }
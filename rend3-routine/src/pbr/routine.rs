@@ -1,24 +1,84 @@
-use rend3::{Renderer, RendererDataCore};
-use wgpu::{BlendState, Features};
+use rend3::{managers::SkinningMode, types::ObjectDepthMode, Renderer, RendererDataCore};
+use wgpu::{BlendState, CompareFunction, DepthBiasState, Features};
 
 use crate::{
-    common::{PerMaterialArchetypeInterface, WholeFrameInterfaces},
+    common::{
+        hooked_shader, HookedStage, ObjectPass, PerMaterialArchetypeInterface, RenderStateOverride,
+        RenderStateOverrides, ShaderHooks, WholeFrameInterfaces,
+    },
     depth::DepthRoutine,
     forward::ForwardRoutine,
     pbr::{PbrMaterial, TransparencyType},
 };
 
+const DEPTH_MODES: [ObjectDepthMode; 3] = [
+    ObjectDepthMode::Default,
+    ObjectDepthMode::Biased,
+    ObjectDepthMode::AlwaysOnTop,
+];
+
+/// Pipeline state drawing objects in `depth_mode` needs.
+pub(crate) fn depth_mode_override(renderer: &Renderer, depth_mode: ObjectDepthMode) -> RenderStateOverride {
+    // Biased objects are pulled towards the camera, which is towards the near depth.
+    let sign = match renderer.depth_mode.is_reversed() {
+        true => 1,
+        false => -1,
+    };
+    match depth_mode {
+        ObjectDepthMode::Default => RenderStateOverride::default(),
+        ObjectDepthMode::Biased => RenderStateOverride {
+            depth_bias: Some(DepthBiasState {
+                constant: sign * 4,
+                slope_scale: sign as f32 * 2.0,
+                clamp: 0.0,
+            }),
+            ..RenderStateOverride::default()
+        },
+        ObjectDepthMode::AlwaysOnTop => RenderStateOverride {
+            depth_compare: Some(CompareFunction::Always),
+            depth_write_enabled: Some(false),
+            ..RenderStateOverride::default()
+        },
+    }
+}
+
 /// Render routine that renders the using PBR materials
 pub struct PbrRoutine {
-    pub opaque_routine: ForwardRoutine<PbrMaterial>,
-    pub cutout_routine: ForwardRoutine<PbrMaterial>,
-    pub blend_routine: ForwardRoutine<PbrMaterial>,
-    pub depth_pipelines: DepthRoutine<PbrMaterial>,
+    /// Routines of every transparency, sidedness and [`ObjectDepthMode`],
+    /// looked up with [`PbrRoutine::forward_routine`].
+    pub forward_routines: Vec<ForwardRoutine<PbrMaterial>>,
+    /// Routines of every sidedness and depth mode writing depth, looked up with
+    /// [`PbrRoutine::depth_routine`].
+    pub depth_routines: Vec<DepthRoutine<PbrMaterial>>,
     pub per_material: PerMaterialArchetypeInterface<PbrMaterial>,
 }
 
 impl PbrRoutine {
     pub fn new(renderer: &Renderer, data_core: &mut RendererDataCore, interfaces: &WholeFrameInterfaces) -> Self {
+        Self::new_with_hooks(renderer, data_core, interfaces, &ShaderHooks::default())
+    }
+
+    /// Creates the routine with `hooks` spliced into its shaders, see
+    /// [`ShaderHooks`].
+    pub fn new_with_hooks(
+        renderer: &Renderer,
+        data_core: &mut RendererDataCore,
+        interfaces: &WholeFrameInterfaces,
+        hooks: &ShaderHooks,
+    ) -> Self {
+        Self::new_with_overrides(renderer, data_core, interfaces, hooks, &RenderStateOverrides::default())
+    }
+
+    /// Creates the routine with `hooks` spliced into its shaders, and
+    /// `overrides` replacing parts of the pipeline state of its passes, see
+    /// [`RenderStateOverrides`].
+    pub fn new_with_overrides(
+        renderer: &Renderer,
+        data_core: &mut RendererDataCore,
+        interfaces: &WholeFrameInterfaces,
+        hooks: &ShaderHooks,
+        overrides: &RenderStateOverrides,
+    ) -> Self {
         profiling::scope!("PbrRenderRoutine::new");
 
         // This ensures the BGLs for the material are created
@@ -28,45 +88,159 @@ impl PbrRoutine {
 
         let unclipped_depth_supported = renderer.features.contains(Features::DEPTH_CLIP_CONTROL);
 
-        let per_material = PerMaterialArchetypeInterface::<PbrMaterial>::new(&renderer.device, renderer.profile);
-
-        let depth_pipelines = DepthRoutine::<PbrMaterial>::new(
-            renderer,
-            data_core,
-            interfaces,
-            &per_material,
-            unclipped_depth_supported,
+        let per_material = PerMaterialArchetypeInterface::<PbrMaterial>::new(
+            &renderer.device,
+            renderer.profile,
+            renderer.skinning_mode,
         );
 
-        let mut inner = |transparency| {
+        // Double sided materials cull nothing, and the depth mode of the
+        // objects applies to all passes but shadows, whatever the overrides say.
+        let archetype_overrides = |double_sided: bool, depth_mode| {
+            let double_sided_override = RenderStateOverride {
+                cull_mode: double_sided.then(|| None),
+                ..RenderStateOverride::default()
+            };
+            let overrides = overrides.clone().with_pass(ObjectPass::Shadow, double_sided_override);
+            [
+                ObjectPass::Prepass,
+                ObjectPass::Opaque,
+                ObjectPass::Cutout,
+                ObjectPass::Blend,
+            ]
+            .into_iter()
+            .fold(overrides, |overrides, pass| {
+                overrides.with_pass(
+                    pass,
+                    RenderStateOverride {
+                        cull_mode: double_sided_override.cull_mode,
+                        ..depth_mode_override(renderer, depth_mode)
+                    },
+                )
+            })
+        };
+
+        let mut depth_routines = Vec::with_capacity(4);
+        for depth_mode in [ObjectDepthMode::Default, ObjectDepthMode::Biased] {
+            for double_sided in [false, true] {
+                depth_routines.push(DepthRoutine::<PbrMaterial>::new(
+                    renderer,
+                    data_core,
+                    interfaces,
+                    &per_material,
+                    unclipped_depth_supported,
+                    hooks,
+                    &archetype_overrides(double_sided, depth_mode),
+                ));
+            }
+        }
+
+        // The shaders the forward routine loads by default, with the hooks spliced in.
+        let (forward_vert_cpu, forward_vert_gpu) = match renderer.skinning_mode {
+            SkinningMode::PreSkinned => ("opaque.vert.cpu.wgsl", "opaque.vert.gpu.spv"),
+            SkinningMode::VertexShader => ("opaque-skinned.vert.cpu.wgsl", "opaque-skinned.vert.gpu.spv"),
+        };
+        let forward_vert = unsafe {
+            hooked_shader(
+                &renderer.device,
+                renderer.profile,
+                "forward pass vert",
+                forward_vert_cpu,
+                forward_vert_gpu,
+                "opaque.vert.downlevel.wgsl",
+                HookedStage::Vertex,
+                hooks,
+            )
+        };
+        let forward_frag = unsafe {
+            hooked_shader(
+                &renderer.device,
+                renderer.profile,
+                "forward pass frag",
+                "opaque.frag.cpu.wgsl",
+                "opaque.frag.gpu.spv",
+                "opaque.frag.downlevel.wgsl",
+                HookedStage::ForwardFragment,
+                hooks,
+            )
+        };
+
+        let mut inner = |transparency, double_sided: bool, depth_mode| {
+            let on_top = depth_mode == ObjectDepthMode::AlwaysOnTop;
+            let label = match transparency {
+                TransparencyType::Opaque => "opaque pass",
+                TransparencyType::Cutout => "cutout pass",
+                TransparencyType::Blend => "blend forward pass",
+            };
+            let label = match double_sided {
+                true => format!("double sided {}", label),
+                false => label.to_string(),
+            };
+            let label = match depth_mode {
+                ObjectDepthMode::Default => label,
+                ObjectDepthMode::Biased => format!("biased {}", label),
+                ObjectDepthMode::AlwaysOnTop => format!("always on top {}", label),
+            };
             ForwardRoutine::new(
                 renderer,
                 data_core,
                 interfaces,
                 &per_material,
-                None,
-                None,
+                Some(("main", &forward_vert)),
+                Some(("main", &forward_frag)),
                 &[],
-                match transparency {
-                    TransparencyType::Opaque | TransparencyType::Cutout => None,
-                    TransparencyType::Blend => Some(BlendState::ALPHA_BLENDING),
+                // Nothing discards the cut out parts without a prepass, so they are blended away.
+                match (transparency, on_top) {
+                    (TransparencyType::Opaque, _) | (TransparencyType::Cutout, false) => None,
+                    (TransparencyType::Cutout, true) | (TransparencyType::Blend, _) => Some(BlendState::ALPHA_BLENDING),
                 },
-                !matches!(transparency, TransparencyType::Blend),
+                !matches!(transparency, TransparencyType::Blend) && !on_top,
                 wgpu::PrimitiveTopology::TriangleList,
-                match transparency {
-                    TransparencyType::Opaque => "opaque pass",
-                    TransparencyType::Cutout => "cutout pass",
-                    TransparencyType::Blend => "blend forward pass",
-                },
+                archetype_overrides(double_sided, depth_mode).get(match transparency {
+                    TransparencyType::Opaque => ObjectPass::Opaque,
+                    TransparencyType::Cutout => ObjectPass::Cutout,
+                    TransparencyType::Blend => ObjectPass::Blend,
+                }),
+                &label,
             )
         };
 
+        let mut forward_routines = Vec::with_capacity(DEPTH_MODES.len() * 6);
+        for depth_mode in DEPTH_MODES {
+            for transparency in [
+                TransparencyType::Opaque,
+                TransparencyType::Cutout,
+                TransparencyType::Blend,
+            ] {
+                for double_sided in [false, true] {
+                    forward_routines.push(inner(transparency, double_sided, depth_mode));
+                }
+            }
+        }
+
         Self {
-            opaque_routine: inner(TransparencyType::Opaque),
-            cutout_routine: inner(TransparencyType::Cutout),
-            blend_routine: inner(TransparencyType::Blend),
-            depth_pipelines,
+            forward_routines,
+            depth_routines,
             per_material,
         }
     }
+
+    /// The forward routine drawing objects in `depth_mode` with materials of
+    /// `transparency`.
+    pub fn forward_routine(
+        &self,
+        transparency: TransparencyType,
+        double_sided: bool,
+        depth_mode: ObjectDepthMode,
+    ) -> &ForwardRoutine<PbrMaterial> {
+        &self.forward_routines[(depth_mode as usize * 3 + transparency as usize) * 2 + double_sided as usize]
+    }
+
+    /// The routine drawing the shadows and depth prepass of objects in
+    /// `depth_mode`. [`ObjectDepthMode::AlwaysOnTop`] objects write no depth,
+    /// so they get the routine of [`ObjectDepthMode::Default`].
+    pub fn depth_routine(&self, double_sided: bool, depth_mode: ObjectDepthMode) -> &DepthRoutine<PbrMaterial> {
+        let biased = depth_mode == ObjectDepthMode::Biased;
+        &self.depth_routines[biased as usize * 2 + double_sided as usize]
+    }
 }
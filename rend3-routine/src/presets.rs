@@ -0,0 +1,256 @@
+//! Minimal rendergraphs built from the pieces of the [`BaseRenderGraph`],
+//! for when a full frame is more than needed.
+//!
+//! - [`BaseRenderGraph::add_depth_only_to_graph`] renders the depth of the
+//!   PBR materials and writes it to the output, e.g. for picking or depth
+//!   captures of a minimap.
+//! - [`BaseRenderGraph::add_shadows_to_graph`] only renders the shadow maps of
+//!   the directional lights.
+//! - [`BaseRenderGraph::add_forward_to_graph`] renders the scene into the HDR
+//!   targets, and leaves them to the caller instead of tonemapping them.
+
+use rend3::{
+    graph::{ReadyData, RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    types::SampleCount,
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer,
+};
+use wgpu::{
+    BindGroupLayout, BindingType, Color, ColorTargetState, ColorWrites, FragmentState, FrontFace, MultisampleState,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType, TextureViewDimension,
+    VertexState,
+};
+
+use crate::{
+    base::{BaseRenderGraph, BaseRenderGraphIntermediateState, BaseRenderGraphSettings},
+    pbr, shaders, skybox,
+};
+
+/// Writes a single sampled depth target to the output, the raw depth value
+/// in every color channel.
+///
+/// Use a float output format like `R32Float` to keep the precision.
+pub struct DepthOutputRoutine {
+    pipeline: RenderPipeline,
+    bgl: BindGroupLayout,
+}
+
+impl DepthOutputRoutine {
+    pub fn new(renderer: &Renderer, output_format: TextureFormat) -> Self {
+        profiling::scope!("DepthOutputRoutine::new");
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .build(&renderer.device, Some("depth output bgl"));
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("depth output"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "depth_output.wgsl",
+                include_str!("../shaders/src/depth_output.wgsl"),
+            )),
+        });
+
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("depth output"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("depth output"),
+            layout: Some(&pll),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Cw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[ColorTargetState {
+                    format: output_format,
+                    blend: None,
+                    write_mask: ColorWrites::all(),
+                }],
+            }),
+            multiview: None,
+        });
+
+        Self { pipeline, bgl }
+    }
+
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        depth: RenderTargetHandle,
+        output: RenderTargetHandle,
+    ) {
+        let mut builder = graph.add_node("Depth Output");
+
+        let depth_handle = builder.add_render_target_input(depth);
+        let output_handle = builder.add_render_target_output(output);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: output_handle,
+                clear: Color::BLACK,
+                resolve: None,
+            }],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+            let depth = graph_data.get_render_target(depth_handle);
+
+            let bg = temps.add(BindGroupBuilder::new().append_texture_view(depth).build(
+                &renderer.device,
+                Some("depth output bg"),
+                &this.bgl,
+            ));
+
+            rpass.set_pipeline(&this.pipeline);
+            rpass.set_bind_group(0, bg, &[]);
+            rpass.draw(0..3, 0..1);
+        });
+    }
+}
+
+impl BaseRenderGraph {
+    /// Render the depth of the opaque and cutout PBR materials, and write it
+    /// to the output with `depth_output`.
+    ///
+    /// Only the resolution and depth format of `settings` are used. Returns
+    /// the intermediate state, whose depth target holds the depth.
+    pub fn add_depth_only_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        ready: &ReadyData,
+        pbr: &'node pbr::PbrRoutine,
+        depth_output: &'node DepthOutputRoutine,
+        settings: &BaseRenderGraphSettings,
+    ) -> BaseRenderGraphIntermediateState {
+        let settings = BaseRenderGraphSettings {
+            samples: SampleCount::One,
+            supersampling: 1,
+            ..*settings
+        };
+
+        self.draw_statistics.reset();
+        let state = BaseRenderGraphIntermediateState::new(graph, ready, &settings);
+
+        state.pre_skinning(graph);
+        state.pbr_pre_culling(graph);
+        state.create_frame_uniforms(graph, self, settings.ambient, settings.render_resolution());
+        state.skinning(graph, self);
+        state.pbr_culling(graph, self, pbr);
+        state.clear(graph, self, settings.clear_color);
+        state.pbr_prepass_rendering(graph, pbr, SampleCount::One);
+
+        let surface = graph.add_surface_texture();
+        depth_output.add_to_graph(graph, state.depth, surface);
+
+        state
+    }
+
+    /// Render only the shadow maps of the directional lights, into the shadow
+    /// atlas of the renderer. Only the resolution of `settings` is used.
+    pub fn add_shadows_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        ready: &ReadyData,
+        pbr: &'node pbr::PbrRoutine,
+        settings: &BaseRenderGraphSettings,
+    ) -> BaseRenderGraphIntermediateState {
+        self.draw_statistics.reset();
+        let state = BaseRenderGraphIntermediateState::new(graph, ready, settings);
+
+        state.pre_skinning(graph);
+        state.pbr_pre_culling(graph);
+        state.create_frame_uniforms(graph, self, settings.ambient, settings.render_resolution());
+        state.skinning(graph, self);
+        state.pbr_shadow_culling(graph, self, pbr);
+        state.pbr_shadow_rendering(graph, pbr);
+
+        // Nothing in this graph reads the shadow maps, so mark them as used
+        // outside of it to keep the nodes rendering them.
+        let mut builder = graph.add_node("Keep Shadows");
+        for shadow_index in 0..state.shadow_uniform_bg.len() {
+            builder.add_shadow_output(shadow_index);
+        }
+        builder.add_external_output();
+        builder.build(|_, _, _, _, _, _| ());
+
+        state
+    }
+
+    /// Render the PBR materials and the skybox into the HDR targets, without
+    /// the post phase or tonemapping.
+    ///
+    /// Returns the intermediate state with the HDR targets. Nodes left without
+    /// a node reading their targets get culled, so read
+    /// [`BaseRenderGraphIntermediateState::color`], or its resolve target with
+    /// MSAA, or its downsampled target when supersampling, in a node of your
+    /// own.
+    pub fn add_forward_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        ready: &ReadyData,
+        pbr: &'node pbr::PbrRoutine,
+        skybox: Option<&'node skybox::SkyboxRoutine>,
+        settings: &BaseRenderGraphSettings,
+    ) -> BaseRenderGraphIntermediateState {
+        let samples = settings.samples;
+
+        self.draw_statistics.reset();
+        let state = BaseRenderGraphIntermediateState::new(graph, ready, settings);
+
+        state.pre_skinning(graph);
+        state.pbr_pre_culling(graph);
+        state.create_frame_uniforms(graph, self, settings.ambient, settings.render_resolution());
+        state.skinning(graph, self);
+        if !settings.skip_shadows {
+            state.pbr_shadow_culling(graph, self, pbr);
+        }
+        state.pbr_culling(graph, self, pbr);
+        state.clear(graph, self, settings.clear_color);
+
+        match settings.skip_shadows {
+            true => state.clear_shadows(graph, self),
+            false => state.pbr_shadow_rendering(graph, pbr),
+        }
+        state.pbr_prepass_rendering(graph, pbr, samples);
+        state.skybox(graph, skybox, samples);
+        state.pbr_forward_rendering_opaque(graph, pbr, samples);
+        if !settings.skip_transparency {
+            state.pbr_forward_rendering_transparent(graph, pbr, samples);
+        }
+        state.supersample(graph, self);
+
+        state
+    }
+}
@@ -0,0 +1,200 @@
+//! Planar reflections for mirrors and water surfaces.
+//!
+//! The scene is rendered a second time from the main camera mirrored about
+//! the plane of the reflector, into a texture which materials of the
+//! reflector can use like any other texture.
+
+use std::sync::Arc;
+
+use glam::{Mat3, Mat4, UVec2, Vec2, Vec3, Vec4};
+use rend3::{
+    graph::RenderGraph,
+    types::{Camera, CameraProjection, Handedness, SampleCount, TextureFormat, TextureHandle},
+    util::output::OutputFrame,
+    Renderer, RendererError,
+};
+use wgpu::TextureView;
+
+use crate::{base::BaseRenderGraph, pbr::PbrRoutine, skybox::SkyboxRoutine, tonemapping::TonemappingRoutine};
+
+/// Format reflections are rendered in.
+pub const REFLECTION_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Rectangle on the plane of a reflector, which the reflection is rendered
+/// to fill.
+///
+/// For a water surface extending past the view, use the part of it that can
+/// be seen, as the whole rectangle shares the resolution of the texture.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ReflectionPlane {
+    pub center: Vec3,
+    /// Direction the reflecting side faces.
+    pub normal: Vec3,
+    /// Direction in the plane which is up in the texture. Doesn't need to be
+    /// perpendicular to the normal.
+    pub up: Vec3,
+    /// Width and height of the rectangle.
+    pub size: Vec2,
+}
+
+impl ReflectionPlane {
+    fn normal(&self) -> Vec3 {
+        self.normal.normalize()
+    }
+
+    /// Mirrors points about the plane.
+    pub fn reflection_matrix(&self) -> Mat4 {
+        let normal = self.normal();
+        let distance = normal.dot(self.center);
+        let linear = Mat3::IDENTITY - 2.0 * Mat3::from_cols(normal * normal.x, normal * normal.y, normal * normal.z);
+        Mat4::from_cols(
+            linear.x_axis.extend(0.0),
+            linear.y_axis.extend(0.0),
+            linear.z_axis.extend(0.0),
+            (2.0 * distance * normal).extend(1.0),
+        )
+    }
+
+    /// Camera seeing the reflection of the scene the way `eye` sees it in
+    /// the rectangle, or `None` if `eye` is behind the plane.
+    ///
+    /// The camera looks through the rectangle from the mirrored eye, so its
+    /// frustum is off-center and its near plane lies in the reflector,
+    /// clipping away everything behind it. `near_offset` pushes the near
+    /// plane further back, so the reflector itself isn't seen.
+    pub fn reflection_camera(&self, eye: Vec3, handedness: Handedness, near_offset: f32) -> Option<Camera> {
+        let normal = self.normal();
+        let distance = normal.dot(eye - self.center);
+        if distance <= 0.0 {
+            return None;
+        }
+
+        // Looking at the back of the plane keeps the view a rotation, so the
+        // winding of triangles is preserved. The texture ends up mirrored
+        // horizontally, which `REFLECTION_UV_TRANSFORM` undoes.
+        let mirrored_eye = eye - 2.0 * distance * normal;
+        let up = (self.up - normal * normal.dot(self.up)).normalize();
+        let view = match handedness {
+            Handedness::Left => Mat4::look_at_lh(mirrored_eye, mirrored_eye + normal, up),
+            Handedness::Right => Mat4::look_at_rh(mirrored_eye, mirrored_eye + normal, up),
+        };
+
+        let center = view.transform_point3(self.center);
+        let right = view.transform_vector3(up.cross(normal).normalize() * self.size.x * 0.5);
+        let top = view.transform_vector3(up * self.size.y * 0.5);
+        let (min, max) = [right + top, right - top, -right + top, -right - top]
+            .into_iter()
+            .map(|corner| (center + corner).truncate())
+            .fold((Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)), |(min, max), corner| {
+                (min.min(corner), max.max(corner))
+            });
+
+        Some(Camera {
+            projection: CameraProjection::Asymmetric {
+                left: (min.x / distance).atan(),
+                right: (max.x / distance).atan(),
+                up: (max.y / distance).atan(),
+                down: (min.y / distance).atan(),
+                near: distance + near_offset,
+            },
+            view,
+        })
+    }
+}
+
+/// Transform for the uvs of a material sampling a reflection, for a
+/// reflector mesh whose uvs go from (0, 0) at the top left of its
+/// [`ReflectionPlane`] to (1, 1) at the bottom right, as seen from the front.
+pub const REFLECTION_UV_TRANSFORM: Mat3 = glam::const_mat3!([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 1.0]);
+
+/// A planar reflection, rendered into a texture of its own with the default
+/// rendergraph.
+///
+/// Use [`PlanarReflection::texture`] in the material of the reflector, e.g.
+/// as the albedo of a mirror or blended into the albedo of water, with
+/// [`REFLECTION_UV_TRANSFORM`] as its uv transform. Every frame, call
+/// [`PlanarReflection::render`] before rendering the main view.
+pub struct PlanarReflection {
+    pub plane: ReflectionPlane,
+    /// Distance the near plane is pushed behind the reflector.
+    pub near_offset: f32,
+    tonemapping: TonemappingRoutine,
+    texture: TextureHandle,
+    view: Arc<TextureView>,
+    resolution: UVec2,
+}
+
+impl PlanarReflection {
+    /// Creates a reflection rendered at `resolution`. Fails if the device
+    /// can't create a texture that large.
+    pub fn new(
+        renderer: &Renderer,
+        base_rendergraph: &BaseRenderGraph,
+        plane: ReflectionPlane,
+        resolution: UVec2,
+    ) -> Result<Self, RendererError> {
+        let (texture, view) = renderer.add_texture_2d_render_target(resolution, REFLECTION_FORMAT)?;
+        Ok(Self {
+            plane,
+            near_offset: 0.01,
+            tonemapping: TonemappingRoutine::new(renderer, &base_rendergraph.interfaces, REFLECTION_FORMAT),
+            texture,
+            view,
+            resolution,
+        })
+    }
+
+    /// Texture the reflection is rendered into.
+    pub fn texture(&self) -> &TextureHandle {
+        &self.texture
+    }
+
+    pub fn resolution(&self) -> UVec2 {
+        self.resolution
+    }
+
+    /// Renders the reflection of the scene seen by `camera`. Returns false,
+    /// keeping the previous reflection, if the camera is behind the plane.
+    ///
+    /// This changes the camera of the renderer, so set the one of the main
+    /// view again afterwards.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        renderer: &Arc<Renderer>,
+        base_rendergraph: &BaseRenderGraph,
+        pbr: &PbrRoutine,
+        skybox: Option<&SkyboxRoutine>,
+        camera: &Camera,
+        samples: SampleCount,
+        ambient: Vec4,
+        clear_color: Vec4,
+    ) -> bool {
+        profiling::scope!("Planar Reflection");
+
+        let eye = camera.view.inverse().w_axis.truncate();
+        let reflection_camera = match self.plane.reflection_camera(eye, renderer.handedness, self.near_offset) {
+            Some(camera) => camera,
+            None => return false,
+        };
+        renderer.set_camera_data(reflection_camera);
+
+        let (cmd_bufs, ready) = renderer.ready();
+        let mut graph = RenderGraph::new();
+        base_rendergraph.add_to_graph(
+            &mut graph,
+            &ready,
+            crate::base::BaseRenderGraphRoutines {
+                pbr,
+                skybox,
+                tonemapping: &self.tonemapping,
+            },
+            &crate::base::BaseRenderGraphSettings::new(self.resolution)
+                .with_samples(samples)
+                .with_ambient(ambient)
+                .with_clear_color(clear_color),
+        );
+        graph.execute(renderer, OutputFrame::View(Arc::clone(&self.view)), cmd_bufs, &ready);
+        true
+    }
+}
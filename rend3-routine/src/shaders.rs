@@ -1,6 +1,12 @@
 //! Holds the sources and binaries for all shaders.
 
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
 use include_dir::{include_dir, Dir};
+use parking_lot::RwLock;
 
 /// All shaders in SPIRV form. This is what is used when GpuDriven to
 /// get spirv passthrough.
@@ -8,3 +14,73 @@ pub static SPIRV_SHADERS: Dir = include_dir!("$CARGO_MANIFEST_DIR/shaders/spirv"
 /// Naga-compatible shaders in SPIRV form. This is what is used when CpuDriven and
 /// the naga-passing GpuDriven shaders.
 pub static WGSL_SHADERS: Dir = include_dir!("$CARGO_MANIFEST_DIR/shaders/wgsl");
+
+static SHADER_DIRECTORY: RwLock<Option<PathBuf>> = parking_lot::const_rwlock(None);
+
+/// Makes routines created from now on load their shaders from `directory`
+/// instead of the copies built into the binary. `None` goes back to the
+/// built in shaders.
+///
+/// The directory is laid out like `rend3-routine/shaders`, with the compiled
+/// shaders in `wgsl` and `spirv` and the handwritten ones in `src`. Shaders
+/// missing from it fall back to the built in ones. Used to iterate on shaders
+/// without rebuilding, see [`ShaderWatcher`](crate::hot_reload::ShaderWatcher).
+pub fn set_shader_directory(directory: Option<PathBuf>) {
+    *SHADER_DIRECTORY.write() = directory;
+}
+
+/// The directory set with [`set_shader_directory`].
+pub fn shader_directory() -> Option<PathBuf> {
+    SHADER_DIRECTORY.read().clone()
+}
+
+fn read_from_directory(subdirectory: &str, name: &str) -> Option<Vec<u8>> {
+    let path = SHADER_DIRECTORY
+        .read()
+        .as_deref()
+        .map(|dir| dir.join(subdirectory).join(name))?;
+    match std::fs::read(&path) {
+        Ok(contents) => Some(contents),
+        Err(e) => {
+            log::warn!(
+                "Failed to read shader {}, using the built in one: {}",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+fn utf8_or_builtin(contents: Option<Vec<u8>>, builtin: &'static str, path: &Path) -> Cow<'static, str> {
+    match contents.map(String::from_utf8) {
+        Some(Ok(source)) => Cow::Owned(source),
+        Some(Err(_)) => {
+            log::warn!("Shader {} isn't utf-8, using the built in one", path.display());
+            Cow::Borrowed(builtin)
+        }
+        None => Cow::Borrowed(builtin),
+    }
+}
+
+/// Source of the compiled wgsl shader `name`.
+pub fn wgsl_shader(name: &str) -> Cow<'static, str> {
+    let builtin = WGSL_SHADERS.get_file(name).unwrap().contents_utf8().unwrap();
+    utf8_or_builtin(
+        read_from_directory("wgsl", name),
+        builtin,
+        &Path::new("wgsl").join(name),
+    )
+}
+
+/// Binary of the compiled spirv shader `name`.
+pub fn spirv_shader(name: &str) -> Cow<'static, [u8]> {
+    let builtin = SPIRV_SHADERS.get_file(name).unwrap().contents();
+    read_from_directory("spirv", name).map_or(Cow::Borrowed(builtin), Cow::Owned)
+}
+
+/// Source of the handwritten wgsl shader `name`, `builtin` being the copy
+/// included in the binary.
+pub fn source_shader(name: &str, builtin: &'static str) -> Cow<'static, str> {
+    utf8_or_builtin(read_from_directory("src", name), builtin, &Path::new("src").join(name))
+}
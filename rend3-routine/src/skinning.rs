@@ -1,12 +1,25 @@
+//! GPU skinning of meshes attached to skeletons.
+//!
+//! With [`SkinningMode::PreSkinned`], skinned vertices are computed once per
+//! frame by a compute pass, into the copy of the vertex data each skeleton
+//! owns in the mesh buffers. The depth prepass, shadow passes and forward
+//! pass then draw those vertices like any other mesh, without skinning again
+//! in their vertex shaders.
+//!
+//! With [`SkinningMode::VertexShader`], there are no copies and no compute
+//! pass: the vertex shaders of every pass skin the vertices of the mesh with
+//! the joint matrices uploaded here, found through the joint offset of each
+//! object.
+//!
+//! [`SkinningMode::PreSkinned`]: rend3::managers::SkinningMode::PreSkinned
+//! [`SkinningMode::VertexShader`]: rend3::managers::SkinningMode::VertexShader
+
 use std::{mem, num::NonZeroU64};
 
 use glam::{Mat4, UVec2};
 use rend3::{
     graph::{DataHandle, RenderGraph},
-    managers::{
-        MeshBuffers, SkeletonManager, VERTEX_JOINT_INDEX_SIZE, VERTEX_JOINT_WEIGHT_SIZE, VERTEX_NORMAL_SIZE,
-        VERTEX_POSITION_SIZE, VERTEX_TANGENT_SIZE,
-    },
+    managers::{MeshBuffers, SkeletonManager, VertexQuantization, VERTEX_JOINT_INDEX_SIZE, VERTEX_JOINT_WEIGHT_SIZE},
     util::{
         bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
         math::round_up_div,
@@ -18,6 +31,11 @@ use wgpu::{
     ShaderModuleDescriptor, ShaderStages,
 };
 
+use crate::{
+    common::{preprocess_wgsl, ShaderDefines},
+    shaders,
+};
+
 /// The per-skeleton data, as uploaded to the GPU compute shader.
 #[repr(C, align(16))]
 #[derive(Copy, Clone)]
@@ -45,7 +63,9 @@ pub fn add_pre_skin_to_graph(graph: &mut RenderGraph, pre_skin_data: DataHandle<
 /// The two buffers uploaded to the GPU during pre-skinning.
 pub struct PreSkinningBuffers {
     gpu_skinning_inputs: Buffer,
-    joint_matrices: Buffer,
+    /// Joint matrices of all skeletons, each starting at the joint offset
+    /// of the skeleton. Bound by the vertex shaders when skinning in them.
+    pub(crate) joint_matrices: Buffer,
 }
 
 fn build_gpu_skinning_input_buffers(device: &Device, skeleton_manager: &SkeletonManager) -> PreSkinningBuffers {
@@ -59,9 +79,11 @@ fn build_gpu_skinning_input_buffers(device: &Device, skeleton_manager: &Skeleton
         mapped_at_creation: true,
     });
 
+    // Vertex shaders bind the matrices whether or not there are skeletons,
+    // so the buffer can't be empty.
     let joint_matrices = device.create_buffer(&BufferDescriptor {
         label: Some("joint matrices"),
-        size: (skeleton_manager.global_joint_count() * mem::size_of::<Mat4>()) as u64,
+        size: (skeleton_manager.global_joint_count().max(1) * mem::size_of::<Mat4>()) as u64,
         usage: BufferUsages::STORAGE,
         mapped_at_creation: true,
     });
@@ -69,10 +91,6 @@ fn build_gpu_skinning_input_buffers(device: &Device, skeleton_manager: &Skeleton
     let mut skinning_input_data = gpu_skinning_inputs.slice(..).get_mapped_range_mut();
     let mut joint_matrices_data = joint_matrices.slice(..).get_mapped_range_mut();
 
-    // Skeletons have a variable number of joints, so we need to keep track of
-    // the global index here.
-    let mut joint_matrix_idx = 0;
-
     // Iterate over the skeletons, fill the buffers
     for (idx, skeleton) in skeleton_manager.skeletons().enumerate() {
         // SAFETY: We are always accessing elements in bounds and all accesses are
@@ -81,7 +99,7 @@ fn build_gpu_skinning_input_buffers(device: &Device, skeleton_manager: &Skeleton
             let input = GpuSkinningInput {
                 skeleton_range: skeleton.ranges.skeleton_range,
                 mesh_range: skeleton.ranges.mesh_range,
-                joint_idx: joint_matrix_idx,
+                joint_idx: skeleton.joint_offset,
             };
 
             // The skinning inputs buffer has as many elements as skeletons, so
@@ -90,15 +108,14 @@ fn build_gpu_skinning_input_buffers(device: &Device, skeleton_manager: &Skeleton
             skin_input_ptr.add(idx).write_unaligned(input);
 
             let joint_matrices_ptr = joint_matrices_data.as_mut_ptr() as *mut [[f32; 4]; 4];
-            for joint_matrix in &skeleton.joint_matrices {
-                // Here, the access can't be OOB either: The joint_matrix_idx
-                // will get incremented once for every joint matrix, and the
-                // length of the buffer is exactly the sum of all joint matrix
-                // vector lengths.
+            for (joint, joint_matrix) in skeleton.joint_matrices.iter().enumerate() {
+                // Here, the access can't be OOB either: The skeleton manager
+                // packs the joints of the skeletons one after the other, so
+                // the joints of the last one end at the global joint count,
+                // the length of the buffer.
                 joint_matrices_ptr
-                    .add(joint_matrix_idx as usize)
+                    .add(skeleton.joint_offset as usize + joint)
                     .write_unaligned(joint_matrix.to_cols_array_2d());
-                joint_matrix_idx += 1;
             }
         }
     }
@@ -124,16 +141,18 @@ pub struct GpuSkinner {
 impl GpuSkinner {
     const WORKGROUP_SIZE: u32 = 64;
 
-    pub fn new(device: &wgpu::Device) -> GpuSkinner {
+    /// Creates the skinning pass for mesh buffers stored with
+    /// `quantization`. Quantized positions can't be skinned.
+    pub fn new(device: &wgpu::Device, quantization: VertexQuantization) -> GpuSkinner {
         let storage_buffer_ty = |read_only, size| BindingType::Buffer {
             ty: BufferBindingType::Storage { read_only },
             has_dynamic_offset: false,
             min_binding_size: size,
         };
 
-        let pos_size = NonZeroU64::new(VERTEX_POSITION_SIZE as u64);
-        let nrm_size = NonZeroU64::new(VERTEX_NORMAL_SIZE as u64);
-        let tan_size = NonZeroU64::new(VERTEX_TANGENT_SIZE as u64);
+        let pos_size = NonZeroU64::new(quantization.position_size() as u64);
+        let nrm_size = NonZeroU64::new(quantization.normal_size() as u64);
+        let tan_size = NonZeroU64::new(quantization.normal_size() as u64);
         let j_idx_size = NonZeroU64::new(VERTEX_JOINT_INDEX_SIZE as u64);
         let j_wt_size = NonZeroU64::new(VERTEX_JOINT_WEIGHT_SIZE as u64);
         let mat_size = NonZeroU64::new(mem::size_of::<Mat4>() as u64);
@@ -172,9 +191,13 @@ impl GpuSkinner {
             push_constant_ranges: &[],
         });
 
+        let defines =
+            ShaderDefines::new().with_if("QUANTIZED_NORMALS", quantization.contains(VertexQuantization::NORMALS));
+        let source = shaders::source_shader("skinning.wgsl", include_str!("../shaders/src/skinning.wgsl"));
+        let source = preprocess_wgsl(&source, &defines).expect("skinning.wgsl has broken #ifdef blocks");
         let module = device.create_shader_module(&ShaderModuleDescriptor {
             label: Some("Gpu skinning compute shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/src/skinning.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
         });
 
         let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
@@ -242,9 +265,12 @@ impl GpuSkinner {
 pub struct SkinningOutput;
 
 /// Performs skinning on the GPU.
+///
+/// Without a skinner, as in the downlevel profile which has no compute
+/// shaders, skinned meshes are left in their bind pose.
 pub fn add_skinning_to_graph<'node>(
     graph: &mut RenderGraph<'node>,
-    gpu_skinner: &'node GpuSkinner,
+    gpu_skinner: Option<&'node GpuSkinner>,
     pre_skin_data: DataHandle<PreSkinningBuffers>,
     skinned_data: DataHandle<SkinningOutput>,
 ) {
@@ -252,10 +278,9 @@ pub fn add_skinning_to_graph<'node>(
     let pre_skin_handle = builder.add_data_input(pre_skin_data);
     let skinned_data_handle = builder.add_data_output(skinned_data);
 
-    let skinner_pt = builder.passthrough_ref(gpu_skinner);
+    let skinner_pt = gpu_skinner.map(|gpu_skinner| builder.passthrough_ref(gpu_skinner));
 
     builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
-        let skinner = pt.get(skinner_pt);
         let encoder = encoder_or_pass.get_encoder();
         let skin_input = graph_data
             .get_data(temps, pre_skin_handle)
@@ -263,7 +288,9 @@ pub fn add_skinning_to_graph<'node>(
 
         // Avoid running the compute pass if there are no skeletons. This
         // prevents binding an empty buffer
-        if graph_data.skeleton_manager.skeletons().len() > 0 {
+        let has_skeletons = graph_data.skeleton_manager.skeletons().len() > 0;
+        if let (Some(skinner_pt), true) = (skinner_pt, has_skeletons) {
+            let skinner = pt.get(skinner_pt);
             skinner.execute_pass(
                 &renderer.device,
                 encoder,
@@ -1,24 +1,22 @@
 //! Routine that renders a cubemap as a skybox.
 
-use std::borrow::Cow;
-
 use rend3::{
     graph::{
         DataHandle, DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets,
         RenderTargetHandle,
     },
-    types::{SampleCount, TextureHandle},
+    types::{DepthMode, SampleCount, TextureHandle},
     util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
     Renderer,
 };
 use wgpu::{
-    BindGroup, BindGroupLayout, BindingType, Color, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
-    DepthStencilState, Face, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode,
-    PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource,
-    ShaderStages, StencilState, TextureFormat, TextureSampleType, TextureViewDimension, VertexState,
+    BindGroup, BindGroupLayout, BindingType, Color, ColorTargetState, ColorWrites, DepthBiasState, DepthStencilState,
+    Face, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState,
+    PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StencilState, TextureFormat, TextureSampleType, TextureViewDimension, VertexState,
 };
 
-use crate::{common::WholeFrameInterfaces, shaders::WGSL_SHADERS};
+use crate::{common::WholeFrameInterfaces, shaders::wgsl_shader};
 
 struct StoredSkybox {
     bg: Option<BindGroup>,
@@ -32,6 +30,7 @@ pub struct SkyboxRoutine {
     pipelines: SkyboxPipelines,
     bgl: BindGroupLayout,
     current_skybox: StoredSkybox,
+    depth_mode: DepthMode,
 }
 
 impl SkyboxRoutine {
@@ -55,6 +54,7 @@ impl SkyboxRoutine {
             current_skybox: StoredSkybox { bg: None, handle: None },
             bgl,
             pipelines,
+            depth_mode: renderer.depth_mode,
         }
     }
 
@@ -107,7 +107,7 @@ impl SkyboxRoutine {
             }],
             depth_stencil: Some(RenderPassDepthTarget {
                 target: DepthHandle::RenderTarget(hdr_depth_handle),
-                depth_clear: Some(0.0),
+                depth_clear: Some(self.depth_mode.far_depth()),
                 stencil_clear: None,
             }),
         });
@@ -146,23 +146,14 @@ impl SkyboxPipelines {
         profiling::scope!("build skybox pipeline");
         let skybox_pass_vert = renderer.device.create_shader_module(&ShaderModuleDescriptor {
             label: Some("skybox vert"),
-            source: ShaderSource::Wgsl(Cow::Borrowed(
-                WGSL_SHADERS
-                    .get_file("skybox.vert.wgsl")
-                    .unwrap()
-                    .contents_utf8()
-                    .unwrap(),
-            )),
+            source: ShaderSource::Wgsl(wgsl_shader(match renderer.depth_mode.is_reversed() {
+                true => "skybox.vert.wgsl",
+                false => "skybox.vert.standard.wgsl",
+            })),
         });
         let skybox_pass_frag = renderer.device.create_shader_module(&ShaderModuleDescriptor {
             label: Some("skybox frag"),
-            source: ShaderSource::Wgsl(Cow::Borrowed(
-                WGSL_SHADERS
-                    .get_file("skybox.frag.wgsl")
-                    .unwrap()
-                    .contents_utf8()
-                    .unwrap(),
-            )),
+            source: ShaderSource::Wgsl(wgsl_shader("skybox.frag.wgsl")),
         });
 
         let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
@@ -192,7 +183,7 @@ impl SkyboxPipelines {
                 depth_stencil: Some(DepthStencilState {
                     format: TextureFormat::Depth32Float,
                     depth_write_enabled: true,
-                    depth_compare: CompareFunction::GreaterEqual,
+                    depth_compare: renderer.depth_mode.closer_or_equal(),
                     stencil: StencilState::default(),
                     bias: DepthBiasState::default(),
                 }),
@@ -0,0 +1,402 @@
+//! Textured quads in the world, for things like health bars, impostors and
+//! simple particles, which don't need to be full PBR objects.
+//!
+//! Collect [`Sprite`]s in a [`SpriteBatch`] every frame and draw them with a
+//! [`SpriteRoutine`] into the HDR targets of the base rendergraph, after the
+//! forward pass, see [`BaseRenderGraphIntermediateState::sprites`]. Sprites
+//! are tested against the depth of the scene but don't write it, and are
+//! alpha blended back to front. Consecutive sprites with the same texture are
+//! drawn together, so putting many images in one atlas texture keeps the
+//! number of draws low.
+//!
+//! [`BaseRenderGraphIntermediateState::sprites`]: crate::base::BaseRenderGraphIntermediateState::sprites
+
+use std::{cmp::Ordering, mem, ops::Range};
+
+use glam::{Mat4, UVec2, Vec2, Vec3, Vec4};
+use rend3::{
+    graph::{DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    types::{DepthMode, SampleCount, TextureHandle},
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroupLayout, BindingType, BlendState, BufferAddress, BufferUsages, Color, ColorTargetState,
+    ColorWrites, DepthBiasState, DepthStencilState, FilterMode, FragmentState, FrontFace, MultisampleState,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor,
+    Sampler, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState,
+    TextureFormat, TextureSampleType, TextureViewDimension, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::shaders;
+
+/// How a sprite is oriented.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SpriteFacing {
+    /// Always faces the camera, like a billboard.
+    Camera,
+    /// Keeps its up direction along the given world space axis and turns
+    /// around it to face the camera, like a tree impostor.
+    Axis(Vec3),
+}
+
+/// A textured quad in the world.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    /// 2D texture to draw, possibly an atlas of many images.
+    pub texture: TextureHandle,
+    /// Center in world space.
+    pub position: Vec3,
+    /// Width and height in world units.
+    pub size: Vec2,
+    /// Top left corner of the region of the texture to draw, in texture
+    /// coordinates.
+    pub uv_min: Vec2,
+    /// Bottom right corner of the region of the texture to draw.
+    pub uv_max: Vec2,
+    /// Linear color the texture is multiplied with, with alpha.
+    pub color: Vec4,
+    pub facing: SpriteFacing,
+}
+
+impl Sprite {
+    /// White camera facing sprite showing the whole texture.
+    pub fn new(texture: TextureHandle, position: Vec3, size: Vec2) -> Self {
+        Self {
+            texture,
+            position,
+            size,
+            uv_min: Vec2::ZERO,
+            uv_max: Vec2::ONE,
+            color: Vec4::ONE,
+            facing: SpriteFacing::Camera,
+        }
+    }
+
+    /// Shows the cell at `index` of an atlas texture divided into a grid of
+    /// `cells` columns and rows, counting along rows from the top left.
+    pub fn with_atlas_cell(mut self, cells: UVec2, index: u32) -> Self {
+        let cell_size = Vec2::ONE / cells.as_vec2();
+        let cell = UVec2::new(index % cells.x, index / cells.x);
+        self.uv_min = cell.as_vec2() * cell_size;
+        self.uv_max = self.uv_min + cell_size;
+        self
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_facing(mut self, facing: SpriteFacing) -> Self {
+        self.facing = facing;
+        self
+    }
+}
+
+/// Sprites to draw with a [`SpriteRoutine`].
+#[derive(Debug, Default, Clone)]
+pub struct SpriteBatch {
+    sprites: Vec<Sprite>,
+}
+
+impl SpriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes all sprites, e.g. once a frame has been rendered.
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn push(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+}
+
+impl Extend<Sprite> for SpriteBatch {
+    fn extend<T: IntoIterator<Item = Sprite>>(&mut self, iter: T) {
+        self.sprites.extend(iter);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct SpriteVertex {
+    // Arrays, as the alignment of glam's vectors would pad the struct.
+    /// Clip space position.
+    position: [f32; 4],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for SpriteVertex {}
+unsafe impl bytemuck::Zeroable for SpriteVertex {}
+
+/// Sprites sharing a texture, drawn with one draw call.
+struct SpriteRun<'a> {
+    texture: &'a TextureHandle,
+    vertices: Range<u32>,
+}
+
+/// Sorts the sprites back to front and turns them into vertices, with the
+/// runs of sprites sharing a texture.
+fn build_vertices<'a>(
+    sprites: &'a [Sprite],
+    view_proj: Mat4,
+    inv_view: Mat4,
+) -> (Vec<SpriteVertex>, Vec<SpriteRun<'a>>) {
+    let camera_location = inv_view.w_axis.truncate();
+    let camera_right = inv_view.x_axis.truncate();
+    let camera_up = inv_view.y_axis.truncate();
+
+    let mut sorted: Vec<(f32, &Sprite)> = sprites
+        .iter()
+        .map(|sprite| (sprite.position.distance_squared(camera_location), sprite))
+        .collect();
+    sorted.sort_by(|(left, _), (right, _)| right.partial_cmp(left).unwrap_or(Ordering::Equal));
+
+    let mut vertices = Vec::with_capacity(sorted.len() * 6);
+    let mut runs: Vec<SpriteRun<'a>> = Vec::new();
+    for (_, sprite) in sorted {
+        let (right, up) = match sprite.facing {
+            SpriteFacing::Camera => (camera_right, camera_up),
+            SpriteFacing::Axis(axis) => {
+                let up = axis.normalize_or_zero();
+                let right = up.cross(camera_location - sprite.position).normalize_or_zero();
+                // Looking along the axis, there is no side to turn to.
+                let right = if right == Vec3::ZERO { camera_right } else { right };
+                // Keep the texture the right way around whichever side it is seen from.
+                let right = if right.dot(camera_right) < 0.0 { -right } else { right };
+                (right, up)
+            }
+        };
+        let right = right * sprite.size.x / 2.0;
+        let up = up * sprite.size.y / 2.0;
+
+        let corner = |x: f32, y: f32| SpriteVertex {
+            position: (view_proj * (sprite.position + right * (x * 2.0 - 1.0) + up * (1.0 - y * 2.0)).extend(1.0))
+                .to_array(),
+            uv: (sprite.uv_min + Vec2::new(x, y) * (sprite.uv_max - sprite.uv_min)).to_array(),
+            color: sprite.color.to_array(),
+        };
+
+        let start = vertices.len() as u32;
+        vertices.extend_from_slice(&[
+            corner(0.0, 0.0),
+            corner(1.0, 0.0),
+            corner(0.0, 1.0),
+            corner(0.0, 1.0),
+            corner(1.0, 0.0),
+            corner(1.0, 1.0),
+        ]);
+        let end = vertices.len() as u32;
+
+        match runs.last_mut() {
+            Some(run) if run.texture.get_raw() == sprite.texture.get_raw() => run.vertices.end = end,
+            _ => runs.push(SpriteRun {
+                texture: &sprite.texture,
+                vertices: start..end,
+            }),
+        }
+    }
+
+    (vertices, runs)
+}
+
+/// Draws the contents of a [`SpriteBatch`].
+///
+/// See module for documentation.
+pub struct SpriteRoutine {
+    pipeline_s1: RenderPipeline,
+    pipeline_s4: RenderPipeline,
+    bgl: BindGroupLayout,
+    sampler: Sampler,
+    depth_mode: DepthMode,
+}
+
+impl SpriteRoutine {
+    pub fn new(renderer: &Renderer) -> Self {
+        profiling::scope!("SpriteRoutine::new");
+
+        let sampler = renderer.device.create_sampler(&SamplerDescriptor {
+            label: Some("sprite"),
+            // Atlas regions shouldn't bleed into the other side of the texture.
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .build(&renderer.device, Some("sprite bgl"));
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("sprite"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "sprite.wgsl",
+                include_str!("../shaders/src/sprite.wgsl"),
+            )),
+        });
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("sprite"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let inner = |samples: SampleCount| {
+            renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("sprite"),
+                layout: Some(&pll),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[VertexBufferLayout {
+                        array_stride: mem::size_of::<SpriteVertex>() as BufferAddress,
+                        step_mode: VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x2, 2 => Float32x4],
+                    }],
+                },
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Cw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format: TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: renderer.depth_mode.closer_or_equal(),
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState {
+                    count: samples as u32,
+                    ..Default::default()
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::all(),
+                    }],
+                }),
+                multiview: None,
+            })
+        };
+
+        Self {
+            pipeline_s1: inner(SampleCount::One),
+            pipeline_s4: inner(SampleCount::Four),
+            bgl,
+            sampler,
+            depth_mode: renderer.depth_mode,
+        }
+    }
+
+    /// Draws the sprites in `batch` into the HDR targets, seen from the camera
+    /// of the renderer.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        batch: &'node SpriteBatch,
+        color: RenderTargetHandle,
+        resolve: Option<RenderTargetHandle>,
+        depth: RenderTargetHandle,
+        samples: SampleCount,
+    ) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let mut builder = graph.add_node("Sprites");
+
+        let hdr_color_handle = builder.add_render_target_output(color);
+        let hdr_resolve = builder.add_optional_render_target_output(resolve);
+        let hdr_depth_handle = builder.add_render_target_input(depth);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: hdr_color_handle,
+                clear: Color::BLACK,
+                resolve: hdr_resolve,
+            }],
+            depth_stencil: Some(RenderPassDepthTarget {
+                target: DepthHandle::RenderTarget(hdr_depth_handle),
+                depth_clear: Some(self.depth_mode.far_depth()),
+                stencil_clear: None,
+            }),
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+        let batch_handle = builder.passthrough_ref(batch);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let batch = pt.get(batch_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("sprites");
+
+            let camera = graph_data.camera_manager;
+            let (vertices, runs) = build_vertices(&batch.sprites, camera.view_proj(), camera.view().inverse());
+
+            let vertex_buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("sprite vertices"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: BufferUsages::VERTEX,
+            }));
+
+            let pipeline = match samples {
+                SampleCount::One => &this.pipeline_s1,
+                SampleCount::Four => &this.pipeline_s4,
+            };
+            rpass.set_pipeline(pipeline);
+            rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+
+            for run in runs {
+                let bg = temps.add(
+                    BindGroupBuilder::new()
+                        .append_texture_view(graph_data.d2_texture_manager.get_view(run.texture.get_raw()))
+                        .append_sampler(&this.sampler)
+                        .build(&renderer.device, Some("sprite bg"), &this.bgl),
+                );
+
+                rpass.set_bind_group(0, bg, &[]);
+                rpass.draw(run.vertices, 0..1);
+            }
+        });
+    }
+}
@@ -0,0 +1,374 @@
+//! Screen-space subsurface scattering, softening the lighting of skin, wax
+//! and marble so it doesn't look like plastic.
+//!
+//! Materials opt in with [`PbrMaterial::subsurface`], naming one of the
+//! [`DiffusionProfile`]s of the [`SubsurfaceRoutine`]. The opaque pass marks
+//! their pixels in the alpha of the HDR color target, then the routine blurs
+//! them horizontally and vertically, following the surface: neither other
+//! objects nor pixels across a depth discontinuity bleed in.
+//!
+//! Add the routine right after [`BasePhase::ForwardOpaque`] on the HDR color
+//! target, as blended materials overwrite the marks. With MSAA, add it in
+//! [`BasePhase::Post`] on the resolved target instead, as the resolve of
+//! every pass overwrites what was blurred before.
+//!
+//! The whole lighting is blurred, including specular and albedo, so texture
+//! detail softens a bit along with it. Not available on the downlevel
+//! profile, as GL can't load from depth textures.
+//!
+//! [`PbrMaterial::subsurface`]: crate::pbr::PbrMaterial::subsurface
+//! [`BasePhase::ForwardOpaque`]: crate::base::BasePhase::ForwardOpaque
+//! [`BasePhase::Post`]: crate::base::BasePhase::Post
+
+use std::{mem, num::NonZeroU64};
+
+use glam::{IVec2, Mat4, UVec2, Vec3, Vec4};
+use rend3::{
+    graph::{RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetDescriptor, RenderTargetHandle},
+    types::SampleCount,
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer, RendererProfile,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayout, BindingType, BufferBindingType, BufferUsages, Color, ColorTargetState, ColorWrites, FragmentState,
+    MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType, TextureUsages,
+    TextureViewDimension, VertexState,
+};
+
+use crate::{
+    common::{preprocess_wgsl, ShaderDefines},
+    shaders,
+};
+
+/// Most profiles a [`SubsurfaceRoutine`] holds, indexed by
+/// [`SubsurfaceScattering::profile`](crate::pbr::SubsurfaceScattering::profile).
+pub const MAX_DIFFUSION_PROFILES: usize = 16;
+
+/// How far light spreads under a surface.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DiffusionProfile {
+    /// Standard deviation of the spread of red, green and blue light, in
+    /// world units. Red usually goes furthest.
+    pub scatter_radius: Vec3,
+    /// How much of the lighting is replaced by the scattered lighting, from
+    /// 0 to 1.
+    pub strength: f32,
+}
+
+impl DiffusionProfile {
+    /// Human skin, in meters.
+    pub fn skin() -> Self {
+        Self {
+            scatter_radius: Vec3::new(0.0036, 0.0014, 0.0008),
+            strength: 1.0,
+        }
+    }
+
+    /// Candle wax, in meters.
+    pub fn wax() -> Self {
+        Self {
+            scatter_radius: Vec3::new(0.006, 0.004, 0.0025),
+            strength: 1.0,
+        }
+    }
+
+    /// White marble, in meters.
+    pub fn marble() -> Self {
+        Self {
+            scatter_radius: Vec3::new(0.004, 0.0038, 0.0034),
+            strength: 0.8,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct SubsurfaceUniforms {
+    unproject: Vec4,
+    direction: IVec2,
+    pixel_scale: f32,
+    perspective: u32,
+    profile_count: u32,
+    _padding: [u32; 3],
+    profiles: [Vec4; MAX_DIFFUSION_PROFILES],
+}
+
+unsafe impl bytemuck::Zeroable for SubsurfaceUniforms {}
+unsafe impl bytemuck::Pod for SubsurfaceUniforms {}
+
+impl SubsurfaceUniforms {
+    fn new(proj: Mat4, resolution: UVec2, direction: IVec2, profiles: &[DiffusionProfile]) -> Self {
+        let inv_proj = proj.inverse();
+        let pixel_scale = match direction.x != 0 {
+            true => proj.x_axis.x * resolution.x as f32,
+            false => proj.y_axis.y * resolution.y as f32,
+        } * 0.5;
+
+        let mut shader_profiles = [Vec4::ZERO; MAX_DIFFUSION_PROFILES];
+        for (shader_profile, profile) in shader_profiles.iter_mut().zip(profiles) {
+            *shader_profile = profile.scatter_radius.extend(profile.strength);
+        }
+
+        Self {
+            unproject: Vec4::new(
+                inv_proj.z_axis.z,
+                inv_proj.w_axis.z,
+                inv_proj.z_axis.w,
+                inv_proj.w_axis.w,
+            ),
+            direction,
+            pixel_scale,
+            perspective: (proj.w_axis.w == 0.0) as u32,
+            profile_count: profiles.len() as u32,
+            _padding: [0; 3],
+            profiles: shader_profiles,
+        }
+    }
+}
+
+/// Blurs the lighting of materials with subsurface scattering, see the
+/// [module](self) docs.
+pub struct SubsurfaceRoutine {
+    profiles: Vec<DiffusionProfile>,
+    bgl: BindGroupLayout,
+    /// `None` on the downlevel profile.
+    pipeline: Option<RenderPipeline>,
+}
+
+impl SubsurfaceRoutine {
+    /// `samples` is the sample count of the depth buffer passed to
+    /// [`add_to_graph`](Self::add_to_graph).
+    pub fn new(renderer: &Renderer, samples: SampleCount) -> Self {
+        profiling::scope!("SubsurfaceRoutine::new");
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(mem::size_of::<SubsurfaceUniforms>() as u64),
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: samples.needs_resolve(),
+                },
+                None,
+            )
+            .build(&renderer.device, Some("subsurface bgl"));
+
+        let pipeline = (renderer.profile != RendererProfile::Downlevel).then(|| {
+            let defines = ShaderDefines::new().with_if("MULTISAMPLED", samples.needs_resolve());
+            let source = shaders::source_shader("subsurface.wgsl", include_str!("../shaders/src/subsurface.wgsl"));
+            let source = preprocess_wgsl(&source, &defines).expect("subsurface.wgsl has broken #ifdef blocks");
+            let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+                label: Some("subsurface"),
+                source: ShaderSource::Wgsl(source.into()),
+            });
+
+            let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("subsurface"),
+                bind_group_layouts: &[&bgl],
+                push_constant_ranges: &[],
+            });
+
+            renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("subsurface"),
+                layout: Some(&pll),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    }],
+                }),
+                multiview: None,
+            })
+        });
+
+        Self {
+            profiles: Vec::new(),
+            bgl,
+            pipeline,
+        }
+    }
+
+    pub fn profiles(&self) -> &[DiffusionProfile] {
+        &self.profiles
+    }
+
+    /// Sets the profiles materials pick from by index.
+    ///
+    /// # Panics
+    ///
+    /// If there are more than [`MAX_DIFFUSION_PROFILES`].
+    pub fn set_profiles(&mut self, profiles: Vec<DiffusionProfile>) {
+        assert!(
+            profiles.len() <= MAX_DIFFUSION_PROFILES,
+            "{} diffusion profiles, the most is {}",
+            profiles.len(),
+            MAX_DIFFUSION_PROFILES
+        );
+        self.profiles = profiles;
+    }
+
+    /// Blurs the marked pixels of the `Rgba16Float` target `color`, following
+    /// the surfaces of `depth`, both of size `resolution`.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        color: RenderTargetHandle,
+        depth: RenderTargetHandle,
+        resolution: UVec2,
+    ) {
+        if self.pipeline.is_none() || self.profiles.is_empty() {
+            return;
+        }
+
+        let blurred = graph.add_render_target(RenderTargetDescriptor {
+            label: Some("subsurface blur".into()),
+            resolution,
+            samples: SampleCount::One,
+            format: TextureFormat::Rgba16Float,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        });
+
+        self.add_blur(
+            graph,
+            "Subsurface Horizontal",
+            color,
+            depth,
+            blurred,
+            IVec2::X,
+            resolution,
+        );
+        self.add_blur(
+            graph,
+            "Subsurface Vertical",
+            blurred,
+            depth,
+            color,
+            IVec2::Y,
+            resolution,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn add_blur<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        label: &str,
+        source: RenderTargetHandle,
+        depth: RenderTargetHandle,
+        output: RenderTargetHandle,
+        direction: IVec2,
+        resolution: UVec2,
+    ) {
+        let mut builder = graph.add_node(label);
+
+        let source_handle = builder.add_render_target_input(source);
+        let depth_handle = builder.add_render_target_input(depth);
+        let output_handle = builder.add_render_target_output(output);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: output_handle,
+                clear: Color::BLACK,
+                resolve: None,
+            }],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+            let pipeline = match this.pipeline {
+                Some(ref pipeline) => pipeline,
+                None => return,
+            };
+
+            profiling::scope!("subsurface blur");
+
+            let uniforms =
+                SubsurfaceUniforms::new(graph_data.camera_manager.proj(), resolution, direction, &this.profiles);
+            let buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("subsurface uniforms"),
+                contents: bytemuck::bytes_of(&uniforms),
+                usage: BufferUsages::UNIFORM,
+            }));
+
+            let bg = temps.add(
+                BindGroupBuilder::new()
+                    .append_buffer(buffer)
+                    .append_texture_view(graph_data.get_render_target(source_handle))
+                    .append_texture_view(graph_data.get_render_target(depth_handle))
+                    .build(&renderer.device, Some("subsurface bg"), &this.bgl),
+            );
+
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, bg, &[]);
+            rpass.draw(0..3, 0..1);
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{IVec2, Mat4, UVec2, Vec4};
+
+    use super::SubsurfaceUniforms;
+
+    /// What subsurface.wgsl does with the uniforms.
+    fn view_depth(uniforms: &SubsurfaceUniforms, proj: Mat4, view_z: f32) -> f32 {
+        let clip = proj * Vec4::new(0.3, -0.2, view_z, 1.0);
+        let depth = clip.z / clip.w;
+        let u = uniforms.unproject;
+        ((u.x * depth + u.y) / (u.z * depth + u.w)).abs()
+    }
+
+    #[test]
+    fn recovers_view_depth() {
+        let resolution = UVec2::new(1920, 1080);
+        let perspective = Mat4::perspective_infinite_reverse_lh(1.0, 16.0 / 9.0, 0.1);
+        let uniforms = SubsurfaceUniforms::new(perspective, resolution, IVec2::Y, &[]);
+        assert_eq!(uniforms.perspective, 1);
+        assert!((view_depth(&uniforms, perspective, 5.0) - 5.0).abs() < 1e-3);
+        assert!((view_depth(&uniforms, perspective, 0.5) - 0.5).abs() < 1e-4);
+        // A unit at depth one covers proj[1][1] half screens.
+        assert!((uniforms.pixel_scale - perspective.y_axis.y * 540.0).abs() < 1e-3);
+
+        let orthographic = Mat4::orthographic_lh(-10.0, 10.0, -5.0, 5.0, 1.0, 100.0);
+        let uniforms = SubsurfaceUniforms::new(orthographic, resolution, IVec2::X, &[]);
+        assert_eq!(uniforms.perspective, 0);
+        assert!((view_depth(&uniforms, orthographic, 40.0) - 40.0).abs() < 1e-3);
+        assert!((uniforms.pixel_scale - 96.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,150 @@
+//! Supersampling: rendering the scene at a multiple of the output resolution
+//! and filtering it down, which smooths every edge, including those MSAA
+//! misses like alpha cutouts and shading aliasing.
+//!
+//! Set [`BaseRenderGraphSettings::supersampling`] for the base rendergraph
+//! to render its HDR targets at the larger resolution and filter them down
+//! before tonemapping. The [`SupersampleRoutine`] can also be used on its
+//! own, e.g. to take screenshots at a higher quality than the window is
+//! rendered at.
+//!
+//! [`BaseRenderGraphSettings::supersampling`]: crate::base::BaseRenderGraphSettings::supersampling
+
+use std::num::NonZeroU64;
+
+use rend3::{
+    graph::{RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayout, BindingType, BufferBindingType, BufferUsages, Color, ColorTargetState, ColorWrites, Device,
+    FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType,
+    TextureViewDimension, VertexState,
+};
+
+use crate::shaders;
+
+/// Largest supersampling factor, at which 16 times as many pixels are
+/// rendered.
+pub const MAX_SUPERSAMPLING: u32 = 4;
+
+/// Filters HDR targets down to a fraction of their resolution.
+pub struct SupersampleRoutine {
+    bgl: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl SupersampleRoutine {
+    pub fn new(device: &Device) -> Self {
+        profiling::scope!("SupersampleRoutine::new");
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(4),
+                },
+                None,
+            )
+            .build(device, Some("supersample bgl"));
+
+        let module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("supersample"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "supersample.wgsl",
+                include_str!("../shaders/src/supersample.wgsl"),
+            )),
+        });
+        let pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("supersample"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("supersample"),
+            layout: Some(&pll),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[ColorTargetState {
+                    format: TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            multiview: None,
+        });
+
+        Self { bgl, pipeline }
+    }
+
+    /// Filters the single sampled `Rgba16Float` target `src` down into
+    /// `dst`, which must be `factor` times smaller on both axes.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        src: RenderTargetHandle,
+        dst: RenderTargetHandle,
+        factor: u32,
+    ) {
+        let mut builder = graph.add_node("Supersample");
+
+        let input_handle = builder.add_render_target_input(src);
+        let output_handle = builder.add_render_target_output(dst);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: output_handle,
+                clear: Color::BLACK,
+                resolve: None,
+            }],
+            depth_stencil: None,
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("supersample");
+
+            let params = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("supersample params"),
+                contents: bytemuck::bytes_of(&factor.clamp(1, MAX_SUPERSAMPLING)),
+                usage: BufferUsages::UNIFORM,
+            }));
+            let bg = temps.add(
+                BindGroupBuilder::new()
+                    .append_texture_view(graph_data.get_render_target(input_handle))
+                    .append_buffer(params)
+                    .build(&renderer.device, Some("supersample bg"), &this.bgl),
+            );
+
+            rpass.set_pipeline(&this.pipeline);
+            rpass.set_bind_group(0, bg, &[]);
+            rpass.draw(0..3, 0..1);
+        });
+    }
+}
@@ -8,8 +8,13 @@
 //! output. Each TonemappingRoutine instance only has a single pipeline, so if
 //! you need to render to two different formats potentially, use two different
 //! routines.
+//!
+//! On HDR displays, [`TonemappingOutput::ScRgb`] and [`TonemappingOutput::Pq`]
+//! keep the range above SDR white instead of clamping it away. For
+//! transparent windows and overlays, clear the scene to a transparent color
+//! and use [`TonemappingAlpha::Premultiplied`].
 
-use std::borrow::Cow;
+use std::num::NonZeroU64;
 
 use rend3::{
     graph::{DataHandle, RenderGraph, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
@@ -17,44 +22,110 @@ use rend3::{
     Renderer,
 };
 use wgpu::{
-    BindGroup, BindGroupLayout, BindingType, Color, ColorTargetState, ColorWrites, Device, FragmentState, FrontFace,
-    MultisampleState, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline,
-    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType,
-    TextureViewDimension, VertexState,
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupLayout, BindingType, Buffer, BufferBindingType, BufferUsages, Color, ColorTargetState,
+    ColorWrites, Device, FragmentState, FrontFace, MultisampleState, PipelineLayoutDescriptor, PolygonMode,
+    PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, TextureFormat, TextureSampleType, TextureViewDimension, VertexState,
+};
+
+use crate::{
+    common::WholeFrameInterfaces,
+    shaders::{source_shader, wgsl_shader},
 };
 
-use crate::{common::WholeFrameInterfaces, shaders::WGSL_SHADERS};
+/// How the output of the tonemapping routine is encoded for the display.
+///
+/// HDR outputs map a linear value of 1.0 to `paper_white` nits, the
+/// brightness of SDR white, and clamp to `max_luminance` nits, the peak
+/// brightness of the display.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TonemappingOutput {
+    /// Clamped to the 0 to 1 range, gamma encoded unless the output format
+    /// is srgb.
+    Sdr,
+    /// Linear Rec.709 where 1.0 is 80 nits, for `Rgba16Float` outputs.
+    ScRgb { paper_white: f32, max_luminance: f32 },
+    /// Rec.2020 encoded with the SMPTE ST 2084 perceptual quantizer, as used
+    /// by HDR10, for `Rgb10a2Unorm` outputs.
+    Pq { paper_white: f32, max_luminance: f32 },
+}
+
+impl Default for TonemappingOutput {
+    fn default() -> Self {
+        Self::Sdr
+    }
+}
+
+impl TonemappingOutput {
+    pub fn is_hdr(&self) -> bool {
+        !matches!(self, Self::Sdr)
+    }
+
+    /// Format of the surface this output is meant for, `None` for SDR which
+    /// works with any format.
+    pub fn format(&self) -> Option<TextureFormat> {
+        match self {
+            Self::Sdr => None,
+            Self::ScRgb { .. } => Some(TextureFormat::Rgba16Float),
+            Self::Pq { .. } => Some(TextureFormat::Rgb10a2Unorm),
+        }
+    }
+
+    /// Contents of the uniform buffer of the handwritten shader.
+    fn params(&self, alpha: TonemappingAlpha, srgb_output: bool) -> [u32; 4] {
+        let (mode, paper_white, max_luminance) = match *self {
+            Self::Sdr => (0, 0.0, 0.0),
+            Self::ScRgb {
+                paper_white,
+                max_luminance,
+            } => (1, paper_white, max_luminance),
+            Self::Pq {
+                paper_white,
+                max_luminance,
+            } => (2, paper_white, max_luminance),
+        };
+        let flags = (alpha == TonemappingAlpha::Premultiplied) as u32 | (srgb_output as u32) << 1;
+        [mode, paper_white.to_bits(), max_luminance.to_bits(), flags]
+    }
+}
+
+/// How the tonemapping routine writes alpha.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TonemappingAlpha {
+    /// Alpha and color are written as the scene has them.
+    Passthrough,
+    /// Color is premultiplied by alpha after being encoded, as compositors
+    /// blending the output over other content expect.
+    Premultiplied,
+}
+
+impl Default for TonemappingAlpha {
+    fn default() -> Self {
+        Self::Passthrough
+    }
+}
 
 fn create_pipeline(
     device: &Device,
     interfaces: &WholeFrameInterfaces,
     bgl: &BindGroupLayout,
     output_format: TextureFormat,
+    handwritten: bool,
 ) -> RenderPipeline {
     profiling::scope!("TonemappingPass::new");
     let blit_vert = device.create_shader_module(&ShaderModuleDescriptor {
         label: Some("tonemapping vert"),
-        source: ShaderSource::Wgsl(Cow::Borrowed(
-            WGSL_SHADERS
-                .get_file("blit.vert.wgsl")
-                .unwrap()
-                .contents_utf8()
-                .unwrap(),
-        )),
+        source: ShaderSource::Wgsl(wgsl_shader("blit.vert.wgsl")),
     });
 
     let blit_frag = device.create_shader_module(&ShaderModuleDescriptor {
         label: Some("tonemapping frag"),
-        source: ShaderSource::Wgsl(Cow::Borrowed(
-            WGSL_SHADERS
-                .get_file(match output_format.describe().srgb {
-                    true => "blit-linear.frag.wgsl",
-                    false => "blit-srgb.frag.wgsl",
-                })
-                .unwrap()
-                .contents_utf8()
-                .unwrap(),
-        )),
+        source: ShaderSource::Wgsl(match (handwritten, output_format.describe().srgb) {
+            (true, _) => source_shader("tonemapping.wgsl", include_str!("../shaders/src/tonemapping.wgsl")),
+            (false, true) => wgsl_shader("blit-linear.frag.wgsl"),
+            (false, false) => wgsl_shader("blit-srgb.frag.wgsl"),
+        }),
     });
 
     let pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
@@ -101,25 +172,103 @@ fn create_pipeline(
 pub struct TonemappingRoutine {
     bgl: BindGroupLayout,
     pipeline: RenderPipeline,
+    output: TonemappingOutput,
+    alpha: TonemappingAlpha,
+    srgb_output: bool,
+    /// Uniform buffer of the handwritten shader, used for HDR outputs and
+    /// premultiplied alpha.
+    params: Option<Buffer>,
 }
 
 impl TonemappingRoutine {
     pub fn new(renderer: &Renderer, interfaces: &WholeFrameInterfaces, output_format: TextureFormat) -> Self {
-        let bgl = BindGroupLayoutBuilder::new()
-            .append(
+        Self::with_output(
+            renderer,
+            interfaces,
+            output_format,
+            TonemappingOutput::Sdr,
+            TonemappingAlpha::Passthrough,
+        )
+    }
+
+    /// Creates a routine encoding its output as `output`, usually with the
+    /// format given by [`TonemappingOutput::format`].
+    pub fn with_output(
+        renderer: &Renderer,
+        interfaces: &WholeFrameInterfaces,
+        output_format: TextureFormat,
+        output: TonemappingOutput,
+        alpha: TonemappingAlpha,
+    ) -> Self {
+        let srgb_output = output_format.describe().srgb;
+        let handwritten = output.is_hdr() || alpha == TonemappingAlpha::Premultiplied;
+        let mut bglb = BindGroupLayoutBuilder::new();
+        bglb.append(
+            ShaderStages::FRAGMENT,
+            BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            None,
+        );
+        let params = handwritten.then(|| {
+            bglb.append(
                 ShaderStages::FRAGMENT,
-                BindingType::Texture {
-                    sample_type: TextureSampleType::Float { filterable: true },
-                    view_dimension: TextureViewDimension::D2,
-                    multisampled: false,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(16),
                 },
                 None,
-            )
-            .build(&renderer.device, Some("bind bgl"));
+            );
+            renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("tonemapping params"),
+                contents: bytemuck::cast_slice(&output.params(alpha, srgb_output)),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            })
+        });
+        let bgl = bglb.build(&renderer.device, Some("bind bgl"));
+
+        let pipeline = create_pipeline(&renderer.device, interfaces, &bgl, output_format, handwritten);
+
+        Self {
+            bgl,
+            pipeline,
+            output,
+            alpha,
+            srgb_output,
+            params,
+        }
+    }
 
-        let pipeline = create_pipeline(&renderer.device, interfaces, &bgl, output_format);
+    pub fn output(&self) -> TonemappingOutput {
+        self.output
+    }
+
+    pub fn alpha(&self) -> TonemappingAlpha {
+        self.alpha
+    }
 
-        Self { bgl, pipeline }
+    /// Changes the luminances of an HDR output, e.g. from a brightness
+    /// setting, or switches between HDR encodings.
+    ///
+    /// Switching between SDR and HDR needs a new routine, as the pipeline
+    /// and usually the output format differ.
+    pub fn set_output(&mut self, renderer: &Renderer, output: TonemappingOutput) {
+        assert_eq!(
+            self.output.is_hdr(),
+            output.is_hdr(),
+            "Switching between SDR and HDR tonemapping needs a new routine"
+        );
+        if let Some(ref params) = self.params {
+            renderer.queue.write_buffer(
+                params,
+                0,
+                bytemuck::cast_slice(&output.params(self.alpha, self.srgb_output)),
+            );
+        }
+        self.output = output;
     }
 
     pub fn add_to_graph<'node>(
@@ -155,11 +304,12 @@ impl TonemappingRoutine {
 
             profiling::scope!("tonemapping");
 
-            let blit_src_bg = temps.add(BindGroupBuilder::new().append_texture_view(hdr_color).build(
-                &renderer.device,
-                Some("blit src bg"),
-                &this.bgl,
-            ));
+            let mut bgb = BindGroupBuilder::new();
+            bgb.append_texture_view(hdr_color);
+            if let Some(ref params) = this.params {
+                bgb.append_buffer(params);
+            }
+            let blit_src_bg = temps.add(bgb.build(&renderer.device, Some("blit src bg"), &this.bgl));
 
             rpass.set_pipeline(&this.pipeline);
             rpass.set_bind_group(0, forward_uniform_bg, &[]);
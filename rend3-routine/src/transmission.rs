@@ -0,0 +1,419 @@
+//! Refraction and rough transmission through blended materials, for glass
+//! and liquids.
+//!
+//! After the opaque pass, the [`TransmissionRoutine`] copies the HDR color
+//! into a mip chain of ever blurrier copies. Blended materials with
+//! [`PbrMaterial::transmission`] then draw what is behind them from it,
+//! offset along the view ray refracted by their ior and thickness, read from
+//! a blurrier level the rougher they are, and tinted by their albedo and
+//! the absorption of their volume. Their surface is blended over that by the
+//! regular blend pass.
+//!
+//! Add the routine with
+//! [`BaseRenderGraphIntermediateState::transmission`] before
+//! [`BasePhase::Transparent`]. Only the opaque scene is seen through
+//! transmissive objects, so other blended objects and transmissive objects
+//! behind them disappear.
+//!
+//! In the GpuDriven profile, albedo textures don't tint what is seen
+//! through, only the albedo factor does.
+//!
+//! [`PbrMaterial::transmission`]: crate::pbr::PbrMaterial::transmission
+//! [`BaseRenderGraphIntermediateState::transmission`]: crate::base::BaseRenderGraphIntermediateState::transmission
+//! [`BasePhase::Transparent`]: crate::base::BasePhase::Transparent
+
+use std::{
+    mem,
+    num::{NonZeroU32, NonZeroU64},
+};
+
+use glam::UVec2;
+use rend3::{
+    graph::{RenderGraph, RenderTargetHandle},
+    types::ObjectDepthMode,
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer, RendererDataCore, RendererProfile,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupLayout, BindingType, BlendState, Buffer, BufferBindingType, BufferUsages, Color,
+    ColorTargetState, ColorWrites, Extent3d, FilterMode, FragmentState, LoadOp, MultisampleState, Operations,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use crate::{
+    common::{preprocess_wgsl, RenderStateOverride, ShaderDefines, WholeFrameInterfaces},
+    custom::frame_prelude,
+    forward::ForwardRoutine,
+    pbr::{self, PbrMaterial, PbrRoutine},
+    shaders,
+};
+
+/// Levels of the mip chain of a scene color of `resolution`, down to a
+/// single pixel.
+fn mip_count(resolution: UVec2) -> u32 {
+    32 - resolution.max_element().max(1).leading_zeros()
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct TransmissionUniforms {
+    max_lod: f32,
+    _padding: [f32; 3],
+}
+
+unsafe impl bytemuck::Zeroable for TransmissionUniforms {}
+unsafe impl bytemuck::Pod for TransmissionUniforms {}
+
+/// The copy of the opaque scene transmissive materials see, with one view
+/// and bind group per level.
+struct SceneColor {
+    resolution: UVec2,
+    levels: Vec<TextureView>,
+    /// Reads level `i` to draw level `i + 1`.
+    downsample_bgs: Vec<BindGroup>,
+    /// Group 3 of the transmission pass.
+    bind_groups: Vec<BindGroup>,
+    _uniforms: Buffer,
+}
+
+/// Draws what is seen through blended materials with transmission, see the
+/// [module](self) docs.
+pub struct TransmissionRoutine {
+    /// Default and Biased depth modes, then single and double sided.
+    forward_routines: Vec<ForwardRoutine<PbrMaterial>>,
+    scene_bgl: BindGroupLayout,
+    downsample_bgl: BindGroupLayout,
+    downsample_pipeline: RenderPipeline,
+    sampler: Sampler,
+    scene: SceneColor,
+}
+
+impl TransmissionRoutine {
+    /// `resolution` is the one of the HDR color target, see
+    /// [`resize`](Self::resize).
+    pub fn new(
+        renderer: &Renderer,
+        data_core: &mut RendererDataCore,
+        interfaces: &WholeFrameInterfaces,
+        pbr: &PbrRoutine,
+        resolution: UVec2,
+    ) -> Self {
+        profiling::scope!("TransmissionRoutine::new");
+
+        let device = &renderer.device;
+
+        let scene_bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(mem::size_of::<TransmissionUniforms>() as u64),
+                },
+                None,
+            )
+            .build(device, Some("transmission scene bgl"));
+
+        let downsample_bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .build(device, Some("transmission downsample bgl"));
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("transmission"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+
+        let downsample_module = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("transmission downsample"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "downsample.wgsl",
+                include_str!("../shaders/src/downsample.wgsl"),
+            )),
+        });
+        let downsample_pll = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("transmission downsample"),
+            bind_group_layouts: &[&downsample_bgl],
+            push_constant_ranges: &[],
+        });
+        let downsample_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("transmission downsample"),
+            layout: Some(&downsample_pll),
+            vertex: VertexState {
+                module: &downsample_module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &downsample_module,
+                entry_point: "fs_main",
+                targets: &[ColorTargetState {
+                    format: TextureFormat::Rgba16Float,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                }],
+            }),
+            multiview: None,
+        });
+
+        let defines = ShaderDefines::new()
+            .with_if("GPU_DRIVEN", renderer.profile == RendererProfile::GpuDriven)
+            .with_if("DOWNLEVEL", renderer.profile == RendererProfile::Downlevel);
+        let source = shaders::source_shader("transmission.wgsl", include_str!("../shaders/src/transmission.wgsl"));
+        let source = preprocess_wgsl(&source, &defines).expect("transmission.wgsl has broken #ifdef blocks");
+        let frag = device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("transmission frag"),
+            source: ShaderSource::Wgsl(format!("{}{}", frame_prelude(), source).into()),
+        });
+
+        let mut forward_routines = Vec::with_capacity(4);
+        for depth_mode in [ObjectDepthMode::Default, ObjectDepthMode::Biased] {
+            for double_sided in [false, true] {
+                let label = match double_sided {
+                    true => "double sided transmission pass",
+                    false => "transmission pass",
+                };
+                let label = match depth_mode {
+                    ObjectDepthMode::Biased => format!("biased {}", label),
+                    _ => label.to_string(),
+                };
+                forward_routines.push(ForwardRoutine::new(
+                    renderer,
+                    data_core,
+                    interfaces,
+                    &pbr.per_material,
+                    None,
+                    Some(("main", &frag)),
+                    std::slice::from_ref(&scene_bgl),
+                    Some(BlendState::ALPHA_BLENDING),
+                    false,
+                    PrimitiveTopology::TriangleList,
+                    RenderStateOverride {
+                        cull_mode: double_sided.then(|| None),
+                        ..pbr::depth_mode_override(renderer, depth_mode)
+                    },
+                    &label,
+                ));
+            }
+        }
+
+        let scene = create_scene_color(renderer, &scene_bgl, &downsample_bgl, &sampler, resolution);
+
+        Self {
+            forward_routines,
+            scene_bgl,
+            downsample_bgl,
+            downsample_pipeline,
+            sampler,
+            scene,
+        }
+    }
+
+    /// Recreates the copy of the scene when the resolution of the HDR color
+    /// target changes. A stale size still works, but the background seen
+    /// through transmissive materials is blurrier or sharper than it should.
+    pub fn resize(&mut self, renderer: &Renderer, resolution: UVec2) {
+        if self.scene.resolution != resolution {
+            self.scene = create_scene_color(
+                renderer,
+                &self.scene_bgl,
+                &self.downsample_bgl,
+                &self.sampler,
+                resolution,
+            );
+        }
+    }
+
+    /// The routine drawing transmission of blended objects in `depth_mode`,
+    /// either [`ObjectDepthMode::Default`] or [`ObjectDepthMode::Biased`].
+    pub fn forward_routine(&self, double_sided: bool, depth_mode: ObjectDepthMode) -> &ForwardRoutine<PbrMaterial> {
+        let biased = depth_mode == ObjectDepthMode::Biased;
+        &self.forward_routines[biased as usize * 2 + double_sided as usize]
+    }
+
+    /// Bind groups to pass to
+    /// [`ForwardRoutine::add_forward_to_graph`] of the
+    /// [`forward_routine`](Self::forward_routine)s.
+    pub fn extra_bgs(&self) -> &Vec<BindGroup> {
+        &self.scene.bind_groups
+    }
+
+    /// Copies the single sampled `color` into the scene color and blurs it
+    /// down its mip chain. Must come before the passes of the
+    /// [`forward_routine`](Self::forward_routine)s.
+    pub fn add_scene_copy_to_graph<'node>(&'node self, graph: &mut RenderGraph<'node>, color: RenderTargetHandle) {
+        let mut builder = graph.add_node("Transmission Scene Copy");
+
+        let color_handle = builder.add_render_target_input(color);
+        // The scene color lives outside of the graph.
+        builder.add_external_output();
+
+        let pt_handle = builder.passthrough_ref(self);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let encoder = encoder_or_pass.get_encoder();
+
+            profiling::scope!("transmission scene copy");
+
+            let source_bg = temps.add(
+                BindGroupBuilder::new()
+                    .append_texture_view(graph_data.get_render_target(color_handle))
+                    .append_sampler(&this.sampler)
+                    .build(
+                        &renderer.device,
+                        Some("transmission scene source"),
+                        &this.downsample_bgl,
+                    ),
+            );
+
+            for (level, view) in this.scene.levels.iter().enumerate() {
+                let bg = match level.checked_sub(1) {
+                    Some(previous) => &this.scene.downsample_bgs[previous],
+                    None => &*source_bg,
+                };
+                let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                    label: Some("transmission downsample"),
+                    color_attachments: &[RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Clear(Color::BLACK),
+                            store: true,
+                        },
+                    }],
+                    depth_stencil_attachment: None,
+                });
+                rpass.set_pipeline(&this.downsample_pipeline);
+                rpass.set_bind_group(0, bg, &[]);
+                rpass.draw(0..3, 0..1);
+            }
+        });
+    }
+}
+
+fn create_scene_color(
+    renderer: &Renderer,
+    scene_bgl: &BindGroupLayout,
+    downsample_bgl: &BindGroupLayout,
+    sampler: &Sampler,
+    resolution: UVec2,
+) -> SceneColor {
+    let device = &renderer.device;
+    let mip_level_count = mip_count(resolution);
+
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("transmission scene color"),
+        size: Extent3d {
+            width: resolution.x.max(1),
+            height: resolution.y.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba16Float,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+
+    let levels: Vec<_> = (0..mip_level_count)
+        .map(|level| {
+            texture.create_view(&TextureViewDescriptor {
+                label: Some("transmission scene color level"),
+                base_mip_level: level,
+                mip_level_count: NonZeroU32::new(1),
+                ..TextureViewDescriptor::default()
+            })
+        })
+        .collect();
+
+    let downsample_bgs = levels[..levels.len() - 1]
+        .iter()
+        .map(|view| {
+            BindGroupBuilder::new()
+                .append_texture_view(view)
+                .append_sampler(sampler)
+                .build(device, Some("transmission downsample"), downsample_bgl)
+        })
+        .collect();
+
+    let uniforms = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("transmission uniforms"),
+        contents: bytemuck::bytes_of(&TransmissionUniforms {
+            max_lod: (mip_level_count - 1) as f32,
+            _padding: [0.0; 3],
+        }),
+        usage: BufferUsages::UNIFORM,
+    });
+
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    let bind_groups = vec![BindGroupBuilder::new()
+        .append_texture_view(&view)
+        .append_sampler(sampler)
+        .append_buffer(&uniforms)
+        .build(device, Some("transmission scene"), scene_bgl)];
+
+    SceneColor {
+        resolution,
+        levels,
+        downsample_bgs,
+        bind_groups,
+        _uniforms: uniforms,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::UVec2;
+
+    use super::mip_count;
+
+    #[test]
+    fn mip_chain_reaches_one_pixel() {
+        assert_eq!(mip_count(UVec2::new(1, 1)), 1);
+        assert_eq!(mip_count(UVec2::new(2, 1)), 2);
+        assert_eq!(mip_count(UVec2::new(1920, 1080)), 11);
+        assert_eq!(mip_count(UVec2::new(600, 1024)), 11);
+        assert_eq!(mip_count(UVec2::new(1023, 1023)), 10);
+        assert_eq!(mip_count(UVec2::ZERO), 1);
+    }
+}
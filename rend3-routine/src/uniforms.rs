@@ -4,8 +4,8 @@
 use glam::{Mat4, UVec2, Vec4};
 use rend3::{
     graph::{DataHandle, RenderGraph},
-    managers::CameraManager,
-    util::{bind_merge::BindGroupBuilder, frustum::ShaderFrustum},
+    managers::{CameraManager, VertexQuantization},
+    util::{bind_merge::BindGroupBuilder, geometry::ShaderFrustum},
 };
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
@@ -14,6 +14,9 @@ use wgpu::{
 
 use crate::common::{Samplers, WholeFrameInterfaces};
 
+/// Most clip planes [`FrameUniforms`] can hold.
+pub const MAX_CLIP_PLANES: usize = 4;
+
 /// The actual structure passed to the shader.
 #[derive(Debug, Copy, Clone)]
 #[repr(C, align(16))]
@@ -27,6 +30,13 @@ pub struct FrameUniforms {
     pub frustum: ShaderFrustum,
     pub ambient: Vec4,
     pub resolution: UVec2,
+    pub clip_plane_count: u32,
+    /// Bits of the [`VertexQuantization`] of the renderer, telling the
+    /// shaders whether normals and tangents are octahedral encoded.
+    pub vertex_quantization: u32,
+    /// World space planes, cutting away everything with a negative
+    /// `plane.dot(position.extend(1.0))`.
+    pub clip_planes: [Vec4; MAX_CLIP_PLANES],
 }
 impl FrameUniforms {
     /// Use the given camera to generate these uniforms.
@@ -44,49 +54,88 @@ impl FrameUniforms {
             inv_view: view.inverse(),
             inv_view_proj: view_proj.inverse(),
             inv_origin_view_proj: origin_view_proj.inverse(),
-            frustum: ShaderFrustum::from_matrix(camera.proj()),
+            frustum: ShaderFrustum::from_matrix(camera.proj(), camera.depth_mode()),
             ambient,
             resolution,
+            clip_plane_count: 0,
+            vertex_quantization: 0,
+            clip_planes: [Vec4::ZERO; MAX_CLIP_PLANES],
         }
     }
+
+    /// Cuts away everything on the negative side of `clip_planes`.
+    pub fn with_clip_planes(mut self, clip_planes: &[Vec4]) -> Self {
+        assert!(
+            clip_planes.len() <= MAX_CLIP_PLANES,
+            "at most {} clip planes are supported",
+            MAX_CLIP_PLANES
+        );
+        self.clip_plane_count = clip_planes.len() as u32;
+        self.clip_planes[..clip_planes.len()].copy_from_slice(clip_planes);
+        self
+    }
+
+    /// Decodes vertices stored with `quantization`.
+    pub fn with_vertex_quantization(mut self, quantization: VertexQuantization) -> Self {
+        self.vertex_quantization = quantization.bits() as u32;
+        self
+    }
 }
 
 unsafe impl bytemuck::Zeroable for FrameUniforms {}
 unsafe impl bytemuck::Pod for FrameUniforms {}
 
-/// Add the creation of these uniforms to the graph.
+/// Add the creation of these uniforms to the graph. Each shadow gets uniforms
+/// seen from the camera of its light, in `shadow_uniform_bgs`.
+#[allow(clippy::too_many_arguments)]
 pub fn add_to_graph<'node>(
     graph: &mut RenderGraph<'node>,
-    shadow_uniform_bg: DataHandle<BindGroup>,
+    shadow_uniform_bgs: &[DataHandle<BindGroup>],
     forward_uniform_bg: DataHandle<BindGroup>,
     interfaces: &'node WholeFrameInterfaces,
     samplers: &'node Samplers,
     ambient: Vec4,
     resolution: UVec2,
+    clip_planes: &'node [Vec4],
 ) {
     let mut builder = graph.add_node("build uniform data");
-    let shadow_handle = builder.add_data_output(shadow_uniform_bg);
+    let shadow_handles: Vec<_> = shadow_uniform_bgs
+        .iter()
+        .map(|&shadow_uniform_bg| builder.add_data_output(shadow_uniform_bg))
+        .collect();
     let forward_handle = builder.add_data_output(forward_uniform_bg);
-    builder.build(move |_pt, renderer, _encoder_or_pass, _temps, _ready, graph_data| {
-        let mut bgb = BindGroupBuilder::new();
+    builder.build(move |_pt, renderer, _encoder_or_pass, _temps, ready, graph_data| {
+        let create_uniform_buffer = |camera| {
+            let uniforms = FrameUniforms::new(camera, ambient, resolution)
+                .with_clip_planes(clip_planes)
+                .with_vertex_quantization(renderer.vertex_quantization);
+            renderer.device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("frame uniform"),
+                contents: bytemuck::bytes_of(&uniforms),
+                usage: BufferUsages::UNIFORM,
+            })
+        };
 
-        samplers.add_to_bg(&mut bgb);
+        for (shadow_handle, light_camera) in shadow_handles.into_iter().zip(&ready.directional_light_cameras) {
+            let uniform_buffer = create_uniform_buffer(light_camera);
 
-        let uniforms = FrameUniforms::new(graph_data.camera_manager, ambient, resolution);
-        let uniform_buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("frame uniform"),
-            contents: bytemuck::bytes_of(&uniforms),
-            usage: BufferUsages::UNIFORM,
-        });
+            let mut bgb = BindGroupBuilder::new();
+            samplers.add_to_bg(&mut bgb);
+            bgb.append_buffer(&uniform_buffer);
 
-        bgb.append_buffer(&uniform_buffer);
+            let shadow_uniform_bg = bgb.build(
+                &renderer.device,
+                Some("shadow uniform bg"),
+                &interfaces.depth_uniform_bgl,
+            );
+            graph_data.set_data(shadow_handle, Some(shadow_uniform_bg));
+        }
 
-        let shadow_uniform_bg = bgb.build(
-            &renderer.device,
-            Some("shadow uniform bg"),
-            &interfaces.depth_uniform_bgl,
-        );
+        let uniform_buffer = create_uniform_buffer(graph_data.camera_manager);
 
+        let mut bgb = BindGroupBuilder::new();
+        samplers.add_to_bg(&mut bgb);
+        bgb.append_buffer(&uniform_buffer);
         graph_data.directional_light_manager.add_to_bg(&mut bgb);
 
         let forward_uniform_bg = bgb.build(
@@ -95,7 +144,6 @@ pub fn add_to_graph<'node>(
             &interfaces.forward_uniform_bgl,
         );
 
-        graph_data.set_data(shadow_handle, Some(shadow_uniform_bg));
         graph_data.set_data(forward_handle, Some(forward_uniform_bg));
     })
 }
@@ -0,0 +1,424 @@
+//! Raymarched volumes, for scientific and medical data such as CT scans or
+//! simulated densities.
+//!
+//! A [`Volume`] draws a 3D texture added with
+//! [`Renderer::add_texture_3d`] inside a box, reading the density from its
+//! red channel and turning it into color and opacity with a
+//! [`TransferFunction`]. Draw them with a [`VolumeRoutine`] into the HDR
+//! targets of the base rendergraph, after the forward pass, see
+//! [`BaseRenderGraphIntermediateState::volumes`].
+//!
+//! Volumes are depth tested against the scene where the view ray leaves their
+//! box, so geometry inside a volume hides it entirely along those pixels
+//! rather than cutting it off. The camera must use a perspective projection.
+//!
+//! [`BaseRenderGraphIntermediateState::volumes`]: crate::base::BaseRenderGraphIntermediateState::volumes
+
+use std::cmp::Ordering;
+
+use glam::{Mat4, Vec3, Vec4};
+use rend3::{
+    graph::{DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    types::{DepthMode, SampleCount, TextureHandle},
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroupLayout, BindingType, BlendState, BufferBindingType, BufferUsages, Color, ColorTargetState,
+    ColorWrites, DepthBiasState, DepthStencilState, FilterMode, FragmentState, FrontFace, MultisampleState,
+    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor,
+    Sampler, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, StencilState,
+    TextureFormat, TextureSampleType, TextureViewDimension, VertexState,
+};
+
+use crate::shaders;
+
+/// Most points a [`TransferFunction`] can have.
+pub const MAX_TRANSFER_POINTS: usize = 16;
+/// Most slicing planes a [`Volume`] can have.
+pub const MAX_SLICE_PLANES: usize = 4;
+
+/// Maps densities to linear color and opacity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferFunction {
+    /// Colors spaced evenly over densities from 0 to 1, linearly interpolated
+    /// in between. Alpha is the opacity of a unit length of the volume at that
+    /// density. Between 1 and [`MAX_TRANSFER_POINTS`] points.
+    pub points: Vec<Vec4>,
+}
+
+impl TransferFunction {
+    /// Ramps from transparent black at density 0 to opaque white at 1.
+    pub fn grayscale() -> Self {
+        Self {
+            points: vec![Vec4::ZERO, Vec4::ONE],
+        }
+    }
+
+    /// The color of `density`, as the shader computes it.
+    pub fn sample(&self, density: f32) -> Vec4 {
+        let last = self.points.len() - 1;
+        let x = density.clamp(0.0, 1.0) * last as f32;
+        let index = x.floor() as usize;
+        let next = (index + 1).min(last);
+        self.points[index].lerp(self.points[next], x.fract())
+    }
+}
+
+impl Default for TransferFunction {
+    fn default() -> Self {
+        Self::grayscale()
+    }
+}
+
+/// How the densities along a view ray are combined.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VolumeMode {
+    /// Shows the transfer function of the highest density along the ray, like
+    /// a maximum intensity projection of a scan.
+    MaxIntensity,
+    /// Accumulates the color of every sample, each hiding what is behind it
+    /// by its opacity.
+    EmissionAbsorption,
+}
+
+/// A 3D texture drawn as a volume.
+#[derive(Debug, Clone)]
+pub struct Volume {
+    /// 3D texture whose red channel holds the density.
+    pub texture: TextureHandle,
+    /// Places the box spanning 0 to 1 on every axis, which holds the texture,
+    /// in the world.
+    pub transform: Mat4,
+    pub transfer_function: TransferFunction,
+    pub mode: VolumeMode,
+    /// Samples taken along the diagonal of the box.
+    pub steps: u32,
+    /// Multiplies the opacity of the transfer function in
+    /// [`VolumeMode::EmissionAbsorption`].
+    pub density_scale: f32,
+    /// World space planes cutting the volume, keeping the side
+    /// `dot(plane.xyz, position) + plane.w >= 0`. At most
+    /// [`MAX_SLICE_PLANES`].
+    pub slice_planes: Vec<Vec4>,
+}
+
+impl Volume {
+    /// Max intensity projection of `texture` in the box placed by
+    /// `transform`.
+    pub fn new(texture: TextureHandle, transform: Mat4) -> Self {
+        Self {
+            texture,
+            transform,
+            transfer_function: TransferFunction::default(),
+            mode: VolumeMode::MaxIntensity,
+            steps: 128,
+            density_scale: 1.0,
+            slice_planes: Vec::new(),
+        }
+    }
+
+    pub fn with_transfer_function(mut self, transfer_function: TransferFunction) -> Self {
+        self.transfer_function = transfer_function;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: VolumeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_steps(mut self, steps: u32) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    pub fn with_slice_plane(mut self, plane: Vec4) -> Self {
+        self.slice_planes.push(plane);
+        self
+    }
+}
+
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+struct VolumeUniforms {
+    model_view_proj: Mat4,
+    camera: Vec4,
+    planes: [Vec4; MAX_SLICE_PLANES],
+    transfer: [Vec4; MAX_TRANSFER_POINTS],
+    transfer_count: u32,
+    plane_count: u32,
+    mode: u32,
+    steps: u32,
+    density_scale: f32,
+    _padding: [f32; 3],
+}
+
+unsafe impl bytemuck::Pod for VolumeUniforms {}
+unsafe impl bytemuck::Zeroable for VolumeUniforms {}
+
+impl VolumeUniforms {
+    fn new(volume: &Volume, view_proj: Mat4, camera_location: Vec3) -> Self {
+        assert!(
+            (1..=MAX_TRANSFER_POINTS).contains(&volume.transfer_function.points.len()),
+            "transfer functions need between 1 and {} points",
+            MAX_TRANSFER_POINTS
+        );
+        assert!(
+            volume.slice_planes.len() <= MAX_SLICE_PLANES,
+            "volumes have at most {} slice planes",
+            MAX_SLICE_PLANES
+        );
+
+        let mut planes = [Vec4::ZERO; MAX_SLICE_PLANES];
+        // Planes are row vectors, so the transform carries them to local space
+        // transposed.
+        for (dst, &plane) in planes.iter_mut().zip(&volume.slice_planes) {
+            *dst = volume.transform.transpose() * plane;
+        }
+        let mut transfer = [Vec4::ZERO; MAX_TRANSFER_POINTS];
+        transfer[..volume.transfer_function.points.len()].copy_from_slice(&volume.transfer_function.points);
+
+        Self {
+            model_view_proj: view_proj * volume.transform,
+            camera: volume.transform.inverse().transform_point3(camera_location).extend(1.0),
+            planes,
+            transfer,
+            transfer_count: volume.transfer_function.points.len() as u32,
+            plane_count: volume.slice_planes.len() as u32,
+            mode: match volume.mode {
+                VolumeMode::MaxIntensity => 0,
+                VolumeMode::EmissionAbsorption => 1,
+            },
+            steps: volume.steps.max(1),
+            density_scale: volume.density_scale,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// Draws [`Volume`]s.
+///
+/// See module for documentation.
+pub struct VolumeRoutine {
+    pipeline_s1: RenderPipeline,
+    pipeline_s4: RenderPipeline,
+    bgl: BindGroupLayout,
+    sampler: Sampler,
+    depth_mode: DepthMode,
+}
+
+impl VolumeRoutine {
+    pub fn new(renderer: &Renderer) -> Self {
+        profiling::scope!("VolumeRoutine::new");
+
+        let sampler = renderer.device.create_sampler(&SamplerDescriptor {
+            label: Some("volume"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..SamplerDescriptor::default()
+        });
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::VERTEX_FRAGMENT,
+                BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D3,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .build(&renderer.device, Some("volume bgl"));
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("volume"),
+            source: ShaderSource::Wgsl(shaders::source_shader(
+                "volume.wgsl",
+                include_str!("../shaders/src/volume.wgsl"),
+            )),
+        });
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("volume"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let inner = |samples: SampleCount| {
+            renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("volume"),
+                layout: Some(&pll),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Ccw,
+                    // The shader picks the faces to march from.
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format: TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: renderer.depth_mode.closer_or_equal(),
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState {
+                    count: samples as u32,
+                    ..Default::default()
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: ColorWrites::all(),
+                    }],
+                }),
+                multiview: None,
+            })
+        };
+
+        Self {
+            pipeline_s1: inner(SampleCount::One),
+            pipeline_s4: inner(SampleCount::Four),
+            bgl,
+            sampler,
+            depth_mode: renderer.depth_mode,
+        }
+    }
+
+    /// Draws `volumes` into the HDR targets, back to front, seen from the
+    /// camera of the renderer.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        volumes: &'node [Volume],
+        color: RenderTargetHandle,
+        resolve: Option<RenderTargetHandle>,
+        depth: RenderTargetHandle,
+        samples: SampleCount,
+    ) {
+        if volumes.is_empty() {
+            return;
+        }
+
+        let mut builder = graph.add_node("Volumes");
+
+        let hdr_color_handle = builder.add_render_target_output(color);
+        let hdr_resolve = builder.add_optional_render_target_output(resolve);
+        let hdr_depth_handle = builder.add_render_target_input(depth);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: hdr_color_handle,
+                clear: Color::BLACK,
+                resolve: hdr_resolve,
+            }],
+            depth_stencil: Some(RenderPassDepthTarget {
+                target: DepthHandle::RenderTarget(hdr_depth_handle),
+                depth_clear: Some(self.depth_mode.far_depth()),
+                stencil_clear: None,
+            }),
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+        let volume_handles: Vec<_> = volumes.iter().map(|volume| builder.passthrough_ref(volume)).collect();
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let volumes: Vec<&Volume> = volume_handles.into_iter().map(|handle| pt.get(handle)).collect();
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("volumes");
+
+            let camera = graph_data.camera_manager;
+            let view_proj = camera.view_proj();
+            let camera_location = camera.view().inverse().w_axis.truncate();
+
+            let mut sorted: Vec<(f32, &Volume)> = volumes
+                .into_iter()
+                .map(|volume| {
+                    let center = volume.transform.transform_point3(Vec3::splat(0.5));
+                    (center.distance_squared(camera_location), volume)
+                })
+                .collect();
+            sorted.sort_by(|(left, _), (right, _)| right.partial_cmp(left).unwrap_or(Ordering::Equal));
+
+            let pipeline = match samples {
+                SampleCount::One => &this.pipeline_s1,
+                SampleCount::Four => &this.pipeline_s4,
+            };
+            rpass.set_pipeline(pipeline);
+
+            for (_, volume) in sorted {
+                let uniforms = VolumeUniforms::new(volume, view_proj, camera_location);
+                let buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("volume uniforms"),
+                    contents: bytemuck::bytes_of(&uniforms),
+                    usage: BufferUsages::UNIFORM,
+                });
+                let bg = temps.add(
+                    BindGroupBuilder::new()
+                        .append_buffer(&buffer)
+                        .append_texture_view(graph_data.d3_texture_manager.get_view(volume.texture.get_raw()))
+                        .append_sampler(&this.sampler)
+                        .build(&renderer.device, Some("volume bg"), &this.bgl),
+                );
+
+                rpass.set_bind_group(0, bg, &[]);
+                rpass.draw(0..36, 0..1);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec4;
+
+    use super::TransferFunction;
+
+    #[test]
+    fn transfer_function_interpolates() {
+        let transfer = TransferFunction {
+            points: vec![Vec4::ZERO, Vec4::new(1.0, 0.0, 0.0, 0.5), Vec4::ONE],
+        };
+        assert_eq!(transfer.sample(-1.0), Vec4::ZERO);
+        assert_eq!(transfer.sample(0.25), Vec4::new(0.5, 0.0, 0.0, 0.25));
+        assert_eq!(transfer.sample(0.5), Vec4::new(1.0, 0.0, 0.0, 0.5));
+        assert_eq!(transfer.sample(2.0), Vec4::ONE);
+
+        let constant = TransferFunction {
+            points: vec![Vec4::ONE],
+        };
+        assert_eq!(constant.sample(0.7), Vec4::ONE);
+    }
+}
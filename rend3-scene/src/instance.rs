@@ -0,0 +1,300 @@
+use std::{collections::HashMap, error::Error};
+
+use glam::{Mat4, Vec3, Vec4};
+use rend3::{
+    types::{
+        Camera, DirectionalLightHandle, MaterialHandle, MeshHandle, Object, ObjectHandle, ObjectMeshKind, TextureHandle,
+    },
+    Renderer,
+};
+use rend3_routine::pbr::{
+    AlbedoComponent, AoMRTextures, MaterialComponent, NormalTexture, NormalTextureYDirection, PbrMaterial, Transparency,
+};
+
+use crate::{
+    Scene, SceneCamera, SceneCells, SceneDirectionalLight, SceneError, SceneMaterial, SceneObject, SceneTransparency,
+};
+
+/// Loads the asset files a [`Scene`] references, e.g. by reading them from
+/// disk and decoding them with rend3-gltf and an image crate.
+///
+/// Every path is only loaded once per [`SceneInstance`].
+pub trait SceneAssets {
+    fn load_mesh(&mut self, renderer: &Renderer, path: &str) -> Result<MeshHandle, Box<dyn Error + Send + Sync>>;
+
+    /// `srgb` is true for color textures, and false for data such as
+    /// normals.
+    fn load_texture(
+        &mut self,
+        renderer: &Renderer,
+        path: &str,
+        srgb: bool,
+    ) -> Result<TextureHandle, Box<dyn Error + Send + Sync>>;
+}
+
+/// A [`Scene`] added to a renderer, keeping the handles that keep it alive.
+///
+/// Objects, materials and lights are referred to by their index in the
+/// scene. Removing an object shifts the indices of the ones after it.
+pub struct SceneInstance {
+    scene: Scene,
+    materials: Vec<MaterialHandle>,
+    objects: Vec<ObjectHandle>,
+    directional_lights: Vec<DirectionalLightHandle>,
+    meshes: HashMap<String, MeshHandle>,
+    textures: HashMap<(String, bool), TextureHandle>,
+}
+
+impl SceneInstance {
+    /// Adds everything in `scene` to the renderer, and sets its camera if
+    /// the scene has one.
+    pub fn load(renderer: &Renderer, scene: Scene, assets: &mut impl SceneAssets) -> Result<Self, SceneError> {
+        profiling::scope!("SceneInstance::load");
+
+        scene.validate()?;
+
+        let mut instance = Self {
+            scene: Scene {
+                camera: None,
+                materials: Vec::with_capacity(scene.materials.len()),
+                objects: Vec::with_capacity(scene.objects.len()),
+                directional_lights: Vec::with_capacity(scene.directional_lights.len()),
+                ..scene.clone()
+            },
+            materials: Vec::with_capacity(scene.materials.len()),
+            objects: Vec::with_capacity(scene.objects.len()),
+            directional_lights: Vec::with_capacity(scene.directional_lights.len()),
+            meshes: HashMap::new(),
+            textures: HashMap::new(),
+        };
+
+        for material in scene.materials {
+            instance.add_material(renderer, assets, material)?;
+        }
+        for object in scene.objects {
+            instance.add_object(renderer, assets, object)?;
+        }
+        for light in scene.directional_lights {
+            instance.add_directional_light(renderer, light);
+        }
+        if let Some(camera) = scene.camera {
+            instance.set_camera(renderer, camera.into());
+        }
+        if let Some(cells) = &scene.cells {
+            renderer.set_cell_graph(Some(cells.to_graph()?));
+        }
+
+        Ok(instance)
+    }
+
+    /// The scene as it currently is, to be saved.
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn object_handle(&self, object: usize) -> &ObjectHandle {
+        &self.objects[object]
+    }
+
+    pub fn material_handle(&self, material: usize) -> &MaterialHandle {
+        &self.materials[material]
+    }
+
+    pub fn directional_light_handle(&self, light: usize) -> &DirectionalLightHandle {
+        &self.directional_lights[light]
+    }
+
+    /// Index of the first object with the given name.
+    pub fn find_object(&self, name: &str) -> Option<usize> {
+        self.scene
+            .objects
+            .iter()
+            .position(|object| object.name.as_deref() == Some(name))
+    }
+
+    /// Adds a material, returning its index.
+    pub fn add_material(
+        &mut self,
+        renderer: &Renderer,
+        assets: &mut impl SceneAssets,
+        material: SceneMaterial,
+    ) -> Result<usize, SceneError> {
+        let handle = renderer.add_material(self.pbr_material(renderer, assets, &material)?);
+        self.materials.push(handle);
+        self.scene.materials.push(material);
+        Ok(self.materials.len() - 1)
+    }
+
+    /// Replaces a material. Objects using it are updated as well.
+    pub fn update_material(
+        &mut self,
+        renderer: &Renderer,
+        assets: &mut impl SceneAssets,
+        material: usize,
+        data: SceneMaterial,
+    ) -> Result<(), SceneError> {
+        let pbr = self.pbr_material(renderer, assets, &data)?;
+        renderer.update_material(&self.materials[material], pbr);
+        self.scene.materials[material] = data;
+        Ok(())
+    }
+
+    /// Adds an object, returning its index.
+    pub fn add_object(
+        &mut self,
+        renderer: &Renderer,
+        assets: &mut impl SceneAssets,
+        object: SceneObject,
+    ) -> Result<usize, SceneError> {
+        if object.material >= self.materials.len() {
+            return Err(SceneError::MaterialOutOfRange {
+                object: self.objects.len(),
+                material: object.material,
+                count: self.materials.len(),
+            });
+        }
+        let mesh = self.mesh(renderer, assets, &object.mesh)?;
+        let handle = renderer.add_object(Object {
+            mesh_kind: ObjectMeshKind::Static(mesh),
+            material: self.materials[object.material].clone(),
+            transform: object.transform(),
+        });
+        self.objects.push(handle);
+        self.scene.objects.push(object);
+        Ok(self.objects.len() - 1)
+    }
+
+    pub fn set_object_transform(&mut self, renderer: &Renderer, object: usize, transform: Mat4) {
+        renderer.set_object_transform(&self.objects[object], transform);
+        self.scene.objects[object].transform = transform.to_cols_array();
+    }
+
+    /// Removes an object from the scene and the renderer.
+    pub fn remove_object(&mut self, object: usize) -> SceneObject {
+        self.objects.remove(object);
+        self.scene.objects.remove(object)
+    }
+
+    /// Adds a directional light, returning its index.
+    pub fn add_directional_light(&mut self, renderer: &Renderer, light: SceneDirectionalLight) -> usize {
+        self.directional_lights
+            .push(renderer.add_directional_light(light.into()));
+        self.scene.directional_lights.push(light);
+        self.directional_lights.len() - 1
+    }
+
+    pub fn update_directional_light(&mut self, renderer: &Renderer, light: usize, data: SceneDirectionalLight) {
+        renderer.update_directional_light(
+            &self.directional_lights[light],
+            rend3::types::DirectionalLightChange {
+                color: Some(Vec3::from(data.color)),
+                intensity: Some(data.intensity),
+                direction: Some(Vec3::from(data.direction)),
+                distance: Some(data.distance),
+            },
+        );
+        self.scene.directional_lights[light] = data;
+    }
+
+    /// Removes a directional light from the scene and the renderer.
+    pub fn remove_directional_light(&mut self, light: usize) -> SceneDirectionalLight {
+        self.directional_lights.remove(light);
+        self.scene.directional_lights.remove(light)
+    }
+
+    /// Sets the camera of the renderer, storing it in the scene.
+    pub fn set_camera(&mut self, renderer: &Renderer, camera: Camera) {
+        renderer.set_camera_data(camera);
+        self.scene.camera = Some(SceneCamera::from(camera));
+    }
+
+    /// Sets the cells and portals of the renderer, storing them in the scene.
+    pub fn set_cells(&mut self, renderer: &Renderer, cells: Option<SceneCells>) -> Result<(), SceneError> {
+        let graph = cells.as_ref().map(SceneCells::to_graph).transpose()?;
+        renderer.set_cell_graph(graph);
+        self.scene.cells = cells;
+        Ok(())
+    }
+
+    fn mesh(
+        &mut self,
+        renderer: &Renderer,
+        assets: &mut impl SceneAssets,
+        path: &str,
+    ) -> Result<MeshHandle, SceneError> {
+        if let Some(handle) = self.meshes.get(path) {
+            return Ok(handle.clone());
+        }
+        let handle = assets.load_mesh(renderer, path).map_err(|source| SceneError::Asset {
+            path: path.to_owned(),
+            source,
+        })?;
+        self.meshes.insert(path.to_owned(), handle.clone());
+        Ok(handle)
+    }
+
+    fn texture(
+        &mut self,
+        renderer: &Renderer,
+        assets: &mut impl SceneAssets,
+        path: &Option<String>,
+        srgb: bool,
+    ) -> Result<Option<TextureHandle>, SceneError> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let key = (path.clone(), srgb);
+        if let Some(handle) = self.textures.get(&key) {
+            return Ok(Some(handle.clone()));
+        }
+        let handle = assets
+            .load_texture(renderer, path, srgb)
+            .map_err(|source| SceneError::Asset {
+                path: path.clone(),
+                source,
+            })?;
+        self.textures.insert(key, handle.clone());
+        Ok(Some(handle))
+    }
+
+    fn pbr_material(
+        &mut self,
+        renderer: &Renderer,
+        assets: &mut impl SceneAssets,
+        material: &SceneMaterial,
+    ) -> Result<PbrMaterial, SceneError> {
+        let albedo = Vec4::from(material.albedo);
+        let emissive = Vec3::from(material.emissive);
+        Ok(PbrMaterial {
+            albedo: match self.texture(renderer, assets, &material.albedo_texture, true)? {
+                Some(texture) => AlbedoComponent::TextureValue { texture, value: albedo },
+                None => AlbedoComponent::Value(albedo),
+            },
+            transparency: match material.transparency {
+                SceneTransparency::Opaque => Transparency::Opaque,
+                SceneTransparency::Cutout { cutout } => Transparency::Cutout { cutout },
+                SceneTransparency::Blend => Transparency::Blend,
+            },
+            normal: match self.texture(renderer, assets, &material.normal_texture, false)? {
+                Some(texture) => NormalTexture::Tricomponent(texture, NormalTextureYDirection::Up),
+                None => NormalTexture::None,
+            },
+            aomr_textures: match self.texture(renderer, assets, &material.aomr_texture, false)? {
+                Some(texture) => AoMRTextures::Combined { texture: Some(texture) },
+                None => AoMRTextures::None,
+            },
+            metallic_factor: Some(material.metallic),
+            roughness_factor: Some(material.roughness),
+            emissive: match self.texture(renderer, assets, &material.emissive_texture, true)? {
+                Some(texture) => MaterialComponent::TextureValue {
+                    texture,
+                    value: emissive,
+                },
+                None => MaterialComponent::Value(emissive),
+            },
+            unlit: material.unlit,
+            ..PbrMaterial::default()
+        })
+    }
+}
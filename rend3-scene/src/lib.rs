@@ -0,0 +1,445 @@
+//! Serializable scenes for rend3.
+//!
+//! A [`Scene`] describes the objects, materials, directional lights and
+//! camera of a renderer, with meshes and textures referenced by the paths of
+//! the asset files they come from. It can be stored with any serde format,
+//! [`Scene::to_json`] and [`Scene::from_json`] being provided for
+//! convenience.
+//!
+//! [`SceneInstance::load`] adds a scene to a renderer, loading the assets it
+//! references through [`SceneAssets`]. Edits made through the instance are
+//! applied to both the renderer and the scene, so [`SceneInstance::scene`]
+//! is always a snapshot of what is being rendered, ready to be saved.
+//!
+//! Indoor scenes can carry [`SceneCells`], cells and portals baked by a level
+//! editor, which are handed to
+//! [`Renderer::set_cell_graph`](rend3::Renderer::set_cell_graph) on load.
+
+use std::error::Error;
+
+use glam::{Mat4, Vec3, Vec3A};
+use rend3::{
+    types::{Camera, CameraProjection, DirectionalLight},
+    util::cells::{Cell, CellGraph, CellGraphError, Portal},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+mod instance;
+
+pub use instance::{SceneAssets, SceneInstance};
+
+/// Version of the format written by this crate.
+pub const SCENE_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum SceneError {
+    #[error("Scene format version {0} is newer than the supported version {SCENE_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("Object {object} uses material {material}, but the scene only has {count} materials")]
+    MaterialOutOfRange {
+        object: usize,
+        material: usize,
+        count: usize,
+    },
+    #[error("Failed to load asset {path}")]
+    Asset {
+        path: String,
+        #[source]
+        source: Box<dyn Error + Send + Sync + 'static>,
+    },
+    #[error("Invalid cells")]
+    Cells(#[from] CellGraphError),
+    #[error("Failed to (de)serialize the scene")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Everything needed to recreate what a renderer shows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub version: u32,
+    #[serde(default)]
+    pub camera: Option<SceneCamera>,
+    #[serde(default)]
+    pub materials: Vec<SceneMaterial>,
+    #[serde(default)]
+    pub objects: Vec<SceneObject>,
+    #[serde(default)]
+    pub directional_lights: Vec<SceneDirectionalLight>,
+    #[serde(default)]
+    pub cells: Option<SceneCells>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            version: SCENE_VERSION,
+            camera: None,
+            materials: Vec::new(),
+            objects: Vec::new(),
+            directional_lights: Vec::new(),
+            cells: None,
+        }
+    }
+}
+
+impl Scene {
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SceneError> {
+        let scene: Self = serde_json::from_str(json)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    /// Checks the version and that all references within the scene are in
+    /// range. Done by [`Scene::from_json`] and [`SceneInstance::load`].
+    pub fn validate(&self) -> Result<(), SceneError> {
+        if self.version > SCENE_VERSION {
+            return Err(SceneError::UnsupportedVersion(self.version));
+        }
+        for (idx, object) in self.objects.iter().enumerate() {
+            if object.material >= self.materials.len() {
+                return Err(SceneError::MaterialOutOfRange {
+                    object: idx,
+                    material: object.material,
+                    count: self.materials.len(),
+                });
+            }
+        }
+        if let Some(cells) = &self.cells {
+            cells.to_graph()?;
+        }
+        Ok(())
+    }
+}
+
+/// An instance of a mesh in the world.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneObject {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Path of the mesh, as understood by [`SceneAssets::load_mesh`].
+    pub mesh: String,
+    /// Index into [`Scene::materials`].
+    pub material: usize,
+    /// Column major.
+    pub transform: [f32; 16],
+}
+
+impl SceneObject {
+    pub fn new(mesh: impl Into<String>, material: usize, transform: Mat4) -> Self {
+        Self {
+            name: None,
+            mesh: mesh.into(),
+            material,
+            transform: transform.to_cols_array(),
+        }
+    }
+
+    pub fn transform(&self) -> Mat4 {
+        Mat4::from_cols_array(&self.transform)
+    }
+}
+
+/// How the alpha of a material is used.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode")]
+pub enum SceneTransparency {
+    Opaque,
+    Cutout { cutout: f32 },
+    Blend,
+}
+
+impl Default for SceneTransparency {
+    fn default() -> Self {
+        Self::Opaque
+    }
+}
+
+/// The subset of a [`PbrMaterial`](rend3_routine::pbr::PbrMaterial) that
+/// asset pipelines usually produce, with textures referenced by path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneMaterial {
+    pub name: Option<String>,
+    /// Linear RGBA, multiplied with the albedo texture.
+    pub albedo: [f32; 4],
+    pub albedo_texture: Option<String>,
+    pub normal_texture: Option<String>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Ambient occlusion in R, roughness in G and metallic in B, as in glTF.
+    pub aomr_texture: Option<String>,
+    /// Linear RGB, multiplied with the emissive texture.
+    pub emissive: [f32; 3],
+    pub emissive_texture: Option<String>,
+    pub transparency: SceneTransparency,
+    pub unlit: bool,
+}
+
+impl Default for SceneMaterial {
+    fn default() -> Self {
+        Self {
+            name: None,
+            albedo: [1.0; 4],
+            albedo_texture: None,
+            normal_texture: None,
+            metallic: 0.0,
+            roughness: 1.0,
+            aomr_texture: None,
+            emissive: [0.0; 3],
+            emissive_texture: None,
+            transparency: SceneTransparency::Opaque,
+            unlit: false,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneDirectionalLight {
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub direction: [f32; 3],
+    /// Distance from the camera shadows are rendered up to.
+    pub distance: f32,
+}
+
+impl From<DirectionalLight> for SceneDirectionalLight {
+    fn from(light: DirectionalLight) -> Self {
+        Self {
+            color: light.color.to_array(),
+            intensity: light.intensity,
+            direction: light.direction.to_array(),
+            distance: light.distance,
+        }
+    }
+}
+
+impl From<SceneDirectionalLight> for DirectionalLight {
+    fn from(light: SceneDirectionalLight) -> Self {
+        Self {
+            color: Vec3::from(light.color),
+            intensity: light.intensity,
+            direction: Vec3::from(light.direction),
+            distance: light.distance,
+        }
+    }
+}
+
+/// Mirrors [`Cell`].
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneCell {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+/// Mirrors [`Portal`].
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScenePortal {
+    pub cells: [usize; 2],
+    pub corners: [[f32; 3]; 4],
+}
+
+/// Precomputed visibility of an indoor scene, see [`rend3::util::cells`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneCells {
+    pub cells: Vec<SceneCell>,
+    #[serde(default)]
+    pub portals: Vec<ScenePortal>,
+}
+
+impl SceneCells {
+    pub fn to_graph(&self) -> Result<CellGraph, CellGraphError> {
+        let cells = self
+            .cells
+            .iter()
+            .map(|cell| Cell {
+                min: Vec3::from(cell.min),
+                max: Vec3::from(cell.max),
+            })
+            .collect();
+        let portals = self
+            .portals
+            .iter()
+            .map(|portal| Portal {
+                cells: portal.cells,
+                corners: portal.corners.map(Vec3::from),
+            })
+            .collect();
+        CellGraph::new(cells, portals)
+    }
+}
+
+impl From<&CellGraph> for SceneCells {
+    fn from(graph: &CellGraph) -> Self {
+        Self {
+            cells: graph
+                .cells()
+                .iter()
+                .map(|cell| SceneCell {
+                    min: cell.min.to_array(),
+                    max: cell.max.to_array(),
+                })
+                .collect(),
+            portals: graph
+                .portals()
+                .iter()
+                .map(|portal| ScenePortal {
+                    cells: portal.cells,
+                    corners: portal.corners.map(|corner| corner.to_array()),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Mirrors [`CameraProjection`], angles being in the same units.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum SceneProjection {
+    Orthographic {
+        size: [f32; 3],
+    },
+    Perspective {
+        vfov: f32,
+        near: f32,
+    },
+    Asymmetric {
+        left: f32,
+        right: f32,
+        up: f32,
+        down: f32,
+        near: f32,
+    },
+    Raw {
+        matrix: [f32; 16],
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneCamera {
+    pub projection: SceneProjection,
+    /// World to view matrix, column major.
+    pub view: [f32; 16],
+}
+
+impl From<Camera> for SceneCamera {
+    fn from(camera: Camera) -> Self {
+        let projection = match camera.projection {
+            CameraProjection::Orthographic { size } => SceneProjection::Orthographic { size: size.to_array() },
+            CameraProjection::Perspective { vfov, near } => SceneProjection::Perspective { vfov, near },
+            CameraProjection::Asymmetric {
+                left,
+                right,
+                up,
+                down,
+                near,
+            } => SceneProjection::Asymmetric {
+                left,
+                right,
+                up,
+                down,
+                near,
+            },
+            CameraProjection::Raw(matrix) => SceneProjection::Raw {
+                matrix: matrix.to_cols_array(),
+            },
+        };
+        Self {
+            projection,
+            view: camera.view.to_cols_array(),
+        }
+    }
+}
+
+impl From<SceneCamera> for Camera {
+    fn from(camera: SceneCamera) -> Self {
+        let projection = match camera.projection {
+            SceneProjection::Orthographic { size } => CameraProjection::Orthographic {
+                size: Vec3A::from(size),
+            },
+            SceneProjection::Perspective { vfov, near } => CameraProjection::Perspective { vfov, near },
+            SceneProjection::Asymmetric {
+                left,
+                right,
+                up,
+                down,
+                near,
+            } => CameraProjection::Asymmetric {
+                left,
+                right,
+                up,
+                down,
+                near,
+            },
+            SceneProjection::Raw { matrix } => CameraProjection::Raw(Mat4::from_cols_array(&matrix)),
+        };
+        Self {
+            projection,
+            view: Mat4::from_cols_array(&camera.view),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Scene, SceneCamera, SceneCell, SceneCells, SceneError, SceneMaterial, SceneObject, ScenePortal,
+        SceneTransparency,
+    };
+    use glam::{Mat4, Vec3};
+    use rend3::types::{Camera, CameraProjection};
+
+    #[test]
+    fn json_round_trip() {
+        let scene = Scene {
+            camera: Some(SceneCamera::from(Camera {
+                projection: CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+                view: Mat4::look_at_rh(Vec3::new(0.0, 2.0, 5.0), Vec3::ZERO, Vec3::Y),
+            })),
+            materials: vec![SceneMaterial {
+                albedo_texture: Some("textures/bricks.png".into()),
+                transparency: SceneTransparency::Cutout { cutout: 0.5 },
+                ..SceneMaterial::default()
+            }],
+            objects: vec![SceneObject::new("models/wall.glb", 0, Mat4::from_translation(Vec3::X))],
+            cells: Some(SceneCells {
+                cells: vec![
+                    SceneCell {
+                        min: [0.0; 3],
+                        max: [4.0; 3],
+                    },
+                    SceneCell {
+                        min: [4.0, 0.0, 0.0],
+                        max: [8.0, 4.0, 4.0],
+                    },
+                ],
+                portals: vec![ScenePortal {
+                    cells: [0, 1],
+                    corners: [[4.0, 0.0, 1.0], [4.0, 0.0, 3.0], [4.0, 2.0, 3.0], [4.0, 2.0, 1.0]],
+                }],
+            }),
+            ..Scene::default()
+        };
+
+        let json = scene.to_json().unwrap();
+        assert_eq!(Scene::from_json(&json).unwrap(), scene);
+
+        let broken = json.replace("\"material\": 0", "\"material\": 1");
+        assert!(matches!(
+            Scene::from_json(&broken),
+            Err(SceneError::MaterialOutOfRange {
+                object: 0,
+                material: 1,
+                count: 1
+            })
+        ));
+
+        let mut broken = scene;
+        broken.cells.as_mut().unwrap().portals[0].cells = [0, 2];
+        assert!(matches!(broken.validate(), Err(SceneError::Cells(_))));
+    }
+}
@@ -0,0 +1,200 @@
+//! Mirrors of renderer objects keyed by the entity ids of any ECS, batching
+//! per-frame changes into the renderer.
+//!
+//! ECSes like hecs, legion or bevy_ecs tell which components changed, but
+//! not whether the value the renderer holds is different. The mirrors here
+//! remember what was last sent for each entity: systems write every changed
+//! component into them, and [`TransformSync::flush`] and
+//! [`VisibilitySync::flush`] send only what actually differs, once per
+//! frame, in the order entities were first changed.
+//!
+//! ```ignore
+//! // When spawning an entity with a renderable:
+//! let handle = renderer.add_object(object);
+//! transforms.insert(entity, handle.clone(), transform);
+//! visibility.insert(entity, handle);
+//!
+//! // Each frame, from change detection queries:
+//! for (entity, transform) in changed_transforms {
+//!     transforms.set(&entity, transform.compute_matrix());
+//! }
+//! transforms.retain(|entity| world.contains(*entity));
+//! transforms.flush(&renderer);
+//! visibility.flush(&renderer);
+//! ```
+//!
+//! Objects are removed from the renderer when the last handle to them is
+//! dropped, so removing an entity from every mirror holding it removes its
+//! object, unless the caller keeps another handle.
+
+use std::hash::Hash;
+
+use glam::Mat4;
+use rend3::{
+    types::{ObjectGroup, ObjectGroupHandle, ObjectHandle},
+    Renderer,
+};
+
+mod mirror;
+
+use mirror::Mirror;
+
+/// Transforms of objects, keyed by entity, see the [crate](crate) docs.
+pub struct TransformSync<K> {
+    mirror: Mirror<K, ObjectHandle, Mat4>,
+}
+
+impl<K> Default for TransformSync<K> {
+    fn default() -> Self {
+        Self {
+            mirror: Mirror::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> TransformSync<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.mirror.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mirror.len() == 0
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.mirror.contains(key)
+    }
+
+    pub fn handle(&self, key: &K) -> Option<&ObjectHandle> {
+        self.mirror.handle(key)
+    }
+
+    /// Entities whose transform changed since the last flush.
+    pub fn pending_count(&self) -> usize {
+        self.mirror.pending_count()
+    }
+
+    /// Tracks the object of `key`, whose transform is currently `transform`,
+    /// usually the one it was added with. Returns the handle it replaces.
+    pub fn insert(&mut self, key: K, handle: ObjectHandle, transform: Mat4) -> Option<ObjectHandle> {
+        self.mirror.insert(key, handle, transform)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<ObjectHandle> {
+        self.mirror.remove(key).map(|(handle, _)| handle)
+    }
+
+    /// Stops tracking the entities `keep` returns false for, such as
+    /// despawned ones.
+    pub fn retain(&mut self, keep: impl FnMut(&K) -> bool) {
+        self.mirror.retain(keep, |_, _| {})
+    }
+
+    /// Sets the transform of the object of `key` at the next flush. Returns
+    /// false if `key` isn't tracked.
+    pub fn set(&mut self, key: &K, transform: Mat4) -> bool {
+        self.mirror.set(key, transform)
+    }
+
+    /// Sends the transforms which changed since the last flush, returning how
+    /// many.
+    pub fn flush(&mut self, renderer: &Renderer) -> usize {
+        self.mirror
+            .flush(|handle, &transform| renderer.set_object_transform(handle, transform))
+    }
+}
+
+/// Visibility of objects, keyed by entity, see the [crate](crate) docs.
+///
+/// Hidden objects are moved into a hidden object group owned by the mirror,
+/// and shown ones out of it, so the objects must not be in groups of their
+/// own.
+pub struct VisibilitySync<K> {
+    mirror: Mirror<K, ObjectHandle, bool>,
+    hidden: ObjectGroupHandle,
+    /// Removed objects still in the hidden group.
+    released: Vec<ObjectHandle>,
+}
+
+impl<K: Hash + Eq + Clone> VisibilitySync<K> {
+    pub fn new(renderer: &Renderer) -> Self {
+        Self {
+            mirror: Mirror::default(),
+            hidden: renderer.add_object_group(ObjectGroup {
+                visible: false,
+                ..ObjectGroup::default()
+            }),
+            released: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mirror.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mirror.len() == 0
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.mirror.contains(key)
+    }
+
+    pub fn handle(&self, key: &K) -> Option<&ObjectHandle> {
+        self.mirror.handle(key)
+    }
+
+    /// Entities whose visibility changed since the last flush.
+    pub fn pending_count(&self) -> usize {
+        self.mirror.pending_count()
+    }
+
+    /// Tracks the visible object of `key`. Returns the handle it replaces.
+    pub fn insert(&mut self, key: K, handle: ObjectHandle) -> Option<ObjectHandle> {
+        self.mirror.insert(key, handle, true)
+    }
+
+    /// Stops tracking `key`. If its object was hidden, it leaves the hidden
+    /// group at the next flush, as the group keeps it alive until then.
+    pub fn remove(&mut self, key: &K) -> Option<ObjectHandle> {
+        let (handle, visible) = self.mirror.remove(key)?;
+        if !visible {
+            self.released.push(handle.clone());
+        }
+        Some(handle)
+    }
+
+    /// Stops tracking the entities `keep` returns false for, such as
+    /// despawned ones, see [`remove`](Self::remove).
+    pub fn retain(&mut self, keep: impl FnMut(&K) -> bool) {
+        let released = &mut self.released;
+        self.mirror.retain(keep, |handle, visible| {
+            if !visible {
+                released.push(handle);
+            }
+        })
+    }
+
+    /// Shows or hides the object of `key` at the next flush. Returns false
+    /// if `key` isn't tracked.
+    pub fn set(&mut self, key: &K, visible: bool) -> bool {
+        self.mirror.set(key, visible)
+    }
+
+    /// Sends the visibilities which changed since the last flush, returning
+    /// how many.
+    pub fn flush(&mut self, renderer: &Renderer) -> usize {
+        for handle in self.released.drain(..) {
+            renderer.set_object_group(&handle, None);
+        }
+
+        let hidden = &self.hidden;
+        self.mirror.flush(|handle, &visible| {
+            renderer.set_object_group(handle, (!visible).then(|| hidden));
+        })
+    }
+}
@@ -0,0 +1,158 @@
+use std::hash::Hash;
+
+use rend3::util::typedefs::FastHashMap;
+
+struct Entry<H, V> {
+    handle: H,
+    /// What the renderer was last told.
+    synced: V,
+    /// Set since the last flush, if it differs from `synced`.
+    pending: Option<V>,
+}
+
+/// Last value sent to the renderer for each key, and the changes since.
+pub(crate) struct Mirror<K, H, V> {
+    entries: FastHashMap<K, Entry<H, V>>,
+    /// Keys with a pending value, in the order they were first changed.
+    dirty: Vec<K>,
+}
+
+impl<K, H, V> Default for Mirror<K, H, V> {
+    fn default() -> Self {
+        Self {
+            entries: FastHashMap::default(),
+            dirty: Vec::new(),
+        }
+    }
+}
+
+impl<K, H, V> Mirror<K, H, V>
+where
+    K: Hash + Eq + Clone,
+    V: PartialEq,
+{
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn handle(&self, key: &K) -> Option<&H> {
+        self.entries.get(key).map(|entry| &entry.handle)
+    }
+
+    /// Keys changed since the last flush, counting changes cancelled since.
+    pub fn pending_count(&self) -> usize {
+        self.dirty.len()
+    }
+
+    /// Tracks `key`, whose renderer side currently holds `synced`. Replaces
+    /// the entry of a key already tracked, dropping its pending value.
+    pub fn insert(&mut self, key: K, handle: H, synced: V) -> Option<H> {
+        let old = self.entries.insert(
+            key,
+            Entry {
+                handle,
+                synced,
+                pending: None,
+            },
+        );
+        old.map(|entry| entry.handle)
+    }
+
+    /// Stops tracking `key`, returning its handle and synced value.
+    pub fn remove(&mut self, key: &K) -> Option<(H, V)> {
+        self.entries.remove(key).map(|entry| (entry.handle, entry.synced))
+    }
+
+    /// Stops tracking the keys `keep` returns false for, passing their
+    /// handle and synced value to `removed`.
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool, mut removed: impl FnMut(H, V)) {
+        let keys: Vec<K> = self.entries.keys().filter(|key| !keep(key)).cloned().collect();
+        for key in keys {
+            if let Some((handle, synced)) = self.remove(&key) {
+                removed(handle, synced);
+            }
+        }
+    }
+
+    /// Queues `value` for `key`, returns false if `key` isn't tracked.
+    /// Setting the value the renderer already holds cancels what is pending.
+    pub fn set(&mut self, key: &K, value: V) -> bool {
+        let entry = match self.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        if entry.synced == value {
+            entry.pending = None;
+        } else {
+            if entry.pending.is_none() {
+                self.dirty.push(key.clone());
+            }
+            entry.pending = Some(value);
+        }
+        true
+    }
+
+    /// Calls `apply` with every pending value, which become the synced
+    /// values. Returns how many there were.
+    pub fn flush(&mut self, mut apply: impl FnMut(&H, &V)) -> usize {
+        let mut count = 0;
+        for key in self.dirty.drain(..) {
+            // Removed or reset since it was changed.
+            let entry = match self.entries.get_mut(&key) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if let Some(value) = entry.pending.take() {
+                apply(&entry.handle, &value);
+                entry.synced = value;
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Mirror;
+
+    fn flushed(mirror: &mut Mirror<u32, &'static str, i32>) -> Vec<(&'static str, i32)> {
+        let mut applied = Vec::new();
+        mirror.flush(|&handle, &value| applied.push((handle, value)));
+        applied
+    }
+
+    #[test]
+    fn flushes_only_changes() {
+        let mut mirror = Mirror::default();
+        mirror.insert(1, "a", 0);
+        mirror.insert(2, "b", 0);
+        mirror.insert(3, "c", 0);
+
+        assert!(mirror.set(&2, 5));
+        assert!(mirror.set(&1, 0));
+        assert!(mirror.set(&3, 1));
+        assert!(mirror.set(&2, 6));
+        assert!(!mirror.set(&4, 1));
+        assert_eq!(mirror.pending_count(), 2);
+        assert_eq!(flushed(&mut mirror), vec![("b", 6), ("c", 1)]);
+        assert!(flushed(&mut mirror).is_empty());
+
+        // Going back to the synced value cancels the change.
+        mirror.set(&2, 7);
+        mirror.set(&2, 6);
+        // Removed entries are skipped.
+        mirror.set(&3, 2);
+        assert_eq!(mirror.remove(&3), Some(("c", 1)));
+        assert!(flushed(&mut mirror).is_empty());
+
+        let mut removed = Vec::new();
+        mirror.retain(|&key| key != 2, |handle, synced| removed.push((handle, synced)));
+        assert_eq!(removed, vec![("b", 6)]);
+        assert_eq!(mirror.len(), 1);
+    }
+}
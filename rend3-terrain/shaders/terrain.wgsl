@@ -0,0 +1,220 @@
+// Terrain chunks drawn by TerrainRoutine, see routine.rs.
+
+struct Frustum {
+    left: vec4<f32>;
+    right: vec4<f32>;
+    top: vec4<f32>;
+    bottom: vec4<f32>;
+    near: vec4<f32>;
+};
+
+/// Start of FrameUniforms in uniforms.rs of rend3-routine.
+struct FrameUniforms {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    origin_view_proj: mat4x4<f32>;
+    inv_view: mat4x4<f32>;
+    inv_view_proj: mat4x4<f32>;
+    inv_origin_view_proj: mat4x4<f32>;
+    frustum: Frustum;
+    ambient: vec4<f32>;
+};
+
+/// Layout of ShaderDirectionalLight in directional.rs.
+struct DirectionalLight {
+    view_proj: mat4x4<f32>;
+    color: vec3<f32>;
+    direction: vec3<f32>;
+    offset: vec2<f32>;
+    size: f32;
+};
+
+struct DirectionalLightBuffer {
+    total_lights: u32;
+    lights: array<DirectionalLight>;
+};
+
+/// Bound instead of DirectionalLightBuffer in the Downlevel profile. Must
+/// match DOWNLEVEL_MAX_DIRECTIONAL_LIGHTS.
+struct DownlevelDirectionalLightBuffer {
+    total_lights: u32;
+    lights: array<DirectionalLight, 4>;
+};
+
+[[group(0), binding(0)]]
+var primary_sampler: sampler;
+[[group(0), binding(2)]]
+var comparison_sampler: sampler_comparison;
+[[group(0), binding(3)]]
+var<uniform> frame: FrameUniforms;
+[[group(0), binding(4)]]
+var<storage, read> directional_lights: DirectionalLightBuffer;
+[[group(0), binding(5)]]
+var shadows: texture_depth_2d_array;
+
+/// Layout of TerrainUniforms in routine.rs.
+struct TerrainUniforms {
+    /// Of the camera or light the chunks are drawn for.
+    view_proj: mat4x4<f32>;
+    /// Camera the levels of detail were picked for, which the geomorph
+    /// follows in the shadow passes too.
+    camera: vec4<f32>;
+    /// World units covered by one repetition of each layer.
+    layer_tiling: vec4<f32>;
+    tile_size: f32;
+    tile_resolution: f32;
+    chunk_resolution: f32;
+    skirt_depth: f32;
+    layer_count: u32;
+};
+
+[[group(1), binding(0)]]
+var<uniform> terrain: TerrainUniforms;
+[[group(1), binding(1)]]
+var layer0: texture_2d<f32>;
+[[group(1), binding(2)]]
+var layer1: texture_2d<f32>;
+[[group(1), binding(3)]]
+var layer2: texture_2d<f32>;
+[[group(1), binding(4)]]
+var layer3: texture_2d<f32>;
+
+/// Heights of the tile, with an apron of one sample taken from the
+/// neighbouring tiles all around.
+[[group(2), binding(0)]]
+var heights: texture_2d<f32>;
+/// Weights of the layers at every height sample.
+[[group(2), binding(1)]]
+var splat: texture_2d<f32>;
+
+struct GridVertex {
+    /// Position in quads of the chunk.
+    [[location(0)]] grid: vec2<f32>;
+    /// One at the bottom of the skirts.
+    [[location(1)]] skirt: f32;
+};
+
+/// Layout of ChunkInstanceAbi in routine.rs.
+struct ChunkInstance {
+    /// Corner of the chunk on the XZ plane.
+    [[location(2)]] origin: vec2<f32>;
+    /// Height sample of the tile at the corner of the chunk.
+    [[location(3)]] sample_origin: vec2<f32>;
+    /// Width of the chunk in world units.
+    [[location(4)]] size: f32;
+    /// Height samples per quad of the chunk.
+    [[location(5)]] step: f32;
+    /// Distances from the camera at which the chunk starts and finishes
+    /// morphing into its parent.
+    [[location(6)]] morph: vec2<f32>;
+};
+
+struct TerrainVertex {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] world: vec3<f32>;
+    /// Position in height samples of the tile.
+    [[location(1)]] sample: vec2<f32>;
+};
+
+fn height(sample: vec2<i32>) -> f32 {
+    let texel = clamp(sample + vec2<i32>(1), vec2<i32>(0), textureDimensions(heights) - vec2<i32>(1));
+    return textureLoad(heights, texel, 0).r;
+}
+
+/// Heights are 32 bit floats, which can't be filtered everywhere.
+fn filtered_height(sample: vec2<f32>) -> f32 {
+    let base = floor(sample);
+    let fraction = sample - base;
+    let texel = vec2<i32>(base);
+    let top = mix(height(texel), height(texel + vec2<i32>(1, 0)), fraction.x);
+    let bottom = mix(height(texel + vec2<i32>(0, 1)), height(texel + vec2<i32>(1, 1)), fraction.x);
+    return mix(top, bottom, fraction.y);
+}
+
+[[stage(vertex)]]
+fn vs_main(vertex: GridVertex, instance: ChunkInstance) -> TerrainVertex {
+    let quad_size = instance.size / terrain.chunk_resolution;
+
+    let unmorphed_xz = instance.origin + vertex.grid * quad_size;
+    let unmorphed_height = height(vec2<i32>(instance.sample_origin + vertex.grid * instance.step));
+    let unmorphed = vec3<f32>(unmorphed_xz.x, unmorphed_height, unmorphed_xz.y);
+
+    // Odd vertices slide onto their even neighbours as the chunk gets as far
+    // as its parent is drawn at, so levels blend into each other without
+    // popping, and edges meet coarser neighbours on their vertices.
+    let morph = clamp(
+        (distance(unmorphed, terrain.camera.xyz) - instance.morph.x) / (instance.morph.y - instance.morph.x),
+        0.0,
+        1.0
+    );
+    let grid = vertex.grid - fract(vertex.grid * 0.5) * 2.0 * morph;
+
+    let sample = instance.sample_origin + grid * instance.step;
+    let xz = instance.origin + grid * quad_size;
+    let y = filtered_height(sample) - vertex.skirt * terrain.skirt_depth;
+    let world = vec3<f32>(xz.x, y, xz.y);
+
+    return TerrainVertex(terrain.view_proj * vec4<f32>(world, 1.0), world, sample);
+}
+
+[[stage(fragment)]]
+fn fs_main(vertex: TerrainVertex) -> [[location(0)]] vec4<f32> {
+    // Central differences of the full resolution heights whatever the level
+    // of detail, reaching into the apron at the edges of the tile, so coarse
+    // chunks are lit like fine ones and tiles join without seams.
+    let spacing = terrain.tile_size / terrain.tile_resolution;
+    let dx = filtered_height(vertex.sample + vec2<f32>(1.0, 0.0)) - filtered_height(vertex.sample - vec2<f32>(1.0, 0.0));
+    let dz = filtered_height(vertex.sample + vec2<f32>(0.0, 1.0)) - filtered_height(vertex.sample - vec2<f32>(0.0, 1.0));
+    let normal = normalize(vec3<f32>(-dx, 2.0 * spacing, -dz));
+
+    // Splat weights are stored at the height samples, so the centers of the
+    // texels.
+    let splat_uv = (vertex.sample + 0.5) / vec2<f32>(textureDimensions(splat));
+    let weights = textureSample(splat, primary_sampler, splat_uv);
+
+    let uv = vertex.world.xz;
+    let albedo0 = textureSample(layer0, primary_sampler, uv / terrain.layer_tiling.x).rgb;
+    let albedo1 = textureSample(layer1, primary_sampler, uv / terrain.layer_tiling.y).rgb;
+    let albedo2 = textureSample(layer2, primary_sampler, uv / terrain.layer_tiling.z).rgb;
+    let albedo3 = textureSample(layer3, primary_sampler, uv / terrain.layer_tiling.w).rgb;
+
+    // Weights of missing layers are ignored, and the first layer covers
+    // samples with no weight left.
+    let present = step(vec4<f32>(0.5, 1.5, 2.5, 3.5), vec4<f32>(f32(terrain.layer_count)));
+    var layer_weights = weights * present;
+    let total = dot(layer_weights, vec4<f32>(1.0));
+    if (total > 0.0) {
+        layer_weights = layer_weights / total;
+    } else {
+        layer_weights = vec4<f32>(1.0, 0.0, 0.0, 0.0);
+    }
+    let albedo = albedo0 * layer_weights.x + albedo1 * layer_weights.y + albedo2 * layer_weights.z
+        + albedo3 * layer_weights.w;
+
+    // Lambertian, lit by the directional lights with the same shadow lookup
+    // as the opaque pass: light i has layer i of the shadows, and is
+    // unshadowed outside of its map.
+    var color = vec3<f32>(0.0);
+    var i = 0u;
+    loop {
+        if (i >= directional_lights.total_lights) {
+            break;
+        }
+        let light = directional_lights.lights[i];
+
+        let shadow_ndc = (light.view_proj * vec4<f32>(vertex.world, 1.0)).xyz;
+        let shadow_uv = vec2<f32>(shadow_ndc.x * 0.5 + 0.5, 0.5 - shadow_ndc.y * 0.5);
+        let covered = all(shadow_uv >= vec2<f32>(0.0)) && all(shadow_uv <= vec2<f32>(1.0))
+            && shadow_ndc.z >= -1.0 && shadow_ndc.z <= 1.0;
+        var lit = 1.0;
+        if (covered) {
+            lit = textureSampleCompareLevel(shadows, comparison_sampler, shadow_uv, i32(i), shadow_ndc.z);
+        }
+
+        let n_dot_l = max(dot(normal, -light.direction), 0.0);
+        color = color + albedo * light.color * (n_dot_l * lit / 3.14159265);
+        i = i + 1u;
+    }
+
+    return vec4<f32>(max(color, frame.ambient.rgb * albedo), 1.0);
+}
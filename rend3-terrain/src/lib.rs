@@ -0,0 +1,706 @@
+//! Heightmap terrain for large outdoor worlds.
+//!
+//! The terrain is a grid of square tiles, each with its own heightmap and
+//! splat map, which can be inserted and removed at any time to stream the
+//! world in around the camera. Loading tiles, e.g. on a background thread,
+//! is left to the user, [`TerrainTile`] is plain data.
+//!
+//! Every tile is split into a quadtree of chunks, and each
+//! [`Terrain::update`] picks chunks of finer levels of detail the closer they
+//! are to the camera, in the style of CDLOD. All chunks are drawn with the
+//! same grid of quads, placed and displaced by the vertex shader from the
+//! heightmap of their tile on the GPU, so the amount of geometry stays about
+//! constant however large the world is and nothing is built on the CPU as
+//! the camera moves. Vertices morph into the grid of the parent level as
+//! chunks near the distance it takes over, so levels of detail change without
+//! popping, and skirts hanging down from the edges of every chunk hide what
+//! cracks are left.
+//!
+//! Normals are computed per pixel from the full resolution heightmap, which
+//! carries a border of samples from the neighbouring tiles, so coarse chunks
+//! are shaded like the finest ones and tiles join without seams. Up to four
+//! [`TerrainLayer`]s are blended by the splat map of a tile in the fragment
+//! shader.
+//!
+//! Terrain is drawn by a [`TerrainRoutine`] into the targets of the base
+//! rendergraph, casting shadows from every directional light and receiving
+//! them, see [`TerrainRoutine`] for how to add it.
+
+use std::mem;
+
+use glam::{IVec2, UVec2, Vec2, Vec3};
+use rend3::{
+    types::TextureHandle,
+    util::{geometry::Aabb, typedefs::FastHashMap},
+    Renderer, RendererError,
+};
+use thiserror::Error;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferUsages, Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+};
+
+mod mesh;
+mod routine;
+mod splat;
+
+pub use routine::TerrainRoutine;
+pub use splat::TerrainLayer;
+
+/// Fraction of the way from the distance a chunk is split at to the distance
+/// its parent is drawn from at which the chunk starts morphing into its
+/// parent.
+const MORPH_START: f32 = 0.75;
+
+/// A tile doesn't fit the settings of the terrain, or a layer couldn't be
+/// added to the renderer.
+#[derive(Debug, Error)]
+pub enum TerrainError {
+    #[error("Tile has {actual} height samples, expected {expected}")]
+    HeightCount { expected: usize, actual: usize },
+    #[error("Tile has {actual} splat weights, expected {expected}")]
+    SplatCount { expected: usize, actual: usize },
+    #[error("Layer texture could not be added to the renderer")]
+    Layer(#[from] RendererError),
+}
+
+/// Layout and look of a [`Terrain`].
+#[derive(Debug, Clone)]
+pub struct TerrainSettings {
+    /// Width of a tile along X and Z in world units. Tile (0, 0) starts at
+    /// the origin and extends towards positive X and Z.
+    pub tile_size: f32,
+    /// Quads along each side of a tile at the finest level of detail, so
+    /// tiles have one more height sample along each side.
+    pub tile_resolution: u32,
+    /// Quads along each side of a chunk, at any level of detail.
+    pub chunk_resolution: u32,
+    /// Times a tile is split into four chunks at the finest level of detail.
+    pub lod_levels: u32,
+    /// Chunks closer to the camera than this many times their width are
+    /// split into finer ones. Below about two, chunks may meet neighbours of
+    /// a coarser level before they finish morphing, leaving cracks for the
+    /// skirts to hide.
+    pub lod_distance: f32,
+    /// How far the skirts hang down below the edges of chunks.
+    pub skirt_depth: f32,
+    /// Layers blended by the splat maps of the tiles, of which the first four
+    /// are used. With no layers, the terrain is white.
+    pub layers: Vec<TerrainLayer>,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            tile_size: 256.0,
+            tile_resolution: 256,
+            chunk_resolution: 32,
+            lod_levels: 3,
+            lod_distance: 2.0,
+            skirt_depth: 2.0,
+            layers: Vec::new(),
+        }
+    }
+}
+
+impl TerrainSettings {
+    /// Height samples along each side of a tile.
+    pub fn samples_per_side(&self) -> usize {
+        self.tile_resolution as usize + 1
+    }
+}
+
+/// Heights and splat weights of one tile.
+///
+/// Samples are stored row by row, with X increasing along a row and Z from
+/// row to row. The last row and column lie on the first ones of the
+/// neighbouring tiles and should have the same values.
+#[derive(Debug, Clone, Default)]
+pub struct TerrainTile {
+    /// Heights in world units.
+    pub heights: Vec<f32>,
+    /// Weights of the first four layers at every sample. None uses the first
+    /// layer everywhere.
+    pub splat: Option<Vec<[u8; 4]>>,
+}
+
+/// Chunk in the quadtree of a tile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct ChunkKey {
+    /// Zero is the whole tile.
+    level: u32,
+    x: u32,
+    z: u32,
+}
+
+impl ChunkKey {
+    const ROOT: Self = Self { level: 0, x: 0, z: 0 };
+
+    fn children(self) -> [ChunkKey; 4] {
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, z)| ChunkKey {
+            level: self.level + 1,
+            x: self.x * 2 + x,
+            z: self.z * 2 + z,
+        })
+    }
+}
+
+/// A chunk picked by the last [`Terrain::update`].
+#[derive(Debug, Copy, Clone)]
+struct SelectedChunk {
+    tile: IVec2,
+    key: ChunkKey,
+    aabb: Aabb,
+}
+
+/// Textures of a tile, sampled by the terrain shader.
+struct TileTextures {
+    heights: wgpu::Texture,
+    heights_view: TextureView,
+    splat_view: TextureView,
+}
+
+struct TileData {
+    tile: TerrainTile,
+    /// Lowest and highest sample of every chunk, level by level, row by row.
+    bounds: Vec<Vec<(f32, f32)>>,
+    /// Created by the first update after the tile is inserted.
+    textures: Option<TileTextures>,
+    /// A neighbour changed since the heights were uploaded, so their border
+    /// is out of date.
+    stale: bool,
+}
+
+/// What all tiles share on the GPU.
+struct TerrainGpu {
+    grid_vertices: Buffer,
+    grid_indices: Buffer,
+    index_count: u32,
+    layers: Vec<TextureHandle>,
+}
+
+/// Heights of all loaded tiles, by sample.
+struct Heights<'a> {
+    tiles: &'a FastHashMap<IVec2, TileData>,
+    resolution: i32,
+}
+
+impl<'a> Heights<'a> {
+    /// Height at a sample of `tile`, which may be outside of it. Samples in
+    /// tiles which aren't loaded are clamped to the edge of `tile`.
+    fn get(&self, tile: IVec2, sample: IVec2) -> f32 {
+        let global = tile * self.resolution + sample;
+        let owner = IVec2::new(
+            global.x.div_euclid(self.resolution),
+            global.y.div_euclid(self.resolution),
+        );
+        let (owner, sample) = match self.tiles.contains_key(&owner) {
+            true => (owner, global - owner * self.resolution),
+            false => (tile, sample.clamp(IVec2::ZERO, IVec2::splat(self.resolution))),
+        };
+        let heights = &self.tiles[&owner].tile.heights;
+        heights[(sample.y * (self.resolution + 1) + sample.x) as usize]
+    }
+}
+
+/// Tiled heightmap terrain, drawn by a [`TerrainRoutine`].
+///
+/// See module for documentation.
+pub struct Terrain {
+    settings: TerrainSettings,
+    tiles: FastHashMap<IVec2, TileData>,
+    gpu: Option<TerrainGpu>,
+    /// Camera of the last update.
+    camera: Vec3,
+    selected: Vec<SelectedChunk>,
+}
+
+impl Terrain {
+    /// # Panics
+    ///
+    /// If `chunk_resolution` isn't even, or `tile_resolution` isn't a
+    /// multiple of `chunk_resolution` times two to the power of `lod_levels`,
+    /// so the chunks of every level fit the samples of the heightmap and can
+    /// morph into their parent.
+    pub fn new(settings: TerrainSettings) -> Self {
+        assert!(
+            settings.chunk_resolution > 0
+                && settings.chunk_resolution % 2 == 0
+                && settings.tile_resolution % (settings.chunk_resolution << settings.lod_levels) == 0,
+            "tile_resolution {} must be a multiple of an even chunk_resolution {} times 2^lod_levels {}",
+            settings.tile_resolution,
+            settings.chunk_resolution,
+            settings.lod_levels
+        );
+
+        Self {
+            settings,
+            tiles: FastHashMap::default(),
+            gpu: None,
+            camera: Vec3::ZERO,
+            selected: Vec::new(),
+        }
+    }
+
+    pub fn settings(&self) -> &TerrainSettings {
+        &self.settings
+    }
+
+    /// Adds or replaces the tile at `coord`. It is uploaded by the next
+    /// update.
+    pub fn insert_tile(&mut self, coord: IVec2, tile: TerrainTile) -> Result<(), TerrainError> {
+        profiling::scope!("Terrain::insert_tile");
+
+        let expected = self.settings.samples_per_side().pow(2);
+        if tile.heights.len() != expected {
+            return Err(TerrainError::HeightCount {
+                expected,
+                actual: tile.heights.len(),
+            });
+        }
+        if let Some(ref splat) = tile.splat {
+            if splat.len() != expected {
+                return Err(TerrainError::SplatCount {
+                    expected,
+                    actual: splat.len(),
+                });
+            }
+        }
+
+        let bounds = chunk_bounds(&self.settings, &tile.heights);
+        self.tiles.insert(
+            coord,
+            TileData {
+                tile,
+                bounds,
+                textures: None,
+                stale: false,
+            },
+        );
+        self.invalidate_neighbours(coord);
+        Ok(())
+    }
+
+    /// Removes the tile at `coord` along with its textures.
+    pub fn remove_tile(&mut self, coord: IVec2) -> Option<TerrainTile> {
+        let data = self.tiles.remove(&coord)?;
+        self.invalidate_neighbours(coord);
+        Some(data.tile)
+    }
+
+    pub fn has_tile(&self, coord: IVec2) -> bool {
+        self.tiles.contains_key(&coord)
+    }
+
+    /// Coordinates of the tile containing a point on the XZ plane.
+    pub fn tile_coord(&self, position: Vec2) -> IVec2 {
+        (position / self.settings.tile_size).floor().as_ivec2()
+    }
+
+    /// Height of the terrain at a point on the XZ plane, interpolated between
+    /// samples, if its tile is loaded.
+    pub fn height_at(&self, position: Vec2) -> Option<f32> {
+        let coord = self.tile_coord(position);
+        self.tiles.get(&coord)?;
+
+        let resolution = self.settings.tile_resolution as i32;
+        let heights = Heights {
+            tiles: &self.tiles,
+            resolution,
+        };
+        let sample = (position / self.settings.tile_size - coord.as_vec2()) * resolution as f32;
+        let base = sample.floor();
+        let fraction = sample - base;
+        let base = base.as_ivec2();
+
+        let h00 = heights.get(coord, base);
+        let h10 = heights.get(coord, base + IVec2::X);
+        let h01 = heights.get(coord, base + IVec2::Y);
+        let h11 = heights.get(coord, base + IVec2::ONE);
+        let top = h00 + (h10 - h00) * fraction.x;
+        let bottom = h01 + (h11 - h01) * fraction.x;
+        Some(top + (bottom - top) * fraction.y)
+    }
+
+    /// Number of chunks picked by the last update, over all tiles.
+    pub fn chunk_count(&self) -> usize {
+        self.selected.len()
+    }
+
+    /// Uploads new tiles and the borders of tiles whose neighbours changed,
+    /// then picks the chunks to draw for a camera at `camera`. Call whenever
+    /// the camera moved enough to matter, e.g. every frame.
+    pub fn update(&mut self, renderer: &Renderer, camera: Vec3) -> Result<(), TerrainError> {
+        profiling::scope!("Terrain::update");
+
+        if self.gpu.is_none() {
+            self.gpu = Some(TerrainGpu::new(renderer, &self.settings)?);
+        }
+
+        let outdated: Vec<IVec2> = self
+            .tiles
+            .iter()
+            .filter(|(_, data)| data.textures.is_none() || data.stale)
+            .map(|(&coord, _)| coord)
+            .collect();
+        for coord in outdated {
+            self.upload_tile(renderer, coord);
+        }
+
+        self.camera = camera;
+        let mut selected = mem::take(&mut self.selected);
+        selected.clear();
+        let mut keys = Vec::new();
+        for &coord in self.tiles.keys() {
+            self.select_chunks(coord, ChunkKey::ROOT, camera, &mut keys);
+            selected.extend(keys.drain(..).map(|key| SelectedChunk {
+                tile: coord,
+                key,
+                aabb: self.chunk_aabb(coord, key),
+            }));
+        }
+        self.selected = selected;
+
+        Ok(())
+    }
+
+    fn upload_tile(&mut self, renderer: &Renderer, coord: IVec2) {
+        profiling::scope!("Terrain::upload_tile");
+
+        let heights = self.bordered_heights(coord);
+        let side = self.settings.samples_per_side() as u32;
+        let bordered_size = Extent3d {
+            width: side + 2,
+            height: side + 2,
+            depth_or_array_layers: 1,
+        };
+
+        let data = self.tiles.get_mut(&coord).unwrap();
+        data.stale = false;
+        if let Some(ref textures) = data.textures {
+            renderer.queue.write_texture(
+                ImageCopyTexture {
+                    texture: &textures.heights,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                bytemuck::cast_slice(&heights),
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(bordered_size.width * 4),
+                    rows_per_image: None,
+                },
+                bordered_size,
+            );
+            return;
+        }
+
+        let heights_texture = renderer.device.create_texture_with_data(
+            &renderer.queue,
+            &TextureDescriptor {
+                label: Some(&format!("terrain heights {} {}", coord.x, coord.y)),
+                size: bordered_size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::R32Float,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            },
+            bytemuck::cast_slice(&heights),
+        );
+        let splat_view = renderer
+            .device
+            .create_texture_with_data(
+                &renderer.queue,
+                &TextureDescriptor {
+                    label: Some(&format!("terrain splat {} {}", coord.x, coord.y)),
+                    size: Extent3d {
+                        width: side,
+                        height: side,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8Unorm,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                },
+                &splat::splat_texels(&data.tile),
+            )
+            .create_view(&TextureViewDescriptor::default());
+
+        data.textures = Some(TileTextures {
+            heights_view: heights_texture.create_view(&TextureViewDescriptor::default()),
+            heights: heights_texture,
+            splat_view,
+        });
+    }
+
+    /// Heights of a tile with a border of one sample all around, taken from
+    /// the neighbouring tiles where they are loaded.
+    fn bordered_heights(&self, coord: IVec2) -> Vec<f32> {
+        let resolution = self.settings.tile_resolution as i32;
+        let heights = Heights {
+            tiles: &self.tiles,
+            resolution,
+        };
+        (-1..=resolution + 1)
+            .flat_map(|z| (-1..=resolution + 1).map(move |x| IVec2::new(x, z)))
+            .map(|sample| heights.get(coord, sample))
+            .collect()
+    }
+
+    fn chunk_size(&self, level: u32) -> f32 {
+        self.settings.tile_size / (1 << level) as f32
+    }
+
+    /// Distance from the camera within which chunks of `level` are split.
+    fn lod_range(&self, level: u32) -> f32 {
+        self.chunk_size(level) * self.settings.lod_distance
+    }
+
+    /// Distances from the camera over which chunks of `level` morph into
+    /// their parent, which is drawn from the end of the range on.
+    fn morph_range(&self, level: u32) -> (f32, f32) {
+        if level == 0 {
+            return (f32::MAX / 2.0, f32::MAX);
+        }
+        let start = self.lod_range(level);
+        let end = self.lod_range(level - 1);
+        (start + (end - start) * MORPH_START, end)
+    }
+
+    /// Box around a chunk and its skirts.
+    fn chunk_aabb(&self, coord: IVec2, key: ChunkKey) -> Aabb {
+        let size = self.chunk_size(key.level);
+        let corner = coord.as_vec2() * self.settings.tile_size + UVec2::new(key.x, key.z).as_vec2() * size;
+        let per_side = 1 << key.level;
+        let (low, high) = self.tiles[&coord].bounds[key.level as usize][(key.z * per_side + key.x) as usize];
+        Aabb {
+            min: Vec3::new(corner.x, low - self.settings.skirt_depth, corner.y),
+            max: Vec3::new(corner.x + size, high, corner.y + size),
+        }
+    }
+
+    fn select_chunks(&self, coord: IVec2, key: ChunkKey, camera: Vec3, selected: &mut Vec<ChunkKey>) {
+        let aabb = self.chunk_aabb(coord, key);
+        let distance = camera.clamp(aabb.min, aabb.max).distance(camera);
+
+        if key.level < self.settings.lod_levels && distance < self.lod_range(key.level) {
+            for child in key.children() {
+                self.select_chunks(coord, child, camera, selected);
+            }
+        } else {
+            selected.push(key);
+        }
+    }
+
+    /// The borders of the heights of the neighbours come from this tile, so
+    /// they are uploaded again.
+    fn invalidate_neighbours(&mut self, coord: IVec2) {
+        for z in -1..=1 {
+            for x in -1..=1 {
+                if (x, z) == (0, 0) {
+                    continue;
+                }
+                if let Some(data) = self.tiles.get_mut(&(coord + IVec2::new(x, z))) {
+                    data.stale = true;
+                }
+            }
+        }
+    }
+}
+
+impl TerrainGpu {
+    fn new(renderer: &Renderer, settings: &TerrainSettings) -> Result<Self, TerrainError> {
+        let (vertices, indices) = mesh::chunk_grid(settings.chunk_resolution);
+        let grid_vertices = renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("terrain grid vertices"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::VERTEX,
+        });
+        let grid_indices = renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("terrain grid indices"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: BufferUsages::INDEX,
+        });
+
+        let layers = settings
+            .layers
+            .iter()
+            .take(4)
+            .enumerate()
+            .map(|(idx, layer)| renderer.add_texture_2d(layer.texture(idx)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            grid_vertices,
+            grid_indices,
+            index_count: indices.len() as u32,
+            layers,
+        })
+    }
+}
+
+/// Lowest and highest height of every chunk of every level, from the finest
+/// level up.
+fn chunk_bounds(settings: &TerrainSettings, heights: &[f32]) -> Vec<Vec<(f32, f32)>> {
+    let side = settings.samples_per_side();
+    let finest_per_side = 1usize << settings.lod_levels;
+    let chunk_samples = settings.tile_resolution as usize / finest_per_side;
+
+    let mut finest = Vec::with_capacity(finest_per_side * finest_per_side);
+    for z in 0..finest_per_side {
+        for x in 0..finest_per_side {
+            let rows = z * chunk_samples..=(z + 1) * chunk_samples;
+            let columns = x * chunk_samples..=(x + 1) * chunk_samples;
+            let bounds = rows
+                .flat_map(|row| heights[row * side..][columns.clone()].iter().copied())
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), height| {
+                    (low.min(height), high.max(height))
+                });
+            finest.push(bounds);
+        }
+    }
+
+    let mut levels = vec![finest];
+    for level in (0..settings.lod_levels).rev() {
+        let per_side = 1usize << level;
+        let finer = levels.last().unwrap();
+        let coarser = (0..per_side * per_side)
+            .map(|idx| {
+                let (x, z) = (idx % per_side, idx / per_side);
+                [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .map(|&(dx, dz)| finer[(z * 2 + dz) * per_side * 2 + x * 2 + dx])
+                    .fold(
+                        (f32::INFINITY, f32::NEG_INFINITY),
+                        |(low, high), (child_low, child_high)| (low.min(child_low), high.max(child_high)),
+                    )
+            })
+            .collect();
+        levels.push(coarser);
+    }
+    levels.reverse();
+    levels
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{IVec2, Vec2, Vec3};
+
+    use super::{ChunkKey, Terrain, TerrainSettings, TerrainTile};
+
+    fn settings() -> TerrainSettings {
+        TerrainSettings {
+            tile_size: 64.0,
+            tile_resolution: 16,
+            chunk_resolution: 4,
+            lod_levels: 2,
+            ..TerrainSettings::default()
+        }
+    }
+
+    fn tile(height: impl Fn(usize, usize) -> f32) -> TerrainTile {
+        let side = settings().samples_per_side();
+        TerrainTile {
+            heights: (0..side * side).map(|idx| height(idx % side, idx / side)).collect(),
+            splat: None,
+        }
+    }
+
+    fn select(terrain: &Terrain, camera: Vec3) -> Vec<ChunkKey> {
+        let mut selected = Vec::new();
+        terrain.select_chunks(IVec2::ZERO, ChunkKey::ROOT, camera, &mut selected);
+        selected
+    }
+
+    #[test]
+    fn chunks_get_finer_towards_the_camera() {
+        let mut terrain = Terrain::new(settings());
+        terrain.insert_tile(IVec2::ZERO, tile(|_, _| 0.0)).unwrap();
+
+        let selected = select(&terrain, Vec3::new(-40.0, 1.0, -40.0));
+        let area: f32 = selected.iter().map(|key| terrain.chunk_size(key.level).powi(2)).sum();
+        assert_eq!(area, 64.0 * 64.0);
+        let level_at = |x: u32, z: u32| {
+            selected
+                .iter()
+                .find(|key| {
+                    let quads = 16 >> key.level;
+                    (key.x * quads..(key.x + 1) * quads).contains(&x)
+                        && (key.z * quads..(key.z + 1) * quads).contains(&z)
+                })
+                .unwrap()
+                .level
+        };
+        assert_eq!(level_at(0, 0), 2);
+        assert_eq!(level_at(15, 0), 1);
+        assert_eq!(level_at(15, 15), 1);
+        assert_eq!(selected.len(), 7);
+
+        assert_eq!(select(&terrain, Vec3::new(1000.0, 0.0, 1000.0)), [ChunkKey::ROOT]);
+
+        // Chunks are as far as their bounds, so a camera just above a high
+        // tile is close to all of it.
+        assert_eq!(select(&terrain, Vec3::new(32.0, 200.0, 32.0)), [ChunkKey::ROOT]);
+        terrain.insert_tile(IVec2::ZERO, tile(|_, _| 190.0)).unwrap();
+        let selected = select(&terrain, Vec3::new(32.0, 200.0, 32.0));
+        assert_eq!(selected.len(), 16);
+        assert!(selected.iter().all(|key| key.level == 2));
+    }
+
+    #[test]
+    fn heights_interpolate_between_samples() {
+        let mut terrain = Terrain::new(settings());
+        terrain
+            .insert_tile(IVec2::ZERO, tile(|x, z| x as f32 + 10.0 * z as f32))
+            .unwrap();
+
+        // Samples are four units apart.
+        assert_eq!(terrain.height_at(Vec2::new(8.0, 4.0)), Some(12.0));
+        assert_eq!(terrain.height_at(Vec2::new(6.0, 0.0)), Some(1.5));
+        assert_eq!(terrain.height_at(Vec2::new(2.0, 6.0)), Some(15.5));
+        assert_eq!(terrain.height_at(Vec2::new(-1.0, 6.0)), None);
+        assert_eq!(terrain.height_at(Vec2::new(6.0, 64.0)), None);
+
+        // The last samples of a tile are the first of the next one.
+        terrain
+            .insert_tile(IVec2::X, tile(|x, z| 100.0 + x as f32 + 10.0 * z as f32))
+            .unwrap();
+        assert_eq!(terrain.height_at(Vec2::new(64.0, 0.0)), Some(100.0));
+        assert_eq!(terrain.height_at(Vec2::new(62.0, 0.0)), Some(57.5));
+    }
+
+    #[test]
+    fn neighbours_invalidate_borders() {
+        let mut terrain = Terrain::new(settings());
+        terrain.insert_tile(IVec2::ZERO, tile(|_, _| 0.0)).unwrap();
+        let border = |terrain: &Terrain| {
+            let heights = terrain.bordered_heights(IVec2::ZERO);
+            // Last column of the first row of the tile, in the tile to the right.
+            heights[19 + 18]
+        };
+        assert_eq!(border(&terrain), 0.0);
+
+        terrain.insert_tile(IVec2::X, tile(|_, _| 5.0)).unwrap();
+        assert!(terrain.tiles[&IVec2::ZERO].stale);
+        assert!(!terrain.tiles[&IVec2::X].stale);
+        assert_eq!(border(&terrain), 5.0);
+
+        // Diagonal neighbours share a corner.
+        terrain.tiles.get_mut(&IVec2::ZERO).unwrap().stale = false;
+        terrain.insert_tile(IVec2::new(-1, -1), tile(|_, _| 0.0)).unwrap();
+        assert!(terrain.tiles[&IVec2::ZERO].stale);
+
+        terrain.tiles.get_mut(&IVec2::ZERO).unwrap().stale = false;
+        terrain.insert_tile(IVec2::new(2, 0), tile(|_, _| 0.0)).unwrap();
+        assert!(!terrain.tiles[&IVec2::ZERO].stale);
+
+        terrain.remove_tile(IVec2::X);
+        assert!(terrain.tiles[&IVec2::ZERO].stale);
+        assert_eq!(border(&terrain), 0.0);
+    }
+}
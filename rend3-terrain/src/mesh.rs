@@ -0,0 +1,63 @@
+/// Vertex of the grid every chunk is drawn with.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct GridVertex {
+    /// Position in quads of the chunk.
+    pub grid: [f32; 2],
+    /// One at the bottom of the skirts.
+    pub skirt: f32,
+}
+
+unsafe impl bytemuck::Pod for GridVertex {}
+unsafe impl bytemuck::Zeroable for GridVertex {}
+
+/// Builds the grid of `resolution` quads along each side shared by all
+/// chunks, with skirts along its edges. Heights and placement come from the
+/// vertex shader.
+pub(crate) fn chunk_grid(resolution: u32) -> (Vec<GridVertex>, Vec<u32>) {
+    let side = resolution as usize + 1;
+    let mut vertices = Vec::with_capacity(side * side + 4 * side);
+    let mut indices = Vec::with_capacity(resolution as usize * resolution as usize * 6 + 4 * side * 6);
+
+    for z in 0..=resolution {
+        for x in 0..=resolution {
+            vertices.push(GridVertex {
+                grid: [x as f32, z as f32],
+                skirt: 0.0,
+            });
+        }
+    }
+
+    // Terrain is drawn without culling, so the winding doesn't matter.
+    let vertex = |x: u32, z: u32| z * side as u32 + x;
+    for z in 0..resolution {
+        for x in 0..resolution {
+            let (v00, v10, v01, v11) = (vertex(x, z), vertex(x + 1, z), vertex(x, z + 1), vertex(x + 1, z + 1));
+            indices.extend_from_slice(&[v10, v00, v01, v01, v11, v10]);
+        }
+    }
+
+    let last = resolution;
+    let edges: [Vec<u32>; 4] = [
+        (0..=last).map(|x| vertex(x, 0)).collect(),
+        (0..=last).map(|x| vertex(x, last)).collect(),
+        (0..=last).map(|z| vertex(0, z)).collect(),
+        (0..=last).map(|z| vertex(last, z)).collect(),
+    ];
+    for edge in edges {
+        let skirt_start = vertices.len() as u32;
+        for &top in &edge {
+            vertices.push(GridVertex {
+                skirt: 1.0,
+                ..vertices[top as usize]
+            });
+        }
+        for idx in 0..edge.len() as u32 - 1 {
+            let (top0, top1) = (edge[idx as usize], edge[idx as usize + 1]);
+            let (bottom0, bottom1) = (skirt_start + idx, skirt_start + idx + 1);
+            indices.extend_from_slice(&[top0, top1, bottom0, bottom0, top1, bottom1]);
+        }
+    }
+
+    (vertices, indices)
+}
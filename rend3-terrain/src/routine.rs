@@ -0,0 +1,508 @@
+use std::{mem, ops::Range};
+
+use glam::{IVec2, Mat4, UVec2, Vec2, Vec4};
+use rend3::{
+    graph::{
+        DepthHandle, RenderGraph, RenderGraphDataStore, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets,
+        RpassTemporaryPool,
+    },
+    types::{DepthMode, SampleCount},
+    util::{
+        bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+        geometry::ShaderFrustum,
+    },
+    Renderer, RendererProfile,
+};
+use rend3_routine::{base::BaseRenderGraphIntermediateState, common::WholeFrameInterfaces};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayout, BindingType, BufferAddress, BufferBindingType, BufferUsages, Color, ColorTargetState, ColorWrites,
+    DepthBiasState, DepthStencilState, Extent3d, FragmentState, FrontFace, IndexFormat, MultisampleState,
+    PipelineLayout, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPass,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StencilState, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::{mesh::GridVertex, SelectedChunk, Terrain};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct ChunkInstanceAbi {
+    origin: Vec2,
+    sample_origin: Vec2,
+    size: f32,
+    step: f32,
+    morph: Vec2,
+}
+
+unsafe impl bytemuck::Pod for ChunkInstanceAbi {}
+unsafe impl bytemuck::Zeroable for ChunkInstanceAbi {}
+
+#[repr(C, align(16))]
+#[derive(Debug, Copy, Clone)]
+struct TerrainUniforms {
+    view_proj: Mat4,
+    camera: Vec4,
+    layer_tiling: Vec4,
+    tile_size: f32,
+    tile_resolution: f32,
+    chunk_resolution: f32,
+    skirt_depth: f32,
+    layer_count: u32,
+    _padding: [u32; 3],
+}
+
+unsafe impl bytemuck::Pod for TerrainUniforms {}
+unsafe impl bytemuck::Zeroable for TerrainUniforms {}
+
+/// Source of the terrain shader for `profile`.
+fn shader_source(profile: RendererProfile) -> String {
+    let source = include_str!("../shaders/terrain.wgsl");
+    match profile {
+        // Downlevel can't use storage buffers, so it has a fixed number of
+        // directional lights in a uniform buffer.
+        RendererProfile::Downlevel => source.replace(
+            "var<storage, read> directional_lights: DirectionalLightBuffer;",
+            "var<uniform> directional_lights: DownlevelDirectionalLightBuffer;",
+        ),
+        _ => source.to_owned(),
+    }
+}
+
+/// Draws [`Terrain`]s into the targets of the base rendergraph.
+///
+/// Add the shadows after [`BasePhase::Shadows`] and the terrain itself after
+/// [`BasePhase::ForwardOpaque`], with hooks:
+///
+/// ```ignore
+/// let hooks = BaseRenderGraphHooks::new()
+///     .after(BasePhase::Shadows, |graph, state| {
+///         terrain_routine.add_shadows_to_graph(graph, &terrain, state)
+///     })
+///     .after(BasePhase::ForwardOpaque, |graph, state| {
+///         terrain_routine.add_to_graph(graph, &terrain, state, samples)
+///     });
+/// ```
+///
+/// Chunks are culled against the frustum of the camera, and of each light
+/// for its shadow. The terrain is lit by the ambient light and the
+/// directional lights, with a Lambertian surface.
+///
+/// [`BasePhase::Shadows`]: rend3_routine::base::BasePhase::Shadows
+/// [`BasePhase::ForwardOpaque`]: rend3_routine::base::BasePhase::ForwardOpaque
+pub struct TerrainRoutine {
+    forward_s1: RenderPipeline,
+    forward_s4: RenderPipeline,
+    shadow: RenderPipeline,
+    terrain_bgl: BindGroupLayout,
+    tile_bgl: BindGroupLayout,
+    /// Bound in place of missing layers.
+    white: TextureView,
+    depth_mode: DepthMode,
+}
+
+impl TerrainRoutine {
+    pub fn new(renderer: &Renderer, interfaces: &WholeFrameInterfaces) -> Self {
+        profiling::scope!("TerrainRoutine::new");
+
+        let white = renderer
+            .device
+            .create_texture_with_data(
+                &renderer.queue,
+                &TextureDescriptor {
+                    label: Some("terrain white"),
+                    size: Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8Unorm,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                },
+                &[255; 4],
+            )
+            .create_view(&TextureViewDescriptor::default());
+
+        let texture = |filterable: bool| BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable },
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        };
+        let mut terrain_bglb = BindGroupLayoutBuilder::new();
+        terrain_bglb.append(
+            ShaderStages::VERTEX_FRAGMENT,
+            BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            None,
+        );
+        for _ in 0..4 {
+            terrain_bglb.append(ShaderStages::FRAGMENT, texture(true), None);
+        }
+        let terrain_bgl = terrain_bglb.build(&renderer.device, Some("terrain bgl"));
+        let tile_bgl = BindGroupLayoutBuilder::new()
+            .append(ShaderStages::VERTEX_FRAGMENT, texture(false), None)
+            .append(ShaderStages::FRAGMENT, texture(true), None)
+            .build(&renderer.device, Some("terrain tile bgl"));
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("terrain"),
+            source: ShaderSource::Wgsl(shader_source(renderer.profile).into()),
+        });
+        let forward_pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("terrain forward"),
+            bind_group_layouts: &[&interfaces.forward_uniform_bgl, &terrain_bgl, &tile_bgl],
+            push_constant_ranges: &[],
+        });
+        let shadow_pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("terrain shadow"),
+            bind_group_layouts: &[&interfaces.depth_uniform_bgl, &terrain_bgl, &tile_bgl],
+            push_constant_ranges: &[],
+        });
+
+        // Push casters away from the light like the shadows of objects, which is
+        // towards the far depth.
+        let sign = match renderer.depth_mode.is_reversed() {
+            true => -1,
+            false => 1,
+        };
+        let shadow_bias = DepthBiasState {
+            constant: 2 * sign,
+            slope_scale: 2.0 * sign as f32,
+            clamp: 0.0,
+        };
+
+        let forward = |samples: SampleCount| {
+            build_pipeline(
+                renderer,
+                &forward_pll,
+                &module,
+                samples,
+                DepthBiasState::default(),
+                Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[ColorTargetState {
+                        format: TextureFormat::Rgba16Float,
+                        blend: None,
+                        write_mask: ColorWrites::all(),
+                    }],
+                }),
+            )
+        };
+
+        Self {
+            forward_s1: forward(SampleCount::One),
+            forward_s4: forward(SampleCount::Four),
+            shadow: build_pipeline(renderer, &shadow_pll, &module, SampleCount::One, shadow_bias, None),
+            terrain_bgl,
+            tile_bgl,
+            white,
+            depth_mode: renderer.depth_mode,
+        }
+    }
+
+    /// Draws `terrain` into the HDR targets of the base rendergraph, which
+    /// have `samples`.
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        terrain: &'node Terrain,
+        state: &BaseRenderGraphIntermediateState,
+        samples: SampleCount,
+    ) {
+        if terrain.selected.is_empty() {
+            return;
+        }
+
+        let mut builder = graph.add_node("Terrain");
+
+        let _ = builder.add_shadow_array_input();
+        let forward_uniform_handle = builder.add_data_input(state.forward_uniform_bg);
+        let hdr_color_handle = builder.add_render_target_output(state.color);
+        let hdr_resolve = builder.add_optional_render_target_output(state.resolve);
+        let hdr_depth_handle = builder.add_render_target_output(state.depth);
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: hdr_color_handle,
+                clear: Color::BLACK,
+                resolve: hdr_resolve,
+            }],
+            depth_stencil: Some(RenderPassDepthTarget {
+                target: DepthHandle::RenderTarget(hdr_depth_handle),
+                depth_clear: Some(self.depth_mode.far_depth()),
+                stencil_clear: None,
+            }),
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+        let terrain_handle = builder.passthrough_ref(terrain);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let terrain = pt.get(terrain_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+            let forward_uniform_bg = graph_data.get_data(temps, forward_uniform_handle).unwrap();
+
+            profiling::scope!("terrain");
+
+            let pipeline = match samples {
+                SampleCount::One => &this.forward_s1,
+                SampleCount::Four => &this.forward_s4,
+            };
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, forward_uniform_bg, &[]);
+            let view_proj = graph_data.camera_manager.view_proj();
+            this.draw(rpass, renderer, temps, &graph_data, terrain, view_proj);
+        });
+    }
+
+    /// Draws `terrain` into the shadow map of every directional light.
+    pub fn add_shadows_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        terrain: &'node Terrain,
+        state: &BaseRenderGraphIntermediateState,
+    ) {
+        if terrain.selected.is_empty() {
+            return;
+        }
+
+        for (shadow_index, &shadow_uniform_bg) in state.shadow_uniform_bg.iter().enumerate() {
+            let mut builder = graph.add_node(format!("Terrain Shadow S{}", shadow_index).as_str());
+
+            let shadow_uniform_handle = builder.add_data_input(shadow_uniform_bg);
+            let shadow_output_handle = builder.add_shadow_output(shadow_index);
+
+            let rpass_handle = builder.add_renderpass(RenderPassTargets {
+                targets: vec![],
+                depth_stencil: Some(RenderPassDepthTarget {
+                    target: DepthHandle::Shadow(shadow_output_handle),
+                    depth_clear: Some(self.depth_mode.far_depth()),
+                    stencil_clear: None,
+                }),
+            });
+
+            let pt_handle = builder.passthrough_ref(self);
+            let terrain_handle = builder.passthrough_ref(terrain);
+
+            builder.build(move |pt, renderer, encoder_or_pass, temps, ready, graph_data| {
+                let this = pt.get(pt_handle);
+                let terrain = pt.get(terrain_handle);
+                let rpass = encoder_or_pass.get_rpass(rpass_handle);
+                let shadow_uniform_bg = graph_data.get_data(temps, shadow_uniform_handle).unwrap();
+
+                profiling::scope!("terrain shadow");
+
+                rpass.set_pipeline(&this.shadow);
+                rpass.set_bind_group(0, shadow_uniform_bg, &[]);
+                let view_proj = ready.directional_light_cameras[shadow_index].view_proj();
+                this.draw(rpass, renderer, temps, &graph_data, terrain, view_proj);
+            });
+        }
+    }
+
+    /// Draws the chunks of `terrain` inside the frustum of `view_proj`, one
+    /// draw per tile, with the frame uniforms and pipeline already bound.
+    fn draw<'pass>(
+        &'pass self,
+        rpass: &mut RenderPass<'pass>,
+        renderer: &Renderer,
+        temps: &'pass RpassTemporaryPool<'pass>,
+        graph_data: &RenderGraphDataStore<'pass>,
+        terrain: &'pass Terrain,
+        view_proj: Mat4,
+    ) {
+        let gpu = match terrain.gpu {
+            Some(ref gpu) => gpu,
+            None => return,
+        };
+
+        // Chunks of a tile are next to each other, so each tile is one range
+        // of instances.
+        let frustum = ShaderFrustum::from_matrix(view_proj, self.depth_mode);
+        let mut instances = Vec::with_capacity(terrain.selected.len());
+        let mut draws: Vec<(IVec2, Range<u32>)> = Vec::new();
+        for chunk in terrain
+            .selected
+            .iter()
+            .filter(|chunk| frustum.contains_aabb(chunk.aabb))
+        {
+            let idx = instances.len() as u32;
+            match draws.last_mut() {
+                Some((tile, range)) if *tile == chunk.tile => range.end = idx + 1,
+                _ => draws.push((chunk.tile, idx..idx + 1)),
+            }
+            instances.push(chunk_instance(terrain, chunk));
+        }
+        if instances.is_empty() {
+            return;
+        }
+
+        let instance_buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("terrain chunks"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: BufferUsages::VERTEX,
+        }));
+
+        let settings = terrain.settings();
+        let mut layer_tiling = Vec4::ONE;
+        for (idx, layer) in settings.layers.iter().take(4).enumerate() {
+            layer_tiling[idx] = layer.tiling;
+        }
+        let uniforms = TerrainUniforms {
+            view_proj,
+            camera: terrain.camera.extend(1.0),
+            layer_tiling,
+            tile_size: settings.tile_size,
+            tile_resolution: settings.tile_resolution as f32,
+            chunk_resolution: settings.chunk_resolution as f32,
+            skirt_depth: settings.skirt_depth,
+            layer_count: gpu.layers.len() as u32,
+            _padding: [0; 3],
+        };
+        let uniform_buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("terrain uniforms"),
+            contents: bytemuck::bytes_of(&uniforms),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let mut terrain_bgb = BindGroupBuilder::new();
+        terrain_bgb.append_buffer(&uniform_buffer);
+        for idx in 0..4 {
+            let view = match gpu.layers.get(idx) {
+                Some(layer) => graph_data.d2_texture_manager.get_view(layer.get_raw()),
+                None => &self.white,
+            };
+            terrain_bgb.append_texture_view(view);
+        }
+        let terrain_bg = temps.add(terrain_bgb.build(&renderer.device, Some("terrain bg"), &self.terrain_bgl));
+
+        rpass.set_bind_group(1, terrain_bg, &[]);
+        rpass.set_vertex_buffer(0, gpu.grid_vertices.slice(..));
+        rpass.set_vertex_buffer(1, instance_buffer.slice(..));
+        rpass.set_index_buffer(gpu.grid_indices.slice(..), IndexFormat::Uint32);
+
+        for (tile, range) in draws {
+            let textures = match terrain.tiles[&tile].textures {
+                Some(ref textures) => textures,
+                None => continue,
+            };
+            let tile_bg = temps.add(
+                BindGroupBuilder::new()
+                    .append_texture_view(&textures.heights_view)
+                    .append_texture_view(&textures.splat_view)
+                    .build(&renderer.device, Some("terrain tile bg"), &self.tile_bgl),
+            );
+            rpass.set_bind_group(2, tile_bg, &[]);
+            rpass.draw_indexed(0..gpu.index_count, 0, range);
+        }
+    }
+}
+
+fn chunk_instance(terrain: &Terrain, chunk: &SelectedChunk) -> ChunkInstanceAbi {
+    let settings = terrain.settings();
+    let size = terrain.chunk_size(chunk.key.level);
+    let chunk_samples = settings.tile_resolution >> chunk.key.level;
+    let position = UVec2::new(chunk.key.x, chunk.key.z);
+    let (morph_start, morph_end) = terrain.morph_range(chunk.key.level);
+    ChunkInstanceAbi {
+        origin: chunk.tile.as_vec2() * settings.tile_size + position.as_vec2() * size,
+        sample_origin: (position * chunk_samples).as_vec2(),
+        size,
+        step: (chunk_samples / settings.chunk_resolution) as f32,
+        morph: Vec2::new(morph_start, morph_end),
+    }
+}
+
+fn build_pipeline(
+    renderer: &Renderer,
+    pll: &PipelineLayout,
+    module: &ShaderModule,
+    samples: SampleCount,
+    bias: DepthBiasState,
+    fragment: Option<FragmentState>,
+) -> RenderPipeline {
+    let grid_attributes = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32];
+    let instance_attributes = wgpu::vertex_attr_array![
+        2 => Float32x2,
+        3 => Float32x2,
+        4 => Float32,
+        5 => Float32,
+        6 => Float32x2,
+    ];
+
+    renderer.device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("terrain"),
+        layout: Some(pll),
+        vertex: VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[
+                VertexBufferLayout {
+                    array_stride: mem::size_of::<GridVertex>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &grid_attributes,
+                },
+                VertexBufferLayout {
+                    array_stride: mem::size_of::<ChunkInstanceAbi>() as BufferAddress,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &instance_attributes,
+                },
+            ],
+        },
+        // Heightfields are seen from above, and skirts from either side, so
+        // nothing is culled.
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: TextureFormat::Depth32Float,
+            depth_write_enabled: true,
+            depth_compare: renderer.depth_mode.closer_or_equal(),
+            stencil: StencilState::default(),
+            bias,
+        }),
+        multisample: MultisampleState {
+            count: samples as u32,
+            ..Default::default()
+        },
+        fragment,
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use naga::{
+        front::wgsl,
+        valid::{Capabilities, ValidationFlags, Validator},
+    };
+    use rend3::RendererProfile;
+
+    use super::shader_source;
+
+    #[test]
+    fn shader_is_valid() {
+        for profile in [RendererProfile::GpuDriven, RendererProfile::Downlevel] {
+            let module =
+                wgsl::parse_str(&shader_source(profile)).unwrap_or_else(|err| panic!("{:?}: {}", profile, err));
+            if let Err(err) = Validator::new(ValidationFlags::all(), Capabilities::empty()).validate(&module) {
+                panic!("{:?}: {}", profile, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use glam::UVec2;
+use rend3::types::{ColorSpace, MipmapCount, MipmapSource, Texture, TextureFormat};
+
+use crate::TerrainTile;
+
+/// A material blended over the terrain by the splat maps.
+#[derive(Debug, Clone)]
+pub struct TerrainLayer {
+    /// Srgb RGBA8 pixels, row by row.
+    pub albedo: Vec<u8>,
+    pub size: UVec2,
+    /// World units covered by one repetition of the texture.
+    pub tiling: f32,
+}
+
+impl TerrainLayer {
+    pub(crate) fn texture(&self, index: usize) -> Texture {
+        Texture {
+            label: Some(format!("terrain layer {}", index)),
+            data: self.albedo.clone(),
+            color_space: ColorSpace::FromFormat,
+            format: TextureFormat::Rgba8UnormSrgb,
+            size: self.size,
+            mip_count: MipmapCount::Maximum,
+            mip_source: MipmapSource::Generated,
+        }
+    }
+}
+
+/// Texels of the splat texture of a tile, one per height sample. Tiles
+/// without a splat map use the first layer everywhere.
+pub(crate) fn splat_texels(tile: &TerrainTile) -> Vec<u8> {
+    match tile.splat {
+        Some(ref splat) => splat.iter().flatten().copied().collect(),
+        None => [255, 0, 0, 0].repeat(tile.heights.len()),
+    }
+}
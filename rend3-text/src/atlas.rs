@@ -0,0 +1,163 @@
+use glam::{UVec2, Vec2};
+use rend3::util::typedefs::FastHashMap;
+use wgpu::{Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, Queue, Texture, TextureAspect};
+
+/// Size glyphs are rasterized at, in pixels per em. Text of any size is
+/// scaled from this, the distance field keeps it sharp.
+pub const RASTER_PX: f32 = 48.0;
+
+/// Distance in atlas texels from the edge of a glyph at which its distance
+/// field saturates. Glyphs are padded by this much.
+pub const SPREAD: u32 = 6;
+
+/// Width and height of the atlas texture.
+pub const ATLAS_SIZE: u32 = 1024;
+
+/// Where a glyph is in the atlas.
+#[derive(Debug, Copy, Clone)]
+pub struct AtlasGlyph {
+    /// Top left corner in texels, including the padding.
+    pub origin: UVec2,
+    /// Size in texels, including the padding.
+    pub size: UVec2,
+}
+
+impl AtlasGlyph {
+    pub fn uv_min(&self) -> Vec2 {
+        self.origin.as_vec2() / ATLAS_SIZE as f32
+    }
+
+    pub fn uv_max(&self) -> Vec2 {
+        (self.origin + self.size).as_vec2() / ATLAS_SIZE as f32
+    }
+}
+
+/// Packs glyph distance fields into rows of the atlas texture as they are
+/// first drawn.
+pub struct GlyphAtlas {
+    glyphs: FastHashMap<u16, AtlasGlyph>,
+    cursor: UVec2,
+    row_height: u32,
+}
+
+impl GlyphAtlas {
+    pub fn new() -> Self {
+        Self {
+            glyphs: FastHashMap::default(),
+            cursor: UVec2::ZERO,
+            row_height: 0,
+        }
+    }
+
+    /// Forgets all glyphs, to make room for the ones drawn now.
+    pub fn clear(&mut self) {
+        self.glyphs.clear();
+        self.cursor = UVec2::ZERO;
+        self.row_height = 0;
+    }
+
+    pub fn get(&self, glyph_index: u16) -> Option<AtlasGlyph> {
+        self.glyphs.get(&glyph_index).copied()
+    }
+
+    /// Rasterizes the glyph and uploads it, if it isn't in the atlas yet.
+    /// Returns None if the atlas is full.
+    pub fn insert(
+        &mut self,
+        font: &fontdue::Font,
+        glyph_index: u16,
+        queue: &Queue,
+        texture: &Texture,
+    ) -> Option<AtlasGlyph> {
+        if let Some(glyph) = self.get(glyph_index) {
+            return Some(glyph);
+        }
+
+        let (metrics, coverage) = font.rasterize_indexed(glyph_index, RASTER_PX);
+        let bitmap_size = UVec2::new(metrics.width as u32, metrics.height as u32);
+        let size = bitmap_size + UVec2::splat(2 * SPREAD);
+
+        if self.cursor.x + size.x > ATLAS_SIZE {
+            self.cursor = UVec2::new(0, self.cursor.y + self.row_height);
+            self.row_height = 0;
+        }
+        if self.cursor.y + size.y > ATLAS_SIZE {
+            return None;
+        }
+
+        let glyph = AtlasGlyph {
+            origin: self.cursor,
+            size,
+        };
+        self.cursor.x += size.x;
+        self.row_height = self.row_height.max(size.y);
+
+        let field = distance_field(&coverage, bitmap_size);
+        queue.write_texture(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: glyph.origin.x,
+                    y: glyph.origin.y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            &field,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(size.x),
+                rows_per_image: None,
+            },
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.glyphs.insert(glyph_index, glyph);
+        Some(glyph)
+    }
+}
+
+/// Turns a coverage bitmap into a distance field padded by [`SPREAD`] on each
+/// side. 0.5 is on the outline, higher values are inside.
+fn distance_field(coverage: &[u8], bitmap_size: UVec2) -> Vec<u8> {
+    let size = bitmap_size + UVec2::splat(2 * SPREAD);
+    let spread = SPREAD as i32;
+    let inside = |x: i32, y: i32| {
+        let (x, y) = (x - spread, y - spread);
+        x >= 0
+            && y >= 0
+            && x < bitmap_size.x as i32
+            && y < bitmap_size.y as i32
+            && coverage[(y as u32 * bitmap_size.x + x as u32) as usize] >= 128
+    };
+
+    let mut field = Vec::with_capacity((size.x * size.y) as usize);
+    for y in 0..size.y as i32 {
+        for x in 0..size.x as i32 {
+            let is_inside = inside(x, y);
+
+            // Brute force search for the closest texel on the other side of the outline.
+            let mut closest_squared = (spread * spread) as f32;
+            for dy in -spread..=spread {
+                for dx in -spread..=spread {
+                    let squared = (dx * dx + dy * dy) as f32;
+                    if squared < closest_squared && inside(x + dx, y + dy) != is_inside {
+                        closest_squared = squared;
+                    }
+                }
+            }
+
+            // The outline runs between texels, half a texel from both sides of it.
+            let distance = closest_squared.sqrt() - 0.5;
+            let signed = if is_inside { distance } else { -distance };
+            let value = 0.5 + signed / (2.0 * SPREAD as f32);
+            field.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+    }
+    field
+}
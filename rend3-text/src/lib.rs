@@ -0,0 +1,557 @@
+//! Render routine drawing text with TrueType and OpenType fonts.
+//!
+//! Collect strings in a [`TextBatch`], either as overlays placed in pixels on
+//! the screen, or as quads in the world: flat like signs, or billboards that
+//! always face the camera. A [`TextRoutine`] then lays them out and draws them.
+//! Glyphs are rasterized by fontdue into an atlas of distance fields the first
+//! time they are drawn, so text stays sharp at any size.
+//!
+//! Text is drawn with the depth test when it is given the depth buffer of the
+//! scene, so world space labels can be hidden behind geometry. That depth
+//! buffer belongs to the HDR targets of the base rendergraph, so to use it,
+//! build the graph from [`BaseRenderGraphIntermediateState`] and draw the text
+//! into its `color`, `resolve` and `depth` after forward rendering, with a
+//! routine created for [`TextureFormat::Rgba16Float`]. Without the depth
+//! buffer, e.g. when drawing onto the surface after tonemapping, all text is
+//! drawn on top.
+//!
+//! [`BaseRenderGraphIntermediateState`]: rend3_routine::base::BaseRenderGraphIntermediateState
+
+use std::mem;
+
+use glam::{Mat4, UVec2, Vec2, Vec3, Vec4};
+use parking_lot::Mutex;
+use rend3::{
+    graph::{DepthHandle, RenderGraph, RenderPassDepthTarget, RenderPassTarget, RenderPassTargets, RenderTargetHandle},
+    types::{DepthMode, SampleCount},
+    util::bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
+    Renderer,
+};
+use rend3_routine::common::srgb_to_linear;
+use thiserror::Error;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindingType, BlendState, BufferAddress, BufferUsages, Color, ColorTargetState, ColorWrites,
+    CompareFunction, DepthBiasState, DepthStencilState, Device, FilterMode, FragmentState, FrontFace, MultisampleState,
+    PipelineLayout, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline,
+    RenderPipelineDescriptor, SamplerBindingType, SamplerDescriptor, ShaderModule, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, StencilState, Texture, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureSampleType, TextureUsages, TextureViewDescriptor, TextureViewDimension, VertexBufferLayout, VertexState,
+    VertexStepMode,
+};
+
+mod atlas;
+
+use atlas::{GlyphAtlas, ATLAS_SIZE, RASTER_PX, SPREAD};
+
+/// Parsing a font failed.
+#[derive(Debug, Error)]
+#[error("Failed to parse font: {0}")]
+pub struct FontError(pub &'static str);
+
+/// A font to draw text with.
+pub struct Font {
+    inner: fontdue::Font,
+}
+
+impl Font {
+    /// Parses a TrueType or OpenType font, or a font collection, in which
+    /// case the first font is used.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FontError> {
+        let inner = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default()).map_err(FontError)?;
+        Ok(Self { inner })
+    }
+}
+
+/// How a string of text is drawn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextStyle {
+    /// Srgb color, with alpha.
+    pub color: Vec4,
+    /// Size of the font. In pixels for text on the screen, in world units for
+    /// text in the world.
+    pub size: f32,
+    /// Hide the text behind the scene. Only has an effect for text in the
+    /// world, when the routine is given the depth buffer.
+    pub depth_test: bool,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            color: Vec4::ONE,
+            size: 24.0,
+            depth_test: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Placement {
+    /// Top left corner in pixels from the top left of the output.
+    Screen(Vec2),
+    /// Centered on the origin of the transform, in its XY plane.
+    World(Mat4),
+    /// Centered on a point, facing the camera.
+    Billboard(Vec3),
+}
+
+#[derive(Debug, Clone)]
+struct Text {
+    placement: Placement,
+    text: String,
+    style: TextStyle,
+}
+
+/// Strings to draw with a [`TextRoutine`].
+#[derive(Debug, Default, Clone)]
+pub struct TextBatch {
+    texts: Vec<Text>,
+}
+
+impl TextBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes all strings, e.g. once a frame has been rendered.
+    pub fn clear(&mut self) {
+        self.texts.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty()
+    }
+
+    /// Text with its top left corner at `position`, in pixels from the top
+    /// left of the output.
+    pub fn screen(&mut self, position: Vec2, text: impl Into<String>, style: TextStyle) {
+        self.push(Placement::Screen(position), text, style);
+    }
+
+    /// Text centered on the origin of `transform`, running along its X axis
+    /// with its Y axis up, like a sign in the world.
+    pub fn world(&mut self, transform: Mat4, text: impl Into<String>, style: TextStyle) {
+        self.push(Placement::World(transform), text, style);
+    }
+
+    /// Text centered on `position` in the world, always facing the camera.
+    pub fn billboard(&mut self, position: Vec3, text: impl Into<String>, style: TextStyle) {
+        self.push(Placement::Billboard(position), text, style);
+    }
+
+    fn push(&mut self, placement: Placement, text: impl Into<String>, style: TextStyle) {
+        self.texts.push(Text {
+            placement,
+            text: text.into(),
+            style,
+        });
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct TextVertex {
+    // Arrays, as the alignment of glam's vectors would pad the struct.
+    /// Clip space position.
+    position: [f32; 4],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+unsafe impl bytemuck::Pod for TextVertex {}
+unsafe impl bytemuck::Zeroable for TextVertex {}
+
+/// Pipelines for one sample count.
+struct TextPipelines {
+    /// For passes without a depth buffer.
+    no_depth: RenderPipeline,
+    depth_tested: RenderPipeline,
+    depth_ignored: RenderPipeline,
+}
+
+impl TextPipelines {
+    fn new(
+        device: &Device,
+        pll: &PipelineLayout,
+        module: &ShaderModule,
+        format: TextureFormat,
+        samples: SampleCount,
+        depth_mode: DepthMode,
+    ) -> Self {
+        Self {
+            no_depth: create_pipeline(device, pll, module, format, samples, None),
+            depth_tested: create_pipeline(device, pll, module, format, samples, Some(depth_mode.closer_or_equal())),
+            depth_ignored: create_pipeline(device, pll, module, format, samples, Some(CompareFunction::Always)),
+        }
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    pll: &PipelineLayout,
+    module: &ShaderModule,
+    format: TextureFormat,
+    samples: SampleCount,
+    depth_compare: Option<CompareFunction>,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("text"),
+        layout: Some(pll),
+        vertex: VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[VertexBufferLayout {
+                array_stride: mem::size_of::<TextVertex>() as BufferAddress,
+                step_mode: VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x2, 2 => Float32x4],
+            }],
+        },
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Cw,
+            cull_mode: None,
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: depth_compare.map(|depth_compare| DepthStencilState {
+            format: TextureFormat::Depth32Float,
+            depth_write_enabled: false,
+            depth_compare,
+            stencil: StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        multisample: MultisampleState {
+            count: samples as u32,
+            ..MultisampleState::default()
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point: "fs_main",
+            targets: &[ColorTargetState {
+                format,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::all(),
+            }],
+        }),
+        multiview: None,
+    })
+}
+
+/// Draws the contents of a [`TextBatch`].
+///
+/// See module for documentation.
+pub struct TextRoutine {
+    font: Font,
+    atlas: Mutex<GlyphAtlas>,
+    atlas_texture: Texture,
+    atlas_bg: BindGroup,
+    pipelines_s1: TextPipelines,
+    pipelines_s4: TextPipelines,
+    linear_output: bool,
+    depth_mode: DepthMode,
+}
+
+impl TextRoutine {
+    /// Creates a routine drawing text in `font` into targets of `format`.
+    pub fn new(renderer: &Renderer, font: Font, format: TextureFormat) -> Self {
+        profiling::scope!("TextRoutine::new");
+
+        let atlas_texture = renderer.device.create_texture(&TextureDescriptor {
+            label: Some("text atlas"),
+            size: wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        });
+        let atlas_view = atlas_texture.create_view(&TextureViewDescriptor::default());
+        let sampler = renderer.device.create_sampler(&SamplerDescriptor {
+            label: Some("text atlas"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..SamplerDescriptor::default()
+        });
+
+        let bgl = BindGroupLayoutBuilder::new()
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                None,
+            )
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Sampler(SamplerBindingType::Filtering),
+                None,
+            )
+            .build(&renderer.device, Some("text bgl"));
+        let atlas_bg = BindGroupBuilder::new()
+            .append_texture_view(&atlas_view)
+            .append_sampler(&sampler)
+            .build(&renderer.device, Some("text bg"), &bgl);
+
+        let module = renderer.device.create_shader_module(&ShaderModuleDescriptor {
+            label: Some("text"),
+            source: ShaderSource::Wgsl(include_str!("text.wgsl").into()),
+        });
+        let pll = renderer.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("text"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+        let pipelines =
+            |samples| TextPipelines::new(&renderer.device, &pll, &module, format, samples, renderer.depth_mode);
+
+        Self {
+            font,
+            atlas: Mutex::new(GlyphAtlas::new()),
+            atlas_texture,
+            atlas_bg,
+            pipelines_s1: pipelines(SampleCount::One),
+            pipelines_s4: pipelines(SampleCount::Four),
+            // Float targets hold linear color, like srgb ones after decoding.
+            linear_output: format.describe().srgb || format == TextureFormat::Rgba16Float,
+            depth_mode: renderer.depth_mode,
+        }
+    }
+
+    /// Draws everything in `batch` on top of `color`, seen from the camera of
+    /// the renderer. With `depth`, text in the world with
+    /// [`TextStyle::depth_test`] is hidden behind the scene.
+    ///
+    /// `resolution` and `samples` are those of `color` and `depth`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_to_graph<'node>(
+        &'node self,
+        graph: &mut RenderGraph<'node>,
+        batch: &'node TextBatch,
+        color: RenderTargetHandle,
+        resolve: Option<RenderTargetHandle>,
+        depth: Option<RenderTargetHandle>,
+        resolution: UVec2,
+        samples: SampleCount,
+    ) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let mut builder = graph.add_node("Text");
+
+        let color_handle = builder.add_render_target_output(color);
+        let resolve_handle = builder.add_optional_render_target_output(resolve);
+        let depth_handle = depth.map(|depth| builder.add_render_target_output(depth));
+
+        let rpass_handle = builder.add_renderpass(RenderPassTargets {
+            targets: vec![RenderPassTarget {
+                color: color_handle,
+                clear: Color::BLACK,
+                resolve: resolve_handle,
+            }],
+            depth_stencil: depth_handle.map(|depth| RenderPassDepthTarget {
+                target: DepthHandle::RenderTarget(depth),
+                depth_clear: Some(self.depth_mode.far_depth()),
+                stencil_clear: None,
+            }),
+        });
+
+        let pt_handle = builder.passthrough_ref(self);
+        let batch_handle = builder.passthrough_ref(batch);
+
+        builder.build(move |pt, renderer, encoder_or_pass, temps, _ready, graph_data| {
+            let this = pt.get(pt_handle);
+            let batch = pt.get(batch_handle);
+            let rpass = encoder_or_pass.get_rpass(rpass_handle);
+
+            profiling::scope!("text");
+
+            // The camera is only final once the renderer is readied.
+            let camera = graph_data.camera_manager;
+            let vertex_builder = VertexBuilder {
+                view_proj: camera.view_proj(),
+                inv_view: camera.view().inverse(),
+                resolution: resolution.as_vec2(),
+                linear_output: this.linear_output,
+                near_depth: this.depth_mode.near_depth(),
+            };
+            let (tested, untested) = this.build_vertices(renderer, batch, &vertex_builder, depth.is_some());
+
+            let pipelines = match samples {
+                SampleCount::One => &this.pipelines_s1,
+                SampleCount::Four => &this.pipelines_s4,
+            };
+            let untested_pipeline = match depth {
+                Some(_) => &pipelines.depth_ignored,
+                None => &pipelines.no_depth,
+            };
+
+            rpass.set_bind_group(0, &this.atlas_bg, &[]);
+            // Text hidden by the scene first, so text drawn on top isn't hidden by it.
+            for (pipeline, vertices) in [(&pipelines.depth_tested, tested), (untested_pipeline, untested)] {
+                if vertices.is_empty() {
+                    continue;
+                }
+
+                let vertex_buffer = temps.add(renderer.device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("text vertices"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: BufferUsages::VERTEX,
+                }));
+
+                rpass.set_pipeline(pipeline);
+                rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                rpass.draw(0..vertices.len() as u32, 0..1);
+            }
+        });
+    }
+
+    /// Lays out the batch, adding missing glyphs to the atlas, and returns the
+    /// vertices of depth tested and untested text.
+    fn build_vertices(
+        &self,
+        renderer: &Renderer,
+        batch: &TextBatch,
+        vertex_builder: &VertexBuilder,
+        has_depth: bool,
+    ) -> (Vec<TextVertex>, Vec<TextVertex>) {
+        let mut layout = fontdue::layout::Layout::new(fontdue::layout::CoordinateSystem::PositiveYDown);
+        let layouts: Vec<_> = batch
+            .texts
+            .iter()
+            .map(|text| {
+                layout.reset(&fontdue::layout::LayoutSettings::default());
+                layout.append(
+                    &[&self.font.inner],
+                    &fontdue::layout::TextStyle::new(&text.text, RASTER_PX, 0),
+                );
+                (layout.glyphs().clone(), layout.height())
+            })
+            .collect();
+
+        let mut atlas = self.atlas.lock();
+        let insert_all = |atlas: &mut GlyphAtlas| {
+            layouts.iter().flat_map(|(glyphs, _)| glyphs).all(|glyph| {
+                atlas
+                    .insert(
+                        &self.font.inner,
+                        glyph.key.glyph_index,
+                        &renderer.queue,
+                        &self.atlas_texture,
+                    )
+                    .is_some()
+            })
+        };
+        if !insert_all(&mut atlas) {
+            // Start over with only the glyphs needed now.
+            atlas.clear();
+            if !insert_all(&mut atlas) {
+                log::warn!("Text atlas is too small for all glyphs drawn, some are missing");
+            }
+        }
+
+        let mut tested = Vec::new();
+        let mut untested = Vec::new();
+        for (text, (glyphs, height)) in batch.texts.iter().zip(&layouts) {
+            let width = glyphs
+                .iter()
+                .map(|glyph| glyph.x + glyph.width as f32)
+                .fold(0.0_f32, f32::max);
+            let vertices = if text.style.depth_test && has_depth && !matches!(text.placement, Placement::Screen(_)) {
+                &mut tested
+            } else {
+                &mut untested
+            };
+            vertex_builder.text(vertices, text, glyphs, Vec2::new(width, *height), &atlas);
+        }
+        (tested, untested)
+    }
+}
+
+/// Turns laid out text into vertices.
+struct VertexBuilder {
+    view_proj: Mat4,
+    inv_view: Mat4,
+    resolution: Vec2,
+    linear_output: bool,
+    near_depth: f32,
+}
+
+impl VertexBuilder {
+    fn text(
+        &self,
+        vertices: &mut Vec<TextVertex>,
+        text: &Text,
+        glyphs: &[fontdue::layout::GlyphPosition],
+        block_size: Vec2,
+        atlas: &GlyphAtlas,
+    ) {
+        let scale = text.style.size / RASTER_PX;
+        let color = match self.linear_output {
+            true => srgb_to_linear(text.style.color),
+            false => text.style.color,
+        };
+
+        // Clip space position of a point in the layout, in pixels of the
+        // rasterized font with y down.
+        let to_clip = |point: Vec2| -> Vec4 {
+            let centered = (point - block_size / 2.0) * scale * Vec2::new(1.0, -1.0);
+            match text.placement {
+                Placement::Screen(position) => {
+                    let pixel = position + point * scale;
+                    let ndc = pixel / self.resolution * Vec2::new(2.0, -2.0) + Vec2::new(-1.0, 1.0);
+                    ndc.extend(self.near_depth).extend(1.0)
+                }
+                Placement::World(transform) => self.view_proj * transform * centered.extend(0.0).extend(1.0),
+                Placement::Billboard(position) => {
+                    let right = self.inv_view.x_axis.truncate();
+                    let up = self.inv_view.y_axis.truncate();
+                    self.view_proj * (position + right * centered.x + up * centered.y).extend(1.0)
+                }
+            }
+        };
+
+        for glyph in glyphs {
+            let atlas_glyph = match atlas.get(glyph.key.glyph_index) {
+                Some(atlas_glyph) => atlas_glyph,
+                None => continue,
+            };
+            if glyph.width == 0 || glyph.height == 0 {
+                continue;
+            }
+
+            let start = Vec2::new(glyph.x, glyph.y) - SPREAD as f32;
+            let size = atlas_glyph.size.as_vec2();
+            let (uv_min, uv_max) = (atlas_glyph.uv_min(), atlas_glyph.uv_max());
+
+            let corner = |x: f32, y: f32| TextVertex {
+                position: to_clip(start + Vec2::new(x, y) * size).to_array(),
+                uv: (uv_min + Vec2::new(x, y) * (uv_max - uv_min)).to_array(),
+                color: color.to_array(),
+            };
+
+            vertices.extend_from_slice(&[
+                corner(0.0, 0.0),
+                corner(1.0, 0.0),
+                corner(0.0, 1.0),
+                corner(0.0, 1.0),
+                corner(1.0, 0.0),
+                corner(1.0, 1.0),
+            ]);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Conversions from the image crate, behind the `image` feature. Decoders
+//! are enabled with the `image-png`, `image-jpeg`, `image-hdr` and
+//! `image-exr` features.
+
+use glam::UVec2;
+use image::{DynamicImage, GenericImageView};
+use thiserror::Error;
+
+use crate::{ColorSpace, MipmapCount, MipmapSource, Texture, TextureFormat};
+
+/// Magic number OpenEXR files start with.
+const EXR_MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+#[derive(Debug, Error)]
+pub enum ImageTextureError {
+    #[error("Failed to decode image")]
+    Image(#[from] image::ImageError),
+    #[cfg(feature = "image-exr")]
+    #[error("Failed to decode OpenEXR image")]
+    Exr(#[from] exr::error::Error),
+    #[error("OpenEXR images need the image-exr feature")]
+    ExrDisabled,
+}
+
+/// Format hdr and OpenEXR images are stored in, see
+/// [`Texture::from_encoded_bytes_with_hdr_format`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum HdrFormat {
+    /// 8 bytes per texel, keeping alpha and negative values. Mips are
+    /// generated by the renderer.
+    Rgba16Float,
+    /// 4 bytes per texel, without alpha, and negative values clamped to 0.
+    /// Half the memory of `Rgba16Float` for environment maps. The format
+    /// can't be rendered into, so mips are computed while decoding.
+    Rg11b10Float,
+}
+
+impl Texture {
+    /// Converts a decoded image into a texture with a full mip chain,
+    /// generated by the renderer.
+    ///
+    /// 8 bit images become `Rgba8UnormSrgb` if `srgb`, for color, or
+    /// `Rgba8Unorm` otherwise, for data like normal maps. 16 bit images keep
+    /// their precision as `Rgba16Float` when they hold data, and become 8 bit
+    /// srgb when they hold color.
+    pub fn from_image(image: DynamicImage, srgb: bool) -> Self {
+        let (width, height) = image.dimensions();
+        let size = UVec2::new(width, height);
+        let sixteen_bit = matches!(
+            image,
+            DynamicImage::ImageLuma16(_)
+                | DynamicImage::ImageLumaA16(_)
+                | DynamicImage::ImageRgb16(_)
+                | DynamicImage::ImageRgba16(_)
+        );
+
+        match (sixteen_bit, srgb) {
+            (true, false) => {
+                let texels = image
+                    .to_rgba16()
+                    .into_raw()
+                    .into_iter()
+                    .map(|value| value as f32 / u16::MAX as f32);
+                Self::from_f32_texels(texels, size, HdrFormat::Rgba16Float)
+            }
+            (_, srgb) => Self {
+                label: None,
+                data: image.to_rgba8().into_raw(),
+                format: match srgb {
+                    true => TextureFormat::Rgba8UnormSrgb,
+                    false => TextureFormat::Rgba8Unorm,
+                },
+                color_space: ColorSpace::FromFormat,
+                size,
+                mip_count: MipmapCount::Maximum,
+                mip_source: MipmapSource::Generated,
+            },
+        }
+    }
+
+    /// Decodes a png, jpeg, Radiance hdr or OpenEXR file, depending on the
+    /// enabled features, into a texture as [`Texture::from_image`] does.
+    ///
+    /// Hdr and OpenEXR files hold linear light, brighter than 1 for the sky
+    /// and lights of environment maps. They become `Rgba16Float` whatever
+    /// `srgb` is.
+    pub fn from_encoded_bytes(bytes: &[u8], srgb: bool) -> Result<Self, ImageTextureError> {
+        Self::from_encoded_bytes_with_hdr_format(bytes, srgb, HdrFormat::Rgba16Float)
+    }
+
+    /// Decodes an image like [`Texture::from_encoded_bytes`], storing hdr and
+    /// OpenEXR images as `hdr_format`.
+    pub fn from_encoded_bytes_with_hdr_format(
+        bytes: &[u8],
+        srgb: bool,
+        hdr_format: HdrFormat,
+    ) -> Result<Self, ImageTextureError> {
+        if bytes.starts_with(&EXR_MAGIC) {
+            return Self::from_exr_bytes(bytes, hdr_format);
+        }
+
+        match image::guess_format(bytes)? {
+            #[cfg(feature = "image-hdr")]
+            image::ImageFormat::Hdr => {
+                let decoder = image::codecs::hdr::HdrDecoder::new(std::io::Cursor::new(bytes))?;
+                let metadata = decoder.metadata();
+                let pixels = decoder.read_image_hdr()?;
+                let texels = pixels.into_iter().flat_map(|pixel| {
+                    let [r, g, b] = pixel.0;
+                    [r, g, b, 1.0]
+                });
+                Ok(Self::from_f32_texels(
+                    texels,
+                    UVec2::new(metadata.width, metadata.height),
+                    hdr_format,
+                ))
+            }
+            format => Ok(Self::from_image(
+                image::load_from_memory_with_format(bytes, format)?,
+                srgb,
+            )),
+        }
+    }
+
+    #[cfg(feature = "image-exr")]
+    fn from_exr_bytes(bytes: &[u8], hdr_format: HdrFormat) -> Result<Self, ImageTextureError> {
+        use exr::prelude::*;
+
+        let image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .specific_channels()
+            .required("R")
+            .required("G")
+            .required("B")
+            .optional("A", 1.0_f32)
+            .collect_pixels(
+                |resolution, _channels| {
+                    let texels = vec![0.0_f32; resolution.width() * resolution.height() * 4];
+                    (resolution.width(), texels)
+                },
+                |(width, texels), position, (r, g, b, a): (f32, f32, f32, f32)| {
+                    let start = (position.y() * *width + position.x()) * 4;
+                    texels[start..start + 4].copy_from_slice(&[r, g, b, a]);
+                },
+            )
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(std::io::Cursor::new(bytes))?;
+
+        let size = image.layer_data.size;
+        let (_, texels) = image.layer_data.channel_data.pixels;
+        Ok(Self::from_f32_texels(
+            texels,
+            UVec2::new(size.width() as u32, size.height() as u32),
+            hdr_format,
+        ))
+    }
+
+    #[cfg(not(feature = "image-exr"))]
+    fn from_exr_bytes(_bytes: &[u8], _hdr_format: HdrFormat) -> Result<Self, ImageTextureError> {
+        Err(ImageTextureError::ExrDisabled)
+    }
+
+    /// Rgba values, linear, packed into a texture of `format`.
+    fn from_f32_texels(texels: impl IntoIterator<Item = f32>, size: UVec2, format: HdrFormat) -> Self {
+        let (data, format, mip_source) = match format {
+            HdrFormat::Rgba16Float => (
+                texels
+                    .into_iter()
+                    .flat_map(|value| f32_to_f16(value).to_le_bytes())
+                    .collect(),
+                TextureFormat::Rgba16Float,
+                MipmapSource::Generated,
+            ),
+            HdrFormat::Rg11b10Float => {
+                let texels: Vec<f32> = texels.into_iter().collect();
+                let data = mip_chain(texels, size)
+                    .flat_map(|level| {
+                        level
+                            .chunks_exact(4)
+                            .map(|texel| pack_rg11b10(texel[0], texel[1], texel[2]))
+                            .collect::<Vec<_>>()
+                    })
+                    .flat_map(u32::to_le_bytes)
+                    .collect();
+                (data, TextureFormat::Rg11b10Float, MipmapSource::Uploaded)
+            }
+        };
+        Self {
+            label: None,
+            data,
+            format,
+            color_space: ColorSpace::FromFormat,
+            size,
+            mip_count: MipmapCount::Maximum,
+            mip_source,
+        }
+    }
+}
+
+/// Every mip level of an rgba image, starting with the image itself, each
+/// texel averaging the up to 2x2 texels above it.
+fn mip_chain(texels: Vec<f32>, size: UVec2) -> impl Iterator<Item = Vec<f32>> {
+    let mut level = Some((texels, size));
+    std::iter::from_fn(move || {
+        let (texels, size) = level.take()?;
+        if size.x > 1 || size.y > 1 {
+            let next_size = (size / 2).max(UVec2::ONE);
+            let mut next = Vec::with_capacity((next_size.x * next_size.y * 4) as usize);
+            for y in 0..next_size.y {
+                for x in 0..next_size.x {
+                    // Odd sizes drop their last row or column, as mip sizes round down.
+                    let rows = (y * 2)..(y * 2 + 2).min(size.y);
+                    let columns = (x * 2)..(x * 2 + 2).min(size.x);
+                    let count = (rows.len() * columns.len()) as f32;
+                    for channel in 0..4 {
+                        let mut sum = 0.0;
+                        for row in rows.clone() {
+                            for column in columns.clone() {
+                                sum += texels[((row * size.x + column) * 4 + channel) as usize];
+                            }
+                        }
+                        next.push(sum / count);
+                    }
+                }
+            }
+            level = Some((next, next_size));
+        }
+        Some(texels)
+    })
+}
+
+/// Packs linear rgb into the bits of an `Rg11b10Float` texel.
+fn pack_rg11b10(r: f32, g: f32, b: f32) -> u32 {
+    f32_to_unsigned_float(r, 6) | (f32_to_unsigned_float(g, 6) << 11) | (f32_to_unsigned_float(b, 5) << 22)
+}
+
+/// Bits of the half float nearest to `value`, rounding ties to even.
+/// Values out of range become infinite, and NaN stays NaN.
+fn f32_to_f16(value: f32) -> u16 {
+    let sign = ((value.to_bits() >> 16) & 0x8000) as u16;
+    sign | f32_to_unsigned_float(value.abs(), 10) as u16
+}
+
+/// Bits of the float with a 5 bit exponent, `mantissa_bits` of mantissa and
+/// no sign nearest to `value`, rounding ties to even. Negative values become
+/// 0, values out of range infinite, and NaN stays NaN.
+fn f32_to_unsigned_float(value: f32, mantissa_bits: u32) -> u32 {
+    let infinity = 0x1f << mantissa_bits;
+    if value.is_nan() {
+        return infinity | (1 << (mantissa_bits - 1));
+    }
+    if value <= 0.0 {
+        return 0;
+    }
+
+    let bits = value.to_bits();
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+    let dropped_bits = 23 - mantissa_bits;
+
+    // Rebias from 127 to 15.
+    let small_exponent = exponent - 127 + 15;
+    if small_exponent >= 0x1f {
+        return infinity;
+    }
+
+    let (small, remainder, halfway) = if small_exponent <= 0 {
+        // Subnormal, or too small for even that.
+        if small_exponent < -(mantissa_bits as i32) {
+            return 0;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (dropped_bits as i32 + 1 - small_exponent) as u32;
+        (mantissa >> shift, mantissa & ((1 << shift) - 1), 1 << (shift - 1))
+    } else {
+        (
+            ((small_exponent as u32) << mantissa_bits) | (mantissa >> dropped_bits),
+            mantissa & ((1 << dropped_bits) - 1),
+            1 << (dropped_bits - 1),
+        )
+    };
+    let round_up = remainder > halfway || (remainder == halfway && small & 1 == 1);
+    // Rounding up may carry into the exponent, which is still correct, up to
+    // infinity.
+    small + round_up as u32
+}
+
+#[cfg(test)]
+mod test {
+    use glam::UVec2;
+
+    use super::{f32_to_f16, mip_chain, pack_rg11b10};
+
+    #[test]
+    fn converts_to_half_floats() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NAN) & 0x7c00, 0x7c00);
+        assert_ne!(f32_to_f16(f32::NAN) & 0x03ff, 0);
+        // Smallest subnormal, and the halfway point below it rounding to zero.
+        assert_eq!(f32_to_f16(5.960_464_5e-8), 0x0001);
+        assert_eq!(f32_to_f16(2.980_232_2e-8), 0x0000);
+        // 1 + 2^-11 is halfway between 1 and the next half, and rounds to even.
+        assert_eq!(f32_to_f16(1.000_488_3), 0x3c00);
+        assert_eq!(f32_to_f16(1.001_464_8), 0x3c02);
+    }
+
+    #[test]
+    fn packs_rg11b10() {
+        assert_eq!(pack_rg11b10(0.0, 0.0, 0.0), 0);
+        // 1 has exponent 15 and no mantissa in every channel.
+        assert_eq!(pack_rg11b10(1.0, 0.0, 0.0), 15 << 6);
+        assert_eq!(pack_rg11b10(0.0, 1.0, 0.0), (15 << 6) << 11);
+        assert_eq!(pack_rg11b10(0.0, 0.0, 1.0), (15 << 5) << 22);
+        // Negative values clamp, and large ones become infinite.
+        assert_eq!(pack_rg11b10(-4.0, 1.0e6, 0.0), (0x1f << 6) << 11);
+        // 1.5 fits, 1 + 2^-6 is halfway between 1 and the next value of the
+        // blue channel, and rounds to even.
+        assert_eq!(pack_rg11b10(1.5, 0.0, 1.015_625), (15 << 6 | 1 << 5) | (15 << 5) << 22);
+    }
+
+    #[test]
+    fn mip_chain_averages_down_to_one_texel() {
+        // 3x1, one channel set to the index of the texel.
+        let texels = (0..3).flat_map(|i| [i as f32, 0.0, 0.0, 1.0]).collect();
+        let levels: Vec<_> = mip_chain(texels, UVec2::new(3, 1)).collect();
+        assert_eq!(levels.len(), 2);
+        assert_eq!(levels[1], vec![0.5, 0.0, 0.0, 1.0]);
+    }
+}
@@ -3,7 +3,7 @@
 //! This is reexported in the rend3 crate proper and includes all the "surface"
 //! api arguments.
 
-use glam::{Mat4, UVec2, Vec2, Vec3, Vec3A, Vec4};
+use glam::{Mat3, Mat4, UVec2, Vec2, Vec3, Vec3A, Vec4};
 use std::{
     fmt::Debug,
     hash::Hash,
@@ -14,8 +14,20 @@ use std::{
 };
 use thiserror::Error;
 
+#[cfg(feature = "image")]
+mod image_texture;
+mod mesh_ops;
+#[cfg(feature = "meshopt")]
+mod optimize;
+pub mod primitives;
+mod tangents;
+
 /// Reexport of the glam version rend3 is using.
 pub use glam;
+#[cfg(feature = "image")]
+pub use image_texture::{HdrFormat, ImageTextureError};
+#[cfg(feature = "meshopt")]
+pub use optimize::MeshOptimization;
 
 /// Non-owning resource handle.
 ///
@@ -27,6 +39,19 @@ pub struct RawResourceHandle<T> {
     _phantom: PhantomData<T>,
 }
 
+impl<T> RawResourceHandle<T> {
+    /// Create a handle from the index of a resource.
+    ///
+    /// Part of rend3's internal interface, for registries handing back the
+    /// handles of resources they store.
+    pub fn new(idx: usize) -> Self {
+        Self {
+            idx,
+            _phantom: PhantomData,
+        }
+    }
+}
+
 // Need Debug/Copy/Clone impls that don't require T: Trait.
 impl<T> Debug for RawResourceHandle<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -121,6 +146,19 @@ impl<T> ResourceHandle<T> {
     pub fn get_weak_refcount(&self) -> Weak<()> {
         Arc::downgrade(&self.refcount)
     }
+
+    /// Recreates the owning handle of index `idx` from its weak refcount,
+    /// `None` if every handle was dropped.
+    ///
+    /// Part of rend3's internal interface, for registries handing back the
+    /// handles of resources they store.
+    pub fn from_weak_refcount(refcount: &Weak<()>, idx: usize) -> Option<Self> {
+        Some(Self {
+            refcount: refcount.upgrade()?,
+            idx,
+            _phantom: PhantomData,
+        })
+    }
 }
 
 #[macro_export]
@@ -138,7 +176,8 @@ declare_handle!(
     MaterialHandle<MaterialTag>,
     ObjectHandle<Object>,
     DirectionalLightHandle<DirectionalLight>,
-    SkeletonHandle<Skeleton>
+    SkeletonHandle<Skeleton>,
+    ObjectGroupHandle<ObjectGroup>
 );
 
 #[macro_export]
@@ -156,7 +195,8 @@ declare_raw_handle!(
     RawMaterialHandle<MaterialTag>,
     RawObjectHandle<Object>,
     RawDirectionalLightHandle<DirectionalLight>,
-    RawSkeletonHandle<Skeleton>
+    RawSkeletonHandle<Skeleton>,
+    RawObjectGroupHandle<ObjectGroup>
 );
 
 macro_rules! changeable_struct {
@@ -189,7 +229,7 @@ macro_rules! changeable_struct {
 
 // WGPU REEXPORTS
 #[doc(inline)]
-pub use wgt::{Backend, Backends, Color, DeviceType, PresentMode, TextureFormat, TextureUsages};
+pub use wgt::{Backend, Backends, Color, DeviceType, PowerPreference, PresentMode, TextureFormat, TextureUsages};
 
 /// The maximum amount of vertices any one object can have.
 ///
@@ -247,6 +287,12 @@ pub struct MeshBuilder {
     handedness: Handedness,
     flip_winding_order: bool,
     double_sided: bool,
+    weld_epsilon: Option<f32>,
+    transform: Option<Mat4>,
+    flat_normals: bool,
+
+    #[cfg(feature = "meshopt")]
+    optimization: Option<MeshOptimization>,
 }
 impl MeshBuilder {
     /// Create a new [`MeshBuilder`] with a given set of positions.
@@ -375,8 +421,12 @@ impl MeshBuilder {
 
     /// Build a mesh, adding whatever components weren't provided.
     ///
-    /// If normals weren't provided, they will be calculated. If mesh
-    /// is right handed, will be converted to left handed.
+    /// Welding and transforms are applied first. If normals weren't provided,
+    /// or flat normals were asked for, they will be calculated. If mesh
+    /// is right handed, will be converted to left handed. If tangents weren't
+    /// provided but uvs were, they are generated with
+    /// [`Mesh::generate_tangents`], which may add vertices. Optimization
+    /// comes last.
     ///
     /// All others will be filled with defaults.
     pub fn build(self) -> Result<Mesh, MeshValidationError> {
@@ -408,15 +458,29 @@ impl MeshBuilder {
             mesh.flip_winding_order();
         }
 
-        if !has_normals {
+        if let Some(epsilon) = self.weld_epsilon {
+            mesh.weld(epsilon);
+        }
+
+        if let Some(transform) = self.transform {
+            mesh.transform(transform);
+        }
+
+        if self.flat_normals {
+            mesh.recompute_flat_normals(self.handedness);
+        } else if !has_normals {
             // SAFETY: We've validated this mesh or had its validity unsafely asserted.
             unsafe { mesh.calculate_normals(self.handedness, true) };
         }
 
         // Don't need to bother with tangents if there are no meaningful UVs
         if !has_tangents && has_uvs {
-            // SAFETY: We've validated this mesh or had its validity unsafely asserted.
-            unsafe { mesh.calculate_tangents(true) };
+            mesh.generate_tangents();
+        }
+
+        #[cfg(feature = "meshopt")]
+        if let Some(optimization) = self.optimization {
+            mesh.optimize(&optimization);
         }
 
         Ok(mesh)
@@ -782,12 +846,60 @@ pub enum MipmapSource {
     Generated,
 }
 
+/// Function mapping linear light to the values stored in a texture.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TransferFunction {
+    Linear,
+    /// The piecewise sRGB curve.
+    Srgb,
+    /// The BT.709 camera curve, used by video.
+    Rec709,
+    /// A pure power curve, stored values being linear values to the power of
+    /// `1 / gamma`.
+    Gamma(f32),
+}
+
+/// Color space of the data of a [`Texture`].
+///
+/// When the format can't express the color space, rend3 converts the data
+/// to sRGB, or to linear values for float formats, as the texture is added.
+/// Only uncompressed 8 bit RGBA and BGRA formats and `Rgba32Float` can be
+/// converted; other formats are used as is, with a warning.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorSpace {
+    /// Trust the format: `*Srgb` formats are sRGB, the others hold linear
+    /// values.
+    FromFormat,
+    /// sRGB, as most photos and painted color textures are.
+    Srgb,
+    /// Linear values, or data which isn't color, such as normals, roughness
+    /// or ambient occlusion.
+    Linear,
+    /// Rec.709 primaries with the BT.709 transfer function, as video frames
+    /// are.
+    Rec709,
+    /// Any other RGB color space, given by its transfer function and the
+    /// matrix converting its linear values to linear sRGB primaries.
+    Custom {
+        transfer: TransferFunction,
+        to_srgb_primaries: Mat3,
+    },
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        Self::FromFormat
+    }
+}
+
 /// A bitmap image used as a data source for a texture.
 #[derive(Debug, Clone)]
 pub struct Texture {
     pub label: Option<String>,
     pub data: Vec<u8>,
     pub format: TextureFormat,
+    /// Color space of `data`, overriding the one implied by `format`.
+    pub color_space: ColorSpace,
     pub size: UVec2,
     pub mip_count: MipmapCount,
     pub mip_source: MipmapSource,
@@ -832,10 +944,26 @@ pub trait Material: Send + Sync + 'static {
     const TEXTURE_COUNT: u32;
     /// The amount of data that will be provided to `to_data`.
     const DATA_SIZE: u32;
+    /// Bitmask of the textures given by `to_textures` which hold data rather
+    /// than color, such as normal maps. rend3 warns when these are in srgb
+    /// formats, as the GPU would then gamma decode them.
+    const DATA_TEXTURES: u32 = 0;
+    /// Whether the textures given by `to_textures` are 2D arrays added with
+    /// `Renderer::add_texture_2d_array`, rather than 2D textures. They are
+    /// bound as `texture_2d_array`, and shaders pick the layer set with
+    /// `Renderer::set_object_texture_layer`, which the forward vertex shader of
+    /// rend3-routine passes on as a flat `u32` at location 7. Not supported in
+    /// the GpuDriven profile, where the textures of these materials are left
+    /// unbound.
+    const TEXTURE_ARRAYS: bool = false;
 
     /// u64 key that determine's an object's archetype. When you query for
     /// objects from the object manager, you must provide this key to get all
     /// objects with this key.
+    ///
+    /// The top two bits are reserved for the [`ObjectDepthMode`] of each
+    /// object, see [`ObjectDepthMode::to_key_bits`], and the bit below them
+    /// for hidden objects, see [`ObjectGroup::HIDDEN_KEY_BIT`].
     fn object_key(&self) -> u64;
 
     /// Fill up the given slice with textures.
@@ -862,6 +990,61 @@ changeable_struct! {
     }
 }
 
+/// How an object is depth tested against the rest of the scene, set with
+/// `Renderer::set_object_depth_mode`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ObjectDepthMode {
+    /// Tested and written like any other object.
+    Default,
+    /// Depth is biased towards the camera, so geometry lying on a surface,
+    /// like decals or outlines, doesn't z-fight with it.
+    Biased,
+    /// Drawn over everything else without depth testing, like gizmos and
+    /// waypoint markers. These objects cast no shadows and don't occlude each
+    /// other, so they are drawn in archetype order.
+    AlwaysOnTop,
+}
+
+impl Default for ObjectDepthMode {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+impl ObjectDepthMode {
+    /// Bits of [`Material::object_key`] reserved for the depth mode.
+    pub const KEY_MASK: u64 = 0b11 << 62;
+
+    /// The depth mode bits of the archetype key of objects in this mode.
+    pub fn to_key_bits(self) -> u64 {
+        (self as u64) << 62
+    }
+}
+
+/// Objects moved, hidden and removed together, added with
+/// `Renderer::add_object_group`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ObjectGroup {
+    /// Applied on top of the transform of every object in the group.
+    pub transform: Mat4,
+    pub visible: bool,
+}
+
+impl Default for ObjectGroup {
+    fn default() -> Self {
+        Self {
+            transform: Mat4::IDENTITY,
+            visible: true,
+        }
+    }
+}
+
+impl ObjectGroup {
+    /// Bit of the archetype key of objects in hidden groups. No routine asks
+    /// for these archetypes, so the objects are skipped entirely.
+    pub const HIDDEN_KEY_BIT: u64 = 1 << 61;
+}
+
 /// Describes how the camera should look at the scene.
 #[derive(Debug, Default, Copy, Clone)]
 pub struct Camera {
@@ -883,6 +1066,18 @@ pub enum CameraProjection {
         /// Near plane distance. All projection uses a infinite far plane.
         near: f32,
     },
+    /// Perspective projection with a separate angle to each edge of the view,
+    /// as reported by VR runtimes for each eye. Ignores the aspect ratio.
+    Asymmetric {
+        /// Angles in radians from the view direction to each edge. `left` and
+        /// `down` are usually negative.
+        left: f32,
+        right: f32,
+        up: f32,
+        down: f32,
+        /// Near plane distance. All projection uses a infinite far plane.
+        near: f32,
+    },
     Raw(Mat4),
 }
 
@@ -906,6 +1101,106 @@ changeable_struct! {
     }
 }
 
+/// Settings shared by the shadows of every directional light, set with
+/// `Renderer::set_shadow_settings`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShadowSettings {
+    /// Caps the distance of every light, so shadows can be tuned to the
+    /// scale of the scene without changing each light.
+    pub max_distance: Option<f32>,
+    /// Objects in [`ObjectShadowMode::Default`] whose bounding sphere is
+    /// narrower than this many texels of the shadow map cast no shadow. Far
+    /// reaching shadow maps have larger texels, so they skip more objects.
+    pub min_caster_texels: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            max_distance: None,
+            min_caster_texels: 0.0,
+        }
+    }
+}
+
+/// Whether an object casts shadows, set with
+/// `Renderer::set_object_shadow_mode`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ObjectShadowMode {
+    /// Casts shadows unless it is smaller than
+    /// [`ShadowSettings::min_caster_texels`].
+    Default,
+    /// Casts shadows whatever its size, e.g. for a thin but important pole.
+    Always,
+    /// Casts no shadows.
+    Never,
+    /// Casts shadows while within this distance of the camera, and follows
+    /// [`ObjectShadowMode::Default`] there. Good for clutter like grass.
+    MaxDistance(f32),
+}
+
+impl Default for ObjectShadowMode {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+/// How much of an object is drawn, set with `Renderer::set_object_fade`.
+///
+/// Opaque passes can't blend, so a faded object leaves out pixels in a fixed
+/// dithered pattern instead ("screen-door" transparency). Its depth and
+/// shadows are dithered the same way.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ObjectFade {
+    /// Fraction of the pixels drawn, from 0.0 for none to 1.0 for all.
+    pub opacity: f32,
+    /// Draws the pixels an uninverted fade leaves out. An object faded to
+    /// `x` and one faded to `1.0 - x`, inverted, together cover every pixel
+    /// once, see [`ObjectFade::cross_fade`].
+    pub inverted: bool,
+}
+
+impl ObjectFade {
+    pub const OPAQUE: Self = Self {
+        opacity: 1.0,
+        inverted: false,
+    };
+
+    pub fn new(opacity: f32) -> Self {
+        Self {
+            opacity,
+            inverted: false,
+        }
+    }
+
+    /// Fades of the outgoing and incoming object `amount` of the way, from
+    /// 0.0 to 1.0, through a transition, e.g. between two levels of detail.
+    pub fn cross_fade(amount: f32) -> (Self, Self) {
+        let amount = amount.clamp(0.0, 1.0);
+        let outgoing = Self {
+            opacity: 1.0 - amount,
+            inverted: true,
+        };
+        (outgoing, Self::new(amount))
+    }
+
+    /// Value read by the shaders: the opacity, or the opacity minus two when
+    /// inverted.
+    pub fn to_shader(self) -> f32 {
+        let opacity = self.opacity.clamp(0.0, 1.0);
+        match self.inverted {
+            false => opacity,
+            true => opacity - 2.0,
+        }
+    }
+}
+
+impl Default for ObjectFade {
+    fn default() -> Self {
+        Self::OPAQUE
+    }
+}
+
 /// The sample count when doing multisampling.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[repr(u8)]
@@ -961,6 +1256,80 @@ impl Default for Handedness {
     }
 }
 
+/// How depth values are laid out in depth buffers, including shadow maps.
+///
+/// The renderer builds its projections, depth tests and shadow comparisons
+/// from this, so custom passes sampling depth need to follow it too.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DepthMode {
+    /// The near plane is at 1.0 and infinitely far away is at 0.0. Has the
+    /// best precision, so this is the default.
+    ReversedZInfinite,
+    /// The near plane is at 1.0 and the far plane at 0.0.
+    ReversedZFinite { far: f32 },
+    /// The near plane is at 0.0 and the far plane at 1.0.
+    Standard { far: f32 },
+}
+
+impl Default for DepthMode {
+    fn default() -> Self {
+        Self::ReversedZInfinite
+    }
+}
+
+impl DepthMode {
+    /// True if closer fragments have greater depth.
+    pub fn is_reversed(self) -> bool {
+        !matches!(self, Self::Standard { .. })
+    }
+
+    /// Depth of the far plane, which depth buffers are cleared to.
+    pub fn far_depth(self) -> f32 {
+        match self.is_reversed() {
+            true => 0.0,
+            false => 1.0,
+        }
+    }
+
+    /// Depth of the near plane.
+    pub fn near_depth(self) -> f32 {
+        1.0 - self.far_depth()
+    }
+
+    /// Depth comparison which passes fragments at least as close as the depth
+    /// they are compared against.
+    pub fn closer_or_equal(self) -> wgt::CompareFunction {
+        match self.is_reversed() {
+            true => wgt::CompareFunction::GreaterEqual,
+            false => wgt::CompareFunction::LessEqual,
+        }
+    }
+
+    /// Coefficients `(a, b)` such that a perspective projection with the given
+    /// near plane stores `a + b / distance` for a point `distance` in front of
+    /// the camera.
+    pub fn perspective_coefficients(self, near: f32) -> (f32, f32) {
+        match self {
+            Self::ReversedZInfinite => (0.0, near),
+            Self::ReversedZFinite { far } => {
+                let a = near / (near - far);
+                (a, -a * far)
+            }
+            Self::Standard { far } => {
+                let a = far / (far - near);
+                (a, -a * near)
+            }
+        }
+    }
+
+    /// Distance in front of the camera of a point with the given value in the
+    /// depth buffer, for a perspective projection with the given near plane.
+    pub fn perspective_distance(self, depth: f32, near: f32) -> f32 {
+        let (a, b) = self.perspective_coefficients(near);
+        b / (depth - a)
+    }
+}
+
 /// A Skeleton stores the necessary data to do vertex skinning for an [Object].
 #[derive(Debug, Clone)]
 pub struct Skeleton {
@@ -0,0 +1,326 @@
+//! Whole-mesh operations procedural geometry keeps needing: recomputing
+//! normals, welding vertices back together and baking transforms.
+
+use std::collections::HashMap;
+
+use glam::{IVec3, Mat3, Mat4, Vec2, Vec3};
+
+use crate::{Handedness, Mesh, MeshBuilder};
+
+impl Mesh {
+    /// Recomputes smooth normals from the positions, averaging the normals
+    /// of the faces around each vertex. Vertices at hard edges must not be
+    /// shared between the faces on either side for the edge to stay hard.
+    ///
+    /// # Panics
+    ///
+    /// If the mesh doesn't pass [`Mesh::validate`].
+    pub fn recompute_normals(&mut self, handedness: Handedness) {
+        self.validate().expect("recomputing normals of an invalid mesh");
+        // SAFETY: The mesh was just validated.
+        unsafe { self.calculate_normals(handedness, false) };
+    }
+
+    /// Gives every face its own vertices and their normal the normal of the
+    /// face, for faceted shading. Every triangle ends up with three vertices.
+    ///
+    /// # Panics
+    ///
+    /// If the mesh doesn't pass [`Mesh::validate`].
+    pub fn recompute_flat_normals(&mut self, handedness: Handedness) {
+        self.validate().expect("recomputing normals of an invalid mesh");
+
+        fn unshare<T: Copy>(attribute: &mut Vec<T>, indices: &[u32]) {
+            *attribute = indices.iter().map(|&idx| attribute[idx as usize]).collect();
+        }
+        unshare(&mut self.vertex_positions, &self.indices);
+        unshare(&mut self.vertex_normals, &self.indices);
+        unshare(&mut self.vertex_tangents, &self.indices);
+        unshare(&mut self.vertex_uv0, &self.indices);
+        unshare(&mut self.vertex_uv1, &self.indices);
+        unshare(&mut self.vertex_colors, &self.indices);
+        unshare(&mut self.vertex_joint_indices, &self.indices);
+        unshare(&mut self.vertex_joint_weights, &self.indices);
+        self.indices = (0..self.indices.len() as u32).collect();
+
+        // SAFETY: Every attribute now has a vertex per index, in order. As no
+        // vertex is shared, the smooth normal of each is that of its face.
+        unsafe { self.calculate_normals(handedness, false) };
+    }
+
+    /// Merges vertices closer than `epsilon` to each other whose other
+    /// attributes are also within `epsilon` of each other, and whose colors
+    /// and joint indices are equal. Triangles collapsed by the merge are
+    /// removed, as are vertices no triangle uses anymore.
+    ///
+    /// An `epsilon` of zero only merges exact duplicates, looked up by the
+    /// bits of their attributes in linear time.
+    ///
+    /// # Panics
+    ///
+    /// If the mesh doesn't pass [`Mesh::validate`].
+    pub fn weld(&mut self, epsilon: f32) {
+        self.validate().expect("welding an invalid mesh");
+
+        let merged = match epsilon > 0.0 {
+            true => self.merge_near_vertices(epsilon),
+            false => self.merge_exact_vertices(),
+        };
+
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|idx| merged[idx as usize]);
+            if a != b && b != c && c != a {
+                indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+        self.indices = indices;
+
+        let mut remap = vec![!0; self.vertex_positions.len()];
+        let mut used = 0;
+        for &index in &self.indices {
+            if remap[index as usize] == !0 {
+                remap[index as usize] = used;
+                used += 1;
+            }
+        }
+        for index in &mut self.indices {
+            *index = remap[*index as usize];
+        }
+        self.remap_vertices(used as usize, &remap);
+    }
+
+    /// Index of the vertex each vertex merges into, comparing every vertex
+    /// with the kept ones in the grid cells as large as epsilon around its
+    /// own.
+    fn merge_near_vertices(&self, epsilon: f32) -> Vec<u32> {
+        let mut grid: HashMap<IVec3, Vec<u32>> = HashMap::new();
+        let mut merged: Vec<u32> = Vec::with_capacity(self.vertex_positions.len());
+        for vertex in 0..self.vertex_positions.len() {
+            let cell = (self.vertex_positions[vertex] / epsilon).floor().as_ivec3();
+            let neighbors =
+                (-1..=1).flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| IVec3::new(x, y, z))));
+            let existing = neighbors
+                .filter_map(|offset| grid.get(&(cell + offset)))
+                .flatten()
+                .copied()
+                .find(|&other| self.vertices_match(vertex, other as usize, epsilon));
+            merged.push(match existing {
+                Some(other) => other,
+                None => {
+                    grid.entry(cell).or_default().push(vertex as u32);
+                    vertex as u32
+                }
+            });
+        }
+        merged
+    }
+
+    /// Index of the vertex each vertex merges into, looking exact duplicates
+    /// up by the bits of their attributes.
+    fn merge_exact_vertices(&self) -> Vec<u32> {
+        // Adding zero turns -0.0 into 0.0, which compare equal.
+        fn bits<const N: usize>(values: [f32; N]) -> [u32; N] {
+            values.map(|value| (value + 0.0).to_bits())
+        }
+
+        let mut kept: HashMap<_, u32> = HashMap::with_capacity(self.vertex_positions.len());
+        (0..self.vertex_positions.len())
+            .map(|vertex| {
+                let key = (
+                    bits(self.vertex_positions[vertex].to_array()),
+                    bits(self.vertex_normals[vertex].to_array()),
+                    bits(self.vertex_tangents[vertex].to_array()),
+                    bits(self.vertex_uv0[vertex].to_array()),
+                    bits(self.vertex_uv1[vertex].to_array()),
+                    self.vertex_colors[vertex],
+                    self.vertex_joint_indices[vertex],
+                    bits(self.vertex_joint_weights[vertex].to_array()),
+                );
+                *kept.entry(key).or_insert(vertex as u32)
+            })
+            .collect()
+    }
+
+    fn vertices_match(&self, a: usize, b: usize, epsilon: f32) -> bool {
+        fn near3(values: &[Vec3], a: usize, b: usize, epsilon: f32) -> bool {
+            values[a].abs_diff_eq(values[b], epsilon)
+        }
+        fn near2(values: &[Vec2], a: usize, b: usize, epsilon: f32) -> bool {
+            values[a].abs_diff_eq(values[b], epsilon)
+        }
+
+        self.vertex_positions[a].distance_squared(self.vertex_positions[b]) <= epsilon * epsilon
+            && near3(&self.vertex_normals, a, b, epsilon)
+            && near3(&self.vertex_tangents, a, b, epsilon)
+            && near2(&self.vertex_uv0, a, b, epsilon)
+            && near2(&self.vertex_uv1, a, b, epsilon)
+            && self.vertex_colors[a] == self.vertex_colors[b]
+            && self.vertex_joint_indices[a] == self.vertex_joint_indices[b]
+            && self.vertex_joint_weights[a].abs_diff_eq(self.vertex_joint_weights[b], epsilon)
+    }
+
+    /// Moves every vertex to its index in `remap`, `!0` dropping it, leaving
+    /// `vertex_count` vertices.
+    pub(crate) fn remap_vertices(&mut self, vertex_count: usize, remap: &[u32]) {
+        fn apply<T: Copy + Default>(attribute: &mut Vec<T>, vertex_count: usize, remap: &[u32]) {
+            let mut remapped = vec![T::default(); vertex_count];
+            for (value, &new) in attribute.iter().zip(remap) {
+                if new != !0 {
+                    remapped[new as usize] = *value;
+                }
+            }
+            *attribute = remapped;
+        }
+
+        apply(&mut self.vertex_positions, vertex_count, remap);
+        apply(&mut self.vertex_normals, vertex_count, remap);
+        apply(&mut self.vertex_tangents, vertex_count, remap);
+        apply(&mut self.vertex_uv0, vertex_count, remap);
+        apply(&mut self.vertex_uv1, vertex_count, remap);
+        apply(&mut self.vertex_colors, vertex_count, remap);
+        apply(&mut self.vertex_joint_indices, vertex_count, remap);
+        apply(&mut self.vertex_joint_weights, vertex_count, remap);
+    }
+
+    /// Turns the mesh inside out, flipping both the winding order and the
+    /// normals. Tangents keep following u.
+    pub fn invert(&mut self) {
+        self.flip_winding_order();
+        for normal in &mut self.vertex_normals {
+            *normal = -*normal;
+        }
+    }
+
+    /// Bakes `transform` into the positions, normals and tangents of the
+    /// mesh.
+    ///
+    /// Transforms which mirror the mesh also flip its winding order, so the
+    /// same side of every face stays the front. Tangents are mirrored along
+    /// with the mesh, but as rend3 derives bitangents from the normal and
+    /// tangent, normal maps on mirrored meshes have their green channel
+    /// flipped.
+    pub fn transform(&mut self, transform: Mat4) {
+        let linear = Mat3::from_mat4(transform);
+        let normal_matrix = linear.inverse().transpose();
+
+        for position in &mut self.vertex_positions {
+            *position = transform.transform_point3(*position);
+        }
+        for normal in &mut self.vertex_normals {
+            *normal = (normal_matrix * *normal).normalize_or_zero();
+        }
+        for (tangent, &normal) in self.vertex_tangents.iter_mut().zip(&self.vertex_normals) {
+            let transformed = linear * *tangent;
+            *tangent = (transformed - normal * normal.dot(transformed)).normalize_or_zero();
+        }
+
+        if linear.determinant() < 0.0 {
+            self.flip_winding_order();
+        }
+    }
+}
+
+impl MeshBuilder {
+    /// Welds vertices within `epsilon` of each other when the mesh is built,
+    /// before normals are calculated. See [`Mesh::weld`].
+    pub fn with_welding(mut self, epsilon: f32) -> Self {
+        self.weld_epsilon = Some(epsilon);
+        self
+    }
+
+    /// Bakes a transform into the mesh when it's built, before normals are
+    /// calculated. See [`Mesh::transform`].
+    pub fn with_transform(mut self, transform: Mat4) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Replaces the normals of the mesh with flat ones when it's built. See
+    /// [`Mesh::recompute_flat_normals`].
+    pub fn with_flat_normals(mut self) -> Self {
+        self.flat_normals = true;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Vec2, Vec3};
+
+    use crate::{Handedness, MeshBuilder};
+
+    #[test]
+    fn welding_merges_close_vertices() {
+        // A quad made of two unindexed triangles, one corner slightly off.
+        let positions = vec![
+            Vec3::ZERO,
+            Vec3::Y,
+            Vec3::X,
+            Vec3::new(1.0, 0.0, 1e-5),
+            Vec3::Y,
+            Vec3::new(1.0, 1.0, 0.0),
+        ];
+        let mut mesh = MeshBuilder::new(positions, Handedness::Left)
+            .with_vertex_uv0(vec![Vec2::ZERO; 6])
+            .with_welding(1e-4)
+            .build()
+            .unwrap();
+        assert_eq!(mesh.vertex_positions.len(), 4);
+        assert_eq!(mesh.indices, [0, 1, 2, 2, 1, 3]);
+        for normal in &mesh.vertex_normals {
+            assert!(normal.abs_diff_eq(-Vec3::Z, 1e-3));
+        }
+
+        // Collapsing an edge removes its triangle.
+        mesh.weld(2.0);
+        assert_eq!(mesh.vertex_positions.len(), 0);
+        assert!(mesh.indices.is_empty());
+    }
+
+    #[test]
+    fn welding_exact_duplicates_scales_to_large_meshes() {
+        // A grid of unindexed quads, every inner corner repeated by the six
+        // triangles around it.
+        let size = 256;
+        let corner = |x: usize, y: usize| Vec3::new(x as f32, y as f32, 0.0);
+        let mut positions = Vec::with_capacity(size * size * 6);
+        for y in 0..size {
+            for x in 0..size {
+                positions.extend_from_slice(&[
+                    corner(x, y),
+                    corner(x, y + 1),
+                    corner(x + 1, y),
+                    corner(x + 1, y),
+                    corner(x, y + 1),
+                    corner(x + 1, y + 1),
+                ]);
+            }
+        }
+        // Negative zero is the same position, the next float over isn't.
+        positions[0] = Vec3::new(-0.0, 0.0, 0.0);
+        positions[5] = Vec3::new(1.0, 1.0, f32::from_bits(1));
+
+        let mesh = MeshBuilder::new(positions, Handedness::Left)
+            .with_welding(0.0)
+            .build()
+            .unwrap();
+        assert_eq!(mesh.vertex_positions.len(), (size + 1) * (size + 1) + 1);
+        assert_eq!(mesh.indices.len(), size * size * 6);
+    }
+
+    #[test]
+    fn mirroring_keeps_faces_in_front() {
+        let positions = vec![Vec3::ZERO, Vec3::Y, Vec3::X];
+        let original = MeshBuilder::new(positions, Handedness::Left).build().unwrap();
+        let mut mirrored = original.clone();
+        mirrored.transform(Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0)));
+
+        assert_eq!(mirrored.indices, [2, 1, 0]);
+        assert!(mirrored.vertex_normals[0].abs_diff_eq(Vec3::Z, 1e-6));
+
+        let mut recomputed = mirrored.clone();
+        recomputed.recompute_normals(Handedness::Left);
+        assert_eq!(recomputed.vertex_normals, mirrored.vertex_normals);
+    }
+}
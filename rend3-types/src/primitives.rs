@@ -0,0 +1,633 @@
+//! Generators of common shapes, for prototypes and debug geometry.
+//!
+//! Each shape is described by a struct with sensible defaults, whose
+//! `builder` function returns a [`MeshBuilder`] with positions, normals,
+//! tangents, uvs and indices filled in. Shapes are centered on the origin
+//! with +Y up, and look the same whatever the handedness: right handed
+//! meshes are mirrored along Z, so textures aren't mirrored.
+//!
+//! ```ignore
+//! let mesh = primitives::UvSphere {
+//!     radius: 0.5,
+//!     ..Default::default()
+//! }
+//! .builder(Handedness::Left)
+//! .build()?;
+//! ```
+//!
+//! Uvs go from 0 to 1 across every face of a box and around every surface
+//! of revolution, with u increasing to the right and v downwards when seen
+//! from outside.
+
+use std::{collections::HashMap, f32::consts::PI};
+
+use glam::{UVec2, Vec2, Vec3};
+
+use crate::{Handedness, MeshBuilder};
+
+/// Vertex and index buffers the shapes are written into, in left handed
+/// coordinates.
+#[derive(Default)]
+struct Geometry {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    tangents: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    indices: Vec<u32>,
+}
+
+impl Geometry {
+    fn vertex(&mut self, position: Vec3, normal: Vec3, tangent: Vec3, uv: Vec2) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.tangents.push(tangent);
+        self.uvs.push(uv);
+        self.positions.len() as u32 - 1
+    }
+
+    /// Adds a triangle, wound to face the side its vertex normals point to.
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let [pa, pb, pc] = [a, b, c].map(|idx| self.positions[idx as usize]);
+        let normal = self.normals[a as usize] + self.normals[b as usize] + self.normals[c as usize];
+        if (pb - pa).cross(pc - pa).dot(normal) >= 0.0 {
+            self.indices.extend_from_slice(&[a, b, c]);
+        } else {
+            self.indices.extend_from_slice(&[a, c, b]);
+        }
+    }
+
+    fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    /// Adds a rectangle facing `normal`, `right` and `up` being the
+    /// directions of its sides as seen from the front.
+    fn face(&mut self, center: Vec3, normal: Vec3, right: Vec3, up: Vec3, subdivisions: UVec2) {
+        let start = self.positions.len() as u32;
+        let columns = subdivisions.x.max(1);
+        let rows = subdivisions.y.max(1);
+        for row in 0..=rows {
+            let v = row as f32 / rows as f32;
+            for column in 0..=columns {
+                let u = column as f32 / columns as f32;
+                let position = center + right * (u * 2.0 - 1.0) + up * (1.0 - v * 2.0);
+                self.vertex(position, normal, right.normalize(), Vec2::new(u, v));
+            }
+        }
+        self.grid(start, columns, rows);
+    }
+
+    /// Adds the quads of `rows` by `columns` cells, whose corners were added
+    /// row by row from `start`.
+    fn grid(&mut self, start: u32, columns: u32, rows: u32) {
+        let stride = columns + 1;
+        for row in 0..rows {
+            for column in 0..columns {
+                let corner = start + row * stride + column;
+                self.quad(corner, corner + 1, corner + stride + 1, corner + stride);
+            }
+        }
+    }
+
+    /// Revolves `profile` around +Y, `sectors` times. Each point is a
+    /// distance from the axis, a height, a normal in the same plane and a v
+    /// coordinate.
+    fn lathe(&mut self, profile: &[ProfilePoint], sectors: u32) {
+        let start = self.positions.len() as u32;
+        let sectors = sectors.max(3);
+        for point in profile {
+            for sector in 0..=sectors {
+                let u = sector as f32 / sectors as f32;
+                let (sin, cos) = (u * 2.0 * PI).sin_cos();
+                let position = Vec3::new(point.radius * cos, point.height, point.radius * sin);
+                let normal = Vec3::new(point.normal.x * cos, point.normal.y, point.normal.x * sin).normalize_or_zero();
+                let tangent = Vec3::new(-sin, 0.0, cos);
+                self.vertex(position, normal, tangent, Vec2::new(u, point.v));
+            }
+        }
+        self.grid(start, sectors, profile.len() as u32 - 1);
+    }
+
+    /// Adds a disk of `radius` at `height`, facing up or down.
+    fn cap(&mut self, radius: f32, height: f32, facing_up: bool, sectors: u32) {
+        let sectors = sectors.max(3);
+        let (normal, v_sign) = match facing_up {
+            true => (Vec3::Y, -1.0),
+            false => (-Vec3::Y, 1.0),
+        };
+        let center = self.vertex(Vec3::new(0.0, height, 0.0), normal, Vec3::X, Vec2::splat(0.5));
+        let start = self.positions.len() as u32;
+        for sector in 0..=sectors {
+            let (sin, cos) = (sector as f32 / sectors as f32 * 2.0 * PI).sin_cos();
+            let uv = Vec2::new(0.5 + cos * 0.5, 0.5 + v_sign * sin * 0.5);
+            self.vertex(Vec3::new(radius * cos, height, radius * sin), normal, Vec3::X, uv);
+        }
+        for sector in 0..sectors {
+            self.triangle(center, start + sector, start + sector + 1);
+        }
+    }
+
+    fn builder(mut self, handedness: Handedness) -> MeshBuilder {
+        if handedness == Handedness::Right {
+            // Mirroring also turns the winding into the right handed one.
+            for vector in self
+                .positions
+                .iter_mut()
+                .chain(&mut self.normals)
+                .chain(&mut self.tangents)
+            {
+                vector.z = -vector.z;
+            }
+        }
+        MeshBuilder::new(self.positions, handedness)
+            .with_vertex_normals(self.normals)
+            .with_vertex_tangents(self.tangents)
+            .with_vertex_uv0(self.uvs)
+            .with_indices(self.indices)
+    }
+}
+
+struct ProfilePoint {
+    radius: f32,
+    height: f32,
+    /// Outwards and upwards components of the normal.
+    normal: Vec2,
+    v: f32,
+}
+
+/// Points of an arc of `radius` around `(0, height)`, from the angle `start`
+/// to `end` measured from +Y, with v going from `v_start` to `v_end`.
+fn arc(
+    radius: f32,
+    height: f32,
+    (start, end): (f32, f32),
+    segments: u32,
+    (v_start, v_end): (f32, f32),
+) -> Vec<ProfilePoint> {
+    (0..=segments)
+        .map(|segment| {
+            let t = segment as f32 / segments as f32;
+            let (sin, cos) = (start + (end - start) * t).sin_cos();
+            ProfilePoint {
+                radius: radius * sin,
+                height: height + radius * cos,
+                normal: Vec2::new(sin, cos),
+                v: v_start + (v_end - v_start) * t,
+            }
+        })
+        .collect()
+}
+
+/// Sphere made of rings of latitude and longitude, with uvs mapped like an
+/// equirectangular image.
+#[derive(Debug, Clone)]
+pub struct UvSphere {
+    pub radius: f32,
+    /// Slices around the vertical axis.
+    pub sectors: u32,
+    /// Rings from pole to pole.
+    pub stacks: u32,
+}
+
+impl Default for UvSphere {
+    fn default() -> Self {
+        Self {
+            radius: 1.0,
+            sectors: 32,
+            stacks: 16,
+        }
+    }
+}
+
+impl UvSphere {
+    pub fn builder(&self, handedness: Handedness) -> MeshBuilder {
+        let mut geometry = Geometry::default();
+        let profile = arc(self.radius, 0.0, (0.0, PI), self.stacks.max(2), (0.0, 1.0));
+        geometry.lathe(&profile, self.sectors);
+        geometry.builder(handedness)
+    }
+}
+
+/// Sphere made of a subdivided icosahedron, whose triangles are close to
+/// evenly sized, unlike those of a [`UvSphere`].
+#[derive(Debug, Clone)]
+pub struct Icosphere {
+    pub radius: f32,
+    /// Times each triangle is split in four, each one quadrupling the
+    /// triangle count.
+    pub subdivisions: u32,
+}
+
+impl Default for Icosphere {
+    fn default() -> Self {
+        Self {
+            radius: 1.0,
+            subdivisions: 3,
+        }
+    }
+}
+
+impl Icosphere {
+    pub fn builder(&self, handedness: Handedness) -> MeshBuilder {
+        let phi = (1.0 + 5.0_f32.sqrt()) / 2.0;
+        let mut directions: Vec<Vec3> = [
+            (-1.0, phi, 0.0),
+            (1.0, phi, 0.0),
+            (-1.0, -phi, 0.0),
+            (1.0, -phi, 0.0),
+            (0.0, -1.0, phi),
+            (0.0, 1.0, phi),
+            (0.0, -1.0, -phi),
+            (0.0, 1.0, -phi),
+            (phi, 0.0, -1.0),
+            (phi, 0.0, 1.0),
+            (-phi, 0.0, -1.0),
+            (-phi, 0.0, 1.0),
+        ]
+        .iter()
+        .map(|&(x, y, z)| Vec3::new(x, y, z).normalize())
+        .collect();
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        for _ in 0..self.subdivisions {
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    directions.push((directions[a as usize] + directions[b as usize]).normalize());
+                    directions.len() as u32 - 1
+                })
+            };
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        // Vertices are duplicated along the seam and at the poles, where
+        // each triangle needs its own u.
+        let mut geometry = Geometry::default();
+        let mut vertices = HashMap::new();
+        for triangle in triangles {
+            let mut uvs = triangle.map(|idx| sphere_uv(directions[idx as usize]));
+            let max_u = uvs.iter().map(|uv| uv.x).fold(0.0, f32::max);
+            for uv in &mut uvs {
+                if max_u - uv.x > 0.5 {
+                    uv.x += 1.0;
+                }
+            }
+            for corner in 0..3 {
+                if directions[triangle[corner] as usize].y.abs() > 0.9999 {
+                    uvs[corner].x = (uvs[(corner + 1) % 3].x + uvs[(corner + 2) % 3].x) / 2.0;
+                }
+            }
+
+            let [a, b, c] = [0, 1, 2].map(|corner| {
+                let idx = triangle[corner];
+                let uv = uvs[corner];
+                *vertices.entry((idx, uv.x.to_bits())).or_insert_with(|| {
+                    let direction = directions[idx as usize];
+                    let (sin, cos) = (uv.x * 2.0 * PI).sin_cos();
+                    geometry.vertex(direction * self.radius, direction, Vec3::new(-sin, 0.0, cos), uv)
+                })
+            });
+            geometry.triangle(a, b, c);
+        }
+        geometry.builder(handedness)
+    }
+}
+
+/// Equirectangular uv of a direction, matching [`UvSphere`].
+fn sphere_uv(direction: Vec3) -> Vec2 {
+    let mut longitude = direction.z.atan2(direction.x);
+    if longitude < 0.0 {
+        longitude += 2.0 * PI;
+    }
+    Vec2::new(longitude / (2.0 * PI), direction.y.clamp(-1.0, 1.0).acos() / PI)
+}
+
+/// Box with a separate set of vertices per face, so edges stay sharp.
+#[derive(Debug, Clone)]
+pub struct Cuboid {
+    pub half_extents: Vec3,
+}
+
+impl Default for Cuboid {
+    fn default() -> Self {
+        Self {
+            half_extents: Vec3::splat(0.5),
+        }
+    }
+}
+
+impl Cuboid {
+    pub fn builder(&self, handedness: Handedness) -> MeshBuilder {
+        let mut geometry = Geometry::default();
+        let half = self.half_extents;
+        let faces = [
+            (Vec3::X, Vec3::Y),
+            (-Vec3::X, Vec3::Y),
+            (Vec3::Y, Vec3::Z),
+            (-Vec3::Y, -Vec3::Z),
+            (Vec3::Z, Vec3::Y),
+            (-Vec3::Z, Vec3::Y),
+        ];
+        for (normal, up) in faces {
+            // Seen from the front, so looking along -normal.
+            let right = up.cross(-normal);
+            geometry.face(normal * half, normal, right * half, up * half, UVec2::ONE);
+        }
+        geometry.builder(handedness)
+    }
+}
+
+/// Rectangle in the XZ plane facing +Y, split into a grid of quads.
+#[derive(Debug, Clone)]
+pub struct Plane {
+    /// Extent along X and Z.
+    pub size: Vec2,
+    /// Quads along X and Z.
+    pub subdivisions: UVec2,
+}
+
+impl Default for Plane {
+    fn default() -> Self {
+        Self {
+            size: Vec2::ONE,
+            subdivisions: UVec2::ONE,
+        }
+    }
+}
+
+impl Plane {
+    pub fn builder(&self, handedness: Handedness) -> MeshBuilder {
+        let mut geometry = Geometry::default();
+        geometry.face(
+            Vec3::ZERO,
+            Vec3::Y,
+            Vec3::X * self.size.x / 2.0,
+            Vec3::Z * self.size.y / 2.0,
+            self.subdivisions,
+        );
+        geometry.builder(handedness)
+    }
+}
+
+/// Cylinder along Y, closed by flat caps.
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    pub radius: f32,
+    pub height: f32,
+    /// Slices around the vertical axis.
+    pub sectors: u32,
+}
+
+impl Default for Cylinder {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            height: 1.0,
+            sectors: 32,
+        }
+    }
+}
+
+impl Cylinder {
+    pub fn builder(&self, handedness: Handedness) -> MeshBuilder {
+        let mut geometry = Geometry::default();
+        let half = self.height / 2.0;
+        let side = |height, v| ProfilePoint {
+            radius: self.radius,
+            height,
+            normal: Vec2::X,
+            v,
+        };
+        geometry.lathe(&[side(half, 0.0), side(-half, 1.0)], self.sectors);
+        geometry.cap(self.radius, half, true, self.sectors);
+        geometry.cap(self.radius, -half, false, self.sectors);
+        geometry.builder(handedness)
+    }
+}
+
+/// Cylinder along Y with hemispheres as caps, as used for character
+/// colliders.
+#[derive(Debug, Clone)]
+pub struct Capsule {
+    pub radius: f32,
+    /// Height of the cylinder between the centers of the hemispheres.
+    pub height: f32,
+    /// Slices around the vertical axis.
+    pub sectors: u32,
+    /// Rings of each hemisphere.
+    pub rings: u32,
+}
+
+impl Default for Capsule {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            height: 1.0,
+            sectors: 32,
+            rings: 8,
+        }
+    }
+}
+
+impl Capsule {
+    pub fn builder(&self, handedness: Handedness) -> MeshBuilder {
+        let mut geometry = Geometry::default();
+        let half = self.height / 2.0;
+        let rings = self.rings.max(1);
+        // v follows the length of the profile.
+        let cap_length = self.radius * PI / 2.0;
+        let total = cap_length * 2.0 + self.height;
+        let (v_top, v_bottom) = (cap_length / total, (cap_length + self.height) / total);
+
+        let mut profile = arc(self.radius, half, (0.0, PI / 2.0), rings, (0.0, v_top));
+        profile.extend(arc(self.radius, -half, (PI / 2.0, PI), rings, (v_bottom, 1.0)));
+        geometry.lathe(&profile, self.sectors);
+        geometry.builder(handedness)
+    }
+}
+
+/// Cone along Y with its tip up, closed by a flat base.
+#[derive(Debug, Clone)]
+pub struct Cone {
+    pub radius: f32,
+    pub height: f32,
+    /// Slices around the vertical axis.
+    pub sectors: u32,
+}
+
+impl Default for Cone {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            height: 1.0,
+            sectors: 32,
+        }
+    }
+}
+
+impl Cone {
+    pub fn builder(&self, handedness: Handedness) -> MeshBuilder {
+        let mut geometry = Geometry::default();
+        let half = self.height / 2.0;
+        let normal = Vec2::new(self.height, self.radius).normalize();
+        geometry.lathe(
+            &[
+                ProfilePoint {
+                    radius: 0.0,
+                    height: half,
+                    normal,
+                    v: 0.0,
+                },
+                ProfilePoint {
+                    radius: self.radius,
+                    height: -half,
+                    normal,
+                    v: 1.0,
+                },
+            ],
+            self.sectors,
+        );
+        geometry.cap(self.radius, -half, false, self.sectors);
+        geometry.builder(handedness)
+    }
+}
+
+/// Ring around Y made of a circle revolved around the axis.
+#[derive(Debug, Clone)]
+pub struct Torus {
+    /// Distance from the center to the middle of the ring.
+    pub major_radius: f32,
+    /// Radius of the ring itself.
+    pub minor_radius: f32,
+    /// Slices around the vertical axis.
+    pub major_segments: u32,
+    /// Segments around the ring.
+    pub minor_segments: u32,
+}
+
+impl Default for Torus {
+    fn default() -> Self {
+        Self {
+            major_radius: 0.5,
+            minor_radius: 0.25,
+            major_segments: 32,
+            minor_segments: 16,
+        }
+    }
+}
+
+impl Torus {
+    pub fn builder(&self, handedness: Handedness) -> MeshBuilder {
+        let mut geometry = Geometry::default();
+        let segments = self.minor_segments.max(3);
+        // Starts at the top of the ring, going outwards first.
+        let profile: Vec<ProfilePoint> = (0..=segments)
+            .map(|segment| {
+                let v = segment as f32 / segments as f32;
+                let (sin, cos) = (v * 2.0 * PI).sin_cos();
+                ProfilePoint {
+                    radius: self.major_radius + self.minor_radius * sin,
+                    height: self.minor_radius * cos,
+                    normal: Vec2::new(sin, cos),
+                    v,
+                }
+            })
+            .collect();
+        geometry.lathe(&profile, self.major_segments);
+        geometry.builder(handedness)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{UVec2, Vec3};
+
+    use super::{Capsule, Cone, Cuboid, Cylinder, Icosphere, Plane, Torus, UvSphere};
+    use crate::{Handedness, Mesh, MeshBuilder};
+
+    fn shapes(handedness: Handedness) -> Vec<(&'static str, MeshBuilder)> {
+        vec![
+            ("uv sphere", UvSphere::default().builder(handedness)),
+            ("icosphere", Icosphere::default().builder(handedness)),
+            ("cuboid", Cuboid::default().builder(handedness)),
+            (
+                "plane",
+                Plane {
+                    subdivisions: UVec2::new(3, 2),
+                    ..Plane::default()
+                }
+                .builder(handedness),
+            ),
+            ("cylinder", Cylinder::default().builder(handedness)),
+            ("capsule", Capsule::default().builder(handedness)),
+            ("cone", Cone::default().builder(handedness)),
+            ("torus", Torus::default().builder(handedness)),
+        ]
+    }
+
+    /// Whether the faces of `mesh` wind the way rend3 expects for their
+    /// normals, the way the normals would be computed.
+    fn faces_follow_normals(mesh: &Mesh, handedness: Handedness) -> bool {
+        mesh.indices.chunks_exact(3).all(|tri| {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|idx| mesh.vertex_positions[idx as usize]);
+            let face = match handedness {
+                Handedness::Left => (b - a).cross(c - a),
+                Handedness::Right => (c - a).cross(b - a),
+            };
+            let normal: Vec3 = tri
+                .iter()
+                .map(|&idx| mesh.vertex_normals[idx as usize])
+                .fold(Vec3::ZERO, |sum, n| sum + n);
+            face.dot(normal) >= -1e-6
+        })
+    }
+
+    #[test]
+    fn shapes_are_valid_and_face_outwards() {
+        for handedness in [Handedness::Left, Handedness::Right] {
+            for (name, builder) in shapes(handedness) {
+                let mesh = builder.build().unwrap_or_else(|e| panic!("{}: {}", name, e));
+                assert!(faces_follow_normals(&mesh, handedness), "{} {:?}", name, handedness);
+                for (normal, tangent) in mesh.vertex_normals.iter().zip(&mesh.vertex_tangents) {
+                    assert!((normal.length() - 1.0).abs() < 1e-4, "{}", name);
+                    assert!(normal.dot(*tangent).abs() < 1e-4, "{}", name);
+                }
+            }
+        }
+
+        // Closed convex shapes face away from their center.
+        let mesh = Icosphere::default().builder(Handedness::Left).build().unwrap();
+        for tri in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|idx| mesh.vertex_positions[idx as usize]);
+            assert!((b - a).cross(c - a).dot(a + b + c) > 0.0);
+        }
+    }
+}
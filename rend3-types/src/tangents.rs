@@ -0,0 +1,239 @@
+//! Tangent generation following the approach of MikkTSpace, which is what
+//! normal maps are baked against by most tools and what glTF asks for when a
+//! file has no tangents.
+//!
+//! Compared to averaging the raw tangent of every face touching a vertex:
+//! - each face contributes its tangent projected onto the plane of the
+//!   vertex normal, weighted by the angle of its corner, so splitting a face
+//!   in two doesn't change the result;
+//! - faces whose uvs are degenerate don't contribute, instead of poisoning
+//!   their vertices with infinities;
+//! - vertices shared by faces whose uvs are mirrored relative to each other
+//!   are split, as averaging tangents pointing in opposite directions leaves
+//!   close to nothing, which shows up as faceted or inverted shading along
+//!   mirror seams.
+//!
+//! rend3 derives the bitangent as the cross product of the normal and the
+//! tangent, so there is no sign to store: mirrored faces keep the direction
+//! of their tangent along u.
+
+use glam::{Vec2, Vec3};
+
+use crate::Mesh;
+
+/// Faces whose uv area, in uv units squared, is below this have no usable
+/// tangent space.
+const DEGENERATE_UV_AREA: f32 = 1e-12;
+
+struct FaceTangent {
+    /// Direction of increasing u, not normalized.
+    tangent: Vec3,
+    /// Whether the uvs are mirrored, their bitangent pointing away from
+    /// the cross product of the normal and the tangent.
+    mirrored: bool,
+}
+
+fn face_tangent(positions: [Vec3; 3], uvs: [Vec2; 3], normal: Vec3) -> Option<FaceTangent> {
+    let edge1 = positions[1] - positions[0];
+    let edge2 = positions[2] - positions[0];
+    let uv1 = uvs[1] - uvs[0];
+    let uv2 = uvs[2] - uvs[0];
+
+    let det = uv1.x * uv2.y - uv1.y * uv2.x;
+    if det.abs() < DEGENERATE_UV_AREA {
+        return None;
+    }
+    let tangent = (edge1 * uv2.y - edge2 * uv1.y) / det;
+    let bitangent = (edge2 * uv1.x - edge1 * uv2.x) / det;
+    if !tangent.is_finite() || tangent.length_squared() == 0.0 {
+        return None;
+    }
+    Some(FaceTangent {
+        tangent,
+        mirrored: normal.cross(tangent).dot(bitangent) < 0.0,
+    })
+}
+
+/// Angle of the corner of a triangle at `corner`, between the edges to
+/// `a` and `b`.
+fn corner_angle(corner: Vec3, a: Vec3, b: Vec3) -> f32 {
+    let (to_a, to_b) = ((a - corner).normalize_or_zero(), (b - corner).normalize_or_zero());
+    to_a.dot(to_b).clamp(-1.0, 1.0).acos()
+}
+
+/// Some unit vector perpendicular to `normal`, for vertices without any
+/// usable face.
+fn any_perpendicular(normal: Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 { Vec3::X } else { Vec3::Y };
+    (axis - normal * normal.dot(axis)).normalize_or_zero()
+}
+
+impl Mesh {
+    /// Generates tangents from the normals and the first uv set, the way
+    /// MikkTSpace does, so normal maps baked by other tools shade the same.
+    ///
+    /// Vertices shared by faces whose uvs are mirrored relative to each
+    /// other are duplicated, which adds vertices and rewrites indices.
+    /// Normals must already be filled in.
+    ///
+    /// # Panics
+    ///
+    /// If the mesh doesn't pass [`Mesh::validate`].
+    pub fn generate_tangents(&mut self) {
+        self.validate().expect("generating tangents of an invalid mesh");
+
+        let original_count = self.vertex_positions.len();
+        // Vertex used by the faces of each orientation touching a vertex,
+        // the first orientation seen keeping the original vertex.
+        let mut split: Vec<[Option<u32>; 2]> = vec![[None; 2]; original_count];
+        let mut accumulated = vec![Vec3::ZERO; original_count];
+        // Faces without a tangent space go last, joining whichever vertex
+        // their corners ended up with.
+        let mut degenerate = Vec::new();
+
+        for face in 0..self.indices.len() / 3 {
+            let corners = [0, 1, 2].map(|corner| self.indices[face * 3 + corner] as usize);
+            let positions = corners.map(|idx| self.vertex_positions[idx]);
+            let uvs = corners.map(|idx| self.vertex_uv0[idx]);
+            let normal = corners
+                .iter()
+                .map(|&idx| self.vertex_normals[idx])
+                .fold(Vec3::ZERO, |sum, n| sum + n);
+
+            let face_tangent = match face_tangent(positions, uvs, normal) {
+                Some(face_tangent) => face_tangent,
+                None => {
+                    degenerate.push(face);
+                    continue;
+                }
+            };
+
+            let orientation = face_tangent.mirrored as usize;
+            for corner in 0..3 {
+                let original = corners[corner];
+                let vertex = match split[original][orientation] {
+                    Some(vertex) => vertex,
+                    None if split[original] == [None, None] => {
+                        split[original][orientation] = Some(original as u32);
+                        original as u32
+                    }
+                    None => {
+                        let vertex = self.duplicate_vertex(original);
+                        split[original][orientation] = Some(vertex);
+                        accumulated.push(Vec3::ZERO);
+                        vertex
+                    }
+                };
+                self.indices[face * 3 + corner] = vertex;
+
+                let vertex_normal = self.vertex_normals[original];
+                let projected = (face_tangent.tangent - vertex_normal * vertex_normal.dot(face_tangent.tangent))
+                    .normalize_or_zero();
+                let angle = corner_angle(
+                    positions[corner],
+                    positions[(corner + 1) % 3],
+                    positions[(corner + 2) % 3],
+                );
+                accumulated[vertex as usize] += projected * angle;
+            }
+        }
+
+        for face in degenerate {
+            for corner in 0..3 {
+                let original = self.indices[face * 3 + corner] as usize;
+                if let [Some(vertex), _] | [None, Some(vertex)] = split[original] {
+                    self.indices[face * 3 + corner] = vertex;
+                }
+            }
+        }
+
+        for (tangent, (&normal, sum)) in self
+            .vertex_tangents
+            .iter_mut()
+            .zip(self.vertex_normals.iter().zip(accumulated))
+        {
+            let orthogonal = (sum - normal * normal.dot(sum)).normalize_or_zero();
+            *tangent = if orthogonal == Vec3::ZERO {
+                any_perpendicular(normal)
+            } else {
+                orthogonal
+            };
+        }
+    }
+
+    /// Appends a copy of every attribute of vertex `idx`, returning the
+    /// index of the copy.
+    fn duplicate_vertex(&mut self, idx: usize) -> u32 {
+        self.vertex_positions.push(self.vertex_positions[idx]);
+        self.vertex_normals.push(self.vertex_normals[idx]);
+        self.vertex_tangents.push(self.vertex_tangents[idx]);
+        self.vertex_uv0.push(self.vertex_uv0[idx]);
+        self.vertex_uv1.push(self.vertex_uv1[idx]);
+        self.vertex_colors.push(self.vertex_colors[idx]);
+        self.vertex_joint_indices.push(self.vertex_joint_indices[idx]);
+        self.vertex_joint_weights.push(self.vertex_joint_weights[idx]);
+        self.vertex_positions.len() as u32 - 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Vec2, Vec3};
+
+    use crate::{Handedness, MeshBuilder};
+
+    /// Two quads in the XY plane sharing the edge at x = 0, the uvs of the
+    /// left one mirrored.
+    fn mirrored_quads() -> MeshBuilder {
+        let positions = vec![
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+        ];
+        let uvs = vec![
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+        ];
+        MeshBuilder::new(positions, Handedness::Left)
+            .with_vertex_normals(vec![-Vec3::Z; 6])
+            .with_vertex_uv0(uvs)
+            .with_indices(vec![0, 3, 4, 0, 4, 1, 1, 4, 5, 1, 5, 2])
+    }
+
+    #[test]
+    fn mirrored_seams_are_split() {
+        let mesh = mirrored_quads().build().unwrap();
+        // The two vertices on the seam are duplicated.
+        assert_eq!(mesh.vertex_positions.len(), 8);
+        for (face, expected) in mesh.indices.chunks_exact(3).zip([-Vec3::X, -Vec3::X, Vec3::X, Vec3::X]) {
+            for &idx in face {
+                assert!(mesh.vertex_tangents[idx as usize].abs_diff_eq(expected, 1e-5));
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_uvs_are_ignored() {
+        let positions = vec![Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::new(1.0, 1.0, 0.0)];
+        let uvs = vec![Vec2::ZERO, Vec2::X, Vec2::Y, Vec2::Y];
+        let mesh = MeshBuilder::new(positions, Handedness::Left)
+            .with_vertex_normals(vec![-Vec3::Z; 4])
+            .with_vertex_uv0(uvs)
+            .with_indices(vec![0, 2, 1, 1, 2, 3])
+            .build()
+            .unwrap();
+        for (tangent, normal) in mesh.vertex_tangents.iter().zip(&mesh.vertex_normals) {
+            assert!(tangent.is_finite());
+            assert!((tangent.length() - 1.0).abs() < 1e-5);
+            assert!(tangent.dot(*normal).abs() < 1e-5);
+        }
+        assert!(mesh.vertex_tangents[0].abs_diff_eq(Vec3::X, 1e-5));
+    }
+}
@@ -1,55 +1,46 @@
 // This compute shader is used to copy one portion of the allocated vertex
 // megabuffer to another non-overlapping region of the same buffer.
 
-// The arrays are tightly packed, so we can't use types like vec3 which don't
-// have the right alignment. Instead, we manually create these structs to ensure
-// arrays will get the right stride.
-struct Vec4 { x: u32; y: u32; z: u32; w: u32; };
-struct Vec3 { x: u32; y: u32; z: u32; };
-struct Vec2 { x: u32; y: u32; };
-struct Color { rgba: u32; };
-
-struct Vec4Array { d: [[stride(16)]] array<Vec4>; };
-
-struct Vec3Array { d: [[stride(12)]] array<Vec3>; };
-
-struct Vec2Array { d: [[stride(8)]] array<Vec2>; };
-
-struct ColorArray { d: [[stride(4)]] array<Color>; };
+// Vertex formats change with the quantization of the renderer, so vertices
+// are copied as a number of words given for each buffer.
+struct Words { d: [[stride(4)]] array<u32>; };
 
 struct BufferCopierParams {
     src_offset: u32;
     dst_offset: u32;
     count: u32;
+    // Words per vertex of each buffer. Attributes the renderer was created
+    // without have a default value per instance instead of one per vertex,
+    // and zero words.
+    words: [[stride(4)]] array<u32, 8>;
 };
 
 [[group(0), binding(0)]]
-var<storage, read_write> positions: Vec3Array;
+var<storage, read_write> positions: Words;
 
 [[group(0), binding(1)]]
-var<storage, read_write> normals: Vec3Array;
+var<storage, read_write> normals: Words;
 
 [[group(0), binding(2)]]
-var<storage, read_write> tangents: Vec3Array;
+var<storage, read_write> tangents: Words;
 
 [[group(0), binding(3)]]
-var<storage, read_write> uv0s: Vec2Array;
+var<storage, read_write> uv0s: Words;
 
 [[group(0), binding(4)]]
-var<storage, read_write> uv1s: Vec2Array;
+var<storage, read_write> uv1s: Words;
 
 [[group(0), binding(5)]]
-var<storage, read_write> colors: ColorArray;
+var<storage, read_write> colors: Words;
 
 [[group(0), binding(6)]]
-/// It's a vec4<u16>, so we can treat it as a vec2<u32> for the copy
-var<storage, read_write> joint_indices: Vec2Array;
+var<storage, read_write> joint_indices: Words;
 
 [[group(0), binding(7)]]
-var<storage, read_write> joint_weights: Vec4Array;
+var<storage, read_write> joint_weights: Words;
 
 [[group(0), binding(8)]]
-var<uniform> params: BufferCopierParams;
+var<storage, read> params: BufferCopierParams;
 
 [[stage(compute), workgroup_size(256)]]
 fn main([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
@@ -59,12 +50,39 @@ fn main([[builtin(global_invocation_id)]] global_id: vec3<u32>) {
         return;
     }
 
-    positions.d     [i + params.dst_offset] = positions.d     [i + params.src_offset];
-    normals.d       [i + params.dst_offset] = normals.d       [i + params.src_offset];
-    tangents.d      [i + params.dst_offset] = tangents.d      [i + params.src_offset];
-    uv0s.d          [i + params.dst_offset] = uv0s.d          [i + params.src_offset];
-    uv1s.d          [i + params.dst_offset] = uv1s.d          [i + params.src_offset];
-    colors.d        [i + params.dst_offset] = colors.d        [i + params.src_offset];
-    joint_indices.d [i + params.dst_offset] = joint_indices.d [i + params.src_offset];
-    joint_weights.d [i + params.dst_offset] = joint_weights.d [i + params.src_offset];
-}
\ No newline at end of file
+    let src = i + params.src_offset;
+    let dst = i + params.dst_offset;
+
+    var w = params.words[0];
+    for (var j = 0u; j < w; j = j + 1u) {
+        positions.d[dst * w + j] = positions.d[src * w + j];
+    }
+    w = params.words[1];
+    for (var j = 0u; j < w; j = j + 1u) {
+        normals.d[dst * w + j] = normals.d[src * w + j];
+    }
+    w = params.words[2];
+    for (var j = 0u; j < w; j = j + 1u) {
+        tangents.d[dst * w + j] = tangents.d[src * w + j];
+    }
+    w = params.words[3];
+    for (var j = 0u; j < w; j = j + 1u) {
+        uv0s.d[dst * w + j] = uv0s.d[src * w + j];
+    }
+    w = params.words[4];
+    for (var j = 0u; j < w; j = j + 1u) {
+        uv1s.d[dst * w + j] = uv1s.d[src * w + j];
+    }
+    w = params.words[5];
+    for (var j = 0u; j < w; j = j + 1u) {
+        colors.d[dst * w + j] = colors.d[src * w + j];
+    }
+    w = params.words[6];
+    for (var j = 0u; j < w; j = j + 1u) {
+        joint_indices.d[dst * w + j] = joint_indices.d[src * w + j];
+    }
+    w = params.words[7];
+    for (var j = 0u; j < w; j = j + 1u) {
+        joint_weights.d[dst * w + j] = joint_weights.d[src * w + j];
+    }
+}
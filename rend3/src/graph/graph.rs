@@ -8,7 +8,7 @@ use std::{
 
 use wgpu::{
     CommandBuffer, CommandEncoder, CommandEncoderDescriptor, LoadOp, Operations, RenderPass, RenderPassColorAttachment,
-    RenderPassDepthStencilAttachment, RenderPassDescriptor, TextureView, TextureViewDescriptor,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor, SurfaceError, TextureView, TextureViewDescriptor,
 };
 
 use crate::{
@@ -20,6 +20,7 @@ use crate::{
     managers::{CameraManager, TextureManagerReadyOutput},
     util::{
         output::OutputFrame,
+        timings::Stopwatch,
         typedefs::{FastHashMap, FastHashSet, RendererStatistics, SsoString},
     },
     Renderer,
@@ -96,6 +97,7 @@ impl<'node> RenderGraph<'node> {
     ) -> Option<RendererStatistics> {
         profiling::scope!("RenderGraph::execute");
 
+        let mut watch = Stopwatch::start();
         let mut awaiting_inputs = FastHashSet::default();
         // The surface is used externally
         awaiting_inputs.insert(GraphResource::OutputTexture);
@@ -260,14 +262,16 @@ impl<'node> RenderGraph<'node> {
 
         profiling::scope!("Run Nodes");
 
+        let graph_setup = watch.lap();
+
         let shadow_views = data_core.directional_light_manager.get_layer_views();
 
         let output_cell = UnsafeCell::new(output);
-        let encoder_cell = UnsafeCell::new(
-            renderer
-                .device
-                .create_command_encoder(&CommandEncoderDescriptor::default()),
-        );
+        let debug_markers = data_core.profiler.enable_debug_marker;
+
+        let encoder_cell = UnsafeCell::new(renderer.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("rendergraph"),
+        }));
         let rpass_temps_cell = UnsafeCell::new(RpassTemporaryPool::new());
 
         let mut next_rpass_idx = 0;
@@ -289,9 +293,9 @@ impl<'node> RenderGraph<'node> {
                         // SAFETY: There are two things which borrow this encoder: the renderpass and the node's
                         // encoder reference. Both of these have died by this point.
                         unsafe { &mut *encoder_cell.get() },
-                        renderer
-                            .device
-                            .create_command_encoder(&CommandEncoderDescriptor::default()),
+                        renderer.device.create_command_encoder(&CommandEncoderDescriptor {
+                            label: Some("rendergraph after acquire"),
+                        }),
                     )
                     .finish(),
                 );
@@ -299,9 +303,20 @@ impl<'node> RenderGraph<'node> {
                 // Early submit before acquire
                 renderer.queue.submit(cmd_bufs.drain(..));
 
-                // TODO: error
                 // SAFETY: Same context as the above unsafe.
-                unsafe { &mut *output_cell.get() }.acquire().unwrap();
+                let output = unsafe { &mut *output_cell.get() };
+                match output.acquire() {
+                    Ok(()) if output.is_suboptimal() => {
+                        *renderer.surface_error.lock() = Some(SurfaceError::Outdated);
+                    }
+                    Ok(()) => {}
+                    Err(e) => {
+                        // Nothing after this point can run without the output, so skip the rest of the frame.
+                        log::warn!("Failed to acquire the surface, skipping frame: {}", e);
+                        *renderer.surface_error.lock() = Some(e);
+                        break;
+                    }
+                }
             }
 
             if !compatible[idx] {
@@ -312,6 +327,9 @@ impl<'node> RenderGraph<'node> {
                 if let Some(ref desc) = node.rpass {
                     rpass = Some(Self::create_rpass_from_desc(
                         desc,
+                        // Passes are named after their first node, the rest show up as debug groups inside them.
+                        // SAFETY: The temporaries are only cleared once the renderpass has died.
+                        debug_markers.then(|| &**unsafe { &*rpass_temps_cell.get() }.add(node.label.clone())),
                         // SAFETY: There are two things which borrow this encoder: the renderpass and the node's
                         // encoder reference. Both of these have died by this point.
                         unsafe { &mut *encoder_cell.get() },
@@ -344,6 +362,9 @@ impl<'node> RenderGraph<'node> {
                     object_manager: &data_core.object_manager,
                     d2_texture_manager: &data_core.d2_texture_manager,
                     d2c_texture_manager: &data_core.d2c_texture_manager,
+                    d3_texture_manager: &data_core.d3_texture_manager,
+
+                    debug_markers,
                 };
 
                 let mut encoder_or_rpass = match rpass {
@@ -401,6 +422,13 @@ impl<'node> RenderGraph<'node> {
         cmd_bufs.push(resolve_encoder.finish());
 
         renderer.queue.submit(cmd_bufs);
+        renderer.visibility.finish_frame();
+
+        {
+            let mut timings = renderer.cpu_timings.lock();
+            timings.graph_setup = graph_setup;
+            timings.graph_encoding = watch.lap();
+        }
 
         // SAFETY: this is safe as we've dropped all renderpasses that possibly borrowed
         // it
@@ -413,6 +441,7 @@ impl<'node> RenderGraph<'node> {
     #[allow(clippy::too_many_arguments)]
     fn create_rpass_from_desc<'rpass>(
         desc: &RenderPassTargets,
+        label: Option<&'rpass str>,
         encoder: &'rpass mut CommandEncoder,
         node_idx: usize,
         pass_end_idx: usize,
@@ -514,7 +543,7 @@ impl<'node> RenderGraph<'node> {
         //     1.0,
         // );
         encoder.begin_render_pass(&RenderPassDescriptor {
-            label: None,
+            label,
             color_attachments: &color_attachments,
             depth_stencil_attachment,
         })
@@ -60,6 +60,11 @@ pub struct RenderGraphDataStore<'a> {
     pub object_manager: &'a ObjectManager,
     pub d2_texture_manager: &'a TextureManager,
     pub d2c_texture_manager: &'a TextureManager,
+    pub d3_texture_manager: &'a TextureManager,
+
+    /// Whether to emit debug groups and markers, see
+    /// [`Renderer::set_debug_markers`](crate::Renderer::set_debug_markers).
+    pub debug_markers: bool,
 }
 
 impl<'a> RenderGraphDataStore<'a> {
@@ -1,16 +1,18 @@
 use crate::{
     managers::{MaterialManager, ObjectManager, TextureManager},
     types::{Camera, DirectionalLight, DirectionalLightChange, DirectionalLightHandle, Mesh, Object, RawObjectHandle},
+    util::cells::CellGraph,
     RendererProfile,
 };
 use glam::Mat4;
 use parking_lot::Mutex;
 use rend3_types::{
-    MaterialHandle, MeshHandle, ObjectChange, ObjectHandle, RawDirectionalLightHandle, RawSkeletonHandle, Skeleton,
-    SkeletonHandle, TextureHandle,
+    MaterialHandle, MeshHandle, ObjectChange, ObjectDepthMode, ObjectFade, ObjectGroup, ObjectGroupHandle,
+    ObjectHandle, ObjectShadowMode, RawDirectionalLightHandle, RawObjectGroupHandle, RawSkeletonHandle, ShadowSettings,
+    Skeleton, SkeletonHandle, TextureHandle,
 };
-use std::{mem, panic::Location};
-use wgpu::{CommandBuffer, Device, Texture, TextureDescriptor, TextureView};
+use std::{mem, panic::Location, sync::Arc};
+use wgpu::{CommandBuffer, Device, Queue, Texture, TextureDescriptor, TextureView, TextureViewDimension};
 
 pub struct Instruction {
     pub kind: InstructionKind,
@@ -30,16 +32,22 @@ pub enum InstructionKind {
     AddTexture {
         handle: TextureHandle,
         desc: TextureDescriptor<'static>,
-        texture: Texture,
+        texture: Arc<Texture>,
         view: TextureView,
         buffer: Option<CommandBuffer>,
-        cube: bool,
+        dimension: TextureViewDimension,
     },
     AddMaterial {
         handle: MaterialHandle,
         fill_invoke: Box<
-            dyn FnOnce(&mut MaterialManager, &Device, RendererProfile, &mut TextureManager, &MaterialHandle)
-                + Send
+            dyn FnOnce(
+                    &mut MaterialManager,
+                    &Device,
+                    RendererProfile,
+                    &mut TextureManager,
+                    &mut TextureManager,
+                    &MaterialHandle,
+                ) + Send
                 + Sync,
         >,
     },
@@ -51,6 +59,23 @@ pub enum InstructionKind {
                     &Device,
                     RendererProfile,
                     &mut TextureManager,
+                    &mut TextureManager,
+                    &mut ObjectManager,
+                    &MaterialHandle,
+                ) + Send
+                + Sync,
+        >,
+    },
+    ModifyMaterial {
+        handle: MaterialHandle,
+        modify_invoke: Box<
+            dyn FnOnce(
+                    &mut MaterialManager,
+                    &Device,
+                    &Queue,
+                    RendererProfile,
+                    &mut TextureManager,
+                    &mut TextureManager,
                     &mut ObjectManager,
                     &MaterialHandle,
                 ) + Send
@@ -65,6 +90,49 @@ pub enum InstructionKind {
         handle: RawObjectHandle,
         transform: Mat4,
     },
+    SetObjectTextureLayer {
+        handle: RawObjectHandle,
+        layer: u32,
+    },
+    SetObjectDepthMode {
+        handle: RawObjectHandle,
+        depth_mode: ObjectDepthMode,
+    },
+    SetObjectSortKey {
+        handle: RawObjectHandle,
+        sort_key: i32,
+    },
+    SetObjectShadowMode {
+        handle: RawObjectHandle,
+        shadow_mode: ObjectShadowMode,
+    },
+    SetObjectFade {
+        handle: RawObjectHandle,
+        fade: ObjectFade,
+    },
+    SetObjectLabel {
+        handle: RawObjectHandle,
+        label: Option<String>,
+    },
+    AddObjectGroup {
+        handle: ObjectGroupHandle,
+        group: ObjectGroup,
+    },
+    SetObjectGroup {
+        handle: ObjectHandle,
+        group: Option<RawObjectGroupHandle>,
+    },
+    SetObjectGroupTransform {
+        handle: RawObjectGroupHandle,
+        transform: Mat4,
+    },
+    SetObjectGroupVisible {
+        handle: RawObjectGroupHandle,
+        visible: bool,
+    },
+    SetCellGraph {
+        cells: Option<CellGraph>,
+    },
     SetSkeletonJointDeltas {
         handle: RawSkeletonHandle,
         joint_matrices: Vec<Mat4>,
@@ -77,6 +145,16 @@ pub enum InstructionKind {
         handle: RawDirectionalLightHandle,
         change: DirectionalLightChange,
     },
+    SetDirectionalLightLabel {
+        handle: RawDirectionalLightHandle,
+        label: Option<String>,
+    },
+    SetShadowSettings {
+        settings: ShadowSettings,
+    },
+    SetDeletionBudget {
+        budget: Option<usize>,
+    },
     SetAspectRatio {
         ratio: f32,
     },
@@ -90,6 +168,13 @@ pub enum InstructionKind {
     },
 }
 
+/// Instructions recorded by any thread, and the ones being applied.
+///
+/// Producers only ever hold the producer lock for a single push, and
+/// [`InstructionStreamPair::swap`] takes it just long enough to swap the
+/// vectors, so worker threads recording instructions never wait on a frame
+/// being readied. Instructions recorded during the swap land in the next
+/// frame, in the order each thread recorded them.
 pub struct InstructionStreamPair {
     pub producer: Mutex<Vec<Instruction>>,
     pub consumer: Mutex<Vec<Instruction>>,
@@ -113,3 +198,45 @@ impl InstructionStreamPair {
         self.producer.lock().push(Instruction { kind, location })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{panic::Location, sync::Arc, thread};
+
+    use super::{InstructionKind, InstructionStreamPair};
+
+    #[test]
+    fn pushes_from_threads_keep_their_order() {
+        let stream = Arc::new(InstructionStreamPair::new());
+        let threads: Vec<_> = (0..4)
+            .map(|thread| {
+                let stream = Arc::clone(&stream);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        let ratio = (thread * 1000 + i) as f32;
+                        stream.push(InstructionKind::SetAspectRatio { ratio }, *Location::caller());
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        stream.swap();
+        let consumer = stream.consumer.lock();
+        assert_eq!(consumer.len(), 400);
+        assert!(stream.producer.lock().is_empty());
+
+        let mut last = [-1.0_f32; 4];
+        for instruction in consumer.iter() {
+            let ratio = match instruction.kind {
+                InstructionKind::SetAspectRatio { ratio } => ratio,
+                _ => unreachable!(),
+            };
+            let thread = (ratio / 1000.0) as usize;
+            assert!(ratio > last[thread]);
+            last[thread] = ratio;
+        }
+    }
+}
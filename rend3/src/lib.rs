@@ -42,6 +42,8 @@
 //! - `rend3-framework`: Vastly simplifies correct handling of the window and
 //!   surface across platforms.
 //! - `rend3-gltf`: Modular gltf file and scene loader.
+//! - `rend3-sync`: Mirrors of objects keyed by ECS entities, sending only what
+//!   changed each frame.
 //!
 //! ### Integration
 //!
@@ -54,6 +56,8 @@
 //!   immediate mode gui.
 //! - `rend3-imgui`: Integration with the [imgui](https://github.com/ocornut/imgui)
 //!   immediate mode gui.
+//! - `rend3-rapier-debug`: Debug rendering of [rapier](https://rapier.rs) physics
+//!   worlds.
 //!
 //! # Features and Platform Support
 //!
@@ -69,6 +73,7 @@
 //! |:----------|----------------|----------------|------------------|---------------------|
 //! | GpuDriven | Bindless       | On GPU         | On GPU           | Merged Indirect     |
 //! | CpuDriven | Bound          | On CPU         | ❌                | Instanced Direct    |
+//! | Downlevel | Bound          | On CPU         | ❌                | Direct              |
 //!
 //! ### Profile Support
 //!
@@ -87,6 +92,10 @@
 //! |                      |        | Intel 4XXX+                                | ❌      | ✅     |
 //! | Android              | Vulkan | All                                        | ❌      | ✅     |
 //!
+//! The Downlevel profile is used where neither of them is supported, like WebGL2 and GLES 3.0. It
+//! has no storage buffers or compute shaders, so skinned meshes are drawn in their bind pose and
+//! only the first four directional lights are shaded.
+//!
 //! Footnotes:
 //! - ✅ Supported
 //! - 🚧 In Progress
@@ -169,10 +178,19 @@ pub mod util {
     pub mod bind_merge;
     pub mod buffer;
     pub mod buffer_copier;
-    pub mod frustum;
+    pub mod cells;
+    pub mod color_space;
+    pub mod deletion;
+    pub mod events;
+    pub mod geometry;
     pub mod math;
     pub mod mipmap;
+    pub mod occlusion;
     pub mod output;
+    pub mod quantize;
+    pub mod raycast;
+    pub mod scaling;
+    pub mod snapshot;
     /// Core datastructures that associate handles with data in a gpu-friendly
     /// format.
     pub mod registry {
@@ -184,7 +202,11 @@ pub mod util {
         pub use basic::*;
         pub use erased::*;
     }
+    pub mod streaming;
+    pub mod timings;
     pub mod typedefs;
+    pub mod upload;
+    pub mod visibility;
 }
 
 pub mod graph;
@@ -194,7 +216,7 @@ mod setup;
 mod surface;
 
 pub use profile::*;
-pub use renderer::{error::*, Renderer, RendererDataCore};
+pub use renderer::{error::*, MemoryUsage, Renderer, RendererDataCore, RendererSettings};
 pub use setup::*;
 pub use surface::*;
 
@@ -1,11 +1,15 @@
-use crate::types::{Camera, CameraProjection};
-use glam::{Mat4, Vec3};
-use rend3_types::Handedness;
+use crate::{
+    types::{Camera, CameraProjection},
+    util::raycast::Ray,
+};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use rend3_types::{DepthMode, Handedness};
 
 /// Manages the camera's location and projection settings.
 #[derive(Debug, Clone)]
 pub struct CameraManager {
     handedness: Handedness,
+    depth_mode: DepthMode,
     orig_view: Mat4,
     proj: Mat4,
     inv_view: Mat4,
@@ -15,15 +19,16 @@ pub struct CameraManager {
 impl CameraManager {
     /// Builds a new camera, using the given aspect ratio. If no aspect ratio is
     /// given it is assumed that no aspect ratio scaling should be done.
-    pub fn new(data: Camera, handedness: Handedness, aspect_ratio: Option<f32>) -> Self {
+    pub fn new(data: Camera, handedness: Handedness, depth_mode: DepthMode, aspect_ratio: Option<f32>) -> Self {
         profiling::scope!("CameraManager::new");
 
         let aspect_ratio = aspect_ratio.unwrap_or(1.0);
-        let proj = compute_projection_matrix(data, handedness, aspect_ratio);
+        let proj = compute_projection_matrix(data, handedness, depth_mode, aspect_ratio);
         let orig_view = compute_origin_matrix(data);
 
         Self {
             handedness,
+            depth_mode,
             orig_view,
             proj,
             inv_view: data.view.inverse(),
@@ -44,7 +49,7 @@ impl CameraManager {
     }
 
     pub fn set_aspect_data(&mut self, data: Camera, aspect_ratio: f32) {
-        self.proj = compute_projection_matrix(data, self.handedness, aspect_ratio);
+        self.proj = compute_projection_matrix(data, self.handedness, self.depth_mode, aspect_ratio);
         self.orig_view = compute_origin_matrix(data);
         self.inv_view = data.view.inverse();
         self.data = data;
@@ -59,6 +64,10 @@ impl CameraManager {
         self.handedness
     }
 
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
     pub fn view(&self) -> Mat4 {
         self.data.view
     }
@@ -78,25 +87,97 @@ impl CameraManager {
     pub fn location(&self) -> Vec3 {
         self.inv_view.w_axis.truncate()
     }
+
+    /// Ray through the pixel at `position` of a view `resolution` pixels
+    /// large, with the origin in the top left corner. The ray starts on the
+    /// near plane and its direction is normalized.
+    pub fn world_ray_from_screen(&self, position: Vec2, resolution: Vec2) -> Ray {
+        let ndc = (position / resolution * 2.0 - Vec2::ONE) * Vec2::new(1.0, -1.0);
+        let inv_view_proj = self.view_proj().inverse();
+        let near_depth = self.depth_mode.near_depth();
+        // The far plane may be at infinity, so aim at a point halfway there.
+        let middle_depth = (near_depth + self.depth_mode.far_depth()) * 0.5;
+        let near = inv_view_proj.project_point3(ndc.extend(near_depth));
+        let middle = inv_view_proj.project_point3(ndc.extend(middle_depth));
+        Ray::new(near, (middle - near).normalize())
+    }
+
+    /// Pixel `world` lands on in a view `resolution` pixels large, with the
+    /// origin in the top left corner. `None` if the point is behind the
+    /// camera. Points off screen land outside of the resolution.
+    pub fn project_to_screen(&self, world: Vec3, resolution: Vec2) -> Option<Vec2> {
+        let clip = self.view_proj() * world.extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = Vec2::new(clip.x, -clip.y) / clip.w;
+        Some((ndc + Vec2::ONE) * 0.5 * resolution)
+    }
 }
 
-fn compute_projection_matrix(data: Camera, handedness: Handedness, aspect_ratio: f32) -> Mat4 {
+fn compute_projection_matrix(data: Camera, handedness: Handedness, depth_mode: DepthMode, aspect_ratio: f32) -> Mat4 {
     match data.projection {
         CameraProjection::Orthographic { size } => {
             let half = size * 0.5;
+            // Orthographic projections are always finite, so only the direction of depth matters.
+            let (near, far) = match depth_mode.is_reversed() {
+                true => (half.z, -half.z),
+                false => (-half.z, half.z),
+            };
             if handedness == Handedness::Left {
-                Mat4::orthographic_lh(-half.x, half.x, -half.y, half.y, half.z, -half.z)
+                Mat4::orthographic_lh(-half.x, half.x, -half.y, half.y, near, far)
             } else {
-                Mat4::orthographic_rh(-half.x, half.x, -half.y, half.y, half.z, -half.z)
+                Mat4::orthographic_rh(-half.x, half.x, -half.y, half.y, near, far)
             }
         }
         CameraProjection::Perspective { vfov, near } => {
-            if handedness == Handedness::Left {
-                Mat4::perspective_infinite_reverse_lh(vfov.to_radians(), aspect_ratio, near)
-            } else {
-                Mat4::perspective_infinite_reverse_rh(vfov.to_radians(), aspect_ratio, near)
+            let vfov = vfov.to_radians();
+            // glam maps its near plane to 0 and its far plane to 1, so swapping them reverses depth.
+            match (depth_mode, handedness) {
+                (DepthMode::ReversedZInfinite, Handedness::Left) => {
+                    Mat4::perspective_infinite_reverse_lh(vfov, aspect_ratio, near)
+                }
+                (DepthMode::ReversedZInfinite, Handedness::Right) => {
+                    Mat4::perspective_infinite_reverse_rh(vfov, aspect_ratio, near)
+                }
+                (DepthMode::ReversedZFinite { far }, Handedness::Left) => {
+                    Mat4::perspective_lh(vfov, aspect_ratio, far, near)
+                }
+                (DepthMode::ReversedZFinite { far }, Handedness::Right) => {
+                    Mat4::perspective_rh(vfov, aspect_ratio, far, near)
+                }
+                (DepthMode::Standard { far }, Handedness::Left) => Mat4::perspective_lh(vfov, aspect_ratio, near, far),
+                (DepthMode::Standard { far }, Handedness::Right) => Mat4::perspective_rh(vfov, aspect_ratio, near, far),
             }
         }
+        CameraProjection::Asymmetric {
+            left,
+            right,
+            up,
+            down,
+            near,
+        } => {
+            let (tan_left, tan_right, tan_up, tan_down) = (left.tan(), right.tan(), up.tan(), down.tan());
+            let width = tan_right - tan_left;
+            let height = tan_up - tan_down;
+            // Sign of z along the view direction.
+            let forward = match handedness {
+                Handedness::Left => 1.0,
+                Handedness::Right => -1.0,
+            };
+            let (depth_a, depth_b) = depth_mode.perspective_coefficients(near);
+            Mat4::from_cols(
+                Vec4::new(2.0 / width, 0.0, 0.0, 0.0),
+                Vec4::new(0.0, 2.0 / height, 0.0, 0.0),
+                Vec4::new(
+                    -forward * (tan_right + tan_left) / width,
+                    -forward * (tan_up + tan_down) / height,
+                    forward * depth_a,
+                    forward,
+                ),
+                Vec4::new(0.0, 0.0, depth_b, 0.0),
+            )
+        }
         CameraProjection::Raw(proj) => proj,
     }
 }
@@ -107,3 +188,122 @@ fn compute_origin_matrix(data: Camera) -> Mat4 {
     view.w_axis = glam::Vec4::W;
     view
 }
+
+#[cfg(test)]
+mod test {
+    use super::{compute_projection_matrix, CameraManager};
+    use crate::types::{Camera, CameraProjection, DepthMode, Handedness};
+    use glam::{Mat4, Vec2, Vec3, Vec3A};
+
+    fn depth_at(projection: CameraProjection, handedness: Handedness, depth_mode: DepthMode, distance: f32) -> f32 {
+        let camera = Camera {
+            projection,
+            view: Mat4::IDENTITY,
+        };
+        let forward = match handedness {
+            Handedness::Left => distance,
+            Handedness::Right => -distance,
+        };
+        compute_projection_matrix(camera, handedness, depth_mode, 1.0)
+            .project_point3(Vec3::new(0.0, 0.0, forward))
+            .z
+    }
+
+    #[test]
+    fn depth_modes() {
+        let fov = 45.0_f32.to_radians();
+        let projections = [
+            CameraProjection::Perspective { vfov: 90.0, near: 0.5 },
+            CameraProjection::Asymmetric {
+                left: -fov,
+                right: fov,
+                up: fov,
+                down: -fov,
+                near: 0.5,
+            },
+        ];
+        let modes = [
+            DepthMode::ReversedZInfinite,
+            DepthMode::ReversedZFinite { far: 100.0 },
+            DepthMode::Standard { far: 100.0 },
+        ];
+        for projection in projections {
+            for depth_mode in modes {
+                for handedness in [Handedness::Left, Handedness::Right] {
+                    let near = depth_at(projection, handedness, depth_mode, 0.5);
+                    assert!(
+                        (near - depth_mode.near_depth()).abs() < 1e-5,
+                        "{:?} {:?}",
+                        projection,
+                        depth_mode
+                    );
+                    if let DepthMode::ReversedZFinite { far } | DepthMode::Standard { far } = depth_mode {
+                        let far = depth_at(projection, handedness, depth_mode, far);
+                        assert!(
+                            (far - depth_mode.far_depth()).abs() < 1e-5,
+                            "{:?} {:?}",
+                            projection,
+                            depth_mode
+                        );
+                    }
+                    let depth = depth_at(projection, handedness, depth_mode, 10.0);
+                    assert!((depth_mode.perspective_distance(depth, 0.5) - 10.0).abs() < 1e-3);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn screen_rays_hit_projected_points() {
+        let projections = [
+            CameraProjection::Perspective { vfov: 60.0, near: 0.1 },
+            CameraProjection::Orthographic {
+                size: Vec3A::new(8.0, 6.0, 40.0),
+            },
+        ];
+        let modes = [DepthMode::ReversedZInfinite, DepthMode::Standard { far: 100.0 }];
+        let resolution = Vec2::new(800.0, 600.0);
+        for projection in projections {
+            for depth_mode in modes {
+                for handedness in [Handedness::Left, Handedness::Right] {
+                    let forward = match handedness {
+                        Handedness::Left => Vec3::Z,
+                        Handedness::Right => -Vec3::Z,
+                    };
+                    let camera = Camera {
+                        projection,
+                        view: Mat4::look_at_lh(Vec3::new(1.0, 2.0, 3.0), Vec3::ZERO, Vec3::Y),
+                    };
+                    let manager = CameraManager::new(camera, handedness, depth_mode, Some(4.0 / 3.0));
+
+                    // Straight ahead lands in the middle of the screen.
+                    let ahead = manager.location() + manager.inv_view.transform_vector3(forward) * 10.0;
+                    let center = manager.project_to_screen(ahead, resolution).unwrap();
+                    assert!(center.abs_diff_eq(resolution * 0.5, 1e-2), "{:?}", center);
+
+                    let point = manager.location()
+                        + manager
+                            .inv_view
+                            .transform_vector3(forward * 10.0 + Vec3::new(1.0, 1.0, 0.0));
+                    let pixel = manager.project_to_screen(point, resolution).unwrap();
+                    // Up in the world is up on screen, towards smaller y.
+                    assert!(pixel.y < resolution.y * 0.5);
+                    let ray = manager.world_ray_from_screen(pixel, resolution);
+                    let closest = ray.origin + ray.direction * (point - ray.origin).dot(ray.direction);
+                    assert!(
+                        closest.abs_diff_eq(point, 1e-3),
+                        "{:?} {:?} {:?}",
+                        projection,
+                        depth_mode,
+                        handedness
+                    );
+
+                    if let CameraProjection::Perspective { .. } = projection {
+                        let behind = manager.location() - manager.inv_view.transform_vector3(forward);
+                        assert_eq!(manager.project_to_screen(behind, resolution), None);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -7,11 +7,11 @@ use crate::{
         registry::ResourceRegistry,
         typedefs::FastHashMap,
     },
-    INTERNAL_SHADOW_DEPTH_FORMAT, SHADOW_DIMENSIONS,
+    RendererProfile, INTERNAL_SHADOW_DEPTH_FORMAT, SHADOW_DIMENSIONS,
 };
 use arrayvec::ArrayVec;
 use glam::{Mat4, UVec2, Vec2, Vec3, Vec3A};
-use rend3_types::{DirectionalLightChange, Handedness, RawDirectionalLightHandle};
+use rend3_types::{DirectionalLightChange, Handedness, RawDirectionalLightHandle, ShadowSettings};
 use std::{
     mem::{self, size_of},
     num::{NonZeroU32, NonZeroU64},
@@ -23,9 +23,22 @@ use wgpu::{
     TextureDimension, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
 };
 
+/// Lights don't live in a storage buffer in the downlevel profile, so the
+/// shaders only read a fixed amount of them from a uniform buffer.
+pub const DOWNLEVEL_MAX_DIRECTIONAL_LIGHTS: usize = 4;
+
 /// Internal representation of a directional light.
 pub struct InternalDirectionalLight {
     pub inner: DirectionalLight,
+    pub label: Option<String>,
+}
+
+/// A live directional light, as listed by `Renderer::directional_lights`.
+#[derive(Debug, Clone)]
+pub struct DirectionalLightInfo {
+    pub handle: DirectionalLightHandle,
+    pub light: DirectionalLight,
+    pub label: Option<String>,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -52,6 +65,7 @@ unsafe impl bytemuck::Pod for ShaderDirectionalLight {}
 
 /// Manages directional lights and their associated shadow maps.
 pub struct DirectionalLightManager {
+    profile: RendererProfile,
     buffer: WrappedPotBuffer,
 
     view: TextureView,
@@ -63,27 +77,33 @@ pub struct DirectionalLightManager {
     bg: BindGroup,
 
     registry: ResourceRegistry<InternalDirectionalLight, DirectionalLight>,
+    settings: ShadowSettings,
 }
 impl DirectionalLightManager {
-    pub fn new(device: &Device) -> Self {
+    pub fn new(device: &Device, profile: RendererProfile) -> Self {
         profiling::scope!("DirectionalLightManager::new");
 
         let registry = ResourceRegistry::new();
 
+        let usage = match profile {
+            RendererProfile::Downlevel => BufferUsages::UNIFORM,
+            _ => BufferUsages::STORAGE,
+        };
         let buffer = WrappedPotBuffer::new(
             device,
             0,
             mem::size_of::<ShaderDirectionalLight>() as _,
-            BufferUsages::STORAGE,
+            usage,
             Some("directional lights"),
         );
 
         let (view, layer_views) = create_shadow_texture(device, Extent3d::default());
 
-        let bgl = create_shadow_bgl(device);
+        let bgl = create_shadow_bgl(device, profile);
         let bg = create_shadow_bg(device, &bgl, &buffer, &view);
 
         Self {
+            profile,
             buffer,
             view,
             layer_views,
@@ -94,6 +114,7 @@ impl DirectionalLightManager {
             bg,
 
             registry,
+            settings: ShadowSettings::default(),
         }
     }
 
@@ -104,13 +125,27 @@ impl DirectionalLightManager {
     }
 
     pub fn fill(&mut self, handle: &DirectionalLightHandle, light: DirectionalLight) {
-        self.registry.insert(handle, InternalDirectionalLight { inner: light });
+        self.registry.insert(
+            handle,
+            InternalDirectionalLight {
+                inner: light,
+                label: None,
+            },
+        );
     }
 
     pub fn get_mut(&mut self, handle: RawDirectionalLightHandle) -> &mut InternalDirectionalLight {
         self.registry.get_mut(handle)
     }
 
+    pub fn set_settings(&mut self, settings: ShadowSettings) {
+        self.settings = settings;
+    }
+
+    pub fn settings(&self) -> ShadowSettings {
+        self.settings
+    }
+
     pub fn get_layer_views(&self) -> &[TextureView] {
         &self.layer_views
     }
@@ -120,28 +155,33 @@ impl DirectionalLightManager {
         internal.inner.update_from_changes(change);
     }
 
-    pub fn add_to_bgl(bglb: &mut BindGroupLayoutBuilder) {
-        bglb.append(
-            ShaderStages::FRAGMENT,
-            BindingType::Buffer {
-                ty: BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset: false,
-                min_binding_size: NonZeroU64::new(
-                    (mem::size_of::<ShaderDirectionalLightBufferHeader>() + mem::size_of::<ShaderDirectionalLight>())
-                        as _,
-                ),
-            },
-            None,
-        )
-        .append(
-            ShaderStages::FRAGMENT,
-            BindingType::Texture {
-                sample_type: TextureSampleType::Depth,
-                view_dimension: TextureViewDimension::D2Array,
-                multisampled: false,
-            },
-            None,
-        );
+    pub fn set_label(&mut self, handle: RawDirectionalLightHandle, label: Option<String>) {
+        self.registry.get_mut(handle).label = label;
+    }
+
+    /// Every light whose handles weren't all dropped.
+    pub fn lights(&self) -> Vec<DirectionalLightInfo> {
+        self.registry
+            .iter_handles()
+            .map(|(handle, internal)| DirectionalLightInfo {
+                handle,
+                light: internal.inner.clone(),
+                label: internal.label.clone(),
+            })
+            .collect()
+    }
+
+    pub fn add_to_bgl(bglb: &mut BindGroupLayoutBuilder, profile: RendererProfile) {
+        bglb.append(ShaderStages::FRAGMENT, light_buffer_binding_type(profile), None)
+            .append(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                None,
+            );
     }
 
     pub fn add_to_bg<'a>(&'a self, bgb: &mut BindGroupBuilder<'a>) {
@@ -155,7 +195,7 @@ impl DirectionalLightManager {
     pub fn ready(&mut self, device: &Device, queue: &Queue, user_camera: &CameraManager) -> Vec<CameraManager> {
         profiling::scope!("Directional Light Ready");
 
-        self.registry.remove_all_dead(|_, _, _| ());
+        self.registry.remove_all_dead(|_, _, _, _| ());
 
         let registered_count: usize = self.registry.values().len();
         let recreate_view = registered_count != self.coords.len() && registered_count != 0;
@@ -170,17 +210,31 @@ impl DirectionalLightManager {
 
         let registry = &self.registry;
 
-        let size =
-            registered_count * size_of::<ShaderDirectionalLight>() + size_of::<ShaderDirectionalLightBufferHeader>();
+        // The downlevel shaders read a fixed size array of lights.
+        let light_count = match self.profile {
+            RendererProfile::Downlevel => {
+                if recreate_view && registered_count > DOWNLEVEL_MAX_DIRECTIONAL_LIGHTS {
+                    log::warn!(
+                        "{} directional lights in use, only the first {} are rendered in the downlevel profile",
+                        registered_count,
+                        DOWNLEVEL_MAX_DIRECTIONAL_LIGHTS
+                    );
+                }
+                DOWNLEVEL_MAX_DIRECTIONAL_LIGHTS
+            }
+            _ => registered_count,
+        };
+
+        let size = light_count * size_of::<ShaderDirectionalLight>() + size_of::<ShaderDirectionalLightBufferHeader>();
 
         let mut cameras = Vec::with_capacity(registered_count);
 
         let mut buffer = Vec::with_capacity(size);
         buffer.extend_from_slice(bytemuck::bytes_of(&ShaderDirectionalLightBufferHeader {
-            total_lights: registry.count() as u32,
+            total_lights: registry.count().min(light_count) as u32,
         }));
-        for (coords, light) in self.coords.iter().zip(registry.values()) {
-            let cs = shadow(light, user_camera);
+        for (coords, light) in self.coords.iter().zip(registry.values()).take(light_count) {
+            let cs = shadow(light, self.settings.max_distance, user_camera);
             for camera in &cs {
                 buffer.extend_from_slice(bytemuck::bytes_of(&ShaderDirectionalLight {
                     view_proj: camera.view_proj(),
@@ -192,6 +246,7 @@ impl DirectionalLightManager {
             }
             cameras.extend_from_slice(&cs);
         }
+        buffer.resize(size, 0);
 
         let reallocated_buffer = self.buffer.write_to_buffer(device, queue, &buffer);
 
@@ -216,12 +271,17 @@ pub struct ShadowCoordinates {
 }
 
 // TODO: re-enable cascades
-fn shadow(l: &InternalDirectionalLight, user_camera: &CameraManager) -> ArrayVec<CameraManager, 4> {
+fn shadow(
+    l: &InternalDirectionalLight,
+    max_distance: Option<f32>,
+    user_camera: &CameraManager,
+) -> ArrayVec<CameraManager, 4> {
     let mut cascades = ArrayVec::new();
 
     let camera_location = user_camera.location();
 
-    let shadow_texel_size = l.inner.distance / SHADOW_DIMENSIONS as f32;
+    let distance = max_distance.map_or(l.inner.distance, |max| l.inner.distance.min(max));
+    let shadow_texel_size = distance / SHADOW_DIMENSIONS as f32;
 
     let look_at = match user_camera.handedness() {
         Handedness::Left => Mat4::look_at_lh,
@@ -240,11 +300,12 @@ fn shadow(l: &InternalDirectionalLight, user_camera: &CameraManager) -> ArrayVec
     cascades.push(CameraManager::new(
         Camera {
             projection: CameraProjection::Orthographic {
-                size: Vec3A::splat(l.inner.distance),
+                size: Vec3A::splat(distance),
             },
             view: look_at(new_shadow_location, new_shadow_location + l.inner.direction, Vec3::Y),
         },
         user_camera.handedness(),
+        user_camera.depth_mode(),
         None,
     ));
 
@@ -425,7 +486,22 @@ fn create_shadow_texture(device: &Device, size: Extent3d) -> (TextureView, Vec<T
     (primary_view, layer_views)
 }
 
-fn create_shadow_bgl(device: &Device) -> BindGroupLayout {
+fn light_buffer_binding_type(profile: RendererProfile) -> BindingType {
+    let (ty, light_count) = match profile {
+        RendererProfile::Downlevel => (BufferBindingType::Uniform, DOWNLEVEL_MAX_DIRECTIONAL_LIGHTS),
+        _ => (BufferBindingType::Storage { read_only: true }, 1),
+    };
+    BindingType::Buffer {
+        ty,
+        has_dynamic_offset: false,
+        min_binding_size: NonZeroU64::new(
+            (mem::size_of::<ShaderDirectionalLightBufferHeader>()
+                + light_count * mem::size_of::<ShaderDirectionalLight>()) as _,
+        ),
+    }
+}
+
+fn create_shadow_bgl(device: &Device, profile: RendererProfile) -> BindGroupLayout {
     profiling::scope!("shadow bgl creation");
     device.create_bind_group_layout(&BindGroupLayoutDescriptor {
         label: Some("shadow bgl"),
@@ -433,14 +509,7 @@ fn create_shadow_bgl(device: &Device) -> BindGroupLayout {
             BindGroupLayoutEntry {
                 binding: 0,
                 visibility: ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
-                    min_binding_size: NonZeroU64::new(
-                        (mem::size_of::<ShaderDirectionalLightBufferHeader>()
-                            + mem::size_of::<ShaderDirectionalLight>()) as _,
-                    ),
-                },
+                ty: light_buffer_binding_type(profile),
                 count: None,
             },
             BindGroupLayoutEntry {
@@ -5,6 +5,7 @@ use crate::{
     util::{
         bind_merge::{BindGroupBuilder, BindGroupLayoutBuilder},
         buffer::WrappedPotBuffer,
+        deletion::DeletionQueue,
         math::round_up_pot,
         registry::ArchitypicalErasedRegistry,
         typedefs::FastHashMap,
@@ -12,20 +13,46 @@ use crate::{
     RendererProfile,
 };
 use list_any::VecAny;
-use rend3_types::{Material, MaterialTag, RawMaterialHandle, RawObjectHandle};
+use rend3_types::{Material, MaterialTag, RawMaterialHandle, RawObjectHandle, RawTextureHandle};
 use std::{
     any::TypeId,
     num::{NonZeroU32, NonZeroU64},
+    ops::Range,
     sync::atomic::{AtomicUsize, Ordering},
 };
 use wgpu::{
     BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
     BufferBinding, BufferBindingType, BufferDescriptor, BufferUsages, Device, Queue, ShaderStages, TextureSampleType,
-    TextureViewDimension,
+    TextureViewDimension, COPY_BUFFER_ALIGNMENT,
 };
 
 const TEXTURE_MASK_SIZE: u32 = 4;
 
+/// Material data is bound as a uniform in the downlevel profile. The downlevel
+/// shaders which don't know the material type read it as an array of this
+/// many bytes, so every material buffer is at least this big.
+const DOWNLEVEL_MATERIAL_SIZE: u32 = 256;
+
+/// Size of the buffer holding the data and texture mask of a single material
+/// in the cpu profiles.
+fn cpu_material_size(data_size: u32, profile: RendererProfile) -> u32 {
+    let size = round_up_pot(data_size, 16) + round_up_pot(TEXTURE_MASK_SIZE, 16);
+    if profile == RendererProfile::Downlevel {
+        size.max(DOWNLEVEL_MATERIAL_SIZE)
+    } else {
+        size
+    }
+}
+
+/// How the cpu profiles bind material data.
+fn cpu_material_binding(profile: RendererProfile) -> (BufferBindingType, BufferUsages) {
+    if profile == RendererProfile::Downlevel {
+        (BufferBindingType::Uniform, BufferUsages::UNIFORM)
+    } else {
+        (BufferBindingType::Storage { read_only: true }, BufferUsages::STORAGE)
+    }
+}
+
 /// Internal representation of a material.
 pub struct InternalMaterial {
     pub bind_group: ProfileData<BindGroup, ()>,
@@ -107,7 +134,10 @@ impl MaterialManager {
                         visibility: ShaderStages::FRAGMENT,
                         ty: BindingType::Texture {
                             sample_type: TextureSampleType::Float { filterable: true },
-                            view_dimension: TextureViewDimension::D2,
+                            view_dimension: match M::TEXTURE_ARRAYS {
+                                true => TextureViewDimension::D2Array,
+                                false => TextureViewDimension::D2,
+                            },
                             multisampled: false,
                         },
                         count: None,
@@ -118,11 +148,9 @@ impl MaterialManager {
                         binding: 0,
                         visibility: ShaderStages::VERTEX_FRAGMENT,
                         ty: BindingType::Buffer {
-                            ty: BufferBindingType::Storage { read_only: true },
+                            ty: cpu_material_binding(profile).0,
                             has_dynamic_offset: false,
-                            min_binding_size: NonZeroU64::new(
-                                (round_up_pot(M::DATA_SIZE, 16) + round_up_pot(TEXTURE_MASK_SIZE, 16)) as _,
-                            ),
+                            min_binding_size: NonZeroU64::new(cpu_material_size(M::DATA_SIZE, profile) as _),
                         },
                         count: None,
                     });
@@ -153,52 +181,39 @@ impl MaterialManager {
         &mut self,
         device: &Device,
         profile: RendererProfile,
-        texture_manager_2d: &mut TextureManager,
+        texture_manager: &mut TextureManager,
         material: &M,
     ) -> InternalMaterial {
-        let null_tex = texture_manager_2d.get_null_view();
-
-        let translation_fn = texture_manager_2d.translation_fn();
-
         let type_info = self.ensure_archetype_inner::<M>(device, profile);
 
-        let (bind_group, material_buffer) = if profile == RendererProfile::CpuDriven {
+        if M::DATA_TEXTURES != 0 {
             let mut textures = vec![None; M::TEXTURE_COUNT as usize];
             material.to_textures(&mut textures);
-
-            // TODO(material): stack allocation
-            let material_uprounded = round_up_pot(M::DATA_SIZE, 16) as usize;
-            let actual_size = material_uprounded + round_up_pot(TEXTURE_MASK_SIZE, 16) as usize;
-            let mut data = vec![0u8; actual_size as usize];
-            material.to_data(&mut data[..M::DATA_SIZE as usize]);
-
-            let material_buffer = device.create_buffer(&BufferDescriptor {
-                label: None,
-                usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
-                size: data.len() as _,
-                mapped_at_creation: true,
-            });
-            let mut material_buffer_mapping = material_buffer.slice(..).get_mapped_range_mut();
-
-            let mut builder = BindGroupBuilder::new();
-            builder.append_buffer(&material_buffer);
-
-            let mut texture_mask = 0_u32;
             for (idx, texture) in textures.into_iter().enumerate() {
-                let view = texture.map(|tex| texture_manager_2d.get_view_from_index(translation_fn(tex)));
-                builder.append(BindingResource::TextureView(view.unwrap_or(null_tex)));
-
-                let enabled = texture.is_some();
-                texture_mask |= (enabled as u32) << idx as u32;
+                let texture = match texture {
+                    Some(texture) if (M::DATA_TEXTURES >> idx) & 1 == 1 => texture,
+                    _ => continue,
+                };
+                let format = texture_manager.get_internal(texture.get_raw()).desc.format;
+                if format.describe().srgb {
+                    log::warn!(
+                        "Texture {} of {} holds data but is in {:?}, so it will be gamma decoded. Use a linear format or ColorSpace::Linear",
+                        idx,
+                        std::any::type_name::<M>(),
+                        format
+                    );
+                }
             }
+        }
 
-            *bytemuck::from_bytes_mut(&mut data[material_uprounded..material_uprounded + 4]) = texture_mask;
-
-            material_buffer_mapping.copy_from_slice(&data);
-            drop(material_buffer_mapping);
-            material_buffer.unmap();
-
-            let bind_group = builder.build(device, None, type_info.bgl.as_ref().as_cpu());
+        let (bind_group, material_buffer) = if profile != RendererProfile::GpuDriven {
+            let (bind_group, material_buffer) = create_cpu_material(
+                device,
+                profile,
+                texture_manager,
+                type_info.bgl.as_ref().as_cpu(),
+                material,
+            );
 
             (ProfileData::Cpu(bind_group), ProfileData::Cpu(material_buffer))
         } else {
@@ -217,11 +232,11 @@ impl MaterialManager {
         &mut self,
         device: &Device,
         profile: RendererProfile,
-        texture_manager_2d: &mut TextureManager,
+        texture_manager: &mut TextureManager,
         handle: &MaterialHandle,
         material: M,
     ) {
-        let internal = self.fill_inner(device, profile, texture_manager_2d, &material);
+        let internal = self.fill_inner(device, profile, texture_manager, &material);
 
         self.registry.insert(handle, material, internal);
     }
@@ -230,14 +245,14 @@ impl MaterialManager {
         &mut self,
         device: &Device,
         profile: RendererProfile,
-        texture_manager_2d: &mut TextureManager,
+        texture_manager: &mut TextureManager,
         object_manager: &mut ObjectManager,
         handle: &MaterialHandle,
         material: M,
     ) {
         // TODO(material): if this doesn't change archetype, this should do a buffer
         // write cpu side.
-        let internal = self.fill_inner(device, profile, texture_manager_2d, &material);
+        let internal = self.fill_inner(device, profile, texture_manager, &material);
 
         let archetype_changed = self.registry.update(handle, material, |internal, idx| {
             for object in &internal.objects {
@@ -281,6 +296,61 @@ impl MaterialManager {
         }
     }
 
+    /// Changes a material in place. Unless its textures change, the bind
+    /// group and buffer of the material are kept, and only the range of its
+    /// data which changed is written.
+    #[allow(clippy::too_many_arguments)]
+    pub fn modify<M: Material>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        profile: RendererProfile,
+        texture_manager: &mut TextureManager,
+        object_manager: &mut ObjectManager,
+        handle: &MaterialHandle,
+        modify: impl FnOnce(&mut M),
+    ) {
+        let (material, internal) = self.registry.get_mut_full::<M>(handle.get_raw());
+
+        let data_size = round_up_pot(M::DATA_SIZE, 16) as usize;
+        let mut old_data = vec![0u8; data_size];
+        material.to_data(&mut old_data[..M::DATA_SIZE as usize]);
+        let old_textures = raw_textures(material);
+
+        modify(material);
+
+        let mut new_data = vec![0u8; data_size];
+        material.to_data(&mut new_data[..M::DATA_SIZE as usize]);
+
+        let key = material.object_key();
+        if key != internal.key {
+            for object in &internal.objects {
+                object_manager.set_key(
+                    *object,
+                    MaterialKeyPair {
+                        ty: TypeId::of::<M>(),
+                        key,
+                    },
+                )
+            }
+            internal.key = key;
+        }
+
+        // The GpuDriven profile writes every material each frame in ready.
+        if profile == RendererProfile::GpuDriven {
+            return;
+        }
+
+        if raw_textures(material) != old_textures {
+            let bgl = self.type_info[&TypeId::of::<M>()].bgl.as_cpu();
+            let (bind_group, material_buffer) = create_cpu_material(device, profile, texture_manager, bgl, material);
+            internal.bind_group = ProfileData::Cpu(bind_group);
+            internal.material_buffer = ProfileData::Cpu(material_buffer);
+        } else if let Some(range) = dirty_range(&old_data, &new_data) {
+            queue.write_buffer(internal.material_buffer.as_cpu(), range.start as u64, &new_data[range]);
+        }
+    }
+
     pub fn get_material<M: Material>(&self, handle: RawMaterialHandle) -> &M {
         self.registry.get_ref::<M>(handle)
     }
@@ -340,23 +410,38 @@ impl MaterialManager {
         &mut arch.non_erased[index].inner.objects
     }
 
+    /// Number of materials of type `M`.
+    pub fn archetype_len<M: Material>(&self) -> usize {
+        self.registry
+            .archetype_lengths()
+            .find(|&(ty, _)| ty == TypeId::of::<M>())
+            .map_or(0, |(_, len)| len)
+    }
+
     pub fn get_internal_index(&self, handle: RawMaterialHandle) -> usize {
         self.registry.get_index(handle)
     }
 
+    /// Removes the materials whose handles were all dropped, retiring the
+    /// bind groups and buffers they have in the cpu profiles into
+    /// `deletion_queue`, and uploads the material data when GpuDriven.
     pub fn ready(
         &mut self,
         device: &Device,
         queue: &Queue,
         object_manager: &mut ObjectManager,
         texture_manager: &TextureManager,
+        deletion_queue: &mut DeletionQueue,
     ) {
         profiling::scope!("Material Ready");
-        self.registry.remove_all_dead(|internal, idx| {
-            for object in &internal.objects {
-                object_manager.set_material_index(*object, idx);
-            }
-        });
+        self.registry.remove_all_dead(
+            |internal, idx| {
+                for object in &internal.objects {
+                    object_manager.set_material_index(*object, idx);
+                }
+            },
+            |internal| deletion_queue.retire((internal.bind_group, internal.material_buffer)),
+        );
 
         if let ProfileData::Gpu(ref mut buffer) = self.buffer {
             profiling::scope!("Update GPU Material Buffer");
@@ -403,6 +488,83 @@ impl MaterialManager {
     }
 }
 
+/// Creates the buffer holding the data and texture mask of a material for the
+/// cpu profiles, and the bind group binding it with the textures.
+fn create_cpu_material<M: Material>(
+    device: &Device,
+    profile: RendererProfile,
+    texture_manager: &mut TextureManager,
+    bgl: &BindGroupLayout,
+    material: &M,
+) -> (BindGroup, Buffer) {
+    let null_tex = texture_manager.get_null_view();
+
+    let translation_fn = texture_manager.translation_fn();
+
+    let mut textures = vec![None; M::TEXTURE_COUNT as usize];
+    material.to_textures(&mut textures);
+
+    // TODO(material): stack allocation
+    let material_uprounded = round_up_pot(M::DATA_SIZE, 16) as usize;
+    let actual_size = cpu_material_size(M::DATA_SIZE, profile) as usize;
+    let mut data = vec![0u8; actual_size];
+    material.to_data(&mut data[..M::DATA_SIZE as usize]);
+
+    let material_buffer = device.create_buffer(&BufferDescriptor {
+        label: None,
+        usage: BufferUsages::COPY_DST | cpu_material_binding(profile).1,
+        size: data.len() as _,
+        mapped_at_creation: true,
+    });
+    let mut material_buffer_mapping = material_buffer.slice(..).get_mapped_range_mut();
+
+    let mut builder = BindGroupBuilder::new();
+    builder.append_buffer(&material_buffer);
+
+    let mut texture_mask = 0_u32;
+    for (idx, texture) in textures.into_iter().enumerate() {
+        let view = texture.map(|tex| texture_manager.get_view_from_index(translation_fn(tex)));
+        builder.append(BindingResource::TextureView(view.unwrap_or(null_tex)));
+
+        let enabled = texture.is_some();
+        texture_mask |= (enabled as u32) << idx as u32;
+    }
+
+    *bytemuck::from_bytes_mut(&mut data[material_uprounded..material_uprounded + 4]) = texture_mask;
+
+    material_buffer_mapping.copy_from_slice(&data);
+    drop(material_buffer_mapping);
+    material_buffer.unmap();
+
+    let bind_group = builder.build(device, None, bgl);
+
+    (bind_group, material_buffer)
+}
+
+fn raw_textures<M: Material>(material: &M) -> Vec<Option<RawTextureHandle>> {
+    let mut textures = vec![None; M::TEXTURE_COUNT as usize];
+    material.to_textures(&mut textures);
+    textures
+        .into_iter()
+        .map(|texture| texture.map(TextureHandle::get_raw))
+        .collect()
+}
+
+/// Range of bytes which differ between `old` and `new`, widened to the
+/// alignment of buffer writes.
+fn dirty_range(old: &[u8], new: &[u8]) -> Option<Range<usize>> {
+    let start = old.iter().zip(new).position(|(old, new)| old != new)?;
+    let end = old.len()
+        - old
+            .iter()
+            .rev()
+            .zip(new.iter().rev())
+            .position(|(old, new)| old != new)?;
+
+    let align = COPY_BUFFER_ALIGNMENT as usize;
+    Some(start / align * align..round_up_pot(end, align).min(new.len()))
+}
+
 fn write_gpu_materials<M: Material>(
     dest: &mut [u8],
     vec_any: &VecAny,
@@ -426,10 +588,11 @@ fn write_gpu_materials<M: Material>(
             // Get the texture handles from the material
             mat.to_textures(&mut texture_ref_tmp);
 
-            // Translate them and write them into the slice.
+            // Translate them and write them into the slice. Arrays aren't in the bindless
+            // 2D textures, so they stay unbound.
             let texture_slice = bytemuck::cast_slice_mut(&mut dest[offset..offset + texture_bytes]);
             for (idx, tex) in texture_ref_tmp.iter_mut().enumerate() {
-                texture_slice[idx] = tex.take().map(|tex| translation_fn(tex));
+                texture_slice[idx] = tex.take().filter(|_| !M::TEXTURE_ARRAYS).map(&mut *translation_fn);
             }
 
             offset += mat_size;
@@ -456,3 +619,22 @@ fn get_material_key<M: Material>(vec_any: &VecAny, index: usize) -> MaterialKeyP
         key,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::dirty_range;
+
+    #[test]
+    fn dirty_range_is_aligned() {
+        let old = [0_u8; 16];
+        let mut new = old;
+        assert_eq!(dirty_range(&old, &new), None);
+
+        new[5] = 1;
+        new[9] = 1;
+        assert_eq!(dirty_range(&old, &new), Some(4..12));
+
+        new[15] = 1;
+        assert_eq!(dirty_range(&old, &new), Some(4..16));
+    }
+}
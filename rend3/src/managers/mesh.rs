@@ -3,20 +3,26 @@ use crate::{
     types::{Mesh, MeshHandle},
     util::{
         buffer_copier::{VertexBufferCopier, VertexBufferCopierParams},
-        frustum::BoundingSphere,
+        geometry::BoundingSphere,
+        quantize::{f16_from_f32, octahedral_encode, PositionDequantization},
+        raycast::MeshBvh,
         registry::ResourceRegistry,
     },
+    RendererProfile,
 };
 use glam::{Vec2, Vec3};
 use range_alloc::RangeAllocator;
 use rend3_types::{RawMeshHandle, RawSkeletonHandle};
 use std::{
+    borrow::Cow,
     mem::size_of,
     ops::Range,
     sync::atomic::{AtomicUsize, Ordering},
 };
 use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
     Buffer, BufferAddress, BufferDescriptor, BufferUsages, CommandEncoder, Device, IndexFormat, Queue, RenderPass,
+    VertexFormat,
 };
 
 /// Size of a single vertex position.
@@ -56,10 +62,120 @@ pub const VERTEX_JOINT_WEIGHT_SLOT: u32 = 7;
 /// Note that this slot is only used in the GpuDriven profile.
 pub const VERTEX_OBJECT_INDEX_SLOT: u32 = 8;
 
+bitflags::bitflags! {
+    /// Vertex attributes the mesh manager stores besides positions and
+    /// normals, chosen when creating the [`Renderer`](crate::Renderer).
+    ///
+    /// Attributes left out take almost no memory or bandwidth: their buffer
+    /// holds a default value per instance, which every vertex of the instance
+    /// reads through an instance-stepped vertex buffer layout. Meshes can
+    /// still have them; they are dropped when the mesh is uploaded. The defaults are a tangent along
+    /// +X, uvs of zero, white, and joint weights binding fully to joint 0.
+    pub struct VertexAttributes: u8 {
+        const TANGENT = 1 << 0;
+        const UV0 = 1 << 1;
+        const UV1 = 1 << 2;
+        const COLOR = 1 << 3;
+        /// Joint indices and weights. Skinning needs them.
+        const JOINTS = 1 << 4;
+    }
+}
+
+impl Default for VertexAttributes {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+bitflags::bitflags! {
+    /// Vertex attributes the mesh manager stores in a smaller format, chosen
+    /// when creating the [`Renderer`](crate::Renderer). The encodings are
+    /// described in [`quantize`](crate::util::quantize).
+    ///
+    /// Positions and texture coordinates are decoded by the vertex fetch,
+    /// the bounds of quantized positions being folded into the matrices of
+    /// each object. Octahedral normals and tangents reach the vertex shader
+    /// as their two encoded components, which the shaders of rend3-routine
+    /// decode, told so by the `vertex_quantization` of its frame uniforms.
+    /// When positions are quantized, normals and tangents are stored scaled
+    /// by the bounds of their mesh, which the matrices scale back.
+    pub struct VertexQuantization: u8 {
+        /// Normals and tangents as two octahedral snorm16, 4 bytes instead
+        /// of 12.
+        const NORMALS = 1 << 0;
+        /// Texture coordinates as half floats, 4 bytes instead of 8.
+        const UVS = 1 << 1;
+        /// Positions as snorm16 inside the bounds of their mesh, 8 bytes
+        /// instead of 12. Meshes can't be skinned on the gpu, as skinned
+        /// vertices leave the bounds.
+        const POSITIONS = 1 << 2;
+    }
+}
+
+impl Default for VertexQuantization {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl VertexQuantization {
+    /// Size of a single vertex position.
+    pub fn position_size(self) -> usize {
+        match self.contains(Self::POSITIONS) {
+            true => size_of::<[i16; 4]>(),
+            false => VERTEX_POSITION_SIZE,
+        }
+    }
+
+    pub fn position_format(self) -> VertexFormat {
+        match self.contains(Self::POSITIONS) {
+            true => VertexFormat::Snorm16x4,
+            false => VertexFormat::Float32x3,
+        }
+    }
+
+    /// Size of a single vertex normal or tangent.
+    pub fn normal_size(self) -> usize {
+        match self.contains(Self::NORMALS) {
+            true => size_of::<[i16; 2]>(),
+            false => VERTEX_NORMAL_SIZE,
+        }
+    }
+
+    /// Format of normals and tangents.
+    pub fn normal_format(self) -> VertexFormat {
+        match self.contains(Self::NORMALS) {
+            true => VertexFormat::Snorm16x2,
+            false => VertexFormat::Float32x3,
+        }
+    }
+
+    /// Size of a single vertex texture coordinate.
+    pub fn uv_size(self) -> usize {
+        match self.contains(Self::UVS) {
+            true => size_of::<[u16; 2]>(),
+            false => VERTEX_UV_SIZE,
+        }
+    }
+
+    pub fn uv_format(self) -> VertexFormat {
+        match self.contains(Self::UVS) {
+            true => VertexFormat::Float16x2,
+            false => VertexFormat::Float32x2,
+        }
+    }
+}
+
+/// Octahedral encoding of +X, the tangent of meshes when tangents aren't
+/// stored.
+const DEFAULT_ENCODED_TANGENT: [i16; 2] = [i16::MAX, 0];
+
 /// Pre-allocated vertex count in the vertex megabuffers.
 pub const STARTING_VERTICES: usize = 1 << 16;
 /// Pre-allocated index count in the index megabuffer.
 pub const STARTING_INDICES: usize = 1 << 16;
+/// Pre-allocated instance count in the buffers of the attributes left out.
+pub const STARTING_DEFAULT_INSTANCES: usize = 1 << 10;
 
 /// Internal representation of a mesh.
 pub struct InternalMesh {
@@ -77,6 +193,11 @@ pub struct InternalMesh {
     /// For skinned meshes, stores the number of joints present in the joint
     /// index buffer
     pub num_joints: u32,
+    /// Copy of the geometry for ray casting.
+    pub bvh: MeshBvh,
+    /// Maps the stored positions to mesh space, the identity unless
+    /// positions are quantized.
+    pub position_dequantization: PositionDequantization,
 }
 
 impl InternalMesh {
@@ -88,6 +209,8 @@ impl InternalMesh {
             bounding_sphere: BoundingSphere::from_mesh(&[]),
             skeletons: Vec::new(),
             num_joints: 0,
+            bvh: MeshBvh::default(),
+            position_dequantization: PositionDequantization::IDENTITY,
         }
     }
 }
@@ -104,9 +227,54 @@ pub struct MeshBuffers {
     pub vertex_joint_weight: Buffer,
 
     pub index: Buffer,
+
+    /// Attributes whose buffers hold a value per vertex, the others holding
+    /// a default value per instance.
+    pub attributes: VertexAttributes,
+    /// Instances the buffers of the attributes not in `attributes` hold a
+    /// default value for. Draws must not go past them.
+    pub default_instances: usize,
+    /// Attributes stored in a smaller format.
+    pub quantization: VertexQuantization,
 }
 
 impl MeshBuffers {
+    /// Every vertex buffer, with the size of a value and the attribute it
+    /// needs to hold a value per vertex, empty if it always does.
+    fn vertex_buffers(&self) -> [(&Buffer, usize, VertexAttributes); 8] {
+        let quantization = self.quantization;
+        [
+            (
+                &self.vertex_position,
+                quantization.position_size(),
+                VertexAttributes::empty(),
+            ),
+            (
+                &self.vertex_normal,
+                quantization.normal_size(),
+                VertexAttributes::empty(),
+            ),
+            (
+                &self.vertex_tangent,
+                quantization.normal_size(),
+                VertexAttributes::TANGENT,
+            ),
+            (&self.vertex_uv0, quantization.uv_size(), VertexAttributes::UV0),
+            (&self.vertex_uv1, quantization.uv_size(), VertexAttributes::UV1),
+            (&self.vertex_color, VERTEX_COLOR_SIZE, VertexAttributes::COLOR),
+            (
+                &self.vertex_joint_index,
+                VERTEX_JOINT_INDEX_SIZE,
+                VertexAttributes::JOINTS,
+            ),
+            (
+                &self.vertex_joint_weight,
+                VERTEX_JOINT_WEIGHT_SIZE,
+                VertexAttributes::JOINTS,
+            ),
+        ]
+    }
+
     pub fn bind<'rpass>(&'rpass self, rpass: &mut RenderPass<'rpass>) {
         rpass.set_vertex_buffer(VERTEX_POSITION_SLOT, self.vertex_position.slice(..));
         rpass.set_vertex_buffer(VERTEX_NORMAL_SLOT, self.vertex_normal.slice(..));
@@ -129,15 +297,38 @@ pub struct MeshManager {
     index_alloc: RangeAllocator<usize>,
 
     registry: ResourceRegistry<InternalMesh, Mesh>,
+    /// Meshes removed by the last call to [`MeshManager::ready`].
+    removed: Vec<RawMeshHandle>,
 
-    buffer_copier: VertexBufferCopier,
+    /// Usages of the vertex and index buffers besides copies.
+    storage_usage: BufferUsages,
+    /// Not available in the downlevel profile, which has no compute shaders.
+    buffer_copier: Option<VertexBufferCopier>,
 }
 
 impl MeshManager {
-    pub fn new(device: &Device) -> Self {
+    pub fn new(
+        device: &Device,
+        profile: RendererProfile,
+        attributes: VertexAttributes,
+        quantization: VertexQuantization,
+    ) -> Self {
         profiling::scope!("MeshManager::new");
 
-        let buffers = create_buffers(device, STARTING_VERTICES, STARTING_INDICES);
+        let (storage_usage, buffer_copier) = match profile {
+            RendererProfile::Downlevel => (BufferUsages::empty(), None),
+            _ => (BufferUsages::STORAGE, Some(VertexBufferCopier::new(device))),
+        };
+
+        let buffers = create_buffers(
+            device,
+            STARTING_VERTICES,
+            STARTING_INDICES,
+            STARTING_DEFAULT_INSTANCES,
+            storage_usage,
+            attributes,
+            quantization,
+        );
 
         let vertex_alloc = RangeAllocator::new(0..STARTING_VERTICES);
         let index_alloc = RangeAllocator::new(0..STARTING_INDICES);
@@ -149,7 +340,9 @@ impl MeshManager {
             vertex_alloc,
             index_alloc,
             registry,
-            buffer_copier: VertexBufferCopier::new(device),
+            removed: Vec::new(),
+            storage_usage,
+            buffer_copier,
         }
     }
 
@@ -213,46 +406,93 @@ impl MeshManager {
         let vertex_range = vertex_range.unwrap();
         let index_range = index_range.unwrap();
 
-        queue.write_buffer(
-            &self.buffers.vertex_position,
-            (vertex_range.start * VERTEX_POSITION_SIZE) as BufferAddress,
-            bytemuck::cast_slice(&mesh.vertex_positions),
-        );
-        queue.write_buffer(
-            &self.buffers.vertex_tangent,
-            (vertex_range.start * VERTEX_TANGENT_SIZE) as BufferAddress,
-            bytemuck::cast_slice(&mesh.vertex_tangents),
-        );
-        queue.write_buffer(
-            &self.buffers.vertex_normal,
-            (vertex_range.start * VERTEX_NORMAL_SIZE) as BufferAddress,
-            bytemuck::cast_slice(&mesh.vertex_normals),
-        );
-        queue.write_buffer(
-            &self.buffers.vertex_uv0,
-            (vertex_range.start * VERTEX_UV_SIZE) as BufferAddress,
-            bytemuck::cast_slice(&mesh.vertex_uv0),
-        );
-        queue.write_buffer(
-            &self.buffers.vertex_uv1,
-            (vertex_range.start * VERTEX_UV_SIZE) as BufferAddress,
-            bytemuck::cast_slice(&mesh.vertex_uv1),
-        );
-        queue.write_buffer(
-            &self.buffers.vertex_color,
-            (vertex_range.start * VERTEX_COLOR_SIZE) as BufferAddress,
-            bytemuck::cast_slice(&mesh.vertex_colors),
-        );
-        queue.write_buffer(
-            &self.buffers.vertex_joint_index,
-            (vertex_range.start * VERTEX_JOINT_INDEX_SIZE) as BufferAddress,
-            bytemuck::cast_slice(&mesh.vertex_joint_indices),
-        );
-        queue.write_buffer(
-            &self.buffers.vertex_joint_weight,
-            (vertex_range.start * VERTEX_JOINT_WEIGHT_SIZE) as BufferAddress,
-            bytemuck::cast_slice(&mesh.vertex_joint_weights),
-        );
+        let attributes = self.buffers.attributes;
+        let quantization = self.buffers.quantization;
+        let position_dequantization = match quantization.contains(VertexQuantization::POSITIONS) {
+            true => PositionDequantization::from_positions(&mesh.vertex_positions),
+            false => PositionDequantization::IDENTITY,
+        };
+        let positions: Cow<[u8]> = match quantization.contains(VertexQuantization::POSITIONS) {
+            true => Cow::Owned(
+                bytemuck::cast_slice(
+                    &mesh
+                        .vertex_positions
+                        .iter()
+                        .map(|&position| position_dequantization.quantize(position))
+                        .collect::<Vec<_>>(),
+                )
+                .to_vec(),
+            ),
+            false => Cow::Borrowed(bytemuck::cast_slice(&mesh.vertex_positions)),
+        };
+        // The matrices scale quantized positions up, which the inverse
+        // transpose scaling directions down undoes.
+        fn encode_directions(directions: &[Vec3], scale: Vec3, quantization: VertexQuantization) -> Cow<'_, [u8]> {
+            match (
+                quantization.contains(VertexQuantization::NORMALS),
+                quantization.contains(VertexQuantization::POSITIONS),
+            ) {
+                (true, _) => Cow::Owned(
+                    bytemuck::cast_slice(
+                        &directions
+                            .iter()
+                            .map(|&direction| octahedral_encode(direction * scale))
+                            .collect::<Vec<_>>(),
+                    )
+                    .to_vec(),
+                ),
+                (false, true) => Cow::Owned(
+                    bytemuck::cast_slice(
+                        &directions
+                            .iter()
+                            .map(|&direction| (direction * scale).normalize_or_zero())
+                            .collect::<Vec<_>>(),
+                    )
+                    .to_vec(),
+                ),
+                (false, false) => Cow::Borrowed(bytemuck::cast_slice(directions)),
+            }
+        }
+        fn encode_uvs(uvs: &[Vec2], quantization: VertexQuantization) -> Cow<'_, [u8]> {
+            match quantization.contains(VertexQuantization::UVS) {
+                true => Cow::Owned(
+                    bytemuck::cast_slice(
+                        &uvs.iter()
+                            .map(|uv| [f16_from_f32(uv.x), f16_from_f32(uv.y)])
+                            .collect::<Vec<_>>(),
+                    )
+                    .to_vec(),
+                ),
+                false => Cow::Borrowed(bytemuck::cast_slice(uvs)),
+            }
+        }
+        let scale = position_dequantization.scale;
+        let directions = |directions| encode_directions(directions, scale, quantization);
+        let uvs = |uvs| encode_uvs(uvs, quantization);
+        let vertex_data: [(Cow<[u8]>, VertexAttributes); 8] = [
+            (positions, VertexAttributes::empty()),
+            (directions(&mesh.vertex_normals), VertexAttributes::empty()),
+            (directions(&mesh.vertex_tangents), VertexAttributes::TANGENT),
+            (uvs(&mesh.vertex_uv0), VertexAttributes::UV0),
+            (uvs(&mesh.vertex_uv1), VertexAttributes::UV1),
+            (
+                Cow::Borrowed(bytemuck::cast_slice(&mesh.vertex_colors)),
+                VertexAttributes::COLOR,
+            ),
+            (
+                Cow::Borrowed(bytemuck::cast_slice(&mesh.vertex_joint_indices)),
+                VertexAttributes::JOINTS,
+            ),
+            (
+                Cow::Borrowed(bytemuck::cast_slice(&mesh.vertex_joint_weights)),
+                VertexAttributes::JOINTS,
+            ),
+        ];
+        for ((buffer, size, _), (data, needs)) in self.buffers.vertex_buffers().into_iter().zip(vertex_data) {
+            if attributes.contains(needs) {
+                queue.write_buffer(buffer, (vertex_range.start * size) as BufferAddress, &data);
+            }
+        }
         queue.write_buffer(
             &self.buffers.index,
             (index_range.start * INDEX_SIZE) as BufferAddress,
@@ -260,6 +500,7 @@ impl MeshManager {
         );
 
         let bounding_sphere = BoundingSphere::from_mesh(&mesh.vertex_positions);
+        let bvh = MeshBvh::new(&mesh.vertex_positions, &mesh.indices);
 
         let mesh = InternalMesh {
             vertex_range,
@@ -267,6 +508,8 @@ impl MeshManager {
             bounding_sphere,
             num_joints: num_joints as u32,
             skeletons: Vec::new(),
+            bvh,
+            position_dequantization,
         };
 
         self.registry.insert(handle, mesh);
@@ -305,25 +548,41 @@ impl MeshManager {
         // Copies one region of the vertex buffer to another using a compute
         // shader. This is necessary because wgpu's copy_buffer_to_buffer does
         // not allow copies whithin the same buffer.
-        self.buffer_copier.execute(
-            device,
-            encoder,
-            [
-                &self.buffers.vertex_position,
-                &self.buffers.vertex_normal,
-                &self.buffers.vertex_tangent,
-                &self.buffers.vertex_uv0,
-                &self.buffers.vertex_uv1,
-                &self.buffers.vertex_color,
-                &self.buffers.vertex_joint_index,
-                &self.buffers.vertex_joint_weight,
-            ],
-            VertexBufferCopierParams {
-                src_offset: original.vertex_range.start as u32,
-                dst_offset: vertex_range.start as u32,
-                count: vertex_range.len() as u32,
-            },
-        );
+        match self.buffer_copier {
+            Some(ref buffer_copier) => {
+                let vertex_buffers = self.buffers.vertex_buffers();
+                let attributes = self.buffers.attributes;
+                buffer_copier.execute(
+                    device,
+                    encoder,
+                    vertex_buffers.map(|(buffer, _, _)| buffer),
+                    VertexBufferCopierParams {
+                        src_offset: original.vertex_range.start as u32,
+                        dst_offset: vertex_range.start as u32,
+                        count: vertex_range.len() as u32,
+                        words: vertex_buffers.map(|(_, size, needs)| match attributes.contains(needs) {
+                            true => (size / 4) as u32,
+                            false => 0,
+                        }),
+                    },
+                )
+            }
+            // Without compute shaders, bounce the vertices through temporary buffers.
+            None => {
+                let staging = create_buffers(
+                    device,
+                    needed_verts,
+                    0,
+                    1,
+                    BufferUsages::empty(),
+                    self.buffers.attributes,
+                    self.buffers.quantization,
+                );
+                let staging_range = 0..needed_verts;
+                copy_to_new_buffers(encoder, &self.buffers, &staging, &original.vertex_range, &staging_range);
+                copy_to_new_buffers(encoder, &staging, &self.buffers, &staging_range, &vertex_range);
+            }
+        }
 
         vertex_range
     }
@@ -336,6 +595,63 @@ impl MeshManager {
         &self.buffers
     }
 
+    /// Grows the buffers of the attributes the renderer doesn't store so
+    /// draws can use instance indices up to `instances`.
+    pub fn reserve_default_instances(&mut self, device: &Device, instances: usize) {
+        if instances <= self.buffers.default_instances {
+            return;
+        }
+        profiling::scope!("MeshManager::reserve_default_instances");
+
+        let default_instances = instances.next_power_of_two();
+        log::debug!(
+            "Recreating default vertex buffers from {} to {} instances",
+            self.buffers.default_instances,
+            default_instances
+        );
+
+        let attributes = self.buffers.attributes;
+        let new_buffers = create_buffers(
+            device,
+            0,
+            0,
+            default_instances,
+            self.storage_usage,
+            attributes,
+            self.buffers.quantization,
+        );
+        let buffers = &mut self.buffers;
+        for (buffer, new_buffer, needs) in [
+            (
+                &mut buffers.vertex_tangent,
+                new_buffers.vertex_tangent,
+                VertexAttributes::TANGENT,
+            ),
+            (&mut buffers.vertex_uv0, new_buffers.vertex_uv0, VertexAttributes::UV0),
+            (&mut buffers.vertex_uv1, new_buffers.vertex_uv1, VertexAttributes::UV1),
+            (
+                &mut buffers.vertex_color,
+                new_buffers.vertex_color,
+                VertexAttributes::COLOR,
+            ),
+            (
+                &mut buffers.vertex_joint_index,
+                new_buffers.vertex_joint_index,
+                VertexAttributes::JOINTS,
+            ),
+            (
+                &mut buffers.vertex_joint_weight,
+                new_buffers.vertex_joint_weight,
+                VertexAttributes::JOINTS,
+            ),
+        ] {
+            if !attributes.contains(needs) {
+                *buffer = new_buffer;
+            }
+        }
+        buffers.default_instances = default_instances;
+    }
+
     pub fn internal_data(&self, handle: RawMeshHandle) -> &InternalMesh {
         self.registry.get(handle)
     }
@@ -344,12 +660,21 @@ impl MeshManager {
         self.registry.get_mut(handle)
     }
 
+    /// Meshes whose handles were all dropped, removed by the last call to
+    /// [`MeshManager::ready`].
+    pub fn removed(&self) -> &[RawMeshHandle] {
+        &self.removed
+    }
+
     pub fn ready(&mut self) {
         profiling::scope!("MeshManager::ready");
 
         let vertex_alloc = &mut self.vertex_alloc;
         let index_alloc = &mut self.index_alloc;
-        self.registry.remove_all_dead(|_, _, mesh| {
+        let removed = &mut self.removed;
+        removed.clear();
+        self.registry.remove_all_dead(|_, _, handle, mesh| {
+            removed.push(handle);
             if mesh.vertex_range.is_empty() {
                 return;
             }
@@ -372,6 +697,64 @@ impl MeshManager {
         let new_vert_count = (self.vertex_count() + needed_verts as usize).next_power_of_two();
         let new_index_count = (self.index_count() + needed_indices as usize).next_power_of_two();
 
+        self.relocate(
+            device,
+            encoder,
+            object_manager,
+            skeleton_manager,
+            new_vert_count,
+            new_index_count,
+        );
+    }
+
+    /// Moves every mesh, one after the other, into megabuffers just large
+    /// enough to hold them, undoing the fragmentation left by removed meshes.
+    /// Returns how many bytes this freed, which is zero if the buffers were
+    /// already as small as they can get, though the meshes are still packed.
+    pub fn compact(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        object_manager: &mut ObjectManager,
+        skeleton_manager: &mut SkeletonManager,
+    ) -> u64 {
+        profiling::scope!("MeshManager::compact");
+
+        let mut used_verts = 0;
+        let mut used_indices = 0;
+        for mesh in self.registry.values() {
+            used_verts += mesh.vertex_range.len();
+            used_indices += mesh.index_range.len();
+            for &skeleton in &mesh.skeletons {
+                if let Some(ref range) = skeleton_manager.internal_data(skeleton).skeleton_vertex_range {
+                    used_verts += range.len();
+                }
+            }
+        }
+
+        let before = self.allocated_bytes();
+        self.relocate(
+            device,
+            encoder,
+            object_manager,
+            skeleton_manager,
+            used_verts.next_power_of_two().max(STARTING_VERTICES),
+            used_indices.next_power_of_two().max(STARTING_INDICES),
+        );
+        before.saturating_sub(self.allocated_bytes())
+    }
+
+    /// Copies every mesh into new buffers of the given sizes, which must be
+    /// large enough to hold all of them.
+    fn relocate(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        object_manager: &mut ObjectManager,
+        skeleton_manager: &mut SkeletonManager,
+        new_vert_count: usize,
+        new_index_count: usize,
+    ) {
         log::debug!(
             "Recreating vertex buffer from {} to {}",
             self.vertex_count(),
@@ -383,7 +766,15 @@ impl MeshManager {
             new_index_count
         );
 
-        let new_buffers = create_buffers(device, new_vert_count, new_index_count);
+        let new_buffers = create_buffers(
+            device,
+            new_vert_count,
+            new_index_count,
+            self.buffers.default_instances,
+            self.storage_usage,
+            self.buffers.attributes,
+            self.buffers.quantization,
+        );
 
         let mut new_vert_alloc = RangeAllocator::new(0..new_vert_count);
         let mut new_index_alloc = RangeAllocator::new(0..new_index_count);
@@ -405,22 +796,24 @@ impl MeshManager {
                 &new_vert_range,
             );
 
-            // Copy the skeleton data that was copied from this mesh
+            // Copy the skeleton data that was copied from this mesh. Skeletons
+            // skinned in the vertex shader have no copy.
             for skeleton_handle in mesh.skeletons.iter() {
                 let skeleton = skeleton_manager.internal_data(*skeleton_handle);
-                let new_skeleton_vert_range = new_vert_alloc
-                    .allocate_range(skeleton.skeleton_vertex_range.len())
-                    .unwrap();
-                copy_to_new_buffers(
-                    encoder,
-                    &self.buffers,
-                    &new_buffers,
-                    &skeleton.skeleton_vertex_range,
-                    &new_skeleton_vert_range,
-                );
+                let new_skeleton_vert_range = skeleton.skeleton_vertex_range.as_ref().map(|skeleton_vert_range| {
+                    let new_skeleton_vert_range = new_vert_alloc.allocate_range(skeleton_vert_range.len()).unwrap();
+                    copy_to_new_buffers(
+                        encoder,
+                        &self.buffers,
+                        &new_buffers,
+                        skeleton_vert_range,
+                        &new_skeleton_vert_range,
+                    );
+                    new_skeleton_vert_range
+                });
 
                 // Update the cache range data on the skeleton
-                skeleton_manager.set_skeleton_range(*skeleton_handle, &new_skeleton_vert_range, &new_vert_range);
+                skeleton_manager.set_skeleton_range(*skeleton_handle, new_skeleton_vert_range, &new_vert_range);
             }
 
             // Copy indices over to new buffer, adjusting their value by the difference
@@ -447,6 +840,20 @@ impl MeshManager {
         self.index_alloc = new_index_alloc;
     }
 
+    /// Size of the vertex and index megabuffers in bytes, including the
+    /// space not used by any mesh.
+    pub fn allocated_bytes(&self) -> u64 {
+        let vertex_size: usize = self
+            .buffers
+            .vertex_buffers()
+            .iter()
+            .filter(|(_, _, needs)| self.buffers.attributes.contains(*needs))
+            .map(|(_, size, _)| size)
+            .sum();
+
+        (self.vertex_count() * vertex_size + self.index_count() * INDEX_SIZE) as u64
+    }
+
     fn vertex_count(&self) -> usize {
         self.vertex_alloc.initial_range().end
     }
@@ -456,6 +863,8 @@ impl MeshManager {
     }
 }
 
+/// Copies the vertices in `current_range` to `new_range`, skipping the
+/// attributes which only have a default value.
 fn copy_to_new_buffers(
     encoder: &mut CommandEncoder,
     current_buffers: &MeshBuffers,
@@ -463,70 +872,15 @@ fn copy_to_new_buffers(
     current_range: &Range<usize>,
     new_range: &Range<usize>,
 ) {
-    copy_vert(
-        encoder,
-        &current_buffers.vertex_position,
-        &new_buffers.vertex_position,
-        current_range,
-        new_range,
-        VERTEX_POSITION_SIZE,
-    );
-    copy_vert(
-        encoder,
-        &current_buffers.vertex_normal,
-        &new_buffers.vertex_normal,
-        current_range,
-        new_range,
-        VERTEX_NORMAL_SIZE,
-    );
-    copy_vert(
-        encoder,
-        &current_buffers.vertex_tangent,
-        &new_buffers.vertex_tangent,
-        current_range,
-        new_range,
-        VERTEX_TANGENT_SIZE,
-    );
-    copy_vert(
-        encoder,
-        &current_buffers.vertex_uv0,
-        &new_buffers.vertex_uv0,
-        current_range,
-        new_range,
-        VERTEX_UV_SIZE,
-    );
-    copy_vert(
-        encoder,
-        &current_buffers.vertex_uv1,
-        &new_buffers.vertex_uv1,
-        current_range,
-        new_range,
-        VERTEX_UV_SIZE,
-    );
-    copy_vert(
-        encoder,
-        &current_buffers.vertex_color,
-        &new_buffers.vertex_color,
-        current_range,
-        new_range,
-        VERTEX_COLOR_SIZE,
-    );
-    copy_vert(
-        encoder,
-        &current_buffers.vertex_joint_index,
-        &new_buffers.vertex_joint_index,
-        current_range,
-        new_range,
-        VERTEX_JOINT_INDEX_SIZE,
-    );
-    copy_vert(
-        encoder,
-        &current_buffers.vertex_joint_weight,
-        &new_buffers.vertex_joint_weight,
-        current_range,
-        new_range,
-        VERTEX_JOINT_WEIGHT_SIZE,
-    );
+    for ((src, size, needs), (dst, _, _)) in current_buffers
+        .vertex_buffers()
+        .into_iter()
+        .zip(new_buffers.vertex_buffers())
+    {
+        if current_buffers.attributes.contains(needs) {
+            copy_vert(encoder, src, dst, current_range, new_range, size);
+        }
+    }
 }
 
 fn copy_vert(
@@ -549,78 +903,91 @@ fn copy_vert(
     );
 }
 
-fn create_buffers(device: &Device, vertex_count: usize, index_count: usize) -> MeshBuffers {
+fn create_buffers(
+    device: &Device,
+    vertex_count: usize,
+    index_count: usize,
+    default_instances: usize,
+    storage_usage: BufferUsages,
+    attributes: VertexAttributes,
+    quantization: VertexQuantization,
+) -> MeshBuffers {
     profiling::scope!("mesh buffers creation");
 
-    let position_bytes = vertex_count * VERTEX_POSITION_SIZE;
-    let normal_bytes = vertex_count * VERTEX_NORMAL_SIZE;
-    let tangent_bytes = vertex_count * VERTEX_TANGENT_SIZE;
-    let uv_bytes = vertex_count * VERTEX_UV_SIZE;
-    let color_bytes = vertex_count * VERTEX_COLOR_SIZE;
-    let joint_index_bytes = vertex_count * VERTEX_JOINT_INDEX_SIZE;
-    let joint_weight_bytes = vertex_count * VERTEX_JOINT_WEIGHT_SIZE;
-    let index_bytes = index_count * INDEX_SIZE;
-
-    let vertex_position = device.create_buffer(&BufferDescriptor {
-        label: Some("position vertex buffer"),
-        size: position_bytes as BufferAddress,
-        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::VERTEX | BufferUsages::STORAGE,
-        mapped_at_creation: false,
-    });
-
-    let vertex_normal = device.create_buffer(&BufferDescriptor {
-        label: Some("normal vertex buffer"),
-        size: normal_bytes as BufferAddress,
-        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::VERTEX | BufferUsages::STORAGE,
-        mapped_at_creation: false,
-    });
-
-    let vertex_tangent = device.create_buffer(&BufferDescriptor {
-        label: Some("tangent vertex buffer"),
-        size: tangent_bytes as BufferAddress,
-        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::VERTEX | BufferUsages::STORAGE,
-        mapped_at_creation: false,
-    });
-
-    let vertex_uv0 = device.create_buffer(&BufferDescriptor {
-        label: Some("uv0 vertex buffer"),
-        size: uv_bytes as BufferAddress,
-        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::VERTEX | BufferUsages::STORAGE,
-        mapped_at_creation: false,
-    });
-
-    let vertex_uv1 = device.create_buffer(&BufferDescriptor {
-        label: Some("uv1 vertex buffer"),
-        size: uv_bytes as BufferAddress,
-        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::VERTEX | BufferUsages::STORAGE,
-        mapped_at_creation: false,
-    });
-
-    let vertex_color = device.create_buffer(&BufferDescriptor {
-        label: Some("color vertex buffer"),
-        size: color_bytes as BufferAddress,
-        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::VERTEX | BufferUsages::STORAGE,
-        mapped_at_creation: false,
-    });
-
-    let vertex_joint_index = device.create_buffer(&BufferDescriptor {
-        label: Some("joint index vertex buffer"),
-        size: joint_index_bytes as BufferAddress,
-        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::VERTEX | BufferUsages::STORAGE,
-        mapped_at_creation: false,
-    });
+    let usage = BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::VERTEX | storage_usage;
+    let vertex_buffer = |label: &str, size: usize, needs: VertexAttributes, default: &[u8]| {
+        if attributes.contains(needs) {
+            device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size: (vertex_count * size) as BufferAddress,
+                usage,
+                mapped_at_creation: false,
+            })
+        } else {
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some(label),
+                contents: &default.repeat(default_instances),
+                usage,
+            })
+        }
+    };
 
-    let vertex_joint_weight = device.create_buffer(&BufferDescriptor {
-        label: Some("joint weight vertex buffer"),
-        size: joint_weight_bytes as BufferAddress,
-        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::VERTEX | BufferUsages::STORAGE,
-        mapped_at_creation: false,
-    });
+    let vertex_position = vertex_buffer(
+        "position vertex buffer",
+        quantization.position_size(),
+        VertexAttributes::empty(),
+        &[],
+    );
+    let vertex_normal = vertex_buffer(
+        "normal vertex buffer",
+        quantization.normal_size(),
+        VertexAttributes::empty(),
+        &[],
+    );
+    let vertex_tangent = vertex_buffer(
+        "tangent vertex buffer",
+        quantization.normal_size(),
+        VertexAttributes::TANGENT,
+        match quantization.contains(VertexQuantization::NORMALS) {
+            true => bytemuck::bytes_of(&DEFAULT_ENCODED_TANGENT),
+            false => bytemuck::bytes_of(&Vec3::X),
+        },
+    );
+    let vertex_uv0 = vertex_buffer(
+        "uv0 vertex buffer",
+        quantization.uv_size(),
+        VertexAttributes::UV0,
+        &[0; VERTEX_UV_SIZE][..quantization.uv_size()],
+    );
+    let vertex_uv1 = vertex_buffer(
+        "uv1 vertex buffer",
+        quantization.uv_size(),
+        VertexAttributes::UV1,
+        &[0; VERTEX_UV_SIZE][..quantization.uv_size()],
+    );
+    let vertex_color = vertex_buffer(
+        "color vertex buffer",
+        VERTEX_COLOR_SIZE,
+        VertexAttributes::COLOR,
+        &[255; 4],
+    );
+    let vertex_joint_index = vertex_buffer(
+        "joint index vertex buffer",
+        VERTEX_JOINT_INDEX_SIZE,
+        VertexAttributes::JOINTS,
+        &[0; VERTEX_JOINT_INDEX_SIZE],
+    );
+    let vertex_joint_weight = vertex_buffer(
+        "joint weight vertex buffer",
+        VERTEX_JOINT_WEIGHT_SIZE,
+        VertexAttributes::JOINTS,
+        bytemuck::bytes_of(&[1.0_f32, 0.0, 0.0, 0.0]),
+    );
 
     let index = device.create_buffer(&BufferDescriptor {
         label: Some("index buffer"),
-        size: index_bytes as BufferAddress,
-        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::INDEX | BufferUsages::STORAGE,
+        size: (index_count * INDEX_SIZE) as BufferAddress,
+        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST | BufferUsages::INDEX | storage_usage,
         mapped_at_creation: false,
     });
 
@@ -634,5 +1001,8 @@ fn create_buffers(device: &Device, vertex_count: usize, index_count: usize) -> M
         vertex_joint_index,
         vertex_joint_weight,
         index,
+        attributes,
+        quantization,
+        default_instances,
     }
 }
@@ -1,15 +1,27 @@
 use std::{
     any::TypeId,
-    sync::atomic::{AtomicUsize, Ordering},
+    cmp::Ordering,
+    sync::atomic::{self, AtomicUsize},
 };
 
 use crate::{
     managers::{MaterialKeyPair, MaterialManager, MeshManager},
     types::{Object, ObjectHandle},
-    util::{frustum::BoundingSphere, registry::ArchetypicalRegistry},
+    util::{
+        cells::CellGraph,
+        geometry::BoundingSphere,
+        quantize::PositionDequantization,
+        raycast::{self, Ray, RayHit},
+        registry::{ArchetypicalRegistry, ResourceRegistry},
+        snapshot::{ObjectGroupSnapshot, ObjectSnapshot},
+        typedefs::FastHashMap,
+    },
 };
 use glam::{Mat4, Vec3A};
-use rend3_types::{Material, MaterialHandle, ObjectChange, ObjectMeshKind, RawObjectHandle};
+use rend3_types::{
+    Material, MaterialHandle, ObjectChange, ObjectDepthMode, ObjectFade, ObjectGroup, ObjectGroupHandle,
+    ObjectMeshKind, ObjectShadowMode, RawObjectGroupHandle, RawObjectHandle,
+};
 
 use super::SkeletonManager;
 
@@ -24,8 +36,19 @@ pub struct GpuCullingInput {
     pub transform: Mat4,
     // xyz position; w radius
     pub bounding_sphere: BoundingSphere,
+    /// Of the mesh, folded into the matrices the culling pass outputs.
+    pub position_dequantization: PositionDequantization,
+    /// [`ObjectFade::to_shader`] of the fade of the object.
+    pub fade: f32,
+    /// [`InternalSkeleton::joint_offset`](super::InternalSkeleton::joint_offset)
+    /// of the skeleton of the object, or [`NO_JOINTS`].
+    pub joint_offset: u32,
+    pub pad0: [u8; 8],
 }
 
+/// [`GpuCullingInput::joint_offset`] of objects without a skeleton.
+pub const NO_JOINTS: u32 = u32::MAX;
+
 unsafe impl bytemuck::Pod for GpuCullingInput {}
 unsafe impl bytemuck::Zeroable for GpuCullingInput {}
 
@@ -33,22 +56,87 @@ unsafe impl bytemuck::Zeroable for GpuCullingInput {}
 #[repr(C, align(16))]
 #[derive(Debug, Clone)]
 pub struct InternalObject {
+    pub handle: RawObjectHandle,
     pub mesh_kind: ObjectMeshKind,
     pub material_handle: MaterialHandle,
     // Index into the material archetype array
     pub location: Vec3A,
     pub input: GpuCullingInput,
+    /// Layer of the texture arrays of the material the object is drawn with.
+    pub texture_layer: u32,
+    pub depth_mode: ObjectDepthMode,
+    /// Draw order of the object within its archetype, lowest first. Objects
+    /// with the same key are ordered by distance if the pass sorts.
+    pub sort_key: i32,
+    /// Cell of the [`CellGraph`] the object fits in, if any.
+    pub cell: Option<usize>,
+    /// Transform relative to the group of the object. `input.transform` is
+    /// the resulting world transform.
+    pub local_transform: Mat4,
+    pub group: Option<RawObjectGroupHandle>,
+    /// Whether the group of the object is hidden.
+    pub hidden: bool,
+    pub shadow_mode: ObjectShadowMode,
+    pub fade: ObjectFade,
+    /// Whether the object was added this frame, or its world transform
+    /// changed. Skinned objects deform without moving, so this alone doesn't
+    /// make them static.
+    pub moved: bool,
+    /// World transform at the end of the last frame, equal to
+    /// `input.transform` unless the object moved.
+    pub previous_transform: Mat4,
+    /// Name tools find the object by, see [`ObjectManager::find_by_label`].
+    pub label: Option<String>,
 }
 
 impl InternalObject {
     pub fn mesh_location(&self) -> Vec3A {
         self.location + Vec3A::from(self.input.bounding_sphere.center)
     }
+
+    fn find_cell(&self, cells: Option<&CellGraph>) -> Option<usize> {
+        let sphere = self.input.bounding_sphere.apply_transform(self.input.transform);
+        cells?.cell_containing(sphere)
+    }
+
+    /// Updates the world transform, and everything derived from it. Returns
+    /// true if this made the object move for the first time this frame.
+    fn place(&mut self, group_transform: Mat4, cells: Option<&CellGraph>) -> bool {
+        let transform = group_transform * self.local_transform;
+        if transform == self.input.transform {
+            return false;
+        }
+        let started_moving = !self.moved;
+        self.moved = true;
+        self.input.transform = transform;
+        self.location = transform.transform_point3a(Vec3A::ZERO);
+        self.cell = self.find_cell(cells);
+        started_moving
+    }
+}
+
+struct InternalObjectGroup {
+    inner: ObjectGroup,
+    /// Keeps the objects alive as long as the group is.
+    objects: Vec<ObjectHandle>,
+}
+
+fn group_transform(
+    groups: &ResourceRegistry<InternalObjectGroup, ObjectGroup>,
+    group: Option<RawObjectGroupHandle>,
+) -> Mat4 {
+    group.map_or(Mat4::IDENTITY, |group| groups.get(group).inner.transform)
 }
 
 /// Manages objects. That's it. ¯\\\_(ツ)\_/¯
 pub struct ObjectManager {
     registry: ArchetypicalRegistry<MaterialKeyPair, InternalObject, Object>,
+    /// Objects removed by the last call to [`ObjectManager::ready`].
+    removed: Vec<RawObjectHandle>,
+    /// Objects added or moved this frame.
+    moved: Vec<RawObjectHandle>,
+    cells: Option<CellGraph>,
+    groups: ResourceRegistry<InternalObjectGroup, ObjectGroup>,
 }
 impl ObjectManager {
     pub fn new() -> Self {
@@ -56,15 +144,27 @@ impl ObjectManager {
 
         let registry = ArchetypicalRegistry::new();
 
-        Self { registry }
+        Self {
+            registry,
+            removed: Vec::new(),
+            moved: Vec::new(),
+            cells: None,
+            groups: ResourceRegistry::new(),
+        }
     }
 
     pub fn allocate(counter: &AtomicUsize) -> ObjectHandle {
-        let idx = counter.fetch_add(1, Ordering::Relaxed);
+        let idx = counter.fetch_add(1, atomic::Ordering::Relaxed);
 
         ObjectHandle::new(idx)
     }
 
+    pub fn allocate_group(counter: &AtomicUsize) -> ObjectGroupHandle {
+        let idx = counter.fetch_add(1, atomic::Ordering::Relaxed);
+
+        ObjectGroupHandle::new(idx)
+    }
+
     pub fn fill(
         &mut self,
         handle: &ObjectHandle,
@@ -73,44 +173,93 @@ impl ObjectManager {
         skeleton_manager: &SkeletonManager,
         material_manager: &mut MaterialManager,
     ) {
-        let (internal_mesh, vertex_range) = match &object.mesh_kind {
+        let (internal_mesh, vertex_range, joint_offset) = match &object.mesh_kind {
             ObjectMeshKind::Animated(skeleton) => {
                 let skeleton = skeleton_manager.internal_data(skeleton.get_raw());
+                let vertex_range = skeleton.vertex_range(mesh_manager);
                 let mesh = mesh_manager.internal_data_mut(skeleton.mesh_handle.get_raw());
-                (mesh, skeleton.skeleton_vertex_range.clone())
+                (mesh, vertex_range, skeleton.joint_offset)
             }
             ObjectMeshKind::Static(mesh) => {
                 let mesh = mesh_manager.internal_data_mut(mesh.get_raw());
                 let vertex_range = mesh.vertex_range.clone();
-                (mesh, vertex_range)
+                (mesh, vertex_range, NO_JOINTS)
             }
         };
         let bounding_sphere = internal_mesh.bounding_sphere;
+        let position_dequantization = internal_mesh.position_dequantization;
         let index_range = internal_mesh.index_range.clone();
 
         let (material_key, object_list) = material_manager.get_material_key_and_objects(object.material.get_raw());
         object_list.push(handle.get_raw());
 
-        let shader_object = InternalObject {
+        let mut shader_object = InternalObject {
+            handle: handle.get_raw(),
             location: object.transform.transform_point3a(Vec3A::ZERO),
             input: GpuCullingInput {
                 material_index: material_manager.get_internal_index(object.material.get_raw()) as u32,
                 transform: object.transform,
                 bounding_sphere,
+                position_dequantization,
                 start_idx: index_range.start as u32,
                 count: (index_range.end - index_range.start) as u32,
                 vertex_offset: vertex_range.start as i32,
+                fade: ObjectFade::OPAQUE.to_shader(),
+                joint_offset,
+                pad0: [0; 8],
             },
             material_handle: object.material,
             mesh_kind: object.mesh_kind,
+            texture_layer: 0,
+            depth_mode: ObjectDepthMode::Default,
+            sort_key: 0,
+            cell: None,
+            local_transform: object.transform,
+            group: None,
+            hidden: false,
+            shadow_mode: ObjectShadowMode::Default,
+            fade: ObjectFade::OPAQUE,
+            moved: true,
+            previous_transform: object.transform,
+            label: None,
         };
+        shader_object.cell = shader_object.find_cell(self.cells.as_ref());
 
         self.registry.insert(handle, shader_object, material_key);
+        self.moved.push(handle.get_raw());
+    }
+
+    /// Forgets which objects moved during the last frame. Must be called
+    /// before changing any object of a new frame.
+    pub fn begin_frame(&mut self) {
+        for handle in self.moved.drain(..) {
+            let object = self.registry.get_value_mut(handle);
+            object.moved = false;
+            object.previous_transform = object.input.transform;
+        }
     }
 
     pub fn ready(&mut self, material_manager: &mut MaterialManager) {
         profiling::scope!("Object Manager Ready");
+
+        // Objects of dropped groups which are still alive leave the group
+        // where they are, and show up again if it was hidden.
+        let mut ungrouped = Vec::new();
+        self.groups
+            .remove_all_dead(|_, _, _, group| ungrouped.extend(group.objects));
+        for object in ungrouped {
+            let internal = self.registry.get_value_mut(object.get_raw());
+            internal.group = None;
+            internal.local_transform = internal.input.transform;
+            if internal.hidden {
+                self.set_hidden(object.get_raw(), false);
+            }
+        }
+
+        let removed = &mut self.removed;
+        removed.clear();
         self.registry.remove_all_dead(|handle, object| {
+            removed.push(RawObjectHandle::new(handle));
             // Remove from material list
             {
                 let objects = material_manager.get_objects(object.material_handle.get_raw());
@@ -118,6 +267,21 @@ impl ObjectManager {
                 objects.swap_remove(index);
             }
         });
+        let registry = &self.registry;
+        self.moved.retain(|&handle| registry.get_value(handle).is_some());
+    }
+
+    /// Objects whose handles were all dropped, removed by the last call to
+    /// [`ObjectManager::ready`].
+    pub fn removed(&self) -> &[RawObjectHandle] {
+        &self.removed
+    }
+
+    /// Objects added this frame, or whose world transform changed, see
+    /// [`InternalObject::moved`]. Everything else is where it was last frame,
+    /// so what was rendered of it, like shadows, can be reused.
+    pub fn moved(&self) -> &[RawObjectHandle] {
+        &self.moved
     }
 
     pub fn set_material_index(&mut self, handle: RawObjectHandle, index: usize) {
@@ -125,9 +289,10 @@ impl ObjectManager {
         object.input.material_index = index as u32;
     }
 
-    /// Objects contain cached data that stores vertex ranges for the gpu
-    /// culling calls. When the vertex buffers are reallocated all this data is
-    /// invalidated. This function needs to be called to fix it.
+    /// Objects contain cached data that stores vertex ranges and joint offsets
+    /// for the gpu culling calls. When the vertex buffers are reallocated, or
+    /// the joints of skeletons move, all this data is invalidated. This
+    /// function needs to be called to fix it.
     pub fn fix_objects_after_realloc(&mut self, mesh_manager: &MeshManager, skeleton_manager: &SkeletonManager) {
         for object in self.registry.iter_all_values_mut() {
             match &object.mesh_kind {
@@ -136,7 +301,8 @@ impl ObjectManager {
                     let mesh = mesh_manager.internal_data(skeleton.mesh_handle.get_raw());
                     object.input.start_idx = mesh.index_range.start as u32;
                     object.input.count = mesh.index_range.len() as u32;
-                    object.input.vertex_offset = skeleton.skeleton_vertex_range.start as i32;
+                    object.input.vertex_offset = skeleton.vertex_range(mesh_manager).start as i32;
+                    object.input.joint_offset = skeleton.joint_offset;
                 }
                 ObjectMeshKind::Static(mesh_handle) => {
                     let mesh = mesh_manager.internal_data(mesh_handle.get_raw());
@@ -148,14 +314,192 @@ impl ObjectManager {
         }
     }
 
-    pub fn set_key(&mut self, handle: RawObjectHandle, key: MaterialKeyPair) {
+    /// Number of objects, across all material archetypes.
+    pub fn count(&self) -> usize {
+        self.registry.count()
+    }
+
+    /// Moves the object to the archetype of `key`, keeping its depth mode
+    /// and whether it is hidden.
+    pub fn set_key(&mut self, handle: RawObjectHandle, mut key: MaterialKeyPair) {
+        let object = self.registry.get_value_mut(handle);
+        let hidden_bit = if object.hidden { ObjectGroup::HIDDEN_KEY_BIT } else { 0 };
+        key.key = key.key & !(ObjectDepthMode::KEY_MASK | ObjectGroup::HIDDEN_KEY_BIT)
+            | object.depth_mode.to_key_bits()
+            | hidden_bit;
         self.registry.set_key(handle, key);
     }
 
+    fn set_hidden(&mut self, handle: RawObjectHandle, hidden: bool) {
+        self.registry.get_value_mut(handle).hidden = hidden;
+        let key = self.registry.get_key(handle);
+        self.set_key(handle, key);
+    }
+
+    /// Sets the transform of the object relative to its group.
     pub fn set_object_transform(&mut self, handle: RawObjectHandle, transform: Mat4) {
         let object = self.registry.get_value_mut(handle);
-        object.input.transform = transform;
-        object.location = transform.transform_point3a(Vec3A::ZERO)
+        object.local_transform = transform;
+        if object.place(group_transform(&self.groups, object.group), self.cells.as_ref()) {
+            self.moved.push(handle);
+        }
+    }
+
+    pub fn fill_group(&mut self, handle: &ObjectGroupHandle, group: ObjectGroup) {
+        self.groups.insert(
+            handle,
+            InternalObjectGroup {
+                inner: group,
+                objects: Vec::new(),
+            },
+        );
+    }
+
+    /// Moves the object into `group`, or out of its group with `None`. Its
+    /// transform becomes relative to the new group.
+    pub fn set_object_group(&mut self, handle: &ObjectHandle, group: Option<RawObjectGroupHandle>) {
+        let raw = handle.get_raw();
+        if let Some(old) = self.registry.get_value_mut(raw).group {
+            let objects = &mut self.groups.get_mut(old).objects;
+            let index = objects.iter().position(|o| o.get_raw() == raw).unwrap();
+            objects.swap_remove(index);
+        }
+
+        let visible = match group {
+            Some(group) => {
+                let group = self.groups.get_mut(group);
+                group.objects.push(handle.clone());
+                group.inner.visible
+            }
+            None => true,
+        };
+
+        let object = self.registry.get_value_mut(raw);
+        object.group = group;
+        if object.place(group_transform(&self.groups, group), self.cells.as_ref()) {
+            self.moved.push(raw);
+        }
+        if object.hidden == visible {
+            self.set_hidden(raw, !visible);
+        }
+    }
+
+    pub fn set_object_group_transform(&mut self, handle: RawObjectGroupHandle, transform: Mat4) {
+        let group = self.groups.get_mut(handle);
+        group.inner.transform = transform;
+        for object in &group.objects {
+            let raw = object.get_raw();
+            if self.registry.get_value_mut(raw).place(transform, self.cells.as_ref()) {
+                self.moved.push(raw);
+            }
+        }
+    }
+
+    pub fn set_object_group_visible(&mut self, handle: RawObjectGroupHandle, visible: bool) {
+        let group = self.groups.get_mut(handle);
+        if group.inner.visible == visible {
+            return;
+        }
+        group.inner.visible = visible;
+        let objects: Vec<RawObjectHandle> = group.objects.iter().map(ObjectHandle::get_raw).collect();
+        for object in objects {
+            self.set_hidden(object, !visible);
+        }
+    }
+
+    /// Replaces the cells objects are culled by, and moves every object into
+    /// the cell it now fits in.
+    pub fn set_cell_graph(&mut self, cells: Option<CellGraph>) {
+        self.cells = cells;
+        for object in self.registry.iter_all_values_mut() {
+            object.cell = object.find_cell(self.cells.as_ref());
+        }
+    }
+
+    pub fn cell_graph(&self) -> Option<&CellGraph> {
+        self.cells.as_ref()
+    }
+
+    pub fn set_object_texture_layer(&mut self, handle: RawObjectHandle, layer: u32) {
+        self.registry.get_value_mut(handle).texture_layer = layer;
+    }
+
+    pub fn set_object_sort_key(&mut self, handle: RawObjectHandle, sort_key: i32) {
+        self.registry.get_value_mut(handle).sort_key = sort_key;
+    }
+
+    pub fn set_object_shadow_mode(&mut self, handle: RawObjectHandle, shadow_mode: ObjectShadowMode) {
+        self.registry.get_value_mut(handle).shadow_mode = shadow_mode;
+    }
+
+    pub fn set_object_fade(&mut self, handle: RawObjectHandle, fade: ObjectFade) {
+        let object = self.registry.get_value_mut(handle);
+        object.fade = fade;
+        object.input.fade = fade.to_shader();
+    }
+
+    pub fn set_object_label(&mut self, handle: RawObjectHandle, label: Option<String>) {
+        self.registry.get_value_mut(handle).label = label;
+    }
+
+    /// Every live object and group, with owning handles.
+    pub fn snapshot(&self) -> (Vec<ObjectSnapshot>, Vec<ObjectGroupSnapshot>) {
+        let groups: Vec<ObjectGroupSnapshot> = self
+            .groups
+            .iter_handles()
+            .map(|(handle, group)| ObjectGroupSnapshot {
+                handle,
+                group: group.inner,
+            })
+            .collect();
+        let group_handles: FastHashMap<usize, &ObjectGroupHandle> = groups
+            .iter()
+            .map(|group| (group.handle.get_raw().idx, &group.handle))
+            .collect();
+
+        let objects = self
+            .registry
+            .iter_all_handles()
+            .map(|(handle, object)| ObjectSnapshot {
+                handle,
+                object: Object {
+                    mesh_kind: object.mesh_kind.clone(),
+                    material: object.material_handle.clone(),
+                    transform: object.local_transform,
+                },
+                group: object
+                    .group
+                    .and_then(|group| group_handles.get(&group.idx).copied().cloned()),
+                label: object.label.clone(),
+                texture_layer: object.texture_layer,
+                depth_mode: object.depth_mode,
+                sort_key: object.sort_key,
+                shadow_mode: object.shadow_mode,
+                fade: object.fade,
+            })
+            .collect();
+
+        (objects, groups)
+    }
+
+    /// Handles of the live objects labeled `label`, in no particular order.
+    pub fn find_by_label(&self, label: &str) -> Vec<ObjectHandle> {
+        self.registry
+            .iter_all_handles()
+            .filter(|(_, object)| object.label.as_deref() == Some(label))
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    pub fn set_object_depth_mode(&mut self, handle: RawObjectHandle, depth_mode: ObjectDepthMode) {
+        self.registry.get_value_mut(handle).depth_mode = depth_mode;
+        let key = self.registry.get_key(handle);
+        self.set_key(handle, key);
+    }
+
+    /// The object, `None` if it was removed or not added yet.
+    pub fn get_object(&self, handle: RawObjectHandle) -> Option<&InternalObject> {
+        self.registry.get_value(handle)
     }
 
     pub fn get_objects<M: Material>(&self, key: u64) -> &[InternalObject] {
@@ -168,6 +512,36 @@ impl ObjectManager {
             .unwrap_or(&[])
     }
 
+    /// Closest hit of the ray with every object it hits, nearest first.
+    /// Skinned objects are hit in their bind pose.
+    pub fn raycast(&self, ray: &Ray, mesh_manager: &MeshManager, skeleton_manager: &SkeletonManager) -> Vec<RayHit> {
+        profiling::scope!("ObjectManager::raycast");
+
+        let mut hits: Vec<RayHit> = self
+            .registry
+            .iter_all_values()
+            .filter(|object| !object.hidden)
+            .filter_map(|object| {
+                let mesh_handle = match &object.mesh_kind {
+                    ObjectMeshKind::Animated(skeleton) => {
+                        skeleton_manager.internal_data(skeleton.get_raw()).mesh_handle.get_raw()
+                    }
+                    ObjectMeshKind::Static(mesh) => mesh.get_raw(),
+                };
+                let mesh = mesh_manager.internal_data(mesh_handle);
+                raycast::raycast_object(
+                    ray,
+                    object.handle,
+                    object.input.transform,
+                    mesh.bounding_sphere,
+                    &mesh.bvh,
+                )
+            })
+            .collect();
+        hits.sort_by(|left, right| left.distance.partial_cmp(&right.distance).unwrap_or(Ordering::Equal));
+        hits
+    }
+
     pub fn duplicate_object(
         &mut self,
         src_handle: ObjectHandle,
@@ -178,12 +552,30 @@ impl ObjectManager {
         material_manager: &mut MaterialManager,
     ) {
         let src_obj = self.registry.get_value_mut(src_handle.get_raw());
+        let texture_layer = src_obj.texture_layer;
+        let depth_mode = src_obj.depth_mode;
+        let sort_key = src_obj.sort_key;
+        let group = src_obj.group;
+        let shadow_mode = src_obj.shadow_mode;
+        let fade = src_obj.fade;
+        let label = src_obj.label.clone();
         let dst_obj = Object {
             mesh_kind: change.mesh_kind.unwrap_or_else(|| src_obj.mesh_kind.clone()),
             material: change.material.unwrap_or_else(|| src_obj.material_handle.clone()),
-            transform: change.transform.unwrap_or(src_obj.input.transform),
+            transform: change.transform.unwrap_or(src_obj.local_transform),
         };
         self.fill(&dst_handle, dst_obj, mesh_manager, skeleton_manager, material_manager);
+        self.set_object_texture_layer(dst_handle.get_raw(), texture_layer);
+        self.set_object_sort_key(dst_handle.get_raw(), sort_key);
+        self.set_object_shadow_mode(dst_handle.get_raw(), shadow_mode);
+        self.set_object_fade(dst_handle.get_raw(), fade);
+        self.set_object_label(dst_handle.get_raw(), label);
+        if depth_mode != ObjectDepthMode::Default {
+            self.set_object_depth_mode(dst_handle.get_raw(), depth_mode);
+        }
+        if group.is_some() {
+            self.set_object_group(&dst_handle, group);
+        }
     }
 }
 
@@ -12,6 +12,32 @@ use glam::{Mat4, UVec2};
 use rend3_types::{MeshHandle, RawSkeletonHandle, Skeleton, SkeletonHandle};
 use wgpu::{CommandEncoder, Device};
 
+/// Where skinned meshes are deformed by their skeletons, trading memory for
+/// ALU. Chosen by [`RendererSettings::skinning_mode`](crate::RendererSettings::skinning_mode).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SkinningMode {
+    /// Skinned by a compute pass once per frame, into a copy of the vertices
+    /// each skeleton owns in the mesh buffers. Every pass then draws the
+    /// result like any other mesh, at the cost of the memory of the copies.
+    PreSkinned,
+    /// Skinned by the vertex shaders of every pass drawing the mesh, straight
+    /// from the vertices of the mesh. Skeletons cost no vertex memory, but
+    /// the depth prepass, the shadow passes and the forward pass all skin the
+    /// vertices again.
+    ///
+    /// Needs [`VertexAttributes::JOINTS`](crate::managers::VertexAttributes::JOINTS),
+    /// unquantized positions, and vertex shaders reading storage buffers,
+    /// which the downlevel profile lacks. The renderer falls back to
+    /// [`SkinningMode::PreSkinned`] without them.
+    VertexShader,
+}
+
+impl Default for SkinningMode {
+    fn default() -> Self {
+        Self::PreSkinned
+    }
+}
+
 /// Internal representation of a Skeleton
 #[derive(Debug)]
 pub struct InternalSkeleton {
@@ -20,11 +46,31 @@ pub struct InternalSkeleton {
     /// The list of per-joint transformation matrices that will be applied to
     /// vertices.
     pub joint_matrices: Vec<Mat4>,
-    /// The portion of the vertex buffer data owned by this skeleton
-    pub skeleton_vertex_range: Range<usize>,
+    /// The portion of the vertex buffer data owned by this skeleton. None
+    /// with [`SkinningMode::VertexShader`], where the skeleton draws the
+    /// vertices of its mesh.
+    pub skeleton_vertex_range: Option<Range<usize>>,
     /// The vertex ranges that is sent to the GPU Skinning compute shader,
     /// cached here for improved performance.
     pub ranges: GpuVertexRanges,
+    /// Index of the first joint of this skeleton in the joint matrices of
+    /// all skeletons, laid out in the order of [`SkeletonManager::skeletons`].
+    pub joint_offset: u32,
+}
+
+impl InternalSkeleton {
+    /// The vertices objects using this skeleton are drawn from: the copy
+    /// owned by the skeleton, or the vertices of the mesh when skinned in
+    /// the vertex shader.
+    pub fn vertex_range(&self, mesh_manager: &MeshManager) -> Range<usize> {
+        match self.skeleton_vertex_range {
+            Some(ref range) => range.clone(),
+            None => mesh_manager
+                .internal_data(self.mesh_handle.get_raw())
+                .vertex_range
+                .clone(),
+        }
+    }
 }
 
 /// The skeleton and mes vertex ranges, in a format that's suitable to be sent
@@ -37,7 +83,7 @@ pub struct GpuVertexRanges {
     /// The range of the vertex buffer that holds the original mesh.
     pub mesh_range: glam::UVec2,
     /// The range of the vertex buffer that holds the duplicate mesh data, owned
-    /// by the Skeleton
+    /// by the Skeleton. The mesh range when there is no duplicate.
     pub skeleton_range: glam::UVec2,
 }
 
@@ -49,9 +95,10 @@ pub struct SkeletonManager {
     registry: ResourceRegistry<InternalSkeleton, Skeleton>,
     /// The number of joints of all the skeletons in this manager
     global_joint_count: usize,
+    mode: SkinningMode,
 }
 impl SkeletonManager {
-    pub fn new() -> Self {
+    pub fn new(mode: SkinningMode) -> Self {
         profiling::scope!("SkeletonManager::new");
 
         let registry = ResourceRegistry::new();
@@ -59,6 +106,7 @@ impl SkeletonManager {
         Self {
             registry,
             global_joint_count: 0,
+            mode,
         }
     }
 
@@ -89,9 +137,16 @@ impl SkeletonManager {
             skeleton.joint_matrices.len(),
         );
 
+        // Skeletons are appended after all others.
+        let joint_offset = self.global_joint_count as u32;
         self.global_joint_count += num_joints;
 
-        let skeleton_range = mesh_manager.allocate_skeleton_mesh(device, encoder, object_manager, self, &skeleton.mesh);
+        let skeleton_range = match self.mode {
+            SkinningMode::PreSkinned => {
+                Some(mesh_manager.allocate_skeleton_mesh(device, encoder, object_manager, self, &skeleton.mesh))
+            }
+            SkinningMode::VertexShader => None,
+        };
 
         // It is important that we fetch the internal mesh again after calling
         // `allocate_skeleton_mesh`, because that may trigger a reallocation and
@@ -103,8 +158,9 @@ impl SkeletonManager {
         let internal_mesh = mesh_manager.internal_data_mut(skeleton.mesh.get_raw());
         internal_mesh.skeletons.push(handle.get_raw());
         let mesh_range = internal_mesh.vertex_range.clone();
+        let owned_range = skeleton_range.clone().unwrap_or_else(|| mesh_range.clone());
         let input = GpuVertexRanges {
-            skeleton_range: UVec2::new(skeleton_range.start as u32, skeleton_range.end as u32),
+            skeleton_range: UVec2::new(owned_range.start as u32, owned_range.end as u32),
             mesh_range: UVec2::new(mesh_range.start as u32, mesh_range.end as u32),
         };
 
@@ -117,13 +173,19 @@ impl SkeletonManager {
             mesh_handle: skeleton.mesh,
             skeleton_vertex_range: skeleton_range,
             ranges: input,
+            joint_offset,
         };
         self.registry.insert(handle, internal);
     }
 
-    pub fn ready(&mut self, mesh_manager: &mut MeshManager) {
+    /// Removes the skeletons whose handles were all dropped. Returns true if
+    /// this moved the joints of the remaining skeletons, which objects need
+    /// to be told about with [`ObjectManager::fix_objects_after_realloc`].
+    pub fn ready(&mut self, mesh_manager: &mut MeshManager) -> bool {
         profiling::scope!("Skeleton Manager Ready");
-        self.registry.remove_all_dead(|_, handle_idx, skeleton| {
+        let mut removed = false;
+        self.registry.remove_all_dead(|_, handle_idx, _, skeleton| {
+            removed = true;
             self.global_joint_count -= skeleton.joint_matrices.len();
 
             // Clean back references in the mesh data
@@ -132,8 +194,12 @@ impl SkeletonManager {
             mesh.skeletons.swap_remove(index);
 
             // Free the owned region of the vertex buffer
-            mesh_manager.free_skeleton_mesh(skeleton.skeleton_vertex_range);
+            if let Some(range) = skeleton.skeleton_vertex_range {
+                mesh_manager.free_skeleton_mesh(range);
+            }
         });
+
+        removed && assign_joint_offsets(self.registry.values_mut())
     }
 
     pub fn set_joint_matrices(&mut self, handle: RawSkeletonHandle, mut joint_matrices: Vec<Mat4>) {
@@ -163,22 +229,75 @@ impl SkeletonManager {
         self.global_joint_count
     }
 
+    /// Where skinned meshes are deformed.
+    pub fn mode(&self) -> SkinningMode {
+        self.mode
+    }
+
     pub fn set_skeleton_range(
         &mut self,
         handle: RawSkeletonHandle,
-        new_skeleton_vert_range: &Range<usize>,
+        new_skeleton_vert_range: Option<Range<usize>>,
         new_mesh_vert_range: &Range<usize>,
     ) {
         let skeleton = self.registry.get_mut(handle);
-        skeleton.skeleton_vertex_range = new_skeleton_vert_range.clone();
+        let owned_range = new_skeleton_vert_range
+            .clone()
+            .unwrap_or_else(|| new_mesh_vert_range.clone());
+        skeleton.skeleton_vertex_range = new_skeleton_vert_range;
         skeleton.ranges.mesh_range = UVec2::new(new_mesh_vert_range.start as u32, new_mesh_vert_range.end as u32);
-        skeleton.ranges.skeleton_range =
-            UVec2::new(new_skeleton_vert_range.start as u32, new_skeleton_vert_range.end as u32);
+        skeleton.ranges.skeleton_range = UVec2::new(owned_range.start as u32, owned_range.end as u32);
     }
 }
 
 impl Default for SkeletonManager {
     fn default() -> Self {
-        Self::new()
+        Self::new(SkinningMode::default())
+    }
+}
+
+/// Packs the joints of `skeletons` one after the other, in order. Returns
+/// true if any skeleton moved.
+fn assign_joint_offsets<'a>(skeletons: impl Iterator<Item = &'a mut InternalSkeleton>) -> bool {
+    let mut moved = false;
+    let mut joint_offset = 0;
+    for skeleton in skeletons {
+        moved |= skeleton.joint_offset != joint_offset;
+        skeleton.joint_offset = joint_offset;
+        joint_offset += skeleton.joint_matrices.len() as u32;
+    }
+    moved
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, UVec2};
+    use rend3_types::MeshHandle;
+
+    use super::{assign_joint_offsets, GpuVertexRanges, InternalSkeleton};
+
+    fn skeleton(joints: usize, joint_offset: u32) -> InternalSkeleton {
+        InternalSkeleton {
+            mesh_handle: MeshHandle::new(0),
+            joint_matrices: vec![Mat4::IDENTITY; joints],
+            skeleton_vertex_range: None,
+            ranges: GpuVertexRanges {
+                mesh_range: UVec2::ZERO,
+                skeleton_range: UVec2::ZERO,
+            },
+            joint_offset,
+        }
+    }
+
+    #[test]
+    fn joints_pack_after_removals() {
+        // The skeleton with 4 joints at offset 2 was removed, and the last
+        // skeleton swapped into its place.
+        let mut skeletons = [skeleton(2, 0), skeleton(1, 9), skeleton(3, 6)];
+        assert!(assign_joint_offsets(skeletons.iter_mut()));
+        let offsets: Vec<u32> = skeletons.iter().map(|s| s.joint_offset).collect();
+        assert_eq!(offsets, [0, 2, 3]);
+
+        assert!(!assign_joint_offsets(skeletons.iter_mut()));
     }
 }
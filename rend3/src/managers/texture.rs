@@ -1,4 +1,9 @@
-use crate::{profile::ProfileData, types::TextureHandle, util::registry::ResourceRegistry, RendererProfile};
+use crate::{
+    profile::ProfileData,
+    types::TextureHandle,
+    util::{deletion::DeletionQueue, registry::ResourceRegistry},
+    RendererProfile,
+};
 use rend3_types::{RawTextureHandle, TextureFormat, TextureUsages};
 use std::{
     num::NonZeroU32,
@@ -24,7 +29,9 @@ pub const MAX_TEXTURE_COUNT: u32 = 1 << 17;
 
 /// Internal representation of a Texture.
 pub struct InternalTexture {
-    pub texture: Texture,
+    /// Shared with [`StreamingTexture`](crate::util::streaming::StreamingTexture)s,
+    /// which copy into it.
+    pub texture: Arc<Texture>,
     pub desc: TextureDescriptor<'static>,
 }
 
@@ -43,6 +50,8 @@ pub struct TextureManager {
 
     views: Vec<TextureView>,
     registry: ResourceRegistry<InternalTexture, rend3_types::Texture>,
+    /// Textures removed by the last call to [`TextureManager::ready`].
+    removed: Vec<RawTextureHandle>,
 
     dimension: TextureViewDimension,
 }
@@ -71,6 +80,7 @@ impl TextureManager {
             null_view,
             views,
             registry,
+            removed: Vec::new(),
             dimension,
         }
     }
@@ -85,7 +95,7 @@ impl TextureManager {
         &mut self,
         handle: &TextureHandle,
         desc: TextureDescriptor<'static>,
-        texture: Texture,
+        texture: Arc<Texture>,
         view: TextureView,
     ) {
         self.group_dirty = self.group_dirty.map_gpu(|_| true);
@@ -99,13 +109,18 @@ impl TextureManager {
         self.registry.get_index_of(handle)
     }
 
-    pub fn ready(&mut self, device: &Device) -> TextureManagerReadyOutput {
+    /// Removes the textures whose handles were all dropped, retiring them
+    /// into `deletion_queue`, and rebuilds the bind group if needed.
+    pub fn ready(&mut self, device: &Device, deletion_queue: &mut DeletionQueue) -> TextureManagerReadyOutput {
         profiling::scope!("TextureManager::ready");
 
         let views = &mut self.views;
-        self.registry.remove_all_dead(|_, index, _| {
+        let removed = &mut self.removed;
+        removed.clear();
+        self.registry.remove_all_dead(|_, index, handle, texture| {
             // Do the same swap remove move as the registry did
-            views.swap_remove(index);
+            deletion_queue.retire((texture, views.swap_remove(index)));
+            removed.push(handle);
         });
 
         if let ProfileData::Gpu(group_dirty) = self.group_dirty {
@@ -132,6 +147,12 @@ impl TextureManager {
         }
     }
 
+    /// Textures whose handles were all dropped, removed by the last call to
+    /// [`TextureManager::ready`].
+    pub fn removed(&self) -> &[RawTextureHandle] {
+        &self.removed
+    }
+
     pub fn get_internal(&self, handle: RawTextureHandle) -> &InternalTexture {
         self.registry.get(handle)
     }
@@ -148,6 +169,25 @@ impl TextureManager {
         &self.null_view
     }
 
+    /// Estimated size of all textures in bytes, counting every mip level.
+    pub fn allocated_bytes(&self) -> u64 {
+        self.registry
+            .values()
+            .map(|internal| {
+                let info = internal.desc.format.describe();
+                let (block_width, block_height) = info.block_dimensions;
+                (0..internal.desc.mip_level_count)
+                    .filter_map(|mip| internal.desc.mip_level_size(mip))
+                    .map(|size| {
+                        let blocks_x = (size.width + block_width as u32 - 1) / block_width as u32;
+                        let blocks_y = (size.height + block_height as u32 - 1) / block_height as u32;
+                        blocks_x as u64 * blocks_y as u64 * size.depth_or_array_layers as u64 * info.block_size as u64
+                    })
+                    .sum::<u64>()
+            })
+            .sum()
+    }
+
     pub fn gpu_bgl(&self) -> &BindGroupLayout {
         self.layout.as_gpu()
     }
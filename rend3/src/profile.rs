@@ -4,14 +4,17 @@
 pub enum RendererProfile {
     CpuDriven,
     GpuDriven,
+    /// Cpu-driven rendering restricted to what WebGL2 and GLES 3.0 can do: no
+    /// storage buffers or compute shaders, and at most four bind groups.
+    Downlevel,
 }
 
 impl RendererProfile {
     /// Turns a RendererMode into a [`ProfileData`] calling the appropriate
-    /// initalization function.
+    /// initalization function. The downlevel profile uses the cpu data.
     pub fn into_data<C, G>(self, cpu: impl FnOnce() -> C, gpu: impl FnOnce() -> G) -> ProfileData<C, G> {
         match self {
-            Self::CpuDriven => ProfileData::Cpu(cpu()),
+            Self::CpuDriven | Self::Downlevel => ProfileData::Cpu(cpu()),
             Self::GpuDriven => ProfileData::Gpu(gpu()),
         }
     }
@@ -25,6 +28,8 @@ pub enum ProfileData<C, G> {
 }
 #[allow(dead_code)] // Even if these are unused, don't warn
 impl<C, G> ProfileData<C, G> {
+    /// The profile this data was made for. Downlevel data reports as
+    /// [`RendererProfile::CpuDriven`].
     pub fn profile(&self) -> RendererProfile {
         match self {
             Self::Cpu(_) => RendererProfile::CpuDriven,
@@ -1,5 +1,6 @@
+use rend3_types::MeshValidationError;
 use thiserror::Error;
-use wgpu::Features;
+use wgpu::{Extent3d, Features, TextureFormat, TextureSampleType};
 
 /// Enum mapping to each of a device's limit.
 #[derive(Debug)]
@@ -54,3 +55,42 @@ pub enum RendererInitializationError {
     #[error("Requesting a device failed")]
     RequestDeviceFailed,
 }
+
+/// Reason why creating a resource failed, or error reported by the device to
+/// the handler set with
+/// [`Renderer::set_error_handler`](crate::Renderer::set_error_handler).
+#[derive(Error, Debug)]
+pub enum RendererError {
+    #[error(
+        "Texture format {:?} has sample type {:?}, but textures must be sample-able as floats with a linear filter",
+        format,
+        sample_type
+    )]
+    UnsupportedTextureFormat {
+        format: TextureFormat,
+        sample_type: TextureSampleType,
+    },
+    #[error("Texture of size {:?} exceeds the device limit of {}", size, limit)]
+    TextureTooLarge { size: Extent3d, limit: u32 },
+    #[error("Texture has {} mip levels but its size allows at most {}", mip_count, max)]
+    TooManyMipLevels { mip_count: u32, max: u32 },
+    #[error("Texture data is {} bytes but {} are needed", actual, expected)]
+    TextureDataTooSmall { actual: usize, expected: usize },
+    #[error("Mip level {} is out of range of a texture with {} mip levels", mip, mip_count)]
+    MipOutOfRange { mip: u32, mip_count: u32 },
+    #[error("Mesh failed validation")]
+    InvalidMesh(#[from] MeshValidationError),
+    #[error("The device ran out of memory")]
+    OutOfMemory,
+    #[error("The device reported a validation error: {}", description)]
+    Validation { description: String },
+}
+
+impl From<wgpu::Error> for RendererError {
+    fn from(error: wgpu::Error) -> Self {
+        match error {
+            wgpu::Error::OutOfMemory { .. } => Self::OutOfMemory,
+            wgpu::Error::Validation { description, .. } => Self::Validation { description },
+        }
+    }
+}
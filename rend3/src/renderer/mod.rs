@@ -2,31 +2,48 @@ use crate::{
     graph::{GraphTextureStore, ReadyData},
     instruction::{InstructionKind, InstructionStreamPair},
     managers::{
-        CameraManager, DirectionalLightManager, InternalTexture, MaterialManager, MeshManager, ObjectManager,
-        SkeletonManager, TextureManager,
+        CameraManager, DirectionalLightInfo, DirectionalLightManager, InternalTexture, MaterialManager, MeshManager,
+        ObjectManager, SkeletonManager, SkinningMode, TextureManager, VertexAttributes, VertexQuantization,
     },
     types::{
         Camera, DirectionalLight, DirectionalLightChange, DirectionalLightHandle, MaterialHandle, Mesh, MeshHandle,
-        Object, ObjectHandle, Texture, TextureHandle,
+        Object, ObjectGroup, ObjectGroupHandle, ObjectHandle, Texture, TextureHandle,
     },
-    util::mipmap::MipmapGenerator,
-    ExtendedAdapterInfo, InstanceAdapterDevice, RendererInitializationError, RendererProfile,
+    util::{
+        cells::CellGraph,
+        color_space,
+        deletion::DeletionQueue,
+        events::{EventSubscribers, RendererEvent, SubscriptionHandle},
+        mipmap::MipmapGenerator,
+        occlusion::{OcclusionQueries, OcclusionQuery, OcclusionTarget},
+        raycast::{Ray, RayHit},
+        snapshot::RendererSnapshot,
+        streaming::{StreamingTexture, StreamingTextureDescriptor},
+        timings::CpuTimings,
+        upload::{UploadPriority, UploadQueue, UploadTicket},
+        visibility::{VisibilityCamera, VisibilityRecorder},
+    },
+    ExtendedAdapterInfo, InstanceAdapterDevice, RendererError, RendererInitializationError, RendererProfile,
 };
-use glam::Mat4;
+use glam::{Mat4, UVec2, Vec2, Vec3};
 use parking_lot::Mutex;
 use rend3_types::{
-    Handedness, Material, MipmapCount, MipmapSource, ObjectChange, Skeleton, SkeletonHandle, TextureFormat,
+    DepthMode, Handedness, Material, MipmapCount, MipmapSource, ObjectChange, ObjectDepthMode, ObjectFade,
+    ObjectShadowMode, RawObjectHandle, SampleCount, ShadowSettings, Skeleton, SkeletonHandle, TextureFormat,
     TextureFromTexture, TextureUsages,
 };
 use std::{
     num::NonZeroU32,
     panic::Location,
-    sync::{atomic::AtomicUsize, Arc},
+    sync::{
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+        Arc,
+    },
 };
 use wgpu::{
     util::DeviceExt, CommandBuffer, CommandEncoderDescriptor, Device, DownlevelCapabilities, Extent3d, Features,
-    ImageCopyTexture, ImageDataLayout, Limits, Origin3d, Queue, TextureAspect, TextureDescriptor, TextureDimension,
-    TextureSampleType, TextureViewDescriptor, TextureViewDimension,
+    ImageCopyTexture, ImageDataLayout, Limits, Origin3d, Queue, SurfaceError, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureSampleType, TextureView, TextureViewDescriptor, TextureViewDimension,
 };
 use wgpu_profiler::GpuProfiler;
 
@@ -36,6 +53,18 @@ mod setup;
 
 /// Core struct which contains the renderer world. Primary way to interact with
 /// the world.
+///
+/// None of the `add_*` and `update_*` functions wait on the GPU: they either
+/// record an instruction that is applied when the next frame is rendered, or
+/// hand the data straight to the queue. They can be called from async code,
+/// including on wasm where the main thread must never block.
+///
+/// The renderer is `Send + Sync`, so asset loading threads can share the
+/// `Arc<Renderer>` and call `add_mesh`, `add_texture_*`, `add_object` and the
+/// rest directly. Each call returns its handle right away, and the creation is
+/// applied by the next [`Renderer::ready`], in the order each thread made its
+/// calls. Handles can be passed between threads and used in other calls
+/// before the resource they point to is created.
 pub struct Renderer {
     instructions: InstructionStreamPair,
 
@@ -56,14 +85,84 @@ pub struct Renderer {
     pub downlevel: DownlevelCapabilities,
     /// Handedness of all parts of this renderer.
     pub handedness: Handedness,
+    /// Layout of depth in all depth buffers of this renderer.
+    pub depth_mode: DepthMode,
+    /// Vertex attributes meshes are stored with.
+    pub vertex_attributes: VertexAttributes,
+    /// Vertex attributes stored in a smaller format.
+    pub vertex_quantization: VertexQuantization,
+    /// Where skinned meshes are deformed, after falling back from modes the
+    /// renderer can't support.
+    pub skinning_mode: SkinningMode,
 
     /// Identifier allocator.
     current_ident: AtomicUsize,
+    /// MSAA sample count set with [`Renderer::set_sample_count`].
+    sample_count: AtomicU8,
     /// All the lockable data
     pub data_core: Mutex<RendererDataCore>,
 
     /// Tool which generates mipmaps from a texture.
     pub mipmap_generator: MipmapGenerator,
+    /// Objects culling let through in the last rendergraph.
+    pub visibility: VisibilityRecorder,
+    /// Occlusion queries waiting for an occlusion routine.
+    pub occlusion: OcclusionQueries,
+    /// Meshes and textures waiting to be added by priority.
+    pub uploads: UploadQueue,
+    /// Callbacks told about resources being added and removed.
+    pub events: EventSubscribers,
+
+    /// CPU time spent by the last frame.
+    pub(crate) cpu_timings: Mutex<CpuTimings>,
+    /// Error from acquiring the output of the last rendergraph.
+    pub(crate) surface_error: Mutex<Option<SurfaceError>>,
+}
+
+// Worker threads rely on sharing the renderer.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Renderer>();
+};
+
+/// Approximate GPU memory held by the renderer, as returned by
+/// [`Renderer::memory_usage`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Bytes of the vertex and index megabuffers, including unused space.
+    pub mesh_bytes: u64,
+    /// Bytes of all 2D textures, including 2D arrays.
+    pub texture_2d_bytes: u64,
+    /// Bytes of all cube textures.
+    pub texture_cube_bytes: u64,
+    /// Bytes of all 3D textures.
+    pub texture_3d_bytes: u64,
+    /// Number of objects in the scene.
+    pub object_count: usize,
+}
+
+impl MemoryUsage {
+    /// Sum of all the byte counts.
+    pub fn total_bytes(&self) -> u64 {
+        self.mesh_bytes + self.texture_2d_bytes + self.texture_cube_bytes + self.texture_3d_bytes
+    }
+}
+
+/// Settings of a [`Renderer`] which can't change once it is created, as
+/// routines build their pipelines around them.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct RendererSettings {
+    /// Layout of depth in all depth buffers.
+    pub depth_mode: DepthMode,
+    /// Vertex attributes to store, leaving out those the application never
+    /// uses to save memory and bandwidth.
+    pub vertex_attributes: VertexAttributes,
+    /// Vertex attributes to store in a smaller format, trading precision
+    /// for memory and bandwidth.
+    pub vertex_quantization: VertexQuantization,
+    /// Where skinned meshes are deformed, trading the memory of a copy of
+    /// the vertices per skeleton for skinning in every pass.
+    pub skinning_mode: SkinningMode,
 }
 
 /// All the mutex protected data within the renderer
@@ -76,6 +175,11 @@ pub struct RendererDataCore {
     pub d2_texture_manager: TextureManager,
     /// Manages all Cube textures, including bindless bind groups.
     pub d2c_texture_manager: TextureManager,
+    /// Manages all 2D array textures, used by materials with
+    /// [`Material::TEXTURE_ARRAYS`].
+    pub d2a_texture_manager: TextureManager,
+    /// Manages all 3D textures.
+    pub d3_texture_manager: TextureManager,
     /// Manages all materials, including material bind groups when CpuDriven.
     pub material_manager: MaterialManager,
     /// Manages all objects.
@@ -85,6 +189,10 @@ pub struct RendererDataCore {
     /// Manages skeletons, and their owned portion of the MeshManager's buffers
     pub skeleton_manager: SkeletonManager,
 
+    /// Holds on to removed resources until they can be destroyed without
+    /// stalling a frame.
+    pub deletion_queue: DeletionQueue,
+
     /// Stores gpu timing and debug scopes.
     pub profiler: GpuProfiler,
 
@@ -104,7 +212,35 @@ impl Renderer {
         handedness: Handedness,
         aspect_ratio: Option<f32>,
     ) -> Result<Arc<Self>, RendererInitializationError> {
-        setup::create_renderer(iad, handedness, aspect_ratio)
+        Self::new_with_settings(iad, handedness, RendererSettings::default(), aspect_ratio)
+    }
+
+    /// Create a new renderer which lays out depth according to `depth_mode`
+    /// instead of the default reversed-Z with an infinite far plane.
+    ///
+    /// The depth mode can't be changed afterwards, as routines build their
+    /// pipelines around it.
+    pub fn new_with_depth_mode(
+        iad: InstanceAdapterDevice,
+        handedness: Handedness,
+        depth_mode: DepthMode,
+        aspect_ratio: Option<f32>,
+    ) -> Result<Arc<Self>, RendererInitializationError> {
+        let settings = RendererSettings {
+            depth_mode,
+            ..RendererSettings::default()
+        };
+        Self::new_with_settings(iad, handedness, settings, aspect_ratio)
+    }
+
+    /// Create a new renderer with the given [`RendererSettings`].
+    pub fn new_with_settings(
+        iad: InstanceAdapterDevice,
+        handedness: Handedness,
+        settings: RendererSettings,
+        aspect_ratio: Option<f32>,
+    ) -> Result<Arc<Self>, RendererInitializationError> {
+        setup::create_renderer(iad, handedness, settings, aspect_ratio)
     }
 
     /// Adds a 3D mesh to the renderer. This doesn't instantiate it to world. To
@@ -113,8 +249,14 @@ impl Renderer {
     ///
     /// The handle will keep the mesh alive. All objects created will also keep
     /// the mesh alive.
+    ///
+    /// Meshes whose fields were filled in by hand instead of with
+    /// [`MeshBuilder`](crate::types::MeshBuilder) are validated here, as
+    /// out of range indices would otherwise read other meshes.
     #[track_caller]
-    pub fn add_mesh(&self, mesh: Mesh) -> MeshHandle {
+    pub fn add_mesh(&self, mesh: Mesh) -> Result<MeshHandle, RendererError> {
+        mesh.validate()?;
+
         let handle = MeshManager::allocate(&self.current_ident);
 
         self.instructions.push(
@@ -125,7 +267,27 @@ impl Renderer {
             *Location::caller(),
         );
 
-        handle
+        Ok(handle)
+    }
+
+    /// Queues a mesh to be added by a later call to [`Renderer::ready`], once
+    /// no upload with a higher `priority` is waiting and the upload budget
+    /// allows, see [`util::upload`](crate::util::upload).
+    pub fn queue_mesh(&self, mesh: Mesh, priority: UploadPriority) -> UploadTicket<MeshHandle> {
+        self.uploads.queue_mesh(mesh, priority)
+    }
+
+    /// Queues a 2D texture like [`Renderer::queue_mesh`], added with
+    /// [`Renderer::add_texture_2d`] when its turn comes.
+    pub fn queue_texture_2d(&self, texture: Texture, priority: UploadPriority) -> UploadTicket<TextureHandle> {
+        self.uploads.queue_texture_2d(texture, priority)
+    }
+
+    /// Sets how many bytes of queued meshes and textures each call to
+    /// [`Renderer::ready`] uploads, `None` for no limit, the default.
+    /// Resources added directly aren't counted.
+    pub fn set_upload_budget(&self, budget: Option<u64>) {
+        self.uploads.set_budget(budget);
     }
 
     /// Adds a skeleton into the renderer. This combines a [`Mesh`] with a set
@@ -152,10 +314,11 @@ impl Renderer {
     /// The handle will keep the texture alive. All materials created with this
     /// texture will also keep the texture alive.
     #[track_caller]
-    pub fn add_texture_2d(&self, texture: Texture) -> TextureHandle {
+    pub fn add_texture_2d(&self, mut texture: Texture) -> Result<TextureHandle, RendererError> {
         profiling::scope!("Add Texture 2D");
 
-        Self::validation_texture_format(texture.format);
+        texture.format = color_space::resolve_color_space(texture.format, texture.color_space, &mut texture.data);
+        Self::validate_texture_format(texture.format)?;
 
         let handle = TextureManager::allocate(&self.current_ident);
         let size = Extent3d {
@@ -179,6 +342,12 @@ impl Renderer {
             usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::COPY_DST,
         };
 
+        let data_mips = match texture.mip_source {
+            MipmapSource::Uploaded => mip_level_count,
+            MipmapSource::Generated => 1,
+        };
+        self.validate_texture(&desc, size.max_mips(), texture.data.len(), data_mips)?;
+
         let (buffer, tex) = match texture.mip_source {
             MipmapSource::Uploaded => (
                 None,
@@ -227,14 +396,14 @@ impl Renderer {
             InstructionKind::AddTexture {
                 handle: handle.clone(),
                 desc,
-                texture: tex,
+                texture: Arc::new(tex),
                 view,
                 buffer,
-                cube: false,
+                dimension: TextureViewDimension::D2,
             },
             *Location::caller(),
         );
-        handle
+        Ok(handle)
     }
 
     /// Add a 2D texture to the renderer by copying a set of mipmaps from an
@@ -243,7 +412,7 @@ impl Renderer {
     /// The handle will keep the texture alive. All materials created with this
     /// texture will also keep the texture alive.
     #[track_caller]
-    pub fn add_texture_2d_from_texture(&self, texture: TextureFromTexture) -> TextureHandle {
+    pub fn add_texture_2d_from_texture(&self, texture: TextureFromTexture) -> Result<TextureHandle, RendererError> {
         profiling::scope!("Add Texture 2D From Texture");
 
         let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor::default());
@@ -257,11 +426,23 @@ impl Renderer {
             desc: old_texture_desc,
         } = data_core.d2_texture_manager.get_internal(texture.src.get_raw());
 
-        let new_size = old_texture_desc.mip_level_size(texture.start_mip).unwrap();
+        let new_size = old_texture_desc
+            .mip_level_size(texture.start_mip)
+            .ok_or(RendererError::MipOutOfRange {
+                mip: texture.start_mip,
+                mip_count: old_texture_desc.mip_level_count,
+            })?;
 
         let mip_level_count = texture
             .mip_count
             .map_or_else(|| old_texture_desc.mip_level_count - texture.start_mip, |c| c.get());
+        let end_mip = texture.start_mip + mip_level_count;
+        if end_mip > old_texture_desc.mip_level_count {
+            return Err(RendererError::MipOutOfRange {
+                mip: end_mip - 1,
+                mip_count: old_texture_desc.mip_level_count,
+            });
+        }
 
         let desc = TextureDescriptor {
             size: new_size,
@@ -291,21 +472,23 @@ impl Renderer {
                     origin: Origin3d::ZERO,
                     aspect: TextureAspect::All,
                 },
-                old_texture_desc.mip_level_size(old_mip).unwrap(),
+                old_texture_desc
+                    .mip_level_size(old_mip)
+                    .expect("mip levels were checked to be in range"),
             );
         }
         self.instructions.push(
             InstructionKind::AddTexture {
                 handle: handle.clone(),
-                texture: tex,
+                texture: Arc::new(tex),
                 desc,
                 view,
                 buffer: Some(encoder.finish()),
-                cube: false,
+                dimension: TextureViewDimension::D2,
             },
             *Location::caller(),
         );
-        handle
+        Ok(handle)
     }
 
     /// Adds a Cube texture to the renderer. This can be used as a cube
@@ -313,10 +496,11 @@ impl Renderer {
     ///
     /// The handle will keep the texture alive.
     #[track_caller]
-    pub fn add_texture_cube(&self, texture: Texture) -> TextureHandle {
+    pub fn add_texture_cube(&self, mut texture: Texture) -> Result<TextureHandle, RendererError> {
         profiling::scope!("Add Texture Cube");
 
-        Self::validation_texture_format(texture.format);
+        texture.format = color_space::resolve_color_space(texture.format, texture.color_space, &mut texture.data);
+        Self::validate_texture_format(texture.format)?;
 
         let handle = TextureManager::allocate(&self.current_ident);
         let size = Extent3d {
@@ -325,9 +509,15 @@ impl Renderer {
             depth_or_array_layers: 6,
         };
 
+        // The six faces are layers, which don't count towards the mip count.
+        let max_mips = Extent3d {
+            depth_or_array_layers: 1,
+            ..size
+        }
+        .max_mips();
         let mip_level_count = match texture.mip_count {
             MipmapCount::Specific(v) => v.get(),
-            MipmapCount::Maximum => size.max_mips(),
+            MipmapCount::Maximum => max_mips,
         };
 
         let desc = TextureDescriptor {
@@ -339,6 +529,7 @@ impl Renderer {
             format: texture.format,
             usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
         };
+        self.validate_texture(&desc, max_mips, texture.data.len(), mip_level_count)?;
 
         let tex = self.device.create_texture_with_data(&self.queue, &desc, &texture.data);
 
@@ -349,32 +540,404 @@ impl Renderer {
         self.instructions.push(
             InstructionKind::AddTexture {
                 handle: handle.clone(),
-                texture: tex,
+                texture: Arc::new(tex),
                 desc,
                 view,
                 buffer: None,
-                cube: true,
+                dimension: TextureViewDimension::Cube,
             },
             *Location::caller(),
         );
-        handle
+        Ok(handle)
     }
 
-    fn validation_texture_format(format: TextureFormat) {
+    /// Adds a 2D array texture of `layers` layers to the renderer. This can be
+    /// used in a [`Material`] with [`Material::TEXTURE_ARRAYS`] set, which
+    /// lets many objects whose textures differ share one material, each
+    /// picking its layer with
+    /// [`set_object_texture_layer`](Self::set_object_texture_layer).
+    ///
+    /// `texture.data` holds the layers one after the other, each with all its
+    /// mip levels. Mipmaps can't be generated for arrays, so
+    /// [`MipmapSource::Generated`] is treated as uploaded.
+    ///
+    /// The handle will keep the texture alive.
+    #[track_caller]
+    pub fn add_texture_2d_array(&self, mut texture: Texture, layers: u32) -> Result<TextureHandle, RendererError> {
+        profiling::scope!("Add Texture 2D Array");
+
+        texture.format = color_space::resolve_color_space(texture.format, texture.color_space, &mut texture.data);
+        Self::validate_texture_format(texture.format)?;
+
+        let handle = TextureManager::allocate(&self.current_ident);
+        let size = Extent3d {
+            width: texture.size.x,
+            height: texture.size.y,
+            depth_or_array_layers: layers,
+        };
+
+        // Layers don't shrink with the mip levels, unlike the depth of 3D
+        // textures, so they can't count towards the mip count.
+        let max_mips = Extent3d {
+            depth_or_array_layers: 1,
+            ..size
+        }
+        .max_mips();
+        let mip_level_count = match texture.mip_count {
+            MipmapCount::Specific(v) => v.get(),
+            MipmapCount::Maximum => max_mips,
+        };
+
+        let desc = TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: texture.format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::COPY_DST,
+        };
+        self.validate_texture(&desc, max_mips, texture.data.len(), mip_level_count)?;
+
+        let tex = self.device.create_texture_with_data(&self.queue, &desc, &texture.data);
+
+        let view = tex.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..TextureViewDescriptor::default()
+        });
+        self.instructions.push(
+            InstructionKind::AddTexture {
+                handle: handle.clone(),
+                texture: Arc::new(tex),
+                desc,
+                view,
+                buffer: None,
+                dimension: TextureViewDimension::D2Array,
+            },
+            *Location::caller(),
+        );
+        Ok(handle)
+    }
+
+    /// Adds a 3D texture of `depth` slices to the renderer, e.g. a density
+    /// volume for the `VolumeRoutine` of rend3-routine.
+    ///
+    /// `texture.data` holds each mip level one after the other, each with all
+    /// its slices. Mipmaps can't be generated for 3D textures, so
+    /// [`MipmapSource::Generated`] is treated as uploaded.
+    ///
+    /// The handle will keep the texture alive.
+    #[track_caller]
+    pub fn add_texture_3d(&self, mut texture: Texture, depth: u32) -> Result<TextureHandle, RendererError> {
+        profiling::scope!("Add Texture 3D");
+
+        texture.format = color_space::resolve_color_space(texture.format, texture.color_space, &mut texture.data);
+        Self::validate_texture_format(texture.format)?;
+
+        let handle = TextureManager::allocate(&self.current_ident);
+        let size = Extent3d {
+            width: texture.size.x,
+            height: texture.size.y,
+            depth_or_array_layers: depth,
+        };
+
+        let mip_level_count = match texture.mip_count {
+            MipmapCount::Specific(v) => v.get(),
+            MipmapCount::Maximum => size.max_mips(),
+        };
+
+        let desc = TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: texture.format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::COPY_DST,
+        };
+        self.validate_texture(&desc, size.max_mips(), texture.data.len(), mip_level_count)?;
+
+        let tex = self.device.create_texture_with_data(&self.queue, &desc, &texture.data);
+
+        let view = tex.create_view(&TextureViewDescriptor::default());
+        self.instructions.push(
+            InstructionKind::AddTexture {
+                handle: handle.clone(),
+                texture: Arc::new(tex),
+                desc,
+                view,
+                buffer: None,
+                dimension: TextureViewDimension::D3,
+            },
+            *Location::caller(),
+        );
+        Ok(handle)
+    }
+
+    /// Adds a 2D texture with a single mip level that can be rendered into,
+    /// e.g. by [`OutputFrame::View`](crate::util::output::OutputFrame::View)
+    /// with the returned view. This can be used in a [`Material`] like any
+    /// other texture.
+    ///
+    /// The handle will keep the texture alive, as will the view.
+    #[track_caller]
+    pub fn add_texture_2d_render_target(
+        &self,
+        size: UVec2,
+        format: TextureFormat,
+    ) -> Result<(TextureHandle, Arc<TextureView>), RendererError> {
+        profiling::scope!("Add Texture 2D Render Target");
+
+        Self::validate_texture_format(format)?;
+
+        let handle = TextureManager::allocate(&self.current_ident);
+        let desc = TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        };
+        self.validate_texture(&desc, 1, 0, 0)?;
+
+        let tex = self.device.create_texture(&desc);
+
+        let view = tex.create_view(&TextureViewDescriptor::default());
+        let target_view = Arc::new(tex.create_view(&TextureViewDescriptor::default()));
+        self.instructions.push(
+            InstructionKind::AddTexture {
+                handle: handle.clone(),
+                desc,
+                texture: Arc::new(tex),
+                view,
+                buffer: None,
+                dimension: TextureViewDimension::D2,
+            },
+            *Location::caller(),
+        );
+        Ok((handle, target_view))
+    }
+
+    /// Adds a cube texture with a single mip level that can be rendered
+    /// into, which can be used as a skybox or sampled by materials like any
+    /// other cube texture.
+    ///
+    /// Returns a cube view for sampling and a 2D view of each face to render
+    /// into, in the order +X, -X, +Y, -Y, +Z, -Z.
+    #[track_caller]
+    #[allow(clippy::type_complexity)]
+    pub fn add_texture_cube_render_target(
+        &self,
+        size: u32,
+        format: TextureFormat,
+    ) -> Result<(TextureHandle, Arc<TextureView>, [Arc<TextureView>; 6]), RendererError> {
+        let (handle, cube_view, mut mips) = self.add_texture_cube_render_target_with_mips(size, 1, format)?;
+        Ok((handle, cube_view, mips.remove(0)))
+    }
+
+    /// Adds a cube texture with `mip_count` mip levels that can each be
+    /// rendered into, like prefiltered environment maps whose mips hold
+    /// increasingly rough reflections.
+    ///
+    /// Returns a cube view of all the mips for sampling, and for each mip a
+    /// 2D view of each face, in the order +X, -X, +Y, -Y, +Z, -Z. There is
+    /// always at least one mip.
+    #[track_caller]
+    #[allow(clippy::type_complexity)]
+    pub fn add_texture_cube_render_target_with_mips(
+        &self,
+        size: u32,
+        mip_count: u32,
+        format: TextureFormat,
+    ) -> Result<(TextureHandle, Arc<TextureView>, Vec<[Arc<TextureView>; 6]>), RendererError> {
+        profiling::scope!("Add Texture Cube Render Target");
+
+        Self::validate_texture_format(format)?;
+        let mip_count = mip_count.max(1);
+
+        let handle = TextureManager::allocate(&self.current_ident);
+        let desc = TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: mip_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        };
+        let max_mips = Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        }
+        .max_mips();
+        self.validate_texture(&desc, max_mips, 0, 0)?;
+
+        let tex = self.device.create_texture(&desc);
+
+        let cube_desc = TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..TextureViewDescriptor::default()
+        };
+        let view = tex.create_view(&cube_desc);
+        let cube_view = Arc::new(tex.create_view(&cube_desc));
+        let mip_views = (0..mip_count)
+            .map(|mip| {
+                [0, 1, 2, 3, 4, 5].map(|face| {
+                    Arc::new(tex.create_view(&TextureViewDescriptor {
+                        dimension: Some(TextureViewDimension::D2),
+                        base_mip_level: mip,
+                        mip_level_count: NonZeroU32::new(1),
+                        base_array_layer: face,
+                        array_layer_count: NonZeroU32::new(1),
+                        ..TextureViewDescriptor::default()
+                    }))
+                })
+            })
+            .collect();
+        self.instructions.push(
+            InstructionKind::AddTexture {
+                handle: handle.clone(),
+                desc,
+                texture: Arc::new(tex),
+                view,
+                buffer: None,
+                dimension: TextureViewDimension::Cube,
+            },
+            *Location::caller(),
+        );
+        Ok((handle, cube_view, mip_views))
+    }
+
+    /// Adds a 2D texture with a single mip level whose contents are uploaded
+    /// from the CPU every frame, like a video or a webcam feed, see
+    /// [`StreamingTexture`]. This can be used in a [`Material`] like any
+    /// other texture through [`StreamingTexture::handle`].
+    #[track_caller]
+    pub fn create_streaming_texture(
+        &self,
+        desc: StreamingTextureDescriptor,
+    ) -> Result<StreamingTexture, RendererError> {
+        profiling::scope!("Create Streaming Texture");
+
+        Self::validate_texture_format(desc.format)?;
+
+        let handle = TextureManager::allocate(&self.current_ident);
+        let tex_desc = TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: desc.size.x,
+                height: desc.size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: desc.format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        };
+        self.validate_texture(&tex_desc, 1, 0, 0)?;
+
+        let tex = Arc::new(self.device.create_texture(&TextureDescriptor {
+            label: desc.label.as_deref(),
+            ..tex_desc.clone()
+        }));
+        let view = tex.create_view(&TextureViewDescriptor::default());
+        self.instructions.push(
+            InstructionKind::AddTexture {
+                handle: handle.clone(),
+                desc: tex_desc,
+                texture: Arc::clone(&tex),
+                view,
+                buffer: None,
+                dimension: TextureViewDimension::D2,
+            },
+            *Location::caller(),
+        );
+        Ok(StreamingTexture::new(
+            handle,
+            tex,
+            Arc::clone(&self.device),
+            Arc::clone(&self.queue),
+            &desc,
+        ))
+    }
+
+    fn validate_texture_format(format: TextureFormat) -> Result<(), RendererError> {
         let sample_type = format.describe().sample_type;
-        if let TextureSampleType::Float { filterable } = sample_type {
-            if !filterable {
-                panic!(
-                    "Textures formats must allow filtering with a linear filter. {:?} has sample type {:?} which does not.",
-                    format, sample_type
+        match sample_type {
+            TextureSampleType::Float { filterable: true } => Ok(()),
+            _ => Err(RendererError::UnsupportedTextureFormat { format, sample_type }),
+        }
+    }
+
+    /// Checks `desc` against the limits of the device, and that `data_len`
+    /// bytes hold its first `data_mips` mip levels, which wgpu would
+    /// otherwise panic or raise a device error on.
+    fn validate_texture(
+        &self,
+        desc: &TextureDescriptor,
+        max_mips: u32,
+        data_len: usize,
+        data_mips: u32,
+    ) -> Result<(), RendererError> {
+        let size = desc.size;
+        let (limit, over) = match desc.dimension {
+            TextureDimension::D3 => {
+                let limit = self.limits.max_texture_dimension_3d;
+                (
+                    limit,
+                    size.width.max(size.height).max(size.depth_or_array_layers) > limit,
                 )
             }
-        } else {
-            panic!(
-                "Textures formats must be sample-able as floating point. {:?} has sample type {:?}.",
-                format, sample_type
-            )
+            _ => {
+                let limit = self.limits.max_texture_dimension_2d;
+                let layers = size.depth_or_array_layers > self.limits.max_texture_array_layers;
+                (limit, size.width.max(size.height) > limit || layers)
+            }
+        };
+        if over {
+            return Err(RendererError::TextureTooLarge { size, limit });
+        }
+
+        if desc.mip_level_count > max_mips {
+            return Err(RendererError::TooManyMipLevels {
+                mip_count: desc.mip_level_count,
+                max: max_mips,
+            });
         }
+
+        let format = desc.format.describe();
+        let (block_width, block_height) = (format.block_dimensions.0 as u32, format.block_dimensions.1 as u32);
+        let expected: usize = (0..data_mips)
+            .filter_map(|mip| desc.mip_level_size(mip))
+            .map(|mip_size| {
+                let blocks_wide = (mip_size.width + block_width - 1) / block_width;
+                let blocks_high = (mip_size.height + block_height - 1) / block_height;
+                blocks_wide as usize
+                    * blocks_high as usize
+                    * mip_size.depth_or_array_layers as usize
+                    * format.block_size as usize
+            })
+            .sum();
+        if data_len < expected {
+            return Err(RendererError::TextureDataTooSmall {
+                actual: data_len,
+                expected,
+            });
+        }
+
+        Ok(())
     }
 
     /// Adds a material to the renderer. This can be used in an [`Object`].
@@ -389,9 +952,12 @@ impl Renderer {
         self.instructions.push(
             InstructionKind::AddMaterial {
                 handle: handle.clone(),
-                fill_invoke: Box::new(move |material_manager, device, profile, d2_manager, mat_handle| {
-                    material_manager.fill(device, profile, d2_manager, mat_handle, material)
-                }),
+                fill_invoke: Box::new(
+                    move |material_manager, device, profile, d2_manager, d2a_manager, mat_handle| {
+                        let texture_manager = material_texture_manager::<M>(d2_manager, d2a_manager);
+                        material_manager.fill(device, profile, texture_manager, mat_handle, material)
+                    },
+                ),
             },
             *Location::caller(),
         );
@@ -405,8 +971,55 @@ impl Renderer {
             InstructionKind::ChangeMaterial {
                 handle: handle.clone(),
                 change_invoke: Box::new(
-                    move |material_manager, device, profile, d2_manager, object_manager, mat_handle| {
-                        material_manager.update(device, profile, d2_manager, object_manager, mat_handle, material)
+                    move |material_manager, device, profile, d2_manager, d2a_manager, object_manager, mat_handle| {
+                        let texture_manager = material_texture_manager::<M>(d2_manager, d2a_manager);
+                        material_manager.update(device, profile, texture_manager, object_manager, mat_handle, material)
+                    },
+                ),
+            },
+            *Location::caller(),
+        )
+    }
+
+    /// Changes fields of a material in place, e.g. to animate its emissive
+    /// intensity or texture coordinates every frame.
+    ///
+    /// Unlike [`update_material`](Self::update_material), this keeps the bind
+    /// group and buffer of the material. In the CPU profiles only the bytes of
+    /// its data which changed are uploaded, which for a single field is a write
+    /// of a few bytes. The GpuDriven profile uploads the data of every
+    /// material each frame regardless. Changing a texture rebuilds the bind
+    /// group, costing as much as `update_material`.
+    ///
+    /// `modify` runs during the next call to render, so it must not block.
+    #[track_caller]
+    pub fn update_material_with<M: Material>(
+        &self,
+        handle: &MaterialHandle,
+        modify: impl FnOnce(&mut M) + Send + Sync + 'static,
+    ) {
+        self.instructions.push(
+            InstructionKind::ModifyMaterial {
+                handle: handle.clone(),
+                modify_invoke: Box::new(
+                    move |material_manager,
+                          device,
+                          queue,
+                          profile,
+                          d2_manager,
+                          d2a_manager,
+                          object_manager,
+                          mat_handle| {
+                        let texture_manager = material_texture_manager::<M>(d2_manager, d2a_manager);
+                        material_manager.modify(
+                            device,
+                            queue,
+                            profile,
+                            texture_manager,
+                            object_manager,
+                            mat_handle,
+                            modify,
+                        )
                     },
                 ),
             },
@@ -451,7 +1064,8 @@ impl Renderer {
         dst_handle
     }
 
-    /// Move the given object to a new transform location.
+    /// Move the given object to a new transform location. For objects in a
+    /// group, the transform is relative to the group.
     #[track_caller]
     pub fn set_object_transform(&self, handle: &ObjectHandle, transform: Mat4) {
         self.instructions.push(
@@ -463,6 +1077,184 @@ impl Renderer {
         );
     }
 
+    /// Sets the layer of the texture arrays the object samples, when its
+    /// material has [`Material::TEXTURE_ARRAYS`] set. Objects start at layer 0.
+    ///
+    /// Only the CPU profiles pass the layer to shaders.
+    #[track_caller]
+    pub fn set_object_texture_layer(&self, handle: &ObjectHandle, layer: u32) {
+        self.instructions.push(
+            InstructionKind::SetObjectTextureLayer {
+                handle: handle.get_raw(),
+                layer,
+            },
+            *Location::caller(),
+        );
+    }
+
+    /// Sets how the object is depth tested, see [`ObjectDepthMode`]. Objects
+    /// start in [`ObjectDepthMode::Default`].
+    ///
+    /// The mode moves the object to its own archetype, which render routines
+    /// draw with separate pipelines.
+    #[track_caller]
+    pub fn set_object_depth_mode(&self, handle: &ObjectHandle, depth_mode: ObjectDepthMode) {
+        self.instructions.push(
+            InstructionKind::SetObjectDepthMode {
+                handle: handle.get_raw(),
+                depth_mode,
+            },
+            *Location::caller(),
+        );
+    }
+
+    /// Sets the draw order of the object among the objects of its material
+    /// archetype, lowest first. Objects start at 0, and objects with the same
+    /// key keep the usual order, front to back for opaque objects and back to
+    /// front for blended ones.
+    ///
+    /// Useful to draw decals after the surfaces they lie on, or to layer 3D
+    /// UI panels. Keys don't order objects of different archetypes, e.g.
+    /// opaque objects are always drawn before blended ones.
+    #[track_caller]
+    pub fn set_object_sort_key(&self, handle: &ObjectHandle, sort_key: i32) {
+        self.instructions.push(
+            InstructionKind::SetObjectSortKey {
+                handle: handle.get_raw(),
+                sort_key,
+            },
+            *Location::caller(),
+        );
+    }
+
+    /// Sets whether the object casts shadows, see [`ObjectShadowMode`].
+    /// Objects start in [`ObjectShadowMode::Default`].
+    ///
+    /// Only CPU culling applies shadow modes and
+    /// [`ShadowSettings::min_caster_texels`]. With GPU culling every object
+    /// casts shadows.
+    #[track_caller]
+    pub fn set_object_shadow_mode(&self, handle: &ObjectHandle, shadow_mode: ObjectShadowMode) {
+        self.instructions.push(
+            InstructionKind::SetObjectShadowMode {
+                handle: handle.get_raw(),
+                shadow_mode,
+            },
+            *Location::caller(),
+        );
+    }
+
+    /// Draws only part of the object's pixels, see [`ObjectFade`]. Objects
+    /// start at [`ObjectFade::OPAQUE`].
+    ///
+    /// The shadows of the object are dithered the same way, so they fade
+    /// along with it.
+    #[track_caller]
+    pub fn set_object_fade(&self, handle: &ObjectHandle, fade: ObjectFade) {
+        self.instructions.push(
+            InstructionKind::SetObjectFade {
+                handle: handle.get_raw(),
+                fade,
+            },
+            *Location::caller(),
+        );
+    }
+
+    /// Names the object, for tools to find it with
+    /// [`Renderer::find_objects_by_label`]. Duplicates of the object copy its
+    /// label, `None` removes it.
+    #[track_caller]
+    pub fn set_object_label(&self, handle: &ObjectHandle, label: Option<String>) {
+        self.instructions.push(
+            InstructionKind::SetObjectLabel {
+                handle: handle.get_raw(),
+                label,
+            },
+            *Location::caller(),
+        );
+    }
+
+    /// Objects labeled `label` with [`Renderer::set_object_label`], as of the
+    /// last call to [`Renderer::ready`], in no particular order.
+    ///
+    /// The handles keep the objects alive like any other, so tools should
+    /// drop them once done. Must not be called while a rendergraph is
+    /// executing.
+    pub fn find_objects_by_label(&self, label: &str) -> Vec<ObjectHandle> {
+        self.data_core.lock().object_manager.find_by_label(label)
+    }
+
+    /// Adds a group objects can be put in with [`Renderer::set_object_group`],
+    /// to move, hide and remove them with one call.
+    ///
+    /// The group keeps its objects alive. Dropping its handle removes every
+    /// object of the group no other handle keeps alive, the others leave the
+    /// group where they are.
+    #[track_caller]
+    pub fn add_object_group(&self, group: ObjectGroup) -> ObjectGroupHandle {
+        let handle = ObjectManager::allocate_group(&self.current_ident);
+        self.instructions.push(
+            InstructionKind::AddObjectGroup {
+                handle: handle.clone(),
+                group,
+            },
+            *Location::caller(),
+        );
+        handle
+    }
+
+    /// Moves the object into `group`, or out of its group with `None`. The
+    /// transform of the object is kept, and becomes relative to the group.
+    ///
+    /// Duplicates of the object join the same group.
+    #[track_caller]
+    pub fn set_object_group(&self, handle: &ObjectHandle, group: Option<&ObjectGroupHandle>) {
+        self.instructions.push(
+            InstructionKind::SetObjectGroup {
+                handle: handle.clone(),
+                group: group.map(ObjectGroupHandle::get_raw),
+            },
+            *Location::caller(),
+        );
+    }
+
+    /// Sets the transform applied on top of the transform of every object in
+    /// the group.
+    #[track_caller]
+    pub fn set_object_group_transform(&self, handle: &ObjectGroupHandle, transform: Mat4) {
+        self.instructions.push(
+            InstructionKind::SetObjectGroupTransform {
+                handle: handle.get_raw(),
+                transform,
+            },
+            *Location::caller(),
+        );
+    }
+
+    /// Hides or shows every object in the group. Hidden objects are skipped
+    /// by culling, rendering and raycasts.
+    #[track_caller]
+    pub fn set_object_group_visible(&self, handle: &ObjectGroupHandle, visible: bool) {
+        self.instructions.push(
+            InstructionKind::SetObjectGroupVisible {
+                handle: handle.get_raw(),
+                visible,
+            },
+            *Location::caller(),
+        );
+    }
+
+    /// Sets the cells and portals culling of the main camera goes through
+    /// before testing objects against the frustum, see [`crate::util::cells`].
+    /// `None` removes them.
+    ///
+    /// Only CPU culling uses the cells. With GPU culling they are ignored.
+    #[track_caller]
+    pub fn set_cell_graph(&self, cells: Option<CellGraph>) {
+        self.instructions
+            .push(InstructionKind::SetCellGraph { cells }, *Location::caller());
+    }
+
     /// Sets the joint positions for a skeleton. See
     /// [Renderer::set_skeleton_joint_matrices] to set the vertex
     /// transformations directly, without having to supply two separate
@@ -533,6 +1325,47 @@ impl Renderer {
         )
     }
 
+    /// Names the light, reported back by [`Renderer::directional_lights`].
+    #[track_caller]
+    pub fn set_directional_light_label(&self, handle: &DirectionalLightHandle, label: Option<String>) {
+        self.instructions.push(
+            InstructionKind::SetDirectionalLightLabel {
+                handle: handle.get_raw(),
+                label,
+            },
+            *Location::caller(),
+        )
+    }
+
+    /// Every live directional light with its settings and label, as of the
+    /// last call to [`Renderer::ready`].
+    ///
+    /// Must not be called while a rendergraph is executing.
+    pub fn directional_lights(&self) -> Vec<DirectionalLightInfo> {
+        self.data_core.lock().directional_light_manager.lights()
+    }
+
+    /// Sets the shadow distance cap and caster size cutoff of every
+    /// directional light, see [`ShadowSettings`].
+    #[track_caller]
+    pub fn set_shadow_settings(&self, settings: ShadowSettings) {
+        self.instructions
+            .push(InstructionKind::SetShadowSettings { settings }, *Location::caller())
+    }
+
+    /// Sets how many removed textures and materials are destroyed a frame,
+    /// `None` destroying them all as soon as the gpu is done with them.
+    /// Defaults to
+    /// [`DEFAULT_DELETION_BUDGET`](crate::util::deletion::DEFAULT_DELETION_BUDGET).
+    ///
+    /// Lower budgets avoid frame time spikes when unloading large scenes, at
+    /// the cost of their memory being freed over more frames.
+    #[track_caller]
+    pub fn set_deletion_budget(&self, budget: Option<usize>) {
+        self.instructions
+            .push(InstructionKind::SetDeletionBudget { budget }, *Location::caller())
+    }
+
     /// Sets the aspect ratio of the camera. This should correspond with the
     /// aspect ratio of the user.
     #[track_caller]
@@ -548,6 +1381,258 @@ impl Renderer {
             .push(InstructionKind::SetCameraData { data }, *Location::caller())
     }
 
+    /// Takes the error from acquiring the surface during the last rendergraph
+    /// execution, if there was one.
+    ///
+    /// When the surface can't be acquired, the rest of the frame is skipped
+    /// instead of panicking. `Outdated` surfaces, as well as surfaces which
+    /// were acquired but are suboptimal, need to be reconfigured. `Lost`
+    /// surfaces need to be recreated.
+    pub fn take_surface_error(&self) -> Option<SurfaceError> {
+        self.surface_error.lock().take()
+    }
+
+    /// CPU time spent in the parts of the last [`Renderer::ready`] and
+    /// rendergraph execution, see [`CpuTimings`].
+    pub fn cpu_timings(&self) -> CpuTimings {
+        *self.cpu_timings.lock()
+    }
+
+    /// Sets whether culling records the objects it lets through, for
+    /// [`Renderer::last_visible_objects`]. Off by default.
+    ///
+    /// Recording repeats the frustum tests of culling on the CPU, which in the
+    /// GpuDriven profile avoids waiting frames for a readback of the GPU
+    /// culling results.
+    pub fn set_visibility_recording(&self, enabled: bool) {
+        self.visibility.set_enabled(enabled);
+    }
+
+    /// Objects which survived culling against `camera` in the last executed
+    /// rendergraph, sorted by index. Empty unless
+    /// [`Renderer::set_visibility_recording`] is on.
+    ///
+    /// Culling only tests bounding spheres against the frustum, so objects
+    /// hidden behind others still count as visible.
+    pub fn last_visible_objects(&self, camera: VisibilityCamera) -> Vec<RawObjectHandle> {
+        self.visibility.last(camera)
+    }
+
+    /// Starts an occlusion query, answered a frame or two later by the
+    /// occlusion routine of the rendergraph, see
+    /// [`util::occlusion`](crate::util::occlusion).
+    pub fn begin_occlusion_query(&self, target: OcclusionTarget) -> OcclusionQuery {
+        self.occlusion.begin(target)
+    }
+
+    /// Routes errors the device reports outside of any `add_*` call, like
+    /// running out of memory while growing the mesh buffers in
+    /// [`Renderer::ready`] or an invalid pipeline in a custom routine, to
+    /// `handler` instead of panicking.
+    ///
+    /// Replaces any handler set directly on the device. Lost devices aren't
+    /// reported here, they show up as [`SurfaceError::Lost`] from
+    /// [`Renderer::take_surface_error`].
+    pub fn set_error_handler(&self, handler: impl Fn(RendererError) + Send + 'static) {
+        self.device
+            .on_uncaptured_error(move |error| handler(RendererError::from(error)));
+    }
+
+    /// Adds a callback called with every [`RendererEvent`], e.g. to mirror
+    /// objects into a physics world or track which assets are resident.
+    ///
+    /// Events are sent from [`Renderer::ready`] once the instructions of the
+    /// frame are applied. Added events fire once the data is handed to the
+    /// GPU queue, removed events once the last handle is dropped and the
+    /// renderer frees the resource.
+    pub fn subscribe(&self, subscriber: impl Fn(&RendererEvent) + Send + Sync + 'static) -> SubscriptionHandle {
+        self.events.subscribe(subscriber)
+    }
+
+    /// Removes a callback added with [`Renderer::subscribe`].
+    pub fn unsubscribe(&self, handle: SubscriptionHandle) {
+        self.events.unsubscribe(handle)
+    }
+
+    /// MSAA sample count the application renders with, as set by
+    /// [`Renderer::set_sample_count`]. Defaults to [`SampleCount::One`].
+    pub fn sample_count(&self) -> SampleCount {
+        SampleCount::try_from(self.sample_count.load(Ordering::Relaxed)).expect("stored sample count is valid")
+    }
+
+    /// Sets the MSAA sample count rendergraphs should use, e.g. from a
+    /// graphics settings menu, for routines and settings which read it with
+    /// [`Renderer::sample_count`].
+    ///
+    /// Switching doesn't stall: the routines of rend3-routine build their
+    /// pipelines for every sample count up front, and rendergraph targets
+    /// are cached per sample count, so the next graph allocates targets of
+    /// the new count and the ones of the old count are freed a frame later.
+    pub fn set_sample_count(&self, samples: SampleCount) {
+        self.sample_count.store(samples as u8, Ordering::Relaxed);
+    }
+
+    /// Sets whether rendergraphs label their passes and emit debug groups and
+    /// markers, which name every node, pass and draw batch in captures of
+    /// tools like RenderDoc or Xcode. On by default.
+    ///
+    /// Markers cost a little CPU time per draw batch, so they can be turned
+    /// off when not capturing.
+    pub fn set_debug_markers(&self, enabled: bool) {
+        self.data_core.lock().profiler.enable_debug_marker = enabled;
+    }
+
+    /// Approximate GPU memory used by meshes and textures, as of the last
+    /// call to [`Renderer::ready`].
+    ///
+    /// Must not be called while a rendergraph is executing.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let data_core = self.data_core.lock();
+
+        MemoryUsage {
+            mesh_bytes: data_core.mesh_manager.allocated_bytes(),
+            texture_2d_bytes: data_core.d2_texture_manager.allocated_bytes()
+                + data_core.d2a_texture_manager.allocated_bytes(),
+            texture_cube_bytes: data_core.d2c_texture_manager.allocated_bytes(),
+            texture_3d_bytes: data_core.d3_texture_manager.allocated_bytes(),
+            object_count: data_core.object_manager.count(),
+        }
+    }
+
+    /// Packs the meshes of the renderer into megabuffers just large enough
+    /// for them, after many meshes were loaded and removed left holes and
+    /// oversized buffers behind. Returns how many bytes of GPU memory this
+    /// freed, see [`MemoryUsage::mesh_bytes`].
+    ///
+    /// Every mesh is copied, so this takes a while with many meshes; call it
+    /// at points like level transitions. Textures are allocated one by one,
+    /// so they don't fragment and are left alone. Must not be called while a
+    /// rendergraph is executing.
+    pub fn compact(&self) -> u64 {
+        profiling::scope!("Renderer::compact");
+
+        let mut data_core = self.data_core.lock();
+        let data_core = &mut *data_core;
+
+        let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("mesh compaction"),
+        });
+        let reclaimed = data_core.mesh_manager.compact(
+            &self.device,
+            &mut encoder,
+            &mut data_core.object_manager,
+            &mut data_core.skeleton_manager,
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        log::debug!("Compacting meshes freed {} bytes", reclaimed);
+        reclaimed
+    }
+
+    /// Casts a ray against the meshes of all objects, as of the last call
+    /// to [`Renderer::ready`]. Returns the closest hit of every object the ray
+    /// hits, nearest first.
+    ///
+    /// Skinned objects are hit in their bind pose. Must not be called while a
+    /// rendergraph is executing.
+    pub fn raycast(&self, ray: Ray) -> Vec<RayHit> {
+        let data_core = self.data_core.lock();
+
+        data_core
+            .object_manager
+            .raycast(&ray, &data_core.mesh_manager, &data_core.skeleton_manager)
+    }
+
+    /// Ray from the camera through the pixel at `position` of a view
+    /// `resolution` pixels large, origin in the top left, for picking with
+    /// [`Renderer::raycast`]. Uses the camera and aspect ratio as of the last
+    /// call to [`Renderer::ready`], see
+    /// [`CameraManager::world_ray_from_screen`].
+    pub fn world_ray_from_screen(&self, position: Vec2, resolution: Vec2) -> Ray {
+        self.data_core
+            .lock()
+            .camera_manager
+            .world_ray_from_screen(position, resolution)
+    }
+
+    /// Pixel of a view `resolution` pixels large the point `world` lands on,
+    /// `None` if it is behind the camera, see
+    /// [`CameraManager::project_to_screen`].
+    pub fn project_to_screen(&self, world: Vec3, resolution: Vec2) -> Option<Vec2> {
+        self.data_core
+            .lock()
+            .camera_manager
+            .project_to_screen(world, resolution)
+    }
+
+    /// Captures the camera and every live object, object group and
+    /// directional light, as of the last call to [`Renderer::ready`], see
+    /// [`util::snapshot`](crate::util::snapshot).
+    ///
+    /// Must not be called while a rendergraph is executing.
+    pub fn snapshot(&self) -> RendererSnapshot {
+        let data_core = self.data_core.lock();
+        let (objects, groups) = data_core.object_manager.snapshot();
+
+        RendererSnapshot {
+            camera: data_core.camera_manager.get_data(),
+            shadow_settings: data_core.directional_light_manager.settings(),
+            cells: data_core.object_manager.cell_graph().cloned(),
+            objects,
+            groups,
+            directional_lights: data_core.directional_light_manager.lights(),
+        }
+    }
+
+    /// Puts back the settings of everything in `snapshot`, which must have
+    /// been taken from this renderer.
+    ///
+    /// Resources added since the snapshot are left as they are. The mesh and
+    /// material of an object can't be changed in place, so those of the
+    /// snapshot are ignored.
+    #[track_caller]
+    pub fn restore(&self, snapshot: &RendererSnapshot) {
+        self.set_camera_data(snapshot.camera);
+        self.set_shadow_settings(snapshot.shadow_settings);
+        self.set_cell_graph(snapshot.cells.clone());
+
+        for group in &snapshot.groups {
+            self.set_object_group_transform(&group.handle, group.group.transform);
+            self.set_object_group_visible(&group.handle, group.group.visible);
+        }
+
+        for object in &snapshot.objects {
+            let handle = &object.handle;
+            self.set_object_group(handle, object.group.as_ref());
+            self.set_object_transform(handle, object.object.transform);
+            self.set_object_label(handle, object.label.clone());
+            self.set_object_texture_layer(handle, object.texture_layer);
+            self.set_object_depth_mode(handle, object.depth_mode);
+            self.set_object_sort_key(handle, object.sort_key);
+            self.set_object_shadow_mode(handle, object.shadow_mode);
+            self.set_object_fade(handle, object.fade);
+        }
+
+        for light in &snapshot.directional_lights {
+            let DirectionalLight {
+                color,
+                intensity,
+                direction,
+                distance,
+            } = light.light;
+            self.update_directional_light(
+                &light.handle,
+                DirectionalLightChange {
+                    color: Some(color),
+                    intensity: Some(intensity),
+                    direction: Some(direction),
+                    distance: Some(distance),
+                },
+            );
+            self.set_directional_light_label(&light.handle, light.label.clone());
+        }
+    }
+
     /// Render a frame of the scene onto the given output, using the given
     /// RenderRoutine.
     ///
@@ -557,3 +1642,14 @@ impl Renderer {
         ready::ready(self)
     }
 }
+
+/// The texture manager holding the textures of materials of type `M`.
+fn material_texture_manager<'a, M: Material>(
+    d2_manager: &'a mut TextureManager,
+    d2a_manager: &'a mut TextureManager,
+) -> &'a mut TextureManager {
+    match M::TEXTURE_ARRAYS {
+        true => d2a_manager,
+        false => d2_manager,
+    }
+}
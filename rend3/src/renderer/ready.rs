@@ -1,13 +1,80 @@
 use crate::{
     graph::ReadyData,
     instruction::{Instruction, InstructionKind},
+    util::{
+        events::RendererEvent,
+        timings::{CpuTimings, Stopwatch},
+    },
     Renderer,
 };
-use wgpu::{CommandBuffer, CommandEncoderDescriptor};
+use std::time::Duration;
+use wgpu::{CommandBuffer, CommandEncoderDescriptor, Maintain, TextureViewDimension};
+
+/// Part of [`CpuTimings`] instructions count towards.
+#[derive(Copy, Clone)]
+enum Part {
+    Meshes,
+    Textures,
+    Objects,
+    Lights,
+}
+
+impl Part {
+    fn of(kind: &InstructionKind) -> Option<Self> {
+        match kind {
+            InstructionKind::AddMesh { .. }
+            | InstructionKind::AddSkeleton { .. }
+            | InstructionKind::SetSkeletonJointDeltas { .. } => Some(Self::Meshes),
+            InstructionKind::AddTexture { .. }
+            | InstructionKind::AddMaterial { .. }
+            | InstructionKind::ChangeMaterial { .. }
+            | InstructionKind::ModifyMaterial { .. } => Some(Self::Textures),
+            InstructionKind::AddObject { .. }
+            | InstructionKind::SetObjectTransform { .. }
+            | InstructionKind::SetObjectTextureLayer { .. }
+            | InstructionKind::SetObjectDepthMode { .. }
+            | InstructionKind::SetObjectSortKey { .. }
+            | InstructionKind::SetObjectShadowMode { .. }
+            | InstructionKind::SetObjectFade { .. }
+            | InstructionKind::SetObjectLabel { .. }
+            | InstructionKind::AddObjectGroup { .. }
+            | InstructionKind::SetObjectGroup { .. }
+            | InstructionKind::SetObjectGroupTransform { .. }
+            | InstructionKind::SetObjectGroupVisible { .. }
+            | InstructionKind::SetCellGraph { .. }
+            | InstructionKind::DuplicateObject { .. } => Some(Self::Objects),
+            InstructionKind::AddDirectionalLight { .. }
+            | InstructionKind::ChangeDirectionalLight { .. }
+            | InstructionKind::SetDirectionalLightLabel { .. }
+            | InstructionKind::SetShadowSettings { .. } => Some(Self::Lights),
+            InstructionKind::SetDeletionBudget { .. }
+            | InstructionKind::SetAspectRatio { .. }
+            | InstructionKind::SetCameraData { .. } => None,
+        }
+    }
+
+    fn time(self, timings: &mut CpuTimings) -> &mut Duration {
+        match self {
+            Self::Meshes => &mut timings.meshes,
+            Self::Textures => &mut timings.textures,
+            Self::Objects => &mut timings.objects,
+            Self::Lights => &mut timings.lights,
+        }
+    }
+}
 
 pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
     profiling::scope!("Renderer::ready");
 
+    let mut timings = CpuTimings::default();
+    let mut total = Stopwatch::start();
+    let mut watch = Stopwatch::start();
+
+    // Queued uploads become instructions of this frame.
+    let camera = renderer.data_core.lock().camera_manager.location();
+    renderer.uploads.upload(renderer, camera);
+    timings.queued_uploads = watch.lap();
+
     renderer.instructions.swap();
 
     let mut instructions = renderer.instructions.consumer.lock();
@@ -19,12 +86,23 @@ pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
         label: Some("primary encoder"),
     });
 
-    let mut data_core = renderer.data_core.lock();
-    let data_core = &mut *data_core;
+    let mut data_core_guard = renderer.data_core.lock();
+    let data_core = &mut *data_core_guard;
+
+    // Destroys what previous frames removed, before this frame removes more.
+    renderer.device.poll(Maintain::Poll);
+    data_core.deletion_queue.destroy();
+    data_core.object_manager.begin_frame();
+
+    // Skip building the events entirely when nobody listens.
+    let send_events = !renderer.events.is_empty();
+    let mut events = Vec::new();
 
     {
         profiling::scope!("Instruction Processing");
+        watch.lap();
         for Instruction { kind, location: _ } in instructions.drain(..) {
+            let part = Part::of(&kind);
             match kind {
                 InstructionKind::AddMesh { handle, mesh } => {
                     profiling::scope!("Add Mesh");
@@ -41,6 +119,9 @@ pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
                         mesh,
                     );
                     data_core.profiler.end_scope(&mut encoder);
+                    if send_events {
+                        events.push(RendererEvent::MeshAdded(handle.get_raw()));
+                    }
                 }
                 InstructionKind::AddSkeleton { handle, skeleton } => {
                     profiling::scope!("Add Skeleton");
@@ -63,13 +144,18 @@ pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
                     texture,
                     view,
                     buffer,
-                    cube,
+                    dimension,
                 } => {
                     cmd_bufs.extend(buffer);
-                    if cube {
-                        data_core.d2c_texture_manager.fill(&handle, desc, texture, view);
-                    } else {
-                        data_core.d2_texture_manager.fill(&handle, desc, texture, view);
+                    let texture_manager = match dimension {
+                        TextureViewDimension::Cube => &mut data_core.d2c_texture_manager,
+                        TextureViewDimension::D2Array => &mut data_core.d2a_texture_manager,
+                        TextureViewDimension::D3 => &mut data_core.d3_texture_manager,
+                        _ => &mut data_core.d2_texture_manager,
+                    };
+                    texture_manager.fill(&handle, desc, texture, view);
+                    if send_events {
+                        events.push(RendererEvent::TextureAdded(handle.get_raw()));
                     }
                 }
                 InstructionKind::AddMaterial { handle, fill_invoke } => {
@@ -79,6 +165,7 @@ pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
                         &renderer.device,
                         renderer.profile,
                         &mut data_core.d2_texture_manager,
+                        &mut data_core.d2a_texture_manager,
                         &handle,
                     );
                 }
@@ -90,6 +177,21 @@ pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
                         &renderer.device,
                         renderer.profile,
                         &mut data_core.d2_texture_manager,
+                        &mut data_core.d2a_texture_manager,
+                        &mut data_core.object_manager,
+                        &handle,
+                    )
+                }
+                InstructionKind::ModifyMaterial { handle, modify_invoke } => {
+                    profiling::scope!("Modify Material");
+
+                    modify_invoke(
+                        &mut data_core.material_manager,
+                        &renderer.device,
+                        &renderer.queue,
+                        renderer.profile,
+                        &mut data_core.d2_texture_manager,
+                        &mut data_core.d2a_texture_manager,
                         &mut data_core.object_manager,
                         &handle,
                     )
@@ -102,10 +204,46 @@ pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
                         &data_core.skeleton_manager,
                         &mut data_core.material_manager,
                     );
+                    if send_events {
+                        events.push(RendererEvent::ObjectAdded(handle.get_raw()));
+                    }
                 }
                 InstructionKind::SetObjectTransform { handle, transform } => {
                     data_core.object_manager.set_object_transform(handle, transform);
                 }
+                InstructionKind::SetObjectTextureLayer { handle, layer } => {
+                    data_core.object_manager.set_object_texture_layer(handle, layer);
+                }
+                InstructionKind::SetObjectDepthMode { handle, depth_mode } => {
+                    data_core.object_manager.set_object_depth_mode(handle, depth_mode);
+                }
+                InstructionKind::SetObjectSortKey { handle, sort_key } => {
+                    data_core.object_manager.set_object_sort_key(handle, sort_key);
+                }
+                InstructionKind::SetObjectShadowMode { handle, shadow_mode } => {
+                    data_core.object_manager.set_object_shadow_mode(handle, shadow_mode);
+                }
+                InstructionKind::SetObjectFade { handle, fade } => {
+                    data_core.object_manager.set_object_fade(handle, fade);
+                }
+                InstructionKind::SetObjectLabel { handle, label } => {
+                    data_core.object_manager.set_object_label(handle, label);
+                }
+                InstructionKind::AddObjectGroup { handle, group } => {
+                    data_core.object_manager.fill_group(&handle, group);
+                }
+                InstructionKind::SetObjectGroup { handle, group } => {
+                    data_core.object_manager.set_object_group(&handle, group);
+                }
+                InstructionKind::SetObjectGroupTransform { handle, transform } => {
+                    data_core.object_manager.set_object_group_transform(handle, transform);
+                }
+                InstructionKind::SetObjectGroupVisible { handle, visible } => {
+                    data_core.object_manager.set_object_group_visible(handle, visible);
+                }
+                InstructionKind::SetCellGraph { cells } => {
+                    data_core.object_manager.set_cell_graph(cells);
+                }
                 InstructionKind::SetSkeletonJointDeltas { handle, joint_matrices } => {
                     data_core.skeleton_manager.set_joint_matrices(handle, joint_matrices);
                 }
@@ -117,6 +255,15 @@ pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
                         .directional_light_manager
                         .update_directional_light(handle, change);
                 }
+                InstructionKind::SetDirectionalLightLabel { handle, label } => {
+                    data_core.directional_light_manager.set_label(handle, label);
+                }
+                InstructionKind::SetShadowSettings { settings } => {
+                    data_core.directional_light_manager.set_settings(settings);
+                }
+                InstructionKind::SetDeletionBudget { budget } => {
+                    data_core.deletion_queue.set_budget(budget);
+                }
                 InstructionKind::SetAspectRatio { ratio } => data_core.camera_manager.set_aspect_ratio(Some(ratio)),
                 InstructionKind::SetCameraData { data } => {
                     data_core.camera_manager.set_data(data);
@@ -126,6 +273,7 @@ pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
                     dst_handle,
                     change,
                 } => {
+                    let dst_raw = dst_handle.get_raw();
                     data_core.object_manager.duplicate_object(
                         src_handle,
                         dst_handle,
@@ -134,39 +282,127 @@ pub fn ready(renderer: &Renderer) -> (Vec<CommandBuffer>, ReadyData) {
                         &data_core.skeleton_manager,
                         &mut data_core.material_manager,
                     );
+                    if send_events {
+                        events.push(RendererEvent::ObjectAdded(dst_raw));
+                    }
                 }
             }
+            let elapsed = watch.lap();
+            if let Some(part) = part {
+                *part.time(&mut timings) += elapsed;
+            }
         }
     }
 
     // Do these in dependency order
     // Level 3
-    data_core.object_manager.ready(&mut data_core.material_manager);
-
-    // Level 2
-    let d2_texture = data_core.d2_texture_manager.ready(&renderer.device);
-
-    // Level 1
-    // The material manager needs to be able to pull correct internal indices from
-    // the d2 texture manager, so it has to go first.
-    data_core.material_manager.ready(
-        &renderer.device,
-        &renderer.queue,
-        &mut data_core.object_manager,
-        &data_core.d2_texture_manager,
-    );
-
-    // Level 0
-    let d2c_texture = data_core.d2c_texture_manager.ready(&renderer.device);
-    let directional_light_cameras =
+    {
+        profiling::scope!("Object Ready");
+        data_core.object_manager.ready(&mut data_core.material_manager);
+        timings.objects += watch.lap();
+    }
+
+    let (d2_texture, d2c_texture) = {
+        profiling::scope!("Texture Ready");
+
+        // Level 2
+        let d2_texture = data_core
+            .d2_texture_manager
+            .ready(&renderer.device, &mut data_core.deletion_queue);
+
+        // Level 1
+        // The material manager needs to be able to pull correct internal indices from
+        // the d2 texture manager, so it has to go first.
+        data_core.material_manager.ready(
+            &renderer.device,
+            &renderer.queue,
+            &mut data_core.object_manager,
+            &data_core.d2_texture_manager,
+            &mut data_core.deletion_queue,
+        );
+
+        // Level 0
+        let d2c_texture = data_core
+            .d2c_texture_manager
+            .ready(&renderer.device, &mut data_core.deletion_queue);
+        data_core
+            .d2a_texture_manager
+            .ready(&renderer.device, &mut data_core.deletion_queue);
+        data_core
+            .d3_texture_manager
+            .ready(&renderer.device, &mut data_core.deletion_queue);
+        timings.textures += watch.lap();
+
+        (d2_texture, d2c_texture)
+    };
+
+    let directional_light_cameras = {
+        profiling::scope!("Light Ready");
+        let cameras =
+            data_core
+                .directional_light_manager
+                .ready(&renderer.device, &renderer.queue, &data_core.camera_manager);
+        timings.lights += watch.lap();
+        cameras
+    };
+    {
+        profiling::scope!("Mesh Ready");
+        data_core.mesh_manager.ready();
+        // Culled draws use the index of their object as instance index.
+        let object_count = data_core.object_manager.count();
         data_core
-            .directional_light_manager
-            .ready(&renderer.device, &renderer.queue, &data_core.camera_manager);
-    data_core.mesh_manager.ready();
-    data_core.skeleton_manager.ready(&mut data_core.mesh_manager);
+            .mesh_manager
+            .reserve_default_instances(&renderer.device, object_count);
+        if data_core.skeleton_manager.ready(&mut data_core.mesh_manager) {
+            data_core
+                .object_manager
+                .fix_objects_after_realloc(&data_core.mesh_manager, &data_core.skeleton_manager);
+        }
+        timings.meshes += watch.lap();
+    }
+    // Frames up to the last one may still use what was removed, the one about
+    // to be rendered won't.
+    data_core.deletion_queue.submitted(&renderer.queue);
+
+    if send_events {
+        let removed_textures = [
+            &data_core.d2_texture_manager,
+            &data_core.d2c_texture_manager,
+            &data_core.d2a_texture_manager,
+            &data_core.d3_texture_manager,
+        ]
+        .into_iter()
+        .flat_map(|manager| manager.removed());
+        events.extend(
+            (data_core
+                .object_manager
+                .removed()
+                .iter()
+                .copied()
+                .map(RendererEvent::ObjectRemoved))
+            .chain(
+                data_core
+                    .mesh_manager
+                    .removed()
+                    .iter()
+                    .copied()
+                    .map(RendererEvent::MeshRemoved),
+            )
+            .chain(removed_textures.copied().map(RendererEvent::TextureRemoved)),
+        );
+    }
 
     cmd_bufs.push(encoder.finish());
 
+    // Subscribers may call back into the renderer, so they can only run once
+    // nothing is locked anymore.
+    drop(data_core_guard);
+    drop(instructions);
+    renderer.events.dispatch(&events);
+
+    timings.ready = total.lap();
+    *renderer.cpu_timings.lock() = timings;
+
     (
         cmd_bufs,
         ReadyData {
@@ -3,29 +3,59 @@ use crate::{
     instruction::InstructionStreamPair,
     managers::{
         CameraManager, DirectionalLightManager, MaterialManager, MeshManager, ObjectManager, SkeletonManager,
-        TextureManager,
+        SkinningMode, TextureManager, VertexAttributes, VertexQuantization,
     },
-    renderer::RendererDataCore,
-    util::mipmap::MipmapGenerator,
-    InstanceAdapterDevice, Renderer, RendererInitializationError,
+    renderer::{RendererDataCore, RendererSettings},
+    util::{
+        deletion::DeletionQueue, events::EventSubscribers, mipmap::MipmapGenerator, occlusion::OcclusionQueries,
+        timings::CpuTimings, upload::UploadQueue, visibility::VisibilityRecorder,
+    },
+    InstanceAdapterDevice, Renderer, RendererInitializationError, RendererProfile,
 };
 use parking_lot::Mutex;
-use rend3_types::{Camera, Handedness, TextureFormat};
-use std::sync::{atomic::AtomicUsize, Arc};
+use rend3_types::{Camera, Handedness, SampleCount, TextureFormat};
+use std::sync::{
+    atomic::{AtomicU8, AtomicUsize},
+    Arc,
+};
 use wgpu::TextureViewDimension;
 
 pub fn create_renderer(
     iad: InstanceAdapterDevice,
     handedness: Handedness,
+    settings: RendererSettings,
     aspect_ratio: Option<f32>,
 ) -> Result<Arc<Renderer>, RendererInitializationError> {
     profiling::scope!("Renderer::new");
 
+    let RendererSettings {
+        depth_mode,
+        vertex_attributes,
+        vertex_quantization,
+        skinning_mode,
+    } = settings;
+
+    let skinning_mode = match skinning_mode {
+        SkinningMode::VertexShader if iad.profile == RendererProfile::Downlevel => {
+            log::warn!("Vertex shader skinning needs storage buffers, pre-skinning instead");
+            SkinningMode::PreSkinned
+        }
+        SkinningMode::VertexShader if !vertex_attributes.contains(VertexAttributes::JOINTS) => {
+            log::warn!("The renderer stores no joints to skin in the vertex shader, pre-skinning instead");
+            SkinningMode::PreSkinned
+        }
+        SkinningMode::VertexShader if vertex_quantization.contains(VertexQuantization::POSITIONS) => {
+            log::warn!("Quantized positions can't be skinned in the vertex shader, pre-skinning instead");
+            SkinningMode::PreSkinned
+        }
+        mode => mode,
+    };
+
     let features = iad.device.features();
     let limits = iad.device.limits();
     let downlevel = iad.adapter.get_downlevel_properties();
 
-    let camera_manager = CameraManager::new(Camera::default(), handedness, aspect_ratio);
+    let camera_manager = CameraManager::new(Camera::default(), handedness, depth_mode, aspect_ratio);
 
     let d2_texture_manager = TextureManager::new(
         &iad.device,
@@ -39,11 +69,25 @@ pub fn create_renderer(
         limits.max_sampled_textures_per_shader_stage,
         TextureViewDimension::Cube,
     );
-    let mesh_manager = MeshManager::new(&iad.device);
+    // Arrays are only ever bound by material bind groups, so they never get
+    // a bindless bind group, whatever the profile.
+    let d2a_texture_manager = TextureManager::new(
+        &iad.device,
+        RendererProfile::CpuDriven,
+        limits.max_sampled_textures_per_shader_stage,
+        TextureViewDimension::D2Array,
+    );
+    let d3_texture_manager = TextureManager::new(
+        &iad.device,
+        RendererProfile::CpuDriven,
+        limits.max_sampled_textures_per_shader_stage,
+        TextureViewDimension::D3,
+    );
+    let mesh_manager = MeshManager::new(&iad.device, iad.profile, vertex_attributes, vertex_quantization);
     let material_manager = MaterialManager::new(&iad.device, iad.profile);
     let object_manager = ObjectManager::new();
-    let directional_light_manager = DirectionalLightManager::new(&iad.device);
-    let skeleton_manager = SkeletonManager::new();
+    let directional_light_manager = DirectionalLightManager::new(&iad.device, iad.profile);
+    let skeleton_manager = SkeletonManager::new(skinning_mode);
 
     let mipmap_generator = MipmapGenerator::new(
         &iad.device,
@@ -70,21 +114,36 @@ pub fn create_renderer(
         limits,
         downlevel,
         handedness,
+        depth_mode,
+        vertex_attributes,
+        vertex_quantization,
+        skinning_mode,
 
         current_ident: AtomicUsize::new(0),
+        sample_count: AtomicU8::new(SampleCount::One as u8),
         data_core: Mutex::new(RendererDataCore {
             camera_manager,
             mesh_manager,
             d2_texture_manager,
             d2c_texture_manager,
+            d2a_texture_manager,
+            d3_texture_manager,
             material_manager,
             object_manager,
             directional_light_manager,
             skeleton_manager,
+            deletion_queue: DeletionQueue::new(),
             profiler,
             graph_texture_store: GraphTextureStore::new(),
         }),
 
         mipmap_generator,
+        visibility: VisibilityRecorder::new(),
+        occlusion: OcclusionQueries::new(),
+        uploads: UploadQueue::new(),
+        events: EventSubscribers::new(),
+
+        cpu_timings: Mutex::new(CpuTimings::default()),
+        surface_error: Mutex::new(None),
     }))
 }
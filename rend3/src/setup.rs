@@ -3,7 +3,7 @@ use std::sync::Arc;
 use arrayvec::ArrayVec;
 use wgpu::{
     Adapter, AdapterInfo, Backend, Backends, BufferAddress, Device, DeviceDescriptor, DeviceType, Features, Instance,
-    Limits, Queue,
+    Limits, PowerPreference, Queue,
 };
 
 #[allow(unused_imports)]
@@ -36,6 +36,9 @@ pub const GPU_DRIVEN_REQUIRED_FEATURES: Features = {
 /// Features required to run in the GpuDriven profile.
 pub const CPU_DRIVEN_REQUIRED_FEATURES: Features = Features::from_bits_truncate(0);
 
+/// Features required to run in the Downlevel profile.
+pub const DOWNLEVEL_REQUIRED_FEATURES: Features = Features::from_bits_truncate(0);
+
 /// Features that rend3 can use if it they are available, but we don't require.
 pub const OPTIONAL_FEATURES: Features = Features::from_bits_truncate(
     Features::DEPTH_CLIP_CONTROL.bits()
@@ -51,6 +54,7 @@ pub fn check_features(profile: RendererProfile, device: Features) -> Result<Feat
     let required = match profile {
         RendererProfile::GpuDriven => GPU_DRIVEN_REQUIRED_FEATURES,
         RendererProfile::CpuDriven => CPU_DRIVEN_REQUIRED_FEATURES,
+        RendererProfile::Downlevel => DOWNLEVEL_REQUIRED_FEATURES,
     };
     let optional = OPTIONAL_FEATURES & device;
     let missing = required - device;
@@ -123,6 +127,38 @@ pub const CPU_REQUIRED_LIMITS: Limits = Limits {
     max_compute_workgroups_per_dimension: 65535,
 };
 
+/// Limits required to run in the Downlevel profile. Fits within the limits of
+/// WebGL2.
+pub const DOWNLEVEL_REQUIRED_LIMITS: Limits = Limits {
+    max_texture_dimension_1d: 2048,
+    max_texture_dimension_2d: 2048,
+    max_texture_dimension_3d: 256,
+    max_texture_array_layers: 256,
+    max_bind_groups: 4,
+    max_dynamic_uniform_buffers_per_pipeline_layout: 1,
+    max_dynamic_storage_buffers_per_pipeline_layout: 0,
+    max_sampled_textures_per_shader_stage: 10,
+    max_samplers_per_shader_stage: 2,
+    max_storage_buffers_per_shader_stage: 0,
+    max_storage_textures_per_shader_stage: 0,
+    max_uniform_buffers_per_shader_stage: 4,
+    max_uniform_buffer_binding_size: MAX_UNIFORM_BUFFER_BINDING_SIZE as u32,
+    max_storage_buffer_binding_size: 0,
+    max_vertex_buffers: 6,
+    max_vertex_attributes: 6,
+    max_vertex_buffer_array_stride: 128,
+    max_push_constant_size: 0,
+    min_uniform_buffer_offset_alignment: 256,
+    min_storage_buffer_offset_alignment: 256,
+    max_inter_stage_shader_components: 60,
+    max_compute_workgroup_storage_size: 0,
+    max_compute_invocations_per_workgroup: 0,
+    max_compute_workgroup_size_x: 0,
+    max_compute_workgroup_size_y: 0,
+    max_compute_workgroup_size_z: 0,
+    max_compute_workgroups_per_dimension: 0,
+};
+
 fn check_limit_unlimited(d: u32, r: u32, ty: LimitType) -> Result<u32, RendererInitializationError> {
     if d < r {
         Err(RendererInitializationError::LowDeviceLimit {
@@ -153,8 +189,18 @@ pub fn check_limits(profile: RendererProfile, device_limits: &Limits) -> Result<
     let required_limits = match profile {
         RendererProfile::GpuDriven => GPU_REQUIRED_LIMITS,
         RendererProfile::CpuDriven => CPU_REQUIRED_LIMITS,
+        RendererProfile::Downlevel => DOWNLEVEL_REQUIRED_LIMITS,
     };
 
+    check_required_limits(&required_limits, device_limits)
+}
+
+/// Check that the given limit set meets all the required limits. Returns the
+/// limits of the device.
+pub fn check_required_limits(
+    required_limits: &Limits,
+    device_limits: &Limits,
+) -> Result<Limits, RendererInitializationError> {
     Ok(Limits {
         max_texture_dimension_1d: check_limit_unlimited(
             device_limits.max_texture_dimension_1d,
@@ -324,6 +370,16 @@ impl<T> PotentialAdapter<T> {
             profile = RendererProfile::CpuDriven;
         }
 
+        // Adapters without storage buffers or compute shaders (WebGL2, old GLES)
+        // only run the downlevel profile.
+        if (features.is_err() || limits.is_err() || desired_profile == Some(RendererProfile::Downlevel))
+            && desired_profile.map_or(true, |p| p == RendererProfile::Downlevel)
+        {
+            features = check_features(RendererProfile::Downlevel, inner_features);
+            limits = check_limits(RendererProfile::Downlevel, &inner_limits);
+            profile = RendererProfile::Downlevel;
+        }
+
         Ok(PotentialAdapter {
             inner,
             info,
@@ -397,17 +453,77 @@ pub struct InstanceAdapterDevice {
     pub info: ExtendedAdapterInfo,
 }
 
+/// Choices used by [`create_iad_with_settings`] to pick an adapter and create
+/// the device.
+#[derive(Debug, Clone)]
+pub struct IadSettings {
+    /// Only use adapters of this backend.
+    pub backend: Option<Backend>,
+    /// Only use adapters whose name contains this string, ignoring case.
+    pub device_name: Option<String>,
+    /// Use this profile instead of the best one the adapter supports.
+    pub profile: Option<RendererProfile>,
+    /// Whether discrete or integrated GPUs are preferred when both are present.
+    pub power_preference: PowerPreference,
+    /// Allow software adapters like llvmpipe or WARP. They are only used when
+    /// no hardware adapter is usable.
+    pub allow_software: bool,
+    /// Features the adapter must support. Adapters which don't are skipped.
+    pub required_features: Features,
+    /// Features requested during device creation even if the adapter doesn't
+    /// advertise them.
+    ///
+    /// **NOTE:** Some adapters will not advertise all of its supported
+    /// features.
+    pub additional_features: Features,
+    /// Limits the adapter must support, on top of the limits required by the
+    /// profile. Adapters which don't are skipped.
+    pub required_limits: Option<Limits>,
+}
+
+impl Default for IadSettings {
+    fn default() -> Self {
+        Self {
+            backend: None,
+            device_name: None,
+            profile: None,
+            power_preference: PowerPreference::HighPerformance,
+            allow_software: true,
+            required_features: Features::empty(),
+            additional_features: Features::empty(),
+            required_limits: None,
+        }
+    }
+}
+
 /// Creates an Instance/Adapter/Device/Queue using the given choices. Tries to
 /// get the best combination.
 ///
 /// **NOTE:** Some adapters will not advertise all of its supported features.
 /// The `additional_features` parameter can be used to explicitly request
 /// additional features during device creation.
+///
+/// See [`create_iad_with_settings`] for more options.
 pub async fn create_iad(
     desired_backend: Option<Backend>,
     desired_device: Option<String>,
     desired_profile: Option<RendererProfile>,
     additional_features: Option<Features>,
+) -> Result<InstanceAdapterDevice, RendererInitializationError> {
+    create_iad_with_settings(&IadSettings {
+        backend: desired_backend,
+        device_name: desired_device,
+        profile: desired_profile,
+        additional_features: additional_features.unwrap_or_else(Features::empty),
+        ..IadSettings::default()
+    })
+    .await
+}
+
+/// Creates an Instance/Adapter/Device/Queue, picking the best adapter allowed
+/// by the given settings.
+pub async fn create_iad_with_settings(
+    settings: &IadSettings,
 ) -> Result<InstanceAdapterDevice, RendererInitializationError> {
     profiling::scope!("create_iad");
     #[cfg(not(target_arch = "wasm32"))]
@@ -436,7 +552,7 @@ pub async fn create_iad(
         #[cfg(target_arch = "wasm32")]
         let adapters = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
+                power_preference: settings.power_preference,
                 force_fallback_adapter: false,
                 compatible_surface: None,
             })
@@ -448,7 +564,12 @@ pub async fn create_iad(
             let info = adapter.get_info();
             let limits = adapter.limits();
             let features = adapter.features();
-            let potential = PotentialAdapter::new(adapter, info, limits, features, desired_profile);
+            let supports_required = features.contains(settings.required_features)
+                && settings
+                    .required_limits
+                    .as_ref()
+                    .map_or(true, |required| check_required_limits(required, &limits).is_ok());
+            let potential = PotentialAdapter::new(adapter, info, limits, features, settings.profile);
 
             log::info!(
                 "{:?} Adapter {}: {:#?}",
@@ -457,14 +578,14 @@ pub async fn create_iad(
                 potential.as_ref().map(|p| &p.info)
             );
 
-            let desired = if let Some(ref desired_device) = desired_device {
-                potential
+            let desired = potential.as_ref().map_or(false, |p| {
+                let name_matches = settings
+                    .device_name
                     .as_ref()
-                    .map(|i| i.info.name.to_lowercase().contains(desired_device))
-                    .unwrap_or(false)
-            } else {
-                true
-            };
+                    .map_or(true, |name| p.info.name.to_lowercase().contains(&name.to_lowercase()));
+                let software_allowed = settings.allow_software || p.info.device_type != DeviceType::Cpu;
+                name_matches && software_allowed && supports_required
+            });
 
             if let (Ok(potential), true) = (potential, desired) {
                 log::debug!("Adapter usable in the {:?} profile", potential.profile);
@@ -477,17 +598,20 @@ pub async fn create_iad(
     }
 
     for backend_adapters in valid_adapters.values_mut() {
-        backend_adapters.sort_by_key(|a: &PotentialAdapter<Adapter>| match a.info.device_type {
-            DeviceType::DiscreteGpu => 0,
-            DeviceType::IntegratedGpu => 1,
-            DeviceType::VirtualGpu => 2,
-            DeviceType::Cpu => 3,
-            DeviceType::Other => 4,
+        backend_adapters.sort_by_key(|a: &PotentialAdapter<Adapter>| {
+            match (a.info.device_type, settings.power_preference) {
+                (DeviceType::DiscreteGpu, PowerPreference::HighPerformance) => 0,
+                (DeviceType::IntegratedGpu, PowerPreference::LowPower) => 0,
+                (DeviceType::DiscreteGpu | DeviceType::IntegratedGpu, _) => 1,
+                (DeviceType::VirtualGpu, _) => 2,
+                (DeviceType::Cpu, _) => 3,
+                (DeviceType::Other, _) => 4,
+            }
         });
     }
 
     for backend in &default_backend_order {
-        if let Some(desired_backend) = desired_backend {
+        if let Some(desired_backend) = settings.backend {
             if desired_backend != *backend {
                 log::debug!("Skipping unwanted backend {:?}", backend);
                 continue;
@@ -511,7 +635,8 @@ pub async fn create_iad(
                         label: None,
                         features: adapter
                             .features
-                            .union(additional_features.unwrap_or_else(Features::empty)),
+                            .union(settings.required_features)
+                            .union(settings.additional_features),
                         limits: adapter.limits,
                     },
                     None,
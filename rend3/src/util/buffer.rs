@@ -1,8 +1,17 @@
 //! Automatic management of Power-of-Two sized buffers.
 
 use crate::util::typedefs::SsoString;
-use std::{ops::Deref, sync::Arc};
-use wgpu::{Buffer, BufferAddress, BufferDescriptor, BufferUsages, Device, Queue};
+use std::{
+    future::Future,
+    ops::Deref,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+use wgpu::{Buffer, BufferAddress, BufferAsyncError, BufferDescriptor, BufferUsages, Device, Queue};
+
+/// Future returned by `BufferSlice::map_async`, boxed so it can be stored.
+pub type MapFuture = Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send>>;
 
 /// Creates, fills, and automatically resizes a power-of-two sized buffer.
 pub struct WrappedPotBuffer {
@@ -100,6 +109,24 @@ fn will_resize_inner(current: BufferAddress, desired: BufferAddress, minimum: Bu
     }
 }
 
+/// Polls a future once, without waiting for it to be woken up. Mappings
+/// finish while the device is polled, so poll it first.
+pub fn poll_now<T>(future: &mut Pin<Box<dyn Future<Output = T> + Send>>) -> Poll<T> {
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    // SAFETY: The waker does nothing, so its vtable trivially upholds the
+    // contract of RawWaker.
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    future.as_mut().poll(&mut Context::from_waker(&waker))
+}
+
 #[cfg(test)]
 mod test {
     use super::will_resize_inner;
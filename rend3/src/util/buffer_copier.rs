@@ -18,6 +18,9 @@ pub struct VertexBufferCopierParams {
     pub dst_offset: u32,
     /// Size of the copy
     pub count: u32,
+    /// Size of a vertex in 4 byte words, for each of the buffers. Buffers
+    /// with zero words are left alone.
+    pub words: [u32; 8],
 }
 
 // SAFETY: The type only contains u32 values.
@@ -67,7 +70,7 @@ impl VertexBufferCopier {
                     binding: 8,
                     visibility: wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
                         has_dynamic_offset: false,
                         min_binding_size: None,
                     },
@@ -116,7 +119,7 @@ impl VertexBufferCopier {
         let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: None,
             contents: bytemuck::cast_slice(&[params]),
-            usage: wgpu::BufferUsages::UNIFORM,
+            usage: wgpu::BufferUsages::STORAGE,
         });
 
         let bind_group = device.create_bind_group(&BindGroupDescriptor {
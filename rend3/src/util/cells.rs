@@ -0,0 +1,245 @@
+//! Cells and portals, so culling of indoor scenes can skip the rooms the
+//! camera can't see into before testing objects against the frustum.
+//!
+//! A [`CellGraph`] splits the scene into boxes, the cells, connected by
+//! quads, the portals, usually doorways and windows. Set one with
+//! [`Renderer::set_cell_graph`](crate::Renderer::set_cell_graph). Each object
+//! whose bounding sphere fits in a cell belongs to it, the rest are always
+//! tested against the frustum.
+//!
+//! From the cell holding the camera, [`CellGraph::visible_cells`] walks
+//! through the portals in view, narrowing the part of the screen the next
+//! cell can be seen through at each portal. Objects of cells it doesn't reach
+//! are culled. When the camera is outside of every cell, every cell counts as
+//! visible.
+
+use glam::{Mat4, Vec2, Vec3, Vec4Swizzles};
+use thiserror::Error;
+
+use crate::util::geometry::BoundingSphere;
+
+/// An axis aligned box of the scene, e.g. a room.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cell {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Cell {
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn contains_sphere(&self, sphere: BoundingSphere) -> bool {
+        (sphere.center - sphere.radius).cmpge(self.min).all() && (sphere.center + sphere.radius).cmple(self.max).all()
+    }
+}
+
+/// An opening between two cells, bounded by a quad.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Portal {
+    /// Indices of the two cells the portal connects.
+    pub cells: [usize; 2],
+    /// Corners of the quad, in order around it.
+    pub corners: [Vec3; 4],
+}
+
+#[derive(Debug, Error)]
+pub enum CellGraphError {
+    #[error("Portal {portal} connects cell {cell}, but there are only {count} cells")]
+    CellOutOfRange { portal: usize, cell: usize, count: usize },
+}
+
+/// Screen space rectangle in normalized device coordinates.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Rect {
+    min: Vec2,
+    max: Vec2,
+}
+
+impl Rect {
+    fn screen() -> Self {
+        Self {
+            min: Vec2::splat(-1.0),
+            max: Vec2::splat(1.0),
+        }
+    }
+
+    fn intersect(self, other: Self) -> Option<Self> {
+        let min = self.min.max(other.min);
+        let max = self.max.min(other.max);
+        min.cmple(max).all().then(|| Self { min, max })
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+/// Cells connected by portals, see the [module](self) docs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellGraph {
+    cells: Vec<Cell>,
+    portals: Vec<Portal>,
+    /// Portals leading out of each cell.
+    cell_portals: Vec<Vec<usize>>,
+}
+
+impl CellGraph {
+    pub fn new(cells: Vec<Cell>, portals: Vec<Portal>) -> Result<Self, CellGraphError> {
+        let mut cell_portals = vec![Vec::new(); cells.len()];
+        for (portal_idx, portal) in portals.iter().enumerate() {
+            for &cell in &portal.cells {
+                cell_portals
+                    .get_mut(cell)
+                    .ok_or(CellGraphError::CellOutOfRange {
+                        portal: portal_idx,
+                        cell,
+                        count: cells.len(),
+                    })?
+                    .push(portal_idx);
+            }
+        }
+        Ok(Self {
+            cells,
+            portals,
+            cell_portals,
+        })
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn portals(&self) -> &[Portal] {
+        &self.portals
+    }
+
+    /// First cell containing `point`.
+    pub fn cell_at(&self, point: Vec3) -> Option<usize> {
+        self.cells.iter().position(|cell| cell.contains_point(point))
+    }
+
+    /// First cell the whole of `sphere` fits in.
+    pub fn cell_containing(&self, sphere: BoundingSphere) -> Option<usize> {
+        self.cells.iter().position(|cell| cell.contains_sphere(sphere))
+    }
+
+    /// Which cells can be seen by a camera at `location` with the
+    /// projection `view_proj`, indexed by cell. `None` when the camera is
+    /// outside of every cell.
+    pub fn visible_cells(&self, location: Vec3, view_proj: Mat4) -> Option<Vec<bool>> {
+        let start = self.cell_at(location)?;
+
+        // Part of the screen each cell is seen through. Rects only ever grow,
+        // and are built from a finite set of portal rects, so this settles.
+        let mut rects: Vec<Option<Rect>> = vec![None; self.cells.len()];
+        rects[start] = Some(Rect::screen());
+        let mut stack = vec![start];
+        while let Some(cell) = stack.pop() {
+            let rect = rects[cell].expect("cells are only pushed once they have a rect");
+            for &portal_idx in &self.cell_portals[cell] {
+                let portal = &self.portals[portal_idx];
+                let other = match portal.cells {
+                    [a, b] if a == cell => b,
+                    [a, _] => a,
+                };
+                let narrowed = match project_portal(portal, view_proj).and_then(|r| r.intersect(rect)) {
+                    Some(narrowed) => narrowed,
+                    None => continue,
+                };
+                let merged = rects[other].map_or(narrowed, |existing| existing.union(narrowed));
+                if rects[other] != Some(merged) {
+                    rects[other] = Some(merged);
+                    stack.push(other);
+                }
+            }
+        }
+
+        Some(rects.iter().map(Option::is_some).collect())
+    }
+}
+
+/// Screen rect covered by the portal, or `None` when it is entirely behind
+/// the camera.
+fn project_portal(portal: &Portal, view_proj: Mat4) -> Option<Rect> {
+    let clip = portal.corners.map(|corner| view_proj * corner.extend(1.0));
+    let behind = clip.iter().filter(|c| c.w <= f32::EPSILON).count();
+    match behind {
+        4 => None,
+        // A portal crossing the camera plane can cover any part of the
+        // screen, e.g. while walking through a doorway.
+        1..=3 => Some(Rect::screen()),
+        _ => {
+            let ndc = clip.map(|c| c.xy() / c.w);
+            let min = ndc.iter().fold(Vec2::splat(f32::INFINITY), |a, &b| a.min(b));
+            let max = ndc.iter().fold(Vec2::splat(f32::NEG_INFINITY), |a, &b| a.max(b));
+            Rect { min, max }.intersect(Rect::screen())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Vec3};
+
+    use super::{Cell, CellGraph, Portal};
+
+    /// Three rooms along +Z, each 10 units deep, joined by doorways.
+    fn corridor() -> CellGraph {
+        let cell = |z: f32| Cell {
+            min: Vec3::new(-5.0, 0.0, z),
+            max: Vec3::new(5.0, 5.0, z + 10.0),
+        };
+        let door = |cells, x: f32, z: f32| Portal {
+            cells,
+            corners: [
+                Vec3::new(x - 1.0, 0.0, z),
+                Vec3::new(x + 1.0, 0.0, z),
+                Vec3::new(x + 1.0, 3.0, z),
+                Vec3::new(x - 1.0, 3.0, z),
+            ],
+        };
+        CellGraph::new(
+            vec![cell(0.0), cell(10.0), cell(20.0)],
+            vec![door([0, 1], 0.0, 10.0), door([1, 2], 4.0, 20.0)],
+        )
+        .unwrap()
+    }
+
+    fn view_proj(eye: Vec3, target: Vec3) -> Mat4 {
+        Mat4::perspective_lh(1.0, 1.0, 0.1, 100.0) * Mat4::look_at_lh(eye, target, Vec3::Y)
+    }
+
+    #[test]
+    fn walks_through_portals_in_view() {
+        let graph = corridor();
+        let forward = |eye: Vec3| graph.visible_cells(eye, view_proj(eye, eye + Vec3::Z));
+
+        // Close to the first doorway, the second one shows through it.
+        assert_eq!(forward(Vec3::new(0.0, 1.5, 8.0)), Some(vec![true, true, true]));
+
+        // Further back, the first doorway only shows the wall next to the
+        // second one.
+        assert_eq!(forward(Vec3::new(0.0, 1.5, 2.0)), Some(vec![true, true, false]));
+
+        // Looking away, no doorway is in view.
+        let eye = Vec3::new(0.0, 1.5, 8.0);
+        let behind = graph.visible_cells(eye, view_proj(eye, eye - Vec3::Z));
+        assert_eq!(behind, Some(vec![true, false, false]));
+
+        assert_eq!(forward(Vec3::new(0.0, 1.5, -50.0)), None);
+    }
+
+    #[test]
+    fn rejects_unknown_cells() {
+        let portal = Portal {
+            cells: [0, 3],
+            corners: [Vec3::ZERO; 4],
+        };
+        assert!(CellGraph::new(vec![], vec![portal]).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Conversion of texture data between color spaces.
+
+use glam::{Mat3, Vec3, Vec3Swizzles};
+
+use crate::types::{ColorSpace, TextureFormat, TransferFunction};
+
+/// Makes texture data in `format` whose contents are in `color_space`
+/// readable by shaders as linear color, returning the format to create the
+/// texture with.
+///
+/// Swaps between the srgb and linear variants of the format when that is
+/// enough, otherwise converts `data` in place. Formats which can't be
+/// converted are returned as is, with a warning.
+pub fn resolve_color_space(format: TextureFormat, color_space: ColorSpace, data: &mut [u8]) -> TextureFormat {
+    let (transfer, primaries) = match color_space {
+        ColorSpace::FromFormat => return format,
+        ColorSpace::Srgb => (TransferFunction::Srgb, None),
+        ColorSpace::Linear => (TransferFunction::Linear, None),
+        ColorSpace::Rec709 => (TransferFunction::Rec709, None),
+        ColorSpace::Custom {
+            transfer,
+            to_srgb_primaries,
+        } => (transfer, Some(to_srgb_primaries)),
+    };
+
+    if primaries.is_none() {
+        match transfer {
+            TransferFunction::Srgb => {
+                if let Some(srgb) = srgb_variant(format) {
+                    return srgb;
+                }
+            }
+            TransferFunction::Linear => return linear_variant(format).unwrap_or(format),
+            _ => {}
+        }
+    }
+
+    let primaries = primaries.unwrap_or(Mat3::IDENTITY);
+    let to_linear = |encoded: Vec3| primaries * Vec3::from(encoded.to_array().map(|value| decode(transfer, value)));
+    match format {
+        TextureFormat::Rgba8Unorm
+        | TextureFormat::Rgba8UnormSrgb
+        | TextureFormat::Bgra8Unorm
+        | TextureFormat::Bgra8UnormSrgb => {
+            let bgra = matches!(format, TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb);
+            for pixel in data.chunks_exact_mut(4) {
+                let encoded = Vec3::new(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32) / 255.0;
+                let linear = match bgra {
+                    true => to_linear(encoded.zyx()).zyx(),
+                    false => to_linear(encoded),
+                };
+                for (byte, value) in pixel.iter_mut().zip(linear.to_array()) {
+                    *byte = (encode_srgb(value.clamp(0.0, 1.0)) * 255.0).round() as u8;
+                }
+            }
+            srgb_variant(format).unwrap()
+        }
+        TextureFormat::Rgba32Float => {
+            for pixel in data.chunks_exact_mut(16) {
+                let mut channels = [0.0_f32; 3];
+                for (channel, bytes) in channels.iter_mut().zip(pixel.chunks_exact(4)) {
+                    *channel = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                }
+                let linear = to_linear(Vec3::from(channels));
+                for (bytes, value) in pixel.chunks_exact_mut(4).zip(linear.to_array()) {
+                    bytes.copy_from_slice(&value.to_le_bytes());
+                }
+            }
+            format
+        }
+        _ => {
+            log::warn!(
+                "Textures in {:?} can't be converted from {:?}, using the data as is",
+                format,
+                color_space
+            );
+            format
+        }
+    }
+}
+
+/// Formats which have an srgb variant, and that variant.
+const SRGB_VARIANTS: &[(TextureFormat, TextureFormat)] = {
+    use TextureFormat as F;
+    &[
+        (F::Rgba8Unorm, F::Rgba8UnormSrgb),
+        (F::Bgra8Unorm, F::Bgra8UnormSrgb),
+        (F::Bc1RgbaUnorm, F::Bc1RgbaUnormSrgb),
+        (F::Bc2RgbaUnorm, F::Bc2RgbaUnormSrgb),
+        (F::Bc3RgbaUnorm, F::Bc3RgbaUnormSrgb),
+        (F::Bc7RgbaUnorm, F::Bc7RgbaUnormSrgb),
+        (F::Etc2Rgb8Unorm, F::Etc2Rgb8UnormSrgb),
+        (F::Etc2Rgb8A1Unorm, F::Etc2Rgb8A1UnormSrgb),
+        (F::Etc2Rgba8Unorm, F::Etc2Rgba8UnormSrgb),
+        (F::Astc4x4RgbaUnorm, F::Astc4x4RgbaUnormSrgb),
+        (F::Astc5x4RgbaUnorm, F::Astc5x4RgbaUnormSrgb),
+        (F::Astc5x5RgbaUnorm, F::Astc5x5RgbaUnormSrgb),
+        (F::Astc6x5RgbaUnorm, F::Astc6x5RgbaUnormSrgb),
+        (F::Astc6x6RgbaUnorm, F::Astc6x6RgbaUnormSrgb),
+        (F::Astc8x5RgbaUnorm, F::Astc8x5RgbaUnormSrgb),
+        (F::Astc8x6RgbaUnorm, F::Astc8x6RgbaUnormSrgb),
+        (F::Astc10x5RgbaUnorm, F::Astc10x5RgbaUnormSrgb),
+        (F::Astc10x6RgbaUnorm, F::Astc10x6RgbaUnormSrgb),
+        (F::Astc8x8RgbaUnorm, F::Astc8x8RgbaUnormSrgb),
+        (F::Astc10x8RgbaUnorm, F::Astc10x8RgbaUnormSrgb),
+        (F::Astc10x10RgbaUnorm, F::Astc10x10RgbaUnormSrgb),
+        (F::Astc12x10RgbaUnorm, F::Astc12x10RgbaUnormSrgb),
+        (F::Astc12x12RgbaUnorm, F::Astc12x12RgbaUnormSrgb),
+    ]
+};
+
+fn srgb_variant(format: TextureFormat) -> Option<TextureFormat> {
+    SRGB_VARIANTS
+        .iter()
+        .find(|&&(linear, srgb)| format == linear || format == srgb)
+        .map(|&(_, srgb)| srgb)
+}
+
+fn linear_variant(format: TextureFormat) -> Option<TextureFormat> {
+    SRGB_VARIANTS
+        .iter()
+        .find(|&&(_, srgb)| format == srgb)
+        .map(|&(linear, _)| linear)
+}
+
+fn decode(transfer: TransferFunction, value: f32) -> f32 {
+    match transfer {
+        TransferFunction::Linear => value,
+        TransferFunction::Srgb => match value <= 0.04045 {
+            true => value / 12.92,
+            false => ((value + 0.055) / 1.055).powf(2.4),
+        },
+        TransferFunction::Rec709 => match value < 0.081 {
+            true => value / 4.5,
+            false => ((value + 0.099) / 1.099).powf(1.0 / 0.45),
+        },
+        TransferFunction::Gamma(gamma) => value.max(0.0).powf(gamma),
+    }
+}
+
+/// Encodes a linear value with the srgb transfer function.
+pub fn encode_srgb(value: f32) -> f32 {
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::resolve_color_space;
+    use crate::types::{ColorSpace, TextureFormat};
+
+    #[test]
+    fn relabels_or_converts() {
+        let mut data = vec![10, 128, 250, 255];
+
+        // Relabeling the format is enough, the data stays untouched.
+        let format = resolve_color_space(TextureFormat::Rgba8Unorm, ColorSpace::Srgb, &mut data);
+        assert_eq!(format, TextureFormat::Rgba8UnormSrgb);
+        let format = resolve_color_space(TextureFormat::Rgba8UnormSrgb, ColorSpace::Linear, &mut data);
+        assert_eq!(format, TextureFormat::Rgba8Unorm);
+        assert_eq!(data, [10, 128, 250, 255]);
+
+        // Video is converted to srgb, keeping black, white and alpha.
+        let mut data = vec![0, 255, 128, 77];
+        let format = resolve_color_space(TextureFormat::Rgba8Unorm, ColorSpace::Rec709, &mut data);
+        assert_eq!(format, TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(data[0], 0);
+        assert_eq!(data[1], 255);
+        assert!(data[2] != 128);
+        assert_eq!(data[3], 77);
+    }
+}
@@ -0,0 +1,210 @@
+//! Destruction of gpu resources spread over frames.
+//!
+//! Dropping the last handle of thousands of textures at once, as happens
+//! when a large scene is unloaded, would destroy them all in the next call
+//! to [`Renderer::ready`](crate::Renderer::ready). Instead the texture and
+//! material managers retire what they remove into a [`DeletionQueue`], which
+//! holds on to it until the gpu finished the frames that could still use it,
+//! then destroys at most a budget of values a frame.
+//!
+//! Meshes and objects don't go through the queue: a mesh only frees its
+//! range of the megabuffers, and objects own no gpu resources.
+
+use std::{any::Any, collections::VecDeque, future::Future, pin::Pin, task::Poll};
+
+use wgpu::Queue;
+
+use crate::util::buffer::poll_now;
+
+/// Values [`DeletionQueue`] destroys a frame by default.
+pub const DEFAULT_DELETION_BUDGET: usize = 256;
+
+type Retired = Box<dyn Any + Send>;
+type DoneFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Values retired during one frame, waiting for the gpu.
+struct Batch {
+    done: DoneFuture,
+    values: Vec<Retired>,
+}
+
+/// Holds on to removed resources until they can be destroyed cheaply, see
+/// the [module](self) docs.
+pub struct DeletionQueue {
+    /// Retired since the last call to [`DeletionQueue::submitted`].
+    retiring: Vec<Retired>,
+    /// Oldest first.
+    waiting: VecDeque<Batch>,
+    /// Values the gpu is done with.
+    finished: VecDeque<Retired>,
+    budget: Option<usize>,
+}
+
+impl DeletionQueue {
+    pub fn new() -> Self {
+        Self {
+            retiring: Vec::new(),
+            waiting: VecDeque::new(),
+            finished: VecDeque::new(),
+            budget: Some(DEFAULT_DELETION_BUDGET),
+        }
+    }
+
+    /// Sets how many values are destroyed a frame, `None` destroying all of
+    /// them as soon as the gpu is done with them.
+    pub fn set_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+    }
+
+    /// Holds on to `value` until it can be destroyed.
+    pub fn retire<T: Send + 'static>(&mut self, value: T) {
+        self.retiring.push(Box::new(value));
+    }
+
+    /// Marks everything retired so far as used by the work submitted to
+    /// `queue` up to now.
+    pub fn submitted(&mut self, queue: &Queue) {
+        if self.retiring.is_empty() {
+            return;
+        }
+        self.wait_for(Box::pin(queue.on_submitted_work_done()));
+    }
+
+    /// Moves everything retired so far into a batch destroyed once `done`
+    /// resolves.
+    fn wait_for(&mut self, done: DoneFuture) {
+        self.waiting.push_back(Batch {
+            done,
+            values: std::mem::take(&mut self.retiring),
+        });
+    }
+
+    /// Destroys what the gpu is done with, up to the budget. The device must
+    /// have been polled for the gpu to be seen as done. Returns how many
+    /// values were destroyed.
+    pub fn destroy(&mut self) -> usize {
+        profiling::scope!("DeletionQueue::destroy");
+
+        // Work finishes in order, so later batches can't be done earlier.
+        while let Some(batch) = self.waiting.front_mut() {
+            match poll_now(&mut batch.done) {
+                Poll::Ready(()) => {
+                    let batch = self.waiting.pop_front().unwrap();
+                    self.finished.extend(batch.values);
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        let available = self.finished.len();
+        let count = self.budget.map_or(available, |budget| budget.min(available));
+        self.finished.drain(..count);
+        count
+    }
+
+    /// Values retired which aren't destroyed yet.
+    pub fn pending(&self) -> usize {
+        self.retiring.len() + self.waiting.iter().map(|batch| batch.values.len()).sum::<usize>() + self.finished.len()
+    }
+}
+
+impl Default for DeletionQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+    };
+
+    use super::DeletionQueue;
+
+    /// Stands in for the gpu finishing a submission.
+    #[derive(Clone, Default)]
+    struct Gpu(Arc<AtomicBool>);
+
+    impl Gpu {
+        fn finish(&self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl Future for Gpu {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            match self.0.load(Ordering::Relaxed) {
+                true => Poll::Ready(()),
+                false => Poll::Pending,
+            }
+        }
+    }
+
+    /// Retires `count` values and submits them, returning the submission.
+    fn submit(queue: &mut DeletionQueue, count: usize, value: &Arc<()>) -> Gpu {
+        for _ in 0..count {
+            queue.retire(Arc::clone(value));
+        }
+        let gpu = Gpu::default();
+        queue.wait_for(Box::pin(gpu.clone()));
+        gpu
+    }
+
+    #[test]
+    fn waits_for_the_gpu() {
+        let value = Arc::new(());
+        let mut queue = DeletionQueue::new();
+        queue.set_budget(None);
+
+        queue.retire(Arc::clone(&value));
+        assert_eq!(queue.pending(), 1);
+        // Not submitted yet.
+        assert_eq!(queue.destroy(), 0);
+
+        let first = submit(&mut queue, 2, &value);
+        let second = submit(&mut queue, 1, &value);
+        assert_eq!(queue.pending(), 4);
+        assert_eq!(queue.destroy(), 0);
+
+        // Submissions finish in order, a later one isn't checked before an
+        // earlier one.
+        second.finish();
+        assert_eq!(queue.destroy(), 0);
+        first.finish();
+        assert_eq!(queue.destroy(), 4);
+        assert_eq!(queue.pending(), 0);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn destroys_up_to_the_budget() {
+        let value = Arc::new(());
+        let mut queue = DeletionQueue::new();
+        queue.set_budget(Some(3));
+
+        submit(&mut queue, 5, &value).finish();
+        let pending = submit(&mut queue, 2, &value);
+        assert_eq!(queue.pending(), 7);
+
+        assert_eq!(queue.destroy(), 3);
+        assert_eq!(queue.pending(), 4);
+        assert_eq!(Arc::strong_count(&value), 5);
+        assert_eq!(queue.destroy(), 2);
+        assert_eq!(queue.destroy(), 0);
+        assert_eq!(queue.pending(), 2);
+
+        pending.finish();
+        assert_eq!(queue.destroy(), 2);
+        assert_eq!(queue.pending(), 0);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
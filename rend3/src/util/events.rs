@@ -0,0 +1,126 @@
+//! Callbacks told about meshes, textures and objects coming and going, so
+//! external systems like physics mirrors, asset trackers or editors can stay
+//! in sync with the renderer without polling it.
+//!
+//! Subscribe with [`Renderer::subscribe`](crate::Renderer::subscribe). Events
+//! are sent from [`Renderer::ready`](crate::Renderer::ready), after the data
+//! of the renderer is unlocked, so subscribers may call back into the
+//! renderer.
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use rend3_types::{RawMeshHandle, RawObjectHandle, RawTextureHandle};
+
+/// Change to the resources of the renderer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RendererEvent {
+    /// The mesh was added, and its data handed to the GPU queue.
+    MeshAdded(RawMeshHandle),
+    /// Every handle to the mesh was dropped, and its data freed.
+    MeshRemoved(RawMeshHandle),
+    /// The texture was added, and its data handed to the GPU queue.
+    TextureAdded(RawTextureHandle),
+    /// Every handle to the texture was dropped, and it was evicted from the
+    /// bindless texture arrays.
+    TextureRemoved(RawTextureHandle),
+    /// The object was added or duplicated, and takes part in the next
+    /// rendergraph.
+    ObjectAdded(RawObjectHandle),
+    /// Every handle to the object was dropped, and it was removed.
+    ObjectRemoved(RawObjectHandle),
+}
+
+/// Identifies a subscriber, to remove it with
+/// [`Renderer::unsubscribe`](crate::Renderer::unsubscribe).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SubscriptionHandle(usize);
+
+type Subscriber = Arc<dyn Fn(&RendererEvent) + Send + Sync>;
+
+#[derive(Default)]
+struct Subscribers {
+    next: usize,
+    list: Vec<(SubscriptionHandle, Subscriber)>,
+}
+
+/// Subscribers to [`RendererEvent`]s, see the [module](self) docs.
+#[derive(Default)]
+pub struct EventSubscribers {
+    subscribers: Mutex<Subscribers>,
+}
+
+impl EventSubscribers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&self, subscriber: impl Fn(&RendererEvent) + Send + Sync + 'static) -> SubscriptionHandle {
+        let mut subscribers = self.subscribers.lock();
+        let handle = SubscriptionHandle(subscribers.next);
+        subscribers.next += 1;
+        subscribers.list.push((handle, Arc::new(subscriber)));
+        handle
+    }
+
+    /// Removes the subscriber. Does nothing if it was already removed.
+    pub fn unsubscribe(&self, handle: SubscriptionHandle) {
+        self.subscribers.lock().list.retain(|(h, _)| *h != handle);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.lock().list.is_empty()
+    }
+
+    /// Sends every event to every subscriber, in order.
+    ///
+    /// The subscribers are cloned out of the lock first, so they can
+    /// subscribe or unsubscribe from inside the callback.
+    pub(crate) fn dispatch(&self, events: &[RendererEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        let subscribers: Vec<Subscriber> = self
+            .subscribers
+            .lock()
+            .list
+            .iter()
+            .map(|(_, s)| Arc::clone(s))
+            .collect();
+        for event in events {
+            for subscriber in &subscribers {
+                subscriber(event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+    use rend3_types::{MeshHandle, ObjectHandle};
+
+    use super::{EventSubscribers, RendererEvent};
+
+    #[test]
+    fn dispatch_reaches_current_subscribers() {
+        let subscribers = EventSubscribers::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+
+        let seen_clone = Arc::clone(&seen);
+        let handle = subscribers.subscribe(move |event| seen_clone.lock().push(*event));
+        assert!(!subscribers.is_empty());
+
+        let mesh = RendererEvent::MeshAdded(MeshHandle::new(0).get_raw());
+        let object = RendererEvent::ObjectRemoved(ObjectHandle::new(4).get_raw());
+        subscribers.dispatch(&[mesh, object]);
+        assert_eq!(*seen.lock(), [mesh, object]);
+
+        subscribers.unsubscribe(handle);
+        subscribers.dispatch(&[mesh]);
+        assert_eq!(seen.lock().len(), 2);
+        assert!(subscribers.is_empty());
+    }
+}
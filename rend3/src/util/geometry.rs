@@ -0,0 +1,415 @@
+//! Frustums, bounding volumes and their projections, as used by the cullers.
+//!
+//! Culling or picking levels of detail in user code with these matches what
+//! the renderer decides exactly.
+//!
+//! Frustum plane extraction follows <https://www.gamedevs.org/uploads/fast-extraction-viewing-frustum-planes-from-world-view-projection-matrix.pdf>.
+
+use glam::{BVec3, Mat3, Mat4, Vec3, Vec3A, Vec4Swizzles};
+use rend3_types::DepthMode;
+
+/// Represents a point in space and a radius from that point.
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(16))]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+impl BoundingSphere {
+    pub fn from_mesh(mesh: &[Vec3]) -> Self {
+        let center = find_mesh_center(mesh);
+        let radius = find_mesh_bounding_sphere_radius(center, mesh);
+
+        Self {
+            center: Vec3::from(center),
+            radius,
+        }
+    }
+
+    pub fn apply_transform(self, model_view: Mat4) -> Self {
+        let max_scale = model_view
+            .x_axis
+            .xyz()
+            .length_squared()
+            .max(
+                model_view
+                    .y_axis
+                    .xyz()
+                    .length_squared()
+                    .max(model_view.z_axis.xyz().length_squared()),
+            )
+            .sqrt();
+        let center = model_view * self.center.extend(1.0);
+
+        Self {
+            center: center.truncate(),
+            radius: max_scale * self.radius,
+        }
+    }
+
+    /// Whether the two spheres overlap.
+    pub fn intersects(self, other: Self) -> bool {
+        let radii = self.radius + other.radius;
+        self.center.distance_squared(other.center) <= radii * radii
+    }
+
+    /// Smallest box containing the sphere.
+    pub fn aabb(self) -> Aabb {
+        Aabb {
+            min: self.center - self.radius,
+            max: self.center + self.radius,
+        }
+    }
+}
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Smallest box containing all the points, `None` if there are none.
+    pub fn from_points(points: &[Vec3]) -> Option<Self> {
+        let first = *points.first()?;
+        Some(
+            points
+                .iter()
+                .skip(1)
+                .fold(Self { min: first, max: first }, |aabb, &point| Self {
+                    min: aabb.min.min(point),
+                    max: aabb.max.max(point),
+                }),
+        )
+    }
+
+    pub fn center(self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// One of the 8 corners, `0b001` picking the max x and so on.
+    pub fn corner(self, index: u8) -> Vec3 {
+        Vec3::select(
+            BVec3::new(index & 1 != 0, index & 2 != 0, index & 4 != 0),
+            self.max,
+            self.min,
+        )
+    }
+
+    pub fn contains_point(self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    /// Whether the two boxes overlap.
+    pub fn intersects(self, other: Self) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    /// Smallest axis aligned box containing the transformed box.
+    pub fn transform(self, transform: Mat4) -> Self {
+        let center = transform.transform_point3(self.center());
+        let linear = Mat3::from_mat4(transform);
+        let abs = Mat3::from_cols(linear.x_axis.abs(), linear.y_axis.abs(), linear.z_axis.abs());
+        let half_extents = abs * self.half_extents();
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+}
+
+/// Oriented bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Obb {
+    pub center: Vec3,
+    /// Vectors from the center to the middle of three faces of the box.
+    pub half_axes: Mat3,
+}
+
+impl Obb {
+    /// The box transformed by `transform`, keeping its orientation.
+    pub fn from_aabb(aabb: Aabb, transform: Mat4) -> Self {
+        let half_extents = aabb.half_extents();
+        let linear = Mat3::from_mat4(transform);
+        Self {
+            center: transform.transform_point3(aabb.center()),
+            half_axes: Mat3::from_cols(
+                linear.x_axis * half_extents.x,
+                linear.y_axis * half_extents.y,
+                linear.z_axis * half_extents.z,
+            ),
+        }
+    }
+
+    /// How far the box extends from its center along `direction`, which
+    /// must be normalized.
+    pub fn extent_along(self, direction: Vec3) -> f32 {
+        direction.dot(self.half_axes.x_axis).abs()
+            + direction.dot(self.half_axes.y_axis).abs()
+            + direction.dot(self.half_axes.z_axis).abs()
+    }
+}
+
+fn find_mesh_center(mesh: &[Vec3]) -> Vec3A {
+    let first = if let Some(first) = mesh.first() {
+        *first
+    } else {
+        return Vec3A::ZERO;
+    };
+    // Bounding box time baby!
+    let mut max = Vec3A::from(first);
+    let mut min = max;
+
+    for pos in mesh.iter().skip(1) {
+        let pos = Vec3A::from(*pos);
+        max = max.max(pos);
+        min = min.min(pos);
+    }
+
+    (max + min) / 2.0
+}
+
+fn find_mesh_bounding_sphere_radius(mesh_center: Vec3A, mesh: &[Vec3]) -> f32 {
+    mesh.iter().fold(0.0, |distance, pos| {
+        distance.max((Vec3A::from(*pos) - mesh_center).length())
+    })
+}
+
+/// Represents a plane as a vec4 (or vec3 + f32)
+#[derive(Debug, Copy, Clone)]
+#[repr(C, align(16))]
+pub struct ShaderPlane {
+    pub abc: Vec3,
+    pub d: f32,
+}
+
+impl ShaderPlane {
+    pub fn new(a: f32, b: f32, c: f32, d: f32) -> Self {
+        Self {
+            abc: Vec3::new(a, b, c),
+            d,
+        }
+    }
+
+    pub fn normalize(mut self) -> Self {
+        let mag = self.abc.length();
+
+        self.abc /= mag;
+        self.d /= mag;
+
+        self
+    }
+
+    pub fn distance(self, point: Vec3) -> f32 {
+        self.abc.dot(point) + self.d
+    }
+}
+
+/// A frustum composed of 5 different planes. Has no far plane as it assumes
+/// infinite.
+#[derive(Debug, Copy, Clone)]
+#[repr(C, align(16))]
+pub struct ShaderFrustum {
+    left: ShaderPlane,
+    right: ShaderPlane,
+    top: ShaderPlane,
+    bottom: ShaderPlane,
+    near: ShaderPlane,
+}
+
+impl ShaderFrustum {
+    /// Extracts the frustum of a projection matrix which lays out depth
+    /// according to `depth_mode`.
+    pub fn from_matrix(matrix: Mat4, depth_mode: DepthMode) -> Self {
+        let mat_arr = matrix.to_cols_array_2d();
+
+        let left = ShaderPlane::new(
+            mat_arr[0][3] + mat_arr[0][0],
+            mat_arr[1][3] + mat_arr[1][0],
+            mat_arr[2][3] + mat_arr[2][0],
+            mat_arr[3][3] + mat_arr[3][0],
+        );
+
+        let right = ShaderPlane::new(
+            mat_arr[0][3] - mat_arr[0][0],
+            mat_arr[1][3] - mat_arr[1][0],
+            mat_arr[2][3] - mat_arr[2][0],
+            mat_arr[3][3] - mat_arr[3][0],
+        );
+
+        let top = ShaderPlane::new(
+            mat_arr[0][3] - mat_arr[0][1],
+            mat_arr[1][3] - mat_arr[1][1],
+            mat_arr[2][3] - mat_arr[2][1],
+            mat_arr[3][3] - mat_arr[3][1],
+        );
+
+        let bottom = ShaderPlane::new(
+            mat_arr[0][3] + mat_arr[0][1],
+            mat_arr[1][3] + mat_arr[1][1],
+            mat_arr[2][3] + mat_arr[2][1],
+            mat_arr[3][3] + mat_arr[3][1],
+        );
+
+        // no far plane, as depth may be infinite. Finite far planes are not
+        // culled against, which is conservative.
+
+        let near = match depth_mode.is_reversed() {
+            // this is the far plane in the algorithm, but we're using inverse Z, so near
+            // and far get flipped.
+            true => ShaderPlane::new(
+                mat_arr[0][3] - mat_arr[0][2],
+                mat_arr[1][3] - mat_arr[1][2],
+                mat_arr[2][3] - mat_arr[2][2],
+                mat_arr[3][3] - mat_arr[3][2],
+            ),
+            false => ShaderPlane::new(mat_arr[0][2], mat_arr[1][2], mat_arr[2][2], mat_arr[3][2]),
+        };
+
+        Self {
+            left: left.normalize(),
+            right: right.normalize(),
+            top: top.normalize(),
+            bottom: bottom.normalize(),
+            near: near.normalize(),
+        }
+    }
+
+    /// The planes of the frustum, whose normals point inwards, in the space
+    /// the matrix it was extracted from projects from.
+    pub fn planes(&self) -> [ShaderPlane; 5] {
+        [self.left, self.right, self.top, self.bottom, self.near]
+    }
+
+    /// Determins if the sphere is at all inside the frustum.
+    pub fn contains_sphere(&self, sphere: BoundingSphere) -> bool {
+        let neg_radius = -sphere.radius;
+
+        for plane in &self.planes() {
+            let inside = plane.distance(sphere.center) >= neg_radius;
+            if !inside {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Determines if the box is at all inside the frustum. Conservative, so
+    /// boxes near the corners of the frustum may pass while outside.
+    pub fn contains_aabb(&self, aabb: Aabb) -> bool {
+        self.planes().iter().all(|plane| {
+            // The corner furthest along the normal.
+            let corner = Vec3::select(plane.abc.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            plane.distance(corner) >= 0.0
+        })
+    }
+
+    /// Determines if the box is at all inside the frustum. Conservative like
+    /// [`contains_aabb`](Self::contains_aabb).
+    pub fn contains_obb(&self, obb: Obb) -> bool {
+        self.planes()
+            .iter()
+            .all(|plane| plane.distance(obb.center) >= -obb.extent_along(plane.abc))
+    }
+}
+
+/// Normalized device coordinates covered by the box seen through
+/// `view_proj`, as the min and max corners. `None` if part of the box is
+/// behind the camera, which may cover any part of the screen.
+pub fn project_aabb(aabb: Aabb, view_proj: Mat4) -> Option<Aabb> {
+    let mut ndc = Aabb {
+        min: Vec3::splat(f32::INFINITY),
+        max: Vec3::splat(f32::NEG_INFINITY),
+    };
+    for corner in 0..8 {
+        let clip = view_proj * aabb.corner(corner).extend(1.0);
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+        let point = clip.xyz() / clip.w;
+        ndc.min = ndc.min.min(point);
+        ndc.max = ndc.max.max(point);
+    }
+    Some(ndc)
+}
+
+/// Fraction of the height of the screen covered by a view space sphere seen
+/// through `proj`, which exceeds 1 as it fills the screen.
+///
+/// This is the size at the center of the screen, spheres towards the edges
+/// of wide perspective projections cover somewhat more. Spheres around the
+/// camera return infinity.
+pub fn projected_sphere_size(sphere: BoundingSphere, proj: Mat4) -> f32 {
+    // The w of the center in clip space: its depth for perspective
+    // projections and 1 for orthographic ones.
+    let w = proj.row(3).dot(sphere.center.extend(1.0));
+    let perspective = proj.w_axis.w == 0.0;
+    if perspective && w <= sphere.radius {
+        return f32::INFINITY;
+    }
+    proj.y_axis.y.abs() * sphere.radius / w
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Vec3};
+    use rend3_types::DepthMode;
+
+    use super::{project_aabb, projected_sphere_size, Aabb, BoundingSphere, Obb, ShaderFrustum};
+
+    #[test]
+    fn boxes_are_culled_like_spheres() {
+        let proj = Mat4::perspective_infinite_reverse_lh(1.0, 1.0, 0.1);
+        let frustum = ShaderFrustum::from_matrix(proj, DepthMode::ReversedZInfinite);
+        let cube = |center: Vec3| Aabb {
+            min: center - 1.0,
+            max: center + 1.0,
+        };
+
+        for (center, visible) in [
+            (Vec3::new(0.0, 0.0, 10.0), true),
+            (Vec3::new(0.0, 0.0, -10.0), false),
+            (Vec3::new(20.0, 0.0, 10.0), false),
+            (Vec3::new(0.0, 6.0, 10.0), true),
+        ] {
+            let aabb = cube(center);
+            assert_eq!(frustum.contains_aabb(aabb), visible, "{:?}", center);
+            assert_eq!(frustum.contains_obb(Obb::from_aabb(aabb, Mat4::IDENTITY)), visible);
+            let sphere = BoundingSphere {
+                center,
+                radius: 3.0_f32.sqrt(),
+            };
+            assert_eq!(frustum.contains_sphere(sphere), visible);
+        }
+
+        // The rotated box reaches sqrt(2) along x, but less towards the plane.
+        let rotated = Obb::from_aabb(cube(Vec3::ZERO), Mat4::from_rotation_y(std::f32::consts::FRAC_PI_4));
+        let at = |x: f32| Obb {
+            center: Vec3::new(x, 0.0, 10.0),
+            ..rotated
+        };
+        assert!(frustum.contains_obb(at(6.5)));
+        assert!(!frustum.contains_obb(at(7.0)));
+    }
+
+    #[test]
+    fn projected_sizes() {
+        let proj = Mat4::perspective_infinite_reverse_lh(std::f32::consts::FRAC_PI_2, 1.0, 0.1);
+        let sphere = |z: f32| BoundingSphere {
+            center: Vec3::new(0.0, 0.0, z),
+            radius: 1.0,
+        };
+        assert!((projected_sphere_size(sphere(10.0), proj) - 0.1).abs() < 1e-5);
+        assert_eq!(projected_sphere_size(sphere(0.5), proj), f32::INFINITY);
+
+        let ndc = project_aabb(sphere(10.0).aabb(), proj).unwrap();
+        assert!((ndc.max.y - 1.0 / 9.0).abs() < 1e-5);
+        assert!(project_aabb(sphere(0.5).aabb(), proj).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+//! Occlusion queries, telling user logic whether objects or boxes were hidden
+//! behind the scene, e.g. to fade lens flares when the sun goes behind a
+//! building.
+//!
+//! Start a query with
+//! [`Renderer::begin_occlusion_query`](crate::Renderer::begin_occlusion_query),
+//! then poll [`OcclusionQuery::result`] on the following frames. Queries are
+//! answered by an occlusion routine in the rendergraph, like
+//! `rend3_routine::occlusion::OcclusionRoutine`, which tests them against the
+//! depth buffer and reads the results back without stalling, so they arrive
+//! a frame or two later. Queries stay pending while no such routine runs.
+//!
+//! A query answers a single frame, start a new one each frame to keep
+//! tracking visibility.
+
+use std::sync::Arc;
+
+use glam::Vec3;
+use parking_lot::Mutex;
+use rend3_types::{ObjectHandle, RawObjectHandle};
+
+use crate::managers::ObjectManager;
+
+/// What an occlusion query tests.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OcclusionTarget {
+    /// The bounding box of the bounding sphere of the object. Objects which
+    /// are removed or hidden by their group count as occluded.
+    Object(RawObjectHandle),
+    /// A world space box.
+    Aabb { min: Vec3, max: Vec3 },
+}
+
+impl OcclusionTarget {
+    pub fn object(handle: &ObjectHandle) -> Self {
+        Self::Object(handle.get_raw())
+    }
+
+    /// World space box to test, `None` if there is nothing to test.
+    pub fn world_bounds(&self, objects: &ObjectManager) -> Option<(Vec3, Vec3)> {
+        match *self {
+            Self::Object(handle) => {
+                let object = objects.get_object(handle).filter(|object| !object.hidden)?;
+                let aabb = object
+                    .input
+                    .bounding_sphere
+                    .apply_transform(object.input.transform)
+                    .aabb();
+                Some((aabb.min, aabb.max))
+            }
+            Self::Aabb { min, max } => Some((min, max)),
+        }
+    }
+}
+
+type ResultSlot = Arc<Mutex<Option<bool>>>;
+
+/// A started occlusion query, see the [module](self) docs.
+#[derive(Debug, Clone)]
+pub struct OcclusionQuery {
+    target: OcclusionTarget,
+    result: ResultSlot,
+}
+
+impl OcclusionQuery {
+    pub fn target(&self) -> OcclusionTarget {
+        self.target
+    }
+
+    /// Whether any part of the target passed the depth test, `None` while
+    /// the query is pending.
+    pub fn result(&self) -> Option<bool> {
+        *self.result.lock()
+    }
+}
+
+/// A query handed to an occlusion routine, to be resolved once its result is
+/// read back.
+#[derive(Debug)]
+pub struct PendingOcclusionQuery {
+    pub target: OcclusionTarget,
+    result: ResultSlot,
+}
+
+impl PendingOcclusionQuery {
+    pub fn resolve(self, visible: bool) {
+        *self.result.lock() = Some(visible);
+    }
+}
+
+/// Queries waiting for an occlusion routine, see the [module](self) docs.
+#[derive(Default)]
+pub struct OcclusionQueries {
+    pending: Mutex<Vec<PendingOcclusionQuery>>,
+}
+
+impl OcclusionQueries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin(&self, target: OcclusionTarget) -> OcclusionQuery {
+        let result = ResultSlot::default();
+        self.pending.lock().push(PendingOcclusionQuery {
+            target,
+            result: Arc::clone(&result),
+        });
+        OcclusionQuery { target, result }
+    }
+
+    /// Takes every query started since the last call. Called by occlusion
+    /// routines.
+    pub fn take_pending(&self) -> Vec<PendingOcclusionQuery> {
+        std::mem::take(&mut *self.pending.lock())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::{OcclusionQueries, OcclusionTarget};
+
+    #[test]
+    fn queries_resolve_once_taken() {
+        let queries = OcclusionQueries::new();
+        let target = OcclusionTarget::Aabb {
+            min: Vec3::ZERO,
+            max: Vec3::ONE,
+        };
+        let first = queries.begin(target);
+        let second = queries.begin(target);
+        assert_eq!(first.result(), None);
+
+        let mut pending = queries.take_pending();
+        assert_eq!(pending.len(), 2);
+        assert!(queries.take_pending().is_empty());
+
+        pending.remove(1).resolve(false);
+        pending.remove(0).resolve(true);
+        assert_eq!(first.result(), Some(true));
+        assert_eq!(second.result(), Some(false));
+    }
+}
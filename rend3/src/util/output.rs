@@ -23,6 +23,8 @@ pub enum OutputFrame {
 impl OutputFrame {
     /// If needed, acquire the surface. If the frame is Surface, after this call
     /// it will be SurfaceAcquired.
+    ///
+    /// Timeouts are retried a few times before being returned.
     pub fn acquire(&mut self) -> Result<(), SurfaceError> {
         if let Self::Surface { surface } = self {
             profiling::scope!("OutputFrame::acquire");
@@ -38,7 +40,7 @@ impl OutputFrame {
                     Err(e) => return Err(e),
                 }
             }
-            let surface_tex = retrieved_frame.expect("Swapchain acquire timed out 10 times.");
+            let surface_tex = retrieved_frame.ok_or(SurfaceError::Timeout)?;
 
             let view = surface_tex.texture.create_view(&TextureViewDescriptor::default());
 
@@ -48,6 +50,15 @@ impl OutputFrame {
         Ok(())
     }
 
+    /// True if the surface was acquired, but no longer matches the window
+    /// exactly and should be reconfigured.
+    pub fn is_suboptimal(&self) -> bool {
+        match self {
+            Self::SurfaceAcquired { surface_tex, .. } => surface_tex.suboptimal,
+            _ => false,
+        }
+    }
+
     /// Turn the given surface into a texture view, if it has one.
     pub fn as_view(&self) -> Option<&TextureView> {
         match self {
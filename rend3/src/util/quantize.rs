@@ -0,0 +1,253 @@
+//! Compact encodings of vertex attributes, see
+//! [`VertexQuantization`](crate::managers::VertexQuantization).
+//!
+//! Directions are octahedral encoded: the unit sphere is projected onto an
+//! octahedron, which is unfolded into a square, stored as two snorm16.
+//! Positions are stored as snorm16 inside the bounds of their mesh, which the
+//! culling pass folds back into the matrices of each object.
+
+use glam::{Mat4, Vec2, Vec3};
+
+/// Maps positions quantized by [`PositionDequantization::quantize`] back to
+/// mesh space, as `offset + scale * position`. Laid out like two `vec3`s.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(C, align(16))]
+pub struct PositionDequantization {
+    pub offset: Vec3,
+    _pad0: u32,
+    pub scale: Vec3,
+    _pad1: u32,
+}
+
+unsafe impl bytemuck::Pod for PositionDequantization {}
+unsafe impl bytemuck::Zeroable for PositionDequantization {}
+
+impl PositionDequantization {
+    /// For positions stored as they are.
+    pub const IDENTITY: Self = Self::new(Vec3::ZERO, Vec3::ONE);
+
+    pub const fn new(offset: Vec3, scale: Vec3) -> Self {
+        Self {
+            offset,
+            _pad0: 0,
+            scale,
+            _pad1: 0,
+        }
+    }
+
+    /// Fits the bounds of `positions`, the identity if there are none.
+    ///
+    /// Axes along which the mesh is flat still get a small scale, so the
+    /// matrix stays invertible for the normals.
+    pub fn from_positions(positions: &[Vec3]) -> Self {
+        let (min, max) = match positions.split_first() {
+            Some((&first, rest)) => rest.iter().fold((first, first), |(min, max), &position| {
+                (min.min(position), max.max(position))
+            }),
+            None => return Self::IDENTITY,
+        };
+        let half_size = (max - min) * 0.5;
+        let floor = (half_size.max_element() * 1.0e-3).max(f32::EPSILON);
+        Self::new((min + max) * 0.5, half_size.max(Vec3::splat(floor)))
+    }
+
+    /// Snorm16 of `position` relative to the bounds, w being 1.
+    pub fn quantize(&self, position: Vec3) -> [i16; 4] {
+        let [x, y, z] = ((position - self.offset) / self.scale).to_array();
+        [snorm16(x), snorm16(y), snorm16(z), i16::MAX]
+    }
+
+    /// The mapping as a matrix, taking quantized positions to mesh space.
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_translation(self.offset) * Mat4::from_scale(self.scale)
+    }
+}
+
+impl Default for PositionDequantization {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+fn snorm16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+/// Octahedral encoding of the direction `direction`, which doesn't need to
+/// be normalized. A zero vector encodes +Z.
+pub fn octahedral_encode(direction: Vec3) -> [i16; 2] {
+    let l1 = direction.x.abs() + direction.y.abs() + direction.z.abs();
+    if l1 == 0.0 || !l1.is_finite() {
+        return [0, 0];
+    }
+    let n = direction / l1;
+    let mut xy = Vec2::new(n.x, n.y);
+    if n.z < 0.0 {
+        let sign = Vec2::new(sign_not_zero(xy.x), sign_not_zero(xy.y));
+        xy = (Vec2::ONE - Vec2::new(xy.y.abs(), xy.x.abs())) * sign;
+    }
+    [snorm16(xy.x), snorm16(xy.y)]
+}
+
+/// Unit direction encoded by [`octahedral_encode`], as the shaders decode it.
+pub fn octahedral_decode(encoded: [i16; 2]) -> Vec3 {
+    let x = (encoded[0] as f32 / i16::MAX as f32).max(-1.0);
+    let y = (encoded[1] as f32 / i16::MAX as f32).max(-1.0);
+    let mut n = Vec3::new(x, y, 1.0 - x.abs() - y.abs());
+    let t = (-n.z).max(0.0);
+    n.x -= t * sign_not_zero(n.x);
+    n.y -= t * sign_not_zero(n.y);
+    n.normalize()
+}
+
+fn sign_not_zero(value: f32) -> f32 {
+    match value >= 0.0 {
+        true => 1.0,
+        false => -1.0,
+    }
+}
+
+/// Bits of the half precision float closest to `value`, rounding ties to
+/// even. Too large values become infinite.
+pub fn f16_from_f32(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    // Shifts `mantissa` right, rounding to nearest even.
+    let round = |mantissa: u32, shift: u32| {
+        let halfway = 1 << (shift - 1);
+        let shifted = mantissa >> shift;
+        let rest = mantissa & ((halfway << 1) - 1);
+        match rest > halfway || (rest == halfway && shifted & 1 == 1) {
+            true => shifted + 1,
+            false => shifted,
+        }
+    };
+
+    if exponent <= 0 {
+        // Subnormal, or too small for one.
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        return sign | round(mantissa, (14 - exponent) as u32) as u16;
+    }
+
+    // A mantissa rounding up carries into the exponent, up to infinity.
+    sign | round((exponent as u32) << 23 | mantissa, 13) as u16
+}
+
+/// Value of the half precision float with the given bits.
+pub fn f32_from_f16(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+
+    let magnitude = match exponent {
+        // Subnormal, exactly representable as a float.
+        0 => {
+            let value = mantissa as f32 * 2.0_f32.powi(-24);
+            return match sign != 0 {
+                true => -value,
+                false => value,
+            };
+        }
+        0x1f => 0x7f80_0000 | mantissa << 13,
+        _ => (exponent + 127 - 15) << 23 | mantissa << 13,
+    };
+    f32::from_bits(sign | magnitude)
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::{f16_from_f32, f32_from_f16, octahedral_decode, octahedral_encode, PositionDequantization};
+
+    #[test]
+    fn directions_survive_encoding() {
+        let directions = [
+            Vec3::X,
+            -Vec3::Y,
+            -Vec3::Z,
+            Vec3::new(1.0, -2.0, 3.0),
+            Vec3::new(-0.3, 0.2, -0.9),
+            Vec3::new(0.0, -1.0, -1.0),
+        ];
+        for direction in directions {
+            let decoded = octahedral_decode(octahedral_encode(direction));
+            assert!(decoded.dot(direction.normalize()) > 0.99999, "{:?}", direction);
+        }
+        assert_eq!(octahedral_decode(octahedral_encode(Vec3::ZERO)), Vec3::Z);
+    }
+
+    #[test]
+    fn halves_round_to_nearest_even() {
+        assert_eq!(f16_from_f32(0.0), 0x0000);
+        assert_eq!(f16_from_f32(-0.0), 0x8000);
+        assert_eq!(f16_from_f32(1.0), 0x3c00);
+        assert_eq!(f16_from_f32(-2.5), 0xc100);
+        assert_eq!(f16_from_f32(65504.0), 0x7bff);
+        assert_eq!(f16_from_f32(65520.0), 0x7c00);
+        assert_eq!(f16_from_f32(f32::NEG_INFINITY), 0xfc00);
+        // Smallest subnormal, and halfway below it.
+        assert_eq!(f16_from_f32(2.0_f32.powi(-24)), 0x0001);
+        assert_eq!(f16_from_f32(2.0_f32.powi(-25)), 0x0000);
+        // 1 + 2^-11 is halfway between 1 and the next half, rounding to 1.
+        assert_eq!(f16_from_f32(1.0 + 2.0_f32.powi(-11)), 0x3c00);
+        assert_eq!(f16_from_f32(1.0 + 3.0 * 2.0_f32.powi(-11)), 0x3c02);
+    }
+
+    #[test]
+    fn halves_decode_exactly() {
+        for value in [
+            0.0,
+            -0.0,
+            1.0,
+            -2.5,
+            65504.0,
+            2.0_f32.powi(-24),
+            -2.0_f32.powi(-20),
+            0.1,
+        ] {
+            let bits = f16_from_f32(value);
+            assert_eq!(f16_from_f32(f32_from_f16(bits)), bits, "{}", value);
+        }
+        assert_eq!(f32_from_f16(0x3c00), 1.0);
+        assert_eq!(f32_from_f16(0x8000).to_bits(), (-0.0_f32).to_bits());
+        assert_eq!(f32_from_f16(0x0001), 2.0_f32.powi(-24));
+        assert_eq!(f32_from_f16(0x7c00), f32::INFINITY);
+        assert!(f32_from_f16(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn positions_dequantize_inside_their_bounds() {
+        let positions = [
+            Vec3::new(-4.0, 1.0, 2.0),
+            Vec3::new(6.0, 3.0, 2.0),
+            Vec3::new(0.5, 2.0, 2.0),
+        ];
+        let dequantization = PositionDequantization::from_positions(&positions);
+        let matrix = dequantization.matrix();
+        for position in positions {
+            let [x, y, z, w] = dequantization
+                .quantize(position)
+                .map(|value| value as f32 / i16::MAX as f32);
+            assert_eq!(w, 1.0);
+            let decoded = matrix.transform_point3(Vec3::new(x, y, z));
+            assert!(decoded.abs_diff_eq(position, 1.0e-3), "{:?} {:?}", decoded, position);
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! Ray casting against the meshes of the objects in the renderer.
+//!
+//! Every mesh keeps a copy of its positions and indices in a bounding volume
+//! hierarchy, so [`Renderer::raycast`](crate::Renderer::raycast) can be used
+//! for picking and placement without the application keeping its own copy of
+//! the geometry.
+
+use std::cmp::Ordering;
+
+use glam::{Mat4, Vec3};
+use rend3_types::RawObjectHandle;
+
+use crate::util::geometry::BoundingSphere;
+
+/// Triangles per leaf of a [`MeshBvh`].
+const LEAF_SIZE: usize = 4;
+
+/// Half-line starting at `origin`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Doesn't need to be normalized.
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    /// Point `distance` along the ray, in units of the length of the
+    /// direction.
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    fn transform(&self, transform: Mat4) -> Self {
+        Self {
+            origin: transform.transform_point3(self.origin),
+            direction: transform.transform_vector3(self.direction),
+        }
+    }
+
+    /// Distance at which the ray enters the box, if it does before `max`.
+    fn hits_aabb(&self, inv_direction: Vec3, min: Vec3, max: Vec3, max_distance: f32) -> Option<f32> {
+        let t0 = (min - self.origin) * inv_direction;
+        let t1 = (max - self.origin) * inv_direction;
+        let near = t0.min(t1).max_element().max(0.0);
+        let far = t0.max(t1).min_element().min(max_distance);
+        (near <= far).then(|| near)
+    }
+
+    fn hits_sphere(&self, sphere: BoundingSphere) -> bool {
+        let to_center = sphere.center - self.origin;
+        let along = (to_center.dot(self.direction) / self.direction.length_squared()).max(0.0);
+        let closest = self.origin + self.direction * along;
+        closest.distance_squared(sphere.center) <= sphere.radius * sphere.radius
+    }
+}
+
+/// Closest intersection of a ray with an object.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayHit {
+    pub object: RawObjectHandle,
+    /// Distance from the origin of the ray, in world units.
+    pub distance: f32,
+    pub position: Vec3,
+    /// Normal of the triangle in world space, facing the side its vertices
+    /// wind counter-clockwise around, whichever side the ray hit.
+    pub normal: Vec3,
+    /// Index of the triangle in the indices of the mesh.
+    pub triangle: u32,
+}
+
+/// Intersection of a ray with a mesh, in the space of the mesh.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MeshHit {
+    /// Distance in units of the length of the direction of the ray.
+    pub distance: f32,
+    pub triangle: u32,
+}
+
+#[derive(Debug, Clone)]
+struct BvhNode {
+    min: Vec3,
+    max: Vec3,
+    /// Leaves: first triangle in `order`. Branches: index of the second
+    /// child, the first one directly follows its parent.
+    start: u32,
+    /// Zero for branches.
+    count: u32,
+}
+
+/// Bounding volume hierarchy over the triangles of a mesh.
+#[derive(Debug, Clone, Default)]
+pub struct MeshBvh {
+    positions: Vec<Vec3>,
+    indices: Vec<u32>,
+    nodes: Vec<BvhNode>,
+    /// Triangles, ordered so every leaf covers a contiguous range.
+    order: Vec<u32>,
+}
+
+impl MeshBvh {
+    pub fn new(positions: &[Vec3], indices: &[u32]) -> Self {
+        profiling::scope!("MeshBvh::new");
+
+        let triangle_count = indices.len() / 3;
+        let mut bvh = Self {
+            positions: positions.to_vec(),
+            indices: indices.to_vec(),
+            nodes: Vec::with_capacity(triangle_count * 2 / LEAF_SIZE + 1),
+            order: (0..triangle_count as u32).collect(),
+        };
+        if triangle_count != 0 {
+            let centroids: Vec<Vec3> = (0..triangle_count)
+                .map(|triangle| {
+                    let [a, b, c] = bvh.triangle(triangle as u32);
+                    (a + b + c) / 3.0
+                })
+                .collect();
+            bvh.build(&centroids, 0, triangle_count);
+        }
+        bvh
+    }
+
+    /// Bytes of the copies of the geometry and the hierarchy.
+    pub fn allocated_bytes(&self) -> u64 {
+        (self.positions.len() * std::mem::size_of::<Vec3>()
+            + (self.indices.len() + self.order.len()) * std::mem::size_of::<u32>()
+            + self.nodes.len() * std::mem::size_of::<BvhNode>()) as u64
+    }
+
+    fn triangle(&self, triangle: u32) -> [Vec3; 3] {
+        let first = triangle as usize * 3;
+        [
+            self.positions[self.indices[first] as usize],
+            self.positions[self.indices[first + 1] as usize],
+            self.positions[self.indices[first + 2] as usize],
+        ]
+    }
+
+    /// Builds the subtree over `order[start..end]`, splitting at the median
+    /// along the longest axis of the centroids.
+    fn build(&mut self, centroids: &[Vec3], start: usize, end: usize) {
+        let (mut min, mut max) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+        let (mut centroid_min, mut centroid_max) = (min, max);
+        for &triangle in &self.order[start..end] {
+            for vertex in self.triangle(triangle) {
+                min = min.min(vertex);
+                max = max.max(vertex);
+            }
+            centroid_min = centroid_min.min(centroids[triangle as usize]);
+            centroid_max = centroid_max.max(centroids[triangle as usize]);
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(BvhNode {
+            min,
+            max,
+            start: start as u32,
+            count: (end - start) as u32,
+        });
+        if end - start <= LEAF_SIZE {
+            return;
+        }
+
+        let extent = centroid_max - centroid_min;
+        let axis = match extent.x >= extent.y && extent.x >= extent.z {
+            true => 0,
+            false if extent.y >= extent.z => 1,
+            false => 2,
+        };
+        let middle = (start + end) / 2;
+        self.order[start..end].select_nth_unstable_by(middle - start, |&left, &right| {
+            centroids[left as usize][axis]
+                .partial_cmp(&centroids[right as usize][axis])
+                .unwrap_or(Ordering::Equal)
+        });
+
+        self.build(centroids, start, middle);
+        self.nodes[node].start = self.nodes.len() as u32;
+        self.nodes[node].count = 0;
+        self.build(centroids, middle, end);
+    }
+
+    /// Closest triangle hit by the ray, from either side.
+    pub fn raycast(&self, ray: &Ray) -> Option<MeshHit> {
+        let root = self.nodes.first()?;
+        let inv_direction = ray.direction.recip();
+
+        let mut closest: Option<MeshHit> = None;
+        let mut stack = vec![(0_usize, ray.hits_aabb(inv_direction, root.min, root.max, f32::MAX)?)];
+        while let Some((idx, entry)) = stack.pop() {
+            let max_distance = closest.map_or(f32::MAX, |hit| hit.distance);
+            if entry > max_distance {
+                continue;
+            }
+
+            let node = &self.nodes[idx];
+            if node.count != 0 {
+                for &triangle in &self.order[node.start as usize..(node.start + node.count) as usize] {
+                    if let Some(distance) = intersect_triangle(ray, self.triangle(triangle)) {
+                        if distance < closest.map_or(f32::MAX, |hit| hit.distance) {
+                            closest = Some(MeshHit { distance, triangle });
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // Visit the nearer child first by pushing it last.
+            let children = [idx + 1, node.start as usize].map(|child| {
+                let child_node = &self.nodes[child];
+                (
+                    child,
+                    ray.hits_aabb(inv_direction, child_node.min, child_node.max, max_distance),
+                )
+            });
+            let [first, second] = match children[0].1.unwrap_or(f32::MAX) <= children[1].1.unwrap_or(f32::MAX) {
+                true => [children[1], children[0]],
+                false => children,
+            };
+            for (child, entry) in [first, second] {
+                if let Some(entry) = entry {
+                    stack.push((child, entry));
+                }
+            }
+        }
+        closest
+    }
+
+    /// Normal of a triangle, facing the side its vertices wind
+    /// counter-clockwise around.
+    pub fn triangle_normal(&self, triangle: u32) -> Vec3 {
+        let [a, b, c] = self.triangle(triangle);
+        (b - a).cross(c - a).normalize_or_zero()
+    }
+}
+
+/// Möller–Trumbore, hitting both sides of the triangle.
+fn intersect_triangle(ray: &Ray, [a, b, c]: [Vec3; 3]) -> Option<f32> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant == 0.0 {
+        return None;
+    }
+    let inv_determinant = determinant.recip();
+    let to_origin = ray.origin - a;
+    let u = to_origin.dot(p) * inv_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(edge1);
+    let v = ray.direction.dot(q) * inv_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = edge2.dot(q) * inv_determinant;
+    (distance >= 0.0).then(|| distance)
+}
+
+/// Casts a ray against one object, returning the hit in world space.
+pub(crate) fn raycast_object(
+    ray: &Ray,
+    object: RawObjectHandle,
+    transform: Mat4,
+    bounding_sphere: BoundingSphere,
+    bvh: &MeshBvh,
+) -> Option<RayHit> {
+    if !ray.hits_sphere(bounding_sphere.apply_transform(transform)) {
+        return None;
+    }
+
+    // The distance along the ray is the same in both spaces, as the direction
+    // is transformed with the origin.
+    let local = ray.transform(transform.inverse());
+    let hit = bvh.raycast(&local)?;
+    let normal = transform
+        .inverse()
+        .transpose()
+        .transform_vector3(bvh.triangle_normal(hit.triangle))
+        .normalize_or_zero();
+    // Mirroring transforms flip which side triangles wind counter-clockwise around.
+    let normal = match transform.determinant() < 0.0 {
+        true => -normal,
+        false => normal,
+    };
+
+    Some(RayHit {
+        object,
+        distance: hit.distance * ray.direction.length(),
+        position: ray.at(hit.distance),
+        normal,
+        triangle: hit.triangle,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MeshBvh, Ray};
+    use glam::Vec3;
+
+    #[test]
+    fn closest_triangle() {
+        // A grid of quads in the xy plane, and one more in front of it.
+        let mut positions = Vec::new();
+        let mut indices = Vec::new();
+        for y in 0..8 {
+            for x in 0..8 {
+                let base = positions.len() as u32;
+                let corner = Vec3::new(x as f32, y as f32, 0.0);
+                positions.extend_from_slice(&[
+                    corner,
+                    corner + Vec3::X,
+                    corner + Vec3::Y,
+                    corner + Vec3::new(1.0, 1.0, 0.0),
+                ]);
+                indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 1, base + 3]);
+            }
+        }
+        let front = positions.len() as u32;
+        positions.extend_from_slice(&[
+            Vec3::new(3.0, 3.0, 2.0),
+            Vec3::new(5.0, 3.0, 2.0),
+            Vec3::new(3.0, 5.0, 2.0),
+        ]);
+        indices.extend_from_slice(&[front, front + 1, front + 2]);
+
+        let bvh = MeshBvh::new(&positions, &indices);
+
+        let hit = bvh.raycast(&Ray::new(Vec3::new(3.5, 3.5, 10.0), -Vec3::Z)).unwrap();
+        assert_eq!(hit.triangle, 128);
+        assert!((hit.distance - 8.0).abs() < 1e-5);
+
+        let hit = bvh
+            .raycast(&Ray::new(Vec3::new(6.25, 1.25, -3.0), Vec3::Z * 2.0))
+            .unwrap();
+        assert_eq!(hit.triangle, (8 + 6) * 2);
+        assert!((hit.distance - 1.5).abs() < 1e-5);
+
+        assert_eq!(bvh.raycast(&Ray::new(Vec3::new(9.0, 1.0, 1.0), -Vec3::Z)), None);
+        assert_eq!(bvh.raycast(&Ray::new(Vec3::new(1.0, 1.0, 1.0), Vec3::Z)), None);
+    }
+}
@@ -118,10 +118,19 @@ where
         }
     }
 
+    pub fn get_key(&self, handle: RawResourceHandle<HandleType>) -> K {
+        self.handle_info[&handle.idx].key
+    }
+
     pub fn count(&self) -> usize {
         self.handle_info.len()
     }
 
+    pub fn get_value(&self, handle: RawResourceHandle<HandleType>) -> Option<&V> {
+        let handle_info = self.handle_info.get(&handle.idx)?;
+        self.archetype_map[&handle_info.key].data.get(handle_info.index)
+    }
+
     pub fn get_value_mut(&mut self, handle: RawResourceHandle<HandleType>) -> &mut V {
         let handle_info = &self.handle_info[&handle.idx];
         &mut self.archetype_map.get_mut(&handle_info.key).unwrap().data[handle_info.index]
@@ -131,6 +140,25 @@ where
         Some(&self.archetype_map.get(key)?.data)
     }
 
+    /// Returns an iterator over all values regardless of its archetype
+    pub fn iter_all_values(&self) -> impl Iterator<Item = &V> {
+        self.archetype_map.values().flat_map(|val| val.data.iter())
+    }
+
+    /// Returns an iterator over all values regardless of its archetype, with
+    /// a recreated owning handle. Values whose handles were all dropped are
+    /// skipped.
+    pub fn iter_all_handles(&self) -> impl Iterator<Item = (ResourceHandle<HandleType>, &V)> {
+        self.archetype_map.values().flat_map(|val| {
+            val.metadata.iter().zip(&val.data).filter_map(|(metadata, data)| {
+                Some((
+                    ResourceHandle::from_weak_refcount(&metadata.refcount, metadata.handle)?,
+                    data,
+                ))
+            })
+        })
+    }
+
     /// Returns an iterator over all values regardless of its archetype
     pub fn iter_all_values_mut(&mut self) -> impl Iterator<Item = &mut V> {
         self.archetype_map.values_mut().flat_map(|val| val.data.iter_mut())
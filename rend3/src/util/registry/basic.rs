@@ -36,13 +36,15 @@ impl<T, HandleType> ResourceRegistry<T, HandleType> {
         );
     }
 
-    pub fn remove_all_dead(&mut self, mut func: impl FnMut(&mut Self, usize, T)) {
+    /// Removes every value whose handles were all dropped, calling `func`
+    /// with the index the value had and its handle.
+    pub fn remove_all_dead(&mut self, mut func: impl FnMut(&mut Self, usize, RawResourceHandle<HandleType>, T)) {
         profiling::scope!("ResourceRegistry::remove_all_dead");
         for idx in (0..self.mapping.len()).rev() {
             let element = self.mapping.get_index(idx).unwrap().1;
             if element.refcount.strong_count() == 0 {
-                let (_, value) = self.mapping.swap_remove_index(idx).unwrap();
-                func(self, idx, value.data)
+                let (handle_idx, value) = self.mapping.swap_remove_index(idx).unwrap();
+                func(self, idx, RawResourceHandle::new(handle_idx), value.data)
             }
         }
     }
@@ -53,6 +55,16 @@ impl<T, HandleType> ResourceRegistry<T, HandleType> {
             .map(|(idx, ResourceStorage { data, .. })| (idx, data))
     }
 
+    /// Values with a recreated owning handle, skipping those whose handles
+    /// were all dropped.
+    pub fn iter_handles(&self) -> impl Iterator<Item = (ResourceHandle<HandleType>, &T)> {
+        self.mapping
+            .iter()
+            .filter_map(|(&idx, ResourceStorage { refcount, data })| {
+                Some((ResourceHandle::from_weak_refcount(refcount, idx)?, data))
+            })
+    }
+
     pub fn values(&self) -> impl ExactSizeIterator<Item = &T> + Clone {
         self.mapping.values().map(|ResourceStorage { data, .. }| data)
     }
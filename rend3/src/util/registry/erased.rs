@@ -37,6 +37,7 @@ pub struct Archetype<Metadata> {
         &mut Vec<NonErasedData<Metadata>>,
         &mut FastHashMap<usize, PerHandleData>,
         &mut dyn FnMut(&Metadata, usize),
+        &mut dyn FnMut(Metadata),
     ),
 }
 
@@ -139,7 +140,13 @@ impl<HandleType, Metadata> ArchitypicalErasedRegistry<HandleType, Metadata> {
         }
     }
 
-    pub fn remove_all_dead(&mut self, mut renormalize: impl FnMut(&Metadata, usize)) {
+    /// Removes every value whose handles were all dropped, handing their
+    /// metadata to `removed`.
+    pub fn remove_all_dead(
+        &mut self,
+        mut renormalize: impl FnMut(&Metadata, usize),
+        mut removed: impl FnMut(Metadata),
+    ) {
         profiling::scope!("ResourceRegistry::remove_all_dead");
         for archetype in self.archetype_map.values_mut() {
             (archetype.remove_all_dead)(
@@ -147,6 +154,7 @@ impl<HandleType, Metadata> ArchitypicalErasedRegistry<HandleType, Metadata> {
                 &mut archetype.non_erased,
                 &mut self.handle_map,
                 &mut renormalize,
+                &mut removed,
             );
         }
     }
@@ -179,6 +187,18 @@ impl<HandleType, Metadata> ArchitypicalErasedRegistry<HandleType, Metadata> {
         (t_ref, meta_ref)
     }
 
+    pub fn get_mut_full<T: Send + Sync + 'static>(
+        &mut self,
+        handle: RawResourceHandle<HandleType>,
+    ) -> (&mut T, &mut Metadata) {
+        let archetype = self.archetype_map.get_mut(&TypeId::of::<T>()).unwrap();
+        let index = self.handle_map[&handle.idx].index;
+        let t_ref = &mut archetype.vec.downcast_slice_mut::<T>().unwrap()[index];
+        let meta_ref = &mut archetype.non_erased[index].inner;
+
+        (t_ref, meta_ref)
+    }
+
     pub fn get_metadata_mut<T: Send + Sync + 'static>(
         &mut self,
         handle: RawResourceHandle<HandleType>,
@@ -244,6 +264,7 @@ fn remove_all_dead<T: Send + Sync + 'static, Metadata>(
     non_erased: &mut Vec<NonErasedData<Metadata>>,
     per_handle_map: &mut FastHashMap<usize, PerHandleData>,
     renormalize: &mut dyn FnMut(&Metadata, usize),
+    removed: &mut dyn FnMut(Metadata),
 ) {
     let mut vec = vec_any.downcast_mut::<T>().unwrap();
 
@@ -265,6 +286,7 @@ fn remove_all_dead<T: Send + Sync + 'static, Metadata>(
                 per_handle_map.get_mut(&metadata.handle).unwrap().index = idx;
                 renormalize(&metadata.inner, idx);
             }
+            removed(old_metadata.inner);
         }
     }
 }
@@ -0,0 +1,313 @@
+//! Adaptive quality, lowering settings while the GPU can't hold a target
+//! frame rate and raising them again once it can, e.g. for mobile and
+//! integrated GPUs.
+//!
+//! A [`ScalingController`] is given the GPU time of every frame, and a list of
+//! [`QualitySetting`]s the app knows how to apply: the render scale, the
+//! MSAA sample count, shadow distance or skipping shadows, and the quality of
+//! effects of its own like ambient occlusion or bloom. Settings are lowered a
+//! level at a time in list order, the first all the way to its minimum
+//! before the next, and raised again in the reverse order, so list them from
+//! the least to the most noticeable.
+//!
+//! The controller only picks levels. The app applies the change
+//! [`ScalingController::record_statistics`] returns, or applies it from a
+//! callback added with [`ScalingController::on_change`], which is also the
+//! place to update a settings UI.
+//!
+//! GPU times come from the timestamp queries of the renderer. Where they
+//! aren't supported the statistics are empty, and the app can pass the time
+//! between frames to [`ScalingController::record_frame`] instead.
+
+use std::time::Duration;
+
+use crate::util::typedefs::RendererStatistics;
+
+/// Most windows the controller waits before raising a setting again after
+/// raising it made frames too slow.
+const MAX_RAISE_DELAY: u32 = 16;
+
+/// A setting the controller scales between `min_level` and `max_level`,
+/// higher being better looking and slower.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualitySetting {
+    pub name: String,
+    pub level: u32,
+    pub min_level: u32,
+    pub max_level: u32,
+}
+
+impl QualitySetting {
+    /// A setting starting at its highest level.
+    pub fn new(name: impl Into<String>, min_level: u32, max_level: u32) -> Self {
+        Self {
+            name: name.into(),
+            level: max_level,
+            min_level,
+            max_level,
+        }
+    }
+
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = level.clamp(self.min_level, self.max_level);
+        self
+    }
+}
+
+/// A setting the controller moved to another level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityChange {
+    /// Index of the setting in the list the controller was created with.
+    pub setting: usize,
+    pub name: String,
+    pub previous_level: u32,
+    pub level: u32,
+}
+
+type ChangeCallback = Box<dyn FnMut(&QualityChange) + Send>;
+
+/// Scales [`QualitySetting`]s to hold a frame time, see the [module](self)
+/// docs.
+pub struct ScalingController {
+    target: Duration,
+    settings: Vec<QualitySetting>,
+    enabled: bool,
+    /// Frames averaged before each decision.
+    window: u32,
+    /// Fraction of the target frames must stay under for settings to be
+    /// raised.
+    raise_below: f32,
+    frames: u32,
+    total: Duration,
+    /// Consecutive windows fast enough to raise a setting.
+    fast_windows: u32,
+    /// Fast windows needed to raise a setting, doubled each time raising one
+    /// made frames too slow, so the levels don't keep flipping.
+    raise_delay: u32,
+    last_raised: bool,
+    callbacks: Vec<ChangeCallback>,
+}
+
+impl ScalingController {
+    /// Holds `target_fps`, deciding every 30 frames, and raising settings
+    /// while frames take less than 80% of the target time.
+    pub fn new(target_fps: f32, settings: Vec<QualitySetting>) -> Self {
+        Self {
+            target: Duration::from_secs_f32(1.0 / target_fps.max(1.0)),
+            settings,
+            enabled: true,
+            window: 30,
+            raise_below: 0.8,
+            frames: 0,
+            total: Duration::ZERO,
+            fast_windows: 0,
+            raise_delay: 1,
+            last_raised: false,
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Frames averaged before each decision. Longer windows react slower,
+    /// but aren't thrown off by single slow frames.
+    pub fn with_window(mut self, frames: u32) -> Self {
+        self.window = frames.max(1);
+        self
+    }
+
+    /// Fraction of the target time frames must stay under for settings to be
+    /// raised. The gap to 1.0 keeps a raise from immediately overshooting.
+    pub fn with_raise_threshold(mut self, fraction: f32) -> Self {
+        self.raise_below = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Calls `callback` with every change the controller makes.
+    pub fn on_change(&mut self, callback: impl FnMut(&QualityChange) + Send + 'static) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    pub fn target_frame_time(&self) -> Duration {
+        self.target
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: f32) {
+        self.target = Duration::from_secs_f32(1.0 / target_fps.max(1.0));
+        self.reset_window();
+    }
+
+    pub fn settings(&self) -> &[QualitySetting] {
+        &self.settings
+    }
+
+    /// Moves a setting to `level`, e.g. when the user picks it in a settings
+    /// UI. Doesn't call the callbacks.
+    pub fn set_level(&mut self, setting: usize, level: u32) {
+        let setting = &mut self.settings[setting];
+        setting.level = level.clamp(setting.min_level, setting.max_level);
+        self.reset_window();
+    }
+
+    /// While disabled, frames are ignored and the levels stay where they are.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.reset_window();
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Records the GPU time of the frame `statistics` were returned for by
+    /// `RenderGraph::execute`. Frames without timings are skipped.
+    pub fn record_statistics(&mut self, statistics: &RendererStatistics) -> Option<QualityChange> {
+        gpu_frame_time(statistics).and_then(|time| self.record_frame(time))
+    }
+
+    /// Records the time a frame took, returning the change made if this
+    /// frame completes a window which calls for one.
+    pub fn record_frame(&mut self, time: Duration) -> Option<QualityChange> {
+        if !self.enabled {
+            return None;
+        }
+
+        self.frames += 1;
+        self.total += time;
+        if self.frames < self.window {
+            return None;
+        }
+        let average = self.total / self.frames;
+        self.frames = 0;
+        self.total = Duration::ZERO;
+
+        let change = if average > self.target {
+            self.fast_windows = 0;
+            if self.last_raised {
+                self.raise_delay = (self.raise_delay * 2).min(MAX_RAISE_DELAY);
+            }
+            self.lower()
+        } else if average.as_secs_f32() < self.target.as_secs_f32() * self.raise_below {
+            self.fast_windows += 1;
+            if self.fast_windows < self.raise_delay {
+                return None;
+            }
+            self.fast_windows = 0;
+            self.raise()
+        } else {
+            // Settled within the target, so raising last time worked out.
+            self.fast_windows = 0;
+            if self.last_raised {
+                self.raise_delay = 1;
+            }
+            self.last_raised = false;
+            return None;
+        };
+
+        if let Some(ref change) = change {
+            for callback in &mut self.callbacks {
+                callback(change);
+            }
+        }
+        change
+    }
+
+    fn lower(&mut self) -> Option<QualityChange> {
+        self.last_raised = false;
+        let index = self
+            .settings
+            .iter()
+            .position(|setting| setting.level > setting.min_level)?;
+        Some(self.step(index, false))
+    }
+
+    fn raise(&mut self) -> Option<QualityChange> {
+        let index = self
+            .settings
+            .iter()
+            .rposition(|setting| setting.level < setting.max_level)?;
+        self.last_raised = true;
+        Some(self.step(index, true))
+    }
+
+    fn step(&mut self, index: usize, up: bool) -> QualityChange {
+        let setting = &mut self.settings[index];
+        let previous_level = setting.level;
+        setting.level = match up {
+            true => previous_level + 1,
+            false => previous_level - 1,
+        };
+        QualityChange {
+            setting: index,
+            name: setting.name.clone(),
+            previous_level,
+            level: setting.level,
+        }
+    }
+
+    fn reset_window(&mut self) {
+        self.frames = 0;
+        self.total = Duration::ZERO;
+        self.fast_windows = 0;
+    }
+}
+
+/// GPU time of the frame, from the start of its first rendergraph node to
+/// the end of its last. `None` without timestamp queries.
+pub fn gpu_frame_time(statistics: &RendererStatistics) -> Option<Duration> {
+    let start = statistics
+        .iter()
+        .map(|scope| scope.time.start)
+        .fold(f64::INFINITY, f64::min);
+    let end = statistics
+        .iter()
+        .map(|scope| scope.time.end)
+        .fold(f64::NEG_INFINITY, f64::max);
+    (end >= start).then(|| Duration::from_secs_f64(end - start))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{QualitySetting, ScalingController};
+
+    fn levels(controller: &ScalingController) -> Vec<u32> {
+        controller.settings().iter().map(|setting| setting.level).collect()
+    }
+
+    fn run(controller: &mut ScalingController, millis: u64, frames: u32) {
+        for _ in 0..frames {
+            controller.record_frame(Duration::from_millis(millis));
+        }
+    }
+
+    #[test]
+    fn lowers_in_order_and_raises_in_reverse() {
+        let settings = vec![
+            QualitySetting::new("bloom", 0, 1),
+            QualitySetting::new("render scale", 1, 3),
+        ];
+        let mut controller = ScalingController::new(50.0, settings).with_window(4);
+
+        // 25ms frames are too slow for 20ms.
+        run(&mut controller, 25, 4);
+        assert_eq!(levels(&controller), [0, 3]);
+        run(&mut controller, 25, 8);
+        assert_eq!(levels(&controller), [0, 1]);
+        // Nothing left to lower.
+        run(&mut controller, 25, 4);
+        assert_eq!(levels(&controller), [0, 1]);
+        // Within the target, nothing changes.
+        run(&mut controller, 18, 4);
+        assert_eq!(levels(&controller), [0, 1]);
+
+        run(&mut controller, 10, 4);
+        assert_eq!(levels(&controller), [0, 2]);
+        // Raising made frames too slow, so the next raise waits two windows.
+        run(&mut controller, 25, 4);
+        assert_eq!(levels(&controller), [0, 1]);
+        run(&mut controller, 10, 4);
+        assert_eq!(levels(&controller), [0, 1]);
+        run(&mut controller, 10, 4);
+        assert_eq!(levels(&controller), [0, 2]);
+    }
+}
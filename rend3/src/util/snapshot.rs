@@ -0,0 +1,68 @@
+//! Snapshots of the scene of a renderer, for hot-reloading application code.
+//!
+//! [`Renderer::snapshot`](crate::Renderer::snapshot) captures the camera and
+//! every live object, object group and directional light, with owning
+//! handles and the settings each was given. The handles keep the resources
+//! alive, along with the meshes, skeletons and materials the objects use, so
+//! nothing is unloaded or uploaded again while the code which owned them
+//! restarts.
+//!
+//! A hot-reloading host keeps the snapshot across the reload and hands it to
+//! the new code, which takes the handles it needs back, found by label with
+//! [`RendererSnapshot::objects_labeled`]. Settings changed since the
+//! snapshot, e.g. by the code being reloaded, are put back with
+//! [`Renderer::restore`](crate::Renderer::restore).
+//!
+//! A snapshot belongs to the renderer it was taken from. Handles of another
+//! renderer point at unrelated resources.
+
+use rend3_types::{
+    Camera, Object, ObjectDepthMode, ObjectFade, ObjectGroup, ObjectGroupHandle, ObjectHandle, ObjectShadowMode,
+    ShadowSettings,
+};
+
+use crate::{managers::DirectionalLightInfo, util::cells::CellGraph};
+
+/// An object as captured by [`Renderer::snapshot`](crate::Renderer::snapshot).
+#[derive(Debug, Clone)]
+pub struct ObjectSnapshot {
+    pub handle: ObjectHandle,
+    /// The transform is relative to the group of the object.
+    pub object: Object,
+    pub group: Option<ObjectGroupHandle>,
+    pub label: Option<String>,
+    pub texture_layer: u32,
+    pub depth_mode: ObjectDepthMode,
+    pub sort_key: i32,
+    pub shadow_mode: ObjectShadowMode,
+    pub fade: ObjectFade,
+}
+
+/// An object group as captured by
+/// [`Renderer::snapshot`](crate::Renderer::snapshot).
+#[derive(Debug, Clone)]
+pub struct ObjectGroupSnapshot {
+    pub handle: ObjectGroupHandle,
+    pub group: ObjectGroup,
+}
+
+/// The scene of a renderer, see the [module](self) docs.
+#[derive(Debug, Clone)]
+pub struct RendererSnapshot {
+    pub camera: Camera,
+    pub shadow_settings: ShadowSettings,
+    pub cells: Option<CellGraph>,
+    pub objects: Vec<ObjectSnapshot>,
+    pub groups: Vec<ObjectGroupSnapshot>,
+    pub directional_lights: Vec<DirectionalLightInfo>,
+}
+
+impl RendererSnapshot {
+    /// Objects labeled `label` with
+    /// [`Renderer::set_object_label`](crate::Renderer::set_object_label).
+    pub fn objects_labeled<'a>(&'a self, label: &'a str) -> impl Iterator<Item = &'a ObjectSnapshot> + 'a {
+        self.objects
+            .iter()
+            .filter(move |object| object.label.as_deref() == Some(label))
+    }
+}
@@ -0,0 +1,237 @@
+//! Textures whose contents are replaced every frame from the CPU, like the
+//! frames of a video or a webcam feed.
+//!
+//! [`Renderer::create_streaming_texture`](crate::Renderer::create_streaming_texture)
+//! adds a texture which can be used in materials like any other, along with
+//! a [`StreamingTexture`] to upload its frames through. Each upload goes
+//! through one of two staging buffers: a frame is written into a buffer
+//! which is already mapped while the other one is copied into the texture
+//! and mapped again, so uploading never waits on the gpu.
+
+use std::{num::NonZeroU32, sync::Arc, task::Poll};
+
+use glam::UVec2;
+use parking_lot::Mutex;
+use rend3_types::{TextureFormat, TextureHandle};
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Device, Extent3d, ImageCopyBuffer,
+    ImageCopyTexture, ImageDataLayout, Maintain, MapMode, Origin3d, Queue, Texture, TextureAspect,
+    COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use crate::{
+    util::buffer::{poll_now, MapFuture},
+    RendererError,
+};
+
+/// Description of a [`StreamingTexture`].
+#[derive(Debug, Clone)]
+pub struct StreamingTextureDescriptor {
+    pub label: Option<String>,
+    pub size: UVec2,
+    pub format: TextureFormat,
+}
+
+struct Staging {
+    buffer: Buffer,
+    /// Mapping requested after the last copy out of the buffer.
+    mapping: Option<MapFuture>,
+    mapped: bool,
+}
+
+struct StagingPair {
+    staging: [Staging; 2],
+    /// Buffer the next frame is written into, if it is mapped by then.
+    next: usize,
+}
+
+/// Uploader of the frames of a texture created by
+/// [`Renderer::create_streaming_texture`](crate::Renderer::create_streaming_texture).
+///
+/// The texture lives as long as its handle, which this holds onto.
+pub struct StreamingTexture {
+    handle: TextureHandle,
+    texture: Arc<Texture>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    extent: Extent3d,
+    /// Rows of texel blocks in a frame.
+    rows: u32,
+    /// Bytes in each row of a frame, as passed to [`update`](Self::update).
+    row_bytes: u32,
+    /// Bytes in each row of a staging buffer, as aligned for copies.
+    padded_row_bytes: u32,
+    staging: Mutex<StagingPair>,
+}
+
+impl StreamingTexture {
+    pub(crate) fn new(
+        handle: TextureHandle,
+        texture: Arc<Texture>,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        desc: &StreamingTextureDescriptor,
+    ) -> Self {
+        let (rows, row_bytes) = frame_layout(desc.format, desc.size);
+        let padded_row_bytes = align_row(row_bytes);
+
+        let staging = [0, 1].map(|index| Staging {
+            buffer: device.create_buffer(&BufferDescriptor {
+                label: desc
+                    .label
+                    .as_ref()
+                    .map(|label| format!("{} staging {}", label, index))
+                    .as_deref(),
+                size: padded_row_bytes as u64 * rows as u64,
+                usage: BufferUsages::MAP_WRITE | BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            }),
+            mapping: None,
+            mapped: true,
+        });
+
+        Self {
+            handle,
+            texture,
+            device,
+            queue,
+            extent: Extent3d {
+                width: desc.size.x,
+                height: desc.size.y,
+                depth_or_array_layers: 1,
+            },
+            rows,
+            row_bytes,
+            padded_row_bytes,
+            staging: Mutex::new(StagingPair { staging, next: 0 }),
+        }
+    }
+
+    /// Handle of the texture, to put in materials.
+    pub fn handle(&self) -> &TextureHandle {
+        &self.handle
+    }
+
+    /// Bytes [`update`](Self::update) expects: the tightly packed rows of
+    /// the texture.
+    pub fn frame_size(&self) -> usize {
+        self.rows as usize * self.row_bytes as usize
+    }
+
+    /// Uploads a new frame, shown from the next frame the renderer renders
+    /// once the gpu is done copying it.
+    ///
+    /// Returns false without uploading if both staging buffers are still in
+    /// use by the gpu, in which case the frame is dropped: the feed is
+    /// expected to send a newer one soon enough.
+    pub fn update(&self, frame: &[u8]) -> Result<bool, RendererError> {
+        profiling::scope!("StreamingTexture::update");
+
+        let expected = self.frame_size();
+        if frame.len() < expected {
+            return Err(RendererError::TextureDataTooSmall {
+                actual: frame.len(),
+                expected,
+            });
+        }
+
+        let mut pair = self.staging.lock();
+        self.device.poll(Maintain::Poll);
+        for staging in &mut pair.staging {
+            if let Some(mapping) = &mut staging.mapping {
+                match poll_now(mapping) {
+                    Poll::Ready(Ok(())) => {
+                        staging.mapping = None;
+                        staging.mapped = true;
+                    }
+                    // The buffer is still unmapped, so ask again.
+                    Poll::Ready(Err(_)) => {
+                        staging.mapping = Some(Box::pin(staging.buffer.slice(..).map_async(MapMode::Write)));
+                    }
+                    Poll::Pending => {}
+                }
+            }
+        }
+
+        // Prefers the buffer whose turn it is, which was mapped the longest
+        // ago.
+        let next = pair.next;
+        let index = match [next, 1 - next].into_iter().find(|&index| pair.staging[index].mapped) {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        let staging = &mut pair.staging[index];
+
+        {
+            let mut mapped = staging.buffer.slice(..).get_mapped_range_mut();
+            let row_bytes = self.row_bytes as usize;
+            for (dst, src) in mapped
+                .chunks_exact_mut(self.padded_row_bytes as usize)
+                .zip(frame[..expected].chunks_exact(row_bytes))
+            {
+                dst[..row_bytes].copy_from_slice(src);
+            }
+        }
+        staging.buffer.unmap();
+        staging.mapped = false;
+
+        let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("streaming texture upload"),
+        });
+        encoder.copy_buffer_to_texture(
+            ImageCopyBuffer {
+                buffer: &staging.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(self.padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            self.extent,
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        staging.mapping = Some(Box::pin(staging.buffer.slice(..).map_async(MapMode::Write)));
+        pair.next = 1 - index;
+
+        Ok(true)
+    }
+}
+
+/// Rows of texel blocks in a frame of `size`, and the bytes in each.
+fn frame_layout(format: TextureFormat, size: UVec2) -> (u32, u32) {
+    let info = format.describe();
+    let (block_width, block_height) = (info.block_dimensions.0 as u32, info.block_dimensions.1 as u32);
+    let blocks_wide = (size.x + block_width - 1) / block_width;
+    let rows = (size.y + block_height - 1) / block_height;
+    (rows, blocks_wide * info.block_size as u32)
+}
+
+fn align_row(row_bytes: u32) -> u32 {
+    let align = COPY_BYTES_PER_ROW_ALIGNMENT;
+    ((row_bytes + align - 1) / align) * align
+}
+
+#[cfg(test)]
+mod test {
+    use glam::UVec2;
+    use rend3_types::TextureFormat;
+
+    use super::{align_row, frame_layout};
+
+    #[test]
+    fn frame_rows() {
+        assert_eq!(frame_layout(TextureFormat::Rgba8UnormSrgb, UVec2::new(3, 2)), (2, 12));
+        assert_eq!(align_row(12), 256);
+        assert_eq!(align_row(512), 512);
+
+        // Blocks of 4x4 texels, rounded up.
+        assert_eq!(frame_layout(TextureFormat::Bc1RgbaUnorm, UVec2::new(6, 9)), (3, 16));
+    }
+}
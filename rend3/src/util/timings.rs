@@ -0,0 +1,67 @@
+//! CPU time spent in the renderer each frame, to tell whether a hitch comes
+//! from uploads, scene updates or encoding the rendergraph.
+//!
+//! The same parts show up as `profiling` scopes, for profilers which see
+//! them. [`Renderer::cpu_timings`](crate::Renderer::cpu_timings) reports
+//! them without one.
+
+use std::time::Duration;
+
+use instant::Instant;
+
+/// CPU time spent in parts of [`Renderer::ready`](crate::Renderer::ready)
+/// and `RenderGraph::execute` during the last frame.
+///
+/// Meshes and textures are only counted when they are added by
+/// [`Renderer::ready`](crate::Renderer::ready). Textures are created by
+/// `Renderer::add_texture_*` on the calling thread, which isn't counted,
+/// unless they were queued with
+/// [`Renderer::queue_texture_2d`](crate::Renderer::queue_texture_2d).
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct CpuTimings {
+    /// Adding the meshes and textures queued for upload, see
+    /// [`util::upload`](crate::util::upload).
+    pub queued_uploads: Duration,
+    /// Adding meshes and skeletons, and uploading their vertices.
+    pub meshes: Duration,
+    /// Adding textures, and adding and changing materials.
+    pub textures: Duration,
+    /// Adding, changing and removing objects and object groups.
+    pub objects: Duration,
+    /// Adding and changing directional lights, and placing their shadows.
+    pub lights: Duration,
+    /// All of [`Renderer::ready`](crate::Renderer::ready), including the
+    /// parts above.
+    pub ready: Duration,
+    /// Pruning the nodes of the rendergraph and allocating its render
+    /// targets.
+    pub graph_setup: Duration,
+    /// Running the nodes of the rendergraph, which encode its passes, and
+    /// submitting them.
+    pub graph_encoding: Duration,
+}
+
+impl CpuTimings {
+    /// Time spent by the renderer during the frame.
+    pub fn total(&self) -> Duration {
+        self.ready + self.graph_setup + self.graph_encoding
+    }
+}
+
+/// Measures time from its creation, for filling [`CpuTimings`].
+#[derive(Debug, Copy, Clone)]
+pub struct Stopwatch(Instant);
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self(Instant::now())
+    }
+
+    /// Time since the last lap, or since the start.
+    pub fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.0);
+        self.0 = now;
+        elapsed
+    }
+}
@@ -0,0 +1,231 @@
+//! Uploads of meshes and 2D textures scheduled by priority, so the assets in
+//! front of the camera show up first when a lot of them load at once.
+//!
+//! Queue an upload with
+//! [`Renderer::queue_mesh`](crate::Renderer::queue_mesh) or
+//! [`Renderer::queue_texture_2d`](crate::Renderer::queue_texture_2d), then
+//! poll [`UploadTicket::take`] on the following frames for its handle. Each
+//! call to [`Renderer::ready`](crate::Renderer::ready) adds the most
+//! important uploads to the renderer, until the budget set with
+//! [`Renderer::set_upload_budget`](crate::Renderer::set_upload_budget) is
+//! spent.
+//!
+//! Dropping a ticket before its upload happens cancels it.
+
+use std::{cmp::Ordering, sync::Arc};
+
+use glam::Vec3;
+use parking_lot::Mutex;
+use rend3_types::{Mesh, MeshHandle, Texture, TextureHandle};
+
+use crate::{Renderer, RendererError};
+
+/// How soon an upload happens.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct UploadPriority {
+    /// Uploads with a higher value go first.
+    pub value: i32,
+    /// Among uploads of the same value, those nearest to the camera go
+    /// first, and those without a location last.
+    pub location: Option<Vec3>,
+}
+
+impl UploadPriority {
+    pub fn value(value: i32) -> Self {
+        Self { value, location: None }
+    }
+
+    pub fn at(location: Vec3) -> Self {
+        Self {
+            value: 0,
+            location: Some(location),
+        }
+    }
+}
+
+type ResultSlot<T> = Arc<Mutex<Option<Result<T, RendererError>>>>;
+
+/// A queued upload, see the [module](self) docs.
+#[derive(Debug)]
+pub struct UploadTicket<T> {
+    result: ResultSlot<T>,
+}
+
+impl<T> UploadTicket<T> {
+    /// The handle of the uploaded resource, or why it failed, once. `None`
+    /// while the upload is queued.
+    pub fn take(&self) -> Option<Result<T, RendererError>> {
+        self.result.lock().take()
+    }
+}
+
+enum UploadKind {
+    Mesh(Mesh, ResultSlot<MeshHandle>),
+    Texture2d(Texture, ResultSlot<TextureHandle>),
+}
+
+impl UploadKind {
+    fn cancelled(&self) -> bool {
+        match self {
+            Self::Mesh(_, result) => Arc::strong_count(result) == 1,
+            Self::Texture2d(_, result) => Arc::strong_count(result) == 1,
+        }
+    }
+}
+
+struct PendingUpload {
+    priority: UploadPriority,
+    bytes: u64,
+    kind: UploadKind,
+}
+
+#[derive(Default)]
+struct QueueState {
+    pending: Vec<PendingUpload>,
+    budget: Option<u64>,
+}
+
+/// Uploads waiting for [`Renderer::ready`](crate::Renderer::ready), see the
+/// [module](self) docs.
+#[derive(Default)]
+pub struct UploadQueue {
+    state: Mutex<QueueState>,
+}
+
+impl UploadQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the bytes of vertex, index and texture data uploaded per frame,
+    /// `None` for no limit, the default. An upload larger than the budget
+    /// still happens once it is the most important one.
+    pub fn set_budget(&self, budget: Option<u64>) {
+        self.state.lock().budget = budget;
+    }
+
+    pub fn queue_mesh(&self, mesh: Mesh, priority: UploadPriority) -> UploadTicket<MeshHandle> {
+        let bytes = mesh_bytes(&mesh);
+        let result = ResultSlot::default();
+        self.push(priority, bytes, UploadKind::Mesh(mesh, Arc::clone(&result)));
+        UploadTicket { result }
+    }
+
+    pub fn queue_texture_2d(&self, texture: Texture, priority: UploadPriority) -> UploadTicket<TextureHandle> {
+        let bytes = texture.data.len() as u64;
+        let result = ResultSlot::default();
+        self.push(priority, bytes, UploadKind::Texture2d(texture, Arc::clone(&result)));
+        UploadTicket { result }
+    }
+
+    fn push(&self, priority: UploadPriority, bytes: u64, kind: UploadKind) {
+        self.state.lock().pending.push(PendingUpload { priority, bytes, kind });
+    }
+
+    /// Uploads the most important pending uploads within the budget, seen
+    /// from `camera`. Called by [`Renderer::ready`](crate::Renderer::ready)
+    /// before it processes instructions.
+    pub fn upload(&self, renderer: &Renderer, camera: Vec3) {
+        profiling::scope!("UploadQueue::upload");
+
+        let uploads = {
+            let mut state = self.state.lock();
+            state.pending.retain(|upload| !upload.kind.cancelled());
+            if state.pending.is_empty() {
+                return;
+            }
+            let order: Vec<(UploadPriority, u64)> = state
+                .pending
+                .iter()
+                .map(|upload| (upload.priority, upload.bytes))
+                .collect();
+            let selected = schedule(&order, camera, state.budget);
+
+            let mut pending: Vec<Option<PendingUpload>> = state.pending.drain(..).map(Some).collect();
+            let uploads: Vec<PendingUpload> = selected.iter().filter_map(|&index| pending[index].take()).collect();
+            state.pending = pending.into_iter().flatten().collect();
+            uploads
+        };
+
+        // Adding takes locks of the renderer, so the queue isn't held.
+        for upload in uploads {
+            match upload.kind {
+                UploadKind::Mesh(mesh, result) => *result.lock() = Some(renderer.add_mesh(mesh)),
+                UploadKind::Texture2d(texture, result) => *result.lock() = Some(renderer.add_texture_2d(texture)),
+            }
+        }
+    }
+}
+
+fn mesh_bytes(mesh: &Mesh) -> u64 {
+    use std::mem::size_of_val;
+
+    let bytes = size_of_val(&*mesh.vertex_positions)
+        + size_of_val(&*mesh.vertex_normals)
+        + size_of_val(&*mesh.vertex_tangents)
+        + size_of_val(&*mesh.vertex_uv0)
+        + size_of_val(&*mesh.vertex_uv1)
+        + size_of_val(&*mesh.vertex_colors)
+        + size_of_val(&*mesh.vertex_joint_indices)
+        + size_of_val(&*mesh.vertex_joint_weights)
+        + size_of_val(&*mesh.indices);
+    bytes as u64
+}
+
+/// Most important first.
+fn compare(left: &UploadPriority, right: &UploadPriority, camera: Vec3) -> Ordering {
+    let distance = |priority: &UploadPriority| {
+        priority
+            .location
+            .map_or(f32::INFINITY, |location| location.distance_squared(camera))
+    };
+    right
+        .value
+        .cmp(&left.value)
+        .then_with(|| distance(left).partial_cmp(&distance(right)).unwrap_or(Ordering::Equal))
+}
+
+/// Indices of the uploads to do this frame, most important first, spending
+/// at most `budget` bytes. The most important upload always fits.
+fn schedule(uploads: &[(UploadPriority, u64)], camera: Vec3, budget: Option<u64>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..uploads.len()).collect();
+    order.sort_by(|&left, &right| compare(&uploads[left].0, &uploads[right].0, camera));
+
+    let budget = match budget {
+        Some(budget) => budget,
+        None => return order,
+    };
+    let mut spent = 0;
+    order
+        .into_iter()
+        .enumerate()
+        .take_while(|&(rank, index)| {
+            spent += uploads[index].1;
+            rank == 0 || spent <= budget
+        })
+        .map(|(_, index)| index)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::{schedule, UploadPriority};
+
+    #[test]
+    fn important_uploads_go_first_within_budget() {
+        let uploads = [
+            (UploadPriority::at(Vec3::new(0.0, 0.0, 50.0)), 100),
+            (UploadPriority::value(0), 100),
+            (UploadPriority::at(Vec3::new(0.0, 0.0, 5.0)), 100),
+            (UploadPriority::value(1), 1000),
+        ];
+        assert_eq!(schedule(&uploads, Vec3::ZERO, None), [3, 2, 0, 1]);
+        // The first upload is over budget, but still happens.
+        assert_eq!(schedule(&uploads, Vec3::ZERO, Some(500)), [3]);
+        let cheap = &uploads[..3];
+        assert_eq!(schedule(cheap, Vec3::ZERO, Some(250)), [2, 0]);
+        assert_eq!(schedule(cheap, Vec3::new(0.0, 0.0, 60.0), Some(250)), [0, 2]);
+    }
+}
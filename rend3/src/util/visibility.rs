@@ -0,0 +1,121 @@
+//! Objects which survived culling in the last rendergraph, so gameplay logic
+//! like audio occlusion, AI level of detail or streaming priority can reuse
+//! the visibility work of the renderer.
+//!
+//! Recording is off by default. Turn it on with
+//! [`Renderer::set_visibility_recording`](crate::Renderer::set_visibility_recording),
+//! then read the results after executing a rendergraph with
+//! [`Renderer::last_visible_objects`](crate::Renderer::last_visible_objects).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+use rend3_types::RawObjectHandle;
+
+use crate::util::typedefs::FastHashMap;
+
+/// Camera objects were culled against.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VisibilityCamera {
+    /// The camera set with
+    /// [`Renderer::set_camera_data`](crate::Renderer::set_camera_data).
+    Main,
+    /// The shadow camera with this index in
+    /// [`ReadyData::directional_light_cameras`](crate::graph::ReadyData::directional_light_cameras).
+    Shadow(usize),
+}
+
+#[derive(Default)]
+struct Frames {
+    /// Objects recorded by the rendergraph being executed.
+    recording: FastHashMap<VisibilityCamera, Vec<RawObjectHandle>>,
+    /// Objects of the last rendergraph executed.
+    last: FastHashMap<VisibilityCamera, Vec<RawObjectHandle>>,
+}
+
+/// Collects the objects culling let through, see the [module](self) docs.
+#[derive(Default)]
+pub struct VisibilityRecorder {
+    enabled: AtomicBool,
+    frames: Mutex<Frames>,
+}
+
+impl VisibilityRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            *self.frames.lock() = Frames::default();
+        }
+    }
+
+    /// Adds objects which survived culling against `camera` in the
+    /// rendergraph being executed. Called by culling routines.
+    pub fn record(&self, camera: VisibilityCamera, objects: impl IntoIterator<Item = RawObjectHandle>) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.frames.lock().recording.entry(camera).or_default().extend(objects);
+    }
+
+    /// Makes the objects recorded so far the results of the last rendergraph.
+    pub(crate) fn finish_frame(&self) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut frames = self.frames.lock();
+        let Frames { recording, last } = &mut *frames;
+        std::mem::swap(recording, last);
+        recording.clear();
+        for objects in last.values_mut() {
+            // Objects of several material archetypes can be culled one set
+            // at a time, but each is only visible once.
+            objects.sort_unstable_by_key(|object| object.idx);
+            objects.dedup();
+        }
+    }
+
+    /// Objects visible to `camera` in the last rendergraph, sorted by index.
+    pub fn last(&self, camera: VisibilityCamera) -> Vec<RawObjectHandle> {
+        self.frames.lock().last.get(&camera).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rend3_types::{ObjectHandle, RawObjectHandle};
+
+    use super::{VisibilityCamera, VisibilityRecorder};
+
+    fn raw(idx: usize) -> RawObjectHandle {
+        ObjectHandle::new(idx).get_raw()
+    }
+
+    #[test]
+    fn frames_swap_on_finish() {
+        let recorder = VisibilityRecorder::new();
+        recorder.record(VisibilityCamera::Main, [raw(1)]);
+        recorder.finish_frame();
+        assert!(recorder.last(VisibilityCamera::Main).is_empty());
+
+        recorder.set_enabled(true);
+        recorder.record(VisibilityCamera::Main, [raw(3), raw(1)]);
+        recorder.record(VisibilityCamera::Main, [raw(3)]);
+        recorder.record(VisibilityCamera::Shadow(0), [raw(2)]);
+        assert!(recorder.last(VisibilityCamera::Main).is_empty());
+
+        recorder.finish_frame();
+        assert_eq!(recorder.last(VisibilityCamera::Main), [raw(1), raw(3)]);
+        assert_eq!(recorder.last(VisibilityCamera::Shadow(0)), [raw(2)]);
+
+        recorder.finish_frame();
+        assert!(recorder.last(VisibilityCamera::Main).is_empty());
+    }
+}